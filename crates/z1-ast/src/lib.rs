@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
 
-pub type Ident = String;
+mod symbol;
+
+pub use symbol::{Names, Session, Symbol};
+
+pub type Ident = Symbol;
 
 /// Byte-offset span within a source string.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
@@ -33,6 +37,13 @@ impl ModulePath {
     }
 }
 
+/// The dotted form, `http.server`.
+impl std::fmt::Display for ModulePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&Symbol::join(&self.0, "."))
+    }
+}
+
 /// Parsed module representation (header + top-level items).
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Module {
@@ -40,7 +51,22 @@ pub struct Module {
     pub version: Option<String>,
     pub ctx_budget: Option<u32>,
     pub caps: Vec<String>,
+    /// Targets the cell may be compiled for (`targets=[ts,wasm]`); empty
+    /// when it builds for any
+    #[serde(default)]
+    pub targets: Vec<String>,
+    /// Oldest toolchain version the cell builds with (`toolchain>=0.3`)
+    #[serde(default)]
+    pub toolchain: Option<String>,
+    /// Items as written and as added by `expansions`
     pub items: Vec<Item>,
+    /// Templates the cell declares (`template handler(name) { ... }`)
+    #[serde(default)]
+    pub templates: Vec<Template>,
+    /// Instantiations of `templates`, in source order; each item they add
+    /// to `items` carries the span of its expansion
+    #[serde(default)]
+    pub expansions: Vec<Expansion>,
     pub span: Span,
 }
 
@@ -59,7 +85,11 @@ impl Module {
             version,
             ctx_budget,
             caps,
+            targets: Vec::new(),
+            toolchain: None,
             items,
+            templates: Vec::new(),
+            expansions: Vec::new(),
             span,
         }
     }
@@ -71,6 +101,7 @@ pub enum Item {
     Symbol(SymbolMap),
     Type(TypeDecl),
     Fn(FnDecl),
+    Test(TestDecl),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -97,14 +128,27 @@ pub struct SymbolPair {
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TypeDecl {
     pub name: Ident,
+    /// Type parameters in brackets after the name: `List[T]`
+    #[serde(default)]
+    pub type_params: Vec<Ident>,
     pub expr: TypeExpr,
     pub span: Span,
+    /// `///` lines before the declaration, markers stripped
+    #[serde(default)]
+    pub doc: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TypeExpr {
     Path(Vec<Ident>),
     Record(Vec<RecordField>),
+    /// `Ok(U32) | Err(Str)`: a value of exactly one of the variants
+    Union(Vec<UnionVariant>),
+    /// `List[U32]`: a generic type applied to arguments
+    Generic {
+        base: Vec<Ident>,
+        args: Vec<TypeExpr>,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -114,14 +158,59 @@ pub struct RecordField {
     pub span: Span,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnionVariant {
+    pub name: Ident,
+    /// Type in parentheses after the name; `None` for a bare tag
+    pub payload: Option<Box<TypeExpr>>,
+    pub span: Span,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FnDecl {
     pub name: Ident,
+    /// Type parameters in brackets after the name: `map[T, U]`
+    #[serde(default)]
+    pub type_params: Vec<Ident>,
     pub params: Vec<Param>,
     pub ret: TypeExpr,
     pub effects: Vec<Ident>,
     pub body: Block,
     pub span: Span,
+    /// `///` lines before the declaration, markers stripped
+    #[serde(default)]
+    pub doc: Option<String>,
+}
+
+/// Inline test: `#test "name" { ... }`, run by `z1 test` against its cell
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestDecl {
+    pub name: String,
+    pub body: Block,
+    pub span: Span,
+}
+
+/// Template: `template handler(name, path) { ... }`, items written once
+/// and added to the cell by each [`Expansion`] of it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Template {
+    pub name: Ident,
+    pub params: Vec<Ident>,
+    /// The templated items, unparsed
+    pub body: Block,
+    pub span: Span,
+    /// `///` lines before the declaration, markers stripped
+    #[serde(default)]
+    pub doc: Option<String>,
+}
+
+/// Template instantiation: `expand handler(get_user, "/users")`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Expansion {
+    pub template: Ident,
+    /// Source text of each argument
+    pub args: Vec<String>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -134,9 +223,12 @@ pub struct Param {
 /// Block of statements (function body or control flow body)
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub struct Block {
-    /// Raw source text captured during parsing (temporary until full formatter/AST round-trip)
+    /// Source text as written, braces included, for printing the body back
+    /// as written and rewriting it in place: formatter and refactors only.
+    /// Analyses read `statements`; blocks nested in a body leave `raw`
+    /// empty, and those of test files keep only `raw` until z1-test runs them.
     pub raw: String,
-    /// Parsed statements (may be empty until statement parsing is complete)
+    /// Parsed statements; empty for template bodies, which hold items
     pub statements: Vec<Stmt>,
     pub span: Span,
 }
@@ -242,6 +334,12 @@ pub enum Expr {
     },
     /// Record initialization: `Point { x: 1, y: 2 }`
     Record { fields: Vec<RecordInit>, span: Span },
+    /// Record update: `Point { ..base, x: 2 }`, `base` with `fields` replaced
+    RecordUpdate {
+        base: Box<Expr>,
+        fields: Vec<RecordInit>,
+        span: Span,
+    },
     /// Qualified path: `H.Req`, `std.io.File`
     Path(Vec<Ident>, Span),
     /// Parenthesized expression: `(expr)`
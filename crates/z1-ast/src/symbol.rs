@@ -0,0 +1,457 @@
+//! Interned identifiers.
+//!
+//! A [`Symbol`] is an index into the name table of a [`Session`], so
+//! copying one allocates nothing and comparing or hashing two compares or
+//! hashes a pair of `u32`s. Each distinct name is stored once per session,
+//! and freed with it.
+//!
+//! Symbols are interned into and resolved against the current session of
+//! the thread: the one entered with [`Session::enter`], or else one the
+//! thread creates on first use and keeps until it exits. A short-lived
+//! process can rely on the latter; a long-running host, like `z1 daemon` or
+//! the Python bindings, enters a session of its own and drops it when done.
+//! Threads working on the same symbols enter the same session. A symbol is
+//! only valid in the session that interned it: resolving it in another
+//! panics, and its text must not be kept past the session's end.
+//!
+//! Symbols order by their text, not by their index, so sorting and
+//! `BTreeMap` keys are as deterministic as with `String`s: the order in
+//! which names were first interned never shows in the output. Resolving a
+//! symbol takes the table's read lock; code resolving many at once, like a
+//! sort, holds it through [`Session::names`] instead.
+
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU32, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// An interned identifier.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol {
+    index: u32,
+    session: u32,
+}
+
+/// A name table, and the values cached alongside it.
+///
+/// Cloning a session shares it; its names are freed when the last clone is
+/// dropped.
+#[derive(Clone)]
+pub struct Session(Arc<Table>);
+
+struct Table {
+    id: u32,
+    names: RwLock<Interner>,
+    locals: Mutex<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>,
+}
+
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<Arc<str>, u32>,
+    names: Vec<Arc<str>>,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Session>> = const { RefCell::new(None) };
+}
+
+/// Run `f` with the current session of the thread, creating one if it has
+/// none.
+fn with_current<R>(f: impl FnOnce(&Session) -> R) -> R {
+    CURRENT.with(|current| {
+        if current.borrow().is_none() {
+            *current.borrow_mut() = Some(Session::new());
+        }
+        f(current.borrow().as_ref().expect("session was just set"))
+    })
+}
+
+impl Session {
+    /// A session with an empty name table.
+    pub fn new() -> Self {
+        static NEXT_ID: AtomicU32 = AtomicU32::new(0);
+        Session(Arc::new(Table {
+            id: NEXT_ID.fetch_add(1, AtomicOrdering::Relaxed),
+            names: RwLock::default(),
+            locals: Mutex::default(),
+        }))
+    }
+
+    /// The current session of the thread.
+    pub fn current() -> Self {
+        with_current(Session::clone)
+    }
+
+    /// Run `f` with this session as the current one of the thread, then
+    /// restore the previous one.
+    pub fn enter<R>(&self, f: impl FnOnce() -> R) -> R {
+        struct Restore(Option<Session>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                let previous = self.0.take();
+                CURRENT.with(|current| *current.borrow_mut() = previous);
+            }
+        }
+
+        let _restore = Restore(CURRENT.with(|current| current.borrow_mut().replace(self.clone())));
+        f()
+    }
+
+    /// Number of distinct names interned in this session.
+    pub fn len(&self) -> usize {
+        self.names().table.names.len()
+    }
+
+    /// Whether no name was interned in this session.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The name table, read-locked until the guard is dropped, so that
+    /// resolving many symbols takes the lock once. Interning while holding
+    /// it deadlocks.
+    pub fn names(&self) -> Names<'_> {
+        Names {
+            session: self.0.id,
+            table: self.0.names.read().expect("symbol table poisoned"),
+        }
+    }
+
+    /// The value of type `T` cached in this session, made by `init` within
+    /// it on first use. Values holding symbols, like parsed modules, are
+    /// cached here rather than for the whole process, and dropped with
+    /// their names.
+    pub fn local<T: Any + Send + Sync>(&self, init: impl FnOnce() -> T) -> Arc<T> {
+        let cached = |locals: &HashMap<TypeId, Arc<dyn Any + Send + Sync>>| {
+            let value = Arc::clone(locals.get(&TypeId::of::<T>())?);
+            Some(
+                value
+                    .downcast::<T>()
+                    .expect("session local of another type"),
+            )
+        };
+        if let Some(value) = cached(&self.0.locals.lock().expect("session locals poisoned")) {
+            return value;
+        }
+        // Not holding the lock, which `init` may need for values of its own
+        let value = Arc::new(self.enter(init));
+        let mut locals = self.0.locals.lock().expect("session locals poisoned");
+        // Another thread may have made one in between
+        if let Some(value) = cached(&locals) {
+            return value;
+        }
+        locals.insert(TypeId::of::<T>(), value.clone());
+        value
+    }
+
+    fn intern(&self, name: &str) -> Symbol {
+        let symbol = |index: u32| Symbol {
+            index,
+            session: self.0.id,
+        };
+        if let Some(index) = self.names().table.ids.get(name) {
+            return symbol(*index);
+        }
+        let mut table = self.0.names.write().expect("symbol table poisoned");
+        // Another thread may have interned it in between
+        if let Some(index) = table.ids.get(name) {
+            return symbol(*index);
+        }
+        let name: Arc<str> = name.into();
+        let index = table.names.len() as u32;
+        table.names.push(Arc::clone(&name));
+        table.ids.insert(name, index);
+        symbol(index)
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session::new()
+    }
+}
+
+/// Sessions are equal when they share a name table.
+impl PartialEq for Session {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Session {}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("id", &self.0.id)
+            .field("names", &self.len())
+            .finish()
+    }
+}
+
+/// The name table of a session, locked for reading; see [`Session::names`].
+pub struct Names<'a> {
+    session: u32,
+    table: RwLockReadGuard<'a, Interner>,
+}
+
+impl Names<'_> {
+    /// The text of `symbol`.
+    ///
+    /// # Panics
+    ///
+    /// When `symbol` was interned in another session.
+    pub fn get(&self, symbol: Symbol) -> &str {
+        assert!(
+            symbol.session == self.session,
+            "symbol #{} of session {} resolved in session {}",
+            symbol.index,
+            symbol.session,
+            self.session
+        );
+        &self.table.names[symbol.index as usize]
+    }
+
+    /// Compare two symbols by their text.
+    pub fn cmp(&self, a: Symbol, b: Symbol) -> Ordering {
+        if a == b {
+            Ordering::Equal
+        } else {
+            self.get(a).cmp(self.get(b))
+        }
+    }
+}
+
+impl Symbol {
+    /// The symbol of `name` in the current session, added to its table on
+    /// first use.
+    pub fn intern(name: &str) -> Self {
+        with_current(|session| session.intern(name))
+    }
+
+    /// The interned text.
+    ///
+    /// # Panics
+    ///
+    /// When the symbol was interned in another session than the current one.
+    pub fn as_str(&self) -> &str {
+        let text = with_current(|session| session.names().get(*self) as *const str);
+        // SAFETY: names are only added to a table, each in an `Arc<str>`
+        // whose heap allocation never moves, and the table lives as long as
+        // its session, which the symbol must not outlive.
+        unsafe { &*text }
+    }
+
+    /// Sort `symbols` by their text, like `[Symbol]::sort`.
+    pub fn sort(symbols: &mut [Symbol]) {
+        with_current(|session| {
+            let names = session.names();
+            symbols.sort_by(|a, b| names.cmp(*a, *b));
+        });
+    }
+
+    /// The texts of `symbols` separated by `sep`, like `[String]::join`.
+    pub fn join(symbols: &[Symbol], sep: &str) -> String {
+        with_current(|session| {
+            let names = session.names();
+            let mut joined = String::new();
+            for (idx, symbol) in symbols.iter().enumerate() {
+                if idx > 0 {
+                    joined.push_str(sep);
+                }
+                joined.push_str(names.get(*symbol));
+            }
+            joined
+        })
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self == other {
+            Ordering::Equal
+        } else {
+            with_current(|session| session.names().cmp(*self, *other))
+        }
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Default for Symbol {
+    fn default() -> Self {
+        Symbol::intern("")
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(name: &String) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(name: String) -> Self {
+        Symbol::intern(&name)
+    }
+}
+
+impl From<Symbol> for String {
+    fn from(symbol: Symbol) -> Self {
+        symbol.as_str().to_string()
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for Symbol {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<Symbol> for str {
+    fn eq(&self, other: &Symbol) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl PartialEq<Symbol> for &str {
+    fn eq(&self, other: &Symbol) -> bool {
+        *self == other.as_str()
+    }
+}
+
+impl PartialEq<Symbol> for String {
+    fn eq(&self, other: &Symbol) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Debug output is that of the text, as it was when identifiers were
+/// `String`s.
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        Ok(Symbol::intern(&name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interning_is_idempotent() {
+        let a = Symbol::intern("handler");
+        assert_eq!(a, Symbol::intern(&String::from("handler")));
+        assert_ne!(a, Symbol::intern("serve"));
+        assert_eq!(a.as_str(), "handler");
+        assert_eq!(a, "handler");
+        assert_eq!(format!("{a} {a:?}"), "handler \"handler\"");
+    }
+
+    #[test]
+    fn symbols_order_by_text() {
+        let late = Symbol::intern("aardvark_interned_late");
+        let early = Symbol::intern("zebra");
+        let mut symbols = vec![Symbol::intern("zebra"), late];
+        symbols.sort();
+        assert_eq!(symbols, [late, early]);
+        let mut symbols = vec![early, late];
+        Symbol::sort(&mut symbols);
+        assert_eq!(symbols, [late, early]);
+    }
+
+    #[test]
+    fn sessions_keep_their_own_names() {
+        let outer = Symbol::intern("handler");
+        let session = Session::new();
+        let (inner, names) = session.enter(|| {
+            let inner = Symbol::intern("handler");
+            assert_eq!(inner, "handler");
+            assert_eq!(Session::current(), session);
+            (inner, Session::current().len())
+        });
+        assert_ne!(inner, outer);
+        assert_eq!(names, 1);
+        assert_eq!(outer, "handler");
+        let cached = session.local(|| Symbol::intern("cached"));
+        assert_eq!(session.enter(|| cached.to_string()), "cached");
+        assert_eq!(session.len(), 2);
+        // Another thread sees the names once it enters the session
+        let joined = std::thread::scope(|scope| {
+            scope
+                .spawn(|| session.enter(|| Symbol::join(&[inner, inner], ".")))
+                .join()
+                .unwrap()
+        });
+        assert_eq!(joined, "handler.handler");
+    }
+
+    #[test]
+    #[should_panic(expected = "resolved in session")]
+    fn symbols_of_another_session_do_not_resolve() {
+        let stale = Session::new().enter(|| Symbol::intern("handler"));
+        let _ = stale.as_str();
+    }
+}
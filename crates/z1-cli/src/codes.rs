@@ -0,0 +1,223 @@
+//! Registry of diagnostic codes.
+//!
+//! Every diagnostic the CLI reports carries one of these codes. The registry
+//! holds what is known about each: a title (used as the SARIF rule
+//! description), an extended explanation, an example that triggers it and
+//! the usual fixes. `z1 explain <CODE>` prints an entry, and the error
+//! printers point at it.
+
+use serde::Serialize;
+
+pub const PARSE: &str = "P001";
+pub const RESOLVE: &str = "R001";
+pub const TYPE: &str = "T001";
+pub const EFFECT: &str = "E001";
+pub const EFFECT_WARNING: &str = "W001";
+pub const UNUSED_IMPORT: &str = "W002";
+pub const STALE_SYMBOLS: &str = "W003";
+pub const CONTEXT: &str = "C001";
+pub const POLICY: &str = "L001";
+
+/// A diagnostic code and its documentation.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct CodeInfo {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub explanation: &'static str,
+    /// A cell that triggers the diagnostic.
+    pub example: &'static str,
+    pub fixes: &'static [&'static str],
+}
+
+/// All diagnostic codes, in the order `z1 explain --list` prints them.
+pub const CODES: &[CodeInfo] = &[
+    CodeInfo {
+        code: PARSE,
+        title: "Parse error",
+        explanation: "\
+The cell is not valid Z1 source: the parser met a token it did not expect,
+or a literal it could not read. Parsing stops at the first error, so nothing
+after it is checked until it is fixed. Both the compact and the relaxed
+syntax are accepted, but a cell must not mix keywords from both within one
+declaration.",
+        example: "\
+m app.main:1.0 ctx=100 caps=[]
+f add(a: U32, b: U32)->U32 eff [pure] { ret a + b;",
+        fixes: &[
+            "Close every `{`, `(` and `[` that is opened; the caret shows where the parser gave up.",
+            "Check the token named as expected against the declaration's grammar in the spec.",
+            "Run `z1 fmt` on the surrounding code once it parses to spot misplaced items.",
+        ],
+    },
+    CodeInfo {
+        code: RESOLVE,
+        title: "Unresolved import",
+        explanation: "\
+An import (`u \"path\"`) names a module that could not be found. Imports
+are resolved against the standard library, the project's source directories
+and its installed dependencies; a dependency listed in z1.toml but missing
+from `.z1/deps` is reported too, as is a file whose module header does not
+match the path it is imported as.",
+        example: "\
+m app.main:1.0 ctx=100 caps=[]
+u \"util/txt\" only [shout]",
+        fixes: &[
+            "Check the import path for typos; it is relative to a source directory, without extension.",
+            "Run `z1 add <name>` to fetch a missing dependency.",
+            "Make the imported file's `m` header match the path it is imported as.",
+        ],
+    },
+    CodeInfo {
+        code: TYPE,
+        title: "Type error",
+        explanation: "\
+A name does not check against its declaration: an imported symbol that the
+imported module does not declare, a call with the wrong number or types of
+arguments, a value of the wrong type, or a function that needs a capability
+its module does not grant. Z1 has no implicit conversions, so declared and
+actual types must match exactly.",
+        example: "\
+// src/util/text.z1c declares `shout`, not `whisper`
+m main:1.0 caps=[]
+u \"util/text\" only [whisper]",
+        fixes: &[
+            "Import only symbols the imported module declares; check their spelling.",
+            "Pass as many arguments as the function declares, each of its parameter's type.",
+            "Change the declared type, or the expression, so that both agree.",
+        ],
+    },
+    CodeInfo {
+        code: EFFECT,
+        title: "Effect error",
+        explanation: "\
+A function declares an effect its module is not granted. Each module lists
+its capabilities in its header (`caps=[...]`); a function may only use the
+effects among them, and calling an imported function brings its effects
+into the caller's module. Unknown effect names are reported too.",
+        example: "\
+m app.main:1.0 ctx=100 caps=[]
+f fetch()->Unit eff [net] { ret (); }",
+        fixes: &[
+            "Add the effect to the module's capabilities: `caps=[net]`.",
+            "Remove the effect from the function if it does not need it.",
+            "Move effectful code into a module that is granted the capability.",
+        ],
+    },
+    CodeInfo {
+        code: EFFECT_WARNING,
+        title: "Effect warning",
+        explanation: "\
+A capability or effect is declared but never needed: a module grants a
+capability none of its functions use, or a function declares an effect its
+body does not perform. Unused grants widen what a cell may do without
+reason. Warnings fail `z1 lint` only with `--deny-warnings`.",
+        example: "\
+m app.main:1.0 ctx=100 caps=[net]
+f add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }",
+        fixes: &[
+            "Run `z1 fix` to remove unused capabilities.",
+            "Remove the unused capability from the module's `caps=[...]`.",
+            "Remove the unused effect from the function's `eff [...]`.",
+        ],
+    },
+    CodeInfo {
+        code: UNUSED_IMPORT,
+        title: "Unused import",
+        explanation: "\
+An import, or some of the names in its `only [...]` list, is never referred
+to by the cell. Names that appear only in string literals or comments are
+not uses. Unused imports still have to be resolved, count against the import
+limit of the policy and cost context tokens. `z1 fix` removes them: the names
+nothing uses are dropped from the list, and the whole import when none is used.",
+        example: "\
+m app.main:1.0 ctx=100 caps=[]
+u \"util/text\" only [shout]
+f add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }",
+        fixes: &[
+            "Run `z1 fix` to remove the unused names or imports.",
+            "Remove the names from the import's `only [...]` list by hand.",
+        ],
+    },
+    CodeInfo {
+        code: STALE_SYMBOLS,
+        title: "Stale symbol map entry",
+        explanation: "\
+The cell's symbol map (`#sym { long: short }`) has entries for names the
+cell no longer declares or uses, typically left behind by a rename or a
+removed function. They cost context tokens without shortening anything.
+`z1 fix` regenerates the directive without them, along with the
+`// SymbolMap:` comment of relaxed cells.",
+        example: "\
+m app.main:1.0 ctx=100 caps=[]
+#sym { add: a2, retired: rt }
+f add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }",
+        fixes: &[
+            "Run `z1 fix` to regenerate the symbol map.",
+            "Delete the entries from the `#sym { ... }` directive by hand.",
+        ],
+    },
+    CodeInfo {
+        code: CONTEXT,
+        title: "Context budget exceeded",
+        explanation: "\
+The cell, or one of its functions, is estimated to take more tokens than
+its budget. The cell budget is set in the module header (`ctx=N`); `z1 ctx`
+prints the estimate per function. Budgets keep cells small enough to be
+read and edited as a whole.",
+        example: "\
+m app.main:1.0 ctx=2 caps=[]
+f add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }",
+        fixes: &[
+            "Split the cell into smaller modules and import them.",
+            "Raise `ctx=N` in the module header if the cell is meant to be larger.",
+            "Run `z1 ctx <cell>` to find the functions that take the most tokens.",
+        ],
+    },
+    CodeInfo {
+        code: POLICY,
+        title: "Policy violation",
+        explanation: "\
+The cell breaks a limit of the project policy: too many AST nodes, exports,
+imports, parameters or locals, a context budget above the policy maximum,
+or an effect not among the module's capabilities. Limits default to the
+built-in policy and can be set in the `[policy]` table of z1.toml.",
+        example: "\
+m app.main:1.0 ctx=100 caps=[]
+f wide(a: U32, b: U32, c: U32, d: U32, e: U32, f: U32, g: U32)->U32 eff [pure] { ret a; }",
+        fixes: &[
+            "Split large functions or cells so they stay within the limits.",
+            "Group many parameters into a record type.",
+            "Raise the limit in z1.toml's `[policy]` table if the project allows it.",
+        ],
+    },
+];
+
+/// Registry entry of `code`, ignoring case.
+pub fn lookup(code: &str) -> Option<&'static CodeInfo> {
+    CODES
+        .iter()
+        .find(|info| info.code.eq_ignore_ascii_case(code.trim()))
+}
+
+/// Hint pointing at the extended explanation of `code`.
+pub fn explain_hint(code: &str) -> String {
+    format!("For more information about this error, try `z1 explain {code}`.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_unique_and_found() {
+        for info in CODES {
+            assert_eq!(lookup(info.code).unwrap().code, info.code);
+            assert_eq!(
+                CODES.iter().filter(|other| other.code == info.code).count(),
+                1
+            );
+        }
+        assert_eq!(lookup("p001").unwrap().code, PARSE);
+        assert!(lookup("Z1E0042").is_none());
+    }
+}
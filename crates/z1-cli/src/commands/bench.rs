@@ -5,33 +5,135 @@ use std::time::Instant;
 
 use anyhow::{bail, Context, Result};
 use chrono::Utc;
-use clap::Args;
-use serde::Serialize;
+use clap::{Args, ValueHint};
+use serde::{Deserialize, Serialize};
 use z1_ctx::{estimate_cell_with_config, EstimateConfig};
 use z1_fmt::{FmtOptions, Mode};
 use z1_hash::module_hashes;
+use z1_ir::optimize::OptLevel;
+
+use crate::exit::Failure;
 
 const DEFAULT_CELL: &str = "fixtures/cells/http_server.z1c";
 const DEFAULT_OUTPUT: &str = "benchmarks/latest.json";
+const DEFAULT_THRESHOLDS: &str = "benchmarks/thresholds.toml";
+const DEFAULT_HISTORY: &str = "benchmarks/history.jsonl";
 const OUTPUT_TRIM_BYTES: usize = 2000;
+/// Growth over the last recorded run that fails it, unless configured
+const DEFAULT_REGRESSION_PERCENT: f64 = 10.0;
+/// Compiles per asserted cell; the fastest counts, to damp noise
+const COMPILE_RUNS: usize = 3;
+/// Functions of the synthetic cell the front end is timed on, about 400 KB
+const FRONT_END_FUNCTIONS: usize = 1000;
 
 #[derive(Debug, Args)]
 pub struct BenchArgs {
     /// Path to the canonical benchmark cell.
-    #[arg(long, default_value = DEFAULT_CELL)]
+    #[arg(long, default_value = DEFAULT_CELL, value_hint = ValueHint::FilePath)]
     pub cell: String,
     /// Destination JSON file for benchmark results.
-    #[arg(long, default_value = DEFAULT_OUTPUT)]
+    #[arg(long, default_value = DEFAULT_OUTPUT, value_hint = ValueHint::FilePath)]
     pub output: String,
     /// Continue running even if a command fails.
     #[arg(long)]
     pub continue_on_error: bool,
+    /// Limits cells must stay under (default: benchmarks/thresholds.toml,
+    /// when present).
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    pub thresholds: Option<String>,
+    /// JSON Lines file regressions are measured against, and passing runs
+    /// given --thresholds or --record are appended to.
+    #[arg(long, default_value = DEFAULT_HISTORY, value_hint = ValueHint::FilePath)]
+    pub history: String,
+    /// Append this run to the history even without --thresholds.
+    #[arg(long)]
+    pub record: bool,
+    /// Growth over the last recorded run, in percent, that fails the run
+    /// (default: `regression_percent` of the thresholds file, else 10).
+    #[arg(long, value_name = "PERCENT")]
+    pub max_regression: Option<f64>,
+    /// Only measure cells: skip timing cargo fmt, clippy and test.
+    #[arg(long)]
+    pub no_commands: bool,
+}
+
+/// `benchmarks/thresholds.toml`: limits per cell.
+///
+/// ```toml
+/// regression_percent = 10
+///
+/// [[cell]]
+/// path = "fixtures/cells/http_server.z1c"
+/// max_tokens = 128
+/// max_wasm_bytes = 4096
+/// max_compile_ms = 50
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Thresholds {
+    regression_percent: Option<f64>,
+    #[serde(default, rename = "cell")]
+    cells: Vec<CellThresholds>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CellThresholds {
+    path: String,
+    max_tokens: Option<u32>,
+    max_wasm_bytes: Option<usize>,
+    max_compile_ms: Option<f64>,
+}
+
+/// What is asserted of a cell, measured once per run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CellMeasurement {
+    cell: String,
+    tokens: u32,
+    wasm_bytes: usize,
+    compile_ms: f64,
+}
+
+/// Lex and parse times of a large synthetic cell, fastest of a few runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FrontEndMeasurement {
+    cell_bytes: usize,
+    tokens: usize,
+    lex_ms: f64,
+    parse_ms: f64,
+}
+
+/// How many times faster the front end got since the last recorded run
+#[derive(Debug, Serialize)]
+struct FrontEndSpeedup {
+    since: String,
+    lex: f64,
+    parse: f64,
+}
+
+/// One line of the history file
+#[derive(Debug, Serialize, Deserialize)]
+struct HistoryEntry {
+    git_head: String,
+    timestamp: String,
+    cells: Vec<CellMeasurement>,
+    /// Absent from entries recorded before the front end was timed
+    #[serde(default)]
+    front_end: Option<FrontEndMeasurement>,
 }
 
 #[derive(Debug, Serialize)]
 struct BenchReport {
     meta: MetaSection,
     commands: Vec<CommandReport>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    measurements: Vec<CellMeasurement>,
+    front_end: FrontEndMeasurement,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    front_end_speedup: Option<FrontEndSpeedup>,
+    /// Thresholds exceeded and regressions, one message each
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    violations: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -127,6 +229,37 @@ pub fn run(args: BenchArgs) -> Result<()> {
     let ctx_estimate = estimate_cell_with_config(&module, &ctx_config)?;
     let context_metrics = ContextMetrics::from_estimate(&ctx_estimate);
 
+    let thresholds = load_thresholds(args.thresholds.as_deref(), &repo_root)?;
+    let history_path = resolve_path(&args.history, &repo_root);
+    let previous = last_history_entry(&history_path)?;
+    let regression_percent = args
+        .max_regression
+        .or(thresholds.regression_percent)
+        .unwrap_or(DEFAULT_REGRESSION_PERCENT);
+    let mut measurements = Vec::new();
+    let mut violations = Vec::new();
+    for limits in &thresholds.cells {
+        let measured = measure_cell(&resolve_path(&limits.path, &repo_root), &limits.path)?;
+        violations.extend(check_limits(&measured, limits));
+        let before = previous
+            .iter()
+            .flat_map(|entry| &entry.cells)
+            .find(|cell| cell.cell == measured.cell);
+        if let Some(before) = before {
+            violations.extend(check_regression(&measured, before, regression_percent));
+        }
+        measurements.push(measured);
+    }
+    let front_end = measure_front_end()?;
+    let front_end_speedup = previous.as_ref().and_then(|entry| {
+        let before = entry.front_end.as_ref()?;
+        Some(FrontEndSpeedup {
+            since: entry.git_head.clone(),
+            lex: speedup(before.lex_ms, front_end.lex_ms),
+            parse: speedup(before.parse_ms, front_end.parse_ms),
+        })
+    });
+
     let mut commands = Vec::new();
     let command_specs: &[(&str, &[&str])] = &[
         ("cargo fmt", &["cargo", "fmt", "--all"]),
@@ -149,6 +282,7 @@ pub fn run(args: BenchArgs) -> Result<()> {
         ),
     ];
 
+    let command_specs = if args.no_commands { &[] } else { command_specs };
     for (label, args_vec) in command_specs {
         let report = run_shell_command(label, args_vec, &repo_root)?;
         let success = report.success;
@@ -173,7 +307,14 @@ pub fn run(args: BenchArgs) -> Result<()> {
         context: context_metrics,
     };
 
-    let report = BenchReport { meta, commands };
+    let report = BenchReport {
+        meta,
+        commands,
+        measurements,
+        front_end,
+        front_end_speedup,
+        violations,
+    };
 
     if let Some(parent) = output_path.parent() {
         fs::create_dir_all(parent)
@@ -189,10 +330,233 @@ pub fn run(args: BenchArgs) -> Result<()> {
         "[bench] wrote {}",
         relative_display(&output_path, &repo_root)
     );
+    let front_end = &report.front_end;
+    println!(
+        "[bench] front end: lex {:.1} MiB/s, parse {:.1} MiB/s on a {} KiB cell",
+        mib_per_s(front_end.cell_bytes, front_end.lex_ms),
+        mib_per_s(front_end.cell_bytes, front_end.parse_ms),
+        front_end.cell_bytes / 1024
+    );
+    if let Some(speedup) = &report.front_end_speedup {
+        println!(
+            "[bench] front end: lex {:.2}x and parse {:.2}x as fast as at {}",
+            speedup.lex,
+            speedup.parse,
+            short_rev(&speedup.since)
+        );
+    }
+
+    if !report.violations.is_empty() {
+        for violation in &report.violations {
+            eprintln!("[bench] {violation}");
+        }
+        return Err(Failure::Check.error(format!(
+            "{} benchmark threshold(s) exceeded",
+            report.violations.len()
+        )));
+    }
+
+    // Failed runs are not recorded, so a regression keeps failing until it
+    // is fixed or the thresholds are raised. Runs against the default
+    // thresholds are not recorded either, so a local run leaves the tree clean
+    if args.thresholds.is_some() || args.record {
+        let entry = HistoryEntry {
+            git_head: report.meta.git_head.clone(),
+            timestamp: report.meta.timestamp.clone(),
+            cells: report.measurements,
+            front_end: Some(report.front_end),
+        };
+        append_history(&history_path, &entry)?;
+        println!(
+            "[bench] recorded {}",
+            relative_display(&history_path, &repo_root)
+        );
+    }
 
     Ok(())
 }
 
+/// Thresholds from `path`, or from the default file when it exists.
+fn load_thresholds(path: Option<&str>, root: &Path) -> Result<Thresholds> {
+    let file = resolve_path(path.unwrap_or(DEFAULT_THRESHOLDS), root);
+    if path.is_none() && !file.exists() {
+        return Ok(Thresholds::default());
+    }
+    let text =
+        fs::read_to_string(&file).with_context(|| format!("failed to read {}", file.display()))?;
+    toml::from_str(&text).map_err(|err| {
+        Failure::Usage.error(format!("invalid {}: {}", file.display(), err.message()))
+    })
+}
+
+fn last_history_entry(path: &Path) -> Result<Option<HistoryEntry>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let text =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let Some(line) = text.lines().rev().find(|line| !line.trim().is_empty()) else {
+        return Ok(None);
+    };
+    let entry = serde_json::from_str(line)
+        .with_context(|| format!("invalid last entry in {}", path.display()))?;
+    Ok(Some(entry))
+}
+
+fn append_history(path: &Path, entry: &HistoryEntry) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let mut text = if path.exists() {
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?
+    } else {
+        String::new()
+    };
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text.push_str(&serde_json::to_string(entry)?);
+    text.push('\n');
+    fs::write(path, text).with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Context tokens, WASM size and compile time (parse, lower, optimize and
+/// emit WASM) of the cell at `path`.
+fn measure_cell(path: &Path, display: &str) -> Result<CellMeasurement> {
+    let source =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let mut compile_ms = f64::INFINITY;
+    let mut wasm_bytes = 0;
+    let mut module = None;
+    for _ in 0..COMPILE_RUNS {
+        let started = Instant::now();
+        let parsed = z1_parse::parse_module(&source)
+            .map_err(|err| Failure::Parse.error(format!("failed to parse {display}: {err}")))?;
+        let ir =
+            z1_ir::lower_to_ir(&parsed).with_context(|| format!("failed to lower {display}"))?;
+        let wasm = z1_codegen_wasm::generate_wasm_binary_optimized(&ir, OptLevel::default())
+            .map_err(|err| anyhow::anyhow!("failed to compile {display}: {err}"))?;
+        compile_ms = compile_ms.min(started.elapsed().as_secs_f64() * 1000.0);
+        wasm_bytes = wasm.len();
+        module = Some(parsed);
+    }
+    let config = EstimateConfig {
+        enforce_budget: false,
+        ..EstimateConfig::default()
+    };
+    let module = module.context("no compile runs")?;
+    let tokens = estimate_cell_with_config(&module, &config)?.total_tokens;
+    Ok(CellMeasurement {
+        cell: display.to_string(),
+        tokens,
+        wasm_bytes,
+        compile_ms: (compile_ms * 1000.0).round() / 1000.0,
+    })
+}
+
+/// Lex and parse times of a synthetic cell of [`FRONT_END_FUNCTIONS`]
+/// functions.
+fn measure_front_end() -> Result<FrontEndMeasurement> {
+    let source = z1_parse::synthetic_cell(FRONT_END_FUNCTIONS);
+    let mut lex_ms = f64::INFINITY;
+    let mut parse_ms = f64::INFINITY;
+    let mut tokens = 0;
+    for _ in 0..COMPILE_RUNS {
+        let started = Instant::now();
+        tokens = z1_lex::lex(&source).len();
+        lex_ms = lex_ms.min(started.elapsed().as_secs_f64() * 1000.0);
+        let started = Instant::now();
+        z1_parse::parse_module(&source)
+            .map_err(|err| anyhow::anyhow!("failed to parse the synthetic cell: {err}"))?;
+        parse_ms = parse_ms.min(started.elapsed().as_secs_f64() * 1000.0);
+    }
+    Ok(FrontEndMeasurement {
+        cell_bytes: source.len(),
+        tokens,
+        lex_ms: (lex_ms * 1000.0).round() / 1000.0,
+        parse_ms: (parse_ms * 1000.0).round() / 1000.0,
+    })
+}
+
+/// How many times faster `now_ms` is than `before_ms`.
+fn speedup(before_ms: f64, now_ms: f64) -> f64 {
+    if now_ms > 0.0 {
+        (before_ms / now_ms * 100.0).round() / 100.0
+    } else {
+        0.0
+    }
+}
+
+fn mib_per_s(bytes: usize, ms: f64) -> f64 {
+    if ms > 0.0 {
+        bytes as f64 / (1024.0 * 1024.0) / (ms / 1000.0)
+    } else {
+        0.0
+    }
+}
+
+fn short_rev(rev: &str) -> &str {
+    rev.get(..7).unwrap_or(rev)
+}
+
+fn check_limits(measured: &CellMeasurement, limits: &CellThresholds) -> Vec<String> {
+    let cell = &measured.cell;
+    let mut violations = Vec::new();
+    if let Some(max) = limits.max_tokens.filter(|max| measured.tokens > *max) {
+        violations.push(format!(
+            "{cell}: {} tokens, over the limit of {max}",
+            measured.tokens
+        ));
+    }
+    if let Some(max) = limits
+        .max_wasm_bytes
+        .filter(|max| measured.wasm_bytes > *max)
+    {
+        violations.push(format!(
+            "{cell}: {} bytes of WASM, over the limit of {max}",
+            measured.wasm_bytes
+        ));
+    }
+    if let Some(max) = limits
+        .max_compile_ms
+        .filter(|max| measured.compile_ms > *max)
+    {
+        violations.push(format!(
+            "{cell}: compiled in {}ms, over the limit of {max}ms",
+            measured.compile_ms
+        ));
+    }
+    violations
+}
+
+fn check_regression(
+    measured: &CellMeasurement,
+    before: &CellMeasurement,
+    percent: f64,
+) -> Vec<String> {
+    let metrics = [
+        ("tokens", measured.tokens as f64, before.tokens as f64),
+        (
+            "WASM bytes",
+            measured.wasm_bytes as f64,
+            before.wasm_bytes as f64,
+        ),
+        ("compile ms", measured.compile_ms, before.compile_ms),
+    ];
+    metrics
+        .into_iter()
+        .filter(|(_, now, was)| *was > 0.0 && *now > was * (1.0 + percent / 100.0))
+        .map(|(metric, now, was)| {
+            format!(
+                "{}: {metric} grew {:.1}% ({was} to {now}), more than the allowed {percent}%",
+                measured.cell,
+                (now / was - 1.0) * 100.0
+            )
+        })
+        .collect()
+}
+
 impl CellMetrics {
     fn new(fmt_mode: Mode, fmt_clean: bool, compact: &str, relaxed: &str) -> Self {
         let compact_chars = compact.chars().count();
@@ -0,0 +1,287 @@
+//! `z1 build`: incrementally compile every cell of a workspace.
+//!
+//! Cells are compiled in import order, for one or more targets, into a tree
+//! per target under the output directory. Only cells that are new, changed,
+//! or depend on a cell whose API hash changed are recompiled; the state of
+//! the last build of each target is kept in `.z1/buildstate`. Each cell is
+//! parsed once per build and its checks run at most once, however many
+//! targets it is compiled for; they are skipped altogether when they passed
+//! in an earlier build and neither the cell nor the API of its imports has
+//! changed since (see [`incremental`](crate::commands::incremental)).
+//!
+//! Inside a project, targets and the output directory default to the
+//! `[target]` table of its `z1.toml`, and a cell whose imports do not resolve
+//! fails to build.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use clap::{Args, ValueEnum};
+use z1_ast::Module;
+use z1_resolve::Project;
+use z1_store::{find_workspace, BuildState, CellInfo, Store};
+
+use crate::commands::cells::{collect_cells, relative};
+use crate::commands::compile::{compile_module, CheckContext, CompileOptions, CompileTarget};
+use crate::commands::incremental::CheckCache;
+use crate::exit::Failure;
+use crate::output;
+
+#[derive(Debug, Args)]
+pub struct BuildArgs {
+    /// Workspace directory (default: the nearest directory holding `z1.toml`
+    /// or `.z1`, else the current directory).
+    pub dir: Option<PathBuf>,
+    /// Compilation targets, each built into its own output tree (default:
+    /// the targets in `z1.toml`, else type-script)
+    #[arg(short, long, value_enum, value_delimiter = ',')]
+    pub target: Vec<BuildTarget>,
+    /// Output directory (default: `out-dir` in `z1.toml`, else
+    /// `<dir>/.z1/out`); target `t` is written to `<out-dir>/<t>`.
+    #[arg(long)]
+    pub out_dir: Option<PathBuf>,
+    /// Rebuild and re-check every cell, ignoring the recorded build and
+    /// check states.
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BuildTarget {
+    TypeScript,
+    Wasm,
+}
+
+impl BuildTarget {
+    /// Name of the target in the build state, the output tree and `z1.toml`.
+    fn name(self) -> &'static str {
+        match self {
+            BuildTarget::TypeScript => "ts",
+            BuildTarget::Wasm => "wasm",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [BuildTarget::TypeScript, BuildTarget::Wasm]
+            .into_iter()
+            .find(|target| target.name() == name)
+    }
+}
+
+/// A parsed workspace cell.
+pub(crate) struct WorkspaceCell {
+    pub info: CellInfo,
+    pub source: String,
+    pub module: Module,
+}
+
+pub fn run(args: BuildArgs) -> Result<()> {
+    let dir = workspace_dir(args.dir)?;
+    let project = if dir.join(z1_resolve::MANIFEST_FILE).is_file() {
+        Some(Project::load(&dir)?)
+    } else {
+        None
+    };
+    let store = Store::open(&dir);
+    let out_dir = args
+        .out_dir
+        .clone()
+        .or_else(|| project.as_ref().and_then(Project::out_dir))
+        .unwrap_or_else(|| store.root().join("out"));
+
+    let mut requested = args.target;
+    if requested.is_empty() {
+        if let Some(project) = &project {
+            for name in &project.manifest.target.targets {
+                match BuildTarget::from_name(name) {
+                    Some(target) => requested.push(target),
+                    None => bail!("unknown target `{name}` in z1.toml (expected ts or wasm)"),
+                }
+            }
+        }
+    }
+    if requested.is_empty() {
+        requested.push(BuildTarget::TypeScript);
+    }
+    let mut targets: Vec<BuildTarget> = Vec::new();
+    for target in requested {
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+
+    let workspace = workspace_sources(&dir)?;
+    let cells: Vec<CellInfo> = workspace.iter().map(|cell| cell.info.clone()).collect();
+    let mut state = if args.force {
+        BuildState::default()
+    } else {
+        BuildState::load(&store)?
+    };
+    let mut checks = CheckCache::new("build");
+    if args.force {
+        checks = checks.fresh();
+    }
+
+    // Modules whose checks passed during this build
+    let mut checked: HashSet<String> = HashSet::new();
+    let (mut built, mut up_to_date, mut failures) = (0, 0, 0);
+    // Class of the first failure, for the exit code
+    let mut failure = None;
+    for target in targets {
+        let target_name = target.name();
+        let plan = state.plan(&cells, target_name)?;
+        let target_dir = out_dir.join(target_name);
+        std::fs::create_dir_all(&target_dir)?;
+
+        let mut failed: HashSet<&str> = HashSet::new();
+        for (cell, reason) in &plan.dirty {
+            if let Some(dep) = cell
+                .imports
+                .iter()
+                .find(|import| failed.contains(import.as_str()))
+            {
+                failures += 1;
+                failed.insert(&cell.module);
+                eprintln!("✗ {}: dependency `{dep}` failed to build", cell.path);
+                continue;
+            }
+
+            if output::show_details() {
+                println!("Building {} [{target_name}] ({reason})", cell.module);
+            }
+            let parsed = workspace
+                .iter()
+                .find(|parsed| parsed.info.module == cell.module)
+                .expect("planned cells come from the workspace");
+            let input_path = dir.join(&cell.path);
+            let mut entry = None;
+            let check = !checked.contains(&cell.module) && {
+                let context = CheckContext::for_cell(&input_path)?;
+                entry = checks.entry(&input_path, &parsed.module, &context)?;
+                !checks.is_clean(entry.as_ref())
+            };
+            let opts = CompileOptions {
+                input_path,
+                output_path: Some(target_dir.join(output_name(&cell.module, target))),
+                target: match target {
+                    BuildTarget::TypeScript => CompileTarget::TypeScript,
+                    BuildTarget::Wasm => CompileTarget::Wasm,
+                },
+                binary: target == BuildTarget::Wasm,
+                component: false,
+                debug: false,
+                emit_glue: false,
+                require_entry: false,
+                opt_size: false,
+                wasm_gc: false,
+                validate: true,
+                check,
+                emit_ir: false,
+                opt_level: z1_ir::optimize::OptLevel::O1,
+                verbose: false,
+                stdout: false,
+                reproducible: false,
+                verify_reproducible: false,
+                provenance: None,
+                cache: None,
+                hooks: Default::default(),
+            };
+            match compile_module(&opts, &parsed.source, &parsed.module) {
+                Ok(report) => {
+                    if output::show_details() {
+                        report.print();
+                    }
+                    built += 1;
+                    checked.insert(cell.module.clone());
+                    state.record(cell, &cells, target_name);
+                    if let (true, Some(entry)) = (check, entry) {
+                        checks.record(entry, true);
+                    }
+                }
+                Err(err) => {
+                    if let Some(entry) = entry {
+                        checks.record(entry, false);
+                    }
+                    failures += 1;
+                    failed.insert(&cell.module);
+                    failure.get_or_insert(Failure::of(&err));
+                    eprintln!("✗ {}: {err}", cell.path);
+                }
+            }
+        }
+        up_to_date += plan.order.len() - plan.dirty.len();
+    }
+    state.retain(&cells);
+    state.save(&store)?;
+    checks.save()?;
+
+    if output::show_summary() {
+        println!(
+            "Built {built} cell(s), {up_to_date} up to date; {}",
+            checks.stats()
+        );
+    }
+    if let Some(failure) = failure {
+        return Err(failure.error(format!("{failures} cell(s) failed to build")));
+    }
+    Ok(())
+}
+
+/// `dir`, defaulting to the nearest project root, else the nearest
+/// workspace, else the current directory.
+pub(crate) fn workspace_dir(dir: Option<PathBuf>) -> Result<PathBuf> {
+    if let Some(dir) = dir {
+        return Ok(dir);
+    }
+    let cwd = std::env::current_dir()?;
+    Ok(match Project::discover(&cwd)? {
+        Some(project) => project.root,
+        None => find_workspace(&cwd).unwrap_or(cwd),
+    })
+}
+
+/// File name of the output of `module` for `target`.
+///
+/// Matches the paths generated imports refer to, so `app.lib` is written as
+/// `app_lib.ts` and imported from `./app_lib.js`.
+fn output_name(module: &str, target: BuildTarget) -> String {
+    format!("{}.{}", module.replace('.', "_"), target.name())
+}
+
+/// Parse and hash every cell under `dir`.
+///
+/// A module kept in both compact and relaxed form is built once, from the
+/// first file; copies that disagree semantically are an error.
+pub(crate) fn workspace_sources(dir: &Path) -> Result<Vec<WorkspaceCell>> {
+    let mut cells: Vec<WorkspaceCell> = Vec::new();
+    for path in collect_cells(dir)? {
+        let source = std::fs::read_to_string(&path)?;
+        let module = z1_parse::parse_module(&source)
+            .map_err(|err| anyhow::anyhow!("{}: {err}", path.display()))?;
+        let info = CellInfo::from_module(relative(dir, &path), &module);
+        match cells.iter().find(|other| other.info.module == info.module) {
+            Some(other) if other.info.semantic == info.semantic => continue,
+            Some(other) => bail!(
+                "module `{}` is defined differently by {} and {}",
+                info.module,
+                other.info.path,
+                info.path
+            ),
+            None => cells.push(WorkspaceCell {
+                info,
+                source,
+                module,
+            }),
+        }
+    }
+    Ok(cells)
+}
+
+/// [`CellInfo`] of every cell under `dir`, as for [`workspace_sources`].
+pub(crate) fn workspace_cells(dir: &Path) -> Result<Vec<CellInfo>> {
+    Ok(workspace_sources(dir)?
+        .into_iter()
+        .map(|cell| cell.info)
+        .collect())
+}
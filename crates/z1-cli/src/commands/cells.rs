@@ -0,0 +1,431 @@
+//! Finding and processing many cells at once.
+//!
+//! Commands that take cells also take directories, searched recursively,
+//! and globs such as `src/**/*.z1c` (`*` and `?` stay within a path
+//! segment, `**` spans any number of them). Walks skip store directories
+//! (`.z1`) and whatever `.z1ignore` files exclude.
+//!
+//! A `.z1ignore` holds one gitignore-style pattern per line: `#` starts a
+//! comment, `!` re-includes, a trailing `/` matches only directories, and a
+//! pattern containing `/` is anchored to the file's directory while one
+//! without matches a name at any depth. Ignore files apply to their own
+//! directory and everything below it. Those between the walked directory
+//! and the enclosing project root apply too, or outside a project, those
+//! between it and the current directory.
+
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use z1_ast::Session;
+use z1_resolve::MANIFEST_FILE;
+
+/// Name of the files listing paths that walks skip.
+pub const IGNORE_FILE: &str = ".z1ignore";
+
+const CELL_EXTENSIONS: [&str; 2] = ["z1c", "z1r"];
+
+/// Cells found for one command-line argument.
+pub(crate) struct Expansion {
+    /// Directory the cells were searched under, for directories and globs
+    pub base: Option<PathBuf>,
+    pub cells: Vec<PathBuf>,
+}
+
+/// Expand a file, directory or glob argument into cells, sorted.
+pub(crate) fn expand_arg(arg: &str) -> Result<Expansion> {
+    let path = Path::new(arg);
+    if path.is_dir() {
+        return Ok(Expansion {
+            base: Some(path.to_path_buf()),
+            cells: collect_cells(path)?,
+        });
+    }
+    if !is_glob(arg) {
+        return Ok(Expansion {
+            base: None,
+            cells: vec![path.to_path_buf()],
+        });
+    }
+
+    // Walk the longest leading part without wildcards
+    let segments: Vec<&str> = arg.split('/').collect();
+    let split = segments
+        .iter()
+        .position(|segment| is_glob(segment))
+        .unwrap_or(segments.len());
+    let base = match segments[..split].join("/") {
+        prefix if prefix.is_empty() && arg.starts_with('/') => PathBuf::from("/"),
+        prefix if prefix.is_empty() => PathBuf::from("."),
+        prefix => PathBuf::from(prefix),
+    };
+    let pattern = segments[split..].join("/");
+    let cells: Vec<PathBuf> = if base.is_dir() {
+        collect_cells(&base)?
+            .into_iter()
+            .filter(|cell| glob_match(&pattern, &relative(&base, cell)))
+            .map(|cell| match base.as_os_str() == "." {
+                true => cell.strip_prefix(&base).unwrap_or(&cell).to_path_buf(),
+                false => cell,
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    if cells.is_empty() {
+        bail!("no cells match `{arg}`");
+    }
+    Ok(Expansion {
+        base: Some(base),
+        cells,
+    })
+}
+
+/// Expand every argument, dropping cells named more than once.
+pub fn expand(args: &[String]) -> Result<Vec<PathBuf>> {
+    let mut cells: Vec<PathBuf> = Vec::new();
+    for arg in args {
+        for cell in expand_arg(arg)?.cells {
+            if !cells.contains(&cell) {
+                cells.push(cell);
+            }
+        }
+    }
+    Ok(cells)
+}
+
+pub(crate) fn is_glob(arg: &str) -> bool {
+    arg.contains(['*', '?'])
+}
+
+/// Whether `path` has a cell extension (`.z1c` or `.z1r`).
+pub(crate) fn is_cell(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| CELL_EXTENSIONS.iter().any(|cell| ext == *cell))
+}
+
+/// `.z1c`/`.z1r` files under `dir`, recursively, sorted.
+///
+/// Store directories (`.z1`) are skipped: the packages installed there are
+/// dependencies, not cells of the workspace. So is anything a `.z1ignore`
+/// excludes.
+pub(crate) fn collect_cells(dir: &Path) -> Result<Vec<PathBuf>> {
+    collect_files(dir, is_cell)
+}
+
+/// Files under `dir` that `wanted` accepts, walked as [`collect_cells`] does.
+pub(crate) fn collect_files(dir: &Path, wanted: fn(&Path) -> bool) -> Result<Vec<PathBuf>> {
+    let absolute =
+        std::path::absolute(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+    let mut rules = Vec::new();
+    let cwd = std::env::current_dir().ok();
+    let top = absolute
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| ancestor.join(MANIFEST_FILE).is_file())
+        .or_else(|| cwd.as_deref().filter(|cwd| absolute.starts_with(cwd)));
+    if let Some(top) = top {
+        let mut above: Vec<&Path> = absolute
+            .ancestors()
+            .skip(1)
+            .take_while(|ancestor| ancestor.starts_with(top))
+            .collect();
+        above.reverse();
+        for ancestor in above {
+            rules.extend(IgnoreRule::load(ancestor)?);
+        }
+    }
+
+    let mut files = Vec::new();
+    walk(dir, &absolute, &mut rules, wanted, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+fn walk(
+    dir: &Path,
+    absolute: &Path,
+    rules: &mut Vec<IgnoreRule>,
+    wanted: fn(&Path) -> bool,
+    files: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let inherited = rules.len();
+    rules.extend(IgnoreRule::load(absolute)?);
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name();
+        let entry_absolute = absolute.join(&name);
+        let is_dir = path.is_dir();
+        if ignored(rules, &entry_absolute, is_dir) {
+            continue;
+        }
+        if is_dir {
+            if name != z1_store::STORE_DIR {
+                walk(&path, &entry_absolute, rules, wanted, files)?;
+            }
+        } else if wanted(&path) {
+            files.push(path);
+        }
+    }
+    rules.truncate(inherited);
+    Ok(())
+}
+
+/// One pattern of a `.z1ignore`.
+#[derive(Debug)]
+struct IgnoreRule {
+    /// Directory holding the ignore file
+    base: PathBuf,
+    pattern: String,
+    negated: bool,
+    dir_only: bool,
+    anchored: bool,
+}
+
+impl IgnoreRule {
+    /// Rules of the `.z1ignore` in `dir`, if there is one.
+    fn load(dir: &Path) -> Result<Vec<IgnoreRule>> {
+        let path = dir.join(IGNORE_FILE);
+        if !path.is_file() {
+            return Ok(Vec::new());
+        }
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(text
+            .lines()
+            .filter_map(|line| IgnoreRule::parse(dir, line))
+            .collect())
+    }
+
+    fn parse(base: &Path, line: &str) -> Option<IgnoreRule> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let pattern = line.trim_start_matches('/');
+        if pattern.is_empty() {
+            return None;
+        }
+        Some(IgnoreRule {
+            base: base.to_path_buf(),
+            pattern: pattern.to_string(),
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let Ok(rel) = path.strip_prefix(&self.base) else {
+            return false;
+        };
+        if self.anchored {
+            glob_match(&self.pattern, &relative(Path::new(""), rel))
+        } else {
+            rel.file_name()
+                .is_some_and(|name| glob_match(&self.pattern, &name.to_string_lossy()))
+        }
+    }
+}
+
+/// Whether the last rule matching `path` excludes it.
+fn ignored(rules: &[IgnoreRule], path: &Path, is_dir: bool) -> bool {
+    rules
+        .iter()
+        .rev()
+        .find(|rule| rule.matches(path, is_dir))
+        .is_some_and(|rule| !rule.negated)
+}
+
+/// Match a `/`-separated path against a glob.
+pub(crate) fn glob_match(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let path: Vec<&str> = path.split('/').collect();
+    match_segments(&pattern, &path)
+}
+
+fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_segments(rest, &path[skip..])),
+        Some((segment, rest)) => match path.split_first() {
+            Some((name, path_rest)) => {
+                match_segment(segment.as_bytes(), name.as_bytes())
+                    && match_segments(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+/// Match one path segment; `*` matches any run of bytes, `?` any one.
+fn match_segment(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|skip| match_segment(rest, &name[skip..])),
+        Some((b'?', rest)) => !name.is_empty() && match_segment(rest, &name[1..]),
+        Some((byte, rest)) => name.first() == Some(byte) && match_segment(rest, &name[1..]),
+    }
+}
+
+/// `/`-separated path of `cell` relative to `dir`.
+pub(crate) fn relative(dir: &Path, cell: &Path) -> String {
+    let rel = cell.strip_prefix(dir).unwrap_or(cell);
+    rel.components()
+        .map(|part| part.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Apply `f` to `items` on all available cores, keeping their order. The
+/// workers intern names in the caller's [`Session`].
+pub(crate) fn par_map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let workers = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let chunk_size = items.len().div_ceil(workers).max(1);
+    let f = &f;
+    let session = &Session::current();
+    thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || session.enter(|| chunk.iter().map(f).collect::<Vec<_>>()))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                // Re-raise a worker's panic as it was, for the crash report
+                handle
+                    .join()
+                    .unwrap_or_else(|payload| std::panic::resume_unwind(payload))
+            })
+            .collect()
+    })
+}
+
+/// Counts printed after a command processed several cells.
+pub(crate) struct Summary {
+    pub command: &'static str,
+    pub cells: usize,
+    pub elapsed: Duration,
+    pub rows: Vec<(&'static str, usize)>,
+}
+
+impl Summary {
+    /// Print to stderr, keeping stdout for the command's own output.
+    pub fn print(&self) {
+        if crate::output::show_summary() {
+            eprint!("{self}");
+        }
+    }
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{}: {} cell(s) in {:.2}s",
+            self.command,
+            self.cells,
+            self.elapsed.as_secs_f64()
+        )?;
+        let width = self.rows.iter().map(|(label, _)| label.len()).max();
+        for (label, count) in &self.rows {
+            writeln!(
+                f,
+                "  {label:<width$}  {count:>6}",
+                width = width.unwrap_or(0)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs_match_segments() {
+        assert!(glob_match("**/*.z1c", "main.z1c"));
+        assert!(glob_match("**/*.z1c", "a/b/main.z1c"));
+        assert!(!glob_match("**/*.z1c", "a/main.z1r"));
+        assert!(glob_match("*.z1?", "main.z1r"));
+        assert!(!glob_match("*.z1c", "a/main.z1c"));
+        assert!(glob_match("a/**/gen/*", "a/gen/x.z1c"));
+        assert!(glob_match("a/**/gen/*", "a/b/c/gen/x.z1c"));
+        assert!(!glob_match("a/**/gen/*", "b/gen/x.z1c"));
+    }
+
+    #[test]
+    fn z1ignore_excludes_and_reincludes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+        for cell in [
+            "main.z1c",
+            "gen/out.z1c",
+            "gen/keep.z1c",
+            "sub/gen/deep.z1c",
+            "sub/scratch.z1r",
+            "vendor/lib.z1c",
+        ] {
+            let path = root.join(cell);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "m x:1.0\n").unwrap();
+        }
+        fs::write(
+            root.join(IGNORE_FILE),
+            "# generated\ngen/\n!gen/keep.z1c\n/vendor\n",
+        )
+        .unwrap();
+        fs::write(root.join("sub").join(IGNORE_FILE), "*.z1r\n").unwrap();
+
+        let cells: Vec<String> = collect_cells(root)
+            .unwrap()
+            .iter()
+            .map(|cell| relative(root, cell))
+            .collect();
+        // A re-included file inside an excluded directory stays excluded,
+        // as with gitignore: the directory is never walked
+        assert_eq!(cells, ["main.z1c"]);
+
+        // Walking a subdirectory outside the current directory, the parent's
+        // ignore file applies only within a project
+        let sub = |root: &Path| -> Vec<String> {
+            let sub = root.join("sub");
+            collect_cells(&sub)
+                .unwrap()
+                .iter()
+                .map(|cell| relative(&sub, cell))
+                .collect()
+        };
+        assert_eq!(sub(root), ["gen/deep.z1c"]);
+        fs::write(root.join(MANIFEST_FILE), "[project]\nname = \"app\"\n").unwrap();
+        assert!(sub(root).is_empty());
+    }
+
+    #[test]
+    fn par_map_keeps_order() {
+        let items: Vec<usize> = (0..100).collect();
+        assert_eq!(
+            par_map(&items, |item| item * 2),
+            (0..200).step_by(2).collect::<Vec<_>>()
+        );
+    }
+}
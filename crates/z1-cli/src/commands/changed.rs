@@ -0,0 +1,137 @@
+//! `--changed-only`: check just the cells a commit touches.
+//!
+//! `z1 hash`, `z1 ctx` and `z1 lint` take the changed files as arguments or,
+//! when none are given, one per line on stdin, as printed by
+//! `git diff --cached --name-only` in a pre-commit hook. Files that are not
+//! cells, or that the change deleted, are skipped. Each command then reports
+//! against the cell as it is at `--baseline` (`HEAD` by default), read with
+//! `git show`; a cell the baseline does not have is new.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result};
+use clap::Args;
+
+use crate::commands::cells::is_cell;
+use crate::exit::Failure;
+
+#[derive(Debug, Clone, Args)]
+pub struct ChangedArgs {
+    /// Process only the given changed files (read one per line from stdin
+    /// when none are given) and report relative to --baseline.
+    #[arg(long)]
+    pub changed_only: bool,
+    /// Git revision --changed-only compares against.
+    #[arg(
+        long,
+        value_name = "REV",
+        default_value = "HEAD",
+        requires = "changed_only"
+    )]
+    pub baseline: String,
+}
+
+/// The revision changed cells are compared against.
+#[derive(Debug, Clone)]
+pub struct Baseline {
+    rev: String,
+}
+
+impl ChangedArgs {
+    /// The changed cells among `paths`, or among the lines of stdin when
+    /// `paths` is empty, in the order given.
+    pub fn cells<P: AsRef<Path>>(&self, paths: &[P]) -> Result<Vec<PathBuf>> {
+        if !paths.is_empty() {
+            return Ok(existing_cells(
+                paths.iter().map(|path| path.as_ref().to_path_buf()),
+            ));
+        }
+        let mut list = String::new();
+        std::io::stdin()
+            .read_to_string(&mut list)
+            .context("Failed to read the changed files from stdin")?;
+        Ok(existing_cells(
+            list.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(PathBuf::from),
+        ))
+    }
+
+    /// The `--baseline` revision, checked to name a commit.
+    pub fn baseline(&self) -> Result<Baseline> {
+        let output = Command::new("git")
+            .args(["rev-parse", "--verify", "--quiet"])
+            .arg(format!("{}^{{commit}}", self.baseline))
+            .output()
+            .context("failed to execute git rev-parse")?;
+        if !output.status.success() {
+            return Err(Failure::Usage.error(format!(
+                "--baseline {} does not name a commit of a git repository",
+                self.baseline
+            )));
+        }
+        Ok(Baseline {
+            rev: self.baseline.clone(),
+        })
+    }
+}
+
+impl Baseline {
+    pub fn rev(&self) -> &str {
+        &self.rev
+    }
+
+    /// Source of `cell` at the baseline; `None` when the cell is new.
+    pub fn source(&self, cell: &Path) -> Option<String> {
+        let dir = match cell.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let name = cell.file_name()?.to_str()?;
+        // `./` makes the path relative to `dir` instead of the repository root
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .arg("show")
+            .arg(format!("{}:./{name}", self.rev))
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8(output.stdout).ok()
+    }
+}
+
+/// The paths that name cells still on disk, without duplicates.
+fn existing_cells(paths: impl Iterator<Item = PathBuf>) -> Vec<PathBuf> {
+    let mut cells: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        if is_cell(&path) && path.is_file() && !cells.contains(&path) {
+            cells.push(path);
+        }
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn keeps_existing_cells() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.z1c", "b.z1r", "notes.md"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        let paths =
+            ["a.z1c", "notes.md", "gone.z1c", "b.z1r", "a.z1c"].map(|name| dir.path().join(name));
+
+        let cells = existing_cells(paths.into_iter());
+        assert_eq!(cells, [dir.path().join("a.z1c"), dir.path().join("b.z1r")]);
+    }
+}
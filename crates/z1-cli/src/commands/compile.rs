@@ -8,35 +8,598 @@
 //! 5. Policy gate enforcement
 //! 6. IR generation (placeholder)
 //! 7. Code generation (TypeScript or WASM)
+//!
+//! Directories and globs are compiled in a batch ([`compile_batch`]): cells
+//! go through the pipeline in parallel, sharing one cache of parsed imports,
+//! and the failures are summed up by class at the end.
+//!
+//! Every stage runs in a span of the [`crate::trace::STAGE`] target, timed
+//! by `--trace-timings` and logged by `--log-json`.
 
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
-use z1_ast::Module;
-
+use std::time::{Duration, Instant};
+use tracing::{info, info_span};
+use z1_ast::{Item, Module, Session};
+use z1_hash::{hash_bytes, module_hashes, HashAlgo};
+use z1_policy::PolicyLimits;
+use z1_prov::{CellBinding, ProvenanceChainExt, ProvenanceEntry};
+use z1_resolve::{CellCache, Project, ResolveError, ResolvedModule, Resolver};
+use z1_store::find_workspace;
+
+use crate::commands::cells::{collect_cells, par_map, Summary};
+use crate::commands::hooks::Hooks;
+use crate::commands::prov::{load_or_new, sign_latest, KeyArgs};
+use crate::crash;
 use crate::error_printer;
+use crate::events;
+use crate::exit::Failure;
+use crate::output;
+use crate::trace::STAGE;
 
 /// Compilation target language.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CompileTarget {
     TypeScript,
     Wasm,
 }
 
-/// Compilation options.
+/// Compilation options; all but `provenance` and `cache` can be sent to
+/// `z1 daemon`.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct CompileOptions {
     pub input_path: PathBuf,
     pub output_path: Option<PathBuf>,
     pub target: CompileTarget,
     pub binary: bool,
+    /// Emit a WebAssembly component plus its `.wit` interface.
+    pub component: bool,
+    /// Embed debug info (Z1 source lines, DWARF) in WASM output.
+    pub debug: bool,
+    /// Write JS glue and ABI documentation next to WASM output.
+    pub emit_glue: bool,
+    /// Fail unless the cell has an entrypoint (`fn main()`).
+    pub require_entry: bool,
+    /// Favor small WASM output (`--opt-size`).
+    pub opt_size: bool,
+    /// Lower records and strings to WasmGC types (`--wasm-features gc`).
+    pub wasm_gc: bool,
+    /// Validate generated WASM with `wasmparser` before writing it.
+    pub validate: bool,
     pub check: bool,
     pub emit_ir: bool,
+    #[serde(with = "opt_level")]
     pub opt_level: z1_ir::optimize::OptLevel,
     pub verbose: bool,
+    /// Write the generated code, or the IR dump, to stdout instead of a
+    /// file; the report's `output` is then `-`.
+    pub stdout: bool,
+    /// Pin everything that could differ between two builds of the same cell
+    /// (`--reproducible`): paths embedded in the output are file names, and
+    /// provenance timestamps come from `SOURCE_DATE_EPOCH` when it is set.
+    pub reproducible: bool,
+    /// Build twice and fail unless both builds write the same bytes
+    /// (`--verify-reproducible`); implies `reproducible`.
+    pub verify_reproducible: bool,
+    /// Append a provenance entry for the build to a chain.
+    #[serde(skip)]
+    pub provenance: Option<ProvenanceOptions>,
+    /// Cells already read, which the cell and its imports are loaded through.
+    #[serde(skip)]
+    pub cache: Option<CellCache>,
+    /// Custom validations and transforms run at stages of the pipeline.
+    #[serde(skip)]
+    pub hooks: Hooks,
+}
+
+/// `OptLevel` as `O0`, `O1` or `O2`.
+mod opt_level {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use z1_ir::optimize::OptLevel;
+
+    pub fn serialize<S: Serializer>(level: &OptLevel, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{level:?}"))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<OptLevel, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Provenance recording for a build (`--prov`).
+#[derive(Clone)]
+pub struct ProvenanceOptions {
+    /// Chain file the entry is appended to; created if missing.
+    pub chain: PathBuf,
+    /// Actor recorded on the entry.
+    pub actor: String,
+    /// Signing key; the entry is left unsigned when none is configured.
+    pub key: KeyArgs,
+    /// Signer identifier (default: the actor).
+    pub signer: Option<String>,
+}
+
+/// Files written by a compilation.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompileReport {
+    /// Generated code, or the IR dump with `emit_ir`
+    pub output: PathBuf,
+    /// Size of `output` in bytes
+    pub bytes: usize,
+    /// Whether `output` is the IR dump
+    #[serde(skip)]
+    pub ir: bool,
+    /// Size, token estimate and optimizations of the output
+    pub stats: CompileStats,
+    /// Other files written next to the output
+    pub artifacts: Vec<Artifact>,
+    /// Chain the build was recorded in, with `provenance`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<ProvenanceRecord>,
+}
+
+/// A file written alongside the compiled output.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Artifact {
+    pub kind: ArtifactKind,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtifactKind {
+    /// Component interface (`--component`)
+    Wit,
+    /// WAT the DWARF line info points at (`--debug --binary`)
+    Wat,
+    /// JS glue (`--emit-glue`)
+    Glue,
+    /// ABI reference (`--emit-glue`)
+    Abi,
+    /// Runtime shim of an imported std cell
+    Shim,
+    /// TypeScript declarations (`--target ts,wasm`)
+    Declarations,
+}
+
+impl ArtifactKind {
+    /// Name of the kind in JSON output.
+    pub fn name(self) -> &'static str {
+        match self {
+            ArtifactKind::Wit => "wit",
+            ArtifactKind::Wat => "wat",
+            ArtifactKind::Glue => "glue",
+            ArtifactKind::Abi => "abi",
+            ArtifactKind::Shim => "shim",
+            ArtifactKind::Declarations => "declarations",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ArtifactKind::Wit => "WIT interface",
+            ArtifactKind::Wat => "Debug WAT",
+            ArtifactKind::Glue => "JS glue",
+            ArtifactKind::Abi => "ABI reference",
+            ArtifactKind::Shim => "Runtime shim",
+            ArtifactKind::Declarations => "Type declarations",
+        }
+    }
+}
+
+/// What a compilation produced and how much optimizing it took, for CI
+/// trend dashboards (`--stats json`).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompileStats {
+    /// Output extension: `ts`, `wat`, `wasm` or `ir.txt`
+    pub format: String,
+    pub bytes: usize,
+    /// Estimated tokens an agent spends reading the output; `None` for
+    /// binary WASM
+    pub tokens: Option<u32>,
+    pub optimizations: OptimizationStats,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OptimizationStats {
+    /// `O0`, `O1` or `O2`
+    pub level: String,
+    pub constants_folded: usize,
+    pub dead_code_eliminated: usize,
+    pub functions_inlined: usize,
+    /// Passes until the optimizer reached a fixpoint
+    pub iterations: usize,
+}
+
+impl CompileStats {
+    fn new(
+        format: &'static str,
+        code: &[u8],
+        level: z1_ir::optimize::OptLevel,
+        opt: &z1_ir::optimize::OptStats,
+    ) -> Self {
+        let tokens = (format != "wasm").then(|| {
+            z1_ctx::estimate_tokens_from_chars(
+                String::from_utf8_lossy(code).chars().count(),
+                z1_ctx::DEFAULT_CHARS_PER_TOKEN,
+            )
+        });
+        Self {
+            format: format.to_string(),
+            bytes: code.len(),
+            tokens,
+            optimizations: OptimizationStats {
+                level: format!("{level:?}"),
+                constants_folded: opt.constants_folded,
+                dead_code_eliminated: opt.dead_code_eliminated,
+                functions_inlined: opt.functions_inlined,
+                iterations: opt.total_iterations,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProvenanceRecord {
+    pub chain: PathBuf,
+    pub signed: bool,
+}
+
+impl CompileReport {
+    /// Every file written: the output, then the artifacts.
+    pub fn files(&self) -> Vec<PathBuf> {
+        std::iter::once(&self.output)
+            .chain(self.artifacts.iter().map(|artifact| &artifact.path))
+            .cloned()
+            .collect()
+    }
+
+    /// Print the report for humans.
+    pub fn print(&self) {
+        if !output::show_summary() {
+            return;
+        }
+        if self.ir {
+            println!("✓ IR emitted to: {}", self.output.display());
+            return;
+        }
+        let tokens = match self.stats.tokens {
+            Some(tokens) => format!(", ~{tokens} tokens"),
+            None => String::new(),
+        };
+        println!(
+            "✓ Compiled to: {} ({} bytes{tokens})",
+            self.output.display(),
+            self.bytes
+        );
+        let opt = &self.stats.optimizations;
+        println!(
+            "✓ Optimized at {}: {} folded, {} eliminated, {} inlined",
+            opt.level, opt.constants_folded, opt.dead_code_eliminated, opt.functions_inlined
+        );
+        for artifact in &self.artifacts {
+            println!("✓ {}: {}", artifact.kind.label(), artifact.path.display());
+        }
+        if let Some(prov) = &self.provenance {
+            println!(
+                "✓ Provenance recorded in: {}{}",
+                prov.chain.display(),
+                if prov.signed {
+                    " (signed)"
+                } else {
+                    " (unsigned)"
+                }
+            );
+        }
+    }
+}
+
+/// Outcome of compiling many cells at once (`z1 compile src/`).
+#[derive(Debug, Serialize)]
+pub struct BatchReport {
+    pub compiled: usize,
+    pub failed: usize,
+    /// Every cell, in the order given
+    pub cells: Vec<BatchCell>,
+    #[serde(skip)]
+    pub elapsed: Duration,
+}
+
+/// A cell of a batch: what it compiled to, or why it failed.
+#[derive(Debug, Serialize)]
+pub struct BatchCell {
+    pub path: PathBuf,
+    /// One report per target
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<CompileReport>,
+    /// Metadata file, with several targets
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip)]
+    pub failure: Option<Failure>,
+}
+
+impl BatchReport {
+    /// Class of the first failed cell, for the exit code.
+    pub fn failure(&self) -> Option<Failure> {
+        self.cells.iter().find_map(|cell| cell.failure)
+    }
+
+    /// Print each cell's result, then the totals with failures by class.
+    pub fn print(&self) {
+        for cell in &self.cells {
+            match &cell.error {
+                Some(error) => eprintln!("✗ {}: {error}", cell.path.display()),
+                None if output::show_details() => {
+                    for report in &cell.targets {
+                        report.print();
+                    }
+                    if let Some(meta) = &cell.meta {
+                        println!("✓ Metadata: {}", meta.display());
+                    }
+                }
+                None => {}
+            }
+        }
+        let mut rows = vec![("compiled", self.compiled), ("failed", self.failed)];
+        for failure in [
+            Failure::Parse,
+            Failure::Resolve,
+            Failure::Type,
+            Failure::Effect,
+            Failure::Budget,
+            Failure::Policy,
+            Failure::Error,
+        ] {
+            let count = self
+                .cells
+                .iter()
+                .filter(|cell| cell.failure == Some(failure))
+                .count();
+            if count > 0 {
+                rows.push((failure_label(failure), count));
+            }
+        }
+        Summary {
+            command: "compile",
+            cells: self.cells.len(),
+            elapsed: self.elapsed,
+            rows,
+        }
+        .print();
+    }
+}
+
+/// Row of the batch summary counting failures of class `failure`.
+fn failure_label(failure: Failure) -> &'static str {
+    match failure {
+        Failure::Parse => "  parse errors",
+        Failure::Resolve => "  unresolved imports",
+        Failure::Type => "  type errors",
+        Failure::Effect => "  effect errors",
+        Failure::Budget => "  over budget",
+        Failure::Policy => "  policy violations",
+        _ => "  other errors",
+    }
+}
+
+/// Outputs of a compilation to several targets (`--target ts,wasm`).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TargetsReport {
+    /// One report per target, in the order requested
+    pub targets: Vec<CompileReport>,
+    /// Metadata describing the cell and its outputs
+    pub meta: PathBuf,
+}
+
+impl TargetsReport {
+    /// Print the report for humans.
+    pub fn print(&self) {
+        for report in &self.targets {
+            report.print();
+        }
+        if output::show_summary() {
+            println!("✓ Metadata: {}", self.meta.display());
+        }
+    }
+}
+
+/// Contents of the `.meta.json` file of a multi-target compilation.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CellMeta {
+    /// Dotted module path
+    pub module: String,
+    pub version: Option<String>,
+    pub semhash: String,
+    pub formhash: String,
+    pub caps: Vec<String>,
+    /// Estimated tokens of the cell in compact form
+    pub tokens: u32,
+    /// Declared context budget
+    pub budget: Option<u32>,
+    pub imports: Vec<String>,
+    pub exports: Vec<MetaExport>,
+    /// Every file written, outputs and artifacts alike
+    pub outputs: Vec<MetaOutput>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MetaExport {
+    pub name: String,
+    /// `fn` or `type`
+    pub kind: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MetaOutput {
+    pub path: PathBuf,
+    pub bytes: u64,
+    /// SHA3-256 of the file
+    pub sha3: String,
+}
+
+impl CellMeta {
+    fn new(
+        opts: &CompileOptions,
+        module: &Module,
+        lowered: &Lowered,
+        reports: &[CompileReport],
+    ) -> Result<Self> {
+        let hashes = module_hashes(module);
+        let estimate = z1_ctx::estimate_cell_with_config(
+            module,
+            &z1_ctx::EstimateConfig {
+                enforce_budget: false,
+                ..Default::default()
+            },
+        )?;
+        let exports = lowered
+            .ir
+            .exports
+            .iter()
+            .map(|name| MetaExport {
+                name: name.to_string(),
+                kind: if lowered.ir.types.iter().any(|t| &t.name == name) {
+                    "type"
+                } else {
+                    "fn"
+                }
+                .to_string(),
+            })
+            .collect();
+        let mut outputs = Vec::new();
+        for path in reports.iter().flat_map(CompileReport::files) {
+            let bytes =
+                fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+            outputs.push(MetaOutput {
+                path: embedded_path(opts, &path),
+                bytes: bytes.len() as u64,
+                sha3: hash_bytes(&bytes, HashAlgo::Sha3_256),
+            });
+        }
+        Ok(Self {
+            module: module.path.to_string(),
+            version: module.version.clone(),
+            semhash: hashes.semantic,
+            formhash: hashes.format,
+            caps: module.caps.clone(),
+            tokens: estimate.total_tokens,
+            budget: estimate.budget,
+            imports: module
+                .items
+                .iter()
+                .filter_map(|item| match item {
+                    Item::Import(import) => Some(import.path.clone()),
+                    _ => None,
+                })
+                .collect(),
+            exports,
+            outputs,
+        })
+    }
+}
+
+/// What a cell is checked against: the policy limits of the enclosing
+/// project's `z1.toml` and a resolver for its imports.
+///
+/// A cell in a workspace without a manifest resolves its imports against the
+/// workspace's cells by the module paths they declare, under the default
+/// limits. Outside both, only `std` imports resolve, to the bundled standard
+/// library, and other imports stay opaque.
+#[derive(Default)]
+pub(crate) struct CheckContext {
+    pub limits: PolicyLimits,
+    pub resolver: Option<Resolver>,
+}
+
+impl CheckContext {
+    /// Context of the cell at `path`.
+    pub(crate) fn for_cell(path: &Path) -> Result<Self> {
+        if let Some(project) = Project::discover(path)? {
+            return Ok(Self {
+                resolver: Some(project.resolver()?),
+                limits: project.manifest.policy,
+            });
+        }
+        let resolver = match std::path::absolute(path).ok().and_then(find_workspace) {
+            Some(root) => Some(workspace_resolver(root)?),
+            None => None,
+        };
+        Ok(Self {
+            resolver,
+            limits: PolicyLimits::default(),
+        })
+    }
+
+    /// Load imports through `cache`.
+    pub(crate) fn with_cache(mut self, cache: &CellCache) -> Self {
+        self.resolver = self
+            .resolver
+            .map(|resolver| resolver.with_cache(cache.clone()));
+        self
+    }
+
+    /// The cell `import` names; `None` for an import that stays opaque.
+    pub(crate) fn load_import(&self, import: &str) -> Option<Result<ResolvedModule, ResolveError>> {
+        match &self.resolver {
+            Some(resolver) => Some(resolver.load(import)),
+            None => z1_resolve::load_std(import),
+        }
+    }
+
+    /// Imports of `module` by import path, failing on the first that does
+    /// not resolve.
+    pub(crate) fn load_imports(&self, module: &Module) -> Result<BTreeMap<String, ResolvedModule>> {
+        let mut imports = BTreeMap::new();
+        for item in &module.items {
+            let Item::Import(import) = item else {
+                continue;
+            };
+            if imports.contains_key(&import.path) {
+                continue;
+            }
+            if let Some(resolved) = self.load_import(&import.path) {
+                let resolved = resolved?;
+                imports.insert(resolved.import.clone(), resolved);
+            }
+        }
+        Ok(imports)
+    }
+}
+
+/// The cache of parsed cells of the current [`Session`], shared by every
+/// context checked in it, so checking many cells of one workspace parses
+/// each only once.
+pub(crate) fn cell_cache() -> CellCache {
+    CellCache::clone(&Session::current().local(CellCache::new))
+}
+
+/// A resolver over the cells under the workspace `root`, indexed by module
+/// path, read through [`cell_cache`].
+fn workspace_resolver(root: PathBuf) -> Result<Resolver> {
+    let cache = cell_cache();
+    let mut modules = BTreeMap::new();
+    for path in collect_cells(&root)? {
+        if let Some(module) = cache.load(&path)?.module {
+            modules.entry(module.path.to_string()).or_insert(path);
+        }
+    }
+    Ok(Resolver::new(vec![root], BTreeMap::new())
+        .with_modules(modules)
+        .with_cache(cache.clone()))
 }
 
 /// Orchestrate the full compilation pipeline.
-pub fn compile(opts: CompileOptions) -> Result<()> {
+pub fn compile(opts: CompileOptions) -> Result<CompileReport> {
     if opts.verbose {
         println!("Compiling: {}", opts.input_path.display());
     }
@@ -45,33 +608,283 @@ pub fn compile(opts: CompileOptions) -> Result<()> {
     let source = fs::read_to_string(&opts.input_path)
         .with_context(|| format!("Failed to read {}", opts.input_path.display()))?;
 
+    compile_source(&opts, &source)
+}
+
+/// Run the pipeline on `source`, the text of the cell `opts.input_path`
+/// names, e.g. read from stdin.
+pub fn compile_source(opts: &CompileOptions, source: &str) -> Result<CompileReport> {
+    if opts.verify_reproducible {
+        return verify_reproducible(
+            opts,
+            |opts| compile_source(opts, source),
+            |report| report.files(),
+        );
+    }
+    let _cell = info_span!("cell", path = %opts.input_path.display()).entered();
+    let _crash = crash::processing(&opts.input_path, source);
     if opts.verbose {
         println!("  [1/7] Parsing...");
     }
+    let (source, module) = parse(opts, source)?;
+    compile_module(opts, &source, &module)
+}
 
+/// Parse `source`, running the parse hooks before and after; returns the
+/// source as the hooks left it.
+fn parse(opts: &CompileOptions, source: &str) -> Result<(String, Module)> {
+    let mut source = source.to_string();
+    opts.hooks.pre_parse(&opts.input_path, &mut source)?;
     let file_path = opts.input_path.to_string_lossy().to_string();
-    let module = z1_parse::parse_module(&source).map_err(|e| {
+    let parsed = info_span!(target: STAGE, "parse").in_scope(|| z1_parse::parse_module(&source));
+    let mut module = parsed.map_err(|e| {
         let config = error_printer::ErrorPrinterConfig::default();
         error_printer::print_parse_error(&e, &source, &file_path, &config);
-        anyhow::anyhow!("Parse failed")
+        Failure::Parse.error("Parse failed")
     })?;
+    opts.hooks.post_parse(&opts.input_path, &mut module)?;
+    Ok((source, module))
+}
+
+/// Compile the cells at `paths` to `targets` in parallel, each to its
+/// default output paths, as [`compile`] or, with several targets,
+/// [`compile_targets`] would.
+///
+/// Imports are parsed once for the whole batch, through `opts.cache` or a
+/// cache shared by the batch. A cell that fails does not stop the others.
+pub fn compile_batch(
+    opts: &CompileOptions,
+    paths: &[PathBuf],
+    targets: &[CompileTarget],
+) -> BatchReport {
+    let started = Instant::now();
+    let cache = opts.cache.clone().unwrap_or_default();
+    let results = par_map(paths, |path| {
+        let opts = CompileOptions {
+            input_path: path.clone(),
+            output_path: None,
+            target: targets[0],
+            cache: Some(cache.clone()),
+            ..opts.clone()
+        };
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => {
+                let err =
+                    anyhow::Error::new(err).context(format!("Failed to read {}", path.display()));
+                events::compile_failed(path, None, &err);
+                return Err(err);
+            }
+        };
+        let result = match targets {
+            [_] => compile_source(&opts, &source).map(|report| (vec![report], None)),
+            _ => compile_targets(&opts, &source, targets)
+                .map(|report| (report.targets, Some(report.meta))),
+        };
+        // Streamed as each cell finishes, ahead of the report
+        match &result {
+            Ok((reports, meta)) => events::compiled(path, reports, meta.as_deref()),
+            Err(err) => events::compile_failed(path, Some(&source), err),
+        }
+        result
+    });
+
+    let cells: Vec<BatchCell> = paths
+        .iter()
+        .zip(results)
+        .map(|(path, result)| match result {
+            Ok((targets, meta)) => BatchCell {
+                path: path.clone(),
+                targets,
+                meta,
+                error: None,
+                failure: None,
+            },
+            Err(err) => BatchCell {
+                path: path.clone(),
+                targets: Vec::new(),
+                meta: None,
+                error: Some(format!("{err:#}")),
+                failure: Some(Failure::of(&err)),
+            },
+        })
+        .collect();
+    let failed = cells.iter().filter(|cell| cell.error.is_some()).count();
+    BatchReport {
+        compiled: cells.len() - failed,
+        failed,
+        cells,
+        elapsed: started.elapsed(),
+    }
+}
+
+/// Compile `source` to every target in `targets` in one run (`--target
+/// ts,wasm`), checking and lowering it once.
+///
+/// Each target is written to its default output path, with the artifacts its
+/// options ask for; the TypeScript output also gets a `.d.ts` declaration
+/// file. A `.meta.json` file next to the outputs describes the cell and
+/// everything written for it. `opts.target`, `output_path`, `stdout` and
+/// `emit_ir` are ignored.
+pub fn compile_targets(
+    opts: &CompileOptions,
+    source: &str,
+    targets: &[CompileTarget],
+) -> Result<TargetsReport> {
+    if opts.verify_reproducible {
+        return verify_reproducible(
+            opts,
+            |opts| compile_targets(opts, source, targets),
+            |report| {
+                let mut files: Vec<PathBuf> = report
+                    .targets
+                    .iter()
+                    .flat_map(CompileReport::files)
+                    .collect();
+                files.push(report.meta.clone());
+                files
+            },
+        );
+    }
+    let _cell = info_span!("cell", path = %opts.input_path.display()).entered();
+    let _crash = crash::processing(&opts.input_path, source);
+    if opts.verbose {
+        println!("  [1/7] Parsing...");
+    }
+    let (source, module) = parse(opts, source)?;
+    let opts = CompileOptions {
+        output_path: None,
+        stdout: false,
+        emit_ir: false,
+        ..opts.clone()
+    };
+    check_requirements(&module, targets)?;
+    let lowered = lower(&opts, &source, &module)?;
+
+    let mut reports = Vec::new();
+    for &target in targets {
+        let mut report = generate(&opts, target, &source, &module, &lowered)?;
+        if target == CompileTarget::TypeScript {
+            let path = determine_output_path(&opts.input_path, &None, "d.ts");
+            fs::write(&path, z1_codegen_ts::generate_declarations(&lowered.ir))
+                .with_context(|| format!("Failed to write to {}", path.display()))?;
+            report.artifacts.push(Artifact {
+                kind: ArtifactKind::Declarations,
+                path,
+            });
+        }
+        reports.push(report);
+    }
+
+    let meta = CellMeta::new(&opts, &module, &lowered, &reports)?;
+    let meta_path = determine_output_path(&opts.input_path, &None, "meta.json");
+    fs::write(&meta_path, serde_json::to_string_pretty(&meta)? + "\n")
+        .with_context(|| format!("Failed to write to {}", meta_path.display()))?;
+
+    Ok(TargetsReport {
+        targets: reports,
+        meta: meta_path,
+    })
+}
+
+/// Run the pipeline from step 2 on an already parsed cell.
+///
+/// `source` is the text `module` was parsed from, used for diagnostics and
+/// debug info; `opts.input_path` still names the cell.
+pub fn compile_module(
+    opts: &CompileOptions,
+    source: &str,
+    module: &Module,
+) -> Result<CompileReport> {
+    check_requirements(module, &[opts.target])?;
+    let lowered = lower(opts, source, module)?;
+
+    // If emit-ir, write IR and stop
+    if opts.emit_ir {
+        let ir_debug = format!("; IR for module: {}\n\n{:#?}", lowered.ir.name, lowered.ir);
+        let output_path = write_output(opts, "ir.txt", ir_debug.as_bytes())?;
+
+        return Ok(CompileReport {
+            output: output_path,
+            bytes: ir_debug.len(),
+            ir: true,
+            stats: CompileStats::new(
+                "ir.txt",
+                ir_debug.as_bytes(),
+                opts.opt_level,
+                &lowered.stats,
+            ),
+            artifacts: vec![],
+            provenance: None,
+        });
+    }
+
+    generate(opts, opts.target, source, module, &lowered)
+}
+
+/// The target-independent half of the pipeline: a checked cell lowered to
+/// optimized IR, which every requested target is generated from.
+struct Lowered {
+    ir: z1_ir::IrModule,
+    stats: z1_ir::optimize::OptStats,
+    /// Bundled std cells among the imports
+    bundled: Vec<String>,
+}
+
+/// Steps 2 to 6: check `module` and lower it to optimized IR.
+fn lower(opts: &CompileOptions, source: &str, module: &Module) -> Result<Lowered> {
+    let file_path = opts.input_path.to_string_lossy().to_string();
+
+    // Imports and policy limits come from the enclosing project, if any
+    // Bundled std cells among the imports get their runtime shims written
+    // next to the output
+    let (context, imports, bundled) = if opts.check {
+        let _stage = info_span!(target: STAGE, "resolve").entered();
+        let mut context = CheckContext::for_cell(&opts.input_path)?;
+        if let Some(cache) = &opts.cache {
+            context = context.with_cache(cache);
+        }
+        let resolved = context
+            .load_imports(module)
+            .context("Import resolution failed")
+            .map_err(|err| Failure::Resolve.wrap(err))?;
+        info!(imports = resolved.len(), "imports resolved");
+        if opts.verbose && !resolved.is_empty() {
+            println!("      Imports: {} resolved", resolved.len());
+        }
+        let bundled: Vec<String> = resolved
+            .values()
+            .filter(|resolved| resolved.bundled)
+            .map(|resolved| resolved.import.clone())
+            .collect();
+        let imports = resolved
+            .into_iter()
+            .map(|(import, resolved)| (import, resolved.module))
+            .collect();
+        (context, imports, bundled)
+    } else {
+        (CheckContext::default(), BTreeMap::new(), Vec::new())
+    };
 
     // Step 2: Type check (if enabled)
     if opts.check {
         if opts.verbose {
             println!("  [2/7] Type checking...");
         }
-        check_types(&module, &source, &file_path).context("Type check failed")?;
+        let _stage = info_span!(target: STAGE, "typeck").entered();
+        check_types(module, &imports, source, &file_path).context("Type check failed")?;
     } else if opts.verbose {
         println!("  [2/7] Type checking... (skipped)");
     }
+    opts.hooks.post_typecheck(&opts.input_path, module)?;
 
     // Step 3: Effect check (if enabled)
     if opts.check {
         if opts.verbose {
             println!("  [3/7] Effect checking...");
         }
-        check_effects(&module, &source, &file_path).context("Effect check failed")?;
+        let _stage = info_span!(target: STAGE, "effects").entered();
+        check_effects(module, &imports, source, &file_path).context("Effect check failed")?;
     } else if opts.verbose {
         println!("  [3/7] Effect checking... (skipped)");
     }
@@ -81,7 +894,13 @@ pub fn compile(opts: CompileOptions) -> Result<()> {
         if opts.verbose {
             println!("  [4/7] Context estimation...");
         }
-        let estimate = check_context(&module)?;
+        let estimate = info_span!(target: STAGE, "ctx")
+            .in_scope(|| check_context(module, source, &file_path))?;
+        info!(
+            tokens = estimate.total_tokens,
+            budget = ?estimate.budget,
+            "context estimated"
+        );
 
         if opts.verbose {
             let total = estimate.total_tokens;
@@ -100,7 +919,8 @@ pub fn compile(opts: CompileOptions) -> Result<()> {
         if opts.verbose {
             println!("  [5/7] Policy checking...");
         }
-        check_policy(&module).context("Policy check failed")?;
+        let _stage = info_span!(target: STAGE, "policy").entered();
+        check_policy(module, &context.limits, source, &file_path).context("Policy check failed")?;
     } else if opts.verbose {
         println!("  [5/7] Policy checking... (skipped)");
     }
@@ -109,13 +929,25 @@ pub fn compile(opts: CompileOptions) -> Result<()> {
     if opts.verbose {
         println!("  [6/7] Lowering to IR...");
     }
-    let mut ir_module = z1_ir::lower_to_ir(&module).context("IR generation failed")?;
+    let mut ir_module = info_span!(target: STAGE, "lower")
+        .in_scope(|| z1_ir::lower_to_ir(module))
+        .context("IR generation failed")?;
+    // Effectful imports are performed through capability objects
+    z1_ir::bind_capabilities(&mut ir_module, module, &imports);
 
     // Apply optimizations
     if opts.verbose {
         println!("  [6.5/7] Optimizing (level {:?})...", opts.opt_level);
     }
-    let opt_stats = z1_ir::optimize::optimize(&mut ir_module, opts.opt_level);
+    let opt_stats = info_span!(target: STAGE, "optimize")
+        .in_scope(|| z1_ir::optimize::optimize(&mut ir_module, opts.opt_level));
+    info!(
+        folded = opt_stats.constants_folded,
+        eliminated = opt_stats.dead_code_eliminated,
+        inlined = opt_stats.functions_inlined,
+        "optimized"
+    );
+    opts.hooks.post_ir(&opts.input_path, &mut ir_module)?;
     if opts.verbose && opt_stats.total_optimizations() > 0 {
         println!(
             "      Optimizations: {} folded, {} eliminated, {} inlined",
@@ -123,109 +955,495 @@ pub fn compile(opts: CompileOptions) -> Result<()> {
         );
     }
 
-    // If emit-ir, write IR and stop
-    if opts.emit_ir {
-        let output_path = determine_output_path(&opts.input_path, &opts.output_path, "ir.txt");
-        let ir_debug = format!("; IR for module: {}\n\n{ir_module:#?}", ir_module.name);
-        fs::write(&output_path, &ir_debug)
-            .with_context(|| format!("Failed to write IR to {}", output_path.display()))?;
+    Ok(Lowered {
+        ir: ir_module,
+        stats: opt_stats,
+        bundled,
+    })
+}
 
-        println!("✓ IR emitted to: {}", output_path.display());
-        return Ok(());
+/// Step 7: generate the code of `lowered` for `target` and write it, with
+/// the artifacts that go along with it.
+fn generate(
+    opts: &CompileOptions,
+    target: CompileTarget,
+    source: &str,
+    module: &Module,
+    lowered: &Lowered,
+) -> Result<CompileReport> {
+    let ir_module = &lowered.ir;
+    if opts.require_entry && z1_codegen_wasm::entrypoint(ir_module).is_none() {
+        let entry = z1_codegen_wasm::ENTRYPOINT;
+        if ir_module.functions.iter().any(|f| f.name == entry) {
+            anyhow::bail!("Entrypoint `{entry}` must not take parameters");
+        }
+        anyhow::bail!("No entrypoint: define `fn {entry}()` to produce a runnable module");
     }
 
     // Step 7: Code generation
     if opts.verbose {
-        println!("  [7/7] Generating {}...", target_name(opts.target));
+        println!("  [7/7] Generating {}...", target_name(target));
     }
 
-    let (code, extension) = match opts.target {
+    let stage = info_span!(target: STAGE, "codegen").entered();
+    let mut artifacts = Vec::new();
+    let (code, extension) = match target {
         CompileTarget::TypeScript => {
-            let ts_code = z1_codegen_ts::generate_typescript(&ir_module);
+            let ts_code = z1_codegen_ts::generate_typescript(ir_module);
             (ts_code.into_bytes(), "ts")
         }
         CompileTarget::Wasm => {
-            if opts.binary {
-                // Generate binary WASM
-                let wasm_binary =
-                    z1_codegen_wasm::generate_wasm_binary_optimized(&ir_module, opts.opt_level)
-                        .map_err(|e| anyhow::anyhow!("WASM binary generation failed: {e}"))?;
-
-                // Note: Validation is available but commented out due to known issues in WAT generation
-                // Uncomment this when WAT generation is fully correct
-                // z1_codegen_wasm::validate_wasm_binary(&wasm_binary)
-                //     .map_err(|e| anyhow::anyhow!("WASM binary validation failed: {}", e))?;
-
-                (wasm_binary, "wasm")
+            if opts.component {
+                let component =
+                    z1_codegen_wasm::component::generate_component(ir_module, opts.opt_level)
+                        .map_err(|e| anyhow::anyhow!("WASM component generation failed: {e}"))?;
+                let wit = z1_codegen_wasm::component::generate_wit(ir_module)
+                    .map_err(|e| anyhow::anyhow!("WIT generation failed: {e}"))?;
+
+                let wasm_path = determine_output_path(&opts.input_path, &opts.output_path, "wasm");
+                let wit_path = wasm_path.with_extension("wit");
+                fs::write(&wit_path, wit)
+                    .with_context(|| format!("Failed to write to {}", wit_path.display()))?;
+                artifacts.push(Artifact {
+                    kind: ArtifactKind::Wit,
+                    path: wit_path,
+                });
+
+                (component, "wasm")
             } else {
-                // Generate text WAT
-                let wat_code = z1_codegen_wasm::generate_wasm_optimized(&ir_module, opts.opt_level);
-                (wat_code.into_bytes(), "wat")
+                // `ir_module` has already been optimized
+                let options = z1_codegen_wasm::WasmOptions {
+                    debug: opts.debug.then(|| {
+                        let path = embedded_path(opts, &opts.input_path);
+                        debug_info(module, source, &path.to_string_lossy())
+                    }),
+                    gc: opts.wasm_gc,
+                    opt_size: opts.opt_size,
+                    ..Default::default()
+                };
+
+                if opts.binary {
+                    let wasm_binary = if opts.debug {
+                        // DWARF line info points at the WAT, so keep it next to the binary
+                        let wat_code =
+                            z1_codegen_wasm::generate_wasm_with_options(ir_module, &options);
+                        let wasm_path =
+                            determine_output_path(&opts.input_path, &opts.output_path, "wasm");
+                        let wat_path = wasm_path.with_extension("wat");
+                        fs::write(&wat_path, &wat_code).with_context(|| {
+                            format!("Failed to write to {}", wat_path.display())
+                        })?;
+                        let binary = z1_codegen_wasm::generate_wasm_binary_with_debug(
+                            &wat_code,
+                            &embedded_path(opts, &wat_path),
+                        );
+                        artifacts.push(Artifact {
+                            kind: ArtifactKind::Wat,
+                            path: wat_path,
+                        });
+                        binary
+                    } else {
+                        z1_codegen_wasm::generate_wasm_binary_with_options(ir_module, &options)
+                    }
+                    .map_err(|e| anyhow::anyhow!("WASM binary generation failed: {e}"))?;
+
+                    if opts.validate {
+                        z1_codegen_wasm::validate_wasm_binary(&wasm_binary)
+                            .map_err(|e| anyhow::anyhow!("WASM binary validation failed: {e}"))?;
+                    }
+
+                    (wasm_binary, "wasm")
+                } else {
+                    // Generate text WAT
+                    let wat_code = z1_codegen_wasm::generate_wasm_with_options(ir_module, &options);
+
+                    if opts.validate {
+                        z1_codegen_wasm::validate_wat(&wat_code)
+                            .map_err(|e| anyhow::anyhow!("WASM validation failed: {e}"))?;
+                    }
+
+                    (wat_code.into_bytes(), "wat")
+                }
             }
         }
     };
 
-    // Write output
-    let output_path = determine_output_path(&opts.input_path, &opts.output_path, extension);
-    fs::write(&output_path, code)
-        .with_context(|| format!("Failed to write to {}", output_path.display()))?;
+    drop(stage);
+    info!(output = extension, bytes = code.len(), "code generated");
 
-    println!("✓ Compiled to: {}", output_path.display());
+    let mut code = code;
+    opts.hooks.pre_emit(&opts.input_path, target, &mut code)?;
+    let output_path = write_output(opts, extension, &code)?;
 
-    Ok(())
+    let provenance = match &opts.provenance {
+        Some(prov) => Some(record_provenance(prov, module, opts, extension, &code)?),
+        None => None,
+    };
+
+    let backend = match target {
+        CompileTarget::TypeScript => Some(z1_std::Backend::TypeScript),
+        CompileTarget::Wasm if opts.emit_glue => Some(z1_std::Backend::Wasm),
+        CompileTarget::Wasm => None,
+    };
+    if let (Some(backend), false) = (backend, opts.stdout) {
+        artifacts.extend(write_shims(&output_path, &lowered.bundled, backend)?);
+    }
+
+    if opts.emit_glue && target == CompileTarget::Wasm {
+        let glue_path = output_path.with_extension("js");
+        fs::write(
+            &glue_path,
+            z1_codegen_wasm::abi::generate_js_glue(ir_module),
+        )
+        .with_context(|| format!("Failed to write to {}", glue_path.display()))?;
+        let abi_path = output_path.with_extension("abi.md");
+        fs::write(&abi_path, z1_codegen_wasm::abi::generate_abi_doc(ir_module))
+            .with_context(|| format!("Failed to write to {}", abi_path.display()))?;
+        artifacts.push(Artifact {
+            kind: ArtifactKind::Glue,
+            path: glue_path,
+        });
+        artifacts.push(Artifact {
+            kind: ArtifactKind::Abi,
+            path: abi_path,
+        });
+    }
+
+    Ok(CompileReport {
+        output: output_path,
+        bytes: code.len(),
+        ir: false,
+        stats: CompileStats::new(extension, &code, opts.opt_level, &lowered.stats),
+        artifacts,
+        provenance,
+    })
+}
+
+/// Write the runtime shims of the bundled std cells `imports` names for
+/// `backend` next to `output`, under the file names generated imports refer
+/// to: `std_http.ts` for `std/http`.
+fn write_shims(
+    output: &Path,
+    imports: &[String],
+    backend: z1_std::Backend,
+) -> Result<Vec<Artifact>> {
+    let extension = match backend {
+        z1_std::Backend::TypeScript => "ts",
+        z1_std::Backend::Wasm => "js",
+    };
+    let dir = output.parent().unwrap_or(Path::new(""));
+    let mut artifacts = Vec::new();
+    for import in imports {
+        let Some(shim) = z1_std::cell(import).and_then(|cell| cell.shim(backend)) else {
+            continue;
+        };
+        let path = dir.join(format!("{}.{extension}", import.replace('/', "_")));
+        fs::write(&path, shim).with_context(|| format!("Failed to write to {}", path.display()))?;
+        artifacts.push(Artifact {
+            kind: ArtifactKind::Shim,
+            path,
+        });
+    }
+    Ok(artifacts)
 }
 
 /// Type check the module using z1-typeck.
-fn check_types(module: &Module, source: &str, file_path: &str) -> Result<()> {
-    z1_typeck::check_module(module).map_err(|e| {
+fn check_types(
+    module: &Module,
+    imports: &BTreeMap<String, Module>,
+    source: &str,
+    file_path: &str,
+) -> Result<()> {
+    z1_typeck::check_module_with_imports(module, imports).map_err(|e| {
         let config = error_printer::ErrorPrinterConfig::default();
         error_printer::print_type_error(&e, source, file_path, &config);
-        anyhow::anyhow!("Type check failed")
+        Failure::Type.error("Type check failed")
     })
 }
 
-/// Effect check the module using z1-effects.
-fn check_effects(module: &Module, source: &str, file_path: &str) -> Result<()> {
-    z1_effects::check_module(module).map_err(|e| {
-        let config = error_printer::ErrorPrinterConfig::default();
-        error_printer::print_effect_error(&e, source, file_path, &config);
-        anyhow::anyhow!("Effect check failed")
-    })
+/// Effect check the module and the functions it imports using z1-effects.
+fn check_effects(
+    module: &Module,
+    imports: &BTreeMap<String, Module>,
+    source: &str,
+    file_path: &str,
+) -> Result<()> {
+    z1_effects::check_module(module)
+        .and_then(|()| z1_effects::check_imports(module, imports))
+        .map_err(|e| {
+            let config = error_printer::ErrorPrinterConfig::default();
+            error_printer::print_effect_error(&e, source, file_path, &config);
+            Failure::Effect.error("Effect check failed")
+        })
 }
 
 /// Context estimation with budget enforcement.
-fn check_context(module: &Module) -> Result<z1_ctx::CellEstimate> {
-    let estimate = z1_ctx::estimate_cell(module)?;
-
-    if let Some(budget) = module.ctx_budget {
-        if estimate.total_tokens > budget {
-            anyhow::bail!(
-                "Context budget exceeded: {} tokens used, {} allowed",
-                estimate.total_tokens,
-                budget
-            );
+fn check_context(module: &Module, source: &str, file_path: &str) -> Result<z1_ctx::CellEstimate> {
+    z1_ctx::estimate_cell(module).map_err(|err| {
+        let config = error_printer::ErrorPrinterConfig::default();
+        error_printer::print_context_error(&err, source, file_path, &config);
+        let failure = ctx_failure(&err);
+        match err {
+            z1_ctx::CtxError::Format(_) => failure.wrap(err.into()),
+            _ => failure.error("Context budget exceeded"),
         }
-    }
+    })
+}
 
-    Ok(estimate)
+/// Class of a context estimation error: budgets exceeded, or a cell the
+/// estimator could not format.
+pub(crate) fn ctx_failure(err: &z1_ctx::CtxError) -> Failure {
+    match err {
+        z1_ctx::CtxError::Format(_) => Failure::Error,
+        _ => Failure::Budget,
+    }
 }
 
 /// Policy gate enforcement using z1-policy.
-fn check_policy(module: &Module) -> Result<()> {
-    let policy = z1_policy::PolicyLimits::default();
-    let checker = z1_policy::PolicyChecker::new(policy);
+fn check_policy(
+    module: &Module,
+    limits: &PolicyLimits,
+    source: &str,
+    file_path: &str,
+) -> Result<()> {
+    let checker = z1_policy::PolicyChecker::new(limits.clone());
 
     checker.check_module(module).map_err(|violations| {
-        let msg = violations
-            .iter()
-            .map(|v| format!("  - {v}"))
-            .collect::<Vec<_>>()
-            .join("\n");
-        anyhow::anyhow!("Policy violations:\n{msg}")
+        let config = error_printer::ErrorPrinterConfig::default();
+        error_printer::print_policy_violations(&violations, module, source, file_path, &config);
+        Failure::Policy.error(format!("{} policy violation(s)", violations.len()))
     })
 }
 
-/// Determine output file path.
+/// Refuse to build a cell for a target it does not declare in `targets=[..]`
+/// or with a toolchain older than its `toolchain>=` requirement.
+fn check_requirements(module: &Module, targets: &[CompileTarget]) -> Result<()> {
+    if !module.targets.is_empty() {
+        for &target in targets {
+            let name = target_tag(target);
+            if !module.targets.iter().any(|declared| declared == name) {
+                return Err(Failure::Policy.error(format!(
+                    "cell only builds for targets=[{}], not {name}",
+                    module.targets.join(",")
+                )));
+            }
+        }
+    }
+    if let Some(required) = &module.toolchain {
+        let current = env!("CARGO_PKG_VERSION");
+        if version_older(current, required) {
+            return Err(Failure::Policy.error(format!(
+                "cell requires toolchain>={required}, but this is z1-cli {current}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Whether dotted version `version` sorts before `required`, comparing
+/// numeric segments in order; missing segments count as zero.
+fn version_older(version: &str, required: &str) -> bool {
+    let segments = |version: &str| -> Vec<u64> {
+        version
+            .split('.')
+            .map(|segment| segment.parse().unwrap_or(0))
+            .collect()
+    };
+    let (version, required) = (segments(version), segments(required));
+    let len = version.len().max(required.len());
+    let padded = |segments: &[u64]| -> Vec<u64> {
+        (0..len)
+            .map(|i| segments.get(i).copied().unwrap_or(0))
+            .collect()
+    };
+    padded(&version) < padded(&required)
+}
+
+/// Source lines of every function, for WASM debug info.
+fn debug_info(module: &Module, source: &str, file_path: &str) -> z1_codegen_wasm::DebugInfo {
+    let function_lines = module
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            z1_ast::Item::Fn(func) => {
+                let offset = (func.span.start as usize).min(source.len());
+                let line = source.as_bytes()[..offset]
+                    .iter()
+                    .filter(|&&b| b == b'\n')
+                    .count() as u32
+                    + 1;
+                Some((func.name.to_string(), line))
+            }
+            _ => None,
+        })
+        .collect();
+
+    z1_codegen_wasm::DebugInfo {
+        source_path: file_path.to_string(),
+        function_lines,
+    }
+}
+
+/// Append (and sign, if a key is configured) a provenance entry for a build.
+///
+/// The entry ID names the module and its input semantic hash, the diff hash
+/// is the hash of the written artifact, and the prompt hash covers the full
+/// build description (toolchain, target, optimization level, the targets
+/// and toolchain the cell requires, input and output hashes), which is also
+/// kept as the excerpt.
+fn record_provenance(
+    prov: &ProvenanceOptions,
+    module: &Module,
+    opts: &CompileOptions,
+    extension: &str,
+    code: &[u8],
+) -> Result<ProvenanceRecord> {
+    let toolchain = format!("z1-cli/{}", env!("CARGO_PKG_VERSION"));
+    let semhash = module_hashes(module).semantic;
+    let artifact = hash_bytes(code, HashAlgo::Sha3_256);
+    let mut requires = String::new();
+    if !module.targets.is_empty() {
+        requires.push_str(&format!(" targets={}", module.targets.join(",")));
+    }
+    if let Some(required) = &module.toolchain {
+        requires.push_str(&format!(" toolchain>={required}"));
+    }
+    let description = format!(
+        "{toolchain} compile target={extension} opt={:?}{requires} input={semhash} output={artifact}",
+        opts.opt_level
+    );
+
+    let module_path = module.path.to_string();
+    let mut chain = load_or_new(&prov.chain)?;
+    let entry = ProvenanceEntry {
+        entry_id: format!("build:{module_path}@{semhash}"),
+        prev: None,
+        merged: vec![],
+        actor: prov.actor.clone(),
+        model: "none".to_string(),
+        prompt_sha3: hash_bytes(description.as_bytes(), HashAlgo::Sha3_256),
+        prompt_excerpt: description.chars().take(200).collect(),
+        redaction: None,
+        tools: vec![toolchain],
+        diff_sha3: artifact,
+        cells: vec![CellBinding {
+            module: module_path,
+            semhash,
+        }],
+        timestamp: build_time(opts),
+        signatures: vec![],
+        keyless: vec![],
+        rotation: None,
+        checkpoint: None,
+        timestamp_token: None,
+    };
+    chain.append(entry).context("invalid provenance entry")?;
+
+    let signed = match prov.key.signer()? {
+        Some(signing_key) => {
+            let signer = prov.signer.as_deref().unwrap_or(&prov.actor);
+            sign_latest(&mut chain, signing_key.as_ref(), signer)?;
+            true
+        }
+        None => false,
+    };
+    chain
+        .save_to_file(&prov.chain)
+        .with_context(|| format!("failed to write {}", prov.chain.display()))?;
+
+    Ok(ProvenanceRecord {
+        chain: prov.chain.clone(),
+        signed,
+    })
+}
+
+/// `path` as embedded in the output: just its file name with
+/// `opts.reproducible`, since the directory differs between checkouts. The
+/// files a cell compiles to are written next to each other, so the name is
+/// also the path relative to any of them.
+fn embedded_path(opts: &CompileOptions, path: &Path) -> PathBuf {
+    match (opts.reproducible, path.file_name()) {
+        (true, Some(name)) => PathBuf::from(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// When a build happens: `SOURCE_DATE_EPOCH` with `opts.reproducible`, if
+/// set, otherwise now.
+fn build_time(opts: &CompileOptions) -> DateTime<Utc> {
+    let pinned = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .filter(|_| opts.reproducible)
+        .and_then(|epoch| epoch.trim().parse().ok())
+        .and_then(|epoch| DateTime::from_timestamp(epoch, 0));
+    pinned.unwrap_or_else(Utc::now)
+}
+
+/// Run `build` twice with `reproducible` set, failing with
+/// [`Failure::Check`] unless the second build writes the same files, byte
+/// for byte, as the first. Only the second build records provenance.
+fn verify_reproducible<R>(
+    opts: &CompileOptions,
+    build: impl Fn(&CompileOptions) -> Result<R>,
+    files: impl Fn(&R) -> Vec<PathBuf>,
+) -> Result<R> {
+    let opts = CompileOptions {
+        reproducible: true,
+        verify_reproducible: false,
+        ..opts.clone()
+    };
+    let first = build(&CompileOptions {
+        verbose: false,
+        provenance: None,
+        ..opts.clone()
+    })?;
+    let mut written = Vec::new();
+    for path in files(&first) {
+        let bytes =
+            fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        written.push((path, bytes));
+    }
+
+    let second = build(&opts)?;
+    let rewritten = files(&second);
+    for (path, bytes) in &written {
+        let same = rewritten.contains(path) && fs::read(path).ok().as_ref() == Some(bytes);
+        if !same {
+            return Err(Failure::Check.error(format!(
+                "Build is not reproducible: {} differs between two builds",
+                path.display()
+            )));
+        }
+    }
+    if let Some(path) = rewritten
+        .iter()
+        .find(|path| !written.iter().any(|(written, _)| written == *path))
+    {
+        return Err(Failure::Check.error(format!(
+            "Build is not reproducible: only the second build wrote {}",
+            path.display()
+        )));
+    }
+    Ok(second)
+}
+
+/// Write `code` to the output path for `extension`, or to stdout with
+/// `opts.stdout`; returns where it went.
+fn write_output(opts: &CompileOptions, extension: &str, code: &[u8]) -> Result<PathBuf> {
+    if opts.stdout {
+        let mut stdout = io::stdout().lock();
+        stdout
+            .write_all(code)
+            .and_then(|()| stdout.flush())
+            .context("Failed to write to stdout")?;
+        return Ok(PathBuf::from("-"));
+    }
+    let output_path = determine_output_path(&opts.input_path, &opts.output_path, extension);
+    fs::write(&output_path, code)
+        .with_context(|| format!("Failed to write to {}", output_path.display()))?;
+    Ok(output_path)
+}
+
 fn determine_output_path(input: &Path, output: &Option<PathBuf>, extension: &str) -> PathBuf {
     if let Some(out) = output {
         return out.clone();
@@ -236,6 +1454,14 @@ fn determine_output_path(input: &Path, output: &Option<PathBuf>, extension: &str
 }
 
 /// Get human-readable target name.
+/// The name of `target` in a cell's `targets=[..]` header field.
+fn target_tag(target: CompileTarget) -> &'static str {
+    match target {
+        CompileTarget::TypeScript => "ts",
+        CompileTarget::Wasm => "wasm",
+    }
+}
+
 fn target_name(target: CompileTarget) -> &'static str {
     match target {
         CompileTarget::TypeScript => "TypeScript",
@@ -316,10 +1542,23 @@ fn foo(x: U32, y: U32, z: U32) -> U32
             output_path: Some(output.clone()),
             target: CompileTarget::TypeScript,
             binary: false,
+            component: false,
+            debug: false,
+            emit_glue: false,
+            require_entry: false,
+            opt_size: false,
+            wasm_gc: false,
+            validate: true,
             check: true,
             emit_ir: false,
             opt_level: z1_ir::optimize::OptLevel::O0,
             verbose: false,
+            stdout: false,
+            reproducible: false,
+            verify_reproducible: false,
+            provenance: None,
+            cache: None,
+            hooks: Hooks::default(),
         };
 
         let result = compile(opts);
@@ -342,10 +1581,23 @@ fn foo(x: U32, y: U32, z: U32) -> U32
             output_path: Some(output.clone()),
             target: CompileTarget::Wasm,
             binary: false,
+            component: false,
+            debug: false,
+            emit_glue: false,
+            require_entry: false,
+            opt_size: false,
+            wasm_gc: false,
+            validate: true,
             check: true,
             emit_ir: false,
             opt_level: z1_ir::optimize::OptLevel::O0,
             verbose: false,
+            stdout: false,
+            reproducible: false,
+            verify_reproducible: false,
+            provenance: None,
+            cache: None,
+            hooks: Hooks::default(),
         };
 
         let result = compile(opts);
@@ -368,10 +1620,23 @@ fn foo(x: U32, y: U32, z: U32) -> U32
             output_path: Some(output.clone()),
             target: CompileTarget::TypeScript,
             binary: false,
+            component: false,
+            debug: false,
+            emit_glue: false,
+            require_entry: false,
+            opt_size: false,
+            wasm_gc: false,
+            validate: true,
             check: true,
             emit_ir: true,
             opt_level: z1_ir::optimize::OptLevel::O0,
             verbose: false,
+            stdout: false,
+            reproducible: false,
+            verify_reproducible: false,
+            provenance: None,
+            cache: None,
+            hooks: Hooks::default(),
         };
 
         let result = compile(opts);
@@ -392,10 +1657,23 @@ fn foo(x: U32, y: U32, z: U32) -> U32
             output_path: None,
             target: CompileTarget::TypeScript,
             binary: false,
+            component: false,
+            debug: false,
+            emit_glue: false,
+            require_entry: false,
+            opt_size: false,
+            wasm_gc: false,
+            validate: true,
             check: true,
             emit_ir: false,
             opt_level: z1_ir::optimize::OptLevel::O0,
             verbose: false,
+            stdout: false,
+            reproducible: false,
+            verify_reproducible: false,
+            provenance: None,
+            cache: None,
+            hooks: Hooks::default(),
         };
 
         let result = compile(opts);
@@ -413,10 +1691,23 @@ fn foo(x: U32, y: U32, z: U32) -> U32
             output_path: None,
             target: CompileTarget::TypeScript,
             binary: false,
+            component: false,
+            debug: false,
+            emit_glue: false,
+            require_entry: false,
+            opt_size: false,
+            wasm_gc: false,
+            validate: true,
             check: true,
             emit_ir: false,
             opt_level: z1_ir::optimize::OptLevel::O0,
             verbose: false,
+            stdout: false,
+            reproducible: false,
+            verify_reproducible: false,
+            provenance: None,
+            cache: None,
+            hooks: Hooks::default(),
         };
 
         let result = compile(opts);
@@ -441,10 +1732,23 @@ fn foo(x: U32, y: U32, z: U32) -> U32
             output_path: None,
             target: CompileTarget::TypeScript,
             binary: false,
+            component: false,
+            debug: false,
+            emit_glue: false,
+            require_entry: false,
+            opt_size: false,
+            wasm_gc: false,
+            validate: true,
             check: true,
             emit_ir: false,
             opt_level: z1_ir::optimize::OptLevel::O0,
             verbose: false,
+            stdout: false,
+            reproducible: false,
+            verify_reproducible: false,
+            provenance: None,
+            cache: None,
+            hooks: Hooks::default(),
         };
 
         let result = compile(opts);
@@ -477,10 +1781,23 @@ fn f6() -> Unit eff [pure] { ret Unit; }
             output_path: None,
             target: CompileTarget::TypeScript,
             binary: false,
+            component: false,
+            debug: false,
+            emit_glue: false,
+            require_entry: false,
+            opt_size: false,
+            wasm_gc: false,
+            validate: true,
             check: true,
             emit_ir: false,
             opt_level: z1_ir::optimize::OptLevel::O0,
             verbose: false,
+            stdout: false,
+            reproducible: false,
+            verify_reproducible: false,
+            provenance: None,
+            cache: None,
+            hooks: Hooks::default(),
         };
 
         let result = compile(opts);
@@ -494,6 +1811,24 @@ fn f6() -> Unit eff [pure] { ret Unit; }
         );
     }
 
+    #[test]
+    fn test_requirements_refuse_undeclared_target_and_old_toolchain() {
+        let module =
+            z1_parse::parse_module("m app:1.0 caps=[] targets=[ts] toolchain>=0.1\n").unwrap();
+        assert!(check_requirements(&module, &[CompileTarget::TypeScript]).is_ok());
+        let err = check_requirements(&module, &[CompileTarget::TypeScript, CompileTarget::Wasm])
+            .unwrap_err();
+        assert!(err.to_string().contains("not wasm"), "{err}");
+
+        let module = z1_parse::parse_module("m app:1.0 caps=[] toolchain>=99.0\n").unwrap();
+        let err = check_requirements(&module, &[CompileTarget::Wasm]).unwrap_err();
+        assert!(err.to_string().contains("toolchain>=99.0"), "{err}");
+
+        assert!(version_older("0.1.0", "0.3"));
+        assert!(!version_older("0.3.0", "0.3"));
+        assert!(!version_older("0.10.0", "0.9"));
+    }
+
     #[test]
     fn test_compile_with_no_check_skips_checks() {
         // This cell has an effect error, but we skip checks
@@ -504,10 +1839,23 @@ fn f6() -> Unit eff [pure] { ret Unit; }
             output_path: None,
             target: CompileTarget::TypeScript,
             binary: false,
+            component: false,
+            debug: false,
+            emit_glue: false,
+            require_entry: false,
+            opt_size: false,
+            wasm_gc: false,
+            validate: true,
             check: false,
             emit_ir: false,
             opt_level: z1_ir::optimize::OptLevel::O0,
             verbose: false,
+            stdout: false,
+            reproducible: false,
+            verify_reproducible: false,
+            provenance: None,
+            cache: None,
+            hooks: Hooks::default(),
         };
 
         let result = compile(opts);
@@ -528,10 +1876,23 @@ fn f6() -> Unit eff [pure] { ret Unit; }
             output_path: Some(custom_output.clone()),
             target: CompileTarget::TypeScript,
             binary: false,
+            component: false,
+            debug: false,
+            emit_glue: false,
+            require_entry: false,
+            opt_size: false,
+            wasm_gc: false,
+            validate: true,
             check: true,
             emit_ir: false,
             opt_level: z1_ir::optimize::OptLevel::O0,
             verbose: false,
+            stdout: false,
+            reproducible: false,
+            verify_reproducible: false,
+            provenance: None,
+            cache: None,
+            hooks: Hooks::default(),
         };
 
         let result = compile(opts);
@@ -548,10 +1909,23 @@ fn f6() -> Unit eff [pure] { ret Unit; }
             output_path: None,
             target: CompileTarget::TypeScript,
             binary: false,
+            component: false,
+            debug: false,
+            emit_glue: false,
+            require_entry: false,
+            opt_size: false,
+            wasm_gc: false,
+            validate: true,
             check: true,
             emit_ir: false,
             opt_level: z1_ir::optimize::OptLevel::O0,
             verbose: true, // Enable verbose output
+            stdout: false,
+            reproducible: false,
+            verify_reproducible: false,
+            provenance: None,
+            cache: None,
+            hooks: Hooks::default(),
         };
 
         // This will print to stdout, which we can't easily capture in tests
@@ -631,4 +2005,244 @@ fn f6() -> Unit eff [pure] { ret Unit; }
     //         assert!(wat.contains("Generated by Zero1 compiler"));
     //         assert!(wat.contains("WebAssembly"));
     //     }
+
+    #[test]
+    fn test_compile_records_signed_provenance() {
+        let (dir, input) = setup_test_cell(simple_valid_cell());
+        let chain_path = dir.path().join("build.z1p");
+        let key_path = dir.path().join("key.json");
+        let (private_key, public_key) = z1_prov::keygen();
+        fs::write(
+            &key_path,
+            serde_json::json!({ "private_key": hex::encode(private_key) }).to_string(),
+        )
+        .unwrap();
+
+        for level in [z1_ir::optimize::OptLevel::O0, z1_ir::optimize::OptLevel::O2] {
+            let opts = CompileOptions {
+                input_path: input.clone(),
+                output_path: None,
+                target: CompileTarget::TypeScript,
+                binary: false,
+                component: false,
+                debug: false,
+                emit_glue: false,
+                require_entry: false,
+                opt_size: false,
+                wasm_gc: false,
+                validate: true,
+                check: true,
+                emit_ir: false,
+                opt_level: level,
+                verbose: false,
+                stdout: false,
+                reproducible: false,
+                verify_reproducible: false,
+                cache: None,
+                hooks: Hooks::default(),
+                provenance: Some(ProvenanceOptions {
+                    chain: chain_path.clone(),
+                    actor: "ci:test".to_string(),
+                    key: KeyArgs {
+                        key: Some(key_path.clone()),
+                        ..Default::default()
+                    },
+                    signer: None,
+                }),
+            };
+            compile(opts).unwrap();
+        }
+
+        let chain = z1_prov::ProvenanceChain::load_from_file(&chain_path).unwrap();
+        assert_eq!(chain.len(), 2);
+        z1_prov::verify_chain(&chain).unwrap();
+        let keys = [("ci:test".to_string(), public_key)].into_iter().collect();
+        z1_prov::verify_chain_signatures(&chain, &keys, None).unwrap();
+
+        let entry = chain.latest().unwrap();
+        let source = fs::read_to_string(&input).unwrap();
+        let semhash = module_hashes(&z1_parse::parse_module(&source).unwrap()).semantic;
+        assert_eq!(entry.entry_id, format!("build:test@{semhash}"));
+        let artifact = fs::read(input.with_extension("ts")).unwrap();
+        assert_eq!(entry.diff_sha3, hash_bytes(&artifact, HashAlgo::Sha3_256));
+        assert!(entry.prompt_excerpt.contains("target=ts opt=O2"));
+        assert_eq!(
+            entry.cells,
+            [CellBinding {
+                module: "test".to_string(),
+                semhash
+            }]
+        );
+    }
+
+    #[test]
+    fn test_hooks_run_at_each_stage() {
+        use crate::commands::hooks::{Hook, Hooks};
+        use std::sync::Mutex;
+
+        /// Records the stages it sees, rejects a function named `debug` and
+        /// stamps the output.
+        #[derive(Default)]
+        struct Audit {
+            stages: Mutex<Vec<&'static str>>,
+        }
+
+        impl Hook for std::sync::Arc<Audit> {
+            fn name(&self) -> &str {
+                "audit"
+            }
+
+            fn pre_parse(&self, _cell: &Path, source: &mut String) -> Result<()> {
+                self.stages.lock().unwrap().push("pre-parse");
+                *source = source.replace("fn add", "fn sum");
+                Ok(())
+            }
+
+            fn post_parse(&self, _cell: &Path, module: &mut Module) -> Result<()> {
+                self.stages.lock().unwrap().push("post-parse");
+                match module
+                    .items
+                    .iter()
+                    .any(|item| matches!(item, Item::Fn(f) if f.name == "debug"))
+                {
+                    true => Err(Failure::Policy.error("`debug` must not ship")),
+                    false => Ok(()),
+                }
+            }
+
+            fn post_typecheck(&self, _cell: &Path, _module: &Module) -> Result<()> {
+                self.stages.lock().unwrap().push("post-typecheck");
+                Ok(())
+            }
+
+            fn post_ir(&self, _cell: &Path, _ir: &mut z1_ir::IrModule) -> Result<()> {
+                self.stages.lock().unwrap().push("post-IR");
+                Ok(())
+            }
+
+            fn pre_emit(
+                &self,
+                _cell: &Path,
+                _target: CompileTarget,
+                code: &mut Vec<u8>,
+            ) -> Result<()> {
+                self.stages.lock().unwrap().push("pre-emit");
+                code.splice(0..0, b"// audited\n".iter().copied());
+                Ok(())
+            }
+        }
+
+        let audit = std::sync::Arc::new(Audit::default());
+        let (_dir, input) = setup_test_cell(simple_valid_cell());
+        let opts = CompileOptions {
+            input_path: input.clone(),
+            output_path: None,
+            target: CompileTarget::TypeScript,
+            binary: false,
+            component: false,
+            debug: false,
+            emit_glue: false,
+            require_entry: false,
+            opt_size: false,
+            wasm_gc: false,
+            validate: true,
+            check: true,
+            emit_ir: false,
+            opt_level: z1_ir::optimize::OptLevel::O0,
+            verbose: false,
+            stdout: false,
+            reproducible: false,
+            verify_reproducible: false,
+            provenance: None,
+            cache: None,
+            hooks: Hooks::new().with(audit.clone()),
+        };
+
+        compile(opts.clone()).unwrap();
+        let code = fs::read_to_string(input.with_extension("ts")).unwrap();
+        assert!(code.starts_with("// audited\n"), "{code}");
+        assert!(code.contains("export function sum("), "{code}");
+        assert_eq!(
+            *audit.stages.lock().unwrap(),
+            [
+                "pre-parse",
+                "post-parse",
+                "post-typecheck",
+                "post-IR",
+                "pre-emit"
+            ]
+        );
+
+        let source = simple_valid_cell().replace("fn add", "fn debug");
+        let err = compile_source(&opts, &source).unwrap_err();
+        assert_eq!(Failure::of(&err), Failure::Policy);
+        assert!(format!("{err:#}").starts_with("post-parse hook `audit` failed: "));
+    }
+
+    #[test]
+    fn test_verify_reproducible_catches_differing_builds() {
+        use crate::commands::hooks::{Hook, Hooks};
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        /// Stamps the output with a counter, as a build timestamp would.
+        struct Stamp(AtomicU32);
+
+        impl Hook for Stamp {
+            fn name(&self) -> &str {
+                "stamp"
+            }
+
+            fn pre_emit(
+                &self,
+                _cell: &Path,
+                _target: CompileTarget,
+                code: &mut Vec<u8>,
+            ) -> Result<()> {
+                let build = self.0.fetch_add(1, Ordering::SeqCst);
+                code.extend(format!("// build {build}\n").bytes());
+                Ok(())
+            }
+        }
+
+        let (_dir, input) = setup_test_cell(simple_valid_cell());
+        let opts = CompileOptions {
+            input_path: input.clone(),
+            output_path: None,
+            target: CompileTarget::Wasm,
+            binary: true,
+            component: false,
+            debug: true,
+            emit_glue: true,
+            require_entry: false,
+            opt_size: false,
+            wasm_gc: false,
+            validate: true,
+            check: true,
+            emit_ir: false,
+            opt_level: z1_ir::optimize::OptLevel::O1,
+            verbose: false,
+            stdout: false,
+            reproducible: false,
+            verify_reproducible: true,
+            provenance: None,
+            cache: None,
+            hooks: Hooks::default(),
+        };
+        let report = compile(opts.clone()).unwrap();
+        assert_eq!(report.files().len(), 4);
+
+        let opts = CompileOptions {
+            hooks: Hooks::new().with(Stamp(AtomicU32::new(0))),
+            ..opts
+        };
+        let err = compile(opts.clone()).unwrap_err();
+        assert_eq!(Failure::of(&err), Failure::Check);
+        assert!(
+            err.to_string()
+                .contains("test.wasm differs between two builds"),
+            "{err}"
+        );
+        let err = compile_targets(&opts, simple_valid_cell(), &[CompileTarget::Wasm]).unwrap_err();
+        assert_eq!(Failure::of(&err), Failure::Check);
+    }
 }
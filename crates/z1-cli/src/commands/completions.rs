@@ -0,0 +1,62 @@
+//! `z1 completions` and `z1 man`: shell completions and manual pages.
+//!
+//! Both are generated from the CLI definition, so they cover every
+//! subcommand and flag. Completions offer the possible values of enumerated
+//! flags (targets, optimization levels, output formats, ...) and complete
+//! file or directory names where an argument takes a path.
+
+use std::io;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::{Args, Command, ValueHint};
+use clap_complete::Shell;
+
+use crate::output::{self, OutputFormat};
+
+#[derive(Debug, Args)]
+pub struct CompletionsArgs {
+    /// Shell to generate completions for.
+    #[arg(value_enum)]
+    pub shell: Shell,
+    /// Name the completions are registered for, if the binary is not
+    /// installed as `z1`.
+    #[arg(long, default_value = "z1")]
+    pub bin_name: String,
+}
+
+#[derive(Debug, Args)]
+pub struct ManArgs {
+    /// Write one page per command (`z1.1`, `z1-fmt.1`, ...) into this
+    /// directory instead of printing z1(1).
+    #[arg(long, value_hint = ValueHint::DirPath)]
+    pub out_dir: Option<PathBuf>,
+}
+
+/// Print completions for `cmd`, the top-level command, to stdout.
+pub fn run_completions(
+    args: CompletionsArgs,
+    mut cmd: Command,
+    format: OutputFormat,
+) -> Result<()> {
+    format.require("completions", &[])?;
+    clap_complete::generate(args.shell, &mut cmd, args.bin_name, &mut io::stdout());
+    Ok(())
+}
+
+/// Print the manual page of `cmd`, or write pages for it and every
+/// subcommand.
+pub fn run_man(args: ManArgs, cmd: Command, format: OutputFormat) -> Result<()> {
+    format.require("man", &[])?;
+    let Some(dir) = args.out_dir else {
+        clap_mangen::Man::new(cmd).render(&mut io::stdout())?;
+        return Ok(());
+    };
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    clap_mangen::generate_to(cmd, &dir)
+        .with_context(|| format!("Failed to write manual pages to {}", dir.display()))?;
+    if output::show_summary() {
+        println!("Wrote manual pages to {}", dir.display());
+    }
+    Ok(())
+}
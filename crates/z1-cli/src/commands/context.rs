@@ -0,0 +1,136 @@
+//! `z1 context`: the context an agent needs to edit one function.
+//!
+//! The bundle comes from [`z1_ctx::extract_context`]: the cell header, the
+//! function, the types it mentions, the signatures of what it calls, and the
+//! declarations it uses from its imports, nearest first until `--budget`
+//! runs out. The bundle is printed on stdout, ready to paste into a prompt.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueHint};
+use serde::Serialize;
+use z1_ctx::{ContextItem, ExtractError, ExtractOptions};
+
+use crate::commands::compile::CheckContext;
+use crate::commands::defaults::Defaults;
+use crate::commands::lint::display_path;
+use crate::commands::migrate::mode_of;
+use crate::exit::Failure;
+use crate::output::{self, print_json, OutputFormat};
+
+#[derive(Debug, Args)]
+pub struct ContextArgs {
+    /// Cell declaring the function.
+    #[arg(value_name = "CELL", value_hint = ValueHint::FilePath)]
+    pub cell: PathBuf,
+    /// Function to bundle the context of.
+    pub function: String,
+    /// Tokens the bundle may take (default: everything the function reaches).
+    #[arg(long, value_name = "TOKENS")]
+    pub budget: Option<u32>,
+    /// Custom characters-per-token ratio (default: `chars-per-token` in
+    /// z1.toml, else 3.8).
+    #[arg(long)]
+    pub chars_per_token: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct ContextReport {
+    cell: String,
+    function: String,
+    tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    budget: Option<u32>,
+    items: Vec<ItemRow>,
+    /// Declarations left out to stay within the budget
+    omitted: Vec<ItemRow>,
+    source: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ItemRow {
+    kind: &'static str,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    import: Option<String>,
+}
+
+impl From<ContextItem> for ItemRow {
+    fn from(item: ContextItem) -> Self {
+        ItemRow {
+            kind: item.kind.as_str(),
+            name: item.name,
+            import: item.import,
+        }
+    }
+}
+
+pub fn run(args: ContextArgs, format: OutputFormat) -> Result<()> {
+    format.require("context", &[OutputFormat::Json])?;
+    let cell = &args.cell;
+    let path = display_path(cell);
+    let source =
+        fs::read_to_string(cell).with_context(|| format!("Failed to read {}", cell.display()))?;
+    let module = z1_parse::parse_module(&source)
+        .map_err(|err| Failure::Parse.error(format!("{path}: {err}")))?;
+    let imports: BTreeMap<_, _> = CheckContext::for_cell(cell)?
+        .load_imports(&module)
+        .context("Import resolution failed")
+        .map_err(|err| Failure::Resolve.wrap(err))?
+        .into_iter()
+        .map(|(import, resolved)| (import, resolved.module))
+        .collect();
+    let mut options = ExtractOptions {
+        budget: args.budget,
+        mode: mode_of(cell),
+        ..ExtractOptions::default()
+    };
+    let chars_per_token = match args.chars_per_token {
+        Some(ratio) => Some(ratio),
+        None => Defaults::discover(cell)?.chars_per_token,
+    };
+    if let Some(ratio) = chars_per_token {
+        options.chars_per_token = ratio;
+    }
+    let bundle =
+        z1_ctx::extract_context(&module, &args.function, &imports, &options).map_err(|err| {
+            match err {
+                ExtractError::UnknownFunction(_) => Failure::Usage.error(format!("{path}: {err}")),
+                ExtractError::OverBudget { .. } => Failure::Budget.error(format!("{path}: {err}")),
+                err => anyhow::anyhow!("{path}: {err}"),
+            }
+        })?;
+
+    let report = ContextReport {
+        cell: path,
+        function: args.function,
+        tokens: bundle.tokens,
+        budget: args.budget,
+        items: bundle.items.into_iter().map(ItemRow::from).collect(),
+        omitted: bundle.omitted.into_iter().map(ItemRow::from).collect(),
+        source: bundle.source,
+    };
+    if format.is_json() {
+        return print_json(&report);
+    }
+    print!("{}", report.source);
+    if output::show_summary() {
+        eprintln!(
+            "Context of `{}` in {}: {} tokens, {} declaration(s)",
+            report.function,
+            report.cell,
+            report.tokens,
+            report.items.len()
+        );
+        for row in &report.omitted {
+            match &row.import {
+                Some(import) => eprintln!("  omitted {} `{}` from {import}", row.kind, row.name),
+                None => eprintln!("  omitted {} `{}`", row.kind, row.name),
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,531 @@
+//! `z1 daemon`: a build server that keeps cells warm between invocations.
+//!
+//! Small compiles are dominated by reading and parsing cells and their
+//! imports. The daemon listens on a local socket, `.z1/daemon.sock` in the
+//! workspace by default, and serves `fmt`, `lint` and `compile` from a
+//! [`CellCache`] that only rereads a cell when its length or modification
+//! time changes. Those commands use it transparently when it is listening:
+//! each request is one line of JSON answered by one line of JSON. A request
+//! the daemon cannot serve, a cell that does not parse or a failed compile,
+//! is run again in the client, so errors print exactly as without it. Set
+//! `Z1_NO_DAEMON` to bypass it.
+//!
+//! Each request carries the [`build_id`] of the client, and the daemon
+//! refuses to serve one from a different build: after an upgrade or a
+//! rebuild, commands run in the client until the daemon is restarted.
+//! `status` and `stop` are answered whatever the build.
+//!
+//! Requests are served in a [`z1_ast::Session`] holding the interned
+//! names and the cache. Once it holds more than `--max-names` names, later
+//! requests start a fresh session, and the old one is dropped with its
+//! cells when the requests in flight are done.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::Result;
+use clap::{Args, Subcommand, ValueHint};
+use serde::{Deserialize, Serialize};
+
+use crate::commands::build::workspace_dir;
+use crate::commands::compile::{CompileOptions, CompileReport};
+use crate::diagnostics::Diagnostic;
+use crate::exit::Failure;
+use crate::output::{self, print_json, OutputFormat};
+
+/// Socket of a workspace's daemon, relative to the workspace.
+const SOCKET: &str = ".z1/daemon.sock";
+
+/// Interned names after which the daemon starts a fresh session, by default.
+const MAX_NAMES: usize = 1_000_000;
+
+#[derive(Debug, Args)]
+pub struct DaemonArgs {
+    #[command(subcommand)]
+    pub command: Option<DaemonCommand>,
+    /// Socket to listen on or talk to (default: .z1/daemon.sock in the
+    /// workspace).
+    #[arg(long, global = true, env = "Z1_DAEMON_SOCKET", value_hint = ValueHint::FilePath)]
+    pub socket: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DaemonCommand {
+    /// Serve requests in the foreground until stopped (the default).
+    Start {
+        /// Drop the names and cells held once there are this many names.
+        #[arg(long, default_value_t = MAX_NAMES)]
+        max_names: usize,
+    },
+    /// Report whether a daemon is listening, and what it holds.
+    Status,
+    /// Ask the daemon to exit.
+    Stop,
+}
+
+/// A request with the build of the client that sent it.
+#[cfg(unix)]
+#[derive(Serialize, Deserialize)]
+struct Envelope<R> {
+    /// [`build_id`] of the client; empty from clients predating it
+    #[serde(default)]
+    build: String,
+    #[serde(flatten)]
+    request: R,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "request", rename_all = "kebab-case")]
+enum Request {
+    Status,
+    Stop,
+    Format {
+        path: PathBuf,
+        relaxed: bool,
+        reflow_symbols: bool,
+    },
+    Lint {
+        path: PathBuf,
+        /// Path diagnostics are reported under
+        display: String,
+    },
+    Compile {
+        options: Box<CompileOptions>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "response", rename_all = "kebab-case")]
+enum Response {
+    Status(Status),
+    Stopping,
+    Formatted {
+        source: String,
+        formatted: String,
+    },
+    Linted {
+        source: String,
+        diagnostics: Vec<Diagnostic>,
+    },
+    Compiled {
+        report: CompileReport,
+    },
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Status {
+    pid: u32,
+    version: String,
+    uptime_secs: u64,
+    /// Cells held in memory
+    cells: usize,
+    /// Requests served, not counting this one
+    requests: u64,
+    /// Distinct names interned in the session of new requests
+    #[serde(default)]
+    names: usize,
+}
+
+pub fn run(args: DaemonArgs, format: OutputFormat) -> Result<()> {
+    let socket = match args.socket {
+        Some(socket) => socket,
+        None => workspace_dir(None)?.join(SOCKET),
+    };
+    let start = DaemonCommand::Start {
+        max_names: MAX_NAMES,
+    };
+    match args.command.unwrap_or(start) {
+        DaemonCommand::Start { max_names } => {
+            format.require("daemon", &[])?;
+            server::serve(&socket, max_names)
+        }
+        DaemonCommand::Status => {
+            format.require("daemon status", &[OutputFormat::Json])?;
+            let Some(Response::Status(status)) = send(&socket, &Request::Status) else {
+                return Err(
+                    Failure::Error.error(format!("no daemon is listening on {}", socket.display()))
+                );
+            };
+            if format.is_json() {
+                return print_json(&status);
+            }
+            println!(
+                "z1 daemon {} (pid {}) listening on {}: {} cell(s) cached, {} name(s) interned, {} request(s) served in {}s",
+                status.version,
+                status.pid,
+                socket.display(),
+                status.cells,
+                status.names,
+                status.requests,
+                status.uptime_secs
+            );
+            Ok(())
+        }
+        DaemonCommand::Stop => {
+            format.require("daemon stop", &[])?;
+            if send(&socket, &Request::Stop).is_none() {
+                return Err(
+                    Failure::Error.error(format!("no daemon is listening on {}", socket.display()))
+                );
+            }
+            if output::show_summary() {
+                eprintln!("Stopped the daemon on {}", socket.display());
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Formatted text of the cell at `path` and its source, from the daemon.
+pub fn format(
+    path: &Path,
+    mode: z1_fmt::Mode,
+    options: &z1_fmt::FmtOptions,
+) -> Option<(String, String)> {
+    let request = Request::Format {
+        path: std::path::absolute(path).ok()?,
+        relaxed: mode == z1_fmt::Mode::Relaxed,
+        reflow_symbols: options.symmap_style == z1_fmt::SymMapStyle::Reflow,
+    };
+    match request_daemon(&request)? {
+        Response::Formatted { source, formatted } => Some((source, formatted)),
+        _ => None,
+    }
+}
+
+/// Source and lint diagnostics of the cell at `path`, from the daemon.
+pub(crate) fn lint(path: &Path, display: &str) -> Option<(String, Vec<Diagnostic>)> {
+    let request = Request::Lint {
+        path: std::path::absolute(path).ok()?,
+        display: display.to_string(),
+    };
+    match request_daemon(&request)? {
+        Response::Linted {
+            source,
+            diagnostics,
+        } => Some((source, diagnostics)),
+        _ => None,
+    }
+}
+
+/// Compile through the daemon; `None` for a build that prints as it goes or
+/// records provenance, and for one that failed.
+pub fn compile(opts: &CompileOptions) -> Option<CompileReport> {
+    if opts.verbose || opts.stdout || opts.provenance.is_some() || opts.verify_reproducible {
+        return None;
+    }
+    // The daemon runs elsewhere, so paths must not depend on its directory
+    let cwd = std::env::current_dir().ok()?;
+    let mut options = opts.clone();
+    options.input_path = cwd.join(&opts.input_path);
+    options.output_path = opts.output_path.as_ref().map(|path| cwd.join(path));
+    let Response::Compiled { mut report } = request_daemon(&Request::Compile {
+        options: Box::new(options),
+    })?
+    else {
+        return None;
+    };
+
+    // Report paths as the client named them
+    let relative = |path: PathBuf| {
+        if opts.input_path.is_absolute() {
+            return path;
+        }
+        match path.strip_prefix(&cwd) {
+            Ok(path) => path.to_path_buf(),
+            Err(_) => path,
+        }
+    };
+    report.ir = opts.emit_ir;
+    report.output = relative(report.output);
+    for artifact in &mut report.artifacts {
+        artifact.path = relative(std::mem::take(&mut artifact.path));
+    }
+    Some(report)
+}
+
+/// Identity of the running z1 binary: its version, and the size and
+/// modification time of its executable, which change with every build.
+#[cfg(unix)]
+fn build_id() -> &'static str {
+    static BUILD: OnceLock<String> = OnceLock::new();
+    BUILD.get_or_init(|| {
+        let version = env!("CARGO_PKG_VERSION");
+        let built = std::env::current_exe()
+            .and_then(|exe| exe.metadata())
+            .and_then(|meta| Ok((meta.len(), meta.modified()?)));
+        match built {
+            Ok((len, modified)) => {
+                let modified = modified
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default();
+                format!("{version}+{len}.{}", modified.as_nanos())
+            }
+            Err(_) => version.to_string(),
+        }
+    })
+}
+
+/// Send `request` to the daemon of the current workspace, if one is
+/// listening; `None` when none is or it could not serve the request.
+fn request_daemon(request: &Request) -> Option<Response> {
+    static DAEMON: OnceLock<Option<PathBuf>> = OnceLock::new();
+    let socket = DAEMON.get_or_init(|| {
+        if std::env::var_os("Z1_NO_DAEMON").is_some() {
+            return None;
+        }
+        let socket = match std::env::var_os("Z1_DAEMON_SOCKET") {
+            Some(socket) => PathBuf::from(socket),
+            None => workspace_dir(None).ok()?.join(SOCKET),
+        };
+        socket.exists().then_some(socket)
+    });
+    match send(socket.as_deref()?, request)? {
+        Response::Error { .. } => None,
+        response => Some(response),
+    }
+}
+
+#[cfg(unix)]
+fn send(socket: &Path, request: &Request) -> Option<Response> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = UnixStream::connect(socket).ok()?;
+    let mut line = serde_json::to_string(&Envelope {
+        build: build_id().to_string(),
+        request,
+    })
+    .ok()?;
+    line.push('\n');
+    stream.write_all(line.as_bytes()).ok()?;
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response).ok()?;
+    serde_json::from_str(&response).ok()
+}
+
+#[cfg(not(unix))]
+fn send(_socket: &Path, _request: &Request) -> Option<Response> {
+    None
+}
+
+#[cfg(unix)]
+mod server {
+    use std::fs;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::Path;
+    use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use anyhow::{Context, Result};
+    use z1_ast::Session;
+
+    use super::{build_id, Envelope, Request, Response, Status};
+    use crate::commands::compile::{self, CheckContext};
+    use crate::commands::lint;
+    use crate::exit::Failure;
+    use crate::output;
+
+    struct State {
+        /// Session of new requests, holding their names and cells
+        session: Mutex<Session>,
+        started: Instant,
+        requests: AtomicU64,
+        /// Requests being handled
+        active: AtomicUsize,
+        stopping: AtomicBool,
+        max_names: usize,
+    }
+
+    pub(super) fn serve(socket: &Path, max_names: usize) -> Result<()> {
+        if socket.exists() {
+            if UnixStream::connect(socket).is_ok() {
+                return Err(Failure::Error.error(format!(
+                    "a daemon is already listening on {}",
+                    socket.display()
+                )));
+            }
+            // Left behind by a daemon that did not exit cleanly
+            fs::remove_file(socket)?;
+        }
+        if let Some(parent) = socket.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let listener = UnixListener::bind(socket)
+            .with_context(|| format!("Failed to listen on {}", socket.display()))?;
+        if output::show_summary() {
+            eprintln!("z1 daemon listening on {}", socket.display());
+        }
+
+        let state = Arc::new(State {
+            session: Mutex::new(Session::new()),
+            started: Instant::now(),
+            requests: AtomicU64::new(0),
+            active: AtomicUsize::new(0),
+            stopping: AtomicBool::new(false),
+            max_names,
+        });
+        for stream in listener.incoming() {
+            if state.stopping.load(Ordering::SeqCst) {
+                break;
+            }
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let state = Arc::clone(&state);
+            let socket = socket.to_path_buf();
+            state.active.fetch_add(1, Ordering::SeqCst);
+            thread::spawn(move || {
+                let session = state.session.lock().expect("daemon poisoned").clone();
+                if let Err(err) = session.enter(|| handle(stream, &state)) {
+                    eprintln!("z1 daemon: {err:#}");
+                }
+                if session.len() > state.max_names {
+                    renew(&state, &session, &socket);
+                }
+                if state.stopping.load(Ordering::SeqCst) {
+                    // Wake the listener so it sees the daemon is stopping
+                    let _ = UnixStream::connect(&socket);
+                }
+                state.active.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+        while state.active.load(Ordering::SeqCst) > 0 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        fs::remove_file(socket).ok();
+        Ok(())
+    }
+
+    /// Serve later requests in a fresh session, unless another request
+    /// already replaced `full`. Requests still using it keep it until they
+    /// are done.
+    fn renew(state: &State, full: &Session, socket: &Path) {
+        let mut session = state.session.lock().expect("daemon poisoned");
+        if *session != *full {
+            return;
+        }
+        *session = Session::new();
+        if output::show_summary() {
+            eprintln!(
+                "z1 daemon on {} dropping {} names and their cells",
+                socket.display(),
+                full.len()
+            );
+        }
+    }
+
+    fn handle(stream: UnixStream, state: &State) -> Result<()> {
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line)?;
+        if line.is_empty() {
+            return Ok(());
+        }
+        let response = match serde_json::from_str::<Envelope<Request>>(&line) {
+            Ok(Envelope { build, request })
+                if build != build_id() && !matches!(request, Request::Status | Request::Stop) =>
+            {
+                Response::Error {
+                    message: format!(
+                        "client build {build} does not match daemon build {}",
+                        build_id()
+                    ),
+                }
+            }
+            Ok(Envelope { request, .. }) => {
+                respond(request, state).unwrap_or_else(|err| Response::Error {
+                    message: format!("{err:#}"),
+                })
+            }
+            Err(err) => Response::Error {
+                message: format!("invalid request: {err}"),
+            },
+        };
+        let mut line = serde_json::to_string(&response)?;
+        line.push('\n');
+        (&stream).write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    fn respond(request: Request, state: &State) -> Result<Response> {
+        let served = state.requests.fetch_add(1, Ordering::SeqCst);
+        let cache = compile::cell_cache();
+        Ok(match request {
+            Request::Status => Response::Status(Status {
+                pid: std::process::id(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                uptime_secs: state.started.elapsed().as_secs(),
+                cells: cache.len(),
+                requests: served,
+                names: Session::current().len(),
+            }),
+            Request::Stop => {
+                state.stopping.store(true, Ordering::SeqCst);
+                Response::Stopping
+            }
+            Request::Format {
+                path,
+                relaxed,
+                reflow_symbols,
+            } => {
+                let cell = cache.load(&path)?;
+                let module = cell.module.context("the cell does not parse")?;
+                let mode = if relaxed {
+                    z1_fmt::Mode::Relaxed
+                } else {
+                    z1_fmt::Mode::Compact
+                };
+                let options = z1_fmt::FmtOptions {
+                    symmap_style: if reflow_symbols {
+                        z1_fmt::SymMapStyle::Reflow
+                    } else {
+                        z1_fmt::SymMapStyle::Respect
+                    },
+                };
+                Response::Formatted {
+                    formatted: z1_fmt::format_module(&module, mode, &options)?,
+                    source: cell.source,
+                }
+            }
+            Request::Lint { path, display } => {
+                let context = CheckContext::for_cell(&path)?.with_cache(&cache);
+                let cell = cache.load(&path)?;
+                let diagnostics = match &cell.module {
+                    Some(module) => lint::lint_module(module, &cell.source, &display, &context),
+                    None => lint::lint_source(&cell.source, &display, &context),
+                };
+                Response::Linted {
+                    source: cell.source,
+                    diagnostics,
+                }
+            }
+            Request::Compile { mut options } => {
+                let cell = cache.load(&options.input_path)?;
+                let module = cell.module.context("the cell does not parse")?;
+                options.cache = Some(cache);
+                Response::Compiled {
+                    report: compile::compile_module(&options, &cell.source, &module)?,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(not(unix))]
+mod server {
+    use std::path::Path;
+
+    use anyhow::Result;
+
+    use crate::exit::Failure;
+
+    pub(super) fn serve(_socket: &Path, _max_names: usize) -> Result<()> {
+        Err(Failure::Error.error("z1 daemon needs Unix domain sockets"))
+    }
+}
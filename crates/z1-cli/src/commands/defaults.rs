@@ -0,0 +1,91 @@
+//! Flag defaults from the enclosing project's `z1.toml`.
+//!
+//! The `[defaults]` table sets what `z1 fmt`, `z1 ctx`, `z1 context`,
+//! `z1 pack` and `z1 compile` use for a flag the command line leaves out,
+//! and `z1 compile` compiles for the targets of the `[target]` table, as
+//! `z1 build` does:
+//!
+//! ```toml
+//! [defaults]
+//! fmt-mode = "compact"
+//! symmap = "reflow"
+//! chars-per-token = 4.0
+//! opt-level = 2
+//! ```
+//!
+//! The project is the one enclosing the first path a command is given, or
+//! the current directory. Outside a project every flag keeps its built-in
+//! default.
+
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use z1_fmt::{Mode, SymMapStyle};
+use z1_ir::optimize::OptLevel;
+use z1_resolve::{CliDefaults, Project};
+
+use super::compile::CompileTarget;
+
+/// Flag defaults of one project, each `None` when the manifest leaves it out.
+#[derive(Debug, Clone, Default)]
+pub struct Defaults {
+    pub fmt_mode: Option<Mode>,
+    pub symmap: Option<SymMapStyle>,
+    pub chars_per_token: Option<f64>,
+    pub opt_level: Option<OptLevel>,
+    /// Targets of the `[target]` table, empty when it names none
+    pub targets: Vec<CompileTarget>,
+}
+
+impl Defaults {
+    /// Defaults of the project enclosing `start`, a file, directory or glob.
+    pub fn discover(start: &Path) -> Result<Self> {
+        match Project::discover(start)? {
+            Some(project) => Self::of(&project),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Defaults of the first of `paths`, or of the current directory.
+    pub fn for_paths<P: AsRef<Path>>(paths: &[P]) -> Result<Self> {
+        Self::discover(paths.first().map_or(Path::new("."), AsRef::as_ref))
+    }
+
+    fn of(project: &Project) -> Result<Self> {
+        let CliDefaults {
+            fmt_mode,
+            symmap,
+            chars_per_token,
+            opt_level,
+        } = &project.manifest.defaults;
+        let mut targets = Vec::new();
+        for name in &project.manifest.target.targets {
+            let target = match name.as_str() {
+                "ts" => CompileTarget::TypeScript,
+                "wasm" => CompileTarget::Wasm,
+                _ => bail!("unknown target `{name}` in z1.toml (expected ts or wasm)"),
+            };
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+        // The manifest checked every value when it was parsed
+        Ok(Self {
+            fmt_mode: fmt_mode.as_deref().map(|mode| match mode {
+                "compact" => Mode::Compact,
+                _ => Mode::Relaxed,
+            }),
+            symmap: symmap.as_deref().map(|style| match style {
+                "reflow" => SymMapStyle::Reflow,
+                _ => SymMapStyle::Respect,
+            }),
+            chars_per_token: *chars_per_token,
+            opt_level: opt_level.map(|level| match level {
+                0 => OptLevel::O0,
+                1 => OptLevel::O1,
+                _ => OptLevel::O2,
+            }),
+            targets,
+        })
+    }
+}
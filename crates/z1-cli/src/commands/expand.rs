@@ -0,0 +1,115 @@
+//! `z1 expand`: a cell with its templates materialized.
+//!
+//! The parser already expands each `expand handler(...)` into the items of
+//! the template; every other command sees those items only. This prints the
+//! cell as they see it, with the items written out in place of the
+//! expansions and the template declarations left out, in the mode of the
+//! cell. The cell itself is left untouched.
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueHint};
+use serde::Serialize;
+use z1_ast::{Item, Module};
+use z1_fmt::{format_module, FmtOptions};
+
+use crate::commands::lint::display_path;
+use crate::commands::migrate::mode_of;
+use crate::exit::Failure;
+use crate::output::{self, print_json, OutputFormat};
+
+#[derive(Debug, Args)]
+pub struct ExpandArgs {
+    /// Cell whose templates to expand.
+    #[arg(value_name = "CELL", value_hint = ValueHint::FilePath)]
+    pub cell: PathBuf,
+}
+
+#[derive(Debug, Serialize)]
+struct ExpandReport {
+    cell: String,
+    templates: Vec<String>,
+    expansions: Vec<ExpansionRow>,
+    /// The cell with every expansion written out
+    source: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ExpansionRow {
+    template: String,
+    args: Vec<String>,
+    /// Items the expansion adds, by name
+    items: Vec<String>,
+}
+
+pub fn run(args: ExpandArgs, format: OutputFormat) -> Result<()> {
+    format.require("expand", &[OutputFormat::Json])?;
+    let cell = &args.cell;
+    let path = display_path(cell);
+    let source =
+        fs::read_to_string(cell).with_context(|| format!("Failed to read {}", cell.display()))?;
+    let module = z1_parse::parse_module(&source)
+        .map_err(|err| Failure::Parse.error(format!("{path}: {err}")))?;
+    let expanded = Module {
+        templates: Vec::new(),
+        expansions: Vec::new(),
+        ..module.clone()
+    };
+    let text = format_module(&expanded, mode_of(cell), &FmtOptions::default())
+        .with_context(|| format!("Failed to format {path}"))?;
+
+    let report = ExpandReport {
+        cell: path,
+        templates: module
+            .templates
+            .iter()
+            .map(|template| template.name.to_string())
+            .collect(),
+        expansions: module
+            .expansions
+            .iter()
+            .map(|expansion| ExpansionRow {
+                template: expansion.template.to_string(),
+                args: expansion.args.clone(),
+                items: module
+                    .items
+                    .iter()
+                    .filter_map(|item| match item {
+                        Item::Type(decl) if decl.span == expansion.span => {
+                            Some(decl.name.to_string())
+                        }
+                        Item::Fn(decl) if decl.span == expansion.span => {
+                            Some(decl.name.to_string())
+                        }
+                        Item::Test(test) if test.span == expansion.span => Some(test.name.clone()),
+                        _ => None,
+                    })
+                    .collect(),
+            })
+            .collect(),
+        source: text,
+    };
+    if format.is_json() {
+        return print_json(&report);
+    }
+    print!("{}", report.source);
+    if output::show_summary() {
+        eprintln!(
+            "Expanded {} instantiation(s) of {} template(s) in {}",
+            report.expansions.len(),
+            report.templates.len(),
+            report.cell
+        );
+        for expansion in &report.expansions {
+            eprintln!(
+                "  {}({}): {}",
+                expansion.template,
+                expansion.args.join(", "),
+                expansion.items.join(", ")
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,91 @@
+//! `z1 explain`: extended explanations of diagnostic codes.
+//!
+//! Entries come from the registry in [`crate::codes`], the same one that
+//! assigns codes to diagnostics and describes the SARIF rules of `z1 lint`.
+
+use anyhow::Result;
+use clap::Args;
+use colored::*;
+
+use crate::codes::{self, CodeInfo};
+use crate::diagnostics::suggest_similar_name;
+use crate::exit::Failure;
+use crate::output::{print_json, OutputFormat};
+
+#[derive(Debug, Args)]
+pub struct ExplainArgs {
+    /// Diagnostic code, such as `T001` (case-insensitive).
+    #[arg(required_unless_present = "list", conflicts_with = "list")]
+    pub code: Option<String>,
+    /// List every code with its title.
+    #[arg(long)]
+    pub list: bool,
+}
+
+pub fn run(args: ExplainArgs, format: OutputFormat) -> Result<()> {
+    format.require("explain", &[OutputFormat::Json])?;
+    let Some(code) = args.code else {
+        if format.is_json() {
+            return print_json(&codes::CODES);
+        }
+        for info in codes::CODES {
+            println!("{}  {}", info.code, info.title);
+        }
+        return Ok(());
+    };
+
+    let Some(info) = codes::lookup(&code) else {
+        let known: Vec<String> = codes::CODES.iter().map(|info| info.code.into()).collect();
+        let mut message = format!("unknown diagnostic code `{code}`");
+        if let Some(similar) = suggest_similar_name(&code.trim().to_uppercase(), &known) {
+            message.push_str(&format!("; did you mean `{similar}`?"));
+        }
+        message.push_str("\nRun `z1 explain --list` to see every code.");
+        return Err(Failure::Usage.error(message));
+    };
+    if format.is_json() {
+        return print_json(info);
+    }
+    print!("{}", render(info, std::env::var("NO_COLOR").is_err()));
+    Ok(())
+}
+
+/// Text form of `info`: title, explanation, example and fixes.
+fn render(info: &CodeInfo, use_colors: bool) -> String {
+    let heading = |text: &str| {
+        if use_colors {
+            text.bold().to_string()
+        } else {
+            text.to_string()
+        }
+    };
+
+    let mut out = format!("{}\n\n", heading(&format!("{}: {}", info.code, info.title)));
+    out.push_str(info.explanation);
+    out.push_str("\n\n");
+    out.push_str(&heading("Example:"));
+    out.push('\n');
+    for line in info.example.lines() {
+        out.push_str(&format!("    {line}\n"));
+    }
+    out.push('\n');
+    out.push_str(&heading("Common fixes:"));
+    out.push('\n');
+    for fix in info.fixes {
+        out.push_str(&format!("  - {fix}\n"));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_every_section() {
+        let text = render(codes::lookup("C001").unwrap(), false);
+        assert!(text.starts_with("C001: Context budget exceeded\n\n"));
+        assert!(text.contains("\n\nExample:\n    m app.main:1.0 ctx=2 caps=[]\n"));
+        assert!(text.contains("Common fixes:\n  - Split the cell"));
+    }
+}
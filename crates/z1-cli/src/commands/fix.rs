@@ -0,0 +1,512 @@
+//! `z1 fix`: apply the safe fixes attached to diagnostics.
+//!
+//! Checkers report what is wrong (an unused capability, unused imported
+//! names, stale symbol map entries); this module turns those reports into
+//! text edits, which `z1 lint --format json` also shows. `z1 fix` applies
+//! them and lints again until no fix is left, since fixes touching the same
+//! text are applied one pass at a time. A cell whose fixed source has more
+//! errors than the original is left unchanged.
+
+use std::fs;
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueHint};
+use serde::Serialize;
+use z1_ast::{Import, Module, Span, SymbolMap, SymbolPair};
+
+use crate::commands::cells;
+use crate::commands::compile::CheckContext;
+use crate::commands::lint::{display_path, lint_source};
+use crate::diagnostics::{Diagnostic, DiagnosticLevel, Edit, Fix};
+use crate::output::{self, print_json, OutputFormat};
+
+/// Passes after which fixing stops, even if fixes are still reported.
+const MAX_PASSES: usize = 8;
+
+/// Lines of unchanged context around each hunk of `--dry-run` diffs.
+const DIFF_CONTEXT: usize = 3;
+
+#[derive(Debug, Args)]
+pub struct FixArgs {
+    /// Cells, directories of cells, or globs such as `src/**/*.z1c`.
+    #[arg(value_name = "PATH", default_value = ".", value_hint = ValueHint::AnyPath)]
+    pub paths: Vec<String>,
+    /// Print the changes as a unified diff instead of writing them.
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct FixReport {
+    dry_run: bool,
+    fixed: usize,
+    cells: Vec<FixedCell>,
+}
+
+#[derive(Debug, Serialize)]
+struct FixedCell {
+    path: String,
+    /// Messages of the applied fixes, in the order they were applied
+    fixes: Vec<String>,
+    /// Why the fixes were not kept, if they were not
+    #[serde(skip_serializing_if = "Option::is_none")]
+    skipped: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diff: Option<String>,
+}
+
+pub fn run(args: FixArgs, format: OutputFormat) -> Result<()> {
+    format.require("fix", &[OutputFormat::Json])?;
+    let files = cells::expand(&args.paths)?;
+
+    let mut report = FixReport {
+        dry_run: args.dry_run,
+        fixed: 0,
+        cells: Vec::new(),
+    };
+    for file in &files {
+        let source = fs::read_to_string(file)
+            .with_context(|| format!("Failed to read {}", file.display()))?;
+        let path = display_path(file);
+        let context = CheckContext::for_cell(file)?;
+        let Some(cell) = fix_source(&source, &path, &context) else {
+            continue;
+        };
+        let mut diff = None;
+        if cell.skipped.is_none() {
+            report.fixed += cell.fixes.len();
+            if args.dry_run {
+                diff = Some(unified_diff(&path, &source, &cell.source));
+            } else {
+                fs::write(file, &cell.source)
+                    .with_context(|| format!("Failed to write {}", file.display()))?;
+            }
+        }
+        report.cells.push(FixedCell {
+            path,
+            fixes: cell.fixes,
+            skipped: cell.skipped,
+            diff,
+        });
+    }
+
+    if format.is_json() {
+        return print_json(&report);
+    }
+    for cell in &report.cells {
+        if let Some(diff) = &cell.diff {
+            print!("{diff}");
+        }
+        if let Some(reason) = &cell.skipped {
+            eprintln!("Skipped {}: {reason}", cell.path);
+        } else if output::show_details() {
+            for fix in &cell.fixes {
+                eprintln!("{}: {fix}", cell.path);
+            }
+        }
+    }
+    if output::show_summary() {
+        let cells = report
+            .cells
+            .iter()
+            .filter(|cell| cell.skipped.is_none())
+            .count();
+        let verb = if args.dry_run { "Would fix" } else { "Fixed" };
+        eprintln!("{verb} {} issue(s) in {cells} cell(s)", report.fixed);
+    }
+    Ok(())
+}
+
+/// Outcome of fixing one cell.
+struct FixedSource {
+    source: String,
+    fixes: Vec<String>,
+    skipped: Option<String>,
+}
+
+/// Apply the fixes of the diagnostics of `source` until none is left;
+/// `None` when there was nothing to fix.
+fn fix_source(source: &str, path: &str, context: &CheckContext) -> Option<FixedSource> {
+    let errors = |diagnostics: &[Diagnostic]| {
+        diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.level == DiagnosticLevel::Error)
+            .count()
+    };
+
+    let mut diagnostics = lint_source(source, path, context);
+    let errors_before = errors(&diagnostics);
+    let mut fixed = source.to_string();
+    let mut messages = Vec::new();
+    for _ in 0..MAX_PASSES {
+        let fixes: Vec<&Fix> = diagnostics.iter().filter_map(|d| d.fix.as_ref()).collect();
+        let (next, applied) = apply_fixes(&fixed, &fixes);
+        if applied.is_empty() {
+            break;
+        }
+        messages.extend(applied);
+        fixed = next;
+        diagnostics = lint_source(&fixed, path, context);
+    }
+    if messages.is_empty() {
+        return None;
+    }
+
+    let skipped = (errors(&diagnostics) > errors_before)
+        .then(|| "the fixed cell would have more errors than the original".to_string());
+    Some(FixedSource {
+        source: fixed,
+        fixes: messages,
+        skipped,
+    })
+}
+
+/// Apply every fix whose edits do not overlap those of a fix applied
+/// before it; returns the new source and the messages of the applied fixes.
+fn apply_fixes(source: &str, fixes: &[&Fix]) -> (String, Vec<String>) {
+    let overlaps = |a: &Edit, b: &Edit| a.span.start < b.span.end && b.span.start < a.span.end;
+    let in_bounds = |edit: &Edit| {
+        edit.span.start <= edit.span.end
+            && source.is_char_boundary(edit.span.start as usize)
+            && source.is_char_boundary(edit.span.end as usize)
+            && (edit.span.end as usize) <= source.len()
+    };
+
+    let mut edits: Vec<&Edit> = Vec::new();
+    let mut messages = Vec::new();
+    for fix in fixes {
+        let applicable = fix
+            .edits
+            .iter()
+            .all(|edit| in_bounds(edit) && !edits.iter().any(|accepted| overlaps(accepted, edit)));
+        if applicable && !fix.edits.is_empty() {
+            edits.extend(&fix.edits);
+            messages.push(fix.message.clone());
+        }
+    }
+
+    edits.sort_by_key(|edit| edit.span.start);
+    let mut fixed = source.to_string();
+    for edit in edits.iter().rev() {
+        fixed.replace_range(
+            edit.span.start as usize..edit.span.end as usize,
+            &edit.replacement,
+        );
+    }
+    (fixed, messages)
+}
+
+/// Fix removing `cap` from the capabilities in the header of `module`.
+pub(crate) fn remove_capability(module: &Module, source: &str, cap: &str) -> Option<Fix> {
+    let header_end = module
+        .items
+        .iter()
+        .map(item_start)
+        .min()
+        .unwrap_or(module.span.end) as usize;
+    let header_start = module.span.start as usize;
+    let header = source.get(header_start..header_end.min(source.len()))?;
+    let caps = header.find("caps")?;
+    let open = caps + header[caps..].find('[')?;
+    let close = open + header[open..].find(']')?;
+
+    let entries = list_entries(source, header_start + open + 1, header_start + close);
+    let index = entries
+        .iter()
+        .position(|&(start, end)| &source[start..end] == cap)?;
+    Some(Fix {
+        message: format!("Remove unused capability '{cap}'"),
+        edits: vec![Edit {
+            span: removal_span(&entries, index),
+            replacement: String::new(),
+        }],
+    })
+}
+
+/// Fix removing the unused `names` of `import`, or the whole import when
+/// `entire`.
+pub(crate) fn remove_import_names(
+    import: &Import,
+    source: &str,
+    names: &[String],
+    entire: bool,
+) -> Option<Fix> {
+    if entire {
+        return Some(Fix {
+            message: format!("Remove unused import '{}'", import.path),
+            edits: vec![Edit {
+                span: line_span(source, import.span),
+                replacement: String::new(),
+            }],
+        });
+    }
+
+    let start = import.span.start as usize;
+    let text = source.get(start..import.span.end as usize)?;
+    let only = text.rfind("only")?;
+    let open = only + text[only..].find('[')?;
+    let close = open + text[open..].find(']')?;
+    let kept: Vec<&str> = import
+        .only
+        .iter()
+        .map(|name| name.as_str())
+        .filter(|name| !names.iter().any(|unused| unused == name))
+        .collect();
+    Some(Fix {
+        message: format!(
+            "Remove unused {} from import '{}'",
+            names.join(", "),
+            import.path
+        ),
+        edits: vec![Edit {
+            span: Span::new((start + open + 1) as u32, (start + close) as u32),
+            replacement: kept.join(", "),
+        }],
+    })
+}
+
+/// Fix regenerating the `#sym` directive of `map` without its `stale`
+/// pairs, along with the `// SymbolMap:` comment before it, if any.
+pub(crate) fn regenerate_symbol_map(
+    map: &SymbolMap,
+    stale: &[&SymbolPair],
+    source: &str,
+) -> Option<Fix> {
+    let kept: Vec<(String, String)> = map
+        .pairs
+        .iter()
+        .filter(|pair| !stale.contains(pair))
+        .map(|pair| (pair.long.to_string(), pair.short.to_string()))
+        .collect();
+
+    let comment = comment_before(source, map.span);
+    let mut edits = Vec::new();
+    if kept.is_empty() {
+        if let Some(comment) = comment {
+            edits.push(Edit {
+                span: line_span(source, comment),
+                replacement: String::new(),
+            });
+        }
+        edits.push(Edit {
+            span: line_span(source, map.span),
+            replacement: String::new(),
+        });
+    } else {
+        if let Some(comment) = comment {
+            edits.push(Edit {
+                span: comment,
+                replacement: z1_fmt::symbol_comment(&kept),
+            });
+        }
+        edits.push(Edit {
+            span: map.span,
+            replacement: z1_fmt::symbol_directive(&kept),
+        });
+    }
+    let names: Vec<&str> = stale.iter().map(|pair| pair.long.as_str()).collect();
+    Some(Fix {
+        message: format!("Remove stale symbol map entries for {}", names.join(", ")),
+        edits,
+    })
+}
+
+fn item_start(item: &z1_ast::Item) -> u32 {
+    match item {
+        z1_ast::Item::Import(import) => import.span.start,
+        z1_ast::Item::Symbol(map) => map.span.start,
+        z1_ast::Item::Type(decl) => decl.span.start,
+        z1_ast::Item::Fn(decl) => decl.span.start,
+        z1_ast::Item::Test(test) => test.span.start,
+    }
+}
+
+/// Byte ranges of the trimmed, comma-separated entries in `start..end`.
+fn list_entries(source: &str, start: usize, end: usize) -> Vec<(usize, usize)> {
+    let mut entries = Vec::new();
+    let mut entry_start = start;
+    for (offset, part) in source[start..end].split(',').map(|part| {
+        let offset = entry_start;
+        entry_start += part.len() + 1;
+        (offset, part)
+    }) {
+        let leading = part.len() - part.trim_start().len();
+        let trimmed = part.trim();
+        if !trimmed.is_empty() {
+            entries.push((offset + leading, offset + leading + trimmed.len()));
+        }
+    }
+    entries
+}
+
+/// Span removing entry `index` of `entries` and one separator next to it.
+fn removal_span(entries: &[(usize, usize)], index: usize) -> Span {
+    let (start, end) = entries[index];
+    let (start, end) = if index + 1 < entries.len() {
+        (start, entries[index + 1].0)
+    } else if index > 0 {
+        (entries[index - 1].1, end)
+    } else {
+        (start, end)
+    };
+    Span::new(start as u32, end as u32)
+}
+
+/// `span` widened to the whole lines it covers, including the line break,
+/// when nothing else is on them.
+fn line_span(source: &str, span: Span) -> Span {
+    let start = span.start as usize;
+    let end = span.end as usize;
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[end..]
+        .find('\n')
+        .map_or(source.len(), |i| end + i + 1);
+    if source[line_start..start].trim().is_empty() && source[end..line_end].trim().is_empty() {
+        Span::new(line_start as u32, line_end as u32)
+    } else {
+        span
+    }
+}
+
+/// Span of the `// SymbolMap:` comment on the line before `span`, if any.
+fn comment_before(source: &str, span: Span) -> Option<Span> {
+    let line_start = source[..span.start as usize].rfind('\n')?;
+    let previous_start = source[..line_start].rfind('\n').map_or(0, |i| i + 1);
+    let line = &source[previous_start..line_start];
+    let trimmed = line.trim();
+    if !trimmed.starts_with("// SymbolMap:") {
+        return None;
+    }
+    let start = previous_start + (line.len() - line.trim_start().len());
+    Some(Span::new(start as u32, (start + trimmed.len()) as u32))
+}
+
+/// Unified diff of `old` and `new`, both the contents of `path`.
+pub(crate) fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Op {
+        Same,
+        Delete,
+        Insert,
+    }
+
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let (n, m) = (old_lines.len(), new_lines.len());
+
+    // Longest common subsequence table over the suffixes of both sides
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old_lines[i] == new_lines[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    // Each operation with the old and new line indices it is at
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old_lines[i] == new_lines[j] {
+            ops.push((Op::Same, i, j));
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push((Op::Delete, i, j));
+            i += 1;
+        } else {
+            ops.push((Op::Insert, i, j));
+            j += 1;
+        }
+    }
+
+    let changes: Vec<usize> = (0..ops.len()).filter(|&k| ops[k].0 != Op::Same).collect();
+    let mut out = String::new();
+    if changes.is_empty() {
+        return out;
+    }
+    out.push_str(&format!("--- a/{path}\n+++ b/{path}\n"));
+
+    let mut group_start = 0;
+    while group_start < changes.len() {
+        let mut group_end = group_start;
+        while group_end + 1 < changes.len()
+            && changes[group_end + 1] - changes[group_end] <= 2 * DIFF_CONTEXT
+        {
+            group_end += 1;
+        }
+        let first = changes[group_start].saturating_sub(DIFF_CONTEXT);
+        let last = (changes[group_end] + DIFF_CONTEXT + 1).min(ops.len());
+        let hunk = &ops[first..last];
+
+        let old_count = hunk.iter().filter(|op| op.0 != Op::Insert).count();
+        let new_count = hunk.iter().filter(|op| op.0 != Op::Delete).count();
+        let line = |index: usize, count: usize| if count == 0 { index } else { index + 1 };
+        out.push_str(&format!(
+            "@@ -{},{old_count} +{},{new_count} @@\n",
+            line(hunk[0].1, old_count),
+            line(hunk[0].2, new_count)
+        ));
+        for &(op, i, j) in hunk {
+            match op {
+                Op::Same => out.push_str(&format!(" {}\n", old_lines[i])),
+                Op::Delete => out.push_str(&format!("-{}\n", old_lines[i])),
+                Op::Insert => out.push_str(&format!("+{}\n", new_lines[j])),
+            }
+        }
+        group_start = group_end + 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixes_for(source: &str) -> Option<FixedSource> {
+        let context = CheckContext::for_cell(std::path::Path::new("test.z1c")).unwrap();
+        fix_source(source, "test.z1c", &context)
+    }
+
+    #[test]
+    fn prunes_capabilities_over_several_passes() {
+        let source = "m app.main:1.0 ctx=100 caps=[net, fs.ro, time]\n\
+                      f add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }\n";
+        let fixed = fixes_for(source).unwrap();
+        assert_eq!(fixed.fixes.len(), 3);
+        assert!(fixed.source.starts_with("m app.main:1.0 ctx=100 caps=[]\n"));
+        assert!(fixed.skipped.is_none());
+    }
+
+    #[test]
+    fn removes_unused_imports_and_symbols() {
+        let source = "module app.main : 1.0\n  caps = []\n\n\
+                      // SymbolMap: { add ↔ a2, retired ↔ rt }\n\
+                      #sym { add: a2, retired: rt }\n\n\
+                      use \"std/http\" as H only [listen, Req]\n\
+                      use \"std/time\" as T\n\n\
+                      fn add(a: H.Req, b: U32) -> U32\n  eff [pure]\n{\n  ret b;\n}\n";
+        let fixed = fixes_for(source).unwrap();
+        assert_eq!(
+            fixed.source,
+            "module app.main : 1.0\n  caps = []\n\n\
+             // SymbolMap: { add ↔ a2 }\n\
+             #sym { add: a2 }\n\n\
+             use \"std/http\" as H only [Req]\n\n\
+             fn add(a: H.Req, b: U32) -> U32\n  eff [pure]\n{\n  ret b;\n}\n"
+        );
+    }
+
+    #[test]
+    fn diff_has_hunks_with_context() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\n";
+        let new = "a\nb\nc\nD\ne\nf\ng\nh\n";
+        assert_eq!(
+            unified_diff("x.z1c", old, new),
+            "--- a/x.z1c\n+++ b/x.z1c\n@@ -1,7 +1,7 @@\n a\n b\n c\n-d\n+D\n e\n f\n g\n"
+        );
+        assert_eq!(unified_diff("x.z1c", old, old), "");
+    }
+}
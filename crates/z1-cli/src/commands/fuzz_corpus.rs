@@ -0,0 +1,93 @@
+//! `z1 fuzz-corpus`: seed the corpora of the fuzz targets from cells.
+//!
+//! Seeds come from [`z1_fuzz::corpus`]: each cell, and one small cell per
+//! item of it. Every fuzz target gets the same seeds, in its own directory
+//! under `--out` (`fuzz/corpus/<target>/`, where `cargo fuzz run <target>`
+//! looks first), named by content hash so that running the command again
+//! only adds what is new.
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueHint};
+use serde::Serialize;
+use z1_fuzz::corpus::Corpus;
+
+use crate::commands::cells;
+use crate::commands::lint::display_path;
+use crate::output::{self, print_json, OutputFormat};
+
+/// The fuzz targets under `fuzz/fuzz_targets`.
+const TARGETS: [&str; 3] = ["lex", "parse", "round_trip"];
+
+#[derive(Debug, Args)]
+pub struct FuzzCorpusArgs {
+    /// Cells, directories of cells, or globs such as `examples/**/*.z1c`.
+    #[arg(value_name = "PATH", default_value = ".", value_hint = ValueHint::AnyPath)]
+    pub paths: Vec<String>,
+    /// Directory holding one corpus directory per fuzz target.
+    #[arg(long, default_value = "fuzz/corpus", value_hint = ValueHint::DirPath)]
+    pub out: PathBuf,
+}
+
+#[derive(Debug, Serialize)]
+struct CorpusReport {
+    out: String,
+    targets: Vec<&'static str>,
+    cells: usize,
+    seeds: usize,
+    /// Seeds not already in the corpus of every target
+    written: usize,
+}
+
+pub fn run(args: FuzzCorpusArgs, format: OutputFormat) -> Result<()> {
+    format.require("fuzz-corpus", &[OutputFormat::Json])?;
+    let files = cells::expand(&args.paths)?;
+
+    let mut corpus = Corpus::new();
+    for file in &files {
+        let source = fs::read_to_string(file)
+            .with_context(|| format!("Failed to read {}", file.display()))?;
+        let added = corpus.add_cell(&source);
+        if output::show_details() && !format.is_json() {
+            eprintln!("{}: {added} seed(s)", display_path(file));
+        }
+    }
+
+    let mut written = 0;
+    for target in TARGETS {
+        let dir = args.out.join(target);
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        for (name, seed) in corpus.iter() {
+            let path = dir.join(name);
+            if !path.exists() {
+                fs::write(&path, seed)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                written += 1;
+            }
+        }
+    }
+
+    let report = CorpusReport {
+        out: display_path(&args.out),
+        targets: TARGETS.to_vec(),
+        cells: files.len(),
+        seeds: corpus.len(),
+        written,
+    };
+    if format.is_json() {
+        return print_json(&report);
+    }
+    if output::show_summary() {
+        eprintln!(
+            "{} seed(s) from {} cell(s) for {} target(s) in {}, {} new file(s)",
+            report.seeds,
+            report.cells,
+            report.targets.len(),
+            report.out,
+            report.written
+        );
+    }
+    Ok(())
+}
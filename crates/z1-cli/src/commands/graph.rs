@@ -0,0 +1,299 @@
+//! `z1 graph`: the import graph of a workspace.
+//!
+//! Every cell is a node annotated with its estimated context tokens, its
+//! budget and its capabilities; capabilities no function uses are listed
+//! separately, so over-privileged cells stand out. Imports of cells outside
+//! the workspace become external nodes, annotated too when the enclosing
+//! project resolves them. The graph is printed as Graphviz DOT, as a
+//! Mermaid flowchart, or with `--format json` as nodes and edges.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+use z1_ast::Module;
+use z1_effects::EffectWarning;
+use z1_resolve::Project;
+
+use crate::commands::build::{workspace_dir, workspace_sources};
+use crate::output::{print_json, OutputFormat};
+
+#[derive(Debug, Args)]
+pub struct GraphArgs {
+    /// Workspace directory (default: the nearest directory holding `z1.toml`
+    /// or `.z1`, else the current directory).
+    pub dir: Option<PathBuf>,
+    /// Graph language of the text output (default: dot)
+    #[arg(long, value_enum)]
+    pub emit: Option<GraphEmit>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GraphEmit {
+    Dot,
+    Mermaid,
+}
+
+#[derive(Debug, Serialize)]
+struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+}
+
+#[derive(Debug, Serialize)]
+struct Node {
+    module: String,
+    /// Cell path relative to the workspace; `None` for external cells
+    path: Option<String>,
+    external: bool,
+    /// Estimated context tokens; `None` for unresolved external cells
+    tokens: Option<u32>,
+    budget: Option<u32>,
+    caps: Vec<String>,
+    /// Capabilities no function of the cell uses
+    unused_caps: Vec<String>,
+}
+
+impl Node {
+    fn over_budget(&self) -> bool {
+        matches!((self.tokens, self.budget), (Some(tokens), Some(budget)) if tokens > budget)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Edge {
+    from: String,
+    to: String,
+}
+
+pub fn run(args: GraphArgs, format: OutputFormat) -> Result<()> {
+    format.require("graph", &[OutputFormat::Json])?;
+    if format.is_json() && args.emit.is_some() {
+        bail!("--emit selects the text output; drop it with --format json");
+    }
+    let dir = workspace_dir(args.dir)?;
+    let graph = build_graph(&dir)?;
+    if format.is_json() {
+        return print_json(&graph);
+    }
+    match args.emit.unwrap_or(GraphEmit::Dot) {
+        GraphEmit::Dot => print!("{}", dot(&graph)),
+        GraphEmit::Mermaid => print!("{}", mermaid(&graph)),
+    }
+    Ok(())
+}
+
+fn build_graph(dir: &std::path::Path) -> Result<Graph> {
+    let cells = workspace_sources(dir)?;
+    let resolver = match Project::discover(dir)? {
+        Some(project) => Some(project.resolver()?),
+        None => None,
+    };
+
+    let mut nodes: BTreeMap<String, Node> = BTreeMap::new();
+    let mut edges = Vec::new();
+    for cell in &cells {
+        nodes.insert(
+            cell.info.module.clone(),
+            node(&cell.module, Some(cell.info.path.clone())),
+        );
+        for import in &cell.info.imports {
+            edges.push(Edge {
+                from: cell.info.module.clone(),
+                to: import.clone(),
+            });
+        }
+    }
+    for edge in &edges {
+        if nodes.contains_key(&edge.to) {
+            continue;
+        }
+        let external = match resolver.as_ref().map(|resolver| resolver.load(&edge.to)) {
+            Some(Ok(resolved)) => node(&resolved.module, None),
+            _ => Node {
+                module: edge.to.clone(),
+                path: None,
+                external: true,
+                tokens: None,
+                budget: None,
+                caps: Vec::new(),
+                unused_caps: Vec::new(),
+            },
+        };
+        nodes.insert(edge.to.clone(), external);
+    }
+    edges.sort_by(|a, b| (&a.from, &a.to).cmp(&(&b.from, &b.to)));
+    edges.dedup_by(|a, b| a.from == b.from && a.to == b.to);
+
+    Ok(Graph {
+        nodes: nodes.into_values().collect(),
+        edges,
+    })
+}
+
+/// Node for `module`; a cell without a workspace path is external.
+fn node(module: &Module, path: Option<String>) -> Node {
+    let config = z1_ctx::EstimateConfig {
+        enforce_budget: false,
+        ..Default::default()
+    };
+    let tokens = z1_ctx::estimate_cell_with_config(module, &config)
+        .ok()
+        .map(|estimate| estimate.total_tokens);
+    let unused_caps = z1_effects::collect_effect_warnings(module)
+        .into_iter()
+        .filter_map(|warning| match warning {
+            EffectWarning::UnusedCapability { capability, .. } => Some(capability),
+            _ => None,
+        })
+        .collect();
+    Node {
+        module: module.path.to_string(),
+        external: path.is_none(),
+        path,
+        tokens,
+        budget: module.ctx_budget,
+        caps: module.caps.clone(),
+        unused_caps,
+    }
+}
+
+/// Label lines of a node: module, tokens against budget, capabilities.
+fn label_lines(node: &Node) -> Vec<String> {
+    let mut lines = vec![node.module.clone()];
+    match (node.tokens, node.budget) {
+        (Some(tokens), Some(budget)) => lines.push(format!("{tokens} / {budget} tokens")),
+        (Some(tokens), None) => lines.push(format!("{tokens} tokens")),
+        _ => {}
+    }
+    if !node.caps.is_empty() {
+        lines.push(format!("caps: {}", node.caps.join(", ")));
+    }
+    if !node.unused_caps.is_empty() {
+        lines.push(format!("unused: {}", node.unused_caps.join(", ")));
+    }
+    lines
+}
+
+/// Graphviz DOT: external cells dashed, cells over budget red and cells
+/// with unused capabilities orange.
+fn dot(graph: &Graph) -> String {
+    let mut out = String::from("digraph workspace {\n  rankdir=LR;\n  node [shape=box];\n");
+    for node in &graph.nodes {
+        let label = label_lines(node)
+            .iter()
+            .map(|line| line.replace('"', "\\\""))
+            .collect::<Vec<_>>()
+            .join("\\n");
+        let mut attrs = vec![format!("label=\"{label}\"")];
+        if node.external {
+            attrs.push("style=dashed".to_string());
+        }
+        if node.over_budget() {
+            attrs.push("color=red".to_string());
+        } else if !node.unused_caps.is_empty() {
+            attrs.push("color=orange".to_string());
+        }
+        out.push_str(&format!("  \"{}\" [{}];\n", node.module, attrs.join(", ")));
+    }
+    for edge in &graph.edges {
+        out.push_str(&format!("  \"{}\" -> \"{}\";\n", edge.from, edge.to));
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Mermaid flowchart with the same highlighting as [`dot`].
+fn mermaid(graph: &Graph) -> String {
+    let ids: BTreeMap<&str, String> = graph
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| (node.module.as_str(), format!("n{index}")))
+        .collect();
+    let mut out = String::from("graph LR\n");
+    for node in &graph.nodes {
+        let label = label_lines(node)
+            .iter()
+            .map(|line| line.replace('"', "#quot;"))
+            .collect::<Vec<_>>()
+            .join("<br/>");
+        let class = if node.over_budget() {
+            ":::heavy"
+        } else if !node.unused_caps.is_empty() {
+            ":::overprivileged"
+        } else if node.external {
+            ":::external"
+        } else {
+            ""
+        };
+        out.push_str(&format!(
+            "  {}[\"{label}\"]{class}\n",
+            ids[node.module.as_str()]
+        ));
+    }
+    for edge in &graph.edges {
+        out.push_str(&format!(
+            "  {} --> {}\n",
+            ids[edge.from.as_str()],
+            ids[edge.to.as_str()]
+        ));
+    }
+    out.push_str("  classDef external stroke-dasharray: 5 5\n");
+    out.push_str("  classDef heavy stroke:#d00,stroke-width:2px\n");
+    out.push_str("  classDef overprivileged stroke:#e80,stroke-width:2px\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> Graph {
+        let main = z1_parse::parse_module(
+            "m app.main:1.0 ctx=1 caps=[net]\nu \"app/util\" only [id]\nf run()->Unit eff [pure] { }\n",
+        )
+        .unwrap();
+        let util = z1_parse::parse_module(
+            "m app.util:1.0 caps=[]\nf id(a: U32)->U32 eff [pure] { ret a; }\n",
+        )
+        .unwrap();
+        Graph {
+            nodes: vec![
+                node(&main, Some("main.z1c".to_string())),
+                node(&util, Some("util.z1c".to_string())),
+            ],
+            edges: vec![Edge {
+                from: "app.main".to_string(),
+                to: "app.util".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn nodes_carry_tokens_and_unused_caps() {
+        let graph = graph();
+        let main = &graph.nodes[0];
+        assert!(main.tokens.unwrap() > 1);
+        assert!(main.over_budget());
+        assert_eq!(main.unused_caps, ["net"]);
+        assert!(!graph.nodes[1].over_budget());
+    }
+
+    #[test]
+    fn dot_and_mermaid_render_nodes_and_edges() {
+        let graph = graph();
+        let dot = dot(&graph);
+        assert!(dot.starts_with("digraph workspace {"));
+        assert!(dot.contains("\"app.main\" -> \"app.util\";"));
+        assert!(dot.contains("color=red"));
+        assert!(dot.contains("caps: net\\nunused: net"));
+
+        let mermaid = mermaid(&graph);
+        assert!(mermaid.starts_with("graph LR\n"));
+        assert!(mermaid.contains("  n0 --> n1\n"));
+        assert!(mermaid.contains(":::heavy"));
+    }
+}
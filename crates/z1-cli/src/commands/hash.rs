@@ -0,0 +1,539 @@
+//! `z1 hash`: compute, record and verify cell hashes.
+//!
+//! For a directory or several inputs, one `<semhash> <path>` line is printed
+//! per cell (paths relative to the directory when a single one is given).
+//! Saved to a file, that output is a pin list which `--manifest` checks the
+//! directory against. Directories and globs are expanded as described in
+//! [`cells`](crate::commands::cells). Cells are hashed in parallel, with a
+//! summary on stderr; `-` reads a cell from stdin. With `--changed-only`,
+//! each changed cell is also marked with the kind of change it has against
+//! the baseline: interface, behavior, naming or layout only (see
+//! [`changed`](crate::commands::changed)).
+
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, ValueHint};
+use serde::Serialize;
+use z1_ast::Module;
+use z1_hash::{
+    module_hashes_with_options, split_hash, HashAlgo, HashOptions, Manifest, ModuleHashes,
+    MANIFEST_EXTENSION,
+};
+
+use crate::commands::cells::{collect_cells, expand_arg, is_glob, par_map, relative, Summary};
+use crate::commands::changed::ChangedArgs;
+use crate::commands::lint::display_path;
+use crate::error_printer;
+use crate::exit::Failure;
+use crate::output::{self, print_json, OutputFormat};
+
+#[derive(Debug, Args)]
+pub struct HashArgs {
+    /// Source cells, directories of cells, globs such as `src/**/*.z1c`, or
+    /// `-` for stdin.
+    #[arg(required_unless_present = "changed_only", value_hint = ValueHint::AnyPath)]
+    pub paths: Vec<String>,
+    /// Also write per-item hashes to `<cell>.z1hash`.
+    #[arg(long)]
+    pub write_manifest: bool,
+    /// Digest algorithm (sha3-256 or blake3).
+    #[arg(long, default_value = "sha3-256")]
+    pub algo: HashAlgo,
+    /// Canonicalize before hashing (sort caps, effects and items) so
+    /// reordered but otherwise identical cells hash alike.
+    #[arg(long)]
+    pub canonical: bool,
+    /// Fail unless the cell's semantic hash equals this value.
+    #[arg(long, value_name = "SEMHASH", conflicts_with = "manifest")]
+    pub verify: Option<String>,
+    /// Check every cell of the directory against a pin list.
+    #[arg(long, value_name = "FILE", conflicts_with = "write_manifest")]
+    pub manifest: Option<PathBuf>,
+    /// Print a JSON array with one record per cell (as `--format json`
+    /// does when no check is requested).
+    #[arg(long, conflicts_with_all = ["manifest", "write_manifest", "verify"])]
+    pub json: bool,
+    #[command(flatten)]
+    pub changed: ChangedArgs,
+}
+
+/// Where a cell's source comes from.
+enum Source {
+    File(PathBuf),
+    Text(String),
+}
+
+#[derive(Debug, Serialize)]
+struct HashRecord {
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    semhash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    formhash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    apihash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    manifest: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    verified: Option<bool>,
+    /// Change against the `--changed-only` baseline
+    #[serde(skip_serializing_if = "Option::is_none")]
+    change: Option<Change>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// How a cell differs from the `--changed-only` baseline, from the hash
+/// that tells the largest kind of change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Change {
+    /// The baseline does not have the cell
+    New,
+    /// The API hash changed
+    Api,
+    /// The semantic hash changed, but not the API hash
+    Semantic,
+    /// Only the format hash changed: symbol map names
+    Naming,
+    /// Every hash is the same; only layout or comments changed
+    LayoutOnly,
+    /// The source is byte for byte the same
+    Unchanged,
+}
+
+impl Change {
+    fn as_str(self) -> &'static str {
+        match self {
+            Change::New => "new",
+            Change::Api => "api",
+            Change::Semantic => "semantic",
+            Change::Naming => "naming",
+            Change::LayoutOnly => "layout-only",
+            Change::Unchanged => "unchanged",
+        }
+    }
+}
+
+/// Outcome of `--manifest`, as printed by `--format json`.
+#[derive(Debug, Serialize)]
+struct PinReport {
+    verified: usize,
+    failures: Vec<PinFailure>,
+}
+
+#[derive(Debug, Serialize)]
+struct PinFailure {
+    path: String,
+    reason: String,
+}
+
+pub fn run(mut args: HashArgs, format: OutputFormat) -> Result<()> {
+    format.require("hash", &[OutputFormat::Json])?;
+    let checks = args.manifest.is_some() || args.verify.is_some() || args.write_manifest;
+    args.json |= format.is_json() && !checks;
+    if args.changed.changed_only {
+        if checks {
+            bail!("--changed-only does not combine with --verify, --manifest or --write-manifest");
+        }
+        return hash_changed(&args);
+    }
+    if let Some(pins) = &args.manifest {
+        let [dir] = args.paths.as_slice() else {
+            bail!("--manifest checks exactly one directory");
+        };
+        return verify_pins(Path::new(dir), pins, args.canonical, format);
+    }
+    match args.paths.as_slice() {
+        [path] if path != "-" && !args.json && !Path::new(path).is_dir() && !is_glob(path) => {
+            hash_cell(&args, path, format)
+        }
+        _ => hash_many(&args),
+    }
+}
+
+/// Hash a single cell file, optionally verifying it or writing its manifest.
+fn hash_cell(args: &HashArgs, path_arg: &str, format: OutputFormat) -> Result<()> {
+    let path = Path::new(path_arg);
+    let module = parse_cell(path)?;
+    let algo = match &args.verify {
+        // Verify with the algorithm the expected hash was made with
+        Some(expected) => parse_expected(expected)?.0,
+        None => args.algo,
+    };
+    let hashes = hashes(&module, algo, args.canonical);
+    let json = format.is_json();
+    let show = !json && output::show_summary();
+    if show {
+        println!("semhash: {}", hashes.semantic);
+        println!("formhash: {}", hashes.format);
+        println!("apihash: {}", hashes.api);
+    }
+    let mut manifest = None;
+    if args.write_manifest {
+        let manifest_path = path.with_extension(MANIFEST_EXTENSION);
+        fs::write(
+            &manifest_path,
+            Manifest::from_module_with(&canonical_or(module, args.canonical), algo).to_string(),
+        )?;
+        if show {
+            println!("manifest: {}", manifest_path.display());
+        }
+        manifest = Some(manifest_path);
+    }
+    if let Some(expected) = &args.verify {
+        if !same_hash(expected, &hashes.semantic) {
+            return Err(Failure::Check.error(format!(
+                "{path_arg}: semantic hash mismatch\n  expected: {expected}\n  found:    {}",
+                hashes.semantic
+            )));
+        }
+        if show {
+            println!("verified: {path_arg}");
+        }
+    }
+    if json {
+        print_json(&HashRecord {
+            path: path_arg.to_string(),
+            semhash: Some(hashes.semantic),
+            formhash: Some(hashes.format),
+            apihash: Some(hashes.api),
+            manifest,
+            verified: args.verify.is_some().then_some(true),
+            change: None,
+            error: None,
+        })?;
+    }
+    Ok(())
+}
+
+/// Hash several inputs in parallel and list them as text or JSON.
+fn hash_many(args: &HashArgs) -> Result<()> {
+    if args.verify.is_some() || args.write_manifest {
+        bail!("--verify and --write-manifest take a single cell");
+    }
+
+    let started = Instant::now();
+    let single_dir = args.paths.len() == 1;
+    let mut sources = Vec::new();
+    for path_arg in &args.paths {
+        if path_arg == "-" {
+            let mut text = String::new();
+            std::io::stdin()
+                .read_to_string(&mut text)
+                .context("Failed to read stdin")?;
+            sources.push((path_arg.clone(), Source::Text(text)));
+        } else {
+            let expansion = expand_arg(path_arg)?;
+            for cell in expansion.cells {
+                let name = match &expansion.base {
+                    Some(base) if single_dir => relative(base, &cell),
+                    Some(_) => cell.display().to_string(),
+                    None => path_arg.clone(),
+                };
+                sources.push((name, Source::File(cell)));
+            }
+        }
+    }
+
+    let results = par_map(&sources, |(_, source)| {
+        hash_source(source, args.algo, args.canonical)
+    });
+    let mut failures = 0;
+    // Class of the first failure, for the exit code
+    let mut failure = None;
+    let mut records = Vec::with_capacity(results.len());
+    for ((name, _), result) in sources.iter().zip(results) {
+        let mut record = HashRecord {
+            path: name.clone(),
+            semhash: None,
+            formhash: None,
+            apihash: None,
+            manifest: None,
+            verified: None,
+            change: None,
+            error: None,
+        };
+        match result {
+            Ok(hashes) => {
+                if !args.json && output::show_details() {
+                    println!("{} {name}", hashes.semantic);
+                }
+                record.semhash = Some(hashes.semantic);
+                record.formhash = Some(hashes.format);
+                record.apihash = Some(hashes.api);
+            }
+            Err(err) => {
+                failures += 1;
+                failure.get_or_insert(Failure::of(&err));
+                if !args.json {
+                    eprintln!("✗ {name}: {err:#}");
+                }
+                record.error = Some(format!("{err:#}"));
+            }
+        }
+        records.push(record);
+    }
+    if !args.json && records.len() > 1 {
+        Summary {
+            command: "hash",
+            cells: records.len(),
+            elapsed: started.elapsed(),
+            rows: vec![("hashed", records.len() - failures), ("failed", failures)],
+        }
+        .print();
+    }
+    if args.json {
+        print_json(&records)?;
+        // The records carry the errors; keep stdout a single document
+        if let Some(failure) = failure {
+            failure.exit();
+        }
+    }
+    if let Some(failure) = failure {
+        return Err(failure.error(format!("{failures} cell(s) could not be hashed")));
+    }
+    Ok(())
+}
+
+/// Hash the changed cells, marking how each differs from the baseline.
+fn hash_changed(args: &HashArgs) -> Result<()> {
+    let started = Instant::now();
+    let cells = args.changed.cells(&args.paths)?;
+    let baseline = args.changed.baseline()?;
+    let results = par_map(&cells, |cell| -> Result<(ModuleHashes, Change)> {
+        let text = fs::read_to_string(cell)?;
+        let old_text = baseline.source(cell);
+        let hashes = hash_source(&Source::Text(text.clone()), args.algo, args.canonical)?;
+        let change = match old_text {
+            None => Change::New,
+            Some(old_text) if old_text == text => Change::Unchanged,
+            Some(old_text) => match hash_source(&Source::Text(old_text), args.algo, args.canonical)
+            {
+                Ok(old) if old.api != hashes.api => Change::Api,
+                Ok(old) if old.semantic != hashes.semantic => Change::Semantic,
+                Ok(old) if old.format != hashes.format => Change::Naming,
+                Ok(_) => Change::LayoutOnly,
+                // A baseline that does not parse has no interface to compare
+                Err(_) => Change::Api,
+            },
+        };
+        Ok((hashes, change))
+    });
+
+    let mut failure = None;
+    let mut records = Vec::with_capacity(results.len());
+    for (cell, result) in cells.iter().zip(results) {
+        let name = display_path(cell);
+        let mut record = HashRecord {
+            path: name.clone(),
+            semhash: None,
+            formhash: None,
+            apihash: None,
+            manifest: None,
+            verified: None,
+            change: None,
+            error: None,
+        };
+        match result {
+            Ok((hashes, change)) => {
+                if !args.json && output::show_details() {
+                    println!("{} {name} ({})", hashes.semantic, change.as_str());
+                }
+                record.semhash = Some(hashes.semantic);
+                record.formhash = Some(hashes.format);
+                record.apihash = Some(hashes.api);
+                record.change = Some(change);
+            }
+            Err(err) => {
+                failure.get_or_insert(Failure::of(&err));
+                if !args.json {
+                    eprintln!("✗ {name}: {err:#}");
+                }
+                record.error = Some(format!("{err:#}"));
+            }
+        }
+        records.push(record);
+    }
+    let count = |change| {
+        records
+            .iter()
+            .filter(|record| record.change == Some(change))
+            .count()
+    };
+    let failures = records
+        .iter()
+        .filter(|record| record.error.is_some())
+        .count();
+    if args.json {
+        print_json(&records)?;
+        if let Some(failure) = failure {
+            failure.exit();
+        }
+    } else {
+        Summary {
+            command: "hash",
+            cells: records.len(),
+            elapsed: started.elapsed(),
+            rows: vec![
+                ("new", count(Change::New)),
+                ("api", count(Change::Api)),
+                ("semantic", count(Change::Semantic)),
+                ("naming", count(Change::Naming)),
+                ("layout-only", count(Change::LayoutOnly)),
+                ("unchanged", count(Change::Unchanged)),
+                ("failed", failures),
+            ],
+        }
+        .print();
+    }
+    if let Some(failure) = failure {
+        return Err(failure.error(format!("{failures} cell(s) could not be hashed")));
+    }
+    Ok(())
+}
+
+fn hash_source(source: &Source, algo: HashAlgo, canonical: bool) -> Result<ModuleHashes> {
+    let read;
+    let text = match source {
+        Source::File(path) => {
+            read = fs::read_to_string(path)?;
+            &read
+        }
+        Source::Text(text) => text,
+    };
+    let module = z1_parse::parse_module(text).map_err(|err| Failure::Parse.error(err))?;
+    Ok(hashes(&module, algo, canonical))
+}
+
+/// Check each pinned cell under `dir`; cells that are not pinned fail too.
+fn verify_pins(dir: &Path, pins: &Path, canonical: bool, format: OutputFormat) -> Result<()> {
+    if !dir.is_dir() {
+        bail!(
+            "--manifest checks a directory, but {} is not one",
+            dir.display()
+        );
+    }
+    let text =
+        fs::read_to_string(pins).with_context(|| format!("Failed to read {}", pins.display()))?;
+
+    let mut pinned = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((hash, rel)) = line.split_once(char::is_whitespace) else {
+            bail!(
+                "{}:{}: expected `<semhash> <path>`",
+                pins.display(),
+                idx + 1
+            );
+        };
+        parse_expected(hash).with_context(|| format!("{}:{}", pins.display(), idx + 1))?;
+        pinned.push((hash.to_string(), rel.trim().to_string()));
+    }
+
+    let json = format.is_json();
+    let mut failures = Vec::new();
+    for (expected, rel) in &pinned {
+        let cell = dir.join(rel);
+        let (algo, _) = parse_expected(expected)?;
+        let status = if !cell.is_file() {
+            Some("missing".to_string())
+        } else {
+            match parse_cell(&cell) {
+                Ok(module) => {
+                    let actual = hashes(&module, algo, canonical).semantic;
+                    (!same_hash(expected, &actual))
+                        .then(|| format!("mismatch (expected {expected}, found {actual})"))
+                }
+                Err(err) => Some(format!("{err:#}")),
+            }
+        };
+        match status {
+            Some(reason) => {
+                if !json && output::show_details() {
+                    println!("FAIL {rel}: {reason}");
+                }
+                failures.push(PinFailure {
+                    path: rel.clone(),
+                    reason,
+                });
+            }
+            None if !json && output::show_details() => println!("ok   {rel}"),
+            None => {}
+        }
+    }
+    let verified = pinned.len() - failures.len();
+    for cell in collect_cells(dir)? {
+        let rel = relative(dir, &cell);
+        if !pinned.iter().any(|(_, pinned_rel)| *pinned_rel == rel) {
+            if !json && output::show_details() {
+                println!("FAIL {rel}: not pinned");
+            }
+            failures.push(PinFailure {
+                path: rel,
+                reason: "not pinned".to_string(),
+            });
+        }
+    }
+
+    if json {
+        let failed = !failures.is_empty();
+        print_json(&PinReport { verified, failures })?;
+        if failed {
+            Failure::Check.exit();
+        }
+        return Ok(());
+    }
+    if !failures.is_empty() {
+        return Err(Failure::Check.error(format!(
+            "{} cell(s) failed verification against {}",
+            failures.len(),
+            pins.display()
+        )));
+    }
+    if output::show_summary() {
+        println!("verified {} cell(s)", pinned.len());
+    }
+    Ok(())
+}
+
+fn canonical_or(module: Module, canonical: bool) -> Module {
+    if canonical {
+        z1_hash::canonicalize(&module)
+    } else {
+        module
+    }
+}
+
+fn hashes(module: &Module, algo: HashAlgo, canonicalize: bool) -> ModuleHashes {
+    module_hashes_with_options(module, &HashOptions { algo, canonicalize })
+}
+
+fn parse_expected(hash: &str) -> Result<(HashAlgo, &str)> {
+    split_hash(hash).with_context(|| format!("`{hash}` is not a semantic hash"))
+}
+
+/// Compare hashes by algorithm and digest; bare hex counts as SHA3-256.
+fn same_hash(expected: &str, actual: &str) -> bool {
+    match (split_hash(expected), split_hash(actual)) {
+        (Some((a, a_hex)), Some((b, b_hex))) => a == b && a_hex.eq_ignore_ascii_case(b_hex),
+        _ => false,
+    }
+}
+
+fn parse_cell(path: &Path) -> Result<Module> {
+    let display = path.display().to_string();
+    let source = fs::read_to_string(path).with_context(|| format!("Failed to read {display}"))?;
+    z1_parse::parse_module(&source).map_err(|e| {
+        let config = error_printer::ErrorPrinterConfig::default();
+        error_printer::print_parse_error(&e, &source, &display, &config);
+        Failure::Parse.error(format!("Parse failed: {display}"))
+    })
+}
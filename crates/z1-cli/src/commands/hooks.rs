@@ -0,0 +1,171 @@
+//! Hooks into the compile pipeline.
+//!
+//! A [`Hook`] sees a cell at fixed points of [`compile`](super::compile):
+//!
+//! - `pre_parse`: the source text, which it may rewrite
+//! - `post_parse`: the AST, which it may rewrite
+//! - `post_typecheck`: the AST once it type checks
+//! - `post_ir`: the optimized IR, which it may rewrite
+//! - `pre_emit`: the generated code of each target, which it may rewrite
+//!
+//! Every stage defaults to doing nothing, so a hook implements only those it
+//! needs. A hook that returns an error fails the compilation; errors
+//! classified with [`Failure`](crate::exit::Failure) keep their exit code,
+//! others exit 1. Hooks run in the order they were added.
+//!
+//! ```
+//! use std::path::Path;
+//!
+//! use anyhow::Result;
+//! use z1_ast::{Item, Module};
+//! use z1_cli::commands::hooks::{Hook, Hooks};
+//! use z1_cli::exit::Failure;
+//!
+//! /// Rejects functions named `debug` in shipped cells.
+//! struct NoDebug;
+//!
+//! impl Hook for NoDebug {
+//!     fn name(&self) -> &str {
+//!         "no-debug"
+//!     }
+//!
+//!     fn post_parse(&self, _cell: &Path, module: &mut Module) -> Result<()> {
+//!         match module.items.iter().any(|item| matches!(item, Item::Fn(f) if f.name == "debug")) {
+//!             true => Err(Failure::Policy.error("`debug` must not ship")),
+//!             false => Ok(()),
+//!         }
+//!     }
+//! }
+//!
+//! let hooks = Hooks::new().with(NoDebug);
+//! ```
+//!
+//! Cells handed to the pipeline already parsed, as by `z1 build` and the
+//! daemon, skip `pre_parse` and `post_parse`.
+
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use z1_ast::Module;
+use z1_ir::IrModule;
+
+use super::compile::CompileTarget;
+
+/// Custom validations or transforms at stages of the compile pipeline.
+pub trait Hook: Send + Sync {
+    /// Name shown when the hook fails.
+    fn name(&self) -> &str;
+
+    /// Before parsing, with the source of `cell`.
+    fn pre_parse(&self, _cell: &Path, _source: &mut String) -> Result<()> {
+        Ok(())
+    }
+
+    /// After parsing, before any check.
+    fn post_parse(&self, _cell: &Path, _module: &mut Module) -> Result<()> {
+        Ok(())
+    }
+
+    /// After type checking; also runs when checks are disabled.
+    fn post_typecheck(&self, _cell: &Path, _module: &Module) -> Result<()> {
+        Ok(())
+    }
+
+    /// After lowering and optimizing.
+    fn post_ir(&self, _cell: &Path, _ir: &mut IrModule) -> Result<()> {
+        Ok(())
+    }
+
+    /// Before the code generated for `target` is written.
+    fn pre_emit(&self, _cell: &Path, _target: CompileTarget, _code: &mut Vec<u8>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Stage of the pipeline a hook runs at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    PreParse,
+    PostParse,
+    PostTypecheck,
+    PostIr,
+    PreEmit,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Stage::PreParse => "pre-parse",
+            Stage::PostParse => "post-parse",
+            Stage::PostTypecheck => "post-typecheck",
+            Stage::PostIr => "post-IR",
+            Stage::PreEmit => "pre-emit",
+        })
+    }
+}
+
+/// The hooks of a compilation, in the order they run.
+#[derive(Clone, Default)]
+pub struct Hooks {
+    hooks: Vec<Arc<dyn Hook>>,
+}
+
+// The `z1` binary runs no hooks of its own; they are added by programs
+// embedding the library
+#[allow(dead_code)]
+impl Hooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `hook`, after those already added.
+    pub fn with(mut self, hook: impl Hook + 'static) -> Self {
+        self.hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Run `stage` of every hook, stopping at the first that fails.
+    fn run(&self, stage: Stage, mut f: impl FnMut(&dyn Hook) -> Result<()>) -> Result<()> {
+        for hook in &self.hooks {
+            f(hook.as_ref()).with_context(|| format!("{stage} hook `{}` failed", hook.name()))?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn pre_parse(&self, cell: &Path, source: &mut String) -> Result<()> {
+        self.run(Stage::PreParse, |hook| hook.pre_parse(cell, source))
+    }
+
+    pub(crate) fn post_parse(&self, cell: &Path, module: &mut Module) -> Result<()> {
+        self.run(Stage::PostParse, |hook| hook.post_parse(cell, module))
+    }
+
+    pub(crate) fn post_typecheck(&self, cell: &Path, module: &Module) -> Result<()> {
+        self.run(Stage::PostTypecheck, |hook| {
+            hook.post_typecheck(cell, module)
+        })
+    }
+
+    pub(crate) fn post_ir(&self, cell: &Path, ir: &mut IrModule) -> Result<()> {
+        self.run(Stage::PostIr, |hook| hook.post_ir(cell, ir))
+    }
+
+    pub(crate) fn pre_emit(
+        &self,
+        cell: &Path,
+        target: CompileTarget,
+        code: &mut Vec<u8>,
+    ) -> Result<()> {
+        self.run(Stage::PreEmit, |hook| hook.pre_emit(cell, target, code))
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.hooks.iter().map(|hook| hook.name()))
+            .finish()
+    }
+}
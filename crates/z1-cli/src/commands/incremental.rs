@@ -0,0 +1,155 @@
+//! Incremental checks for `z1 lint` and `z1 build`.
+//!
+//! The static checks of a cell are skipped when they came out clean before
+//! and neither the cell nor the API of any of its imports has changed since,
+//! under the same checker version and policy limits (see
+//! [`z1_store::CheckState`]). The record lives in the store of the project
+//! or workspace holding the cell; cells outside both are always checked.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::Serialize;
+use z1_ast::Module;
+use z1_hash::{hash_bytes, HashAlgo};
+use z1_resolve::Project;
+use z1_store::{find_workspace, CheckKey, CheckState, Store};
+
+use crate::commands::compile::CheckContext;
+
+/// Cells whose checks were reused or run.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub(crate) struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} check(s) reused, {} run", self.hits, self.misses)
+    }
+}
+
+/// The checks of one cell, as recorded.
+pub(crate) struct CheckEntry {
+    root: PathBuf,
+    module: String,
+    config: String,
+    key: CheckKey,
+}
+
+/// Check states of the workspaces the checked cells belong to.
+pub(crate) struct CheckCache {
+    /// Pass the states are recorded under
+    pass: &'static str,
+    /// Start from empty states rather than the recorded ones
+    fresh: bool,
+    states: BTreeMap<PathBuf, (Store, CheckState)>,
+    stats: CacheStats,
+}
+
+impl CheckCache {
+    pub(crate) fn new(pass: &'static str) -> Self {
+        Self {
+            pass,
+            fresh: false,
+            states: BTreeMap::new(),
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Ignore what earlier runs recorded.
+    pub(crate) fn fresh(mut self) -> Self {
+        self.fresh = true;
+        self
+    }
+
+    pub(crate) fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// What the checks of `module`, read from `file`, depend on; `None`
+    /// outside any workspace or when an import does not resolve.
+    pub(crate) fn entry(
+        &mut self,
+        file: &Path,
+        module: &Module,
+        context: &CheckContext,
+    ) -> Result<Option<CheckEntry>> {
+        let Some(root) = workspace_root(file)? else {
+            return Ok(None);
+        };
+        let Ok(imports) = context.load_imports(module) else {
+            return Ok(None);
+        };
+        if !self.states.contains_key(&root) {
+            let store = Store::open(&root);
+            let state = if self.fresh {
+                CheckState::default()
+            } else {
+                CheckState::load(&store)?
+            };
+            self.states.insert(root.clone(), (store, state));
+        }
+        let limits = serde_json::to_string(&context.limits)?;
+        let config = format!("{} {limits}", env!("CARGO_PKG_VERSION"));
+        Ok(Some(CheckEntry {
+            root,
+            module: module.path.to_string(),
+            config: hash_bytes(config.as_bytes(), HashAlgo::default()),
+            key: CheckKey::new(
+                module,
+                imports
+                    .iter()
+                    .map(|(import, resolved)| (import.as_str(), &resolved.module)),
+            ),
+        }))
+    }
+
+    /// Whether the checks of `entry` came out clean before and can be
+    /// skipped, counting the cell as a hit or a miss.
+    pub(crate) fn is_clean(&mut self, entry: Option<&CheckEntry>) -> bool {
+        let clean = entry.is_some_and(|entry| {
+            self.states[&entry.root]
+                .1
+                .is_clean(self.pass, &entry.config, &entry.module, &entry.key)
+        });
+        if clean {
+            self.stats.hits += 1;
+        } else {
+            self.stats.misses += 1;
+        }
+        clean
+    }
+
+    /// Record whether the checks of `entry` came out clean.
+    pub(crate) fn record(&mut self, entry: CheckEntry, clean: bool) {
+        let state = &mut self
+            .states
+            .get_mut(&entry.root)
+            .expect("entries come with their state")
+            .1;
+        if clean {
+            state.record(self.pass, &entry.config, &entry.module, entry.key);
+        } else {
+            state.forget(self.pass, &entry.module);
+        }
+    }
+
+    pub(crate) fn save(&self) -> Result<()> {
+        for (store, state) in self.states.values() {
+            state.save(store)?;
+        }
+        Ok(())
+    }
+}
+
+/// Root of the project, else the workspace, holding `file`.
+fn workspace_root(file: &Path) -> Result<Option<PathBuf>> {
+    if let Some(project) = Project::discover(file)? {
+        return Ok(Some(project.root));
+    }
+    Ok(std::path::absolute(file).ok().and_then(find_workspace))
+}
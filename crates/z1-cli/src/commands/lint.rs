@@ -0,0 +1,512 @@
+//! `z1 lint`: run every static check without compiling.
+//!
+//! Each cell is parsed, its imports are resolved, and it is then type
+//! checked, effect checked, estimated against its context budget and checked
+//! against the policy limits. Inside a project, imports are resolved and
+//! policy limits read through its `z1.toml`. Unlike
+//! `z1 compile`, every checker runs and every diagnostic is reported; the
+//! exit code names the earliest stage that reported an error. With
+//! `--changed-only`, only the diagnostics a change introduces are reported
+//! and counted (see [`changed`](crate::commands::changed)).
+//!
+//! Inside a project or workspace, the checks of a cell that came out clean
+//! are skipped until the cell or the API of one of its imports changes (see
+//! [`incremental`](crate::commands::incremental)); `--no-cache` checks every
+//! cell.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::codes;
+use crate::commands::cells::collect_cells;
+use crate::commands::changed::{Baseline, ChangedArgs};
+use crate::commands::compile::CheckContext;
+use crate::commands::daemon;
+use crate::commands::fix;
+use crate::commands::incremental::{CacheStats, CheckCache};
+use crate::commands::watch::{watch, Invalidation};
+use crate::diagnostics::{
+    extract_line_info, print_diagnostic, print_explain_hint, Diagnostic, DiagnosticConfig,
+    DiagnosticLevel,
+};
+use crate::events::{self, Event, Summary};
+use crate::exit::Failure;
+use crate::output::{self, print_json, OutputFormat};
+
+#[derive(Debug, Args)]
+pub struct LintArgs {
+    /// Cells or directories to lint (directories are searched recursively;
+    /// default: `.`).
+    #[arg(value_name = "PATH")]
+    pub paths: Vec<PathBuf>,
+    /// Fail when any warning is reported.
+    #[arg(long)]
+    pub deny_warnings: bool,
+    /// Lint again whenever a cell changes semantically.
+    #[arg(long, conflicts_with = "changed_only")]
+    pub watch: bool,
+    /// Check every cell, even those unchanged since they last came out clean.
+    #[arg(long)]
+    pub no_cache: bool,
+    #[command(flatten)]
+    pub changed: ChangedArgs,
+}
+
+/// Stage of the lint pipeline a diagnostic comes from, in pipeline order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LintCategory {
+    Parse,
+    Resolve,
+    Type,
+    Effect,
+    Context,
+    Policy,
+    /// A warning from any stage, when warnings are denied
+    Warning,
+}
+
+impl LintCategory {
+    /// Failure, and so exit code, of a run whose earliest failing stage is
+    /// `self`.
+    pub fn failure(self) -> Failure {
+        match self {
+            LintCategory::Parse => Failure::Parse,
+            LintCategory::Type => Failure::Type,
+            LintCategory::Effect => Failure::Effect,
+            LintCategory::Context => Failure::Budget,
+            LintCategory::Policy => Failure::Policy,
+            LintCategory::Warning => Failure::Warnings,
+            LintCategory::Resolve => Failure::Resolve,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            LintCategory::Parse => "parse",
+            LintCategory::Resolve => "resolve",
+            LintCategory::Type => "type",
+            LintCategory::Effect => "effect",
+            LintCategory::Context => "context",
+            LintCategory::Policy => "policy",
+            LintCategory::Warning => "warning",
+        }
+    }
+
+    /// Category of an error diagnostic, from its code.
+    fn of(diagnostic: &Diagnostic) -> Option<Self> {
+        match diagnostic.code.as_deref()? {
+            codes::PARSE => Some(LintCategory::Parse),
+            codes::RESOLVE => Some(LintCategory::Resolve),
+            codes::TYPE => Some(LintCategory::Type),
+            codes::EFFECT => Some(LintCategory::Effect),
+            codes::CONTEXT => Some(LintCategory::Context),
+            codes::POLICY => Some(LintCategory::Policy),
+            _ => None,
+        }
+    }
+}
+
+/// A linted cell and what was found in it.
+struct LintedCell {
+    path: String,
+    source: String,
+    diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Debug, Serialize)]
+struct LintReport<'a> {
+    cells: usize,
+    errors: usize,
+    warnings: usize,
+    diagnostics: Vec<&'a Diagnostic>,
+    /// Revision the diagnostics are relative to, with `--changed-only`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    baseline: Option<&'a str>,
+    /// Baseline diagnostics the change resolved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resolved: Option<usize>,
+    /// Cells whose checks were reused or run, unless caching is off
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache: Option<CacheStats>,
+}
+
+pub fn run(args: LintArgs, format: OutputFormat) -> Result<()> {
+    if args.changed.changed_only {
+        let files = args.changed.cells(&args.paths)?;
+        let baseline = args.changed.baseline()?;
+        if let Some(category) = lint(&files, &args, Some(&baseline), format)? {
+            category.failure().exit();
+        }
+        return Ok(());
+    }
+
+    let paths = if args.paths.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        args.paths.clone()
+    };
+    if args.watch {
+        return watch(&paths, Invalidation::Semantic, |files| {
+            match lint(files, &args, None, format)? {
+                Some(category) => bail!("{} checks failed", category.name()),
+                None => Ok(()),
+            }
+        });
+    }
+
+    let mut files = Vec::new();
+    for path in &paths {
+        if path.is_dir() {
+            files.extend(collect_cells(path)?);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    if let Some(category) = lint(&files, &args, None, format)? {
+        category.failure().exit();
+    }
+    Ok(())
+}
+
+/// Lint `files` and print the results; returns the earliest failing stage.
+///
+/// Against a `baseline`, diagnostics the cells already had there are left
+/// out, and every cell is checked.
+fn lint(
+    files: &[PathBuf],
+    args: &LintArgs,
+    baseline: Option<&Baseline>,
+    format: OutputFormat,
+) -> Result<Option<LintCategory>> {
+    let mut cells = Vec::new();
+    let mut resolved = 0;
+    let mut cache = (baseline.is_none() && !args.no_cache).then(|| CheckCache::new("lint"));
+    for file in files {
+        let path = display_path(file);
+        let (source, mut diagnostics) = match daemon::lint(file, &path) {
+            Some(linted) => linted,
+            None => {
+                let source = std::fs::read_to_string(file)
+                    .with_context(|| format!("Failed to read {}", file.display()))?;
+                let context = CheckContext::for_cell(file)?;
+                let diagnostics = match cache.as_mut() {
+                    Some(cache) => lint_cached(cache, file, &source, &path, &context)?,
+                    None => lint_source(&source, &path, &context),
+                };
+                (source, diagnostics)
+            }
+        };
+        if let Some(old) = baseline.and_then(|baseline| baseline.source(file)) {
+            let context = CheckContext::for_cell(file)?;
+            let (fresh, gone) = new_diagnostics(diagnostics, &lint_source(&old, &path, &context));
+            diagnostics = fresh;
+            resolved += gone;
+        }
+        events::diagnostics(&source, &diagnostics);
+        cells.push(LintedCell {
+            path,
+            source,
+            diagnostics,
+        });
+    }
+
+    let stats = cache.as_ref().map(CheckCache::stats);
+    if let Some(cache) = &cache {
+        cache.save()?;
+    }
+    let diagnostics: Vec<&Diagnostic> = cells.iter().flat_map(|cell| &cell.diagnostics).collect();
+    let errors = count(&diagnostics, DiagnosticLevel::Error);
+    let warnings = count(&diagnostics, DiagnosticLevel::Warning);
+
+    match format {
+        OutputFormat::Text => {
+            if output::show_details() {
+                let config = DiagnosticConfig::default();
+                for cell in &cells {
+                    for diagnostic in &cell.diagnostics {
+                        print_diagnostic(diagnostic, &cell.source, &config);
+                    }
+                }
+                print_explain_hint(cells.iter().flat_map(|cell| &cell.diagnostics), &config);
+            }
+            if let (true, Some(baseline)) = (output::show_summary(), baseline) {
+                println!(
+                    "Linted {} changed cell(s): {errors} new error(s), {warnings} new warning(s), {resolved} resolved since {}",
+                    cells.len(),
+                    baseline.rev()
+                );
+            } else if output::show_summary() {
+                let reused = stats.map(|stats| format!("; {stats}")).unwrap_or_default();
+                println!(
+                    "Linted {} cell(s): {errors} error(s), {warnings} warning(s){reused}",
+                    cells.len()
+                );
+            }
+            if output::show_summary() {
+                let fixable = diagnostics.iter().filter(|d| d.fix.is_some()).count();
+                if fixable > 0 {
+                    println!("{fixable} issue(s) can be fixed with `z1 fix`");
+                }
+            }
+        }
+        OutputFormat::Json => print_json(&LintReport {
+            cells: cells.len(),
+            errors,
+            warnings,
+            diagnostics,
+            baseline: baseline.map(Baseline::rev),
+            resolved: baseline.map(|_| resolved),
+            cache: stats,
+        })?,
+        OutputFormat::Sarif => print_json(&sarif(&cells))?,
+        // Diagnostics were streamed as each cell was linted
+        OutputFormat::Events => {}
+    }
+
+    let category = cells
+        .iter()
+        .flat_map(|cell| &cell.diagnostics)
+        .filter_map(|diagnostic| match diagnostic.level {
+            DiagnosticLevel::Error => LintCategory::of(diagnostic),
+            DiagnosticLevel::Warning if args.deny_warnings => Some(LintCategory::Warning),
+            _ => None,
+        })
+        .min();
+    events::emit(&Event::Summary(Summary::new(
+        category.map(LintCategory::failure),
+        &[
+            ("cells", cells.len()),
+            ("errors", errors),
+            ("warnings", warnings),
+        ],
+    )));
+    Ok(category)
+}
+
+/// Run every checker on `source`, collecting their diagnostics.
+///
+/// A cell that does not parse yields only its parse error. Imports that do
+/// not resolve are reported and left opaque for the later checks.
+pub(crate) fn lint_source(
+    source: &str,
+    file_path: &str,
+    context: &CheckContext,
+) -> Vec<Diagnostic> {
+    let _cell = crate::crash::processing(Path::new(file_path), source);
+    match z1_parse::parse_module(source) {
+        Ok(module) => lint_module(&module, source, file_path, context),
+        Err(err) => vec![Diagnostic::from_parse_error(&err, file_path.to_string())],
+    }
+}
+
+/// [`lint_source`], skipping the checks of a cell `cache` has seen come out
+/// clean with the same hashes.
+fn lint_cached(
+    cache: &mut CheckCache,
+    file: &Path,
+    source: &str,
+    file_path: &str,
+    context: &CheckContext,
+) -> Result<Vec<Diagnostic>> {
+    let Ok(module) = z1_parse::parse_module(source) else {
+        return Ok(lint_source(source, file_path, context));
+    };
+    let entry = cache.entry(file, &module, context)?;
+    if cache.is_clean(entry.as_ref()) {
+        return Ok(Vec::new());
+    }
+    let _cell = crate::crash::processing(Path::new(file_path), source);
+    let diagnostics = lint_module(&module, source, file_path, context);
+    if let Some(entry) = entry {
+        cache.record(entry, diagnostics.is_empty());
+    }
+    Ok(diagnostics)
+}
+
+/// [`lint_source`] for a cell already parsed from `source`.
+pub(crate) fn lint_module(
+    module: &z1_ast::Module,
+    source: &str,
+    file_path: &str,
+    context: &CheckContext,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut imports = BTreeMap::new();
+    for item in &module.items {
+        let z1_ast::Item::Import(import) = item else {
+            continue;
+        };
+        match context.load_import(&import.path) {
+            Some(Ok(resolved)) => {
+                imports.insert(resolved.import, resolved.module);
+            }
+            Some(Err(err)) => diagnostics.push(Diagnostic::from_resolve_error(
+                &err,
+                import.span,
+                file_path.to_string(),
+            )),
+            None => {}
+        }
+    }
+    if let Err(err) = z1_typeck::check_module_with_imports(module, &imports) {
+        diagnostics.push(Diagnostic::from_type_error(&err, file_path.to_string()));
+    }
+    for warning in z1_typeck::collect_warnings(module) {
+        let mut diagnostic = Diagnostic::from_type_warning(&warning, file_path.to_string());
+        if let z1_typeck::TypeWarning::UnusedImport {
+            names,
+            entire,
+            span,
+            ..
+        } = &warning
+        {
+            let fix = imports_of(module)
+                .find(|import| import.span == *span)
+                .and_then(|import| fix::remove_import_names(import, source, names, *entire));
+            diagnostic.fix = fix;
+        }
+        diagnostics.push(diagnostic);
+    }
+    for item in &module.items {
+        let z1_ast::Item::Symbol(map) = item else {
+            continue;
+        };
+        let stale: Vec<_> = z1_fmt::stale_symbols(module)
+            .into_iter()
+            .filter(|pair| map.pairs.contains(pair))
+            .collect();
+        if !stale.is_empty() {
+            let mut diagnostic =
+                Diagnostic::from_stale_symbols(&stale, map.span, file_path.to_string());
+            diagnostic.fix = fix::regenerate_symbol_map(map, &stale, source);
+            diagnostics.push(diagnostic);
+        }
+    }
+    match z1_effects::check_module(module)
+        .and_then(|()| z1_effects::check_imports(module, &imports))
+    {
+        Ok(()) => diagnostics.extend(z1_effects::collect_effect_warnings(module).iter().map(
+            |warning| {
+                let mut diagnostic =
+                    Diagnostic::from_effect_warning(warning, file_path.to_string());
+                if let z1_effects::EffectWarning::UnusedCapability { capability, .. } = warning {
+                    diagnostic.fix = fix::remove_capability(module, source, capability);
+                }
+                diagnostic
+            },
+        )),
+        Err(err) => diagnostics.push(Diagnostic::from_effect_error(&err, file_path.to_string())),
+    }
+    if let Err(err) = z1_ctx::estimate_cell(module) {
+        diagnostics.push(Diagnostic::from_ctx_error(&err, file_path.to_string()));
+    }
+    let checker = z1_policy::PolicyChecker::new(context.limits.clone());
+    if let Err(violations) = checker.check_module(module) {
+        diagnostics.extend(violations.iter().map(|violation| {
+            Diagnostic::from_policy_violation(violation, module, file_path.to_string())
+        }));
+    }
+    diagnostics
+}
+
+/// The diagnostics of `current` not already reported at the baseline, and
+/// how many baseline diagnostics are gone. Diagnostics match by level, code
+/// and message, as their spans move with the edits around them.
+fn new_diagnostics(current: Vec<Diagnostic>, baseline: &[Diagnostic]) -> (Vec<Diagnostic>, usize) {
+    let mut matched = vec![false; baseline.len()];
+    let mut fresh = Vec::new();
+    for diagnostic in current {
+        let known = baseline.iter().enumerate().position(|(idx, old)| {
+            !matched[idx]
+                && old.level == diagnostic.level
+                && old.code == diagnostic.code
+                && old.message == diagnostic.message
+        });
+        match known {
+            Some(idx) => matched[idx] = true,
+            None => fresh.push(diagnostic),
+        }
+    }
+    let gone = matched.iter().filter(|matched| !**matched).count();
+    (fresh, gone)
+}
+
+fn imports_of(module: &z1_ast::Module) -> impl Iterator<Item = &z1_ast::Import> {
+    module.items.iter().filter_map(|item| match item {
+        z1_ast::Item::Import(import) => Some(import),
+        _ => None,
+    })
+}
+
+fn count(diagnostics: &[&Diagnostic], level: DiagnosticLevel) -> usize {
+    diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.level == level)
+        .count()
+}
+
+/// `/`-separated form of `path`, as reported in diagnostics.
+pub(crate) fn display_path(path: &Path) -> String {
+    let path = path.strip_prefix(".").unwrap_or(path);
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// SARIF 2.1.0 log of the diagnostics of `cells`.
+fn sarif(cells: &[LintedCell]) -> serde_json::Value {
+    let rules: Vec<_> = codes::CODES
+        .iter()
+        .map(|info| {
+            json!({
+                "id": info.code,
+                "shortDescription": { "text": info.title },
+                "fullDescription": { "text": info.explanation.replace('\n', " ") },
+            })
+        })
+        .collect();
+
+    let mut results = Vec::new();
+    for cell in cells {
+        for diagnostic in &cell.diagnostics {
+            let level = match diagnostic.level {
+                DiagnosticLevel::Error => "error",
+                DiagnosticLevel::Warning => "warning",
+                DiagnosticLevel::Info | DiagnosticLevel::Help => "note",
+            };
+            let mut message = diagnostic.message.clone();
+            if let Some(suggestion) = &diagnostic.suggestion {
+                message = format!("{message}\nHelp: {suggestion}");
+            }
+            let mut location = json!({ "artifactLocation": { "uri": cell.path } });
+            if let Some(span) = diagnostic.span {
+                let (line, column, _) = extract_line_info(&cell.source, span);
+                location["region"] = json!({ "startLine": line, "startColumn": column });
+            }
+            results.push(json!({
+                "ruleId": diagnostic.code,
+                "level": level,
+                "message": { "text": message },
+                "locations": [{ "physicalLocation": location }],
+            }));
+        }
+    }
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "z1 lint",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                }
+            },
+            "results": results,
+        }]
+    })
+}
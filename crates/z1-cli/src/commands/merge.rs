@@ -0,0 +1,139 @@
+//! `z1 merge`: three-way merge of a cell, for use as a git merge driver.
+//!
+//! The merge comes from [`z1_merge::merge_sources`]: edits to different
+//! items merge whatever lines they share, and an item both sides changed
+//! differently is left between conflict markers carrying the semantic hash
+//! of each version. Like git's own drivers, the result replaces OURS and the
+//! command fails when conflicts remain.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueHint};
+use serde::Serialize;
+use z1_merge::{Conflict, MergeError};
+
+use crate::commands::lint::display_path;
+use crate::commands::migrate::mode_of;
+use crate::exit::Failure;
+use crate::output::{self, print_json, OutputFormat};
+
+#[derive(Debug, Args)]
+pub struct MergeArgs {
+    /// Common ancestor of both versions (git's `%O`).
+    #[arg(value_name = "BASE", value_hint = ValueHint::FilePath)]
+    pub base: PathBuf,
+    /// Our version (git's `%A`); the merge is written here.
+    #[arg(value_name = "OURS", value_hint = ValueHint::FilePath)]
+    pub ours: PathBuf,
+    /// Their version (git's `%B`).
+    #[arg(value_name = "THEIRS", value_hint = ValueHint::FilePath)]
+    pub theirs: PathBuf,
+    /// Write the merge here instead of OURS.
+    #[arg(long, short = 'o', value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+    /// Print the merge instead of writing it.
+    #[arg(long, conflicts_with = "output")]
+    pub stdout: bool,
+    /// Path of the cell being merged (git's `%P`), deciding compact or
+    /// relaxed output; the temporary files git passes have no extension.
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+struct MergeReport {
+    cell: String,
+    /// Where the merge was written, unless printed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+    conflicts: Vec<ConflictReport>,
+    /// The merge itself, with --stdout
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ConflictReport {
+    kind: &'static str,
+    name: String,
+    message: String,
+    base: Option<String>,
+    ours: Option<String>,
+    theirs: Option<String>,
+}
+
+impl From<&Conflict> for ConflictReport {
+    fn from(conflict: &Conflict) -> Self {
+        ConflictReport {
+            kind: conflict.kind.as_str(),
+            name: conflict.name.clone(),
+            message: conflict.to_string(),
+            base: conflict.base.clone(),
+            ours: conflict.ours.clone(),
+            theirs: conflict.theirs.clone(),
+        }
+    }
+}
+
+pub fn run(args: MergeArgs, format: OutputFormat) -> Result<()> {
+    format.require("merge", &[OutputFormat::Json])?;
+    let read = |path: &Path| {
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+    };
+    let (base, ours, theirs) = (read(&args.base)?, read(&args.ours)?, read(&args.theirs)?);
+    let cell = args.path.as_deref().unwrap_or(&args.ours);
+    let path = display_path(cell);
+    let merged =
+        z1_merge::merge_sources(&base, &ours, &theirs, mode_of(cell)).map_err(|err| match err {
+            MergeError::Parse { .. } => Failure::Parse.error(format!("{path}: {err}")),
+            err => anyhow::anyhow!("{path}: {err}"),
+        })?;
+
+    let output = (!args.stdout).then(|| args.output.clone().unwrap_or_else(|| args.ours.clone()));
+    match &output {
+        Some(output) => fs::write(output, &merged.source)
+            .with_context(|| format!("Failed to write {}", output.display()))?,
+        None if !format.is_json() => print!("{}", merged.source),
+        None => {}
+    }
+    let report = MergeReport {
+        cell: path,
+        output: output.as_deref().map(display_path),
+        conflicts: merged.conflicts.iter().map(ConflictReport::from).collect(),
+        source: args.stdout.then(|| merged.source.clone()),
+    };
+
+    if format.is_json() {
+        print_json(&report)?;
+        if !report.conflicts.is_empty() {
+            // The report already lists the conflicts; no error document.
+            Failure::Check.exit();
+        }
+        return Ok(());
+    }
+    if output::show_summary() {
+        for conflict in &merged.conflicts {
+            eprintln!("conflict: {conflict}");
+            for (side, hash) in [
+                ("ours", &conflict.ours),
+                ("base", &conflict.base),
+                ("theirs", &conflict.theirs),
+            ] {
+                eprintln!("  {side:<6} {}", hash.as_deref().unwrap_or("(deleted)"));
+            }
+        }
+        if merged.conflicts.is_empty() {
+            eprintln!("Merged {} cleanly", report.cell);
+        }
+    }
+    if !merged.conflicts.is_empty() {
+        return Err(Failure::Check.error(format!(
+            "{}: {} conflict(s) left to resolve",
+            report.cell,
+            merged.conflicts.len()
+        )));
+    }
+    Ok(())
+}
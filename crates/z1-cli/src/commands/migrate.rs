@@ -0,0 +1,219 @@
+//! `z1 migrate`: upgrade cells to a newer syntax version.
+//!
+//! The rewrite rules live in [`z1_migrate`]; this command applies the steps
+//! between `--from` and `--to` to every cell, writes the cells that changed
+//! back in place and appends one provenance entry for the run, binding each
+//! migrated cell to its new semantic hash.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use clap::{Args, ValueHint};
+use serde::Serialize;
+use z1_hash::{hash_bytes, module_hashes, HashAlgo};
+use z1_prov::{CellBinding, ProvenanceChainExt, ProvenanceEntry};
+
+use crate::commands::cells;
+use crate::commands::fix::unified_diff;
+use crate::commands::lint::display_path;
+use crate::commands::prov::{load_or_new, sign_latest, KeyArgs};
+use crate::exit::Failure;
+use crate::output::{self, print_json, OutputFormat};
+
+#[derive(Debug, Args)]
+pub struct MigrateArgs {
+    /// Cells, directories of cells, or globs such as `src/**/*.z1c`.
+    #[arg(value_name = "PATH", default_value = ".", value_hint = ValueHint::AnyPath)]
+    pub paths: Vec<String>,
+    /// Syntax version the cells are written in.
+    #[arg(long)]
+    pub from: String,
+    /// Syntax version to migrate to.
+    #[arg(long, default_value = z1_migrate::CURRENT_SYNTAX)]
+    pub to: String,
+    /// Print the changes as a unified diff instead of writing them.
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Provenance chain the migration is recorded in; created if missing.
+    #[arg(long, value_name = "CHAIN", default_value = "provenance.z1p", value_hint = ValueHint::FilePath)]
+    pub prov: PathBuf,
+    /// Do not record the migration in a provenance chain.
+    #[arg(long, conflicts_with = "prov")]
+    pub no_prov: bool,
+    /// Actor recorded on the provenance entry.
+    #[arg(long, env = "Z1_ACTOR", default_value = "toolchain:z1-cli")]
+    pub actor: String,
+    #[command(flatten)]
+    pub key: KeyArgs,
+    /// Signer identifier for the provenance signature (default: the actor).
+    #[arg(long = "as", env = "Z1_SIGNER")]
+    pub signer: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct MigrateReport {
+    from: String,
+    to: String,
+    dry_run: bool,
+    cells: Vec<MigratedCell>,
+    /// Chain the migration was recorded in, if it was
+    #[serde(skip_serializing_if = "Option::is_none")]
+    provenance: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+struct MigratedCell {
+    path: String,
+    /// Rewrites per rule, in the order the rules were applied
+    rewrites: Vec<RuleCount>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diff: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct RuleCount {
+    rule: &'static str,
+    count: usize,
+}
+
+pub fn run(args: MigrateArgs, format: OutputFormat) -> Result<()> {
+    format.require("migrate", &[OutputFormat::Json])?;
+    let steps = z1_migrate::plan(&args.from, &args.to).map_err(|err| Failure::Usage.error(err))?;
+    let files = cells::expand(&args.paths)?;
+
+    let mut report = MigrateReport {
+        from: args.from.clone(),
+        to: args.to.clone(),
+        dry_run: args.dry_run,
+        cells: Vec::new(),
+        provenance: None,
+    };
+    let mut bindings = Vec::new();
+    let mut patch = String::new();
+    for file in &files {
+        let source = fs::read_to_string(file)
+            .with_context(|| format!("Failed to read {}", file.display()))?;
+        let path = display_path(file);
+        let migrated = z1_migrate::migrate_source(&source, mode_of(file), &steps)
+            .map_err(|err| Failure::Parse.error(format!("{path}: {err}")))?;
+        if !migrated.changed() {
+            continue;
+        }
+
+        let diff = unified_diff(&path, &source, &migrated.source);
+        patch.push_str(&diff);
+        if !args.dry_run {
+            fs::write(file, &migrated.source)
+                .with_context(|| format!("Failed to write {}", file.display()))?;
+            let module = z1_parse::parse_module(&migrated.source)
+                .map_err(|err| Failure::Parse.error(format!("{path}: {err}")))?;
+            bindings.push(CellBinding {
+                module: module.path.to_string(),
+                semhash: module_hashes(&module).semantic,
+            });
+        }
+        report.cells.push(MigratedCell {
+            path,
+            rewrites: migrated
+                .rewrites
+                .iter()
+                .map(|rewrite| RuleCount {
+                    rule: rewrite.rule,
+                    count: rewrite.count,
+                })
+                .collect(),
+            diff: args.dry_run.then_some(diff),
+        });
+    }
+
+    if !args.dry_run && !args.no_prov && !bindings.is_empty() {
+        record_migration(&args, bindings, &patch)?;
+        report.provenance = Some(args.prov.clone());
+    }
+
+    if format.is_json() {
+        return print_json(&report);
+    }
+    for cell in &report.cells {
+        if let Some(diff) = &cell.diff {
+            print!("{diff}");
+        } else if output::show_details() {
+            let rules: Vec<String> = cell
+                .rewrites
+                .iter()
+                .map(|rewrite| format!("{} ({})", rewrite.rule, rewrite.count))
+                .collect();
+            eprintln!("{}: {}", cell.path, rules.join(", "));
+        }
+    }
+    if output::show_summary() {
+        let verb = if args.dry_run {
+            "Would migrate"
+        } else {
+            "Migrated"
+        };
+        eprintln!(
+            "{verb} {} of {} cell(s) from syntax {} to {}",
+            report.cells.len(),
+            files.len(),
+            args.from,
+            args.to
+        );
+        if let Some(chain) = &report.provenance {
+            eprintln!("Recorded in {}", chain.display());
+        }
+    }
+    Ok(())
+}
+
+/// Formatter mode a cell is written in, from its extension.
+pub(crate) fn mode_of(path: &Path) -> z1_fmt::Mode {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("z1r") => z1_fmt::Mode::Relaxed,
+        _ => z1_fmt::Mode::Compact,
+    }
+}
+
+/// Append an entry for the migration to the `--prov` chain, signed when a
+/// key is configured.
+fn record_migration(args: &MigrateArgs, cells: Vec<CellBinding>, patch: &str) -> Result<()> {
+    let toolchain = format!("z1-cli/{}", env!("CARGO_PKG_VERSION"));
+    let modules: Vec<&str> = cells.iter().map(|cell| cell.module.as_str()).collect();
+    let description = format!(
+        "{toolchain} migrate syntax {} -> {} cells={}",
+        args.from,
+        args.to,
+        modules.join(",")
+    );
+
+    let mut chain = load_or_new(&args.prov)?;
+    let entry = ProvenanceEntry {
+        entry_id: format!("migrate:{}->{}@{}", args.from, args.to, chain.len()),
+        prev: None,
+        merged: vec![],
+        actor: args.actor.clone(),
+        model: "none".to_string(),
+        prompt_sha3: hash_bytes(description.as_bytes(), HashAlgo::Sha3_256),
+        prompt_excerpt: description.chars().take(200).collect(),
+        redaction: None,
+        tools: vec![toolchain],
+        diff_sha3: hash_bytes(patch.as_bytes(), HashAlgo::Sha3_256),
+        cells,
+        timestamp: Utc::now(),
+        signatures: vec![],
+        keyless: vec![],
+        rotation: None,
+        checkpoint: None,
+        timestamp_token: None,
+    };
+    chain.append(entry).context("invalid provenance entry")?;
+    if let Some(signing_key) = args.key.signer()? {
+        let signer = args.signer.as_deref().unwrap_or(&args.actor);
+        sign_latest(&mut chain, signing_key.as_ref(), signer)?;
+    }
+    chain
+        .save_to_file(&args.prov)
+        .with_context(|| format!("failed to write {}", args.prov.display()))
+}
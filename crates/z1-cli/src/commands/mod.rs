@@ -1,3 +1,31 @@
 pub mod bench;
+pub mod build;
+pub mod cells;
+pub mod changed;
 pub mod compile;
+pub mod completions;
+pub mod context;
+pub mod daemon;
+pub mod defaults;
+pub mod expand;
+pub mod explain;
+pub mod fix;
+pub mod fuzz_corpus;
+pub mod graph;
+pub mod hash;
+pub mod hooks;
+pub mod incremental;
+pub mod lint;
+pub mod merge;
+pub mod migrate;
+pub mod new;
+pub mod pack;
 pub mod prov;
+pub mod registry;
+pub mod rename;
+pub mod run;
+pub mod split;
+pub mod sync;
+pub mod test;
+pub mod tokens;
+pub mod watch;
@@ -0,0 +1,291 @@
+//! `z1 new` and `z1 init`: project and cell scaffolding.
+//!
+//! A project is a directory holding a `z1.toml` manifest, with cells under
+//! `src/` and tests under `tests/`. A cell's module path mirrors its file:
+//! `http.server` lives in `src/http/server.z1r`. Generated cells are run
+//! through the formatter, so they pass `z1 fmt --check` as written.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, Subcommand};
+use serde_json::json;
+
+use z1_resolve::{Project, MANIFEST_FILE as MANIFEST};
+
+use crate::output::{print_json, OutputFormat};
+
+#[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct NewArgs {
+    #[command(subcommand)]
+    pub command: Option<NewCommand>,
+    /// Directory to create the project in; its name is the project name.
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum NewCommand {
+    /// Add a cell with a valid header to the current project
+    Cell(CellArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct CellArgs {
+    /// Module path of the cell, e.g. `http.server`
+    pub module: String,
+    /// Capabilities the cell may use, e.g. `net,fs.ro`
+    #[arg(long, value_delimiter = ',')]
+    pub caps: Vec<String>,
+    /// Context budget in tokens
+    #[arg(long, default_value_t = 256)]
+    pub ctx: u32,
+    /// Project directory (default: the nearest directory holding z1.toml)
+    #[arg(long)]
+    pub dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct InitArgs {
+    /// Directory to turn into a project.
+    #[arg(default_value = ".")]
+    pub dir: PathBuf,
+    /// Project name (default: the directory name)
+    #[arg(long)]
+    pub name: Option<String>,
+}
+
+pub fn run_new(args: NewArgs, format: OutputFormat) -> Result<()> {
+    format.require("new", &[OutputFormat::Json])?;
+    if let Some(NewCommand::Cell(cell)) = args.command {
+        return new_cell(cell, format);
+    }
+    let Some(dir) = args.path else {
+        bail!("provide a project directory, or `z1 new cell <module>` to add a cell");
+    };
+    if dir.exists() {
+        bail!(
+            "{} already exists; use `z1 init` to scaffold it",
+            dir.display()
+        );
+    }
+    let name = project_name(&dir)?;
+    let created = scaffold(&dir, &name)?;
+    report(&created, format, || {
+        println!("Created project `{name}` in {}", dir.display());
+    })
+}
+
+pub fn run_init(args: InitArgs, format: OutputFormat) -> Result<()> {
+    format.require("init", &[OutputFormat::Json])?;
+    let name = match args.name {
+        Some(name) => name,
+        None => project_name(&args.dir)?,
+    };
+    let created = scaffold(&args.dir, &name)?;
+    report(&created, format, || {
+        println!("Initialized project `{name}` in {}", args.dir.display());
+    })
+}
+
+fn new_cell(args: CellArgs, format: OutputFormat) -> Result<()> {
+    let project = match args.dir {
+        Some(dir) => {
+            if !dir.join(MANIFEST).is_file() {
+                bail!("{} has no {MANIFEST}", dir.display());
+            }
+            Project::load(&dir)?
+        }
+        None => {
+            let cwd = std::env::current_dir()?;
+            Project::discover(&cwd)?.with_context(|| {
+                format!("no {MANIFEST} found here or above; run `z1 init` first")
+            })?
+        }
+    };
+
+    let segments = module_segments(&args.module)?;
+    for cap in &args.caps {
+        let base = cap.split_once('.').map_or(cap.as_str(), |(base, _)| base);
+        if matches!(
+            z1_effects::Effect::parse(base),
+            None | Some(z1_effects::Effect::Pure)
+        ) {
+            bail!("unknown capability `{cap}`");
+        }
+    }
+
+    // New cells go in the first source root
+    let mut path = project
+        .source_roots()
+        .into_iter()
+        .next()
+        .with_context(|| format!("{MANIFEST} lists no source roots"))?;
+    path.extend(&segments);
+    path.set_extension("z1r");
+    let source = cell_source(&args.module, args.ctx, &args.caps)?;
+    let created = write_new(&[(path, source)])?;
+    report(&created, format, || {})
+}
+
+/// Write the files of a new project named `name` into `dir`.
+fn scaffold(dir: &Path, name: &str) -> Result<Vec<PathBuf>> {
+    // Report `z1.toml` rather than `./z1.toml`
+    let dir = if dir == Path::new(".") {
+        Path::new("")
+    } else {
+        dir
+    };
+    let files = [
+        (dir.join(MANIFEST), manifest_source(name)),
+        (dir.join("src").join("main.z1r"), example_source()?),
+        (
+            dir.join("tests").join("main.z1t"),
+            TEST_TEMPLATE.to_string(),
+        ),
+        (dir.join(".gitignore"), "/.z1/\n".to_string()),
+    ];
+    write_new(&files)
+}
+
+/// Write `files`, failing before writing anything if one exists.
+fn write_new(files: &[(PathBuf, String)]) -> Result<Vec<PathBuf>> {
+    if let Some((path, _)) = files.iter().find(|(path, _)| path.exists()) {
+        bail!("{} already exists", path.display());
+    }
+    for (path, contents) in files {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(files.iter().map(|(path, _)| path.clone()).collect())
+}
+
+fn report(created: &[PathBuf], format: OutputFormat, summary: impl FnOnce()) -> Result<()> {
+    if format.is_json() {
+        return print_json(&json!({ "created": created }));
+    }
+    summary();
+    for path in created {
+        println!("  created {}", path.display());
+    }
+    Ok(())
+}
+
+fn project_name(dir: &Path) -> Result<String> {
+    let dir = if dir.as_os_str() == "." {
+        std::env::current_dir()?
+    } else {
+        dir.to_path_buf()
+    };
+    dir.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .with_context(|| format!("cannot name a project after {}; pass --name", dir.display()))
+}
+
+fn module_segments(module: &str) -> Result<Vec<&str>> {
+    let segments: Vec<&str> = module.split('.').collect();
+    let valid = |segment: &&str| {
+        let mut chars = segment.chars();
+        chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    };
+    if !segments.iter().all(valid) {
+        bail!("invalid module path `{module}`: expected identifiers separated by `.`");
+    }
+    Ok(segments)
+}
+
+fn manifest_source(name: &str) -> String {
+    let limits = z1_policy::PolicyLimits::default();
+    format!(
+        "[project]\n\
+         name = {name:?}\n\
+         version = \"0.1.0\"\n\
+         \n\
+         [policy]\n\
+         cell_max_ast_nodes = {}\n\
+         cell_max_exports = {}\n\
+         deps_max_fanin = {}\n\
+         fn_max_params = {}\n\
+         fn_max_locals = {}\n\
+         ctx_max_per_fn = {}\n",
+        limits.cell_max_ast_nodes,
+        limits.cell_max_exports,
+        limits.deps_max_fanin,
+        limits.fn_max_params,
+        limits.fn_max_locals,
+        limits.ctx_max_per_fn,
+    )
+}
+
+const EXAMPLE_TEMPLATE: &str = "module main : 1.0
+  ctx = 256
+  caps = []
+
+#sym { Greeting: Gr, add: ad, main: mn }
+
+type Greeting = { name: Str, count: U32 }
+
+fn add(a: U32, b: U32) -> U32
+  eff [pure]
+{
+  ret a + b;
+}
+
+fn main() -> Unit
+  eff [pure]
+{
+}
+";
+
+const TEST_TEMPLATE: &str = r#"spec "addition works" with { tags: ["unit"] } {
+  assert_eq(1 + 2, 3);
+}
+
+prop "addition commutes"
+for_all (a: U32, b: U32) runs 100 {
+  assert a + b == b + a;
+}
+"#;
+
+fn example_source() -> Result<String> {
+    formatted(EXAMPLE_TEMPLATE)
+}
+
+/// A cell declaring `caps`, with a stub function using their effects.
+///
+/// Fine-grained capabilities such as `fs.ro` have no effect of the same name
+/// and are left for the cell's functions to use.
+fn cell_source(module: &str, ctx: u32, caps: &[String]) -> Result<String> {
+    let mut effects: Vec<&str> = Vec::new();
+    for cap in caps {
+        if !cap.contains('.') && !effects.contains(&cap.as_str()) {
+            effects.push(cap);
+        }
+    }
+    if effects.is_empty() {
+        effects.push("pure");
+    }
+    formatted(&format!(
+        "module {module} : 1.0\n  ctx = {ctx}\n  caps = [{}]\n\n\
+         fn init() -> Unit\n  eff [{}]\n{{\n}}\n",
+        caps.join(", "),
+        effects.join(", ")
+    ))
+}
+
+/// `source` in the formatter's relaxed layout.
+fn formatted(source: &str) -> Result<String> {
+    let module = z1_parse::parse_module(source)
+        .map_err(|err| anyhow::anyhow!("generated cell does not parse: {err}"))?;
+    Ok(z1_fmt::format_module(
+        &module,
+        z1_fmt::Mode::Relaxed,
+        &z1_fmt::FmtOptions::default(),
+    )?)
+}
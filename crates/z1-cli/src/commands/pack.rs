@@ -0,0 +1,158 @@
+//! `z1 pack`: a cell and its dependencies as one prompt-ready bundle.
+//!
+//! The imports of the cell are resolved as `z1 lint` resolves them, then the
+//! imports of those, and so on; imports that stay opaque are left out. The
+//! pack comes from [`z1_ctx::pack`]: the cell in full, preceded by each
+//! dependency as its full source or, when `--budget` is short, as its
+//! interface. It is printed on stdout, ready to paste into a prompt.
+
+use std::collections::{BTreeSet, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueHint};
+use serde::Serialize;
+use z1_ast::{Item, Module};
+use z1_ctx::{PackError, PackOptions, PackedDep};
+
+use crate::commands::compile::CheckContext;
+use crate::commands::defaults::Defaults;
+use crate::commands::lint::display_path;
+use crate::commands::migrate::mode_of;
+use crate::exit::Failure;
+use crate::output::{self, print_json, OutputFormat};
+
+#[derive(Debug, Args)]
+pub struct PackArgs {
+    /// Entry cell of the pack.
+    #[arg(value_name = "CELL", value_hint = ValueHint::FilePath)]
+    pub cell: PathBuf,
+    /// Tokens the pack may take (default: every dependency in full).
+    #[arg(long, value_name = "TOKENS")]
+    pub budget: Option<u32>,
+    /// Custom characters-per-token ratio (default: `chars-per-token` in
+    /// z1.toml, else 3.8).
+    #[arg(long)]
+    pub chars_per_token: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct PackReport {
+    cell: String,
+    tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    budget: Option<u32>,
+    /// Transitive dependencies, nearest first
+    deps: Vec<DepRow>,
+    source: String,
+}
+
+#[derive(Debug, Serialize)]
+struct DepRow {
+    import: String,
+    /// `interface` or `source`; `null` when left out
+    form: Option<&'static str>,
+    interface_tokens: u32,
+    source_tokens: u32,
+}
+
+impl From<PackedDep> for DepRow {
+    fn from(dep: PackedDep) -> Self {
+        DepRow {
+            import: dep.import,
+            form: dep.form.map(|form| form.as_str()),
+            interface_tokens: dep.interface_tokens,
+            source_tokens: dep.source_tokens,
+        }
+    }
+}
+
+pub fn run(args: PackArgs, format: OutputFormat) -> Result<()> {
+    format.require("pack", &[OutputFormat::Json])?;
+    let cell = &args.cell;
+    let path = display_path(cell);
+    let source =
+        fs::read_to_string(cell).with_context(|| format!("Failed to read {}", cell.display()))?;
+    let module = z1_parse::parse_module(&source)
+        .map_err(|err| Failure::Parse.error(format!("{path}: {err}")))?;
+    let deps = dependencies(&CheckContext::for_cell(cell)?, &module)
+        .context("Import resolution failed")
+        .map_err(|err| Failure::Resolve.wrap(err))?;
+    let mut options = PackOptions {
+        budget: args.budget,
+        mode: mode_of(cell),
+        ..PackOptions::default()
+    };
+    let chars_per_token = match args.chars_per_token {
+        Some(ratio) => Some(ratio),
+        None => Defaults::discover(cell)?.chars_per_token,
+    };
+    if let Some(ratio) = chars_per_token {
+        options.chars_per_token = ratio;
+    }
+    let pack = z1_ctx::pack(&module, &deps, &options).map_err(|err| match err {
+        PackError::OverBudget { .. } => Failure::Budget.error(format!("{path}: {err}")),
+        err => anyhow::anyhow!("{path}: {err}"),
+    })?;
+
+    let report = PackReport {
+        cell: path,
+        tokens: pack.tokens,
+        budget: args.budget,
+        deps: pack.deps.into_iter().map(DepRow::from).collect(),
+        source: pack.source,
+    };
+    if format.is_json() {
+        return print_json(&report);
+    }
+    print!("{}", report.source);
+    if output::show_summary() {
+        eprintln!(
+            "Pack of {}: {} tokens, {} dependency(ies)",
+            report.cell,
+            report.tokens,
+            report.deps.len()
+        );
+        for dep in &report.deps {
+            match dep.form {
+                Some(form) => eprintln!("  {} as {form}", dep.import),
+                None => eprintln!("  omitted {}", dep.import),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The cells `module` imports, directly or through other imports, nearest
+/// first, by the import path each was first reached through.
+fn dependencies(context: &CheckContext, module: &Module) -> Result<Vec<(String, Module)>> {
+    let mut deps = Vec::new();
+    let mut seen = BTreeSet::new();
+    let mut queue: VecDeque<String> = imports(module).collect();
+    while let Some(import) = queue.pop_front() {
+        if !seen.insert(import.clone()) {
+            continue;
+        }
+        let Some(resolved) = context.load_import(&import) else {
+            continue;
+        };
+        let resolved = resolved?;
+        // Import paths may name one cell several ways; a cycle leads back
+        // to the cell itself
+        let duplicate = !seen.insert(resolved.import.clone()) && resolved.import != import;
+        if duplicate || resolved.module.path == module.path {
+            continue;
+        }
+        queue.extend(imports(&resolved.module));
+        deps.push((import, resolved.module));
+    }
+    Ok(deps)
+}
+
+fn imports(module: &Module) -> impl Iterator<Item = String> + '_ {
+    module.items.iter().filter_map(|item| match item {
+        Item::Import(import) => Some(import.path.clone()),
+        _ => None,
+    })
+}
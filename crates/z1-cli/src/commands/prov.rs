@@ -1,12 +1,33 @@
 //! Provenance CLI commands.
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
 use clap::{Args, Subcommand};
 use colored::Colorize;
+use serde::Serialize;
+use serde_json::json;
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
-use z1_prov::{keygen, verify_chain, verify_chain_signatures, ProvenanceChain, ProvenanceChainExt};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use z1_hash::{hash_bytes, module_hashes, HashAlgo};
+use z1_prov::{
+    append_checkpoint, compact, compute_entry_hash, endorse_rotation, keygen, merge_chains,
+    sign_entry_with, sign_keyless, timestamp_entry, untested_cells, verify_chain,
+    verify_chain_keyless, verify_chain_signatures, verify_chain_sources, verify_chain_timestamps,
+    verify_checkpoint, AuthorityKey, CellBinding, CertificateAuthority, CommandSigner, EntrySigner,
+    FulcioAuthority, Identity, KeylessTrust, Keystore, LocalAuthority, LocalCertificateAuthority,
+    LocalTransparencyLog, MergeOutcome, ProvenanceChain, ProvenanceChainExt, ProvenanceEntry,
+    ProvenanceQuery, RedactionPolicy, RekorLog, ReportOptions, Rfc3161Authority, SoftwareSigner,
+    TimestampAuthority, TransparencyLog, VerificationReport, SECRET_PATTERNS,
+};
+
+use crate::commands::build::workspace_cells;
+use crate::exit::Failure;
+use crate::output::{print_json, OutputFormat};
+
+/// Environment variable holding the keystore passphrase for non-interactive use.
+const PASSPHRASE_ENV: &str = "Z1_KEY_PASSPHRASE";
 
 #[derive(Debug, Args)]
 pub struct ProvArgs {
@@ -16,30 +37,442 @@ pub struct ProvArgs {
 
 #[derive(Debug, Subcommand)]
 pub enum ProvCommand {
-    /// Display a provenance chain in human-readable format
-    Log {
-        /// Path to the provenance chain file (.z1p)
-        file: PathBuf,
-    },
+    /// Display the entries of a provenance chain, optionally filtered
+    Log(LogArgs),
     /// Verify the integrity of a provenance chain
-    Verify {
-        /// Path to the provenance chain file (.z1p)
-        file: PathBuf,
-        /// Optional path to JSON file mapping signer IDs to public keys (hex-encoded)
-        #[arg(long)]
-        keys: Option<PathBuf>,
-    },
+    Verify(VerifyArgs),
     /// Generate a new Ed25519 keypair
     Keygen {
         /// Optional output path for the keypair (default: prints to stdout)
+        #[arg(conflicts_with = "name")]
         output: Option<PathBuf>,
+        /// Store the key encrypted in the keystore under this name
+        #[arg(long)]
+        name: Option<String>,
+        #[command(flatten)]
+        keystore: KeystoreArgs,
+    },
+    /// List the keys in the keystore
+    Keys {
+        #[command(flatten)]
+        keystore: KeystoreArgs,
     },
+    /// Append a new entry to a provenance chain (created if missing)
+    Append(AppendArgs),
+    /// Sign an entry of a provenance chain with a local keypair
+    Sign(SignArgs),
+    /// Rotate a signer's key, endorsing the new key with the old one
+    Rotate(RotateArgs),
+    /// Attach a trusted timestamp to the latest entry of a chain
+    Stamp(StampArgs),
+    /// Sign the latest entry with an ephemeral key certified for an identity
+    SignKeyless(SignKeylessArgs),
+    /// Merge another copy of a chain into this one, recording a merge entry
+    Merge(MergeArgs),
+    /// Checkpoint the chain and drop the history before the checkpoint
+    Compact(CompactArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct LogArgs {
+    /// Path to the provenance chain file (.z1p)
+    pub file: PathBuf,
+    /// Only entries by this actor (`agent:foo` also matches `agent:foo/1.2`)
+    #[arg(long)]
+    pub actor: Option<String>,
+    /// Only entries generated with this model
+    #[arg(long)]
+    pub model: Option<String>,
+    /// Only entries at or after this time (YYYY-MM-DD or RFC 3339)
+    #[arg(long, value_parser = parse_time)]
+    pub since: Option<DateTime<Utc>>,
+    /// Only entries before this time (YYYY-MM-DD or RFC 3339)
+    #[arg(long, value_parser = parse_time)]
+    pub until: Option<DateTime<Utc>>,
+    /// Only entries for this cell name or entry ID
+    #[arg(long)]
+    pub cell: Option<String>,
+    /// Only entries whose entry, prompt or diff hash starts with this prefix
+    #[arg(long)]
+    pub hash: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct VerifyArgs {
+    /// Path to the provenance chain file (.z1p)
+    pub file: PathBuf,
+    /// Optional path to JSON file mapping signer IDs to public keys (hex-encoded)
+    #[arg(long)]
+    pub keys: Option<PathBuf>,
+    /// JSON file mapping timestamp authority IDs to public keys (hex-encoded),
+    /// or for RFC 3161 services to their root certificates (PEM)
+    #[arg(long)]
+    pub tsa_keys: Option<PathBuf>,
+    /// Fail unless every entry carries a trusted timestamp (requires --tsa-keys)
+    #[arg(long, requires = "tsa_keys")]
+    pub require_timestamps: bool,
+    /// JSON file with trusted certificate authorities, transparency logs and
+    /// identities for keyless signatures
+    #[arg(long)]
+    pub keyless_trust: Option<PathBuf>,
+    /// Fail unless every entry carries a keyless signature (requires --keyless-trust)
+    #[arg(long, requires = "keyless_trust")]
+    pub require_keyless: bool,
+    /// Check the cells recorded in the chain against this workspace
+    #[arg(long)]
+    pub workspace: Option<PathBuf>,
+    /// Check the workspace against the chain as of this entry instead of its
+    /// latest state (requires --workspace)
+    #[arg(long, requires = "workspace")]
+    pub at: Option<String>,
+    /// Fail unless every workspace cell passed a test run recorded by
+    /// `z1 test --prov` at its current semantic hash (requires --workspace)
+    #[arg(long, requires = "workspace")]
+    pub require_tests: bool,
+    /// Archived history written by `z1 prov compact --archive`, checked
+    /// against the checkpoint the chain starts at
+    #[arg(long)]
+    pub history: Option<PathBuf>,
+    /// Write a JSON report with the outcome of every check on every entry,
+    /// even when verification fails
+    #[arg(long)]
+    pub report: Option<PathBuf>,
+}
+
+/// A selected entry as printed by `log --format json`.
+#[derive(Serialize)]
+struct LogRecord<'a> {
+    /// Position in the chain, starting at 1
+    index: usize,
+    hash: String,
+    #[serde(flatten)]
+    entry: &'a ProvenanceEntry,
+}
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct KeystoreArgs {
+    /// Keystore directory (default: ~/.z1/keys)
+    #[arg(long, env = "Z1_KEYSTORE")]
+    pub keystore: Option<PathBuf>,
+}
+
+/// Where a signing key comes from: a plain keypair file or the keystore.
+#[derive(Debug, Clone, Default, Args)]
+pub struct KeyArgs {
+    /// Keypair file written by `z1 prov keygen <output>`
+    #[arg(long, env = "Z1_SIGNING_KEY", conflicts_with = "key_name")]
+    pub key: Option<PathBuf>,
+    /// Name of a key in the encrypted keystore
+    #[arg(long, env = "Z1_KEY_NAME")]
+    pub key_name: Option<String>,
+    #[command(flatten)]
+    pub keystore: KeystoreArgs,
+    /// PKCS#11 module of a hardware token holding the key (e.g. libykcs11.so);
+    /// signs through OpenSC's pkcs11-tool, with the PIN from Z1_PKCS11_PIN
+    #[arg(long, env = "Z1_PKCS11_MODULE", conflicts_with_all = ["key", "key_name", "sign_command"], requires = "pkcs11_key_id")]
+    pub pkcs11_module: Option<PathBuf>,
+    /// ID of the key object on the token (hex)
+    #[arg(long, env = "Z1_PKCS11_KEY_ID")]
+    pub pkcs11_key_id: Option<String>,
+    /// Program that signs the 32-byte hash on stdin and prints the signature
+    #[arg(long, env = "Z1_SIGN_COMMAND", conflicts_with_all = ["key", "key_name"])]
+    pub sign_command: Option<String>,
+    /// Public key (hex) the device must sign with; checked after signing
+    #[arg(long, env = "Z1_SIGNER_PUBLIC_KEY")]
+    pub device_public_key: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct AppendArgs {
+    /// Path to the provenance chain file (.z1p)
+    pub file: PathBuf,
+    /// Entry identifier (e.g. "cell:http.server@v3")
+    #[arg(long = "id")]
+    pub entry_id: String,
+    /// Actor who made the change (human or agent)
+    #[arg(long, env = "Z1_ACTOR")]
+    pub actor: String,
+    /// Model used for generation
+    #[arg(long, env = "Z1_MODEL", default_value = "none")]
+    pub model: String,
+    /// Prompt text; only its hash and an excerpt are recorded
+    #[arg(long, conflicts_with = "prompt_file")]
+    pub prompt: Option<String>,
+    /// Read the prompt from a file (`-` for stdin)
+    #[arg(long)]
+    pub prompt_file: Option<PathBuf>,
+    /// Diff or changed file whose hash is recorded (`-` for stdin)
+    #[arg(long)]
+    pub diff: PathBuf,
+    /// Tool used during generation (repeatable)
+    #[arg(long = "tool")]
+    pub tools: Vec<String>,
+    /// Cell the change produced; its module and semantic hash are recorded (repeatable)
+    #[arg(long = "cell")]
+    pub cells: Vec<PathBuf>,
+    #[command(flatten)]
+    pub redaction: RedactArgs,
+}
+
+/// How the recorded prompt excerpt is redacted.
+#[derive(Debug, Clone, Default, Args)]
+pub struct RedactArgs {
+    /// JSON redaction policy to start from
+    #[arg(long, env = "Z1_REDACTION_POLICY")]
+    pub redaction_policy: Option<PathBuf>,
+    /// Remove common credential formats (API keys, tokens, passwords, private keys)
+    #[arg(long)]
+    pub redact_secrets: bool,
+    /// Remove matches of this regular expression (repeatable)
+    #[arg(long = "redact")]
+    pub patterns: Vec<String>,
+    /// Remove this word, ignoring case (repeatable)
+    #[arg(long = "redact-keyword")]
+    pub keywords: Vec<String>,
+    /// Maximum excerpt length in characters
+    #[arg(long)]
+    pub excerpt_len: Option<usize>,
+    /// Record only the prompt hash, no excerpt
+    #[arg(long)]
+    pub hash_only_prompt: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct SignArgs {
+    /// Path to the provenance chain file (.z1p)
+    pub file: PathBuf,
+    #[command(flatten)]
+    pub key: KeyArgs,
+    /// Signer identifier recorded with the signature (e.g. "dev:alice@keys/ed25519")
+    #[arg(long = "as", env = "Z1_SIGNER")]
+    pub signer: String,
+    /// Entry to sign (default: the latest entry)
+    #[arg(long = "id")]
+    pub entry_id: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct RotateArgs {
+    /// Path to the provenance chain file (.z1p)
+    pub file: PathBuf,
+    /// Signer whose key is rotated
+    #[arg(long = "as", env = "Z1_SIGNER")]
+    pub signer: String,
+    /// Current keystore key of the signer
+    #[arg(long)]
+    pub from: String,
+    /// Keystore key that replaces it
+    #[arg(long)]
+    pub to: String,
+    #[command(flatten)]
+    pub keystore: KeystoreArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct StampArgs {
+    /// Path to the provenance chain file (.z1p)
+    pub file: PathBuf,
+    /// RFC 3161 time-stamping service to stamp with (e.g.
+    /// https://freetsa.org/tsr)
+    #[arg(long, env = "Z1_TSA_URL")]
+    pub tsa_url: Option<String>,
+    /// Timestamp authority identifier recorded on the token (default: the
+    /// service URL)
+    #[arg(long, env = "Z1_TSA", required_unless_present = "tsa_url")]
+    pub authority: Option<String>,
+    /// Key of a local authority, which can stamp any time: verifiers must
+    /// trust its holder
+    #[command(flatten)]
+    pub key: KeyArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct SignKeylessArgs {
+    /// Path to the provenance chain file (.z1p)
+    pub file: PathBuf,
+    /// Identity to certify (e.g. a CI workflow URI or an email)
+    #[arg(long, env = "Z1_OIDC_SUBJECT", required_unless_present = "fulcio")]
+    pub identity: Option<String>,
+    /// OIDC issuer that authenticated the identity
+    #[arg(long, env = "Z1_OIDC_ISSUER", required_unless_present = "fulcio")]
+    pub oidc_issuer: Option<String>,
+    /// Certificate authority identifier, trusted by verifiers as a root
+    #[arg(long, env = "Z1_CA", default_value = "ca:local")]
+    pub ca: String,
+    /// Certificate authority signing key
+    #[command(flatten)]
+    pub ca_key: KeyArgs,
+    /// Certify with a Fulcio instance instead of a local key (e.g.
+    /// https://fulcio.sigstore.dev); the identity is read from the token
+    #[arg(long, env = "Z1_FULCIO_URL", requires = "oidc_token")]
+    pub fulcio: Option<String>,
+    /// OIDC identity token to present to Fulcio
+    #[arg(long, env = "SIGSTORE_ID_TOKEN", hide_env_values = true)]
+    pub oidc_token: Option<String>,
+    /// Transparency log identifier
+    #[arg(long, env = "Z1_TLOG", default_value = "log:local")]
+    pub log: String,
+    /// Transparency log keypair file written by `z1 prov keygen <output>`
+    #[arg(long, env = "Z1_TLOG_KEY", required_unless_present = "rekor")]
+    pub log_key: Option<PathBuf>,
+    /// Record the signature in a Rekor instance instead (e.g.
+    /// https://rekor.sigstore.dev); needs a Fulcio certificate
+    #[arg(long, env = "Z1_REKOR_URL", requires = "fulcio")]
+    pub rekor: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct MergeArgs {
+    /// Provenance chain to merge into (.z1p)
+    pub file: PathBuf,
+    /// Diverged copy of the chain whose entries are brought in
+    pub theirs: PathBuf,
+    /// Merge entry identifier (default: "merge:<their latest entry>")
+    #[arg(long = "id")]
+    pub entry_id: Option<String>,
+    /// Actor performing the merge
+    #[arg(long, env = "Z1_ACTOR")]
+    pub actor: String,
+    /// Key to sign the merge entry with
+    #[command(flatten)]
+    pub key: KeyArgs,
+    /// Signer identifier recorded with the signature
+    #[arg(long = "as", env = "Z1_SIGNER")]
+    pub signer: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct CompactArgs {
+    /// Path to the provenance chain file (.z1p)
+    pub file: PathBuf,
+    /// Checkpoint entry identifier (default: "checkpoint:<root prefix>")
+    #[arg(long = "id")]
+    pub entry_id: Option<String>,
+    /// Actor recording the checkpoint
+    #[arg(long, env = "Z1_ACTOR")]
+    pub actor: String,
+    /// Write the dropped history to this file
+    #[arg(long)]
+    pub archive: Option<PathBuf>,
+    /// Key to sign the checkpoint entry with
+    #[command(flatten)]
+    pub key: KeyArgs,
+    /// Signer identifier recorded with the signature
+    #[arg(long = "as", env = "Z1_SIGNER")]
+    pub signer: Option<String>,
+}
+
+impl KeystoreArgs {
+    pub fn open(&self) -> Result<Keystore> {
+        let dir = match &self.keystore {
+            Some(dir) => dir.clone(),
+            None => std::env::var_os("HOME")
+                .or_else(|| std::env::var_os("USERPROFILE"))
+                .map(|home| PathBuf::from(home).join(".z1").join("keys"))
+                .context("cannot locate the home directory; pass --keystore")?,
+        };
+        Ok(Keystore::open(dir))
+    }
+}
+
+impl RedactArgs {
+    /// The configured policy, or `None` when it is the default truncation.
+    pub fn policy(&self) -> Result<Option<RedactionPolicy>> {
+        let mut policy = match &self.redaction_policy {
+            Some(path) => {
+                let json = fs::read_to_string(path)
+                    .with_context(|| format!("failed to read {}", path.display()))?;
+                serde_json::from_str(&json)
+                    .with_context(|| format!("invalid redaction policy {}", path.display()))?
+            }
+            None => RedactionPolicy::default(),
+        };
+        if self.redact_secrets {
+            policy
+                .patterns
+                .extend(SECRET_PATTERNS.iter().map(|p| p.to_string()));
+        }
+        policy.patterns.extend(self.patterns.iter().cloned());
+        policy.keywords.extend(self.keywords.iter().cloned());
+        if let Some(len) = self.excerpt_len {
+            policy.max_len = len;
+        }
+        policy.hash_only |= self.hash_only_prompt;
+        Ok((policy != RedactionPolicy::default()).then_some(policy))
+    }
+}
+
+impl KeyArgs {
+    /// Signer for the configured key or device, if any.
+    pub fn signer(&self) -> Result<Option<Box<dyn EntrySigner>>> {
+        let device = if let Some(module) = &self.pkcs11_module {
+            let key_id = self
+                .pkcs11_key_id
+                .as_deref()
+                .context("--pkcs11-module requires --pkcs11-key-id")?;
+            let pin = std::env::var("Z1_PKCS11_PIN").ok();
+            CommandSigner::pkcs11(module, key_id, pin.as_deref())
+        } else if let Some(command) = &self.sign_command {
+            let mut words = command.split_whitespace().map(str::to_string);
+            let program = words.next().context("--sign-command is empty")?;
+            CommandSigner::new(program, words)
+        } else {
+            return Ok(self
+                .load()?
+                .map(|key| Box::new(SoftwareSigner::new(&key)) as Box<dyn EntrySigner>));
+        };
+        let device = match &self.device_public_key {
+            Some(hex_key) => device.with_public_key(
+                hex::decode(hex_key)
+                    .ok()
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .context("--device-public-key must be a 32-byte hex key")?,
+            ),
+            None => device,
+        };
+        Ok(Some(Box::new(device)))
+    }
+
+    /// Private key from the configured source, if any.
+    pub fn load(&self) -> Result<Option<[u8; 32]>> {
+        if let Some(path) = &self.key {
+            return load_private_key(path).map(Some);
+        }
+        let Some(name) = &self.key_name else {
+            return Ok(None);
+        };
+        let passphrase = read_passphrase(&format!("Passphrase for key `{name}`: "), false)?;
+        let key = self.keystore.open()?.unlock(name, &passphrase)?;
+        Ok(Some(key))
+    }
 }
 
 /// Handle the z1prov log command.
-pub fn cmd_log(file: PathBuf) -> Result<()> {
-    let chain = ProvenanceChain::load_from_file(&file)
+pub fn cmd_log(args: LogArgs, format: OutputFormat) -> Result<()> {
+    let file = &args.file;
+    let chain = ProvenanceChain::load_from_file(file)
         .with_context(|| format!("failed to load provenance chain from {}", file.display()))?;
+    let query = ProvenanceQuery {
+        actor: args.actor,
+        model: args.model,
+        since: args.since,
+        until: args.until,
+        cell: args.cell,
+        hash: args.hash,
+    };
+    let selected: Vec<_> = query.filter(&chain).collect();
+
+    if format.is_json() {
+        let records: Vec<_> = selected
+            .into_iter()
+            .map(|(idx, entry)| LogRecord {
+                index: idx + 1,
+                hash: compute_entry_hash(entry),
+                entry,
+            })
+            .collect();
+        return print_json(&records);
+    }
 
     if chain.is_empty() {
         println!("{}", "Chain is empty".yellow());
@@ -49,15 +482,21 @@ pub fn cmd_log(file: PathBuf) -> Result<()> {
     println!("{}", "Provenance Chain".bold().underline());
     println!("{}: {}", "File".bold(), file.display());
     println!("{}: {}", "Entries".bold(), chain.len());
+    if query != ProvenanceQuery::default() {
+        println!("{}: {}", "Matching".bold(), selected.len());
+    }
     println!("{}: {}", "Merkle Root".bold(), chain.merkle_root);
     println!();
 
-    for (idx, entry) in chain.entries.iter().enumerate() {
+    for (idx, entry) in selected {
         println!("{} {}", "Entry".bold().cyan(), (idx + 1).to_string().cyan());
         println!("  {}: {}", "ID".bold(), entry.entry_id);
         if let Some(prev) = &entry.prev {
             println!("  {}: {}", "Previous".bold(), prev);
         }
+        for parent in &entry.merged {
+            println!("  {}: {}", "Merged".bold(), parent);
+        }
         println!("  {}: {}", "Actor".bold(), entry.actor);
         println!("  {}: {}", "Model".bold(), entry.model);
         println!("  {}: {}", "Timestamp".bold(), entry.timestamp);
@@ -67,10 +506,51 @@ pub fn cmd_log(file: PathBuf) -> Result<()> {
             "Prompt Excerpt".bold(),
             entry.prompt_excerpt
         );
+        if let Some(policy) = &entry.redaction {
+            let summary = if policy.hash_only {
+                "hash only".to_string()
+            } else {
+                format!(
+                    "max {} chars, {} patterns, {} keywords",
+                    policy.max_len,
+                    policy.patterns.len(),
+                    policy.keywords.len()
+                )
+            };
+            println!("  {}: {}", "Redaction".bold(), summary);
+        }
         if !entry.tools.is_empty() {
             println!("  {}: [{}]", "Tools".bold(), entry.tools.join(", "));
         }
         println!("  {}: {}", "Diff SHA3".bold(), entry.diff_sha3);
+        for cell in &entry.cells {
+            println!("  {}: {} {}", "Cell".bold(), cell.module, cell.semhash);
+        }
+        if let Some(token) = &entry.timestamp_token {
+            println!(
+                "  {}: {} by {}",
+                "Trusted Time".bold(),
+                token.time,
+                token.authority.green()
+            );
+        }
+        if let Some(checkpoint) = &entry.checkpoint {
+            println!(
+                "  {}: {} entries, root {}",
+                "Checkpoint".bold(),
+                checkpoint.entries,
+                checkpoint.root
+            );
+        }
+        if let Some(rotation) = &entry.rotation {
+            println!(
+                "  {}: {} {} -> {}",
+                "Key Rotation".bold(),
+                rotation.signer.green(),
+                rotation.old_key,
+                rotation.new_key
+            );
+        }
 
         if !entry.signatures.is_empty() {
             println!("  {}:", "Signatures".bold());
@@ -85,6 +565,18 @@ pub fn cmd_log(file: PathBuf) -> Result<()> {
         } else {
             println!("  {}: {}", "Signatures".bold(), "none".yellow());
         }
+        for keyless in &entry.keyless {
+            if let Some(leaf) = keyless.chain.first() {
+                println!(
+                    "  {}: {} ({}) logged as {}#{}",
+                    "Keyless".bold(),
+                    leaf.subject.green(),
+                    leaf.issuer,
+                    keyless.log_entry.log_id,
+                    keyless.log_entry.index
+                );
+            }
+        }
         println!();
     }
 
@@ -92,41 +584,192 @@ pub fn cmd_log(file: PathBuf) -> Result<()> {
 }
 
 /// Handle the z1prov verify command.
-pub fn cmd_verify(file: PathBuf, keys_file: Option<PathBuf>) -> Result<()> {
-    let chain = ProvenanceChain::load_from_file(&file)
+///
+/// With `--format json`, the checks that ran are summarized in one object;
+/// a failed check is reported as an error instead.
+pub fn cmd_verify(args: VerifyArgs, format: OutputFormat) -> Result<()> {
+    let json = format.is_json();
+    let file = &args.file;
+    let chain = ProvenanceChain::load_from_file(file)
         .with_context(|| format!("failed to load provenance chain from {}", file.display()))?;
+    let public_keys = args.keys.as_deref().map(load_public_keys).transpose()?;
+    let authorities = args
+        .tsa_keys
+        .as_deref()
+        .map(load_authority_keys)
+        .transpose()?;
+    let keyless_trust = args
+        .keyless_trust
+        .as_deref()
+        .map(load_keyless_trust)
+        .transpose()?;
+    let mut summary = json!({ "file": file, "entries": chain.len() });
+
+    // Write the full report before failing on the first error
+    if let Some(report_path) = &args.report {
+        let options = ReportOptions {
+            public_keys: public_keys.clone(),
+            required_signers: None,
+            authorities: authorities.clone(),
+            require_timestamps: args.require_timestamps,
+            keyless: keyless_trust.clone(),
+            require_keyless: args.require_keyless,
+        };
+        let report = VerificationReport::generate(&chain, &options);
+        let report_json = serde_json::to_string_pretty(&report)?;
+        fs::write(report_path, report_json)
+            .with_context(|| format!("failed to write {}", report_path.display()))?;
+        summary["report"] = json!(report_path);
+        if !json {
+            println!(
+                "{}: {} ({} of {} entries failed)",
+                "Report".bold(),
+                report_path.display(),
+                report.failed,
+                report.entries.len()
+            );
+        }
+    }
 
     // Verify Merkle chain structure
-    verify_chain(&chain).context("Merkle chain verification failed")?;
+    verify_chain(&chain)
+        .context("Merkle chain verification failed")
+        .map_err(|err| Failure::Check.wrap(err))?;
 
-    println!("{} Merkle chain structure valid", "✓".green().bold());
+    if !json {
+        println!("{} Merkle chain structure valid", "✓".green().bold());
+    }
 
-    // If public keys provided, verify signatures
-    if let Some(keys_path) = keys_file {
-        let keys_json = fs::read_to_string(&keys_path).context("failed to read keys file")?;
-
-        let keys_map: HashMap<String, String> =
-            serde_json::from_str(&keys_json).context("failed to parse keys JSON")?;
-
-        let mut public_keys = HashMap::new();
-        for (signer_id, hex_key) in keys_map {
-            let key_bytes = hex::decode(&hex_key)
-                .with_context(|| format!("invalid hex key for {signer_id}"))?;
-            if key_bytes.len() != 32 {
-                anyhow::bail!("public key for {signer_id} must be 32 bytes");
-            }
-            let mut key_array = [0u8; 32];
-            key_array.copy_from_slice(&key_bytes);
-            public_keys.insert(signer_id, key_array);
+    // If archived history is given, check it against the first checkpoint
+    if let Some(history_path) = &args.history {
+        let history = ProvenanceChain::load_from_file(history_path)
+            .with_context(|| format!("failed to load history from {}", history_path.display()))?;
+        let checkpoint = chain
+            .entries
+            .first()
+            .with_context(|| format!("chain {} is empty", file.display()))?;
+        verify_checkpoint(&history, checkpoint)
+            .with_context(|| {
+                format!(
+                    "history {} does not match checkpoint {}",
+                    history_path.display(),
+                    checkpoint.entry_id
+                )
+            })
+            .map_err(|err| Failure::Check.wrap(err))?;
+        summary["archived"] = json!(history.len());
+        if !json {
+            println!(
+                "{} {} archived entries match {}",
+                "✓".green().bold(),
+                history.len(),
+                checkpoint.entry_id
+            );
         }
+    }
 
-        verify_chain_signatures(&chain, &public_keys, None)
-            .context("signature verification failed")?;
+    // If public keys provided, verify signatures
+    if let Some(public_keys) = &public_keys {
+        verify_chain_signatures(&chain, public_keys, None)
+            .context("signature verification failed")
+            .map_err(|err| Failure::Check.wrap(err))?;
 
         let sig_count: usize = chain.entries.iter().map(|e| e.signatures.len()).sum();
-        println!("{} {} signatures verified", "✓".green().bold(), sig_count);
+        summary["signatures"] = json!(sig_count);
+        if !json {
+            println!("{} {} signatures verified", "✓".green().bold(), sig_count);
+        }
     }
 
+    // If authority keys provided, verify timestamp tokens
+    if let Some(authorities) = &authorities {
+        verify_chain_timestamps(&chain, authorities, args.require_timestamps)
+            .context("timestamp verification failed")
+            .map_err(|err| Failure::Check.wrap(err))?;
+
+        let stamp_count = chain
+            .entries
+            .iter()
+            .filter(|e| e.timestamp_token.is_some())
+            .count();
+        summary["timestamps"] = json!(stamp_count);
+        if !json {
+            println!("{} {} timestamps verified", "✓".green().bold(), stamp_count);
+        }
+    }
+
+    // If keyless trust is given, verify keyless signatures
+    if let Some(trust) = &keyless_trust {
+        verify_chain_keyless(&chain, trust, args.require_keyless)
+            .context("keyless signature verification failed")
+            .map_err(|err| Failure::Check.wrap(err))?;
+
+        let keyless_count: usize = chain.entries.iter().map(|e| e.keyless.len()).sum();
+        summary["keyless"] = json!(keyless_count);
+        if !json {
+            println!(
+                "{} {} keyless signatures verified",
+                "✓".green().bold(),
+                keyless_count
+            );
+        }
+    }
+
+    // If a workspace is given, check the recorded cells against its source
+    if let Some(dir) = &args.workspace {
+        let tree: HashMap<String, String> = workspace_cells(dir)?
+            .into_iter()
+            .map(|cell| (cell.module, cell.semantic))
+            .collect();
+        let report = verify_chain_sources(&chain, &tree, args.at.as_deref())
+            .with_context(|| format!("source verification against {} failed", dir.display()))
+            .map_err(|err| Failure::Check.wrap(err))?;
+
+        if !json {
+            println!(
+                "{} {} cells match {}",
+                "✓".green().bold(),
+                report.verified.len(),
+                dir.display()
+            );
+            if !report.unrecorded.is_empty() {
+                println!(
+                    "{} {} cells have no provenance: {}",
+                    "!".yellow().bold(),
+                    report.unrecorded.len(),
+                    report.unrecorded.join(", ")
+                );
+            }
+        }
+        summary["cells"] = json!({
+            "verified": report.verified,
+            "unrecorded": report.unrecorded,
+        });
+
+        if args.require_tests {
+            let untested = untested_cells(&chain, &tree);
+            if !untested.is_empty() {
+                return Err(Failure::Check.error(format!(
+                    "{} cells have no passing test run: {}",
+                    untested.len(),
+                    untested.join(", ")
+                )));
+            }
+            summary["cells"]["tested"] = json!(tree.len());
+            if !json {
+                println!(
+                    "{} {} cells passed their tests",
+                    "✓".green().bold(),
+                    tree.len()
+                );
+            }
+        }
+    }
+
+    if json {
+        summary["valid"] = json!(true);
+        return print_json(&summary);
+    }
     println!();
     println!("{}", "Summary:".bold().underline());
     println!("  {}: {}", "Entries".bold(), chain.len());
@@ -136,7 +779,37 @@ pub fn cmd_verify(file: PathBuf, keys_file: Option<PathBuf>) -> Result<()> {
 }
 
 /// Handle the z1prov keygen command.
-pub fn cmd_keygen(output: Option<PathBuf>) -> Result<()> {
+pub fn cmd_keygen(
+    output: Option<PathBuf>,
+    name: Option<String>,
+    keystore: KeystoreArgs,
+    format: OutputFormat,
+) -> Result<()> {
+    if let Some(name) = name {
+        let keystore = keystore.open()?;
+        let passphrase = read_passphrase(&format!("New passphrase for key `{name}`: "), true)?;
+        let key = keystore.generate(&name, &passphrase)?;
+        if format.is_json() {
+            return print_json(&json!({
+                "name": key.name,
+                "public_key": hex::encode(key.public_key),
+                "keystore": keystore.dir(),
+            }));
+        }
+        println!(
+            "{} Key {} stored in {}",
+            "✓".green(),
+            key.name.bold(),
+            keystore.dir().display()
+        );
+        println!(
+            "{}: {}",
+            "Public Key".bold().green(),
+            hex::encode(key.public_key)
+        );
+        return Ok(());
+    }
+
     let (private_key, public_key) = keygen();
 
     let private_hex = hex::encode(private_key);
@@ -150,7 +823,12 @@ pub fn cmd_keygen(output: Option<PathBuf>) -> Result<()> {
         let json = serde_json::to_string_pretty(&keypair)?;
         fs::write(&path, json)
             .with_context(|| format!("failed to write keypair to {}", path.display()))?;
+        if format.is_json() {
+            return print_json(&json!({ "path": path, "public_key": public_hex }));
+        }
         println!("{} Keypair written to {}", "✓".green(), path.display());
+    } else if format.is_json() {
+        return print_json(&json!({ "private_key": private_hex, "public_key": public_hex }));
     } else {
         println!("{}", "Generated Ed25519 Keypair".bold().underline());
         println!("{}: {}", "Private Key".bold().red(), private_hex);
@@ -164,3 +842,677 @@ pub fn cmd_keygen(output: Option<PathBuf>) -> Result<()> {
 
     Ok(())
 }
+
+/// Handle the z1prov append command.
+pub fn cmd_append(args: AppendArgs, format: OutputFormat) -> Result<()> {
+    if args.prompt_file.as_deref() == Some(Path::new("-")) && args.diff == Path::new("-") {
+        bail!("--prompt-file and --diff cannot both read stdin");
+    }
+    let mut chain = load_or_new(&args.file)?;
+
+    let prompt = match (&args.prompt, &args.prompt_file) {
+        (Some(text), _) => text.clone().into_bytes(),
+        (None, Some(path)) => read_input(path)?,
+        (None, None) => Vec::new(),
+    };
+    let diff = read_input(&args.diff)?;
+    let redaction = args.redaction.policy()?;
+    let prompt_excerpt = redaction
+        .clone()
+        .unwrap_or_default()
+        .excerpt(&String::from_utf8_lossy(&prompt))?;
+    let cells = args
+        .cells
+        .iter()
+        .map(|path| cell_binding(path))
+        .collect::<Result<Vec<_>>>()?;
+
+    let entry = ProvenanceEntry {
+        entry_id: args.entry_id,
+        prev: None,
+        merged: vec![],
+        actor: args.actor,
+        model: args.model,
+        prompt_sha3: hash_bytes(&prompt, HashAlgo::Sha3_256),
+        prompt_excerpt,
+        redaction,
+        tools: args.tools,
+        diff_sha3: hash_bytes(&diff, HashAlgo::Sha3_256),
+        cells,
+        timestamp: Utc::now(),
+        signatures: vec![],
+        keyless: vec![],
+        rotation: None,
+        checkpoint: None,
+        timestamp_token: None,
+    };
+    let entry_id = entry.entry_id.clone();
+    let hash = chain.append(entry).context("invalid provenance entry")?;
+    chain
+        .save_to_file(&args.file)
+        .with_context(|| format!("failed to write {}", args.file.display()))?;
+
+    if format.is_json() {
+        return print_json(&json!({
+            "entry_id": entry_id,
+            "index": chain.len(),
+            "hash": hash,
+        }));
+    }
+    println!(
+        "{} Appended {} as entry {}",
+        "✓".green(),
+        entry_id,
+        chain.len()
+    );
+    println!("{}: {}", "Entry Hash".bold(), hash);
+    Ok(())
+}
+
+/// Handle the z1prov sign command.
+pub fn cmd_sign(args: SignArgs, format: OutputFormat) -> Result<()> {
+    let mut chain = ProvenanceChain::load_from_file(&args.file).with_context(|| {
+        format!(
+            "failed to load provenance chain from {}",
+            args.file.display()
+        )
+    })?;
+    let signing_key = args
+        .key
+        .signer()?
+        .context("no signing key: pass --key, --key-name, --pkcs11-module or --sign-command")?;
+
+    let index = match &args.entry_id {
+        Some(id) => chain
+            .entries
+            .iter()
+            .position(|entry| &entry.entry_id == id)
+            .with_context(|| format!("no entry `{id}` in {}", args.file.display()))?,
+        None => match chain.len() {
+            0 => bail!("chain {} is empty", args.file.display()),
+            len => len - 1,
+        },
+    };
+    if index + 1 != chain.len() {
+        bail!(
+            "entry `{}` already has successors; only the latest entry can be signed",
+            chain.entries[index].entry_id
+        );
+    }
+
+    let entry_id = sign_latest(&mut chain, signing_key.as_ref(), &args.signer)?;
+    chain
+        .save_to_file(&args.file)
+        .with_context(|| format!("failed to write {}", args.file.display()))?;
+
+    if format.is_json() {
+        return print_json(&json!({ "entry_id": entry_id, "signer": args.signer }));
+    }
+    println!(
+        "{} Signed {} as {}",
+        "✓".green(),
+        entry_id,
+        args.signer.green()
+    );
+    Ok(())
+}
+
+/// Handle the z1prov keys command.
+pub fn cmd_keys(keystore: KeystoreArgs, format: OutputFormat) -> Result<()> {
+    let keystore = keystore.open()?;
+    let keys = keystore.list()?;
+    if format.is_json() {
+        let keys: Vec<_> = keys
+            .iter()
+            .map(|key| {
+                json!({
+                    "name": key.name,
+                    "public_key": hex::encode(key.public_key),
+                    "created": key.created,
+                })
+            })
+            .collect();
+        return print_json(&keys);
+    }
+    if keys.is_empty() {
+        println!("{}", "Keystore is empty".yellow());
+        return Ok(());
+    }
+    for key in keys {
+        println!(
+            "{}  {}  {}",
+            key.name.bold(),
+            hex::encode(key.public_key),
+            key.created.format("%Y-%m-%d")
+        );
+    }
+    Ok(())
+}
+
+/// Handle the z1prov rotate command.
+///
+/// Appends a rotation entry endorsed and signed by the signer's current key;
+/// verifiers expect the signer's later entries to be signed by the new key.
+pub fn cmd_rotate(args: RotateArgs, format: OutputFormat) -> Result<()> {
+    let keystore = args.keystore.open()?;
+    let new_key = keystore.get(&args.to)?;
+    let passphrase = read_passphrase(&format!("Passphrase for key `{}`: ", args.from), false)?;
+    let old_private = keystore.unlock(&args.from, &passphrase)?;
+
+    let mut chain = load_or_new(&args.file)?;
+    let rotation = endorse_rotation(&args.signer, &old_private, &new_key.public_key);
+    let description = format!(
+        "rotate {} key {} -> {}",
+        args.signer, rotation.old_key, rotation.new_key
+    );
+    let entry = ProvenanceEntry {
+        entry_id: format!("key-rotation:{}@{}", args.signer, &rotation.new_key[..16]),
+        prev: None,
+        merged: vec![],
+        actor: args.signer.clone(),
+        model: "none".to_string(),
+        prompt_sha3: hash_bytes(description.as_bytes(), HashAlgo::Sha3_256),
+        prompt_excerpt: excerpt(&description),
+        redaction: None,
+        tools: vec![],
+        diff_sha3: hash_bytes(&new_key.public_key, HashAlgo::Sha3_256),
+        cells: vec![],
+        timestamp: Utc::now(),
+        signatures: vec![],
+        keyless: vec![],
+        rotation: Some(rotation),
+        checkpoint: None,
+        timestamp_token: None,
+    };
+    chain.append(entry).context("invalid provenance entry")?;
+    let entry_id = sign_latest(&mut chain, &SoftwareSigner::new(&old_private), &args.signer)?;
+    chain
+        .save_to_file(&args.file)
+        .with_context(|| format!("failed to write {}", args.file.display()))?;
+
+    if format.is_json() {
+        return print_json(&json!({
+            "entry_id": entry_id,
+            "signer": args.signer,
+            "from": args.from,
+            "to": args.to,
+        }));
+    }
+    println!(
+        "{} Rotated {} from {} to {}",
+        "✓".green(),
+        args.signer.green(),
+        args.from,
+        args.to.bold()
+    );
+    Ok(())
+}
+
+/// Handle the z1prov sign-keyless command.
+///
+/// The CA and log are local keys here, e.g. a CI service's own authority;
+/// the ephemeral signing key never leaves this process.
+pub fn cmd_sign_keyless(args: SignKeylessArgs, format: OutputFormat) -> Result<()> {
+    let mut chain = ProvenanceChain::load_from_file(&args.file).with_context(|| {
+        format!(
+            "failed to load provenance chain from {}",
+            args.file.display()
+        )
+    })?;
+    let (ca, identity): (Box<dyn CertificateAuthority>, Identity) = match &args.fulcio {
+        Some(url) => {
+            let fulcio = FulcioAuthority::new(url, args.oidc_token.unwrap_or_default());
+            let identity = fulcio.identity()?;
+            (Box::new(fulcio), identity)
+        }
+        None => {
+            let ca_key = args
+                .ca_key
+                .load()?
+                .context("no certificate authority key: pass --key or --key-name")?;
+            let identity = Identity {
+                subject: args.identity.unwrap_or_default(),
+                issuer: args.oidc_issuer.unwrap_or_default(),
+            };
+            (
+                Box::new(LocalCertificateAuthority::root(&args.ca, &ca_key)),
+                identity,
+            )
+        }
+    };
+    let (log, log_name): (Box<dyn TransparencyLog>, String) = match (&args.rekor, &args.log_key) {
+        (Some(url), _) => (Box::new(RekorLog::new(url)), url.clone()),
+        (None, Some(log_key)) => {
+            let logged: usize = chain.entries.iter().map(|e| e.keyless.len()).sum();
+            let log = LocalTransparencyLog::new(&args.log, &load_private_key(log_key)?)
+                .with_next_index(logged as u64);
+            (Box::new(log), args.log.clone())
+        }
+        (None, None) => bail!("no transparency log: pass --log-key or --rekor"),
+    };
+
+    // Keyless signatures are part of the hash the next entry links to
+    let entry = chain
+        .entries
+        .last_mut()
+        .with_context(|| format!("chain {} is empty", args.file.display()))?;
+    let signature = sign_keyless(entry, &identity, ca.as_ref(), log.as_ref())?;
+    let index = signature.log_entry.index;
+    entry.keyless.push(signature);
+    let entry_id = entry.entry_id.clone();
+    chain.update_merkle_root();
+    chain
+        .save_to_file(&args.file)
+        .with_context(|| format!("failed to write {}", args.file.display()))?;
+
+    if format.is_json() {
+        return print_json(&json!({
+            "entry_id": entry_id,
+            "identity": identity.subject,
+            "log": log_name,
+            "index": index,
+        }));
+    }
+    println!(
+        "{} Signed {} as {} (logged as {}#{})",
+        "✓".green(),
+        entry_id,
+        identity.subject.green(),
+        log_name,
+        index
+    );
+    Ok(())
+}
+
+/// Handle the z1prov stamp command.
+pub fn cmd_stamp(args: StampArgs, format: OutputFormat) -> Result<()> {
+    let mut chain = ProvenanceChain::load_from_file(&args.file).with_context(|| {
+        format!(
+            "failed to load provenance chain from {}",
+            args.file.display()
+        )
+    })?;
+    let authority: Box<dyn TimestampAuthority> = match &args.tsa_url {
+        Some(url) => {
+            let service = Rfc3161Authority::new(url);
+            match &args.authority {
+                Some(id) => Box::new(service.with_id(id)),
+                None => Box::new(service),
+            }
+        }
+        None => {
+            let private_key = args
+                .key
+                .load()?
+                .context("no authority key: pass --tsa-url, --key or --key-name")?;
+            Box::new(LocalAuthority::new(
+                args.authority.as_deref().unwrap_or_default(),
+                &private_key,
+            ))
+        }
+    };
+    let authority_id = authority.id().to_string();
+
+    // Like signatures, the token is part of the hash the next entry links to
+    let entry = chain
+        .entries
+        .last_mut()
+        .with_context(|| format!("chain {} is empty", args.file.display()))?;
+    timestamp_entry(entry, authority.as_ref())?;
+    let entry_id = entry.entry_id.clone();
+    let time = entry
+        .timestamp_token
+        .as_ref()
+        .map(|token| token.time)
+        .expect("token was just attached");
+    chain.update_merkle_root();
+    chain
+        .save_to_file(&args.file)
+        .with_context(|| format!("failed to write {}", args.file.display()))?;
+
+    if format.is_json() {
+        return print_json(&json!({
+            "entry_id": entry_id,
+            "authority": authority_id,
+            "time": time,
+        }));
+    }
+    println!(
+        "{} Stamped {} by {} at {}",
+        "✓".green(),
+        entry_id,
+        authority_id.green(),
+        time
+    );
+    Ok(())
+}
+
+/// Handle the z1prov merge command.
+///
+/// When both chains have new entries, a merge entry joining their heads is
+/// appended so the chain has a single head again.
+pub fn cmd_merge(args: MergeArgs, format: OutputFormat) -> Result<()> {
+    let mut chain = load_or_new(&args.file)?;
+    let theirs = ProvenanceChain::load_from_file(&args.theirs).with_context(|| {
+        format!(
+            "failed to load provenance chain from {}",
+            args.theirs.display()
+        )
+    })?;
+    let signing_key = args.key.signer()?;
+    if signing_key.is_some() && args.signer.is_none() {
+        bail!("signing the merge entry requires --as <signer>");
+    }
+
+    let outcome = merge_chains(&mut chain, &theirs).with_context(|| {
+        format!(
+            "cannot merge {} into {}",
+            args.theirs.display(),
+            args.file.display()
+        )
+    })?;
+    let json = format.is_json();
+    let mut summary = json!({ "outcome": "up_to_date", "appended": 0 });
+    match outcome {
+        MergeOutcome::UpToDate if json => {
+            summary["merkle_root"] = json!(chain.merkle_root);
+            return print_json(&summary);
+        }
+        MergeOutcome::UpToDate => {
+            println!("{} Already up to date", "✓".green());
+            return Ok(());
+        }
+        MergeOutcome::FastForward(appended) => {
+            summary = json!({ "outcome": "fast_forward", "appended": appended });
+            if !json {
+                println!("{} Fast-forwarded {} entries", "✓".green(), appended);
+            }
+        }
+        MergeOutcome::Diverged(appended) => {
+            let description = format!(
+                "merge {} into {}",
+                args.theirs.display(),
+                args.file.display()
+            );
+            let entry_id = args.entry_id.unwrap_or_else(|| {
+                let latest = theirs.latest().expect("diverged chains are non-empty");
+                format!("merge:{}", latest.entry_id)
+            });
+            let entry = ProvenanceEntry {
+                entry_id: entry_id.clone(),
+                prev: None,
+                merged: vec![],
+                actor: args.actor,
+                model: "none".to_string(),
+                prompt_sha3: hash_bytes(description.as_bytes(), HashAlgo::Sha3_256),
+                prompt_excerpt: excerpt(&description),
+                redaction: None,
+                tools: vec![],
+                diff_sha3: hash_bytes(chain.heads().join("\n").as_bytes(), HashAlgo::Sha3_256),
+                cells: vec![],
+                timestamp: Utc::now(),
+                signatures: vec![],
+                keyless: vec![],
+                rotation: None,
+                checkpoint: None,
+                timestamp_token: None,
+            };
+            chain.append(entry).context("invalid provenance entry")?;
+            if let (Some(key), Some(signer)) = (&signing_key, &args.signer) {
+                sign_latest(&mut chain, key.as_ref(), signer)?;
+            }
+            if !json {
+                println!(
+                    "{} Merged {} entries from {} as {}",
+                    "✓".green(),
+                    appended,
+                    args.theirs.display(),
+                    entry_id
+                );
+            }
+            summary = json!({ "outcome": "merged", "appended": appended, "entry_id": entry_id });
+        }
+    }
+
+    chain
+        .save_to_file(&args.file)
+        .with_context(|| format!("failed to write {}", args.file.display()))?;
+    if json {
+        summary["merkle_root"] = json!(chain.merkle_root);
+        return print_json(&summary);
+    }
+    println!("{}: {}", "Merkle Root".bold(), chain.merkle_root);
+    Ok(())
+}
+
+/// Handle the z1prov compact command.
+///
+/// Appends a checkpoint entry, then drops every entry before it. Verifiers of
+/// the compacted chain trust the rotated keys the checkpoint records; the
+/// archive ties them back to the original history.
+pub fn cmd_compact(args: CompactArgs, format: OutputFormat) -> Result<()> {
+    let mut chain = ProvenanceChain::load_from_file(&args.file).with_context(|| {
+        format!(
+            "failed to load provenance chain from {}",
+            args.file.display()
+        )
+    })?;
+    if chain.is_empty() {
+        bail!("chain {} is empty", args.file.display());
+    }
+    if let Some(archive) = &args.archive {
+        if archive.exists() {
+            bail!("archive {} already exists", archive.display());
+        }
+    }
+    let signing_key = args.key.signer()?;
+    if signing_key.is_some() && args.signer.is_none() {
+        bail!("signing the checkpoint entry requires --as <signer>");
+    }
+
+    let root = chain.compute_merkle_root();
+    let description = format!("checkpoint of {} entries", chain.len());
+    let entry = ProvenanceEntry {
+        entry_id: args
+            .entry_id
+            .unwrap_or_else(|| format!("checkpoint:{}", &root[..16])),
+        prev: None,
+        merged: vec![],
+        actor: args.actor,
+        model: "none".to_string(),
+        prompt_sha3: hash_bytes(description.as_bytes(), HashAlgo::Sha3_256),
+        prompt_excerpt: excerpt(&description),
+        redaction: None,
+        tools: vec![],
+        diff_sha3: hash_bytes(root.as_bytes(), HashAlgo::Sha3_256),
+        cells: vec![],
+        timestamp: Utc::now(),
+        signatures: vec![],
+        keyless: vec![],
+        rotation: None,
+        checkpoint: None,
+        timestamp_token: None,
+    };
+    append_checkpoint(&mut chain, entry).context("cannot checkpoint the chain")?;
+    if let (Some(key), Some(signer)) = (&signing_key, &args.signer) {
+        sign_latest(&mut chain, key.as_ref(), signer)?;
+    }
+    let history = compact(&mut chain).context("cannot compact the chain")?;
+
+    if let Some(archive) = &args.archive {
+        history
+            .save_to_file(archive)
+            .with_context(|| format!("failed to write {}", archive.display()))?;
+    }
+    chain
+        .save_to_file(&args.file)
+        .with_context(|| format!("failed to write {}", args.file.display()))?;
+
+    if format.is_json() {
+        return print_json(&json!({
+            "entry_id": chain.entries[0].entry_id,
+            "compacted": history.len(),
+            "archive": args.archive,
+            "merkle_root": chain.merkle_root,
+        }));
+    }
+    println!(
+        "{} Compacted {} entries into {}",
+        "✓".green(),
+        history.len(),
+        chain.entries[0].entry_id
+    );
+    if let Some(archive) = &args.archive {
+        println!("{}: {}", "Archive".bold(), archive.display());
+    }
+    println!("{}: {}", "Merkle Root".bold(), chain.merkle_root);
+    Ok(())
+}
+
+/// Sign the latest entry of `chain` as `signer`, replacing any earlier
+/// signature by the same signer, and return the entry ID.
+///
+/// Only the latest entry may be signed: signatures are part of the hash the
+/// next entry's `prev` links to.
+pub(crate) fn sign_latest(
+    chain: &mut ProvenanceChain,
+    signing_key: &dyn EntrySigner,
+    signer: &str,
+) -> Result<String> {
+    let entry = chain
+        .entries
+        .last_mut()
+        .expect("sign_latest requires a non-empty chain");
+    let signature = sign_entry_with(entry, signing_key, signer)
+        .with_context(|| format!("failed to sign {} as {signer}", entry.entry_id))?;
+    entry.signatures.retain(|sig| sig.by != signer);
+    entry.signatures.push(signature);
+    let entry_id = entry.entry_id.clone();
+    chain.update_merkle_root();
+    Ok(entry_id)
+}
+
+pub(crate) fn load_or_new(path: &Path) -> Result<ProvenanceChain> {
+    if !path.exists() {
+        return Ok(ProvenanceChain::new());
+    }
+    ProvenanceChain::load_from_file(path)
+        .with_context(|| format!("failed to load provenance chain from {}", path.display()))
+}
+
+/// Module path and semantic hash of the cell at `path`.
+pub(crate) fn cell_binding(path: &Path) -> Result<CellBinding> {
+    let source =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let module = z1_parse::parse_module(&source)
+        .map_err(|err| anyhow::anyhow!("{}: {err}", path.display()))?;
+    Ok(CellBinding {
+        module: module.path.to_string(),
+        semhash: module_hashes(&module).semantic,
+    })
+}
+
+/// Parse a `--since`/`--until` value: a date (midnight UTC) or an RFC 3339 time.
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|date| date.and_time(Default::default()).and_utc())
+        .map_err(|_| format!("expected YYYY-MM-DD or an RFC 3339 time, got `{value}`"))
+}
+
+/// Keystore passphrase from `Z1_KEY_PASSPHRASE`, or prompted on the terminal.
+fn read_passphrase(prompt: &str, confirm: bool) -> Result<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    let passphrase = rpassword::prompt_password(prompt).context("failed to read passphrase")?;
+    if confirm && rpassword::prompt_password("Repeat passphrase: ")? != passphrase {
+        bail!("passphrases do not match");
+    }
+    Ok(passphrase)
+}
+
+/// Contents of `path`, or stdin when `path` is `-`.
+fn read_input(path: &Path) -> Result<Vec<u8>> {
+    if path == Path::new("-") {
+        let mut bytes = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut bytes)
+            .context("failed to read stdin")?;
+        return Ok(bytes);
+    }
+    fs::read(path).with_context(|| format!("failed to read {}", path.display()))
+}
+
+/// Excerpt of a tool-generated description under the default policy.
+fn excerpt(description: &str) -> String {
+    RedactionPolicy::default()
+        .excerpt(description)
+        .expect("the default policy has no patterns")
+}
+
+/// Public keys from a JSON file mapping identifiers to hex-encoded keys.
+fn load_keyless_trust(path: &Path) -> Result<KeylessTrust> {
+    let json =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_str(&json)
+        .with_context(|| format!("invalid keyless trust file {}", path.display()))
+}
+
+fn load_public_keys(path: &Path) -> Result<HashMap<String, [u8; 32]>> {
+    let keys_json = fs::read_to_string(path).context("failed to read keys file")?;
+    let keys_map: HashMap<String, String> =
+        serde_json::from_str(&keys_json).context("failed to parse keys JSON")?;
+
+    let mut public_keys = HashMap::new();
+    for (id, hex_key) in keys_map {
+        let key_bytes =
+            hex::decode(&hex_key).with_context(|| format!("invalid hex key for {id}"))?;
+        let key_array: [u8; 32] = key_bytes
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("public key for {id} must be 32 bytes"))?;
+        public_keys.insert(id, key_array);
+    }
+    Ok(public_keys)
+}
+
+/// Timestamp authority keys: hex Ed25519 public keys of local authorities,
+/// or PEM root certificates of RFC 3161 services.
+fn load_authority_keys(path: &Path) -> Result<HashMap<String, AuthorityKey>> {
+    let keys_json = fs::read_to_string(path).context("failed to read keys file")?;
+    let keys_map: HashMap<String, String> =
+        serde_json::from_str(&keys_json).context("failed to parse keys JSON")?;
+
+    let mut authorities = HashMap::new();
+    for (id, key) in keys_map {
+        let key = if key.contains("-----BEGIN CERTIFICATE-----") {
+            AuthorityKey::X509(key)
+        } else {
+            let key_bytes =
+                hex::decode(&key).with_context(|| format!("invalid hex key for {id}"))?;
+            let key_array: [u8; 32] = key_bytes
+                .try_into()
+                .map_err(|_| anyhow::anyhow!("public key for {id} must be 32 bytes"))?;
+            AuthorityKey::Ed25519(key_array)
+        };
+        authorities.insert(id, key);
+    }
+    Ok(authorities)
+}
+
+/// Private key from a keypair file written by `z1 prov keygen`.
+fn load_private_key(path: &Path) -> Result<[u8; 32]> {
+    let json = fs::read_to_string(path)
+        .with_context(|| format!("failed to read keypair from {}", path.display()))?;
+    let keypair: serde_json::Value =
+        serde_json::from_str(&json).context("failed to parse keypair JSON")?;
+    let hex_key = keypair["private_key"]
+        .as_str()
+        .context("keypair has no private_key")?;
+    let bytes = hex::decode(hex_key).context("invalid hex private key")?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("private key must be 32 bytes"))
+}
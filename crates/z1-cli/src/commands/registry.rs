@@ -0,0 +1,212 @@
+//! `z1 add` and `z1 publish`: sharing cells through a package registry.
+//!
+//! `publish` uploads the cells under a project's source roots as the version
+//! in its `z1.toml`. `add` fetches a package into `.z1/packages`, adds it to
+//! `[dependencies]` and pins it in `z1.lock`; run without a package, it
+//! installs everything `z1.lock` pins, failing if the registry no longer
+//! matches the pinned digests.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use semver::Version;
+use serde_json::json;
+use z1_prov::{ProvenanceChain, ProvenanceChainExt};
+use z1_registry::{Lockfile, Package, PackageRecord, Registry, LOCK_FILE};
+use z1_resolve::{Project, MANIFEST_FILE, PACKAGES_DIR};
+
+use crate::commands::cells::{collect_cells, relative};
+use crate::commands::compile::CheckContext;
+use crate::commands::lint::lint_source;
+use crate::diagnostics::DiagnosticLevel;
+use crate::output::{print_json, OutputFormat};
+
+#[derive(Debug, Args)]
+pub struct AddArgs {
+    /// Package as `name` (latest release) or `name@version`; omit to install
+    /// every package pinned in z1.lock.
+    pub package: Option<String>,
+    /// Registry directory
+    #[arg(long, env = "Z1_REGISTRY")]
+    pub registry: PathBuf,
+    /// Project directory (default: the nearest directory holding z1.toml)
+    #[arg(long)]
+    pub dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct PublishArgs {
+    /// Registry directory
+    #[arg(long, env = "Z1_REGISTRY")]
+    pub registry: PathBuf,
+    /// Provenance chain to publish with the package; it must record the
+    /// published cells.
+    #[arg(long)]
+    pub prov: Option<PathBuf>,
+    /// Project directory (default: the nearest directory holding z1.toml)
+    #[arg(long)]
+    pub dir: Option<PathBuf>,
+}
+
+pub fn run_add(args: AddArgs, format: OutputFormat) -> Result<()> {
+    format.require("add", &[OutputFormat::Json])?;
+    let project = find_project(args.dir)?;
+    let registry = Registry::open(&args.registry);
+    let lock_path = project.root.join(LOCK_FILE);
+    let mut lock = Lockfile::load(&lock_path)?;
+
+    let Some(spec) = args.package else {
+        let mut installed = Vec::new();
+        for locked in &lock.packages {
+            let record = registry.record(&locked.name, &parse_version(&locked.version)?)?;
+            lock.check(&record)?;
+            install(&registry, &record, &project.root)?;
+            installed.push(record);
+        }
+        if format.is_json() {
+            let installed: Vec<_> = installed.iter().map(summary).collect();
+            return print_json(&json!({ "installed": installed }));
+        }
+        if installed.is_empty() {
+            println!("No packages are pinned in {LOCK_FILE}");
+        }
+        for record in &installed {
+            println!("Installed {} {}", record.name, record.version);
+        }
+        return Ok(());
+    };
+
+    let (name, version) = match spec.split_once('@') {
+        Some((name, version)) => (name, parse_version(version)?),
+        None => (spec.as_str(), registry.latest(&spec)?),
+    };
+    let record = registry.record(name, &version)?;
+    lock.check(&record)?;
+    install(&registry, &record, &project.root)?;
+    z1_resolve::set_registry_dependency(
+        &project.root.join(MANIFEST_FILE),
+        &record.name,
+        &record.version,
+    )?;
+    lock.pin(&record);
+    lock.save(&lock_path)?;
+
+    if format.is_json() {
+        return print_json(&summary(&record));
+    }
+    println!(
+        "Added {} {} ({} cell(s){})",
+        record.name,
+        record.version,
+        record.cells.len(),
+        if record.provenance.is_some() {
+            ", provenance verified"
+        } else {
+            ""
+        }
+    );
+    Ok(())
+}
+
+pub fn run_publish(args: PublishArgs, format: OutputFormat) -> Result<()> {
+    format.require("publish", &[OutputFormat::Json])?;
+    let project = find_project(args.dir)?;
+    let info = &project.manifest.project;
+    let version = parse_version(&info.version)?;
+
+    let mut cells: Vec<(String, z1_ast::Module)> = Vec::new();
+    let mut failing = 0;
+    for root in project.source_roots() {
+        for path in collect_cells(&root)? {
+            // A cell kept in both forms is published once, from the compact one
+            if path.extension().is_some_and(|ext| ext == "z1r")
+                && path.with_extension("z1c").is_file()
+            {
+                continue;
+            }
+            let source = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let display = path.display().to_string();
+            let context = CheckContext::for_cell(&path)?;
+            if lint_source(&source, &display, &context)
+                .iter()
+                .any(|diagnostic| diagnostic.level == DiagnosticLevel::Error)
+            {
+                failing += 1;
+                eprintln!("✗ {display} fails checks");
+                continue;
+            }
+            let module = z1_parse::parse_module(&source)
+                .map_err(|err| anyhow::anyhow!("{display}: {err}"))?;
+            cells.push((relative(&root, &path), module));
+        }
+    }
+    if failing > 0 {
+        bail!("{failing} cell(s) fail checks; run `z1 lint` for details");
+    }
+    if cells.is_empty() {
+        bail!("{} has no cells to publish", project.root.display());
+    }
+
+    let provenance = match &args.prov {
+        Some(path) => Some(
+            ProvenanceChain::load_from_file(path)
+                .with_context(|| format!("Failed to load {}", path.display()))?,
+        ),
+        None => None,
+    };
+    let record = Registry::open(&args.registry).publish(&Package {
+        name: info.name.clone(),
+        version,
+        cells,
+        provenance,
+    })?;
+
+    if format.is_json() {
+        return print_json(&summary(&record));
+    }
+    println!(
+        "Published {} {} ({} cell(s)) to {}",
+        record.name,
+        record.version,
+        record.cells.len(),
+        args.registry.display()
+    );
+    println!("  digest {}", record.digest);
+    Ok(())
+}
+
+fn find_project(dir: Option<PathBuf>) -> Result<Project> {
+    let start = match dir {
+        Some(dir) => dir,
+        None => std::env::current_dir()?,
+    };
+    Project::discover(&start)?
+        .with_context(|| format!("no {MANIFEST_FILE} found in {} or above", start.display()))
+}
+
+fn parse_version(version: &str) -> Result<Version> {
+    Version::parse(version).with_context(|| format!("invalid version `{version}`"))
+}
+
+/// Fetch `record` into the project's package directory.
+fn install(registry: &Registry, record: &PackageRecord, root: &Path) -> Result<()> {
+    let dest = root
+        .join(PACKAGES_DIR)
+        .join(&record.name)
+        .join(&record.version);
+    registry
+        .fetch(record, &dest)
+        .with_context(|| format!("Failed to fetch {} {}", record.name, record.version))
+}
+
+fn summary(record: &PackageRecord) -> serde_json::Value {
+    json!({
+        "name": record.name,
+        "version": record.version,
+        "digest": record.digest,
+        "cells": record.cells.len(),
+        "provenance": record.provenance.is_some(),
+    })
+}
@@ -0,0 +1,96 @@
+//! `z1 rename`: rename an identifier throughout a cell.
+//!
+//! The rename comes from [`z1_refactor::rename_source`]: the declaration
+//! the name resolves to, its mentions in signatures and bodies, and the
+//! `#sym` table are renamed at once, and nothing else in the cell changes,
+//! not even fields or locals that share the name. A rename that would clash
+//! with a field, local or other name already in use, or that names
+//! something the cell does not declare, fails without touching the file.
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueHint};
+use serde::Serialize;
+use z1_refactor::RenameError;
+
+use crate::commands::fix::unified_diff;
+use crate::commands::lint::display_path;
+use crate::exit::Failure;
+use crate::output::{self, print_json, OutputFormat};
+
+#[derive(Debug, Args)]
+pub struct RenameArgs {
+    /// Cell to rename in.
+    #[arg(value_name = "CELL", value_hint = ValueHint::FilePath)]
+    pub cell: PathBuf,
+    /// Identifier to rename: a function, type, field, parameter, local,
+    /// import alias or symbol map name the cell declares. A name declared
+    /// more than once resolves to a function, type or alias first, then to
+    /// a field, then to parameters and locals.
+    pub old: String,
+    /// New name.
+    pub new: String,
+    /// Print the change as a unified diff instead of writing it.
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct RenameReport {
+    cell: String,
+    old: String,
+    new: String,
+    /// Identifiers renamed
+    count: usize,
+    written: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diff: Option<String>,
+}
+
+pub fn run(args: RenameArgs, format: OutputFormat) -> Result<()> {
+    format.require("rename", &[OutputFormat::Json])?;
+    let cell = &args.cell;
+    let path = display_path(cell);
+    let source =
+        fs::read_to_string(cell).with_context(|| format!("Failed to read {}", cell.display()))?;
+    let renamed =
+        z1_refactor::rename_source(&source, &args.old, &args.new).map_err(|err| match err {
+            RenameError::Parse(_) => Failure::Parse.error(format!("{path}: {err}")),
+            err => Failure::Usage.error(format!("{path}: {err}")),
+        })?;
+
+    let diff = unified_diff(&path, &source, &renamed.source);
+    if !args.dry_run {
+        fs::write(cell, &renamed.source)
+            .with_context(|| format!("Failed to write {}", cell.display()))?;
+    }
+    let report = RenameReport {
+        cell: path,
+        old: args.old,
+        new: args.new,
+        count: renamed.count,
+        written: !args.dry_run,
+        diff: args.dry_run.then_some(diff),
+    };
+
+    if format.is_json() {
+        return print_json(&report);
+    }
+    if let Some(diff) = &report.diff {
+        print!("{diff}");
+    }
+    if output::show_summary() {
+        let verb = if args.dry_run {
+            "Would rename"
+        } else {
+            "Renamed"
+        };
+        eprintln!(
+            "{verb} `{}` to `{}` in {}: {} occurrence(s)",
+            report.old, report.new, report.cell, report.count
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,70 @@
+//! `z1 run`: evaluate a cell with the reference interpreter.
+
+use std::fs;
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, ValueHint};
+use z1_eval::{EvalOptions, Interpreter, NoHost, Value};
+
+use crate::error_printer;
+
+#[derive(Debug, Args)]
+pub struct RunArgs {
+    /// Path to the Z1 cell to run.
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub path: String,
+    /// Function to call.
+    #[arg(long, default_value = "main")]
+    pub entry: String,
+    /// Arguments for the entry function, parsed by parameter type.
+    #[arg(value_name = "ARG", num_args = 0.., allow_hyphen_values = true)]
+    pub args: Vec<String>,
+    /// Stop after this many statements and loop iterations.
+    #[arg(long)]
+    pub max_steps: Option<u64>,
+}
+
+pub fn run(args: RunArgs) -> Result<()> {
+    let source =
+        fs::read_to_string(&args.path).with_context(|| format!("Failed to read {}", args.path))?;
+    let module = z1_parse::parse_module(&source).map_err(|e| {
+        let config = error_printer::ErrorPrinterConfig::default();
+        error_printer::print_parse_error(&e, &source, &args.path, &config);
+        anyhow::anyhow!("Parse failed")
+    })?;
+    let ir_module = z1_ir::lower_to_ir(&module).context("IR generation failed")?;
+
+    let Some(entry) = ir_module.functions.iter().find(|f| f.name == args.entry) else {
+        bail!("No function `{}` in {}", args.entry, args.path);
+    };
+    if entry.params.len() != args.args.len() {
+        bail!(
+            "`{}` takes {} argument(s), got {}",
+            entry.name,
+            entry.params.len(),
+            args.args.len()
+        );
+    }
+
+    // The CLI provides no effect handlers: host calls fail with a clear error
+    let mut host = NoHost;
+    let options = EvalOptions {
+        max_steps: args.max_steps,
+        ..EvalOptions::default()
+    };
+    let mut interpreter = Interpreter::with_options(&ir_module, &mut host, options);
+    let values = entry
+        .params
+        .iter()
+        .zip(&args.args)
+        .map(|((_, ty), text)| Value::parse(text, ty, interpreter.types()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let result = interpreter
+        .call(&entry.name, values)
+        .with_context(|| format!("Evaluation of `{}` failed", entry.name))?;
+    if result != Value::Unit {
+        println!("{result}");
+    }
+    Ok(())
+}
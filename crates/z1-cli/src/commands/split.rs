@@ -0,0 +1,213 @@
+//! `z1 split`: split a cell over its limits into smaller cells.
+//!
+//! The plan comes from [`z1_split`]: declarations are packed, callees first,
+//! into cells within the context budget and the policy's cell limits, each
+//! with a header restricted to the capabilities it needs and imports of what
+//! it takes from the others. The plan is printed and, once confirmed, the new
+//! cells are written next to the original as `<stem>_1`, `<stem>_2`, ...; the
+//! original keeps its module path and the declarations nothing else depends
+//! on.
+
+use std::fs;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, ValueHint};
+use serde::Serialize;
+use z1_split::{plan_split, SplitOptions};
+
+use crate::commands::compile::CheckContext;
+use crate::commands::lint::display_path;
+use crate::commands::migrate::mode_of;
+use crate::exit::Failure;
+use crate::output::{self, print_json, OutputFormat};
+
+#[derive(Debug, Args)]
+pub struct SplitArgs {
+    /// Cell to split.
+    #[arg(value_name = "CELL", value_hint = ValueHint::FilePath)]
+    pub cell: PathBuf,
+    /// Context budget of each cell of the split (default: the cell's `ctx=N`).
+    #[arg(long, value_name = "TOKENS")]
+    pub budget: Option<u32>,
+    /// Print the cells of the split without writing them.
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Write the cells without asking for confirmation.
+    #[arg(long, short = 'y', conflicts_with = "dry_run")]
+    pub yes: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct SplitReport {
+    cell: String,
+    /// Whether the cells were written
+    written: bool,
+    cells: Vec<SplitCell>,
+}
+
+#[derive(Debug, Serialize)]
+struct SplitCell {
+    path: String,
+    module: String,
+    /// Types and functions the cell holds
+    names: Vec<String>,
+    tokens: u32,
+    caps: Vec<String>,
+    /// Cell limits the cell still breaks
+    violations: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+}
+
+pub fn run(args: SplitArgs, format: OutputFormat) -> Result<()> {
+    format.require("split", &[OutputFormat::Json])?;
+    let cell = &args.cell;
+    let path = display_path(cell);
+    let source =
+        fs::read_to_string(cell).with_context(|| format!("Failed to read {}", cell.display()))?;
+    let module = z1_parse::parse_module(&source)
+        .map_err(|err| Failure::Parse.error(format!("{path}: {err}")))?;
+    let options = SplitOptions {
+        limits: CheckContext::for_cell(cell)?.limits,
+        budget: args.budget,
+    };
+    let plan = plan_split(&module, &options)?;
+    if plan.is_whole() {
+        let part = &plan.parts[0];
+        if part.violations.is_empty() {
+            if output::show_summary() && !format.is_json() {
+                eprintln!("{path} is within its limits; nothing to split");
+            }
+            return Ok(());
+        }
+        return Err(Failure::Policy.error(format!(
+            "{path} cannot be split: its declarations all depend on each other ({})",
+            part.violations[0]
+        )));
+    }
+
+    let mode = mode_of(cell);
+    let last = plan.parts.len() - 1;
+    let mut report = SplitReport {
+        cell: path.clone(),
+        written: false,
+        cells: Vec::new(),
+    };
+    let mut files = Vec::new();
+    for (idx, part) in plan.parts.iter().enumerate() {
+        let file = if idx == last {
+            cell.clone()
+        } else {
+            part_file(cell, idx)?
+        };
+        if idx != last && file.exists() {
+            bail!("{} already exists; not overwriting it", file.display());
+        }
+        report.cells.push(SplitCell {
+            path: display_path(&file),
+            module: part.module.path.to_string(),
+            names: part.names.clone(),
+            tokens: part.tokens,
+            caps: part.module.caps.clone(),
+            violations: part.violations.iter().map(ToString::to_string).collect(),
+            source: Some(part.render(mode)?),
+        });
+        files.push(file);
+    }
+
+    if !format.is_json() {
+        print_plan(&report, args.dry_run);
+    }
+    let confirmed = if args.dry_run || args.yes {
+        args.yes
+    } else if format.is_json() || !std::io::stdin().is_terminal() {
+        return Err(Failure::Usage.error(
+            "pass --yes to write the split without confirmation, or --dry-run to preview it",
+        ));
+    } else {
+        confirm(report.cells.len())?
+    };
+    if confirmed {
+        for (file, split) in files.iter().zip(&report.cells) {
+            let source = split.source.as_deref().unwrap_or_default();
+            fs::write(file, source)
+                .with_context(|| format!("Failed to write {}", file.display()))?;
+        }
+        report.written = true;
+    }
+
+    if format.is_json() {
+        if !args.dry_run {
+            report
+                .cells
+                .iter_mut()
+                .for_each(|split| split.source = None);
+        }
+        return print_json(&report);
+    }
+    if output::show_summary() {
+        if report.written {
+            eprintln!("Split {path} into {} cells", report.cells.len());
+        } else if !args.dry_run {
+            eprintln!("Nothing written");
+        }
+    }
+    Ok(())
+}
+
+/// File of the `idx`th new cell split from `cell`: `server.z1c` gives
+/// `server_1.z1c` for the first.
+fn part_file(cell: &Path, idx: usize) -> Result<PathBuf> {
+    let (Some(stem), Some(ext)) = (cell.file_stem(), cell.extension()) else {
+        bail!("{} is not a cell file", cell.display());
+    };
+    let name = format!(
+        "{}_{}.{}",
+        stem.to_string_lossy(),
+        idx + 1,
+        ext.to_string_lossy()
+    );
+    Ok(cell.with_file_name(name))
+}
+
+/// Print the cells of the split, with their sources for a dry run.
+fn print_plan(report: &SplitReport, sources: bool) {
+    if output::show_details() {
+        println!("Split {} into {} cells:", report.cell, report.cells.len());
+        let width = report
+            .cells
+            .iter()
+            .map(|split| split.path.len())
+            .max()
+            .unwrap_or(0);
+        for split in &report.cells {
+            println!(
+                "  {:<width$}  {:>6} tokens  caps=[{}]  {}",
+                split.path,
+                split.tokens,
+                split.caps.join(", "),
+                split.names.join(", ")
+            );
+            for violation in &split.violations {
+                println!("    warning: {violation}");
+            }
+        }
+    }
+    if sources {
+        for split in &report.cells {
+            println!("\n==> {} <==", split.path);
+            print!("{}", split.source.as_deref().unwrap_or_default());
+        }
+    }
+}
+
+/// Ask on the terminal whether to write `count` cells.
+fn confirm(count: usize) -> Result<bool> {
+    eprint!("Write {count} cells? [y/N] ");
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
@@ -0,0 +1,280 @@
+//! `z1 sync`: keep `.z1c`/`.z1r` pairs of a cell in step.
+//!
+//! A cell can live in both representations side by side, `app.z1c` next to
+//! `app.z1r`. The two are in sync when their semantic hashes are equal, so
+//! that they differ only in formatting. A pair that has diverged is
+//! reported and fails the command until `--from` names the side to keep,
+//! from which the other is regenerated. A cell named on the command line
+//! without its counterpart gets one generated; cells found in directories
+//! and globs are only checked against a counterpart that already exists.
+//!
+//! Every regenerated file is parsed back and must hash like its source, so
+//! a formatter bug cannot silently change a cell.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum, ValueHint};
+use serde::Serialize;
+use z1_fmt::{FmtOptions, Mode};
+use z1_hash::module_hashes;
+
+use crate::commands::cells;
+use crate::commands::lint::display_path;
+use crate::exit::Failure;
+use crate::output::{self, print_json, OutputFormat};
+
+#[derive(Debug, Args)]
+pub struct SyncArgs {
+    /// Cells, directories of cells, or globs such as `src/**/*.z1c`.
+    #[arg(value_name = "PATH", default_value = ".", value_hint = ValueHint::AnyPath)]
+    pub paths: Vec<String>,
+    /// Representation to keep when a pair has diverged; the other is
+    /// regenerated from it.
+    #[arg(long, value_enum)]
+    pub from: Option<Side>,
+    /// Write nothing; fail if a pair has diverged or a named cell has no
+    /// counterpart.
+    #[arg(long, conflicts_with = "from")]
+    pub check: bool,
+}
+
+/// One representation of a cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Side {
+    /// The `.z1c` file
+    Compact,
+    /// The `.z1r` file
+    Relaxed,
+}
+
+impl Side {
+    fn extension(self) -> &'static str {
+        match self {
+            Side::Compact => "z1c",
+            Side::Relaxed => "z1r",
+        }
+    }
+
+    fn mode(self) -> Mode {
+        match self {
+            Side::Compact => Mode::Compact,
+            Side::Relaxed => Mode::Relaxed,
+        }
+    }
+
+    fn other(self) -> Side {
+        match self {
+            Side::Compact => Side::Relaxed,
+            Side::Relaxed => Side::Compact,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Status {
+    InSync,
+    /// The missing counterpart was generated
+    Created,
+    /// The side not named by `--from` was regenerated
+    Regenerated,
+    Diverged,
+    /// A named cell has no counterpart (`--check`)
+    Missing,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Status::InSync => "in sync",
+            Status::Created => "created",
+            Status::Regenerated => "regenerated",
+            Status::Diverged => "diverged",
+            Status::Missing => "missing",
+        }
+    }
+
+    fn fails(self) -> bool {
+        matches!(self, Status::Diverged | Status::Missing)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SyncReport {
+    check: bool,
+    pairs: Vec<PairReport>,
+}
+
+#[derive(Debug, Serialize)]
+struct PairReport {
+    compact: String,
+    relaxed: String,
+    status: Status,
+    /// Semantic hash of the compact file, unless it is still to be created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    compact_semhash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    relaxed_semhash: Option<String>,
+}
+
+pub fn run(args: SyncArgs, format: OutputFormat) -> Result<()> {
+    format.require("sync", &[OutputFormat::Json])?;
+
+    // Pairs by path without extension; whether a cell of the pair was named
+    // on its own decides if a missing counterpart is generated
+    let mut pairs: BTreeMap<PathBuf, bool> = BTreeMap::new();
+    for arg in &args.paths {
+        let expansion = cells::expand_arg(arg)?;
+        for cell in expansion.cells {
+            let named = pairs.entry(cell.with_extension("")).or_default();
+            *named |= expansion.base.is_none();
+        }
+    }
+
+    let mut report = SyncReport {
+        check: args.check,
+        pairs: Vec::new(),
+    };
+    for (stem, named) in pairs {
+        let path = |side: Side| stem.with_extension(side.extension());
+        let exists = |side: Side| path(side).is_file();
+        // The side a missing counterpart is generated from
+        let lone = match (exists(Side::Compact), exists(Side::Relaxed)) {
+            (true, true) => None,
+            (true, false) if named => Some(Side::Compact),
+            (false, true) if named => Some(Side::Relaxed),
+            (false, false) if named => {
+                return Err(Failure::Usage.error(format!(
+                    "{}: no such cell",
+                    display_path(&path(Side::Compact))
+                )))
+            }
+            _ => continue,
+        };
+
+        let mut pair = PairReport {
+            compact: display_path(&path(Side::Compact)),
+            relaxed: display_path(&path(Side::Relaxed)),
+            status: Status::InSync,
+            compact_semhash: None,
+            relaxed_semhash: None,
+        };
+        let (from, status) = match lone {
+            Some(side) => (side, Status::Created),
+            None => {
+                let compact = semhash(&path(Side::Compact))?;
+                let relaxed = semhash(&path(Side::Relaxed))?;
+                let in_sync = compact == relaxed;
+                pair.compact_semhash = Some(compact);
+                pair.relaxed_semhash = Some(relaxed);
+                match args.from {
+                    _ if in_sync => (Side::Compact, Status::InSync),
+                    Some(side) => (side, Status::Regenerated),
+                    None => (Side::Compact, Status::Diverged),
+                }
+            }
+        };
+        pair.status = status;
+        if matches!(status, Status::Created | Status::Regenerated) {
+            if args.check {
+                pair.status = Status::Missing;
+            } else {
+                let semhash = regenerate(&path(from), &path(from.other()), from.other())?;
+                pair.compact_semhash = Some(semhash.clone());
+                pair.relaxed_semhash = Some(semhash);
+            }
+        }
+        report.pairs.push(pair);
+    }
+
+    let failed = report
+        .pairs
+        .iter()
+        .filter(|pair| pair.status.fails())
+        .count();
+    if format.is_json() {
+        print_json(&report)?;
+        if failed > 0 {
+            Failure::Check.exit();
+        }
+        return Ok(());
+    }
+    for pair in &report.pairs {
+        if pair.status == Status::Diverged {
+            eprintln!(
+                "{} and {} have diverged:\n  {} {}\n  {} {}",
+                pair.compact,
+                pair.relaxed,
+                pair.compact,
+                pair.compact_semhash.as_deref().unwrap_or_default(),
+                pair.relaxed,
+                pair.relaxed_semhash.as_deref().unwrap_or_default()
+            );
+        } else if pair.status.fails() || output::show_details() {
+            eprintln!(
+                "{} ↔ {}: {}",
+                pair.compact,
+                pair.relaxed,
+                pair.status.as_str()
+            );
+        }
+    }
+    if output::show_summary() {
+        let written = report
+            .pairs
+            .iter()
+            .filter(|pair| matches!(pair.status, Status::Created | Status::Regenerated))
+            .count();
+        eprintln!(
+            "{} pair(s): {} in sync, {} written, {} failing",
+            report.pairs.len(),
+            report.pairs.len() - written - failed,
+            written,
+            failed
+        );
+    }
+    if failed > 0 {
+        let hint = if args.check {
+            "run `z1 sync` to update them"
+        } else {
+            "pick the side to keep with --from compact|relaxed"
+        };
+        return Err(Failure::Check.error(format!("{failed} pair(s) out of sync; {hint}")));
+    }
+    Ok(())
+}
+
+fn parse(path: &Path) -> Result<(String, z1_ast::Module)> {
+    let source =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let module = z1_parse::parse_module(&source)
+        .map_err(|err| Failure::Parse.error(format!("{}: {err}", display_path(path))))?;
+    Ok((source, module))
+}
+
+fn semhash(path: &Path) -> Result<String> {
+    Ok(module_hashes(&parse(path)?.1).semantic)
+}
+
+/// Write `to` in `side`'s representation of the cell at `from`, checking
+/// that it hashes like `from`; returns the semantic hash.
+fn regenerate(from: &Path, to: &Path, side: Side) -> Result<String> {
+    let (_, module) = parse(from)?;
+    let expected = module_hashes(&module).semantic;
+    let generated = z1_fmt::format_module(&module, side.mode(), &FmtOptions::default())?;
+    let actual = z1_parse::parse_module(&generated)
+        .map(|module| module_hashes(&module).semantic)
+        .ok();
+    if actual.as_ref() != Some(&expected) {
+        return Err(Failure::Check.error(format!(
+            "{}: regenerating it from {} would change its semantics",
+            display_path(to),
+            display_path(from)
+        )));
+    }
+    fs::write(to, generated).with_context(|| format!("Failed to write {}", to.display()))?;
+    Ok(expected)
+}
@@ -0,0 +1,448 @@
+//! `z1 test`: run `.z1t` test files and the `#test` blocks of cells.
+//!
+//! Without paths, the tests of the enclosing project (or of the current
+//! directory outside a project) are discovered: `.z1t` files under `tests/`
+//! and cells with inline tests under the source roots. A directory argument
+//! is searched for both; walks skip what `.z1ignore` files exclude.
+//!
+//! Results are printed once every file has run, by one of the reporters of
+//! [`z1_test::report`]: pretty text (the default), JSON (`--reporter json` or
+//! `--format json`) or JUnit XML (`--reporter junit`) for CI dashboards.
+//! With `--output`, the report is written to a file instead of stdout.
+//!
+//! Failed `assert_eq`s of records list the fields that differ, colored on a
+//! terminal.
+//!
+//! `--coverage` follows the pretty report with the statement coverage of
+//! every function of the cells the specs ran; `--lcov` writes it as an lcov
+//! tracefile. Discovered cells without tests are included, at zero.
+//!
+//! Specs run against a controlled clock and seeded random numbers; a failed
+//! run prints its seed, and `--seed` runs with it again. Each spec has a time
+//! limit (`--timeout`, 10s by default) and optionally step and memory limits;
+//! a spec that runs out of time or steps fails as a timeout.
+//!
+//! `--doc` runs the fenced examples in the doc comments of cells instead,
+//! keeping them in step with the code they document.
+//!
+//! `--prov` appends an entry recording the run to a provenance chain (and
+//! signs it when a key is configured): the cells tested with their semantic
+//! hashes, the outcome counts and the toolchain. `z1 prov verify
+//! --require-tests` then only accepts cells with a passing run.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use clap::{Args, ValueEnum, ValueHint};
+use z1_hash::{hash_bytes, HashAlgo};
+use z1_prov::{CellBinding, ProvenanceChainExt, ProvenanceEntry, TestOutcome, TEST_ENTRY_PREFIX};
+use z1_resolve::Project;
+use z1_test::{
+    CoverageReport, FileReport, JsonReporter, JunitReporter, PrettyReporter, Reporter, TestFile,
+};
+
+use crate::commands::cells::{collect_cells, collect_files, is_cell};
+use crate::commands::prov::{cell_binding, load_or_new, sign_latest, KeyArgs};
+use crate::diagnostics::Diagnostic;
+use crate::events::{self, Event, Summary};
+use crate::exit::Failure;
+use crate::output::{self, OutputFormat};
+
+#[derive(Debug, Args)]
+pub struct TestArgs {
+    /// `.z1t` files, cells with `#test` blocks, or directories to search
+    /// (default: the project's `tests/` and source roots).
+    #[arg(value_hint = ValueHint::AnyPath)]
+    pub paths: Vec<String>,
+    /// Filter tests by tags (comma-separated).
+    #[arg(long)]
+    pub tags: Option<String>,
+    /// Run only tests whose name contains this text.
+    #[arg(long, value_name = "PATTERN")]
+    pub filter: Option<String>,
+    /// Show verbose output: every test with its outcome and duration.
+    #[arg(long, short = 'v')]
+    pub verbose: bool,
+    /// Write the golden files of snapshot tests instead of comparing with them.
+    #[arg(long)]
+    pub update_snapshots: bool,
+    /// How to report the results (default: pretty, or json with --format json).
+    #[arg(long, value_enum)]
+    pub reporter: Option<ReporterKind>,
+    /// Write the report to this file instead of stdout.
+    #[arg(long, short = 'o', value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+    /// Report statement coverage of the cells the tests run, per function.
+    #[arg(long)]
+    pub coverage: bool,
+    /// Write statement coverage as an lcov tracefile (implies --coverage).
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub lcov: Option<PathBuf>,
+    /// Seed the random numbers specs draw, to reproduce a reported run.
+    #[arg(long, value_name = "N")]
+    pub seed: Option<u64>,
+    /// Time limit of each spec, in milliseconds (default: 10000).
+    #[arg(long, value_name = "MS")]
+    pub timeout: Option<u32>,
+    /// Time limit of all the tests of each file, in milliseconds.
+    #[arg(long, value_name = "MS")]
+    pub file_timeout: Option<u32>,
+    /// Statements and loop iterations each spec may run.
+    #[arg(long, value_name = "N")]
+    pub max_steps: Option<u64>,
+    /// Bytes the variables of each spec may hold at once.
+    #[arg(long, value_name = "BYTES")]
+    pub max_memory: Option<u64>,
+    /// Run the examples in the doc comments of cells instead of their tests.
+    #[arg(long)]
+    pub doc: bool,
+    /// Append a provenance entry recording the cells tested and the outcome
+    /// to this chain file (created if missing).
+    #[arg(long, value_name = "CHAIN", value_hint = ValueHint::FilePath)]
+    pub prov: Option<PathBuf>,
+    /// Actor recorded on the provenance entry.
+    #[arg(long, env = "Z1_ACTOR", default_value = "toolchain:z1-cli")]
+    pub actor: String,
+    #[command(flatten)]
+    pub key: KeyArgs,
+    /// Signer identifier for the provenance signature (default: the actor).
+    #[arg(long = "as", env = "Z1_SIGNER")]
+    pub signer: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReporterKind {
+    /// Human-readable text
+    Pretty,
+    /// A single JSON document with every test
+    Json,
+    /// JUnit XML, for CI test dashboards
+    Junit,
+}
+
+pub fn run(args: TestArgs, format: OutputFormat) -> Result<()> {
+    format.require("test", &[OutputFormat::Json, OutputFormat::Events])?;
+    let kind = match (args.reporter, format.is_json()) {
+        (Some(ReporterKind::Pretty), true) => {
+            return Err(Failure::Usage.error("--reporter pretty conflicts with --format json"))
+        }
+        (Some(kind), _) => kind,
+        (None, true) => ReporterKind::Json,
+        (None, false) => ReporterKind::Pretty,
+    };
+
+    // Parse tag filters if provided
+    let tags_include = if let Some(tags) = &args.tags {
+        tags.split(',').map(|s| s.trim().to_string()).collect()
+    } else {
+        vec![]
+    };
+
+    let config = z1_test::TestConfig {
+        tags_include,
+        filter: args.filter.clone(),
+        seed: args.seed,
+        timeout_ms: args.timeout,
+        file_timeout_ms: args.file_timeout,
+        max_steps: args.max_steps,
+        max_memory: args.max_memory,
+        ..Default::default()
+    };
+
+    let paths = if args.paths.is_empty() {
+        discover_defaults()?
+    } else {
+        let mut paths = Vec::new();
+        for arg in &args.paths {
+            let path = PathBuf::from(arg);
+            if path.is_dir() {
+                paths.extend(discover(&path)?);
+            } else {
+                paths.push(path);
+            }
+        }
+        paths
+    };
+
+    let coverage = args.coverage || args.lcov.is_some();
+    let mut runner = z1_test::TestRunner::new(config)
+        .update_snapshots(args.update_snapshots)
+        .coverage(coverage);
+    let mut files = Vec::new();
+    let mut covered = CoverageReport::default();
+    let mut tested = Vec::new();
+    for path in &paths {
+        let loaded = if args.doc {
+            load_doc(path)?
+        } else {
+            load(path)?
+        };
+        let Some((source, file)) = loaded else {
+            // Functions no test reaches are the point of coverage; cells
+            // whose bodies the test interpreter cannot run are left out
+            if coverage {
+                if let Ok(untested) = CoverageReport::for_cell(path) {
+                    covered.merge(untested);
+                }
+            }
+            continue;
+        };
+        if is_cell(path) {
+            tested.push(path.clone());
+        } else {
+            let dir = path.parent().unwrap_or(Path::new(""));
+            tested.extend(file.uses.iter().map(|cell| dir.join(&cell.path)));
+        }
+        let results = runner.run_file_at(&file, path);
+        covered.merge(results.coverage.clone());
+        let file = FileReport {
+            path: path.display().to_string(),
+            source,
+            results,
+        };
+        emit_failures(&file);
+        files.push(file);
+    }
+    if files.is_empty() {
+        let what = if args.doc {
+            "no doc examples found"
+        } else {
+            "no .z1t files or #test blocks found"
+        };
+        return Err(Failure::Usage.error(what));
+    }
+
+    let reporter: Box<dyn Reporter> = match kind {
+        ReporterKind::Pretty => Box::new(PrettyReporter {
+            verbose: args.verbose,
+            details: output::show_details(),
+            summary: output::show_summary(),
+            color: args.output.is_none() && colored::control::SHOULD_COLORIZE.should_colorize(),
+        }),
+        ReporterKind::Json => Box::new(JsonReporter),
+        ReporterKind::Junit => Box::new(JunitReporter),
+    };
+    let mut report = Vec::new();
+    reporter.report(&files, &mut report)?;
+    match &args.output {
+        Some(file) => {
+            fs::write(file, &report)
+                .with_context(|| format!("Failed to write {}", file.display()))?;
+            if kind != ReporterKind::Pretty && output::show_summary() && !format.is_json() {
+                eprintln!("Wrote test report to {}", file.display());
+            }
+        }
+        None if format == OutputFormat::Events => {}
+        None => print!("{}", String::from_utf8_lossy(&report)),
+    }
+
+    if coverage {
+        if kind == ReporterKind::Pretty && args.output.is_none() && output::show_summary() {
+            let mut summary = Vec::new();
+            covered.write_summary(&mut summary)?;
+            print!("{}", String::from_utf8_lossy(&summary));
+        }
+        if let Some(file) = &args.lcov {
+            let mut lcov = Vec::new();
+            covered.write_lcov(&mut lcov)?;
+            fs::write(file, lcov).with_context(|| format!("Failed to write {}", file.display()))?;
+            if output::show_summary() && !format.is_json() {
+                eprintln!("Wrote coverage to {}", file.display());
+            }
+        }
+    }
+
+    if let Some(chain) = &args.prov {
+        record_tests(&args, chain, &files, &tested)?;
+        if output::show_summary() && !format.is_json() {
+            eprintln!("Recorded test run in {}", chain.display());
+        }
+    }
+
+    let count = |outcome: fn(&FileReport) -> usize| files.iter().map(outcome).sum();
+    let failed = count(|file| file.results.failed);
+    let failure = (failed > 0).then_some(Failure::Test);
+    events::emit(&Event::Summary(Summary::new(
+        failure,
+        &[
+            ("files", files.len()),
+            ("passed", count(|file| file.results.passed)),
+            ("failed", failed),
+            ("skipped", count(|file| file.results.skipped)),
+        ],
+    )));
+    if let Some(failure) = failure {
+        failure.exit();
+    }
+    Ok(())
+}
+
+/// A `diagnostic` event for each test of `file` that failed the run.
+fn emit_failures(file: &FileReport) {
+    if !events::enabled() {
+        return;
+    }
+    let failures: Vec<Diagnostic> = file
+        .results
+        .cases
+        .iter()
+        .filter_map(|case| {
+            let error = case.failure()?;
+            Some(
+                Diagnostic::error(format!("{}: {error}", case.name), file.path.clone())
+                    .with_span(case.span),
+            )
+        })
+        .collect();
+    events::diagnostics(&file.source, &failures);
+}
+
+/// Append (and sign, if a key is configured) a provenance entry for a run.
+///
+/// The entry binds the cells the run tested to their semantic hashes, the
+/// prompt hash covers the description of the outcome read back by
+/// [`TestOutcome::of`], which is also kept as the excerpt, and the diff hash
+/// is the hash of the run's JSON report.
+fn record_tests(
+    args: &TestArgs,
+    chain_path: &Path,
+    files: &[FileReport],
+    tested: &[PathBuf],
+) -> Result<()> {
+    let toolchain = format!("z1-cli/{}", env!("CARGO_PKG_VERSION"));
+    let outcome = files
+        .iter()
+        .fold(TestOutcome::default(), |outcome, file| TestOutcome {
+            passed: outcome.passed + file.results.passed,
+            failed: outcome.failed + file.results.failed,
+            skipped: outcome.skipped + file.results.skipped,
+            xfailed: outcome.xfailed + file.results.xfailed,
+        });
+    let description = outcome.describe(&toolchain);
+    let mut report = Vec::new();
+    JsonReporter.report(files, &mut report)?;
+
+    // A cell that cannot be read failed its tests already; the run records
+    // the cells it could hash
+    let cells: BTreeMap<String, String> = tested
+        .iter()
+        .filter_map(|path| cell_binding(path).ok())
+        .map(|cell| (cell.module, cell.semhash))
+        .collect();
+
+    let mut chain = load_or_new(chain_path)?;
+    let entry = ProvenanceEntry {
+        entry_id: format!("{TEST_ENTRY_PREFIX}{}", chain.len()),
+        prev: None,
+        merged: vec![],
+        actor: args.actor.clone(),
+        model: "none".to_string(),
+        prompt_sha3: hash_bytes(description.as_bytes(), HashAlgo::Sha3_256),
+        prompt_excerpt: description.chars().take(200).collect(),
+        redaction: None,
+        tools: vec![toolchain],
+        diff_sha3: hash_bytes(&report, HashAlgo::Sha3_256),
+        cells: cells
+            .into_iter()
+            .map(|(module, semhash)| CellBinding { module, semhash })
+            .collect(),
+        timestamp: Utc::now(),
+        signatures: vec![],
+        keyless: vec![],
+        rotation: None,
+        checkpoint: None,
+        timestamp_token: None,
+    };
+    chain.append(entry).context("invalid provenance entry")?;
+
+    if let Some(signing_key) = args.key.signer()? {
+        let signer = args.signer.as_deref().unwrap_or(&args.actor);
+        sign_latest(&mut chain, signing_key.as_ref(), signer)?;
+    }
+    chain
+        .save_to_file(chain_path)
+        .with_context(|| format!("failed to write {}", chain_path.display()))
+}
+
+/// Test files and cells of the enclosing project, or of the current
+/// directory outside a project.
+fn discover_defaults() -> Result<Vec<PathBuf>> {
+    let cwd = std::env::current_dir()?;
+    let (root, sources) = match Project::discover(&cwd)? {
+        Some(project) => {
+            let sources = project.source_roots();
+            (project.root, sources)
+        }
+        None => (cwd.clone(), vec![cwd.clone()]),
+    };
+    let tests = root.join("tests");
+    let mut paths = if tests.is_dir() {
+        collect_files(&tests, is_test_file)?
+    } else {
+        Vec::new()
+    };
+    for source in sources.iter().filter(|source| source.is_dir()) {
+        paths.extend(collect_cells(source)?);
+    }
+    paths.sort();
+    paths.dedup();
+    // Report paths as the user would type them
+    Ok(paths
+        .into_iter()
+        .map(|path| match path.strip_prefix(&cwd) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => path,
+        })
+        .collect())
+}
+
+/// `.z1t` files and cells under `dir`; cells without inline tests are
+/// dropped when loaded.
+fn discover(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = collect_files(dir, is_test_file)?;
+    paths.extend(collect_cells(dir)?);
+    paths.sort();
+    Ok(paths)
+}
+
+fn is_test_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "z1t")
+}
+
+/// Source and tests of `path`: a `.z1t` file, or a cell run for its `#test`
+/// blocks (`None` when it has none).
+fn load(path: &Path) -> Result<Option<(String, TestFile)>> {
+    let source =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    if !is_cell(path) {
+        let file = z1_test::parse_test_file(&source).map_err(|e| {
+            Failure::Parse.error(format!("Failed to parse {}: {e}", path.display()))
+        })?;
+        return Ok(Some((source, file)));
+    }
+    if !source.contains("#test") {
+        return Ok(None);
+    }
+    let module = z1_parse::parse_module(&source)
+        .map_err(|e| Failure::Parse.error(format!("Failed to parse {}: {e}", path.display())))?;
+    Ok(z1_test::inline_tests(&module, path).map(|file| (source, file)))
+}
+
+/// Source of the cell at `path` and its doc examples (`None` for test files
+/// and cells without examples).
+fn load_doc(path: &Path) -> Result<Option<(String, TestFile)>> {
+    if !is_cell(path) {
+        return Ok(None);
+    }
+    let source =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    if !source.contains("```") {
+        return Ok(None);
+    }
+    let module = z1_parse::parse_module(&source)
+        .map_err(|e| Failure::Parse.error(format!("Failed to parse {}: {e}", path.display())))?;
+    Ok(z1_test::doc_tests(&module, path).map(|file| (source, file)))
+}
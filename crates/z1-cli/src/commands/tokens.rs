@@ -0,0 +1,257 @@
+//! `z1 tokens`: where the context tokens of a workspace go.
+//!
+//! The compact form of every cell is split by construct (header, imports,
+//! symbol maps, types, signatures, bodies) and by identifier, as in
+//! [`z1_ctx::breakdown`], and the counts are added up across cells. The
+//! report ranks the identifiers that take the most tokens, which are the
+//! first candidates for shorter names or symbol map entries.
+
+use std::collections::HashMap;
+use std::fs;
+use std::time::Instant;
+
+use anyhow::Result;
+use clap::{Args, ValueHint};
+use serde::Serialize;
+use z1_ctx::{Breakdown, Construct};
+
+use crate::commands::cells;
+use crate::exit::Failure;
+use crate::output::{self, print_json, OutputFormat};
+
+#[derive(Debug, Args)]
+pub struct TokensArgs {
+    /// Cells, directories of cells, or globs such as `src/**/*.z1c`.
+    #[arg(value_name = "PATH", default_value = ".", value_hint = ValueHint::AnyPath)]
+    pub paths: Vec<String>,
+    /// Number of identifiers to list.
+    #[arg(long, default_value_t = 20)]
+    pub top: usize,
+    /// Custom characters-per-token ratio (default: 3.8).
+    #[arg(long)]
+    pub chars_per_token: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct TokensReport {
+    cells: usize,
+    total_tokens: u32,
+    constructs: Vec<ConstructRow>,
+    /// The `--top` identifiers taking the most tokens
+    identifiers: Vec<IdentRow>,
+    failed: Vec<FailedCell>,
+}
+
+#[derive(Debug, Serialize)]
+struct ConstructRow {
+    construct: &'static str,
+    tokens: u32,
+    /// Share of all characters, in percent
+    percent: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct IdentRow {
+    name: String,
+    occurrences: usize,
+    /// Cells the identifier appears in
+    cells: usize,
+    tokens: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct FailedCell {
+    path: String,
+    error: String,
+}
+
+/// Characters and occurrences of an identifier across cells.
+#[derive(Default)]
+struct IdentTotal {
+    occurrences: usize,
+    cells: usize,
+    chars: usize,
+}
+
+pub fn run(args: TokensArgs, format: OutputFormat) -> Result<()> {
+    format.require("tokens", &[OutputFormat::Json])?;
+    let started = Instant::now();
+    let files: Vec<String> = cells::expand(&args.paths)?
+        .iter()
+        .map(|cell| cell.display().to_string())
+        .collect();
+    let breakdowns = cells::par_map(&files, |path| -> Result<Breakdown> {
+        let source = fs::read_to_string(path)?;
+        let module = z1_parse::parse_module(&source).map_err(|err| Failure::Parse.error(err))?;
+        Ok(z1_ctx::breakdown(&module)?)
+    });
+
+    let chars_per_token = args
+        .chars_per_token
+        .unwrap_or(z1_ctx::DEFAULT_CHARS_PER_TOKEN);
+    let report = aggregate(&files, &breakdowns, args.top, chars_per_token);
+    let failure = breakdowns
+        .iter()
+        .find_map(|result| result.as_ref().err())
+        .map(Failure::of);
+
+    if format.is_json() {
+        print_json(&report)?;
+    } else {
+        for cell in &report.failed {
+            eprintln!("{}: {}", cell.path, cell.error);
+        }
+        if output::show_details() {
+            print!("{}", render(&report));
+        }
+        if output::show_summary() {
+            println!("total: {} tokens", report.total_tokens);
+        }
+        cells::Summary {
+            command: "tokens",
+            cells: files.len(),
+            elapsed: started.elapsed(),
+            rows: vec![("estimated", report.cells), ("failed", report.failed.len())],
+        }
+        .print();
+    }
+    if let Some(failure) = failure {
+        failure.exit();
+    }
+    Ok(())
+}
+
+/// Adds up the breakdowns of `files`, keeping the `top` identifiers.
+fn aggregate(
+    files: &[String],
+    breakdowns: &[Result<Breakdown>],
+    top: usize,
+    chars_per_token: f64,
+) -> TokensReport {
+    let tokens = |chars| z1_ctx::estimate_tokens_from_chars(chars, chars_per_token);
+    let mut constructs: HashMap<Construct, usize> = HashMap::new();
+    let mut identifiers: HashMap<&str, IdentTotal> = HashMap::new();
+    let mut total_chars = 0;
+    let mut failed = Vec::new();
+    for (path, result) in files.iter().zip(breakdowns) {
+        let breakdown = match result {
+            Ok(breakdown) => breakdown,
+            Err(err) => {
+                failed.push(FailedCell {
+                    path: path.clone(),
+                    error: err.to_string(),
+                });
+                continue;
+            }
+        };
+        total_chars += breakdown.char_count;
+        for (construct, chars) in &breakdown.constructs {
+            *constructs.entry(*construct).or_default() += chars;
+        }
+        for ident in &breakdown.identifiers {
+            let total = identifiers.entry(&ident.name).or_default();
+            total.occurrences += ident.occurrences;
+            total.cells += 1;
+            total.chars += ident.chars;
+        }
+    }
+
+    let mut ranked: Vec<(&str, IdentTotal)> = identifiers.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.chars.cmp(&a.1.chars).then_with(|| a.0.cmp(b.0)));
+    TokensReport {
+        cells: files.len() - failed.len(),
+        total_tokens: tokens(total_chars),
+        constructs: Construct::ALL
+            .iter()
+            .map(|construct| {
+                let chars = constructs.get(construct).copied().unwrap_or(0);
+                ConstructRow {
+                    construct: construct.as_str(),
+                    tokens: tokens(chars),
+                    percent: if total_chars == 0 {
+                        0.0
+                    } else {
+                        chars as f64 * 100.0 / total_chars as f64
+                    },
+                }
+            })
+            .collect(),
+        identifiers: ranked
+            .into_iter()
+            .take(top)
+            .map(|(name, total)| IdentRow {
+                name: name.to_string(),
+                occurrences: total.occurrences,
+                cells: total.cells,
+                tokens: tokens(total.chars),
+            })
+            .collect(),
+        failed,
+    }
+}
+
+/// Text form of `report`: one table per construct, one per identifier.
+fn render(report: &TokensReport) -> String {
+    let mut out = format!("{:<12}  {:>8}  {:>6}\n", "CONSTRUCT", "TOKENS", "SHARE");
+    for row in &report.constructs {
+        out.push_str(&format!(
+            "{:<12}  {:>8}  {:>5.1}%\n",
+            row.construct, row.tokens, row.percent
+        ));
+    }
+    if report.identifiers.is_empty() {
+        return out;
+    }
+    let width = report
+        .identifiers
+        .iter()
+        .map(|row| row.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("IDENTIFIER".len());
+    out.push_str(&format!(
+        "\n{:<width$}  {:>6}  {:>6}  {:>8}\n",
+        "IDENTIFIER", "USES", "CELLS", "TOKENS"
+    ));
+    for row in &report.identifiers {
+        out.push_str(&format!(
+            "{:<width$}  {:>6}  {:>6}  {:>8}\n",
+            row.name, row.occurrences, row.cells, row.tokens
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breakdown_of(source: &str) -> Result<Breakdown> {
+        Ok(z1_ctx::breakdown(&z1_parse::parse_module(source)?)?)
+    }
+
+    #[test]
+    fn aggregates_across_cells() {
+        let files = [
+            "a.z1c".to_string(),
+            "b.z1c".to_string(),
+            "c.z1c".to_string(),
+        ];
+        let breakdowns = [
+            breakdown_of("m a:1.0\nf handleRequest(x: U32)->U32 eff [pure] { ret x; }"),
+            breakdown_of("m b:1.0\nf handleRequest(y: U32)->U32 eff [pure] { ret y; }"),
+            breakdown_of("f {"),
+        ];
+        let report = aggregate(&files, &breakdowns, 2, 1.0);
+
+        assert_eq!(report.cells, 2);
+        assert_eq!(report.failed[0].path, "c.z1c");
+        assert_eq!(report.identifiers.len(), 2);
+        let first = &report.identifiers[0];
+        assert_eq!((first.name.as_str(), first.cells), ("handleRequest", 2));
+        assert_eq!(first.tokens, 2 * "handleRequest".len() as u32);
+        let share: f64 = report.constructs.iter().map(|row| row.percent).sum();
+        assert!((share - 100.0).abs() < 1e-6);
+        assert!(render(&report).contains("\nhandleRequest       2       2        26\n"));
+    }
+}
@@ -0,0 +1,159 @@
+//! `--watch` for `fmt`, `lint` and `compile`.
+//!
+//! The watched cells are hashed up front. After each burst of filesystem
+//! events every cell is hashed again, and only cells whose hash changed are
+//! handed back to the command. `lint` and `compile` compare semantic hashes,
+//! so reformatting a cell does not re-run them; `fmt` compares contents.
+//! Hashes are refreshed after each run, so a command that rewrites its own
+//! inputs does not trigger itself.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use colored::*;
+use notify::{RecursiveMode, Watcher};
+use z1_hash::{hash_bytes, module_hashes, HashAlgo};
+
+use crate::commands::cells::collect_cells;
+
+/// Quiet period that ends a burst of events.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// What counts as a change to a cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Invalidation {
+    /// Any change to the file's contents
+    Content,
+    /// A change to the cell's semantic hash; cells that do not parse fall
+    /// back to their contents
+    Semantic,
+}
+
+/// A watched path: cells are reported under the path they were given as.
+struct Root {
+    given: PathBuf,
+    canonical: PathBuf,
+}
+
+/// Run `run` on every cell in `paths` (files, or directories searched
+/// recursively), then again on the cells that change, until interrupted.
+///
+/// A failing run is reported and watching continues.
+pub fn watch(
+    paths: &[PathBuf],
+    invalidation: Invalidation,
+    mut run: impl FnMut(&[PathBuf]) -> Result<()>,
+) -> Result<()> {
+    let roots = paths
+        .iter()
+        .map(|given| {
+            let canonical = given
+                .canonicalize()
+                .with_context(|| format!("Failed to watch {}", given.display()))?;
+            Ok(Root {
+                given: given.clone(),
+                canonical,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    for root in &roots {
+        // Watch a file's directory, since editors often replace files
+        // rather than write to them
+        let (dir, mode) = if root.canonical.is_dir() {
+            (root.canonical.as_path(), RecursiveMode::Recursive)
+        } else {
+            let parent = root.canonical.parent().unwrap_or(Path::new("/"));
+            (parent, RecursiveMode::NonRecursive)
+        };
+        watcher.watch(dir, mode)?;
+    }
+
+    let mut hashes: HashMap<PathBuf, String> = HashMap::new();
+    let mut changed = changed_cells(&roots, invalidation, &mut hashes)?;
+    loop {
+        if !changed.is_empty() {
+            report(&changed, run(&changed));
+            // Pick up the command's own writes without re-running
+            changed_cells(&roots, invalidation, &mut hashes)?;
+            println!(
+                "{}",
+                format!("Watching {} cell(s); press Ctrl-C to stop", hashes.len()).dimmed()
+            );
+        }
+
+        // Block for the first event, then wait for the burst to end
+        rx.recv().context("File watcher stopped")??;
+        while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+            event?;
+        }
+        changed = changed_cells(&roots, invalidation, &mut hashes)?;
+    }
+}
+
+/// Cells under `roots` that are new or whose hash differs from `hashes`,
+/// which is updated to the current state.
+fn changed_cells(
+    roots: &[Root],
+    invalidation: Invalidation,
+    hashes: &mut HashMap<PathBuf, String>,
+) -> Result<Vec<PathBuf>> {
+    let mut current = HashMap::new();
+    let mut changed = Vec::new();
+    for root in roots {
+        let cells = if root.canonical.is_dir() {
+            collect_cells(&root.canonical)?
+                .into_iter()
+                .map(|cell| {
+                    let rel = cell.strip_prefix(&root.canonical).unwrap_or(&cell);
+                    let given = match root.given.as_os_str() == "." {
+                        true => rel.to_path_buf(),
+                        false => root.given.join(rel),
+                    };
+                    (given, cell.clone())
+                })
+                .collect()
+        } else {
+            vec![(root.given.clone(), root.canonical.clone())]
+        };
+        for (given, canonical) in cells {
+            // Deleted between listing and reading
+            let Some(hash) = cell_hash(&canonical, invalidation) else {
+                continue;
+            };
+            if hashes.get(&canonical) != Some(&hash) {
+                changed.push(given);
+            }
+            current.insert(canonical, hash);
+        }
+    }
+    *hashes = current;
+    Ok(changed)
+}
+
+fn cell_hash(path: &Path, invalidation: Invalidation) -> Option<String> {
+    let source = std::fs::read_to_string(path).ok()?;
+    if invalidation == Invalidation::Semantic {
+        if let Ok(module) = z1_parse::parse_module(&source) {
+            return Some(module_hashes(&module).semantic);
+        }
+    }
+    Some(hash_bytes(source.as_bytes(), HashAlgo::default()))
+}
+
+fn report(cells: &[PathBuf], result: Result<()>) {
+    let time = chrono::Local::now().format("%H:%M:%S");
+    let names = match cells {
+        [cell] => cell.display().to_string(),
+        _ => format!("{} cells", cells.len()),
+    };
+    match result {
+        Ok(()) => println!("{} {}", format!("[{time}] ✓").green().bold(), names),
+        Err(err) => println!("{} {}: {err}", format!("[{time}] ✗").red().bold(), names),
+    }
+}
@@ -0,0 +1,242 @@
+//! Internal errors: panics turned into reports instead of crashes.
+//!
+//! A panic is a bug in z1, never in the cell it was given, but what the
+//! user needs is the same as for any error: one message saying what went
+//! wrong and what to do next. [`install`] replaces the default panic output
+//! with a hook recording the first panic, where it happened and the cell
+//! being processed at the time, which commands declare with
+//! [`processing`]. [`catch`] runs a command and turns a panic into an
+//! [`InternalError`], classified as [`Failure::Internal`], whose message
+//! asks for an issue report.
+//!
+//! With `--crash-report FILE`, the report is also written as JSON to FILE:
+//! the toolchain version, the command line, the panic message and location,
+//! a backtrace, and the source of the offending cell, ready to attach to
+//! the issue.
+
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, Once};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::commands::lint::display_path;
+use crate::exit::Failure;
+
+/// Where internal errors are reported.
+pub const ISSUES_URL: &str = "https://github.com/joeabbey/zero1/issues";
+
+thread_local! {
+    /// The cell this thread is working on.
+    static CURRENT: RefCell<Option<Cell>> = const { RefCell::new(None) };
+}
+
+/// The first panic, as recorded by the hook.
+static CRASH: Mutex<Option<Crash>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize)]
+struct Cell {
+    path: String,
+    source: String,
+}
+
+/// What is known about a panic.
+#[derive(Debug, Clone, Serialize)]
+pub struct Crash {
+    pub message: String,
+    /// `file:line:column` in the z1 sources
+    pub location: Option<String>,
+    /// The cell being processed, if a command declared one
+    pub cell: Option<String>,
+    #[serde(skip)]
+    source: Option<String>,
+    #[serde(skip)]
+    backtrace: String,
+}
+
+/// Declare that the current thread works on the cell at `path` until the
+/// returned guard is dropped.
+pub fn processing(path: &Path, source: &str) -> Processing {
+    let cell = Cell {
+        path: display_path(path),
+        source: source.to_string(),
+    };
+    Processing {
+        previous: CURRENT.with(|current| current.replace(Some(cell))),
+    }
+}
+
+/// Guard returned by [`processing`]; restores the previous cell on drop.
+pub struct Processing {
+    previous: Option<Cell>,
+}
+
+impl Drop for Processing {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// Install the panic hook; the default panic output is kept only with
+/// `RUST_BACKTRACE` set, for debugging z1 itself.
+pub fn install() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let default = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let cell = CURRENT.with(|current| current.borrow().clone());
+            let crash = Crash {
+                message: payload_message(info.payload()),
+                location: info.location().map(|location| location.to_string()),
+                cell: cell.as_ref().map(|cell| cell.path.clone()),
+                source: cell.map(|cell| cell.source),
+                backtrace: Backtrace::force_capture().to_string(),
+            };
+            if let Ok(mut slot) = CRASH.lock() {
+                slot.get_or_insert(crash);
+            }
+            if std::env::var_os("RUST_BACKTRACE").is_some() {
+                default(info);
+            }
+        }));
+    });
+}
+
+/// Run `command`, turning a panic into an [`InternalError`]; with
+/// `crash_report`, the report is written there.
+pub fn catch(crash_report: Option<&Path>, command: impl FnOnce() -> Result<()>) -> Result<()> {
+    let payload = match panic::catch_unwind(AssertUnwindSafe(command)) {
+        Ok(result) => return result,
+        Err(payload) => payload,
+    };
+    let crash = CRASH
+        .lock()
+        .ok()
+        .and_then(|mut slot| slot.take())
+        .unwrap_or_else(|| Crash {
+            message: payload_message(payload.as_ref()),
+            location: None,
+            cell: None,
+            source: None,
+            backtrace: String::new(),
+        });
+    let report = crash_report.and_then(|path| {
+        let written = write_report(path, &crash);
+        if let Err(err) = &written {
+            eprintln!("Failed to write crash report {}: {err}", path.display());
+        }
+        written.ok().map(|()| path.to_path_buf())
+    });
+    Err(Failure::Internal.wrap(InternalError { crash, report }.into()))
+}
+
+/// A panic, reported as an error.
+#[derive(Debug)]
+pub struct InternalError {
+    pub crash: Crash,
+    /// Where the crash report was written
+    pub report: Option<PathBuf>,
+}
+
+impl fmt::Display for InternalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "internal error: {}", self.crash.message)?;
+        if let Some(location) = &self.crash.location {
+            write!(f, " (at {location})")?;
+        }
+        if let Some(cell) = &self.crash.cell {
+            write!(f, "\n  while processing {cell}")?;
+        }
+        write!(
+            f,
+            "\nThis is a bug in z1, not in your cell. Please report it at {ISSUES_URL}"
+        )?;
+        match &self.report {
+            Some(report) => write!(
+                f,
+                ", attaching the crash report written to {}",
+                report.display()
+            ),
+            None => write!(
+                f,
+                "; rerun with --crash-report FILE to write a report to attach"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InternalError {}
+
+#[derive(Serialize)]
+struct CrashReport<'a> {
+    version: &'static str,
+    command: Vec<String>,
+    #[serde(flatten)]
+    crash: &'a Crash,
+    /// Source of the offending cell
+    source: Option<&'a str>,
+    backtrace: Vec<&'a str>,
+}
+
+fn write_report(path: &Path, crash: &Crash) -> Result<()> {
+    let report = CrashReport {
+        version: env!("CARGO_PKG_VERSION"),
+        command: std::env::args().collect(),
+        crash,
+        source: crash.source.as_deref(),
+        backtrace: crash.backtrace.lines().collect(),
+    };
+    std::fs::write(path, serde_json::to_string_pretty(&report)? + "\n")?;
+    Ok(())
+}
+
+fn payload_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "panic with a non-string payload".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panics_become_internal_errors_with_a_report() {
+        install();
+        let dir = tempfile::TempDir::new().unwrap();
+        let report = dir.path().join("crash.json");
+        let err = catch(Some(&report), || {
+            let _cell = processing(Path::new("app.z1c"), "m app:1.0\n");
+            panic!("index out of bounds in test");
+        })
+        .unwrap_err();
+        assert_eq!(Failure::of(&err), Failure::Internal);
+        let message = err.to_string();
+        assert!(
+            message.starts_with("internal error: index out of bounds in test (at "),
+            "{message}"
+        );
+        assert!(
+            message.contains("\n  while processing app.z1c\n"),
+            "{message}"
+        );
+        assert!(message.contains(ISSUES_URL), "{message}");
+
+        let report: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+        assert_eq!(report["message"], "index out of bounds in test");
+        assert_eq!(report["cell"], "app.z1c");
+        assert_eq!(report["source"], "m app:1.0\n");
+
+        assert!(catch(None, || Ok(())).is_ok());
+    }
+}
@@ -10,13 +10,17 @@
 #![allow(clippy::needless_range_loop)]
 #![allow(clippy::should_implement_trait)]
 #![allow(dead_code)]
+use crate::codes;
 use colored::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use z1_ast::Span;
-use z1_effects::EffectError;
+use z1_ast::{Item, Module, Span, SymbolPair};
+use z1_ctx::CtxError;
+use z1_effects::{EffectError, EffectWarning};
 use z1_parse::ParseError;
-use z1_typeck::TypeError;
+use z1_policy::PolicyViolation;
+use z1_resolve::ResolveError;
+use z1_typeck::{TypeError, TypeWarning};
 
 /// Diagnostic severity level.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -59,6 +63,24 @@ pub struct Diagnostic {
     pub source_file: String,
     pub suggestion: Option<String>,
     pub code: Option<String>,
+    /// Edits that resolve the diagnostic, applied by `z1 fix`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fix: Option<Fix>,
+}
+
+/// A safe, machine-applicable change that resolves a diagnostic.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fix {
+    /// What the fix does, e.g. "Remove unused capability 'net'".
+    pub message: String,
+    pub edits: Vec<Edit>,
+}
+
+/// Replace the source text in `span` with `replacement`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Edit {
+    pub span: Span,
+    pub replacement: String,
 }
 
 impl Diagnostic {
@@ -71,6 +93,7 @@ impl Diagnostic {
             source_file,
             suggestion: None,
             code: None,
+            fix: None,
         }
     }
 
@@ -83,6 +106,7 @@ impl Diagnostic {
             source_file,
             suggestion: None,
             code: None,
+            fix: None,
         }
     }
 
@@ -95,6 +119,7 @@ impl Diagnostic {
             source_file,
             suggestion: None,
             code: None,
+            fix: None,
         }
     }
 
@@ -116,6 +141,12 @@ impl Diagnostic {
         self
     }
 
+    /// Attach a fix to this diagnostic.
+    pub fn with_fix(mut self, fix: Fix) -> Self {
+        self.fix = Some(fix);
+        self
+    }
+
     /// Convert a ParseError to a Diagnostic.
     pub fn from_parse_error(error: &ParseError, source_file: String) -> Self {
         let span = match error {
@@ -124,7 +155,14 @@ impl Diagnostic {
 
         Self::error(format!("Parse Error: {error}"), source_file)
             .with_span(span)
-            .with_code("P001".to_string())
+            .with_code(codes::PARSE.to_string())
+    }
+
+    /// Convert a failure to resolve the import at `span` to a Diagnostic.
+    pub fn from_resolve_error(error: &ResolveError, span: Span, source_file: String) -> Self {
+        Self::error(format!("Resolve Error: {error}"), source_file)
+            .with_span(span)
+            .with_code(codes::RESOLVE.to_string())
     }
 
     /// Convert a TypeError to a Diagnostic.
@@ -134,12 +172,13 @@ impl Diagnostic {
             | TypeError::UndefinedType { span, .. }
             | TypeError::UndefinedFunction { span, .. }
             | TypeError::UndefinedVariable { span, .. }
-            | TypeError::ArityMismatch { span, .. } => Some(*span),
+            | TypeError::ArityMismatch { span, .. }
+            | TypeError::UnknownImport { span, .. } => Some(*span),
             _ => None,
         };
 
-        let mut diag =
-            Self::error(format!("Type Error: {error}"), source_file).with_code("T001".to_string());
+        let mut diag = Self::error(format!("Type Error: {error}"), source_file)
+            .with_code(codes::TYPE.to_string());
 
         if let Some(span) = span_opt {
             diag = diag.with_span(span);
@@ -163,11 +202,22 @@ impl Diagnostic {
                 (*fn_span, Some(suggestion))
             }
             EffectError::UnknownEffect { fn_span, .. } => (*fn_span, None),
+            EffectError::ImportedEffect {
+                import_span,
+                effect,
+                module,
+                ..
+            } => {
+                let suggestion = format!(
+                    "Add '{effect}' to module capabilities: module {module} caps=[{effect}]"
+                );
+                (*import_span, Some(suggestion))
+            }
         };
 
         let mut diag = Self::error(format!("Effect Error: {error}"), source_file)
             .with_span(span)
-            .with_code("E001".to_string());
+            .with_code(codes::EFFECT.to_string());
 
         if let Some(s) = suggestion {
             diag = diag.with_suggestion(s);
@@ -175,6 +225,135 @@ impl Diagnostic {
 
         diag
     }
+
+    /// Convert an EffectWarning to a Diagnostic with suggestion.
+    pub fn from_effect_warning(warning: &EffectWarning, source_file: String) -> Self {
+        let suggestion = match warning {
+            EffectWarning::UnusedEffect { effect, .. } => {
+                z1_effects::suggest_unused_effect_fix(effect)
+            }
+            EffectWarning::UnusedCapability {
+                capability, module, ..
+            } => z1_effects::suggest_unused_capability_fix(capability, module),
+            EffectWarning::AsyncWithoutAsync { .. } => {
+                "Remove 'async' from the effect list".to_string()
+            }
+        };
+
+        Self::warning(format!("Effect Warning: {warning}"), source_file)
+            .with_span(warning.span())
+            .with_code(codes::EFFECT_WARNING.to_string())
+            .with_suggestion(suggestion)
+    }
+
+    /// Convert a TypeWarning to a Diagnostic.
+    pub fn from_type_warning(warning: &TypeWarning, source_file: String) -> Self {
+        let diag = Self::warning(format!("Type Warning: {warning}"), source_file)
+            .with_span(warning.span());
+        match warning {
+            TypeWarning::UnusedImport { .. } => diag.with_code(codes::UNUSED_IMPORT.to_string()),
+            _ => diag,
+        }
+    }
+
+    /// Diagnostic for the symbol map at `span`, whose `stale` pairs no
+    /// longer shorten anything.
+    pub fn from_stale_symbols(stale: &[&SymbolPair], span: Span, source_file: String) -> Self {
+        let pairs: Vec<String> = stale
+            .iter()
+            .map(|pair| format!("{} ↔ {}", pair.long, pair.short))
+            .collect();
+        Self::warning(
+            format!("Symbol Map Warning: unused entries {}", pairs.join(", ")),
+            source_file,
+        )
+        .with_span(span)
+        .with_code(codes::STALE_SYMBOLS.to_string())
+        .with_suggestion("Remove the entries from the #sym directive".to_string())
+    }
+
+    /// Convert a CtxError to a Diagnostic.
+    pub fn from_ctx_error(error: &CtxError, source_file: String) -> Self {
+        let mut diag = Self::error(format!("Context Error: {error}"), source_file)
+            .with_code(codes::CONTEXT.to_string());
+
+        match error {
+            CtxError::BudgetExceeded { span, .. } | CtxError::FnBudgetExceeded { span, .. } => {
+                diag = diag.with_span(*span);
+            }
+            CtxError::Format(_) => {}
+        }
+
+        diag
+    }
+
+    /// Convert a PolicyViolation of `module` to a Diagnostic with suggestion.
+    pub fn from_policy_violation(
+        violation: &PolicyViolation,
+        module: &Module,
+        source_file: String,
+    ) -> Self {
+        Self::error(format!("Policy Violation: {violation}"), source_file)
+            .with_span(policy_violation_span(violation, module))
+            .with_code(codes::POLICY.to_string())
+            .with_suggestion(policy_help(violation))
+    }
+}
+
+/// Where `violation` is in `module`: the function it names, the first
+/// import over the limit, or else the module header.
+pub(crate) fn policy_violation_span(violation: &PolicyViolation, module: &Module) -> Span {
+    let function = violation.fn_name().and_then(|name| {
+        module.items.iter().find_map(|item| match item {
+            Item::Fn(decl) if decl.name == name => Some(decl.span),
+            _ => None,
+        })
+    });
+    let import = match violation {
+        PolicyViolation::FaninLimitExceeded { limit, .. } => module
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Import(import) => Some(import.span),
+                _ => None,
+            })
+            .nth(*limit),
+        _ => None,
+    };
+    function.or(import).unwrap_or(module.span)
+}
+
+/// How to resolve `violation`.
+pub(crate) fn policy_help(violation: &PolicyViolation) -> String {
+    match violation {
+        PolicyViolation::AstNodeLimitExceeded { .. } => {
+            "Split the cell into smaller cells and import them".to_string()
+        }
+        PolicyViolation::ExportLimitExceeded { .. } => {
+            "Move some functions or types to another cell".to_string()
+        }
+        PolicyViolation::FaninLimitExceeded { .. } => {
+            "Remove unused imports with `z1 fix`, or split the cell".to_string()
+        }
+        PolicyViolation::ParamLimitExceeded { .. } => {
+            "Group related parameters into a record type".to_string()
+        }
+        PolicyViolation::LocalsLimitExceeded { fn_name, .. }
+        | PolicyViolation::ContextBudgetExceeded { fn_name, .. } => {
+            format!("Split function '{fn_name}' into smaller functions")
+        }
+        PolicyViolation::EffectNotInCapabilities { effect, caps, .. } => {
+            let mut caps = caps.clone();
+            caps.push(effect.clone());
+            format!(
+                "Add '{effect}' to module capabilities: caps=[{}]",
+                caps.join(", ")
+            )
+        }
+        PolicyViolation::CellContextBudgetExceeded { .. } => {
+            "Split the cell, or raise `ctx=N` in the module header".to_string()
+        }
+    }
 }
 
 /// Configuration for diagnostic output.
@@ -374,6 +553,36 @@ pub fn print_diagnostics(diagnostics: &[Diagnostic], source: &str, config: &Diag
             eprintln!("{summary}");
         }
     }
+    print_explain_hint(diagnostics, config);
+}
+
+/// Point at `z1 explain` for the codes of the errors in `diagnostics`.
+pub fn print_explain_hint<'a>(
+    diagnostics: impl IntoIterator<Item = &'a Diagnostic>,
+    config: &DiagnosticConfig,
+) {
+    let mut error_codes: Vec<&str> = diagnostics
+        .into_iter()
+        .filter(|d| matches!(d.level, DiagnosticLevel::Error))
+        .filter_map(|d| d.code.as_deref())
+        .filter(|code| codes::lookup(code).is_some())
+        .collect();
+    error_codes.sort_unstable();
+    error_codes.dedup();
+
+    let hint = match error_codes.as_slice() {
+        [] => return,
+        [code] => codes::explain_hint(code),
+        [first, ..] => format!(
+            "Some errors have detailed explanations: {}.\nFor more information about an error, try `z1 explain {first}`.",
+            error_codes.join(", ")
+        ),
+    };
+    if config.use_colors {
+        eprintln!("{}", hint.bold());
+    } else {
+        eprintln!("{hint}");
+    }
 }
 
 /// Print a single diagnostic with pretty formatting.
@@ -435,7 +644,11 @@ fn print_source_snippet(source: &str, file_path: &str, span: Span, config: &Diag
     eprintln!("{colored_line_num} │ {line_text}");
 
     let caret_offset = col_num - 1;
-    let span_len = (span.end - span.start).max(1) as usize;
+    // Spans covering several lines are marked up to the end of the first
+    let line_rest = line_text.chars().count().saturating_sub(caret_offset);
+    let span_len = (span.end.saturating_sub(span.start) as usize)
+        .min(line_rest)
+        .max(1);
     let carets = "^".repeat(span_len);
     let colored_carets = if config.use_colors {
         carets.red().bold().to_string()
@@ -446,7 +659,7 @@ fn print_source_snippet(source: &str, file_path: &str, span: Span, config: &Diag
 }
 
 /// Extract line number, column number, and line text for a given span.
-fn extract_line_info(source: &str, span: Span) -> (usize, usize, String) {
+pub(crate) fn extract_line_info(source: &str, span: Span) -> (usize, usize, String) {
     let start_offset = span.start as usize;
 
     let mut line_num = 1;
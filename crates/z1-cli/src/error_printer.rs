@@ -5,13 +5,20 @@
 //! - Source snippet showing the relevant line(s)
 //! - Color-coded output (red for errors, yellow for warnings)
 //! - Caret/underline pointing to exact location
-//! - Optional help hints
+//! - Notes and help hints
+//!
+//! Every checker's errors (parse, type, effect, context and policy) are
+//! rendered the same way by [`render_error`].
 
+use crate::codes;
+use crate::diagnostics::{policy_help, policy_violation_span};
 use colored::*;
 use std::env;
-use z1_ast::Span;
+use z1_ast::{Module, Span};
+use z1_ctx::CtxError;
 use z1_effects::EffectError;
 use z1_parse::ParseError;
+use z1_policy::PolicyViolation;
 use z1_typeck::TypeError;
 
 /// Configuration for error printing.
@@ -29,6 +36,15 @@ impl Default for ErrorPrinterConfig {
     }
 }
 
+/// An error as printed: a header, the source it points at, and what to do.
+struct ErrorReport {
+    /// Header, such as `Type Error: ...`
+    header: String,
+    span: Option<Span>,
+    notes: Vec<String>,
+    help: Option<String>,
+}
+
 /// Pretty-print a parse error with source context.
 pub fn print_parse_error(
     error: &ParseError,
@@ -39,17 +55,13 @@ pub fn print_parse_error(
     let span = match error {
         ParseError::Unexpected { span, .. } | ParseError::Invalid { span, .. } => *span,
     };
-
-    let header = format!("Error: {error}");
-    let colored_header = if config.use_colors {
-        header.red().bold().to_string()
-    } else {
-        header
+    let report = ErrorReport {
+        header: format!("Error: {error}"),
+        span: Some(span),
+        notes: Vec::new(),
+        help: None,
     };
-
-    eprintln!("{colored_header}");
-    eprint_source_snippet(source, file_path, span, config);
-    eprintln!();
+    eprint_reports(&[report], codes::PARSE, source, file_path, config);
 }
 
 /// Pretty-print a type error with source context.
@@ -59,27 +71,22 @@ pub fn print_type_error(
     file_path: &str,
     config: &ErrorPrinterConfig,
 ) {
-    let span_opt = match error {
+    let span = match error {
         TypeError::Mismatch { span, .. }
         | TypeError::UndefinedType { span, .. }
         | TypeError::UndefinedFunction { span, .. }
         | TypeError::UndefinedVariable { span, .. }
-        | TypeError::ArityMismatch { span, .. } => Some(*span),
+        | TypeError::ArityMismatch { span, .. }
+        | TypeError::UnknownImport { span, .. } => Some(*span),
         _ => None,
     };
-
-    let header = format!("Type Error: {error}");
-    let colored_header = if config.use_colors {
-        header.red().bold().to_string()
-    } else {
-        header
+    let report = ErrorReport {
+        header: format!("Type Error: {error}"),
+        span,
+        notes: Vec::new(),
+        help: None,
     };
-
-    eprintln!("{colored_header}");
-    if let Some(span) = span_opt {
-        eprint_source_snippet(source, file_path, span, config);
-    }
-    eprintln!();
+    eprint_reports(&[report], codes::TYPE, source, file_path, config);
 }
 
 /// Pretty-print an effect error with source context.
@@ -89,68 +96,208 @@ pub fn print_effect_error(
     file_path: &str,
     config: &ErrorPrinterConfig,
 ) {
-    let span = match error {
-        EffectError::MissingCapability { fn_span, .. } => *fn_span,
-        EffectError::UnknownEffect { fn_span, .. } => *fn_span,
+    let (span, notes) = match error {
+        EffectError::MissingCapability { fn_span, .. } => (
+            *fn_span,
+            vec!["a module grants its capabilities in its header (`caps=[...]`)".to_string()],
+        ),
+        EffectError::UnknownEffect { fn_span, .. } => (*fn_span, Vec::new()),
+        EffectError::ImportedEffect { import_span, .. } => (
+            *import_span,
+            vec![
+                "calling an imported function brings its effects into the caller's module"
+                    .to_string(),
+            ],
+        ),
     };
-
-    let header = format!("Effect Error: {error}");
-    let colored_header = if config.use_colors {
-        header.red().bold().to_string()
-    } else {
-        header
+    // Add helpful hint for missing capability errors
+    let help = match error {
+        EffectError::MissingCapability { effect, module, .. }
+        | EffectError::ImportedEffect { effect, module, .. } => Some(format!(
+            "Add '{effect}' to module capabilities: module {module} caps=[{effect}]"
+        )),
+        EffectError::UnknownEffect { .. } => None,
+    };
+    let report = ErrorReport {
+        header: format!("Effect Error: {error}"),
+        span: Some(span),
+        notes,
+        help,
     };
+    eprint_reports(&[report], codes::EFFECT, source, file_path, config);
+}
 
-    eprintln!("{colored_header}");
-    eprint_source_snippet(source, file_path, span, config);
+/// Pretty-print a context estimation error with source context.
+pub fn print_context_error(
+    error: &CtxError,
+    source: &str,
+    file_path: &str,
+    config: &ErrorPrinterConfig,
+) {
+    let report = context_report(error, file_path);
+    eprint_reports(&[report], codes::CONTEXT, source, file_path, config);
+}
 
-    // Add helpful hint for missing capability errors
-    if let EffectError::MissingCapability { effect, module, .. } = error {
-        let hint =
-            format!("Help: Add '{effect}' to module capabilities: module {module} caps=[{effect}]");
-        let colored_hint = if config.use_colors {
-            hint.yellow().to_string()
+/// Pretty-print the policy violations of `module` with source context.
+pub fn print_policy_violations(
+    violations: &[PolicyViolation],
+    module: &Module,
+    source: &str,
+    file_path: &str,
+    config: &ErrorPrinterConfig,
+) {
+    let reports: Vec<ErrorReport> = violations
+        .iter()
+        .map(|violation| policy_report(violation, module))
+        .collect();
+    eprint_reports(&reports, codes::POLICY, source, file_path, config);
+}
+
+fn context_report(error: &CtxError, file_path: &str) -> ErrorReport {
+    let estimates = format!("`z1 ctx -v {file_path}` prints the estimate of every function");
+    match error {
+        CtxError::BudgetExceeded {
+            actual,
+            budget,
+            suggestion,
+            span,
+        } => ErrorReport {
+            header: format!("Context Error: cell exceeds context budget: {actual}/{budget} tokens"),
+            span: Some(*span),
+            notes: vec![
+                format!("the budget is set by `ctx={budget}` in the module header"),
+                estimates,
+            ],
+            help: Some(suggestion.clone()),
+        },
+        CtxError::FnBudgetExceeded { name, span, .. } => ErrorReport {
+            header: format!("Context Error: {error}"),
+            span: Some(*span),
+            notes: vec![estimates],
+            help: Some(format!("Split function '{name}' into smaller functions")),
+        },
+        CtxError::Format(_) => ErrorReport {
+            header: format!("Context Error: {error}"),
+            span: None,
+            notes: Vec::new(),
+            help: None,
+        },
+    }
+}
+
+fn policy_report(violation: &PolicyViolation, module: &Module) -> ErrorReport {
+    ErrorReport {
+        header: format!("Policy Violation: {violation}"),
+        span: Some(policy_violation_span(violation, module)),
+        notes: violation
+            .limit_key()
+            .map(|key| format!("the limit is `{key}` in the `[policy]` table of z1.toml"))
+            .into_iter()
+            .collect(),
+        help: Some(policy_help(violation)),
+    }
+}
+
+/// Print `reports` to stderr, followed by a pointer to `z1 explain <code>`.
+fn eprint_reports(
+    reports: &[ErrorReport],
+    code: &str,
+    source: &str,
+    file_path: &str,
+    config: &ErrorPrinterConfig,
+) {
+    // One write, so errors of cells compiled in parallel do not interleave
+    let mut text = String::new();
+    for (i, report) in reports.iter().enumerate() {
+        if i > 0 {
+            text.push('\n');
+        }
+        text.push_str(&render_error(report, source, file_path, config));
+    }
+    let hint = codes::explain_hint(code);
+    if config.use_colors {
+        text.push_str(&format!("{}\n\n", hint.bold()));
+    } else {
+        text.push_str(&format!("{hint}\n\n"));
+    }
+    eprint!("{text}");
+}
+
+/// Render `report`: header, source snippet, notes and help.
+fn render_error(
+    report: &ErrorReport,
+    source: &str,
+    file_path: &str,
+    config: &ErrorPrinterConfig,
+) -> String {
+    let paint = |text: String, color: fn(&str) -> ColoredString| {
+        if config.use_colors {
+            color(&text).to_string()
         } else {
-            hint
-        };
-        eprintln!("{colored_hint}");
+            text
+        }
+    };
+
+    let mut out = paint(report.header.clone(), |text| text.red().bold());
+    out.push('\n');
+    if let Some(span) = report.span {
+        out.push_str(&source_snippet(source, file_path, span, config));
     }
-    eprintln!();
+    for note in &report.notes {
+        out.push_str(&format!("  = note: {note}\n"));
+    }
+    if let Some(help) = &report.help {
+        out.push_str(&paint(format!("Help: {help}"), |text| text.yellow()));
+        out.push('\n');
+    }
+    out
 }
 
-/// Print a source snippet with location marker to stderr.
-fn eprint_source_snippet(source: &str, file_path: &str, span: Span, config: &ErrorPrinterConfig) {
+/// A source snippet with location marker.
+fn source_snippet(
+    source: &str,
+    file_path: &str,
+    span: Span,
+    config: &ErrorPrinterConfig,
+) -> String {
     let (line_num, col_num, line_text) = extract_line_info(source, span);
 
-    // Print location header: "  ┌─ file.z1c:5:12"
+    // Location header: "  ┌─ file.z1c:5:12"
     let location = format!("  ┌─ {file_path}:{line_num}:{col_num}");
     let colored_location = if config.use_colors {
         location.blue().to_string()
     } else {
         location
     };
-    eprintln!("{colored_location}");
-    eprintln!("  │");
+    let mut out = format!("{colored_location}\n  │\n");
 
-    // Print line number and source line: " 5 │     let x: U32 = \"hello\";"
+    // Line number and source line: " 5 │     let x: U32 = \"hello\";"
     let line_num_str = format!("{line_num:>3}");
     let colored_line_num = if config.use_colors {
         line_num_str.blue().to_string()
     } else {
         line_num_str
     };
-    eprintln!("{colored_line_num} │ {line_text}");
+    out.push_str(&format!("{colored_line_num} │ {line_text}\n"));
 
-    // Print caret line: "    │            ^^^"
+    // Caret line: "    │            ^^^"
     let caret_offset = col_num - 1; // Column is 1-indexed
-    let span_len = (span.end - span.start).max(1) as usize;
+                                    // Spans covering several lines are marked up to the end of the first
+    let line_rest = line_text.chars().count().saturating_sub(caret_offset);
+    let span_len = (span.end.saturating_sub(span.start) as usize)
+        .min(line_rest)
+        .max(1);
     let carets = "^".repeat(span_len);
     let colored_carets = if config.use_colors {
         carets.red().bold().to_string()
     } else {
         carets
     };
-    eprintln!("    │ {}{colored_carets}", " ".repeat(caret_offset));
+    out.push_str(&format!(
+        "    │ {}{colored_carets}\n",
+        " ".repeat(caret_offset)
+    ));
+    out
 }
 
 /// Extract line number, column number, and line text for a given span.
@@ -317,4 +464,49 @@ mod tests {
         // In a real scenario, you'd capture stdout to verify the hint is printed
         print_effect_error(&error, source, "test.z1c", &no_color_config());
     }
+
+    #[test]
+    fn test_render_context_error_points_at_header() {
+        let source = "m app.b:1.0 ctx=3 caps=[]\nf add(a: U32)->U32 eff [pure] { ret a; }\n";
+        let module = z1_parse::parse_module(source).unwrap();
+        let error = z1_ctx::estimate_cell(&module).unwrap_err();
+        let text = render_error(
+            &context_report(&error, "b.z1c"),
+            source,
+            "b.z1c",
+            &no_color_config(),
+        );
+        assert!(text.starts_with("Context Error: cell exceeds context budget: "));
+        assert!(text.contains("  ┌─ b.z1c:1:1\n"));
+        assert!(text.contains("  = note: the budget is set by `ctx=3` in the module header\n"));
+        assert!(text.contains("Help: Consider splitting function 'add'"));
+    }
+
+    #[test]
+    fn test_render_policy_violation_points_at_function() {
+        let source =
+            "m app:1.0 caps=[]\nf wide(a: U32, b: U32, c: U32)->U32 eff [pure] { ret a; }\n";
+        let module = z1_parse::parse_module(source).unwrap();
+        let checker = z1_policy::PolicyChecker::new(z1_policy::PolicyLimits {
+            fn_max_params: 2,
+            ..Default::default()
+        });
+        let violations = checker.check_module(&module).unwrap_err();
+        let text = render_error(
+            &policy_report(&violations[0], &module),
+            source,
+            "app.z1c",
+            &no_color_config(),
+        );
+        assert!(text.contains("  ┌─ app.z1c:2:1\n"), "{text}");
+        // Carets stop at the end of the function's first line
+        assert!(text.contains(&format!(
+            "    │ {}\n",
+            "^".repeat(source.lines().nth(1).unwrap().len())
+        )));
+        assert!(text.contains(
+            "  = note: the limit is `fn_max_params` in the `[policy]` table of z1.toml\n"
+        ));
+        assert!(text.ends_with("Help: Group related parameters into a record type\n"));
+    }
 }
@@ -0,0 +1,180 @@
+//! The event protocol of the global `--events` flag.
+//!
+//! An agent driving `z1 compile`, `lint` or `test` wants to react to the
+//! first error instead of waiting for one JSON document at the end. With
+//! `--events`, those commands print newline-delimited JSON on stdout
+//! instead, one [`Event`] per line as soon as it is known, tagged by its
+//! `event` field:
+//!
+//! - `diagnostic`: a problem in a cell, as in the `diagnostics` of
+//!   `--format json`, plus its one-based `line` and `column`
+//! - `artifact`: a file written, with the `cell` it was built from, its
+//!   `kind` (`output`, `ir`, `declarations`, `shim`, `meta`, ...), `path`
+//!   and size in `bytes`
+//! - `summary`: always the last line of a run, with `ok`, the `exit_code`
+//!   the command exits with, its counts and, when it failed with one, the
+//!   `error` message
+//!
+//! Cells compiled in parallel interleave their events; each carries the
+//! cell it is about. Human-oriented output still goes to stderr.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Serialize;
+
+use crate::commands::compile::{CheckContext, CompileReport};
+use crate::commands::lint::{display_path, lint_source};
+use crate::diagnostics::{extract_line_info, Diagnostic, DiagnosticLevel};
+use crate::exit::Failure;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// Whether the run printed its summary already.
+static SUMMARIZED: AtomicBool = AtomicBool::new(false);
+
+/// One line of output.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum Event<'a> {
+    Diagnostic {
+        #[serde(flatten)]
+        diagnostic: &'a Diagnostic,
+        line: Option<usize>,
+        column: Option<usize>,
+    },
+    Artifact {
+        cell: String,
+        kind: &'a str,
+        path: String,
+        bytes: Option<u64>,
+    },
+    Summary(Summary),
+}
+
+/// Outcome of a run, printed last.
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    pub ok: bool,
+    pub exit_code: i32,
+    /// Counts of the command, e.g. `cells`, `errors` and `warnings`
+    #[serde(flatten)]
+    pub counts: BTreeMap<&'static str, usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Summary {
+    /// The summary of a run that exits with `failure`, or succeeds.
+    pub fn new(failure: Option<Failure>, counts: &[(&'static str, usize)]) -> Self {
+        Self {
+            ok: failure.is_none(),
+            exit_code: failure.map_or(0, Failure::code),
+            counts: counts.iter().copied().collect(),
+            error: None,
+        }
+    }
+
+    /// The summary of a run that failed with `err`.
+    pub fn error(err: &anyhow::Error, counts: &[(&'static str, usize)]) -> Self {
+        Self {
+            error: Some(format!("{err:#}")),
+            ..Self::new(Some(Failure::of(err)), counts)
+        }
+    }
+}
+
+/// Turn events on; like the verbosity, set once by `main`.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Print `event` as one line, unless events are off.
+pub fn emit(event: &Event) {
+    if !enabled() {
+        return;
+    }
+    if let Event::Summary(_) = event {
+        SUMMARIZED.store(true, Ordering::Relaxed);
+    }
+    let line = serde_json::to_string(event).expect("events serialize");
+    // One write per line, so that events of parallel cells do not mix
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{line}");
+    let _ = stdout.flush();
+}
+
+/// A `diagnostic` event for each of `diagnostics`, found in `source`.
+pub fn diagnostics<'a>(source: &str, diagnostics: impl IntoIterator<Item = &'a Diagnostic>) {
+    for diagnostic in diagnostics {
+        let (line, column) = match diagnostic.span {
+            Some(span) => {
+                let (line, column, _) = extract_line_info(source, span);
+                (Some(line), Some(column))
+            }
+            None => (None, None),
+        };
+        emit(&Event::Diagnostic {
+            diagnostic,
+            line,
+            column,
+        });
+    }
+}
+
+/// An `artifact` event for the file at `path`, built from `cell`.
+pub fn artifact(cell: &Path, kind: &str, path: &Path) {
+    emit(&Event::Artifact {
+        cell: display_path(cell),
+        kind,
+        path: display_path(path),
+        bytes: std::fs::metadata(path).ok().map(|metadata| metadata.len()),
+    });
+}
+
+/// `artifact` events for the files a compilation of `cell` wrote: per
+/// target its output and artifacts, then the metadata of several targets.
+pub fn compiled(cell: &Path, reports: &[CompileReport], meta: Option<&Path>) {
+    for report in reports {
+        let kind = if report.ir { "ir" } else { "output" };
+        artifact(cell, kind, &report.output);
+        for written in &report.artifacts {
+            artifact(cell, written.kind.name(), &written.path);
+        }
+    }
+    if let Some(meta) = meta {
+        artifact(cell, "meta", meta);
+    }
+}
+
+/// `diagnostic` events for a compilation of `cell` that failed with `err`:
+/// what the checks find in its `source`, or else the error itself.
+pub fn compile_failed(cell: &Path, source: Option<&str>, err: &anyhow::Error) {
+    if !enabled() {
+        return;
+    }
+    let display = display_path(cell);
+    let mut found = match source {
+        Some(source) => {
+            let context = CheckContext::for_cell(cell).unwrap_or_default();
+            lint_source(source, &display, &context)
+        }
+        None => Vec::new(),
+    };
+    if !found.iter().any(|d| d.level == DiagnosticLevel::Error) {
+        found.push(Diagnostic::error(format!("{err:#}"), display));
+    }
+    diagnostics(source.unwrap_or_default(), &found);
+}
+
+/// Print the summary of a run that failed before printing its own.
+pub fn finish(err: &anyhow::Error) {
+    if enabled() && !SUMMARIZED.load(Ordering::Relaxed) {
+        emit(&Event::Summary(Summary::error(err, &[])));
+    }
+}
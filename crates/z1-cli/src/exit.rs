@@ -0,0 +1,146 @@
+//! Process exit codes.
+//!
+//! Every command exits 0 on success and with one code per class of failure
+//! otherwise, so scripts can tell a parse error from a policy violation or
+//! an exceeded budget without reading the output. Errors are classified
+//! where they arise, with [`Failure::error`] or [`Failure::wrap`]; anything
+//! unclassified exits 1.
+
+use std::fmt;
+
+/// Exit code table shown by `z1 --help`.
+pub const EXIT_CODES: &str = "\
+Exit codes:
+   0  success
+   1  the command could not run (unreadable files, I/O errors)
+   2  invalid arguments or an unsupported --format
+   3  parse errors
+   4  type errors
+   5  effect or capability errors
+   6  context budget exceeded
+   7  policy violations
+   8  warnings, when denied (lint --deny-warnings)
+   9  imports that do not resolve
+  10  a check found differences (fmt --check, hash --verify/--manifest,
+      prov verify, bench thresholds)
+  11  failing tests
+ 101  internal error: a bug in z1, reported with --crash-report FILE";
+
+/// Class of a failed command, one exit code each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// The command could not run at all
+    Error,
+    /// Invalid command-line usage
+    Usage,
+    Parse,
+    Type,
+    Effect,
+    Budget,
+    Policy,
+    /// Warnings, when they are denied
+    Warnings,
+    Resolve,
+    /// A check found differences: unformatted cells, hash or provenance
+    /// mismatches, exceeded benchmark thresholds
+    Check,
+    Test,
+    /// A panic in z1 itself
+    Internal,
+}
+
+impl Failure {
+    pub fn code(self) -> i32 {
+        match self {
+            Failure::Error => 1,
+            Failure::Usage => 2,
+            Failure::Parse => 3,
+            Failure::Type => 4,
+            Failure::Effect => 5,
+            Failure::Budget => 6,
+            Failure::Policy => 7,
+            Failure::Warnings => 8,
+            Failure::Resolve => 9,
+            Failure::Check => 10,
+            Failure::Test => 11,
+            Failure::Internal => 101,
+        }
+    }
+
+    /// An error of this class with `message`.
+    pub fn error(self, message: impl fmt::Display) -> anyhow::Error {
+        self.wrap(anyhow::anyhow!("{message}"))
+    }
+
+    /// Classify `error`; its message and causes are kept as they are.
+    pub fn wrap(self, error: anyhow::Error) -> anyhow::Error {
+        anyhow::Error::new(Classified {
+            failure: self,
+            error,
+        })
+    }
+
+    /// Class of `error`: the first classified error in its chain, else
+    /// [`Failure::Error`].
+    pub fn of(error: &anyhow::Error) -> Failure {
+        error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<Classified>())
+            .map_or(Failure::Error, |classified| classified.failure)
+    }
+
+    /// Exit with this class's code.
+    pub fn exit(self) -> ! {
+        std::process::exit(self.code())
+    }
+}
+
+#[derive(Debug)]
+struct Classified {
+    failure: Failure,
+    error: anyhow::Error,
+}
+
+impl fmt::Display for Classified {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl std::error::Error for Classified {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn classification_survives_context() {
+        let err = Err::<(), _>(Failure::Policy.error("Policy violations"))
+            .context("Failed to build app.main")
+            .unwrap_err();
+        assert_eq!(Failure::of(&err), Failure::Policy);
+        assert_eq!(
+            format!("{err:#}"),
+            "Failed to build app.main: Policy violations"
+        );
+        assert_eq!(Failure::of(&anyhow::anyhow!("oops")), Failure::Error);
+    }
+
+    #[test]
+    fn wrapping_keeps_message_and_causes() {
+        let inner = Err::<(), _>(anyhow::anyhow!("digest differs"))
+            .context("Merkle chain verification failed")
+            .unwrap_err();
+        let err = Failure::Check.wrap(inner);
+        assert_eq!(Failure::of(&err), Failure::Check);
+        assert_eq!(
+            format!("{err:#}"),
+            "Merkle chain verification failed: digest differs"
+        );
+    }
+}
@@ -2,6 +2,12 @@
 //!
 //! This library exposes internal CLI modules for testing purposes.
 
+pub mod codes;
 pub mod commands;
+pub mod crash;
 pub mod diagnostics;
 pub mod error_printer;
+pub mod events;
+pub mod exit;
+pub mod output;
+pub mod trace;
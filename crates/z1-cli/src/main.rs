@@ -1,20 +1,61 @@
+mod codes;
 mod commands;
+mod crash;
 mod diagnostics;
 mod error_printer;
+mod events;
+mod exit;
+mod output;
+mod trace;
 
-use anyhow::Result;
-use clap::{Args, Parser, Subcommand, ValueEnum};
+use anyhow::{Context, Result};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum, ValueHint};
+use commands::watch::Invalidation;
+use events::{Event, Summary};
+use exit::Failure;
+use output::OutputFormat;
+use serde::Serialize;
 use std::fs;
 use std::io::{self, Read};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 use tracing::info;
 
-/// Zero1 CLI entry point. Commands are stubs until the corresponding crates land.
+/// Zero1 toolchain: format, check, compile and share Z1 cells.
 #[derive(Parser, Debug)]
-#[command(name = "z1", author = "Zero1 Contributors", version)]
+#[command(
+    name = "z1",
+    author = "Zero1 Contributors",
+    version,
+    after_help = exit::EXIT_CODES
+)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Output format; `json` prints one machine-readable document on stdout.
+    #[arg(long, global = true, value_enum, default_value_t)]
+    format: OutputFormat,
+    /// Stream newline-delimited JSON events (diagnostics, artifacts, then a
+    /// summary) on stdout as `compile`, `lint` and `test` progress.
+    #[arg(long, global = true, conflicts_with = "format")]
+    events: bool,
+    /// Print errors only; the exit code reports the outcome.
+    #[arg(long, short = 'q', global = true)]
+    quiet: bool,
+    /// Print totals only, without per-cell results.
+    #[arg(long, global = true, conflicts_with = "quiet")]
+    summary_only: bool,
+    /// Print the time spent in each pipeline stage on stderr.
+    #[arg(long, global = true)]
+    trace_timings: bool,
+    /// Log as JSON lines on stderr, with the elapsed time of each stage;
+    /// `RUST_LOG` sets the level (default: info).
+    #[arg(long, global = true)]
+    log_json: bool,
+    /// On an internal error, write a JSON crash report (backtrace, source
+    /// of the offending cell) to FILE.
+    #[arg(long, global = true, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    crash_report: Option<PathBuf>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -25,34 +66,82 @@ enum Commands {
     /// Display toolchain and provenance information.
     Info,
     /// Compute semantic + format hashes for a `.z1c`/`.z1r` cell.
-    Hash {
-        /// Path to the source cell.
-        path: String,
-    },
+    Hash(commands::hash::HashArgs),
     /// Estimate context token usage for a cell.
     #[command(alias = "z1ctx")]
     Ctx(CtxArgs),
+    /// Rank what takes context tokens across cells, by construct and identifier.
+    Tokens(commands::tokens::TokensArgs),
     /// Provenance chain management and verification.
     #[command(alias = "z1prov", subcommand)]
     Prov(commands::prov::ProvCommand),
     /// Run Z1 test files (.z1t).
     #[command(alias = "z1test")]
-    Test(TestArgs),
+    Test(commands::test::TestArgs),
     /// Run the benchmark harness.
     #[command(alias = "z1bench")]
     Bench(commands::bench::BenchArgs),
     /// Compile Z1 cell to target language.
     #[command(alias = "z1c")]
     Compile(CompileArgs),
+    /// Run every static check (types, effects, context, policy) without compiling.
+    Lint(commands::lint::LintArgs),
+    /// Evaluate a cell with the reference interpreter.
+    Run(commands::run::RunArgs),
+    /// Incrementally compile every cell of a workspace.
+    Build(commands::build::BuildArgs),
+    /// Serve fmt, lint and compile from a build server keeping cells warm.
+    Daemon(commands::daemon::DaemonArgs),
+    /// Print the import graph of a workspace as DOT, Mermaid or JSON.
+    Graph(commands::graph::GraphArgs),
+    /// Create a project, or add a cell to one with `new cell`.
+    New(commands::new::NewArgs),
+    /// Scaffold a project in an existing directory.
+    Init(commands::new::InitArgs),
+    /// Fetch a package from a registry into the project and pin it in z1.lock.
+    Add(commands::registry::AddArgs),
+    /// Publish the project's cells to a registry.
+    Publish(commands::registry::PublishArgs),
+    /// Print shell completions.
+    Completions(commands::completions::CompletionsArgs),
+    /// Print the manual page, or write one per command.
+    Man(commands::completions::ManArgs),
+    /// Explain a diagnostic code, with an example and common fixes.
+    Explain(commands::explain::ExplainArgs),
+    /// Apply safe fixes: remove unused imports and capabilities, regenerate
+    /// symbol maps.
+    Fix(commands::fix::FixArgs),
+    /// Upgrade cells to a newer syntax version, recording it in provenance.
+    Migrate(commands::migrate::MigrateArgs),
+    /// Split a cell over its context or policy limits into smaller cells.
+    Split(commands::split::SplitArgs),
+    /// Rename an identifier throughout a cell, symbol map included.
+    Rename(commands::rename::RenameArgs),
+    /// Merge two versions of a cell item by item; usable as a git merge driver.
+    Merge(commands::merge::MergeArgs),
+    /// Print what editing one function needs: its signature's types, callees
+    /// and imported declarations, within a token budget.
+    Context(commands::context::ContextArgs),
+    /// Bundle a cell with its transitive dependencies, in full or as
+    /// interfaces, within a token budget.
+    Pack(commands::pack::PackArgs),
+    /// Print a cell with its template expansions written out.
+    Expand(commands::expand::ExpandArgs),
+    /// Regenerate `.z1c`/`.z1r` pairs from each other, failing on pairs that
+    /// have diverged.
+    Sync(commands::sync::SyncArgs),
+    /// Write seed corpora for the `fuzz/` targets from existing cells.
+    FuzzCorpus(commands::fuzz_corpus::FuzzCorpusArgs),
 }
 
 #[derive(Debug, Args)]
 struct FmtArgs {
-    /// Paths to `.z1c` / `.z1r` cells. Omit when using --stdin/--files-from.
-    #[arg(value_name = "PATH", num_args = 0..)]
+    /// Cells, directories of cells, or globs such as `src/**/*.z1c`. Omit
+    /// when using --stdin/--files-from.
+    #[arg(value_name = "PATH", num_args = 0.., value_hint = ValueHint::AnyPath)]
     paths: Vec<String>,
     /// Read additional newline-delimited paths from file.
-    #[arg(long = "files-from")]
+    #[arg(long = "files-from", value_hint = ValueHint::FilePath)]
     files_from: Option<String>,
     /// Run in check-only mode without writing files.
     #[arg(long)]
@@ -63,12 +152,44 @@ struct FmtArgs {
     /// Emit formatted output to stdout.
     #[arg(long)]
     stdout: bool,
-    /// Override formatter mode.
+    /// Override formatter mode (default: `fmt-mode` in z1.toml, else by
+    /// file extension).
     #[arg(long, value_enum)]
     mode: Option<FmtModeArg>,
-    /// Symbol map ordering behaviour.
-    #[arg(long, value_enum, default_value_t = FmtSymmapArg::Respect)]
-    symmap: FmtSymmapArg,
+    /// Symbol map ordering behaviour (default: `symmap` in z1.toml, else
+    /// respect).
+    #[arg(long, value_enum)]
+    symmap: Option<FmtSymmapArg>,
+    /// Format again whenever a cell changes.
+    #[arg(long, conflicts_with_all = ["stdin", "stdout"])]
+    watch: bool,
+    /// Edit only the regions that need formatting, leaving every other line
+    /// and its line ending as it is.
+    #[arg(long)]
+    minimal_edits: bool,
+    /// Defaults from the project's z1.toml
+    #[arg(skip)]
+    defaults: commands::defaults::Defaults,
+}
+
+impl FmtArgs {
+    /// Mode to format the cell at `path` in; stdin is relaxed unless set.
+    fn mode(&self, path: Option<&str>) -> z1_fmt::Mode {
+        self.mode
+            .map(Into::into)
+            .or(self.defaults.fmt_mode)
+            .unwrap_or_else(|| infer_mode(path))
+    }
+
+    fn options(&self) -> z1_fmt::FmtOptions {
+        z1_fmt::FmtOptions {
+            symmap_style: self
+                .symmap
+                .map(Into::into)
+                .or(self.defaults.symmap)
+                .unwrap_or_default(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
@@ -103,9 +224,16 @@ impl From<FmtSymmapArg> for z1_fmt::SymMapStyle {
 
 #[derive(Debug, Args)]
 struct CtxArgs {
-    /// Path to the source cell to estimate.
-    path: String,
-    /// Custom characters-per-token ratio (default: 3.8).
+    /// Cells to estimate: files, directories of cells, or globs such as
+    /// `src/**/*.z1c`.
+    #[arg(
+        value_name = "PATH",
+        required_unless_present = "changed_only",
+        value_hint = ValueHint::AnyPath
+    )]
+    paths: Vec<String>,
+    /// Custom characters-per-token ratio (default: `chars-per-token` in
+    /// z1.toml, else 3.8).
     #[arg(long)]
     chars_per_token: Option<f64>,
     /// Skip budget enforcement (only show estimates).
@@ -114,42 +242,71 @@ struct CtxArgs {
     /// Show detailed per-function breakdown.
     #[arg(long, short = 'v')]
     verbose: bool,
-}
-
-#[derive(Debug, Args)]
-struct TestArgs {
-    /// Paths to `.z1t` test files.
-    paths: Vec<String>,
-    /// Filter tests by tags (comma-separated).
-    #[arg(long)]
-    tags: Option<String>,
-    /// Show verbose output.
-    #[arg(long, short = 'v')]
-    verbose: bool,
+    #[command(flatten)]
+    changed: commands::changed::ChangedArgs,
 }
 
 #[derive(Debug, Args)]
 struct CompileArgs {
-    /// Path to Z1 cell to compile
-    path: String,
+    /// Cells to compile: files, directories of cells, or globs such as
+    /// `src/**/*.z1c`, several of which are compiled in parallel; with
+    /// --stdin, the name of the cell read from stdin, used for diagnostics,
+    /// imports and the default output path
+    #[arg(
+        value_name = "PATH",
+        value_hint = ValueHint::AnyPath,
+        required_unless_present = "stdin"
+    )]
+    paths: Vec<String>,
+    /// Read the cell from stdin
+    #[arg(long, conflicts_with = "watch")]
+    stdin: bool,
+    /// Write the generated code (or the IR with --emit-ir) to stdout; with
+    /// --format json, failures are reported on stdout as JSON diagnostics
+    #[arg(long, conflicts_with_all = ["output", "component", "emit_glue", "verbose", "watch"])]
+    stdout: bool,
     /// Output file path (default: same name with target extension)
-    #[arg(short, long)]
+    #[arg(short, long, value_hint = ValueHint::FilePath)]
     output: Option<String>,
-    /// Compilation target
-    #[arg(short, long, value_enum, default_value_t = CompileTargetArg::TypeScript)]
-    target: CompileTargetArg,
+    /// Compilation targets, comma separated; several targets are compiled in
+    /// one run, with a .d.ts for TypeScript and a .meta.json describing the
+    /// cell (default: the targets in z1.toml, else type-script)
+    #[arg(short, long, value_enum, value_delimiter = ',')]
+    target: Vec<CompileTargetArg>,
     /// Generate binary .wasm instead of text .wat (requires --target wasm)
     #[arg(short, long)]
     binary: bool,
+    /// Emit a WebAssembly component and .wit interface (requires --target wasm)
+    #[arg(long, conflicts_with = "binary")]
+    component: bool,
+    /// Embed Z1 source lines and DWARF line info in WASM output (requires --target wasm)
+    #[arg(long, conflicts_with = "component")]
+    debug: bool,
+    /// Write JS glue (.js) and ABI reference (.abi.md) next to WASM output (requires --target wasm)
+    #[arg(long, conflicts_with = "component")]
+    emit_glue: bool,
+    /// Fail unless the cell defines `fn main()`, exported as `_start` (requires --target wasm)
+    #[arg(long, conflicts_with = "component")]
+    require_entry: bool,
+    /// Optimize WASM output for size: pool constants, strip custom sections (requires --target wasm)
+    #[arg(long, conflicts_with = "debug")]
+    opt_size: bool,
+    /// Enable WASM proposals for codegen, comma separated (requires --target wasm)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    wasm_features: Vec<WasmFeatureArg>,
+    /// Skip wasmparser validation of WASM output (requires --target wasm)
+    #[arg(long)]
+    no_validate: bool,
     /// Run all checks before compilation
     #[arg(long, default_value_t = true)]
     check: bool,
     /// Emit IR instead of target code
     #[arg(long)]
     emit_ir: bool,
-    /// Optimization level (0=none, 1=basic, 2=aggressive)
-    #[arg(short = 'O', long, value_enum, default_value_t = OptLevelArg::O1)]
-    opt_level: OptLevelArg,
+    /// Optimization level (0=none, 1=basic, 2=aggressive; default:
+    /// `opt-level` in z1.toml, else 1)
+    #[arg(short = 'O', long, value_enum)]
+    opt_level: Option<OptLevelArg>,
     /// Verbose output
     #[arg(short, long)]
     verbose: bool,
@@ -168,14 +325,57 @@ struct CompileArgs {
     /// Disable colored output
     #[arg(long)]
     no_color: bool,
+    /// Append a provenance entry for this build to a chain file
+    #[arg(long, value_name = "CHAIN", conflicts_with = "emit_ir")]
+    prov: Option<PathBuf>,
+    /// Actor recorded on the provenance entry
+    #[arg(long, env = "Z1_ACTOR", default_value = "toolchain:z1-cli")]
+    prov_actor: String,
+    /// Keypair used to sign the provenance entry (from `z1 prov keygen`)
+    #[arg(long, env = "Z1_SIGNING_KEY", conflicts_with = "prov_key_name")]
+    prov_key: Option<PathBuf>,
+    /// Keystore key used to sign the provenance entry
+    #[arg(long, env = "Z1_KEY_NAME")]
+    prov_key_name: Option<String>,
+    /// Signer identifier for the provenance signature (default: the actor)
+    #[arg(long, env = "Z1_SIGNER")]
+    prov_signer: Option<String>,
+    /// Compile again whenever the cell changes semantically
+    #[arg(long, conflicts_with = "prov")]
+    watch: bool,
+    /// Print artifact size, token estimate and optimization counts instead
+    /// of the summary, e.g. for CI trend dashboards
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with_all = ["stdout", "watch"])]
+    stats: Option<StatsFormatArg>,
+    /// Make the output byte-identical across builds of the same cell: no
+    /// absolute paths in artifacts, provenance timestamps from
+    /// SOURCE_DATE_EPOCH
+    #[arg(long)]
+    reproducible: bool,
+    /// Build twice and fail unless both builds are byte-identical; implies
+    /// --reproducible
+    #[arg(long, conflicts_with_all = ["stdout", "watch"])]
+    verify_reproducible: bool,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum StatsFormatArg {
+    Json,
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
 enum CompileTargetArg {
+    #[value(alias = "ts")]
     TypeScript,
     Wasm,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum WasmFeatureArg {
+    /// WasmGC structs and arrays instead of linear memory
+    Gc,
+}
+
 #[derive(Debug, Clone, Copy, ValueEnum)]
 enum OptLevelArg {
     O0,
@@ -210,171 +410,612 @@ impl From<WarnLevelArg> for diagnostics::WarnLevel {
     }
 }
 
-fn main() -> Result<()> {
+fn main() {
     // Respect NO_COLOR environment variable
     if std::env::var("NO_COLOR").is_ok() {
         colored::control::set_override(false);
     }
 
-    tracing_subscriber::fmt::init();
     let cli = Cli::parse();
-    match cli.command {
-        Commands::Fmt(args) => handle_fmt(args),
-        Commands::Info => {
+    trace::init(cli.trace_timings, cli.log_json);
+    crash::install();
+    let format = match cli.events {
+        true => OutputFormat::Events,
+        false => cli.format,
+    };
+    if cli.events {
+        events::enable();
+    }
+    output::set_verbosity(match (cli.quiet || cli.events, cli.summary_only) {
+        (true, _) => output::Verbosity::Quiet,
+        (_, true) => output::Verbosity::SummaryOnly,
+        _ => output::Verbosity::Normal,
+    });
+    let crash_report = cli.crash_report.clone();
+    let result = crash::catch(crash_report.as_deref(), || run(cli.command, format));
+    if cli.trace_timings {
+        trace::print_timings();
+    }
+    if let Err(err) = &result {
+        events::finish(err);
+        if format.is_json() {
+            output::print_error(err);
+        } else {
+            eprintln!("Error: {err:?}");
+        }
+        Failure::of(err).exit();
+    }
+}
+
+fn run(command: Commands, format: OutputFormat) -> Result<()> {
+    match command {
+        Commands::Fmt(args) => handle_fmt(args, format),
+        Commands::Info => format.require("info", &[]).map(|()| {
             info!("Zero1 CLI scaffolding is ready for agent contributions.");
-            Ok(())
+        }),
+        Commands::Hash(args) => commands::hash::run(args, format),
+        Commands::Ctx(args) => handle_ctx(args, format),
+        Commands::Tokens(args) => commands::tokens::run(args, format),
+        Commands::Prov(cmd) => handle_prov(cmd, format),
+        Commands::Test(args) => commands::test::run(args, format),
+        Commands::Bench(args) => format
+            .require("bench", &[])
+            .and_then(|()| commands::bench::run(args)),
+        Commands::Compile(args) => handle_compile(args, format),
+        Commands::Lint(args) => commands::lint::run(args, format),
+        Commands::Run(args) => format
+            .require("run", &[])
+            .and_then(|()| commands::run::run(args)),
+        Commands::Build(args) => format
+            .require("build", &[])
+            .and_then(|()| commands::build::run(args)),
+        Commands::Daemon(args) => commands::daemon::run(args, format),
+        Commands::Graph(args) => commands::graph::run(args, format),
+        Commands::New(args) => commands::new::run_new(args, format),
+        Commands::Init(args) => commands::new::run_init(args, format),
+        Commands::Add(args) => commands::registry::run_add(args, format),
+        Commands::Publish(args) => commands::registry::run_publish(args, format),
+        Commands::Completions(args) => {
+            commands::completions::run_completions(args, Cli::command(), format)
         }
-        Commands::Hash { path } => handle_hash(path),
-        Commands::Ctx(args) => handle_ctx(args),
-        Commands::Prov(cmd) => handle_prov(cmd),
-        Commands::Test(args) => handle_test(args),
-        Commands::Bench(args) => commands::bench::run(args),
-        Commands::Compile(args) => handle_compile(args),
+        Commands::Man(args) => commands::completions::run_man(args, Cli::command(), format),
+        Commands::Explain(args) => commands::explain::run(args, format),
+        Commands::Fix(args) => commands::fix::run(args, format),
+        Commands::Migrate(args) => commands::migrate::run(args, format),
+        Commands::Split(args) => commands::split::run(args, format),
+        Commands::Rename(args) => commands::rename::run(args, format),
+        Commands::Merge(args) => commands::merge::run(args, format),
+        Commands::Context(args) => commands::context::run(args, format),
+        Commands::Pack(args) => commands::pack::run(args, format),
+        Commands::Expand(args) => commands::expand::run(args, format),
+        Commands::Sync(args) => commands::sync::run(args, format),
+        Commands::FuzzCorpus(args) => commands::fuzz_corpus::run(args, format),
     }
 }
 
-fn handle_compile(args: CompileArgs) -> Result<()> {
-    let target = match args.target {
-        CompileTargetArg::TypeScript => commands::compile::CompileTarget::TypeScript,
-        CompileTargetArg::Wasm => commands::compile::CompileTarget::Wasm,
-    };
+/// Name of a cell read from stdin when no PATH names it.
+const STDIN_NAME: &str = "<stdin>";
+
+fn handle_compile(args: CompileArgs, format: OutputFormat) -> Result<()> {
+    format.require("compile", &[OutputFormat::Json, OutputFormat::Events])?;
+    if format.is_json() && args.verbose {
+        return Err(Failure::Usage.error("--verbose cannot be combined with --format json"));
+    }
+    if format == OutputFormat::Events && (args.verbose || args.stdout || args.stats.is_some()) {
+        return Err(Failure::Usage
+            .error("--verbose, --stdout and --stats cannot be combined with --events"));
+    }
+    let defaults = commands::defaults::Defaults::for_paths(&args.paths)?;
+    let mut targets = Vec::new();
+    for target in &args.target {
+        let target = match target {
+            CompileTargetArg::TypeScript => commands::compile::CompileTarget::TypeScript,
+            CompileTargetArg::Wasm => commands::compile::CompileTarget::Wasm,
+        };
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+    if targets.is_empty() {
+        targets = defaults.targets;
+    }
+    if targets.is_empty() {
+        targets.push(commands::compile::CompileTarget::TypeScript);
+    }
+    let target = targets[0];
+    let wasm = targets.contains(&commands::compile::CompileTarget::Wasm);
+    if targets.len() > 1 {
+        if args.output.is_some() || args.stdout || args.emit_ir {
+            return Err(
+                Failure::Usage.error("--output, --stdout and --emit-ir need a single --target")
+            );
+        }
+        if args.stdin && args.paths.is_empty() {
+            return Err(Failure::Usage.error("--stdin with several targets needs a PATH"));
+        }
+    }
+    let batch = !args.stdin
+        && (args.paths.len() > 1
+            || args
+                .paths
+                .iter()
+                .any(|path| Path::new(path).is_dir() || commands::cells::is_glob(path)));
+    if batch && (args.output.is_some() || args.stdout || args.verbose) {
+        return Err(Failure::Usage.error("--output, --stdout and --verbose need a single cell"));
+    }
+    if args.stdin && args.paths.len() > 1 {
+        return Err(Failure::Usage.error("--stdin reads a single cell"));
+    }
 
     // Validate that --binary only works with --target wasm
-    if args.binary && !matches!(args.target, CompileTargetArg::Wasm) {
-        anyhow::bail!("--binary flag requires --target wasm");
+    if args.binary && !wasm {
+        return Err(Failure::Usage.error("--binary flag requires --target wasm"));
+    }
+    if args.component && !wasm {
+        return Err(Failure::Usage.error("--component flag requires --target wasm"));
+    }
+    if args.debug && !wasm {
+        return Err(Failure::Usage.error("--debug flag requires --target wasm"));
     }
+    if args.emit_glue && !wasm {
+        return Err(Failure::Usage.error("--emit-glue flag requires --target wasm"));
+    }
+    if args.require_entry && !wasm {
+        return Err(Failure::Usage.error("--require-entry flag requires --target wasm"));
+    }
+    if args.opt_size && !wasm {
+        return Err(Failure::Usage.error("--opt-size flag requires --target wasm"));
+    }
+    if !args.wasm_features.is_empty() && !wasm {
+        return Err(Failure::Usage.error("--wasm-features flag requires --target wasm"));
+    }
+    let wasm_gc = args.wasm_features.contains(&WasmFeatureArg::Gc);
+    if wasm_gc && (args.emit_glue || args.component) {
+        return Err(Failure::Usage
+            .error("--wasm-features gc cannot be combined with --emit-glue or --component"));
+    }
+
+    if args.stdout && args.debug && args.binary {
+        return Err(Failure::Usage
+            .error("--stdout cannot be combined with --debug --binary, which writes a .wat file"));
+    }
+    if args.stdin && args.paths.is_empty() && !args.stdout && args.output.is_none() {
+        return Err(
+            Failure::Usage.error("--stdin needs --stdout, --output or a PATH naming the cell")
+        );
+    }
+    let source = if args.stdin {
+        let mut source = String::new();
+        io::stdin()
+            .read_to_string(&mut source)
+            .map_err(|err| Failure::Error.wrap(anyhow::anyhow!("Failed to read stdin: {err}")))?;
+        Some(source)
+    } else {
+        None
+    };
+
+    let provenance = args.prov.map(|chain| commands::compile::ProvenanceOptions {
+        chain,
+        actor: args.prov_actor,
+        key: commands::prov::KeyArgs {
+            key: args.prov_key,
+            key_name: args.prov_key_name,
+            ..Default::default()
+        },
+        signer: args.prov_signer,
+    });
 
     let opts = commands::compile::CompileOptions {
-        input_path: args.path.into(),
+        input_path: args.paths.first().map_or(STDIN_NAME, String::as_str).into(),
         output_path: args.output.map(Into::into),
         target,
         binary: args.binary,
+        component: args.component,
+        debug: args.debug,
+        emit_glue: args.emit_glue,
+        require_entry: args.require_entry,
+        opt_size: args.opt_size,
+        wasm_gc,
+        validate: !args.no_validate,
         check: args.check,
         emit_ir: args.emit_ir,
-        opt_level: args.opt_level.into(),
+        opt_level: args
+            .opt_level
+            .map(Into::into)
+            .or(defaults.opt_level)
+            .unwrap_or(z1_ir::optimize::OptLevel::O1),
         verbose: args.verbose,
+        stdout: args.stdout,
+        reproducible: args.reproducible || args.verify_reproducible,
+        verify_reproducible: args.verify_reproducible,
+        provenance,
+        cache: None,
+        hooks: Default::default(),
     };
 
-    commands::compile::compile(opts)
+    if batch {
+        return compile_batch(&opts, &args.paths, &targets, args.watch, format);
+    }
+    if targets.len() > 1 {
+        return compile_targets(&opts, &targets, source, args.watch, args.stats, format);
+    }
+    if args.watch {
+        format.require("compile --watch", &[])?;
+        let path = opts.input_path.clone();
+        return commands::watch::watch(&[path], Invalidation::Semantic, |_| {
+            commands::compile::compile(opts.clone()).map(|report| report.print())
+        });
+    }
+    let input_path = opts.input_path.clone();
+    let result = match &source {
+        Some(source) => commands::compile::compile_source(&opts, source),
+        None => match commands::daemon::compile(&opts) {
+            Some(report) => Ok(report),
+            None => commands::compile::compile(opts),
+        },
+    };
+    let report = match result {
+        Ok(report) => report,
+        Err(err) if format.is_json() => {
+            let source = source.or_else(|| fs::read_to_string(&input_path).ok());
+            print_compile_failure(&err, &input_path, source.as_deref())?;
+            Failure::of(&err).exit();
+        }
+        Err(err) if format == OutputFormat::Events => {
+            let source = source.or_else(|| fs::read_to_string(&input_path).ok());
+            events::compile_failed(&input_path, source.as_deref(), &err);
+            events::emit(&Event::Summary(Summary::error(&err, &compile_counts(1, 1))));
+            return Err(err);
+        }
+        Err(err) => return Err(err),
+    };
+    if format == OutputFormat::Events {
+        events::compiled(&input_path, std::slice::from_ref(&report), None);
+        events::emit(&Event::Summary(Summary::new(None, &compile_counts(1, 0))));
+        return Ok(());
+    }
+    if args.stdout {
+        return Ok(());
+    }
+    if format.is_json() {
+        return output::print_json(&report);
+    }
+    if let Some(StatsFormatArg::Json) = args.stats {
+        return output::print_json(&report.stats);
+    }
+    report.print();
+    if args.verify_reproducible {
+        print_reproducible(report.files().len());
+    }
+    Ok(())
 }
 
-fn handle_prov(cmd: commands::prov::ProvCommand) -> Result<()> {
-    use commands::prov::ProvCommand;
-    match cmd {
-        ProvCommand::Log { file } => commands::prov::cmd_log(file),
-        ProvCommand::Verify { file, keys } => commands::prov::cmd_verify(file, keys),
-        ProvCommand::Keygen { output } => commands::prov::cmd_keygen(output),
+/// `z1 compile src/`: every cell the paths name, compiled in parallel.
+fn compile_batch(
+    opts: &commands::compile::CompileOptions,
+    paths: &[String],
+    targets: &[commands::compile::CompileTarget],
+    watch: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    let compile = |cells: &[PathBuf]| {
+        let report = commands::compile::compile_batch(opts, cells, targets);
+        if format.is_json() {
+            output::print_json(&report)?;
+        } else {
+            report.print();
+        }
+        let result = match report.failure() {
+            Some(failure) => {
+                Err(failure.error(format!("{} cell(s) failed to compile", report.failed)))
+            }
+            None => Ok(()),
+        };
+        let counts = compile_counts(report.cells.len(), report.failed);
+        events::emit(&Event::Summary(match &result {
+            Ok(()) => Summary::new(None, &counts),
+            Err(err) => Summary::error(err, &counts),
+        }));
+        result
+    };
+    if watch {
+        format.require("compile --watch", &[])?;
+        // Directories are watched as such, so cells added later are compiled
+        let mut watched = Vec::new();
+        for path in paths {
+            match commands::cells::is_glob(path) {
+                true => watched.extend(commands::cells::expand_arg(path)?.cells),
+                false => watched.push(PathBuf::from(path)),
+            }
+        }
+        return commands::watch::watch(&watched, Invalidation::Semantic, compile);
+    }
+    let result = compile(&commands::cells::expand(paths)?);
+    match result {
+        Err(err) if format.is_json() => Failure::of(&err).exit(),
+        result => result,
     }
 }
 
-fn handle_test(args: TestArgs) -> Result<()> {
-    if args.paths.is_empty() {
-        anyhow::bail!("provide at least one .z1t test file");
+/// `z1 compile --target ts,wasm`: every target from one run of the checks.
+fn compile_targets(
+    opts: &commands::compile::CompileOptions,
+    targets: &[commands::compile::CompileTarget],
+    source: Option<String>,
+    watch: bool,
+    stats: Option<StatsFormatArg>,
+    format: OutputFormat,
+) -> Result<()> {
+    let read = || {
+        fs::read_to_string(&opts.input_path)
+            .with_context(|| format!("Failed to read {}", opts.input_path.display()))
+    };
+    if watch {
+        format.require("compile --watch", &[])?;
+        let path = opts.input_path.clone();
+        return commands::watch::watch(&[path], Invalidation::Semantic, |_| {
+            commands::compile::compile_targets(opts, &read()?, targets).map(|report| report.print())
+        });
     }
-
-    // Parse tag filters if provided
-    let tags_include = if let Some(tags) = &args.tags {
-        tags.split(',').map(|s| s.trim().to_string()).collect()
-    } else {
-        vec![]
+    let source = match source {
+        Some(source) => source,
+        None => read()?,
     };
-
-    let config = z1_test::TestConfig {
-        tags_include,
-        ..Default::default()
+    let report = match commands::compile::compile_targets(opts, &source, targets) {
+        Ok(report) => report,
+        Err(err) if format.is_json() => {
+            print_compile_failure(&err, &opts.input_path, Some(&source))?;
+            Failure::of(&err).exit();
+        }
+        Err(err) if format == OutputFormat::Events => {
+            events::compile_failed(&opts.input_path, Some(&source), &err);
+            events::emit(&Event::Summary(Summary::error(&err, &compile_counts(1, 1))));
+            return Err(err);
+        }
+        Err(err) => return Err(err),
     };
+    if format.is_json() {
+        return output::print_json(&report);
+    }
+    if format == OutputFormat::Events {
+        events::compiled(&opts.input_path, &report.targets, Some(&report.meta));
+        events::emit(&Event::Summary(Summary::new(None, &compile_counts(1, 0))));
+        return Ok(());
+    }
+    if let Some(StatsFormatArg::Json) = stats {
+        let stats: Vec<_> = report.targets.iter().map(|report| &report.stats).collect();
+        return output::print_json(&stats);
+    }
+    report.print();
+    if opts.verify_reproducible {
+        let files: usize = report
+            .targets
+            .iter()
+            .map(|report| report.files().len())
+            .sum();
+        // and the metadata
+        print_reproducible(files + 1);
+    }
+    Ok(())
+}
 
-    let mut runner = z1_test::TestRunner::new(config);
-    let mut total_passed = 0;
-    let mut total_failed = 0;
-    let mut total_skipped = 0;
-    let mut all_failures = Vec::new();
-
-    for path in &args.paths {
-        println!("Running tests from: {path}");
-        let source = fs::read_to_string(path)?;
-        let file = z1_test::parse_test_file(&source)
-            .map_err(|e| anyhow::anyhow!("Failed to parse {path}: {e}"))?;
-
-        let results = runner.run_file(&file);
-
-        total_passed += results.passed;
-        total_failed += results.failed;
-        total_skipped += results.skipped;
-
-        if args.verbose {
-            for failure in &results.failures {
-                println!("  FAILED: {} - {}", failure.name, failure.error);
-            }
-        }
+/// Summary counts of compiling `cells` cells, `failed` of them with errors.
+fn compile_counts(cells: usize, failed: usize) -> [(&'static str, usize); 3] {
+    [
+        ("cells", cells),
+        ("compiled", cells - failed),
+        ("failed", failed),
+    ]
+}
 
-        all_failures.extend(results.failures);
+fn print_reproducible(files: usize) {
+    if output::show_summary() {
+        println!("✓ Reproducible: {files} file(s) identical across two builds");
     }
+}
 
-    println!("\nTest Results:");
-    println!("  Passed:  {total_passed}");
-    println!("  Failed:  {total_failed}");
-    println!("  Skipped: {total_skipped}");
+/// Print `{"error": ..., "diagnostics": [...]}` for a failed compilation in
+/// JSON mode, with the diagnostics of every check of `source`.
+fn print_compile_failure(err: &anyhow::Error, path: &Path, source: Option<&str>) -> Result<()> {
+    let mut failure = serde_json::json!({ "error": format!("{err:#}") });
+    if let Some(source) = source {
+        let context = commands::compile::CheckContext::for_cell(path).unwrap_or_default();
+        let display = path.to_string_lossy();
+        failure["diagnostics"] =
+            serde_json::to_value(commands::lint::lint_source(source, &display, &context))?;
+    }
+    output::print_json(&failure)
+}
 
-    if !all_failures.is_empty() {
-        println!("\nFailures:");
-        for failure in all_failures {
-            println!("  - {}: {}", failure.name, failure.error);
-        }
-        std::process::exit(1);
+fn handle_prov(cmd: commands::prov::ProvCommand, format: OutputFormat) -> Result<()> {
+    use commands::prov::ProvCommand;
+    format.require("prov", &[OutputFormat::Json])?;
+    match cmd {
+        ProvCommand::Log(args) => commands::prov::cmd_log(args, format),
+        ProvCommand::Verify(args) => commands::prov::cmd_verify(args, format),
+        ProvCommand::Keygen {
+            output,
+            name,
+            keystore,
+        } => commands::prov::cmd_keygen(output, name, keystore, format),
+        ProvCommand::Keys { keystore } => commands::prov::cmd_keys(keystore, format),
+        ProvCommand::Append(args) => commands::prov::cmd_append(args, format),
+        ProvCommand::Sign(args) => commands::prov::cmd_sign(args, format),
+        ProvCommand::Rotate(args) => commands::prov::cmd_rotate(args, format),
+        ProvCommand::Stamp(args) => commands::prov::cmd_stamp(args, format),
+        ProvCommand::SignKeyless(args) => commands::prov::cmd_sign_keyless(args, format),
+        ProvCommand::Merge(args) => commands::prov::cmd_merge(args, format),
+        ProvCommand::Compact(args) => commands::prov::cmd_compact(args, format),
     }
+}
 
-    Ok(())
+/// Outcome of `z1 fmt`, as printed by `--format json`.
+#[derive(Debug, Serialize)]
+struct FmtSummary {
+    files: Vec<FmtFile>,
+    /// Number of files that were (or, with --check, would be) reformatted
+    changed: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct FmtFile {
+    path: String,
+    changed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
 }
 
-fn handle_fmt(args: FmtArgs) -> Result<()> {
+fn handle_fmt(mut args: FmtArgs, format: OutputFormat) -> Result<()> {
+    format.require("fmt", &[OutputFormat::Json])?;
+    if format.is_json() && (args.stdout || args.watch) {
+        return Err(
+            Failure::Usage.error("--stdout and --watch cannot be combined with --format json")
+        );
+    }
     let mut targets = args.paths.clone();
     if let Some(list_path) = &args.files_from {
         targets.extend(read_file_list(list_path)?);
     }
+    args.defaults = commands::defaults::Defaults::for_paths(&targets)?;
 
     if args.stdin {
         if !targets.is_empty() {
-            anyhow::bail!("--stdin cannot be combined with positional paths or --files-from");
+            return Err(Failure::Usage
+                .error("--stdin cannot be combined with positional paths or --files-from"));
         }
         if !args.stdout && !args.check {
-            anyhow::bail!("--stdin requires --stdout or --check");
+            return Err(Failure::Usage.error("--stdin requires --stdout or --check"));
         }
-        format_stream(&args)?;
-        return Ok(());
+        let changed = format_stream(&args)?;
+        return finish_fmt(vec![("<stdin>".to_string(), Ok(changed))], &args, format);
     }
 
     if targets.is_empty() {
-        anyhow::bail!("provide at least one path, --files-from file, or --stdin");
+        return Err(
+            Failure::Usage.error("provide at least one path, --files-from file, or --stdin")
+        );
     }
 
-    if args.stdout && (args.check || targets.len() > 1) {
-        anyhow::bail!("--stdout only supported for single file without --check");
+    if args.watch {
+        // Directories are watched as such, so cells added later are formatted
+        let mut paths = Vec::new();
+        for target in &targets {
+            match commands::cells::is_glob(target) {
+                true => paths.extend(commands::cells::expand_arg(target)?.cells),
+                false => paths.push(PathBuf::from(target)),
+            }
+        }
+        return commands::watch::watch(&paths, Invalidation::Content, |cells| {
+            let cells: Vec<String> = cells
+                .iter()
+                .map(|cell| cell.display().to_string())
+                .collect();
+            format_files(&cells, &args, format)
+        });
     }
 
-    let mut changes_needed = false;
-    for path in targets {
-        let changed = format_file(&path, &args)?;
-        changes_needed |= changed;
+    let cells: Vec<String> = commands::cells::expand(&targets)?
+        .iter()
+        .map(|cell| cell.display().to_string())
+        .collect();
+    if args.stdout && (args.check || cells.len() > 1) {
+        return Err(Failure::Usage.error("--stdout only supported for single file without --check"));
     }
+    format_files(&cells, &args, format)
+}
 
-    if args.check && changes_needed {
-        anyhow::bail!("formatting changes needed");
+/// Format `paths` in parallel, with a summary when there are several.
+fn format_files(paths: &[String], args: &FmtArgs, format: OutputFormat) -> Result<()> {
+    let started = Instant::now();
+    let results: Vec<(String, Result<bool>)> = paths
+        .iter()
+        .cloned()
+        .zip(commands::cells::par_map(paths, |path| {
+            format_file(path, args)
+        }))
+        .collect();
+    if !format.is_json() && results.len() > 1 {
+        let changed = results
+            .iter()
+            .filter(|(_, result)| matches!(result, Ok(true)))
+            .count();
+        let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+        commands::cells::Summary {
+            command: "fmt",
+            cells: results.len(),
+            elapsed: started.elapsed(),
+            rows: vec![
+                (
+                    if args.check {
+                        "unformatted"
+                    } else {
+                        "reformatted"
+                    },
+                    changed,
+                ),
+                ("unchanged", results.len() - changed - failed),
+                ("failed", failed),
+            ],
+        }
+        .print();
     }
-
-    Ok(())
+    finish_fmt(results, args, format)
 }
 
-fn handle_hash(path: String) -> Result<()> {
-    let source = fs::read_to_string(&path)?;
-    let module = z1_parse::parse_module(&source).map_err(|e| {
-        let config = error_printer::ErrorPrinterConfig::default();
-        error_printer::print_parse_error(&e, &source, &path, &config);
-        anyhow::anyhow!("Parse failed")
-    })?;
-    let hashes = z1_hash::module_hashes(&module);
-    println!("semhash: {}", hashes.semantic);
-    println!("formhash: {}", hashes.format);
+/// Report which files changed, failing under --check if any did and
+/// whenever a file could not be formatted.
+fn finish_fmt(
+    results: Vec<(String, Result<bool>)>,
+    args: &FmtArgs,
+    format: OutputFormat,
+) -> Result<()> {
+    let changes_needed = results.iter().any(|(_, result)| matches!(result, Ok(true)));
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+    let failure = results
+        .iter()
+        .find_map(|(_, result)| result.as_ref().err())
+        .map(Failure::of);
+    if format.is_json() {
+        output::print_json(&FmtSummary {
+            changed: results
+                .iter()
+                .filter(|(_, result)| matches!(result, Ok(true)))
+                .count(),
+            files: results
+                .into_iter()
+                .map(|(path, result)| FmtFile {
+                    path,
+                    changed: matches!(result, Ok(true)),
+                    error: result.err().map(|err| format!("{err:#}")),
+                })
+                .collect(),
+        })?;
+        if let Some(failure) = failure {
+            failure.exit();
+        }
+        if args.check && changes_needed {
+            Failure::Check.exit();
+        }
+        return Ok(());
+    }
+
+    if results.len() == 1 {
+        if let Some((_, Err(err))) = results.into_iter().next() {
+            return Err(err);
+        }
+    } else if let Some(failure) = failure {
+        for (path, result) in &results {
+            if let Err(err) = result {
+                eprintln!("✗ {path}: {err:#}");
+            }
+        }
+        return Err(failure.error(format!("{failed} cell(s) could not be formatted")));
+    }
+
+    if args.check && changes_needed {
+        return Err(Failure::Check.error("formatting changes needed"));
+    }
+
     Ok(())
 }
 
@@ -404,45 +1045,58 @@ fn read_file_list(path: &str) -> Result<Vec<String>> {
         .collect())
 }
 
-fn format_stream(args: &FmtArgs) -> Result<()> {
+/// Format stdin; returns whether the formatted text differs from it.
+fn format_stream(args: &FmtArgs) -> Result<bool> {
     let mut source = String::new();
     io::stdin().read_to_string(&mut source)?;
-    let mode = args.mode.map(Into::into).unwrap_or(z1_fmt::Mode::Relaxed);
-    let options = z1_fmt::FmtOptions {
-        symmap_style: args.symmap.into(),
-    };
+    let _cell = crash::processing(Path::new(STDIN_NAME), &source);
+    let mode = args.mode(None);
+    let options = args.options();
     let module = z1_parse::parse_module(&source).map_err(|e| {
         let config = error_printer::ErrorPrinterConfig::default();
-        error_printer::print_parse_error(&e, &source, "<stdin>", &config);
-        anyhow::anyhow!("Parse failed")
+        error_printer::print_parse_error(&e, &source, STDIN_NAME, &config);
+        Failure::Parse.error("Parse failed")
     })?;
     let formatted = z1_fmt::format_module(&module, mode, &options)?;
-    if args.check {
-        if normalize_newlines(&formatted) != normalize_newlines(&source) {
-            anyhow::bail!("formatting changes needed");
-        }
-        return Ok(());
+    let (changed, formatted) = apply_formatting(&source, formatted, args);
+    if !args.check {
+        print!("{formatted}");
     }
-    print!("{formatted}");
-    Ok(())
+    Ok(changed)
+}
+
+/// Whether formatting changes `source`, and the text it becomes: the
+/// formatter's output, or with --minimal-edits the source with only the
+/// regions that differ replaced.
+fn apply_formatting(source: &str, formatted: String, args: &FmtArgs) -> (bool, String) {
+    if args.minimal_edits {
+        let edits = z1_fmt::minimal_edits(source, &formatted);
+        return (!edits.is_empty(), z1_fmt::apply_edits(source, &edits));
+    }
+    let changed = normalize_newlines(&formatted) != normalize_newlines(source);
+    (changed, formatted)
 }
 
 fn format_file(path: &str, args: &FmtArgs) -> Result<bool> {
-    let source = fs::read_to_string(path)?;
-    let mode = args
-        .mode
-        .map(Into::into)
-        .unwrap_or_else(|| infer_mode(Some(path)));
-    let options = z1_fmt::FmtOptions {
-        symmap_style: args.symmap.into(),
+    let mode = args.mode(Some(path));
+    let options = args.options();
+    let (source, formatted) = match commands::daemon::format(Path::new(path), mode, &options) {
+        Some(formatted) => formatted,
+        None => {
+            let source = fs::read_to_string(path)?;
+            let _cell = crash::processing(Path::new(path), &source);
+            let module = z1_parse::parse_module(&source).map_err(|e| {
+                // Cells are formatted in parallel; keep each report in one piece
+                let _stderr = io::stderr().lock();
+                let config = error_printer::ErrorPrinterConfig::default();
+                error_printer::print_parse_error(&e, &source, path, &config);
+                Failure::Parse.error("Parse failed")
+            })?;
+            let formatted = z1_fmt::format_module(&module, mode, &options)?;
+            (source, formatted)
+        }
     };
-    let module = z1_parse::parse_module(&source).map_err(|e| {
-        let config = error_printer::ErrorPrinterConfig::default();
-        error_printer::print_parse_error(&e, &source, path, &config);
-        anyhow::anyhow!("Parse failed")
-    })?;
-    let formatted = z1_fmt::format_module(&module, mode, &options)?;
-    let changed = normalize_newlines(&formatted) != normalize_newlines(&source);
+    let (changed, formatted) = apply_formatting(&source, formatted, args);
     if args.check {
         return Ok(changed);
     }
@@ -456,12 +1110,236 @@ fn format_file(path: &str, args: &FmtArgs) -> Result<bool> {
     Ok(changed)
 }
 
-fn handle_ctx(args: CtxArgs) -> Result<()> {
-    let source = fs::read_to_string(&args.path)?;
+/// Context estimate of a cell, as printed by `--format json`.
+#[derive(Debug, Serialize)]
+struct CtxSummary<'a> {
+    path: &'a str,
+    total_tokens: u32,
+    budget: Option<u32>,
+    within_budget: bool,
+    /// Estimate at the `--changed-only` baseline; absent for new cells
+    #[serde(skip_serializing_if = "Option::is_none")]
+    baseline_tokens: Option<u32>,
+    functions: Vec<CtxFunction<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct CtxFunction<'a> {
+    name: &'a str,
+    tokens: u32,
+}
+
+impl<'a> CtxSummary<'a> {
+    fn new(path: &'a str, estimate: &'a z1_ctx::CellEstimate) -> Self {
+        CtxSummary {
+            path,
+            total_tokens: estimate.total_tokens,
+            budget: estimate.budget,
+            within_budget: estimate
+                .budget
+                .map_or(true, |budget| estimate.total_tokens <= budget),
+            baseline_tokens: None,
+            functions: estimate
+                .functions
+                .iter()
+                .map(|function| CtxFunction {
+                    name: &function.name,
+                    tokens: function.tokens,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Estimate several cells in parallel and print one row per cell, failing
+/// if any cell does not parse or, unless --no-enforce, exceeds its budget.
+/// Against a `baseline`, only cells whose estimate grew fail their budget.
+fn handle_ctx_many(
+    args: &CtxArgs,
+    cells: Vec<String>,
+    baseline: Option<commands::changed::Baseline>,
+    format: OutputFormat,
+) -> Result<()> {
+    let started = Instant::now();
+    let config = z1_ctx::EstimateConfig {
+        chars_per_token: args
+            .chars_per_token
+            .unwrap_or(z1_ctx::DEFAULT_CHARS_PER_TOKEN),
+        enforce_budget: false,
+    };
+    let estimate = |source: &str| -> Result<z1_ctx::CellEstimate> {
+        let module = z1_parse::parse_module(source).map_err(|err| Failure::Parse.error(err))?;
+        Ok(z1_ctx::estimate_cell_with_config(&module, &config)?)
+    };
+    let estimates = commands::cells::par_map(&cells, |path| estimate(&fs::read_to_string(path)?));
+    // Estimate of each cell at the baseline, when it has the cell and it parses
+    let baselines: Vec<Option<u32>> = match &baseline {
+        Some(baseline) => commands::cells::par_map(&cells, |path| {
+            let source = baseline.source(Path::new(path))?;
+            estimate(&source).ok().map(|estimate| estimate.total_tokens)
+        }),
+        None => vec![None; cells.len()],
+    };
+
+    let over = |estimate: &z1_ctx::CellEstimate| {
+        estimate
+            .budget
+            .is_some_and(|budget| estimate.total_tokens > budget)
+    };
+    // Over budget and, against a baseline, grown since
+    let fails_budget = |estimate: &z1_ctx::CellEstimate, old: Option<u32>| {
+        over(estimate)
+            && (baseline.is_none() || old.map_or(true, |old| estimate.total_tokens > old))
+    };
+    let failed = estimates.iter().filter(|result| result.is_err()).count();
+    let over_budget = estimates
+        .iter()
+        .zip(&baselines)
+        .filter(|(result, old)| {
+            result
+                .as_ref()
+                .is_ok_and(|estimate| fails_budget(estimate, **old))
+        })
+        .count();
+    let failure = match estimates.iter().find_map(|result| result.as_ref().err()) {
+        Some(err) => Some(Failure::of(err)),
+        None if !args.no_enforce && over_budget > 0 => Some(Failure::Budget),
+        None => None,
+    };
+
+    if format.is_json() {
+        let mut summaries = Vec::new();
+        for ((path, result), old) in cells.iter().zip(&estimates).zip(&baselines) {
+            match result {
+                Ok(estimate) => summaries.push(CtxSummary {
+                    baseline_tokens: *old,
+                    ..CtxSummary::new(path, estimate)
+                }),
+                Err(err) => return Err(Failure::of(err).error(format!("{path}: {err}"))),
+            }
+        }
+        output::print_json(&summaries)?;
+        if let Some(failure) = failure {
+            failure.exit();
+        }
+        return Ok(());
+    }
+
+    let width = cells.iter().map(String::len).max().unwrap_or(0).max(5);
+    if output::show_details() && baseline.is_some() {
+        println!(
+            "{:<width$}  {:>8}  {:>8}  {:>8}  {:>8}  STATUS",
+            "CELL", "TOKENS", "BASELINE", "DELTA", "BUDGET"
+        );
+    } else if output::show_details() {
+        println!(
+            "{:<width$}  {:>8}  {:>8}  STATUS",
+            "CELL", "TOKENS", "BUDGET"
+        );
+    }
+    let mut total = 0;
+    for ((path, result), old) in cells.iter().zip(&estimates).zip(&baselines) {
+        let estimate = match result {
+            Ok(estimate) => estimate,
+            Err(err) => {
+                if output::show_details() {
+                    println!("{path:<width$}  {:>8}  {:>8}  error: {err}", "-", "-");
+                }
+                continue;
+            }
+        };
+        total += estimate.total_tokens;
+        if !output::show_details() {
+            continue;
+        }
+        let budget = estimate.budget.map_or("-".to_string(), |b| b.to_string());
+        let status = if fails_budget(estimate, *old) {
+            "EXCEEDS BUDGET"
+        } else if over(estimate) {
+            "over budget, not grown"
+        } else {
+            "ok"
+        };
+        if baseline.is_some() {
+            let (old, delta) = match old {
+                Some(old) => (
+                    old.to_string(),
+                    format!("{:+}", i64::from(estimate.total_tokens) - i64::from(*old)),
+                ),
+                None => ("new".to_string(), "-".to_string()),
+            };
+            println!(
+                "{path:<width$}  {:>8}  {old:>8}  {delta:>8}  {budget:>8}  {status}",
+                estimate.total_tokens
+            );
+        } else {
+            println!(
+                "{path:<width$}  {:>8}  {budget:>8}  {status}",
+                estimate.total_tokens
+            );
+        }
+        if args.verbose {
+            for function in &estimate.functions {
+                println!(
+                    "  {:<w$}  {:>8}",
+                    function.name,
+                    function.tokens,
+                    w = width - 2
+                );
+            }
+        }
+    }
+    if output::show_summary() {
+        println!("{:<width$}  {total:>8}", "total");
+    }
+    commands::cells::Summary {
+        command: "ctx",
+        cells: cells.len(),
+        elapsed: started.elapsed(),
+        rows: vec![
+            ("within budget", cells.len() - over_budget - failed),
+            ("over budget", over_budget),
+            ("failed", failed),
+        ],
+    }
+    .print();
+    if let Some(failure) = failure {
+        failure.exit();
+    }
+    Ok(())
+}
+
+fn handle_ctx(mut args: CtxArgs, format: OutputFormat) -> Result<()> {
+    format.require("ctx", &[OutputFormat::Json])?;
+    if args.chars_per_token.is_none() {
+        args.chars_per_token =
+            commands::defaults::Defaults::for_paths(&args.paths)?.chars_per_token;
+    }
+    if args.changed.changed_only {
+        let cells = args
+            .changed
+            .cells(&args.paths)?
+            .iter()
+            .map(|cell| commands::lint::display_path(cell))
+            .collect();
+        let baseline = args.changed.baseline()?;
+        return handle_ctx_many(&args, cells, Some(baseline), format);
+    }
+    let path = match args.paths.as_slice() {
+        [path] if !Path::new(path).is_dir() && !commands::cells::is_glob(path) => path,
+        _ => {
+            let cells = commands::cells::expand(&args.paths)?
+                .iter()
+                .map(|cell| cell.display().to_string())
+                .collect();
+            return handle_ctx_many(&args, cells, None, format);
+        }
+    };
+    let source = fs::read_to_string(path)?;
     let module = z1_parse::parse_module(&source).map_err(|e| {
         let config = error_printer::ErrorPrinterConfig::default();
-        error_printer::print_parse_error(&e, &source, &args.path, &config);
-        anyhow::anyhow!("Parse failed")
+        error_printer::print_parse_error(&e, &source, path, &config);
+        Failure::Parse.error("Parse failed")
     })?;
 
     let config = z1_ctx::EstimateConfig {
@@ -472,6 +1350,8 @@ fn handle_ctx(args: CtxArgs) -> Result<()> {
     };
 
     match z1_ctx::estimate_cell_with_config(&module, &config) {
+        Ok(estimate) if format.is_json() => output::print_json(&CtxSummary::new(path, &estimate)),
+        Ok(_) if !output::show_summary() => Ok(()),
         Ok(estimate) => {
             if args.verbose {
                 println!("{estimate}");
@@ -489,9 +1369,12 @@ fn handle_ctx(args: CtxArgs) -> Result<()> {
             }
             Ok(())
         }
+        Err(e) if format.is_json() => {
+            Err(commands::compile::ctx_failure(&e).error(format!("Context estimation failed: {e}")))
+        }
         Err(e) => {
             eprintln!("Context estimation failed: {e}");
-            std::process::exit(1);
+            commands::compile::ctx_failure(&e).exit();
         }
     }
 }
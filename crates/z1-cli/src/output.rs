@@ -0,0 +1,95 @@
+//! Output formats selected by the global `--format` flag.
+//!
+//! In `json` mode a command prints exactly one JSON document on stdout; when
+//! it fails before printing one, `{"error": "..."}` is printed instead.
+//! Human-oriented diagnostics still go to stderr. The global `--events` flag
+//! selects a fourth format, streamed [`events`](crate::events).
+//!
+//! How much text output a command prints is set by the global `--quiet` and
+//! `--summary-only` flags; JSON and SARIF documents are printed regardless.
+
+use std::sync::OnceLock;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::exit::Failure;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// A single JSON document on stdout
+    Json,
+    /// SARIF 2.1.0, for code scanning dashboards (lint only)
+    Sarif,
+    /// Newline-delimited JSON events on stdout, set by `--events` (see
+    /// [`events`](crate::events))
+    #[value(skip)]
+    Events,
+}
+
+impl OutputFormat {
+    pub fn is_json(self) -> bool {
+        self == OutputFormat::Json
+    }
+
+    /// Fail unless `command` supports `self`.
+    pub fn require(self, command: &str, supported: &[OutputFormat]) -> Result<()> {
+        if self != OutputFormat::Text && !supported.contains(&self) {
+            let flag = match self.to_possible_value() {
+                Some(value) => format!("--format {}", value.get_name()),
+                None => "--events".to_string(),
+            };
+            return Err(Failure::Usage.error(format!("`{command}` does not support {flag}")));
+        }
+        Ok(())
+    }
+}
+
+/// Print `value` as pretty-printed JSON on stdout.
+pub fn print_json(value: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Print `{"error": "<err>"}` for a command that failed in JSON mode.
+pub fn print_error(err: &anyhow::Error) {
+    let error = serde_json::json!({ "error": format!("{err:#}") });
+    println!("{error:#}");
+}
+
+/// Text output level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Verbosity {
+    #[default]
+    Normal,
+    /// Totals only, without per-cell results (`--summary-only`)
+    SummaryOnly,
+    /// Errors only (`--quiet`)
+    Quiet,
+}
+
+static VERBOSITY: OnceLock<Verbosity> = OnceLock::new();
+
+/// Set the text output level; like colors, it is process-wide and set once
+/// by `main` before a command runs.
+pub fn set_verbosity(verbosity: Verbosity) {
+    let _ = VERBOSITY.set(verbosity);
+}
+
+pub fn verbosity() -> Verbosity {
+    VERBOSITY.get().copied().unwrap_or_default()
+}
+
+/// Whether per-cell results and diagnostics are printed.
+pub fn show_details() -> bool {
+    verbosity() == Verbosity::Normal
+}
+
+/// Whether totals and success messages are printed.
+pub fn show_summary() -> bool {
+    verbosity() != Verbosity::Quiet
+}
@@ -0,0 +1,270 @@
+//! Logging and per-stage timings, set up by the global `--trace-timings`
+//! and `--log-json` flags.
+//!
+//! Each stage of the compile pipeline (parse, resolve, typeck, effects, ctx,
+//! policy, lower, optimize, codegen) runs in a span with the [`STAGE`]
+//! target, inside a `cell` span naming the cell. `--trace-timings` adds up
+//! the time spent in each stage, over every cell compiled, and prints the
+//! breakdown on stderr once the command is done. `--log-json` replaces the
+//! text log with one JSON object per line on stderr: events, and a record
+//! with the elapsed time of every span that closes. Both logs are filtered
+//! by `RUST_LOG`, which defaults to errors only for text and to `info` for
+//! JSON.
+
+use std::fmt;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::filter::EnvFilter;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+/// Target of the spans timing pipeline stages.
+pub const STAGE: &str = "z1::stage";
+
+/// Install the global subscriber.
+pub fn init(trace_timings: bool, log_json: bool) {
+    let filter = |default: &str| {
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default))
+    };
+    tracing_subscriber::registry()
+        .with((!log_json).then(|| tracing_subscriber::fmt::layer().with_filter(filter("error"))))
+        .with(log_json.then(|| JsonLog.with_filter(filter("info"))))
+        .with(trace_timings.then_some(Timings))
+        .init();
+}
+
+/// Time spent in one stage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StageTiming {
+    pub stage: &'static str,
+    pub total: Duration,
+    /// Times the stage ran, e.g. once per cell
+    pub runs: u32,
+}
+
+/// Stage timings in the order the stages first ran.
+static TIMINGS: Mutex<Vec<StageTiming>> = Mutex::new(Vec::new());
+
+/// The stage timings recorded so far.
+pub fn timings() -> Vec<StageTiming> {
+    TIMINGS
+        .lock()
+        .map(|timings| timings.clone())
+        .unwrap_or_default()
+}
+
+/// Print the stage timings as a table on stderr.
+pub fn print_timings() {
+    let timings = timings();
+    if timings.is_empty() {
+        eprintln!("No pipeline stage ran");
+        return;
+    }
+    eprintln!("{:<10} {:>12} {:>6}", "stage", "time", "runs");
+    for timing in &timings {
+        eprintln!(
+            "{:<10} {:>12} {:>6}",
+            timing.stage,
+            format_duration(timing.total),
+            timing.runs
+        );
+    }
+    let total: Duration = timings.iter().map(|timing| timing.total).sum();
+    eprintln!("{:<10} {:>12}", "total", format_duration(total));
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.3}ms", duration.as_secs_f64() * 1000.0)
+}
+
+fn record_timing(stage: &'static str, elapsed: Duration) {
+    let Ok(mut timings) = TIMINGS.lock() else {
+        return;
+    };
+    match timings.iter_mut().find(|timing| timing.stage == stage) {
+        Some(timing) => {
+            timing.total += elapsed;
+            timing.runs += 1;
+        }
+        None => timings.push(StageTiming {
+            stage,
+            total: elapsed,
+            runs: 1,
+        }),
+    }
+}
+
+/// When a span was last entered.
+struct Entered(Instant);
+
+/// Layer adding up the time spent in [`STAGE`] spans, by span name.
+struct Timings;
+
+impl<S> Layer<S> for Timings
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if span.metadata().target() == STAGE {
+                span.extensions_mut().insert(Entered(Instant::now()));
+            }
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(Entered(start)) = span.extensions_mut().remove::<Entered>() {
+                record_timing(span.name(), start.elapsed());
+            }
+        }
+    }
+}
+
+/// Fields of a span, with when it was created.
+struct SpanData {
+    fields: Map<String, Value>,
+    created: Instant,
+}
+
+/// Layer printing events and closed spans as JSON lines on stderr.
+struct JsonLog;
+
+impl JsonLog {
+    fn write(record: Value) {
+        let mut stderr = std::io::stderr().lock();
+        let _ = writeln!(stderr, "{record}");
+    }
+}
+
+impl<S> Layer<S> for JsonLog
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = JsonFields::default();
+        attrs.record(&mut fields);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanData {
+                fields: fields.0,
+                created: Instant::now(),
+            });
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(data) = span.extensions_mut().get_mut::<SpanData>() {
+                let mut fields = JsonFields(std::mem::take(&mut data.fields));
+                values.record(&mut fields);
+                data.fields = fields.0;
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = JsonFields::default();
+        event.record(&mut fields);
+        let spans: Vec<Value> = ctx
+            .event_scope(event)
+            .into_iter()
+            .flat_map(|scope| scope.from_root())
+            .map(|span| {
+                let mut entry = Map::new();
+                entry.insert("name".into(), span.name().into());
+                if let Some(data) = span.extensions().get::<SpanData>() {
+                    entry.extend(data.fields.clone());
+                }
+                Value::Object(entry)
+            })
+            .collect();
+        let metadata = event.metadata();
+        JsonLog::write(serde_json::json!({
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "level": metadata.level().as_str(),
+            "target": metadata.target(),
+            "fields": fields.0,
+            "spans": spans,
+        }));
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let extensions = span.extensions();
+        let Some(data) = extensions.get::<SpanData>() else {
+            return;
+        };
+        let metadata = span.metadata();
+        JsonLog::write(serde_json::json!({
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "level": metadata.level().as_str(),
+            "target": metadata.target(),
+            "span": span.name(),
+            "fields": data.fields,
+            "elapsed_ms": data.created.elapsed().as_secs_f64() * 1000.0,
+        }));
+    }
+}
+
+/// Field visitor collecting values as JSON.
+#[derive(Default)]
+struct JsonFields(Map<String, Value>);
+
+impl Visit for JsonFields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().into(), format!("{value:?}").into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timings_add_up_per_stage() {
+        let subscriber = tracing_subscriber::registry().with(Timings);
+        tracing::subscriber::with_default(subscriber, || {
+            for _ in 0..2 {
+                let _cell = tracing::info_span!("cell").entered();
+                let _stage = tracing::info_span!(target: STAGE, "unit_test_stage").entered();
+            }
+        });
+        let timing = timings()
+            .into_iter()
+            .find(|timing| timing.stage == "unit_test_stage")
+            .expect("stage timed");
+        assert_eq!(timing.runs, 2);
+        assert!(timings().iter().all(|timing| timing.stage != "cell"));
+    }
+}
@@ -0,0 +1,147 @@
+//! Integration tests for `z1 bench` thresholds and history
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+const CELL: &str = "m calc:1.0\nf add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }\n";
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run z1")
+}
+
+fn git(args: &[&str], dir: &Path) {
+    let status = Command::new("git")
+        .args([
+            "-c",
+            "user.name=bench",
+            "-c",
+            "user.email=bench@example.com",
+        ])
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .expect("Failed to run git");
+    assert!(status.success());
+}
+
+fn bench(dir: &Path) -> Output {
+    z1(
+        &[
+            "bench",
+            "--cell",
+            "calc.z1c",
+            "--no-commands",
+            "--thresholds",
+            "thresholds.toml",
+        ],
+        dir,
+    )
+}
+
+#[test]
+fn bench_asserts_thresholds_and_records_history() {
+    let dir = TempDir::new().unwrap();
+    git(&["init", "-q"], dir.path());
+    git(&["commit", "-q", "--allow-empty", "-m", "init"], dir.path());
+    fs::write(dir.path().join("calc.z1c"), CELL).unwrap();
+    let thresholds = |max_tokens: u32| {
+        format!(
+            "regression_percent = 50\n\n[[cell]]\npath = \"calc.z1c\"\n\
+             max_tokens = {max_tokens}\nmax_wasm_bytes = 100000\n"
+        )
+    };
+    fs::write(dir.path().join("thresholds.toml"), thresholds(1000)).unwrap();
+
+    let output = bench(dir.path());
+    assert_eq!(output.status.code(), Some(0), "{output:?}");
+    let report: serde_json::Value = serde_json::from_str(
+        &fs::read_to_string(dir.path().join("benchmarks/latest.json")).unwrap(),
+    )
+    .unwrap();
+    assert!(report["commands"].as_array().unwrap().is_empty());
+    assert_eq!(report["measurements"][0]["cell"], "calc.z1c");
+    assert!(report["measurements"][0]["wasm_bytes"].as_u64().unwrap() > 0);
+    assert!(report["front_end"]["tokens"].as_u64().unwrap() > 0);
+    assert!(report.get("front_end_speedup").is_none());
+    let history = dir.path().join("benchmarks/history.jsonl");
+    assert_eq!(fs::read_to_string(&history).unwrap().lines().count(), 1);
+
+    // Over a limit: the run fails and is not recorded
+    fs::write(dir.path().join("thresholds.toml"), thresholds(1)).unwrap();
+    let output = bench(dir.path());
+    assert_eq!(output.status.code(), Some(10));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("calc.z1c: "), "{stderr}");
+    assert!(stderr.contains("tokens, over the limit of 1"), "{stderr}");
+    assert_eq!(fs::read_to_string(&history).unwrap().lines().count(), 1);
+
+    // Grown past the allowed percentage since the last recorded run
+    fs::write(dir.path().join("thresholds.toml"), thresholds(1000)).unwrap();
+    fs::write(
+        &history,
+        "{\"git_head\":\"0\",\"timestamp\":\"t\",\"cells\":[{\"cell\":\"calc.z1c\",\
+         \"tokens\":2,\"wasm_bytes\":100000,\"compile_ms\":100000.0}],\
+         \"front_end\":{\"cell_bytes\":1,\"tokens\":1,\"lex_ms\":100000.0,\"parse_ms\":100000.0}}\n",
+    )
+    .unwrap();
+    let output = bench(dir.path());
+    assert_eq!(output.status.code(), Some(10));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("calc.z1c: tokens grew"), "{stderr}");
+    assert!(stderr.contains("more than the allowed 50%"), "{stderr}");
+    assert!(!stderr.contains("WASM bytes grew"), "{stderr}");
+    // Front end timings are reported against the last recorded run
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("as fast as at 0"), "{stdout}");
+
+    let output = z1(
+        &[
+            "bench",
+            "--cell",
+            "calc.z1c",
+            "--no-commands",
+            "--thresholds",
+            "none.toml",
+        ],
+        dir.path(),
+    );
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn bench_records_only_given_thresholds_or_record() {
+    let dir = TempDir::new().unwrap();
+    git(&["init", "-q"], dir.path());
+    git(&["commit", "-q", "--allow-empty", "-m", "init"], dir.path());
+    fs::write(dir.path().join("calc.z1c"), CELL).unwrap();
+    let history = dir.path().join("benchmarks/history.jsonl");
+
+    // The default thresholds are asserted but the run is not recorded
+    fs::create_dir(dir.path().join("benchmarks")).unwrap();
+    fs::write(
+        dir.path().join("benchmarks/thresholds.toml"),
+        "[[cell]]\npath = \"calc.z1c\"\nmax_tokens = 1000\n",
+    )
+    .unwrap();
+    let output = z1(
+        &["bench", "--cell", "calc.z1c", "--no-commands"],
+        dir.path(),
+    );
+    assert_eq!(output.status.code(), Some(0), "{output:?}");
+    assert!(!history.exists());
+
+    let output = z1(
+        &["bench", "--cell", "calc.z1c", "--no-commands", "--record"],
+        dir.path(),
+    );
+    assert_eq!(output.status.code(), Some(0), "{output:?}");
+    assert_eq!(fs::read_to_string(&history).unwrap().lines().count(), 1);
+}
@@ -0,0 +1,165 @@
+//! Integration tests for `z1 build`
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+fn z1_build(dir: &Path) -> Output {
+    Command::new("cargo")
+        .args(["run", "-p", "z1-cli", "--", "build"])
+        .arg(dir)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("Failed to run z1 build")
+}
+
+/// Modules rebuilt by a successful `z1 build`, in build order.
+fn rebuilt(dir: &Path) -> Vec<String> {
+    let output = z1_build(dir);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        output.status.success(),
+        "{stdout}{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("Building "))
+        .map(|line| line.split(' ').next().unwrap().to_string())
+        .collect()
+}
+
+fn write_lib(dir: &Path, signature: &str, body: &str) {
+    fs::write(
+        dir.join("lib.z1c"),
+        format!("m app.lib:1.0 ctx=100 caps=[]\nf {signature} eff [pure] {{ {body} }}\n"),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_build_rebuilds_only_affected_cells() {
+    let dir = TempDir::new().unwrap();
+    write_lib(dir.path(), "scale(x: U32)->U32", "ret x;");
+    fs::write(
+        dir.path().join("main.z1c"),
+        "m app.main:1.0 ctx=100 caps=[]\nu \"app/lib\" as L only [scale]\nf main()->Unit eff [pure] { ret Unit }\n",
+    )
+    .unwrap();
+
+    assert_eq!(rebuilt(dir.path()), ["app.lib", "app.main"]);
+    assert!(dir.path().join(".z1/out/ts/app_main.ts").exists());
+    assert!(rebuilt(dir.path()).is_empty());
+
+    // Body edit: the API is unchanged, so dependents stay up to date
+    write_lib(dir.path(), "scale(x: U32)->U32", "ret x + 1;");
+    assert_eq!(rebuilt(dir.path()), ["app.lib"]);
+
+    // Signature edit: dependents rebuild
+    write_lib(dir.path(), "scale(x: U32, y: U32)->U32", "ret x;");
+    assert_eq!(rebuilt(dir.path()), ["app.lib", "app.main"]);
+}
+
+#[test]
+fn test_build_targets_into_separate_trees() {
+    let dir = TempDir::new().unwrap();
+    write_lib(dir.path(), "scale(x: U32)->U32", "ret x;");
+    let src = dir.path().join("src");
+    fs::create_dir(&src).unwrap();
+    fs::write(
+        src.join("main.z1c"),
+        "m app.main:1.0 ctx=100 caps=[]\nu \"app/lib\" as L only [scale]\nf main()->Unit eff [pure] { ret Unit }\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(["build", "--target", "type-script,wasm"])
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    assert!(stdout.contains("Built 4 cell(s), 0 up to date"), "{stdout}");
+    let out = dir.path().join(".z1/out");
+    for file in [
+        "ts/app_lib.ts",
+        "ts/app_main.ts",
+        "wasm/app_lib.wasm",
+        "wasm/app_main.wasm",
+    ] {
+        assert!(out.join(file).exists(), "missing {file}");
+    }
+
+    // The workspace root is found from a subdirectory, and each target keeps
+    // its own build state
+    let output = Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(["build", "--target", "wasm"])
+        .current_dir(&src)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Built 0 cell(s), 2 up to date"), "{stdout}");
+}
+
+#[test]
+fn test_build_skips_dependents_of_failed_cells() {
+    let dir = TempDir::new().unwrap();
+    // Exceeds the policy's parameter limit
+    write_lib(
+        dir.path(),
+        "scale(a: U32, b: U32, c: U32, d: U32, e: U32, f: U32, g: U32)->U32",
+        "ret a;",
+    );
+    fs::write(
+        dir.path().join("main.z1c"),
+        "m app.main:1.0 ctx=100 caps=[]\nu \"app/lib\" as L only [scale]\nf main()->Unit eff [pure] { ret Unit }\n",
+    )
+    .unwrap();
+
+    let output = z1_build(dir.path());
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("main.z1c: dependency `app.lib` failed to build"),
+        "{stderr}"
+    );
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("Building app.main"));
+}
+
+#[test]
+fn test_build_reuses_checks_of_unchanged_cells() {
+    let dir = TempDir::new().unwrap();
+    write_lib(dir.path(), "scale(x: U32)->U32", "ret x;");
+    fs::write(
+        dir.path().join("main.z1c"),
+        "m app.main:1.0 ctx=100 caps=[]\nu \"app/lib\" as L only [scale]\nf main()->Unit eff [pure] { ret Unit }\n",
+    )
+    .unwrap();
+    let build = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+            .arg("build")
+            .args(args)
+            .arg(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let stdout = build(&[]);
+    assert!(stdout.contains("0 check(s) reused, 2 run"), "{stdout}");
+    // A new target compiles every cell again but checks none
+    let stdout = build(&["--target", "wasm"]);
+    assert!(
+        stdout.contains("Built 2 cell(s), 0 up to date; 2 check(s) reused, 0 run"),
+        "{stdout}"
+    );
+
+    write_lib(dir.path(), "scale(x: U32)->U32", "ret x + 1;");
+    let stdout = build(&["--target", "type-script,wasm"]);
+    assert!(stdout.contains("0 check(s) reused, 1 run"), "{stdout}");
+
+    let stdout = build(&["--force"]);
+    assert!(stdout.contains("0 check(s) reused, 2 run"), "{stdout}");
+}
@@ -0,0 +1,112 @@
+//! Integration tests for directory, glob and `.z1ignore` arguments of
+//! `z1 fmt`, `z1 hash` and `z1 ctx`
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run z1")
+}
+
+fn assert_success(output: &Output) {
+    assert!(
+        output.status.success(),
+        "stdout: {}\nstderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+fn write(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
+
+/// Three unformatted cells, one of them under an ignored directory.
+fn setup() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    for (path, module) in [
+        ("src/main.z1c", "app.main"),
+        ("src/util/text.z1c", "app.util.text"),
+        ("src/gen/out.z1c", "app.gen.out"),
+    ] {
+        write(
+            &dir.path().join(path),
+            &format!("m {module}:1.0   ctx=8 caps=[]\nf id(a: U32)->U32 eff [pure] {{ ret a; }}\n"),
+        );
+    }
+    write(&dir.path().join(".z1ignore"), "# generated\ngen/\n");
+    dir
+}
+
+#[test]
+fn test_fmt_walks_directories_and_globs() {
+    let dir = setup();
+    let generated = fs::read_to_string(dir.path().join("src/gen/out.z1c")).unwrap();
+
+    let output = z1(
+        &["fmt", "--check", "--format", "json", "src/**/*.z1c"],
+        dir.path(),
+    );
+    assert_eq!(output.status.code(), Some(10));
+    let summary: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let files: Vec<&str> = summary["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|file| file["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(files, ["src/main.z1c", "src/util/text.z1c"]);
+
+    let output = z1(&["fmt", "."], dir.path());
+    assert_success(&output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("fmt: 2 cell(s)"), "{stderr}");
+    assert!(stderr.contains("reformatted       2"), "{stderr}");
+    assert_success(&z1(&["fmt", "--check", "src"], dir.path()));
+    assert_eq!(
+        fs::read_to_string(dir.path().join("src/gen/out.z1c")).unwrap(),
+        generated
+    );
+
+    let output = z1(&["fmt", "src/**/*.z1r"], dir.path());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no cells match"));
+}
+
+#[test]
+fn test_hash_and_ctx_expand_globs() {
+    let dir = setup();
+
+    let output = z1(&["hash", "src/**/*.z1c"], dir.path());
+    assert_success(&output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let names: Vec<&str> = stdout
+        .lines()
+        .map(|line| line.split_once(' ').unwrap().1)
+        .collect();
+    assert_eq!(names, ["main.z1c", "util/text.z1c"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("hash: 2 cell(s)"));
+
+    let output = z1(&["ctx", "src"], dir.path());
+    assert_eq!(output.status.code(), Some(6));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("CELL"), "{stdout}");
+    assert!(stdout.contains("EXCEEDS BUDGET"), "{stdout}");
+    assert!(!stdout.contains("gen/out.z1c"));
+    assert_success(&z1(&["ctx", "--no-enforce", "src"], dir.path()));
+
+    let output = z1(
+        &["ctx", "--no-enforce", "--format", "json", "src"],
+        dir.path(),
+    );
+    assert_success(&output);
+    let estimates: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(estimates.as_array().unwrap().len(), 2);
+}
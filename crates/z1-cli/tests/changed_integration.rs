@@ -0,0 +1,154 @@
+//! Integration tests for `--changed-only` on `z1 hash`, `z1 ctx` and `z1 lint`
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use tempfile::TempDir;
+
+fn z1(args: &[&str], dir: &Path, stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .env("Z1_NO_DAEMON", "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run z1");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn git(args: &[&str], dir: &Path) {
+    let status = Command::new("git")
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .stdout(Stdio::null())
+        .status()
+        .expect("Failed to run git");
+    assert!(status.success(), "git {args:?} failed");
+}
+
+/// A repository with two committed cells, `net.z1c` declaring an unused
+/// capability.
+fn repo() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("net.z1c"),
+        "m app:1.0 ctx=100 caps=[net]\nf add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("other.z1c"),
+        "m other:1.0 ctx=100 caps=[time]\nf one()->U32 eff [pure] { ret 1; }\n",
+    )
+    .unwrap();
+    git(&["init", "-q"], dir.path());
+    git(&["add", "."], dir.path());
+    git(&["commit", "-q", "-m", "init"], dir.path());
+    dir
+}
+
+#[test]
+fn lint_reports_only_new_diagnostics() {
+    let dir = repo();
+    // Swap the unused capability for another and add a cell
+    fs::write(
+        dir.path().join("net.z1c"),
+        "m app:1.0 ctx=100 caps=[fs]\nf add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("new.z1c"),
+        "m fresh:1.0 ctx=100\nf two()->U32 eff [pure] { ret 2; }\n",
+    )
+    .unwrap();
+
+    let changed = "net.z1c\nnew.z1c\nREADME.md\ndeleted.z1c\n";
+    let output = z1(&["lint", "--changed-only"], dir.path(), changed);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stdout}{stderr}");
+    assert!(stderr.contains("'fs'"), "{stderr}");
+    assert!(!stderr.contains("'net'"), "{stderr}");
+    assert!(!stderr.contains("'time'"), "{stderr}");
+    assert!(
+        stdout.contains(
+            "Linted 2 changed cell(s): 0 new error(s), 1 new warning(s), 1 resolved since HEAD"
+        ),
+        "{stdout}"
+    );
+
+    let output = z1(
+        &["lint", "--changed-only", "--deny-warnings", "net.z1c"],
+        dir.path(),
+        "",
+    );
+    assert!(!output.status.success());
+
+    let output = z1(
+        &["--format", "json", "lint", "--changed-only", "net.z1c"],
+        dir.path(),
+        "",
+    );
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["baseline"], "HEAD");
+    assert_eq!(report["resolved"], 1);
+    assert_eq!(report["diagnostics"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn hash_and_ctx_compare_with_baseline() {
+    let dir = repo();
+    fs::write(
+        dir.path().join("net.z1c"),
+        "m app:1.0 ctx=100 caps=[net]\n\nf add(a: U32, b: U32)->U32 eff [pure] {\n  ret a + b;\n}\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("other.z1c"),
+        "m other:1.0 ctx=100 caps=[time]\nf one()->U32 eff [pure] { ret 1; }\nf two()->U32 eff [pure] { ret 2; }\n",
+    )
+    .unwrap();
+
+    let changed = "net.z1c\nother.z1c\n";
+    let output = z1(&["hash", "--changed-only"], dir.path(), changed);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    assert!(stdout.contains(" net.z1c (layout-only)"), "{stdout}");
+    assert!(stdout.contains(" other.z1c (api)"), "{stdout}");
+
+    let output = z1(&["ctx", "--changed-only", "other.z1c"], dir.path(), "");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    assert!(stdout.contains("BASELINE"), "{stdout}");
+    let row = stdout
+        .lines()
+        .find(|line| line.starts_with("other.z1c"))
+        .unwrap();
+    assert!(
+        row.split_whitespace().nth(3).unwrap().starts_with('+'),
+        "{row}"
+    );
+
+    let output = z1(
+        &[
+            "hash",
+            "--changed-only",
+            "--baseline",
+            "no-such-rev",
+            "net.z1c",
+        ],
+        dir.path(),
+        "",
+    );
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -1,8 +1,9 @@
 //! Integration tests for z1c compile command
 
 use std::fs;
+use std::io::Write;
 use std::path::PathBuf;
-use std::process::Command;
+use std::process::{Command, Output, Stdio};
 use tempfile::TempDir;
 
 fn z1_command() -> Command {
@@ -133,43 +134,327 @@ fn test_binary_flag_requires_wasm_target() {
     );
 }
 
-// NOTE: This test is disabled because WAT generation has known issues that cause validation failures
-// The binary generation feature works correctly; the issue is in the upstream WAT generation
-// #[test]
-// fn test_binary_output_is_valid_wasm() {
-//     let (_dir, input) = setup_test_cell(simple_valid_cell());
-//     let output = input.with_extension("wasm");
-//
-//     // Compile with checks enabled to ensure validity
-//     let status = z1_command()
-//         .args(&[
-//             "compile",
-//             input.to_str().unwrap(),
-//             "--target",
-//             "wasm",
-//             "--binary",
-//             "--output",
-//             output.to_str().unwrap(),
-//             "--check",
-//         ])
-//         .status()
-//         .expect("Failed to run z1 compile");
-//
-//     assert!(status.success(), "Compilation with checks should succeed");
-//
-//     let binary = fs::read(&output).expect("Should read binary");
-//
-//     // Use wasmparser to validate
-//     use wasmparser::Validator;
-//     let mut validator = Validator::new();
-//     let result = validator.validate_all(&binary);
-//
-//     assert!(
-//         result.is_ok(),
-//         "Generated WASM binary should be valid: {:?}",
-//         result.err()
-//     );
-// }
+#[test]
+fn test_binary_output_is_valid_wasm() {
+    let (_dir, input) = setup_test_cell(simple_valid_cell());
+    let output = input.with_extension("wasm");
+
+    // Compile with checks enabled to ensure validity
+    let status = z1_command()
+        .args([
+            "compile",
+            input.to_str().unwrap(),
+            "--target",
+            "wasm",
+            "--binary",
+            "--output",
+            output.to_str().unwrap(),
+            "--check",
+        ])
+        .status()
+        .expect("Failed to run z1 compile");
+
+    assert!(status.success(), "Compilation with checks should succeed");
+
+    let binary = fs::read(&output).expect("Should read binary");
+
+    // Use wasmparser to validate
+    use wasmparser::Validator;
+    let mut validator = Validator::new();
+    let result = validator.validate_all(&binary);
+
+    assert!(
+        result.is_ok(),
+        "Generated WASM binary should be valid: {:?}",
+        result.err()
+    );
+}
+
+#[test]
+fn test_wat_output_for_cell_with_imports_is_valid_wasm() {
+    let cell = r#"module test : 1.0
+  caps = [net]
+
+use "std/http" as H only [listen, Req]
+
+fn start(port: U16) -> Unit
+  eff [net]
+{
+  ret Unit;
+}
+"#;
+    let (_dir, input) = setup_test_cell(cell);
+    let output = input.with_extension("wat");
+
+    let status = z1_command()
+        .args([
+            "compile",
+            input.to_str().unwrap(),
+            "--target",
+            "wasm",
+            "--output",
+            output.to_str().unwrap(),
+        ])
+        .status()
+        .expect("Failed to run z1 compile");
+
+    assert!(status.success(), "Compilation should succeed");
+
+    let wat_text = fs::read_to_string(&output).expect("Should read WAT");
+    let binary = wat::parse_str(&wat_text).expect("WAT should assemble");
+    let mut validator = wasmparser::Validator::new();
+    assert!(
+        validator.validate_all(&binary).is_ok(),
+        "WAT output should validate"
+    );
+}
+
+#[test]
+fn test_no_validate_flag_is_accepted() {
+    let (_dir, input) = setup_test_cell(simple_valid_cell());
+    let output = input.with_extension("wasm");
+
+    let status = z1_command()
+        .args([
+            "compile",
+            input.to_str().unwrap(),
+            "--target",
+            "wasm",
+            "--binary",
+            "--no-validate",
+            "--output",
+            output.to_str().unwrap(),
+        ])
+        .status()
+        .expect("Failed to run z1 compile");
+
+    assert!(
+        status.success(),
+        "Compilation with --no-validate should succeed"
+    );
+    assert!(output.exists(), "Output .wasm file should be created");
+}
+
+#[test]
+fn test_component_flag_writes_component_and_wit() {
+    let (_dir, input) = setup_test_cell(simple_valid_cell());
+    let output = input.with_extension("wasm");
+
+    let status = z1_command()
+        .args([
+            "compile",
+            input.to_str().unwrap(),
+            "--target",
+            "wasm",
+            "--component",
+            "--output",
+            output.to_str().unwrap(),
+        ])
+        .status()
+        .expect("Failed to run z1 compile");
+
+    assert!(status.success(), "Component compilation should succeed");
+
+    let bytes = fs::read(&output).expect("Failed to read component");
+    assert!(
+        wasmparser::Parser::is_component(&bytes),
+        "Output should be a component, not a core module"
+    );
+
+    let wit = fs::read_to_string(output.with_extension("wit")).expect("Failed to read .wit");
+    assert!(wit.contains("package z1:test@1.0.0;"));
+    assert!(wit.contains("export add: func(x: u32, y: u32) -> u32;"));
+}
+
+#[test]
+fn test_debug_flag_embeds_source_lines() {
+    let (_dir, input) = setup_test_cell(simple_valid_cell());
+    let output = input.with_extension("wasm");
+
+    let status = z1_command()
+        .args([
+            "compile",
+            input.to_str().unwrap(),
+            "--target",
+            "wasm",
+            "--binary",
+            "--debug",
+            "--output",
+            output.to_str().unwrap(),
+        ])
+        .status()
+        .expect("Failed to run z1 compile");
+
+    assert!(status.success(), "Debug compilation should succeed");
+
+    // DWARF line info refers to the WAT written next to the binary
+    let wat = fs::read_to_string(output.with_extension("wat")).expect("Failed to read .wat");
+    assert!(
+        wat.contains(":5\n  (func $add"),
+        "WAT should note the fn line"
+    );
+
+    let bytes = fs::read(&output).expect("Failed to read output");
+    let custom_sections: Vec<String> = wasmparser::Parser::new(0)
+        .parse_all(&bytes)
+        .flatten()
+        .filter_map(|payload| match payload {
+            wasmparser::Payload::CustomSection(reader) => Some(reader.name().to_string()),
+            _ => None,
+        })
+        .collect();
+    assert!(custom_sections.iter().any(|s| s == "name"));
+    assert!(custom_sections.iter().any(|s| s == "z1.lines"));
+    assert!(custom_sections.iter().any(|s| s == ".debug_line"));
+}
+
+#[test]
+fn test_emit_glue_writes_js_and_abi_reference() {
+    let (_dir, input) = setup_test_cell(simple_valid_cell());
+    let output = input.with_extension("wasm");
+
+    let status = z1_command()
+        .args([
+            "compile",
+            input.to_str().unwrap(),
+            "--target",
+            "wasm",
+            "--binary",
+            "--emit-glue",
+            "--output",
+            output.to_str().unwrap(),
+        ])
+        .status()
+        .expect("Failed to run z1 compile");
+
+    assert!(
+        status.success(),
+        "Compilation with --emit-glue should succeed"
+    );
+
+    let glue = fs::read_to_string(output.with_extension("js")).expect("Failed to read glue");
+    assert!(glue.contains("export async function instantiate(source, imports = {}) {"));
+    assert!(glue.contains("return ex.add(x, y) >>> 0;"));
+
+    let abi = fs::read_to_string(output.with_extension("abi.md")).expect("Failed to read ABI");
+    assert!(abi.contains("| `add` | `x: U32` (i32), `y: U32` (i32) | U32 (i32) |"));
+}
+
+#[test]
+fn test_wasm_features_gc_omits_linear_memory() {
+    let (_dir, input) = setup_test_cell(simple_valid_cell());
+    let output = input.with_extension("wat");
+
+    let status = z1_command()
+        .args([
+            "compile",
+            input.to_str().unwrap(),
+            "--target",
+            "wasm",
+            "--wasm-features",
+            "gc",
+            "--output",
+            output.to_str().unwrap(),
+        ])
+        .status()
+        .expect("Failed to run z1 compile");
+
+    assert!(status.success(), "GC compilation should succeed");
+
+    let wat = fs::read_to_string(&output).expect("Failed to read output");
+    assert!(wat.contains("(type $str (array (mut i8)))"));
+    assert!(!wat.contains("(memory"));
+}
+
+#[test]
+fn test_opt_size_reports_size_and_strips_names() {
+    let (_dir, input) = setup_test_cell(simple_valid_cell());
+    let small = input.with_extension("wasm");
+    let full = input.with_file_name("full.wasm");
+
+    let output_cmd = z1_command()
+        .args([
+            "compile",
+            input.to_str().unwrap(),
+            "--target",
+            "wasm",
+            "--binary",
+            "--opt-size",
+            "--output",
+            small.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to run z1 compile");
+    assert!(
+        output_cmd.status.success(),
+        "Opt-size compilation should succeed"
+    );
+
+    let small_bytes = fs::read(&small).expect("Failed to read output");
+    let stdout = String::from_utf8_lossy(&output_cmd.stdout);
+    assert!(stdout.contains(&format!("({} bytes)", small_bytes.len())));
+
+    let status = z1_command()
+        .args([
+            "compile",
+            input.to_str().unwrap(),
+            "--target",
+            "wasm",
+            "--binary",
+            "--output",
+            full.to_str().unwrap(),
+        ])
+        .status()
+        .expect("Failed to run z1 compile");
+    assert!(status.success());
+    assert!(small_bytes.len() < fs::read(&full).unwrap().len());
+}
+
+#[test]
+fn test_require_entry() {
+    let (_dir, input) = setup_test_cell(simple_valid_cell());
+    let output = z1_command()
+        .args([
+            "compile",
+            input.to_str().unwrap(),
+            "--target",
+            "wasm",
+            "--require-entry",
+        ])
+        .output()
+        .expect("Failed to run z1 compile");
+    assert!(!output.status.success(), "Cell without main should fail");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No entrypoint"));
+
+    let (_dir, input) = setup_test_cell(
+        r#"module app : 1.0
+  ctx = 100
+  caps = []
+
+fn main() -> Unit
+  eff [pure]
+{
+}
+"#,
+    );
+    let wat_path = input.with_extension("wat");
+    let status = z1_command()
+        .args([
+            "compile",
+            input.to_str().unwrap(),
+            "--target",
+            "wasm",
+            "--require-entry",
+            "--output",
+            wat_path.to_str().unwrap(),
+        ])
+        .status()
+        .expect("Failed to run z1 compile");
+    assert!(status.success(), "Cell with main should compile");
+
+    let wat = fs::read_to_string(&wat_path).expect("Failed to read output");
+    assert!(wat.contains("(export \"_start\" (func $main))"));
+}
 
 #[test]
 fn test_binary_with_optimization_levels() {
@@ -266,3 +551,325 @@ fn test_binary_output_default_path() {
     let binary = fs::read(&expected_output).expect("Should read binary");
     assert_eq!(&binary[0..4], &[0x00, 0x61, 0x73, 0x6D]);
 }
+
+/// Run `z1 <args>` with `source` piped to stdin.
+fn z1_piped(args: &[&str], source: &str) -> Output {
+    let mut child = z1_command()
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run z1 compile");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(source.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_compile_stdin_to_stdout() {
+    let output = z1_piped(&["compile", "--stdin", "--stdout"], simple_valid_cell());
+    assert!(output.status.success());
+    let code = String::from_utf8_lossy(&output.stdout);
+    assert!(code.starts_with("// Generated by Zero1 compiler"), "{code}");
+    assert!(code.contains("export function add"), "{code}");
+
+    let output = z1_piped(
+        &["compile", "--stdin", "--stdout", "--emit-ir"],
+        simple_valid_cell(),
+    );
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("; IR for module: test"));
+
+    let output = z1_piped(
+        &[
+            "compile", "--stdin", "--stdout", "--target", "wasm", "--binary",
+        ],
+        simple_valid_cell(),
+    );
+    assert!(output.status.success());
+    assert!(output.stdout.starts_with(b"\0asm"));
+}
+
+#[test]
+fn test_compile_stdin_names_cell_with_path() {
+    let dir = TempDir::new().unwrap();
+    let input = dir.path().join("piped.z1c");
+    let output = z1_piped(
+        &["compile", "--stdin", input.to_str().unwrap()],
+        simple_valid_cell(),
+    );
+    assert!(output.status.success());
+    // The path only names the cell; the source comes from stdin
+    assert!(!input.exists());
+    assert!(input.with_extension("ts").exists());
+
+    let output = z1_piped(&["compile", "--stdin"], simple_valid_cell());
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--stdin needs --stdout"), "{stderr}");
+}
+
+#[test]
+fn test_compile_stdin_json_diagnostics() {
+    let output = z1_piped(
+        &["--format", "json", "compile", "--stdin", "--stdout"],
+        "module test : 1.0\nfn oops(",
+    );
+    assert_eq!(output.status.code(), Some(3));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(report["error"].is_string());
+    let diagnostic = &report["diagnostics"][0];
+    assert_eq!(diagnostic["code"], "P001");
+    assert_eq!(diagnostic["source_file"], "<stdin>");
+}
+
+#[test]
+fn test_compile_stats_json() {
+    let (_dir, input) = setup_test_cell(simple_valid_cell());
+    let output = z1_command()
+        .args([
+            "compile",
+            input.to_str().unwrap(),
+            "--stats",
+            "json",
+            "-O",
+            "o2",
+        ])
+        .output()
+        .expect("Failed to run z1 compile");
+    assert!(output.status.success());
+
+    let stats: serde_json::Value = serde_json::from_slice(&output.stdout).expect("valid JSON");
+    let code = fs::read_to_string(input.with_extension("ts")).unwrap();
+    assert_eq!(stats["format"], "ts");
+    assert_eq!(stats["bytes"], code.len());
+    assert!(stats["tokens"].as_u64().unwrap() > 0);
+    assert_eq!(stats["optimizations"]["level"], "O2");
+
+    let output = z1_command()
+        .args([
+            "compile",
+            input.to_str().unwrap(),
+            "--target",
+            "wasm",
+            "--binary",
+        ])
+        .output()
+        .expect("Failed to run z1 compile");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("✓ Optimized at O1: "), "{stdout}");
+    assert!(!stdout.contains("tokens"), "{stdout}");
+}
+
+#[test]
+fn test_compile_several_targets_in_one_run() {
+    let (dir, input) = setup_test_cell(simple_valid_cell());
+    let output = z1_command()
+        .args([
+            "compile",
+            input.to_str().unwrap(),
+            "--target",
+            "ts,wasm",
+            "--binary",
+            "--format",
+            "json",
+        ])
+        .output()
+        .expect("Failed to run z1 compile");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).expect("valid JSON");
+    assert_eq!(report["targets"][0]["stats"]["format"], "ts");
+    assert_eq!(report["targets"][1]["stats"]["format"], "wasm");
+
+    let dts = fs::read_to_string(dir.path().join("test.d.ts")).unwrap();
+    assert!(
+        dts.contains("export declare function add(x: number, y: number): number;"),
+        "{dts}"
+    );
+    assert!(dir.path().join("test.wasm").is_file());
+
+    let meta: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(input.with_extension("meta.json")).unwrap())
+            .unwrap();
+    assert_eq!(meta["module"], "test");
+    assert_eq!(meta["caps"][0], "net");
+    assert_eq!(meta["budget"], 100);
+    assert!(meta["tokens"].as_u64().unwrap() > 0);
+    assert_eq!(meta["exports"][0]["name"], "add");
+    assert_eq!(meta["exports"][0]["kind"], "fn");
+    assert!(meta["semhash"].as_str().unwrap().starts_with("sha3-256:"));
+    let outputs: Vec<&str> = meta["outputs"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|output| output["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(outputs.len(), 3, "{outputs:?}");
+    assert!(outputs.iter().any(|path| path.ends_with("test.wasm")));
+
+    // One output path cannot name several outputs
+    let output = z1_command()
+        .args([
+            "compile",
+            input.to_str().unwrap(),
+            "-t",
+            "ts,wasm",
+            "--output",
+            "out.ts",
+        ])
+        .output()
+        .expect("Failed to run z1 compile");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_compile_directory_in_parallel() {
+    let dir = TempDir::new().unwrap();
+    let cells = dir.path().join("cells");
+    fs::create_dir_all(cells.join("nested")).unwrap();
+    fs::write(cells.join("a.z1c"), simple_valid_cell()).unwrap();
+    fs::write(
+        cells.join("nested/b.z1c"),
+        simple_valid_cell().replace("module test", "module b"),
+    )
+    .unwrap();
+    fs::write(cells.join("broken.z1c"), "m broken:1.0\nf (\n").unwrap();
+
+    let output = z1_command()
+        .args(["compile", cells.to_str().unwrap()])
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run z1 compile");
+    assert_eq!(output.status.code(), Some(3));
+    assert!(cells.join("a.ts").is_file());
+    assert!(cells.join("nested/b.ts").is_file());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("compile: 3 cell(s)"), "{stderr}");
+    assert!(stderr.contains("parse errors"), "{stderr}");
+
+    let output = z1_command()
+        .args([
+            "--format",
+            "json",
+            "compile",
+            &format!("{}/**/*.z1c", cells.display()),
+            cells.join("broken.z1c").to_str().unwrap(),
+            "--target",
+            "wasm",
+        ])
+        .output()
+        .expect("Failed to run z1 compile");
+    assert_eq!(output.status.code(), Some(3));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).expect("valid JSON");
+    assert_eq!(report["compiled"], 2);
+    assert_eq!(report["failed"], 1);
+    let failed: Vec<&serde_json::Value> = report["cells"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|cell| cell["error"].is_string())
+        .collect();
+    assert_eq!(failed.len(), 1);
+    assert!(failed[0]["path"].as_str().unwrap().ends_with("broken.z1c"));
+    assert!(cells.join("nested/b.wat").is_file());
+}
+
+#[test]
+fn test_reproducible_builds_are_byte_identical() {
+    let compile = |input: &PathBuf, flag: &str| {
+        z1_command()
+            .args([
+                "compile",
+                input.to_str().unwrap(),
+                "-t",
+                "ts,wasm",
+                "--binary",
+                "--debug",
+                flag,
+            ])
+            .output()
+            .expect("Failed to run z1 compile")
+    };
+    // The same cell in two directories
+    let (first, first_input) = setup_test_cell(simple_valid_cell());
+    let (second, second_input) = setup_test_cell(simple_valid_cell());
+    for input in [&first_input, &second_input] {
+        let output = compile(input, "--reproducible");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    for file in [
+        "test.ts",
+        "test.d.ts",
+        "test.wasm",
+        "test.wat",
+        "test.meta.json",
+    ] {
+        assert_eq!(
+            fs::read(first.path().join(file)).unwrap(),
+            fs::read(second.path().join(file)).unwrap(),
+            "{file} differs"
+        );
+    }
+    let meta = fs::read_to_string(first.path().join("test.meta.json")).unwrap();
+    assert!(meta.contains("\"path\": \"test.wasm\""), "{meta}");
+
+    let output = compile(&first_input, "--verify-reproducible");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout)
+        .contains("✓ Reproducible: 5 file(s) identical across two builds"));
+}
+
+#[test]
+fn test_compile_trace_timings_and_json_log() {
+    let (_dir, input) = setup_test_cell(simple_valid_cell());
+
+    let output = z1_command()
+        .args(["--trace-timings", "compile", input.to_str().unwrap()])
+        .env_remove("RUST_LOG")
+        .output()
+        .expect("Failed to run z1 compile");
+    assert!(output.status.success(), "{output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    for stage in [
+        "parse", "resolve", "typeck", "effects", "ctx", "policy", "lower", "optimize", "codegen",
+    ] {
+        assert!(
+            stderr.lines().any(|line| line.starts_with(stage)),
+            "no {stage} timing in {stderr}"
+        );
+    }
+
+    let output = z1_command()
+        .args(["--log-json", "compile", input.to_str().unwrap()])
+        .env_remove("RUST_LOG")
+        .output()
+        .expect("Failed to run z1 compile");
+    assert!(output.status.success(), "{output:?}");
+    let records: Vec<serde_json::Value> = String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    let typeck = records
+        .iter()
+        .find(|record| record["span"] == "typeck")
+        .expect("typeck span logged");
+    assert_eq!(typeck["target"], "z1::stage");
+    assert!(typeck["elapsed_ms"].as_f64().is_some());
+}
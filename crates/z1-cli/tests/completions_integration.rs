@@ -0,0 +1,44 @@
+//! Integration tests for `z1 completions` and `z1 man`
+
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+fn z1(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .output()
+        .expect("Failed to run z1")
+}
+
+#[test]
+fn test_completions_offer_values_and_paths() {
+    let output = z1(&["completions", "bash"]);
+    assert!(output.status.success());
+    let script = String::from_utf8_lossy(&output.stdout);
+    assert!(script.contains("complete -F _z1"));
+    assert!(script.contains("type-script wasm"));
+    assert!(script.contains("o0 o1 o2"));
+    assert!(script.contains("text json sarif"));
+
+    let output = z1(&["completions", "zsh", "--bin-name", "z1-cli"]);
+    let script = String::from_utf8_lossy(&output.stdout);
+    assert!(script.starts_with("#compdef z1-cli"));
+    // Cells complete as files
+    assert!(script.contains("_files"));
+
+    assert_eq!(z1(&["completions", "tcsh"]).status.code(), Some(2));
+}
+
+#[test]
+fn test_man_pages() {
+    let output = z1(&["man"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains(".TH z1 1"));
+
+    let dir = TempDir::new().unwrap();
+    let output = z1(&["man", "--out-dir", dir.path().to_str().unwrap()]);
+    assert!(output.status.success());
+    assert!(dir.path().join("z1.1").is_file());
+    assert!(dir.path().join("z1-fmt.1").is_file());
+    assert!(dir.path().join("z1-prov-verify.1").is_file());
+}
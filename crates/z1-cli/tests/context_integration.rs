@@ -0,0 +1,78 @@
+//! Integration tests for `z1 context`
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+const CELL: &str = "m app:1.0 caps=[fs.ro]\n\
+                    u \"std/fs/core\" as fs only [exists]\n\
+                    t Name = Str\n\
+                    f check(name: Name)->Bool eff [fs] { ret fs.exists(name); }\n\
+                    f main()->Bool eff [fs] { ret check(\"input.txt\"); }\n\
+                    f unrelated()->U32 eff [pure] { ret 1; }\n";
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run z1")
+}
+
+fn cell() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("app.z1c"), CELL).unwrap();
+    dir
+}
+
+#[test]
+fn test_context_prints_what_the_function_needs() {
+    let dir = cell();
+    let output = z1(&["context", "app.z1c", "main"], dir.path());
+    assert!(output.status.success(), "{output:?}");
+    let bundle = String::from_utf8_lossy(&output.stdout);
+    assert!(bundle.contains("f main()->Bool eff [fs] { ret check(\"input.txt\"); }"));
+    assert!(bundle.contains("f check(name: Name)->Bool eff [fs] { … }"));
+    assert!(bundle.contains("t Name = Str"));
+    // Only the body of `check` uses the import, and it is elided
+    assert!(!bundle.contains("std/fs/core"), "{bundle}");
+    assert!(!bundle.contains("unrelated"), "{bundle}");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Context of `main` in app.z1c"));
+
+    let output = z1(&["context", "app.z1c", "check"], dir.path());
+    assert!(output.status.success(), "{output:?}");
+    let bundle = String::from_utf8_lossy(&output.stdout);
+    assert!(bundle.starts_with("m std.fs.core:"), "{bundle}");
+    assert!(bundle.contains("f exists(path: Str)->Bool eff [fs] { … }"));
+    assert!(bundle.contains("u \"std/fs/core\" as fs only [exists]"));
+}
+
+#[test]
+fn test_context_respects_the_budget() {
+    let dir = cell();
+    let tokens = |output: &Output| -> serde_json::Value {
+        assert!(output.status.success(), "{output:?}");
+        serde_json::from_slice(&output.stdout).unwrap()
+    };
+    let full = tokens(&z1(
+        &["context", "app.z1c", "check", "--format", "json"],
+        dir.path(),
+    ));
+    let budget = (full["tokens"].as_u64().unwrap() - 1).to_string();
+    let report = tokens(&z1(
+        &[
+            "context", "app.z1c", "check", "--budget", &budget, "--format", "json",
+        ],
+        dir.path(),
+    ));
+    assert!(report["tokens"].as_u64().unwrap() < full["tokens"].as_u64().unwrap());
+    assert!(!report["omitted"].as_array().unwrap().is_empty());
+
+    let output = z1(&["context", "app.z1c", "main", "--budget", "5"], dir.path());
+    assert_eq!(output.status.code(), Some(6), "{output:?}");
+    let output = z1(&["context", "app.z1c", "missing"], dir.path());
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+}
@@ -0,0 +1,195 @@
+//! Integration tests for `z1 daemon`
+#![cfg(unix)]
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::process::{Child, Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tempfile::TempDir;
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .env_remove("Z1_DAEMON_SOCKET")
+        .env_remove("Z1_NO_DAEMON")
+        .output()
+        .expect("Failed to run z1")
+}
+
+/// Daemon process, killed if a test fails before stopping it.
+struct Daemon(Child);
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+    }
+}
+
+fn status(dir: &Path) -> serde_json::Value {
+    let output = z1(&["daemon", "status", "--format", "json"], dir);
+    assert!(output.status.success(), "{output:?}");
+    serde_json::from_slice(&output.stdout).expect("valid JSON")
+}
+
+#[test]
+fn test_daemon_serves_lint_fmt_and_compile() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("z1.toml"),
+        "[project]\nname = \"demo\"\nversion = \"0.1.0\"\n",
+    )
+    .unwrap();
+    fs::create_dir_all(dir.path().join("src/util")).unwrap();
+    fs::write(
+        dir.path().join("src/util/text.z1c"),
+        "m util.text:1.0 caps=[]\nf shout(s: Str)->Str eff [pure] { ret s; }\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("src/app.z1c"),
+        "m app:1.0 caps=[]\nuse \"util/text\" as T only [shout]\n\
+         f main(s: Str)->Str eff [pure] { ret T.shout(s); }\n",
+    )
+    .unwrap();
+
+    let mut daemon = Daemon(
+        Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+            .arg("daemon")
+            .current_dir(dir.path())
+            .env_remove("Z1_DAEMON_SOCKET")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Failed to start the daemon"),
+    );
+    let socket = dir.path().join(".z1/daemon.sock");
+    let started = Instant::now();
+    while !socket.exists() {
+        assert!(
+            started.elapsed() < Duration::from_secs(10),
+            "daemon did not start"
+        );
+        thread::sleep(Duration::from_millis(20));
+    }
+    assert_eq!(status(dir.path())["requests"], 0);
+
+    let output = z1(&["lint", "src"], dir.path());
+    assert!(output.status.success(), "{output:?}");
+    let output = z1(&["compile", "src/app.z1c"], dir.path());
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("✓ Compiled to: src/app.ts"), "{stdout}");
+    assert!(dir.path().join("src/app.ts").exists());
+    assert!(z1(&["fmt", "src/util/text.z1c"], dir.path())
+        .status
+        .success());
+    assert!(z1(&["fmt", "--check", "src/util/text.z1c"], dir.path())
+        .status
+        .success());
+
+    // A cell that does not parse is reported by the client as usual
+    fs::write(dir.path().join("src/bad.z1c"), "m bad:1.0\nf oops(").unwrap();
+    let output = z1(&["compile", "src/bad.z1c"], dir.path());
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("src/bad.z1c:2:8"), "{stderr}");
+
+    let served = status(dir.path());
+    assert_eq!(served["requests"], 7, "{served}");
+    assert_eq!(served["cells"], 3, "{served}");
+
+    // Another build is refused, so its client runs the command itself
+    let cell = dir.path().join("src/app.z1c");
+    let request = |build: &str, request: &str| {
+        let mut stream = UnixStream::connect(&socket).unwrap();
+        let line = serde_json::json!({
+            "build": build,
+            "request": request,
+            "path": cell,
+            "relaxed": false,
+            "reflow_symbols": false,
+        });
+        writeln!(stream, "{line}").unwrap();
+        let mut response = String::new();
+        BufReader::new(stream).read_line(&mut response).unwrap();
+        serde_json::from_str::<serde_json::Value>(&response).unwrap()
+    };
+    let refused = request("0.0.0+other", "format");
+    assert_eq!(refused["response"], "error", "{refused}");
+    assert!(refused["message"]
+        .as_str()
+        .unwrap()
+        .contains("does not match daemon build"));
+    assert_eq!(request("0.0.0+other", "status")["response"], "status");
+
+    assert!(z1(&["daemon", "stop"], dir.path()).status.success());
+    assert!(daemon.0.wait().unwrap().success());
+    assert!(!socket.exists());
+    assert_eq!(z1(&["daemon", "status"], dir.path()).status.code(), Some(1));
+}
+
+#[test]
+fn test_daemon_drops_names_past_max_names() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("z1.toml"),
+        "[project]\nname = \"demo\"\nversion = \"0.1.0\"\n",
+    )
+    .unwrap();
+    fs::create_dir_all(dir.path().join("src")).unwrap();
+    fs::write(
+        dir.path().join("src/app.z1c"),
+        "m app:1.0 caps=[]\nf main(s: Str)->Str eff [pure] { ret s; }\n",
+    )
+    .unwrap();
+
+    let mut daemon = Daemon(
+        Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+            .args(["daemon", "start", "--max-names", "1"])
+            .current_dir(dir.path())
+            .env_remove("Z1_DAEMON_SOCKET")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Failed to start the daemon"),
+    );
+    let socket = dir.path().join(".z1/daemon.sock");
+    let started = Instant::now();
+    while !socket.exists() {
+        assert!(
+            started.elapsed() < Duration::from_secs(10),
+            "daemon did not start"
+        );
+        thread::sleep(Duration::from_millis(20));
+    }
+    let pid = status(dir.path())["pid"].clone();
+
+    let output = z1(&["lint", "src"], dir.path());
+    assert!(output.status.success(), "{output:?}");
+
+    // The same process serves later requests with an empty cache and
+    // symbol table
+    let started = Instant::now();
+    loop {
+        let status = status(dir.path());
+        assert_eq!(status["pid"], pid);
+        if status["names"] == 0 {
+            assert_eq!(status["cells"], 0, "{status}");
+            break;
+        }
+        assert!(
+            started.elapsed() < Duration::from_secs(10),
+            "daemon kept its names: {status}"
+        );
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    assert!(z1(&["daemon", "stop"], dir.path()).status.success());
+    assert!(daemon.0.wait().unwrap().success());
+}
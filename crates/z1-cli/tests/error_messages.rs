@@ -4,6 +4,7 @@
 //! - Parse errors show correct source location
 //! - Type errors show correct source location
 //! - Effect errors show correct source location
+//! - Context and policy errors show source location, notes and help
 //! - Error printer formats errors with source snippets
 //! - Color output can be disabled
 
@@ -274,6 +275,10 @@ fn very_long_function_name_that_exceeds_budget(x: U32, y: U32, z: U32) -> U32 {
 
     // Should fail with context budget error
     assert!(!output.status.success(), "Expected compilation to fail");
+    assert!(
+        combined.contains("= note: the budget is set by `ctx=5` in the module header"),
+        "Expected a note on the budget, got: {combined}"
+    );
     assert!(
         combined.to_lowercase().contains("context")
             || combined.to_lowercase().contains("budget")
@@ -370,3 +375,37 @@ fn foo() -> U32 eff [invalid_effect] {
         "Expected source snippet in error, got: {combined}"
     );
 }
+
+#[test]
+fn test_policy_violation_shows_snippet_and_notes() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("z1.toml"),
+        "[project]\nname = \"demo\"\nversion = \"0.1.0\"\n\n[policy]\nfn_max_params = 2\n",
+    )
+    .unwrap();
+    let path = dir.path().join("wide.z1c");
+    fs::write(
+        &path,
+        "m wide:1.0 caps=[]\nf wide(a: U32, b: U32, c: U32)->U32 eff [pure] { ret a; }\n",
+    )
+    .unwrap();
+
+    let output = run_z1c_compile(path.to_str().unwrap());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(7), "{stderr}");
+    assert!(
+        stderr.contains("Policy Violation: Function 'wide' exceeds parameter limit"),
+        "{stderr}"
+    );
+    assert!(stderr.contains("wide.z1c:2:1"), "{stderr}");
+    assert!(
+        stderr.contains("= note: the limit is `fn_max_params`"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("Help: Group related parameters into a record type"),
+        "{stderr}"
+    );
+}
@@ -0,0 +1,149 @@
+//! Integration tests for `--events`
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use serde_json::Value;
+use tempfile::TempDir;
+
+const CELL: &str = "m app.main:1.0 ctx=100 caps=[net]\n\
+                    f add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }\n";
+const BROKEN: &str = "m app.broken:1.0\nf main()->U32 eff [pure] { ret 1;\n";
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run z1")
+}
+
+/// Parse stdout as one JSON event per line.
+fn parse_events(output: &Output) -> Vec<Value> {
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| {
+            serde_json::from_str(line).unwrap_or_else(|err| panic!("not an event ({err}): {line}"))
+        })
+        .collect()
+}
+
+fn workspace() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("main.z1c"), CELL).unwrap();
+    fs::write(dir.path().join("broken.z1c"), BROKEN).unwrap();
+    dir
+}
+
+#[test]
+fn test_lint_streams_diagnostics_then_summary() {
+    let dir = workspace();
+    let output = z1(&["lint", "--events", "broken.z1c", "main.z1c"], dir.path());
+    assert_eq!(output.status.code(), Some(3));
+    let events = parse_events(&output);
+    assert_eq!(events.len(), 3, "{events:?}");
+
+    assert_eq!(events[0]["event"], "diagnostic");
+    assert_eq!(events[0]["level"], "error");
+    assert_eq!(events[0]["code"], "P001");
+    assert_eq!(events[0]["source_file"], "broken.z1c");
+    assert_eq!(events[0]["line"], 2);
+    assert_eq!(events[1]["code"], "W001");
+
+    let summary = &events[2];
+    assert_eq!(summary["event"], "summary");
+    assert_eq!(summary["ok"], false);
+    assert_eq!(summary["exit_code"], 3);
+    assert_eq!(summary["cells"], 2);
+    assert_eq!(summary["errors"], 1);
+    assert_eq!(summary["warnings"], 1);
+}
+
+#[test]
+fn test_compile_reports_artifacts_and_failures() {
+    let dir = workspace();
+    let output = z1(&["compile", "--events", "main.z1c"], dir.path());
+    assert!(output.status.success());
+    let events = parse_events(&output);
+    assert_eq!(events[0]["event"], "artifact");
+    assert_eq!(events[0]["cell"], "main.z1c");
+    assert_eq!(events[0]["kind"], "output");
+    assert_eq!(events[0]["path"], "main.ts");
+    assert!(events[0]["bytes"].as_u64().unwrap() > 0);
+    assert_eq!(events[1]["ok"], true);
+    assert_eq!(events[1]["compiled"], 1);
+
+    let output = z1(&["compile", "--events", "broken.z1c"], dir.path());
+    assert_eq!(output.status.code(), Some(3));
+    let events = parse_events(&output);
+    assert_eq!(events[0]["event"], "diagnostic");
+    assert_eq!(events[0]["code"], "P001");
+    assert_eq!(events[1]["event"], "summary");
+    assert_eq!(events[1]["failed"], 1);
+    assert_eq!(events[1]["error"], "Parse failed");
+}
+
+#[test]
+fn test_compile_batch_summarizes_every_cell() {
+    let dir = workspace();
+    let output = z1(
+        &["compile", "--events", "--target", "ts,wasm", "."],
+        dir.path(),
+    );
+    assert_eq!(output.status.code(), Some(3));
+    let events = parse_events(&output);
+    let kinds: Vec<&str> = events
+        .iter()
+        .filter(|event| event["event"] == "artifact")
+        .map(|event| event["kind"].as_str().unwrap())
+        .collect();
+    assert_eq!(kinds, ["output", "declarations", "output", "meta"]);
+    assert_eq!(
+        events
+            .iter()
+            .filter(|event| event["event"] == "diagnostic")
+            .count(),
+        1
+    );
+
+    let summary = events.last().unwrap();
+    assert_eq!(summary["event"], "summary");
+    assert_eq!(summary["cells"], 2);
+    assert_eq!(summary["compiled"], 1);
+    assert_eq!(summary["failed"], 1);
+}
+
+#[test]
+fn test_test_reports_failed_specs() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("math.z1t"),
+        "spec \"adds\" {\n  assert 1 + 1 == 2;\n}\n\nspec \"wrong\" {\n  assert 1 + 1 == 3;\n}\n",
+    )
+    .unwrap();
+    let output = z1(&["test", "--events", "math.z1t"], dir.path());
+    assert_eq!(output.status.code(), Some(11));
+    let events = parse_events(&output);
+    assert_eq!(events.len(), 2, "{events:?}");
+    assert_eq!(events[0]["event"], "diagnostic");
+    assert!(events[0]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("wrong: "));
+    assert_eq!(events[0]["line"], 5);
+    assert_eq!(events[1]["passed"], 1);
+    assert_eq!(events[1]["failed"], 1);
+}
+
+#[test]
+fn test_unsupported_commands_still_end_with_a_summary() {
+    let dir = workspace();
+    let output = z1(&["fmt", "--events", "main.z1c"], dir.path());
+    assert_eq!(output.status.code(), Some(2));
+    let events = parse_events(&output);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["exit_code"], 2);
+    assert_eq!(events[0]["error"], "`fmt` does not support --events");
+}
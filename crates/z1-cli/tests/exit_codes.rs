@@ -0,0 +1,86 @@
+//! Integration tests for exit codes and the `--quiet`/`--summary-only` flags
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+const CELL: &str =
+    "m app.main:1.0 ctx=100 caps=[]\nf add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }\n";
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run z1")
+}
+
+fn workspace(cells: &[(&str, &str)]) -> TempDir {
+    let dir = TempDir::new().unwrap();
+    for (name, source) in cells {
+        fs::write(dir.path().join(name), source).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn test_failures_exit_with_their_class() {
+    let dir = workspace(&[
+        ("main.z1c", CELL),
+        ("broken.z1c", "m broken:1.0\nf oops("),
+        (
+            "heavy.z1c",
+            "m heavy:1.0 ctx=2 caps=[]\nf add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }\n",
+        ),
+    ]);
+    let code = |args: &[&str]| z1(args, dir.path()).status.code();
+
+    assert_eq!(code(&["compile", "broken.z1c"]), Some(3));
+    assert_eq!(code(&["hash", "broken.z1c"]), Some(3));
+    assert_eq!(code(&["ctx", "heavy.z1c"]), Some(6));
+    assert_eq!(code(&["compile", "heavy.z1c"]), Some(6));
+    let zeros = format!("sha3-256:{}", "0".repeat(64));
+    assert_eq!(code(&["hash", "main.z1c", "--verify", &zeros]), Some(10));
+    assert_eq!(code(&["ctx", "missing.z1c"]), Some(1));
+    assert_eq!(code(&["info", "--format", "json"]), Some(2));
+    assert_eq!(code(&["compile", "main.z1c", "--binary"]), Some(2));
+}
+
+#[test]
+fn test_quiet_and_summary_only() {
+    let dir = workspace(&[
+        ("a.z1c", CELL),
+        ("b.z1c", &CELL.replace("app.main", "app.other")),
+    ]);
+
+    let output = z1(&["lint", ".", "--deny-warnings", "--quiet"], dir.path());
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let output = z1(&["hash", ".", "--summary-only"], dir.path());
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("hash: 2 cell(s)"));
+
+    let output = z1(&["-q", "hash", "."], dir.path());
+    assert!(output.stdout.is_empty() && output.stderr.is_empty());
+
+    // Errors are still reported
+    let output = z1(&["-q", "ctx", "missing.z1c"], dir.path());
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error:"));
+
+    let output = z1(&["--quiet", "--summary-only", "info"], dir.path());
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_help_lists_exit_codes() {
+    let output = z1(&["--help"], Path::new("."));
+    let help = String::from_utf8_lossy(&output.stdout);
+    assert!(help.contains("Exit codes:"));
+    assert!(help.contains("11  failing tests"));
+}
@@ -0,0 +1,56 @@
+//! Integration tests for `z1 expand`
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use serde_json::Value;
+use tempfile::TempDir;
+
+const CELL: &str = "m app.api:1.0 caps=[]\n\
+                    template getter(name, value) { f name()->U32 eff [pure] { let kept: U32 = value; ret kept; } }\n\
+                    expand getter(one, 1)\n\
+                    expand getter(two, kept + 1)\n";
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run z1")
+}
+
+#[test]
+fn test_expand_prints_the_materialized_cell() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("api.z1c"), CELL).unwrap();
+    let output = z1(&["expand", "api.z1c"], dir.path());
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "m app.api:1.0\n\
+         f one()->U32 eff [pure] { let kept: U32 = 1; ret kept; }\n\
+         \n\
+         f two()->U32 eff [pure] { let kept_getter_2: U32 = (kept + 1); ret kept_getter_2; }\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join("api.z1c")).unwrap(),
+        CELL
+    );
+
+    let output = z1(&["--format", "json", "expand", "api.z1c"], dir.path());
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["templates"][0], "getter");
+    assert_eq!(report["expansions"][1]["args"][1], "kept + 1");
+    assert_eq!(report["expansions"][1]["items"][0], "two");
+}
+
+#[test]
+fn test_expand_rejects_unknown_templates() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("bad.z1c"), "m bad:1.0\nexpand missing(x)\n").unwrap();
+    let output = z1(&["expand", "bad.z1c"], dir.path());
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown template `missing`"));
+}
@@ -0,0 +1,94 @@
+//! Integration tests for `z1 explain` and the diagnostic code registry
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run z1")
+}
+
+fn json(output: &Output) -> serde_json::Value {
+    serde_json::from_slice(&output.stdout).expect("valid JSON")
+}
+
+#[test]
+fn test_explain_prints_registry_entries() {
+    let here = Path::new(".");
+    let output = z1(&["explain", "c001"], here);
+    assert!(output.status.success());
+    let text = String::from_utf8_lossy(&output.stdout);
+    assert!(text.starts_with("C001: Context budget exceeded"), "{text}");
+    assert!(text.contains("Example:") && text.contains("Common fixes:"));
+
+    let list = json(&z1(&["explain", "--list", "--format", "json"], here));
+    let codes: Vec<&str> = list
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["code"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        codes,
+        ["P001", "R001", "T001", "E001", "W001", "W002", "W003", "C001", "L001"]
+    );
+
+    let output = z1(&["explain", "T01"], here);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("unknown diagnostic code `T01`"), "{stderr}");
+    assert!(stderr.contains("did you mean `T001`?"), "{stderr}");
+}
+
+#[test]
+fn test_examples_report_their_code() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("z1.toml"),
+        "[project]\nname = \"demo\"\nversion = \"0.1.0\"\n",
+    )
+    .unwrap();
+    fs::create_dir_all(dir.path().join("src/util")).unwrap();
+    fs::write(
+        dir.path().join("src/util/text.z1c"),
+        "m util.text:1.0 caps=[]\nf shout(s: Str)->Str eff [pure] { ret s; }\n",
+    )
+    .unwrap();
+
+    let list = json(&z1(&["explain", "--list", "--format", "json"], dir.path()));
+    for entry in list.as_array().unwrap() {
+        let code = entry["code"].as_str().unwrap();
+        let cell = format!("src/{code}.z1c");
+        fs::write(dir.path().join(&cell), entry["example"].as_str().unwrap()).unwrap();
+
+        let report = json(&z1(&["lint", &cell, "--format", "json"], dir.path()));
+        let reported: Vec<&str> = report["diagnostics"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|diagnostic| diagnostic["code"].as_str())
+            .collect();
+        assert!(reported.contains(&code), "{code}: {report}");
+    }
+}
+
+#[test]
+fn test_errors_point_at_explain() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("broken.z1c"), "m broken:1.0\nf oops(").unwrap();
+
+    let output = z1(&["compile", "broken.z1c"], dir.path());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("try `z1 explain P001`"), "{stderr}");
+
+    let output = z1(&["lint", "broken.z1c"], dir.path());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("try `z1 explain P001`"), "{stderr}");
+}
@@ -0,0 +1,100 @@
+//! Integration tests for `z1 fix` and the fixes reported by `z1 lint`
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+const CELL: &str = "m app.main:1.0 ctx=100 caps=[net, time]
+#sym { add: ad, retired: rt }
+u \"util/text\" only [shout, whisper]
+f add(a: U32, b: U32)->U32 eff [pure] { ret shout(a); }
+";
+
+const FIXED: &str = "m app.main:1.0 ctx=100 caps=[]
+#sym { add: ad }
+u \"util/text\" only [shout]
+f add(a: U32, b: U32)->U32 eff [pure] { ret shout(a); }
+";
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run z1")
+}
+
+fn setup() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("main.z1c"), CELL).unwrap();
+    dir
+}
+
+#[test]
+fn test_lint_reports_fixes() {
+    let dir = setup();
+    let output = z1(&["lint", "main.z1c", "--format", "json"], dir.path());
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let fixes: Vec<&str> = report["diagnostics"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|diagnostic| diagnostic["fix"]["message"].as_str())
+        .collect();
+    assert_eq!(
+        fixes,
+        [
+            "Remove unused whisper from import 'util/text'",
+            "Remove stale symbol map entries for retired",
+            "Remove unused capability 'net'",
+            "Remove unused capability 'time'",
+        ]
+    );
+
+    let output = z1(&["lint", "main.z1c"], dir.path());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("4 issue(s) can be fixed with `z1 fix`"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn test_fix_dry_run_prints_diff() {
+    let dir = setup();
+    let output = z1(&["fix", "--dry-run", "."], dir.path());
+    assert!(output.status.success());
+    let diff = String::from_utf8_lossy(&output.stdout);
+    assert!(diff.starts_with("--- a/main.z1c\n+++ b/main.z1c\n@@ -1,4 +1,4 @@\n"));
+    assert!(diff.contains("-m app.main:1.0 ctx=100 caps=[net, time]\n"));
+    assert!(diff.contains("+m app.main:1.0 ctx=100 caps=[]\n"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Would fix 4 issue(s) in 1 cell(s)"));
+    assert_eq!(
+        fs::read_to_string(dir.path().join("main.z1c")).unwrap(),
+        CELL
+    );
+}
+
+#[test]
+fn test_fix_applies_until_clean() {
+    let dir = setup();
+    let output = z1(&["fix", "--format", "json", "*.z1c"], dir.path());
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["fixed"], 4);
+    assert_eq!(report["cells"][0]["path"], "main.z1c");
+    assert_eq!(
+        fs::read_to_string(dir.path().join("main.z1c")).unwrap(),
+        FIXED
+    );
+
+    assert!(z1(&["lint", "--deny-warnings", "main.z1c"], dir.path())
+        .status
+        .success());
+    let output = z1(&["fix"], dir.path());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Fixed 0 issue(s) in 0 cell(s)"));
+}
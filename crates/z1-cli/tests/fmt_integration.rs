@@ -0,0 +1,53 @@
+//! Integration tests for `z1 fmt --minimal-edits`
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run z1")
+}
+
+fn assert_success(output: &Output) {
+    assert!(
+        output.status.success(),
+        "stdout: {}\nstderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn test_fmt_minimal_edits_keep_formatted_lines() {
+    let dir = TempDir::new().unwrap();
+    let cell = dir.path().join("app.z1c");
+    // CRLF everywhere; only the signature of `two` needs formatting
+    let source = "m app:1.0\r\nt Count = U32\r\n\r\n\
+                  f one()->Count eff [pure] { ret 1; }\r\n\r\n\
+                  f  two()->Count   eff [pure] { ret 2; }\r\n";
+    fs::write(&cell, source).unwrap();
+
+    let output = z1(
+        &["fmt", "--check", "--minimal-edits", "app.z1c"],
+        dir.path(),
+    );
+    assert_eq!(output.status.code(), Some(10));
+
+    assert_success(&z1(&["fmt", "--minimal-edits", "app.z1c"], dir.path()));
+    assert_eq!(
+        fs::read_to_string(&cell).unwrap(),
+        source.replace("f  two()->Count   eff", "f two()->Count eff")
+    );
+    assert_success(&z1(
+        &["fmt", "--check", "--minimal-edits", "app.z1c"],
+        dir.path(),
+    ));
+    // The whole-file formatter agrees, but for the line endings
+    assert_success(&z1(&["fmt", "--check", "app.z1c"], dir.path()));
+}
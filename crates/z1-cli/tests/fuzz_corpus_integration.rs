@@ -0,0 +1,41 @@
+//! Integration tests for `z1 fuzz-corpus`
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run z1")
+}
+
+#[test]
+fn test_fuzz_corpus_seeds_every_target() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("app.z1c"),
+        "m app:1.0 caps=[]\nt Count = U32\nf one()->U32 eff [pure] { ret 1; }\n",
+    )
+    .unwrap();
+
+    let output = z1(&["--format", "json", "fuzz-corpus", "app.z1c"], dir.path());
+    assert!(output.status.success(), "{output:?}");
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["seeds"], 3);
+    assert_eq!(report["written"], 9);
+    for target in ["lex", "parse", "round_trip"] {
+        let seeds = fs::read_dir(dir.path().join("fuzz/corpus").join(target)).unwrap();
+        assert_eq!(seeds.count(), 3, "{target}");
+    }
+
+    // Seeds are named by content, so a second run adds nothing
+    let output = z1(&["--format", "json", "fuzz-corpus", "app.z1c"], dir.path());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["written"], 0);
+}
@@ -0,0 +1,166 @@
+//! Integration tests for `z1 hash`
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use tempfile::TempDir;
+
+fn z1_hash(args: &[&str]) -> Output {
+    Command::new("cargo")
+        .args(["run", "-p", "z1-cli", "--", "hash"])
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("Failed to run z1 hash")
+}
+
+fn copy_fixture(rel: &str, dest: &Path) -> PathBuf {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../../fixtures")
+        .join(rel);
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).unwrap();
+    }
+    fs::copy(source, dest).unwrap();
+    dest.to_path_buf()
+}
+
+#[test]
+fn test_hash_writes_manifest() {
+    let dir = TempDir::new().unwrap();
+    let cell = copy_fixture("cells/http_server.z1c", &dir.path().join("app.z1c"));
+
+    let output = z1_hash(&[
+        "--write-manifest",
+        "--algo",
+        "blake3",
+        cell.to_str().unwrap(),
+    ]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let manifest = fs::read_to_string(dir.path().join("app.z1hash")).unwrap();
+    let manifest = z1_hash::Manifest::parse(&manifest).unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(&format!("semhash: {}", manifest.semantic)));
+    assert!(manifest.semantic.starts_with("blake3:"));
+    assert!(stdout.contains("apihash: blake3:"));
+    let names: Vec<_> = manifest
+        .items
+        .iter()
+        .map(|item| item.name.as_str())
+        .collect();
+    assert_eq!(names, ["Health", "handler", "serve"]);
+}
+
+#[test]
+fn test_hash_verify_pins_single_cell() {
+    let dir = TempDir::new().unwrap();
+    let cell = copy_fixture("fmt/http_server.compact.z1c", &dir.path().join("app.z1c"));
+    let cell = cell.to_str().unwrap();
+
+    let stdout = String::from_utf8(z1_hash(&[cell]).stdout).unwrap();
+    let semhash = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("semhash: "))
+        .unwrap()
+        .to_string();
+
+    assert!(z1_hash(&[cell, "--verify", &semhash]).status.success());
+    // Legacy unprefixed hashes still verify
+    let bare = semhash.strip_prefix("sha3-256:").unwrap();
+    assert!(z1_hash(&[cell, "--verify", bare]).status.success());
+
+    let wrong = format!("sha3-256:{}", "0".repeat(64));
+    let output = z1_hash(&[cell, "--verify", &wrong]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("semantic hash mismatch"));
+}
+
+#[test]
+fn test_hash_manifest_checks_directory() {
+    let dir = TempDir::new().unwrap();
+    let root = dir.path().join("cells");
+    copy_fixture("fmt/http_server.compact.z1c", &root.join("http.z1c"));
+    let small = copy_fixture("policy/valid_small.z1c", &root.join("nested/small.z1c"));
+
+    let listing = z1_hash(&[root.to_str().unwrap()]);
+    assert!(listing.status.success());
+    let pins = dir.path().join("cells.pins");
+    fs::write(&pins, &listing.stdout).unwrap();
+    let listing = String::from_utf8(listing.stdout).unwrap();
+    assert!(listing.contains(" nested/small.z1c\n"));
+
+    let check = |expect_ok: bool| {
+        let output = z1_hash(&[root.to_str().unwrap(), "--manifest", pins.to_str().unwrap()]);
+        assert_eq!(
+            output.status.success(),
+            expect_ok,
+            "{}",
+            String::from_utf8_lossy(&output.stdout)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert!(check(true).contains("verified 2 cell(s)"));
+
+    // Whitespace-only edits keep the semantic hash
+    let source = fs::read_to_string(&small).unwrap();
+    fs::write(&small, source.replace("{ ret Unit }", "{  ret Unit  }")).unwrap();
+    check(true);
+
+    fs::write(&small, source.replace("ctx=100", "ctx=200")).unwrap();
+    copy_fixture("policy/valid_small.z1c", &root.join("extra.z1c"));
+    let report = check(false);
+    assert!(report.contains("FAIL nested/small.z1c: mismatch"));
+    assert!(report.contains("FAIL extra.z1c: not pinned"));
+    assert!(report.contains("ok   http.z1c"));
+}
+
+#[test]
+fn test_hash_many_inputs_as_json_with_stdin() {
+    let dir = TempDir::new().unwrap();
+    let compact = copy_fixture("fmt/http_server.compact.z1c", &dir.path().join("a.z1c"));
+    let broken = dir.path().join("broken.z1c");
+    fs::write(&broken, "fn {").unwrap();
+    let relaxed = fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../../fixtures/fmt/http_server.relaxed.z1r"),
+    )
+    .unwrap();
+
+    let mut child = Command::new("cargo")
+        .args(["run", "-p", "z1-cli", "--", "hash", "--json", "-"])
+        .arg(&compact)
+        .arg(&broken)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run z1 hash");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(relaxed.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    // The unparsable cell fails the run but is still reported
+    assert!(!output.status.success());
+
+    let records: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let records = records.as_array().unwrap();
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0]["path"], "-");
+    // Compact and relaxed forms of one cell hash alike
+    assert_eq!(records[0]["semhash"], records[1]["semhash"]);
+    assert!(records[1]["apihash"]
+        .as_str()
+        .unwrap()
+        .starts_with("sha3-256:"));
+    assert!(records[2]["error"].is_string());
+    assert!(records[2].get("semhash").is_none());
+}
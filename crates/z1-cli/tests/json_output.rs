@@ -0,0 +1,166 @@
+//! Integration tests for `--format json`
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use serde_json::Value;
+use tempfile::TempDir;
+
+const CELL: &str =
+    "m app.main:1.0 ctx=100 caps=[]\nf add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }\n";
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run z1")
+}
+
+/// Parse stdout as a single JSON document.
+fn json(output: &Output) -> Value {
+    serde_json::from_slice(&output.stdout).unwrap_or_else(|err| {
+        panic!(
+            "stdout is not JSON ({err}): {}",
+            String::from_utf8_lossy(&output.stdout)
+        )
+    })
+}
+
+fn workspace() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("main.z1c"), CELL).unwrap();
+    dir
+}
+
+#[test]
+fn test_fmt_check_lists_changed_files() {
+    let dir = workspace();
+    let output = z1(
+        &["--format", "json", "fmt", "--check", "main.z1c"],
+        dir.path(),
+    );
+    assert_eq!(output.status.code(), Some(10));
+    let report = json(&output);
+    assert_eq!(report["changed"], 1);
+    assert_eq!(report["files"][0]["path"], "main.z1c");
+    assert_eq!(report["files"][0]["changed"], true);
+}
+
+#[test]
+fn test_hash_ctx_and_compile_emit_json() {
+    let dir = workspace();
+
+    let output = z1(&["hash", "main.z1c", "--format", "json"], dir.path());
+    assert!(output.status.success());
+    let records = json(&output);
+    assert!(records[0]["semhash"]
+        .as_str()
+        .unwrap()
+        .starts_with("sha3-256:"));
+
+    let output = z1(&["ctx", "main.z1c", "--format", "json"], dir.path());
+    assert!(output.status.success());
+    let estimate = json(&output);
+    assert_eq!(estimate["budget"], 100);
+    assert_eq!(estimate["within_budget"], true);
+    assert_eq!(estimate["functions"][0]["name"], "add");
+
+    let output = z1(&["compile", "main.z1c", "--format", "json"], dir.path());
+    assert!(output.status.success());
+    let report = json(&output);
+    assert_eq!(report["output"], "main.ts");
+    assert!(report["bytes"].as_u64().unwrap() > 0);
+    assert!(dir.path().join("main.ts").exists());
+}
+
+#[test]
+fn test_test_update_snapshots_lists_golden_files() {
+    let dir = workspace();
+    fs::write(
+        dir.path().join("fmt.z1t"),
+        "snapshot \"main\" { cell: \"main.z1c\", emit: relaxed }\n",
+    )
+    .unwrap();
+
+    let output = z1(&["test", "fmt.z1t", "--format", "json"], dir.path());
+    assert_eq!(output.status.code(), Some(11));
+    assert_eq!(json(&output)["failed"], 1);
+
+    let output = z1(
+        &["test", "fmt.z1t", "--update-snapshots", "--format", "json"],
+        dir.path(),
+    );
+    assert!(output.status.success());
+    let summary = json(&output);
+    assert_eq!(summary["passed"], 1);
+    assert_eq!(summary["snapshots_updated"][0], "snapshots/fmt/main.snap");
+
+    let output = z1(&["test", "fmt.z1t", "--format", "json"], dir.path());
+    assert!(output.status.success());
+    assert!(json(&output).get("snapshots_updated").is_none());
+}
+
+#[test]
+fn test_test_and_prov_emit_json() {
+    let dir = workspace();
+    fs::write(
+        dir.path().join("math.z1t"),
+        "spec \"adds\" {\n  assert 1 + 1 == 2;\n}\n",
+    )
+    .unwrap();
+    let output = z1(&["test", "math.z1t", "--format", "json"], dir.path());
+    assert!(output.status.success());
+    let summary = json(&output);
+    assert_eq!(summary["passed"], 1);
+    assert_eq!(summary["failed"], 0);
+
+    let output = z1(
+        &[
+            "--format",
+            "json",
+            "prov",
+            "append",
+            "chain.z1p",
+            "--id",
+            "cell:app.main@v1",
+            "--actor",
+            "agent:test",
+            "--diff",
+            "main.z1c",
+        ],
+        dir.path(),
+    );
+    assert!(output.status.success());
+    let appended = json(&output);
+    assert_eq!(appended["entry_id"], "cell:app.main@v1");
+    assert_eq!(appended["index"], 1);
+
+    let output = z1(
+        &["prov", "verify", "chain.z1p", "--format", "json"],
+        dir.path(),
+    );
+    assert!(output.status.success());
+    let verified = json(&output);
+    assert_eq!(verified["valid"], true);
+    assert_eq!(verified["entries"], 1);
+}
+
+#[test]
+fn test_failures_are_reported_as_json() {
+    let dir = workspace();
+    let output = z1(&["--format", "json", "compile", "missing.z1c"], dir.path());
+    assert!(!output.status.success());
+    let error = json(&output);
+    assert!(error["error"].as_str().unwrap().contains("missing.z1c"));
+
+    let output = z1(&["--format", "json", "run", "main.z1c"], dir.path());
+    assert!(!output.status.success());
+    let error = json(&output);
+    assert!(error["error"]
+        .as_str()
+        .unwrap()
+        .contains("does not support --format json"));
+}
@@ -0,0 +1,168 @@
+//! Integration tests for `z1 lint`
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+fn z1_lint(args: &[&str]) -> Output {
+    Command::new("cargo")
+        .args(["run", "-p", "z1-cli", "--", "lint"])
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("Failed to run z1 lint")
+}
+
+fn write_cell(dir: &Path, name: &str, caps: &str, function: &str) -> String {
+    let path = dir.join(name);
+    fs::write(
+        &path,
+        format!(
+            "m app.{}:1.0 ctx=100 caps=[{caps}]\n{function}\n",
+            name.replace(".z1c", "")
+        ),
+    )
+    .unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn test_lint_clean_cell() {
+    let dir = TempDir::new().unwrap();
+    let cell = write_cell(
+        dir.path(),
+        "ok.z1c",
+        "",
+        "f add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }",
+    );
+
+    let output = z1_lint(&[&cell]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    assert!(stdout.contains("Linted 1 cell(s): 0 error(s), 0 warning(s)"));
+}
+
+#[test]
+fn test_lint_reports_every_checker_as_json() {
+    let dir = TempDir::new().unwrap();
+    write_cell(
+        dir.path(),
+        "types.z1c",
+        "",
+        "f f(a: Missing)->U32 eff [pure] { ret 1; }",
+    );
+    write_cell(
+        dir.path(),
+        "effects.z1c",
+        "",
+        "f f()->U32 eff [net] { ret 1; }",
+    );
+    write_cell(
+        dir.path(),
+        "policy.z1c",
+        "",
+        "f f(a: U32, b: U32, c: U32, d: U32, e: U32, g: U32, h: U32)->U32 eff [pure] { ret a; }",
+    );
+
+    let output = z1_lint(&[dir.path().to_str().unwrap(), "--format", "json"]);
+    // Type errors are the earliest failing stage
+    assert_eq!(output.status.code(), Some(4));
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["cells"], 3);
+    let codes: Vec<&str> = report["diagnostics"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|diagnostic| diagnostic["code"].as_str().unwrap())
+        .collect();
+    assert!(codes.contains(&"T001"), "{codes:?}");
+    assert!(codes.contains(&"E001"), "{codes:?}");
+    assert!(codes.contains(&"L001"), "{codes:?}");
+}
+
+#[test]
+fn test_lint_sarif_and_exit_codes() {
+    let dir = TempDir::new().unwrap();
+    let broken = write_cell(dir.path(), "broken.z1c", "", "f f( {");
+    let output = z1_lint(&[&broken, "--format", "sarif"]);
+    assert_eq!(output.status.code(), Some(3));
+
+    let sarif: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(sarif["version"], "2.1.0");
+    let result = &sarif["runs"][0]["results"][0];
+    assert_eq!(result["ruleId"], "P001");
+    assert_eq!(result["level"], "error");
+    let region = &result["locations"][0]["physicalLocation"]["region"];
+    assert_eq!(region["startLine"], 2);
+    assert_eq!(region["startColumn"], 6);
+
+    // Warnings only fail the run when denied
+    let warn = write_cell(
+        dir.path(),
+        "warn.z1c",
+        "net",
+        "f id(a: U32)->U32 eff [pure] { ret a; }",
+    );
+    assert!(z1_lint(&[&warn]).status.success());
+    assert_eq!(z1_lint(&[&warn, "--deny-warnings"]).status.code(), Some(8));
+}
+
+#[test]
+fn test_lint_rechecks_only_cells_whose_imports_changed_api() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join(".z1")).unwrap();
+    write_cell(
+        dir.path(),
+        "lib.z1c",
+        "",
+        "f scale(x: U32)->U32 eff [pure] { ret x; }",
+    );
+    write_cell(
+        dir.path(),
+        "main.z1c",
+        "",
+        "u \"app/lib\" only [scale]\nf run()->U32 eff [pure] { ret scale(2); }",
+    );
+    let lint = || {
+        let output = Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+            .args(["--format", "json", "lint", "."])
+            .current_dir(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        (
+            report["cache"]["hits"].as_u64().unwrap(),
+            report["cache"]["misses"].as_u64().unwrap(),
+        )
+    };
+
+    assert_eq!(lint(), (0, 2));
+    assert_eq!(lint(), (2, 0));
+
+    // Body edit: the importer's checks still hold
+    write_cell(
+        dir.path(),
+        "lib.z1c",
+        "",
+        "f scale(x: U32)->U32 eff [pure] { ret x + 1; }",
+    );
+    assert_eq!(lint(), (1, 1));
+
+    // Signature edit: the importer is checked again
+    write_cell(
+        dir.path(),
+        "lib.z1c",
+        "",
+        "f scale(x: U32, y: U32)->U32 eff [pure] { ret x; }",
+    );
+    let output = Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(["lint", "."])
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("; 0 check(s) reused, 2 run"), "{stdout}");
+}
@@ -0,0 +1,73 @@
+//! Integration tests for `z1 merge`
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+const BASE: &str = "m app:1.0 caps=[]\n\
+                    f one()->U32 eff [pure] { ret 1; }\n\
+                    f two()->U32 eff [pure] { ret 2; }\n";
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run z1")
+}
+
+/// Write the three versions as git does, in files without an extension.
+fn versions(ours: &str, theirs: &str) -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("base"), BASE).unwrap();
+    fs::write(dir.path().join("ours"), ours).unwrap();
+    fs::write(dir.path().join("theirs"), theirs).unwrap();
+    dir
+}
+
+#[test]
+fn test_merge_combines_edits_to_different_functions() {
+    let dir = versions(
+        &BASE.replace("ret 1;", "ret 10;"),
+        &BASE.replace("ret 2;", "ret 20;"),
+    );
+    let output = z1(
+        &["merge", "base", "ours", "theirs", "--path", "app.z1c"],
+        dir.path(),
+    );
+    assert!(output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Merged app.z1c cleanly"));
+    let merged = fs::read_to_string(dir.path().join("ours")).unwrap();
+    assert!(merged.contains("{ ret 10; }"), "{merged}");
+    assert!(merged.contains("{ ret 20; }"), "{merged}");
+}
+
+#[test]
+fn test_merge_reports_conflicting_functions() {
+    let dir = versions(
+        &BASE.replace("ret 1;", "ret 10;"),
+        &BASE.replace("ret 1;", "ret 11;"),
+    );
+    let output = z1(
+        &["--format", "json", "merge", "base", "ours", "theirs"],
+        dir.path(),
+    );
+    assert_eq!(output.status.code(), Some(10), "{output:?}");
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let conflicts = report["conflicts"].as_array().unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0]["kind"], "fn");
+    assert_eq!(conflicts[0]["name"], "one");
+    assert!(conflicts[0]["ours"]
+        .as_str()
+        .unwrap()
+        .starts_with("sha3-256:"));
+
+    let merged = fs::read_to_string(dir.path().join("ours")).unwrap();
+    assert!(merged.contains("<<<<<<< ours sha3-256:"), "{merged}");
+    assert!(merged.contains(">>>>>>> theirs sha3-256:"), "{merged}");
+    assert!(merged.contains("f two()->U32 eff [pure] { ret 2; }\n"));
+}
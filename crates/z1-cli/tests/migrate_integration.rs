@@ -0,0 +1,92 @@
+//! Integration tests for `z1 migrate`
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+use z1_prov::ProvenanceChainExt;
+
+const LEGACY: &str = "m app:1.0 caps=[Net]\nf get()->Unit eff [Net] { ret (); }\n";
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run z1")
+}
+
+#[test]
+fn test_migrate_rewrites_cells_and_records_provenance() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("app.z1c"), LEGACY).unwrap();
+    fs::write(
+        dir.path().join("lib.z1r"),
+        "module lib : 1.0\n\nfn id(x: U32) -> U32\n  eff [pure]\n{\n  ret x;\n}\n",
+    )
+    .unwrap();
+
+    let output = z1(
+        &[
+            "migrate", "--from", "0.1", "--to", "0.2", "--format", "json",
+        ],
+        dir.path(),
+    );
+    assert!(output.status.success(), "{output:?}");
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let cells = report["cells"].as_array().unwrap();
+    assert_eq!(cells.len(), 1, "{report}");
+    assert_eq!(cells[0]["path"], "app.z1c");
+    assert_eq!(cells[0]["rewrites"][0]["rule"], "lowercase-effects");
+    assert_eq!(
+        fs::read_to_string(dir.path().join("app.z1c")).unwrap(),
+        "m app:1.0 caps=[net]\nf get()->Unit eff [net] { ret (); }\n"
+    );
+
+    let chain =
+        z1_prov::ProvenanceChain::load_from_file(dir.path().join("provenance.z1p")).unwrap();
+    z1_prov::verify_chain(&chain).unwrap();
+    let entry = &chain.entries[0];
+    assert!(
+        entry.entry_id.starts_with("migrate:0.1->0.2"),
+        "{}",
+        entry.entry_id
+    );
+    assert_eq!(entry.cells[0].module, "app");
+
+    // Nothing left to migrate, so nothing else is recorded
+    assert!(z1(&["migrate", "--from", "0.1"], dir.path())
+        .status
+        .success());
+    let chain =
+        z1_prov::ProvenanceChain::load_from_file(dir.path().join("provenance.z1p")).unwrap();
+    assert_eq!(chain.entries.len(), 1);
+}
+
+#[test]
+fn test_migrate_dry_run_prints_diff() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("app.z1c"), LEGACY).unwrap();
+
+    let output = z1(&["migrate", "--from", "0.1", "--dry-run"], dir.path());
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("\n-m app:1.0 caps=[Net]\n"), "{stdout}");
+    assert!(stdout.contains("\n+m app:1.0 caps=[net]\n"), "{stdout}");
+    assert_eq!(
+        fs::read_to_string(dir.path().join("app.z1c")).unwrap(),
+        LEGACY
+    );
+    assert!(!dir.path().join("provenance.z1p").exists());
+}
+
+#[test]
+fn test_migrate_rejects_unknown_versions() {
+    let dir = TempDir::new().unwrap();
+    let output = z1(&["migrate", "--from", "0.2", "--to", "0.1"], dir.path());
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("migrations only upgrade"), "{stderr}");
+}
@@ -0,0 +1,80 @@
+//! Integration tests for `z1 new` and `z1 init`
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run z1")
+}
+
+fn assert_success(output: &Output) {
+    assert!(
+        output.status.success(),
+        "stdout: {}\nstderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn test_new_project_passes_checks() {
+    let dir = TempDir::new().unwrap();
+    assert_success(&z1(&["new", "demo"], dir.path()));
+
+    let project = dir.path().join("demo");
+    let manifest = fs::read_to_string(project.join("z1.toml")).unwrap();
+    assert!(manifest.contains("name = \"demo\""));
+    assert!(manifest.contains("[policy]"));
+    assert!(project.join("tests/main.z1t").is_file());
+
+    assert_success(&z1(&["lint", "--deny-warnings", "src"], &project));
+    assert_success(&z1(&["fmt", "--check", "src/main.z1r"], &project));
+    assert_success(&z1(&["test", "tests/main.z1t"], &project));
+    assert_success(&z1(&["run", "src/main.z1r"], &project));
+
+    // The directory now exists
+    assert!(!z1(&["new", "demo"], dir.path()).status.success());
+}
+
+#[test]
+fn test_new_cell_writes_header() {
+    let dir = TempDir::new().unwrap();
+    assert_success(&z1(&["init", "--name", "app"], dir.path()));
+
+    let src = dir.path().join("src");
+    assert_success(&z1(
+        &[
+            "new",
+            "cell",
+            "http.server",
+            "--caps",
+            "net",
+            "--ctx",
+            "128",
+        ],
+        &src,
+    ));
+    let cell = fs::read_to_string(src.join("http/server.z1r")).unwrap();
+    assert!(cell.starts_with("module http.server : 1.0\n  ctx = 128\n  caps = [net]\n"));
+    assert_success(&z1(&["lint", "--deny-warnings", "."], dir.path()));
+
+    let output = z1(&["new", "cell", "http.server"], dir.path());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already exists"));
+    let output = z1(&["new", "cell", "x", "--caps", "bogus"], dir.path());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown capability `bogus`"));
+}
+
+#[test]
+fn test_new_cell_requires_project() {
+    let dir = TempDir::new().unwrap();
+    let output = z1(&["new", "cell", "a.b"], dir.path());
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("run `z1 init` first"));
+}
@@ -0,0 +1,94 @@
+//! Integration tests for `z1 pack`
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use serde_json::Value;
+use tempfile::TempDir;
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run z1")
+}
+
+fn write(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
+
+/// A project whose `main` imports `util.text`, which imports `util.base`.
+fn project() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    write(
+        &dir.path().join("z1.toml"),
+        "[project]\nname = \"app\"\nversion = \"0.1.0\"\n",
+    );
+    write(
+        &dir.path().join("src/util/base.z1c"),
+        "m util.base:1.0 caps=[]\nf trim(s: Str)->Str eff [pure] { let kept: Str = s; ret kept; }\n",
+    );
+    write(
+        &dir.path().join("src/util/text.z1c"),
+        "m util.text:1.0 caps=[]\nu \"util/base\" only [trim]\n\
+         f shout(s: Str)->Str eff [pure] { let loud: Str = trim(s); ret loud; }\n",
+    );
+    write(
+        &dir.path().join("src/main.z1c"),
+        "m main:1.0 caps=[]\nu \"util/text\" only [shout]\n\
+         f run()->Str eff [pure] { ret shout(\"hi\"); }\n",
+    );
+    dir
+}
+
+fn pack(dir: &Path, args: &[&str]) -> Value {
+    let output = z1(
+        &[&["--format", "json", "pack", "src/main.z1c"], args].concat(),
+        dir,
+    );
+    assert!(output.status.success(), "{output:?}");
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn test_pack_bundles_transitive_dependencies() {
+    let dir = project();
+    let output = z1(&["pack", "src/main.z1c"], dir.path());
+    assert!(output.status.success(), "{output:?}");
+    let text = String::from_utf8_lossy(&output.stdout);
+    let base = text.find("// util/base (source)").expect("base packed");
+    let util = text.find("// util/text (source)").expect("text packed");
+    let main = text.find("m main:1.0").unwrap();
+    assert!(base < util && util < main, "{text}");
+    assert!(text.contains("ret loud;"), "{text}");
+
+    let report = pack(dir.path(), &[]);
+    assert_eq!(report["cell"], "src/main.z1c");
+    assert_eq!(report["deps"][0]["import"], "util/text");
+    assert_eq!(report["deps"][1]["import"], "util/base");
+    assert_eq!(report["deps"][1]["form"], "source");
+}
+
+#[test]
+fn test_pack_falls_back_to_interfaces_within_the_budget() {
+    let dir = project();
+    let full = pack(dir.path(), &[]);
+    let budget = (full["tokens"].as_u64().unwrap() - 1).to_string();
+    let report = pack(dir.path(), &["--budget", &budget]);
+    assert!(report["tokens"].as_u64().unwrap() < full["tokens"].as_u64().unwrap());
+    assert_eq!(report["deps"][0]["form"], "source");
+    assert_eq!(report["deps"][1]["form"], "interface");
+    let source = report["source"].as_str().unwrap();
+    assert!(
+        source.contains("f trim(s: Str)->Str eff [pure] { … }"),
+        "{source}"
+    );
+
+    let output = z1(&["pack", "src/main.z1c", "--budget", "1"], dir.path());
+    assert_eq!(output.status.code(), Some(6));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("over the budget of 1"));
+}
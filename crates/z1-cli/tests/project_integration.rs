@@ -0,0 +1,292 @@
+//! Integration tests for `z1.toml` projects and import resolution
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run z1")
+}
+
+fn write(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
+
+/// A project whose `app.main` imports from its own `util.text` and from a
+/// `std` dependency outside the project.
+fn project(manifest_extra: &str, import: &str) -> TempDir {
+    let dir = TempDir::new().unwrap();
+    write(
+        &dir.path().join("app/z1.toml"),
+        &format!(
+            "[project]\nname = \"app\"\nversion = \"0.1.0\"\n\n\
+             [dependencies]\nstd = {{ path = \"../std\" }}\n{manifest_extra}"
+        ),
+    );
+    write(
+        &dir.path().join("std/clock.z1c"),
+        "m std.clock:1.0 caps=[time]\nt Instant = { ms: U64 }\nf now()->Instant eff [time] { ret Instant { ms: 0 }; }\n",
+    );
+    write(
+        &dir.path().join("app/src/util/text.z1c"),
+        "m util.text:1.0 caps=[]\nf shout(s: Str)->Str eff [pure] { ret s; }\n",
+    );
+    write(
+        &dir.path().join("app/src/main.z1c"),
+        &format!(
+            "m main:1.0 caps=[]\nu \"util/text\" only [shout]\n{import}\n\
+             f run()->Unit eff [pure] {{ }}\n"
+        ),
+    );
+    dir
+}
+
+#[test]
+fn test_lint_resolves_project_imports() {
+    let dir = project("", "u \"std/clock\" as C only [Instant]");
+    let app = dir.path().join("app");
+    let output = z1(&["lint", "src"], &app);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    // Names the imported cell does not declare
+    let dir = project("", "u \"std/clock\" as C only [Duration]");
+    let output = z1(&["lint", "src/main.z1c"], &dir.path().join("app"));
+    assert_eq!(output.status.code(), Some(4));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("'Duration' is not declared"));
+
+    // Imported functions need the caps of their effects
+    let dir = project("", "u \"std/clock\" only [now]");
+    let output = z1(&["lint", "src/main.z1c"], &dir.path().join("app"));
+    assert_eq!(output.status.code(), Some(5));
+}
+
+#[test]
+fn test_unresolved_imports_fail_lint_and_build() {
+    let dir = project("", "u \"std/calendar\" only [Date]");
+    let app = dir.path().join("app");
+
+    let output = z1(&["lint", "src", "--format", "json"], &app);
+    assert_eq!(output.status.code(), Some(9));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let diagnostic = &report["diagnostics"][0];
+    assert_eq!(diagnostic["code"], "R001");
+    assert!(diagnostic["message"]
+        .as_str()
+        .unwrap()
+        .contains("cannot resolve import `std/calendar`"));
+
+    let output = z1(&["build"], &app.join("src"));
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Import resolution failed"));
+}
+
+#[test]
+fn test_build_uses_manifest_targets_and_policy() {
+    let dir = project(
+        "\n[target]\ntargets = [\"ts\", \"wasm\"]\nout-dir = \"dist\"\n",
+        "u \"std/clock\" as C only [Instant]",
+    );
+    let app = dir.path().join("app");
+    let output = z1(&["build"], &app.join("src"));
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(app.join("dist/ts/main.ts").is_file());
+    assert!(app.join("dist/wasm/util_text.wasm").is_file());
+
+    // A stricter policy in the manifest applies to lint
+    let dir = project(
+        "\n[policy]\nfn_max_params = 0\n",
+        "u \"std/clock\" as C only [Instant]",
+    );
+    let output = z1(&["lint", "src"], &dir.path().join("app"));
+    assert_eq!(output.status.code(), Some(7));
+}
+
+#[test]
+fn test_graph_annotates_workspace_and_dependencies() {
+    let dir = project(
+        "",
+        "u \"std/clock\" as C only [Instant]\nu \"std/calendar\"",
+    );
+    let app = dir.path().join("app");
+
+    let output = z1(&["graph", "--format", "json"], &app.join("src"));
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let graph: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let nodes = graph["nodes"].as_array().unwrap();
+    let node = |module: &str| {
+        nodes
+            .iter()
+            .find(|node| node["module"] == module)
+            .unwrap_or_else(|| panic!("no node {module}: {nodes:?}"))
+    };
+    assert_eq!(node("main")["path"], "src/main.z1c");
+    assert!(node("util.text")["tokens"].as_u64().unwrap() > 0);
+    // Resolved through the `std` dependency
+    assert_eq!(node("std.clock")["external"], true);
+    assert_eq!(node("std.clock")["caps"][0], "time");
+    assert!(node("std.calendar")["tokens"].is_null());
+    assert_eq!(graph["edges"].as_array().unwrap().len(), 3);
+
+    let output = z1(&["graph", "--emit", "mermaid"], &app);
+    let mermaid = String::from_utf8_lossy(&output.stdout);
+    assert!(mermaid.starts_with("graph LR\n"), "{mermaid}");
+    let output = z1(&["graph"], &app);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\"main\" -> \"std.clock\";"));
+}
+
+#[test]
+fn test_compile_resolves_imports_in_a_workspace() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join(".z1")).unwrap();
+    // Found by the module path it declares, not where it lives
+    write(
+        &dir.path().join("lib/strings.z1c"),
+        "m util.text:1.0 caps=[]\nf shout(s: Str)->Str eff [pure] { ret s; }\n",
+    );
+    let main =
+        |import: &str| format!("m main:1.0 caps=[]\n{import}\nf run()->Unit eff [pure] {{ }}\n");
+    write(
+        &dir.path().join("app/main.z1c"),
+        &main("u \"util/text\" as T only [shout]"),
+    );
+    let output = z1(&["compile", "app/main.z1c", "--verbose"], dir.path());
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Imports: 1 resolved"));
+
+    // A name the imported cell does not declare
+    write(
+        &dir.path().join("app/main.z1c"),
+        &main("u \"util/text\" as T only [whisper]"),
+    );
+    let output = z1(&["compile", "app/main.z1c"], dir.path());
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("'whisper' is not declared"));
+
+    // A dependency that does not exist
+    write(
+        &dir.path().join("app/main.z1c"),
+        &main("u \"net/http\" as H only [listen]"),
+    );
+    let output = z1(&["compile", "app/main.z1c"], dir.path());
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("cannot resolve import `net/http`"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn test_std_imports_resolve_to_the_bundled_library() {
+    let dir = TempDir::new().unwrap();
+    let cell =
+        |import: &str| format!("m app:1.0 caps=[]\n{import}\nf run()->Unit eff [pure] {{ }}\n");
+    write(
+        &dir.path().join("app.z1c"),
+        &cell("u \"std/math\" as M only [gcd, isqrt]"),
+    );
+    let output = z1(&["compile", "app.z1c"], dir.path());
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let shim = fs::read_to_string(dir.path().join("std_math.ts")).unwrap();
+    assert!(shim.contains("export function gcd("), "{shim}");
+
+    // WASM host imports come with the JS glue
+    let output = z1(
+        &["compile", "app.z1c", "--target", "wasm", "--emit-glue"],
+        dir.path(),
+    );
+    assert!(output.status.success());
+    let shim = fs::read_to_string(dir.path().join("std_math.js")).unwrap();
+    assert!(shim.contains("export function stdMath()"), "{shim}");
+
+    write(
+        &dir.path().join("app.z1c"),
+        &cell("u \"std/math\" only [lcm]"),
+    );
+    let output = z1(&["compile", "app.z1c"], dir.path());
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("'lcm' is not declared"));
+
+    // Imported effects need the caps of the importing cell
+    write(
+        &dir.path().join("app.z1c"),
+        &cell("u \"std/io\" only [println]"),
+    );
+    let output = z1(&["lint", "app.z1c"], dir.path());
+    assert_eq!(output.status.code(), Some(5));
+
+    write(&dir.path().join("app.z1c"), &cell("u \"std/calendar\""));
+    let output = z1(&["lint", "app.z1c", "--format", "json"], dir.path());
+    assert_eq!(output.status.code(), Some(9));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let message = report["diagnostics"][0]["message"].as_str().unwrap();
+    assert!(
+        message.contains("the standard library 1.0 has no such module"),
+        "{message}"
+    );
+}
+
+#[test]
+fn test_manifest_defaults_apply_unless_flags_override_them() {
+    let dir = project(
+        "\n[target]\ntargets = [\"wasm\"]\n\n[defaults]\nfmt-mode = \"relaxed\"\nopt-level = 0\n",
+        "",
+    );
+    let app = dir.path().join("app");
+
+    let relaxed = z1(&["fmt", "--stdout", "src/util/text.z1c"], &app);
+    assert!(relaxed.status.success());
+    assert!(String::from_utf8_lossy(&relaxed.stdout).starts_with("module util.text"));
+    let compact = z1(
+        &["fmt", "--stdout", "--mode", "compact", "src/util/text.z1c"],
+        &app,
+    );
+    assert!(String::from_utf8_lossy(&compact.stdout).starts_with("m util.text"));
+
+    let output = z1(&["compile", "src/util/text.z1c"], &app);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(app.join("src/util/text.wat").is_file());
+    assert!(!app.join("src/util/text.ts").exists());
+    let output = z1(&["compile", "--target", "ts", "src/util/text.z1c"], &app);
+    assert!(output.status.success());
+    assert!(app.join("src/util/text.ts").is_file());
+}
+
+#[test]
+fn test_invalid_manifest_defaults_are_errors() {
+    let dir = project("\n[defaults]\nfmt-mode = \"dense\"\n", "");
+    let output = z1(&["fmt", "--check", "src"], &dir.path().join("app"));
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown fmt-mode `dense`"));
+}
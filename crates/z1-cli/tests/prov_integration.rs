@@ -27,14 +27,21 @@ fn create_test_chain_with_signatures(dir: &TempDir) -> (PathBuf, PathBuf) {
     let entry = ProvenanceEntry {
         entry_id: "cell:test@v1".to_string(),
         prev: None,
+        merged: vec![],
         actor: "agent:test/1.0".to_string(),
         model: "test-model-2025".to_string(),
         prompt_sha3: "a".repeat(64),
         prompt_excerpt: "Test prompt for integration test".to_string(),
+        redaction: None,
         tools: vec!["z1-fmt".to_string()],
         diff_sha3: "b".repeat(64),
+        cells: vec![],
         timestamp: Utc::now(),
         signatures: vec![],
+        keyless: vec![],
+        rotation: None,
+        checkpoint: None,
+        timestamp_token: None,
     };
 
     chain.append(entry.clone()).unwrap();
@@ -177,3 +184,691 @@ fn test_prov_log_empty_chain() {
     let stdout = String::from_utf8_lossy(&output.stdout);
     assert!(stdout.contains("Chain is empty"));
 }
+
+#[test]
+fn test_prov_append_sign_and_verify() {
+    let dir = TempDir::new().unwrap();
+    let chain_path = dir.path().join("chain.z1p");
+    let key_path = dir.path().join("key.json");
+    let diff_path = dir.path().join("change.diff");
+    fs::write(&diff_path, "+fn serve() -> Unit\n").unwrap();
+
+    let run = |args: &[&str]| {
+        Command::new(cli_bin())
+            .arg("prov")
+            .args(args)
+            .env("Z1_ACTOR", "agent:test/1.0")
+            .output()
+            .expect("failed to execute z1-cli")
+    };
+    let chain = chain_path.to_str().unwrap();
+    let diff = diff_path.to_str().unwrap();
+    let key = key_path.to_str().unwrap();
+
+    assert!(run(&["keygen", key]).status.success());
+    for (id, prompt) in [("cell:app@v1", "Add a server"), ("cell:app@v2", "Fix it")] {
+        let output = run(&[
+            "append", chain, "--id", id, "--prompt", prompt, "--diff", diff, "--tool", "z1-fmt",
+        ]);
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    // Only the latest entry can be signed without breaking the chain
+    assert!(!run(&[
+        "sign",
+        chain,
+        "--key",
+        key,
+        "--as",
+        "dev:test",
+        "--id",
+        "cell:app@v1"
+    ])
+    .status
+    .success());
+    assert!(run(&["sign", chain, "--key", key, "--as", "dev:test"])
+        .status
+        .success());
+
+    let loaded = ProvenanceChain::load_from_file(&chain_path).unwrap();
+    assert_eq!(loaded.len(), 2);
+    assert_eq!(loaded.entries[0].actor, "agent:test/1.0");
+    assert_eq!(loaded.entries[0].prompt_excerpt, "Add a server");
+    assert!(loaded.entries[0].diff_sha3.starts_with("sha3-256:"));
+    assert_eq!(loaded.entries[1].signatures[0].by, "dev:test");
+
+    let keypair: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&key_path).unwrap()).unwrap();
+    let keys_path = dir.path().join("keys.json");
+    fs::write(
+        &keys_path,
+        serde_json::json!({ "dev:test": keypair["public_key"] }).to_string(),
+    )
+    .unwrap();
+    let output = run(&["verify", chain, "--keys", keys_path.to_str().unwrap()]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("1 signatures verified"));
+}
+
+#[test]
+fn test_prov_keystore_rotation() {
+    let dir = TempDir::new().unwrap();
+    let chain_path = dir.path().join("chain.z1p");
+    let diff_path = dir.path().join("change.diff");
+    fs::write(&diff_path, "+fn main() -> Unit\n").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = Command::new(cli_bin())
+            .arg("prov")
+            .args(args)
+            .env("Z1_KEYSTORE", dir.path().join("keys"))
+            .env("Z1_KEY_PASSPHRASE", "correct horse")
+            .env("Z1_ACTOR", "dev:alice")
+            .env("Z1_SIGNER", "dev:alice")
+            .output()
+            .expect("failed to execute z1-cli");
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+        )
+    };
+    let chain = chain_path.to_str().unwrap();
+    let diff = diff_path.to_str().unwrap();
+
+    assert!(run(&["keygen", "--name", "alice-2025"]).0);
+    assert!(run(&["keygen", "--name", "alice-2026"]).0);
+    let (ok, listing) = run(&["keys"]);
+    assert!(ok);
+    assert!(listing.contains("alice-2025") && listing.contains("alice-2026"));
+    let contents = fs::read_to_string(dir.path().join("keys/alice-2025.json")).unwrap();
+    assert!(!contents.contains("private_key"));
+
+    assert!(run(&["append", chain, "--id", "cell:app@v1", "--diff", diff]).0);
+    assert!(run(&["sign", chain, "--key-name", "alice-2025"]).0);
+    assert!(
+        run(&[
+            "rotate",
+            chain,
+            "--from",
+            "alice-2025",
+            "--to",
+            "alice-2026"
+        ])
+        .0
+    );
+    assert!(run(&["append", chain, "--id", "cell:app@v2", "--diff", diff]).0);
+    assert!(run(&["sign", chain, "--key-name", "alice-2026"]).0);
+
+    // Verifiers only need the original key; the rotation entry endorses the new one
+    let first = ProvenanceChain::load_from_file(&chain_path).unwrap();
+    let old_key = first.entries[1].rotation.as_ref().unwrap().old_key.clone();
+    let keys_path = dir.path().join("keys.json");
+    fs::write(
+        &keys_path,
+        serde_json::json!({ "dev:alice": old_key }).to_string(),
+    )
+    .unwrap();
+    let keys = keys_path.to_str().unwrap();
+    let (ok, report) = run(&["verify", chain, "--keys", keys]);
+    assert!(ok, "{report}");
+    assert!(report.contains("3 signatures verified"));
+
+    // The retired key no longer verifies
+    assert!(run(&["append", chain, "--id", "cell:app@v3", "--diff", diff]).0);
+    assert!(run(&["sign", chain, "--key-name", "alice-2025"]).0);
+    assert!(!run(&["verify", chain, "--keys", keys]).0);
+}
+
+#[test]
+fn test_prov_stamp_and_verify_timestamps() {
+    let dir = TempDir::new().unwrap();
+    let chain_path = dir.path().join("chain.z1p");
+    let tsa_key = dir.path().join("tsa.json");
+    let diff_path = dir.path().join("change.diff");
+    fs::write(&diff_path, "+fn main() -> Unit\n").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = Command::new(cli_bin())
+            .arg("prov")
+            .args(args)
+            .env("Z1_ACTOR", "agent:test/1.0")
+            .output()
+            .expect("failed to execute z1-cli");
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+        )
+    };
+    let chain = chain_path.to_str().unwrap();
+    let diff = diff_path.to_str().unwrap();
+    let key = tsa_key.to_str().unwrap();
+
+    assert!(run(&["keygen", key]).0);
+    assert!(run(&["append", chain, "--id", "cell:app@v1", "--diff", diff]).0);
+    assert!(run(&["stamp", chain, "--authority", "tsa:ci", "--key", key]).0);
+
+    let keypair: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&tsa_key).unwrap()).unwrap();
+    let tsa_keys = dir.path().join("tsa-keys.json");
+    fs::write(
+        &tsa_keys,
+        serde_json::json!({ "tsa:ci": keypair["public_key"] }).to_string(),
+    )
+    .unwrap();
+    let tsa_keys = tsa_keys.to_str().unwrap();
+
+    let (ok, report) = run(&[
+        "verify",
+        chain,
+        "--tsa-keys",
+        tsa_keys,
+        "--require-timestamps",
+    ]);
+    assert!(ok, "{report}");
+    assert!(report.contains("1 timestamps verified"));
+    assert!(run(&["log", chain]).1.contains("Trusted Time"));
+
+    // An unstamped entry fails only when timestamps are required
+    assert!(run(&["append", chain, "--id", "cell:app@v2", "--diff", diff]).0);
+    assert!(run(&["verify", chain, "--tsa-keys", tsa_keys]).0);
+    assert!(
+        !run(&[
+            "verify",
+            chain,
+            "--tsa-keys",
+            tsa_keys,
+            "--require-timestamps"
+        ])
+        .0
+    );
+}
+
+#[test]
+fn test_prov_merge_diverged_copies() {
+    let dir = TempDir::new().unwrap();
+    let ours_path = dir.path().join("ours.z1p");
+    let theirs_path = dir.path().join("theirs.z1p");
+    let diff_path = dir.path().join("change.diff");
+    fs::write(&diff_path, "+fn main() -> Unit\n").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = Command::new(cli_bin())
+            .arg("prov")
+            .args(args)
+            .env("Z1_ACTOR", "agent:test/1.0")
+            .output()
+            .expect("failed to execute z1-cli");
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+        )
+    };
+    let ours = ours_path.to_str().unwrap();
+    let theirs = theirs_path.to_str().unwrap();
+    let diff = diff_path.to_str().unwrap();
+
+    assert!(run(&["append", ours, "--id", "cell:app@v1", "--diff", diff]).0);
+    fs::copy(&ours_path, &theirs_path).unwrap();
+    assert!(run(&["append", ours, "--id", "cell:app@v2", "--diff", diff]).0);
+    assert!(run(&["append", theirs, "--id", "cell:lib@v2", "--diff", diff]).0);
+
+    let (ok, report) = run(&["merge", ours, theirs, "--id", "merge:lib"]);
+    assert!(ok, "{report}");
+    assert!(report.contains("Merged 1 entries"));
+
+    let merged = ProvenanceChain::load_from_file(&ours_path).unwrap();
+    assert_eq!(merged.len(), 4);
+    assert_eq!(merged.heads().len(), 1);
+    let merge = merged.latest().unwrap();
+    assert_eq!(merge.entry_id, "merge:lib");
+    assert_eq!(merge.merged.len(), 1);
+    assert!(run(&["verify", ours]).0);
+    assert!(run(&["log", ours]).1.contains("Merged"));
+
+    // Merging again is a no-op, and the other copy fast-forwards
+    assert!(run(&["merge", ours, theirs])
+        .1
+        .contains("Already up to date"));
+    assert!(run(&["merge", theirs, ours])
+        .1
+        .contains("Fast-forwarded 2 entries"));
+
+    // The same entry recorded on both branches is a conflict
+    assert!(run(&["append", ours, "--id", "cell:app@v3", "--diff", diff]).0);
+    fs::write(&diff_path, "+fn other() -> Unit\n").unwrap();
+    assert!(run(&["append", theirs, "--id", "cell:app@v3", "--diff", diff]).0);
+    assert!(!run(&["merge", ours, theirs]).0);
+}
+
+#[test]
+fn test_prov_log_filters_as_json() {
+    let dir = TempDir::new().unwrap();
+    let chain_path = dir.path().join("chain.z1p");
+    let diff_path = dir.path().join("change.diff");
+    fs::write(&diff_path, "+fn main() -> Unit\n").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = Command::new(cli_bin())
+            .arg("prov")
+            .args(args)
+            .output()
+            .expect("failed to execute z1-cli");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    let chain = chain_path.to_str().unwrap();
+    let diff = diff_path.to_str().unwrap();
+
+    for (id, actor, model) in [
+        ("cell:app@v1", "agent:foo/1.0", "model-a"),
+        ("cell:lib@v1", "agent:bar/1.0", "model-b"),
+        ("cell:app@v2", "agent:foo/1.1", "model-b"),
+    ] {
+        run(&[
+            "append", chain, "--id", id, "--actor", actor, "--model", model, "--diff", diff,
+        ]);
+    }
+
+    let json = |args: &[&str]| -> Vec<serde_json::Value> {
+        let mut full = vec!["log", chain, "--format", "json"];
+        full.extend_from_slice(args);
+        serde_json::from_str(&run(&full)).unwrap()
+    };
+    let ids = |records: Vec<serde_json::Value>| -> Vec<String> {
+        records
+            .iter()
+            .map(|r| r["entry_id"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let records = json(&["--actor", "agent:foo"]);
+    assert_eq!(records[1]["index"], 3);
+    assert!(records[1]["hash"]
+        .as_str()
+        .unwrap()
+        .starts_with("sha3-256:"));
+    assert_eq!(ids(records), ["cell:app@v1", "cell:app@v2"]);
+    assert_eq!(
+        ids(json(&["--actor", "agent:foo", "--model", "model-b"])),
+        ["cell:app@v2"]
+    );
+    assert_eq!(ids(json(&["--cell", "lib"])), ["cell:lib@v1"]);
+    assert_eq!(json(&["--since", "2999-01-01"]).len(), 0);
+    assert_eq!(json(&["--until", "2999-01-01T00:00:00Z"]).len(), 3);
+
+    let text = run(&["log", chain, "--model", "model-a"]);
+    assert!(text.contains("Matching: 1"));
+    assert!(!text.contains("cell:lib@v1"));
+}
+
+#[test]
+fn test_prov_verify_against_workspace() {
+    let dir = TempDir::new().unwrap();
+    let workspace = dir.path().join("ws");
+    fs::create_dir(&workspace).unwrap();
+    let cell_path = workspace.join("app.z1c");
+    let chain_path = dir.path().join("chain.z1p");
+    let v1 = "m app:1.0 ctx=128 caps=[]\nf main()->Unit eff [pure] { ret Unit; }\n";
+    let v2 = "m app:1.0 ctx=128 caps=[]\nf main()->U32 eff [pure] { ret 1; }\n";
+
+    let run = |args: &[&str]| {
+        let output = Command::new(cli_bin())
+            .arg("prov")
+            .args(args)
+            .env("Z1_ACTOR", "agent:test/1.0")
+            .output()
+            .expect("failed to execute z1-cli");
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).into_owned()
+                + &String::from_utf8_lossy(&output.stderr),
+        )
+    };
+    let chain = chain_path.to_str().unwrap();
+    let cell = cell_path.to_str().unwrap();
+    let ws = workspace.to_str().unwrap();
+
+    for (id, source) in [("cell:app@v1", v1), ("cell:app@v2", v2)] {
+        fs::write(&cell_path, source).unwrap();
+        let (ok, out) = run(&["append", chain, "--id", id, "--diff", cell, "--cell", cell]);
+        assert!(ok, "{out}");
+    }
+
+    let (ok, out) = run(&["verify", chain, "--workspace", ws]);
+    assert!(ok, "{out}");
+    assert!(out.contains("1 cells match"));
+
+    // The current tree is not the one recorded at v1, but a v1 checkout is
+    assert!(!run(&["verify", chain, "--workspace", ws, "--at", "cell:app@v1"]).0);
+    fs::write(&cell_path, v1).unwrap();
+    assert!(run(&["verify", chain, "--workspace", ws, "--at", "cell:app@v1"]).0);
+    let (ok, out) = run(&["verify", chain, "--workspace", ws]);
+    assert!(!ok);
+    assert!(out.contains("does not match the chain"));
+}
+
+#[test]
+fn test_prov_append_redacts_prompt() {
+    let dir = TempDir::new().unwrap();
+    let chain_path = dir.path().join("chain.z1p");
+    let diff_path = dir.path().join("change.diff");
+    let policy_path = dir.path().join("policy.json");
+    fs::write(&diff_path, "+fn main() -> Unit\n").unwrap();
+    fs::write(&policy_path, r#"{"keywords": ["falcon"], "max_len": 60}"#).unwrap();
+
+    let run = |args: &[&str]| {
+        let output = Command::new(cli_bin())
+            .arg("prov")
+            .args(args)
+            .env("Z1_ACTOR", "agent:test/1.0")
+            .output()
+            .expect("failed to execute z1-cli");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    let chain = chain_path.to_str().unwrap();
+    let diff = diff_path.to_str().unwrap();
+    let prompt = "Project Falcon: deploy using token=s3cr3t-value and retry";
+
+    run(&[
+        "append",
+        chain,
+        "--id",
+        "cell:app@v1",
+        "--diff",
+        diff,
+        "--prompt",
+        prompt,
+        "--redaction-policy",
+        policy_path.to_str().unwrap(),
+        "--redact-secrets",
+    ]);
+    run(&[
+        "append",
+        chain,
+        "--id",
+        "cell:app@v2",
+        "--diff",
+        diff,
+        "--prompt",
+        prompt,
+        "--hash-only-prompt",
+    ]);
+
+    let loaded = ProvenanceChain::load_from_file(&chain_path).unwrap();
+    let (first, second) = (&loaded.entries[0], &loaded.entries[1]);
+    assert_eq!(
+        first.prompt_excerpt,
+        "Project [REDACTED]: deploy using [REDACTED] and retry"
+    );
+    let policy = first.redaction.as_ref().unwrap();
+    assert_eq!(policy.max_len, 60);
+    assert_eq!(policy.keywords, ["falcon"]);
+    assert_eq!(second.prompt_excerpt, "");
+    assert!(second.redaction.as_ref().unwrap().hash_only);
+    assert_eq!(first.prompt_sha3, second.prompt_sha3);
+
+    assert!(run(&["log", chain]).contains("Redaction: hash only"));
+    run(&["verify", chain]);
+}
+
+#[test]
+fn test_prov_sign_keyless_and_verify_identity() {
+    let dir = TempDir::new().unwrap();
+    let chain_path = dir.path().join("chain.z1p");
+    let ca_key = dir.path().join("ca.json");
+    let log_key = dir.path().join("log.json");
+    let trust_path = dir.path().join("trust.json");
+    let diff_path = dir.path().join("change.diff");
+    fs::write(&diff_path, "+fn main() -> Unit\n").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = Command::new(cli_bin())
+            .arg("prov")
+            .args(args)
+            .env("Z1_ACTOR", "agent:test/1.0")
+            .env("Z1_OIDC_ISSUER", "https://token.ci.example")
+            .output()
+            .expect("failed to execute z1-cli");
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+        )
+    };
+    let chain = chain_path.to_str().unwrap();
+    let diff = diff_path.to_str().unwrap();
+    let trust = trust_path.to_str().unwrap();
+
+    assert!(run(&["keygen", ca_key.to_str().unwrap()]).0);
+    assert!(run(&["keygen", log_key.to_str().unwrap()]).0);
+    assert!(run(&["append", chain, "--id", "cell:app@v1", "--diff", diff]).0);
+    let (ok, out) = run(&[
+        "sign-keyless",
+        chain,
+        "--identity",
+        "repo:acme/app:ref:main",
+        "--key",
+        ca_key.to_str().unwrap(),
+        "--log-key",
+        log_key.to_str().unwrap(),
+    ]);
+    assert!(ok, "{out}");
+
+    let public_key = |path: &PathBuf| {
+        let keypair: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        keypair["public_key"].clone()
+    };
+    let write_trust = |subject: &str| {
+        let trust = serde_json::json!({
+            "roots": { "ca:local": public_key(&ca_key) },
+            "logs": { "log:local": public_key(&log_key) },
+            "identities": [{ "subject": subject, "issuer": "https://token.ci.example" }],
+        });
+        fs::write(&trust_path, trust.to_string()).unwrap();
+    };
+
+    write_trust("repo:acme/app:ref:main");
+    let (ok, report) = run(&[
+        "verify",
+        chain,
+        "--keyless-trust",
+        trust,
+        "--require-keyless",
+    ]);
+    assert!(ok, "{report}");
+    assert!(report.contains("1 keyless signatures verified"));
+    assert!(run(&["log", chain]).1.contains("repo:acme/app:ref:main"));
+
+    // Signatures from identities outside the trust policy are rejected
+    write_trust("repo:acme/other:ref:main");
+    assert!(!run(&["verify", chain, "--keyless-trust", trust]).0);
+}
+
+#[test]
+fn test_prov_compact_with_archive() {
+    let dir = TempDir::new().unwrap();
+    let chain_path = dir.path().join("chain.z1p");
+    let archive_path = dir.path().join("history.z1p");
+    let diff_path = dir.path().join("change.diff");
+    fs::write(&diff_path, "+fn main() -> Unit\n").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = Command::new(cli_bin())
+            .arg("prov")
+            .args(args)
+            .env("Z1_ACTOR", "agent:test/1.0")
+            .output()
+            .expect("failed to execute z1-cli");
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+        )
+    };
+    let chain = chain_path.to_str().unwrap();
+    let archive = archive_path.to_str().unwrap();
+    let diff = diff_path.to_str().unwrap();
+
+    for id in ["cell:app@v1", "cell:app@v2", "cell:app@v3"] {
+        assert!(run(&["append", chain, "--id", id, "--diff", diff]).0);
+    }
+    let (ok, out) = run(&[
+        "compact",
+        chain,
+        "--id",
+        "checkpoint:1",
+        "--archive",
+        archive,
+    ]);
+    assert!(ok, "{out}");
+    assert!(out.contains("Compacted 3 entries into checkpoint:1"));
+
+    let compacted = ProvenanceChain::load_from_file(&chain_path).unwrap();
+    assert_eq!(compacted.len(), 1);
+    assert!(run(&["log", chain]).1.contains("Checkpoint"));
+
+    // The archive checks out against the checkpoint until it is altered
+    let (ok, report) = run(&["verify", chain, "--history", archive]);
+    assert!(ok, "{report}");
+    assert!(report.contains("3 archived entries match checkpoint:1"));
+    let mut history = ProvenanceChain::load_from_file(&archive_path).unwrap();
+    history.entries.pop();
+    history.save_to_file(&archive_path).unwrap();
+    assert!(!run(&["verify", chain, "--history", archive]).0);
+
+    // Entries appended after compaction link to the checkpoint
+    assert!(run(&["append", chain, "--id", "cell:app@v4", "--diff", diff]).0);
+    assert!(run(&["verify", chain]).0);
+    assert!(!run(&["compact", chain, "--archive", archive]).0);
+}
+
+#[test]
+fn test_prov_verify_writes_report_on_failure() {
+    let dir = TempDir::new().unwrap();
+    let (chain_path, keys_path) = create_test_chain_with_signatures(&dir);
+    let report_path = dir.path().join("report.json");
+
+    // Tamper with the signed entry
+    let mut chain = ProvenanceChain::load_from_file(&chain_path).unwrap();
+    chain.entries[0].model = "other-model".to_string();
+    chain.update_merkle_root();
+    chain.save_to_file(&chain_path).unwrap();
+
+    let output = Command::new(cli_bin())
+        .args([
+            "prov",
+            "verify",
+            chain_path.to_str().unwrap(),
+            "--keys",
+            keys_path.to_str().unwrap(),
+            "--report",
+            report_path.to_str().unwrap(),
+        ])
+        .output()
+        .expect("failed to execute z1-cli");
+    assert!(!output.status.success());
+
+    let report: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&report_path).unwrap()).unwrap();
+    assert_eq!(report["valid"], false);
+    assert_eq!(report["failed"], 1);
+    let entry = &report["entries"][0];
+    assert_eq!(entry["entry_id"], "cell:test@v1");
+    assert_eq!(entry["link"]["status"], "passed");
+    assert_eq!(entry["signatures"]["status"], "failed");
+    assert_eq!(entry["timestamp"]["status"], "skipped");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_prov_sign_with_external_device() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = TempDir::new().unwrap();
+    let chain_path = dir.path().join("chain.z1p");
+    let diff_path = dir.path().join("change.diff");
+    fs::write(&diff_path, "+fn main() -> Unit\n").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = Command::new(cli_bin())
+            .arg("prov")
+            .args(args)
+            .env("Z1_ACTOR", "agent:test/1.0")
+            .output()
+            .expect("failed to execute z1-cli");
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+        )
+    };
+    let chain = chain_path.to_str().unwrap();
+    assert!(
+        run(&[
+            "append",
+            chain,
+            "--id",
+            "cell:app@v1",
+            "--diff",
+            diff_path.to_str().unwrap()
+        ])
+        .0
+    );
+
+    // A stand-in device that returns the signature its key would produce
+    let (private_key, public_key) = keygen();
+    let entry = ProvenanceChain::load_from_file(&chain_path)
+        .unwrap()
+        .entries[0]
+        .clone();
+    let signature = sign_entry(&entry, &private_key, "dev:alice");
+    let device = dir.path().join("device.sh");
+    fs::write(
+        &device,
+        format!("#!/bin/sh\ncat >/dev/null\necho {}\n", signature.sig),
+    )
+    .unwrap();
+    fs::set_permissions(&device, fs::Permissions::from_mode(0o755)).unwrap();
+    let device = device.to_str().unwrap();
+
+    // The device's output is checked against the expected key
+    let (_, other_key) = keygen();
+    let other_key = hex::encode(other_key);
+    let sign = |expected: &str| {
+        run(&[
+            "sign",
+            chain,
+            "--sign-command",
+            device,
+            "--device-public-key",
+            expected,
+            "--as",
+            "dev:alice",
+        ])
+    };
+    assert!(!sign(&other_key).0);
+    let (ok, out) = sign(&hex::encode(public_key));
+    assert!(ok, "{out}");
+
+    let keys_path = dir.path().join("keys.json");
+    fs::write(
+        &keys_path,
+        serde_json::json!({ "dev:alice": hex::encode(public_key) }).to_string(),
+    )
+    .unwrap();
+    assert!(run(&["verify", chain, "--keys", keys_path.to_str().unwrap()]).0);
+}
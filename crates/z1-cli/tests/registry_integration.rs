@@ -0,0 +1,136 @@
+//! Integration tests for `z1 publish` and `z1 add`
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+fn z1(args: &[&str], dir: &Path, registry: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .env("Z1_REGISTRY", registry)
+        .output()
+        .expect("Failed to run z1")
+}
+
+fn assert_success(output: &Output) {
+    assert!(
+        output.status.success(),
+        "stdout: {}\nstderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+fn write(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
+
+fn manifest(name: &str, version: &str) -> String {
+    format!("[project]\nname = \"{name}\"\nversion = \"{version}\"\n")
+}
+
+/// A `json` package project and an `app` project that imports from it.
+fn setup() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    write(&dir.path().join("json/z1.toml"), &manifest("json", "0.1.0"));
+    write(
+        &dir.path().join("json/src/parse.z1c"),
+        "m json.parse:1.0 caps=[]\nt Value = { raw: Str }\nf parse(s: Str)->Value eff [pure] { ret Value { raw: s }; }\n",
+    );
+    write(&dir.path().join("app/z1.toml"), &manifest("app", "0.1.0"));
+    write(
+        &dir.path().join("app/src/main.z1c"),
+        "m main:1.0 caps=[]\nu \"json/parse\" as J only [Value, parse]\nf run()->Unit eff [pure] { }\n",
+    );
+    dir
+}
+
+#[test]
+fn test_publish_add_and_install_from_lock() {
+    let dir = setup();
+    let registry = dir.path().join("registry");
+    let (json, app) = (dir.path().join("json"), dir.path().join("app"));
+
+    assert_success(&z1(&["publish"], &json, &registry));
+    let output = z1(&["publish"], &json, &registry);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already published"));
+
+    // Unresolved until added
+    assert_eq!(z1(&["lint", "src"], &app, &registry).status.code(), Some(9));
+
+    let output = z1(&["add", "json", "--format", "json"], &app, &registry);
+    assert_success(&output);
+    let added: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(added["version"], "0.1.0");
+    assert!(fs::read_to_string(app.join("z1.toml"))
+        .unwrap()
+        .contains("json = { version = \"0.1.0\" }"));
+    let lock = fs::read_to_string(app.join("z1.lock")).unwrap();
+    assert!(lock.contains(added["digest"].as_str().unwrap()));
+    assert_success(&z1(&["lint", "src"], &app, &registry));
+
+    // A fresh checkout installs what the lockfile pins
+    fs::remove_dir_all(app.join(".z1")).unwrap();
+    assert_success(&z1(&["add"], &app, &registry));
+    assert!(app.join(".z1/packages/json/0.1.0/parse.z1c").is_file());
+
+    // The lockfile pins the digest the registry served
+    fs::write(
+        app.join("z1.lock"),
+        lock.replace(added["digest"].as_str().unwrap(), "sha3-256:00"),
+    )
+    .unwrap();
+    let output = z1(&["add"], &app, &registry);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("does not match z1.lock"));
+}
+
+#[test]
+fn test_publish_verifies_provenance() {
+    let dir = setup();
+    let registry = dir.path().join("registry");
+    let json = dir.path().join("json");
+    write(&json.join("change.diff"), "+ parse\n");
+
+    let append = |chain: &str| {
+        z1(
+            &[
+                "prov",
+                "append",
+                chain,
+                "--id",
+                "cell:json.parse@v1",
+                "--actor",
+                "dev:test",
+                "--diff",
+                "change.diff",
+                "--cell",
+                "src/parse.z1c",
+            ],
+            &json,
+            &registry,
+        )
+    };
+    assert_success(&append("chain.z1p"));
+    assert_success(&z1(&["publish", "--prov", "chain.z1p"], &json, &registry));
+
+    let app = dir.path().join("app");
+    let output = z1(&["add", "json@0.1.0"], &app, &registry);
+    assert_success(&output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("provenance verified"));
+    assert!(app.join(".z1/packages/json/0.1.0/provenance.z1p").is_file());
+
+    // A chain recording other contents is refused
+    assert_success(&append("stale.z1p"));
+    write(
+        &json.join("src/parse.z1c"),
+        "m json.parse:1.0 caps=[]\nf parse(s: Str)->Str eff [pure] { ret s; }\n",
+    );
+    write(&json.join("z1.toml"), &manifest("json", "0.2.0"));
+    let output = z1(&["publish", "--prov", "stale.z1p"], &json, &registry);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("provenance of json 0.2.0"));
+}
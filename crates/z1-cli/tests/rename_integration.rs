@@ -0,0 +1,97 @@
+//! Integration tests for `z1 rename`
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+const CELL: &str = "m app:1.0 caps=[]\n\
+                    #sym { helper: hp }\n\
+                    // helper is the only callee\n\
+                    f helper(n: U32)->U32 eff [pure] { ret n; }\n\
+                    f main()->U32 eff [pure] { let n = helper(1); ret helper(n); }\n";
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run z1")
+}
+
+fn cell() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("app.z1c"), CELL).unwrap();
+    dir
+}
+
+#[test]
+fn test_rename_rewrites_the_cell() {
+    let dir = cell();
+    let output = z1(&["rename", "app.z1c", "helper", "double"], dir.path());
+    assert!(output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Renamed `helper` to `double` in app.z1c: 4 occurrence(s)"));
+    assert_eq!(
+        fs::read_to_string(dir.path().join("app.z1c")).unwrap(),
+        CELL.replace("#sym { helper:", "#sym { double:")
+            .replace("f helper(", "f double(")
+            .replace("= helper(1); ret helper(n)", "= double(1); ret double(n)")
+    );
+}
+
+#[test]
+fn test_rename_dry_run_and_conflicts_leave_the_cell() {
+    let dir = cell();
+    let output = z1(
+        &[
+            "rename",
+            "app.z1c",
+            "n",
+            "count",
+            "--dry-run",
+            "--format",
+            "json",
+        ],
+        dir.path(),
+    );
+    assert!(output.status.success(), "{output:?}");
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["count"], 4);
+    assert_eq!(report["written"], false);
+    assert!(report["diff"]
+        .as_str()
+        .unwrap()
+        .contains("+f helper(count: U32)->U32 eff [pure] { ret count; }"));
+
+    let output = z1(&["rename", "app.z1c", "helper", "main"], dir.path());
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("`main` is already used"));
+    assert_eq!(
+        fs::read_to_string(dir.path().join("app.z1c")).unwrap(),
+        CELL
+    );
+}
+
+#[test]
+fn test_rename_leaves_fields_and_locals_of_the_same_name() {
+    let dir = TempDir::new().unwrap();
+    let cell = "m rn:1.0\n\
+                t P = { count: U32 }\n\
+                f count(p: P)->U32 eff [pure] { let count = p.count; ret count; }\n\
+                f main(p: P)->U32 eff [pure] { ret count(p); }\n";
+    fs::write(dir.path().join("rn.z1c"), cell).unwrap();
+    let output = z1(&["rename", "rn.z1c", "count", "total"], dir.path());
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        fs::read_to_string(dir.path().join("rn.z1c")).unwrap(),
+        cell.replace("f count(", "f total(")
+            .replace("ret count(p)", "ret total(p)")
+    );
+
+    let output = z1(&["rename", "rn.z1c", "total", "p"], dir.path());
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("`p` is already a local of `total`"));
+}
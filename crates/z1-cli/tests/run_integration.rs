@@ -0,0 +1,71 @@
+//! Integration tests for `z1 run`
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+fn z1_run(args: &[&str]) -> Output {
+    Command::new("cargo")
+        .args(["run", "-p", "z1-cli", "--", "run"])
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("Failed to run z1 run")
+}
+
+fn setup_test_cell() -> (TempDir, PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("app.z1c");
+    fs::write(
+        &path,
+        r#"module app : 1.0
+  ctx = 100
+  caps = []
+
+fn main() -> Unit
+  eff [pure]
+{
+}
+
+fn scale(x: U32, factor: U16) -> U32
+  eff [pure]
+{
+  ret x;
+}
+"#,
+    )
+    .unwrap();
+    (dir, path)
+}
+
+#[test]
+fn test_run_main_by_default() {
+    let (_dir, cell) = setup_test_cell();
+    let output = z1_run(&[cell.to_str().unwrap()]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    // Unit results print nothing
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_run_rejects_bad_entry_and_arguments() {
+    let (_dir, cell) = setup_test_cell();
+    let cell = cell.to_str().unwrap();
+
+    let output = z1_run(&[cell, "--entry", "missing"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No function `missing`"));
+
+    let output = z1_run(&[cell, "--entry", "scale", "1"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("takes 2 argument(s), got 1"));
+
+    let output = z1_run(&[cell, "--entry", "scale", "1", "70000"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("invalid argument `70000`"));
+}
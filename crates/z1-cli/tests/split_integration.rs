@@ -0,0 +1,98 @@
+//! Integration tests for `z1 split`
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+const SERVER: &str = "m app.server:1.0 ctx=500 caps=[net, time]\n\
+                      u \"std/http\" as H only [listen, Req]\n\
+                      t Count = U32\n\
+                      f handler(r: H.Req)->Count eff [net] { ret helper(); }\n\
+                      f helper()->Count eff [pure] { ret 1; }\n\
+                      f tick()->Unit eff [time] { ret (); }\n\
+                      f main()->Unit eff [net] { ret H.listen(handler(r)); }\n";
+
+const MANIFEST: &str = "[project]\nname = \"app\"\nversion = \"0.1.0\"\nsources = [\".\"]\n\n[policy]\ncell_max_exports = 2\n";
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run z1")
+}
+
+fn workspace() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("z1.toml"), MANIFEST).unwrap();
+    fs::write(dir.path().join("server.z1c"), SERVER).unwrap();
+    dir
+}
+
+#[test]
+fn test_split_writes_cells_with_yes() {
+    let dir = workspace();
+    let output = z1(&["split", "server.z1c", "--yes"], dir.path());
+    assert!(output.status.success(), "{output:?}");
+
+    let first = fs::read_to_string(dir.path().join("server_1.z1c")).unwrap();
+    assert!(first.starts_with("m app.server_1:1.0 ctx=500\n"), "{first}");
+    let second = fs::read_to_string(dir.path().join("server_2.z1c")).unwrap();
+    assert!(
+        second.contains("u \"app/server_1\" only [Count, helper]"),
+        "{second}"
+    );
+    let rest = fs::read_to_string(dir.path().join("server.z1c")).unwrap();
+    assert!(
+        rest.starts_with("m app.server:1.0 ctx=500 caps=[net]\n"),
+        "{rest}"
+    );
+    assert!(rest.contains("f main()"), "{rest}");
+    assert!(!rest.contains("f tick()"), "{rest}");
+}
+
+#[test]
+fn test_split_dry_run_writes_nothing() {
+    let dir = workspace();
+    let output = z1(
+        &["split", "server.z1c", "--dry-run", "--format", "json"],
+        dir.path(),
+    );
+    assert!(output.status.success(), "{output:?}");
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["written"], false);
+    let cells = report["cells"].as_array().unwrap();
+    assert_eq!(cells.len(), 3, "{report}");
+    assert_eq!(cells[0]["path"], "server_1.z1c");
+    assert_eq!(cells[2]["module"], "app.server");
+    assert!(cells[1]["source"].as_str().unwrap().contains("f tick()"));
+
+    assert_eq!(
+        fs::read_to_string(dir.path().join("server.z1c")).unwrap(),
+        SERVER
+    );
+    assert!(!dir.path().join("server_1.z1c").exists());
+}
+
+#[test]
+fn test_split_requires_confirmation_without_terminal() {
+    let dir = workspace();
+    let output = z1(&["split", "server.z1c"], dir.path());
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--yes"), "{stderr}");
+    assert!(!dir.path().join("server_1.z1c").exists());
+}
+
+#[test]
+fn test_split_leaves_small_cells_alone() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("small.z1c"), "m small:1.0\nt A = U32\n").unwrap();
+    let output = z1(&["split", "small.z1c", "--yes"], dir.path());
+    assert!(output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("nothing to split"));
+    assert!(!dir.path().join("small_1.z1c").exists());
+}
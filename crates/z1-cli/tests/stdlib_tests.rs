@@ -114,18 +114,18 @@ fn test_http_server_has_correct_types() {
     assert_eq!(type_count, 3, "Expected 3 type declarations (Req, Res, HS)");
 
     // Verify type names exist (AST uses long names)
-    let type_names: Vec<String> = module
+    let type_names: Vec<z1_ast::Ident> = module
         .items
         .iter()
         .filter_map(|item| match item {
-            z1_ast::Item::Type(t) => Some(t.name.clone()),
+            z1_ast::Item::Type(t) => Some(t.name),
             _ => None,
         })
         .collect();
 
-    assert!(type_names.contains(&"HttpRequest".to_string()));
-    assert!(type_names.contains(&"HttpResponse".to_string()));
-    assert!(type_names.contains(&"HttpServer".to_string()));
+    assert!(type_names.contains(&"HttpRequest".into()));
+    assert!(type_names.contains(&"HttpResponse".into()));
+    assert!(type_names.contains(&"HttpServer".into()));
 }
 
 #[test]
@@ -157,11 +157,11 @@ fn test_listen_function_requires_net_capability() {
 
     // Verify effects include net and async
     assert!(
-        listen_fn.effects.contains(&"net".to_string()),
+        listen_fn.effects.contains(&"net".into()),
         "listen function should have net effect"
     );
     assert!(
-        listen_fn.effects.contains(&"async".to_string()),
+        listen_fn.effects.contains(&"async".into()),
         "listen function should have async effect"
     );
 }
@@ -189,7 +189,7 @@ fn test_all_pure_functions_marked_correctly() {
         assert!(func.is_some(), "Function {fn_name} not found");
         let func = func.unwrap();
         assert!(
-            func.effects.contains(&"pure".to_string()),
+            func.effects.contains(&"pure".into()),
             "Function {fn_name} should be marked as pure"
         );
     }
@@ -273,11 +273,11 @@ fn test_http_client_all_functions_require_net() {
         assert!(func.is_some(), "Function {fn_name} not found");
         let func = func.unwrap();
         assert!(
-            func.effects.contains(&"net".to_string()),
+            func.effects.contains(&"net".into()),
             "Function {fn_name} should have net effect"
         );
         assert!(
-            func.effects.contains(&"async".to_string()),
+            func.effects.contains(&"async".into()),
             "Function {fn_name} should have async effect"
         );
     }
@@ -0,0 +1,75 @@
+//! Integration tests for `z1 sync`
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+const CELL: &str = "m app:1.0 caps=[]\n\
+                    #sym { helper: hp }\n\
+                    f hp(n: U32)->U32 eff [pure] { ret n; }\n";
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run z1")
+}
+
+fn cell() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("app.z1c"), CELL).unwrap();
+    dir
+}
+
+#[test]
+fn test_sync_creates_the_missing_counterpart() {
+    let dir = cell();
+    // Cells found in a directory are only compared with existing counterparts
+    let output = z1(&["sync", "."], dir.path());
+    assert!(output.status.success(), "{output:?}");
+    assert!(!dir.path().join("app.z1r").exists());
+
+    let output = z1(&["sync", "app.z1c", "--check"], dir.path());
+    assert_eq!(output.status.code(), Some(10), "{output:?}");
+    assert!(!dir.path().join("app.z1r").exists());
+
+    let output = z1(&["sync", "app.z1c"], dir.path());
+    assert!(output.status.success(), "{output:?}");
+    let relaxed = fs::read_to_string(dir.path().join("app.z1r")).unwrap();
+    assert!(relaxed.starts_with("module app : 1.0"), "{relaxed}");
+    assert!(relaxed.contains("fn helper(n: U32) -> U32"), "{relaxed}");
+
+    let output = z1(&["sync", "--check"], dir.path());
+    assert!(output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("1 pair(s): 1 in sync"));
+}
+
+#[test]
+fn test_sync_fails_on_diverged_pairs_until_a_side_is_picked() {
+    let dir = cell();
+    assert!(z1(&["sync", "app.z1c"], dir.path()).status.success());
+    let relaxed = dir.path().join("app.z1r");
+    let edited = fs::read_to_string(&relaxed)
+        .unwrap()
+        .replace("ret n;", "ret n + 1;");
+    fs::write(&relaxed, &edited).unwrap();
+
+    let output = z1(&["--format", "json", "sync"], dir.path());
+    assert_eq!(output.status.code(), Some(10), "{output:?}");
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let pair = &report["pairs"][0];
+    assert_eq!(pair["status"], "diverged");
+    assert_ne!(pair["compact_semhash"], pair["relaxed_semhash"]);
+    assert_eq!(fs::read_to_string(&relaxed).unwrap(), edited);
+
+    let output = z1(&["sync", "--from", "relaxed"], dir.path());
+    assert!(output.status.success(), "{output:?}");
+    let compact = fs::read_to_string(dir.path().join("app.z1c")).unwrap();
+    assert!(compact.contains("ret n + 1;"), "{compact}");
+    assert!(compact.contains("#sym { helper: hp }"), "{compact}");
+    assert!(z1(&["sync", "--check"], dir.path()).status.success());
+}
@@ -0,0 +1,326 @@
+//! Integration tests for `z1 test` reporters
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+const TESTS: &str = "spec \"adds\" {\n  assert 1 + 1 == 2;\n}\n\n\
+                     snapshot \"missing\" { cell: \"gone.z1c\", emit: fmt }\n";
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run z1")
+}
+
+fn workspace() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("math.z1t"), TESTS).unwrap();
+    dir
+}
+
+#[test]
+fn test_junit_reporter_writes_report_file() {
+    let dir = workspace();
+    let output = z1(
+        &["test", "math.z1t", "--reporter", "junit", "-o", "junit.xml"],
+        dir.path(),
+    );
+    assert_eq!(output.status.code(), Some(11));
+    assert!(output.stdout.is_empty());
+
+    let xml = fs::read_to_string(dir.path().join("junit.xml")).unwrap();
+    assert!(
+        xml.contains(r#"<testsuite name="math.z1t" tests="2" failures="1" skipped="0""#),
+        "{xml}"
+    );
+    assert!(
+        xml.contains(r#"<testcase name="missing" classname="math.z1t" file="math.z1t" line="5""#)
+    );
+    assert!(xml.contains("<failure message=\"Failed to read"), "{xml}");
+}
+
+#[test]
+fn test_pretty_reporter_lists_tests_and_failure_locations() {
+    let dir = workspace();
+    let output = z1(&["test", "math.z1t", "-v"], dir.path());
+    assert_eq!(output.status.code(), Some(11));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("  ok      spec adds ("), "{stdout}");
+    assert!(stdout.contains("  FAILED  snapshot missing ("), "{stdout}");
+    assert!(stdout.contains("  - missing (math.z1t:5:1): "), "{stdout}");
+}
+
+#[test]
+fn test_json_reporter_includes_durations() {
+    let dir = workspace();
+    let output = z1(&["test", "math.z1t", "--reporter", "json"], dir.path());
+    assert_eq!(output.status.code(), Some(11));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let tests = report["files"][0]["tests"].as_array().unwrap();
+    assert_eq!(tests[0]["status"], "passed");
+    assert!(tests[0]["duration_ms"].is_number());
+    assert_eq!(report["failures"][0]["line"], 5);
+
+    let output = z1(
+        &[
+            "test",
+            "math.z1t",
+            "--reporter",
+            "pretty",
+            "--format",
+            "json",
+        ],
+        dir.path(),
+    );
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_discovers_project_tests_and_filters_by_name() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("z1.toml"),
+        "[project]\nname = \"demo\"\nversion = \"0.1.0\"\n",
+    )
+    .unwrap();
+    fs::create_dir_all(dir.path().join("tests/unit")).unwrap();
+    fs::write(dir.path().join("tests/unit/math.z1t"), TESTS).unwrap();
+    fs::create_dir(dir.path().join("src")).unwrap();
+    fs::write(
+        dir.path().join("src/inc.z1c"),
+        "m inc:1.0\n\
+         f inc(x: U32)->U32 eff [pure] { ret x + 1; }\n\
+         #test \"increments\" { assert_eq(inc(1), 2); }\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("src/plain.z1c"), "m plain:1.0\n").unwrap();
+
+    let output = z1(&["test", "-v"], dir.path());
+    assert_eq!(output.status.code(), Some(11));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("src/inc.z1c"), "{stdout}");
+    assert!(stdout.contains("tests/unit/math.z1t"), "{stdout}");
+    assert!(stdout.contains("  ok      spec increments ("), "{stdout}");
+    assert!(!stdout.contains("plain.z1c"), "{stdout}");
+
+    let output = z1(&["test", "--filter", "add", "-v"], dir.path());
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("  ok      spec adds ("), "{stdout}");
+    assert!(stdout.contains("  skipped spec increments ("), "{stdout}");
+
+    let empty = TempDir::new().unwrap();
+    assert_eq!(z1(&["test"], empty.path()).status.code(), Some(2));
+}
+
+#[test]
+fn test_coverage_reports_functions_and_writes_lcov() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("clamp.z1c"),
+        "m clamp:1.0\n\
+         f clamp(x: U32)->U32 eff [pure] {\n  if x > 9 { ret 9; }\n  ret x;\n}\n\
+         #test \"small\" { assert_eq(clamp(3), 3); }\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("idle.z1c"),
+        "m idle:1.0\nf idle()->U32 eff [pure] { ret 0; }\n",
+    )
+    .unwrap();
+
+    let output = z1(&["test", "--coverage", "--lcov", "cov.info"], dir.path());
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("     66.7%  clamp (2/3), not run: line 3\n"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("      0.0%  idle (0/1)"), "{stdout}");
+    assert!(
+        stdout.contains("  Total: 50.0% (2/4 statements)"),
+        "{stdout}"
+    );
+
+    let lcov = fs::read_to_string(dir.path().join("cov.info")).unwrap();
+    assert!(
+        lcov.contains("SF:clamp.z1c\nFN:2,clamp\nFNDA:1,clamp\n"),
+        "{lcov}"
+    );
+    assert!(lcov.contains("SF:idle.z1c\n"), "{lcov}");
+}
+
+#[test]
+fn test_doc_runs_examples_of_doc_comments() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("inc.z1c"),
+        "m inc:1.0\n\
+         /// Adds one.\n\
+         ///\n\
+         /// ```\n\
+         /// assert_eq(inc(1), 2);\n\
+         /// ```\n\
+         ///\n\
+         /// ```z1,ignore\n\
+         /// assert_eq(inc(1), 3);\n\
+         /// ```\n\
+         f inc(x: U32)->U32 eff [pure] { ret x + 1; }\n\
+         #test \"increments\" { assert_eq(inc(1), 0); }\n",
+    )
+    .unwrap();
+
+    let output = z1(&["test", "--doc", "-v"], dir.path());
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("  ok      spec inc (example 1) ("),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("  skipped spec inc (example 2)"),
+        "{stdout}"
+    );
+    assert!(!stdout.contains("increments"), "{stdout}");
+
+    fs::write(dir.path().join("inc.z1c"), "m inc:1.0\n").unwrap();
+    assert_eq!(z1(&["test", "--doc"], dir.path()).status.code(), Some(2));
+}
+
+#[test]
+fn test_seed_reproduces_random_draws() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("dice.z1t"),
+        "spec \"roll\" { assert_eq(rand.range(1, 1000000), 0); }\n",
+    )
+    .unwrap();
+
+    let output = z1(&["test", "dice.z1t"], dir.path());
+    assert_eq!(output.status.code(), Some(11));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let seed = stdout
+        .split("Seed: ")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .expect("seed printed on failure");
+
+    let error = |stdout: &str| {
+        stdout
+            .lines()
+            .find(|line| line.contains("assert_eq"))
+            .map(str::to_string)
+    };
+    let again = z1(&["test", "dice.z1t", "--seed", seed], dir.path());
+    assert_eq!(
+        error(&String::from_utf8_lossy(&again.stdout)),
+        error(&stdout)
+    );
+}
+
+#[test]
+fn test_prov_records_the_run_for_verification() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("calc.z1c"),
+        "m calc:1.0\nf add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("calc.z1t"),
+        "use \"calc.z1c\";\nspec \"adds\" { assert_eq(add(1, 2), 3); }\n",
+    )
+    .unwrap();
+    assert!(z1(&["prov", "keygen", "key.json"], dir.path())
+        .status
+        .success());
+
+    let output = z1(
+        &[
+            "test", "calc.z1t", "--prov", "prov.z1p", "--key", "key.json", "--as", "ci",
+        ],
+        dir.path(),
+    );
+    assert_eq!(output.status.code(), Some(0), "{output:?}");
+    let chain: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.path().join("prov.z1p")).unwrap()).unwrap();
+    let entry = &chain["entries"][0];
+    assert_eq!(entry["entry_id"], "test:0");
+    assert!(entry["prompt_excerpt"]
+        .as_str()
+        .unwrap()
+        .ends_with(" test passed=1 failed=0 skipped=0 xfailed=0"));
+    assert_eq!(entry["cells"][0]["module"], "calc");
+    assert_eq!(entry["signatures"][0]["by"], "ci");
+
+    let keypair: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.path().join("key.json")).unwrap()).unwrap();
+    fs::write(
+        dir.path().join("keys.json"),
+        serde_json::json!({ "ci": keypair["public_key"] }).to_string(),
+    )
+    .unwrap();
+    let verify = |dir: &Path| {
+        z1(
+            &[
+                "prov",
+                "verify",
+                "prov.z1p",
+                "--keys",
+                "keys.json",
+                "--workspace",
+                ".",
+                "--require-tests",
+            ],
+            dir,
+        )
+    };
+    let output = verify(dir.path());
+    assert_eq!(output.status.code(), Some(0), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("1 cells passed their tests"));
+
+    // A cell no run tested fails the policy
+    fs::write(
+        dir.path().join("util.z1c"),
+        "m util:1.0\nf one()->U32 eff [pure] { ret 1; }\n",
+    )
+    .unwrap();
+    let output = verify(dir.path());
+    assert_eq!(output.status.code(), Some(10));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("1 cells have no passing test run: util")
+    );
+}
+
+#[test]
+fn test_runaway_specs_time_out() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("loop.z1t"),
+        "spec \"spins\" { while true { } }\nspec \"adds\" { assert 1 + 1 == 2; }\n",
+    )
+    .unwrap();
+
+    let output = z1(
+        &["test", "loop.z1t", "--max-steps", "500", "-v"],
+        dir.path(),
+    );
+    assert_eq!(output.status.code(), Some(11));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("  TIMEOUT spec spins ("), "{stdout}");
+    assert!(stdout.contains("  ok      spec adds ("), "{stdout}");
+    assert!(
+        stdout.contains("spins (loop.z1t:1:1): Timed out: step limit of 500 exceeded"),
+        "{stdout}"
+    );
+
+    let output = z1(&["test", "loop.z1t", "--timeout", "20"], dir.path());
+    assert_eq!(output.status.code(), Some(11));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("time limit of 20 ms exceeded"));
+}
@@ -0,0 +1,68 @@
+//! Integration tests for `z1 tokens`
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+fn z1(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("Failed to run z1")
+}
+
+#[test]
+fn test_tokens_ranks_identifiers_across_cells() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir_all(dir.path().join("src")).unwrap();
+    fs::write(
+        dir.path().join("src/a.z1c"),
+        "m a:1.0\nf normalizeRequestPath(p: Str)->Str eff [pure] { ret p; }\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("src/b.z1c"),
+        "m b:1.0\nu \"a\" only [normalizeRequestPath]\nf go(p: Str)->Str eff [pure] { ret normalizeRequestPath(p); }\n",
+    )
+    .unwrap();
+
+    let output = z1(&["tokens", "--format", "json", "--top", "1"], dir.path());
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["cells"], 2);
+    let identifiers = report["identifiers"].as_array().unwrap();
+    assert_eq!(identifiers.len(), 1);
+    assert_eq!(identifiers[0]["name"], "normalizeRequestPath");
+    assert_eq!(identifiers[0]["occurrences"], 3);
+    assert_eq!(identifiers[0]["cells"], 2);
+    let constructs: Vec<&str> = report["constructs"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|row| row["construct"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        constructs,
+        [
+            "header",
+            "imports",
+            "symbol-maps",
+            "types",
+            "signatures",
+            "bodies",
+            "tests"
+        ]
+    );
+
+    let output = z1(&["tokens", "src"], dir.path());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("CONSTRUCT") && stdout.contains("normalizeRequestPath"));
+
+    fs::write(dir.path().join("src/broken.z1c"), "f {").unwrap();
+    let output = z1(&["tokens"], dir.path());
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("broken.z1c"));
+}
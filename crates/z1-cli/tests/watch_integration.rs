@@ -0,0 +1,138 @@
+//! Integration tests for `--watch`
+
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+use tempfile::TempDir;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A `z1 ... --watch` process whose stdout is read line by line.
+struct Watch {
+    child: Child,
+    lines: Receiver<String>,
+}
+
+impl Watch {
+    fn start(dir: &Path, args: &[&str]) -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_z1-cli"))
+            .args(args)
+            .arg("--watch")
+            .current_dir(dir)
+            .env("NO_COLOR", "1")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Failed to start z1 --watch");
+        let stdout = child.stdout.take().unwrap();
+        let (tx, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if tx.send(line.unwrap()).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { child, lines }
+    }
+
+    /// Lines printed up to and including the next "Watching" line.
+    fn next_run(&self) -> Vec<String> {
+        let mut run = Vec::new();
+        loop {
+            let line = self
+                .lines
+                .recv_timeout(TIMEOUT)
+                .unwrap_or_else(|_| panic!("no complete run within {TIMEOUT:?}: {run:?}"));
+            let done = line.starts_with("Watching");
+            run.push(line);
+            if done {
+                return run;
+            }
+        }
+    }
+
+    /// Whether anything is printed within `wait`.
+    fn is_quiet(&self, wait: Duration) -> bool {
+        self.lines.recv_timeout(wait).is_err()
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn write_cell(dir: &Path, name: &str, function: &str) {
+    fs::write(
+        dir.join(name),
+        format!("m app.cell:1.0 ctx=100 caps=[]\n{function}\n"),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_lint_watch_reruns_semantic_changes_only() {
+    let dir = TempDir::new().unwrap();
+    write_cell(
+        dir.path(),
+        "a.z1c",
+        "f add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }",
+    );
+    let watch = Watch::start(dir.path(), &["lint", "."]);
+
+    let run = watch.next_run();
+    assert!(run.iter().any(|line| line.ends_with("✓ a.z1c")), "{run:?}");
+
+    // Exceeds the policy's parameter limit
+    let wide =
+        "f add(a: U32, b: U32, c: U32, d: U32, e: U32, f: U32, g: U32)->U32 eff [pure] { ret a; }";
+    write_cell(dir.path(), "a.z1c", wide);
+    let run = watch.next_run();
+    assert!(
+        run.iter()
+            .any(|line| line.ends_with("✗ a.z1c: policy checks failed")),
+        "{run:?}"
+    );
+
+    // Only the new cell is linted; a formatting-only edit is ignored
+    write_cell(dir.path(), "a.z1c", &wide.replace("{ ret", "{\n    ret"));
+    write_cell(dir.path(), "b.z1c", "f one()->U32 eff [pure] { ret 1; }");
+    let run = watch.next_run();
+    assert!(run.iter().any(|line| line.ends_with("✓ b.z1c")), "{run:?}");
+    assert!(
+        run.iter().any(|line| line.starts_with("Linted 1 cell(s)")),
+        "{run:?}"
+    );
+    assert!(watch.is_quiet(Duration::from_millis(800)));
+}
+
+#[test]
+fn test_fmt_watch_does_not_trigger_itself() {
+    let dir = TempDir::new().unwrap();
+    write_cell(
+        dir.path(),
+        "a.z1c",
+        "f add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }",
+    );
+    let watch = Watch::start(dir.path(), &["fmt", "a.z1c"]);
+    watch.next_run();
+
+    // Formatting rewrites the cell once, without re-running on its own write
+    write_cell(
+        dir.path(),
+        "a.z1c",
+        "f add(a:U32,b:U32)->U32 eff [pure] { ret a + b; }",
+    );
+    let run = watch.next_run();
+    assert!(run.iter().any(|line| line.ends_with("✓ a.z1c")), "{run:?}");
+    assert!(watch.is_quiet(Duration::from_millis(800)));
+    assert!(fs::read_to_string(dir.path().join("a.z1c"))
+        .unwrap()
+        .contains("add(a: U32, b: U32)"));
+}
@@ -2,13 +2,39 @@
 //!
 //! This crate generates TypeScript code from Zero1 IR. It provides a clean,
 //! idiomatic TypeScript output that can be used in Node.js or browser environments.
+//! [`generate_javascript`] emits the same code with type annotations erased,
+//! so it runs directly in Node.js without a TypeScript toolchain, and
+//! [`generate_declarations`] only its types and signatures, as a `.d.ts`
+//! file for JavaScript or WASM output.
+//!
+//! Imported functions bound to a capability (see
+//! [`z1_ir::bind_capabilities`]) are called through a `capabilities` object
+//! typed by the generated `Capabilities` interface, which defaults to the
+//! imports themselves. Hosts pass their own, say restricted or mocked,
+//! implementations to the generated `provideCapabilities`.
+//!
+//! Names that are reserved words of the output are escaped as described in
+//! [`reserved`].
+
+pub mod reserved;
+
+use std::collections::{BTreeMap, HashMap};
 
+use reserved::{escape_type, escape_value, export_specifier};
 use z1_ir::*;
 
 /// TypeScript code generator
 pub struct TsCodegen {
     output: String,
     indent_level: usize,
+    /// Emit type annotations and declarations (TypeScript) or erase them
+    /// (JavaScript)
+    emit_types: bool,
+    /// Emit signatures without bodies (a `.d.ts` declaration file)
+    declarations: bool,
+    /// Capability-bound imports, as referenced (`listen`, `H.listen`), and
+    /// where they are called through (`capabilities.net.listen`)
+    routes: HashMap<String, String>,
 }
 
 impl TsCodegen {
@@ -17,6 +43,26 @@ impl TsCodegen {
         TsCodegen {
             output: String::new(),
             indent_level: 0,
+            emit_types: true,
+            declarations: false,
+            routes: HashMap::new(),
+        }
+    }
+
+    /// Create a generator that erases types, producing JavaScript
+    pub fn javascript() -> Self {
+        TsCodegen {
+            emit_types: false,
+            ..Self::new()
+        }
+    }
+
+    /// Create a generator that only declares types and function
+    /// signatures, producing a `.d.ts` file
+    pub fn declarations() -> Self {
+        TsCodegen {
+            declarations: true,
+            ..Self::new()
         }
     }
 
@@ -27,8 +73,13 @@ impl TsCodegen {
 
         // File header comment
         self.write_line("// Generated by Zero1 compiler");
+        let language = match (self.emit_types, self.declarations) {
+            (_, true) => "TypeScript declarations",
+            (true, false) => "TypeScript",
+            (false, false) => "JavaScript",
+        };
         self.write_line(&format!(
-            "// TypeScript output from module: {}",
+            "// {language} output from module: {}",
             module.name
         ));
         self.write_line(&format!("// Version: {}", module.version));
@@ -41,29 +92,76 @@ impl TsCodegen {
         if !module.imports.is_empty() {
             self.write_line("");
         }
+        self.gen_capabilities(module);
 
         // Type definitions
-        for type_def in &module.types {
-            self.gen_type_def(type_def);
-            self.write_line("");
+        if self.emit_types {
+            for type_def in &module.types {
+                self.gen_type_def(type_def);
+                self.write_line("");
+            }
         }
 
         // Functions
         for func in &module.functions {
-            self.gen_function(func);
+            if self.declarations {
+                self.gen_declaration(func);
+            } else {
+                self.gen_function(func);
+            }
             self.write_line("");
         }
 
-        // Exports
-        if !module.exports.is_empty() {
-            self.write_line(&format!("export {{ {} }};", module.exports.join(", ")));
+        // Exports not already declared with `export` (re-exporting those
+        // is a duplicate export error): escaped declarations under their Z1
+        // name, and the rest
+        let declared: Vec<&str> = module
+            .types
+            .iter()
+            .map(|t| t.name.as_str())
+            .chain(module.functions.iter().map(|f| f.name.as_str()))
+            .collect();
+        let remaining: Vec<String> = module
+            .functions
+            .iter()
+            .map(|f| f.name.as_str())
+            .filter(|name| escape_value(name) != *name)
+            .chain(
+                module
+                    .exports
+                    .iter()
+                    .map(|name| name.as_str())
+                    .filter(|name| !declared.contains(name)),
+            )
+            .map(|name| export_specifier(&escape_value(name), name))
+            .collect();
+        if !remaining.is_empty() {
+            self.write_line(&format!("export {{ {} }};", remaining.join(", ")));
+        }
+        let aliased_types: Vec<String> = module
+            .types
+            .iter()
+            .map(|t| t.name.as_str())
+            .filter(|name| escape_type(name) != *name)
+            .map(|name| export_specifier(&escape_type(name), name))
+            .collect();
+        if self.emit_types && !aliased_types.is_empty() {
+            self.write_line(&format!("export type {{ {} }};", aliased_types.join(", ")));
         }
 
         self.output.clone()
     }
 
     fn gen_import(&mut self, import: &IrImport) {
-        let items = import.items.join(", ");
+        let items = import
+            .items
+            .iter()
+            .map(|item| match escape_value(item) {
+                local if local == *item => local,
+                local => format!("{item} as {local}"),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
         let module_path = import.path.replace('/', "_");
         if !items.is_empty() {
             self.write_line(&format!("import {{ {items} }} from './{module_path}.js';"));
@@ -72,10 +170,106 @@ impl TsCodegen {
         }
     }
 
+    /// The `Capabilities` interface, the `capabilities` object and
+    /// `provideCapabilities`, when any import is bound to a capability.
+    fn gen_capabilities(&mut self, module: &IrModule) {
+        self.routes.clear();
+        let mut granted: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for import in &module.imports {
+            for (item, capability) in &import.capabilities {
+                granted.entry(capability).or_default().push(item);
+                let route = format!("capabilities.{capability}.{item}");
+                if let Some(alias) = &import.alias {
+                    self.routes.insert(format!("{alias}.{item}"), route.clone());
+                }
+                self.routes.insert(item.to_string(), route);
+            }
+        }
+        if granted.is_empty() {
+            return;
+        }
+
+        if self.emit_types {
+            self.write_line(
+                "/** Host operations of this module, by the capability granting them */",
+            );
+            self.write_line("export interface Capabilities {");
+            self.indent_level += 1;
+            for (capability, items) in &granted {
+                self.write_line(&format!("{capability}: {{"));
+                self.indent_level += 1;
+                for item in items {
+                    self.write_line(&format!("{item}: typeof {};", escape_value(item)));
+                }
+                self.indent_level -= 1;
+                self.write_line("};");
+            }
+            self.indent_level -= 1;
+            self.write_line("}");
+            self.write_line("");
+        }
+
+        if self.declarations {
+            self.write_line(
+                "export declare function provideCapabilities(granted: Capabilities): void;",
+            );
+            self.write_line("");
+            return;
+        }
+        let annotation = if self.emit_types {
+            ": Capabilities"
+        } else {
+            ""
+        };
+        self.write_line(&format!("let capabilities{annotation} = {{"));
+        self.indent_level += 1;
+        for (capability, items) in &granted {
+            let items: Vec<String> = items
+                .iter()
+                .map(|item| match escape_value(item) {
+                    local if local == *item => local,
+                    local => format!("{item}: {local}"),
+                })
+                .collect();
+            self.write_line(&format!("{capability}: {{ {} }},", items.join(", ")));
+        }
+        self.indent_level -= 1;
+        self.write_line("};");
+        self.write_line("");
+        self.write_line("/** Perform the effects of this module through `granted` from now on */");
+        self.write_line(&format!(
+            "export function provideCapabilities(granted{annotation}){} {{",
+            self.annotation(&IrType::Unit)
+        ));
+        self.write_line("  capabilities = granted;");
+        self.write_line("}");
+        self.write_line("");
+    }
+
     fn gen_type_def(&mut self, type_def: &IrTypeDef) {
+        // Escaped types are exported under their Z1 name at the end
+        let name = escape_type(&type_def.name);
+        let export = if name == type_def.name { "export " } else { "" };
+        let name = format!("{name}{}", self.type_params(&type_def.type_params));
         match &type_def.ty {
             IrType::Record(fields) => {
-                self.write_line(&format!("export interface {} {{", type_def.name));
+                // Same layout the WASM backend and its JS glue use; that of
+                // a generic record depends on its arguments
+                if type_def.type_params.is_empty() {
+                    let layout = layout::RecordLayout::of(fields);
+                    let offsets: Vec<String> = layout
+                        .fields
+                        .iter()
+                        .map(|f| format!("{}@{}", f.name, f.offset))
+                        .collect();
+                    self.write_line(&format!(
+                        "/** WASM layout: {} bytes, align {} ({}) */",
+                        layout.size,
+                        layout.align,
+                        offsets.join(", ")
+                    ));
+                }
+                self.write_line(&format!("{export}interface {name} {{"));
                 self.indent_level += 1;
                 for (field_name, field_type) in fields {
                     let field_ty = self.type_to_ts(field_type);
@@ -97,14 +291,13 @@ impl TsCodegen {
                     })
                     .collect();
                 self.write_line(&format!(
-                    "export type {} = {};",
-                    type_def.name,
+                    "{export}type {name} = {};",
                     variant_types.join(" | ")
                 ));
             }
             _ => {
                 let ty_ts = self.type_to_ts(&type_def.ty);
-                self.write_line(&format!("export type {} = {ty_ts};", type_def.name));
+                self.write_line(&format!("{export}type {name} = {ty_ts};"));
             }
         }
     }
@@ -116,7 +309,7 @@ impl TsCodegen {
             IrType::Str => "string".to_string(),
             IrType::U16 | IrType::U32 | IrType::U64 => "number".to_string(),
             IrType::Unit => "void".to_string(),
-            IrType::Named(name) => name.clone(),
+            IrType::Named(name) => escape_type(name),
             IrType::Record(fields) => {
                 let field_strs: Vec<String> = fields
                     .iter()
@@ -154,12 +347,9 @@ impl TsCodegen {
         let params: Vec<String> = func
             .params
             .iter()
-            .map(|(name, ty)| {
-                let ty_ts = self.type_to_ts(ty);
-                format!("{name}: {ty_ts}")
-            })
+            .map(|(name, ty)| format!("{}{}", escape_value(name), self.annotation(ty)))
             .collect();
-        let return_type = self.type_to_ts(&func.return_type);
+        let return_type = self.annotation(&func.return_type);
 
         // Check for async effect
         let is_async = func
@@ -168,9 +358,12 @@ impl TsCodegen {
             .any(|e| e.contains("async") || e.contains("Async"));
         let async_kw = if is_async { "async " } else { "" };
 
+        // Escaped functions are exported under their Z1 name at the end
+        let name = escape_value(&func.name);
+        let export = if name == func.name { "export " } else { "" };
+        let type_params = self.type_params(&func.type_params);
         self.write_line(&format!(
-            "export {async_kw}function {}({}): {return_type} {{",
-            func.name,
+            "{export}{async_kw}function {name}{type_params}({}){return_type} {{",
             params.join(", ")
         ));
 
@@ -181,6 +374,30 @@ impl TsCodegen {
         self.write_line("}");
     }
 
+    /// Signature of `func`; async functions return a `Promise`
+    fn gen_declaration(&mut self, func: &IrFunction) {
+        let params: Vec<String> = func
+            .params
+            .iter()
+            .map(|(name, ty)| format!("{}: {}", escape_value(name), self.type_to_ts(ty)))
+            .collect();
+        let mut return_type = self.type_to_ts(&func.return_type);
+        if func
+            .effects
+            .iter()
+            .any(|e| e.contains("async") || e.contains("Async"))
+        {
+            return_type = format!("Promise<{return_type}>");
+        }
+        let name = escape_value(&func.name);
+        let export = if name == func.name { "export " } else { "" };
+        let type_params = self.type_params(&func.type_params);
+        self.write_line(&format!(
+            "{export}declare function {name}{type_params}({}): {return_type};",
+            params.join(", ")
+        ));
+    }
+
     fn gen_block(&mut self, block: &IrBlock) {
         for stmt in &block.statements {
             self.gen_stmt(stmt);
@@ -196,15 +413,12 @@ impl TsCodegen {
                 value,
             } => {
                 let var_kw = if *mutable { "let" } else { "const" };
-                let type_annotation = ty
-                    .as_ref()
-                    .map(|t| {
-                        let ty_ts = self.type_to_ts(t);
-                        format!(": {ty_ts}")
-                    })
-                    .unwrap_or_default();
+                let type_annotation = ty.as_ref().map(|t| self.annotation(t)).unwrap_or_default();
                 let val_expr = self.gen_expr(value);
-                self.write_line(&format!("{var_kw} {name}{type_annotation} = {val_expr};"));
+                self.write_line(&format!(
+                    "{var_kw} {}{type_annotation} = {val_expr};",
+                    escape_value(name)
+                ));
             }
             IrStmt::Assign { target, value } => {
                 let tgt = self.gen_expr(target);
@@ -254,17 +468,25 @@ impl TsCodegen {
 
     fn gen_expr(&self, expr: &IrExpr) -> String {
         match expr {
-            IrExpr::Var(name) => name.clone(),
+            IrExpr::Var(name) => self
+                .routes
+                .get(name.as_str())
+                .cloned()
+                .unwrap_or_else(|| escape_value(name)),
             IrExpr::Literal(lit) => self.gen_literal(lit),
             IrExpr::BinOp { op, left, right } => {
-                let l = self.gen_expr(left);
-                let r = self.gen_expr(right);
+                let l = self.gen_operand(left);
+                let r = self.gen_operand(right);
+                if *op == IrBinOp::Div {
+                    // Integer division truncates
+                    return format!("Math.trunc({l} / {r})");
+                }
                 let op_str = self.binop_to_ts(op);
                 format!("{l} {op_str} {r}")
             }
             IrExpr::UnaryOp { op, expr } => {
                 let op_str = self.unaryop_to_ts(op);
-                let expr_str = self.gen_expr(expr);
+                let expr_str = self.gen_operand(expr);
                 if *op == IrUnaryOp::Await {
                     format!("{op_str} {expr_str}")
                 } else {
@@ -290,10 +512,59 @@ impl TsCodegen {
                     .collect();
                 format!("{{ {} }}", field_strs.join(", "))
             }
-            IrExpr::Path(segments) => segments.join("."),
+            IrExpr::RecordUpdate { base, fields } => {
+                let mut parts = vec![format!("...{}", self.gen_expr(base))];
+                parts.extend(
+                    fields
+                        .iter()
+                        .map(|(name, val)| format!("{name}: {}", self.gen_expr(val))),
+                );
+                format!("{{ {} }}", parts.join(", "))
+            }
+            IrExpr::Path(segments) => {
+                let path = Symbol::join(segments, ".");
+                self.routes.get(&path).cloned().unwrap_or_else(|| {
+                    // Later segments are properties, which may be any name
+                    let mut segments: Vec<String> =
+                        segments.iter().map(|segment| segment.to_string()).collect();
+                    if let Some(first) = segments.first_mut() {
+                        *first = escape_value(first);
+                    }
+                    segments.join(".")
+                })
+            }
+        }
+    }
+
+    /// Operand of an operator: nested binary operations are parenthesized
+    /// since the IR tree, not JS precedence, decides grouping.
+    fn gen_operand(&self, expr: &IrExpr) -> String {
+        let code = self.gen_expr(expr);
+        match expr {
+            IrExpr::BinOp { op, .. } if *op != IrBinOp::Div => format!("({code})"),
+            _ => code,
+        }
+    }
+
+    /// `: T` annotation, or nothing when erasing types
+    fn annotation(&self, ty: &IrType) -> String {
+        if self.emit_types {
+            format!(": {}", self.type_to_ts(ty))
+        } else {
+            String::new()
         }
     }
 
+    /// `<T, U>` type parameter list, or nothing when there are none or when
+    /// erasing types
+    fn type_params(&self, params: &[Ident]) -> String {
+        if params.is_empty() || !self.emit_types {
+            return String::new();
+        }
+        let params: Vec<String> = params.iter().map(|p| escape_type(p)).collect();
+        format!("<{}>", params.join(", "))
+    }
+
     fn gen_literal(&self, lit: &IrLiteral) -> String {
         match lit {
             IrLiteral::Bool(b) => b.to_string(),
@@ -352,6 +623,19 @@ pub fn generate_typescript(module: &IrModule) -> String {
     codegen.generate(module)
 }
 
+/// Generate JavaScript (the TypeScript output with types erased)
+pub fn generate_javascript(module: &IrModule) -> String {
+    let mut codegen = TsCodegen::javascript();
+    codegen.generate(module)
+}
+
+/// Generate a TypeScript declaration file (`.d.ts`): the module's types
+/// and function signatures
+pub fn generate_declarations(module: &IrModule) -> String {
+    let mut codegen = TsCodegen::declarations();
+    codegen.generate(module)
+}
+
 /// Generate TypeScript code from IR module with optimization
 pub fn generate_typescript_optimized(
     module: &IrModule,
@@ -375,8 +659,9 @@ mod tests {
             imports: vec![],
             types: vec![],
             functions: vec![IrFunction {
-                name: "greet".to_string(),
-                params: vec![("name".to_string(), IrType::Str)],
+                name: "greet".into(),
+                type_params: vec![],
+                params: vec![("name".into(), IrType::Str)],
                 return_type: IrType::Str,
                 effects: vec![],
                 body: IrBlock {
@@ -385,13 +670,14 @@ mod tests {
                     }],
                 },
             }],
-            exports: vec!["greet".to_string()],
+            exports: vec!["greet".into()],
         };
 
         let ts = generate_typescript(&module);
         assert!(ts.contains("export function greet(name: string): string"));
         assert!(ts.contains("return \"Hello\";"));
-        assert!(ts.contains("export { greet };"));
+        // Already exported by its declaration
+        assert!(!ts.contains("export { greet };"));
     }
 
     #[test]
@@ -401,19 +687,285 @@ mod tests {
             version: "1.0.0".to_string(),
             imports: vec![],
             types: vec![IrTypeDef {
-                name: "Point".to_string(),
-                ty: IrType::Record(vec![
-                    ("x".to_string(), IrType::U32),
-                    ("y".to_string(), IrType::U32),
-                ]),
+                name: "Point".into(),
+                type_params: vec![],
+                ty: IrType::Record(vec![("x".into(), IrType::U32), ("y".into(), IrType::U32)]),
             }],
             functions: vec![],
-            exports: vec!["Point".to_string()],
+            exports: vec!["Point".into()],
         };
 
         let ts = generate_typescript(&module);
+        assert!(ts.contains("/** WASM layout: 8 bytes, align 4 (x@0, y@4) */"));
         assert!(ts.contains("export interface Point {"));
         assert!(ts.contains("x: number;"));
         assert!(ts.contains("y: number;"));
     }
+
+    #[test]
+    fn test_javascript_erases_types_and_keeps_grouping() {
+        let module = IrModule {
+            name: "test".to_string(),
+            version: "1.0.0".to_string(),
+            imports: vec![],
+            types: vec![IrTypeDef {
+                name: "Point".into(),
+                type_params: vec![],
+                ty: IrType::Record(vec![("x".into(), IrType::U32)]),
+            }],
+            functions: vec![IrFunction {
+                name: "mean".into(),
+                type_params: vec![],
+                params: vec![("a".into(), IrType::U32), ("b".into(), IrType::U32)],
+                return_type: IrType::U32,
+                effects: vec![],
+                body: IrBlock {
+                    statements: vec![IrStmt::Return {
+                        value: Some(IrExpr::BinOp {
+                            op: IrBinOp::Div,
+                            left: Box::new(IrExpr::BinOp {
+                                op: IrBinOp::Add,
+                                left: Box::new(IrExpr::Var("a".into())),
+                                right: Box::new(IrExpr::Var("b".into())),
+                            }),
+                            right: Box::new(IrExpr::Literal(IrLiteral::U32(2))),
+                        }),
+                    }],
+                },
+            }],
+            exports: vec!["Point".into(), "mean".into()],
+        };
+
+        let js = generate_javascript(&module);
+        assert!(js.contains("export function mean(a, b) {"));
+        assert!(js.contains("return Math.trunc((a + b) / 2);"));
+        assert!(!js.contains("interface"));
+        assert!(!js.contains("export {"));
+
+        let ts = generate_typescript(&module);
+        assert!(ts.contains("export function mean(a: number, b: number): number {"));
+        assert!(ts.contains("return Math.trunc((a + b) / 2);"));
+    }
+
+    #[test]
+    fn test_record_update_spreads_its_base() {
+        let module = IrModule {
+            name: "test".to_string(),
+            version: "1.0.0".to_string(),
+            imports: vec![],
+            types: vec![],
+            functions: vec![IrFunction {
+                name: "moved".into(),
+                type_params: vec![],
+                params: vec![("p".into(), IrType::Named("Point".into()))],
+                return_type: IrType::Named("Point".into()),
+                effects: vec![],
+                body: IrBlock {
+                    statements: vec![IrStmt::Return {
+                        value: Some(IrExpr::RecordUpdate {
+                            base: Box::new(IrExpr::Var("p".into())),
+                            fields: vec![("x".into(), IrExpr::Literal(IrLiteral::U32(2)))],
+                        }),
+                    }],
+                },
+            }],
+            exports: vec!["moved".into()],
+        };
+
+        let ts = generate_typescript(&module);
+        assert!(ts.contains("return { ...p, x: 2 };"), "{ts}");
+    }
+
+    #[test]
+    fn test_declarations_keep_types_and_signatures() {
+        let module = IrModule {
+            name: "test".to_string(),
+            version: "1.0.0".to_string(),
+            imports: vec![],
+            types: vec![IrTypeDef {
+                name: "Point".into(),
+                type_params: vec![],
+                ty: IrType::Record(vec![("x".into(), IrType::U32)]),
+            }],
+            functions: vec![IrFunction {
+                name: "fetch".into(),
+                type_params: vec![],
+                params: vec![("url".into(), IrType::Str)],
+                return_type: IrType::Named("Point".into()),
+                effects: vec!["net".into(), "async".into()],
+                body: IrBlock {
+                    statements: vec![IrStmt::Return { value: None }],
+                },
+            }],
+            exports: vec!["Point".into(), "fetch".into()],
+        };
+
+        let dts = generate_declarations(&module);
+        assert!(dts.contains("// TypeScript declarations output from module: test"));
+        assert!(dts.contains("export interface Point {"));
+        assert!(dts.contains("export declare function fetch(url: string): Promise<Point>;"));
+        assert!(!dts.contains("return"));
+    }
+
+    #[test]
+    fn test_reserved_names_are_escaped_and_exported_under_their_name() {
+        let module = IrModule {
+            name: "test".to_string(),
+            version: "1.0.0".to_string(),
+            imports: vec![IrImport {
+                path: "std/store".to_string(),
+                alias: None,
+                items: vec!["export".into()],
+                capabilities: BTreeMap::new(),
+            }],
+            types: vec![IrTypeDef {
+                name: "string".into(),
+                type_params: vec![],
+                ty: IrType::Str,
+            }],
+            functions: vec![IrFunction {
+                name: "delete".into(),
+                type_params: vec![],
+                params: vec![("class".into(), IrType::Named("string".into()))],
+                return_type: IrType::Unit,
+                effects: vec![],
+                body: IrBlock {
+                    statements: vec![
+                        IrStmt::Let {
+                            name: "new".into(),
+                            mutable: false,
+                            ty: None,
+                            value: IrExpr::Var("class".into()),
+                        },
+                        IrStmt::Expr(IrExpr::Call {
+                            func: Box::new(IrExpr::Var("export".into())),
+                            args: vec![IrExpr::Var("new".into())],
+                        }),
+                    ],
+                },
+            }],
+            exports: vec!["string".into(), "delete".into()],
+        };
+
+        let ts = generate_typescript(&module);
+        assert!(ts.contains("import { export as export$ } from './std_store.js';"));
+        assert!(ts.contains("\ntype string$ = string;"));
+        assert!(ts.contains("\nfunction delete$(class$: string$): void {"));
+        assert!(ts.contains("const new$ = class$;"));
+        assert!(ts.contains("export$(new$);"));
+        assert!(ts.contains("export { delete$ as delete };"));
+        assert!(ts.contains("export type { string$ as string };"));
+
+        let js = generate_javascript(&module);
+        assert!(js.contains("export { delete$ as delete };"));
+        assert!(!js.contains("export type"));
+
+        let dts = generate_declarations(&module);
+        assert!(dts.contains("\ndeclare function delete$(class$: string$): void;"));
+        assert!(dts.contains("export { delete$ as delete };"));
+    }
+
+    #[test]
+    fn test_type_parameters_are_emitted_and_erased() {
+        let param = || IrType::Named("T".into());
+        let module = IrModule {
+            name: "test".to_string(),
+            version: "1.0.0".to_string(),
+            imports: vec![],
+            types: vec![IrTypeDef {
+                name: "Pair".into(),
+                type_params: vec!["T".into()],
+                ty: IrType::Record(vec![("first".into(), param()), ("second".into(), param())]),
+            }],
+            functions: vec![IrFunction {
+                name: "first".into(),
+                type_params: vec!["T".into()],
+                params: vec![(
+                    "pair".into(),
+                    IrType::Generic {
+                        base: Box::new(IrType::Named("Pair".into())),
+                        args: vec![param()],
+                    },
+                )],
+                return_type: param(),
+                effects: vec![],
+                body: IrBlock {
+                    statements: vec![IrStmt::Return {
+                        value: Some(IrExpr::Field {
+                            base: Box::new(IrExpr::Var("pair".into())),
+                            field: "first".into(),
+                        }),
+                    }],
+                },
+            }],
+            exports: vec!["Pair".into(), "first".into()],
+        };
+
+        let ts = generate_typescript(&module);
+        assert!(ts.contains("export interface Pair<T> {"));
+        assert!(!ts.contains("WASM layout"));
+        assert!(ts.contains("export function first<T>(pair: Pair<T>): T {"));
+
+        let js = generate_javascript(&module);
+        assert!(js.contains("export function first(pair) {"));
+
+        let dts = generate_declarations(&module);
+        assert!(dts.contains("export declare function first<T>(pair: Pair<T>): T;"));
+    }
+
+    #[test]
+    fn test_capability_bound_imports_go_through_capabilities() {
+        let call = |func: IrExpr| {
+            IrStmt::Expr(IrExpr::Call {
+                func: Box::new(func),
+                args: vec![IrExpr::Literal(IrLiteral::U32(80))],
+            })
+        };
+        let module = IrModule {
+            name: "test".to_string(),
+            version: "1.0.0".to_string(),
+            imports: vec![IrImport {
+                path: "std/http".to_string(),
+                alias: Some("H".into()),
+                items: vec!["listen".into(), "respond".into()],
+                capabilities: [("listen".into(), "net".to_string())].into(),
+            }],
+            types: vec![],
+            functions: vec![IrFunction {
+                name: "serve".into(),
+                type_params: vec![],
+                params: vec![],
+                return_type: IrType::Unit,
+                effects: vec!["net".into()],
+                body: IrBlock {
+                    statements: vec![
+                        call(IrExpr::Var("listen".into())),
+                        call(IrExpr::Path(vec!["H".into(), "listen".into()])),
+                        call(IrExpr::Var("respond".into())),
+                    ],
+                },
+            }],
+            exports: vec!["serve".into()],
+        };
+
+        let ts = generate_typescript(&module);
+        assert!(ts.contains(
+            "export interface Capabilities {\n  net: {\n    listen: typeof listen;\n  };\n}"
+        ));
+        assert!(ts.contains("let capabilities: Capabilities = {\n  net: { listen },\n};"));
+        assert!(ts.contains("export function provideCapabilities(granted: Capabilities): void {"));
+        assert_eq!(ts.matches("capabilities.net.listen(80);").count(), 2);
+        assert!(ts.contains("  respond(80);"));
+
+        let js = generate_javascript(&module);
+        assert!(js.contains("let capabilities = {"));
+        assert!(js.contains("export function provideCapabilities(granted) {"));
+        assert!(!js.contains("interface"));
+
+        let dts = generate_declarations(&module);
+        assert!(dts.contains("export interface Capabilities {"));
+        assert!(dts
+            .contains("export declare function provideCapabilities(granted: Capabilities): void;"));
+        assert!(!dts.contains("let capabilities"));
+    }
 }
@@ -0,0 +1,128 @@
+//! Reserved words of the TypeScript and JavaScript output.
+//!
+//! A Z1 name that is a reserved word of the target, or a name the generated
+//! code relies on, is emitted with a `$` suffix: `delete` becomes
+//! `delete$`. Z1 identifiers never contain `$`, so the escaped name cannot
+//! collide with another one. Exported declarations keep their Z1 name
+//! through an aliased export (`export { delete$ as delete };`), which ES
+//! modules allow for any name.
+
+/// Words that cannot name a value: JavaScript keywords, strict-mode
+/// reserved words, and globals the generated code refers to.
+pub const JS_RESERVED: &[&str] = &[
+    "Infinity",
+    "Math",
+    "NaN",
+    "arguments",
+    "await",
+    "break",
+    "capabilities",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "eval",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "provideCapabilities",
+    "public",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "undefined",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+/// Words that cannot name a type, besides [`JS_RESERVED`]: TypeScript's
+/// predefined types and the types the generated code declares or uses.
+pub const TS_RESERVED_TYPES: &[&str] = &[
+    "Capabilities",
+    "Promise",
+    "any",
+    "bigint",
+    "boolean",
+    "never",
+    "number",
+    "object",
+    "string",
+    "symbol",
+    "unknown",
+];
+
+/// `name` as a value identifier of the output.
+pub fn escape_value(name: &str) -> String {
+    if JS_RESERVED.contains(&name) {
+        format!("{name}$")
+    } else {
+        name.to_string()
+    }
+}
+
+/// `name` as a type identifier of the output.
+pub fn escape_type(name: &str) -> String {
+    if JS_RESERVED.contains(&name) || TS_RESERVED_TYPES.contains(&name) {
+        format!("{name}$")
+    } else {
+        name.to_string()
+    }
+}
+
+/// Export specifier for `name` declared as `local`: `local as name` when
+/// it had to be escaped.
+pub fn export_specifier(local: &str, name: &str) -> String {
+    if local == name {
+        name.to_string()
+    } else {
+        format!("{local} as {name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_reserved_words_only() {
+        assert_eq!(escape_value("delete"), "delete$");
+        assert_eq!(escape_value("Math"), "Math$");
+        assert_eq!(escape_value("remove"), "remove");
+        assert_eq!(escape_type("string"), "string$");
+        assert_eq!(escape_type("class"), "class$");
+        assert_eq!(escape_type("Point"), "Point");
+        assert_eq!(export_specifier("delete$", "delete"), "delete$ as delete");
+        assert_eq!(export_specifier("remove", "remove"), "remove");
+    }
+}
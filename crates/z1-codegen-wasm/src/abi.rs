@@ -0,0 +1,540 @@
+//! Host ABI for generated WASM modules
+//!
+//! Describes how JS/TS hosts call into a Zero1 module and generates the
+//! matching glue code and reference documentation.
+//!
+//! # Zero1 WASM ABI (v1)
+//!
+//! - The module exports its linear memory as `memory`.
+//! - `Bool`, `U16` and `U32` are passed as `i32`; `U64` as `i64` (a JS
+//!   `BigInt`). The glue converts `U64` to and from `number` to match the
+//!   TypeScript backend.
+//! - `Str` and records are passed as an `i32` pointer into linear memory
+//!   using the layout in [`crate::layout`].
+//! - When any function takes a `Str` or record, the module exports
+//!   `alloc(size: i32) -> i32`. Hosts write arguments into memory returned
+//!   by `alloc`; ownership passes to the module.
+//! - The heap is a bump allocator and never frees. Returned strings and
+//!   records stay valid for the lifetime of the instance; the glue copies
+//!   them out eagerly, since growing memory detaches existing JS views.
+//! - Host imports live in the WASM module named after the import path with
+//!   `/` replaced by `_`, take one `i32` per argument and return `i32`.
+//!   Pointer arguments can be decoded with the glue's `readStr`.
+//! - Host imports performing an effect live instead in the WASM module
+//!   named after the capability granting them (`net`, `fs`), so a host can
+//!   grant each capability its own, possibly restricted, implementation.
+//! - Functions are exported under their Z1 name, except for those named
+//!   `memory`, `alloc` or `_start`, exported with a `$` suffix (see
+//!   [`crate::names`]). The glue wraps them under their Z1 name, but for
+//!   `memory`, `readStr` and `writeStr`, which it returns itself.
+//! - Generic functions are exported per instantiation, as `id$U64` for
+//!   `id[T]` called with a `U64` (see [`z1_ir::generics`]).
+
+use std::collections::HashMap;
+use z1_codegen_ts::reserved::escape_value;
+use z1_ir::layout::{self, RecordLayout, ValType};
+use z1_ir::*;
+
+use crate::names;
+
+/// Version of the calling convention described in this module.
+pub const ABI_VERSION: u32 = 1;
+
+/// Names of the object `bind` returns besides the wrappers.
+const WRAPPED_NAMES: &[&str] = &["memory", "readStr", "writeStr"];
+
+/// Names the wrappers of the glue refer to, besides the `read`/`write`
+/// helpers of records.
+const GLUE_NAMES: &[&str] = &[
+    "BigInt", "Number", "decoder", "encoder", "ex", "readStr", "view", "writeStr",
+];
+
+/// How a value crosses the host boundary.
+#[derive(Debug, Clone, PartialEq)]
+enum Repr {
+    Bool,
+    U32,
+    U64,
+    Str,
+    /// Named record with generated read/write helpers
+    Record(String),
+    /// Opaque `i32` handle (unions, generics, unknown types)
+    Handle,
+    Unit,
+}
+
+struct Types<'a> {
+    defs: HashMap<Ident, IrType>,
+    order: Vec<&'a IrTypeDef>,
+}
+
+impl<'a> Types<'a> {
+    fn new(module: &'a IrModule) -> Self {
+        Types {
+            defs: module
+                .types
+                .iter()
+                .map(|t| (t.name, t.ty.clone()))
+                .collect(),
+            order: module.types.iter().collect(),
+        }
+    }
+
+    fn repr(&self, ty: &IrType) -> Repr {
+        if let (IrType::Named(name), IrType::Record(_)) = (ty, layout::resolve(ty, &self.defs)) {
+            // Helpers are generated per declared record, so aliases use the
+            // name of the record they resolve to.
+            return Repr::Record(self.record_name(*name));
+        }
+        match layout::resolve(ty, &self.defs) {
+            IrType::Bool => Repr::Bool,
+            IrType::U16 | IrType::U32 => Repr::U32,
+            IrType::U64 => Repr::U64,
+            IrType::Str => Repr::Str,
+            IrType::Unit => Repr::Unit,
+            _ => Repr::Handle,
+        }
+    }
+
+    fn record_name(&self, name: Ident) -> String {
+        let mut current = name;
+        for _ in 0..=self.defs.len() {
+            match self.defs.get(&current) {
+                Some(IrType::Named(next)) => current = *next,
+                _ => break,
+            }
+        }
+        current.to_string()
+    }
+
+    fn records(&self) -> impl Iterator<Item = (Ident, RecordLayout)> + '_ {
+        self.order.iter().filter_map(|t| match &t.ty {
+            IrType::Record(fields) => Some((t.name, RecordLayout::of(fields))),
+            _ => None,
+        })
+    }
+}
+
+/// Generate an ES module that instantiates the WASM module and wraps its
+/// exports with argument and result marshaling.
+pub fn generate_js_glue(module: &IrModule) -> String {
+    let module = &generics::expand(module);
+    let types = Types::new(module);
+    let mut out = String::new();
+    let mut line = |indent: usize, text: &str| {
+        out.push_str(&"  ".repeat(indent));
+        out.push_str(text);
+        out.push('\n');
+    };
+
+    line(0, "// Generated by Zero1 compiler");
+    line(
+        0,
+        &format!(
+            "// JS glue for module: {} (Zero1 WASM ABI v{ABI_VERSION})",
+            module.name
+        ),
+    );
+    line(0, &format!("// Version: {}", module.version));
+    line(0, "");
+    line(0, &format!("export const ABI_VERSION = {ABI_VERSION};"));
+    line(0, "");
+    line(0, "const encoder = new TextEncoder();");
+    line(0, "const decoder = new TextDecoder();");
+    line(0, "");
+
+    line(0, "/**");
+    line(0, " * Instantiate the module and wrap its exports.");
+    line(0, " *");
+    line(
+        0,
+        " * Host imports are keyed by WASM module name (import path with",
+    );
+    line(
+        0,
+        " * `/` replaced by `_`), or by the capability granting them for",
+    );
+    line(0, " * those performing an effect (`{ net: { listen } }`).");
+    line(0, " */");
+    line(
+        0,
+        "export async function instantiate(source, imports = {}) {",
+    );
+    line(
+        1,
+        "const { instance } = await WebAssembly.instantiate(source, imports);",
+    );
+    line(1, "return bind(instance);");
+    line(0, "}");
+    line(0, "");
+
+    line(0, "/** Wrap the exports of an existing instance. */");
+    line(0, "export function bind(instance) {");
+    line(1, "const ex = instance.exports;");
+    line(
+        1,
+        "// Re-created on every access: memory.grow detaches old buffers",
+    );
+    line(1, "const view = () => new DataView(ex.memory.buffer);");
+    line(0, "");
+    line(1, "const readStr = (ptr) => {");
+    line(2, "const len = view().getUint32(ptr, true);");
+    line(
+        2,
+        &format!(
+            "return decoder.decode(new Uint8Array(ex.memory.buffer, ptr + {}, len));",
+            layout::STR_HEADER_SIZE
+        ),
+    );
+    line(1, "};");
+    line(1, "const writeStr = (value) => {");
+    line(2, "const bytes = encoder.encode(value);");
+    line(
+        2,
+        &format!(
+            "const ptr = ex.alloc({} + bytes.length);",
+            layout::STR_HEADER_SIZE
+        ),
+    );
+    line(2, "view().setUint32(ptr, bytes.length, true);");
+    line(
+        2,
+        &format!(
+            "new Uint8Array(ex.memory.buffer, ptr + {}, bytes.length).set(bytes);",
+            layout::STR_HEADER_SIZE
+        ),
+    );
+    line(2, "return ptr;");
+    line(1, "};");
+
+    for (name, record) in types.records() {
+        line(0, "");
+        line(1, &format!("const read{name} = (ptr) => {{"));
+        line(2, "const v = view();");
+        line(2, "return {");
+        for field in &record.fields {
+            let at = format!("ptr + {}", field.offset);
+            let value = read_value(&types.repr(&field.ty), &at);
+            line(3, &format!("{}: {value},", field.name));
+        }
+        line(2, "};");
+        line(1, "};");
+
+        line(1, &format!("const write{name} = (value) => {{"));
+        // Nested allocations first: they may grow memory.
+        let mut nested = HashMap::new();
+        for field in &record.fields {
+            let source = format!("value.{}", field.name);
+            let ptr = match types.repr(&field.ty) {
+                Repr::Str => format!("writeStr({source})"),
+                Repr::Record(inner) => format!("write{inner}({source})"),
+                _ => continue,
+            };
+            line(2, &format!("const {}Ptr = {ptr};", field.name));
+            nested.insert(field.name, format!("{}Ptr", field.name));
+        }
+        line(2, &format!("const ptr = ex.alloc({});", record.size));
+        line(2, "const v = view();");
+        for field in &record.fields {
+            let at = format!("ptr + {}", field.offset);
+            let value = nested
+                .get(&field.name)
+                .cloned()
+                .unwrap_or_else(|| format!("value.{}", field.name));
+            let repr = match types.repr(&field.ty) {
+                Repr::Str | Repr::Record(_) => Repr::Handle,
+                repr => repr,
+            };
+            line(2, &write_value(&repr, field.val_type(), &at, &value));
+        }
+        line(2, "return ptr;");
+        line(1, "};");
+    }
+
+    line(0, "");
+    line(1, "return {");
+    line(2, "memory: ex.memory,");
+    line(2, "readStr,");
+    line(2, "writeStr,");
+    // Parameters are escaped so as not to shadow what the wrappers use
+    let param = |name: &str| {
+        let helper = types.records().any(|(record, _)| {
+            name.strip_prefix("read")
+                .or_else(|| name.strip_prefix("write"))
+                == Some(record.as_str())
+        });
+        if GLUE_NAMES.contains(&name) || helper {
+            format!("{name}$")
+        } else {
+            escape_value(name)
+        }
+    };
+    for func in &module.functions {
+        let params: Vec<String> = func.params.iter().map(|(n, _)| param(n)).collect();
+        let args: Vec<String> = func
+            .params
+            .iter()
+            .map(|(name, ty)| lower_arg(&types.repr(ty), &param(name)))
+            .collect();
+        let call = format!("ex.{}({})", names::export_name(&func.name), args.join(", "));
+        // Wrappers share the returned object with `memory` and the helpers
+        let wrapper = if WRAPPED_NAMES.contains(&func.name.as_str()) {
+            format!("{}$", func.name)
+        } else {
+            func.name.to_string()
+        };
+        line(2, &format!("{wrapper}({}) {{", params.join(", ")));
+        match types.repr(&func.return_type) {
+            Repr::Unit => line(3, &format!("{call};")),
+            repr => line(3, &format!("return {};", lift_result(&repr, &call))),
+        }
+        line(2, "},");
+    }
+    line(1, "};");
+    line(0, "}");
+
+    out
+}
+
+fn read_value(repr: &Repr, at: &str) -> String {
+    match repr {
+        Repr::Bool => format!("v.getUint32({at}, true) !== 0"),
+        Repr::U64 => format!("Number(v.getBigUint64({at}, true))"),
+        Repr::Str => format!("readStr(v.getUint32({at}, true))"),
+        Repr::Record(name) => format!("read{name}(v.getUint32({at}, true))"),
+        Repr::U32 | Repr::Handle | Repr::Unit => format!("v.getUint32({at}, true)"),
+    }
+}
+
+fn write_value(repr: &Repr, val_type: ValType, at: &str, value: &str) -> String {
+    match (repr, val_type) {
+        (Repr::Bool, _) => format!("v.setUint32({at}, {value} ? 1 : 0, true);"),
+        (_, ValType::I64) => format!("v.setBigUint64({at}, BigInt({value}), true);"),
+        _ => format!("v.setUint32({at}, {value}, true);"),
+    }
+}
+
+fn lower_arg(repr: &Repr, name: &str) -> String {
+    match repr {
+        Repr::Bool => format!("{name} ? 1 : 0"),
+        Repr::U64 => format!("BigInt({name})"),
+        Repr::Str => format!("writeStr({name})"),
+        Repr::Record(record) => format!("write{record}({name})"),
+        Repr::U32 | Repr::Handle | Repr::Unit => name.to_string(),
+    }
+}
+
+fn lift_result(repr: &Repr, call: &str) -> String {
+    match repr {
+        Repr::Bool => format!("{call} !== 0"),
+        Repr::U32 => format!("{call} >>> 0"),
+        Repr::U64 => format!("Number({call})"),
+        Repr::Str => format!("readStr({call})"),
+        Repr::Record(name) => format!("read{name}({call})"),
+        Repr::Handle | Repr::Unit => call.to_string(),
+    }
+}
+
+fn type_label(ty: &IrType) -> String {
+    match ty {
+        IrType::Bool => "Bool".to_string(),
+        IrType::Str => "Str".to_string(),
+        IrType::U16 => "U16".to_string(),
+        IrType::U32 => "U32".to_string(),
+        IrType::U64 => "U64".to_string(),
+        IrType::Unit => "Unit".to_string(),
+        IrType::Named(name) => name.to_string(),
+        IrType::Record(_) => "record".to_string(),
+        IrType::Union(_) => "union".to_string(),
+        IrType::Generic { base, .. } => format!("{}<..>", type_label(base)),
+    }
+}
+
+/// Generate Markdown documenting the module's memory layout and host ABI.
+pub fn generate_abi_doc(module: &IrModule) -> String {
+    let module = &generics::expand(module);
+    let types = Types::new(module);
+    let needs_alloc = module
+        .functions
+        .iter()
+        .flat_map(|f| f.params.iter())
+        .any(|(_, ty)| matches!(types.repr(ty), Repr::Str | Repr::Record(_)));
+
+    let mut out = String::new();
+    out.push_str(&format!(
+        "# ABI: {} {}\n\nZero1 WASM ABI v{ABI_VERSION}.\n\n",
+        module.name, module.version
+    ));
+
+    out.push_str("## Memory\n\n");
+    out.push_str("- Linear memory is exported as `memory`\n");
+    if needs_alloc {
+        out.push_str(
+            "- `alloc(size: i32) -> i32` allocates argument memory; \
+             ownership passes to the module and it is never freed\n",
+        );
+    }
+    out.push_str(&format!(
+        "- `Str`: pointer to a {}-byte little-endian length followed by UTF-8 bytes\n",
+        layout::STR_HEADER_SIZE
+    ));
+    out.push_str("- Records: pointer to fields in declaration order at the offsets below\n");
+    out.push_str("- `U64` is an `i64` (JS `BigInt`); other scalars are `i32`\n");
+
+    let records: Vec<_> = types.records().collect();
+    if !records.is_empty() {
+        out.push_str("\n## Types\n");
+        for (name, record) in records {
+            out.push_str(&format!(
+                "\n### {name} ({} bytes, align {})\n\n",
+                record.size, record.align
+            ));
+            out.push_str("| Field | Offset | Type | WASM |\n|---|---|---|---|\n");
+            let fields = match &types.defs[&name] {
+                IrType::Record(fields) => fields,
+                _ => unreachable!("records() only yields record types"),
+            };
+            for (field, (_, ty)) in record.fields.iter().zip(fields) {
+                out.push_str(&format!(
+                    "| `{}` | {} | {} | {} |\n",
+                    field.name,
+                    field.offset,
+                    type_label(ty),
+                    field.val_type().as_str()
+                ));
+            }
+        }
+    }
+
+    out.push_str("\n## Exports\n\n| Function | Params | Result |\n|---|---|---|\n");
+    for func in &module.functions {
+        let params: Vec<String> = func
+            .params
+            .iter()
+            .map(|(name, ty)| format!("`{name}: {}` ({})", type_label(ty), wasm_label(&types, ty)))
+            .collect();
+        let result = match func.return_type {
+            IrType::Unit => "-".to_string(),
+            ref ty => format!("{} ({})", type_label(ty), wasm_label(&types, ty)),
+        };
+        out.push_str(&format!(
+            "| `{}` | {} | {result} |\n",
+            names::export_name(&func.name),
+            if params.is_empty() {
+                "-".to_string()
+            } else {
+                params.join(", ")
+            }
+        ));
+    }
+    if let Some(main) = crate::entrypoint(module) {
+        out.push_str(&format!(
+            "\nThe entrypoint `{}` is also exported as `{}` (`[] -> []`, result dropped).\n",
+            main.name,
+            crate::START_EXPORT
+        ));
+    }
+
+    if !module.imports.is_empty() {
+        let arities = crate::collect_call_arities(module);
+        out.push_str("\n## Imports\n\n| Module | Function | Signature |\n|---|---|---|\n");
+        for import in &module.imports {
+            let path_module = import.path.replace('/', "_");
+            for item in &import.items {
+                let module_name = import.capabilities.get(item).unwrap_or(&path_module);
+                let arity = arities.get(item).copied().unwrap_or(1);
+                let params = vec!["i32"; arity].join(", ");
+                out.push_str(&format!(
+                    "| `{module_name}` | `{item}` | `({params}) -> i32` |\n"
+                ));
+            }
+        }
+    }
+
+    out
+}
+
+fn wasm_label(types: &Types, ty: &IrType) -> &'static str {
+    match types.repr(ty) {
+        Repr::Str | Repr::Record(_) => "i32 pointer",
+        Repr::U64 => "i64",
+        _ => "i32",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module() -> IrModule {
+        IrModule {
+            name: "shapes".to_string(),
+            version: "1.0".to_string(),
+            imports: vec![],
+            types: vec![
+                IrTypeDef {
+                    name: "Point".into(),
+                    type_params: vec![],
+                    ty: IrType::Record(vec![
+                        ("x".into(), IrType::U32),
+                        ("big".into(), IrType::U64),
+                        ("label".into(), IrType::Str),
+                    ]),
+                },
+                IrTypeDef {
+                    name: "Pt".into(),
+                    type_params: vec![],
+                    ty: IrType::Named("Point".into()),
+                },
+            ],
+            functions: vec![IrFunction {
+                name: "describe".into(),
+                type_params: vec![],
+                params: vec![
+                    ("p".into(), IrType::Named("Pt".into())),
+                    ("loud".into(), IrType::Bool),
+                ],
+                return_type: IrType::Str,
+                effects: vec![],
+                body: IrBlock { statements: vec![] },
+            }],
+            exports: vec!["describe".into()],
+        }
+    }
+
+    #[test]
+    fn glue_marshals_records_and_strings() {
+        let js = generate_js_glue(&module());
+        assert!(js.contains("const readPoint = (ptr) => {"));
+        assert!(js.contains("big: Number(v.getBigUint64(ptr + 8, true)),"));
+        assert!(js.contains("const labelPtr = writeStr(value.label);"));
+        assert!(js.contains("const ptr = ex.alloc(24);"));
+        assert!(js.contains("v.setUint32(ptr + 16, labelPtr, true);"));
+        // Aliases resolve to the record's helpers
+        assert!(js.contains("return readStr(ex.describe(writePoint(p), loud ? 1 : 0));"));
+    }
+
+    #[test]
+    fn glue_escapes_names_it_uses_itself() {
+        let mut module = module();
+        module.functions[0].name = "memory".into();
+        module.functions[0].params[0].0 = "ex".into();
+        module.functions[0].params[1].0 = "class".into();
+        let js = generate_js_glue(&module);
+        assert!(js.contains("memory: ex.memory,"));
+        assert!(js.contains("memory$(ex$, class$) {"));
+        assert!(js.contains("return readStr(ex.memory$(writePoint(ex$), class$ ? 1 : 0));"));
+        assert!(generate_abi_doc(&module).contains("| `memory$` |"));
+    }
+
+    #[test]
+    fn abi_doc_lists_offsets_and_signatures() {
+        let doc = generate_abi_doc(&module());
+        assert!(doc.contains("### Point (24 bytes, align 8)"));
+        assert!(doc.contains("| `big` | 8 | U64 | i64 |"));
+        assert!(doc.contains("`alloc(size: i32) -> i32`"));
+        assert!(doc.contains(
+            "| `describe` | `p: Pt` (i32 pointer), `loud: Bool` (i32) | Str (i32 pointer) |"
+        ));
+    }
+}
@@ -0,0 +1,421 @@
+//! WebAssembly Component Model output
+//!
+//! Derives a WIT interface from a module's exported function signatures and
+//! effects, and wraps the core module into a component that can be composed
+//! with other components or hosted in component runtimes such as wasmtime.
+//!
+//! WIT layout for a cell `http.server : 1.0`:
+//!
+//! ```wit
+//! package z1:http-server@1.0.0;
+//!
+//! interface types { /* records, aliases, opaque host types */ }
+//! interface net {}          // one empty interface per capability effect
+//!
+//! world server {
+//!   use types.{...};
+//!   import net;
+//!   export handler: func(req: req) -> res;
+//! }
+//! ```
+//!
+//! Capability effects become imported marker interfaces so a host can see
+//! (and refuse) the capabilities a component asks for. Types referenced in
+//! signatures but not defined in the cell are declared as opaque resources.
+//!
+//! Component encoding is limited to scalar signatures (`Bool`, `U16`, `U32`,
+//! `U64`) and cells without host imports: strings and records use the Zero1
+//! memory layout (see [`crate::layout`]), not the canonical ABI.
+
+use std::collections::{BTreeSet, HashMap};
+use thiserror::Error;
+use z1_ir::*;
+
+use crate::{layout, WasmOptions};
+
+/// Errors produced while deriving WIT or encoding a component
+#[derive(Debug, Error, PartialEq)]
+pub enum ComponentError {
+    #[error("type `{ty}` in `{context}` has no WIT representation")]
+    UnsupportedType { context: String, ty: String },
+    #[error("component output requires scalar signatures; `{function}` uses `{ty}`")]
+    NonScalarSignature { function: String, ty: String },
+    #[error("component output does not support host imports yet (imports `{0}`)")]
+    UnsupportedImport(String),
+    #[error("component encoding failed: {0}")]
+    Encoding(String),
+}
+
+/// Words that must be escaped with `%` when used as WIT identifiers.
+const WIT_KEYWORDS: &[&str] = &[
+    "as",
+    "async",
+    "bool",
+    "borrow",
+    "char",
+    "constructor",
+    "enum",
+    "export",
+    "f32",
+    "f64",
+    "flags",
+    "from",
+    "func",
+    "future",
+    "import",
+    "include",
+    "interface",
+    "list",
+    "option",
+    "own",
+    "package",
+    "record",
+    "resource",
+    "result",
+    "s16",
+    "s32",
+    "s64",
+    "s8",
+    "static",
+    "stream",
+    "string",
+    "tuple",
+    "type",
+    "u16",
+    "u32",
+    "u64",
+    "u8",
+    "use",
+    "variant",
+    "with",
+    "world",
+];
+
+/// Convert a Zero1 identifier to a WIT kebab-case name.
+///
+/// `snake_case` and `camelCase` boundaries become dashes. WIT words may not
+/// start with a digit, so such words are glued onto the previous word.
+pub fn wit_name(ident: &str) -> String {
+    let mut words: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut prev_lower = false;
+    for ch in ident.chars() {
+        if !ch.is_ascii_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            prev_lower = false;
+            continue;
+        }
+        if ch.is_ascii_uppercase() && prev_lower && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        prev_lower = ch.is_ascii_lowercase() || ch.is_ascii_digit();
+        current.push(ch.to_ascii_lowercase());
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+
+    let mut name = String::new();
+    for word in words {
+        let starts_with_digit = word.starts_with(|c: char| c.is_ascii_digit());
+        if !name.is_empty() && !starts_with_digit {
+            name.push('-');
+        }
+        if name.is_empty() && starts_with_digit {
+            name.push('n');
+        }
+        name.push_str(&word);
+    }
+    if name.is_empty() {
+        name.push('x');
+    }
+    name
+}
+
+/// WIT identifier for `ident`, `%`-escaped when it collides with a keyword.
+fn wit_ident(ident: &str) -> String {
+    let name = wit_name(ident);
+    if WIT_KEYWORDS.contains(&name.as_str()) {
+        format!("%{name}")
+    } else {
+        name
+    }
+}
+
+/// Semver for the WIT package, padding `1.0` to `1.0.0`.
+fn package_version(version: &str) -> Option<String> {
+    let parts: Vec<&str> = version.split('.').collect();
+    if parts.is_empty() || parts.len() > 3 || parts.iter().any(|p| p.parse::<u64>().is_err()) {
+        return None;
+    }
+    let mut padded: Vec<String> = parts.iter().map(|p| p.to_string()).collect();
+    while padded.len() < 3 {
+        padded.push("0".to_string());
+    }
+    Some(padded.join("."))
+}
+
+/// Effects that are capabilities rather than purity markers.
+fn capability_effects(func: &IrFunction) -> impl Iterator<Item = &Ident> {
+    func.effects.iter().filter(|e| e.as_str() != "pure")
+}
+
+/// Tracks type names while rendering signatures.
+struct TypeCtx<'a> {
+    defined: &'a HashMap<Ident, IrType>,
+    /// Referenced types without a definition, declared as resources
+    opaque: BTreeSet<Ident>,
+}
+
+impl TypeCtx<'_> {
+    fn wit_type(&mut self, ty: &IrType, context: &str) -> Result<String, ComponentError> {
+        match ty {
+            IrType::Bool => Ok("bool".to_string()),
+            IrType::Str => Ok("string".to_string()),
+            IrType::U16 => Ok("u16".to_string()),
+            IrType::U32 => Ok("u32".to_string()),
+            IrType::U64 => Ok("u64".to_string()),
+            IrType::Named(name) => {
+                if !self.defined.contains_key(name) {
+                    self.opaque.insert(*name);
+                }
+                Ok(wit_ident(name))
+            }
+            IrType::Unit | IrType::Record(_) | IrType::Union(_) | IrType::Generic { .. } => {
+                Err(ComponentError::UnsupportedType {
+                    context: context.to_string(),
+                    ty: format!("{ty:?}"),
+                })
+            }
+        }
+    }
+}
+
+/// Derive a WIT package describing the module's exports and effects.
+pub fn generate_wit(module: &IrModule) -> Result<String, ComponentError> {
+    // Generic functions are exported per instantiation
+    let module = &generics::expand(module);
+    let defined: HashMap<Ident, IrType> = module
+        .types
+        .iter()
+        .map(|t| (t.name, t.ty.clone()))
+        .collect();
+    let mut ctx = TypeCtx {
+        defined: &defined,
+        opaque: BTreeSet::new(),
+    };
+
+    // Type definitions
+    let mut type_lines = Vec::new();
+    for type_def in &module.types {
+        let name = wit_ident(&type_def.name);
+        match &type_def.ty {
+            IrType::Record(fields) => {
+                type_lines.push(format!("  record {name} {{"));
+                for (field, ty) in fields {
+                    let context = format!("{}.{field}", type_def.name);
+                    let field_ty = ctx.wit_type(ty, &context)?;
+                    type_lines.push(format!("    {}: {field_ty},", wit_ident(field)));
+                }
+                type_lines.push("  }".to_string());
+            }
+            IrType::Union(variants) => {
+                type_lines.push(format!("  variant {name} {{"));
+                for (case, payload) in variants {
+                    match payload {
+                        Some(ty) => {
+                            let context = format!("{}.{case}", type_def.name);
+                            let payload_ty = ctx.wit_type(ty, &context)?;
+                            type_lines.push(format!("    {}({payload_ty}),", wit_ident(case)));
+                        }
+                        None => type_lines.push(format!("    {},", wit_ident(case))),
+                    }
+                }
+                type_lines.push("  }".to_string());
+            }
+            other => {
+                let target = ctx.wit_type(other, &type_def.name)?;
+                type_lines.push(format!("  type {name} = {target};"));
+            }
+        }
+    }
+
+    // Exported functions
+    let mut func_lines = Vec::new();
+    for func in &module.functions {
+        let effects: Vec<&str> = func.effects.iter().map(|effect| effect.as_str()).collect();
+        if !effects.is_empty() {
+            func_lines.push(format!("  /// effects: {}", effects.join(", ")));
+        }
+        let mut params = Vec::new();
+        for (name, ty) in &func.params {
+            let context = format!("{}({name})", func.name);
+            params.push(format!(
+                "{}: {}",
+                wit_ident(name),
+                ctx.wit_type(ty, &context)?
+            ));
+        }
+        let result = match &func.return_type {
+            IrType::Unit => String::new(),
+            ty => format!(" -> {}", ctx.wit_type(ty, &func.name)?),
+        };
+        func_lines.push(format!(
+            "  export {}: func({}){result};",
+            wit_ident(&func.name),
+            params.join(", ")
+        ));
+    }
+
+    for name in &ctx.opaque {
+        type_lines.push(format!("  resource {};", wit_ident(name)));
+    }
+
+    let effects: BTreeSet<&Ident> = module
+        .functions
+        .iter()
+        .flat_map(capability_effects)
+        .collect();
+
+    let package = wit_name(&module.name.replace('.', "-"));
+    let world = wit_ident(module.name.rsplit('.').next().unwrap_or(&module.name));
+
+    let mut out = String::new();
+    out.push_str("// Generated by Zero1 compiler\n");
+    match package_version(&module.version) {
+        Some(version) => out.push_str(&format!("package z1:{package}@{version};\n")),
+        None => out.push_str(&format!("package z1:{package};\n")),
+    }
+
+    let type_names: Vec<String> = module
+        .types
+        .iter()
+        .map(|t| wit_ident(&t.name))
+        .chain(ctx.opaque.iter().map(|n| wit_ident(n)))
+        .collect();
+    if !type_lines.is_empty() {
+        out.push_str("\ninterface types {\n");
+        for line in &type_lines {
+            out.push_str(line);
+            out.push('\n');
+        }
+        out.push_str("}\n");
+    }
+
+    for effect in &effects {
+        out.push_str(&format!(
+            "\n/// Capability marker for the `{effect}` effect\ninterface {} {{}}\n",
+            wit_ident(effect)
+        ));
+    }
+
+    out.push_str(&format!("\nworld {world} {{\n"));
+    if !type_names.is_empty() {
+        out.push_str(&format!("  use types.{{{}}};\n", type_names.join(", ")));
+    }
+    for effect in &effects {
+        out.push_str(&format!("  import {};\n", wit_ident(effect)));
+    }
+    for line in &func_lines {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push_str("}\n");
+
+    Ok(out)
+}
+
+/// Encode the module as a WebAssembly component.
+///
+/// Returns the component binary; [`generate_wit`] yields the matching
+/// interface.
+pub fn generate_component(
+    module: &IrModule,
+    opt_level: optimize::OptLevel,
+) -> Result<Vec<u8>, ComponentError> {
+    if let Some(import) = module.imports.first() {
+        return Err(ComponentError::UnsupportedImport(import.path.clone()));
+    }
+    let module = &generics::expand(module);
+
+    let types: HashMap<Ident, IrType> = module
+        .types
+        .iter()
+        .map(|t| (t.name, t.ty.clone()))
+        .collect();
+    for func in &module.functions {
+        let signature = func
+            .params
+            .iter()
+            .map(|(_, ty)| ty)
+            .chain(std::iter::once(&func.return_type));
+        for ty in signature {
+            let scalar = matches!(
+                layout::resolve(ty, &types),
+                IrType::Bool | IrType::U16 | IrType::U32 | IrType::U64
+            ) || (ty == &func.return_type && *ty == IrType::Unit);
+            if !scalar {
+                return Err(ComponentError::NonScalarSignature {
+                    function: func.name.to_string(),
+                    ty: format!("{ty:?}"),
+                });
+            }
+        }
+    }
+
+    let mut optimized = module.clone();
+    optimize::optimize(&mut optimized, opt_level);
+
+    let wit = generate_wit(&optimized)?;
+    let options = WasmOptions {
+        component_exports: true,
+        ..WasmOptions::default()
+    };
+    let wat_text = crate::generate_wasm_with_options(&optimized, &options);
+    let mut core = wat::parse_str(&wat_text)
+        .map_err(|e| ComponentError::Encoding(format!("WAT parsing failed: {e}")))?;
+
+    let mut resolve = wit_parser::Resolve::default();
+    let package = resolve
+        .push_str("cell.wit", &wit)
+        .map_err(|e| ComponentError::Encoding(format!("{e:#}")))?;
+    let world = resolve
+        .select_world(&[package], None)
+        .map_err(|e| ComponentError::Encoding(format!("{e:#}")))?;
+    wit_component::embed_component_metadata(
+        &mut core,
+        &resolve,
+        world,
+        wit_component::StringEncoding::UTF8,
+    )
+    .map_err(|e| ComponentError::Encoding(format!("{e:#}")))?;
+
+    wit_component::ComponentEncoder::default()
+        .module(&core)
+        .and_then(|encoder| encoder.validate(true).encode())
+        .map_err(|e| ComponentError::Encoding(format!("{e:#}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wit_names_are_kebab_case() {
+        assert_eq!(wit_name("get_x"), "get-x");
+        assert_eq!(wit_name("HttpServer"), "http-server");
+        assert_eq!(wit_name("parseJSON"), "parse-json");
+        assert_eq!(wit_name("vec_2d"), "vec2d");
+        assert_eq!(wit_name("2fast"), "n2fast");
+        assert_eq!(wit_ident("list"), "%list");
+    }
+
+    #[test]
+    fn package_version_is_padded_to_semver() {
+        assert_eq!(package_version("1.0").as_deref(), Some("1.0.0"));
+        assert_eq!(package_version("2.1.3").as_deref(), Some("2.1.3"));
+        assert_eq!(package_version("latest"), None);
+    }
+}
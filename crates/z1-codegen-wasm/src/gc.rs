@@ -0,0 +1,240 @@
+//! WasmGC lowering (`--wasm-features gc`)
+//!
+//! Instead of laying values out in linear memory, records become GC
+//! `struct` types and strings become `(array (mut i8))` values:
+//!
+//! - Every record type is declared once, inside a single `rec` group so
+//!   records may refer to each other.
+//! - Record literals are `struct.new`; field access is `struct.get` /
+//!   `struct.set` by field name.
+//! - String literals are passive data segments copied out with
+//!   `array.new_data`; `array.len` replaces the length header.
+//!
+//! No memory or allocator is emitted, which keeps the output considerably
+//! smaller; the only scratch locals are the `structref`s holding the bases
+//! of record updates. Equality on strings and records compares
+//! references (`ref.eq`), matching pointer comparison in the linear-memory
+//! lowering.
+
+use z1_ir::*;
+
+use crate::{escape_data, layout, WasmCodegen};
+
+/// GC array type holding string bytes.
+pub(crate) const STR_TYPE: &str = "$str";
+
+impl WasmCodegen {
+    /// Register a struct type for every record declared in the module.
+    pub(crate) fn gc_register_types(&mut self, module: &IrModule) {
+        self.gc_structs.clear();
+        for type_def in &module.types {
+            if let IrType::Record(fields) = &type_def.ty {
+                self.gc_structs
+                    .push((type_def.name.to_string(), fields.clone()));
+            }
+        }
+    }
+
+    /// Struct type name for a record type, interning anonymous records.
+    pub(crate) fn gc_struct_name(&mut self, ty: &IrType) -> Option<String> {
+        match ty {
+            IrType::Named(name) => {
+                let mut current = *name;
+                for _ in 0..=self.type_defs.len() {
+                    match self.type_defs.get(&current) {
+                        Some(IrType::Named(next)) => current = *next,
+                        Some(IrType::Record(_)) => return Some(current.to_string()),
+                        _ => return None,
+                    }
+                }
+                None
+            }
+            IrType::Record(fields) => {
+                if let Some((name, _)) = self.gc_structs.iter().find(|(_, f)| f == fields) {
+                    return Some(name.clone());
+                }
+                let name = format!("__anon{}", self.gc_structs.len());
+                self.gc_structs.push((name.clone(), fields.clone()));
+                Some(name)
+            }
+            _ => None,
+        }
+    }
+
+    /// WASM value type of `ty` under the GC lowering.
+    pub(crate) fn gc_val_type(&mut self, ty: &IrType) -> String {
+        if let Some(name) = self.gc_struct_name(ty) {
+            return format!("(ref null ${name})");
+        }
+        match layout::resolve(ty, &self.type_defs) {
+            IrType::Str => format!("(ref null {STR_TYPE})"),
+            resolved => layout::val_type(resolved).as_str().to_string(),
+        }
+    }
+
+    /// Emit the string array type and the record struct types.
+    pub(crate) fn gen_gc_types(&mut self) {
+        self.write_line(";; GC types");
+        self.write_line(&format!("(type {STR_TYPE} (array (mut i8)))"));
+        if self.gc_structs.is_empty() {
+            return;
+        }
+
+        // Resolve field types first: nested anonymous records intern more
+        // structs while we go.
+        let mut idx = 0;
+        let mut declared = Vec::new();
+        while idx < self.gc_structs.len() {
+            let (name, fields) = self.gc_structs[idx].clone();
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(field, ty)| format!("(field ${field} (mut {}))", self.gc_val_type(ty)))
+                .collect();
+            declared.push(format!("(type ${name} (struct {}))", fields.join(" ")));
+            idx += 1;
+        }
+
+        self.write_line("(rec");
+        self.indent_level += 1;
+        for line in declared {
+            self.write_line(&line);
+        }
+        self.indent_level -= 1;
+        self.write_line(")");
+    }
+
+    /// Emit passive data segments for string literals.
+    pub(crate) fn gen_gc_strings(&mut self) {
+        self.write_line(";; String literals");
+        let literals = self.string_literals.clone();
+        for (content, idx) in literals {
+            let escaped = escape_data(content.as_bytes());
+            self.write_line(&format!("(data $str{idx} \"{escaped}\")"));
+        }
+    }
+
+    /// Push a new string array holding `value`.
+    pub(crate) fn gen_gc_string(&mut self, value: &str) {
+        let idx = match self.string_literals.iter().find(|(s, _)| s == value) {
+            Some((_, idx)) => *idx,
+            None => {
+                let idx = self.string_literals.len() as u32;
+                self.string_literals.push((value.to_string(), idx));
+                idx
+            }
+        };
+        let comment = value.replace('\n', "\\n");
+        self.write_line("i32.const 0");
+        self.write_line(&format!("i32.const {}", value.len()));
+        self.write_line(&format!(
+            "array.new_data {STR_TYPE} $str{idx} ;; string \"{comment}\""
+        ));
+    }
+
+    /// Allocate a struct from a record literal.
+    ///
+    /// Fields are evaluated in declaration order of the struct type; fields
+    /// missing from the literal get their type's default value.
+    pub(crate) fn gen_gc_record(&mut self, fields: &[(Ident, IrExpr)], expected: Option<&IrType>) {
+        let declared = expected.and_then(|ty| self.gc_struct_name(ty));
+        let name = match declared {
+            Some(name) => name,
+            None => {
+                let inferred = fields
+                    .iter()
+                    .map(|(name, value)| (*name, self.infer_type(value).unwrap_or(IrType::U32)))
+                    .collect();
+                self.gc_struct_name(&IrType::Record(inferred))
+                    .expect("record types always have a struct")
+            }
+        };
+        let struct_fields = self
+            .gc_structs
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, f)| f.clone())
+            .unwrap_or_default();
+
+        for (field, ty) in &struct_fields {
+            match fields.iter().find(|(n, _)| n == field) {
+                Some((_, value)) => self.gen_expr_expecting(value, Some(ty)),
+                None => {
+                    let default = match self.gc_val_type(ty).as_str() {
+                        "i32" => "i32.const 0".to_string(),
+                        "i64" => "i64.const 0".to_string(),
+                        reference => {
+                            let heap = reference
+                                .trim_start_matches("(ref null ")
+                                .trim_end_matches(')')
+                                .to_string();
+                            format!("ref.null {heap}")
+                        }
+                    };
+                    self.write_line(&format!("{default} ;; .{field} (default)"));
+                }
+            }
+        }
+        for (field, _) in fields {
+            if !struct_fields.iter().any(|(n, _)| n == field) {
+                self.write_line(&format!(";; Warning: field .{field} not in record type"));
+            }
+        }
+        self.write_line(&format!("struct.new ${name}"));
+    }
+
+    /// Copy the struct `base` evaluates to, taking `fields` from the update
+    /// and the other fields from the base.
+    pub(crate) fn gen_gc_record_update(
+        &mut self,
+        base: &IrExpr,
+        fields: &[(Ident, IrExpr)],
+        expected: Option<&IrType>,
+    ) {
+        let Some(name) = self
+            .update_type(base, expected)
+            .and_then(|ty| self.gc_struct_name(&ty))
+        else {
+            self.write_line(";; Warning: record update of a value that is not a record");
+            self.write_line("unreachable");
+            return;
+        };
+        let struct_fields = self
+            .gc_structs
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, f)| f.clone())
+            .unwrap_or_default();
+
+        let source = format!("$__rec{}", self.next_record_temp);
+        self.next_record_temp += 1;
+        self.gen_expr(base);
+        self.write_line(&format!("local.set {source}"));
+
+        for (field, ty) in &struct_fields {
+            match fields.iter().find(|(n, _)| n == field) {
+                Some((_, value)) => self.gen_expr_expecting(value, Some(ty)),
+                None => {
+                    self.write_line(&format!("local.get {source}"));
+                    self.write_line(&format!("ref.cast (ref null ${name})"));
+                    self.write_line(&format!("struct.get ${name} ${field}"));
+                }
+            }
+        }
+        for (field, _) in fields {
+            if !struct_fields.iter().any(|(n, _)| n == field) {
+                self.write_line(&format!(";; Warning: field .{field} not in record type"));
+            }
+        }
+        self.write_line(&format!("struct.new ${name}"));
+    }
+
+    /// Struct type of the record `base` evaluates to, if `field` exists on it.
+    pub(crate) fn gc_field_struct(&mut self, base: &IrExpr, field: &str) -> Option<String> {
+        let base_ty = self.infer_type(base)?;
+        let name = self.gc_struct_name(&base_ty)?;
+        self.gc_structs
+            .iter()
+            .find(|(n, fields)| *n == name && fields.iter().any(|(f, _)| f == field))
+            .map(|(n, _)| n.clone())
+    }
+}
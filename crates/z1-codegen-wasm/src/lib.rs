@@ -2,44 +2,181 @@
 //!
 //! This crate generates WebAssembly Text (WAT) format code from Zero1 IR.
 //! It provides complete statement and expression generation with memory
-//! management for complex types.
+//! management for complex types. Names clashing with those of generated
+//! code are escaped as described in [`names`].
 
-use std::collections::HashMap;
+pub mod abi;
+pub mod component;
+mod gc;
+pub mod names;
+mod size;
+
+pub use size::strip_custom_sections;
+pub use z1_ir::layout;
+
+use layout::{RecordLayout, ValType};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use z1_ir::*;
 
+/// Name of the entrypoint function
+pub const ENTRYPOINT: &str = "main";
+
+/// Export name the entrypoint is exposed under
+pub const START_EXPORT: &str = "_start";
+
+/// The module's entrypoint: a function named `main` taking no parameters.
+pub fn entrypoint(module: &IrModule) -> Option<&IrFunction> {
+    module
+        .functions
+        .iter()
+        .find(|f| f.name == ENTRYPOINT && f.params.is_empty())
+}
+
+/// Options controlling WASM generation
+#[derive(Debug, Clone, Default)]
+pub struct WasmOptions {
+    /// Name function exports after their WIT (kebab-case) identifiers so
+    /// the core module can be wrapped as a component.
+    pub component_exports: bool,
+    /// Source locations to embed as debug info
+    pub debug: Option<DebugInfo>,
+    /// Lower records and strings to WasmGC types instead of linear memory
+    pub gc: bool,
+    /// Favor small output: pool repeated constants and strip custom sections
+    pub opt_size: bool,
+}
+
+/// Source locations used for debug output
+///
+/// With debug info enabled, every function in the WAT is preceded by a
+/// `;; <source>:<line>` comment and the module carries a `z1.lines` custom
+/// section mapping function names to Z1 source lines.
+#[derive(Debug, Clone, Default)]
+pub struct DebugInfo {
+    /// Path of the Z1 source file
+    pub source_path: String,
+    /// 1-based line of each function declaration
+    pub function_lines: HashMap<String, u32>,
+}
+
+impl DebugInfo {
+    fn location(&self, function: &str) -> Option<String> {
+        self.function_lines
+            .get(function)
+            .map(|line| format!("{}:{line}", self.source_path))
+    }
+}
+
 /// WebAssembly code generator
 pub struct WasmCodegen {
+    options: WasmOptions,
     output: String,
     indent_level: usize,
     /// Local variable index mapping
-    local_map: HashMap<String, u32>,
+    local_map: HashMap<Ident, u32>,
+    /// Declared type of each local in the current function
+    local_types: HashMap<Ident, IrType>,
     /// Next available local index
     next_local: u32,
-    /// Memory offset for heap allocations
-    heap_offset: u32,
+    /// Module type definitions, used to resolve `Named` types
+    type_defs: HashMap<Ident, IrType>,
+    /// Parameter and return types of every module function
+    fn_sigs: HashMap<Ident, (Vec<IrType>, IrType)>,
+    /// Return type of the function being generated
+    current_return: IrType,
+    /// Next free offset for static data
+    static_offset: u32,
     /// String literals stored in data section
     string_literals: Vec<(String, u32)>,
+    /// Scratch locals reserved for record construction in the current function
+    record_temps: u32,
+    /// Next unused record scratch local
+    next_record_temp: u32,
+    /// Whether any function needs the bump allocator
+    uses_alloc: bool,
+    /// Whether hosts need `alloc` to pass strings and records in
+    export_alloc: bool,
+    /// Imported host functions, keyed by name
+    imported: HashMap<Ident, usize>,
+    /// Argument count observed at call sites, used to type imports
+    import_arity: HashMap<Ident, usize>,
+    /// GC struct types (name, fields) when lowering with WasmGC
+    gc_structs: Vec<(String, Vec<(Ident, IrType)>)>,
+    /// Integer constants worth pooling into globals (`opt_size`)
+    const_pool: HashSet<i64>,
+    /// Pooled constants emitted so far: (value type, value, immediate text)
+    pooled_consts: Vec<(&'static str, i64, String)>,
 }
 
 impl WasmCodegen {
     /// Create a new WebAssembly code generator
     pub fn new() -> Self {
+        Self::with_options(WasmOptions::default())
+    }
+
+    /// Create a code generator with explicit options
+    pub fn with_options(options: WasmOptions) -> Self {
         WasmCodegen {
+            options,
             output: String::new(),
             indent_level: 0,
             local_map: HashMap::new(),
+            local_types: HashMap::new(),
             next_local: 0,
-            heap_offset: 0,
+            type_defs: HashMap::new(),
+            fn_sigs: HashMap::new(),
+            current_return: IrType::Unit,
+            static_offset: 0,
             string_literals: Vec::new(),
+            record_temps: 0,
+            next_record_temp: 0,
+            uses_alloc: false,
+            export_alloc: false,
+            imported: HashMap::new(),
+            import_arity: HashMap::new(),
+            gc_structs: Vec::new(),
+            const_pool: HashSet::new(),
+            pooled_consts: Vec::new(),
         }
     }
 
     /// Generate WebAssembly code from IR module
     pub fn generate(&mut self, module: &IrModule) -> String {
+        // Generic types are laid out per application
+        let module = &z1_ir::generics::expand(module);
         self.output.clear();
         self.indent_level = 0;
-        self.heap_offset = 1024; // Reserve first 1KB for system use
+        self.static_offset = layout::STATIC_BASE;
         self.string_literals.clear();
+        self.uses_alloc = false;
+        self.imported.clear();
+        self.import_arity.clear();
+        self.type_defs = module
+            .types
+            .iter()
+            .map(|t| (t.name, t.ty.clone()))
+            .collect();
+        self.fn_sigs = module
+            .functions
+            .iter()
+            .map(|f| {
+                let params = f.params.iter().map(|(_, ty)| ty.clone()).collect();
+                (f.name, (params, f.return_type.clone()))
+            })
+            .collect();
+
+        // Hosts copy string and record arguments into linear memory through
+        // the exported allocator (see the `abi` module).
+        self.export_alloc = !self.options.component_exports
+            && !self.options.gc
+            && module
+                .functions
+                .iter()
+                .flat_map(|f| f.params.iter())
+                .any(|(_, ty)| self.is_pointer(ty));
+        self.uses_alloc = self.export_alloc;
+        self.plan_const_pool(module);
 
         // Module header
         self.write_line(";; Generated by Zero1 compiler");
@@ -49,17 +186,14 @@ impl WasmCodegen {
         ));
         self.write_line(&format!(";; Version: {}", module.version));
         self.write_line("");
-        self.write_line("(module");
+        // The module id ends up in the name section alongside the
+        // function and local names.
+        self.write_line(&format!("(module ${}", wat_id(&module.name)));
         self.indent_level += 1;
 
-        // Memory declaration (1 page = 64KB initially)
-        self.write_line(";; Linear memory");
-        self.write_line("(memory $mem 1)");
-        self.write_line("(export \"memory\" (memory $mem))");
-        self.write_line("");
-
-        // Imports
+        // Imports (must precede every function and memory definition)
         if !module.imports.is_empty() {
+            self.import_arity = collect_call_arities(module);
             self.write_line(";; Imports");
             for import in &module.imports {
                 self.gen_import(import);
@@ -67,6 +201,16 @@ impl WasmCodegen {
             self.write_line("");
         }
 
+        if self.options.gc {
+            self.gc_register_types(module);
+        } else {
+            // Memory declaration (1 page = 64KB initially)
+            self.write_line(";; Linear memory");
+            self.write_line("(memory $mem 1)");
+            self.write_line("(export \"memory\" (memory $mem))");
+            self.write_line("");
+        }
+
         // Type definitions (as comments for context)
         if !module.types.is_empty() {
             self.write_line(";; Type definitions");
@@ -82,52 +226,192 @@ impl WasmCodegen {
             self.write_line("");
         }
 
-        // Data section for string literals
-        if !self.string_literals.is_empty() {
+        // Components are driven through their WIT exports instead
+        if !self.options.component_exports {
+            if let Some(main) = entrypoint(module) {
+                self.gen_start(main);
+                self.write_line("");
+            }
+        }
+
+        if self.options.gc {
+            // Struct types may be interned while generating functions, so
+            // they are declared last (WAT allows any field order).
+            self.gen_gc_types();
+            self.write_line("");
+            if !self.string_literals.is_empty() {
+                self.gen_gc_strings();
+                self.write_line("");
+            }
+        } else if !self.string_literals.is_empty() {
+            // Data section for string literals
             self.write_line(";; String literals");
             let literals = self.string_literals.clone();
             for (content, offset) in literals {
-                let escaped = content.replace('\\', "\\\\").replace('"', "\\\"");
+                let escaped = escape_data(&layout::encode_str(&content));
                 self.write_line(&format!("(data (i32.const {offset}) \"{escaped}\")"));
             }
             self.write_line("");
         }
 
+        self.gen_const_pool();
+
+        if self.uses_alloc {
+            self.gen_allocator();
+            self.write_line("");
+        }
+
+        if let Some(debug) = &self.options.debug {
+            let lines: String = module
+                .functions
+                .iter()
+                .filter_map(|f| {
+                    debug
+                        .location(&f.name)
+                        .map(|loc| format!("{}\t{loc}\n", f.name))
+                })
+                .collect();
+            if !lines.is_empty() {
+                let escaped = escape_data(lines.as_bytes());
+                self.write_line(";; Function -> Z1 source line mapping");
+                self.write_line(&format!("(@custom \"z1.lines\" \"{escaped}\")"));
+                self.write_line("");
+            }
+        }
+
         self.indent_level -= 1;
         self.write_line(")");
 
         self.output.clone()
     }
 
+    /// Emit the bump allocator used for records.
+    ///
+    /// The heap starts after the last static data segment. Allocations are
+    /// aligned to [`layout::HEAP_ALIGN`] and memory grows on demand; an
+    /// allocation that cannot be satisfied traps.
+    fn gen_allocator(&mut self) {
+        let heap_base = layout::align_to(self.static_offset, layout::HEAP_ALIGN);
+        let mask = -(layout::HEAP_ALIGN as i32);
+        self.write_line(";; Bump allocator");
+        self.write_line(&format!(
+            "(global $heap_ptr (mut i32) (i32.const {heap_base}))"
+        ));
+        self.write_line("(func $alloc (param $size i32) (result i32)");
+        self.indent_level += 1;
+        self.write_line("(local $ptr i32)");
+        self.write_line("global.get $heap_ptr");
+        self.write_line("local.set $ptr");
+        self.write_line("local.get $ptr");
+        self.write_line("local.get $size");
+        self.write_line("i32.add");
+        self.write_line(&format!("i32.const {}", layout::HEAP_ALIGN - 1));
+        self.write_line("i32.add");
+        self.write_line(&format!("i32.const {mask}"));
+        self.write_line("i32.and");
+        self.write_line("global.set $heap_ptr");
+        self.write_line(";; Grow memory when the heap passes the end of the last page");
+        self.write_line("global.get $heap_ptr");
+        self.write_line("memory.size");
+        self.write_line("i32.const 16");
+        self.write_line("i32.shl");
+        self.write_line("i32.gt_u");
+        self.write_line("(if");
+        self.indent_level += 1;
+        self.write_line("(then");
+        self.indent_level += 1;
+        self.write_line("global.get $heap_ptr");
+        self.write_line("memory.size");
+        self.write_line("i32.const 16");
+        self.write_line("i32.shl");
+        self.write_line("i32.sub");
+        self.write_line("i32.const 65535");
+        self.write_line("i32.add");
+        self.write_line("i32.const 16");
+        self.write_line("i32.shr_u");
+        self.write_line("memory.grow");
+        self.write_line("i32.const -1");
+        self.write_line("i32.eq");
+        self.write_line("(if");
+        self.indent_level += 1;
+        self.write_line("(then");
+        self.indent_level += 1;
+        self.write_line("unreachable");
+        self.indent_level -= 1;
+        self.write_line(")");
+        self.indent_level -= 1;
+        self.write_line(")");
+        self.indent_level -= 1;
+        self.write_line(")");
+        self.indent_level -= 1;
+        self.write_line(")");
+        self.write_line("local.get $ptr");
+        self.indent_level -= 1;
+        self.write_line(")");
+        if self.export_alloc {
+            self.write_line("(export \"alloc\" (func $alloc))");
+        }
+    }
+
     fn gen_import(&mut self, import: &IrImport) {
-        // Generate import statements for external functions
-        let module_name = import.path.replace('/', "_");
+        // Generate import statements for external functions. Host functions
+        // take one i32 per argument seen at call sites (one when never
+        // called) and return an i32. Those performing an effect come from
+        // the module named after the capability granting them, so hosts can
+        // hand over each capability on its own.
+        let path_module = import.path.replace('/', "_");
         for item in &import.items {
+            if self.imported.contains_key(item) {
+                continue;
+            }
+            let module_name = import.capabilities.get(item).unwrap_or(&path_module);
+            let arity = self.import_arity.get(item).copied().unwrap_or(1);
+            let params = " (param i32)".repeat(arity);
+            let id = names::func_id(item);
+            let annotation = names::name_annotation(item, &id);
             self.write_line(&format!(
-                "(import \"{module_name}\" \"{item}\" (func ${item} (param i32) (result i32)))"
+                "(import \"{module_name}\" \"{item}\" (func ${id}{annotation}{params} (result i32)))"
             ));
+            self.imported.insert(*item, arity);
         }
     }
 
     fn gen_function(&mut self, func: &IrFunction) {
         // Reset local state
         self.local_map.clear();
+        self.local_types.clear();
         self.next_local = 0;
+        self.next_record_temp = 0;
+        self.current_return = func.return_type.clone();
+
+        if let Some(location) = self
+            .options
+            .debug
+            .as_ref()
+            .and_then(|d| d.location(&func.name))
+        {
+            self.write_line(&format!(";; {location}"));
+        }
 
         // Build function signature
-        let mut sig = format!("(func ${}", func.name);
+        let id = names::func_id(&func.name);
+        let mut sig = format!("(func ${id}{}", names::name_annotation(&func.name, &id));
 
         // Parameters
         for (param_name, param_type) in &func.params {
-            let wasm_type = self.type_to_wasm(param_type);
-            sig.push_str(&format!(" (param ${param_name} {wasm_type})"));
-            self.local_map.insert(param_name.clone(), self.next_local);
+            let wasm_type = self.local_type(param_type);
+            sig.push_str(&format!(
+                " (param ${} {wasm_type})",
+                names::local_id(param_name)
+            ));
+            self.local_map.insert(*param_name, self.next_local);
+            self.local_types.insert(*param_name, param_type.clone());
             self.next_local += 1;
         }
 
         // Return type
         if func.return_type != IrType::Unit {
-            let ret_type = self.type_to_wasm(&func.return_type);
+            let ret_type = self.local_type(&func.return_type);
             sig.push_str(&format!(" (result {ret_type})"));
         }
 
@@ -137,14 +421,25 @@ impl WasmCodegen {
         // Collect local variables from function body
         let locals = self.collect_locals(&func.body);
         for (local_name, local_type) in locals {
-            let wasm_type = self.type_to_wasm(&local_type);
-            self.write_line(&format!("(local ${local_name} {wasm_type})"));
+            let wasm_type = self.local_type(&local_type);
+            self.write_line(&format!(
+                "(local ${} {wasm_type})",
+                names::local_id(&local_name)
+            ));
             if !self.local_map.contains_key(&local_name) {
                 self.local_map.insert(local_name, self.next_local);
                 self.next_local += 1;
             }
         }
 
+        // Scratch locals holding record pointers while fields are stored,
+        // or under GC the bases of record updates
+        self.record_temps = count_records_in_block(&func.body, self.options.gc);
+        let scratch_type = if self.options.gc { "structref" } else { "i32" };
+        for idx in 0..self.record_temps {
+            self.write_line(&format!("(local $__rec{idx} {scratch_type})"));
+        }
+
         if !func.body.statements.is_empty() {
             self.write_line("");
         }
@@ -152,49 +447,85 @@ impl WasmCodegen {
         // Generate function body
         self.gen_block(&func.body);
 
+        // Falling off the end of a function with a result is a type error
+        // in WASM; bodies that do not end in `return` trap instead.
+        if func.return_type != IrType::Unit
+            && !matches!(func.body.statements.last(), Some(IrStmt::Return { .. }))
+        {
+            self.write_line("unreachable");
+        }
+
         self.indent_level -= 1;
         self.write_line(")");
 
         // Export function
-        self.write_line(&format!("(export \"{}\" (func ${}))", func.name, func.name));
+        let export_name = if self.options.component_exports {
+            component::wit_name(&func.name)
+        } else {
+            names::export_name(&func.name)
+        };
+        self.write_line(&format!("(export \"{export_name}\" (func ${id}))"));
+    }
+
+    /// Export the entrypoint as `_start`, dropping any result so the export
+    /// has the `[] -> []` type WASI command runners expect.
+    fn gen_start(&mut self, main: &IrFunction) {
+        self.write_line(";; Entrypoint");
+        if main.return_type == IrType::Unit {
+            self.write_line(&format!(
+                "(export \"{START_EXPORT}\" (func ${}))",
+                names::func_id(&main.name)
+            ));
+            return;
+        }
+        self.write_line("(func $_start");
+        self.indent_level += 1;
+        self.write_line(&format!("call ${}", names::func_id(&main.name)));
+        self.write_line("drop");
+        self.indent_level -= 1;
+        self.write_line(")");
+        self.write_line(&format!("(export \"{START_EXPORT}\" (func $_start))"));
     }
 
-    fn collect_locals(&self, block: &IrBlock) -> Vec<(String, IrType)> {
+    /// Collect `let` bindings in declaration order, inferring a type for
+    /// bindings without an annotation.
+    fn collect_locals(&mut self, block: &IrBlock) -> Vec<(Ident, IrType)> {
         let mut locals = Vec::new();
+        self.collect_locals_from_block(block, &mut locals);
+        locals
+    }
+
+    fn collect_locals_from_block(&mut self, block: &IrBlock, locals: &mut Vec<(Ident, IrType)>) {
         for stmt in &block.statements {
-            Self::collect_locals_from_stmt(stmt, &mut locals);
+            self.collect_locals_from_stmt(stmt, locals);
         }
-        locals
     }
 
-    fn collect_locals_from_stmt(stmt: &IrStmt, locals: &mut Vec<(String, IrType)>) {
+    fn collect_locals_from_stmt(&mut self, stmt: &IrStmt, locals: &mut Vec<(Ident, IrType)>) {
         match stmt {
-            IrStmt::Let { name, ty, .. } => {
-                if let Some(t) = ty {
-                    locals.push((name.clone(), t.clone()));
-                } else {
-                    // Default to i32 if type not specified
-                    locals.push((name.clone(), IrType::U32));
-                }
+            IrStmt::Let {
+                name, ty, value, ..
+            } => {
+                // Default to U32 when the type cannot be inferred
+                let local_ty = ty
+                    .clone()
+                    .or_else(|| self.infer_type(value))
+                    .unwrap_or(IrType::U32);
+                self.local_types.insert(*name, local_ty.clone());
+                locals.push((*name, local_ty));
             }
             IrStmt::If {
                 then_block,
                 else_block,
                 ..
             } => {
-                for s in &then_block.statements {
-                    Self::collect_locals_from_stmt(s, locals);
-                }
+                self.collect_locals_from_block(then_block, locals);
                 if let Some(eb) = else_block {
-                    for s in &eb.statements {
-                        Self::collect_locals_from_stmt(s, locals);
-                    }
+                    self.collect_locals_from_block(eb, locals);
                 }
             }
             IrStmt::While { body, .. } => {
-                for s in &body.statements {
-                    Self::collect_locals_from_stmt(s, locals);
-                }
+                self.collect_locals_from_block(body, locals);
             }
             _ => {}
         }
@@ -210,27 +541,60 @@ impl WasmCodegen {
         match stmt {
             IrStmt::Let { name, value, .. } => {
                 // Generate expression and store to local
-                self.gen_expr(value);
+                let expected = self.local_types.get(name).cloned();
+                self.gen_expr_expecting(value, expected.as_ref());
                 if self.local_map.contains_key(name) {
-                    self.write_line(&format!("local.set ${name}"));
+                    self.write_line(&format!("local.set ${}", names::local_id(name)));
                 } else {
                     self.write_line(&format!(";; Warning: undefined local {name}"));
                 }
             }
             IrStmt::Assign { target, value } => {
-                // Generate value first
-                self.gen_expr(value);
-
                 // Handle different assignment targets
                 match target {
                     IrExpr::Var(name) => {
-                        self.write_line(&format!("local.set ${name}"));
+                        let expected = self.local_types.get(name).cloned();
+                        self.gen_expr_expecting(value, expected.as_ref());
+                        self.write_line(&format!("local.set ${}", names::local_id(name)));
                     }
-                    IrExpr::Field { field, .. } => {
-                        // For field assignment, we need to calculate offset and store
-                        self.write_line(&format!(";; TODO: field assignment to {field}"));
+                    IrExpr::Field { base, field } if self.options.gc => {
+                        match self.gc_field_struct(base, field) {
+                            Some(name) => {
+                                let field_ty = self.field_layout(base, field).map(|f| f.ty);
+                                self.gen_expr(base);
+                                self.gen_expr_expecting(value, field_ty.as_ref());
+                                self.write_line(&format!("struct.set ${name} ${field}"));
+                            }
+                            None => {
+                                self.write_line(&format!(
+                                    ";; Warning: unknown field .{field} in assignment"
+                                ));
+                                self.write_line("unreachable");
+                            }
+                        }
+                    }
+                    IrExpr::Field { base, field } => {
+                        // Push record pointer, then value, then store at the field offset
+                        match self.field_layout(base, field) {
+                            Some(field_layout) => {
+                                self.gen_expr(base);
+                                self.gen_expr_expecting(value, Some(&field_layout.ty));
+                                self.write_line(&format!(
+                                    "{} offset={}",
+                                    field_layout.val_type().store_instr(),
+                                    field_layout.offset
+                                ));
+                            }
+                            None => {
+                                self.write_line(&format!(
+                                    ";; Warning: unknown field .{field} in assignment"
+                                ));
+                                self.write_line("unreachable");
+                            }
+                        }
                     }
                     _ => {
+                        self.gen_expr(value);
                         self.write_line(";; Warning: unsupported assignment target");
                     }
                 }
@@ -288,122 +652,301 @@ impl WasmCodegen {
             }
             IrStmt::Return { value } => {
                 if let Some(val) = value {
-                    self.gen_expr(val);
+                    let expected = self.current_return.clone();
+                    if expected == IrType::Unit {
+                        // `ret Unit;` in a function without a result
+                        if !is_unit_value(val) && self.produces_value(val) {
+                            self.gen_expr(val);
+                            self.write_line("drop");
+                        }
+                    } else {
+                        self.gen_expr_expecting(val, Some(&expected));
+                    }
                 }
                 self.write_line("return");
             }
             IrStmt::Expr(expr) => {
+                if is_unit_value(expr) {
+                    return;
+                }
                 self.gen_expr(expr);
                 // Drop result if expression produces one
-                match expr {
-                    IrExpr::Call { .. } | IrExpr::BinOp { .. } | IrExpr::UnaryOp { .. } => {
-                        self.write_line("drop");
-                    }
-                    _ => {}
+                if self.produces_value(expr) {
+                    self.write_line("drop");
                 }
             }
         }
     }
 
     fn gen_expr(&mut self, expr: &IrExpr) {
+        self.gen_expr_expecting(expr, None);
+    }
+
+    /// Generate an expression whose type is known from context.
+    ///
+    /// The expected type only matters for record literals, which are laid
+    /// out according to the declared record type when one is available so
+    /// that field accesses elsewhere agree on offsets.
+    fn gen_expr_expecting(&mut self, expr: &IrExpr, expected: Option<&IrType>) {
         match expr {
             IrExpr::Var(name) => {
-                self.write_line(&format!("local.get ${name}"));
+                self.write_line(&format!("local.get ${}", names::local_id(name)));
+            }
+            IrExpr::Literal(IrLiteral::Str(s)) if self.options.gc => {
+                self.gen_gc_string(s);
             }
             IrExpr::Literal(lit) => {
-                self.gen_literal(lit);
+                let wide = expected.is_some_and(|ty| self.is_wide(ty));
+                self.gen_literal(lit, wide);
             }
             IrExpr::BinOp { op, left, right } => {
-                self.gen_expr(left);
-                self.gen_expr(right);
-                self.gen_binop(op);
+                // Operands share a type; 64-bit if either side is
+                let operand_ty = [left, right]
+                    .iter()
+                    .filter_map(|e| self.infer_type(e))
+                    .find(|ty| self.is_wide(ty))
+                    .or_else(|| self.infer_type(left));
+                self.gen_expr_expecting(left, operand_ty.as_ref());
+                self.gen_expr_expecting(right, operand_ty.as_ref());
+                let is_ref =
+                    self.options.gc && operand_ty.as_ref().is_some_and(|ty| self.is_pointer(ty));
+                if is_ref && matches!(op, IrBinOp::Eq | IrBinOp::Ne) {
+                    // GC references compare by identity
+                    self.write_line("ref.eq");
+                    if matches!(op, IrBinOp::Ne) {
+                        self.write_line("i32.eqz");
+                    }
+                    return;
+                }
+                let wide = operand_ty.as_ref().is_some_and(|ty| self.is_wide(ty));
+                self.gen_binop(op, wide);
             }
             IrExpr::UnaryOp { op, expr } => {
-                self.gen_expr(expr);
-                self.gen_unaryop(op);
+                let operand_ty = match op {
+                    IrUnaryOp::Neg => self.infer_type(expr).or_else(|| expected.cloned()),
+                    _ => self.infer_type(expr),
+                };
+                let wide = operand_ty.as_ref().is_some_and(|ty| self.is_wide(ty));
+                if matches!(op, IrUnaryOp::Neg) {
+                    // Negate as 0 - x; the zero must be below x on the stack
+                    self.write_line(if wide { "i64.const 0" } else { "i32.const 0" });
+                }
+                self.gen_expr_expecting(expr, operand_ty.as_ref());
+                self.gen_unaryop(op, wide);
             }
             IrExpr::Call { func, args } => {
+                let callee = match func.as_ref() {
+                    IrExpr::Var(name) => Some(*name),
+                    IrExpr::Path(path) if path.len() == 1 => Some(path[0]),
+                    _ => None,
+                };
+                let param_types = callee
+                    .as_ref()
+                    .and_then(|name| self.fn_sigs.get(name))
+                    .map(|(params, _)| params.clone())
+                    .unwrap_or_default();
+                let is_known = callee.as_ref().is_some_and(|name| {
+                    self.fn_sigs.contains_key(name) || self.imported.contains_key(name)
+                });
+                if !is_known {
+                    self.write_line(";; Warning: call to unknown function");
+                    self.write_line("unreachable");
+                    return;
+                }
+
                 // Generate arguments
-                for arg in args {
-                    self.gen_expr(arg);
+                for (idx, arg) in args.iter().enumerate() {
+                    self.gen_expr_expecting(arg, param_types.get(idx));
                 }
 
                 // Generate call
-                match func.as_ref() {
-                    IrExpr::Var(name) => {
-                        self.write_line(&format!("call ${name}"));
-                    }
-                    IrExpr::Path(path) if path.len() == 1 => {
-                        self.write_line(&format!("call ${}", path[0]));
-                    }
-                    _ => {
-                        self.write_line(";; TODO: indirect call");
+                if let Some(name) = callee {
+                    self.write_line(&format!("call ${}", names::func_id(&name)));
+                }
+            }
+            IrExpr::Field { base, field } if self.options.gc => {
+                self.gen_expr(base);
+                match self.gc_field_struct(base, field) {
+                    Some(name) => self.write_line(&format!("struct.get ${name} ${field}")),
+                    None => {
+                        self.write_line(&format!(";; Warning: unknown field .{field}"));
+                        self.write_line("unreachable");
                     }
                 }
             }
             IrExpr::Field { base, field } => {
-                // For field access, we need to calculate offset and load
+                // Load the field from the record pointer at its layout offset
                 self.gen_expr(base);
-                self.write_line(&format!(";; TODO: field access .{field}"));
-                self.write_line("i32.const 0 ;; placeholder for field offset");
-                self.write_line("i32.add");
-                self.write_line("i32.load");
+                match self.field_layout(base, field) {
+                    Some(field_layout) => {
+                        self.write_line(&format!(
+                            "{} offset={} ;; .{field}",
+                            field_layout.val_type().load_instr(),
+                            field_layout.offset
+                        ));
+                    }
+                    None => {
+                        self.write_line(&format!(";; Warning: unknown field .{field}"));
+                        self.write_line("unreachable");
+                    }
+                }
+            }
+            IrExpr::Record { fields } if self.options.gc => {
+                self.gen_gc_record(fields, expected);
             }
             IrExpr::Record { fields } => {
-                // Allocate memory for record
-                let record_size = fields.len() as u32 * 4; // 4 bytes per field (simplified)
-                let offset = self.heap_offset;
-                self.heap_offset += record_size;
-
-                // Store each field
-                for (idx, (_field_name, field_expr)) in fields.iter().enumerate() {
-                    self.write_line(&format!("i32.const {}", offset + (idx as u32 * 4)));
-                    self.gen_expr(field_expr);
-                    self.write_line("i32.store");
-                }
-
-                // Return pointer to record
-                self.write_line(&format!("i32.const {offset}"));
+                self.gen_record(fields, expected);
+            }
+            IrExpr::RecordUpdate { base, fields } if self.options.gc => {
+                self.gen_gc_record_update(base, fields, expected);
+            }
+            IrExpr::RecordUpdate { base, fields } => {
+                self.gen_record_update(base, fields, expected);
             }
             IrExpr::Path(segments) => {
                 // For now, treat paths as variables
-                let name = segments.join("_");
-                self.write_line(&format!("local.get ${name}"));
+                let name = Symbol::join(segments, "_");
+                self.write_line(&format!("local.get ${}", names::local_id(&name)));
+            }
+        }
+    }
+
+    /// Allocate a record on the heap and store each field at its offset.
+    fn gen_record(&mut self, fields: &[(Ident, IrExpr)], expected: Option<&IrType>) {
+        let record_layout = expected
+            .and_then(|ty| layout::record_layout(ty, &self.type_defs))
+            .unwrap_or_else(|| {
+                let inferred: Vec<(Ident, IrType)> = fields
+                    .iter()
+                    .map(|(name, value)| {
+                        let ty = self.infer_type(value).unwrap_or(IrType::U32);
+                        (*name, ty)
+                    })
+                    .collect();
+                RecordLayout::of(&inferred)
+            });
+
+        let temp = format!("$__rec{}", self.next_record_temp);
+        self.next_record_temp += 1;
+        self.uses_alloc = true;
+
+        self.write_line(&format!("i32.const {}", record_layout.size));
+        self.write_line("call $alloc");
+        self.write_line(&format!("local.set {temp}"));
+
+        for (name, value) in fields {
+            let Some(field_layout) = record_layout.field(name) else {
+                self.write_line(&format!(";; Warning: field .{name} not in record type"));
+                continue;
+            };
+            let field_layout = field_layout.clone();
+            self.write_line(&format!("local.get {temp}"));
+            self.gen_expr_expecting(value, Some(&field_layout.ty));
+            self.write_line(&format!(
+                "{} offset={} ;; .{name}",
+                field_layout.val_type().store_instr(),
+                field_layout.offset
+            ));
+        }
+
+        // Return pointer to record
+        self.write_line(&format!("local.get {temp}"));
+    }
+
+    /// Allocate a copy of the record `base` evaluates to, field by field,
+    /// storing `fields` in place of the copied values.
+    fn gen_record_update(
+        &mut self,
+        base: &IrExpr,
+        fields: &[(Ident, IrExpr)],
+        expected: Option<&IrType>,
+    ) {
+        let Some(record_layout) = self
+            .update_type(base, expected)
+            .and_then(|ty| layout::record_layout(&ty, &self.type_defs))
+        else {
+            self.write_line(";; Warning: record update of a value that is not a record");
+            self.write_line("unreachable");
+            return;
+        };
+
+        let temp = format!("$__rec{}", self.next_record_temp);
+        let source = format!("$__rec{}", self.next_record_temp + 1);
+        self.next_record_temp += 2;
+        self.uses_alloc = true;
+
+        self.gen_expr(base);
+        self.write_line(&format!("local.set {source}"));
+        self.write_line(&format!("i32.const {}", record_layout.size));
+        self.write_line("call $alloc");
+        self.write_line(&format!("local.set {temp}"));
+
+        for field_layout in &record_layout.fields {
+            self.write_line(&format!("local.get {temp}"));
+            match fields.iter().find(|(name, _)| *name == field_layout.name) {
+                Some((_, value)) => self.gen_expr_expecting(value, Some(&field_layout.ty)),
+                None => {
+                    self.write_line(&format!("local.get {source}"));
+                    self.write_line(&format!(
+                        "{} offset={}",
+                        field_layout.val_type().load_instr(),
+                        field_layout.offset
+                    ));
+                }
+            }
+            self.write_line(&format!(
+                "{} offset={} ;; .{}",
+                field_layout.val_type().store_instr(),
+                field_layout.offset,
+                field_layout.name
+            ));
+        }
+        for (name, _) in fields {
+            if record_layout.field(name).is_none() {
+                self.write_line(&format!(";; Warning: field .{name} not in record type"));
             }
         }
+
+        self.write_line(&format!("local.get {temp}"));
+    }
+
+    /// Record type of an update of `base`: the type of the base, or the one
+    /// expected of the result.
+    fn update_type(&self, base: &IrExpr, expected: Option<&IrType>) -> Option<IrType> {
+        self.infer_type(base).or_else(|| expected.cloned())
     }
 
-    fn gen_literal(&mut self, lit: &IrLiteral) {
+    /// Emit a literal. `wide` widens integer literals to `i64` when the
+    /// surrounding expression is 64-bit.
+    fn gen_literal(&mut self, lit: &IrLiteral, wide: bool) {
         match lit {
             IrLiteral::Bool(b) => {
                 let val = if *b { 1 } else { 0 };
                 self.write_line(&format!("i32.const {val}"));
             }
             IrLiteral::Str(s) => {
-                // Store string in data section
-                let offset = self.heap_offset;
-                let len = s.len();
-                self.string_literals.push((s.clone(), offset));
-                self.heap_offset += len as u32 + 1; // +1 for null terminator
-
-                // Return pointer and length (as simple i32 pointer for now)
-                self.write_line(&format!("i32.const {offset} ;; string \"{s}\""));
+                let offset = self.intern_string(s);
+                let comment = s.replace('\n', "\\n");
+                self.write_line(&format!("i32.const {offset} ;; string \"{comment}\""));
             }
             IrLiteral::U16(n) => {
-                self.write_line(&format!("i32.const {n}"));
+                let ty = if wide { "i64" } else { "i32" };
+                self.gen_int_const(ty, n.to_string(), *n as i64);
             }
             IrLiteral::U32(n) => {
-                self.write_line(&format!("i32.const {n}"));
+                let ty = if wide { "i64" } else { "i32" };
+                self.gen_int_const(ty, n.to_string(), *n as i64);
             }
             IrLiteral::U64(n) => {
-                self.write_line(&format!("i64.const {n}"));
+                self.gen_int_const("i64", n.to_string(), *n as i64);
             }
             IrLiteral::Int(n) => {
-                if *n >= i32::MIN as i64 && *n <= i32::MAX as i64 {
-                    self.write_line(&format!("i32.const {n}"));
+                let ty = if !wide && *n >= i32::MIN as i64 && *n <= i32::MAX as i64 {
+                    "i32"
                 } else {
-                    self.write_line(&format!("i64.const {n}"));
-                }
+                    "i64"
+                };
+                self.gen_int_const(ty, n.to_string(), *n);
             }
             IrLiteral::Unit => {
                 // Unit doesn't produce a value
@@ -411,32 +954,132 @@ impl WasmCodegen {
         }
     }
 
-    fn gen_binop(&mut self, op: &IrBinOp) {
+    /// Place a string literal in static data, reusing identical literals.
+    fn intern_string(&mut self, value: &str) -> u32 {
+        if let Some((_, offset)) = self.string_literals.iter().find(|(s, _)| s == value) {
+            return *offset;
+        }
+        let offset = layout::align_to(self.static_offset, layout::STR_HEADER_SIZE);
+        self.string_literals.push((value.to_string(), offset));
+        self.static_offset = offset + layout::STR_HEADER_SIZE + value.len() as u32;
+        offset
+    }
+
+    /// Whether values of `ty` are held in an `i64`.
+    fn is_wide(&self, ty: &IrType) -> bool {
+        layout::val_type(layout::resolve(ty, &self.type_defs)) == ValType::I64
+    }
+
+    /// Whether values of `ty` live in linear memory and are passed by pointer.
+    fn is_pointer(&self, ty: &IrType) -> bool {
+        matches!(
+            layout::resolve(ty, &self.type_defs),
+            IrType::Str | IrType::Record(_)
+        )
+    }
+
+    /// Whether evaluating `expr` leaves a value on the operand stack.
+    fn produces_value(&self, expr: &IrExpr) -> bool {
+        match expr {
+            IrExpr::Literal(IrLiteral::Unit) => false,
+            IrExpr::Call { func, .. } => {
+                let name = match func.as_ref() {
+                    IrExpr::Var(name) => name,
+                    IrExpr::Path(path) if path.len() == 1 => &path[0],
+                    _ => return false,
+                };
+                if self.imported.contains_key(name) {
+                    return true;
+                }
+                self.fn_sigs
+                    .get(name)
+                    .is_some_and(|(_, ret)| *ret != IrType::Unit)
+            }
+            _ => true,
+        }
+    }
+
+    /// Layout of `field` on the record that `base` evaluates to.
+    fn field_layout(&self, base: &IrExpr, field: &str) -> Option<layout::FieldLayout> {
+        let base_ty = self.infer_type(base)?;
+        let record_layout = layout::record_layout(&base_ty, &self.type_defs)?;
+        record_layout.field(field).cloned()
+    }
+
+    /// Best-effort static type of an expression.
+    fn infer_type(&self, expr: &IrExpr) -> Option<IrType> {
+        match expr {
+            IrExpr::Var(name) => self.local_types.get(name).cloned(),
+            IrExpr::Literal(lit) => Some(match lit {
+                IrLiteral::Bool(_) => IrType::Bool,
+                IrLiteral::Str(_) => IrType::Str,
+                IrLiteral::U16(_) => IrType::U16,
+                IrLiteral::U32(_) => IrType::U32,
+                IrLiteral::U64(_) => IrType::U64,
+                IrLiteral::Int(n) if *n >= i32::MIN as i64 && *n <= i32::MAX as i64 => IrType::U32,
+                IrLiteral::Int(_) => IrType::U64,
+                IrLiteral::Unit => IrType::Unit,
+            }),
+            IrExpr::BinOp { op, left, .. } => match op {
+                IrBinOp::Eq
+                | IrBinOp::Ne
+                | IrBinOp::Lt
+                | IrBinOp::Le
+                | IrBinOp::Gt
+                | IrBinOp::Ge
+                | IrBinOp::And
+                | IrBinOp::Or => Some(IrType::Bool),
+                _ => self.infer_type(left),
+            },
+            IrExpr::UnaryOp { op, expr } => match op {
+                IrUnaryOp::Not => Some(IrType::Bool),
+                _ => self.infer_type(expr),
+            },
+            IrExpr::Call { func, .. } => {
+                let name = match func.as_ref() {
+                    IrExpr::Var(name) => name,
+                    IrExpr::Path(path) if path.len() == 1 => &path[0],
+                    _ => return None,
+                };
+                self.fn_sigs.get(name).map(|(_, ret)| ret.clone())
+            }
+            IrExpr::Field { base, field } => self.field_layout(base, field).map(|f| f.ty),
+            IrExpr::Record { fields } => Some(IrType::Record(
+                fields
+                    .iter()
+                    .map(|(name, value)| (*name, self.infer_type(value).unwrap_or(IrType::U32)))
+                    .collect(),
+            )),
+            IrExpr::RecordUpdate { base, .. } => self.infer_type(base),
+            IrExpr::Path(_) => None,
+        }
+    }
+
+    fn gen_binop(&mut self, op: &IrBinOp, wide: bool) {
         let instr = match op {
-            IrBinOp::Add => "i32.add",
-            IrBinOp::Sub => "i32.sub",
-            IrBinOp::Mul => "i32.mul",
-            IrBinOp::Div => "i32.div_u",
-            IrBinOp::Mod => "i32.rem_u",
-            IrBinOp::Eq => "i32.eq",
-            IrBinOp::Ne => "i32.ne",
-            IrBinOp::Lt => "i32.lt_u",
-            IrBinOp::Le => "i32.le_u",
-            IrBinOp::Gt => "i32.gt_u",
-            IrBinOp::Ge => "i32.ge_u",
-            IrBinOp::And => "i32.and",
-            IrBinOp::Or => "i32.or",
+            IrBinOp::Add => "add",
+            IrBinOp::Sub => "sub",
+            IrBinOp::Mul => "mul",
+            IrBinOp::Div => "div_u",
+            IrBinOp::Mod => "rem_u",
+            IrBinOp::Eq => "eq",
+            IrBinOp::Ne => "ne",
+            IrBinOp::Lt => "lt_u",
+            IrBinOp::Le => "le_u",
+            IrBinOp::Gt => "gt_u",
+            IrBinOp::Ge => "ge_u",
+            IrBinOp::And => "and",
+            IrBinOp::Or => "or",
         };
-        self.write_line(instr);
+        let prefix = if wide { "i64" } else { "i32" };
+        self.write_line(&format!("{prefix}.{instr}"));
     }
 
-    fn gen_unaryop(&mut self, op: &IrUnaryOp) {
+    fn gen_unaryop(&mut self, op: &IrUnaryOp, wide: bool) {
         match op {
             IrUnaryOp::Neg => {
-                // Negate: 0 - x
-                self.write_line("i32.const 0");
-                self.write_line("swap ;; TODO: proper negation");
-                self.write_line("i32.sub");
+                // Negate: 0 - x (the zero was pushed before the operand)
+                self.write_line(if wide { "i64.sub" } else { "i32.sub" });
             }
             IrUnaryOp::Not => {
                 // Boolean not: x == 0
@@ -448,15 +1091,24 @@ impl WasmCodegen {
         }
     }
 
+    /// Value type of a parameter, result or local.
+    fn local_type(&mut self, ty: &IrType) -> String {
+        if self.options.gc {
+            self.gc_val_type(ty)
+        } else {
+            self.type_to_wasm(ty).to_string()
+        }
+    }
+
     fn type_to_wasm(&self, ty: &IrType) -> &str {
         match ty {
             IrType::Bool => "i32",
-            IrType::Str => "i32", // Pointer to string data
+            IrType::Str => "i32", // Pointer to string header
             IrType::U16 => "i32",
             IrType::U32 => "i32",
             IrType::U64 => "i64",
-            IrType::Unit => "i32",           // Placeholder
-            IrType::Named(_) => "i32",       // Pointer or value
+            IrType::Unit => "i32", // Placeholder
+            IrType::Named(_) => layout::val_type(layout::resolve(ty, &self.type_defs)).as_str(),
             IrType::Record(_) => "i32",      // Pointer to record
             IrType::Union(_) => "i32",       // Pointer or tagged value
             IrType::Generic { .. } => "i32", // Pointer
@@ -471,6 +1123,188 @@ impl WasmCodegen {
     }
 }
 
+/// Whether `expr` is the unit value, written `()` or `Unit`.
+fn is_unit_value(expr: &IrExpr) -> bool {
+    match expr {
+        IrExpr::Literal(IrLiteral::Unit) => true,
+        IrExpr::Var(name) => name == "Unit",
+        IrExpr::Path(path) => path.len() == 1 && path[0] == "Unit",
+        _ => false,
+    }
+}
+
+/// Largest argument count used when calling each function name.
+fn collect_call_arities(module: &IrModule) -> HashMap<Ident, usize> {
+    let mut arities = HashMap::new();
+    for func in &module.functions {
+        visit_block_exprs(&func.body, &mut |expr| {
+            if let IrExpr::Call { func, args } = expr {
+                if let IrExpr::Var(name) = func.as_ref() {
+                    let entry = arities.entry(*name).or_insert(0);
+                    *entry = usize::max(*entry, args.len());
+                }
+            }
+        });
+    }
+    arities
+}
+
+fn visit_block_exprs(block: &IrBlock, f: &mut impl FnMut(&IrExpr)) {
+    for stmt in &block.statements {
+        match stmt {
+            IrStmt::Let { value, .. } => visit_expr(value, f),
+            IrStmt::Assign { target, value } => {
+                visit_expr(target, f);
+                visit_expr(value, f);
+            }
+            IrStmt::If {
+                cond,
+                then_block,
+                else_block,
+            } => {
+                visit_expr(cond, f);
+                visit_block_exprs(then_block, f);
+                if let Some(else_block) = else_block {
+                    visit_block_exprs(else_block, f);
+                }
+            }
+            IrStmt::While { cond, body } => {
+                visit_expr(cond, f);
+                visit_block_exprs(body, f);
+            }
+            IrStmt::Return { value } => {
+                if let Some(value) = value {
+                    visit_expr(value, f);
+                }
+            }
+            IrStmt::Expr(expr) => visit_expr(expr, f),
+        }
+    }
+}
+
+fn visit_expr(expr: &IrExpr, f: &mut impl FnMut(&IrExpr)) {
+    f(expr);
+    match expr {
+        IrExpr::BinOp { left, right, .. } => {
+            visit_expr(left, f);
+            visit_expr(right, f);
+        }
+        IrExpr::UnaryOp { expr, .. } => visit_expr(expr, f),
+        IrExpr::Call { func, args } => {
+            visit_expr(func, f);
+            for arg in args {
+                visit_expr(arg, f);
+            }
+        }
+        IrExpr::Field { base, .. } => visit_expr(base, f),
+        IrExpr::Record { fields } => {
+            for (_, value) in fields {
+                visit_expr(value, f);
+            }
+        }
+        IrExpr::RecordUpdate { base, fields } => {
+            visit_expr(base, f);
+            for (_, value) in fields {
+                visit_expr(value, f);
+            }
+        }
+        IrExpr::Var(_) | IrExpr::Literal(_) | IrExpr::Path(_) => {}
+    }
+}
+
+/// Number of scratch locals a block needs: one per record literal and two
+/// per record update, or under GC one per record update.
+fn count_records_in_block(block: &IrBlock, gc: bool) -> u32 {
+    block
+        .statements
+        .iter()
+        .map(|stmt| count_records_in_stmt(stmt, gc))
+        .sum()
+}
+
+fn count_records_in_stmt(stmt: &IrStmt, gc: bool) -> u32 {
+    let expr = |expr: &IrExpr| count_records_in_expr(expr, gc);
+    match stmt {
+        IrStmt::Let { value, .. } => expr(value),
+        IrStmt::Assign { target, value } => expr(target) + expr(value),
+        IrStmt::If {
+            cond,
+            then_block,
+            else_block,
+        } => {
+            expr(cond)
+                + count_records_in_block(then_block, gc)
+                + else_block
+                    .as_ref()
+                    .map_or(0, |block| count_records_in_block(block, gc))
+        }
+        IrStmt::While { cond, body } => expr(cond) + count_records_in_block(body, gc),
+        IrStmt::Return { value } => value.as_ref().map_or(0, expr),
+        IrStmt::Expr(value) => expr(value),
+    }
+}
+
+fn count_records_in_expr(expr: &IrExpr, gc: bool) -> u32 {
+    let fields_count = |fields: &[(Ident, IrExpr)]| {
+        fields
+            .iter()
+            .map(|(_, value)| count_records_in_expr(value, gc))
+            .sum::<u32>()
+    };
+    match expr {
+        IrExpr::Record { fields } => u32::from(!gc) + fields_count(fields),
+        IrExpr::RecordUpdate { base, fields } => {
+            let own = if gc { 1 } else { 2 };
+            own + count_records_in_expr(base, gc) + fields_count(fields)
+        }
+        IrExpr::BinOp { left, right, .. } => {
+            count_records_in_expr(left, gc) + count_records_in_expr(right, gc)
+        }
+        IrExpr::UnaryOp { expr, .. } => count_records_in_expr(expr, gc),
+        IrExpr::Call { func, args } => {
+            count_records_in_expr(func, gc)
+                + args
+                    .iter()
+                    .map(|arg| count_records_in_expr(arg, gc))
+                    .sum::<u32>()
+        }
+        IrExpr::Field { base, .. } => count_records_in_expr(base, gc),
+        IrExpr::Var(_) | IrExpr::Literal(_) | IrExpr::Path(_) => 0,
+    }
+}
+
+/// Escape raw bytes for a WAT data string.
+fn escape_data(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' => out.push_str(&format!("\\{byte:02x}")),
+            0x20..=0x7e => out.push(byte as char),
+            _ => out.push_str(&format!("\\{byte:02x}")),
+        }
+    }
+    out
+}
+
+/// Turn a Zero1 name into a valid WAT identifier (without the `$`).
+fn wat_id(name: &str) -> String {
+    let id: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "!#$%&'*+-./:<=>?@\\^_`|~".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if id.is_empty() {
+        "module".to_string()
+    } else {
+        id
+    }
+}
+
 impl Default for WasmCodegen {
     fn default() -> Self {
         Self::new()
@@ -491,6 +1325,12 @@ pub fn generate_wasm_optimized(module: &IrModule, opt_level: z1_ir::optimize::Op
     codegen.generate(&optimized)
 }
 
+/// Generate WebAssembly code from IR module with explicit options
+pub fn generate_wasm_with_options(module: &IrModule, options: &WasmOptions) -> String {
+    let mut codegen = WasmCodegen::with_options(options.clone());
+    codegen.generate(module)
+}
+
 /// Generate binary WebAssembly (.wasm) from IR module
 ///
 /// This function generates WAT text first, then parses it into binary format.
@@ -512,6 +1352,31 @@ pub fn generate_wasm_binary_optimized(
     wat::parse_str(&wat_text).map_err(|e| format!("WAT parsing failed: {e}"))
 }
 
+/// Generate binary WebAssembly (.wasm) from IR module with explicit options
+pub fn generate_wasm_binary_with_options(
+    module: &IrModule,
+    options: &WasmOptions,
+) -> Result<Vec<u8>, String> {
+    let wat_text = generate_wasm_with_options(module, options);
+    let binary = wat::parse_str(&wat_text).map_err(|e| format!("WAT parsing failed: {e}"))?;
+    if options.opt_size {
+        strip_custom_sections(&binary)
+    } else {
+        Ok(binary)
+    }
+}
+
+/// Assemble WAT text into a binary with DWARF line info.
+///
+/// The line table maps every instruction back to `wat_path`, so the WAT
+/// text should be written there for debuggers to display it.
+pub fn generate_wasm_binary_with_debug(wat_text: &str, wat_path: &Path) -> Result<Vec<u8>, String> {
+    wat::Parser::new()
+        .generate_dwarf(wat::GenerateDwarf::Lines)
+        .parse_str(Some(wat_path), wat_text)
+        .map_err(|e| format!("WAT parsing failed: {e}"))
+}
+
 /// Validate that a binary WebAssembly module is well-formed
 ///
 /// Uses the `wasmparser` crate to validate that the binary conforms
@@ -527,6 +1392,12 @@ pub fn validate_wasm_binary(binary: &[u8]) -> Result<(), String> {
     Ok(())
 }
 
+/// Assemble WAT text and validate the resulting binary
+pub fn validate_wat(wat_text: &str) -> Result<(), String> {
+    let binary = wat::parse_str(wat_text).map_err(|e| format!("WAT parsing failed: {e}"))?;
+    validate_wasm_binary(&binary)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -539,24 +1410,22 @@ mod tests {
             imports: vec![],
             types: vec![],
             functions: vec![IrFunction {
-                name: "add".to_string(),
-                params: vec![
-                    ("a".to_string(), IrType::U32),
-                    ("b".to_string(), IrType::U32),
-                ],
+                name: "add".into(),
+                type_params: vec![],
+                params: vec![("a".into(), IrType::U32), ("b".into(), IrType::U32)],
                 return_type: IrType::U32,
                 effects: vec![],
                 body: IrBlock {
                     statements: vec![IrStmt::Return {
                         value: Some(IrExpr::BinOp {
                             op: IrBinOp::Add,
-                            left: Box::new(IrExpr::Var("a".to_string())),
-                            right: Box::new(IrExpr::Var("b".to_string())),
+                            left: Box::new(IrExpr::Var("a".into())),
+                            right: Box::new(IrExpr::Var("b".into())),
                         }),
                     }],
                 },
             }],
-            exports: vec!["add".to_string()],
+            exports: vec!["add".into()],
         };
 
         let wat = generate_wasm(&module);
@@ -576,20 +1445,21 @@ mod tests {
             imports: vec![],
             types: vec![],
             functions: vec![IrFunction {
-                name: "test_let".to_string(),
+                name: "test_let".into(),
+                type_params: vec![],
                 params: vec![],
                 return_type: IrType::U32,
                 effects: vec![],
                 body: IrBlock {
                     statements: vec![
                         IrStmt::Let {
-                            name: "x".to_string(),
+                            name: "x".into(),
                             mutable: false,
                             ty: Some(IrType::U32),
                             value: IrExpr::Literal(IrLiteral::U32(42)),
                         },
                         IrStmt::Return {
-                            value: Some(IrExpr::Var("x".to_string())),
+                            value: Some(IrExpr::Var("x".into())),
                         },
                     ],
                 },
@@ -612,13 +1482,14 @@ mod tests {
             imports: vec![],
             types: vec![],
             functions: vec![IrFunction {
-                name: "test_if".to_string(),
-                params: vec![("cond".to_string(), IrType::Bool)],
+                name: "test_if".into(),
+                type_params: vec![],
+                params: vec![("cond".into(), IrType::Bool)],
                 return_type: IrType::U32,
                 effects: vec![],
                 body: IrBlock {
                     statements: vec![IrStmt::If {
-                        cond: IrExpr::Var("cond".to_string()),
+                        cond: IrExpr::Var("cond".into()),
                         then_block: IrBlock {
                             statements: vec![IrStmt::Return {
                                 value: Some(IrExpr::Literal(IrLiteral::U32(1))),
@@ -650,14 +1521,15 @@ mod tests {
             imports: vec![],
             types: vec![],
             functions: vec![IrFunction {
-                name: "test_while".to_string(),
-                params: vec![("n".to_string(), IrType::U32)],
+                name: "test_while".into(),
+                type_params: vec![],
+                params: vec![("n".into(), IrType::U32)],
                 return_type: IrType::U32,
                 effects: vec![],
                 body: IrBlock {
                     statements: vec![
                         IrStmt::Let {
-                            name: "i".to_string(),
+                            name: "i".into(),
                             mutable: true,
                             ty: Some(IrType::U32),
                             value: IrExpr::Literal(IrLiteral::U32(0)),
@@ -665,22 +1537,22 @@ mod tests {
                         IrStmt::While {
                             cond: IrExpr::BinOp {
                                 op: IrBinOp::Lt,
-                                left: Box::new(IrExpr::Var("i".to_string())),
-                                right: Box::new(IrExpr::Var("n".to_string())),
+                                left: Box::new(IrExpr::Var("i".into())),
+                                right: Box::new(IrExpr::Var("n".into())),
                             },
                             body: IrBlock {
                                 statements: vec![IrStmt::Assign {
-                                    target: IrExpr::Var("i".to_string()),
+                                    target: IrExpr::Var("i".into()),
                                     value: IrExpr::BinOp {
                                         op: IrBinOp::Add,
-                                        left: Box::new(IrExpr::Var("i".to_string())),
+                                        left: Box::new(IrExpr::Var("i".into())),
                                         right: Box::new(IrExpr::Literal(IrLiteral::U32(1))),
                                     },
                                 }],
                             },
                         },
                         IrStmt::Return {
-                            value: Some(IrExpr::Var("i".to_string())),
+                            value: Some(IrExpr::Var("i".into())),
                         },
                     ],
                 },
@@ -713,7 +1585,8 @@ mod tests {
                 imports: vec![],
                 types: vec![],
                 functions: vec![IrFunction {
-                    name: "test_op".to_string(),
+                    name: "test_op".into(),
+                    type_params: vec![],
                     params: vec![],
                     return_type: IrType::U32,
                     effects: vec![],
@@ -747,25 +1620,27 @@ mod tests {
             types: vec![],
             functions: vec![
                 IrFunction {
-                    name: "helper".to_string(),
-                    params: vec![("x".to_string(), IrType::U32)],
+                    name: "helper".into(),
+                    type_params: vec![],
+                    params: vec![("x".into(), IrType::U32)],
                     return_type: IrType::U32,
                     effects: vec![],
                     body: IrBlock {
                         statements: vec![IrStmt::Return {
-                            value: Some(IrExpr::Var("x".to_string())),
+                            value: Some(IrExpr::Var("x".into())),
                         }],
                     },
                 },
                 IrFunction {
-                    name: "caller".to_string(),
+                    name: "caller".into(),
+                    type_params: vec![],
                     params: vec![],
                     return_type: IrType::U32,
                     effects: vec![],
                     body: IrBlock {
                         statements: vec![IrStmt::Return {
                             value: Some(IrExpr::Call {
-                                func: Box::new(IrExpr::Var("helper".to_string())),
+                                func: Box::new(IrExpr::Var("helper".into())),
                                 args: vec![IrExpr::Literal(IrLiteral::U32(42))],
                             }),
                         }],
@@ -787,7 +1662,8 @@ mod tests {
             imports: vec![],
             types: vec![],
             functions: vec![IrFunction {
-                name: "get_string".to_string(),
+                name: "get_string".into(),
+                type_params: vec![],
                 params: vec![],
                 return_type: IrType::Str,
                 effects: vec![],
@@ -802,8 +1678,8 @@ mod tests {
 
         let wat = generate_wasm(&module);
         assert!(wat.contains(";; string \"Hello\""));
-        assert!(wat.contains("(data (i32.const"));
-        assert!(wat.contains("\"Hello\")"));
+        // Length header (5, little-endian) precedes the bytes
+        assert!(wat.contains("(data (i32.const 1024) \"\\05\\00\\00\\00Hello\")"));
     }
 
     #[test]
@@ -814,19 +1690,20 @@ mod tests {
             imports: vec![],
             types: vec![],
             functions: vec![IrFunction {
-                name: "make_point".to_string(),
+                name: "make_point".into(),
+                type_params: vec![],
                 params: vec![],
                 return_type: IrType::Record(vec![
-                    ("x".to_string(), IrType::U32),
-                    ("y".to_string(), IrType::U32),
+                    ("x".into(), IrType::U32),
+                    ("y".into(), IrType::U32),
                 ]),
                 effects: vec![],
                 body: IrBlock {
                     statements: vec![IrStmt::Return {
                         value: Some(IrExpr::Record {
                             fields: vec![
-                                ("x".to_string(), IrExpr::Literal(IrLiteral::U32(10))),
-                                ("y".to_string(), IrExpr::Literal(IrLiteral::U32(20))),
+                                ("x".into(), IrExpr::Literal(IrLiteral::U32(10))),
+                                ("y".into(), IrExpr::Literal(IrLiteral::U32(20))),
                             ],
                         }),
                     }],
@@ -836,8 +1713,12 @@ mod tests {
         };
 
         let wat = generate_wasm(&module);
-        assert!(wat.contains("i32.store"));
-        assert!(wat.contains("i32.const"));
+        assert!(wat.contains("i32.const 8\n"));
+        assert!(wat.contains("call $alloc"));
+        assert!(wat.contains("i32.store offset=0 ;; .x"));
+        assert!(wat.contains("i32.store offset=4 ;; .y"));
+        assert!(wat.contains("(func $alloc"));
+        assert!(wat.contains("(global $heap_ptr (mut i32)"));
     }
 
     #[test]
@@ -848,21 +1729,19 @@ mod tests {
             imports: vec![],
             types: vec![],
             functions: vec![IrFunction {
-                name: "get_x".to_string(),
+                name: "get_x".into(),
+                type_params: vec![],
                 params: vec![(
-                    "point".to_string(),
-                    IrType::Record(vec![
-                        ("x".to_string(), IrType::U32),
-                        ("y".to_string(), IrType::U32),
-                    ]),
+                    "point".into(),
+                    IrType::Record(vec![("x".into(), IrType::U32), ("y".into(), IrType::U32)]),
                 )],
                 return_type: IrType::U32,
                 effects: vec![],
                 body: IrBlock {
                     statements: vec![IrStmt::Return {
                         value: Some(IrExpr::Field {
-                            base: Box::new(IrExpr::Var("point".to_string())),
-                            field: "x".to_string(),
+                            base: Box::new(IrExpr::Var("point".into())),
+                            field: "x".into(),
                         }),
                     }],
                 },
@@ -871,8 +1750,9 @@ mod tests {
         };
 
         let wat = generate_wasm(&module);
-        assert!(wat.contains(";; TODO: field access .x"));
-        assert!(wat.contains("i32.load"));
+        assert!(wat.contains("i32.load offset=0 ;; .x"));
+        // Only the exported allocator's out-of-memory trap
+        assert_eq!(wat.matches("unreachable").count(), 1);
     }
 
     #[test]
@@ -883,13 +1763,14 @@ mod tests {
             imports: vec![],
             types: vec![],
             functions: vec![IrFunction {
-                name: "identity".to_string(),
-                params: vec![("x".to_string(), IrType::U32)],
+                name: "identity".into(),
+                type_params: vec![],
+                params: vec![("x".into(), IrType::U32)],
                 return_type: IrType::U32,
                 effects: vec![],
                 body: IrBlock {
                     statements: vec![IrStmt::Return {
-                        value: Some(IrExpr::Var("x".to_string())),
+                        value: Some(IrExpr::Var("x".into())),
                     }],
                 },
             }],
@@ -935,30 +1816,31 @@ mod tests {
             imports: vec![],
             types: vec![],
             functions: vec![IrFunction {
-                name: "create_records".to_string(),
+                name: "create_records".into(),
+                type_params: vec![],
                 params: vec![],
                 return_type: IrType::U32,
                 effects: vec![],
                 body: IrBlock {
                     statements: vec![
                         IrStmt::Let {
-                            name: "r1".to_string(),
+                            name: "r1".into(),
                             mutable: false,
-                            ty: Some(IrType::Record(vec![("x".to_string(), IrType::U32)])),
+                            ty: Some(IrType::Record(vec![("x".into(), IrType::U32)])),
                             value: IrExpr::Record {
-                                fields: vec![("x".to_string(), IrExpr::Literal(IrLiteral::U32(1)))],
+                                fields: vec![("x".into(), IrExpr::Literal(IrLiteral::U32(1)))],
                             },
                         },
                         IrStmt::Let {
-                            name: "r2".to_string(),
+                            name: "r2".into(),
                             mutable: false,
-                            ty: Some(IrType::Record(vec![("y".to_string(), IrType::U32)])),
+                            ty: Some(IrType::Record(vec![("y".into(), IrType::U32)])),
                             value: IrExpr::Record {
-                                fields: vec![("y".to_string(), IrExpr::Literal(IrLiteral::U32(2)))],
+                                fields: vec![("y".into(), IrExpr::Literal(IrLiteral::U32(2)))],
                             },
                         },
                         IrStmt::Return {
-                            value: Some(IrExpr::Var("r2".to_string())),
+                            value: Some(IrExpr::Var("r2".into())),
                         },
                     ],
                 },
@@ -981,20 +1863,21 @@ mod tests {
             imports: vec![],
             types: vec![],
             functions: vec![IrFunction {
-                name: "factorial".to_string(),
-                params: vec![("n".to_string(), IrType::U32)],
+                name: "factorial".into(),
+                type_params: vec![],
+                params: vec![("n".into(), IrType::U32)],
                 return_type: IrType::U32,
                 effects: vec![],
                 body: IrBlock {
                     statements: vec![
                         IrStmt::Let {
-                            name: "result".to_string(),
+                            name: "result".into(),
                             mutable: true,
                             ty: Some(IrType::U32),
                             value: IrExpr::Literal(IrLiteral::U32(1)),
                         },
                         IrStmt::Let {
-                            name: "i".to_string(),
+                            name: "i".into(),
                             mutable: true,
                             ty: Some(IrType::U32),
                             value: IrExpr::Literal(IrLiteral::U32(1)),
@@ -1002,24 +1885,24 @@ mod tests {
                         IrStmt::While {
                             cond: IrExpr::BinOp {
                                 op: IrBinOp::Le,
-                                left: Box::new(IrExpr::Var("i".to_string())),
-                                right: Box::new(IrExpr::Var("n".to_string())),
+                                left: Box::new(IrExpr::Var("i".into())),
+                                right: Box::new(IrExpr::Var("n".into())),
                             },
                             body: IrBlock {
                                 statements: vec![
                                     IrStmt::Assign {
-                                        target: IrExpr::Var("result".to_string()),
+                                        target: IrExpr::Var("result".into()),
                                         value: IrExpr::BinOp {
                                             op: IrBinOp::Mul,
-                                            left: Box::new(IrExpr::Var("result".to_string())),
-                                            right: Box::new(IrExpr::Var("i".to_string())),
+                                            left: Box::new(IrExpr::Var("result".into())),
+                                            right: Box::new(IrExpr::Var("i".into())),
                                         },
                                     },
                                     IrStmt::Assign {
-                                        target: IrExpr::Var("i".to_string()),
+                                        target: IrExpr::Var("i".into()),
                                         value: IrExpr::BinOp {
                                             op: IrBinOp::Add,
-                                            left: Box::new(IrExpr::Var("i".to_string())),
+                                            left: Box::new(IrExpr::Var("i".into())),
                                             right: Box::new(IrExpr::Literal(IrLiteral::U32(1))),
                                         },
                                     },
@@ -1027,12 +1910,12 @@ mod tests {
                             },
                         },
                         IrStmt::Return {
-                            value: Some(IrExpr::Var("result".to_string())),
+                            value: Some(IrExpr::Var("result".into())),
                         },
                     ],
                 },
             }],
-            exports: vec!["factorial".to_string()],
+            exports: vec!["factorial".into()],
         };
 
         let wat = generate_wasm(&module);
@@ -1055,15 +1938,16 @@ mod tests {
             imports: vec![],
             types: vec![],
             functions: vec![IrFunction {
-                name: "test_not".to_string(),
-                params: vec![("x".to_string(), IrType::Bool)],
+                name: "test_not".into(),
+                type_params: vec![],
+                params: vec![("x".into(), IrType::Bool)],
                 return_type: IrType::Bool,
                 effects: vec![],
                 body: IrBlock {
                     statements: vec![IrStmt::Return {
                         value: Some(IrExpr::UnaryOp {
                             op: IrUnaryOp::Not,
-                            expr: Box::new(IrExpr::Var("x".to_string())),
+                            expr: Box::new(IrExpr::Var("x".into())),
                         }),
                     }],
                 },
@@ -1074,4 +1958,693 @@ mod tests {
         let wat = generate_wasm(&module);
         assert!(wat.contains("i32.eqz"));
     }
+
+    fn point_type() -> IrTypeDef {
+        IrTypeDef {
+            name: "Point".into(),
+            type_params: vec![],
+            ty: IrType::Record(vec![("x".into(), IrType::U32), ("big".into(), IrType::U64)]),
+        }
+    }
+
+    #[test]
+    fn test_record_literal_uses_declared_layout() {
+        // Literal fields are written in a different order than declared
+        let module = IrModule {
+            name: "test".to_string(),
+            version: "1.0.0".to_string(),
+            imports: vec![],
+            types: vec![point_type()],
+            functions: vec![IrFunction {
+                name: "make".into(),
+                type_params: vec![],
+                params: vec![],
+                return_type: IrType::Named("Point".into()),
+                effects: vec![],
+                body: IrBlock {
+                    statements: vec![IrStmt::Return {
+                        value: Some(IrExpr::Record {
+                            fields: vec![
+                                ("big".into(), IrExpr::Literal(IrLiteral::U64(7))),
+                                ("x".into(), IrExpr::Literal(IrLiteral::U32(1))),
+                            ],
+                        }),
+                    }],
+                },
+            }],
+            exports: vec![],
+        };
+
+        let wat = generate_wasm(&module);
+        assert!(wat.contains("i32.const 16\n"), "record size:\n{wat}");
+        assert!(wat.contains("i64.store offset=8 ;; .big"));
+        assert!(wat.contains("i32.store offset=0 ;; .x"));
+    }
+
+    #[test]
+    fn test_record_update_copies_other_fields() {
+        let point = IrType::Named("Point".into());
+        let module = IrModule {
+            name: "test".to_string(),
+            version: "1.0.0".to_string(),
+            imports: vec![],
+            types: vec![point_type()],
+            functions: vec![IrFunction {
+                name: "moved".into(),
+                type_params: vec![],
+                params: vec![("p".into(), point.clone())],
+                return_type: point,
+                effects: vec![],
+                body: IrBlock {
+                    statements: vec![IrStmt::Return {
+                        value: Some(IrExpr::RecordUpdate {
+                            base: Box::new(IrExpr::Var("p".into())),
+                            fields: vec![("x".into(), IrExpr::Literal(IrLiteral::U32(2)))],
+                        }),
+                    }],
+                },
+            }],
+            exports: vec![],
+        };
+
+        let wat = generate_wasm(&module);
+        assert!(wat.contains("(local $__rec1 i32)"), "{wat}");
+        assert!(
+            wat.contains("i32.const 2\n    i32.store offset=0 ;; .x"),
+            "{wat}"
+        );
+        assert!(wat
+            .contains("local.get $__rec1\n    i64.load offset=8\n    i64.store offset=8 ;; .big"));
+        validate_wat(&wat).expect("record update should validate");
+
+        let options = WasmOptions {
+            gc: true,
+            ..WasmOptions::default()
+        };
+        let wat = generate_wasm_with_options(&module, &options);
+        assert!(wat.contains("(local $__rec0 structref)"), "{wat}");
+        assert!(wat.contains("ref.cast (ref null $Point)\n    struct.get $Point $big"));
+        assert!(wat.contains("struct.new $Point"));
+        validate_wat(&wat).expect("GC record update should validate");
+    }
+
+    #[test]
+    fn test_field_load_and_store_follow_field_type() {
+        let module = IrModule {
+            name: "test".to_string(),
+            version: "1.0.0".to_string(),
+            imports: vec![],
+            types: vec![point_type()],
+            functions: vec![IrFunction {
+                name: "bump".into(),
+                type_params: vec![],
+                params: vec![("p".into(), IrType::Named("Point".into()))],
+                return_type: IrType::U64,
+                effects: vec![],
+                body: IrBlock {
+                    statements: vec![
+                        IrStmt::Assign {
+                            target: IrExpr::Field {
+                                base: Box::new(IrExpr::Var("p".into())),
+                                field: "x".into(),
+                            },
+                            value: IrExpr::Literal(IrLiteral::U32(3)),
+                        },
+                        IrStmt::Return {
+                            value: Some(IrExpr::Field {
+                                base: Box::new(IrExpr::Var("p".into())),
+                                field: "big".into(),
+                            }),
+                        },
+                    ],
+                },
+            }],
+            exports: vec![],
+        };
+
+        let wat = generate_wasm(&module);
+        assert!(wat.contains("i32.store offset=0"));
+        assert!(wat.contains("i64.load offset=8 ;; .big"));
+        // No records are constructed; the allocator is only there for hosts
+        assert!(!wat.contains("call $alloc"));
+        assert!(wat.contains("(export \"alloc\" (func $alloc))"));
+    }
+
+    #[test]
+    fn test_names_of_generated_code_are_escaped() {
+        let func = |name: &str, params: Vec<(Ident, IrType)>, body: Vec<IrStmt>| IrFunction {
+            name: name.into(),
+            type_params: vec![],
+            params,
+            return_type: IrType::U32,
+            effects: vec![],
+            body: IrBlock { statements: body },
+        };
+        let module = IrModule {
+            name: "test".to_string(),
+            version: "1.0.0".to_string(),
+            imports: vec![],
+            types: vec![],
+            functions: vec![
+                func(
+                    "alloc",
+                    vec![("text".into(), IrType::Str)],
+                    vec![
+                        IrStmt::Let {
+                            name: "__rec0".into(),
+                            mutable: false,
+                            ty: Some(IrType::U32),
+                            value: IrExpr::Literal(IrLiteral::U32(1)),
+                        },
+                        IrStmt::Return {
+                            value: Some(IrExpr::Var("__rec0".into())),
+                        },
+                    ],
+                ),
+                func(
+                    "memory",
+                    vec![],
+                    vec![IrStmt::Return {
+                        value: Some(IrExpr::Call {
+                            func: Box::new(IrExpr::Var("alloc".into())),
+                            args: vec![IrExpr::Literal(IrLiteral::Str("x".to_string()))],
+                        }),
+                    }],
+                ),
+            ],
+            exports: vec![],
+        };
+
+        let wat = generate_wasm(&module);
+        assert!(wat.contains("(func $alloc$ (@name \"alloc\") (param $text i32) (result i32)"));
+        assert!(wat.contains("(local $__rec0$ i32)"));
+        assert!(wat.contains("call $alloc$"));
+        assert!(wat.contains("(export \"alloc$\" (func $alloc$))"));
+        assert!(wat.contains("(export \"memory$\" (func $memory))"));
+        // The allocator and memory keep their ABI names
+        assert!(wat.contains("(export \"alloc\" (func $alloc))"));
+        assert!(wat.contains("(export \"memory\" (memory $mem))"));
+
+        let binary = generate_wasm_binary(&module).unwrap();
+        validate_wasm_binary(&binary).unwrap();
+        let mut names = Vec::new();
+        for payload in wasmparser::Parser::new(0).parse_all(&binary) {
+            if let wasmparser::Payload::CustomSection(section) = payload.unwrap() {
+                if let wasmparser::KnownCustom::Name(reader) = section.as_known() {
+                    for name in reader {
+                        if let wasmparser::Name::Function(map) = name.unwrap() {
+                            for naming in map {
+                                names.push(naming.unwrap().name.to_string());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        assert!(names.contains(&"alloc".to_string()), "{names:?}");
+        assert!(names.contains(&"memory".to_string()), "{names:?}");
+    }
+
+    #[test]
+    fn test_untyped_let_infers_record_type_for_field_access() {
+        let module = IrModule {
+            name: "test".to_string(),
+            version: "1.0.0".to_string(),
+            imports: vec![],
+            types: vec![],
+            functions: vec![IrFunction {
+                name: "second".into(),
+                type_params: vec![],
+                params: vec![],
+                return_type: IrType::U32,
+                effects: vec![],
+                body: IrBlock {
+                    statements: vec![
+                        IrStmt::Let {
+                            name: "r".into(),
+                            mutable: false,
+                            ty: None,
+                            value: IrExpr::Record {
+                                fields: vec![
+                                    ("a".into(), IrExpr::Literal(IrLiteral::U32(1))),
+                                    ("b".into(), IrExpr::Literal(IrLiteral::U32(2))),
+                                ],
+                            },
+                        },
+                        IrStmt::Return {
+                            value: Some(IrExpr::Field {
+                                base: Box::new(IrExpr::Var("r".into())),
+                                field: "b".into(),
+                            }),
+                        },
+                    ],
+                },
+            }],
+            exports: vec![],
+        };
+
+        let wat = generate_wasm(&module);
+        assert!(wat.contains("i32.load offset=4 ;; .b"));
+    }
+
+    #[test]
+    fn test_identical_string_literals_share_data() {
+        let module = IrModule {
+            name: "test".to_string(),
+            version: "1.0.0".to_string(),
+            imports: vec![],
+            types: vec![],
+            functions: vec![IrFunction {
+                name: "greet".into(),
+                type_params: vec![],
+                params: vec![],
+                return_type: IrType::Str,
+                effects: vec![],
+                body: IrBlock {
+                    statements: vec![
+                        IrStmt::Let {
+                            name: "a".into(),
+                            mutable: false,
+                            ty: Some(IrType::Str),
+                            value: IrExpr::Literal(IrLiteral::Str("hi".to_string())),
+                        },
+                        IrStmt::Let {
+                            name: "b".into(),
+                            mutable: false,
+                            ty: Some(IrType::Str),
+                            value: IrExpr::Literal(IrLiteral::Str("yo\"".to_string())),
+                        },
+                        IrStmt::Return {
+                            value: Some(IrExpr::Literal(IrLiteral::Str("hi".to_string()))),
+                        },
+                    ],
+                },
+            }],
+            exports: vec![],
+        };
+
+        let wat = generate_wasm(&module);
+        assert_eq!(wat.matches("(data ").count(), 2);
+        assert_eq!(wat.matches("i32.const 1024 ;;").count(), 2);
+        // Second literal starts after the first one's header + bytes, 4-byte aligned
+        assert!(wat.contains("(data (i32.const 1032) \"\\03\\00\\00\\00yo\\22\")"));
+    }
+
+    fn single_fn_module(func: IrFunction) -> IrModule {
+        IrModule {
+            name: "test".to_string(),
+            version: "1.0.0".to_string(),
+            imports: vec![],
+            types: vec![],
+            functions: vec![func],
+            exports: vec![],
+        }
+    }
+
+    #[test]
+    fn test_negation_pushes_zero_before_operand() {
+        let module = single_fn_module(IrFunction {
+            name: "neg".into(),
+            type_params: vec![],
+            params: vec![("x".into(), IrType::U32)],
+            return_type: IrType::U32,
+            effects: vec![],
+            body: IrBlock {
+                statements: vec![IrStmt::Return {
+                    value: Some(IrExpr::UnaryOp {
+                        op: IrUnaryOp::Neg,
+                        expr: Box::new(IrExpr::Var("x".into())),
+                    }),
+                }],
+            },
+        });
+
+        let wat = generate_wasm(&module);
+        assert!(wat.contains("i32.const 0\n    local.get $x\n    i32.sub"));
+        assert!(!wat.contains("swap"));
+        validate_wat(&wat).expect("negation should validate");
+    }
+
+    #[test]
+    fn test_u64_arithmetic_uses_i64_instructions() {
+        let module = single_fn_module(IrFunction {
+            name: "inc".into(),
+            type_params: vec![],
+            params: vec![("x".into(), IrType::U64)],
+            return_type: IrType::U64,
+            effects: vec![],
+            body: IrBlock {
+                statements: vec![IrStmt::Return {
+                    value: Some(IrExpr::BinOp {
+                        op: IrBinOp::Add,
+                        left: Box::new(IrExpr::Var("x".into())),
+                        right: Box::new(IrExpr::Literal(IrLiteral::U32(1))),
+                    }),
+                }],
+            },
+        });
+
+        let wat = generate_wasm(&module);
+        assert!(wat.contains("i64.const 1"));
+        assert!(wat.contains("i64.add"));
+        validate_wat(&wat).expect("u64 arithmetic should validate");
+    }
+
+    #[test]
+    fn test_body_without_return_traps_instead_of_falling_through() {
+        let module = single_fn_module(IrFunction {
+            name: "todo".into(),
+            type_params: vec![],
+            params: vec![],
+            return_type: IrType::U32,
+            effects: vec![],
+            body: IrBlock { statements: vec![] },
+        });
+
+        let wat = generate_wasm(&module);
+        assert!(wat.contains("unreachable"));
+        validate_wat(&wat).expect("empty body should validate");
+    }
+
+    #[test]
+    fn test_unit_calls_are_not_dropped() {
+        let module = IrModule {
+            name: "test".to_string(),
+            version: "1.0.0".to_string(),
+            imports: vec![],
+            types: vec![],
+            functions: vec![
+                IrFunction {
+                    name: "noop".into(),
+                    type_params: vec![],
+                    params: vec![],
+                    return_type: IrType::Unit,
+                    effects: vec![],
+                    body: IrBlock {
+                        statements: vec![IrStmt::Return {
+                            value: Some(IrExpr::Var("Unit".into())),
+                        }],
+                    },
+                },
+                IrFunction {
+                    name: "caller".into(),
+                    type_params: vec![],
+                    params: vec![],
+                    return_type: IrType::Unit,
+                    effects: vec![],
+                    body: IrBlock {
+                        statements: vec![
+                            IrStmt::Expr(IrExpr::Call {
+                                func: Box::new(IrExpr::Var("noop".into())),
+                                args: vec![],
+                            }),
+                            IrStmt::Expr(IrExpr::Literal(IrLiteral::U32(5))),
+                        ],
+                    },
+                },
+            ],
+            exports: vec![],
+        };
+
+        let wat = generate_wasm(&module);
+        assert!(!wat.contains("local.get $Unit"));
+        assert_eq!(
+            wat.matches("drop").count(),
+            1,
+            "only the literal is dropped"
+        );
+        validate_wat(&wat).expect("unit calls should validate");
+    }
+
+    #[test]
+    fn test_imports_precede_memory_and_use_call_arity() {
+        let module = IrModule {
+            name: "test".to_string(),
+            version: "1.0.0".to_string(),
+            imports: vec![IrImport {
+                path: "std/io".to_string(),
+                alias: None,
+                items: vec!["write".into(), "flush".into()],
+                capabilities: [("write".into(), "fs".to_string())].into(),
+            }],
+            types: vec![],
+            functions: vec![IrFunction {
+                name: "main".into(),
+                type_params: vec![],
+                params: vec![],
+                return_type: IrType::Unit,
+                effects: vec![],
+                body: IrBlock {
+                    statements: vec![IrStmt::Expr(IrExpr::Call {
+                        func: Box::new(IrExpr::Var("write".into())),
+                        args: vec![
+                            IrExpr::Literal(IrLiteral::U32(1)),
+                            IrExpr::Literal(IrLiteral::Str("hi".to_string())),
+                        ],
+                    })],
+                },
+            }],
+            exports: vec![],
+        };
+
+        let wat = generate_wasm(&module);
+        let import_pos = wat.find("(import").unwrap();
+        let memory_pos = wat.find("(memory").unwrap();
+        assert!(import_pos < memory_pos);
+        assert!(wat.contains("(func $write (param i32) (param i32) (result i32))"));
+        assert!(wat.contains("(func $flush (param i32) (result i32))"));
+        assert!(wat.contains("(import \"fs\" \"write\""));
+        assert!(wat.contains("(import \"std_io\" \"flush\""));
+        validate_wat(&wat).expect("imports should validate");
+    }
+
+    #[test]
+    fn test_unknown_callee_traps() {
+        let module = single_fn_module(IrFunction {
+            name: "f".into(),
+            type_params: vec![],
+            params: vec![],
+            return_type: IrType::U32,
+            effects: vec![],
+            body: IrBlock {
+                statements: vec![IrStmt::Return {
+                    value: Some(IrExpr::Call {
+                        func: Box::new(IrExpr::Var("missing".into())),
+                        args: vec![],
+                    }),
+                }],
+            },
+        });
+
+        let wat = generate_wasm(&module);
+        assert!(!wat.contains("call $missing"));
+        validate_wat(&wat).expect("unknown call should validate");
+    }
+
+    #[test]
+    fn test_debug_info_annotates_functions() {
+        let module = IrModule {
+            name: "my cell".to_string(),
+            version: "1.0".to_string(),
+            imports: vec![],
+            types: vec![],
+            functions: vec![IrFunction {
+                name: "main".into(),
+                type_params: vec![],
+                params: vec![],
+                return_type: IrType::Unit,
+                effects: vec![],
+                body: IrBlock { statements: vec![] },
+            }],
+            exports: vec![],
+        };
+
+        let plain = generate_wasm(&module);
+        assert!(plain.contains("(module $my_cell"));
+        assert!(!plain.contains("z1.lines"));
+
+        let options = WasmOptions {
+            debug: Some(DebugInfo {
+                source_path: "cell.z1c".to_string(),
+                function_lines: [("main".to_string(), 7)].into_iter().collect(),
+            }),
+            ..WasmOptions::default()
+        };
+        let wat = generate_wasm_with_options(&module, &options);
+        assert!(wat.contains(";; cell.z1c:7\n  (func $main"));
+        assert!(wat.contains("(@custom \"z1.lines\" \"main\\09cell.z1c:7\\0a\")"));
+        validate_wat(&wat).expect("debug output should validate");
+    }
+
+    #[test]
+    fn test_gc_lowering_uses_structs_and_arrays() {
+        let point = IrType::Named("Point".into());
+        let module = IrModule {
+            name: "shapes".to_string(),
+            version: "1.0".to_string(),
+            imports: vec![],
+            types: vec![IrTypeDef {
+                name: "Point".into(),
+                type_params: vec![],
+                ty: IrType::Record(vec![
+                    ("x".into(), IrType::U32),
+                    ("label".into(), IrType::Str),
+                ]),
+            }],
+            functions: vec![
+                IrFunction {
+                    name: "make".into(),
+                    type_params: vec![],
+                    params: vec![("x".into(), IrType::U32)],
+                    return_type: point.clone(),
+                    effects: vec![],
+                    body: IrBlock {
+                        statements: vec![IrStmt::Return {
+                            value: Some(IrExpr::Record {
+                                fields: vec![
+                                    (
+                                        "label".into(),
+                                        IrExpr::Literal(IrLiteral::Str("hi".to_string())),
+                                    ),
+                                    ("x".into(), IrExpr::Var("x".into())),
+                                ],
+                            }),
+                        }],
+                    },
+                },
+                IrFunction {
+                    name: "same".into(),
+                    type_params: vec![],
+                    params: vec![("a".into(), point.clone()), ("b".into(), point)],
+                    return_type: IrType::Bool,
+                    effects: vec![],
+                    body: IrBlock {
+                        statements: vec![IrStmt::Return {
+                            value: Some(IrExpr::BinOp {
+                                op: IrBinOp::Eq,
+                                left: Box::new(IrExpr::Field {
+                                    base: Box::new(IrExpr::Var("a".into())),
+                                    field: "label".into(),
+                                }),
+                                right: Box::new(IrExpr::Field {
+                                    base: Box::new(IrExpr::Var("b".into())),
+                                    field: "label".into(),
+                                }),
+                            }),
+                        }],
+                    },
+                },
+            ],
+            exports: vec![],
+        };
+
+        let options = WasmOptions {
+            gc: true,
+            ..WasmOptions::default()
+        };
+        let wat = generate_wasm_with_options(&module, &options);
+        assert!(wat.contains("(type $str (array (mut i8)))"));
+        assert!(wat.contains(
+            "(type $Point (struct (field $x (mut i32)) (field $label (mut (ref null $str)))))"
+        ));
+        assert!(wat.contains("(result (ref null $Point))"));
+        assert!(wat.contains("array.new_data $str $str0"));
+        assert!(wat.contains("(data $str0 \"hi\")"));
+        assert!(wat.contains("struct.new $Point"));
+        assert!(wat.contains("struct.get $Point $label"));
+        assert!(wat.contains("ref.eq"));
+        // Fields are pushed in struct order, not literal order
+        let x = wat.find("local.get $x").unwrap();
+        assert!(x < wat.find("array.new_data").unwrap());
+        assert!(!wat.contains("(memory"));
+        assert!(!wat.contains("$alloc"));
+        validate_wat(&wat).expect("GC output should validate");
+    }
+
+    #[test]
+    fn test_opt_size_pools_repeated_constants() {
+        let big = || IrExpr::Literal(IrLiteral::U64(0x0123_4567_89ab_cdef));
+        let sum = |l: IrExpr, r: IrExpr| IrExpr::BinOp {
+            op: IrBinOp::Add,
+            left: Box::new(l),
+            right: Box::new(r),
+        };
+        let module = IrModule {
+            name: "consts".to_string(),
+            version: "1.0".to_string(),
+            imports: vec![],
+            types: vec![],
+            functions: vec![IrFunction {
+                name: "total".into(),
+                type_params: vec![],
+                params: vec![],
+                return_type: IrType::U64,
+                effects: vec![],
+                body: IrBlock {
+                    statements: vec![IrStmt::Return {
+                        value: Some(sum(
+                            sum(big(), big()),
+                            sum(big(), IrExpr::Literal(IrLiteral::U64(1))),
+                        )),
+                    }],
+                },
+            }],
+            exports: vec![],
+        };
+
+        let plain = generate_wasm(&module);
+        assert_eq!(plain.matches("i64.const 81985529216486895").count(), 3);
+
+        let options = WasmOptions {
+            opt_size: true,
+            ..WasmOptions::default()
+        };
+        let wat = generate_wasm_with_options(&module, &options);
+        assert!(wat.contains("(global $__k0 i64 (i64.const 81985529216486895))"));
+        assert_eq!(wat.matches("global.get $__k0").count(), 3);
+        // Short constants stay inline
+        assert!(wat.contains("i64.const 1\n"));
+        validate_wat(&wat).expect("pooled output should validate");
+    }
+
+    #[test]
+    fn test_main_is_exported_as_start() {
+        let main = |params: Vec<(Ident, IrType)>, return_type: IrType| IrModule {
+            name: "app".to_string(),
+            version: "1.0".to_string(),
+            imports: vec![],
+            types: vec![],
+            functions: vec![IrFunction {
+                name: "main".into(),
+                type_params: vec![],
+                params,
+                return_type,
+                effects: vec![],
+                body: IrBlock { statements: vec![] },
+            }],
+            exports: vec![],
+        };
+
+        let unit = generate_wasm(&main(vec![], IrType::Unit));
+        assert!(unit.contains("(export \"_start\" (func $main))"));
+        validate_wat(&unit).expect("unit entrypoint should validate");
+
+        // Results are dropped by a wrapper so `_start` is `[] -> []`
+        let valued = generate_wasm(&main(vec![], IrType::U32));
+        assert!(valued.contains("(func $_start"));
+        assert!(valued.contains("(export \"_start\" (func $_start))"));
+        validate_wat(&valued).expect("wrapped entrypoint should validate");
+
+        let with_params = main(vec![("argc".into(), IrType::U32)], IrType::Unit);
+        assert!(entrypoint(&with_params).is_none());
+        assert!(!generate_wasm(&with_params).contains("_start"));
+
+        let options = WasmOptions {
+            component_exports: true,
+            ..WasmOptions::default()
+        };
+        assert!(
+            !generate_wasm_with_options(&main(vec![], IrType::Unit), &options).contains("_start")
+        );
+    }
 }
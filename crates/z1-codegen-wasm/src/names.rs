@@ -0,0 +1,55 @@
+//! Identifiers of the WAT output.
+//!
+//! The generator emits functions and locals of its own next to those of the
+//! cell: the allocator `$alloc`, the `$_start` wrapper and the `$__rec<n>`
+//! scratch locals. A Z1 function or local by one of those names gets a `$`
+//! suffix in its WAT id (`$alloc$`), which no Z1 identifier can contain; an
+//! `(@name "alloc")` annotation keeps its Z1 name in the name section.
+//!
+//! Exports keep the Z1 name of a function, except for the names the host
+//! ABI (see [`crate::abi`]) reserves, `memory`, `alloc` and `_start`, under
+//! which a function is exported as `alloc$`.
+
+use crate::START_EXPORT;
+
+/// Function ids of code the generator emits.
+pub const RESERVED_FUNCS: &[&str] = &["alloc", "_start"];
+
+/// Export names the host ABI reserves.
+pub const RESERVED_EXPORTS: &[&str] = &["memory", "alloc", START_EXPORT];
+
+/// Prefix of the scratch locals the generator declares.
+const SCRATCH_PREFIX: &str = "__rec";
+
+/// WAT id of the function `name`, without its `$`.
+pub fn func_id(name: &str) -> String {
+    escape(name, RESERVED_FUNCS.contains(&name))
+}
+
+/// WAT id of the local `name`, without its `$`.
+pub fn local_id(name: &str) -> String {
+    escape(name, name.starts_with(SCRATCH_PREFIX))
+}
+
+/// Name the function `name` is exported under.
+pub fn export_name(name: &str) -> String {
+    escape(name, RESERVED_EXPORTS.contains(&name))
+}
+
+/// `(@name "...")` annotation restoring the Z1 name of an escaped id, with
+/// a leading space; empty when `name` needed no escaping.
+pub(crate) fn name_annotation(name: &str, id: &str) -> String {
+    if name == id {
+        String::new()
+    } else {
+        format!(" (@name \"{name}\")")
+    }
+}
+
+fn escape(name: &str, reserved: bool) -> String {
+    if reserved {
+        format!("{name}$")
+    } else {
+        name.to_string()
+    }
+}
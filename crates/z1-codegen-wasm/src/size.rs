@@ -0,0 +1,153 @@
+//! Size-focused output (`--opt-size`)
+//!
+//! - **Constant pooling**: integer constants whose encoding is long and that
+//!   are used often enough are stored once in an immutable global and read
+//!   with `global.get`.
+//! - **Custom sections**: the name section (and any other custom section) is
+//!   stripped from the binary.
+//!
+//! Function types need no extra pass: the `wat` assembler already emits a
+//! single type entry per distinct signature.
+
+use std::collections::HashMap;
+use z1_ir::*;
+
+use crate::{visit_block_exprs, WasmCodegen};
+
+/// Length of the signed LEB128 encoding of `value`.
+fn sleb_len(value: i64) -> u64 {
+    let mut value = value;
+    let mut len = 1;
+    loop {
+        let byte = value & 0x7f;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            return len;
+        }
+        len += 1;
+    }
+}
+
+/// Whether pooling a constant used `uses` times saves bytes.
+///
+/// Inline, every use costs the opcode plus the immediate. Pooled, every use
+/// is a two-byte `global.get` and the global itself costs the immediate plus
+/// about four bytes of framing.
+fn worth_pooling(value: i64, uses: u64) -> bool {
+    let imm = sleb_len(value);
+    uses * (1 + imm) > imm + 4 + uses * 2
+}
+
+/// Integer value of a literal as encoded in a `const` immediate.
+fn literal_key(lit: &IrLiteral) -> Option<i64> {
+    match lit {
+        IrLiteral::U16(n) => Some(*n as i64),
+        IrLiteral::U32(n) => Some(*n as i64),
+        IrLiteral::U64(n) => Some(*n as i64),
+        IrLiteral::Int(n) => Some(*n),
+        _ => None,
+    }
+}
+
+impl WasmCodegen {
+    /// Decide which integer constants to pool, by use count.
+    pub(crate) fn plan_const_pool(&mut self, module: &IrModule) {
+        self.const_pool.clear();
+        self.pooled_consts.clear();
+        if !self.options.opt_size {
+            return;
+        }
+
+        let mut uses: HashMap<i64, u64> = HashMap::new();
+        for func in &module.functions {
+            visit_block_exprs(&func.body, &mut |expr| {
+                if let IrExpr::Literal(lit) = expr {
+                    if let Some(key) = literal_key(lit) {
+                        *uses.entry(key).or_insert(0) += 1;
+                    }
+                }
+            });
+        }
+        self.const_pool = uses
+            .into_iter()
+            .filter(|(value, count)| worth_pooling(*value, *count))
+            .map(|(value, _)| value)
+            .collect();
+    }
+
+    /// Emit an integer constant, reading it from the pool when planned.
+    pub(crate) fn gen_int_const(&mut self, val_type: &'static str, text: String, key: i64) {
+        if !self.const_pool.contains(&key) {
+            self.write_line(&format!("{val_type}.const {text}"));
+            return;
+        }
+        let idx = match self
+            .pooled_consts
+            .iter()
+            .position(|(ty, value, _)| *ty == val_type && *value == key)
+        {
+            Some(idx) => idx,
+            None => {
+                self.pooled_consts.push((val_type, key, text));
+                self.pooled_consts.len() - 1
+            }
+        };
+        self.write_line(&format!("global.get $__k{idx}"));
+    }
+
+    /// Declare the pooled constants as immutable globals.
+    pub(crate) fn gen_const_pool(&mut self) {
+        if self.pooled_consts.is_empty() {
+            return;
+        }
+        self.write_line(";; Pooled constants");
+        let pooled = self.pooled_consts.clone();
+        for (idx, (val_type, _, text)) in pooled.iter().enumerate() {
+            self.write_line(&format!(
+                "(global $__k{idx} {val_type} ({val_type}.const {text}))"
+            ));
+        }
+        self.write_line("");
+    }
+}
+
+/// Remove every custom section (names, debug info, producers) from a binary.
+pub fn strip_custom_sections(binary: &[u8]) -> Result<Vec<u8>, String> {
+    let mut module = wasm_encoder::Module::new();
+    for payload in wasmparser::Parser::new(0).parse_all(binary) {
+        let payload = payload.map_err(|e| format!("Invalid WASM binary: {e}"))?;
+        if matches!(payload, wasmparser::Payload::CustomSection(_)) {
+            continue;
+        }
+        if let Some((id, range)) = payload.as_section() {
+            module.section(&wasm_encoder::RawSection {
+                id,
+                data: &binary[range],
+            });
+        }
+    }
+    Ok(module.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leb_lengths() {
+        assert_eq!(sleb_len(0), 1);
+        assert_eq!(sleb_len(63), 1);
+        assert_eq!(sleb_len(64), 2);
+        assert_eq!(sleb_len(-64), 1);
+        assert_eq!(sleb_len(u32::MAX as i64), 5);
+        assert_eq!(sleb_len(i64::MAX), 10);
+    }
+
+    #[test]
+    fn only_long_frequent_constants_are_pooled() {
+        assert!(!worth_pooling(1, 100));
+        assert!(!worth_pooling(1_000_000, 2));
+        assert!(worth_pooling(1_000_000, 4));
+        assert!(worth_pooling(i64::MAX, 2));
+    }
+}
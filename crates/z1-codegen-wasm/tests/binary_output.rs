@@ -1,6 +1,10 @@
 //! Tests for WASM binary output generation
 
-use z1_codegen_wasm::{generate_wasm_binary, generate_wasm_binary_optimized, validate_wasm_binary};
+use z1_codegen_wasm::{
+    generate_wasm_binary, generate_wasm_binary_optimized, generate_wasm_binary_with_debug,
+    generate_wasm_binary_with_options, generate_wasm_with_options, strip_custom_sections,
+    validate_wasm_binary, DebugInfo, WasmOptions,
+};
 use z1_ir::*;
 
 /// Helper to create a simple test IR module
@@ -11,24 +15,22 @@ fn simple_module() -> IrModule {
         imports: vec![],
         types: vec![],
         functions: vec![IrFunction {
-            name: "add".to_string(),
-            params: vec![
-                ("a".to_string(), IrType::U32),
-                ("b".to_string(), IrType::U32),
-            ],
+            name: "add".into(),
+            type_params: vec![],
+            params: vec![("a".into(), IrType::U32), ("b".into(), IrType::U32)],
             return_type: IrType::U32,
             effects: vec![],
             body: IrBlock {
                 statements: vec![IrStmt::Return {
                     value: Some(IrExpr::BinOp {
                         op: IrBinOp::Add,
-                        left: Box::new(IrExpr::Var("a".to_string())),
-                        right: Box::new(IrExpr::Var("b".to_string())),
+                        left: Box::new(IrExpr::Var("a".into())),
+                        right: Box::new(IrExpr::Var("b".into())),
                     }),
                 }],
             },
         }],
-        exports: vec!["add".to_string()],
+        exports: vec!["add".into()],
     }
 }
 
@@ -41,20 +43,21 @@ fn complex_module() -> IrModule {
         types: vec![],
         functions: vec![
             IrFunction {
-                name: "factorial".to_string(),
-                params: vec![("n".to_string(), IrType::U32)],
+                name: "factorial".into(),
+                type_params: vec![],
+                params: vec![("n".into(), IrType::U32)],
                 return_type: IrType::U32,
                 effects: vec![],
                 body: IrBlock {
                     statements: vec![
                         IrStmt::Let {
-                            name: "result".to_string(),
+                            name: "result".into(),
                             mutable: true,
                             ty: Some(IrType::U32),
                             value: IrExpr::Literal(IrLiteral::U32(1)),
                         },
                         IrStmt::Let {
-                            name: "i".to_string(),
+                            name: "i".into(),
                             mutable: true,
                             ty: Some(IrType::U32),
                             value: IrExpr::Literal(IrLiteral::U32(1)),
@@ -62,24 +65,24 @@ fn complex_module() -> IrModule {
                         IrStmt::While {
                             cond: IrExpr::BinOp {
                                 op: IrBinOp::Le,
-                                left: Box::new(IrExpr::Var("i".to_string())),
-                                right: Box::new(IrExpr::Var("n".to_string())),
+                                left: Box::new(IrExpr::Var("i".into())),
+                                right: Box::new(IrExpr::Var("n".into())),
                             },
                             body: IrBlock {
                                 statements: vec![
                                     IrStmt::Assign {
-                                        target: IrExpr::Var("result".to_string()),
+                                        target: IrExpr::Var("result".into()),
                                         value: IrExpr::BinOp {
                                             op: IrBinOp::Mul,
-                                            left: Box::new(IrExpr::Var("result".to_string())),
-                                            right: Box::new(IrExpr::Var("i".to_string())),
+                                            left: Box::new(IrExpr::Var("result".into())),
+                                            right: Box::new(IrExpr::Var("i".into())),
                                         },
                                     },
                                     IrStmt::Assign {
-                                        target: IrExpr::Var("i".to_string()),
+                                        target: IrExpr::Var("i".into()),
                                         value: IrExpr::BinOp {
                                             op: IrBinOp::Add,
-                                            left: Box::new(IrExpr::Var("i".to_string())),
+                                            left: Box::new(IrExpr::Var("i".into())),
                                             right: Box::new(IrExpr::Literal(IrLiteral::U32(1))),
                                         },
                                     },
@@ -87,28 +90,29 @@ fn complex_module() -> IrModule {
                             },
                         },
                         IrStmt::Return {
-                            value: Some(IrExpr::Var("result".to_string())),
+                            value: Some(IrExpr::Var("result".into())),
                         },
                     ],
                 },
             },
             IrFunction {
-                name: "helper".to_string(),
-                params: vec![("x".to_string(), IrType::U32)],
+                name: "helper".into(),
+                type_params: vec![],
+                params: vec![("x".into(), IrType::U32)],
                 return_type: IrType::U32,
                 effects: vec![],
                 body: IrBlock {
                     statements: vec![IrStmt::Return {
                         value: Some(IrExpr::BinOp {
                             op: IrBinOp::Add,
-                            left: Box::new(IrExpr::Var("x".to_string())),
+                            left: Box::new(IrExpr::Var("x".into())),
                             right: Box::new(IrExpr::Literal(IrLiteral::U32(10))),
                         }),
                     }],
                 },
             },
         ],
-        exports: vec!["factorial".to_string(), "helper".to_string()],
+        exports: vec!["factorial".into(), "helper".into()],
     }
 }
 
@@ -268,7 +272,8 @@ fn test_binary_with_string_literals() {
         imports: vec![],
         types: vec![],
         functions: vec![IrFunction {
-            name: "get_message".to_string(),
+            name: "get_message".into(),
+            type_params: vec![],
             params: vec![],
             return_type: IrType::Str,
             effects: vec![],
@@ -278,7 +283,7 @@ fn test_binary_with_string_literals() {
                 }],
             },
         }],
-        exports: vec!["get_message".to_string()],
+        exports: vec!["get_message".into()],
     };
 
     let binary = generate_wasm_binary(&module).expect("String literal module should work");
@@ -318,3 +323,241 @@ fn test_invalid_wat_produces_error() {
         "Valid module should generate binary successfully"
     );
 }
+
+#[test]
+fn test_binary_with_records_validates() {
+    let point = IrType::Named("Point".into());
+    let module = IrModule {
+        name: "records".to_string(),
+        version: "1.0.0".to_string(),
+        imports: vec![],
+        types: vec![IrTypeDef {
+            name: "Point".into(),
+            type_params: vec![],
+            ty: IrType::Record(vec![
+                ("x".into(), IrType::U32),
+                ("label".into(), IrType::Str),
+                ("total".into(), IrType::U64),
+            ]),
+        }],
+        functions: vec![
+            IrFunction {
+                name: "make_point".into(),
+                type_params: vec![],
+                params: vec![("x".into(), IrType::U32)],
+                return_type: point.clone(),
+                effects: vec![],
+                body: IrBlock {
+                    statements: vec![IrStmt::Return {
+                        value: Some(IrExpr::Record {
+                            fields: vec![
+                                ("x".into(), IrExpr::Var("x".into())),
+                                (
+                                    "label".into(),
+                                    IrExpr::Literal(IrLiteral::Str("origin".to_string())),
+                                ),
+                                ("total".into(), IrExpr::Literal(IrLiteral::U64(0))),
+                            ],
+                        }),
+                    }],
+                },
+            },
+            IrFunction {
+                name: "get_x".into(),
+                type_params: vec![],
+                params: vec![("p".into(), point)],
+                return_type: IrType::U32,
+                effects: vec![],
+                body: IrBlock {
+                    statements: vec![
+                        IrStmt::Assign {
+                            target: IrExpr::Field {
+                                base: Box::new(IrExpr::Var("p".into())),
+                                field: "total".into(),
+                            },
+                            value: IrExpr::Literal(IrLiteral::U64(9)),
+                        },
+                        IrStmt::Return {
+                            value: Some(IrExpr::Field {
+                                base: Box::new(IrExpr::Var("p".into())),
+                                field: "x".into(),
+                            }),
+                        },
+                    ],
+                },
+            },
+        ],
+        exports: vec!["make_point".into(), "get_x".into()],
+    };
+
+    let binary = generate_wasm_binary(&module).expect("Record module should assemble");
+    assert!(
+        validate_wasm_binary(&binary).is_ok(),
+        "Record module binary should be valid: {:?}",
+        validate_wasm_binary(&binary).err()
+    );
+
+    use wasmparser::{Parser, Payload};
+
+    let mut has_global = false;
+    let mut function_count = 0;
+    for payload in Parser::new(0).parse_all(&binary).flatten() {
+        match payload {
+            Payload::GlobalSection(_) => has_global = true,
+            Payload::FunctionSection(reader) => function_count = reader.count(),
+            _ => {}
+        }
+    }
+
+    assert!(has_global, "Allocator heap pointer should be a global");
+    // make_point, get_x and the allocator
+    assert_eq!(function_count, 3);
+}
+
+#[test]
+fn test_binary_has_name_section() {
+    use wasmparser::{KnownCustom, Name, Parser, Payload};
+
+    let binary = generate_wasm_binary(&simple_module()).unwrap();
+
+    let mut module_name = None;
+    let mut function_names = Vec::new();
+    let mut local_names = Vec::new();
+    for payload in Parser::new(0).parse_all(&binary).flatten() {
+        let Payload::CustomSection(reader) = payload else {
+            continue;
+        };
+        let KnownCustom::Name(names) = reader.as_known() else {
+            continue;
+        };
+        for name in names.into_iter().flatten() {
+            match name {
+                Name::Module { name, .. } => module_name = Some(name.to_string()),
+                Name::Function(map) => {
+                    for naming in map.into_iter().flatten() {
+                        function_names.push(naming.name.to_string());
+                    }
+                }
+                Name::Local(map) => {
+                    for indirect in map.into_iter().flatten() {
+                        for naming in indirect.names.into_iter().flatten() {
+                            local_names.push(naming.name.to_string());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    assert_eq!(module_name.as_deref(), Some("test"));
+    assert_eq!(function_names, vec!["add"]);
+    assert_eq!(local_names, vec!["a", "b"]);
+}
+
+#[test]
+fn test_debug_binary_has_dwarf_and_line_map() {
+    use std::path::Path;
+    use wasmparser::{Parser, Payload};
+
+    let options = WasmOptions {
+        debug: Some(DebugInfo {
+            source_path: "math.z1c".to_string(),
+            function_lines: [("add".to_string(), 5)].into_iter().collect(),
+        }),
+        ..WasmOptions::default()
+    };
+    let wat = generate_wasm_with_options(&simple_module(), &options);
+    let binary = generate_wasm_binary_with_debug(&wat, Path::new("math.wat")).unwrap();
+    assert!(validate_wasm_binary(&binary).is_ok());
+
+    let mut sections = Vec::new();
+    let mut line_map = None;
+    for payload in Parser::new(0).parse_all(&binary).flatten() {
+        if let Payload::CustomSection(reader) = payload {
+            if reader.name() == "z1.lines" {
+                line_map = Some(String::from_utf8(reader.data().to_vec()).unwrap());
+            }
+            sections.push(reader.name().to_string());
+        }
+    }
+
+    assert!(sections.iter().any(|s| s == ".debug_line"));
+    assert_eq!(line_map.as_deref(), Some("add\tmath.z1c:5\n"));
+}
+
+#[test]
+fn test_gc_binary_is_smaller_than_linear_memory() {
+    let module = IrModule {
+        name: "points".to_string(),
+        version: "1.0".to_string(),
+        imports: vec![],
+        types: vec![IrTypeDef {
+            name: "Point".into(),
+            type_params: vec![],
+            ty: IrType::Record(vec![("x".into(), IrType::U32), ("y".into(), IrType::U64)]),
+        }],
+        functions: vec![IrFunction {
+            name: "origin".into(),
+            type_params: vec![],
+            params: vec![],
+            return_type: IrType::Named("Point".into()),
+            effects: vec![],
+            body: IrBlock {
+                statements: vec![IrStmt::Return {
+                    value: Some(IrExpr::Record {
+                        fields: vec![
+                            ("x".into(), IrExpr::Literal(IrLiteral::U32(0))),
+                            ("y".into(), IrExpr::Literal(IrLiteral::U64(0))),
+                        ],
+                    }),
+                }],
+            },
+        }],
+        exports: vec!["origin".into()],
+    };
+
+    let options = WasmOptions {
+        gc: true,
+        ..WasmOptions::default()
+    };
+    let gc = generate_wasm_binary_with_options(&module, &options).unwrap();
+    assert!(
+        validate_wasm_binary(&gc).is_ok(),
+        "{:?}",
+        validate_wasm_binary(&gc).err()
+    );
+
+    let linear = generate_wasm_binary(&module).unwrap();
+    assert!(gc.len() < linear.len(), "{} >= {}", gc.len(), linear.len());
+}
+
+#[test]
+fn test_opt_size_binary_drops_custom_sections() {
+    use wasmparser::{Parser, Payload};
+
+    let options = WasmOptions {
+        opt_size: true,
+        ..WasmOptions::default()
+    };
+    let small = generate_wasm_binary_with_options(&complex_module(), &options).unwrap();
+    assert!(validate_wasm_binary(&small).is_ok());
+
+    let has_custom = Parser::new(0)
+        .parse_all(&small)
+        .flatten()
+        .any(|payload| matches!(payload, Payload::CustomSection(_)));
+    assert!(
+        !has_custom,
+        "opt-size output should have no custom sections"
+    );
+
+    let full = generate_wasm_binary(&complex_module()).unwrap();
+    assert!(
+        small.len() < full.len(),
+        "{} >= {}",
+        small.len(),
+        full.len()
+    );
+    assert_eq!(strip_custom_sections(&full).unwrap(), small);
+}
@@ -0,0 +1,108 @@
+//! Tests for WebAssembly component output
+
+use z1_codegen_wasm::component::{generate_component, generate_wit, ComponentError};
+use z1_ir::optimize::OptLevel;
+use z1_ir::*;
+
+fn function(name: &str, params: Vec<(&str, IrType)>, ret: IrType, effects: &[&str]) -> IrFunction {
+    IrFunction {
+        name: name.into(),
+        type_params: vec![],
+        params: params.into_iter().map(|(n, t)| (n.into(), t)).collect(),
+        return_type: ret,
+        effects: effects.iter().map(|&e| e.into()).collect(),
+        body: IrBlock {
+            statements: vec![IrStmt::Return {
+                value: Some(IrExpr::Var("a".into())),
+            }],
+        },
+    }
+}
+
+fn scalar_module() -> IrModule {
+    IrModule {
+        name: "math.ops".to_string(),
+        version: "1.0".to_string(),
+        imports: vec![],
+        types: vec![],
+        functions: vec![
+            function("add_one", vec![("a", IrType::U32)], IrType::U32, &["pure"]),
+            function("wide", vec![("a", IrType::U64)], IrType::U64, &["time"]),
+        ],
+        exports: vec!["add_one".into(), "wide".into()],
+    }
+}
+
+#[test]
+fn test_wit_describes_exports_and_effects() {
+    let wit = generate_wit(&scalar_module()).unwrap();
+    assert!(wit.contains("package z1:math-ops@1.0.0;"));
+    assert!(wit.contains("interface time {}"));
+    assert!(wit.contains("world ops {"));
+    assert!(wit.contains("import time;"));
+    assert!(wit.contains("export add-one: func(a: u32) -> u32;"));
+    assert!(wit.contains("/// effects: time"));
+    assert!(!wit.contains("interface pure"));
+}
+
+#[test]
+fn test_wit_declares_records_and_opaque_types() {
+    let module = IrModule {
+        name: "http.server".to_string(),
+        version: "1.0".to_string(),
+        imports: vec![],
+        types: vec![IrTypeDef {
+            name: "Health".into(),
+            type_params: vec![],
+            ty: IrType::Record(vec![
+                ("ok".into(), IrType::Bool),
+                ("msg".into(), IrType::Str),
+            ]),
+        }],
+        functions: vec![function(
+            "handler",
+            vec![("req", IrType::Named("Req".into()))],
+            IrType::Named("Health".into()),
+            &["net"],
+        )],
+        exports: vec!["handler".into()],
+    };
+
+    let wit = generate_wit(&module).unwrap();
+    assert!(wit.contains("record health {"));
+    assert!(wit.contains("msg: string,"));
+    assert!(wit.contains("resource req;"));
+    assert!(wit.contains("use types.{health, req};"));
+    assert!(wit.contains("export handler: func(req: req) -> health;"));
+
+    // WIT is valid even though the component itself cannot be encoded yet.
+    let mut resolve = wit_parser::Resolve::default();
+    resolve.push_str("cell.wit", &wit).unwrap();
+    assert!(matches!(
+        generate_component(&module, OptLevel::O0),
+        Err(ComponentError::NonScalarSignature { .. })
+    ));
+}
+
+#[test]
+fn test_component_encodes_and_validates() {
+    let bytes = generate_component(&scalar_module(), OptLevel::O1).unwrap();
+    let mut validator = wasmparser::Validator::new_with_features(wasmparser::WasmFeatures::all());
+    validator.validate_all(&bytes).unwrap();
+    assert!(wasmparser::Parser::is_component(&bytes));
+}
+
+#[test]
+fn test_component_rejects_imports() {
+    let mut module = scalar_module();
+    module.imports.push(IrImport {
+        path: "std/http".to_string(),
+        alias: None,
+        items: vec!["listen".into()],
+        capabilities: Default::default(),
+    });
+    assert_eq!(
+        generate_component(&module, OptLevel::O0),
+        Err(ComponentError::UnsupportedImport("std/http".to_string()))
+    );
+}
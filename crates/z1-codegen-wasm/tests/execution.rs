@@ -0,0 +1,632 @@
+//! Execute generated WASM in wasmtime and compare against a reference
+//! interpreter over the IR.
+//!
+//! Only pure scalar functions are covered: arguments and results are `U32`,
+//! `U64` or `Bool`, and bodies use arithmetic, comparisons, locals, `if`,
+//! `while` and calls to other functions in the module.
+
+use std::collections::HashMap;
+use wasmtime::{Engine, Instance, Module, Store, Val};
+use z1_codegen_wasm::generate_wasm_binary;
+use z1_ir::*;
+
+/// Scalar value shared by the interpreter and the WASM boundary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    I32(u32),
+    I64(u64),
+}
+
+impl Value {
+    fn truthy(self) -> bool {
+        match self {
+            Value::I32(v) => v != 0,
+            Value::I64(v) => v != 0,
+        }
+    }
+
+    fn to_val(self) -> Val {
+        match self {
+            Value::I32(v) => Val::I32(v as i32),
+            Value::I64(v) => Val::I64(v as i64),
+        }
+    }
+
+    fn from_val(val: &Val) -> Value {
+        match val {
+            Val::I32(v) => Value::I32(*v as u32),
+            Val::I64(v) => Value::I64(*v as u64),
+            other => panic!("unexpected WASM value {other:?}"),
+        }
+    }
+}
+
+/// Outcome of running a function: a value or a trap.
+type Outcome = Result<Value, String>;
+
+/// Reference interpreter with WASM integer semantics (wrapping arithmetic,
+/// unsigned division that traps on zero).
+struct Interp<'a> {
+    functions: HashMap<&'a str, &'a IrFunction>,
+    fuel: u64,
+}
+
+enum Flow {
+    Next,
+    Return(Value),
+}
+
+impl<'a> Interp<'a> {
+    fn new(module: &'a IrModule) -> Self {
+        Interp {
+            functions: module
+                .functions
+                .iter()
+                .map(|f| (f.name.as_str(), f))
+                .collect(),
+            fuel: 1_000_000,
+        }
+    }
+
+    fn call(&mut self, name: &str, args: &[Value]) -> Outcome {
+        let func = *self
+            .functions
+            .get(name)
+            .ok_or_else(|| format!("unknown function {name}"))?;
+        let mut locals: HashMap<Ident, Value> = func
+            .params
+            .iter()
+            .map(|(n, _)| *n)
+            .zip(args.iter().copied())
+            .collect();
+        match self.block(&func.body, &mut locals)? {
+            Flow::Return(value) => Ok(value),
+            Flow::Next => Err("unreachable".to_string()),
+        }
+    }
+
+    fn block(
+        &mut self,
+        block: &IrBlock,
+        locals: &mut HashMap<Ident, Value>,
+    ) -> Result<Flow, String> {
+        for stmt in &block.statements {
+            self.fuel = self.fuel.checked_sub(1).ok_or("out of fuel")?;
+            match stmt {
+                IrStmt::Let {
+                    name, ty, value, ..
+                } => {
+                    let wide = matches!(ty, Some(IrType::U64));
+                    let value = self.expr(value, locals, wide)?;
+                    locals.insert(*name, value);
+                }
+                IrStmt::Assign {
+                    target: IrExpr::Var(name),
+                    value,
+                } => {
+                    let wide = matches!(locals.get(name), Some(Value::I64(_)));
+                    let value = self.expr(value, locals, wide)?;
+                    locals.insert(*name, value);
+                }
+                IrStmt::If {
+                    cond,
+                    then_block,
+                    else_block,
+                } => {
+                    let flow = if self.expr(cond, locals, false)?.truthy() {
+                        self.block(then_block, locals)?
+                    } else if let Some(else_block) = else_block {
+                        self.block(else_block, locals)?
+                    } else {
+                        Flow::Next
+                    };
+                    if let Flow::Return(value) = flow {
+                        return Ok(Flow::Return(value));
+                    }
+                }
+                IrStmt::While { cond, body } => {
+                    while self.expr(cond, locals, false)?.truthy() {
+                        if let Flow::Return(value) = self.block(body, locals)? {
+                            return Ok(Flow::Return(value));
+                        }
+                        self.fuel = self.fuel.checked_sub(1).ok_or("out of fuel")?;
+                    }
+                }
+                IrStmt::Return { value: Some(value) } => {
+                    return Ok(Flow::Return(self.expr(value, locals, false)?));
+                }
+                other => return Err(format!("unsupported statement {other:?}")),
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    fn expr(&mut self, expr: &IrExpr, locals: &HashMap<Ident, Value>, wide: bool) -> Outcome {
+        match expr {
+            IrExpr::Var(name) => locals
+                .get(name)
+                .copied()
+                .ok_or_else(|| format!("unbound {name}")),
+            IrExpr::Literal(lit) => Ok(match lit {
+                IrLiteral::Bool(b) => Value::I32(*b as u32),
+                IrLiteral::U16(n) if wide => Value::I64(*n as u64),
+                IrLiteral::U32(n) if wide => Value::I64(*n as u64),
+                IrLiteral::U16(n) => Value::I32(*n as u32),
+                IrLiteral::U32(n) => Value::I32(*n),
+                IrLiteral::U64(n) => Value::I64(*n),
+                other => return Err(format!("unsupported literal {other:?}")),
+            }),
+            IrExpr::UnaryOp { op, expr } => {
+                let value = self.expr(expr, locals, wide)?;
+                Ok(match (op, value) {
+                    (IrUnaryOp::Not, v) => Value::I32(!v.truthy() as u32),
+                    (IrUnaryOp::Neg, Value::I32(v)) => Value::I32(v.wrapping_neg()),
+                    (IrUnaryOp::Neg, Value::I64(v)) => Value::I64(v.wrapping_neg()),
+                    (op, _) => return Err(format!("unsupported unary {op:?}")),
+                })
+            }
+            IrExpr::BinOp { op, left, right } => {
+                // Literals adopt the width of the other operand
+                let operand_wide =
+                    wide || self.is_wide(left, locals) || self.is_wide(right, locals);
+                let l = self.expr(left, locals, operand_wide)?;
+                let r = self.expr(right, locals, operand_wide)?;
+                match (l, r) {
+                    (Value::I32(a), Value::I32(b)) => {
+                        binop(op, a as u64, b as u64, u32::MAX as u64).map(|v| Value::I32(v as u32))
+                    }
+                    (Value::I64(a), Value::I64(b)) => binop(op, a, b, u64::MAX).map(|v| {
+                        if is_comparison(op) {
+                            Value::I32(v as u32)
+                        } else {
+                            Value::I64(v)
+                        }
+                    }),
+                    _ => Err("mixed operand widths".to_string()),
+                }
+            }
+            IrExpr::Call { func, args } => {
+                let IrExpr::Var(name) = func.as_ref() else {
+                    return Err("indirect call".to_string());
+                };
+                let callee = *self.functions.get(name.as_str()).ok_or("unknown callee")?;
+                let args = args
+                    .iter()
+                    .zip(&callee.params)
+                    .map(|(arg, (_, ty))| self.expr(arg, locals, *ty == IrType::U64))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.call(name, &args)
+            }
+            other => Err(format!("unsupported expression {other:?}")),
+        }
+    }
+
+    fn is_wide(&self, expr: &IrExpr, locals: &HashMap<Ident, Value>) -> bool {
+        match expr {
+            IrExpr::Var(name) => matches!(locals.get(name), Some(Value::I64(_))),
+            IrExpr::Literal(IrLiteral::U64(_)) => true,
+            IrExpr::BinOp { op, left, .. } if !is_comparison(op) => self.is_wide(left, locals),
+            IrExpr::UnaryOp { expr, .. } => self.is_wide(expr, locals),
+            IrExpr::Call { func, .. } => match func.as_ref() {
+                IrExpr::Var(name) => self
+                    .functions
+                    .get(name.as_str())
+                    .is_some_and(|f| f.return_type == IrType::U64),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+fn is_comparison(op: &IrBinOp) -> bool {
+    matches!(
+        op,
+        IrBinOp::Eq | IrBinOp::Ne | IrBinOp::Lt | IrBinOp::Le | IrBinOp::Gt | IrBinOp::Ge
+    )
+}
+
+/// Apply `op` to unsigned operands, wrapping at `mask`.
+fn binop(op: &IrBinOp, a: u64, b: u64, mask: u64) -> Result<u64, String> {
+    Ok(match op {
+        IrBinOp::Add => a.wrapping_add(b) & mask,
+        IrBinOp::Sub => a.wrapping_sub(b) & mask,
+        IrBinOp::Mul => a.wrapping_mul(b) & mask,
+        IrBinOp::Div if b == 0 => return Err("integer divide by zero".to_string()),
+        IrBinOp::Mod if b == 0 => return Err("integer divide by zero".to_string()),
+        IrBinOp::Div => a / b,
+        IrBinOp::Mod => a % b,
+        IrBinOp::Eq => (a == b) as u64,
+        IrBinOp::Ne => (a != b) as u64,
+        IrBinOp::Lt => (a < b) as u64,
+        IrBinOp::Le => (a <= b) as u64,
+        IrBinOp::Gt => (a > b) as u64,
+        IrBinOp::Ge => (a >= b) as u64,
+        IrBinOp::And => a & b,
+        IrBinOp::Or => a | b,
+    })
+}
+
+/// Run `name` in wasmtime, mapping traps to `Err`.
+fn run_wasm(store: &mut Store<()>, instance: &Instance, name: &str, args: &[Value]) -> Outcome {
+    let func = instance
+        .get_func(&mut *store, name)
+        .unwrap_or_else(|| panic!("missing export {name}"));
+    let params: Vec<Val> = args.iter().map(|a| a.to_val()).collect();
+    let mut results = vec![Val::I32(0)];
+    match func.call(&mut *store, &params, &mut results) {
+        Ok(()) => Ok(Value::from_val(&results[0])),
+        Err(e) => Err(e.root_cause().to_string()),
+    }
+}
+
+/// Deterministic input generator (xorshift) biased towards edge cases.
+struct Inputs(u64);
+
+impl Inputs {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn value(&mut self, ty: &IrType) -> Value {
+        let raw = match self.next() % 4 {
+            0 => 0,
+            1 => 1,
+            2 => self.next() % 50,
+            _ => self.next(),
+        };
+        match ty {
+            IrType::U64 => Value::I64(raw),
+            IrType::Bool => Value::I32((raw & 1) as u32),
+            IrType::U16 => Value::I32(raw as u16 as u32),
+            _ => Value::I32(raw as u32),
+        }
+    }
+}
+
+/// Compile `module`, then check every function agrees with the interpreter
+/// on a batch of generated inputs.
+fn assert_matches_interpreter(module: &IrModule) {
+    let binary = generate_wasm_binary(module).expect("module should assemble");
+    let engine = Engine::default();
+    let wasm_module = Module::new(&engine, &binary).expect("wasmtime should accept module");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &wasm_module, &[]).expect("instantiate");
+
+    let mut inputs = Inputs(0x5eed_1234_abcd_ef01);
+    for func in &module.functions {
+        for _ in 0..64 {
+            let args: Vec<Value> = func.params.iter().map(|(_, ty)| inputs.value(ty)).collect();
+            let expected = Interp::new(module).call(&func.name, &args);
+            let actual = run_wasm(&mut store, &instance, &func.name, &args);
+            match (&expected, &actual) {
+                (Ok(e), Ok(a)) => assert_eq!(e, a, "{}({args:?})", func.name),
+                (Err(_), Err(_)) => {}
+                _ => panic!(
+                    "{}({args:?}): interpreter {expected:?}, wasm {actual:?}",
+                    func.name
+                ),
+            }
+        }
+    }
+}
+
+fn var(name: &str) -> Box<IrExpr> {
+    Box::new(IrExpr::Var(name.into()))
+}
+
+fn u32_lit(n: u32) -> Box<IrExpr> {
+    Box::new(IrExpr::Literal(IrLiteral::U32(n)))
+}
+
+fn bin(op: IrBinOp, left: Box<IrExpr>, right: Box<IrExpr>) -> IrExpr {
+    IrExpr::BinOp { op, left, right }
+}
+
+fn ret(value: IrExpr) -> IrStmt {
+    IrStmt::Return { value: Some(value) }
+}
+
+fn function(
+    name: &str,
+    params: &[(&str, IrType)],
+    ret: IrType,
+    statements: Vec<IrStmt>,
+) -> IrFunction {
+    IrFunction {
+        name: name.into(),
+        type_params: vec![],
+        params: params
+            .iter()
+            .map(|(n, t)| ((*n).into(), t.clone()))
+            .collect(),
+        return_type: ret,
+        effects: vec!["pure".into()],
+        body: IrBlock { statements },
+    }
+}
+
+fn module(functions: Vec<IrFunction>) -> IrModule {
+    IrModule {
+        name: "exec".to_string(),
+        version: "1.0".to_string(),
+        imports: vec![],
+        types: vec![],
+        exports: functions.iter().map(|f| f.name).collect(),
+        functions,
+    }
+}
+
+#[test]
+fn test_arithmetic_matches_interpreter() {
+    let m = module(vec![
+        function(
+            "poly",
+            &[("a", IrType::U32), ("b", IrType::U32)],
+            IrType::U32,
+            vec![ret(bin(
+                IrBinOp::Sub,
+                Box::new(bin(IrBinOp::Mul, var("a"), var("a"))),
+                Box::new(bin(IrBinOp::Add, var("b"), u32_lit(7))),
+            ))],
+        ),
+        function(
+            "divmod",
+            &[("a", IrType::U32), ("b", IrType::U32)],
+            IrType::U32,
+            vec![ret(bin(
+                IrBinOp::Add,
+                Box::new(bin(IrBinOp::Div, var("a"), var("b"))),
+                Box::new(bin(IrBinOp::Mod, var("a"), var("b"))),
+            ))],
+        ),
+        function(
+            "neg",
+            &[("a", IrType::U32)],
+            IrType::U32,
+            vec![ret(IrExpr::UnaryOp {
+                op: IrUnaryOp::Neg,
+                expr: var("a"),
+            })],
+        ),
+        function(
+            "wide",
+            &[("a", IrType::U64), ("b", IrType::U64)],
+            IrType::U64,
+            vec![ret(bin(
+                IrBinOp::Add,
+                Box::new(bin(IrBinOp::Mul, var("a"), u32_lit(3))),
+                var("b"),
+            ))],
+        ),
+    ]);
+    assert_matches_interpreter(&m);
+}
+
+#[test]
+fn test_control_flow_matches_interpreter() {
+    let m = module(vec![
+        function(
+            "max",
+            &[("a", IrType::U32), ("b", IrType::U32)],
+            IrType::U32,
+            vec![
+                IrStmt::If {
+                    cond: bin(IrBinOp::Gt, var("a"), var("b")),
+                    then_block: IrBlock {
+                        statements: vec![ret(IrExpr::Var("a".into()))],
+                    },
+                    else_block: None,
+                },
+                ret(IrExpr::Var("b".into())),
+            ],
+        ),
+        function(
+            "gcd",
+            &[("a", IrType::U32), ("b", IrType::U32)],
+            IrType::U32,
+            vec![
+                IrStmt::Let {
+                    name: "x".into(),
+                    mutable: true,
+                    ty: Some(IrType::U32),
+                    value: IrExpr::Var("a".into()),
+                },
+                IrStmt::Let {
+                    name: "y".into(),
+                    mutable: true,
+                    ty: Some(IrType::U32),
+                    value: IrExpr::Var("b".into()),
+                },
+                IrStmt::While {
+                    cond: bin(IrBinOp::Ne, var("y"), u32_lit(0)),
+                    body: IrBlock {
+                        statements: vec![
+                            IrStmt::Let {
+                                name: "t".into(),
+                                mutable: false,
+                                ty: Some(IrType::U32),
+                                value: bin(IrBinOp::Mod, var("x"), var("y")),
+                            },
+                            IrStmt::Assign {
+                                target: IrExpr::Var("x".into()),
+                                value: IrExpr::Var("y".into()),
+                            },
+                            IrStmt::Assign {
+                                target: IrExpr::Var("y".into()),
+                                value: IrExpr::Var("t".into()),
+                            },
+                        ],
+                    },
+                },
+                ret(IrExpr::Var("x".into())),
+            ],
+        ),
+        function(
+            "is_even",
+            &[("n", IrType::U32)],
+            IrType::Bool,
+            vec![ret(bin(
+                IrBinOp::Eq,
+                Box::new(bin(IrBinOp::Mod, var("n"), u32_lit(2))),
+                u32_lit(0),
+            ))],
+        ),
+    ]);
+    assert_matches_interpreter(&m);
+}
+
+#[test]
+fn test_recursion_matches_interpreter() {
+    // fact(n) = if n <= 1 { 1 } else { n * fact(n - 1) }, on small n
+    let m = module(vec![
+        function(
+            "fact",
+            &[("n", IrType::U32)],
+            IrType::U32,
+            vec![
+                IrStmt::If {
+                    cond: bin(IrBinOp::Le, var("n"), u32_lit(1)),
+                    then_block: IrBlock {
+                        statements: vec![ret(IrExpr::Literal(IrLiteral::U32(1)))],
+                    },
+                    else_block: None,
+                },
+                ret(bin(
+                    IrBinOp::Mul,
+                    var("n"),
+                    Box::new(IrExpr::Call {
+                        func: var("fact"),
+                        args: vec![bin(IrBinOp::Sub, var("n"), u32_lit(1))],
+                    }),
+                )),
+            ],
+        ),
+        function(
+            "fact_small",
+            &[("n", IrType::U32)],
+            IrType::U32,
+            vec![ret(IrExpr::Call {
+                func: var("fact"),
+                args: vec![bin(IrBinOp::Mod, var("n"), u32_lit(20))],
+            })],
+        ),
+    ]);
+
+    let binary = generate_wasm_binary(&m).unwrap();
+    let engine = Engine::default();
+    let wasm_module = Module::new(&engine, &binary).unwrap();
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &wasm_module, &[]).unwrap();
+    for n in 0..40 {
+        let args = [Value::I32(n)];
+        let expected = Interp::new(&m).call("fact_small", &args);
+        let actual = run_wasm(&mut store, &instance, "fact_small", &args);
+        assert_eq!(expected, actual, "fact_small({n})");
+    }
+}
+
+#[test]
+fn test_division_by_zero_traps_in_both() {
+    let m = module(vec![function(
+        "div",
+        &[("a", IrType::U32), ("b", IrType::U32)],
+        IrType::U32,
+        vec![ret(bin(IrBinOp::Div, var("a"), var("b")))],
+    )]);
+
+    let binary = generate_wasm_binary(&m).unwrap();
+    let engine = Engine::default();
+    let wasm_module = Module::new(&engine, &binary).unwrap();
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &wasm_module, &[]).unwrap();
+    let args = [Value::I32(1), Value::I32(0)];
+    assert!(Interp::new(&m).call("div", &args).is_err());
+    assert!(run_wasm(&mut store, &instance, "div", &args).is_err());
+}
+
+#[test]
+fn test_start_export_runs_main() {
+    let run_start = |m: &IrModule| {
+        let binary = generate_wasm_binary(m).unwrap();
+        let engine = Engine::default();
+        let wasm_module = Module::new(&engine, &binary).unwrap();
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &wasm_module, &[]).unwrap();
+        let start = instance
+            .get_typed_func::<(), ()>(&mut store, "_start")
+            .expect("_start should be a [] -> [] export");
+        start.call(&mut store, ())
+    };
+
+    let ok = module(vec![function(
+        "main",
+        &[],
+        IrType::U32,
+        vec![ret(bin(IrBinOp::Add, u32_lit(1), u32_lit(2)))],
+    )]);
+    assert!(run_start(&ok).is_ok());
+
+    // A trap inside `main` surfaces through `_start`
+    let trapping = module(vec![function(
+        "main",
+        &[],
+        IrType::U32,
+        vec![ret(bin(IrBinOp::Div, u32_lit(1), u32_lit(0)))],
+    )]);
+    assert!(run_start(&trapping).is_err());
+}
+
+#[test]
+fn test_generic_functions_are_instantiated_per_type() {
+    // f twice[T](x: T)->T { ret x + x; }, called with a U64 and a U32
+    let mut twice = function(
+        "twice",
+        &[("x", IrType::Named("T".into()))],
+        IrType::Named("T".into()),
+        vec![ret(bin(IrBinOp::Add, var("x"), var("x")))],
+    );
+    twice.type_params = vec!["T".into()];
+    let call = |arg: &str| IrExpr::Call {
+        func: var("twice"),
+        args: vec![IrExpr::Var(arg.into())],
+    };
+    let m = module(vec![
+        twice,
+        function(
+            "big",
+            &[("n", IrType::U64)],
+            IrType::U64,
+            vec![ret(call("n"))],
+        ),
+        function(
+            "small",
+            &[("n", IrType::U32)],
+            IrType::U32,
+            vec![ret(call("n"))],
+        ),
+    ]);
+
+    let binary = generate_wasm_binary(&m).expect("module should assemble");
+    let engine = Engine::default();
+    let wasm_module = Module::new(&engine, &binary).unwrap();
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &wasm_module, &[]).unwrap();
+    let big = 1u64 << 40;
+    assert_eq!(
+        run_wasm(&mut store, &instance, "big", &[Value::I64(big)]),
+        Ok(Value::I64(big * 2))
+    );
+    assert_eq!(
+        run_wasm(&mut store, &instance, "twice$U64", &[Value::I64(big)]),
+        Ok(Value::I64(big * 2))
+    );
+    assert_eq!(
+        run_wasm(&mut store, &instance, "small", &[Value::I32(u32::MAX)]),
+        Ok(Value::I32(u32::MAX - 1))
+    );
+    assert!(instance.get_func(&mut store, "twice").is_none());
+}
@@ -0,0 +1,92 @@
+//! Golden-file suite for the WASM backend.
+//!
+//! Every fixture cell is compiled to WAT and compared with the checked-in
+//! golden file, then assembled with `wat` and validated with `wasmparser`.
+//! Run with `Z1_UPDATE_GOLDEN=1` to rewrite the golden files after an
+//! intentional codegen change.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use z1_codegen_wasm::{generate_wasm, validate_wasm_binary};
+
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../..")
+}
+
+/// Fixture cells covered by the suite, sorted for stable output.
+fn fixture_cells() -> Vec<PathBuf> {
+    let root = workspace_root();
+    let mut cells = Vec::new();
+    for dir in ["fixtures/cells", "fixtures/wasm"] {
+        for entry in fs::read_dir(root.join(dir)).expect("fixture dir should exist") {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|ext| ext == "z1c") {
+                cells.push(path);
+            }
+        }
+    }
+    cells.sort();
+    cells
+}
+
+fn golden_path(cell: &Path) -> PathBuf {
+    let stem = cell.file_stem().unwrap().to_string_lossy();
+    workspace_root()
+        .join("fixtures/wasm/golden")
+        .join(format!("{stem}.wat"))
+}
+
+fn compile_cell(cell: &Path) -> String {
+    let source = fs::read_to_string(cell).unwrap();
+    let module = z1_parse::parse_module(&source)
+        .unwrap_or_else(|e| panic!("{} should parse: {e:?}", cell.display()));
+    let ir = z1_ir::lower_to_ir(&module)
+        .unwrap_or_else(|e| panic!("{} should lower: {e:?}", cell.display()));
+    generate_wasm(&ir)
+}
+
+#[test]
+fn test_fixture_cells_match_golden_wat() {
+    let update = std::env::var_os("Z1_UPDATE_GOLDEN").is_some();
+    let mut mismatches = Vec::new();
+
+    for cell in fixture_cells() {
+        let wat = compile_cell(&cell);
+        let golden = golden_path(&cell);
+        if update {
+            fs::write(&golden, &wat).unwrap();
+            continue;
+        }
+        match fs::read_to_string(&golden) {
+            Ok(expected) if expected == wat => {}
+            Ok(_) => mismatches.push(format!("{} differs", golden.display())),
+            Err(_) => mismatches.push(format!("{} is missing", golden.display())),
+        }
+    }
+
+    assert!(
+        mismatches.is_empty(),
+        "golden WAT out of date (rerun with Z1_UPDATE_GOLDEN=1):\n{}",
+        mismatches.join("\n")
+    );
+}
+
+#[test]
+fn test_fixture_cells_round_trip_to_valid_wasm() {
+    for cell in fixture_cells() {
+        let wat = compile_cell(&cell);
+        let binary = wat::parse_str(&wat)
+            .unwrap_or_else(|e| panic!("{} WAT should assemble: {e}", cell.display()));
+        validate_wasm_binary(&binary)
+            .unwrap_or_else(|e| panic!("{} should validate: {e}", cell.display()));
+
+        // Printing the binary back to text and reassembling is lossless
+        let printed = wasmprinter::print_bytes(&binary).unwrap();
+        assert_eq!(
+            wat::parse_str(&printed).unwrap(),
+            binary,
+            "{} did not round-trip",
+            cell.display()
+        );
+    }
+}
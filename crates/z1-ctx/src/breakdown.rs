@@ -0,0 +1,181 @@
+//! Attribution of a cell's compact text to constructs and identifiers.
+//!
+//! The estimate of a cell is taken from its compact form; this module splits
+//! that form by the construct each character belongs to (header, imports,
+//! symbol maps, type declarations, function signatures and bodies, inline
+//! tests) and
+//! counts the characters each identifier takes, so the largest consumers of
+//! a budget can be found.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use z1_ast::{Item, Module};
+use z1_fmt::{format_module, FmtOptions, Mode};
+use z1_lex::{lex, TokenKind};
+
+use crate::{size, CtxError};
+
+/// Kind of construct that characters of a cell are attributed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Construct {
+    /// Module header, and the blank lines between sections
+    Header,
+    Imports,
+    SymbolMaps,
+    Types,
+    /// Function names, parameters, return types and effects
+    Signatures,
+    Bodies,
+    /// Inline `#test` blocks
+    Tests,
+}
+
+impl Construct {
+    pub const ALL: [Construct; 7] = [
+        Construct::Header,
+        Construct::Imports,
+        Construct::SymbolMaps,
+        Construct::Types,
+        Construct::Signatures,
+        Construct::Bodies,
+        Construct::Tests,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Construct::Header => "header",
+            Construct::Imports => "imports",
+            Construct::SymbolMaps => "symbol-maps",
+            Construct::Types => "types",
+            Construct::Signatures => "signatures",
+            Construct::Bodies => "bodies",
+            Construct::Tests => "tests",
+        }
+    }
+}
+
+impl fmt::Display for Construct {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Characters an identifier takes in the compact form of a cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentCost {
+    pub name: String,
+    pub occurrences: usize,
+    pub chars: usize,
+}
+
+/// Compact text of a cell, split by construct and by identifier.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakdown {
+    /// Characters per construct, in [`Construct::ALL`] order; they add up to
+    /// `char_count`
+    pub constructs: Vec<(Construct, usize)>,
+    /// Identifiers by the characters they take, most expensive first
+    pub identifiers: Vec<IdentCost>,
+    /// Character count of compact representation
+    pub char_count: usize,
+}
+
+/// Splits the compact form of `module` by construct and identifier.
+///
+/// Each item is formatted on its own, under the module's symbol maps, and
+/// charged the characters it adds to the header. Function bodies are
+/// printed verbatim, so their share of a function is their raw length.
+pub fn breakdown(module: &Module) -> Result<Breakdown, CtxError> {
+    let compact = |items: Vec<Item>| -> Result<usize, CtxError> {
+        let cell = Module {
+            items,
+            ..module.clone()
+        };
+        Ok(format_module(&cell, Mode::Compact, &FmtOptions::default())?.len())
+    };
+    let text = format_module(module, Mode::Compact, &FmtOptions::default())?;
+    let symbol_maps: Vec<Item> = module
+        .items
+        .iter()
+        .filter(|item| matches!(item, Item::Symbol(_)))
+        .cloned()
+        .collect();
+    let header = compact(Vec::new())?;
+    let base = compact(symbol_maps.clone())?;
+
+    let mut chars: HashMap<Construct, usize> = HashMap::new();
+    chars.insert(Construct::SymbolMaps, base - header);
+    for item in &module.items {
+        if matches!(item, Item::Symbol(_)) {
+            continue;
+        }
+        let mut items = symbol_maps.clone();
+        items.push(item.clone());
+        let added = compact(items)?.saturating_sub(base);
+        match item {
+            Item::Import(_) => *chars.entry(Construct::Imports).or_default() += added,
+            Item::Type(_) => *chars.entry(Construct::Types).or_default() += added,
+            Item::Fn(decl) => {
+                // A space before the body and the newline after it
+                let body = (size::block_chars(&decl.body) + 2).min(added);
+                *chars.entry(Construct::Bodies).or_default() += body;
+                *chars.entry(Construct::Signatures).or_default() += added - body;
+            }
+            Item::Test(_) => *chars.entry(Construct::Tests).or_default() += added,
+            Item::Symbol(_) => {}
+        }
+    }
+    let attributed: usize = chars.values().sum();
+    chars.insert(Construct::Header, text.len().saturating_sub(attributed));
+
+    Ok(Breakdown {
+        constructs: Construct::ALL
+            .iter()
+            .map(|construct| (*construct, chars.get(construct).copied().unwrap_or(0)))
+            .collect(),
+        identifiers: identifier_costs(&text),
+        char_count: text.len(),
+    })
+}
+
+/// Identifiers of `text` with their occurrences and characters; qualified
+/// names count once per segment.
+fn identifier_costs(text: &str) -> Vec<IdentCost> {
+    let mut costs: HashMap<&str, IdentCost> = HashMap::new();
+    let tokens = lex(text);
+    for token in tokens.iter().filter(|token| token.kind == TokenKind::Ident) {
+        for segment in token
+            .lexeme
+            .split('.')
+            .filter(|segment| !segment.is_empty())
+        {
+            let cost = costs.entry(segment).or_insert_with(|| IdentCost {
+                name: segment.to_string(),
+                occurrences: 0,
+                chars: 0,
+            });
+            cost.occurrences += 1;
+            cost.chars += segment.len();
+        }
+    }
+    let mut costs: Vec<IdentCost> = costs.into_values().collect();
+    costs.sort_by(|a, b| b.chars.cmp(&a.chars).then_with(|| a.name.cmp(&b.name)));
+    costs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_identifier_segments() {
+        let costs = identifier_costs("f go(req: H.Req)->H.Res { ret H.ok(req); }");
+        let find = |name: &str| costs.iter().find(|cost| cost.name == name).unwrap();
+        assert_eq!(find("H").occurrences, 3);
+        assert_eq!(find("req").chars, 6);
+        assert!(costs
+            .iter()
+            .all(|cost| cost.name != "ret" && cost.name != "f"));
+    }
+}
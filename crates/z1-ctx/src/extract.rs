@@ -0,0 +1,635 @@
+//! Context bundles: what editing one function needs, within a budget.
+//!
+//! The bundle of a function is the smallest cell-shaped text an agent needs
+//! to change it without reading the rest of the workspace: the cell header,
+//! the function itself, the types it mentions, the signatures of the
+//! functions it calls, and the declarations it uses from imported cells.
+//! Other functions appear as signatures only, their bodies elided as
+//! `{ … }`.
+//!
+//! Declarations are taken nearest first: what the function mentions, then
+//! what those mention, and so on; within one step, types of the cell come
+//! before its functions and both before imported declarations. Under a
+//! budget, a declaration that does not fit is left out, along with what only
+//! it would have pulled in, and the rest are still tried.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+use thiserror::Error;
+use z1_ast::{
+    Block, ElseBlock, Expr, FnDecl, Ident, Item, Module, Stmt, Symbol, SymbolMap, TypeExpr,
+};
+use z1_fmt::{format_module, FmtError, FmtOptions, Mode};
+
+use crate::{estimate_tokens_from_chars, DEFAULT_CHARS_PER_TOKEN};
+
+/// Body printed for functions other than the one the bundle is for.
+const ELIDED_BODY: &str = "{ … }";
+
+/// Errors that can occur while extracting a context bundle.
+#[derive(Debug, Error)]
+pub enum ExtractError {
+    #[error("the cell declares no function '{0}'")]
+    UnknownFunction(String),
+
+    #[error("function '{name}' alone takes {actual} tokens, over the budget of {budget}")]
+    OverBudget {
+        name: String,
+        actual: u32,
+        budget: u32,
+    },
+
+    #[error(transparent)]
+    Format(#[from] FmtError),
+}
+
+/// Configuration for [`extract_context`].
+#[derive(Debug, Clone)]
+pub struct ExtractOptions {
+    /// Tokens the bundle may take; `None` bundles everything reachable
+    pub budget: Option<u32>,
+    /// Characters per token ratio (default: 3.8)
+    pub chars_per_token: f64,
+    /// Mode the bundle is printed in
+    pub mode: Mode,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self {
+            budget: None,
+            chars_per_token: DEFAULT_CHARS_PER_TOKEN,
+            mode: Mode::Compact,
+        }
+    }
+}
+
+/// Kind of a declaration in a bundle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ContextKind {
+    Type,
+    /// A function, as its signature
+    Signature,
+}
+
+impl ContextKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ContextKind::Type => "type",
+            ContextKind::Signature => "signature",
+        }
+    }
+}
+
+impl fmt::Display for ContextKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A declaration the function depends on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextItem {
+    pub kind: ContextKind,
+    pub name: String,
+    /// Import path of the cell declaring it; `None` for the cell itself
+    pub import: Option<String>,
+}
+
+/// Outcome of [`extract_context`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextBundle {
+    /// The bundle: interfaces of imported cells, then the cell
+    pub source: String,
+    pub tokens: u32,
+    /// Declarations bundled with the function, nearest first
+    pub items: Vec<ContextItem>,
+    /// Declarations the function depends on that did not fit the budget
+    pub omitted: Vec<ContextItem>,
+}
+
+/// Extract the context bundle of `function` from `module`, whose imports
+/// are given by import path; imports missing from `imports` are left out.
+pub fn extract_context(
+    module: &Module,
+    function: &str,
+    imports: &BTreeMap<String, Module>,
+    options: &ExtractOptions,
+) -> Result<ContextBundle, ExtractError> {
+    let target = module
+        .items
+        .iter()
+        .position(|item| matches!(item, Item::Fn(decl) if decl.name == function))
+        .ok_or_else(|| ExtractError::UnknownFunction(function.to_string()))?;
+
+    let mut cells = vec![Cell::new(None, module)];
+    for item in &module.items {
+        if let Item::Import(import) = item {
+            if let Some(imported) = imports.get(&import.path) {
+                if cells.iter().all(|cell| cell.import != Some(&import.path)) {
+                    cells.push(Cell::new(Some(&import.path), imported));
+                }
+            }
+        }
+    }
+    let mut bundle = Bundle {
+        cells,
+        options,
+        chosen: BTreeSet::from([(0, target)]),
+        target,
+    };
+    let tokens = bundle.tokens()?;
+    if let Some(budget) = options.budget.filter(|&budget| tokens > budget) {
+        return Err(ExtractError::OverBudget {
+            name: function.to_string(),
+            actual: tokens,
+            budget,
+        });
+    }
+
+    let mut items = Vec::new();
+    let mut omitted = Vec::new();
+    let mut seen = bundle.chosen.clone();
+    let mut frontier = vec![(0, target)];
+    while !frontier.is_empty() {
+        let mut next: Vec<(usize, usize)> = frontier
+            .iter()
+            .flat_map(|&(cell, idx)| bundle.references(cell, idx))
+            .filter(|decl| seen.insert(*decl))
+            .collect();
+        next.sort_by_key(|&(cell, idx)| (cell != 0, bundle.kind(cell, idx), cell, idx));
+        frontier.clear();
+        for decl in next {
+            bundle.chosen.insert(decl);
+            let fits = match options.budget {
+                Some(budget) => bundle.tokens()? <= budget,
+                None => true,
+            };
+            if fits {
+                items.push(bundle.item(decl));
+                frontier.push(decl);
+            } else {
+                bundle.chosen.remove(&decl);
+                omitted.push(bundle.item(decl));
+            }
+        }
+    }
+
+    let source = bundle.render()?;
+    Ok(ContextBundle {
+        tokens: estimate_tokens_from_chars(source.len(), options.chars_per_token),
+        source,
+        items,
+        omitted,
+    })
+}
+
+/// The cell a bundle is for, or one of its imports.
+struct Cell<'a> {
+    import: Option<&'a String>,
+    module: &'a Module,
+    /// Names each type and function signature mentions, by item index
+    signatures: BTreeMap<usize, BTreeSet<Ident>>,
+    /// Names each function body mentions, by item index
+    bodies: BTreeMap<usize, BTreeSet<Ident>>,
+}
+
+impl<'a> Cell<'a> {
+    fn new(import: Option<&'a String>, module: &'a Module) -> Self {
+        let symbols: HashMap<Ident, Ident> = module
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Symbol(map) => Some(map),
+                _ => None,
+            })
+            .flat_map(|map| &map.pairs)
+            .map(|pair| (pair.short, pair.long))
+            .collect();
+        // Bodies and types keep the short names of a compact cell
+        let long = |words: BTreeSet<Ident>| -> BTreeSet<Ident> {
+            words
+                .into_iter()
+                .map(|word| symbols.get(&word).copied().unwrap_or(word))
+                .collect()
+        };
+        let mut signatures = BTreeMap::new();
+        let mut bodies = BTreeMap::new();
+        for (idx, item) in module.items.iter().enumerate() {
+            let mut words = BTreeSet::new();
+            match item {
+                Item::Type(decl) => type_words(&decl.expr, &mut words),
+                Item::Fn(decl) => {
+                    for param in &decl.params {
+                        type_words(&param.ty, &mut words);
+                    }
+                    type_words(&decl.ret, &mut words);
+                    let mut body = BTreeSet::new();
+                    block_words(&decl.body, &mut body);
+                    bodies.insert(idx, long(body));
+                }
+                Item::Import(_) | Item::Symbol(_) | Item::Test(_) => continue,
+            }
+            signatures.insert(idx, long(words));
+        }
+        Cell {
+            import,
+            module,
+            signatures,
+            bodies,
+        }
+    }
+
+    fn name(&self, idx: usize) -> Ident {
+        match &self.module.items[idx] {
+            Item::Type(decl) => decl.name,
+            Item::Fn(decl) => decl.name,
+            _ => Ident::default(),
+        }
+    }
+
+    /// Whether an import of this cell is used by the names in `words`.
+    fn uses(&self, import: &z1_ast::Import, words: &BTreeSet<Ident>) -> bool {
+        import
+            .alias
+            .as_ref()
+            .is_some_and(|alias| words.contains(alias))
+            || import.only.iter().any(|name| words.contains(name))
+    }
+}
+
+struct Bundle<'a> {
+    cells: Vec<Cell<'a>>,
+    options: &'a ExtractOptions,
+    /// Declarations in the bundle, as (cell, item index)
+    chosen: BTreeSet<(usize, usize)>,
+    /// Index of the function the bundle is for, in the first cell
+    target: usize,
+}
+
+impl Bundle<'_> {
+    /// Names the declaration `idx` of `cell` mentions as bundled: only the
+    /// target keeps its body.
+    fn mentions(&self, cell: usize, idx: usize) -> BTreeSet<Ident> {
+        let mut words = self.cells[cell].signatures[&idx].clone();
+        if (cell, idx) == (0, self.target) {
+            words.extend(self.cells[0].bodies[&idx].iter().copied());
+        }
+        words
+    }
+
+    /// Declarations that the declaration `idx` of `cell` mentions.
+    fn references(&self, cell: usize, idx: usize) -> Vec<(usize, usize)> {
+        let words = self.mentions(cell, idx);
+        let mut refs: Vec<(usize, usize)> = self.cells[cell]
+            .signatures
+            .keys()
+            .filter(|&&other| other != idx && words.contains(&self.cells[cell].name(other)))
+            .map(|&other| (cell, other))
+            .collect();
+        // Imported declarations are only reached from the cell itself
+        if cell == 0 {
+            for import in self.cells[0]
+                .module
+                .items
+                .iter()
+                .filter_map(|item| match item {
+                    Item::Import(import) if self.cells[0].uses(import, &words) => Some(import),
+                    _ => None,
+                })
+            {
+                let Some(imported) = self
+                    .cells
+                    .iter()
+                    .position(|other| other.import == Some(&import.path))
+                else {
+                    continue;
+                };
+                refs.extend(
+                    self.cells[imported]
+                        .signatures
+                        .keys()
+                        .filter(|&&other| words.contains(&self.cells[imported].name(other)))
+                        .map(|&other| (imported, other)),
+                );
+            }
+        }
+        refs.sort();
+        refs
+    }
+
+    fn kind(&self, cell: usize, idx: usize) -> ContextKind {
+        match self.cells[cell].module.items[idx] {
+            Item::Type(_) => ContextKind::Type,
+            _ => ContextKind::Signature,
+        }
+    }
+
+    fn item(&self, (cell, idx): (usize, usize)) -> ContextItem {
+        ContextItem {
+            kind: self.kind(cell, idx),
+            name: self.cells[cell].name(idx).to_string(),
+            import: self.cells[cell].import.cloned(),
+        }
+    }
+
+    fn tokens(&self) -> Result<u32, FmtError> {
+        let source = self.render()?;
+        Ok(estimate_tokens_from_chars(
+            source.len(),
+            self.options.chars_per_token,
+        ))
+    }
+
+    /// Print the interfaces of the imports the bundle uses, then the cell
+    /// with the imports and symbol pairs its bundled declarations need.
+    fn render(&self) -> Result<String, FmtError> {
+        let mut out = String::new();
+        let mut words = BTreeSet::new();
+        for &(cell, idx) in &self.chosen {
+            if cell == 0 {
+                words.extend(self.mentions(0, idx));
+            }
+        }
+        for (cell, imported) in self.cells.iter().enumerate().skip(1) {
+            let items = self.items_of(cell);
+            if items.is_empty() {
+                continue;
+            }
+            out.push_str(&self.format(imported.module, items)?);
+            out.push('\n');
+        }
+
+        let mut items = Vec::new();
+        for (idx, item) in self.cells[0].module.items.iter().enumerate() {
+            match item {
+                Item::Import(import) if self.cells[0].uses(import, &words) => {
+                    items.push(item.clone());
+                }
+                Item::Symbol(map) => {
+                    let names: BTreeSet<Ident> = self
+                        .chosen
+                        .iter()
+                        .filter(|&&(cell, _)| cell == 0)
+                        .map(|&(_, idx)| self.cells[0].name(idx))
+                        .collect();
+                    let pairs: Vec<_> = map
+                        .pairs
+                        .iter()
+                        .filter(|pair| names.contains(&pair.long) || words.contains(&pair.long))
+                        .cloned()
+                        .collect();
+                    if !pairs.is_empty() {
+                        items.push(Item::Symbol(SymbolMap {
+                            pairs,
+                            span: map.span,
+                        }));
+                    }
+                }
+                _ if self.chosen.contains(&(0, idx)) => {
+                    items.push(self.bundled(0, idx));
+                }
+                _ => {}
+            }
+        }
+        out.push_str(&self.format(self.cells[0].module, items)?);
+        Ok(out)
+    }
+
+    fn items_of(&self, cell: usize) -> Vec<Item> {
+        self.chosen
+            .iter()
+            .filter(|&&(other, _)| other == cell)
+            .map(|&(_, idx)| self.bundled(cell, idx))
+            .collect()
+    }
+
+    /// The declaration as bundled: functions other than the target lose
+    /// their body.
+    fn bundled(&self, cell: usize, idx: usize) -> Item {
+        match &self.cells[cell].module.items[idx] {
+            Item::Fn(decl) if (cell, idx) != (0, self.target) => Item::Fn(signature(decl)),
+            item => item.clone(),
+        }
+    }
+
+    fn format(&self, module: &Module, items: Vec<Item>) -> Result<String, FmtError> {
+        let module = Module {
+            items,
+            ..module.clone()
+        };
+        format_module(&module, self.options.mode, &FmtOptions::default())
+    }
+}
+
+/// `decl` with its body elided.
+pub(crate) fn signature(decl: &FnDecl) -> FnDecl {
+    FnDecl {
+        body: Block {
+            raw: ELIDED_BODY.to_string(),
+            statements: Vec::new(),
+            span: decl.body.span,
+        },
+        ..decl.clone()
+    }
+}
+
+fn split_words(text: &str) -> impl Iterator<Item = Ident> + '_ {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty())
+        .map(Symbol::intern)
+}
+
+/// Names `ty` mentions, qualified ones split into their segments.
+pub fn type_words(ty: &TypeExpr, words: &mut BTreeSet<Ident>) {
+    match ty {
+        // The lexer keeps qualified names such as `H.Req` in one segment
+        TypeExpr::Path(segments) => {
+            for segment in segments {
+                words.extend(split_words(segment));
+            }
+        }
+        TypeExpr::Record(fields) => {
+            for field in fields {
+                type_words(&field.ty, words);
+            }
+        }
+        TypeExpr::Union(variants) => {
+            for payload in variants.iter().filter_map(|v| v.payload.as_deref()) {
+                type_words(payload, words);
+            }
+        }
+        TypeExpr::Generic { base, args } => {
+            for segment in base {
+                words.extend(split_words(segment));
+            }
+            for arg in args {
+                type_words(arg, words);
+            }
+        }
+    }
+}
+
+/// Names a body mentions: bindings, annotations, identifiers and fields.
+/// String literals and comments mention nothing.
+pub fn block_words(block: &Block, words: &mut BTreeSet<Ident>) {
+    for stmt in &block.statements {
+        match stmt {
+            Stmt::Let(stmt) => {
+                words.insert(stmt.name);
+                if let Some(ty) = &stmt.ty {
+                    type_words(ty, words);
+                }
+                expr_words(&stmt.init, words);
+            }
+            Stmt::Assign(stmt) => {
+                expr_words(&stmt.target, words);
+                expr_words(&stmt.value, words);
+            }
+            Stmt::If(stmt) => {
+                let mut stmt = stmt;
+                loop {
+                    expr_words(&stmt.cond, words);
+                    block_words(&stmt.then_block, words);
+                    match stmt.else_block.as_deref() {
+                        Some(ElseBlock::Block(block)) => block_words(block, words),
+                        Some(ElseBlock::If(nested)) => {
+                            stmt = nested;
+                            continue;
+                        }
+                        None => {}
+                    }
+                    break;
+                }
+            }
+            Stmt::While(stmt) => {
+                expr_words(&stmt.cond, words);
+                block_words(&stmt.body, words);
+            }
+            Stmt::Return(stmt) => {
+                if let Some(value) = &stmt.value {
+                    expr_words(value, words);
+                }
+            }
+            Stmt::Expr(stmt) => expr_words(&stmt.expr, words),
+        }
+    }
+}
+
+fn expr_words(expr: &Expr, words: &mut BTreeSet<Ident>) {
+    match expr {
+        Expr::Ident(name, _) => {
+            words.insert(*name);
+        }
+        Expr::Path(segments, _) => words.extend(segments.iter().copied()),
+        Expr::Literal(..) => {}
+        Expr::BinOp { lhs, rhs, .. } => {
+            expr_words(lhs, words);
+            expr_words(rhs, words);
+        }
+        Expr::UnaryOp { expr, .. } | Expr::Paren(expr, _) => expr_words(expr, words),
+        Expr::Call { func, args, .. } => {
+            expr_words(func, words);
+            for arg in args {
+                expr_words(arg, words);
+            }
+        }
+        Expr::Field { base, field, .. } => {
+            expr_words(base, words);
+            words.insert(*field);
+        }
+        Expr::Record { fields, .. } => {
+            for field in fields {
+                words.insert(field.name);
+                expr_words(&field.value, words);
+            }
+        }
+        Expr::RecordUpdate { base, fields, .. } => {
+            expr_words(base, words);
+            for field in fields {
+                words.insert(field.name);
+                expr_words(&field.value, words);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CELL: &str = "m app:1.0 caps=[net]\n\
+                        u \"std/http\" as H only [listen]\n\
+                        t Count = U32\n\
+                        t Unused = Str\n\
+                        f helper(n: Count)->Count eff [pure] { ret n + 1; }\n\
+                        f other()->Unit eff [pure] { ret Unit; }\n\
+                        f handler(n: Count)->Count eff [net] { H.listen(8080); ret helper(n); }\n";
+
+    const HTTP: &str = "m std.http:1.0 caps=[net]\n\
+                        t Port = U16\n\
+                        f listen(port: Port)->Unit eff [net] { ret Unit; }\n\
+                        f close()->Unit eff [net] { ret Unit; }\n";
+
+    fn imports() -> BTreeMap<String, Module> {
+        BTreeMap::from([(
+            "std/http".to_string(),
+            z1_parse::parse_module(HTTP).unwrap(),
+        )])
+    }
+
+    fn names(items: &[ContextItem]) -> Vec<&str> {
+        items.iter().map(|item| item.name.as_str()).collect()
+    }
+
+    #[test]
+    fn bundles_what_the_function_reaches() {
+        let module = z1_parse::parse_module(CELL).unwrap();
+        let bundle =
+            extract_context(&module, "handler", &imports(), &ExtractOptions::default()).unwrap();
+        assert_eq!(names(&bundle.items), ["Count", "helper", "listen", "Port"]);
+        assert!(bundle.omitted.is_empty());
+        assert_eq!(bundle.items[2].import.as_deref(), Some("std/http"));
+        assert!(bundle
+            .source
+            .contains("f listen(port: Port)->Unit eff [net] { … }"));
+        assert!(bundle
+            .source
+            .contains("f helper(n: Count)->Count eff [pure] { … }"));
+        assert!(bundle.source.contains("{ H.listen(8080); ret helper(n); }"));
+        for absent in ["Unused", "other", "close"] {
+            assert!(!bundle.source.contains(absent), "{}", bundle.source);
+        }
+    }
+
+    #[test]
+    fn leaves_out_what_does_not_fit() {
+        let module = z1_parse::parse_module(CELL).unwrap();
+        let full =
+            extract_context(&module, "handler", &imports(), &ExtractOptions::default()).unwrap();
+        let options = ExtractOptions {
+            budget: Some(full.tokens - 1),
+            ..ExtractOptions::default()
+        };
+        let bundle = extract_context(&module, "handler", &imports(), &options).unwrap();
+        assert!(bundle.tokens < full.tokens);
+        assert!(!bundle.omitted.is_empty());
+        for item in bundle.items.iter().chain(&bundle.omitted) {
+            assert!(full.items.contains(item));
+        }
+
+        let options = ExtractOptions {
+            budget: Some(1),
+            ..ExtractOptions::default()
+        };
+        assert!(matches!(
+            extract_context(&module, "handler", &imports(), &options),
+            Err(ExtractError::OverBudget { .. })
+        ));
+        assert!(matches!(
+            extract_context(&module, "missing", &imports(), &options),
+            Err(ExtractError::UnknownFunction(_))
+        ));
+    }
+}
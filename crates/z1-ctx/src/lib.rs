@@ -8,6 +8,10 @@
 //! The MVP uses a naive heuristic: `tokens ≈ ceil(chars / 3.8)`.
 //! Future versions will support model-specific dictionaries (SDict) for improved estimation.
 //!
+//! [`extract_context`] bundles the context needed to edit a single function
+//! within a token budget; [`pack`] bundles a whole cell with its
+//! dependencies, in full or as interfaces.
+//!
 //! ## Usage
 //!
 //! ```rust
@@ -24,6 +28,18 @@
 //! println!("Estimated tokens: {}", estimate.total_tokens);
 //! ```
 
+mod breakdown;
+mod extract;
+mod pack;
+mod size;
+
+pub use breakdown::{breakdown, Breakdown, Construct, IdentCost};
+pub use extract::{
+    block_words, extract_context, type_words, ContextBundle, ContextItem, ContextKind,
+    ExtractError, ExtractOptions,
+};
+pub use pack::{pack, Pack, PackError, PackForm, PackOptions, PackedDep};
+
 use std::fmt;
 use thiserror::Error;
 use z1_ast::{FnDecl, Module, Span};
@@ -155,14 +171,12 @@ pub fn estimate_cell_with_config(
 }
 
 /// Estimates tokens from character count using the configured ratio.
-fn estimate_tokens_from_chars(chars: usize, chars_per_token: f64) -> u32 {
+pub fn estimate_tokens_from_chars(chars: usize, chars_per_token: f64) -> u32 {
     (chars as f64 / chars_per_token).ceil() as u32
 }
 
-/// Estimates token usage for individual functions.
-///
-/// Note: This is approximate as we don't have full statement-level formatting yet.
-/// We estimate based on the size of the function body as a proportion of total content.
+/// Estimates token usage for individual functions, from the compact size
+/// of their parsed bodies and an allowance for their signatures.
 fn estimate_functions(module: &Module, config: &EstimateConfig) -> Vec<FnEstimate> {
     let mut estimates = Vec::new();
 
@@ -178,9 +192,7 @@ fn estimate_functions(module: &Module, config: &EstimateConfig) -> Vec<FnEstimat
 
 /// Estimates tokens for a single function.
 fn estimate_function(fn_decl: &FnDecl, config: &EstimateConfig) -> FnEstimate {
-    // Rough approximation: use body raw text length as basis
-    // In future, we could format individual functions to compact mode
-    let body_len = fn_decl.body.raw.len();
+    let body_len = size::block_chars(&fn_decl.body);
 
     // Add signature overhead (name + params + return type + effects)
     let sig_overhead = fn_decl.name.len()
@@ -195,7 +207,7 @@ fn estimate_function(fn_decl: &FnDecl, config: &EstimateConfig) -> FnEstimate {
     let tokens = estimate_tokens_from_chars(total_chars, config.chars_per_token);
 
     FnEstimate {
-        name: fn_decl.name.clone(),
+        name: fn_decl.name.to_string(),
         tokens,
         chars: total_chars,
         span: fn_decl.span,
@@ -204,12 +216,10 @@ fn estimate_function(fn_decl: &FnDecl, config: &EstimateConfig) -> FnEstimate {
 
 /// Suggests how to split a cell that exceeds its budget.
 fn suggest_split(estimate: &CellEstimate) -> String {
-    if estimate.functions.is_empty() {
-        return "Consider reducing the size of this cell.".to_string();
-    }
-
     // Find largest function by token count
-    let largest_fn = estimate.functions.iter().max_by_key(|f| f.tokens).unwrap();
+    let Some(largest_fn) = estimate.functions.iter().max_by_key(|f| f.tokens) else {
+        return "Consider reducing the size of this cell.".to_string();
+    };
 
     if estimate.functions.len() == 1 {
         format!(
@@ -0,0 +1,273 @@
+//! Context packs: a cell and everything it builds on, within a budget.
+//!
+//! Where a [context bundle](crate::extract_context) serves an edit of one
+//! function, a pack serves work on a whole cell: the cell in full, preceded
+//! by its transitive dependencies. Each dependency appears either as its
+//! full source or as its interface, the cell with its function bodies
+//! elided as `{ … }` and its tests left out.
+//!
+//! Under a budget, every dependency first gets its interface, nearest first,
+//! as long as they fit; then dependencies are upgraded to their full source,
+//! nearest first, as long as those fit. Dependencies whose interface does not
+//! fit are left out. Without a budget, every dependency is packed in full.
+
+use std::fmt;
+
+use thiserror::Error;
+use z1_ast::{Item, Module};
+use z1_fmt::{format_module, FmtError, FmtOptions, Mode};
+
+use crate::extract::signature;
+use crate::{estimate_tokens_from_chars, DEFAULT_CHARS_PER_TOKEN};
+
+/// Errors that can occur while packing a cell.
+#[derive(Debug, Error)]
+pub enum PackError {
+    #[error("the cell alone takes {actual} tokens, over the budget of {budget}")]
+    OverBudget { actual: u32, budget: u32 },
+
+    #[error(transparent)]
+    Format(#[from] FmtError),
+}
+
+/// Configuration for [`pack`].
+#[derive(Debug, Clone)]
+pub struct PackOptions {
+    /// Tokens the pack may take; `None` packs every dependency in full
+    pub budget: Option<u32>,
+    /// Characters per token ratio (default: 3.8)
+    pub chars_per_token: f64,
+    /// Mode the pack is printed in
+    pub mode: Mode,
+}
+
+impl Default for PackOptions {
+    fn default() -> Self {
+        Self {
+            budget: None,
+            chars_per_token: DEFAULT_CHARS_PER_TOKEN,
+            mode: Mode::Compact,
+        }
+    }
+}
+
+/// How a dependency appears in a pack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PackForm {
+    /// Types and function signatures only
+    Interface,
+    Source,
+}
+
+impl PackForm {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PackForm::Interface => "interface",
+            PackForm::Source => "source",
+        }
+    }
+}
+
+impl fmt::Display for PackForm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A dependency of the packed cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedDep {
+    /// Import path the dependency was reached through
+    pub import: String,
+    /// `None` when it did not fit the budget
+    pub form: Option<PackForm>,
+    /// Tokens of its interface and of its full source
+    pub interface_tokens: u32,
+    pub source_tokens: u32,
+}
+
+/// Outcome of [`pack`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pack {
+    /// The pack: dependencies, farthest first, then the cell
+    pub source: String,
+    pub tokens: u32,
+    /// Every dependency, nearest first
+    pub deps: Vec<PackedDep>,
+}
+
+/// Pack `module` with its dependencies, given by import path nearest first.
+pub fn pack(
+    module: &Module,
+    deps: &[(String, Module)],
+    options: &PackOptions,
+) -> Result<Pack, PackError> {
+    let cell = format_module(module, options.mode, &FmtOptions::default())?;
+    let tokens = |text: &str| estimate_tokens_from_chars(text.len(), options.chars_per_token);
+    let mut texts = Vec::with_capacity(deps.len());
+    for (import, dep) in deps {
+        let interface = interface(dep);
+        texts.push([
+            section(import, PackForm::Interface, &interface, options.mode)?,
+            section(import, PackForm::Source, dep, options.mode)?,
+        ]);
+    }
+
+    let mut forms: Vec<Option<PackForm>> = vec![None; deps.len()];
+    match options.budget {
+        None => forms.fill(Some(PackForm::Source)),
+        Some(budget) => {
+            let actual = tokens(&cell);
+            if actual > budget {
+                return Err(PackError::OverBudget { actual, budget });
+            }
+            for form in [PackForm::Interface, PackForm::Source] {
+                for idx in 0..deps.len() {
+                    if form == PackForm::Source && forms[idx].is_none() {
+                        continue;
+                    }
+                    let previous = forms[idx].replace(form);
+                    if tokens(&render(&cell, &texts, &forms)) > budget {
+                        forms[idx] = previous;
+                    }
+                }
+            }
+        }
+    }
+
+    let source = render(&cell, &texts, &forms);
+    Ok(Pack {
+        tokens: tokens(&source),
+        source,
+        deps: deps
+            .iter()
+            .zip(&texts)
+            .zip(forms)
+            .map(|(((import, _), [interface, source]), form)| PackedDep {
+                import: import.clone(),
+                form,
+                interface_tokens: tokens(interface),
+                source_tokens: tokens(source),
+            })
+            .collect(),
+    })
+}
+
+/// `module` without its function bodies and tests.
+fn interface(module: &Module) -> Module {
+    Module {
+        items: module
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Fn(decl) => Some(Item::Fn(signature(decl))),
+                Item::Test(_) => None,
+                item => Some(item.clone()),
+            })
+            .collect(),
+        ..module.clone()
+    }
+}
+
+/// A dependency as packed, under a comment naming it.
+fn section(import: &str, form: PackForm, module: &Module, mode: Mode) -> Result<String, FmtError> {
+    let text = format_module(module, mode, &FmtOptions::default())?;
+    Ok(format!("// {import} ({form})\n{text}\n"))
+}
+
+fn render(cell: &str, texts: &[[String; 2]], forms: &[Option<PackForm>]) -> String {
+    let mut out = String::new();
+    for (text, form) in texts.iter().zip(forms).rev() {
+        match form {
+            Some(PackForm::Interface) => out.push_str(&text[0]),
+            Some(PackForm::Source) => out.push_str(&text[1]),
+            None => {}
+        }
+    }
+    out.push_str(cell);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CELL: &str = "m app:1.0 caps=[net]\n\
+                        u \"lib/util\" only [twice]\n\
+                        f main()->U32 eff [pure] { ret twice(21); }\n";
+
+    const UTIL: &str = "m lib.util:1.0\n\
+                        u \"lib/base\" only [one]\n\
+                        t Count = U32\n\
+                        f twice(n: Count)->Count eff [pure] { let doubled: U32 = n + n; ret doubled; }\n";
+
+    const BASE: &str = "m lib.base:1.0\n\
+                        f one()->U32 eff [pure] { let value: U32 = 1; ret value; }\n";
+
+    fn deps() -> Vec<(String, Module)> {
+        vec![
+            (
+                "lib/util".to_string(),
+                z1_parse::parse_module(UTIL).unwrap(),
+            ),
+            (
+                "lib/base".to_string(),
+                z1_parse::parse_module(BASE).unwrap(),
+            ),
+        ]
+    }
+
+    fn forms(pack: &Pack) -> Vec<Option<PackForm>> {
+        pack.deps.iter().map(|dep| dep.form).collect()
+    }
+
+    #[test]
+    fn packs_every_dependency_in_full_without_a_budget() {
+        let module = z1_parse::parse_module(CELL).unwrap();
+        let pack = pack(&module, &deps(), &PackOptions::default()).unwrap();
+        assert_eq!(forms(&pack), [Some(PackForm::Source); 2]);
+        let base = pack.source.find("// lib/base (source)").unwrap();
+        let util = pack.source.find("// lib/util (source)").unwrap();
+        let cell = pack.source.find("m app:1.0").unwrap();
+        assert!(base < util && util < cell, "{}", pack.source);
+        assert!(pack.source.contains("ret doubled;"));
+    }
+
+    #[test]
+    fn prefers_interfaces_of_every_dependency_to_full_sources() {
+        let module = z1_parse::parse_module(CELL).unwrap();
+        let full = pack(&module, &deps(), &PackOptions::default()).unwrap();
+        let options = PackOptions {
+            budget: Some(full.tokens - 1),
+            ..PackOptions::default()
+        };
+        let packed = pack(&module, &deps(), &options).unwrap();
+        assert!(packed.tokens < full.tokens);
+        assert_eq!(
+            forms(&packed),
+            [Some(PackForm::Source), Some(PackForm::Interface)]
+        );
+        assert!(packed.source.contains("f one()->U32 eff [pure] { … }"));
+
+        let cell = format_module(&module, Mode::Compact, &FmtOptions::default()).unwrap();
+        let options = PackOptions {
+            budget: Some(estimate_tokens_from_chars(
+                cell.len(),
+                DEFAULT_CHARS_PER_TOKEN,
+            )),
+            ..PackOptions::default()
+        };
+        let packed = pack(&module, &deps(), &options).unwrap();
+        assert_eq!(forms(&packed), [None, None]);
+        assert_eq!(packed.source, cell);
+
+        let options = PackOptions {
+            budget: Some(1),
+            ..PackOptions::default()
+        };
+        assert!(matches!(
+            pack(&module, &deps(), &options),
+            Err(PackError::OverBudget { .. })
+        ));
+    }
+}
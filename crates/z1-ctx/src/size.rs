@@ -0,0 +1,158 @@
+//! Characters of a parsed body in compact form.
+//!
+//! The estimates measure bodies from their statements, as the compact form
+//! spells them (`ret x;`, `{ a: 1 }`, `f(a, b)`), rather than from the text
+//! they were written as.
+
+use z1_ast::{BinOp, Block, ElseBlock, Expr, IfStmt, Literal, Stmt, TypeExpr, UnaryOp};
+
+/// Characters of `block`, braces included.
+pub(crate) fn block_chars(block: &Block) -> usize {
+    if block.statements.is_empty() {
+        return 2;
+    }
+    // `{ `, a space between statements, ` }`
+    let stmts: usize = block.statements.iter().map(stmt_chars).sum();
+    stmts + block.statements.len() - 1 + 4
+}
+
+fn stmt_chars(stmt: &Stmt) -> usize {
+    match stmt {
+        Stmt::Let(stmt) => {
+            // `let `, `mut `, ` = `, `;`
+            let mutable = if stmt.mutable { 4 } else { 0 };
+            let ty = stmt.ty.as_ref().map_or(0, |ty| 2 + type_chars(ty));
+            4 + mutable + stmt.name.len() + ty + 3 + expr_chars(&stmt.init) + 1
+        }
+        Stmt::Assign(stmt) => expr_chars(&stmt.target) + 3 + expr_chars(&stmt.value) + 1,
+        Stmt::If(stmt) => if_chars(stmt),
+        Stmt::While(stmt) => 6 + expr_chars(&stmt.cond) + 1 + block_chars(&stmt.body),
+        Stmt::Return(stmt) => 3 + stmt.value.as_ref().map_or(0, |value| 1 + expr_chars(value)) + 1,
+        Stmt::Expr(stmt) => expr_chars(&stmt.expr) + 1,
+    }
+}
+
+fn if_chars(stmt: &IfStmt) -> usize {
+    // `if `, ` else `
+    let else_chars = match stmt.else_block.as_deref() {
+        Some(ElseBlock::Block(block)) => 6 + block_chars(block),
+        Some(ElseBlock::If(stmt)) => 6 + if_chars(stmt),
+        None => 0,
+    };
+    3 + expr_chars(&stmt.cond) + 1 + block_chars(&stmt.then_block) + else_chars
+}
+
+fn expr_chars(expr: &Expr) -> usize {
+    match expr {
+        Expr::Ident(name, _) => name.len(),
+        Expr::Literal(literal, _) => match literal {
+            Literal::Bool(value) => value.to_string().len(),
+            Literal::Str(text) => text.len() + 2,
+            Literal::U16(n) => n.to_string().len(),
+            Literal::U32(n) => n.to_string().len(),
+            Literal::U64(n) => n.to_string().len(),
+            Literal::Int(n) => n.to_string().len(),
+            Literal::Unit => 2,
+        },
+        Expr::BinOp { lhs, op, rhs, .. } => {
+            let op = match op {
+                BinOp::Eq | BinOp::Ne | BinOp::Le | BinOp::Ge | BinOp::And | BinOp::Or => 2,
+                _ => 1,
+            };
+            expr_chars(lhs) + op + 2 + expr_chars(rhs)
+        }
+        Expr::UnaryOp { op, expr, .. } => {
+            let op = match op {
+                UnaryOp::Neg | UnaryOp::Not => 1,
+                UnaryOp::Await => 6,
+            };
+            op + expr_chars(expr)
+        }
+        Expr::Call { func, args, .. } => expr_chars(func) + list_chars(args.iter().map(expr_chars)),
+        Expr::Field { base, field, .. } => expr_chars(base) + 1 + field.len(),
+        Expr::Record { fields, .. } => record_chars(
+            fields
+                .iter()
+                .map(|field| field.name.len() + 2 + expr_chars(&field.value)),
+        ),
+        Expr::RecordUpdate { base, fields, .. } => record_chars(
+            std::iter::once(2 + expr_chars(base)).chain(
+                fields
+                    .iter()
+                    .map(|field| field.name.len() + 2 + expr_chars(&field.value)),
+            ),
+        ),
+        Expr::Path(segments, _) => path_chars(segments.iter().map(|segment| segment.len())),
+        Expr::Paren(expr, _) => expr_chars(expr) + 2,
+    }
+}
+
+fn type_chars(ty: &TypeExpr) -> usize {
+    match ty {
+        TypeExpr::Path(segments) => path_chars(segments.iter().map(|segment| segment.len())),
+        TypeExpr::Record(fields) => record_chars(
+            fields
+                .iter()
+                .map(|field| field.name.len() + 2 + type_chars(&field.ty)),
+        ),
+        // ` | ` between variants
+        TypeExpr::Union(variants) => separated(
+            variants.iter().map(|variant| {
+                variant.name.len()
+                    + variant
+                        .payload
+                        .as_deref()
+                        .map_or(0, |ty| type_chars(ty) + 2)
+            }),
+            3,
+        ),
+        TypeExpr::Generic { base, args } => {
+            path_chars(base.iter().map(|segment| segment.len()))
+                + list_chars(args.iter().map(type_chars))
+        }
+    }
+}
+
+/// `a.b.c`
+fn path_chars(segments: impl Iterator<Item = usize>) -> usize {
+    separated(segments, 1)
+}
+
+/// `(a, b)` or `[a, b]`
+fn list_chars(items: impl Iterator<Item = usize>) -> usize {
+    separated(items, 2) + 2
+}
+
+/// `{ a, b }`
+fn record_chars(fields: impl Iterator<Item = usize>) -> usize {
+    separated(fields, 2) + 4
+}
+
+/// Total of `items` with `sep` characters between each two.
+fn separated(items: impl Iterator<Item = usize>, sep: usize) -> usize {
+    let (mut total, mut count) = (0, 0_usize);
+    for item in items {
+        total += item;
+        count += 1;
+    }
+    total + sep * count.saturating_sub(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use z1_ast::Item;
+
+    #[test]
+    fn bodies_count_their_compact_spelling() {
+        let body = "{ let mut x: H.Req = { ..q, path: \"/\" }; \
+                    while x.n < 3 { x = f(x, !ok); } \
+                    if a == b { ret (1); } else if c { g(); } else { h(); } ret; }";
+        let source = format!("m app:1.0\nf main(q: H.Req)->Unit eff [pure] {body}\n");
+        let module = z1_parse::parse_module(&source).unwrap();
+        let Item::Fn(decl) = &module.items[0] else {
+            panic!("expected a function");
+        };
+        assert_eq!(block_chars(&decl.body), body.len());
+    }
+}
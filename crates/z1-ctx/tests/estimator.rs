@@ -1,4 +1,6 @@
-use z1_ctx::{estimate_cell, estimate_cell_with_config, CtxError, EstimateConfig};
+use z1_ctx::{
+    breakdown, estimate_cell, estimate_cell_with_config, Construct, CtxError, EstimateConfig,
+};
 use z1_parse::parse_module;
 
 #[test]
@@ -232,3 +234,51 @@ f process()->C.Unit eff [pure] { ret C.Unit }
     assert!(estimate.total_tokens > 0);
     assert_eq!(estimate.functions.len(), 1);
 }
+
+#[test]
+fn test_breakdown_by_construct() {
+    let source = r#"
+m test:1.0 ctx=300
+#sym { process: pr }
+u "std/core" as C only [Unit]
+t MyType = { field: C.Unit }
+f process()->C.Unit eff [pure] { ret C.Unit }
+"#;
+
+    let module = parse_module(source).unwrap();
+    let breakdown = breakdown(&module).unwrap();
+    let estimate = estimate_cell(&module).unwrap();
+
+    assert_eq!(breakdown.char_count, estimate.char_count);
+    let chars = |construct| {
+        breakdown
+            .constructs
+            .iter()
+            .find(|(c, _)| *c == construct)
+            .unwrap()
+            .1
+    };
+    assert_eq!(
+        breakdown.constructs.iter().map(|(_, n)| n).sum::<usize>(),
+        breakdown.char_count
+    );
+    // Bodies count as their statements are spelled in compact form
+    assert_eq!(chars(Construct::Bodies), "{ ret C.Unit; }".len() + 2);
+    assert_eq!(
+        chars(Construct::Imports),
+        "u \"std/core\" as C only [Unit]\n\n".len()
+    );
+    assert!(chars(Construct::SymbolMaps) > 0 && chars(Construct::Types) > 0);
+
+    // C appears in the import alias, the type, the signature and the body
+    let c = breakdown
+        .identifiers
+        .iter()
+        .find(|i| i.name == "C")
+        .unwrap();
+    assert_eq!(c.occurrences, 4);
+    assert_eq!(
+        breakdown.identifiers[0].chars,
+        breakdown.identifiers.iter().map(|i| i.chars).max().unwrap()
+    );
+}
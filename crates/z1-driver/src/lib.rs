@@ -0,0 +1,586 @@
+//! One entry point to the Zero1 compiler for tools that embed it.
+//!
+//! Compiling a cell takes a parser, a resolver, three checkers, the policy
+//! gates, the IR and a backend. [`Compiler`] strings them together the way
+//! `z1 compile` does, but in memory: it reads and writes no files (imports
+//! aside, through an optional [`Resolver`]), prints nothing, and returns a
+//! [`CompileResult`] holding the [`Diagnostic`]s of every stage that ran,
+//! the optimized IR and the generated [`Artifact`]s.
+//!
+//! The pipeline stops at the first stage reporting an error, as the CLI
+//! does; warnings never stop it.
+//!
+//! Without the default `fs` feature, the crate touches no file system at
+//! all and builds for `wasm32-unknown-unknown`: there is no [`Resolver`],
+//! so `std` imports resolve to the bundled standard library and others stay
+//! opaque.
+//!
+//! ## Usage
+//!
+//! ```rust
+//! use z1_driver::{CompileOptions, Compiler, Target};
+//!
+//! let compiler = Compiler::new(CompileOptions {
+//!     target: Target::TypeScript,
+//!     ..CompileOptions::default()
+//! });
+//! let result = compiler.compile_source("m app:1.0\nf main()->U32 eff [pure] { ret 1; }\n");
+//! for diagnostic in &result.diagnostics {
+//!     eprintln!("{diagnostic}");
+//! }
+//! let code = result.output().expect("compiled");
+//! assert!(String::from_utf8_lossy(&code.contents).contains("function main"));
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Serialize;
+use z1_ast::{Item, Module, Span};
+use z1_ir::optimize::{OptLevel, OptStats};
+use z1_ir::IrModule;
+use z1_policy::{PolicyLimits, PolicyViolation};
+#[cfg(feature = "fs")]
+pub use z1_resolve::Resolver;
+
+/// Code the cell is compiled to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    /// A TypeScript module, with its `.d.ts` declarations
+    #[default]
+    TypeScript,
+    /// WebAssembly text
+    Wat,
+    /// A WebAssembly binary module
+    Wasm,
+}
+
+impl Target {
+    /// Name of the target in a cell's `targets=[..]` header field.
+    pub fn tag(self) -> &'static str {
+        match self {
+            Target::TypeScript => "ts",
+            Target::Wat | Target::Wasm => "wasm",
+        }
+    }
+}
+
+/// Options of a [`Compiler`].
+#[derive(Debug, Clone)]
+pub struct CompileOptions {
+    pub target: Target,
+    pub opt_level: OptLevel,
+    /// Run the resolver, type and effect checkers, context estimate and
+    /// policy gates before lowering; without it a cell is lowered as soon
+    /// as it parses.
+    pub check: bool,
+    /// Limits enforced by the policy gates.
+    pub policy: PolicyLimits,
+    /// Resolver for imports; without one only `std` imports resolve, to
+    /// the bundled standard library, and others stay opaque.
+    #[cfg(feature = "fs")]
+    pub resolver: Option<Resolver>,
+    /// Options of the WebAssembly backend.
+    pub wasm: z1_codegen_wasm::WasmOptions,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            target: Target::default(),
+            opt_level: OptLevel::default(),
+            check: true,
+            policy: PolicyLimits::default(),
+            #[cfg(feature = "fs")]
+            resolver: None,
+            wasm: z1_codegen_wasm::WasmOptions::default(),
+        }
+    }
+}
+
+/// Pipeline stage a diagnostic comes from, in pipeline order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    Parse,
+    Resolve,
+    Typeck,
+    Effects,
+    Ctx,
+    Policy,
+    Lower,
+    Codegen,
+}
+
+impl Stage {
+    /// Lowercase name of the stage, as serialized.
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Parse => "parse",
+            Stage::Resolve => "resolve",
+            Stage::Typeck => "typeck",
+            Stage::Effects => "effects",
+            Stage::Ctx => "ctx",
+            Stage::Policy => "policy",
+            Stage::Lower => "lower",
+            Stage::Codegen => "codegen",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    /// Lowercase name of the severity, as serialized.
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// A problem found in the cell.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub stage: Stage,
+    /// Diagnostic code, as explained by `z1 explain`
+    pub code: Option<&'static str>,
+    pub message: String,
+    /// Where in the source, when known
+    pub span: Option<Span>,
+}
+
+impl Diagnostic {
+    fn error(stage: Stage, code: Option<&'static str>, message: impl ToString) -> Self {
+        Self {
+            severity: Severity::Error,
+            stage,
+            code,
+            message: message.to_string(),
+            span: None,
+        }
+    }
+
+    fn warning(stage: Stage, code: Option<&'static str>, message: impl ToString) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(stage, code, message)
+        }
+    }
+
+    fn at(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.severity.name())?;
+        if let Some(code) = self.code {
+            write!(f, "[{code}]")?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(span) = self.span {
+            write!(f, " (at {}..{})", span.start, span.end)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtifactKind {
+    /// The generated code
+    Output,
+    /// TypeScript declarations of the output
+    Declarations,
+    /// Runtime shim of a bundled std cell the output imports
+    Shim,
+}
+
+/// A generated file, in memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    pub kind: ArtifactKind,
+    /// File name the generated code expects it under, e.g. `std_http.ts`
+    /// for the shim of `std/http`
+    pub file_name: String,
+    pub contents: Vec<u8>,
+}
+
+/// Everything a compilation produced.
+#[derive(Debug, Clone, Default)]
+pub struct CompileResult {
+    /// Diagnostics of every stage that ran, in pipeline order
+    pub diagnostics: Vec<Diagnostic>,
+    /// The parsed cell, unless it did not parse
+    pub module: Option<Module>,
+    /// Optimized IR, when the cell got that far
+    pub ir: Option<IrModule>,
+    pub opt_stats: Option<OptStats>,
+    /// Generated files; empty unless the compilation succeeded
+    pub artifacts: Vec<Artifact>,
+}
+
+impl CompileResult {
+    /// Whether no stage reported an error.
+    pub fn is_ok(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Warning)
+    }
+
+    /// The generated code.
+    pub fn output(&self) -> Option<&Artifact> {
+        self.artifacts
+            .iter()
+            .find(|artifact| artifact.kind == ArtifactKind::Output)
+    }
+}
+
+/// The compiler, configured once and reused for any number of cells.
+#[derive(Debug, Clone, Default)]
+pub struct Compiler {
+    options: CompileOptions,
+}
+
+impl Compiler {
+    pub fn new(options: CompileOptions) -> Self {
+        Self { options }
+    }
+
+    pub fn options(&self) -> &CompileOptions {
+        &self.options
+    }
+
+    /// Compile the cell `source`.
+    pub fn compile_source(&self, source: &str) -> CompileResult {
+        let mut result = CompileResult::default();
+        match z1_parse::parse_module(source) {
+            Ok(module) => {
+                self.compile_into(&module, &mut result);
+                result.module = Some(module);
+            }
+            Err(err) => {
+                let (z1_parse::ParseError::Unexpected { span, .. }
+                | z1_parse::ParseError::Invalid { span, .. }) = err;
+                result
+                    .diagnostics
+                    .push(Diagnostic::error(Stage::Parse, Some("P001"), &err).at(span));
+            }
+        }
+        result
+    }
+
+    /// Compile a cell already parsed.
+    pub fn compile_module(&self, module: &Module) -> CompileResult {
+        let mut result = CompileResult {
+            module: Some(module.clone()),
+            ..CompileResult::default()
+        };
+        self.compile_into(module, &mut result);
+        result
+    }
+
+    fn compile_into(&self, module: &Module, result: &mut CompileResult) {
+        let diagnostics = &mut result.diagnostics;
+        let mut bundled = Vec::new();
+        let mut imports = BTreeMap::new();
+        if self.options.check {
+            imports = self.resolve(module, diagnostics, &mut bundled);
+            if has_errors(diagnostics) || !check(module, &imports, &self.options, diagnostics) {
+                return;
+            }
+        }
+        if let Err(message) = check_requirements(module, self.options.target) {
+            diagnostics
+                .push(Diagnostic::error(Stage::Policy, Some("L001"), message).at(module.span));
+            return;
+        }
+
+        let mut ir = match z1_ir::lower_to_ir(module) {
+            Ok(ir) => ir,
+            Err(err) => {
+                diagnostics.push(Diagnostic::error(Stage::Lower, None, err));
+                return;
+            }
+        };
+        z1_ir::bind_capabilities(&mut ir, module, &imports);
+        let stats = z1_ir::optimize::optimize(&mut ir, self.options.opt_level);
+        match self.generate(module, &ir, &bundled) {
+            Ok(artifacts) => result.artifacts = artifacts,
+            Err(message) => diagnostics.push(Diagnostic::error(Stage::Codegen, None, message)),
+        }
+        result.ir = Some(ir);
+        result.opt_stats = Some(stats);
+    }
+
+    /// Imports of `module` by import path; the bundled std cells among them
+    /// are added to `bundled`.
+    fn resolve(
+        &self,
+        module: &Module,
+        diagnostics: &mut Vec<Diagnostic>,
+        bundled: &mut Vec<String>,
+    ) -> BTreeMap<String, Module> {
+        let mut imports = BTreeMap::new();
+        for item in &module.items {
+            let Item::Import(import) = item else {
+                continue;
+            };
+            match self.load(&import.path) {
+                Some(Ok(resolved)) => {
+                    if resolved.bundled && !bundled.contains(&resolved.import) {
+                        bundled.push(resolved.import.clone());
+                    }
+                    imports.insert(resolved.import, resolved.module);
+                }
+                Some(Err(err)) => diagnostics
+                    .push(Diagnostic::error(Stage::Resolve, Some("R001"), err).at(import.span)),
+                None => {}
+            }
+        }
+        imports
+    }
+
+    /// The cell `import` names, through the resolver or else from the
+    /// bundled standard library; `None` for an import left opaque.
+    #[cfg(feature = "fs")]
+    fn load(&self, import: &str) -> Option<Result<Loaded, String>> {
+        let loaded = match &self.options.resolver {
+            Some(resolver) => Some(resolver.load(import)),
+            None => z1_resolve::load_std(import),
+        };
+        loaded.map(|loaded| {
+            loaded
+                .map(|resolved| Loaded {
+                    import: resolved.import,
+                    module: resolved.module,
+                    bundled: resolved.bundled,
+                })
+                .map_err(|err| err.to_string())
+        })
+    }
+
+    /// The bundled std cell `import` names; `None` for any other import.
+    #[cfg(not(feature = "fs"))]
+    fn load(&self, import: &str) -> Option<Result<Loaded, String>> {
+        let segments: Vec<&str> = import.split(['/', '.']).collect();
+        if segments.len() < 2 || segments[0] != "std" {
+            return None;
+        }
+        Some(match z1_std::interface(import) {
+            Some(module) => Ok(Loaded {
+                import: import.to_string(),
+                module,
+                bundled: true,
+            }),
+            None => Err(format!(
+                "cannot resolve import `{import}`: the standard library {} has no such module",
+                z1_std::VERSION
+            )),
+        })
+    }
+
+    fn generate(
+        &self,
+        module: &Module,
+        ir: &IrModule,
+        bundled: &[String],
+    ) -> Result<Vec<Artifact>, String> {
+        let stem = module.path.to_string();
+        let artifact = |kind, file_name: String, contents: Vec<u8>| Artifact {
+            kind,
+            file_name,
+            contents,
+        };
+        let mut artifacts = Vec::new();
+        match self.options.target {
+            Target::TypeScript => {
+                artifacts.push(artifact(
+                    ArtifactKind::Output,
+                    format!("{stem}.ts"),
+                    z1_codegen_ts::generate_typescript(ir).into_bytes(),
+                ));
+                artifacts.push(artifact(
+                    ArtifactKind::Declarations,
+                    format!("{stem}.d.ts"),
+                    z1_codegen_ts::generate_declarations(ir).into_bytes(),
+                ));
+                for import in bundled {
+                    let backend = z1_std::Backend::TypeScript;
+                    if let Some(shim) = z1_std::cell(import).and_then(|cell| cell.shim(backend)) {
+                        artifacts.push(artifact(
+                            ArtifactKind::Shim,
+                            format!("{}.ts", import.replace('/', "_")),
+                            shim.as_bytes().to_vec(),
+                        ));
+                    }
+                }
+            }
+            Target::Wat => artifacts.push(artifact(
+                ArtifactKind::Output,
+                format!("{stem}.wat"),
+                z1_codegen_wasm::generate_wasm_with_options(ir, &self.options.wasm).into_bytes(),
+            )),
+            Target::Wasm => artifacts.push(artifact(
+                ArtifactKind::Output,
+                format!("{stem}.wasm"),
+                z1_codegen_wasm::generate_wasm_binary_with_options(ir, &self.options.wasm)
+                    .map_err(|err| format!("WASM binary generation failed: {err}"))?,
+            )),
+        }
+        Ok(artifacts)
+    }
+}
+
+/// A cell an import resolved to.
+struct Loaded {
+    import: String,
+    module: Module,
+    /// Whether it is a std cell bundled with the toolchain
+    bundled: bool,
+}
+
+/// Type check, effect check, estimate and gate `module`, adding what they
+/// report to `diagnostics`; returns whether every stage passed.
+fn check(
+    module: &Module,
+    imports: &BTreeMap<String, Module>,
+    options: &CompileOptions,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> bool {
+    if let Err(err) = z1_typeck::check_module_with_imports(module, imports) {
+        let mut diagnostic = Diagnostic::error(Stage::Typeck, Some("T001"), &err);
+        diagnostic.span = type_error_span(&err);
+        diagnostics.push(diagnostic);
+        return false;
+    }
+    diagnostics.extend(z1_typeck::collect_warnings(module).iter().map(|warning| {
+        let code = matches!(warning, z1_typeck::TypeWarning::UnusedImport { .. }).then_some("W002");
+        Diagnostic::warning(Stage::Typeck, code, warning).at(warning.span())
+    }));
+
+    if let Err(err) =
+        z1_effects::check_module(module).and_then(|()| z1_effects::check_imports(module, imports))
+    {
+        let span = match &err {
+            z1_effects::EffectError::MissingCapability { fn_span, .. }
+            | z1_effects::EffectError::UnknownEffect { fn_span, .. } => *fn_span,
+            z1_effects::EffectError::ImportedEffect { import_span, .. } => *import_span,
+        };
+        diagnostics.push(Diagnostic::error(Stage::Effects, Some("E001"), &err).at(span));
+        return false;
+    }
+    diagnostics.extend(
+        z1_effects::collect_effect_warnings(module)
+            .iter()
+            .map(|warning| {
+                Diagnostic::warning(Stage::Effects, Some("W001"), warning).at(warning.span())
+            }),
+    );
+
+    if let Err(err) = z1_ctx::estimate_cell(module) {
+        let mut diagnostic = Diagnostic::error(Stage::Ctx, Some("C001"), &err);
+        if let z1_ctx::CtxError::BudgetExceeded { span, .. }
+        | z1_ctx::CtxError::FnBudgetExceeded { span, .. } = &err
+        {
+            diagnostic.span = Some(*span);
+        }
+        diagnostics.push(diagnostic);
+        return false;
+    }
+
+    let checker = z1_policy::PolicyChecker::new(options.policy.clone());
+    if let Err(violations) = checker.check_module(module) {
+        diagnostics.extend(violations.iter().map(|violation| {
+            Diagnostic::error(Stage::Policy, Some("L001"), violation)
+                .at(violation_span(violation, module))
+        }));
+        return false;
+    }
+    true
+}
+
+fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity == Severity::Error)
+}
+
+fn type_error_span(err: &z1_typeck::TypeError) -> Option<Span> {
+    use z1_typeck::TypeError;
+    match err {
+        TypeError::Mismatch { span, .. }
+        | TypeError::UndefinedType { span, .. }
+        | TypeError::UndefinedFunction { span, .. }
+        | TypeError::UndefinedVariable { span, .. }
+        | TypeError::ArityMismatch { span, .. }
+        | TypeError::UnknownImport { span, .. } => Some(*span),
+        _ => None,
+    }
+}
+
+/// The function `violation` names, or else the module header.
+fn violation_span(violation: &PolicyViolation, module: &Module) -> Span {
+    violation
+        .fn_name()
+        .and_then(|name| {
+            module.items.iter().find_map(|item| match item {
+                Item::Fn(decl) if decl.name == name => Some(decl.span),
+                _ => None,
+            })
+        })
+        .unwrap_or(module.span)
+}
+
+/// Refuse a `target` the cell does not declare in `targets=[..]`, or a
+/// toolchain older than its `toolchain>=` requirement.
+pub fn check_requirements(module: &Module, target: Target) -> Result<(), String> {
+    let name = target.tag();
+    if !module.targets.is_empty() && !module.targets.iter().any(|declared| declared == name) {
+        return Err(format!(
+            "cell only builds for targets=[{}], not {name}",
+            module.targets.join(",")
+        ));
+    }
+    if let Some(required) = &module.toolchain {
+        let segments = |version: &str| -> Vec<u64> {
+            version
+                .split('.')
+                .map(|segment| segment.parse().unwrap_or(0))
+                .collect()
+        };
+        let (mut current, mut required_segments) =
+            (segments(env!("CARGO_PKG_VERSION")), segments(required));
+        let len = current.len().max(required_segments.len());
+        current.resize(len, 0);
+        required_segments.resize(len, 0);
+        if current < required_segments {
+            return Err(format!(
+                "cell requires toolchain>={required}, but this is z1 {}",
+                env!("CARGO_PKG_VERSION")
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use z1_driver::{ArtifactKind, CompileOptions, Compiler, Severity, Stage, Target};
+
+fn compiler(target: Target) -> Compiler {
+    Compiler::new(CompileOptions {
+        target,
+        ..CompileOptions::default()
+    })
+}
+
+#[test]
+fn compiles_to_typescript_with_declarations_and_shims() {
+    let source = "m app:1.0 caps=[net]\nu \"std/http\" as H only [listen, Req]\n\
+                  f start(port: U16)->Unit eff [net] { ret Unit; }\n";
+    let result = compiler(Target::TypeScript).compile_source(source);
+    assert!(result.is_ok(), "{:?}", result.diagnostics);
+    assert!(result.ir.is_some());
+
+    let files: Vec<(ArtifactKind, &str)> = result
+        .artifacts
+        .iter()
+        .map(|artifact| (artifact.kind, artifact.file_name.as_str()))
+        .collect();
+    assert_eq!(
+        files,
+        [
+            (ArtifactKind::Output, "app.ts"),
+            (ArtifactKind::Declarations, "app.d.ts"),
+            (ArtifactKind::Shim, "std_http.ts"),
+        ]
+    );
+    let code = String::from_utf8(result.output().unwrap().contents.clone()).unwrap();
+    assert!(code.contains("start"), "{code}");
+}
+
+#[test]
+fn compiles_to_wasm_binary_and_text() {
+    let source = "m app:1.0\nf main()->U32 eff [pure] { ret 1; }\n";
+    let binary = compiler(Target::Wasm).compile_source(source);
+    assert!(binary.output().unwrap().contents.starts_with(b"\0asm"));
+    assert_eq!(binary.output().unwrap().file_name, "app.wasm");
+
+    let text = compiler(Target::Wat).compile_source(source);
+    let wat = String::from_utf8(text.output().unwrap().contents.clone()).unwrap();
+    assert!(wat.contains("(module"), "{wat}");
+}
+
+#[test]
+fn stops_at_the_first_failing_stage() {
+    let result = compiler(Target::TypeScript).compile_source("m app:1.0\nf main(->U32\n");
+    assert_eq!(result.diagnostics.len(), 1);
+    assert_eq!(result.diagnostics[0].stage, Stage::Parse);
+    assert_eq!(result.diagnostics[0].code, Some("P001"));
+    assert!(result.module.is_none());
+
+    let source = "m app:1.0\nu \"std/http\" as H only [nope]\n\
+                  f main()->U32 eff [pure] { ret 1; }\n";
+    let result = compiler(Target::TypeScript).compile_source(source);
+    let errors: Vec<_> = result.errors().collect();
+    assert_eq!(errors.len(), 1, "{:?}", result.diagnostics);
+    assert_eq!(errors[0].stage, Stage::Typeck);
+    assert_eq!(errors[0].code, Some("T001"));
+    let span = errors[0].span.expect("span");
+    assert!(source[span.start as usize..].starts_with("u \"std/http\""));
+    assert!(result.ir.is_none() && result.artifacts.is_empty());
+}
+
+#[test]
+fn reports_warnings_without_failing() {
+    let source = "m app:1.0 caps=[net]\nf main()->U32 eff [pure] { ret 1; }\n";
+    let result = compiler(Target::TypeScript).compile_source(source);
+    assert!(result.is_ok());
+    let warning = result.warnings().next().expect("unused capability");
+    assert_eq!(warning.severity, Severity::Warning);
+    assert_eq!(warning.code, Some("W001"));
+    assert!(result.output().is_some());
+}
+
+#[test]
+fn resolves_only_std_imports_without_a_resolver() {
+    let source = "m app:1.0\nu \"std/nope\" only [x]\nf main()->U32 eff [pure] { ret 1; }\n";
+    let result = compiler(Target::TypeScript).compile_source(source);
+    let error = result.errors().next().expect("unknown std cell");
+    assert_eq!(error.stage, Stage::Resolve);
+    assert!(error.message.contains("`std/nope`"), "{}", error.message);
+
+    let opaque = source.replace("\"std/nope\"", "\"util\"");
+    assert!(compiler(Target::TypeScript).compile_source(&opaque).is_ok());
+}
+
+#[cfg(feature = "fs")]
+#[test]
+fn resolves_imports_through_a_resolver() {
+    use std::collections::BTreeMap;
+    use z1_driver::Resolver;
+
+    let dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(
+        dir.path().join("util.z1c"),
+        "m util:1.0\nf one()->U32 eff [pure] { ret 1; }\n",
+    )
+    .unwrap();
+    let source = "m app:1.0\nu \"util\" only [one]\nf main()->U32 eff [pure] { ret one(); }\n";
+
+    let compiler = Compiler::new(CompileOptions {
+        resolver: Some(Resolver::new(
+            vec![dir.path().to_path_buf()],
+            BTreeMap::new(),
+        )),
+        ..CompileOptions::default()
+    });
+    let result = compiler.compile_source(source);
+    assert!(result.is_ok(), "{:?}", result.diagnostics);
+
+    let missing = compiler.compile_source(&source.replace("\"util\"", "\"gone\""));
+    let error = missing.errors().next().expect("unresolved import");
+    assert_eq!(error.stage, Stage::Resolve);
+    assert_eq!(error.code, Some("R001"));
+}
+
+#[test]
+fn refuses_targets_the_cell_does_not_declare() {
+    let source = "m app:1.0 targets=[ts]\nf main()->U32 eff [pure] { ret 1; }\n";
+    assert!(compiler(Target::TypeScript).compile_source(source).is_ok());
+    let result = compiler(Target::Wasm).compile_source(source);
+    let error = result.errors().next().expect("undeclared target");
+    assert_eq!(error.stage, Stage::Policy);
+    assert_eq!(error.message, "cell only builds for targets=[ts], not wasm");
+}
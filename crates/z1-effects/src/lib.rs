@@ -3,16 +3,20 @@
 //! This module enforces the effects system and capability budgets:
 //! - Functions declare effects via `eff [...]` annotations
 //! - Modules declare capabilities via `caps=[...]` in the header
-//! - A function's effects must be a subset of the module's capabilities
+//! - A function's effects must be a subset of the module's capabilities,
+//!   except `async`, which needs no capability
 //! - Pure functions (no effects or `eff [pure]`) can be called from anywhere
 
 mod warnings;
 
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use thiserror::Error;
 use z1_ast::{FnDecl, Module, Span};
 
-pub use warnings::{collect_warnings as collect_effect_warnings, EffectWarning};
+pub use warnings::{
+    collect_warnings as collect_effect_warnings, suggest_unused_capability_fix,
+    suggest_unused_effect_fix, EffectWarning,
+};
 
 #[derive(Debug, Error)]
 pub enum EffectError {
@@ -31,6 +35,15 @@ pub enum EffectError {
         effect: String,
         fn_span: Span,
     },
+
+    #[error("Imported function '{fn_name}' from '{import}' has effect '{effect}' but module '{module}' lacks capability '{effect}'")]
+    ImportedEffect {
+        fn_name: String,
+        import: String,
+        module: String,
+        effect: String,
+        import_span: Span,
+    },
 }
 
 /// Known effect types in Zero1.
@@ -75,6 +88,12 @@ impl Effect {
             Effect::Unsafe => "unsafe",
         }
     }
+
+    /// Whether a module needs a capability for this effect. `pure` and
+    /// `async` need none: async is a language feature, not a capability.
+    pub fn needs_capability(&self) -> bool {
+        !matches!(self, Effect::Pure | Effect::Async)
+    }
 }
 
 /// Parse a capability string into an Effect.
@@ -110,7 +129,7 @@ pub fn check_module(module: &Module) -> Result<()> {
         .filter_map(|cap| parse_capability(cap))
         .collect();
 
-    let module_name = module.path.0.join(".");
+    let module_name = module.path.to_string();
 
     // Check each function
     for item in &module.items {
@@ -122,6 +141,53 @@ pub fn check_module(module: &Module) -> Result<()> {
     Ok(())
 }
 
+/// Check that functions named in `module`'s imports only use effects the
+/// module has capabilities for. `imports` holds the resolved modules keyed
+/// by import path; imports missing from it are skipped.
+///
+/// Whole-module imports are not checked: only the functions a module names
+/// in `only [...]` are known to be called.
+pub fn check_imports(module: &Module, imports: &BTreeMap<String, Module>) -> Result<()> {
+    let module_caps: HashSet<Effect> = module
+        .caps
+        .iter()
+        .filter_map(|cap| parse_capability(cap))
+        .collect();
+
+    for item in &module.items {
+        let z1_ast::Item::Import(import) = item else {
+            continue;
+        };
+        let Some(imported) = imports.get(&import.path) else {
+            continue;
+        };
+        for item in &imported.items {
+            let z1_ast::Item::Fn(fn_decl) = item else {
+                continue;
+            };
+            if !import.only.contains(&fn_decl.name) {
+                continue;
+            }
+            let missing = fn_decl
+                .effects
+                .iter()
+                .filter_map(|eff| Effect::parse(eff))
+                .find(|eff| eff.needs_capability() && !module_caps.contains(eff));
+            if let Some(effect) = missing {
+                return Err(EffectError::ImportedEffect {
+                    fn_name: fn_decl.name.to_string(),
+                    import: import.path.clone(),
+                    module: module.path.to_string(),
+                    effect: effect.as_str().to_string(),
+                    import_span: import.span,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
 /// Check a single function's effects against module capabilities.
 fn check_function(
     fn_decl: &FnDecl,
@@ -141,8 +207,8 @@ fn check_function(
             Some(eff) => fn_effects.push(eff),
             None => {
                 return Err(EffectError::UnknownEffect {
-                    fn_name: fn_decl.name.clone(),
-                    effect: eff_str.clone(),
+                    fn_name: fn_decl.name.to_string(),
+                    effect: eff_str.to_string(),
                     fn_span: fn_decl.span,
                 });
             }
@@ -156,14 +222,14 @@ fn check_function(
 
     // Check each effect is present in module capabilities
     for effect in fn_effects {
-        // Pure is always allowed
-        if effect == Effect::Pure {
+        // Pure and async are always allowed
+        if !effect.needs_capability() {
             continue;
         }
 
         if !module_caps.contains(&effect) {
             return Err(EffectError::MissingCapability {
-                fn_name: fn_decl.name.clone(),
+                fn_name: fn_decl.name.to_string(),
                 module: module_name.to_string(),
                 effect: effect.as_str().to_string(),
                 fn_span: fn_decl.span,
@@ -209,26 +275,63 @@ mod tests {
 
     fn make_module(caps: Vec<&str>, functions: Vec<FnDecl>) -> Module {
         Module {
-            path: ModulePath::from_parts(vec!["test".to_string(), "module".to_string()]),
+            path: ModulePath::from_parts(vec!["test".into(), "module".into()]),
             version: Some("1.0".to_string()),
             ctx_budget: Some(128),
             caps: caps.into_iter().map(String::from).collect(),
+            targets: Vec::new(),
+            toolchain: None,
             items: functions.into_iter().map(Item::Fn).collect(),
+            templates: Vec::new(),
+            expansions: Vec::new(),
             span: Span::new(0, 100),
         }
     }
 
     fn make_fn(name: &str, effects: Vec<&str>) -> FnDecl {
         FnDecl {
-            name: name.to_string(),
+            name: name.into(),
+            type_params: vec![],
             params: vec![],
-            ret: TypeExpr::Path(vec!["Unit".to_string()]),
-            effects: effects.into_iter().map(String::from).collect(),
+            ret: TypeExpr::Path(vec!["Unit".into()]),
+            effects: effects.into_iter().map(Into::into).collect(),
             body: Block::default(),
             span: Span::new(0, 10),
+            doc: None,
         }
     }
 
+    #[test]
+    fn test_imported_function_effects_need_caps() {
+        let server = make_module(vec!["net"], vec![make_fn("listen", vec!["net"])]);
+        let imports: BTreeMap<String, Module> = [("std/http/server".to_string(), server)]
+            .into_iter()
+            .collect();
+        let import = |only: Vec<&str>| {
+            Item::Import(z1_ast::Import {
+                path: "std/http/server".to_string(),
+                alias: None,
+                only: only.into_iter().map(Into::into).collect(),
+                span: Span::new(0, 10),
+            })
+        };
+
+        let mut module = make_module(vec![], vec![]);
+        module.items.push(import(vec!["listen"]));
+        assert!(matches!(
+            check_imports(&module, &imports),
+            Err(EffectError::ImportedEffect { effect, .. }) if effect == "net"
+        ));
+
+        module.caps.push("net".to_string());
+        assert!(check_imports(&module, &imports).is_ok());
+
+        // Whole-module imports do not name what is called
+        let mut module = make_module(vec![], vec![]);
+        module.items.push(import(vec![]));
+        assert!(check_imports(&module, &imports).is_ok());
+    }
+
     #[test]
     fn test_pure_function_no_caps_needed() {
         let module = make_module(vec![], vec![make_fn("pure_fn", vec!["pure"])]);
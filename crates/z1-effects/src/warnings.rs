@@ -84,7 +84,7 @@ pub fn collect_warnings(module: &Module) -> Vec<EffectWarning> {
     }
 
     // Check for unused capabilities
-    let module_name = module.path.0.join(".");
+    let module_name = module.path.to_string();
     for cap in &module.caps {
         if let Some(base_cap) = parse_capability_base(cap) {
             if !used_effects.contains(&base_cap) && base_cap != Effect::Pure {
@@ -114,7 +114,7 @@ fn check_function_warnings(fn_decl: &FnDecl) -> Vec<EffectWarning> {
     // Check for async effect without async operations
     // Note: Full implementation would require analyzing function body
     // For MVP, this is a simplified check
-    if fn_decl.effects.contains(&"async".to_string()) {
+    if fn_decl.effects.contains(&"async".into()) {
         // In a full implementation, we'd check if the body contains any await expressions
         // For now, this is a placeholder
     }
@@ -132,7 +132,6 @@ fn parse_capability_base(cap: &str) -> Option<Effect> {
 }
 
 /// Suggest a fix for unused capability.
-#[allow(dead_code)]
 pub fn suggest_unused_capability_fix(capability: &str, module: &str) -> String {
     format!(
         "Remove '{capability}' from module capabilities if not needed: module {module} caps=[...]"
@@ -140,7 +139,6 @@ pub fn suggest_unused_capability_fix(capability: &str, module: &str) -> String {
 }
 
 /// Suggest a fix for unused effect.
-#[allow(dead_code)]
 pub fn suggest_unused_effect_fix(effect: &str) -> String {
     format!("Remove '{effect}' from effect list if not used")
 }
@@ -191,20 +189,26 @@ mod tests {
         use z1_ast::{Block, Item, ModulePath, TypeExpr};
 
         let fn_decl = FnDecl {
-            name: "test".to_string(),
+            name: "test".into(),
+            type_params: vec![],
             params: vec![],
-            ret: TypeExpr::Path(vec!["Unit".to_string()]),
-            effects: vec!["net".to_string()],
+            ret: TypeExpr::Path(vec!["Unit".into()]),
+            effects: vec!["net".into()],
             body: Block::default(),
             span: Span::new(0, 10),
+            doc: None,
         };
 
         let module = Module {
-            path: ModulePath::from_parts(vec!["test".to_string()]),
+            path: ModulePath::from_parts(vec!["test".into()]),
             version: None,
             ctx_budget: None,
             caps: vec!["net".to_string()],
+            targets: Vec::new(),
+            toolchain: None,
             items: vec![Item::Fn(fn_decl)],
+            templates: Vec::new(),
+            expansions: Vec::new(),
             span: Span::new(0, 100),
         };
 
@@ -224,20 +228,26 @@ mod tests {
         use z1_ast::{Block, Item, ModulePath, TypeExpr};
 
         let fn_decl = FnDecl {
-            name: "test".to_string(),
+            name: "test".into(),
+            type_params: vec![],
             params: vec![],
-            ret: TypeExpr::Path(vec!["Unit".to_string()]),
+            ret: TypeExpr::Path(vec!["Unit".into()]),
             effects: vec![], // No effects
             body: Block::default(),
             span: Span::new(0, 10),
+            doc: None,
         };
 
         let module = Module {
-            path: ModulePath::from_parts(vec!["test".to_string()]),
+            path: ModulePath::from_parts(vec!["test".into()]),
             version: None,
             ctx_budget: None,
             caps: vec!["net".to_string(), "time".to_string()], // Unused capabilities
+            targets: Vec::new(),
+            toolchain: None,
             items: vec![Item::Fn(fn_decl)],
+            templates: Vec::new(),
+            expansions: Vec::new(),
             span: Span::new(0, 100),
         };
 
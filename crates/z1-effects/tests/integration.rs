@@ -5,31 +5,37 @@ use z1_effects::{check_module, EffectError};
 
 fn make_module_with_caps(caps: Vec<&str>, functions: Vec<FnDecl>) -> Module {
     Module {
-        path: ModulePath::from_parts(vec!["http".to_string(), "server".to_string()]),
+        path: ModulePath::from_parts(vec!["http".into(), "server".into()]),
         version: Some("1.0".to_string()),
         ctx_budget: Some(128),
         caps: caps.into_iter().map(String::from).collect(),
+        targets: Vec::new(),
+        toolchain: None,
         items: functions.into_iter().map(Item::Fn).collect(),
+        templates: Vec::new(),
+        expansions: Vec::new(),
         span: Span::new(0, 200),
     }
 }
 
 fn make_fn_with_effects(name: &str, effects: Vec<&str>, span: Span) -> FnDecl {
     FnDecl {
-        name: name.to_string(),
+        name: name.into(),
+        type_params: vec![],
         params: vec![Param {
-            name: "arg".to_string(),
-            ty: TypeExpr::Path(vec!["U16".to_string()]),
+            name: "arg".into(),
+            ty: TypeExpr::Path(vec!["U16".into()]),
             span,
         }],
-        ret: TypeExpr::Path(vec!["Unit".to_string()]),
-        effects: effects.into_iter().map(String::from).collect(),
+        ret: TypeExpr::Path(vec!["Unit".into()]),
+        effects: effects.into_iter().map(Into::into).collect(),
         body: Block {
             raw: "{ ret Unit; }".to_string(),
             statements: vec![],
             span,
         },
         span,
+        doc: None,
     }
 }
 
@@ -213,6 +219,29 @@ fn test_all_effect_types() {
     }
 }
 
+#[test]
+fn test_async_needs_no_capability() {
+    // Like std.time.core's sleep: f slp(seconds: U32)->Unit eff [time, async]
+    let functions = vec![make_fn_with_effects(
+        "sleep",
+        vec!["time", "async"],
+        Span::new(50, 100),
+    )];
+    let module = make_module_with_caps(vec!["time"], functions);
+    assert!(check_module(&module).is_ok());
+
+    let functions = vec![make_fn_with_effects(
+        "sleep",
+        vec!["time", "async"],
+        Span::new(50, 100),
+    )];
+    let module = make_module_with_caps(vec![], functions);
+    assert!(matches!(
+        check_module(&module),
+        Err(EffectError::MissingCapability { effect, .. }) if effect == "time"
+    ));
+}
+
 #[test]
 fn test_case_insensitive_effect_matching() {
     let functions = vec![make_fn_with_effects("fn1", vec!["Net"], Span::new(50, 100))];
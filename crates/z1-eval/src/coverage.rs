@@ -0,0 +1,134 @@
+//! Statement coverage
+//!
+//! The statements of a function are numbered in pre-order: a statement comes
+//! before the statements of its blocks, and a `then` block before its `else`
+//! block. Lowering keeps one IR statement per AST statement (an `else if`
+//! being the nested `if`), so the numbers also index the AST statements and
+//! front ends can map hits back to source lines.
+
+use std::collections::HashMap;
+
+use z1_ir::{Ident, IrBlock, IrModule, IrStmt, Symbol};
+
+/// How often the functions of a module and their statements ran.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    functions: HashMap<Ident, FunctionHits>,
+}
+
+/// Hits of one function.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FunctionHits {
+    /// Times the function was called
+    pub calls: u64,
+    /// Times each statement ran, in pre-order
+    pub statements: Vec<u64>,
+}
+
+impl Coverage {
+    /// No hits yet for any function of `module`.
+    pub fn new(module: &IrModule) -> Self {
+        let functions = module
+            .functions
+            .iter()
+            .map(|func| {
+                let hits = FunctionHits {
+                    calls: 0,
+                    statements: vec![0; count(&func.body)],
+                };
+                (func.name, hits)
+            })
+            .collect();
+        Coverage { functions }
+    }
+
+    /// Hits of the function `name`.
+    pub fn function(&self, name: &str) -> Option<&FunctionHits> {
+        self.functions.get(&Symbol::intern(name))
+    }
+
+    /// Add the hits of `other`, such as a later run of the same cells.
+    pub fn merge(&mut self, other: &Coverage) {
+        for (name, hits) in &other.functions {
+            let total = self.functions.entry(*name).or_default();
+            total.calls += hits.calls;
+            if total.statements.len() < hits.statements.len() {
+                total.statements.resize(hits.statements.len(), 0);
+            }
+            for (total, hits) in total.statements.iter_mut().zip(&hits.statements) {
+                *total += hits;
+            }
+        }
+    }
+}
+
+/// Records hits while the interpreter runs.
+pub(crate) struct Tracker {
+    /// Function and pre-order number of each statement, by address
+    ids: HashMap<*const IrStmt, (Ident, usize)>,
+    pub(crate) coverage: Coverage,
+}
+
+impl Tracker {
+    pub(crate) fn new(module: &IrModule) -> Self {
+        let mut ids = HashMap::new();
+        for func in &module.functions {
+            number(&func.body, func.name, &mut ids, &mut 0);
+        }
+        Tracker {
+            ids,
+            coverage: Coverage::new(module),
+        }
+    }
+
+    pub(crate) fn call(&mut self, function: Ident) {
+        if let Some(hits) = self.coverage.functions.get_mut(&function) {
+            hits.calls += 1;
+        }
+    }
+
+    pub(crate) fn stmt(&mut self, stmt: &IrStmt) {
+        let Some((function, idx)) = self.ids.get(&(stmt as *const IrStmt)) else {
+            return;
+        };
+        if let Some(hits) = self.coverage.functions.get_mut(function) {
+            hits.statements[*idx] += 1;
+        }
+    }
+}
+
+fn number(
+    block: &IrBlock,
+    function: Ident,
+    ids: &mut HashMap<*const IrStmt, (Ident, usize)>,
+    next: &mut usize,
+) {
+    for stmt in &block.statements {
+        ids.insert(stmt as *const IrStmt, (function, *next));
+        *next += 1;
+        for block in blocks(stmt) {
+            number(block, function, ids, next);
+        }
+    }
+}
+
+fn count(block: &IrBlock) -> usize {
+    block
+        .statements
+        .iter()
+        .map(|stmt| 1 + blocks(stmt).into_iter().map(count).sum::<usize>())
+        .sum()
+}
+
+/// Blocks nested in `stmt`, in pre-order.
+fn blocks(stmt: &IrStmt) -> Vec<&IrBlock> {
+    match stmt {
+        IrStmt::If {
+            then_block,
+            else_block,
+            ..
+        } => std::iter::once(then_block).chain(else_block).collect(),
+        IrStmt::While { body, .. } => vec![body],
+        _ => Vec::new(),
+    }
+}
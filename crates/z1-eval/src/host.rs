@@ -0,0 +1,64 @@
+//! Host effect handlers
+//!
+//! Calls that do not resolve to a function of the module (imported items,
+//! `http.listen(..)`-style paths) are effects and are handed to the host by
+//! their dotted name.
+
+use std::collections::HashMap;
+
+use crate::{EvalError, Value};
+
+/// Handles calls the interpreter cannot resolve inside the module.
+pub trait Host {
+    /// Perform the host function `name` with already evaluated arguments.
+    fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, EvalError>;
+}
+
+/// A host without any handlers: every effect fails.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoHost;
+
+impl Host for NoHost {
+    fn call(&mut self, name: &str, _args: Vec<Value>) -> Result<Value, EvalError> {
+        Err(EvalError::UnhandledCall(name.to_string()))
+    }
+}
+
+type Handler = Box<dyn FnMut(Vec<Value>) -> Result<Value, EvalError>>;
+
+/// Host built from closures keyed by call name.
+///
+/// ```
+/// use z1_eval::{Handlers, Value};
+///
+/// let host = Handlers::new().on("log.info", |_args| Ok(Value::Unit));
+/// ```
+#[derive(Default)]
+pub struct Handlers {
+    handlers: HashMap<String, Handler>,
+}
+
+impl Handlers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the handler for `name`, replacing any previous one.
+    pub fn on(
+        mut self,
+        name: impl Into<String>,
+        handler: impl FnMut(Vec<Value>) -> Result<Value, EvalError> + 'static,
+    ) -> Self {
+        self.handlers.insert(name.into(), Box::new(handler));
+        self
+    }
+}
+
+impl Host for Handlers {
+    fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, EvalError> {
+        match self.handlers.get_mut(name) {
+            Some(handler) => handler(args),
+            None => Err(EvalError::UnhandledCall(name.to_string())),
+        }
+    }
+}
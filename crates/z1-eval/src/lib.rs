@@ -0,0 +1,693 @@
+//! Reference interpreter for Zero1 IR
+//!
+//! A tree-walking interpreter over [`z1_ir::IrModule`]. It gives cells a
+//! meaning independent of any backend, so `z1 run` can execute them directly
+//! and codegen output can be checked against it.
+//!
+//! - Integers wrap at the width of their type; division by zero is an error.
+//! - Strings and records are values: `==` compares contents.
+//! - Calls to functions outside the module are effects, performed by a
+//!   [`Host`].
+//!
+//! With [`Interpreter::track_coverage`], the calls and statements run are
+//! counted into a [`Coverage`]. [`EvalOptions`] bound the steps, time and
+//! memory a call may take.
+
+mod coverage;
+mod host;
+mod value;
+
+pub use coverage::{Coverage, FunctionHits};
+pub use host::{Handlers, Host, NoHost};
+pub use value::Value;
+
+use coverage::Tracker;
+use std::collections::HashMap;
+use std::time::Instant;
+use thiserror::Error;
+use z1_ir::*;
+
+/// Errors raised while evaluating a cell.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum EvalError {
+    #[error("unknown function `{0}`")]
+    UnknownFunction(String),
+    #[error("unknown variable `{0}`")]
+    UnknownVariable(String),
+    #[error("`{function}` expects {expected} argument(s), got {found}")]
+    Arity {
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("type error: {0}")]
+    Type(String),
+    #[error("no field `{0}`")]
+    NoField(String),
+    #[error("division by zero")]
+    DivisionByZero,
+    #[error("`{0}` finished without returning a value")]
+    MissingReturn(String),
+    #[error("no handler for host function `{0}`")]
+    UnhandledCall(String),
+    #[error("host function `{name}` failed: {message}")]
+    Host { name: String, message: String },
+    #[error("invalid argument `{text}` for type {ty}")]
+    InvalidArgument { text: String, ty: String },
+    #[error("call depth limit of {0} exceeded")]
+    DepthLimit(usize),
+    #[error("step limit of {0} exceeded")]
+    StepLimit(u64),
+    #[error("time limit exceeded")]
+    TimeLimit,
+    #[error("memory limit of {0} bytes exceeded")]
+    MemoryLimit(usize),
+}
+
+/// Resource limits for evaluation.
+#[derive(Debug, Clone)]
+pub struct EvalOptions {
+    /// Maximum nesting of calls
+    pub max_call_depth: usize,
+    /// Maximum statements and loop iterations executed (`None` = unlimited)
+    pub max_steps: Option<u64>,
+    /// Time after which evaluation stops (`None` = unlimited)
+    pub deadline: Option<Instant>,
+    /// Maximum bytes held by the variables of all calls, as measured by
+    /// [`Value::size`] (`None` = unlimited)
+    pub max_memory: Option<usize>,
+}
+
+impl Default for EvalOptions {
+    fn default() -> Self {
+        Self {
+            max_call_depth: 128,
+            max_steps: None,
+            deadline: None,
+            max_memory: None,
+        }
+    }
+}
+
+/// Steps between two checks of the deadline
+const DEADLINE_INTERVAL: u64 = 256;
+
+/// Tree-walking interpreter over a module.
+pub struct Interpreter<'m, 'h> {
+    module: &'m IrModule,
+    functions: HashMap<&'m str, &'m IrFunction>,
+    types: HashMap<Ident, IrType>,
+    host: &'h mut dyn Host,
+    options: EvalOptions,
+    depth: usize,
+    steps: u64,
+    /// Bytes held by the variables of the calls in progress
+    memory: usize,
+    coverage: Option<Tracker>,
+}
+
+/// Local variables of one call, innermost block last.
+struct Frame {
+    scopes: Vec<HashMap<Ident, Value>>,
+}
+
+impl Frame {
+    fn lookup(&self, name: Ident) -> Result<&Value, EvalError> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name))
+            .ok_or_else(|| EvalError::UnknownVariable(name.to_string()))
+    }
+
+    fn lookup_mut(&mut self, name: Ident) -> Result<&mut Value, EvalError> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(&name))
+            .ok_or_else(|| EvalError::UnknownVariable(name.to_string()))
+    }
+}
+
+enum Flow {
+    Next,
+    Return(Value),
+}
+
+impl<'m, 'h> Interpreter<'m, 'h> {
+    pub fn new(module: &'m IrModule, host: &'h mut dyn Host) -> Self {
+        Self::with_options(module, host, EvalOptions::default())
+    }
+
+    pub fn with_options(
+        module: &'m IrModule,
+        host: &'h mut dyn Host,
+        options: EvalOptions,
+    ) -> Self {
+        Interpreter {
+            module,
+            functions: module
+                .functions
+                .iter()
+                .map(|f| (f.name.as_str(), f))
+                .collect(),
+            types: module
+                .types
+                .iter()
+                .map(|t| (t.name, t.ty.clone()))
+                .collect(),
+            host,
+            options,
+            depth: 0,
+            steps: 0,
+            memory: 0,
+            coverage: None,
+        }
+    }
+
+    /// Count the calls and statements run from now on.
+    pub fn track_coverage(mut self) -> Self {
+        self.coverage = Some(Tracker::new(self.module));
+        self
+    }
+
+    /// Hits counted since [`Interpreter::track_coverage`].
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref().map(|tracker| &tracker.coverage)
+    }
+
+    /// Type definitions of the module, for [`Value::parse`].
+    pub fn types(&self) -> &HashMap<Ident, IrType> {
+        &self.types
+    }
+
+    /// Call the module function `name`.
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, EvalError> {
+        let func = *self
+            .functions
+            .get(name)
+            .ok_or_else(|| EvalError::UnknownFunction(name.to_string()))?;
+        if args.len() != func.params.len() {
+            return Err(EvalError::Arity {
+                function: name.to_string(),
+                expected: func.params.len(),
+                found: args.len(),
+            });
+        }
+        if self.depth >= self.options.max_call_depth {
+            return Err(EvalError::DepthLimit(self.options.max_call_depth));
+        }
+
+        let mut locals = HashMap::new();
+        for ((param, ty), arg) in func.params.iter().zip(args) {
+            locals.insert(*param, arg.coerce(ty, &self.types)?);
+        }
+        let held = locals.values().map(Value::size).sum();
+        let mut frame = Frame {
+            scopes: vec![locals],
+        };
+        if let Some(tracker) = &mut self.coverage {
+            tracker.call(func.name);
+        }
+
+        self.depth += 1;
+        let flow = self
+            .hold(held, 0)
+            .and_then(|()| self.exec_block(&mut frame, &func.body));
+        self.depth -= 1;
+        for scope in &frame.scopes {
+            self.release(scope);
+        }
+
+        match flow? {
+            Flow::Return(value) => value.coerce(&func.return_type, &self.types),
+            Flow::Next if func.return_type == IrType::Unit => Ok(Value::Unit),
+            Flow::Next => Err(EvalError::MissingReturn(name.to_string())),
+        }
+    }
+
+    fn step(&mut self) -> Result<(), EvalError> {
+        self.steps += 1;
+        if let Some(max) = self.options.max_steps {
+            if self.steps > max {
+                return Err(EvalError::StepLimit(max));
+            }
+        }
+        match self.options.deadline {
+            Some(deadline) if self.steps % DEADLINE_INTERVAL == 0 && Instant::now() >= deadline => {
+                Err(EvalError::TimeLimit)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Account for `added` bytes bound to variables and `removed` dropped.
+    fn hold(&mut self, added: usize, removed: usize) -> Result<(), EvalError> {
+        self.memory = (self.memory + added).saturating_sub(removed);
+        match self.options.max_memory {
+            Some(max) if self.memory > max => Err(EvalError::MemoryLimit(max)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Drop the variables of a scope that ended.
+    fn release(&mut self, scope: &HashMap<Ident, Value>) {
+        let size: usize = scope.values().map(Value::size).sum();
+        self.memory = self.memory.saturating_sub(size);
+    }
+
+    fn exec_block(&mut self, frame: &mut Frame, block: &IrBlock) -> Result<Flow, EvalError> {
+        frame.scopes.push(HashMap::new());
+        let result = self.exec_stmts(frame, &block.statements);
+        if let Some(scope) = frame.scopes.pop() {
+            self.release(&scope);
+        }
+        result
+    }
+
+    fn exec_stmts(&mut self, frame: &mut Frame, stmts: &[IrStmt]) -> Result<Flow, EvalError> {
+        for stmt in stmts {
+            self.step()?;
+            if let Some(tracker) = &mut self.coverage {
+                tracker.stmt(stmt);
+            }
+            if let Flow::Return(value) = self.exec_stmt(frame, stmt)? {
+                return Ok(Flow::Return(value));
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    fn exec_stmt(&mut self, frame: &mut Frame, stmt: &IrStmt) -> Result<Flow, EvalError> {
+        match stmt {
+            IrStmt::Let {
+                name, ty, value, ..
+            } => {
+                let mut value = self.eval(frame, value)?;
+                if let Some(ty) = ty {
+                    value = value.coerce(ty, &self.types)?;
+                }
+                let added = value.size();
+                let shadowed = frame
+                    .scopes
+                    .last_mut()
+                    .expect("blocks always push a scope")
+                    .insert(*name, value);
+                self.hold(added, shadowed.as_ref().map_or(0, Value::size))?;
+            }
+            IrStmt::Assign { target, value } => {
+                let value = self.eval(frame, value)?;
+                let slot = place(frame, target)?;
+                let value = retype(value, slot)?;
+                let (added, removed) = (value.size(), slot.size());
+                *slot = value;
+                self.hold(added, removed)?;
+            }
+            IrStmt::If {
+                cond,
+                then_block,
+                else_block,
+            } => {
+                if self.eval(frame, cond)?.as_bool()? {
+                    return self.exec_block(frame, then_block);
+                } else if let Some(else_block) = else_block {
+                    return self.exec_block(frame, else_block);
+                }
+            }
+            IrStmt::While { cond, body } => {
+                while self.eval(frame, cond)?.as_bool()? {
+                    self.step()?;
+                    if let Flow::Return(value) = self.exec_block(frame, body)? {
+                        return Ok(Flow::Return(value));
+                    }
+                }
+            }
+            IrStmt::Return { value } => {
+                let value = match value {
+                    Some(expr) => self.eval(frame, expr)?,
+                    None => Value::Unit,
+                };
+                return Ok(Flow::Return(value));
+            }
+            IrStmt::Expr(expr) => {
+                self.eval(frame, expr)?;
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    fn eval(&mut self, frame: &mut Frame, expr: &IrExpr) -> Result<Value, EvalError> {
+        match expr {
+            IrExpr::Var(name) => frame.lookup(*name).cloned(),
+            IrExpr::Path(segments) if segments.len() == 1 => frame.lookup(segments[0]).cloned(),
+            IrExpr::Path(segments) => Err(EvalError::UnknownVariable(Symbol::join(segments, "."))),
+            IrExpr::Literal(lit) => Ok(Value::from_literal(lit)),
+            IrExpr::BinOp {
+                op: op @ (IrBinOp::And | IrBinOp::Or),
+                left,
+                right,
+            } => {
+                let left = self.eval(frame, left)?.as_bool()?;
+                // Short-circuit: the right operand decides only when needed
+                if left == (*op == IrBinOp::Or) {
+                    return Ok(Value::Bool(left));
+                }
+                Ok(Value::Bool(self.eval(frame, right)?.as_bool()?))
+            }
+            IrExpr::BinOp { op, left, right } => {
+                let left = self.eval(frame, left)?;
+                let right = self.eval(frame, right)?;
+                value::binop(*op, left, right)
+            }
+            IrExpr::UnaryOp { op, expr } => {
+                let value = self.eval(frame, expr)?;
+                match (op, value) {
+                    (IrUnaryOp::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+                    (IrUnaryOp::Neg, Value::Int(n)) => Ok(Value::Int(n.wrapping_neg())),
+                    (IrUnaryOp::Neg, Value::U16(n)) => Ok(Value::U16(n.wrapping_neg())),
+                    (IrUnaryOp::Neg, Value::U32(n)) => Ok(Value::U32(n.wrapping_neg())),
+                    (IrUnaryOp::Neg, Value::U64(n)) => Ok(Value::U64(n.wrapping_neg())),
+                    // Host calls complete synchronously
+                    (IrUnaryOp::Await, value) => Ok(value),
+                    (op, value) => Err(EvalError::Type(format!("cannot apply {op:?} to {value}"))),
+                }
+            }
+            IrExpr::Call { func, args } => {
+                let name = callee_name(func).ok_or_else(|| {
+                    EvalError::Type("only named functions can be called".to_string())
+                })?;
+                let args = args
+                    .iter()
+                    .map(|arg| self.eval(frame, arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if self.functions.contains_key(name.as_str()) {
+                    self.call(&name, args)
+                } else {
+                    self.host.call(&name, args)
+                }
+            }
+            IrExpr::Field { base, field } => {
+                let base = self.eval(frame, base)?;
+                base.field(field).cloned()
+            }
+            IrExpr::Record { fields } => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, value)| Ok((*name, self.eval(frame, value)?)))
+                    .collect::<Result<Vec<_>, EvalError>>()?;
+                Ok(Value::Record(fields))
+            }
+            IrExpr::RecordUpdate { base, fields } => {
+                let mut record = self.eval(frame, base)?;
+                if !matches!(record, Value::Record(_)) {
+                    return Err(EvalError::Type(format!("{record} has no fields")));
+                }
+                for (name, value) in fields {
+                    let value = self.eval(frame, value)?;
+                    *record.field_mut(name)? = value;
+                }
+                Ok(record)
+            }
+        }
+    }
+}
+
+/// Dotted name of a call target: `f`, `http.listen`.
+fn callee_name(expr: &IrExpr) -> Option<String> {
+    match expr {
+        IrExpr::Var(name) => Some(name.to_string()),
+        IrExpr::Path(segments) => Some(Symbol::join(segments, ".")),
+        IrExpr::Field { base, field } => Some(format!("{}.{field}", callee_name(base)?)),
+        _ => None,
+    }
+}
+
+/// The storage an assignment target refers to.
+fn place<'f>(frame: &'f mut Frame, target: &IrExpr) -> Result<&'f mut Value, EvalError> {
+    match target {
+        IrExpr::Var(name) => frame.lookup_mut(*name),
+        IrExpr::Path(segments) if segments.len() == 1 => frame.lookup_mut(segments[0]),
+        IrExpr::Field { base, field } => place(frame, base)?.field_mut(field),
+        other => Err(EvalError::Type(format!("cannot assign to {other:?}"))),
+    }
+}
+
+/// Keep the integer width of the value being replaced.
+fn retype(value: Value, old: &Value) -> Result<Value, EvalError> {
+    let ty = match old {
+        Value::U16(_) => IrType::U16,
+        Value::U32(_) => IrType::U32,
+        Value::U64(_) => IrType::U64,
+        _ => return Ok(value),
+    };
+    value.coerce(&ty, &HashMap::new())
+}
+
+/// Call `entry` in `module` with `args`, performing effects through `host`.
+pub fn run(
+    module: &IrModule,
+    entry: &str,
+    args: Vec<Value>,
+    host: &mut dyn Host,
+) -> Result<Value, EvalError> {
+    Interpreter::new(module, host).call(entry, args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(
+        name: &str,
+        params: &[(&str, IrType)],
+        ret: IrType,
+        body: Vec<IrStmt>,
+    ) -> IrFunction {
+        IrFunction {
+            name: name.into(),
+            type_params: vec![],
+            params: params
+                .iter()
+                .map(|(n, ty)| ((*n).into(), ty.clone()))
+                .collect(),
+            return_type: ret,
+            effects: vec![],
+            body: IrBlock { statements: body },
+        }
+    }
+
+    fn module(functions: Vec<IrFunction>) -> IrModule {
+        IrModule {
+            name: "test".to_string(),
+            version: "1.0".to_string(),
+            imports: vec![],
+            types: vec![],
+            functions,
+            exports: vec![],
+        }
+    }
+
+    fn var(name: &str) -> Box<IrExpr> {
+        Box::new(IrExpr::Var(name.into()))
+    }
+
+    fn int(n: i64) -> Box<IrExpr> {
+        Box::new(IrExpr::Literal(IrLiteral::Int(n)))
+    }
+
+    fn ret(value: IrExpr) -> IrStmt {
+        IrStmt::Return { value: Some(value) }
+    }
+
+    #[test]
+    fn test_untyped_literals_take_the_parameter_type() {
+        let m = module(vec![function(
+            "inc",
+            &[("x", IrType::U16)],
+            IrType::U16,
+            vec![ret(IrExpr::BinOp {
+                op: IrBinOp::Add,
+                left: var("x"),
+                right: int(1),
+            })],
+        )]);
+        assert_eq!(
+            run(&m, "inc", vec![Value::U16(41)], &mut NoHost),
+            Ok(Value::U16(42))
+        );
+        // Wraps at the type width
+        assert_eq!(
+            run(&m, "inc", vec![Value::U16(u16::MAX)], &mut NoHost),
+            Ok(Value::U16(0))
+        );
+    }
+
+    #[test]
+    fn test_while_loop_and_assignment() {
+        // sum = 0; i = 0; while i < n { i = i + 1; sum = sum + i }; return sum
+        let m = module(vec![function(
+            "sum",
+            &[("n", IrType::U32)],
+            IrType::U32,
+            vec![
+                IrStmt::Let {
+                    name: "sum".into(),
+                    mutable: true,
+                    ty: Some(IrType::U32),
+                    value: *int(0),
+                },
+                IrStmt::Let {
+                    name: "i".into(),
+                    mutable: true,
+                    ty: Some(IrType::U32),
+                    value: *int(0),
+                },
+                IrStmt::While {
+                    cond: IrExpr::BinOp {
+                        op: IrBinOp::Lt,
+                        left: var("i"),
+                        right: var("n"),
+                    },
+                    body: IrBlock {
+                        statements: vec![
+                            IrStmt::Assign {
+                                target: *var("i"),
+                                value: IrExpr::BinOp {
+                                    op: IrBinOp::Add,
+                                    left: var("i"),
+                                    right: int(1),
+                                },
+                            },
+                            IrStmt::Assign {
+                                target: *var("sum"),
+                                value: IrExpr::BinOp {
+                                    op: IrBinOp::Add,
+                                    left: var("sum"),
+                                    right: var("i"),
+                                },
+                            },
+                        ],
+                    },
+                },
+                ret(*var("sum")),
+            ],
+        )]);
+        assert_eq!(
+            run(&m, "sum", vec![Value::U32(10)], &mut NoHost),
+            Ok(Value::U32(55))
+        );
+
+        // Statements in pre-order: let, let, while, its two assignments, ret
+        let mut host = NoHost;
+        let mut interp = Interpreter::new(&m, &mut host).track_coverage();
+        interp.call("sum", vec![Value::U32(3)]).unwrap();
+        interp.call("sum", vec![Value::U32(0)]).unwrap();
+        let mut coverage = interp.coverage().unwrap().clone();
+        let hits = coverage.function("sum").unwrap();
+        assert_eq!(hits.calls, 2);
+        assert_eq!(hits.statements, [2, 2, 2, 3, 3, 2]);
+
+        coverage.merge(&Coverage::new(&m));
+        assert_eq!(coverage.function("sum").unwrap().statements[3], 3);
+        let fresh = Coverage::new(&m);
+        assert_eq!(fresh.function("sum").unwrap().statements, [0; 6]);
+    }
+
+    #[test]
+    fn test_limits_and_errors() {
+        let forever = module(vec![function(
+            "spin",
+            &[],
+            IrType::Unit,
+            vec![IrStmt::While {
+                cond: IrExpr::Literal(IrLiteral::Bool(true)),
+                body: IrBlock { statements: vec![] },
+            }],
+        )]);
+        let mut host = NoHost;
+        let options = EvalOptions {
+            max_steps: Some(100),
+            ..EvalOptions::default()
+        };
+        let mut interp = Interpreter::with_options(&forever, &mut host, options);
+        assert_eq!(interp.call("spin", vec![]), Err(EvalError::StepLimit(100)));
+        let options = EvalOptions {
+            deadline: Some(Instant::now()),
+            ..EvalOptions::default()
+        };
+        let mut interp = Interpreter::with_options(&forever, &mut host, options);
+        assert_eq!(interp.call("spin", vec![]), Err(EvalError::TimeLimit));
+
+        // The string doubles until it holds more than the limit
+        let grow = module(vec![function(
+            "grow",
+            &[],
+            IrType::Unit,
+            vec![
+                IrStmt::Let {
+                    name: "s".into(),
+                    mutable: true,
+                    ty: None,
+                    value: IrExpr::Literal(IrLiteral::Str("ab".to_string())),
+                },
+                IrStmt::While {
+                    cond: IrExpr::Literal(IrLiteral::Bool(true)),
+                    body: IrBlock {
+                        statements: vec![IrStmt::Assign {
+                            target: *var("s"),
+                            value: IrExpr::BinOp {
+                                op: IrBinOp::Add,
+                                left: var("s"),
+                                right: var("s"),
+                            },
+                        }],
+                    },
+                },
+            ],
+        )]);
+        let options = EvalOptions {
+            max_memory: Some(1000),
+            ..EvalOptions::default()
+        };
+        let mut interp = Interpreter::with_options(&grow, &mut host, options);
+        assert_eq!(
+            interp.call("grow", vec![]),
+            Err(EvalError::MemoryLimit(1000))
+        );
+        // Variables are dropped on the way out of a failed call
+        assert_eq!(interp.memory, 0);
+
+        let recurse = module(vec![function(
+            "down",
+            &[],
+            IrType::Unit,
+            vec![IrStmt::Expr(IrExpr::Call {
+                func: var("down"),
+                args: vec![],
+            })],
+        )]);
+        assert_eq!(
+            run(&recurse, "down", vec![], &mut NoHost),
+            Err(EvalError::DepthLimit(128))
+        );
+
+        let no_return = module(vec![function("f", &[], IrType::U32, vec![])]);
+        assert_eq!(
+            run(&no_return, "f", vec![], &mut NoHost),
+            Err(EvalError::MissingReturn("f".to_string()))
+        );
+        assert_eq!(
+            run(&no_return, "g", vec![], &mut NoHost),
+            Err(EvalError::UnknownFunction("g".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_value_equality_coerces_literals() {
+        assert!(Value::U32(3).equals(&Value::Int(3)));
+        assert!(!Value::U16(3).equals(&Value::U32(3)));
+        let record = |n| Value::Record(vec![("n".into(), n)]);
+        assert!(record(Value::Int(7)).equals(&record(Value::U64(7))));
+        assert!(!record(Value::Int(7)).equals(&record(Value::Str("7".into()))));
+    }
+}
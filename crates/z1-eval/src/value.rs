@@ -0,0 +1,296 @@
+//! Runtime values
+
+use std::collections::HashMap;
+use std::fmt;
+use z1_ir::{Ident, IrBinOp, IrLiteral, IrType};
+
+use crate::EvalError;
+
+/// A runtime value.
+///
+/// Integers keep the width of their type and wrap on overflow, matching the
+/// WASM backend. `Int` holds untyped literals until they meet a typed value,
+/// parameter or binding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Bool(bool),
+    Str(String),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    Int(i64),
+    Unit,
+    /// Record fields in declaration (or literal) order
+    Record(Vec<(Ident, Value)>),
+}
+
+impl Value {
+    /// Parse a command-line argument as a value of type `ty`.
+    pub fn parse(
+        text: &str,
+        ty: &IrType,
+        types: &HashMap<Ident, IrType>,
+    ) -> Result<Value, EvalError> {
+        let invalid = || EvalError::InvalidArgument {
+            text: text.to_string(),
+            ty: format!("{ty:?}"),
+        };
+        Ok(match resolve(ty, types) {
+            IrType::Bool => Value::Bool(text.parse().map_err(|_| invalid())?),
+            IrType::Str => Value::Str(text.to_string()),
+            IrType::U16 => Value::U16(text.parse().map_err(|_| invalid())?),
+            IrType::U32 => Value::U32(text.parse().map_err(|_| invalid())?),
+            IrType::U64 => Value::U64(text.parse().map_err(|_| invalid())?),
+            IrType::Unit if text == "()" => Value::Unit,
+            _ => return Err(invalid()),
+        })
+    }
+
+    /// `==` as the interpreter evaluates it, recursing into records: an
+    /// untyped literal equals a typed integer of the same value.
+    pub fn equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Record(a), Value::Record(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b)
+                        .all(|((na, va), (nb, vb))| na == nb && va.equals(vb))
+            }
+            _ => matches!(
+                binop(IrBinOp::Eq, self.clone(), other.clone()),
+                Ok(Value::Bool(true))
+            ),
+        }
+    }
+
+    /// Approximate bytes the value holds: the bytes of integers, the text of
+    /// strings, and the field names and values of records.
+    pub fn size(&self) -> usize {
+        match self {
+            Value::Bool(_) => 1,
+            Value::Str(s) => s.len(),
+            Value::U16(_) => 2,
+            Value::U32(_) => 4,
+            Value::U64(_) | Value::Int(_) => 8,
+            Value::Unit => 0,
+            Value::Record(fields) => fields
+                .iter()
+                .map(|(name, value)| name.len() + value.size())
+                .sum(),
+        }
+    }
+
+    pub(crate) fn from_literal(lit: &IrLiteral) -> Value {
+        match lit {
+            IrLiteral::Bool(b) => Value::Bool(*b),
+            IrLiteral::Str(s) => Value::Str(s.clone()),
+            IrLiteral::U16(n) => Value::U16(*n),
+            IrLiteral::U32(n) => Value::U32(*n),
+            IrLiteral::U64(n) => Value::U64(*n),
+            IrLiteral::Int(n) => Value::Int(*n),
+            IrLiteral::Unit => Value::Unit,
+        }
+    }
+
+    /// Give untyped integers the type they are bound to.
+    pub(crate) fn coerce(
+        self,
+        ty: &IrType,
+        types: &HashMap<Ident, IrType>,
+    ) -> Result<Value, EvalError> {
+        let out_of_range = |n: i64| EvalError::Type(format!("{n} does not fit in {ty:?}"));
+        Ok(match (self, resolve(ty, types)) {
+            (Value::Int(n), IrType::U16) => {
+                Value::U16(u16::try_from(n).map_err(|_| out_of_range(n))?)
+            }
+            (Value::Int(n), IrType::U32) => {
+                Value::U32(u32::try_from(n).map_err(|_| out_of_range(n))?)
+            }
+            (Value::Int(n), IrType::U64) => {
+                Value::U64(u64::try_from(n).map_err(|_| out_of_range(n))?)
+            }
+            (Value::Record(fields), IrType::Record(declared)) => {
+                let mut fields = fields;
+                let mut ordered = Vec::with_capacity(fields.len());
+                for (name, field_ty) in declared {
+                    if let Some(idx) = fields.iter().position(|(n, _)| n == name) {
+                        let (name, value) = fields.remove(idx);
+                        ordered.push((name, value.coerce(field_ty, types)?));
+                    }
+                }
+                ordered.extend(fields);
+                Value::Record(ordered)
+            }
+            (value, _) => value,
+        })
+    }
+
+    pub(crate) fn as_bool(&self) -> Result<bool, EvalError> {
+        match self {
+            Value::Bool(b) => Ok(*b),
+            other => Err(EvalError::Type(format!("expected Bool, found {other}"))),
+        }
+    }
+
+    pub(crate) fn field(&self, field: &str) -> Result<&Value, EvalError> {
+        match self {
+            Value::Record(fields) => fields
+                .iter()
+                .find(|(name, _)| name == field)
+                .map(|(_, value)| value)
+                .ok_or_else(|| EvalError::NoField(field.to_string())),
+            other => Err(EvalError::Type(format!("{other} has no fields"))),
+        }
+    }
+
+    pub(crate) fn field_mut(&mut self, field: &str) -> Result<&mut Value, EvalError> {
+        match self {
+            Value::Record(fields) => fields
+                .iter_mut()
+                .find(|(name, _)| name == field)
+                .map(|(_, value)| value)
+                .ok_or_else(|| EvalError::NoField(field.to_string())),
+            other => Err(EvalError::Type(format!("{other} has no fields"))),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Str(s) => write!(f, "{s:?}"),
+            Value::U16(n) => write!(f, "{n}"),
+            Value::U32(n) => write!(f, "{n}"),
+            Value::U64(n) => write!(f, "{n}"),
+            Value::Int(n) => write!(f, "{n}"),
+            Value::Unit => write!(f, "()"),
+            Value::Record(fields) => {
+                write!(f, "{{ ")?;
+                for (idx, (name, value)) in fields.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{name}: {value}")?;
+                }
+                write!(f, " }}")
+            }
+        }
+    }
+}
+
+/// Follow `Named` aliases to the underlying type.
+pub(crate) fn resolve<'a>(ty: &'a IrType, types: &'a HashMap<Ident, IrType>) -> &'a IrType {
+    let mut current = ty;
+    for _ in 0..=types.len() {
+        match current {
+            IrType::Named(name) => match types.get(name) {
+                Some(next) => current = next,
+                None => return current,
+            },
+            _ => return current,
+        }
+    }
+    current
+}
+
+/// Apply an arithmetic or comparison operator.
+///
+/// `And`/`Or` short-circuit and are handled by the interpreter.
+pub(crate) fn binop(op: IrBinOp, left: Value, right: Value) -> Result<Value, EvalError> {
+    // An untyped literal takes the type of the other operand
+    let (left, right) = match (left, right) {
+        (Value::Int(n), typed @ (Value::U16(_) | Value::U32(_) | Value::U64(_))) => (
+            Value::Int(n).coerce(&int_type(&typed), &HashMap::new())?,
+            typed,
+        ),
+        (typed @ (Value::U16(_) | Value::U32(_) | Value::U64(_)), Value::Int(n)) => {
+            let ty = int_type(&typed);
+            (typed, Value::Int(n).coerce(&ty, &HashMap::new())?)
+        }
+        pair => pair,
+    };
+
+    match op {
+        IrBinOp::Eq => return Ok(Value::Bool(left == right)),
+        IrBinOp::Ne => return Ok(Value::Bool(left != right)),
+        _ => {}
+    }
+
+    match (&left, &right) {
+        (Value::U16(a), Value::U16(b)) => {
+            unsigned(op, *a as u64, *b as u64, u16::MAX as u64, |n| {
+                Value::U16(n as u16)
+            })
+        }
+        (Value::U32(a), Value::U32(b)) => {
+            unsigned(op, *a as u64, *b as u64, u32::MAX as u64, |n| {
+                Value::U32(n as u32)
+            })
+        }
+        (Value::U64(a), Value::U64(b)) => unsigned(op, *a, *b, u64::MAX, Value::U64),
+        (Value::Int(a), Value::Int(b)) => signed(op, *a, *b),
+        (Value::Str(a), Value::Str(b)) => match op {
+            IrBinOp::Add => Ok(Value::Str(format!("{a}{b}"))),
+            _ => compare(op, a.cmp(b)),
+        },
+        _ => Err(EvalError::Type(format!(
+            "cannot apply {op:?} to {left} and {right}"
+        ))),
+    }
+}
+
+fn int_type(value: &Value) -> IrType {
+    match value {
+        Value::U16(_) => IrType::U16,
+        Value::U32(_) => IrType::U32,
+        _ => IrType::U64,
+    }
+}
+
+/// Unsigned arithmetic, wrapping at `mask`.
+fn unsigned(
+    op: IrBinOp,
+    a: u64,
+    b: u64,
+    mask: u64,
+    wrap: fn(u64) -> Value,
+) -> Result<Value, EvalError> {
+    let result = match op {
+        IrBinOp::Add => a.wrapping_add(b),
+        IrBinOp::Sub => a.wrapping_sub(b),
+        IrBinOp::Mul => a.wrapping_mul(b),
+        IrBinOp::Div => a.checked_div(b).ok_or(EvalError::DivisionByZero)?,
+        IrBinOp::Mod => a.checked_rem(b).ok_or(EvalError::DivisionByZero)?,
+        _ => return compare(op, a.cmp(&b)),
+    };
+    Ok(wrap(result & mask))
+}
+
+fn signed(op: IrBinOp, a: i64, b: i64) -> Result<Value, EvalError> {
+    let check = |b: i64| {
+        if b == 0 {
+            Err(EvalError::DivisionByZero)
+        } else {
+            Ok(b)
+        }
+    };
+    Ok(Value::Int(match op {
+        IrBinOp::Add => a.wrapping_add(b),
+        IrBinOp::Sub => a.wrapping_sub(b),
+        IrBinOp::Mul => a.wrapping_mul(b),
+        IrBinOp::Div => a.wrapping_div(check(b)?),
+        IrBinOp::Mod => a.wrapping_rem(check(b)?),
+        _ => return compare(op, a.cmp(&b)),
+    }))
+}
+
+fn compare(op: IrBinOp, ordering: std::cmp::Ordering) -> Result<Value, EvalError> {
+    Ok(Value::Bool(match op {
+        IrBinOp::Lt => ordering.is_lt(),
+        IrBinOp::Le => ordering.is_le(),
+        IrBinOp::Gt => ordering.is_gt(),
+        IrBinOp::Ge => ordering.is_ge(),
+        _ => return Err(EvalError::Type(format!("{op:?} is not a comparison"))),
+    }))
+}
@@ -0,0 +1,199 @@
+//! Evaluation of records, strings and host effects.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use z1_eval::{run, EvalError, Handlers, NoHost, Value};
+use z1_ir::*;
+
+fn var(name: &str) -> Box<IrExpr> {
+    Box::new(IrExpr::Var(name.into()))
+}
+
+fn field(base: Box<IrExpr>, name: &str) -> IrExpr {
+    IrExpr::Field {
+        base,
+        field: name.into(),
+    }
+}
+
+fn add(left: IrExpr, right: IrExpr) -> IrExpr {
+    IrExpr::BinOp {
+        op: IrBinOp::Add,
+        left: Box::new(left),
+        right: Box::new(right),
+    }
+}
+
+fn int(n: i64) -> IrExpr {
+    IrExpr::Literal(IrLiteral::Int(n))
+}
+
+fn str_lit(s: &str) -> IrExpr {
+    IrExpr::Literal(IrLiteral::Str(s.to_string()))
+}
+
+fn module(types: Vec<IrTypeDef>, functions: Vec<IrFunction>) -> IrModule {
+    IrModule {
+        name: "test".to_string(),
+        version: "1.0".to_string(),
+        imports: vec![],
+        types,
+        functions,
+        exports: vec![],
+    }
+}
+
+fn function(name: &str, params: Vec<(&str, IrType)>, ret: IrType, body: Vec<IrStmt>) -> IrFunction {
+    IrFunction {
+        name: name.into(),
+        type_params: vec![],
+        params: params.into_iter().map(|(n, ty)| (n.into(), ty)).collect(),
+        return_type: ret,
+        effects: vec![],
+        body: IrBlock { statements: body },
+    }
+}
+
+fn point_type() -> IrTypeDef {
+    IrTypeDef {
+        name: "Point".into(),
+        type_params: vec![],
+        ty: IrType::Record(vec![("x".into(), IrType::U32), ("y".into(), IrType::U64)]),
+    }
+}
+
+#[test]
+fn test_records_follow_declared_types() {
+    let point = IrType::Named("Point".into());
+    let m = module(
+        vec![point_type()],
+        vec![
+            // Literal order differs from declaration order
+            function(
+                "make",
+                vec![("x", IrType::U32)],
+                point.clone(),
+                vec![IrStmt::Return {
+                    value: Some(IrExpr::Record {
+                        fields: vec![("y".into(), int(7)), ("x".into(), *var("x"))],
+                    }),
+                }],
+            ),
+            function(
+                "shift",
+                vec![("p", point.clone())],
+                point,
+                vec![
+                    IrStmt::Let {
+                        name: "q".into(),
+                        mutable: true,
+                        ty: None,
+                        value: *var("p"),
+                    },
+                    IrStmt::Assign {
+                        target: field(var("q"), "y"),
+                        value: add(field(var("q"), "y"), int(1)),
+                    },
+                    IrStmt::Return {
+                        value: Some(*var("q")),
+                    },
+                ],
+            ),
+        ],
+    );
+
+    let made = run(&m, "make", vec![Value::U32(3)], &mut NoHost).unwrap();
+    assert_eq!(
+        made,
+        Value::Record(vec![
+            ("x".into(), Value::U32(3)),
+            ("y".into(), Value::U64(7)),
+        ])
+    );
+    assert_eq!(made.to_string(), "{ x: 3, y: 7 }");
+
+    let shifted = run(&m, "shift", vec![made], &mut NoHost).unwrap();
+    assert_eq!(
+        shifted,
+        Value::Record(vec![
+            ("x".into(), Value::U32(3)),
+            ("y".into(), Value::U64(8)),
+        ])
+    );
+}
+
+#[test]
+fn test_strings_compare_by_content() {
+    let m = module(
+        vec![],
+        vec![function(
+            "is_greeting",
+            vec![("s", IrType::Str)],
+            IrType::Bool,
+            vec![IrStmt::Return {
+                value: Some(IrExpr::BinOp {
+                    op: IrBinOp::Eq,
+                    left: var("s"),
+                    right: Box::new(add(str_lit("hel"), str_lit("lo"))),
+                }),
+            }],
+        )],
+    );
+    let call = |s: &str| {
+        run(
+            &m,
+            "is_greeting",
+            vec![Value::Str(s.to_string())],
+            &mut NoHost,
+        )
+    };
+    assert_eq!(call("hello"), Ok(Value::Bool(true)));
+    assert_eq!(call("bye"), Ok(Value::Bool(false)));
+}
+
+#[test]
+fn test_effects_go_to_the_host() {
+    let log_call = IrExpr::Call {
+        func: Box::new(IrExpr::Path(vec!["log".into(), "info".into()])),
+        args: vec![str_lit("starting")],
+    };
+    let m = module(
+        vec![],
+        vec![function(
+            "main",
+            vec![],
+            IrType::Unit,
+            vec![IrStmt::Expr(log_call)],
+        )],
+    );
+
+    assert_eq!(
+        run(&m, "main", vec![], &mut NoHost),
+        Err(EvalError::UnhandledCall("log.info".to_string()))
+    );
+
+    let logged = Rc::new(RefCell::new(Vec::new()));
+    let sink = logged.clone();
+    let mut host = Handlers::new().on("log.info", move |args| {
+        sink.borrow_mut().extend(args);
+        Ok(Value::Unit)
+    });
+    assert_eq!(run(&m, "main", vec![], &mut host), Ok(Value::Unit));
+    assert_eq!(*logged.borrow(), vec![Value::Str("starting".to_string())]);
+}
+
+#[test]
+fn test_parse_arguments_by_type() {
+    let types = [point_type()].map(|t| (t.name, t.ty)).into_iter().collect();
+    assert_eq!(Value::parse("42", &IrType::U16, &types), Ok(Value::U16(42)));
+    assert_eq!(
+        Value::parse("true", &IrType::Bool, &types),
+        Ok(Value::Bool(true))
+    );
+    assert_eq!(
+        Value::parse("hi", &IrType::Str, &types),
+        Ok(Value::Str("hi".to_string()))
+    );
+    assert!(Value::parse("70000", &IrType::U16, &types).is_err());
+    assert!(Value::parse("1", &IrType::Named("Point".into()), &types).is_err());
+}
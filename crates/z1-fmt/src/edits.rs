@@ -0,0 +1,140 @@
+//! Formatting as minimal text edits.
+//!
+//! Rewriting a whole cell touches every line git attributes to it, even
+//! those already formatted. [`minimal_edits`] instead compares the original
+//! text with the formatter's output line by line and returns one edit per
+//! region that differs, trimmed to the bytes that actually change. Applied
+//! with [`apply_edits`], they turn the original into the formatted text
+//! while leaving every other line, and its line ending, as it was.
+
+use z1_ast::Span;
+
+/// Replace the bytes of the original text covered by `span` with
+/// `replacement`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub span: Span,
+    pub replacement: String,
+}
+
+/// Edits turning `source` into `formatted`, in order and not overlapping.
+/// Lines that differ only in their line ending are left alone, and the text
+/// inserted into a CRLF source uses CRLF too.
+pub fn minimal_edits(source: &str, formatted: &str) -> Vec<TextEdit> {
+    let formatted = if source.contains("\r\n") {
+        formatted.replace("\r\n", "\n").replace('\n', "\r\n")
+    } else {
+        formatted.to_string()
+    };
+    let old: Vec<&str> = source.split_inclusive('\n').collect();
+    let new: Vec<&str> = formatted.split_inclusive('\n').collect();
+    let same = |i: usize, j: usize| same_line(old[i], new[j]);
+
+    // Lines both sides start and end with need no table
+    let prefix = (0..old.len().min(new.len()))
+        .take_while(|&k| same(k, k))
+        .count();
+    let suffix = (0..old.len().min(new.len()) - prefix)
+        .take_while(|&k| same(old.len() - 1 - k, new.len() - 1 - k))
+        .count();
+    let (n, m) = (old.len() - suffix, new.len() - suffix);
+
+    // Longest common subsequence table over the suffixes of the middle lines
+    let width = m - prefix + 1;
+    let mut lcs = vec![0usize; (n - prefix + 1) * width];
+    let at = |i: usize, j: usize| (i - prefix) * width + (j - prefix);
+    for i in (prefix..n).rev() {
+        for j in (prefix..m).rev() {
+            lcs[at(i, j)] = if same(i, j) {
+                lcs[at(i + 1, j + 1)] + 1
+            } else {
+                lcs[at(i + 1, j)].max(lcs[at(i, j + 1)])
+            };
+        }
+    }
+
+    let (old_at, new_at) = (line_offsets(&old), line_offsets(&new));
+
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (prefix, prefix);
+    while i < n || j < m {
+        if i < n && j < m && same(i, j) {
+            i += 1;
+            j += 1;
+            continue;
+        }
+        // A run of changed lines, up to the next line both sides share
+        let (start_i, start_j) = (i, j);
+        while (i < n || j < m) && !(i < n && j < m && same(i, j)) {
+            if i < n && (j == m || lcs[at(i + 1, j)] >= lcs[at(i, j + 1)]) {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        edits.push(trimmed_edit(
+            source,
+            old_at[start_i]..old_at[i],
+            &formatted[new_at[start_j]..new_at[j]],
+        ));
+    }
+    edits
+}
+
+/// Apply `edits`, as returned by [`minimal_edits`], to `source`.
+pub fn apply_edits(source: &str, edits: &[TextEdit]) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut copied = 0;
+    for edit in edits {
+        let (start, end) = (edit.span.start as usize, edit.span.end as usize);
+        out.push_str(&source[copied..start]);
+        out.push_str(&edit.replacement);
+        copied = end;
+    }
+    out.push_str(&source[copied..]);
+    out
+}
+
+/// Byte offset of each line, then of the end of the text.
+fn line_offsets(lines: &[&str]) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(lines.len() + 1);
+    let mut offset = 0;
+    for line in lines {
+        offsets.push(offset);
+        offset += line.len();
+    }
+    offsets.push(offset);
+    offsets
+}
+
+/// Whether two lines are the same but for a CRLF or LF ending.
+fn same_line(old: &str, new: &str) -> bool {
+    fn text(line: &str) -> &str {
+        line.strip_suffix('\n')
+            .map_or(line, |line| line.strip_suffix('\r').unwrap_or(line))
+    }
+    text(old) == text(new) && old.ends_with('\n') == new.ends_with('\n')
+}
+
+/// The edit replacing `range` of `source` with `replacement`, without the
+/// text both start or end with.
+fn trimmed_edit(source: &str, range: std::ops::Range<usize>, replacement: &str) -> TextEdit {
+    let old = &source[range.clone()];
+    let prefix: usize = old
+        .chars()
+        .zip(replacement.chars())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum();
+    let suffix: usize = old[prefix..]
+        .chars()
+        .rev()
+        .zip(replacement[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum();
+    TextEdit {
+        span: Span::new((range.start + prefix) as u32, (range.end - suffix) as u32),
+        replacement: replacement[prefix..replacement.len() - suffix].to_string(),
+    }
+}
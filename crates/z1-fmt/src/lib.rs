@@ -1,28 +1,28 @@
-use std::collections::HashMap;
+mod edits;
+
+use std::collections::{HashMap, HashSet};
 
 use thiserror::Error;
 use z1_ast::{
-    FnDecl, Import, Item, Module, Param, RecordField, SymbolMap, SymbolPair, TypeDecl, TypeExpr,
+    Expansion, FnDecl, Ident, Import, Item, Module, Param, RecordField, Span, Symbol, SymbolMap,
+    SymbolPair, Template, TestDecl, TypeDecl, TypeExpr, UnionVariant,
 };
 
+pub use edits::{apply_edits, minimal_edits, TextEdit};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Mode {
     Compact,
     Relaxed,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum SymMapStyle {
+    #[default]
     Respect,
     Reflow,
 }
 
-impl Default for SymMapStyle {
-    fn default() -> Self {
-        Self::Respect
-    }
-}
-
 #[derive(Debug, Clone)]
 pub struct FmtOptions {
     pub symmap_style: SymMapStyle,
@@ -55,6 +55,115 @@ pub fn format_module(
     Ok(formatter.buf)
 }
 
+/// Format `module`, parsed from `source`, as the [`minimal_edits`] that
+/// turn `source` into the formatted text.
+pub fn format_edits(
+    source: &str,
+    module: &Module,
+    mode: Mode,
+    options: &FmtOptions,
+) -> Result<Vec<TextEdit>, FmtError> {
+    Ok(minimal_edits(
+        source,
+        &format_module(module, mode, options)?,
+    ))
+}
+
+/// `#sym { long: short, ... }` directive declaring `pairs`.
+pub fn symbol_directive(pairs: &[(String, String)]) -> String {
+    let pairs: Vec<String> = pairs
+        .iter()
+        .map(|(long, short)| format!("{long}: {short}"))
+        .collect();
+    format!("#sym {{ {} }}", pairs.join(", "))
+}
+
+/// `// SymbolMap: { long ↔ short, ... }` comment that precedes the
+/// directive in relaxed cells.
+pub fn symbol_comment(pairs: &[(String, String)]) -> String {
+    let pairs: Vec<String> = pairs
+        .iter()
+        .map(|(long, short)| format!("{long} ↔ {short}"))
+        .collect();
+    format!("// SymbolMap: {{ {} }}", pairs.join(", "))
+}
+
+/// Symbol map pairs of `module` that no longer shorten anything: their long
+/// name is not declared or referenced by the module, and neither name
+/// appears in a function body.
+pub fn stale_symbols(module: &Module) -> Vec<&SymbolPair> {
+    fn type_names<'a>(expr: &'a TypeExpr, names: &mut HashSet<&'a str>) {
+        match expr {
+            TypeExpr::Path(parts) => names.extend(parts.iter().flat_map(|part| part.split('.'))),
+            TypeExpr::Record(fields) => {
+                for field in fields {
+                    names.insert(&field.name);
+                    type_names(&field.ty, names);
+                }
+            }
+            TypeExpr::Union(variants) => {
+                for variant in variants {
+                    names.insert(&variant.name);
+                    if let Some(payload) = &variant.payload {
+                        type_names(payload, names);
+                    }
+                }
+            }
+            TypeExpr::Generic { base, args } => {
+                names.extend(base.iter().flat_map(|part| part.split('.')));
+                for arg in args {
+                    type_names(arg, names);
+                }
+            }
+        }
+    }
+
+    let mut names = HashSet::new();
+    for item in &module.items {
+        match item {
+            Item::Import(import) => names.extend(import.alias.as_deref()),
+            Item::Symbol(_) => {}
+            Item::Type(decl) => {
+                names.insert(decl.name.as_str());
+                names.extend(decl.type_params.iter().map(|param| param.as_str()));
+                type_names(&decl.expr, &mut names);
+            }
+            Item::Fn(decl) => {
+                names.insert(decl.name.as_str());
+                names.extend(decl.type_params.iter().map(|param| param.as_str()));
+                for param in &decl.params {
+                    names.insert(param.name.as_str());
+                    type_names(&param.ty, &mut names);
+                }
+                type_names(&decl.ret, &mut names);
+                names.extend(
+                    decl.body
+                        .raw
+                        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                        .filter(|word| !word.is_empty()),
+                );
+            }
+            Item::Test(test) => names.extend(
+                test.body
+                    .raw
+                    .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .filter(|word| !word.is_empty()),
+            ),
+        }
+    }
+
+    module
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Symbol(map) => Some(&map.pairs),
+            _ => None,
+        })
+        .flatten()
+        .filter(|pair| !names.contains(pair.long.as_str()) && !names.contains(pair.short.as_str()))
+        .collect()
+}
+
 struct Formatter<'a> {
     module: &'a Module,
     mode: Mode,
@@ -88,7 +197,36 @@ impl<'a> Formatter<'a> {
     }
 
     fn write_items(&mut self) -> Result<(), FmtError> {
-        for item in &self.module.items {
+        let module = self.module;
+        // Templates and expansions go back where they were written; the
+        // items an expansion adds are printed as the expansion itself
+        let mut written: Vec<(Span, Written<'_>)> = module
+            .templates
+            .iter()
+            .map(|template| (template.span, Written::Template(template)))
+            .chain(
+                module
+                    .expansions
+                    .iter()
+                    .map(|expansion| (expansion.span, Written::Expansion(expansion))),
+            )
+            .collect();
+        written.sort_by_key(|(span, _)| span.start);
+        let mut written = written.into_iter().peekable();
+        for item in &module.items {
+            let span = item_span(item);
+            if module
+                .expansions
+                .iter()
+                .any(|expansion| expansion.span == span)
+            {
+                continue;
+            }
+            while let Some((_, template_item)) =
+                written.next_if(|(written, _)| written.start < span.start)
+            {
+                self.write_template_item(template_item);
+            }
             match item {
                 Item::Import(import) => {
                     self.section_break();
@@ -106,11 +244,40 @@ impl<'a> Formatter<'a> {
                     self.section_break();
                     self.write_fn_decl(func);
                 }
+                Item::Test(test) => {
+                    self.section_break();
+                    self.write_test_decl(test);
+                }
             }
         }
+        for (_, template_item) in written {
+            self.write_template_item(template_item);
+        }
         Ok(())
     }
 
+    fn write_template_item(&mut self, item: Written<'_>) {
+        self.section_break();
+        match item {
+            Written::Template(template) => {
+                self.write_doc(template.doc.as_deref());
+                self.buf.push_str("template ");
+                self.buf.push_str(&template.name);
+                self.buf.push('(');
+                self.buf.push_str(&Symbol::join(&template.params, ", "));
+                self.buf.push(')');
+                write_block(self, &template.body.raw);
+            }
+            Written::Expansion(expansion) => {
+                self.buf.push_str("expand ");
+                self.buf.push_str(&expansion.template);
+                self.buf.push('(');
+                self.buf.push_str(&expansion.args.join(", "));
+                self.buf.push_str(")\n");
+            }
+        }
+    }
+
     fn write_compact_header(&mut self) {
         self.buf.push_str("m ");
         self.buf.push_str(&self.module_path());
@@ -130,6 +297,15 @@ impl<'a> Formatter<'a> {
             self.buf.push_str(&self.module.caps.join(","));
             self.buf.push(']');
         }
+        if !self.module.targets.is_empty() {
+            self.buf.push_str(" targets=[");
+            self.buf.push_str(&self.module.targets.join(","));
+            self.buf.push(']');
+        }
+        if let Some(toolchain) = &self.module.toolchain {
+            self.buf.push_str(" toolchain>=");
+            self.buf.push_str(toolchain);
+        }
         self.buf.push('\n');
     }
 
@@ -151,6 +327,16 @@ impl<'a> Formatter<'a> {
             self.buf.push_str(&self.module.caps.join(", "));
             self.buf.push_str("]\n");
         }
+        if !self.module.targets.is_empty() {
+            self.buf.push_str("  targets = [");
+            self.buf.push_str(&self.module.targets.join(", "));
+            self.buf.push_str("]\n");
+        }
+        if let Some(toolchain) = &self.module.toolchain {
+            self.buf.push_str("  toolchain >= ");
+            self.buf.push_str(toolchain);
+            self.buf.push('\n');
+        }
     }
 
     fn write_import(&mut self, import: &Import) {
@@ -172,7 +358,7 @@ impl<'a> Formatter<'a> {
         }
         if !import.only.is_empty() {
             self.buf.push_str(" only [");
-            self.buf.push_str(&import.only.join(", "));
+            self.buf.push_str(&Symbol::join(&import.only, ", "));
             self.buf.push(']');
         }
         self.buf.push('\n');
@@ -183,57 +369,31 @@ impl<'a> Formatter<'a> {
         if pairs.is_empty() {
             return;
         }
-        match self.mode {
-            Mode::Compact => {
-                self.buf.push_str("#sym { ");
-                for (idx, (long, short)) in pairs.iter().enumerate() {
-                    if idx > 0 {
-                        self.buf.push_str(", ");
-                    }
-                    self.buf.push_str(long);
-                    self.buf.push_str(": ");
-                    self.buf.push_str(short);
-                }
-                self.buf.push_str(" }\n");
-            }
-            Mode::Relaxed => {
-                self.buf.push_str("// SymbolMap: { ");
-                for (idx, (long, short)) in pairs.iter().enumerate() {
-                    if idx > 0 {
-                        self.buf.push_str(", ");
-                    }
-                    self.buf.push_str(long);
-                    self.buf.push_str(" ↔ ");
-                    self.buf.push_str(short);
-                }
-                self.buf.push_str(" }\n");
-                self.buf.push_str("#sym { ");
-                for (idx, (long, short)) in pairs.iter().enumerate() {
-                    if idx > 0 {
-                        self.buf.push_str(", ");
-                    }
-                    self.buf.push_str(long);
-                    self.buf.push_str(": ");
-                    self.buf.push_str(short);
-                }
-                self.buf.push_str(" }\n");
-            }
+        if matches!(self.mode, Mode::Relaxed) {
+            self.buf.push_str(&symbol_comment(&pairs));
+            self.buf.push('\n');
         }
+        self.buf.push_str(&symbol_directive(&pairs));
+        self.buf.push('\n');
     }
 
     fn write_type_decl(&mut self, decl: &TypeDecl) {
+        self.write_doc(decl.doc.as_deref());
         match self.mode {
             Mode::Compact => self.buf.push_str("t "),
             Mode::Relaxed => self.buf.push_str("type "),
         }
         let name = self.symbols.display_ident(&decl.name, self.mode);
         self.buf.push_str(&name);
+        self.buf
+            .push_str(&self.format_type_params(&decl.type_params));
         self.buf.push_str(" = ");
         self.buf.push_str(&self.format_type_expr(&decl.expr));
         self.buf.push('\n');
     }
 
     fn write_fn_decl(&mut self, decl: &FnDecl) {
+        self.write_doc(decl.doc.as_deref());
         let kw = match self.mode {
             Mode::Compact => "f",
             Mode::Relaxed => "fn",
@@ -242,6 +402,8 @@ impl<'a> Formatter<'a> {
         self.buf.push_str(kw);
         self.buf.push(' ');
         self.buf.push_str(&name);
+        self.buf
+            .push_str(&self.format_type_params(&decl.type_params));
         self.buf.push('(');
         let params = decl
             .params
@@ -265,12 +427,30 @@ impl<'a> Formatter<'a> {
                     self.buf.push_str("\n  eff [");
                 }
             }
-            self.buf.push_str(&decl.effects.join(", "));
+            self.buf.push_str(&Symbol::join(&decl.effects, ", "));
             self.buf.push(']');
         }
         write_block(self, &decl.body.raw);
     }
 
+    fn write_doc(&mut self, doc: Option<&str>) {
+        for line in doc.into_iter().flat_map(str::lines) {
+            self.buf.push_str("///");
+            if !line.is_empty() {
+                self.buf.push(' ');
+                self.buf.push_str(line);
+            }
+            self.buf.push('\n');
+        }
+    }
+
+    fn write_test_decl(&mut self, test: &TestDecl) {
+        self.buf.push_str("#test \"");
+        self.buf.push_str(&test.name);
+        self.buf.push('"');
+        write_block(self, &test.body.raw);
+    }
+
     fn format_param(&self, param: &Param) -> String {
         let name = self.symbols.display_ident(&param.name, self.mode);
         let ty = self.format_type_expr(&param.ty);
@@ -279,13 +459,7 @@ impl<'a> Formatter<'a> {
 
     fn format_type_expr(&self, expr: &TypeExpr) -> String {
         match expr {
-            TypeExpr::Path(parts) => {
-                let segments = parts
-                    .iter()
-                    .map(|p| self.symbols.display_ident(p, self.mode))
-                    .collect::<Vec<_>>();
-                segments.join(".")
-            }
+            TypeExpr::Path(parts) => self.format_path(parts),
             TypeExpr::Record(fields) => {
                 let inner = fields
                     .iter()
@@ -294,6 +468,49 @@ impl<'a> Formatter<'a> {
                     .join(", ");
                 format!("{{ {inner} }}")
             }
+            TypeExpr::Union(variants) => variants
+                .iter()
+                .map(|variant| self.format_union_variant(variant))
+                .collect::<Vec<_>>()
+                .join(" | "),
+            TypeExpr::Generic { base, args } => {
+                let base = self.format_path(base);
+                let args = args
+                    .iter()
+                    .map(|arg| self.format_type_expr(arg))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("{base}[{args}]")
+            }
+        }
+    }
+
+    fn format_path(&self, parts: &[Ident]) -> String {
+        let parts = parts
+            .iter()
+            .map(|p| self.symbols.display_ident(p, self.mode))
+            .collect::<Vec<_>>();
+        Symbol::join(&parts, ".")
+    }
+
+    /// `[T, U]`, or nothing for a declaration without type parameters.
+    fn format_type_params(&self, params: &[Ident]) -> String {
+        if params.is_empty() {
+            return String::new();
+        }
+        let params = params
+            .iter()
+            .map(|param| self.symbols.display_ident(param, self.mode))
+            .collect::<Vec<_>>();
+        let params = Symbol::join(&params, ", ");
+        format!("[{params}]")
+    }
+
+    fn format_union_variant(&self, variant: &UnionVariant) -> String {
+        let name = self.symbols.display_ident(&variant.name, self.mode);
+        match &variant.payload {
+            Some(payload) => format!("{name}({})", self.format_type_expr(payload)),
+            None => name.to_string(),
         }
     }
 
@@ -304,13 +521,7 @@ impl<'a> Formatter<'a> {
     }
 
     fn module_path(&self) -> String {
-        self.module
-            .path
-            .as_str_vec()
-            .iter()
-            .map(|segment| segment.as_str())
-            .collect::<Vec<_>>()
-            .join(".")
+        self.module.path.to_string()
     }
 
     fn section_break(&mut self) {
@@ -326,9 +537,27 @@ impl<'a> Formatter<'a> {
     }
 }
 
+/// Template items kept out of `Module::items`.
+#[derive(Clone, Copy)]
+enum Written<'a> {
+    Template(&'a Template),
+    Expansion(&'a Expansion),
+}
+
+fn item_span(item: &Item) -> Span {
+    match item {
+        Item::Import(import) => import.span,
+        Item::Symbol(map) => map.span,
+        Item::Type(decl) => decl.span,
+        Item::Fn(decl) => decl.span,
+        Item::Test(test) => test.span,
+    }
+}
+
+#[derive(Clone)]
 pub struct SymbolTable {
-    long_to_short: HashMap<String, String>,
-    short_to_long: HashMap<String, String>,
+    long_to_short: HashMap<Ident, Ident>,
+    short_to_long: HashMap<Ident, Ident>,
     style: SymMapStyle,
 }
 
@@ -339,8 +568,8 @@ impl SymbolTable {
         for item in &module.items {
             if let Item::Symbol(sym) = item {
                 for pair in &sym.pairs {
-                    long_to_short.insert(pair.long.clone(), pair.short.clone());
-                    short_to_long.insert(pair.short.clone(), pair.long.clone());
+                    long_to_short.insert(pair.long, pair.short);
+                    short_to_long.insert(pair.short, pair.long);
                 }
             }
         }
@@ -356,8 +585,8 @@ impl SymbolTable {
         let mut long_to_short = HashMap::new();
         let mut short_to_long = HashMap::new();
         for pair in &sym_map.pairs {
-            long_to_short.insert(pair.long.clone(), pair.short.clone());
-            short_to_long.insert(pair.short.clone(), pair.long.clone());
+            long_to_short.insert(pair.long, pair.short);
+            short_to_long.insert(pair.short, pair.long);
         }
         Self {
             long_to_short,
@@ -367,32 +596,23 @@ impl SymbolTable {
     }
 
     /// Normalize an identifier to its canonical long form (for parser use)
-    pub fn normalize_ident(&self, ident: &str) -> String {
-        self.short_to_long
-            .get(ident)
-            .cloned()
-            .unwrap_or_else(|| ident.to_string())
+    pub fn normalize_ident(&self, ident: &str) -> Ident {
+        let ident = Symbol::intern(ident);
+        self.short_to_long.get(&ident).copied().unwrap_or(ident)
     }
 
-    fn display_ident(&self, ident: &str, mode: Mode) -> String {
-        match mode {
-            Mode::Compact => self
-                .long_to_short
-                .get(ident)
-                .cloned()
-                .unwrap_or_else(|| ident.to_string()),
-            Mode::Relaxed => self
-                .short_to_long
-                .get(ident)
-                .cloned()
-                .unwrap_or_else(|| ident.to_string()),
-        }
+    fn display_ident(&self, ident: &Ident, mode: Mode) -> Ident {
+        let table = match mode {
+            Mode::Compact => &self.long_to_short,
+            Mode::Relaxed => &self.short_to_long,
+        };
+        table.get(ident).copied().unwrap_or(*ident)
     }
 
     fn ordered_pairs<'a>(&'a self, pairs: &'a [SymbolPair]) -> Vec<(String, String)> {
         let mut local = pairs
             .iter()
-            .map(|p| (p.long.clone(), p.short.clone()))
+            .map(|p| (p.long.to_string(), p.short.to_string()))
             .collect::<Vec<_>>();
         match self.style {
             SymMapStyle::Respect => local,
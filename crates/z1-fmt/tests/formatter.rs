@@ -1,4 +1,7 @@
-use z1_fmt::{format_module, FmtOptions, Mode};
+use z1_fmt::{
+    apply_edits, format_edits, format_module, minimal_edits, stale_symbols, symbol_directive,
+    FmtOptions, Mode,
+};
 use z1_hash::module_hashes;
 use z1_parse::parse_module;
 
@@ -60,3 +63,213 @@ fn formats_statements_fixture() {
     let expected_compact = read_fixture("fixtures/fmt/statements.compact.z1c");
     assert_eq!(compact, expected_compact);
 }
+
+#[test]
+fn finds_stale_symbol_pairs() {
+    let source = read_fixture("fixtures/cells/http_server.z1c");
+    let module = parse_module(&source).expect("parse");
+    assert!(stale_symbols(&module).is_empty());
+
+    let source = source.replace("serve: sv }", "serve: sv, retired: rt }");
+    let module = parse_module(&source).expect("parse");
+    let stale: Vec<&str> = stale_symbols(&module)
+        .iter()
+        .map(|pair| pair.long.as_str())
+        .collect();
+    assert_eq!(stale, ["retired"]);
+
+    let pairs = [("handler".to_string(), "h".to_string())];
+    assert_eq!(symbol_directive(&pairs), "#sym { handler: h }");
+}
+
+#[test]
+fn keeps_doc_comments() {
+    let source = "m calc:1.0\n/// Whole numbers.\nt Count = U32\n\
+                  /// Adds two numbers.\n///\n/// ```\n/// assert_eq(add(1, 2), 3);\n/// ```\n\
+                  f add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }\n";
+    let module = parse_module(source).expect("parse");
+    let compact = format_module(&module, Mode::Compact, &FmtOptions::default()).expect("fmt");
+    assert!(
+        compact.contains("/// Whole numbers.\nt Count = U32\n"),
+        "{compact}"
+    );
+    assert!(
+        compact.contains(
+            "/// Adds two numbers.\n///\n/// ```\n/// assert_eq(add(1, 2), 3);\n/// ```\nf add("
+        ),
+        "{compact}"
+    );
+
+    let relaxed = format_module(&module, Mode::Relaxed, &FmtOptions::default()).expect("fmt");
+    let reparsed = parse_module(&relaxed).expect("parse relaxed");
+    let docs = |module: &z1_ast::Module| -> Vec<Option<String>> {
+        module
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                z1_ast::Item::Fn(decl) => Some(decl.doc.clone()),
+                z1_ast::Item::Type(decl) => Some(decl.doc.clone()),
+                _ => None,
+            })
+            .collect()
+    };
+    assert_eq!(docs(&reparsed), docs(&module));
+}
+
+#[test]
+fn keeps_header_requirements() {
+    let source = "m app:1.0 caps=[net] targets=[ts,wasm] toolchain>=0.3\n\
+                  f main()->Unit eff [pure] { ret Unit; }\n";
+    let module = parse_module(source).expect("parse");
+    assert_eq!(module.targets, vec!["ts", "wasm"]);
+    assert_eq!(module.toolchain.as_deref(), Some("0.3"));
+
+    let compact = format_module(&module, Mode::Compact, &FmtOptions::default()).expect("fmt");
+    assert!(
+        compact.starts_with("m app:1.0 caps=[net] targets=[ts,wasm] toolchain>=0.3\n"),
+        "{compact}"
+    );
+    let relaxed = format_module(&module, Mode::Relaxed, &FmtOptions::default()).expect("fmt");
+    assert!(
+        relaxed.contains("  targets = [ts, wasm]\n  toolchain >= 0.3\n"),
+        "{relaxed}"
+    );
+    for formatted in [compact, relaxed] {
+        let reparsed = parse_module(&formatted).expect("reparse");
+        assert_eq!(reparsed.targets, module.targets);
+        assert_eq!(reparsed.toolchain, module.toolchain);
+        assert_eq!(
+            module_hashes(&reparsed).semantic,
+            module_hashes(&module).semantic
+        );
+    }
+}
+
+#[test]
+fn minimal_edits_touch_only_what_changes() {
+    let source = "m app:1.0\nt Count = U32\n\n\
+                  f one()->Count eff [pure] { ret 1; }\n\n\
+                  f  two()->Count   eff [pure] { ret 2; }\n";
+    let module = parse_module(source).expect("parse");
+    let formatted = format_module(&module, Mode::Compact, &FmtOptions::default()).expect("fmt");
+    let edits = format_edits(source, &module, Mode::Compact, &FmtOptions::default()).expect("fmt");
+    assert_eq!(apply_edits(source, &edits), formatted);
+    // Only the badly spaced signature of `two` is touched
+    assert_eq!(edits.len(), 1, "{edits:?}");
+    let two = source.find("f  two").unwrap() as u32;
+    assert_eq!(edits[0].span.start, two + 2);
+    assert_eq!(
+        edits[0].span.end,
+        source.find(" eff [pure] { ret 2").unwrap() as u32
+    );
+
+    assert!(minimal_edits(&formatted, &formatted).is_empty());
+}
+
+#[test]
+fn minimal_edits_keep_crlf_line_endings() {
+    let source = "m app:1.0\r\nt Count =U32\r\n\r\nt Size = U32\r\n";
+    let module = parse_module(source).expect("parse");
+    let edits = format_edits(source, &module, Mode::Compact, &FmtOptions::default()).expect("fmt");
+    assert_eq!(
+        apply_edits(source, &edits),
+        "m app:1.0\r\nt Count = U32\r\n\r\nt Size = U32\r\n"
+    );
+    assert_eq!(edits.len(), 1, "{edits:?}");
+}
+
+#[test]
+fn minimal_edits_cover_inserted_and_deleted_lines() {
+    let cases = [
+        ("a\nb\nc\n", "a\nc\n"),
+        ("a\nc\n", "a\nb\nc\n"),
+        ("", "a\n"),
+        ("a\n", ""),
+        ("a", "a\n"),
+        ("x\ny\n", "y\nx\n"),
+        ("ä\nb\n", "ö\nb\n"),
+    ];
+    for (source, formatted) in cases {
+        let edits = minimal_edits(source, formatted);
+        assert_eq!(apply_edits(source, &edits), formatted, "{source:?}");
+    }
+}
+
+#[test]
+fn templates_and_expansions_round_trip() {
+    let source = "m api:1.0\n\
+                  /// One route.\n\
+                  template handler(name, path) { f name()->Str eff [pure] { ret path; } }\n\
+                  \n\
+                  expand handler(get_user, \"/users\")\n\
+                  \n\
+                  f main()->U32 eff [pure] { ret 0; }\n\
+                  \n\
+                  expand handler(get_post, \"/posts\")\n";
+    let module = parse_module(source).expect("parse");
+    assert_eq!(module.items.len(), 3);
+    let formatted = format_module(&module, Mode::Compact, &FmtOptions::default()).expect("fmt");
+    assert_eq!(formatted, source);
+    let relaxed = format_module(&module, Mode::Relaxed, &FmtOptions::default()).expect("fmt");
+    let reparsed = parse_module(&relaxed).expect("parse relaxed");
+    assert_eq!(reparsed.items.len(), 3);
+    assert_eq!(
+        module_hashes(&reparsed).semantic,
+        module_hashes(&module).semantic
+    );
+}
+
+#[test]
+fn formats_union_types() {
+    let source = "m app:1.0\n\
+                  t Res = Ok(U32) | Err({ code: U32 })  |Gone;\n\
+                  f get()->Some(U32)|None eff [pure] { ret 0; }\n";
+    let module = parse_module(source).expect("parse");
+    let compact = format_module(&module, Mode::Compact, &FmtOptions::default()).expect("fmt");
+    assert!(
+        compact.contains("t Res = Ok(U32) | Err({ code: U32 }) | Gone\n"),
+        "{compact}"
+    );
+    assert!(
+        compact.contains("f get()->Some(U32) | None eff [pure]"),
+        "{compact}"
+    );
+    let relaxed = format_module(&module, Mode::Relaxed, &FmtOptions::default()).expect("fmt");
+    assert!(
+        relaxed.contains("type Res = Ok(U32) | Err({ code: U32 }) | Gone\n"),
+        "{relaxed}"
+    );
+    for formatted in [compact, relaxed] {
+        let reparsed = parse_module(&formatted).expect("reparse");
+        assert_eq!(
+            module_hashes(&reparsed).semantic,
+            module_hashes(&module).semantic
+        );
+    }
+}
+
+#[test]
+fn formats_type_parameters() {
+    let source = "m app:1.0\n\
+                  t Pair[ T ] = { first: T, second: T }\n\
+                  f swap[T,U](p: Pair[ T ])->Map[T,U] eff [pure] { ret p; }\n";
+    let module = parse_module(source).expect("parse");
+    let compact = format_module(&module, Mode::Compact, &FmtOptions::default()).expect("fmt");
+    assert!(
+        compact.contains("t Pair[T] = { first: T, second: T }\n"),
+        "{compact}"
+    );
+    assert!(
+        compact.contains("f swap[T, U](p: Pair[T])->Map[T, U] eff [pure]"),
+        "{compact}"
+    );
+    let relaxed = format_module(&module, Mode::Relaxed, &FmtOptions::default()).expect("fmt");
+    assert!(relaxed.contains("type Pair[T] = {"), "{relaxed}");
+    for formatted in [compact, relaxed] {
+        let reparsed = parse_module(&formatted).expect("reparse");
+        assert_eq!(
+            module_hashes(&reparsed).semantic,
+            module_hashes(&module).semantic
+        );
+    }
+}
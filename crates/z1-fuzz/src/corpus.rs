@@ -0,0 +1,113 @@
+//! Seed corpora for the fuzz targets.
+//!
+//! A fuzzer finds its way into the parser much faster from real cells than
+//! from random bytes. [`seeds`] turns a cell into seed inputs: the cell
+//! itself and, when it parses, one small cell per item, made of the header
+//! and that item alone. [`Corpus`] collects the seeds of many cells without
+//! duplicates, each named by its content hash as libFuzzer names its own
+//! corpus files.
+
+use std::collections::BTreeMap;
+
+use z1_ast::{Item, Span};
+use z1_hash::{hash_bytes, HashAlgo};
+
+/// Seed inputs derived from the cell `source`: the whole cell first, then
+/// the header followed by each item on its own.
+pub fn seeds(source: &str) -> Vec<String> {
+    let mut seeds = vec![source.to_string()];
+    let Ok(module) = z1_parse::parse_module(source) else {
+        return seeds;
+    };
+    let spans: Vec<Span> = module.items.iter().map(item_span).collect();
+    let Some(header_end) = spans.iter().map(|span| span.start as usize).min() else {
+        return seeds;
+    };
+    let header = &source[..header_end];
+    for span in spans {
+        if let Some(item) = source.get(span.start as usize..span.end as usize) {
+            seeds.push(format!("{header}{item}\n"));
+        }
+    }
+    seeds
+}
+
+/// File name of a seed in a corpus directory: the hex digest of its bytes.
+pub fn seed_name(seed: &str) -> String {
+    let digest = hash_bytes(seed.as_bytes(), HashAlgo::Sha3_256);
+    match digest.split_once(':') {
+        Some((_, hex)) => hex.to_string(),
+        None => digest,
+    }
+}
+
+/// Seeds of many cells, by [`seed_name`].
+#[derive(Debug, Default)]
+pub struct Corpus {
+    seeds: BTreeMap<String, String>,
+}
+
+impl Corpus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the [`seeds`] of `source`; returns how many were new.
+    pub fn add_cell(&mut self, source: &str) -> usize {
+        let before = self.seeds.len();
+        for seed in seeds(source) {
+            self.seeds.entry(seed_name(&seed)).or_insert(seed);
+        }
+        self.seeds.len() - before
+    }
+
+    pub fn len(&self) -> usize {
+        self.seeds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seeds.is_empty()
+    }
+
+    /// Seeds by file name, in name order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.seeds
+            .iter()
+            .map(|(name, seed)| (name.as_str(), seed.as_str()))
+    }
+}
+
+fn item_span(item: &Item) -> Span {
+    match item {
+        Item::Import(import) => import.span,
+        Item::Symbol(symbols) => symbols.span,
+        Item::Type(decl) => decl.span,
+        Item::Fn(decl) => decl.span,
+        Item::Test(test) => test.span,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeds_split_a_cell_into_items() {
+        let source = "m app:1.0 caps=[]\nt Count = U32\nf one()->U32 eff [pure] { ret 1; }\n";
+        let seeds = seeds(source);
+        assert_eq!(
+            seeds,
+            [
+                source.to_string(),
+                "m app:1.0 caps=[]\nt Count = U32\n".to_string(),
+                "m app:1.0 caps=[]\nf one()->U32 eff [pure] { ret 1; }\n".to_string(),
+            ]
+        );
+
+        let mut corpus = Corpus::new();
+        assert_eq!(corpus.add_cell(source), 3);
+        assert_eq!(corpus.add_cell(source), 0);
+        let (name, _) = corpus.iter().next().unwrap();
+        assert_eq!(name.len(), 64);
+    }
+}
@@ -0,0 +1,201 @@
+//! Fuzzing invariants of the Zero1 front end.
+//!
+//! The cargo-fuzz targets under `fuzz/` feed arbitrary bytes to the checks
+//! of this crate and panic on the first [`Violation`], so the same checks
+//! can run as ordinary tests over a fixed corpus:
+//!
+//! - [`check_lex`]: lexing never panics, and every token covers the source
+//!   text it claims, in order.
+//! - [`check_parse`]: parsing never panics, and a parse error points inside
+//!   the source.
+//! - [`check_round_trip`]: a cell that parses formats in both modes, the
+//!   output parses back with the same semantic hash, and formatting it again
+//!   changes nothing.
+//!
+//! [`corpus`] builds seed inputs for the targets from existing cells; `z1
+//! fuzz-corpus` writes them where cargo-fuzz looks for them.
+//!
+//! ## Usage
+//!
+//! ```rust
+//! let source = "m app:1.0 caps=[]\nf main()->U32 eff [pure] { ret 1; }\n";
+//! z1_fuzz::check_lex(source).unwrap();
+//! z1_fuzz::check_parse(source).unwrap();
+//! z1_fuzz::check_round_trip(source).unwrap();
+//! ```
+
+pub mod corpus;
+
+use thiserror::Error;
+use z1_fmt::{format_module, FmtError, FmtOptions, Mode};
+use z1_hash::module_hashes;
+use z1_lex::{lex, TokenKind};
+use z1_parse::{parse_module, ParseError};
+
+/// A broken invariant.
+#[derive(Debug, Error)]
+pub enum Violation {
+    #[error("token {index} ({kind:?}) spans {start}..{end}, {reason}")]
+    TokenSpan {
+        index: usize,
+        kind: TokenKind,
+        start: u32,
+        end: u32,
+        reason: &'static str,
+    },
+    #[error("parse error spans {start}..{end}, past the end of the source ({len} bytes)")]
+    ErrorSpan { start: u32, end: u32, len: usize },
+    #[error("formatting a parsed cell in {mode:?} mode failed: {error}")]
+    Format { mode: Mode, error: FmtError },
+    #[error("{mode:?} output does not parse: {error}\n{output}")]
+    Unparsable {
+        mode: Mode,
+        error: ParseError,
+        output: String,
+    },
+    #[error("{mode:?} output changes the semantic hash\n{output}")]
+    SemanticsChanged { mode: Mode, output: String },
+    #[error("{mode:?} output changes when formatted again\n{first}\n---\n{second}")]
+    Unstable {
+        mode: Mode,
+        first: String,
+        second: String,
+    },
+}
+
+/// Lex `source`, checking that each token's span lies within the source,
+/// on character boundaries, after the previous token, and that its lexeme
+/// is the text it spans. The last token is an empty EOF at the end.
+pub fn check_lex(source: &str) -> Result<(), Violation> {
+    let tokens = lex(source);
+    let mut previous_end = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        let (start, end) = (token.span.start, token.span.end);
+        let violation = |reason| Violation::TokenSpan {
+            index,
+            kind: token.kind,
+            start,
+            end,
+            reason,
+        };
+        if start < previous_end || end < start {
+            return Err(violation("out of order"));
+        }
+        match source.get(start as usize..end as usize) {
+            None => return Err(violation("outside the source or splitting a character")),
+            Some(text) if text != token.lexeme => {
+                return Err(violation("but its lexeme is different text"))
+            }
+            Some(_) => {}
+        }
+        previous_end = end;
+    }
+    match tokens.last() {
+        Some(eof)
+            if eof.kind == TokenKind::Eof
+                && eof.span.start as usize == source.len()
+                && eof.span.end == eof.span.start =>
+        {
+            Ok(())
+        }
+        _ => Err(Violation::TokenSpan {
+            index: tokens.len().saturating_sub(1),
+            kind: TokenKind::Eof,
+            start: source.len() as u32,
+            end: source.len() as u32,
+            reason: "but the stream does not end with EOF there",
+        }),
+    }
+}
+
+/// Parse `source`; a parse error is fine as long as it points within it.
+pub fn check_parse(source: &str) -> Result<(), Violation> {
+    let span = match parse_module(source) {
+        Ok(_) => return Ok(()),
+        Err(ParseError::Unexpected { span, .. } | ParseError::Invalid { span, .. }) => span,
+    };
+    if span.start > span.end || span.end as usize > source.len() {
+        return Err(Violation::ErrorSpan {
+            start: span.start,
+            end: span.end,
+            len: source.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Format the cell `source` parses to in both modes and check that the
+/// output parses back to the same semantics and is a fixed point of the
+/// formatter. Sources that do not parse pass.
+pub fn check_round_trip(source: &str) -> Result<(), Violation> {
+    let Ok(module) = parse_module(source) else {
+        return Ok(());
+    };
+    let semantic = module_hashes(&module).semantic;
+    let options = FmtOptions::default();
+    for mode in [Mode::Compact, Mode::Relaxed] {
+        let first = format_module(&module, mode, &options)
+            .map_err(|error| Violation::Format { mode, error })?;
+        let reparsed = parse_module(&first).map_err(|error| Violation::Unparsable {
+            mode,
+            error,
+            output: first.clone(),
+        })?;
+        if module_hashes(&reparsed).semantic != semantic {
+            return Err(Violation::SemanticsChanged {
+                mode,
+                output: first,
+            });
+        }
+        let second = format_module(&reparsed, mode, &options)
+            .map_err(|error| Violation::Format { mode, error })?;
+        if second != first {
+            return Err(Violation::Unstable {
+                mode,
+                first,
+                second,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Run every check on `data` when it is UTF-8, as the fuzz targets do;
+/// other bytes are skipped, since cells are read as text.
+pub fn check_all(data: &[u8]) -> Result<(), Violation> {
+    let Ok(source) = std::str::from_utf8(data) else {
+        return Ok(());
+    };
+    check_lex(source)?;
+    check_parse(source)?;
+    check_round_trip(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixtures_and_their_prefixes_hold_every_invariant() {
+        let fixtures = [
+            include_str!("../../../fixtures/cells/http_server.z1c"),
+            include_str!("../../../fixtures/fmt/statements.relaxed.z1r"),
+            include_str!("../../../fixtures/wasm/arith.z1c"),
+        ];
+        for fixture in fixtures {
+            for seed in corpus::seeds(fixture) {
+                // Every prefix stands in for a fuzzer truncating the seed
+                for end in (0..=seed.len()).filter(|&end| seed.is_char_boundary(end)) {
+                    if let Err(violation) = check_all(&seed.as_bytes()[..end]) {
+                        panic!("{violation}\ninput:\n{}", &seed[..end]);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn non_utf8_input_is_skipped() {
+        assert!(check_all(&[0xff, 0xfe, b'm']).is_ok());
+    }
+}
@@ -0,0 +1,119 @@
+//! Hash algorithm selection
+//!
+//! Every hash string carries the algorithm that produced it as a prefix
+//! (`sha3-256:<hex>`, `blake3:<hex>`), so stored hashes stay interpretable
+//! when the default algorithm changes.
+
+use std::fmt;
+use std::str::FromStr;
+
+use sha3::{Digest, Sha3_256};
+
+/// Digest algorithm used for semantic and format hashes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum HashAlgo {
+    #[default]
+    Sha3_256,
+    Blake3,
+}
+
+impl HashAlgo {
+    pub const ALL: [HashAlgo; 2] = [HashAlgo::Sha3_256, HashAlgo::Blake3];
+
+    /// Prefix written in front of the hex digest.
+    pub fn prefix(self) -> &'static str {
+        match self {
+            HashAlgo::Sha3_256 => "sha3-256",
+            HashAlgo::Blake3 => "blake3",
+        }
+    }
+}
+
+impl fmt::Display for HashAlgo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.prefix())
+    }
+}
+
+impl FromStr for HashAlgo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        HashAlgo::ALL
+            .into_iter()
+            .find(|algo| algo.prefix() == s)
+            .ok_or_else(|| format!("unknown hash algorithm `{s}` (expected sha3-256 or blake3)"))
+    }
+}
+
+/// Split a hash string into its algorithm and hex digest.
+///
+/// Bare 64-digit hex strings predate algorithm prefixes and are read as
+/// SHA3-256.
+pub fn split_hash(hash: &str) -> Option<(HashAlgo, &str)> {
+    let (algo, hex) = match hash.split_once(':') {
+        Some((prefix, hex)) => (prefix.parse().ok()?, hex),
+        None => (HashAlgo::Sha3_256, hash),
+    };
+    let valid = hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit());
+    valid.then_some((algo, hex))
+}
+
+/// Prefixed digest of raw `bytes`, e.g. a prompt or a diff.
+pub fn hash_bytes(bytes: &[u8], algo: HashAlgo) -> String {
+    let mut hasher = HashState::new(algo);
+    hasher.update(bytes);
+    hasher.finish()
+}
+
+/// Incremental hasher for the selected algorithm.
+pub(crate) enum HashState {
+    Sha3(Box<Sha3_256>),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl HashState {
+    pub(crate) fn new(algo: HashAlgo) -> Self {
+        match algo {
+            HashAlgo::Sha3_256 => HashState::Sha3(Box::default()),
+            HashAlgo::Blake3 => HashState::Blake3(Box::default()),
+        }
+    }
+
+    pub(crate) fn update(&mut self, bytes: impl AsRef<[u8]>) {
+        match self {
+            HashState::Sha3(hasher) => hasher.update(bytes),
+            HashState::Blake3(hasher) => {
+                hasher.update(bytes.as_ref());
+            }
+        }
+    }
+
+    /// Raw digest bytes.
+    pub(crate) fn digest(self) -> Vec<u8> {
+        match self {
+            HashState::Sha3(hasher) => hasher.finalize().to_vec(),
+            HashState::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+        }
+    }
+
+    /// Prefixed hex digest, e.g. `sha3-256:3f1c…`.
+    pub(crate) fn finish(self) -> String {
+        let algo = match &self {
+            HashState::Sha3(_) => HashAlgo::Sha3_256,
+            HashState::Blake3(_) => HashAlgo::Blake3,
+        };
+        format_hash(algo, &self.digest())
+    }
+}
+
+/// Render digest bytes as `<algo>:<hex>`.
+pub(crate) fn format_hash(algo: HashAlgo, digest: &[u8]) -> String {
+    let mut out = String::with_capacity(algo.prefix().len() + 1 + digest.len() * 2);
+    out.push_str(algo.prefix());
+    out.push(':');
+    for byte in digest {
+        out.push_str(&format!("{byte:02x}"));
+    }
+    out
+}
@@ -0,0 +1,68 @@
+//! Canonicalization before hashing
+//!
+//! Cells written by different agents often differ only in the order of
+//! declarations, capabilities or effects, or in the casing of effect names.
+//! [`canonicalize`] rewrites those choices into one canonical form so such
+//! cells hash identically.
+
+use z1_ast::{Item, Module, Names, Session, Symbol};
+
+use crate::HashAlgo;
+
+/// Options controlling how a module is hashed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HashOptions {
+    pub algo: HashAlgo,
+    /// Hash the [`canonicalize`]d module instead of the module as written.
+    pub canonicalize: bool,
+}
+
+/// Canonical form of `module`:
+///
+/// - capabilities and targets sorted and deduplicated,
+/// - effects lowercased, sorted and deduplicated,
+/// - imported names sorted,
+/// - items ordered imports, symbol maps, types, functions, tests, each by
+///   name.
+///
+/// Function bodies, parameter order and record field order are meaningful
+/// and left untouched.
+pub fn canonicalize(module: &Module) -> Module {
+    let mut module = module.clone();
+    module.caps.sort();
+    module.caps.dedup();
+    module.targets.sort();
+    module.targets.dedup();
+
+    for item in &mut module.items {
+        match item {
+            Item::Fn(func) => {
+                for effect in &mut func.effects {
+                    *effect = effect.to_lowercase().into();
+                }
+                Symbol::sort(&mut func.effects);
+                func.effects.dedup();
+            }
+            Item::Import(import) => Symbol::sort(&mut import.only),
+            Item::Symbol(_) | Item::Type(_) | Item::Test(_) => {}
+        }
+    }
+
+    let session = Session::current();
+    let names = session.names();
+    module
+        .items
+        .sort_by(|a, b| item_key(a, &names).cmp(&item_key(b, &names)));
+    drop(names);
+    module
+}
+
+fn item_key<'a>(item: &'a Item, names: &'a Names) -> (u8, &'a str) {
+    match item {
+        Item::Import(import) => (0, import.path.as_str()),
+        Item::Symbol(_) => (1, ""),
+        Item::Type(ty) => (2, names.get(ty.name)),
+        Item::Fn(func) => (3, names.get(func.name)),
+        Item::Test(test) => (4, test.name.as_str()),
+    }
+}
@@ -1,26 +1,133 @@
-use sha3::{Digest, Sha3_256};
+mod algo;
+mod canonical;
+mod manifest;
+mod merkle;
+#[cfg(feature = "fs")]
+mod workspace;
+
+use algo::HashState;
+pub use algo::{hash_bytes, split_hash, HashAlgo};
+pub use canonical::{canonicalize, HashOptions};
+pub use manifest::{
+    item_hashes, item_hashes_with, ItemChange, ItemHash, ItemKind, Manifest, ManifestError,
+    MANIFEST_EXTENSION,
+};
+pub use merkle::{MerkleLeaf, MerkleProof, MerkleTree, ProofStep, Side};
+#[cfg(feature = "fs")]
+pub use workspace::{workspace_root_hash, workspace_root_hash_with, WorkspaceError};
 use z1_ast::{
-    Block, FnDecl, Import, Item, Module, Param, RecordField, SymbolMap, TypeDecl, TypeExpr,
+    BinOp, Block, ElseBlock, Expr, FnDecl, Ident, IfStmt, Import, Item, Literal, Module, Param,
+    RecordField, Session, Stmt, SymbolMap, TypeDecl, TypeExpr, UnaryOp, UnionVariant,
 };
 
-type HashState = Sha3_256;
-
-/// Container for both semantic and format hashes.
+/// Container for the semantic, format and API hashes.
+///
+/// The three hashes separate kinds of change: `api` changes only when the
+/// interface does, `semantic` on any behavioral change, and `format` also on
+/// symbol map (naming) and inline test changes.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ModuleHashes {
     pub semantic: String,
     pub format: String,
+    /// Signature surface: module path, capabilities, types and function
+    /// signatures with effects. Bodies and declaration order are ignored.
+    pub api: String,
 }
 
+/// Hashes of `module` with the default algorithm (SHA3-256).
 pub fn module_hashes(module: &Module) -> ModuleHashes {
+    module_hashes_with(module, HashAlgo::default())
+}
+
+/// Hashes of `module` with `algo`; each hash is prefixed with the algorithm.
+pub fn module_hashes_with(module: &Module, algo: HashAlgo) -> ModuleHashes {
+    module_hashes_with_options(
+        module,
+        &HashOptions {
+            algo,
+            ..HashOptions::default()
+        },
+    )
+}
+
+/// Hashes of `module` as configured by `options`.
+pub fn module_hashes_with_options(module: &Module, options: &HashOptions) -> ModuleHashes {
+    if options.canonicalize {
+        return hashes_of(&canonicalize(module), options.algo);
+    }
+    hashes_of(module, options.algo)
+}
+
+fn hashes_of(module: &Module, algo: HashAlgo) -> ModuleHashes {
     ModuleHashes {
-        semantic: hash_module(module, false),
-        format: hash_module(module, true),
+        semantic: hash_module(module, false, algo),
+        format: hash_module(module, true, algo),
+        api: hash_api(module, algo),
     }
 }
 
-fn hash_module(module: &Module, include_symbol_map: bool) -> String {
-    let mut hasher = Sha3_256::new();
+fn hash_api(module: &Module, algo: HashAlgo) -> String {
+    let mut hasher = HashState::new(algo);
+    feed_str(&mut hasher, "api");
+    for segment in module.path.as_str_vec() {
+        feed_str(&mut hasher, segment);
+    }
+    let mut caps: Vec<&str> = module.caps.iter().map(String::as_str).collect();
+    caps.sort_unstable();
+    caps.dedup();
+    hasher.update((caps.len() as u32).to_le_bytes());
+    for cap in caps {
+        feed_str(&mut hasher, cap);
+    }
+    hash_requirements(&mut hasher, module);
+
+    // Every type and function of a cell is exported
+    let mut items: Vec<&Item> = module
+        .items
+        .iter()
+        .filter(|item| matches!(item, Item::Type(_) | Item::Fn(_)))
+        .collect();
+    let session = Session::current();
+    let names = session.names();
+    items.sort_by_key(|item| match item {
+        Item::Type(ty) => (0, names.get(ty.name)),
+        Item::Fn(func) => (1, names.get(func.name)),
+        Item::Import(_) | Item::Symbol(_) | Item::Test(_) => unreachable!(),
+    });
+    drop(names);
+    for item in items {
+        match item {
+            Item::Type(ty) => {
+                feed_str(&mut hasher, "type");
+                hash_type_decl(&mut hasher, ty);
+            }
+            Item::Fn(func) => {
+                feed_str(&mut hasher, "fn");
+                feed_str(&mut hasher, &func.name);
+                hash_type_params(&mut hasher, &func.type_params);
+                // Parameters are positional: renaming one is not an API change
+                hasher.update((func.params.len() as u32).to_le_bytes());
+                for param in &func.params {
+                    hash_type_expr(&mut hasher, &param.ty);
+                }
+                hash_type_expr(&mut hasher, &func.ret);
+                let mut effects: Vec<&str> =
+                    func.effects.iter().map(|effect| effect.as_str()).collect();
+                effects.sort_unstable();
+                effects.dedup();
+                hasher.update((effects.len() as u32).to_le_bytes());
+                for eff in effects {
+                    feed_str(&mut hasher, eff);
+                }
+            }
+            Item::Import(_) | Item::Symbol(_) | Item::Test(_) => {}
+        }
+    }
+    hasher.finish()
+}
+
+fn hash_module(module: &Module, include_symbol_map: bool, algo: HashAlgo) -> String {
+    let mut hasher = HashState::new(algo);
     feed_str(&mut hasher, "module");
     for segment in module.path.as_str_vec() {
         feed_str(&mut hasher, segment);
@@ -39,11 +146,45 @@ fn hash_module(module: &Module, include_symbol_map: bool) -> String {
     for cap in &module.caps {
         feed_str(&mut hasher, cap);
     }
+    hash_requirements(&mut hasher, module);
     for item in &module.items {
         hash_item(&mut hasher, item, include_symbol_map);
     }
-    let digest = hasher.finalize();
-    format!("{digest:x}")
+    hasher.finish()
+}
+
+/// The `targets` and `toolchain` header fields; targets are a set. A cell
+/// declaring neither feeds nothing, so it hashes as it did before the
+/// fields existed.
+fn hash_requirements(hasher: &mut HashState, module: &Module) {
+    if module.targets.is_empty() && module.toolchain.is_none() {
+        return;
+    }
+    feed_str(hasher, "requires");
+    let mut targets: Vec<&str> = module.targets.iter().map(String::as_str).collect();
+    targets.sort_unstable();
+    targets.dedup();
+    hasher.update((targets.len() as u32).to_le_bytes());
+    for target in targets {
+        feed_str(hasher, target);
+    }
+    feed_opt_str(hasher, module.toolchain.as_deref());
+}
+
+/// Semantic hash of a single item, independent of the module header and of
+/// the other items. Symbol maps and inline tests all hash alike.
+pub fn item_semantic_hash(item: &Item, algo: HashAlgo) -> String {
+    let mut hasher = HashState::new(algo);
+    hash_item(&mut hasher, item, false);
+    hasher.finish()
+}
+
+/// Format hash of a single item: like [`item_semantic_hash`], but it also
+/// changes with doc comments, symbol maps and inline tests.
+pub fn item_format_hash(item: &Item, algo: HashAlgo) -> String {
+    let mut hasher = HashState::new(algo);
+    hash_item(&mut hasher, item, true);
+    hasher.finish()
 }
 
 fn hash_item(hasher: &mut HashState, item: &Item, include_symbol_map: bool) {
@@ -61,11 +202,34 @@ fn hash_item(hasher: &mut HashState, item: &Item, include_symbol_map: bool) {
         Item::Type(ty) => {
             feed_str(hasher, "type");
             hash_type_decl(hasher, ty);
+            if include_symbol_map {
+                hash_doc(hasher, ty.doc.as_deref());
+            }
         }
         Item::Fn(func) => {
             feed_str(hasher, "fn");
             hash_fn_decl(hasher, func);
+            if include_symbol_map {
+                hash_doc(hasher, func.doc.as_deref());
+            }
         }
+        // Tests do not change what the cell does: only the format hash sees them
+        Item::Test(test) => {
+            if include_symbol_map {
+                feed_str(hasher, "test");
+                feed_str(hasher, &test.name);
+                hash_block(hasher, &test.body);
+            }
+        }
+    }
+}
+
+/// Doc comments are only seen by the format hash; cells without them hash
+/// as before they were captured.
+fn hash_doc(hasher: &mut HashState, doc: Option<&str>) {
+    if let Some(doc) = doc {
+        feed_str(hasher, "doc");
+        feed_str(hasher, doc);
     }
 }
 
@@ -88,9 +252,22 @@ fn hash_symbol_map(hasher: &mut HashState, symbols: &SymbolMap) {
 
 fn hash_type_decl(hasher: &mut HashState, ty: &TypeDecl) {
     feed_str(hasher, &ty.name);
+    hash_type_params(hasher, &ty.type_params);
     hash_type_expr(hasher, &ty.expr);
 }
 
+/// Type parameters are only hashed when declared; non-generic items hash as
+/// before generics were added.
+fn hash_type_params(hasher: &mut HashState, params: &[Ident]) {
+    if !params.is_empty() {
+        feed_str(hasher, "type_params");
+        hasher.update((params.len() as u32).to_le_bytes());
+        for param in params {
+            feed_str(hasher, param);
+        }
+    }
+}
+
 fn hash_type_expr(hasher: &mut HashState, expr: &TypeExpr) {
     match expr {
         TypeExpr::Path(segments) => {
@@ -107,6 +284,24 @@ fn hash_type_expr(hasher: &mut HashState, expr: &TypeExpr) {
                 hash_record_field(hasher, field);
             }
         }
+        TypeExpr::Union(variants) => {
+            feed_str(hasher, "union");
+            hasher.update((variants.len() as u32).to_le_bytes());
+            for variant in variants {
+                hash_union_variant(hasher, variant);
+            }
+        }
+        TypeExpr::Generic { base, args } => {
+            feed_str(hasher, "generic");
+            hasher.update((base.len() as u32).to_le_bytes());
+            for segment in base {
+                feed_str(hasher, segment);
+            }
+            hasher.update((args.len() as u32).to_le_bytes());
+            for arg in args {
+                hash_type_expr(hasher, arg);
+            }
+        }
     }
 }
 
@@ -115,8 +310,20 @@ fn hash_record_field(hasher: &mut HashState, field: &RecordField) {
     hash_type_expr(hasher, &field.ty);
 }
 
+fn hash_union_variant(hasher: &mut HashState, variant: &UnionVariant) {
+    feed_str(hasher, &variant.name);
+    match &variant.payload {
+        Some(payload) => {
+            hasher.update([1]);
+            hash_type_expr(hasher, payload);
+        }
+        None => hasher.update([0]),
+    }
+}
+
 fn hash_fn_decl(hasher: &mut HashState, func: &FnDecl) {
     feed_str(hasher, &func.name);
+    hash_type_params(hasher, &func.type_params);
     hasher.update((func.params.len() as u32).to_le_bytes());
     for param in &func.params {
         hash_param(hasher, param);
@@ -135,12 +342,198 @@ fn hash_param(hasher: &mut HashState, param: &Param) {
 }
 
 fn hash_block(hasher: &mut HashState, block: &Block) {
-    // Normalize the raw text to exclude formatting variations.
-    // This ensures semantic hash invariance across compact/relaxed transformations.
+    if !block.statements.is_empty() {
+        // Hash the statement tree: spans, whitespace, comments and
+        // parentheses are trivia, so reformatting a body keeps its hash.
+        feed_str(hasher, "stmts");
+        hash_stmts(hasher, &block.statements);
+        return;
+    }
+    // Bodies without parsed statements fall back to the raw text, with
+    // whitespace removed so compact/relaxed transformations keep the hash.
     let normalized = normalize_block_text(&block.raw);
     feed_str(hasher, &normalized);
 }
 
+fn hash_stmts(hasher: &mut HashState, stmts: &[Stmt]) {
+    hasher.update((stmts.len() as u32).to_le_bytes());
+    for stmt in stmts {
+        hash_stmt(hasher, stmt);
+    }
+}
+
+fn hash_stmt(hasher: &mut HashState, stmt: &Stmt) {
+    match stmt {
+        Stmt::Let(let_stmt) => {
+            feed_str(hasher, if let_stmt.mutable { "let_mut" } else { "let" });
+            feed_str(hasher, &let_stmt.name);
+            match &let_stmt.ty {
+                Some(ty) => {
+                    hasher.update([1]);
+                    hash_type_expr(hasher, ty);
+                }
+                None => hasher.update([0]),
+            }
+            hash_expr(hasher, &let_stmt.init);
+        }
+        Stmt::Assign(assign) => {
+            feed_str(hasher, "assign");
+            hash_expr(hasher, &assign.target);
+            hash_expr(hasher, &assign.value);
+        }
+        Stmt::If(if_stmt) => hash_if(hasher, if_stmt),
+        Stmt::While(while_stmt) => {
+            feed_str(hasher, "while");
+            hash_expr(hasher, &while_stmt.cond);
+            hash_stmts(hasher, &while_stmt.body.statements);
+        }
+        Stmt::Return(ret) => {
+            feed_str(hasher, "ret");
+            match &ret.value {
+                Some(value) => {
+                    hasher.update([1]);
+                    hash_expr(hasher, value);
+                }
+                None => hasher.update([0]),
+            }
+        }
+        Stmt::Expr(expr_stmt) => {
+            feed_str(hasher, "expr");
+            hash_expr(hasher, &expr_stmt.expr);
+        }
+    }
+}
+
+fn hash_if(hasher: &mut HashState, if_stmt: &IfStmt) {
+    feed_str(hasher, "if");
+    hash_expr(hasher, &if_stmt.cond);
+    hash_stmts(hasher, &if_stmt.then_block.statements);
+    match if_stmt.else_block.as_deref() {
+        Some(ElseBlock::Block(block)) => {
+            feed_str(hasher, "else");
+            hash_stmts(hasher, &block.statements);
+        }
+        Some(ElseBlock::If(else_if)) => {
+            feed_str(hasher, "else_if");
+            hash_if(hasher, else_if);
+        }
+        None => feed_str(hasher, "end_if"),
+    }
+}
+
+fn hash_expr(hasher: &mut HashState, expr: &Expr) {
+    match expr {
+        Expr::Ident(name, _) => {
+            feed_str(hasher, "ident");
+            feed_str(hasher, name);
+        }
+        Expr::Literal(lit, _) => hash_literal(hasher, lit),
+        Expr::BinOp { lhs, op, rhs, .. } => {
+            feed_str(hasher, binop_tag(*op));
+            hash_expr(hasher, lhs);
+            hash_expr(hasher, rhs);
+        }
+        Expr::UnaryOp { op, expr, .. } => {
+            feed_str(
+                hasher,
+                match op {
+                    UnaryOp::Neg => "neg",
+                    UnaryOp::Not => "not",
+                    UnaryOp::Await => "await",
+                },
+            );
+            hash_expr(hasher, expr);
+        }
+        Expr::Call { func, args, .. } => {
+            feed_str(hasher, "call");
+            hash_expr(hasher, func);
+            hasher.update((args.len() as u32).to_le_bytes());
+            for arg in args {
+                hash_expr(hasher, arg);
+            }
+        }
+        Expr::Field { base, field, .. } => {
+            feed_str(hasher, "field");
+            hash_expr(hasher, base);
+            feed_str(hasher, field);
+        }
+        Expr::Record { fields, .. } => {
+            feed_str(hasher, "record");
+            hasher.update((fields.len() as u32).to_le_bytes());
+            for field in fields {
+                feed_str(hasher, &field.name);
+                hash_expr(hasher, &field.value);
+            }
+        }
+        Expr::RecordUpdate { base, fields, .. } => {
+            feed_str(hasher, "record_update");
+            hash_expr(hasher, base);
+            hasher.update((fields.len() as u32).to_le_bytes());
+            for field in fields {
+                feed_str(hasher, &field.name);
+                hash_expr(hasher, &field.value);
+            }
+        }
+        Expr::Path(segments, _) => {
+            feed_str(hasher, "path");
+            hasher.update((segments.len() as u32).to_le_bytes());
+            for segment in segments {
+                feed_str(hasher, segment);
+            }
+        }
+        // Grouping is already explicit in the tree
+        Expr::Paren(inner, _) => hash_expr(hasher, inner),
+    }
+}
+
+fn hash_literal(hasher: &mut HashState, lit: &Literal) {
+    match lit {
+        Literal::Bool(b) => {
+            feed_str(hasher, "bool");
+            hasher.update([*b as u8]);
+        }
+        Literal::Str(s) => {
+            feed_str(hasher, "str");
+            feed_str(hasher, s);
+        }
+        Literal::U16(n) => {
+            feed_str(hasher, "u16");
+            hasher.update(n.to_le_bytes());
+        }
+        Literal::U32(n) => {
+            feed_str(hasher, "u32");
+            hasher.update(n.to_le_bytes());
+        }
+        Literal::U64(n) => {
+            feed_str(hasher, "u64");
+            hasher.update(n.to_le_bytes());
+        }
+        Literal::Int(n) => {
+            feed_str(hasher, "int");
+            hasher.update(n.to_le_bytes());
+        }
+        Literal::Unit => feed_str(hasher, "unit"),
+    }
+}
+
+fn binop_tag(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "add",
+        BinOp::Sub => "sub",
+        BinOp::Mul => "mul",
+        BinOp::Div => "div",
+        BinOp::Mod => "mod",
+        BinOp::Eq => "eq",
+        BinOp::Ne => "ne",
+        BinOp::Lt => "lt",
+        BinOp::Le => "le",
+        BinOp::Gt => "gt",
+        BinOp::Ge => "ge",
+        BinOp::And => "and",
+        BinOp::Or => "or",
+    }
+}
+
 /// Normalize block text by removing all whitespace except within string literals.
 /// This ensures that formatting differences (indentation, newlines, etc.) don't affect the semantic hash.
 fn normalize_block_text(raw: &str) -> String {
@@ -218,13 +611,31 @@ mod tests {
             .iter_mut()
             .find(|item| matches!(item, Item::Symbol(_)))
         {
-            symbols.pairs[0].short.push('x');
+            symbols.pairs[0].short = format!("{}x", symbols.pairs[0].short).into();
         }
         let hashes_modified = module_hashes(&modified);
         assert_eq!(hashes.semantic, hashes_modified.semantic);
         assert_ne!(hashes.format, hashes_modified.format);
     }
 
+    #[test]
+    fn doc_comments_only_change_the_format_hash() {
+        let source = include_str!("../../../fixtures/cells/http_server.z1c");
+        let module = z1_parse::parse_module(source).expect("parse");
+        let hashes = module_hashes(&module);
+
+        let mut documented = module.clone();
+        for item in &mut documented.items {
+            if let Item::Fn(func) = item {
+                func.doc = Some("Serves requests.".to_string());
+            }
+        }
+        let hashes_documented = module_hashes(&documented);
+        assert_eq!(hashes.semantic, hashes_documented.semantic);
+        assert_eq!(hashes.api, hashes_documented.api);
+        assert_ne!(hashes.format, hashes_documented.format);
+    }
+
     #[test]
     fn semantic_hash_changes_on_body_edits() {
         let source = include_str!("../../../fixtures/cells/http_server.z1c");
@@ -235,10 +646,173 @@ mod tests {
             .iter_mut()
             .find(|item| matches!(item, Item::Fn(_)))
         {
-            func.body.raw.push_str("// change");
+            func.body
+                .statements
+                .extend(z1_parse::body::parse_body("ret 0;").unwrap());
         }
         let hashes_modified = module_hashes(&module);
         assert_ne!(hashes.semantic, hashes_modified.semantic);
         assert_ne!(hashes.format, hashes_modified.format);
     }
+
+    #[test]
+    fn requirements_are_hashed_only_when_declared() {
+        let source = include_str!("../../../fixtures/cells/http_server.z1c");
+        let module = z1_parse::parse_module(source).expect("parse");
+        let hashes = module_hashes(&module);
+
+        let mut targeted = module.clone();
+        targeted.targets = vec!["wasm".to_string(), "ts".to_string()];
+        let targeted_hashes = module_hashes(&targeted);
+        assert_ne!(hashes.semantic, targeted_hashes.semantic);
+        assert_ne!(hashes.api, targeted_hashes.api);
+
+        // Targets are a set
+        targeted.targets.reverse();
+        assert_eq!(module_hashes(&targeted), targeted_hashes);
+
+        let mut pinned = module.clone();
+        pinned.toolchain = Some("0.3".to_string());
+        assert_ne!(hashes.semantic, module_hashes(&pinned).semantic);
+    }
+
+    /// `ret (a + b);` parsed into statements, with `raw` as written.
+    fn body(raw: &str, op: z1_ast::BinOp, paren: bool) -> Block {
+        use z1_ast::{ReturnStmt, Span};
+        let span = Span::new(0, 0);
+        let mut value = Expr::BinOp {
+            lhs: Box::new(Expr::Ident("a".into(), span)),
+            op,
+            rhs: Box::new(Expr::Ident("b".into(), span)),
+            span,
+        };
+        if paren {
+            value = Expr::Paren(Box::new(value), span);
+        }
+        Block {
+            raw: raw.to_string(),
+            statements: vec![Stmt::Return(ReturnStmt {
+                value: Some(value),
+                span,
+            })],
+            span,
+        }
+    }
+
+    fn with_body(block: Block) -> ModuleHashes {
+        let source = include_str!("../../../fixtures/cells/http_server.z1c");
+        let mut module = z1_parse::parse_module(source).expect("parse");
+        if let Some(Item::Fn(func)) = module
+            .items
+            .iter_mut()
+            .find(|item| matches!(item, Item::Fn(_)))
+        {
+            func.body = block;
+        }
+        module_hashes(&module)
+    }
+
+    #[test]
+    fn semantic_hash_uses_parsed_statements() {
+        let base = with_body(body("ret a + b;", z1_ast::BinOp::Add, false));
+        // Reformatting, comments and redundant parentheses are trivia
+        let reformatted = with_body(body(
+            "\n  // sum\n  ret (a +\n b);\n",
+            z1_ast::BinOp::Add,
+            true,
+        ));
+        assert_eq!(base.semantic, reformatted.semantic);
+
+        let changed = with_body(body("ret a - b;", z1_ast::BinOp::Sub, false));
+        assert_ne!(base.semantic, changed.semantic);
+    }
+
+    #[test]
+    fn hashes_are_prefixed_with_their_algorithm() {
+        let source = include_str!("../../../fixtures/cells/http_server.z1c");
+        let module = z1_parse::parse_module(source).expect("parse");
+
+        let sha3 = module_hashes(&module);
+        let blake3 = module_hashes_with(&module, HashAlgo::Blake3);
+        assert!(sha3.semantic.starts_with("sha3-256:"));
+        assert!(blake3.semantic.starts_with("blake3:"));
+        assert_ne!(split_hash(&sha3.semantic), split_hash(&blake3.semantic));
+        assert_eq!(
+            split_hash(&blake3.format).map(|(algo, _)| algo),
+            Some(HashAlgo::Blake3)
+        );
+
+        // Unprefixed hashes from before algorithm agility read as SHA3-256
+        let (_, hex) = split_hash(&sha3.semantic).unwrap();
+        assert_eq!(split_hash(hex), Some((HashAlgo::Sha3_256, hex)));
+        assert_eq!(split_hash("md5:abc"), None);
+    }
+
+    #[test]
+    fn canonicalization_ignores_declaration_order_and_effect_casing() {
+        let source = include_str!("../../../fixtures/cells/http_server.z1c");
+        let module = z1_parse::parse_module(source).expect("parse");
+
+        let mut shuffled = module.clone();
+        shuffled.items.reverse();
+        shuffled.caps.reverse();
+        shuffled.caps.push(shuffled.caps[0].clone());
+        for item in &mut shuffled.items {
+            if let Item::Fn(func) = item {
+                func.effects = func
+                    .effects
+                    .iter()
+                    .rev()
+                    .map(|e| e.to_uppercase().into())
+                    .collect();
+            }
+        }
+        assert_ne!(
+            module_hashes(&module).semantic,
+            module_hashes(&shuffled).semantic
+        );
+
+        let options = HashOptions {
+            canonicalize: true,
+            ..HashOptions::default()
+        };
+        assert_eq!(
+            module_hashes_with_options(&module, &options),
+            module_hashes_with_options(&shuffled, &options)
+        );
+    }
+
+    #[test]
+    fn api_hash_tracks_signatures_only() {
+        let source = include_str!("../../../fixtures/cells/http_server.z1c");
+        let module = z1_parse::parse_module(source).expect("parse");
+        let base = module_hashes(&module);
+
+        // Body edits and reordering keep the API
+        let mut body_edit = module.clone();
+        body_edit.items.reverse();
+        if let Some(Item::Fn(func)) = body_edit
+            .items
+            .iter_mut()
+            .find(|item| matches!(item, Item::Fn(_)))
+        {
+            func.body
+                .statements
+                .extend(z1_parse::body::parse_body("ret 0;").unwrap());
+        }
+        let edited = module_hashes(&body_edit);
+        assert_ne!(base.semantic, edited.semantic);
+        assert_eq!(base.api, edited.api);
+
+        // Changing an effect changes the API
+        let mut effect_edit = module.clone();
+        if let Some(Item::Fn(func)) = effect_edit
+            .items
+            .iter_mut()
+            .find(|item| matches!(item, Item::Fn(_)))
+        {
+            func.effects.push("fs.rw".into());
+        }
+        assert_ne!(base.api, module_hashes(&effect_edit).api);
+    }
 }
@@ -0,0 +1,321 @@
+//! Per-item hashes and the `.z1hash` manifest
+//!
+//! A manifest records the module hashes plus one semantic hash per function
+//! and type, so tools can tell *which* items changed between two versions of
+//! a cell instead of only *that* the module changed:
+//!
+//! ```text
+//! z1hash 1
+//! module http.server 1.0
+//! semhash sha3-256:3f1c…
+//! formhash sha3-256:9a0b…
+//! type Health sha3-256:51d2…
+//! fn handler sha3-256:77e0…
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+use z1_ast::{Item, Module};
+
+use crate::{item_semantic_hash, module_hashes_with, HashAlgo};
+
+/// File extension used for manifests written next to a cell.
+pub const MANIFEST_EXTENSION: &str = "z1hash";
+
+const MANIFEST_HEADER: &str = "z1hash 1";
+
+/// Kind of a hashed top-level item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ItemKind {
+    Fn,
+    Type,
+}
+
+impl ItemKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ItemKind::Fn => "fn",
+            ItemKind::Type => "type",
+        }
+    }
+}
+
+impl fmt::Display for ItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ItemKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fn" => Ok(ItemKind::Fn),
+            "type" => Ok(ItemKind::Type),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Semantic hash of one function or type declaration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemHash {
+    pub name: String,
+    pub kind: ItemKind,
+    pub semantic: String,
+}
+
+/// Hash every function and type of `module`, in source order.
+///
+/// Imports, symbol maps and inline tests are not items of their own; they
+/// only contribute to the module hashes.
+pub fn item_hashes(module: &Module) -> Vec<ItemHash> {
+    item_hashes_with(module, HashAlgo::default())
+}
+
+/// [`item_hashes`] with an explicit algorithm.
+pub fn item_hashes_with(module: &Module, algo: HashAlgo) -> Vec<ItemHash> {
+    module
+        .items
+        .iter()
+        .filter_map(|item| {
+            let (name, kind) = match item {
+                Item::Fn(func) => (&func.name, ItemKind::Fn),
+                Item::Type(ty) => (&ty.name, ItemKind::Type),
+                Item::Import(_) | Item::Symbol(_) | Item::Test(_) => return None,
+            };
+            Some(ItemHash {
+                name: name.to_string(),
+                kind,
+                semantic: item_semantic_hash(item, algo),
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ManifestError {
+    #[error("missing `{MANIFEST_HEADER}` header")]
+    MissingHeader,
+    #[error("line {line}: {message}")]
+    Malformed { line: usize, message: String },
+}
+
+/// A change to a single item between two manifests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ItemChange {
+    Added { kind: ItemKind, name: String },
+    Removed { kind: ItemKind, name: String },
+    Changed { kind: ItemKind, name: String },
+}
+
+/// Contents of a `.z1hash` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    /// Dotted module path followed by the version, if any
+    pub module: String,
+    pub semantic: String,
+    pub format: String,
+    pub items: Vec<ItemHash>,
+}
+
+impl Manifest {
+    pub fn from_module(module: &Module) -> Self {
+        Self::from_module_with(module, HashAlgo::default())
+    }
+
+    pub fn from_module_with(module: &Module, algo: HashAlgo) -> Self {
+        let hashes = module_hashes_with(module, algo);
+        let mut name = module.path.to_string();
+        if let Some(version) = &module.version {
+            name.push(' ');
+            name.push_str(version);
+        }
+        Self {
+            module: name,
+            semantic: hashes.semantic,
+            format: hashes.format,
+            items: item_hashes_with(module, algo),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ManifestError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(idx, line)| (idx + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        match lines.next() {
+            Some((_, MANIFEST_HEADER)) => {}
+            _ => return Err(ManifestError::MissingHeader),
+        }
+
+        let mut module = None;
+        let mut semantic = None;
+        let mut format = None;
+        let mut items = Vec::new();
+        for (line, content) in lines {
+            let malformed = |message: &str| ManifestError::Malformed {
+                line,
+                message: message.to_string(),
+            };
+            let (key, rest) = content
+                .split_once(' ')
+                .ok_or_else(|| malformed("expected `<key> <value>`"))?;
+            match key {
+                "module" => module = Some(rest.to_string()),
+                "semhash" => semantic = Some(rest.to_string()),
+                "formhash" => format = Some(rest.to_string()),
+                _ => {
+                    let kind = key
+                        .parse::<ItemKind>()
+                        .map_err(|_| malformed(&format!("unknown entry `{key}`")))?;
+                    let (name, hash) = rest
+                        .split_once(' ')
+                        .ok_or_else(|| malformed("expected `<kind> <name> <hash>`"))?;
+                    items.push(ItemHash {
+                        name: name.to_string(),
+                        kind,
+                        semantic: hash.to_string(),
+                    });
+                }
+            }
+        }
+
+        let missing = |key: &str| ManifestError::Malformed {
+            line: 0,
+            message: format!("missing `{key}` entry"),
+        };
+        Ok(Self {
+            module: module.ok_or_else(|| missing("module"))?,
+            semantic: semantic.ok_or_else(|| missing("semhash"))?,
+            format: format.ok_or_else(|| missing("formhash"))?,
+            items,
+        })
+    }
+
+    /// Items added, removed or changed in `newer` relative to `self`.
+    ///
+    /// Items are matched by kind and name; changes are reported in the order
+    /// of `self`, followed by additions in the order of `newer`.
+    pub fn diff(&self, newer: &Manifest) -> Vec<ItemChange> {
+        let find = |items: &'_ [ItemHash], kind: ItemKind, name: &str| {
+            items
+                .iter()
+                .find(|item| item.kind == kind && item.name == name)
+                .map(|item| item.semantic.clone())
+        };
+
+        let mut changes = Vec::new();
+        for old in &self.items {
+            let (kind, name) = (old.kind, old.name.clone());
+            match find(&newer.items, old.kind, &old.name) {
+                None => changes.push(ItemChange::Removed { kind, name }),
+                Some(hash) if hash != old.semantic => {
+                    changes.push(ItemChange::Changed { kind, name })
+                }
+                Some(_) => {}
+            }
+        }
+        for new in &newer.items {
+            if find(&self.items, new.kind, &new.name).is_none() {
+                changes.push(ItemChange::Added {
+                    kind: new.kind,
+                    name: new.name.clone(),
+                });
+            }
+        }
+        changes
+    }
+}
+
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{MANIFEST_HEADER}")?;
+        writeln!(f, "module {}", self.module)?;
+        writeln!(f, "semhash {}", self.semantic)?;
+        writeln!(f, "formhash {}", self.format)?;
+        for item in &self.items {
+            writeln!(f, "{} {} {}", item.kind, item.name, item.semantic)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> Module {
+        let source = include_str!("../../../fixtures/cells/http_server.z1c");
+        z1_parse::parse_module(source).expect("parse")
+    }
+
+    #[test]
+    fn manifest_round_trips() {
+        let manifest = Manifest::from_module(&fixture());
+        assert!(!manifest.items.is_empty());
+        let parsed = Manifest::parse(&manifest.to_string()).expect("parse manifest");
+        assert_eq!(parsed, manifest);
+    }
+
+    #[test]
+    fn diff_reports_only_the_edited_item() {
+        let module = fixture();
+        let before = Manifest::from_module(&module);
+
+        let mut edited = module.clone();
+        let target = edited
+            .items
+            .iter_mut()
+            .find_map(|item| match item {
+                Item::Fn(func) => Some(func),
+                _ => None,
+            })
+            .expect("fixture has a function");
+        target
+            .body
+            .statements
+            .extend(z1_parse::body::parse_body("ret 0;").unwrap());
+        let name = target.name;
+        edited.items.retain(|item| !matches!(item, Item::Type(_)));
+
+        let after = Manifest::from_module(&edited);
+        let changes = before.diff(&after);
+        assert!(changes.contains(&ItemChange::Changed {
+            kind: ItemKind::Fn,
+            name: name.to_string()
+        }));
+        assert!(changes
+            .iter()
+            .all(|change| matches!(change, ItemChange::Changed { .. })
+                || matches!(
+                    change,
+                    ItemChange::Removed {
+                        kind: ItemKind::Type,
+                        ..
+                    }
+                )));
+        assert_eq!(
+            changes
+                .iter()
+                .filter(|change| matches!(change, ItemChange::Changed { .. }))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn parse_rejects_unknown_entries() {
+        assert_eq!(
+            Manifest::parse("module a\n"),
+            Err(ManifestError::MissingHeader)
+        );
+        let err = Manifest::parse("z1hash 1\nconst X abc\n").unwrap_err();
+        assert!(matches!(err, ManifestError::Malformed { line: 2, .. }));
+    }
+}
@@ -0,0 +1,222 @@
+//! Merkle tree over the cells of a workspace
+//!
+//! Leaves are `(module path, semhash)` pairs sorted by module path, so the
+//! root commits to the exact semantic state of every cell regardless of
+//! where the files live. Leaves and inner nodes are hashed with distinct
+//! tags; an unpaired node at the end of a level is carried up unchanged.
+
+use crate::algo::{format_hash, HashState};
+use crate::{split_hash, HashAlgo};
+
+const LEAF_TAG: u8 = 0;
+const NODE_TAG: u8 = 1;
+
+/// A cell committed to by the tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleLeaf {
+    /// Dotted module path
+    pub module: String,
+    pub semantic: String,
+}
+
+/// Which side of the running hash a proof sibling sits on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofStep {
+    pub side: Side,
+    /// Sibling node hash, `<algo>:<hex>`
+    pub hash: String,
+}
+
+/// Inclusion proof for one leaf.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleProof {
+    pub leaf: MerkleLeaf,
+    /// Siblings from the leaf level up to (excluding) the root
+    pub steps: Vec<ProofStep>,
+}
+
+impl MerkleProof {
+    /// Whether this proof connects its leaf to `root`.
+    pub fn verify(&self, root: &str) -> bool {
+        let Some((algo, _)) = split_hash(root) else {
+            return false;
+        };
+        let mut current = leaf_hash(algo, &self.leaf);
+        for step in &self.steps {
+            let sibling = match split_hash(&step.hash) {
+                Some((step_algo, hex)) if step_algo == algo => match decode_hex(hex) {
+                    Some(bytes) => bytes,
+                    None => return false,
+                },
+                _ => return false,
+            };
+            current = match step.side {
+                Side::Left => node_hash(algo, &sibling, &current),
+                Side::Right => node_hash(algo, &current, &sibling),
+            };
+        }
+        format_hash(algo, &current) == root
+    }
+}
+
+/// Merkle tree of cell semantic hashes.
+#[derive(Debug, Clone)]
+pub struct MerkleTree {
+    algo: HashAlgo,
+    leaves: Vec<MerkleLeaf>,
+    /// `levels[0]` holds the leaf hashes, the last level the root
+    levels: Vec<Vec<Vec<u8>>>,
+}
+
+impl MerkleTree {
+    /// Build a tree; leaves are sorted by module path first.
+    pub fn new(mut leaves: Vec<MerkleLeaf>, algo: HashAlgo) -> Self {
+        leaves.sort_by(|a, b| a.module.cmp(&b.module));
+        let mut levels = vec![leaves
+            .iter()
+            .map(|leaf| leaf_hash(algo, leaf))
+            .collect::<Vec<_>>()];
+        while levels.last().is_some_and(|level| level.len() > 1) {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(algo, left, right),
+                    [single] => single.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        Self {
+            algo,
+            leaves,
+            levels,
+        }
+    }
+
+    pub fn algo(&self) -> HashAlgo {
+        self.algo
+    }
+
+    /// Leaves in tree order.
+    pub fn leaves(&self) -> &[MerkleLeaf] {
+        &self.leaves
+    }
+
+    /// Root hash, `<algo>:<hex>`. An empty tree hashes to the empty digest.
+    pub fn root(&self) -> String {
+        match self.levels.last().and_then(|level| level.first()) {
+            Some(root) => format_hash(self.algo, root),
+            None => HashState::new(self.algo).finish(),
+        }
+    }
+
+    /// Inclusion proof for the cell with module path `module`.
+    pub fn proof(&self, module: &str) -> Option<MerkleProof> {
+        let mut index = self
+            .leaves
+            .binary_search_by(|leaf| leaf.module.as_str().cmp(module))
+            .ok()?;
+        let leaf = self.leaves[index].clone();
+        let mut steps = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = index ^ 1;
+            if let Some(hash) = level.get(sibling) {
+                steps.push(ProofStep {
+                    side: if sibling < index {
+                        Side::Left
+                    } else {
+                        Side::Right
+                    },
+                    hash: format_hash(self.algo, hash),
+                });
+            }
+            index /= 2;
+        }
+        Some(MerkleProof { leaf, steps })
+    }
+}
+
+fn leaf_hash(algo: HashAlgo, leaf: &MerkleLeaf) -> Vec<u8> {
+    let mut hasher = HashState::new(algo);
+    hasher.update([LEAF_TAG]);
+    hasher.update(leaf.module.as_bytes());
+    hasher.update([0]);
+    hasher.update(leaf.semantic.as_bytes());
+    hasher.digest()
+}
+
+fn node_hash(algo: HashAlgo, left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut hasher = HashState::new(algo);
+    hasher.update([NODE_TAG]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.digest()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(hex.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(count: usize) -> Vec<MerkleLeaf> {
+        (0..count)
+            .map(|idx| MerkleLeaf {
+                module: format!("app.cell{idx}"),
+                semantic: format!("sha3-256:{idx:064x}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_leaf_proves_against_the_root() {
+        for count in 1..=7 {
+            for algo in HashAlgo::ALL {
+                let tree = MerkleTree::new(leaves(count), algo);
+                let root = tree.root();
+                for leaf in tree.leaves() {
+                    let proof = tree.proof(&leaf.module).expect("proof");
+                    assert!(proof.verify(&root), "{count} leaves, {}", leaf.module);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn root_is_order_independent_and_detects_changes() {
+        let mut shuffled = leaves(5);
+        shuffled.reverse();
+        let tree = MerkleTree::new(leaves(5), HashAlgo::Sha3_256);
+        assert_eq!(
+            tree.root(),
+            MerkleTree::new(shuffled, HashAlgo::Sha3_256).root()
+        );
+
+        let mut edited = leaves(5);
+        edited[3].semantic = format!("sha3-256:{:064x}", 99);
+        assert_ne!(
+            tree.root(),
+            MerkleTree::new(edited, HashAlgo::Sha3_256).root()
+        );
+
+        // A proof for the old content does not verify against another root
+        let mut proof = tree.proof("app.cell3").unwrap();
+        proof.leaf.semantic = format!("sha3-256:{:064x}", 99);
+        assert!(!proof.verify(&tree.root()));
+        assert!(tree.proof("app.missing").is_none());
+    }
+}
@@ -0,0 +1,100 @@
+//! Merkle roots of workspaces of cell files
+//!
+//! The only part of the crate touching the file system, behind the `fs`
+//! feature, so the rest builds for targets without one.
+
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::merkle::{MerkleLeaf, MerkleTree};
+use crate::{module_hashes_with, HashAlgo};
+
+#[derive(Debug, Error)]
+pub enum WorkspaceError {
+    #[error("failed to read {path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("failed to parse {path}: {message}")]
+    Parse { path: PathBuf, message: String },
+    #[error("module `{module}` is defined by both {first} and {second}")]
+    DuplicateModule {
+        module: String,
+        first: PathBuf,
+        second: PathBuf,
+    },
+}
+
+/// Merkle tree over the cells at `paths`, hashed with SHA3-256.
+pub fn workspace_root_hash<P: AsRef<Path>>(paths: &[P]) -> Result<MerkleTree, WorkspaceError> {
+    workspace_root_hash_with(paths, HashAlgo::default())
+}
+
+/// [`workspace_root_hash`] with an explicit algorithm.
+pub fn workspace_root_hash_with<P: AsRef<Path>>(
+    paths: &[P],
+    algo: HashAlgo,
+) -> Result<MerkleTree, WorkspaceError> {
+    let mut cells: Vec<(MerkleLeaf, PathBuf)> = Vec::with_capacity(paths.len());
+    for path in paths {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|source| WorkspaceError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let module = z1_parse::parse_module(&source).map_err(|err| WorkspaceError::Parse {
+            path: path.to_path_buf(),
+            message: err.to_string(),
+        })?;
+        let leaf = MerkleLeaf {
+            module: module.path.to_string(),
+            semantic: module_hashes_with(&module, algo).semantic,
+        };
+        if let Some((_, first)) = cells.iter().find(|(other, _)| other.module == leaf.module) {
+            return Err(WorkspaceError::DuplicateModule {
+                module: leaf.module,
+                first: first.clone(),
+                second: path.to_path_buf(),
+            });
+        }
+        cells.push((leaf, path.to_path_buf()));
+    }
+    Ok(MerkleTree::new(
+        cells.into_iter().map(|(leaf, _)| leaf).collect(),
+        algo,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn workspace_root_commits_to_semantics_not_files() {
+        let fixture = |rel: &str| format!("{}/../../fixtures/{rel}", env!("CARGO_MANIFEST_DIR"));
+        let compact = [
+            fixture("fmt/http_server.compact.z1c"),
+            fixture("policy/valid_small.z1c"),
+        ];
+        let relaxed = [
+            fixture("policy/valid_small.z1c"),
+            fixture("fmt/http_server.relaxed.z1r"),
+        ];
+        let tree = workspace_root_hash(&compact).expect("hash workspace");
+        assert_eq!(tree.leaves().len(), 2);
+        assert_eq!(
+            tree.root(),
+            workspace_root_hash(&relaxed)
+                .expect("hash workspace")
+                .root()
+        );
+
+        let duplicate = [&compact[0], &relaxed[1]];
+        assert!(matches!(
+            workspace_root_hash(&duplicate),
+            Err(WorkspaceError::DuplicateModule { .. })
+        ));
+    }
+}
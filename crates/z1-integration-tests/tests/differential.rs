@@ -0,0 +1,509 @@
+//! Differential testing: the reference interpreter (`z1-eval`), the
+//! TypeScript backend (run in node) and the WASM backend (run in wasmtime)
+//! must agree on pure functions.
+//!
+//! - Arguments and results are scalars (`Bool`, `U16`, `U32`, `U64`, `Unit`).
+//! - Inputs stay clear of integer overflow: JS numbers do not wrap, so
+//!   overflow is outside the cross-backend contract.
+//! - When the interpreter fails (division by zero, missing `ret`), WASM must
+//!   trap. JS has no equivalent failure, so those cases skip node.
+//! - The node leg runs the TypeScript output with types erased
+//!   (`generate_javascript`), and is skipped when node is not installed.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+use wasmtime::{Engine, Instance, Module, Store, Val};
+use z1_eval::{NoHost, Value};
+use z1_ir::*;
+
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../..")
+}
+
+/// A function call to run on every engine.
+struct Case {
+    func: String,
+    args: Vec<Value>,
+}
+
+/// Outcome rendered the way the interpreter displays values.
+type Outcome = Result<String, String>;
+
+fn is_scalar(ty: &IrType) -> bool {
+    matches!(
+        ty,
+        IrType::Bool | IrType::U16 | IrType::U32 | IrType::U64 | IrType::Unit
+    )
+}
+
+fn is_pure_scalar(func: &IrFunction) -> bool {
+    func.effects.iter().all(|e| e == "pure")
+        && is_scalar(&func.return_type)
+        && func.params.iter().all(|(_, ty)| is_scalar(ty))
+}
+
+fn run_interpreter(module: &IrModule, case: &Case) -> Outcome {
+    z1_eval::run(module, &case.func, case.args.clone(), &mut NoHost)
+        .map(|value| value.to_string())
+        .map_err(|e| e.to_string())
+}
+
+fn run_wasm(module: &IrModule, cases: &[Case]) -> Vec<Outcome> {
+    let binary = z1_codegen_wasm::generate_wasm_binary(module).expect("WASM should assemble");
+    let engine = Engine::default();
+    let wasm_module = Module::new(&engine, &binary).expect("WASM should compile");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &wasm_module, &[]).expect("WASM should instantiate");
+
+    cases
+        .iter()
+        .map(|case| {
+            let func = module
+                .functions
+                .iter()
+                .find(|f| f.name == case.func)
+                .unwrap();
+            let export = instance
+                .get_func(&mut store, &case.func)
+                .unwrap_or_else(|| panic!("missing export {}", case.func));
+            let params: Vec<Val> = case
+                .args
+                .iter()
+                .map(|arg| match arg {
+                    Value::Bool(b) => Val::I32(*b as i32),
+                    Value::U16(n) => Val::I32(*n as i32),
+                    Value::U32(n) => Val::I32(*n as i32),
+                    Value::U64(n) => Val::I64(*n as i64),
+                    other => panic!("non-scalar argument {other}"),
+                })
+                .collect();
+            let mut results = vec![Val::I32(0); (func.return_type != IrType::Unit) as usize];
+            export
+                .call(&mut store, &params, &mut results)
+                .map_err(|e| format!("trap: {e}"))?;
+            let value = match (&func.return_type, results.first()) {
+                (IrType::Unit, _) => Value::Unit,
+                (IrType::Bool, Some(Val::I32(v))) => Value::Bool(*v != 0),
+                (IrType::U16, Some(Val::I32(v))) => Value::U16(*v as u16),
+                (IrType::U32, Some(Val::I32(v))) => Value::U32(*v as u32),
+                (IrType::U64, Some(Val::I64(v))) => Value::U64(*v as u64),
+                (ty, result) => panic!("unexpected {ty:?} result {result:?}"),
+            };
+            Ok(value.to_string())
+        })
+        .collect()
+}
+
+fn node_available() -> bool {
+    Command::new("node")
+        .arg("--version")
+        .output()
+        .is_ok_and(|out| out.status.success())
+}
+
+/// Run `cases` against the JavaScript output in one node process.
+fn run_node(module: &IrModule, cases: &[Case]) -> Vec<Outcome> {
+    let mut script = z1_codegen_ts::generate_javascript(module);
+    script.push_str(
+        r#"
+function show(v) {
+  if (v === undefined) return "()";
+  return String(v);
+}
+const cases = ["#,
+    );
+    for case in cases {
+        let args: Vec<String> = case.args.iter().map(|arg| arg.to_string()).collect();
+        script.push_str(&format!("[{}, [{}]], ", case.func, args.join(", ")));
+    }
+    script.push_str(
+        r#"];
+for (const [f, args] of cases) {
+  try {
+    console.log("ok " + show(f(...args)));
+  } catch (e) {
+    console.log("err " + String(e).split("\n")[0]);
+  }
+}
+"#,
+    );
+
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("cell.mjs");
+    fs::write(&path, &script).unwrap();
+    let output = Command::new("node").arg(&path).output().unwrap();
+    assert!(
+        output.status.success(),
+        "node failed:\n{}\n{script}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| match line.split_once(' ') {
+            Some(("ok", value)) => Ok(value.to_string()),
+            _ => Err(line.to_string()),
+        })
+        .collect()
+}
+
+/// Run every case on all engines and report disagreements.
+fn check(module: &IrModule, cases: &[Case]) -> Vec<String> {
+    let expected: Vec<Outcome> = cases
+        .iter()
+        .map(|case| run_interpreter(module, case))
+        .collect();
+    let mut mismatches = Vec::new();
+    let mut report = |engine: &str, case: &Case, want: &Outcome, got: &Outcome| {
+        let args: Vec<String> = case.args.iter().map(|a| a.to_string()).collect();
+        mismatches.push(format!(
+            "{}::{}({}): interpreter {want:?}, {engine} {got:?}",
+            module.name,
+            case.func,
+            args.join(", ")
+        ));
+    };
+
+    let wasm = run_wasm(module, cases);
+    for ((case, want), got) in cases.iter().zip(&expected).zip(&wasm) {
+        if want.is_ok() != got.is_ok() || (want.is_ok() && want != got) {
+            report("wasm", case, want, got);
+        }
+    }
+
+    // Imports would need host modules in node
+    if node_available() && module.imports.is_empty() {
+        let node = run_node(module, cases);
+        for ((case, want), got) in cases.iter().zip(&expected).zip(&node) {
+            if want.is_ok() && want != got {
+                report("node", case, want, got);
+            }
+        }
+    }
+    mismatches
+}
+
+/// Sample arguments for a parameter type.
+fn samples(ty: &IrType) -> Vec<Value> {
+    match ty {
+        IrType::Bool => vec![Value::Bool(false), Value::Bool(true)],
+        IrType::U16 => vec![Value::U16(0), Value::U16(1), Value::U16(300)],
+        IrType::U32 => vec![
+            Value::U32(0),
+            Value::U32(1),
+            Value::U32(7),
+            Value::U32(1000),
+        ],
+        IrType::U64 => vec![Value::U64(0), Value::U64(3), Value::U64(1 << 40)],
+        _ => vec![Value::Unit],
+    }
+}
+
+/// Cartesian product of sample arguments for every parameter.
+fn sample_cases(func: &IrFunction) -> Vec<Case> {
+    let mut arg_sets = vec![Vec::new()];
+    for (_, ty) in &func.params {
+        arg_sets = arg_sets
+            .into_iter()
+            .flat_map(|args: Vec<Value>| {
+                samples(ty).into_iter().map(move |sample| {
+                    let mut next = args.clone();
+                    next.push(sample);
+                    next
+                })
+            })
+            .collect();
+    }
+    arg_sets
+        .into_iter()
+        .map(|args| Case {
+            func: func.name.to_string(),
+            args,
+        })
+        .collect()
+}
+
+#[test]
+fn test_fixture_cells_agree() {
+    let root = workspace_root();
+    let mut cells = Vec::new();
+    for dir in ["fixtures/cells", "fixtures/wasm"] {
+        for entry in fs::read_dir(root.join(dir)).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|ext| ext == "z1c") {
+                cells.push(path);
+            }
+        }
+    }
+    cells.sort();
+
+    let mut mismatches = Vec::new();
+    for cell in cells {
+        let source = fs::read_to_string(&cell).unwrap();
+        let ast = z1_parse::parse_module(&source).unwrap();
+        let module = lower_to_ir(&ast).unwrap();
+        let cases: Vec<Case> = module
+            .functions
+            .iter()
+            .filter(|f| is_pure_scalar(f))
+            .flat_map(sample_cases)
+            .collect();
+        if !cases.is_empty() {
+            mismatches.extend(check(&module, &cases));
+        }
+    }
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
+
+fn var(name: &str) -> Box<IrExpr> {
+    Box::new(IrExpr::Var(name.into()))
+}
+
+fn lit(n: u32) -> Box<IrExpr> {
+    Box::new(IrExpr::Literal(IrLiteral::U32(n)))
+}
+
+fn bin(op: IrBinOp, left: Box<IrExpr>, right: Box<IrExpr>) -> Box<IrExpr> {
+    Box::new(IrExpr::BinOp { op, left, right })
+}
+
+fn call(name: &str, args: Vec<IrExpr>) -> Box<IrExpr> {
+    Box::new(IrExpr::Call {
+        func: var(name),
+        args,
+    })
+}
+
+fn ret(value: IrExpr) -> IrStmt {
+    IrStmt::Return { value: Some(value) }
+}
+
+fn assign(name: &str, value: IrExpr) -> IrStmt {
+    IrStmt::Assign {
+        target: IrExpr::Var(name.into()),
+        value,
+    }
+}
+
+fn function(name: &str, params: &[(&str, IrType)], ret: IrType, body: Vec<IrStmt>) -> IrFunction {
+    IrFunction {
+        name: name.into(),
+        type_params: vec![],
+        params: params
+            .iter()
+            .map(|(n, ty)| ((*n).into(), ty.clone()))
+            .collect(),
+        return_type: ret,
+        effects: vec!["pure".into()],
+        body: IrBlock { statements: body },
+    }
+}
+
+/// Pure functions exercising grouping, integer division, loops, recursion
+/// and boolean logic.
+fn corpus() -> IrModule {
+    use IrBinOp::*;
+    let u32_params = |names: &[&'static str]| -> Vec<(&'static str, IrType)> {
+        names.iter().map(|n| (*n, IrType::U32)).collect()
+    };
+
+    IrModule {
+        name: "corpus".to_string(),
+        version: "1.0".to_string(),
+        imports: vec![],
+        types: vec![],
+        functions: vec![
+            // (a + b) * c + a / (b + 1)
+            function(
+                "mix",
+                &u32_params(&["a", "b", "c"]),
+                IrType::U32,
+                vec![ret(*bin(
+                    Add,
+                    bin(Mul, bin(Add, var("a"), var("b")), var("c")),
+                    bin(Div, var("a"), bin(Add, var("b"), lit(1))),
+                ))],
+            ),
+            function(
+                "quot",
+                &u32_params(&["a", "b"]),
+                IrType::U32,
+                vec![ret(*bin(Div, var("a"), var("b")))],
+            ),
+            function(
+                "gcd",
+                &u32_params(&["a", "b"]),
+                IrType::U32,
+                vec![
+                    IrStmt::While {
+                        cond: *bin(Ne, var("b"), lit(0)),
+                        body: IrBlock {
+                            statements: vec![
+                                IrStmt::Let {
+                                    name: "t".into(),
+                                    mutable: false,
+                                    ty: Some(IrType::U32),
+                                    value: *bin(Mod, var("a"), var("b")),
+                                },
+                                assign("a", *var("b")),
+                                assign("b", *var("t")),
+                            ],
+                        },
+                    },
+                    ret(*var("a")),
+                ],
+            ),
+            function(
+                "fib",
+                &[("n", IrType::U64)],
+                IrType::U64,
+                vec![
+                    IrStmt::If {
+                        cond: *bin(Lt, var("n"), Box::new(IrExpr::Literal(IrLiteral::U64(2)))),
+                        then_block: IrBlock {
+                            statements: vec![ret(*var("n"))],
+                        },
+                        else_block: None,
+                    },
+                    ret(*bin(
+                        Add,
+                        call(
+                            "fib",
+                            vec![*bin(
+                                Sub,
+                                var("n"),
+                                Box::new(IrExpr::Literal(IrLiteral::U64(1))),
+                            )],
+                        ),
+                        call(
+                            "fib",
+                            vec![*bin(
+                                Sub,
+                                var("n"),
+                                Box::new(IrExpr::Literal(IrLiteral::U64(2))),
+                            )],
+                        ),
+                    )),
+                ],
+            ),
+            function(
+                "between",
+                &u32_params(&["x", "lo", "hi"]),
+                IrType::Bool,
+                vec![ret(*bin(
+                    And,
+                    bin(Ge, var("x"), var("lo")),
+                    Box::new(IrExpr::UnaryOp {
+                        op: IrUnaryOp::Not,
+                        expr: bin(Gt, var("x"), var("hi")),
+                    }),
+                ))],
+            ),
+            function(
+                "collatz",
+                &u32_params(&["n"]),
+                IrType::U32,
+                vec![
+                    IrStmt::Let {
+                        name: "steps".into(),
+                        mutable: true,
+                        ty: Some(IrType::U32),
+                        value: *lit(0),
+                    },
+                    IrStmt::While {
+                        cond: *bin(Gt, var("n"), lit(1)),
+                        body: IrBlock {
+                            statements: vec![
+                                IrStmt::If {
+                                    cond: *bin(Eq, bin(Mod, var("n"), lit(2)), lit(0)),
+                                    then_block: IrBlock {
+                                        statements: vec![assign("n", *bin(Div, var("n"), lit(2)))],
+                                    },
+                                    else_block: Some(IrBlock {
+                                        statements: vec![assign(
+                                            "n",
+                                            *bin(Add, bin(Mul, var("n"), lit(3)), lit(1)),
+                                        )],
+                                    }),
+                                },
+                                assign("steps", *bin(Add, var("steps"), lit(1))),
+                            ],
+                        },
+                    },
+                    ret(*var("steps")),
+                ],
+            ),
+            function(
+                "widen",
+                &[("x", IrType::U16), ("flag", IrType::Bool)],
+                IrType::U16,
+                vec![
+                    IrStmt::If {
+                        cond: *bin(
+                            Or,
+                            var("flag"),
+                            bin(Gt, var("x"), Box::new(IrExpr::Literal(IrLiteral::U16(100)))),
+                        ),
+                        then_block: IrBlock {
+                            statements: vec![ret(*bin(
+                                Sub,
+                                bin(Mul, var("x"), Box::new(IrExpr::Literal(IrLiteral::U16(2)))),
+                                var("x"),
+                            ))],
+                        },
+                        else_block: None,
+                    },
+                    ret(*var("x")),
+                ],
+            ),
+        ],
+        exports: vec![],
+    }
+}
+
+#[test]
+fn test_corpus_agrees() {
+    let module = corpus();
+    let mut cases: Vec<Case> = module
+        .functions
+        .iter()
+        .filter(|f| f.name != "fib" && f.name != "collatz")
+        .flat_map(sample_cases)
+        .collect();
+    for n in [0, 1, 2, 10, 20] {
+        cases.push(Case {
+            func: "fib".to_string(),
+            args: vec![Value::U64(n)],
+        });
+    }
+    for n in [1, 6, 27, 97] {
+        cases.push(Case {
+            func: "collatz".to_string(),
+            args: vec![Value::U32(n)],
+        });
+    }
+
+    let mismatches = check(&module, &cases);
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
+
+#[test]
+fn test_integer_division_truncates_and_traps() {
+    let module = corpus();
+    let case = Case {
+        func: "quot".to_string(),
+        args: vec![Value::U32(7), Value::U32(2)],
+    };
+    assert_eq!(run_interpreter(&module, &case), Ok("3".to_string()));
+    assert_eq!(run_wasm(&module, &[case]), vec![Ok("3".to_string())]);
+
+    // JS has no division trap, so this case only runs on WASM
+    let by_zero = Case {
+        func: "quot".to_string(),
+        args: vec![Value::U32(1), Value::U32(0)],
+    };
+    assert!(run_interpreter(&module, &by_zero).is_err());
+    assert!(run_wasm(&module, &[by_zero])[0].is_err());
+}
@@ -0,0 +1,609 @@
+//! Expansion of generics for backends without generics of their own.
+//!
+//! An application of a generic type, like `Pair[U32]`, is replaced with the
+//! definition of `Pair` in which its parameters are substituted:
+//! `{ first: U32, second: U32 }`.
+//!
+//! Generic functions are monomorphized: each call binds the function's type
+//! parameters from the types of its arguments, and is redirected to an
+//! instantiation of the function for those bindings, named after the
+//! function and the bound types (`id$U64` for `id[T]` called with a `U64`).
+//! `$` cannot occur in a Z1 identifier, so instantiations never clash with
+//! the cell's own functions. The generic definitions themselves are
+//! dropped; a parameter no call site binds is instantiated as `U32`.
+
+use std::collections::HashMap;
+
+use crate::{
+    Ident, IrBinOp, IrBlock, IrExpr, IrFunction, IrLiteral, IrModule, IrStmt, IrType, IrTypeDef,
+    IrUnaryOp, Symbol,
+};
+
+/// `module` with every application of one of its generic types expanded in
+/// function signatures and `let` annotations, and every generic function
+/// replaced by its instantiations.
+pub fn expand(module: &IrModule) -> IrModule {
+    let generics: HashMap<Ident, &IrTypeDef> = module
+        .types
+        .iter()
+        .filter(|def| !def.type_params.is_empty())
+        .map(|def| (def.name, def))
+        .collect();
+    let generic_fns = module
+        .functions
+        .iter()
+        .any(|func| !func.type_params.is_empty());
+    if generics.is_empty() && !generic_fns {
+        return module.clone();
+    }
+    let mut expanded = module.clone();
+    for func in &mut expanded.functions {
+        for (_, ty) in &mut func.params {
+            *ty = expand_type(ty, &generics);
+        }
+        func.return_type = expand_type(&func.return_type, &generics);
+        expand_block(&mut func.body, &generics);
+    }
+    if generic_fns {
+        monomorphize(&mut expanded, &generics);
+    }
+    expanded
+}
+
+fn expand_block(block: &mut IrBlock, generics: &HashMap<Ident, &IrTypeDef>) {
+    for stmt in &mut block.statements {
+        match stmt {
+            IrStmt::Let { ty: Some(ty), .. } => *ty = expand_type(ty, generics),
+            IrStmt::If {
+                then_block,
+                else_block,
+                ..
+            } => {
+                expand_block(then_block, generics);
+                if let Some(else_block) = else_block {
+                    expand_block(else_block, generics);
+                }
+            }
+            IrStmt::While { body, .. } => expand_block(body, generics),
+            _ => {}
+        }
+    }
+}
+
+fn expand_type(ty: &IrType, generics: &HashMap<Ident, &IrTypeDef>) -> IrType {
+    match ty {
+        IrType::Generic { base, args } => {
+            let args: Vec<IrType> = args.iter().map(|arg| expand_type(arg, generics)).collect();
+            match base.as_ref() {
+                IrType::Named(name) => match generics.get(name) {
+                    Some(def) if def.type_params.len() == args.len() => {
+                        let bindings: HashMap<Ident, &IrType> =
+                            def.type_params.iter().copied().zip(&args).collect();
+                        expand_type(&substitute(&def.ty, &bindings), generics)
+                    }
+                    _ => IrType::Generic {
+                        base: base.clone(),
+                        args,
+                    },
+                },
+                _ => IrType::Generic {
+                    base: base.clone(),
+                    args,
+                },
+            }
+        }
+        IrType::Record(fields) => IrType::Record(
+            fields
+                .iter()
+                .map(|(name, ty)| (*name, expand_type(ty, generics)))
+                .collect(),
+        ),
+        IrType::Union(variants) => IrType::Union(
+            variants
+                .iter()
+                .map(|(name, ty)| (*name, ty.as_ref().map(|ty| expand_type(ty, generics))))
+                .collect(),
+        ),
+        _ => ty.clone(),
+    }
+}
+
+/// `ty` with the type parameters in `bindings` replaced by their arguments.
+fn substitute(ty: &IrType, bindings: &HashMap<Ident, &IrType>) -> IrType {
+    match ty {
+        IrType::Named(name) => bindings
+            .get(name)
+            .map_or_else(|| ty.clone(), |arg| (*arg).clone()),
+        IrType::Record(fields) => IrType::Record(
+            fields
+                .iter()
+                .map(|(name, ty)| (*name, substitute(ty, bindings)))
+                .collect(),
+        ),
+        IrType::Union(variants) => IrType::Union(
+            variants
+                .iter()
+                .map(|(name, ty)| (*name, ty.as_ref().map(|ty| substitute(ty, bindings))))
+                .collect(),
+        ),
+        IrType::Generic { base, args } => IrType::Generic {
+            base: base.clone(),
+            args: args.iter().map(|arg| substitute(arg, bindings)).collect(),
+        },
+        _ => ty.clone(),
+    }
+}
+
+/// Replace the generic functions of `module` with the instantiations its
+/// other functions call, directly or through other instantiations.
+fn monomorphize(module: &mut IrModule, generics: &HashMap<Ident, &IrTypeDef>) {
+    let mut mono = Monomorphizer {
+        generic: module
+            .functions
+            .iter()
+            .filter(|func| !func.type_params.is_empty())
+            .map(|func| (func.name, func.clone()))
+            .collect(),
+        returns: module
+            .functions
+            .iter()
+            .filter(|func| func.type_params.is_empty())
+            .map(|func| (func.name, func.return_type.clone()))
+            .collect(),
+        types: module
+            .types
+            .iter()
+            .filter(|def| def.type_params.is_empty())
+            .map(|def| (def.name, def.ty.clone()))
+            .collect(),
+        generics,
+        instances: HashMap::new(),
+        pending: Vec::new(),
+    };
+
+    let mut functions = std::mem::take(&mut module.functions);
+    for func in functions
+        .iter_mut()
+        .filter(|func| func.type_params.is_empty())
+    {
+        mono.rewrite_function(func);
+    }
+    let mut done: HashMap<Ident, IrFunction> = HashMap::new();
+    while let Some(mut instance) = mono.pending.pop() {
+        mono.rewrite_function(&mut instance);
+        done.insert(instance.name, instance);
+    }
+
+    // Instantiations take the place of their generic function
+    module.functions = functions
+        .into_iter()
+        .flat_map(|func| {
+            if func.type_params.is_empty() {
+                return vec![func];
+            }
+            mono.instances
+                .get(&func.name)
+                .into_iter()
+                .flatten()
+                .filter_map(|(_, name)| done.remove(name))
+                .collect()
+        })
+        .collect();
+    module.exports = std::mem::take(&mut module.exports)
+        .into_iter()
+        .flat_map(|name| match mono.instances.get(&name) {
+            Some(instances) => instances.iter().map(|(_, name)| *name).collect(),
+            None if mono.generic.contains_key(&name) => Vec::new(),
+            None => vec![name],
+        })
+        .collect();
+}
+
+struct Monomorphizer<'a> {
+    /// Generic functions by name
+    generic: HashMap<Ident, IrFunction>,
+    /// Return types of the non-generic functions and instantiations
+    returns: HashMap<Ident, IrType>,
+    /// Non-generic type definitions, for the fields of named records
+    types: HashMap<Ident, IrType>,
+    generics: &'a HashMap<Ident, &'a IrTypeDef>,
+    /// Instantiations of each generic function: bound types and name
+    instances: HashMap<Ident, Vec<(Vec<IrType>, Ident)>>,
+    /// Instantiations whose bodies still call generic functions
+    pending: Vec<IrFunction>,
+}
+
+impl Monomorphizer<'_> {
+    fn rewrite_function(&mut self, func: &mut IrFunction) {
+        let mut locals: HashMap<Ident, IrType> = func.params.iter().cloned().collect();
+        self.rewrite_block(&mut func.body, &mut locals);
+    }
+
+    fn rewrite_block(&mut self, block: &mut IrBlock, locals: &mut HashMap<Ident, IrType>) {
+        for stmt in &mut block.statements {
+            match stmt {
+                IrStmt::Let {
+                    name, ty, value, ..
+                } => {
+                    self.rewrite_expr(value, locals);
+                    if let Some(ty) = ty.clone().or_else(|| self.infer(value, locals)) {
+                        locals.insert(*name, ty);
+                    }
+                }
+                IrStmt::Assign { target, value } => {
+                    self.rewrite_expr(target, locals);
+                    self.rewrite_expr(value, locals);
+                }
+                IrStmt::If {
+                    cond,
+                    then_block,
+                    else_block,
+                } => {
+                    self.rewrite_expr(cond, locals);
+                    self.rewrite_block(then_block, locals);
+                    if let Some(else_block) = else_block {
+                        self.rewrite_block(else_block, locals);
+                    }
+                }
+                IrStmt::While { cond, body } => {
+                    self.rewrite_expr(cond, locals);
+                    self.rewrite_block(body, locals);
+                }
+                IrStmt::Return { value: Some(value) } | IrStmt::Expr(value) => {
+                    self.rewrite_expr(value, locals);
+                }
+                IrStmt::Return { value: None } => {}
+            }
+        }
+    }
+
+    fn rewrite_expr(&mut self, expr: &mut IrExpr, locals: &HashMap<Ident, IrType>) {
+        match expr {
+            IrExpr::BinOp { left, right, .. } => {
+                self.rewrite_expr(left, locals);
+                self.rewrite_expr(right, locals);
+            }
+            IrExpr::UnaryOp { expr, .. } => self.rewrite_expr(expr, locals),
+            IrExpr::Call { func, args } => {
+                for arg in args.iter_mut() {
+                    self.rewrite_expr(arg, locals);
+                }
+                let callee = match func.as_ref() {
+                    IrExpr::Var(name) => *name,
+                    IrExpr::Path(path) if path.len() == 1 => path[0],
+                    _ => return,
+                };
+                if self.generic.contains_key(&callee) {
+                    let arg_types: Vec<Option<IrType>> =
+                        args.iter().map(|arg| self.infer(arg, locals)).collect();
+                    let name = self.instantiate(callee, &arg_types);
+                    **func = IrExpr::Var(name);
+                }
+            }
+            IrExpr::Field { base, .. } => self.rewrite_expr(base, locals),
+            IrExpr::Record { fields } => {
+                for (_, value) in fields {
+                    self.rewrite_expr(value, locals);
+                }
+            }
+            IrExpr::RecordUpdate { base, fields } => {
+                self.rewrite_expr(base, locals);
+                for (_, value) in fields {
+                    self.rewrite_expr(value, locals);
+                }
+            }
+            IrExpr::Var(_) | IrExpr::Literal(_) | IrExpr::Path(_) => {}
+        }
+    }
+
+    /// Name of the instantiation of `generic` for a call with arguments of
+    /// `arg_types`, created on first use.
+    fn instantiate(&mut self, generic: Ident, arg_types: &[Option<IrType>]) -> Ident {
+        let func = &self.generic[&generic];
+        let mut bound: HashMap<Ident, IrType> = HashMap::new();
+        for ((_, param), arg) in func.params.iter().zip(arg_types) {
+            if let Some(arg) = arg {
+                self.bind(param, arg, &func.type_params, &mut bound);
+            }
+        }
+        let bindings: Vec<IrType> = func
+            .type_params
+            .iter()
+            .map(|param| bound.remove(param).unwrap_or(IrType::U32))
+            .collect();
+        let instances = self.instances.entry(generic).or_default();
+        if let Some((_, name)) = instances.iter().find(|(types, _)| *types == bindings) {
+            return *name;
+        }
+
+        let mut name = bindings.iter().fold(generic.to_string(), |name, ty| {
+            format!("{name}${}", type_name(ty))
+        });
+        if instances.iter().any(|(_, other)| other.as_str() == name) {
+            name = format!("{name}${}", instances.len() + 1);
+        }
+        let name = Symbol::intern(&name);
+        instances.push((bindings.clone(), name));
+
+        let substitution: HashMap<Ident, &IrType> =
+            func.type_params.iter().copied().zip(&bindings).collect();
+        let mut instance = IrFunction {
+            name,
+            type_params: Vec::new(),
+            params: func
+                .params
+                .iter()
+                .map(|(param, ty)| (*param, self.concrete(ty, &substitution)))
+                .collect(),
+            return_type: self.concrete(&func.return_type, &substitution),
+            effects: func.effects.clone(),
+            body: func.body.clone(),
+        };
+        self.substitute_block(&mut instance.body, &substitution);
+        self.returns.insert(name, instance.return_type.clone());
+        self.pending.push(instance);
+        name
+    }
+
+    /// Bind the type parameters `params` occurring in `param` to the
+    /// matching parts of `arg`, keeping earlier bindings.
+    fn bind(
+        &self,
+        param: &IrType,
+        arg: &IrType,
+        params: &[Ident],
+        bound: &mut HashMap<Ident, IrType>,
+    ) {
+        match (param, arg) {
+            (IrType::Named(name), _) if params.contains(name) => {
+                bound.entry(*name).or_insert_with(|| arg.clone());
+            }
+            (IrType::Record(fields), _) => {
+                let IrType::Record(arg_fields) = self.resolve(arg) else {
+                    return;
+                };
+                for (field, ty) in fields {
+                    if let Some((_, arg)) = arg_fields.iter().find(|(name, _)| name == field) {
+                        self.bind(ty, arg, params, bound);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn concrete(&self, ty: &IrType, substitution: &HashMap<Ident, &IrType>) -> IrType {
+        expand_type(&substitute(ty, substitution), self.generics)
+    }
+
+    fn substitute_block(&self, block: &mut IrBlock, substitution: &HashMap<Ident, &IrType>) {
+        for stmt in &mut block.statements {
+            match stmt {
+                IrStmt::Let { ty: Some(ty), .. } => *ty = self.concrete(ty, substitution),
+                IrStmt::If {
+                    then_block,
+                    else_block,
+                    ..
+                } => {
+                    self.substitute_block(then_block, substitution);
+                    if let Some(else_block) = else_block {
+                        self.substitute_block(else_block, substitution);
+                    }
+                }
+                IrStmt::While { body, .. } => self.substitute_block(body, substitution),
+                _ => {}
+            }
+        }
+    }
+
+    /// `ty` with named types resolved through the module's definitions.
+    fn resolve<'t>(&'t self, mut ty: &'t IrType) -> &'t IrType {
+        // Bounded walk so cyclic aliases cannot loop forever
+        for _ in 0..=self.types.len() {
+            match ty {
+                IrType::Named(name) => match self.types.get(name) {
+                    Some(next) => ty = next,
+                    None => break,
+                },
+                _ => break,
+            }
+        }
+        ty
+    }
+
+    /// Best-effort static type of `expr`, as the backends infer it.
+    fn infer(&self, expr: &IrExpr, locals: &HashMap<Ident, IrType>) -> Option<IrType> {
+        match expr {
+            IrExpr::Var(name) => locals.get(name).cloned(),
+            IrExpr::Literal(lit) => Some(match lit {
+                IrLiteral::Bool(_) => IrType::Bool,
+                IrLiteral::Str(_) => IrType::Str,
+                IrLiteral::U16(_) => IrType::U16,
+                IrLiteral::U32(_) => IrType::U32,
+                IrLiteral::U64(_) => IrType::U64,
+                IrLiteral::Int(n) if i32::try_from(*n).is_ok() => IrType::U32,
+                IrLiteral::Int(_) => IrType::U64,
+                IrLiteral::Unit => IrType::Unit,
+            }),
+            IrExpr::BinOp { op, left, .. } => match op {
+                IrBinOp::Eq
+                | IrBinOp::Ne
+                | IrBinOp::Lt
+                | IrBinOp::Le
+                | IrBinOp::Gt
+                | IrBinOp::Ge
+                | IrBinOp::And
+                | IrBinOp::Or => Some(IrType::Bool),
+                _ => self.infer(left, locals),
+            },
+            IrExpr::UnaryOp {
+                op: IrUnaryOp::Not, ..
+            } => Some(IrType::Bool),
+            IrExpr::UnaryOp { expr, .. } => self.infer(expr, locals),
+            IrExpr::Call { func, .. } => match func.as_ref() {
+                IrExpr::Var(name) => self.returns.get(name).cloned(),
+                IrExpr::Path(path) if path.len() == 1 => self.returns.get(&path[0]).cloned(),
+                _ => None,
+            },
+            IrExpr::Field { base, field } => {
+                let base = self.infer(base, locals)?;
+                match self.resolve(&base) {
+                    IrType::Record(fields) => fields
+                        .iter()
+                        .find(|(name, _)| name == field)
+                        .map(|(_, ty)| ty.clone()),
+                    _ => None,
+                }
+            }
+            IrExpr::Record { fields } => Some(IrType::Record(
+                fields
+                    .iter()
+                    .map(|(name, value)| (*name, self.infer(value, locals).unwrap_or(IrType::U32)))
+                    .collect(),
+            )),
+            IrExpr::RecordUpdate { base, .. } => self.infer(base, locals),
+            IrExpr::Path(_) => None,
+        }
+    }
+}
+
+/// Name of a bound type in the name of an instantiation.
+fn type_name(ty: &IrType) -> String {
+    match ty {
+        IrType::Bool => "Bool".to_string(),
+        IrType::Str => "Str".to_string(),
+        IrType::U16 => "U16".to_string(),
+        IrType::U32 => "U32".to_string(),
+        IrType::U64 => "U64".to_string(),
+        IrType::Unit => "Unit".to_string(),
+        IrType::Named(name) => name.to_string(),
+        IrType::Record(_) => "Record".to_string(),
+        IrType::Union(_) => "Union".to_string(),
+        IrType::Generic { .. } => "Generic".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IrExpr, IrFunction};
+
+    #[test]
+    fn applications_of_generic_types_are_expanded() {
+        let pair = IrTypeDef {
+            name: "Pair".into(),
+            type_params: vec!["T".into()],
+            ty: IrType::Record(vec![
+                ("first".into(), IrType::Named("T".into())),
+                ("second".into(), IrType::Named("T".into())),
+            ]),
+        };
+        let applied = |arg: IrType| IrType::Generic {
+            base: Box::new(IrType::Named("Pair".into())),
+            args: vec![arg],
+        };
+        let module = IrModule {
+            name: "test".to_string(),
+            version: "1.0".to_string(),
+            imports: vec![],
+            types: vec![pair],
+            functions: vec![IrFunction {
+                name: "main".into(),
+                type_params: vec![],
+                params: vec![("p".into(), applied(IrType::U64))],
+                return_type: IrType::U32,
+                effects: vec![],
+                body: IrBlock {
+                    statements: vec![IrStmt::Let {
+                        name: "q".into(),
+                        mutable: false,
+                        ty: Some(applied(IrType::U32)),
+                        value: IrExpr::Var("p".into()),
+                    }],
+                },
+            }],
+            exports: vec![],
+        };
+
+        let expanded = expand(&module);
+        let func = &expanded.functions[0];
+        assert_eq!(
+            func.params[0].1,
+            IrType::Record(vec![
+                ("first".into(), IrType::U64),
+                ("second".into(), IrType::U64),
+            ])
+        );
+        let IrStmt::Let { ty: Some(ty), .. } = &func.body.statements[0] else {
+            panic!("expected an annotated let");
+        };
+        assert_eq!(
+            *ty,
+            IrType::Record(vec![
+                ("first".into(), IrType::U32),
+                ("second".into(), IrType::U32),
+            ])
+        );
+    }
+
+    #[test]
+    fn generic_functions_are_instantiated_per_call() {
+        let param = || IrType::Named("T".into());
+        let generic = |name: &str, callee: Option<&str>| IrFunction {
+            name: name.into(),
+            type_params: vec!["T".into()],
+            params: vec![("x".into(), param())],
+            return_type: param(),
+            effects: vec![],
+            body: IrBlock {
+                statements: vec![IrStmt::Return {
+                    value: Some(match callee {
+                        Some(callee) => IrExpr::Call {
+                            func: Box::new(IrExpr::Var(callee.into())),
+                            args: vec![IrExpr::Var("x".into())],
+                        },
+                        None => IrExpr::Var("x".into()),
+                    }),
+                }],
+            },
+        };
+        let main = IrFunction {
+            name: "main".into(),
+            type_params: vec![],
+            params: vec![("n".into(), IrType::U64)],
+            return_type: IrType::U64,
+            effects: vec![],
+            body: IrBlock {
+                statements: vec![IrStmt::Return {
+                    value: Some(IrExpr::Call {
+                        func: Box::new(IrExpr::Var("wrap".into())),
+                        args: vec![IrExpr::Var("n".into())],
+                    }),
+                }],
+            },
+        };
+        let module = IrModule {
+            name: "test".to_string(),
+            version: "1.0".to_string(),
+            imports: vec![],
+            types: vec![],
+            functions: vec![generic("id", None), generic("wrap", Some("id")), main],
+            exports: vec!["id".into(), "wrap".into(), "main".into()],
+        };
+
+        let expanded = expand(&module);
+        let names: Vec<&str> = expanded.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["id$U64", "wrap$U64", "main"]);
+        assert_eq!(expanded.exports, ["id$U64", "wrap$U64", "main"]);
+        let wrap = &expanded.functions[1];
+        assert_eq!(wrap.params[0].1, IrType::U64);
+        assert_eq!(wrap.return_type, IrType::U64);
+        let IrStmt::Return {
+            value: Some(IrExpr::Call { func, .. }),
+        } = &wrap.body.statements[0]
+        else {
+            panic!("expected a returned call");
+        };
+        assert_eq!(**func, IrExpr::Var("id$U64".into()));
+    }
+}
@@ -0,0 +1,210 @@
+//! Linear memory layout for Zero1 values.
+//!
+//! Scalars (`Bool`, `U16`, `U32`, `U64`) live in WASM locals. Strings and
+//! records live in linear memory and are passed around as `i32` pointers:
+//!
+//! - **Str**: a 4-byte little-endian length header followed by the UTF-8
+//!   bytes. The pointer addresses the header.
+//! - **Record**: fields in declaration order, each aligned to its natural
+//!   size (4 bytes for `i32`, 8 bytes for `i64`). Nested records and strings
+//!   are stored by pointer.
+//!
+//! Static data (string literals) starts at [`STATIC_BASE`]; the bump
+//! allocator hands out memory after the last static segment.
+//!
+//! The layout is shared by every backend so that values produced by one
+//! (e.g. WASM) can be read by glue or code generated by another (e.g. TS).
+
+use crate::{Ident, IrType};
+use std::collections::HashMap;
+
+/// First byte of static data. The first 1KB is reserved for system use.
+pub const STATIC_BASE: u32 = 1024;
+
+/// Size of the length header that precedes string bytes.
+pub const STR_HEADER_SIZE: u32 = 4;
+
+/// Alignment of every allocation handed out by `$alloc`.
+pub const HEAP_ALIGN: u32 = 8;
+
+/// WASM value type used to hold a Zero1 value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValType {
+    I32,
+    I64,
+}
+
+impl ValType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ValType::I32 => "i32",
+            ValType::I64 => "i64",
+        }
+    }
+
+    /// Size in bytes when stored in linear memory.
+    pub fn size(self) -> u32 {
+        match self {
+            ValType::I32 => 4,
+            ValType::I64 => 8,
+        }
+    }
+
+    pub fn load_instr(self) -> &'static str {
+        match self {
+            ValType::I32 => "i32.load",
+            ValType::I64 => "i64.load",
+        }
+    }
+
+    pub fn store_instr(self) -> &'static str {
+        match self {
+            ValType::I32 => "i32.store",
+            ValType::I64 => "i64.store",
+        }
+    }
+}
+
+/// Map a Zero1 type to the WASM value type that represents it.
+pub fn val_type(ty: &IrType) -> ValType {
+    match ty {
+        IrType::U64 => ValType::I64,
+        _ => ValType::I32,
+    }
+}
+
+/// Location of a single field inside a record allocation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldLayout {
+    pub name: Ident,
+    pub offset: u32,
+    pub ty: IrType,
+}
+
+impl FieldLayout {
+    pub fn val_type(&self) -> ValType {
+        val_type(&self.ty)
+    }
+}
+
+/// Memory layout of a record value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordLayout {
+    pub fields: Vec<FieldLayout>,
+    /// Total size in bytes, padded to the record alignment.
+    pub size: u32,
+    pub align: u32,
+}
+
+impl RecordLayout {
+    /// Compute the layout for record fields in declaration order.
+    pub fn of(fields: &[(Ident, IrType)]) -> Self {
+        let mut offset = 0;
+        let mut align = 4;
+        let mut out = Vec::with_capacity(fields.len());
+        for (name, ty) in fields {
+            let size = val_type(ty).size();
+            offset = align_to(offset, size);
+            align = align.max(size);
+            out.push(FieldLayout {
+                name: *name,
+                offset,
+                ty: ty.clone(),
+            });
+            offset += size;
+        }
+        RecordLayout {
+            fields: out,
+            size: align_to(offset, align),
+            align,
+        }
+    }
+
+    pub fn field(&self, name: &str) -> Option<&FieldLayout> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+}
+
+/// Round `value` up to the next multiple of `align` (a power of two).
+pub fn align_to(value: u32, align: u32) -> u32 {
+    (value + align - 1) & !(align - 1)
+}
+
+/// Encode a string literal as it is laid out in linear memory.
+pub fn encode_str(value: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(STR_HEADER_SIZE as usize + value.len());
+    bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(value.as_bytes());
+    bytes
+}
+
+/// Resolve `Named` types through the module's type definitions.
+pub fn resolve<'a>(ty: &'a IrType, types: &'a HashMap<Ident, IrType>) -> &'a IrType {
+    let mut current = ty;
+    // Bounded walk so cyclic aliases cannot loop forever.
+    for _ in 0..=types.len() {
+        match current {
+            IrType::Named(name) => match types.get(name) {
+                Some(next) => current = next,
+                None => return current,
+            },
+            _ => return current,
+        }
+    }
+    current
+}
+
+/// Layout of `ty` if it resolves to a record.
+pub fn record_layout(ty: &IrType, types: &HashMap<Ident, IrType>) -> Option<RecordLayout> {
+    match resolve(ty, types) {
+        IrType::Record(fields) => Some(RecordLayout::of(fields)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_fields_are_naturally_aligned() {
+        let layout = RecordLayout::of(&[
+            ("a".into(), IrType::U32),
+            ("b".into(), IrType::U64),
+            ("c".into(), IrType::Bool),
+        ]);
+        assert_eq!(layout.field("a").unwrap().offset, 0);
+        assert_eq!(layout.field("b").unwrap().offset, 8);
+        assert_eq!(layout.field("c").unwrap().offset, 16);
+        assert_eq!(layout.align, 8);
+        assert_eq!(layout.size, 24);
+    }
+
+    #[test]
+    fn named_types_resolve_to_records() {
+        let mut types = HashMap::new();
+        types.insert(
+            "Point".into(),
+            IrType::Record(vec![("x".into(), IrType::U32), ("y".into(), IrType::U32)]),
+        );
+        types.insert("Alias".into(), IrType::Named("Point".into()));
+
+        let layout = record_layout(&IrType::Named("Alias".into()), &types).unwrap();
+        assert_eq!(layout.size, 8);
+        assert_eq!(layout.field("y").unwrap().offset, 4);
+        assert!(record_layout(&IrType::U32, &types).is_none());
+    }
+
+    #[test]
+    fn cyclic_aliases_do_not_loop() {
+        let mut types = HashMap::new();
+        types.insert("A".into(), IrType::Named("B".into()));
+        types.insert("B".into(), IrType::Named("A".into()));
+        assert!(record_layout(&IrType::Named("A".into()), &types).is_none());
+    }
+
+    #[test]
+    fn strings_carry_length_header() {
+        assert_eq!(encode_str("hi"), vec![2, 0, 0, 0, b'h', b'i']);
+    }
+}
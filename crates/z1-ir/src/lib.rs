@@ -4,9 +4,14 @@
 //! optimized for code generation. The IR eliminates syntactic sugar and
 //! normalizes the AST into a form that's easier to compile to target languages.
 
+pub mod generics;
+pub mod layout;
 pub mod optimize;
 
+use std::collections::BTreeMap;
+
 use z1_ast as ast;
+pub use z1_ast::{Ident, Symbol};
 
 /// IR Module - compiled representation of a Z1 cell
 #[derive(Debug, Clone, PartialEq)]
@@ -16,19 +21,24 @@ pub struct IrModule {
     pub imports: Vec<IrImport>,
     pub types: Vec<IrTypeDef>,
     pub functions: Vec<IrFunction>,
-    pub exports: Vec<String>,
+    pub exports: Vec<Ident>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct IrImport {
     pub path: String,
-    pub alias: Option<String>,
-    pub items: Vec<String>,
+    pub alias: Option<Ident>,
+    pub items: Vec<Ident>,
+    /// Capability of the module each effectful item is performed through,
+    /// like `listen` → `net`; filled in by [`bind_capabilities`]
+    pub capabilities: BTreeMap<Ident, String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct IrTypeDef {
-    pub name: String,
+    pub name: Ident,
+    /// Type parameters, referred to as `Named` types in `ty`
+    pub type_params: Vec<Ident>,
     pub ty: IrType,
 }
 
@@ -40,9 +50,9 @@ pub enum IrType {
     U32,
     U64,
     Unit,
-    Named(String),
-    Record(Vec<(String, IrType)>),
-    Union(Vec<(String, Option<IrType>)>),
+    Named(Ident),
+    Record(Vec<(Ident, IrType)>),
+    Union(Vec<(Ident, Option<IrType>)>),
     Generic {
         base: Box<IrType>,
         args: Vec<IrType>,
@@ -51,10 +61,12 @@ pub enum IrType {
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct IrFunction {
-    pub name: String,
-    pub params: Vec<(String, IrType)>,
+    pub name: Ident,
+    /// Type parameters, referred to as `Named` types in the signature
+    pub type_params: Vec<Ident>,
+    pub params: Vec<(Ident, IrType)>,
     pub return_type: IrType,
-    pub effects: Vec<String>,
+    pub effects: Vec<Ident>,
     pub body: IrBlock,
 }
 
@@ -66,7 +78,7 @@ pub struct IrBlock {
 #[derive(Debug, Clone, PartialEq)]
 pub enum IrStmt {
     Let {
-        name: String,
+        name: Ident,
         mutable: bool,
         ty: Option<IrType>,
         value: IrExpr,
@@ -92,7 +104,7 @@ pub enum IrStmt {
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum IrExpr {
-    Var(String),
+    Var(Ident),
     Literal(IrLiteral),
     BinOp {
         op: IrBinOp,
@@ -109,12 +121,17 @@ pub enum IrExpr {
     },
     Field {
         base: Box<IrExpr>,
-        field: String,
+        field: Ident,
     },
     Record {
-        fields: Vec<(String, IrExpr)>,
+        fields: Vec<(Ident, IrExpr)>,
     },
-    Path(Vec<String>),
+    /// Copy of the record `base` with `fields` replaced
+    RecordUpdate {
+        base: Box<IrExpr>,
+        fields: Vec<(Ident, IrExpr)>,
+    },
+    Path(Vec<Ident>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -176,7 +193,7 @@ impl std::error::Error for LoweringError {}
 
 /// Convert Z1 AST to IR
 pub fn lower_to_ir(module: &ast::Module) -> Result<IrModule, LoweringError> {
-    let name = module.path.as_str_vec().join(".");
+    let name = module.path.to_string();
     let version = module
         .version
         .clone()
@@ -204,8 +221,9 @@ fn lower_imports(items: &[ast::Item]) -> Vec<IrImport> {
             if let ast::Item::Import(imp) = item {
                 Some(IrImport {
                     path: imp.path.clone(),
-                    alias: imp.alias.clone(),
+                    alias: imp.alias,
                     items: imp.only.clone(),
+                    capabilities: BTreeMap::new(),
                 })
             } else {
                 None
@@ -214,6 +232,48 @@ fn lower_imports(items: &[ast::Item]) -> Vec<IrImport> {
         .collect()
 }
 
+/// Bind each imported item of `ir` that performs an effect to the capability
+/// of `module` granting it, so the generated code performs it through a
+/// capability object hosts can substitute.
+///
+/// `imports` holds the resolved modules keyed by import path; items of
+/// imports missing from it, pure items and items whose effects `module` has
+/// no capability for are left unbound. An item with several effects is bound
+/// to the first of them `module` has a capability for; `async` grants none.
+pub fn bind_capabilities(
+    ir: &mut IrModule,
+    module: &ast::Module,
+    imports: &BTreeMap<String, ast::Module>,
+) {
+    let granted = |effect: &str| {
+        module
+            .caps
+            .iter()
+            .map(|cap| cap.split('.').next().unwrap_or(cap).to_lowercase())
+            .find(|cap| *cap == effect)
+    };
+    for import in &mut ir.imports {
+        let Some(imported) = imports.get(&import.path) else {
+            continue;
+        };
+        for item in &import.items {
+            let effects = imported.items.iter().find_map(|decl| match decl {
+                ast::Item::Fn(decl) if decl.name == *item => Some(&decl.effects),
+                _ => None,
+            });
+            let capability = effects
+                .into_iter()
+                .flatten()
+                .map(|effect| effect.to_lowercase())
+                .filter(|effect| effect != "pure" && effect != "async")
+                .find_map(|effect| granted(&effect));
+            if let Some(capability) = capability {
+                import.capabilities.insert(*item, capability);
+            }
+        }
+    }
+}
+
 fn lower_types(items: &[ast::Item]) -> Result<Vec<IrTypeDef>, LoweringError> {
     items
         .iter()
@@ -229,36 +289,55 @@ fn lower_types(items: &[ast::Item]) -> Result<Vec<IrTypeDef>, LoweringError> {
 
 fn lower_type_decl(decl: &ast::TypeDecl) -> Result<IrTypeDef, LoweringError> {
     Ok(IrTypeDef {
-        name: decl.name.clone(),
+        name: decl.name,
+        type_params: decl.type_params.clone(),
         ty: lower_type_expr(&decl.expr)?,
     })
 }
 
 fn lower_type_expr(ty: &ast::TypeExpr) -> Result<IrType, LoweringError> {
     match ty {
-        ast::TypeExpr::Path(segments) => {
-            if segments.len() == 1 {
-                match segments[0].as_str() {
-                    "Bool" => Ok(IrType::Bool),
-                    "Str" => Ok(IrType::Str),
-                    "U16" => Ok(IrType::U16),
-                    "U32" => Ok(IrType::U32),
-                    "U64" => Ok(IrType::U64),
-                    "()" => Ok(IrType::Unit),
-                    name => Ok(IrType::Named(name.to_string())),
-                }
-            } else {
-                Ok(IrType::Named(segments.join(".")))
-            }
-        }
+        ast::TypeExpr::Path(segments) => Ok(lower_path_type(segments)),
         ast::TypeExpr::Record(fields) => {
             let mut ir_fields = Vec::new();
             for field in fields {
                 let field_ty = lower_type_expr(&field.ty)?;
-                ir_fields.push((field.name.clone(), field_ty));
+                ir_fields.push((field.name, field_ty));
             }
             Ok(IrType::Record(ir_fields))
         }
+        ast::TypeExpr::Union(variants) => {
+            let mut ir_variants = Vec::new();
+            for variant in variants {
+                let payload = variant
+                    .payload
+                    .as_deref()
+                    .map(lower_type_expr)
+                    .transpose()?;
+                ir_variants.push((variant.name, payload));
+            }
+            Ok(IrType::Union(ir_variants))
+        }
+        ast::TypeExpr::Generic { base, args } => Ok(IrType::Generic {
+            base: Box::new(lower_path_type(base)),
+            args: args.iter().map(lower_type_expr).collect::<Result<_, _>>()?,
+        }),
+    }
+}
+
+fn lower_path_type(segments: &[ast::Ident]) -> IrType {
+    if segments.len() == 1 {
+        match segments[0].as_str() {
+            "Bool" => IrType::Bool,
+            "Str" => IrType::Str,
+            "U16" => IrType::U16,
+            "U32" => IrType::U32,
+            "U64" => IrType::U64,
+            "()" | "Unit" => IrType::Unit,
+            _ => IrType::Named(segments[0]),
+        }
+    } else {
+        IrType::Named(Symbol::intern(&Symbol::join(segments, ".")))
     }
 }
 
@@ -281,7 +360,7 @@ fn lower_function(fn_decl: &ast::FnDecl) -> Result<IrFunction, LoweringError> {
         .iter()
         .map(|param| {
             let ty = lower_type_expr(&param.ty)?;
-            Ok((param.name.clone(), ty))
+            Ok((param.name, ty))
         })
         .collect();
 
@@ -289,7 +368,8 @@ fn lower_function(fn_decl: &ast::FnDecl) -> Result<IrFunction, LoweringError> {
     let body = lower_block(&fn_decl.body)?;
 
     Ok(IrFunction {
-        name: fn_decl.name.clone(),
+        name: fn_decl.name,
+        type_params: fn_decl.type_params.clone(),
         params: params?,
         return_type,
         effects: fn_decl.effects.clone(),
@@ -308,7 +388,7 @@ fn lower_block(block: &ast::Block) -> Result<IrBlock, LoweringError> {
 fn lower_stmt(stmt: &ast::Stmt) -> Result<IrStmt, LoweringError> {
     match stmt {
         ast::Stmt::Let(let_stmt) => Ok(IrStmt::Let {
-            name: let_stmt.name.clone(),
+            name: let_stmt.name,
             mutable: let_stmt.mutable,
             ty: if let Some(ty) = &let_stmt.ty {
                 Some(lower_type_expr(ty)?)
@@ -359,7 +439,7 @@ fn lower_stmt(stmt: &ast::Stmt) -> Result<IrStmt, LoweringError> {
 
 fn lower_expr(expr: &ast::Expr) -> Result<IrExpr, LoweringError> {
     match expr {
-        ast::Expr::Ident(name, _) => Ok(IrExpr::Var(name.clone())),
+        ast::Expr::Ident(name, _) => Ok(IrExpr::Var(*name)),
         ast::Expr::Literal(lit, _) => Ok(IrExpr::Literal(lower_literal(lit))),
         ast::Expr::Path(segments, _) => Ok(IrExpr::Path(segments.clone())),
         ast::Expr::Call { func, args, .. } => Ok(IrExpr::Call {
@@ -368,15 +448,25 @@ fn lower_expr(expr: &ast::Expr) -> Result<IrExpr, LoweringError> {
         }),
         ast::Expr::Field { base, field, .. } => Ok(IrExpr::Field {
             base: Box::new(lower_expr(base)?),
-            field: field.clone(),
+            field: *field,
         }),
         ast::Expr::Record { fields, .. } => {
             let ir_fields: Result<Vec<_>, _> = fields
                 .iter()
-                .map(|f| Ok((f.name.clone(), lower_expr(&f.value)?)))
+                .map(|f| Ok((f.name, lower_expr(&f.value)?)))
                 .collect();
             Ok(IrExpr::Record { fields: ir_fields? })
         }
+        ast::Expr::RecordUpdate { base, fields, .. } => {
+            let ir_fields: Result<Vec<_>, _> = fields
+                .iter()
+                .map(|f| Ok((f.name, lower_expr(&f.value)?)))
+                .collect();
+            Ok(IrExpr::RecordUpdate {
+                base: Box::new(lower_expr(base)?),
+                fields: ir_fields?,
+            })
+        }
         ast::Expr::BinOp { lhs, op, rhs, .. } => Ok(IrExpr::BinOp {
             op: lower_binop(op),
             left: Box::new(lower_expr(lhs)?),
@@ -428,12 +518,12 @@ fn lower_unaryop(op: &ast::UnaryOp) -> IrUnaryOp {
     }
 }
 
-fn collect_exports(items: &[ast::Item]) -> Vec<String> {
+fn collect_exports(items: &[ast::Item]) -> Vec<Ident> {
     items
         .iter()
         .filter_map(|item| match item {
-            ast::Item::Type(td) => Some(td.name.clone()),
-            ast::Item::Fn(fd) => Some(fd.name.clone()),
+            ast::Item::Type(td) => Some(td.name),
+            ast::Item::Fn(fd) => Some(fd.name),
             _ => None,
         })
         .collect()
@@ -446,7 +536,7 @@ mod tests {
     #[test]
     fn test_lower_simple_module() {
         let module = ast::Module::new(
-            ast::ModulePath::from_parts(vec!["test".to_string()]),
+            ast::ModulePath::from_parts(vec!["test".into()]),
             Some("1.0.0".to_string()),
             None,
             vec![],
@@ -465,24 +555,26 @@ mod tests {
     #[test]
     fn test_lower_type_definitions() {
         let type_decl = ast::TypeDecl {
-            name: "Point".to_string(),
+            name: "Point".into(),
+            type_params: vec![],
             expr: ast::TypeExpr::Record(vec![
                 ast::RecordField {
-                    name: "x".to_string(),
-                    ty: Box::new(ast::TypeExpr::Path(vec!["U32".to_string()])),
+                    name: "x".into(),
+                    ty: Box::new(ast::TypeExpr::Path(vec!["U32".into()])),
                     span: ast::Span::new(0, 0),
                 },
                 ast::RecordField {
-                    name: "y".to_string(),
-                    ty: Box::new(ast::TypeExpr::Path(vec!["U32".to_string()])),
+                    name: "y".into(),
+                    ty: Box::new(ast::TypeExpr::Path(vec!["U32".into()])),
                     span: ast::Span::new(0, 0),
                 },
             ]),
             span: ast::Span::new(0, 0),
+            doc: None,
         };
 
         let module = ast::Module::new(
-            ast::ModulePath::from_parts(vec!["test".to_string()]),
+            ast::ModulePath::from_parts(vec!["test".into()]),
             None,
             None,
             vec![],
@@ -505,34 +597,119 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_lower_union_type() {
+        let type_decl = ast::TypeDecl {
+            name: "Result".into(),
+            type_params: vec![],
+            expr: ast::TypeExpr::Union(vec![
+                ast::UnionVariant {
+                    name: "Ok".into(),
+                    payload: Some(Box::new(ast::TypeExpr::Path(vec!["U32".into()]))),
+                    span: ast::Span::new(0, 0),
+                },
+                ast::UnionVariant {
+                    name: "Err".into(),
+                    payload: None,
+                    span: ast::Span::new(0, 0),
+                },
+            ]),
+            span: ast::Span::new(0, 0),
+            doc: None,
+        };
+
+        let module = ast::Module::new(
+            ast::ModulePath::from_parts(vec!["test".into()]),
+            None,
+            None,
+            vec![],
+            vec![ast::Item::Type(type_decl)],
+            ast::Span::new(0, 0),
+        );
+
+        let ir = lower_to_ir(&module).unwrap();
+        assert_eq!(
+            ir.types[0].ty,
+            IrType::Union(vec![("Ok".into(), Some(IrType::U32)), ("Err".into(), None),])
+        );
+    }
+
+    #[test]
+    fn test_lower_generic_type() {
+        let param = || ast::TypeExpr::Path(vec!["T".into()]);
+        let fn_decl = ast::FnDecl {
+            name: "first".into(),
+            type_params: vec!["T".into()],
+            params: vec![ast::Param {
+                name: "p".into(),
+                ty: ast::TypeExpr::Generic {
+                    base: vec!["Pair".into()],
+                    args: vec![param()],
+                },
+                span: ast::Span::new(0, 0),
+            }],
+            ret: param(),
+            effects: vec!["pure".into()],
+            body: ast::Block {
+                raw: String::new(),
+                statements: vec![],
+                span: ast::Span::new(0, 0),
+            },
+            span: ast::Span::new(0, 0),
+            doc: None,
+        };
+
+        let module = ast::Module::new(
+            ast::ModulePath::from_parts(vec!["test".into()]),
+            None,
+            None,
+            vec![],
+            vec![ast::Item::Fn(fn_decl)],
+            ast::Span::new(0, 0),
+        );
+
+        let ir = lower_to_ir(&module).unwrap();
+        assert_eq!(ir.functions[0].type_params, vec!["T"]);
+        assert_eq!(
+            ir.functions[0].params[0].1,
+            IrType::Generic {
+                base: Box::new(IrType::Named("Pair".into())),
+                args: vec![IrType::Named("T".into())],
+            }
+        );
+        assert_eq!(ir.functions[0].return_type, IrType::Named("T".into()));
+    }
+
     #[test]
     fn test_lower_function_with_params() {
         let fn_decl = ast::FnDecl {
-            name: "add".to_string(),
+            name: "add".into(),
+            type_params: vec![],
             params: vec![
                 ast::Param {
-                    name: "a".to_string(),
-                    ty: ast::TypeExpr::Path(vec!["U32".to_string()]),
+                    name: "a".into(),
+                    ty: ast::TypeExpr::Path(vec!["U32".into()]),
                     span: ast::Span::new(0, 0),
                 },
                 ast::Param {
-                    name: "b".to_string(),
-                    ty: ast::TypeExpr::Path(vec!["U32".to_string()]),
+                    name: "b".into(),
+                    ty: ast::TypeExpr::Path(vec!["U32".into()]),
                     span: ast::Span::new(0, 0),
                 },
             ],
-            ret: ast::TypeExpr::Path(vec!["U32".to_string()]),
-            effects: vec!["pure".to_string()],
+            ret: ast::TypeExpr::Path(vec!["U32".into()]),
+            effects: vec!["pure".into()],
             body: ast::Block {
                 raw: String::new(),
                 statements: vec![],
                 span: ast::Span::new(0, 0),
             },
             span: ast::Span::new(0, 0),
+            doc: None,
         };
 
         let module = ast::Module::new(
-            ast::ModulePath::from_parts(vec!["test".to_string()]),
+            ast::ModulePath::from_parts(vec!["test".into()]),
             None,
             None,
             vec![],
@@ -554,8 +731,8 @@ mod tests {
     fn test_lower_let_statement() {
         let let_stmt = ast::Stmt::Let(ast::LetStmt {
             mutable: false,
-            name: "x".to_string(),
-            ty: Some(ast::TypeExpr::Path(vec!["U32".to_string()])),
+            name: "x".into(),
+            ty: Some(ast::TypeExpr::Path(vec!["U32".into()])),
             init: ast::Expr::Literal(ast::Literal::U32(42), ast::Span::new(0, 0)),
             span: ast::Span::new(0, 0),
         });
@@ -653,7 +830,7 @@ mod tests {
     #[test]
     fn test_lower_function_call() {
         let expr = ast::Expr::Call {
-            func: Box::new(ast::Expr::Ident("foo".to_string(), ast::Span::new(0, 0))),
+            func: Box::new(ast::Expr::Ident("foo".into(), ast::Span::new(0, 0))),
             args: vec![
                 ast::Expr::Literal(ast::Literal::U32(1), ast::Span::new(0, 0)),
                 ast::Expr::Literal(ast::Literal::U32(2), ast::Span::new(0, 0)),
@@ -664,7 +841,7 @@ mod tests {
         let ir_expr = lower_expr(&expr).unwrap();
         match ir_expr {
             IrExpr::Call { func, args } => {
-                assert_eq!(*func, IrExpr::Var("foo".to_string()));
+                assert_eq!(*func, IrExpr::Var("foo".into()));
                 assert_eq!(args.len(), 2);
                 assert_eq!(args[0], IrExpr::Literal(IrLiteral::U32(1)));
                 assert_eq!(args[1], IrExpr::Literal(IrLiteral::U32(2)));
@@ -676,15 +853,15 @@ mod tests {
     #[test]
     fn test_lower_field_access() {
         let expr = ast::Expr::Field {
-            base: Box::new(ast::Expr::Ident("obj".to_string(), ast::Span::new(0, 0))),
-            field: "x".to_string(),
+            base: Box::new(ast::Expr::Ident("obj".into(), ast::Span::new(0, 0))),
+            field: "x".into(),
             span: ast::Span::new(0, 0),
         };
 
         let ir_expr = lower_expr(&expr).unwrap();
         match ir_expr {
             IrExpr::Field { base, field } => {
-                assert_eq!(*base, IrExpr::Var("obj".to_string()));
+                assert_eq!(*base, IrExpr::Var("obj".into()));
                 assert_eq!(field, "x");
             }
             _ => panic!("Expected field access"),
@@ -696,12 +873,12 @@ mod tests {
         let expr = ast::Expr::Record {
             fields: vec![
                 ast::RecordInit {
-                    name: "x".to_string(),
+                    name: "x".into(),
                     value: ast::Expr::Literal(ast::Literal::U32(1), ast::Span::new(0, 0)),
                     span: ast::Span::new(0, 0),
                 },
                 ast::RecordInit {
-                    name: "y".to_string(),
+                    name: "y".into(),
                     value: ast::Expr::Literal(ast::Literal::U32(2), ast::Span::new(0, 0)),
                     span: ast::Span::new(0, 0),
                 },
@@ -722,23 +899,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_lower_record_update() {
+        let expr = ast::Expr::RecordUpdate {
+            base: Box::new(ast::Expr::Ident("p".into(), ast::Span::new(0, 0))),
+            fields: vec![ast::RecordInit {
+                name: "x".into(),
+                value: ast::Expr::Literal(ast::Literal::U32(2), ast::Span::new(0, 0)),
+                span: ast::Span::new(0, 0),
+            }],
+            span: ast::Span::new(0, 0),
+        };
+
+        assert_eq!(
+            lower_expr(&expr).unwrap(),
+            IrExpr::RecordUpdate {
+                base: Box::new(IrExpr::Var("p".into())),
+                fields: vec![("x".into(), IrExpr::Literal(IrLiteral::U32(2)))],
+            }
+        );
+    }
+
     #[test]
     fn test_ir_preserves_function_effects() {
         let fn_decl = ast::FnDecl {
-            name: "async_fn".to_string(),
+            name: "async_fn".into(),
+            type_params: vec![],
             params: vec![],
-            ret: ast::TypeExpr::Path(vec!["()".to_string()]),
-            effects: vec!["async".to_string(), "net".to_string()],
+            ret: ast::TypeExpr::Path(vec!["()".into()]),
+            effects: vec!["async".into(), "net".into()],
             body: ast::Block {
                 raw: String::new(),
                 statements: vec![],
                 span: ast::Span::new(0, 0),
             },
             span: ast::Span::new(0, 0),
+            doc: None,
         };
 
         let module = ast::Module::new(
-            ast::ModulePath::from_parts(vec!["test".to_string()]),
+            ast::ModulePath::from_parts(vec!["test".into()]),
             None,
             None,
             vec![],
@@ -754,13 +954,13 @@ mod tests {
     fn test_ir_preserves_imports() {
         let import = ast::Import {
             path: "std/http".to_string(),
-            alias: Some("H".to_string()),
-            only: vec!["listen".to_string(), "Req".to_string()],
+            alias: Some("H".into()),
+            only: vec!["listen".into(), "Req".into()],
             span: ast::Span::new(0, 0),
         };
 
         let module = ast::Module::new(
-            ast::ModulePath::from_parts(vec!["test".to_string()]),
+            ast::ModulePath::from_parts(vec!["test".into()]),
             None,
             None,
             vec![],
@@ -771,27 +971,30 @@ mod tests {
         let ir = lower_to_ir(&module).unwrap();
         assert_eq!(ir.imports.len(), 1);
         assert_eq!(ir.imports[0].path, "std/http");
-        assert_eq!(ir.imports[0].alias, Some("H".to_string()));
+        assert_eq!(ir.imports[0].alias, Some("H".into()));
         assert_eq!(ir.imports[0].items, vec!["listen", "Req"]);
     }
 
     #[test]
     fn test_ir_collects_exports() {
         let module = ast::Module::new(
-            ast::ModulePath::from_parts(vec!["test".to_string()]),
+            ast::ModulePath::from_parts(vec!["test".into()]),
             None,
             None,
             vec![],
             vec![
                 ast::Item::Type(ast::TypeDecl {
-                    name: "Point".to_string(),
-                    expr: ast::TypeExpr::Path(vec!["U32".to_string()]),
+                    name: "Point".into(),
+                    type_params: vec![],
+                    expr: ast::TypeExpr::Path(vec!["U32".into()]),
                     span: ast::Span::new(0, 0),
+                    doc: None,
                 }),
                 ast::Item::Fn(ast::FnDecl {
-                    name: "foo".to_string(),
+                    name: "foo".into(),
+                    type_params: vec![],
                     params: vec![],
-                    ret: ast::TypeExpr::Path(vec!["()".to_string()]),
+                    ret: ast::TypeExpr::Path(vec!["()".into()]),
                     effects: vec![],
                     body: ast::Block {
                         raw: String::new(),
@@ -799,6 +1002,7 @@ mod tests {
                         span: ast::Span::new(0, 0),
                     },
                     span: ast::Span::new(0, 0),
+                    doc: None,
                 }),
             ],
             ast::Span::new(0, 0),
@@ -813,16 +1017,16 @@ mod tests {
         // (a + b) * (c - d)
         let expr = ast::Expr::BinOp {
             lhs: Box::new(ast::Expr::BinOp {
-                lhs: Box::new(ast::Expr::Ident("a".to_string(), ast::Span::new(0, 0))),
+                lhs: Box::new(ast::Expr::Ident("a".into(), ast::Span::new(0, 0))),
                 op: ast::BinOp::Add,
-                rhs: Box::new(ast::Expr::Ident("b".to_string(), ast::Span::new(0, 0))),
+                rhs: Box::new(ast::Expr::Ident("b".into(), ast::Span::new(0, 0))),
                 span: ast::Span::new(0, 0),
             }),
             op: ast::BinOp::Mul,
             rhs: Box::new(ast::Expr::BinOp {
-                lhs: Box::new(ast::Expr::Ident("c".to_string(), ast::Span::new(0, 0))),
+                lhs: Box::new(ast::Expr::Ident("c".into(), ast::Span::new(0, 0))),
                 op: ast::BinOp::Sub,
-                rhs: Box::new(ast::Expr::Ident("d".to_string(), ast::Span::new(0, 0))),
+                rhs: Box::new(ast::Expr::Ident("d".into(), ast::Span::new(0, 0))),
                 span: ast::Span::new(0, 0),
             }),
             span: ast::Span::new(0, 0),
@@ -841,8 +1045,8 @@ mod tests {
                 } = *left
                 {
                     assert_eq!(left_op, IrBinOp::Add);
-                    assert_eq!(*ll, IrExpr::Var("a".to_string()));
-                    assert_eq!(*lr, IrExpr::Var("b".to_string()));
+                    assert_eq!(*ll, IrExpr::Var("a".into()));
+                    assert_eq!(*lr, IrExpr::Var("b".into()));
                 } else {
                     panic!("Expected binary operation on left");
                 }
@@ -855,8 +1059,8 @@ mod tests {
                 } = *right
                 {
                     assert_eq!(right_op, IrBinOp::Sub);
-                    assert_eq!(*rl, IrExpr::Var("c".to_string()));
-                    assert_eq!(*rr, IrExpr::Var("d".to_string()));
+                    assert_eq!(*rl, IrExpr::Var("c".into()));
+                    assert_eq!(*rr, IrExpr::Var("d".into()));
                 } else {
                     panic!("Expected binary operation on right");
                 }
@@ -864,4 +1068,57 @@ mod tests {
             _ => panic!("Expected binary operation"),
         }
     }
+
+    #[test]
+    fn test_bind_capabilities_to_effectful_imports() {
+        let decl = |name: &str, effects: &[&str]| {
+            ast::Item::Fn(ast::FnDecl {
+                name: name.into(),
+                type_params: vec![],
+                params: vec![],
+                ret: ast::TypeExpr::Path(vec!["Unit".into()]),
+                effects: effects.iter().map(|&effect| effect.into()).collect(),
+                body: ast::Block::default(),
+                span: ast::Span::new(0, 0),
+                doc: None,
+            })
+        };
+        let module = |caps: &[&str], items: Vec<ast::Item>| {
+            ast::Module::new(
+                ast::ModulePath::from_parts(vec!["test".into()]),
+                None,
+                None,
+                caps.iter().map(|cap| cap.to_string()).collect(),
+                items,
+                ast::Span::new(0, 0),
+            )
+        };
+        let server = module(
+            &["net", "time"],
+            vec![
+                decl("listen", &["async", "Net"]),
+                decl("sleep", &["time"]),
+                decl("respond", &["pure"]),
+            ],
+        );
+        let cell = module(
+            &["net.out"],
+            vec![ast::Item::Import(ast::Import {
+                path: "std/http".to_string(),
+                alias: None,
+                only: vec!["listen".into(), "sleep".into(), "respond".into()],
+                span: ast::Span::new(0, 0),
+            })],
+        );
+
+        let mut ir = lower_to_ir(&cell).unwrap();
+        bind_capabilities(&mut ir, &cell, &BTreeMap::new());
+        assert!(ir.imports[0].capabilities.is_empty());
+        bind_capabilities(&mut ir, &cell, &[("std/http".to_string(), server)].into());
+        // `sleep` needs `time`, which the cell lacks
+        assert_eq!(
+            ir.imports[0].capabilities,
+            [("listen".into(), "net".to_string())].into()
+        );
+    }
 }
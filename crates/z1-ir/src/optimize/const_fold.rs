@@ -8,7 +8,7 @@
 //! - Constant propagation through assignments
 //! - Simplification of conditional branches with constant conditions
 
-use crate::{IrBinOp, IrBlock, IrExpr, IrFunction, IrLiteral, IrModule, IrStmt, IrUnaryOp};
+use crate::{Ident, IrBinOp, IrBlock, IrExpr, IrFunction, IrLiteral, IrModule, IrStmt, IrUnaryOp};
 use std::collections::HashMap;
 
 /// Performs constant folding on an IR module
@@ -47,7 +47,7 @@ fn fold_constants_in_function(func: &mut IrFunction) -> usize {
 /// Performs constant folding in a block
 fn fold_constants_in_block(
     block: &mut IrBlock,
-    const_map: &mut HashMap<String, IrLiteral>,
+    const_map: &mut HashMap<Ident, IrLiteral>,
 ) -> usize {
     let mut folded_count = 0;
     let mut new_statements = Vec::new();
@@ -65,7 +65,7 @@ fn fold_constants_in_block(
 /// Performs constant folding in a statement
 fn fold_constants_in_stmt(
     stmt: &IrStmt,
-    const_map: &mut HashMap<String, IrLiteral>,
+    const_map: &mut HashMap<Ident, IrLiteral>,
 ) -> (IrStmt, usize) {
     let mut folded_count = 0;
 
@@ -82,12 +82,12 @@ fn fold_constants_in_stmt(
             // Track constant values for non-mutable variables
             if !mutable {
                 if let IrExpr::Literal(lit) = &new_value {
-                    const_map.insert(name.clone(), lit.clone());
+                    const_map.insert(*name, lit.clone());
                 }
             }
 
             IrStmt::Let {
-                name: name.clone(),
+                name: *name,
                 mutable: *mutable,
                 ty: ty.clone(),
                 value: new_value,
@@ -223,7 +223,7 @@ fn fold_constants_in_stmt(
 }
 
 /// Performs constant folding on an expression
-fn fold_expr(expr: &IrExpr, const_map: &HashMap<String, IrLiteral>) -> (IrExpr, usize) {
+fn fold_expr(expr: &IrExpr, const_map: &HashMap<Ident, IrLiteral>) -> (IrExpr, usize) {
     let mut folded_count = 0;
 
     let result = match expr {
@@ -304,7 +304,7 @@ fn fold_expr(expr: &IrExpr, const_map: &HashMap<String, IrLiteral>) -> (IrExpr,
 
             IrExpr::Field {
                 base: Box::new(new_base),
-                field: field.clone(),
+                field: *field,
             }
         }
         IrExpr::Record { fields } => {
@@ -312,11 +312,26 @@ fn fold_expr(expr: &IrExpr, const_map: &HashMap<String, IrLiteral>) -> (IrExpr,
             for (name, field_expr) in fields {
                 let (new_expr, count) = fold_expr(field_expr, const_map);
                 folded_count += count;
-                new_fields.push((name.clone(), new_expr));
+                new_fields.push((*name, new_expr));
             }
 
             IrExpr::Record { fields: new_fields }
         }
+        IrExpr::RecordUpdate { base, fields } => {
+            let (new_base, count) = fold_expr(base, const_map);
+            folded_count += count;
+            let mut new_fields = Vec::new();
+            for (name, field_expr) in fields {
+                let (new_expr, count) = fold_expr(field_expr, const_map);
+                folded_count += count;
+                new_fields.push((*name, new_expr));
+            }
+
+            IrExpr::RecordUpdate {
+                base: Box::new(new_base),
+                fields: new_fields,
+            }
+        }
         _ => expr.clone(),
     };
 
@@ -397,7 +412,8 @@ mod tests {
     #[test]
     fn test_fold_arithmetic_constants() {
         let mut func = IrFunction {
-            name: "test".to_string(),
+            name: "test".into(),
+            type_params: vec![],
             params: vec![],
             return_type: IrType::U32,
             effects: vec![],
@@ -427,7 +443,8 @@ mod tests {
     #[test]
     fn test_fold_comparison_constants() {
         let mut func = IrFunction {
-            name: "test".to_string(),
+            name: "test".into(),
+            type_params: vec![],
             params: vec![],
             return_type: IrType::Bool,
             effects: vec![],
@@ -457,20 +474,21 @@ mod tests {
     #[test]
     fn test_propagate_constants() {
         let mut func = IrFunction {
-            name: "test".to_string(),
+            name: "test".into(),
+            type_params: vec![],
             params: vec![],
             return_type: IrType::U32,
             effects: vec![],
             body: IrBlock {
                 statements: vec![
                     IrStmt::Let {
-                        name: "x".to_string(),
+                        name: "x".into(),
                         mutable: false,
                         ty: Some(IrType::U32),
                         value: IrExpr::Literal(IrLiteral::U32(42)),
                     },
                     IrStmt::Return {
-                        value: Some(IrExpr::Var("x".to_string())),
+                        value: Some(IrExpr::Var("x".into())),
                     },
                 ],
             },
@@ -491,7 +509,8 @@ mod tests {
     #[test]
     fn test_simplify_if_with_constant_condition() {
         let mut func = IrFunction {
-            name: "test".to_string(),
+            name: "test".into(),
+            type_params: vec![],
             params: vec![],
             return_type: IrType::U32,
             effects: vec![],
@@ -10,7 +10,7 @@
 //! - Side-effectful operations (function calls with effects)
 //! - Variables used in nested scopes
 
-use crate::{IrBlock, IrExpr, IrFunction, IrModule, IrStmt};
+use crate::{Ident, IrBlock, IrExpr, IrFunction, IrModule, IrStmt};
 use std::collections::HashSet;
 
 /// Performs dead code elimination on an IR module
@@ -164,7 +164,7 @@ fn remove_unused_variables(block: &mut IrBlock) -> usize {
 }
 
 /// Collects all variables that are actually used (read) in a block
-fn collect_used_variables(block: &IrBlock) -> HashSet<String> {
+fn collect_used_variables(block: &IrBlock) -> HashSet<Ident> {
     let mut used = HashSet::new();
 
     for stmt in &block.statements {
@@ -175,7 +175,7 @@ fn collect_used_variables(block: &IrBlock) -> HashSet<String> {
 }
 
 /// Collects used variables from a statement
-fn collect_used_in_stmt(stmt: &IrStmt, used: &mut HashSet<String>) {
+fn collect_used_in_stmt(stmt: &IrStmt, used: &mut HashSet<Ident>) {
     match stmt {
         IrStmt::Let { value, .. } => {
             collect_used_in_expr(value, used);
@@ -217,10 +217,10 @@ fn collect_used_in_stmt(stmt: &IrStmt, used: &mut HashSet<String>) {
 }
 
 /// Collects used variables from an expression
-fn collect_used_in_expr(expr: &IrExpr, used: &mut HashSet<String>) {
+fn collect_used_in_expr(expr: &IrExpr, used: &mut HashSet<Ident>) {
     match expr {
         IrExpr::Var(name) => {
-            used.insert(name.clone());
+            used.insert(*name);
         }
         IrExpr::BinOp { left, right, .. } => {
             collect_used_in_expr(left, used);
@@ -243,10 +243,16 @@ fn collect_used_in_expr(expr: &IrExpr, used: &mut HashSet<String>) {
                 collect_used_in_expr(field_expr, used);
             }
         }
+        IrExpr::RecordUpdate { base, fields } => {
+            collect_used_in_expr(base, used);
+            for (_, field_expr) in fields {
+                collect_used_in_expr(field_expr, used);
+            }
+        }
         IrExpr::Path(segments) => {
             // Path references are typically module-level, track first segment
             if let Some(first) = segments.first() {
-                used.insert(first.clone());
+                used.insert(*first);
             }
         }
         IrExpr::Literal(_) => {
@@ -265,6 +271,9 @@ fn has_side_effects(expr: &IrExpr) -> bool {
         IrExpr::UnaryOp { expr, .. } => has_side_effects(expr),
         IrExpr::Field { base, .. } => has_side_effects(base),
         IrExpr::Record { fields } => fields.iter().any(|(_, e)| has_side_effects(e)),
+        IrExpr::RecordUpdate { base, fields } => {
+            has_side_effects(base) || fields.iter().any(|(_, e)| has_side_effects(e))
+        }
         // Safe expressions
         IrExpr::Var(_) | IrExpr::Literal(_) | IrExpr::Path(_) => false,
     }
@@ -278,7 +287,8 @@ mod tests {
     #[test]
     fn test_eliminate_unused_variable() {
         let mut func = IrFunction {
-            name: "test".to_string(),
+            name: "test".into(),
+            type_params: vec![],
             params: vec![],
             return_type: IrType::U32,
             effects: vec![],
@@ -286,20 +296,20 @@ mod tests {
                 statements: vec![
                     // x is unused
                     IrStmt::Let {
-                        name: "x".to_string(),
+                        name: "x".into(),
                         mutable: false,
                         ty: Some(IrType::U32),
                         value: IrExpr::Literal(IrLiteral::U32(42)),
                     },
                     // y is used in return
                     IrStmt::Let {
-                        name: "y".to_string(),
+                        name: "y".into(),
                         mutable: false,
                         ty: Some(IrType::U32),
                         value: IrExpr::Literal(IrLiteral::U32(10)),
                     },
                     IrStmt::Return {
-                        value: Some(IrExpr::Var("y".to_string())),
+                        value: Some(IrExpr::Var("y".into())),
                     },
                 ],
             },
@@ -313,7 +323,8 @@ mod tests {
     #[test]
     fn test_remove_code_after_return() {
         let mut func = IrFunction {
-            name: "test".to_string(),
+            name: "test".into(),
+            type_params: vec![],
             params: vec![],
             return_type: IrType::U32,
             effects: vec![],
@@ -324,7 +335,7 @@ mod tests {
                     },
                     // This should be eliminated
                     IrStmt::Let {
-                        name: "x".to_string(),
+                        name: "x".into(),
                         mutable: false,
                         ty: Some(IrType::U32),
                         value: IrExpr::Literal(IrLiteral::U32(10)),
@@ -345,7 +356,8 @@ mod tests {
     #[test]
     fn test_preserve_side_effectful_calls() {
         let mut func = IrFunction {
-            name: "test".to_string(),
+            name: "test".into(),
+            type_params: vec![],
             params: vec![],
             return_type: IrType::Unit,
             effects: vec![],
@@ -353,11 +365,11 @@ mod tests {
                 statements: vec![
                     // x is unused, but the call has side effects
                     IrStmt::Let {
-                        name: "x".to_string(),
+                        name: "x".into(),
                         mutable: false,
                         ty: Some(IrType::U32),
                         value: IrExpr::Call {
-                            func: Box::new(IrExpr::Var("effectful_fn".to_string())),
+                            func: Box::new(IrExpr::Var("effectful_fn".into())),
                             args: vec![],
                         },
                     },
@@ -374,14 +386,15 @@ mod tests {
     #[test]
     fn test_preserve_variables_used_in_nested_scopes() {
         let mut func = IrFunction {
-            name: "test".to_string(),
+            name: "test".into(),
+            type_params: vec![],
             params: vec![],
             return_type: IrType::U32,
             effects: vec![],
             body: IrBlock {
                 statements: vec![
                     IrStmt::Let {
-                        name: "x".to_string(),
+                        name: "x".into(),
                         mutable: false,
                         ty: Some(IrType::U32),
                         value: IrExpr::Literal(IrLiteral::U32(42)),
@@ -391,7 +404,7 @@ mod tests {
                         cond: IrExpr::Literal(IrLiteral::Bool(true)),
                         then_block: IrBlock {
                             statements: vec![IrStmt::Return {
-                                value: Some(IrExpr::Var("x".to_string())),
+                                value: Some(IrExpr::Var("x".into())),
                             }],
                         },
                         else_block: None,
@@ -6,7 +6,7 @@
 //! - Avoid recursive inlining
 //! - Don't inline if it significantly increases code size
 
-use crate::{IrBlock, IrExpr, IrFunction, IrModule, IrStmt};
+use crate::{Ident, IrBlock, IrExpr, IrFunction, IrModule, IrStmt};
 use std::collections::{HashMap, HashSet};
 
 /// Configuration for inlining heuristics
@@ -36,10 +36,10 @@ pub fn inline_functions_with_config(module: &mut IrModule, config: &InlineConfig
     let mut inlined_count = 0;
 
     // Build a map of functions for lookup
-    let func_map: HashMap<String, IrFunction> = module
+    let func_map: HashMap<Ident, IrFunction> = module
         .functions
         .iter()
-        .map(|f| (f.name.clone(), f.clone()))
+        .map(|f| (f.name, f.clone()))
         .collect();
 
     // Identify which functions are recursive (don't inline these)
@@ -54,12 +54,12 @@ pub fn inline_functions_with_config(module: &mut IrModule, config: &InlineConfig
 }
 
 /// Identifies functions that call themselves (directly or indirectly)
-fn identify_recursive_functions(functions: &[IrFunction]) -> HashSet<String> {
+fn identify_recursive_functions(functions: &[IrFunction]) -> HashSet<Ident> {
     let mut recursive = HashSet::new();
 
     for func in functions {
         if calls_function(&func.body, &func.name) {
-            recursive.insert(func.name.clone());
+            recursive.insert(func.name);
         }
     }
 
@@ -124,6 +124,12 @@ fn expr_calls_function(expr: &IrExpr, target_name: &str) -> bool {
         IrExpr::Record { fields } => fields
             .iter()
             .any(|(_, e)| expr_calls_function(e, target_name)),
+        IrExpr::RecordUpdate { base, fields } => {
+            expr_calls_function(base, target_name)
+                || fields
+                    .iter()
+                    .any(|(_, e)| expr_calls_function(e, target_name))
+        }
         _ => false,
     }
 }
@@ -131,8 +137,8 @@ fn expr_calls_function(expr: &IrExpr, target_name: &str) -> bool {
 /// Performs inlining within a single function
 fn inline_in_function(
     func: &mut IrFunction,
-    func_map: &HashMap<String, IrFunction>,
-    recursive_funcs: &HashSet<String>,
+    func_map: &HashMap<Ident, IrFunction>,
+    recursive_funcs: &HashSet<Ident>,
     config: &InlineConfig,
 ) -> usize {
     let mut inlined_count = 0;
@@ -154,8 +160,8 @@ fn inline_in_function(
 /// Performs inlining within a block
 fn inline_in_block(
     block: &mut IrBlock,
-    func_map: &HashMap<String, IrFunction>,
-    recursive_funcs: &HashSet<String>,
+    func_map: &HashMap<Ident, IrFunction>,
+    recursive_funcs: &HashSet<Ident>,
     config: &InlineConfig,
 ) -> usize {
     let mut inlined_count = 0;
@@ -174,8 +180,8 @@ fn inline_in_block(
 /// Performs inlining within a statement
 fn inline_in_stmt(
     stmt: &IrStmt,
-    func_map: &HashMap<String, IrFunction>,
-    recursive_funcs: &HashSet<String>,
+    func_map: &HashMap<Ident, IrFunction>,
+    recursive_funcs: &HashSet<Ident>,
     config: &InlineConfig,
 ) -> (IrStmt, usize) {
     let mut inlined_count = 0;
@@ -191,7 +197,7 @@ fn inline_in_stmt(
             inlined_count += count;
 
             IrStmt::Let {
-                name: name.clone(),
+                name: *name,
                 mutable: *mutable,
                 ty: ty.clone(),
                 value: new_value,
@@ -269,8 +275,8 @@ fn inline_in_stmt(
 /// Performs inlining within an expression
 fn inline_in_expr(
     expr: &IrExpr,
-    func_map: &HashMap<String, IrFunction>,
-    recursive_funcs: &HashSet<String>,
+    func_map: &HashMap<Ident, IrFunction>,
+    recursive_funcs: &HashSet<Ident>,
     config: &InlineConfig,
 ) -> (IrExpr, usize) {
     let mut inlined_count = 0;
@@ -330,7 +336,7 @@ fn inline_in_expr(
 
             IrExpr::Field {
                 base: Box::new(new_base),
-                field: field.clone(),
+                field: *field,
             }
         }
         IrExpr::Record { fields } => {
@@ -339,11 +345,27 @@ fn inline_in_expr(
                 let (new_expr, count) =
                     inline_in_expr(field_expr, func_map, recursive_funcs, config);
                 inlined_count += count;
-                new_fields.push((name.clone(), new_expr));
+                new_fields.push((*name, new_expr));
             }
 
             IrExpr::Record { fields: new_fields }
         }
+        IrExpr::RecordUpdate { base, fields } => {
+            let (new_base, count) = inline_in_expr(base, func_map, recursive_funcs, config);
+            inlined_count += count;
+            let mut new_fields = Vec::new();
+            for (name, field_expr) in fields {
+                let (new_expr, count) =
+                    inline_in_expr(field_expr, func_map, recursive_funcs, config);
+                inlined_count += count;
+                new_fields.push((*name, new_expr));
+            }
+
+            IrExpr::RecordUpdate {
+                base: Box::new(new_base),
+                fields: new_fields,
+            }
+        }
         _ => expr.clone(),
     };
 
@@ -353,7 +375,7 @@ fn inline_in_expr(
 /// Determines if a function should be inlined
 fn should_inline(
     func: &IrFunction,
-    recursive_funcs: &HashSet<String>,
+    recursive_funcs: &HashSet<Ident>,
     config: &InlineConfig,
 ) -> bool {
     // Don't inline recursive functions
@@ -401,9 +423,9 @@ fn try_inline_call(func: &IrFunction, args: &[IrExpr]) -> Option<IrExpr> {
     }
 
     // Build parameter substitution map
-    let mut subst_map: HashMap<String, IrExpr> = HashMap::new();
+    let mut subst_map: HashMap<Ident, IrExpr> = HashMap::new();
     for (param, arg) in func.params.iter().zip(args.iter()) {
-        subst_map.insert(param.0.clone(), arg.clone());
+        subst_map.insert(param.0, arg.clone());
     }
 
     // Try to inline if it's a single return statement
@@ -420,7 +442,7 @@ fn try_inline_call(func: &IrFunction, args: &[IrExpr]) -> Option<IrExpr> {
 }
 
 /// Substitutes parameters in an expression
-fn substitute_expr(expr: &IrExpr, subst_map: &HashMap<String, IrExpr>) -> IrExpr {
+fn substitute_expr(expr: &IrExpr, subst_map: &HashMap<Ident, IrExpr>) -> IrExpr {
     match expr {
         IrExpr::Var(name) => subst_map.get(name).cloned().unwrap_or_else(|| expr.clone()),
         IrExpr::BinOp { op, left, right } => IrExpr::BinOp {
@@ -438,12 +460,19 @@ fn substitute_expr(expr: &IrExpr, subst_map: &HashMap<String, IrExpr>) -> IrExpr
         },
         IrExpr::Field { base, field } => IrExpr::Field {
             base: Box::new(substitute_expr(base, subst_map)),
-            field: field.clone(),
+            field: *field,
         },
         IrExpr::Record { fields } => IrExpr::Record {
             fields: fields
                 .iter()
-                .map(|(n, e)| (n.clone(), substitute_expr(e, subst_map)))
+                .map(|(n, e)| (*n, substitute_expr(e, subst_map)))
+                .collect(),
+        },
+        IrExpr::RecordUpdate { base, fields } => IrExpr::RecordUpdate {
+            base: Box::new(substitute_expr(base, subst_map)),
+            fields: fields
+                .iter()
+                .map(|(n, e)| (*n, substitute_expr(e, subst_map)))
                 .collect(),
         },
         _ => expr.clone(),
@@ -465,10 +494,11 @@ mod tests {
             functions: vec![
                 // Helper function: fn get_ten() -> U32 { return 10; }
                 IrFunction {
-                    name: "get_ten".to_string(),
+                    name: "get_ten".into(),
+                    type_params: vec![],
                     params: vec![],
                     return_type: IrType::U32,
-                    effects: vec!["pure".to_string()],
+                    effects: vec!["pure".into()],
                     body: IrBlock {
                         statements: vec![IrStmt::Return {
                             value: Some(IrExpr::Literal(IrLiteral::U32(10))),
@@ -477,14 +507,15 @@ mod tests {
                 },
                 // Main function: fn main() -> U32 { return get_ten(); }
                 IrFunction {
-                    name: "main".to_string(),
+                    name: "main".into(),
+                    type_params: vec![],
                     params: vec![],
                     return_type: IrType::U32,
                     effects: vec![],
                     body: IrBlock {
                         statements: vec![IrStmt::Return {
                             value: Some(IrExpr::Call {
-                                func: Box::new(IrExpr::Var("get_ten".to_string())),
+                                func: Box::new(IrExpr::Var("get_ten".into())),
                                 args: vec![],
                             }),
                         }],
@@ -517,15 +548,16 @@ mod tests {
             functions: vec![
                 // Helper: fn double(x: U32) -> U32 { return x * 2; }
                 IrFunction {
-                    name: "double".to_string(),
-                    params: vec![("x".to_string(), IrType::U32)],
+                    name: "double".into(),
+                    type_params: vec![],
+                    params: vec![("x".into(), IrType::U32)],
                     return_type: IrType::U32,
-                    effects: vec!["pure".to_string()],
+                    effects: vec!["pure".into()],
                     body: IrBlock {
                         statements: vec![IrStmt::Return {
                             value: Some(IrExpr::BinOp {
                                 op: IrBinOp::Mul,
-                                left: Box::new(IrExpr::Var("x".to_string())),
+                                left: Box::new(IrExpr::Var("x".into())),
                                 right: Box::new(IrExpr::Literal(IrLiteral::U32(2))),
                             }),
                         }],
@@ -533,14 +565,15 @@ mod tests {
                 },
                 // Main: fn main() -> U32 { return double(5); }
                 IrFunction {
-                    name: "main".to_string(),
+                    name: "main".into(),
+                    type_params: vec![],
                     params: vec![],
                     return_type: IrType::U32,
                     effects: vec![],
                     body: IrBlock {
                         statements: vec![IrStmt::Return {
                             value: Some(IrExpr::Call {
-                                func: Box::new(IrExpr::Var("double".to_string())),
+                                func: Box::new(IrExpr::Var("double".into())),
                                 args: vec![IrExpr::Literal(IrLiteral::U32(5))],
                             }),
                         }],
@@ -582,20 +615,21 @@ mod tests {
             functions: vec![
                 // Large function with 3+ statements
                 IrFunction {
-                    name: "large".to_string(),
+                    name: "large".into(),
+                    type_params: vec![],
                     params: vec![],
                     return_type: IrType::U32,
                     effects: vec![],
                     body: IrBlock {
                         statements: vec![
                             IrStmt::Let {
-                                name: "x".to_string(),
+                                name: "x".into(),
                                 mutable: false,
                                 ty: Some(IrType::U32),
                                 value: IrExpr::Literal(IrLiteral::U32(1)),
                             },
                             IrStmt::Let {
-                                name: "y".to_string(),
+                                name: "y".into(),
                                 mutable: false,
                                 ty: Some(IrType::U32),
                                 value: IrExpr::Literal(IrLiteral::U32(2)),
@@ -603,22 +637,23 @@ mod tests {
                             IrStmt::Return {
                                 value: Some(IrExpr::BinOp {
                                     op: IrBinOp::Add,
-                                    left: Box::new(IrExpr::Var("x".to_string())),
-                                    right: Box::new(IrExpr::Var("y".to_string())),
+                                    left: Box::new(IrExpr::Var("x".into())),
+                                    right: Box::new(IrExpr::Var("y".into())),
                                 }),
                             },
                         ],
                     },
                 },
                 IrFunction {
-                    name: "main".to_string(),
+                    name: "main".into(),
+                    type_params: vec![],
                     params: vec![],
                     return_type: IrType::U32,
                     effects: vec![],
                     body: IrBlock {
                         statements: vec![IrStmt::Return {
                             value: Some(IrExpr::Call {
-                                func: Box::new(IrExpr::Var("large".to_string())),
+                                func: Box::new(IrExpr::Var("large".into())),
                                 args: vec![],
                             }),
                         }],
@@ -642,28 +677,30 @@ mod tests {
             functions: vec![
                 // Recursive: fn fact(n: U32) -> U32 { ... fact(n-1) ... }
                 IrFunction {
-                    name: "fact".to_string(),
-                    params: vec![("n".to_string(), IrType::U32)],
+                    name: "fact".into(),
+                    type_params: vec![],
+                    params: vec![("n".into(), IrType::U32)],
                     return_type: IrType::U32,
                     effects: vec![],
                     body: IrBlock {
                         statements: vec![IrStmt::Return {
                             value: Some(IrExpr::Call {
-                                func: Box::new(IrExpr::Var("fact".to_string())),
+                                func: Box::new(IrExpr::Var("fact".into())),
                                 args: vec![IrExpr::Literal(IrLiteral::U32(1))],
                             }),
                         }],
                     },
                 },
                 IrFunction {
-                    name: "main".to_string(),
+                    name: "main".into(),
+                    type_params: vec![],
                     params: vec![],
                     return_type: IrType::U32,
                     effects: vec![],
                     body: IrBlock {
                         statements: vec![IrStmt::Return {
                             value: Some(IrExpr::Call {
-                                func: Box::new(IrExpr::Var("fact".to_string())),
+                                func: Box::new(IrExpr::Var("fact".into())),
                                 args: vec![IrExpr::Literal(IrLiteral::U32(5))],
                             }),
                         }],
@@ -157,10 +157,11 @@ mod tests {
             functions: vec![
                 // Helper: fn get_value() -> U32 { return 5; }
                 IrFunction {
-                    name: "get_value".to_string(),
+                    name: "get_value".into(),
+                    type_params: vec![],
                     params: vec![],
                     return_type: IrType::U32,
-                    effects: vec!["pure".to_string()],
+                    effects: vec!["pure".into()],
                     body: IrBlock {
                         statements: vec![IrStmt::Return {
                             value: Some(IrExpr::Literal(IrLiteral::U32(5))),
@@ -174,39 +175,40 @@ mod tests {
                 //   return y;
                 // }
                 IrFunction {
-                    name: "main".to_string(),
+                    name: "main".into(),
+                    type_params: vec![],
                     params: vec![],
                     return_type: IrType::U32,
                     effects: vec![],
                     body: IrBlock {
                         statements: vec![
                             IrStmt::Let {
-                                name: "x".to_string(),
+                                name: "x".into(),
                                 mutable: false,
                                 ty: Some(IrType::U32),
                                 value: IrExpr::Call {
-                                    func: Box::new(IrExpr::Var("get_value".to_string())),
+                                    func: Box::new(IrExpr::Var("get_value".into())),
                                     args: vec![],
                                 },
                             },
                             IrStmt::Let {
-                                name: "y".to_string(),
+                                name: "y".into(),
                                 mutable: false,
                                 ty: Some(IrType::U32),
                                 value: IrExpr::BinOp {
                                     op: IrBinOp::Add,
-                                    left: Box::new(IrExpr::Var("x".to_string())),
+                                    left: Box::new(IrExpr::Var("x".into())),
                                     right: Box::new(IrExpr::Literal(IrLiteral::U32(3))),
                                 },
                             },
                             IrStmt::Let {
-                                name: "z".to_string(),
+                                name: "z".into(),
                                 mutable: false,
                                 ty: Some(IrType::U32),
                                 value: IrExpr::Literal(IrLiteral::U32(999)),
                             },
                             IrStmt::Return {
-                                value: Some(IrExpr::Var("y".to_string())),
+                                value: Some(IrExpr::Var("y".into())),
                             },
                         ],
                     },
@@ -251,7 +253,8 @@ mod tests {
             imports: vec![],
             types: vec![],
             functions: vec![IrFunction {
-                name: "main".to_string(),
+                name: "main".into(),
+                type_params: vec![],
                 params: vec![],
                 return_type: IrType::U32,
                 effects: vec![],
@@ -272,7 +275,8 @@ mod tests {
             imports: vec![],
             types: vec![],
             functions: vec![IrFunction {
-                name: "main".to_string(),
+                name: "main".into(),
+                type_params: vec![],
                 params: vec![],
                 return_type: IrType::U32,
                 effects: vec![],
@@ -298,10 +302,11 @@ mod tests {
             types: vec![],
             functions: vec![
                 IrFunction {
-                    name: "helper".to_string(),
+                    name: "helper".into(),
+                    type_params: vec![],
                     params: vec![],
                     return_type: IrType::U32,
-                    effects: vec!["pure".to_string()],
+                    effects: vec!["pure".into()],
                     body: IrBlock {
                         statements: vec![IrStmt::Return {
                             value: Some(IrExpr::Literal(IrLiteral::U32(42))),
@@ -309,14 +314,15 @@ mod tests {
                     },
                 },
                 IrFunction {
-                    name: "main".to_string(),
+                    name: "main".into(),
+                    type_params: vec![],
                     params: vec![],
                     return_type: IrType::U32,
                     effects: vec![],
                     body: IrBlock {
                         statements: vec![IrStmt::Return {
                             value: Some(IrExpr::Call {
-                                func: Box::new(IrExpr::Var("helper".to_string())),
+                                func: Box::new(IrExpr::Var("helper".into())),
                                 args: vec![],
                             }),
                         }],
@@ -9,7 +9,7 @@ fn test_end_to_end_http_server_lowering() {
     // In a real scenario, this would come from the parser
 
     let module = ast::Module::new(
-        ast::ModulePath::from_parts(vec!["http".to_string(), "server".to_string()]),
+        ast::ModulePath::from_parts(vec!["http".into(), "server".into()]),
         Some("1.0".to_string()),
         Some(128),
         vec!["net".to_string()],
@@ -17,44 +17,47 @@ fn test_end_to_end_http_server_lowering() {
             // Import: use "std/http" as H only [listen, Req, Res]
             ast::Item::Import(ast::Import {
                 path: "std/http".to_string(),
-                alias: Some("H".to_string()),
-                only: vec!["listen".to_string(), "Req".to_string(), "Res".to_string()],
+                alias: Some("H".into()),
+                only: vec!["listen".into(), "Req".into(), "Res".into()],
                 span: ast::Span::new(0, 0),
             }),
             // Type: Health = { ok: Bool, msg: Str }
             ast::Item::Type(ast::TypeDecl {
-                name: "Health".to_string(),
+                name: "Health".into(),
+                type_params: vec![],
                 expr: ast::TypeExpr::Record(vec![
                     ast::RecordField {
-                        name: "ok".to_string(),
-                        ty: Box::new(ast::TypeExpr::Path(vec!["Bool".to_string()])),
+                        name: "ok".into(),
+                        ty: Box::new(ast::TypeExpr::Path(vec!["Bool".into()])),
                         span: ast::Span::new(0, 0),
                     },
                     ast::RecordField {
-                        name: "msg".to_string(),
-                        ty: Box::new(ast::TypeExpr::Path(vec!["Str".to_string()])),
+                        name: "msg".into(),
+                        ty: Box::new(ast::TypeExpr::Path(vec!["Str".into()])),
                         span: ast::Span::new(0, 0),
                     },
                 ]),
                 span: ast::Span::new(0, 0),
+                doc: None,
             }),
             // Function: handler
             ast::Item::Fn(ast::FnDecl {
-                name: "handler".to_string(),
+                name: "handler".into(),
+                type_params: vec![],
                 params: vec![ast::Param {
-                    name: "q".to_string(),
-                    ty: ast::TypeExpr::Path(vec!["H".to_string(), "Req".to_string()]),
+                    name: "q".into(),
+                    ty: ast::TypeExpr::Path(vec!["H".into(), "Req".into()]),
                     span: ast::Span::new(0, 0),
                 }],
-                ret: ast::TypeExpr::Path(vec!["H".to_string(), "Res".to_string()]),
-                effects: vec!["pure".to_string()],
+                ret: ast::TypeExpr::Path(vec!["H".into(), "Res".into()]),
+                effects: vec!["pure".into()],
                 body: ast::Block {
                     raw: String::new(),
                     statements: vec![ast::Stmt::Return(ast::ReturnStmt {
                         value: Some(ast::Expr::Record {
                             fields: vec![
                                 ast::RecordInit {
-                                    name: "status".to_string(),
+                                    name: "status".into(),
                                     value: ast::Expr::Literal(
                                         ast::Literal::U32(200),
                                         ast::Span::new(0, 0),
@@ -62,7 +65,7 @@ fn test_end_to_end_http_server_lowering() {
                                     span: ast::Span::new(0, 0),
                                 },
                                 ast::RecordInit {
-                                    name: "body".to_string(),
+                                    name: "body".into(),
                                     value: ast::Expr::Literal(
                                         ast::Literal::Str("ok".to_string()),
                                         ast::Span::new(0, 0),
@@ -77,28 +80,30 @@ fn test_end_to_end_http_server_lowering() {
                     span: ast::Span::new(0, 0),
                 },
                 span: ast::Span::new(0, 0),
+                doc: None,
             }),
             // Function: serve
             ast::Item::Fn(ast::FnDecl {
-                name: "serve".to_string(),
+                name: "serve".into(),
+                type_params: vec![],
                 params: vec![ast::Param {
-                    name: "p".to_string(),
-                    ty: ast::TypeExpr::Path(vec!["U16".to_string()]),
+                    name: "p".into(),
+                    ty: ast::TypeExpr::Path(vec!["U16".into()]),
                     span: ast::Span::new(0, 0),
                 }],
-                ret: ast::TypeExpr::Path(vec!["()".to_string()]),
-                effects: vec!["net".to_string()],
+                ret: ast::TypeExpr::Path(vec!["()".into()]),
+                effects: vec!["net".into()],
                 body: ast::Block {
                     raw: String::new(),
                     statements: vec![ast::Stmt::Expr(ast::ExprStmt {
                         expr: ast::Expr::Call {
                             func: Box::new(ast::Expr::Path(
-                                vec!["H".to_string(), "listen".to_string()],
+                                vec!["H".into(), "listen".into()],
                                 ast::Span::new(0, 0),
                             )),
                             args: vec![
-                                ast::Expr::Ident("p".to_string(), ast::Span::new(0, 0)),
-                                ast::Expr::Ident("h".to_string(), ast::Span::new(0, 0)),
+                                ast::Expr::Ident("p".into(), ast::Span::new(0, 0)),
+                                ast::Expr::Ident("h".into(), ast::Span::new(0, 0)),
                             ],
                             span: ast::Span::new(0, 0),
                         },
@@ -107,6 +112,7 @@ fn test_end_to_end_http_server_lowering() {
                     span: ast::Span::new(0, 0),
                 },
                 span: ast::Span::new(0, 0),
+                doc: None,
             }),
         ],
         ast::Span::new(0, 0),
@@ -122,7 +128,7 @@ fn test_end_to_end_http_server_lowering() {
     // Verify imports
     assert_eq!(ir.imports.len(), 1);
     assert_eq!(ir.imports[0].path, "std/http");
-    assert_eq!(ir.imports[0].alias, Some("H".to_string()));
+    assert_eq!(ir.imports[0].alias, Some("H".into()));
     assert_eq!(ir.imports[0].items.len(), 3);
 
     // Verify types
@@ -146,10 +152,7 @@ fn test_end_to_end_http_server_lowering() {
     assert_eq!(ir.functions[0].name, "handler");
     assert_eq!(ir.functions[0].params.len(), 1);
     assert_eq!(ir.functions[0].params[0].0, "q");
-    assert_eq!(
-        ir.functions[0].params[0].1,
-        IrType::Named("H.Req".to_string())
-    );
+    assert_eq!(ir.functions[0].params[0].1, IrType::Named("H.Req".into()));
     assert_eq!(ir.functions[0].effects, vec!["pure"]);
 
     // Check serve function
@@ -161,7 +164,7 @@ fn test_end_to_end_http_server_lowering() {
 
     // Verify exports
     assert_eq!(ir.exports.len(), 3);
-    assert!(ir.exports.contains(&"Health".to_string()));
-    assert!(ir.exports.contains(&"handler".to_string()));
-    assert!(ir.exports.contains(&"serve".to_string()));
+    assert!(ir.exports.contains(&"Health".into()));
+    assert!(ir.exports.contains(&"handler".into()));
+    assert!(ir.exports.contains(&"serve".into()));
 }
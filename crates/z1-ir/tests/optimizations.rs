@@ -16,7 +16,8 @@ fn test_dce_eliminates_unused_variable() {
         imports: vec![],
         types: vec![],
         functions: vec![IrFunction {
-            name: "main".to_string(),
+            name: "main".into(),
+            type_params: vec![],
             params: vec![],
             return_type: IrType::U32,
             effects: vec![],
@@ -24,20 +25,20 @@ fn test_dce_eliminates_unused_variable() {
                 statements: vec![
                     // Unused variable
                     IrStmt::Let {
-                        name: "unused".to_string(),
+                        name: "unused".into(),
                         mutable: false,
                         ty: Some(IrType::U32),
                         value: IrExpr::Literal(IrLiteral::U32(999)),
                     },
                     // Used variable
                     IrStmt::Let {
-                        name: "used".to_string(),
+                        name: "used".into(),
                         mutable: false,
                         ty: Some(IrType::U32),
                         value: IrExpr::Literal(IrLiteral::U32(42)),
                     },
                     IrStmt::Return {
-                        value: Some(IrExpr::Var("used".to_string())),
+                        value: Some(IrExpr::Var("used".into())),
                     },
                 ],
             },
@@ -68,7 +69,8 @@ fn test_dce_removes_unreachable_code_after_return() {
         imports: vec![],
         types: vec![],
         functions: vec![IrFunction {
-            name: "main".to_string(),
+            name: "main".into(),
+            type_params: vec![],
             params: vec![],
             return_type: IrType::U32,
             effects: vec![],
@@ -79,7 +81,7 @@ fn test_dce_removes_unreachable_code_after_return() {
                     },
                     // This should be eliminated
                     IrStmt::Let {
-                        name: "unreachable".to_string(),
+                        name: "unreachable".into(),
                         mutable: false,
                         ty: Some(IrType::U32),
                         value: IrExpr::Literal(IrLiteral::U32(999)),
@@ -105,19 +107,20 @@ fn test_dce_preserves_effectful_operations() {
         imports: vec![],
         types: vec![],
         functions: vec![IrFunction {
-            name: "main".to_string(),
+            name: "main".into(),
+            type_params: vec![],
             params: vec![],
             return_type: IrType::Unit,
-            effects: vec!["net".to_string()],
+            effects: vec!["net".into()],
             body: IrBlock {
                 statements: vec![
                     // Unused but has side effects (function call)
                     IrStmt::Let {
-                        name: "result".to_string(),
+                        name: "result".into(),
                         mutable: false,
                         ty: Some(IrType::U32),
                         value: IrExpr::Call {
-                            func: Box::new(IrExpr::Var("effectful_func".to_string())),
+                            func: Box::new(IrExpr::Var("effectful_func".into())),
                             args: vec![],
                         },
                     },
@@ -143,20 +146,21 @@ fn test_dce_removes_empty_blocks() {
         imports: vec![],
         types: vec![],
         functions: vec![IrFunction {
-            name: "main".to_string(),
+            name: "main".into(),
+            type_params: vec![],
             params: vec![],
             return_type: IrType::U32,
             effects: vec![],
             body: IrBlock {
                 statements: vec![
                     IrStmt::Let {
-                        name: "x".to_string(),
+                        name: "x".into(),
                         mutable: false,
                         ty: Some(IrType::U32),
                         value: IrExpr::Literal(IrLiteral::U32(10)),
                     },
                     IrStmt::Return {
-                        value: Some(IrExpr::Var("x".to_string())),
+                        value: Some(IrExpr::Var("x".into())),
                     },
                 ],
             },
@@ -187,7 +191,8 @@ fn test_const_fold_arithmetic() {
         imports: vec![],
         types: vec![],
         functions: vec![IrFunction {
-            name: "main".to_string(),
+            name: "main".into(),
+            type_params: vec![],
             params: vec![],
             return_type: IrType::U32,
             effects: vec![],
@@ -224,7 +229,8 @@ fn test_const_fold_comparisons() {
         imports: vec![],
         types: vec![],
         functions: vec![IrFunction {
-            name: "main".to_string(),
+            name: "main".into(),
+            type_params: vec![],
             params: vec![],
             return_type: IrType::Bool,
             effects: vec![],
@@ -261,20 +267,21 @@ fn test_const_propagation() {
         imports: vec![],
         types: vec![],
         functions: vec![IrFunction {
-            name: "main".to_string(),
+            name: "main".into(),
+            type_params: vec![],
             params: vec![],
             return_type: IrType::U32,
             effects: vec![],
             body: IrBlock {
                 statements: vec![
                     IrStmt::Let {
-                        name: "x".to_string(),
+                        name: "x".into(),
                         mutable: false,
                         ty: Some(IrType::U32),
                         value: IrExpr::Literal(IrLiteral::U32(42)),
                     },
                     IrStmt::Return {
-                        value: Some(IrExpr::Var("x".to_string())),
+                        value: Some(IrExpr::Var("x".into())),
                     },
                 ],
             },
@@ -303,7 +310,8 @@ fn test_const_fold_simplifies_if_conditions() {
         imports: vec![],
         types: vec![],
         functions: vec![IrFunction {
-            name: "main".to_string(),
+            name: "main".into(),
+            type_params: vec![],
             params: vec![],
             return_type: IrType::U32,
             effects: vec![],
@@ -351,10 +359,11 @@ fn test_inline_trivial_function() {
         types: vec![],
         functions: vec![
             IrFunction {
-                name: "get_value".to_string(),
+                name: "get_value".into(),
+                type_params: vec![],
                 params: vec![],
                 return_type: IrType::U32,
-                effects: vec!["pure".to_string()],
+                effects: vec!["pure".into()],
                 body: IrBlock {
                     statements: vec![IrStmt::Return {
                         value: Some(IrExpr::Literal(IrLiteral::U32(42))),
@@ -362,14 +371,15 @@ fn test_inline_trivial_function() {
                 },
             },
             IrFunction {
-                name: "main".to_string(),
+                name: "main".into(),
+                type_params: vec![],
                 params: vec![],
                 return_type: IrType::U32,
                 effects: vec![],
                 body: IrBlock {
                     statements: vec![IrStmt::Return {
                         value: Some(IrExpr::Call {
-                            func: Box::new(IrExpr::Var("get_value".to_string())),
+                            func: Box::new(IrExpr::Var("get_value".into())),
                             args: vec![],
                         }),
                     }],
@@ -400,29 +410,31 @@ fn test_inline_small_pure_function() {
         types: vec![],
         functions: vec![
             IrFunction {
-                name: "double".to_string(),
-                params: vec![("x".to_string(), IrType::U32)],
+                name: "double".into(),
+                type_params: vec![],
+                params: vec![("x".into(), IrType::U32)],
                 return_type: IrType::U32,
-                effects: vec!["pure".to_string()],
+                effects: vec!["pure".into()],
                 body: IrBlock {
                     statements: vec![IrStmt::Return {
                         value: Some(IrExpr::BinOp {
                             op: IrBinOp::Mul,
-                            left: Box::new(IrExpr::Var("x".to_string())),
+                            left: Box::new(IrExpr::Var("x".into())),
                             right: Box::new(IrExpr::Literal(IrLiteral::U32(2))),
                         }),
                     }],
                 },
             },
             IrFunction {
-                name: "main".to_string(),
+                name: "main".into(),
+                type_params: vec![],
                 params: vec![],
                 return_type: IrType::U32,
                 effects: vec![],
                 body: IrBlock {
                     statements: vec![IrStmt::Return {
                         value: Some(IrExpr::Call {
-                            func: Box::new(IrExpr::Var("double".to_string())),
+                            func: Box::new(IrExpr::Var("double".into())),
                             args: vec![IrExpr::Literal(IrLiteral::U32(5))],
                         }),
                     }],
@@ -456,65 +468,67 @@ fn test_dont_inline_large_function() {
         types: vec![],
         functions: vec![
             IrFunction {
-                name: "large".to_string(),
+                name: "large".into(),
+                type_params: vec![],
                 params: vec![],
                 return_type: IrType::U32,
                 effects: vec![],
                 body: IrBlock {
                     statements: vec![
                         IrStmt::Let {
-                            name: "a".to_string(),
+                            name: "a".into(),
                             mutable: false,
                             ty: Some(IrType::U32),
                             value: IrExpr::Literal(IrLiteral::U32(1)),
                         },
                         IrStmt::Let {
-                            name: "b".to_string(),
+                            name: "b".into(),
                             mutable: false,
                             ty: Some(IrType::U32),
                             value: IrExpr::Literal(IrLiteral::U32(2)),
                         },
                         IrStmt::Let {
-                            name: "c".to_string(),
+                            name: "c".into(),
                             mutable: false,
                             ty: Some(IrType::U32),
                             value: IrExpr::Literal(IrLiteral::U32(3)),
                         },
                         IrStmt::Let {
-                            name: "d".to_string(),
+                            name: "d".into(),
                             mutable: false,
                             ty: Some(IrType::U32),
                             value: IrExpr::BinOp {
                                 op: IrBinOp::Add,
-                                left: Box::new(IrExpr::Var("a".to_string())),
-                                right: Box::new(IrExpr::Var("b".to_string())),
+                                left: Box::new(IrExpr::Var("a".into())),
+                                right: Box::new(IrExpr::Var("b".into())),
                             },
                         },
                         IrStmt::Let {
-                            name: "e".to_string(),
+                            name: "e".into(),
                             mutable: false,
                             ty: Some(IrType::U32),
                             value: IrExpr::BinOp {
                                 op: IrBinOp::Add,
-                                left: Box::new(IrExpr::Var("c".to_string())),
-                                right: Box::new(IrExpr::Var("d".to_string())),
+                                left: Box::new(IrExpr::Var("c".into())),
+                                right: Box::new(IrExpr::Var("d".into())),
                             },
                         },
                         IrStmt::Return {
-                            value: Some(IrExpr::Var("e".to_string())),
+                            value: Some(IrExpr::Var("e".into())),
                         },
                     ],
                 },
             },
             IrFunction {
-                name: "main".to_string(),
+                name: "main".into(),
+                type_params: vec![],
                 params: vec![],
                 return_type: IrType::U32,
                 effects: vec![],
                 body: IrBlock {
                     statements: vec![IrStmt::Return {
                         value: Some(IrExpr::Call {
-                            func: Box::new(IrExpr::Var("large".to_string())),
+                            func: Box::new(IrExpr::Var("large".into())),
                             args: vec![],
                         }),
                     }],
@@ -547,28 +561,30 @@ fn test_dont_inline_recursive_function() {
         types: vec![],
         functions: vec![
             IrFunction {
-                name: "factorial".to_string(),
-                params: vec![("n".to_string(), IrType::U32)],
+                name: "factorial".into(),
+                type_params: vec![],
+                params: vec![("n".into(), IrType::U32)],
                 return_type: IrType::U32,
                 effects: vec![],
                 body: IrBlock {
                     statements: vec![IrStmt::Return {
                         value: Some(IrExpr::Call {
-                            func: Box::new(IrExpr::Var("factorial".to_string())),
+                            func: Box::new(IrExpr::Var("factorial".into())),
                             args: vec![IrExpr::Literal(IrLiteral::U32(1))],
                         }),
                     }],
                 },
             },
             IrFunction {
-                name: "main".to_string(),
+                name: "main".into(),
+                type_params: vec![],
                 params: vec![],
                 return_type: IrType::U32,
                 effects: vec![],
                 body: IrBlock {
                     statements: vec![IrStmt::Return {
                         value: Some(IrExpr::Call {
-                            func: Box::new(IrExpr::Var("factorial".to_string())),
+                            func: Box::new(IrExpr::Var("factorial".into())),
                             args: vec![IrExpr::Literal(IrLiteral::U32(5))],
                         }),
                     }],
@@ -599,10 +615,11 @@ fn test_combined_optimizations() {
         types: vec![],
         functions: vec![
             IrFunction {
-                name: "get_value".to_string(),
+                name: "get_value".into(),
+                type_params: vec![],
                 params: vec![],
                 return_type: IrType::U32,
-                effects: vec!["pure".to_string()],
+                effects: vec!["pure".into()],
                 body: IrBlock {
                     statements: vec![IrStmt::Return {
                         value: Some(IrExpr::Literal(IrLiteral::U32(5))),
@@ -610,7 +627,8 @@ fn test_combined_optimizations() {
                 },
             },
             IrFunction {
-                name: "main".to_string(),
+                name: "main".into(),
+                type_params: vec![],
                 params: vec![],
                 return_type: IrType::U32,
                 effects: vec![],
@@ -618,34 +636,34 @@ fn test_combined_optimizations() {
                     statements: vec![
                         // This will be inlined to 5
                         IrStmt::Let {
-                            name: "x".to_string(),
+                            name: "x".into(),
                             mutable: false,
                             ty: Some(IrType::U32),
                             value: IrExpr::Call {
-                                func: Box::new(IrExpr::Var("get_value".to_string())),
+                                func: Box::new(IrExpr::Var("get_value".into())),
                                 args: vec![],
                             },
                         },
                         // This will be folded to 8
                         IrStmt::Let {
-                            name: "y".to_string(),
+                            name: "y".into(),
                             mutable: false,
                             ty: Some(IrType::U32),
                             value: IrExpr::BinOp {
                                 op: IrBinOp::Add,
-                                left: Box::new(IrExpr::Var("x".to_string())),
+                                left: Box::new(IrExpr::Var("x".into())),
                                 right: Box::new(IrExpr::Literal(IrLiteral::U32(3))),
                             },
                         },
                         // This is unused and will be eliminated
                         IrStmt::Let {
-                            name: "z".to_string(),
+                            name: "z".into(),
                             mutable: false,
                             ty: Some(IrType::U32),
                             value: IrExpr::Literal(IrLiteral::U32(999)),
                         },
                         IrStmt::Return {
-                            value: Some(IrExpr::Var("y".to_string())),
+                            value: Some(IrExpr::Var("y".into())),
                         },
                     ],
                 },
@@ -673,7 +691,8 @@ fn test_optimization_levels_work() {
         imports: vec![],
         types: vec![],
         functions: vec![IrFunction {
-            name: "main".to_string(),
+            name: "main".into(),
+            type_params: vec![],
             params: vec![],
             return_type: IrType::U32,
             effects: vec![],
@@ -715,14 +734,15 @@ fn test_stats_tracking_accurate() {
         imports: vec![],
         types: vec![],
         functions: vec![IrFunction {
-            name: "main".to_string(),
+            name: "main".into(),
+            type_params: vec![],
             params: vec![],
             return_type: IrType::U32,
             effects: vec![],
             body: IrBlock {
                 statements: vec![
                     IrStmt::Let {
-                        name: "unused".to_string(),
+                        name: "unused".into(),
                         mutable: false,
                         ty: Some(IrType::U32),
                         value: IrExpr::Literal(IrLiteral::U32(999)),
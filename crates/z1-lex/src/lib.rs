@@ -1,10 +1,12 @@
 use logos::Logos;
 use z1_ast::Span;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Token {
+/// A token of the source text it was lexed from, whose lexeme it borrows:
+/// lexing copies nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'src> {
     pub kind: TokenKind,
-    pub lexeme: String,
+    pub lexeme: &'src str,
     pub span: Span,
 }
 
@@ -42,6 +44,7 @@ pub enum TokenKind {
     // Punctuation
     Comma,
     Dot,
+    DotDot,
     Colon,
     Semi,
     // Operators
@@ -61,6 +64,7 @@ pub enum TokenKind {
     Or,
     Not,
     Arrow,
+    Pipe,
     // Special
     Sym,
     Hash,
@@ -176,6 +180,8 @@ enum RawToken {
     Le,
     #[token(">=")]
     Ge,
+    #[token("..")]
+    DotDot,
     #[token("&&")]
     And,
     #[token("||")]
@@ -198,6 +204,8 @@ enum RawToken {
     Star,
     #[token("/")]
     Slash,
+    #[token("|")]
+    Pipe,
     #[token("%")]
     Percent,
     #[token("!")]
@@ -241,6 +249,7 @@ impl From<RawToken> for TokenKind {
             RawToken::RBracket => TokenKind::RBracket,
             RawToken::Comma => TokenKind::Comma,
             RawToken::Dot => TokenKind::Dot,
+            RawToken::DotDot => TokenKind::DotDot,
             RawToken::Colon => TokenKind::Colon,
             RawToken::Semi => TokenKind::Semi,
             RawToken::EqEq => TokenKind::EqEq,
@@ -256,6 +265,7 @@ impl From<RawToken> for TokenKind {
             RawToken::Minus => TokenKind::Minus,
             RawToken::Star => TokenKind::Star,
             RawToken::Slash => TokenKind::Slash,
+            RawToken::Pipe => TokenKind::Pipe,
             RawToken::Percent => TokenKind::Percent,
             RawToken::Not => TokenKind::Not,
             RawToken::Arrow => TokenKind::Arrow,
@@ -267,7 +277,7 @@ impl From<RawToken> for TokenKind {
 }
 
 /// Convert source text into a token stream (including a terminal EOF token).
-pub fn lex(source: &str) -> Vec<Token> {
+pub fn lex(source: &str) -> Vec<Token<'_>> {
     let mut tokens = Vec::new();
     let mut lexer = RawToken::lexer(source);
     while let Some(raw) = lexer.next() {
@@ -275,14 +285,14 @@ pub fn lex(source: &str) -> Vec<Token> {
         let span = lexer.span();
         let token = Token {
             kind: TokenKind::from(raw),
-            lexeme: lexer.slice().to_string(),
+            lexeme: lexer.slice(),
             span: Span::new(span.start as u32, span.end as u32),
         };
         tokens.push(token);
     }
     tokens.push(Token {
         kind: TokenKind::Eof,
-        lexeme: String::new(),
+        lexeme: "",
         span: Span::new(source.len() as u32, source.len() as u32),
     });
     tokens
@@ -302,4 +312,23 @@ mod tests {
         assert!(tokens.iter().any(|t| t.kind == TokenKind::KwCaps));
         assert_eq!(tokens.last().map(|t| t.kind), Some(TokenKind::Eof));
     }
+
+    #[test]
+    fn lexes_pipe_apart_from_or() {
+        let kinds = lex("A | B || C")
+            .into_iter()
+            .map(|t| t.kind)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Ident,
+                TokenKind::Pipe,
+                TokenKind::Ident,
+                TokenKind::Or,
+                TokenKind::Ident,
+                TokenKind::Eof
+            ]
+        );
+    }
 }
@@ -0,0 +1,582 @@
+//! Structural three-way merges of Zero1 cells.
+//!
+//! A line-based merge of two edits to a compact cell conflicts as soon as
+//! both touch the same line, and compact cells put a whole function on one.
+//! [`merge`] compares the three versions item by item instead: imports by
+//! path, types, functions and inline tests by name, the `#sym` table pair by
+//! pair, and the header field by field. An item changed on one side only
+//! takes that side's version, and items both sides changed the same way
+//! merge as well, so edits to different functions never conflict.
+//!
+//! Items are compared by their format hash, so a change that is only
+//! whitespace is no change at all. An item both sides changed differently
+//! is a [`Conflict`], reported with the semantic hash of each version; the
+//! merged cell keeps our version of it. [`merge_sources`] prints the merged
+//! cell with each conflicting item between diff3-style markers:
+//!
+//! ```text
+//! <<<<<<< ours sha3-256:1f0c…
+//! f handler()->U32 eff [pure] { ret 2; }
+//! ||||||| base sha3-256:77e0…
+//! f handler()->U32 eff [pure] { ret 1; }
+//! =======
+//! f handler()->U32 eff [pure] { ret 3; }
+//! >>>>>>> theirs sha3-256:a4d9…
+//! ```
+//!
+//! Header fields and symbol pairs have no markers; their conflicts keep our
+//! value and are only reported.
+//!
+//! ## Usage
+//!
+//! ```rust
+//! use z1_fmt::Mode;
+//! use z1_merge::merge_sources;
+//!
+//! let base = "m app:1.0\nf one()->U32 eff [pure] { ret 1; }\nf two()->U32 eff [pure] { ret 2; }\n";
+//! let ours = base.replace("ret 1;", "ret 10;");
+//! let theirs = base.replace("ret 2;", "ret 20;");
+//! let merged = merge_sources(base, &ours, &theirs, Mode::Compact).unwrap();
+//! assert!(merged.conflicts.is_empty());
+//! assert!(merged.source.contains("ret 10;") && merged.source.contains("ret 20;"));
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use thiserror::Error;
+use z1_ast::{Ident, Item, Module, Span, Symbol, SymbolMap, SymbolPair};
+use z1_fmt::{format_module, FmtError, FmtOptions, Mode};
+use z1_hash::{item_format_hash, item_semantic_hash, HashAlgo};
+
+/// Errors that can occur while merging cells.
+#[derive(Debug, Error)]
+pub enum MergeError {
+    #[error("{side}: {error}")]
+    Parse {
+        /// `base`, `ours` or `theirs`
+        side: &'static str,
+        error: z1_parse::ParseError,
+    },
+
+    #[error(transparent)]
+    Format(#[from] FmtError),
+}
+
+/// What a [`Conflict`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// A field of the module header
+    Header,
+    Import,
+    /// A pair of the `#sym` table, by long name
+    Symbol,
+    Type,
+    Fn,
+    Test,
+}
+
+impl ConflictKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ConflictKind::Header => "header",
+            ConflictKind::Import => "import",
+            ConflictKind::Symbol => "sym",
+            ConflictKind::Type => "type",
+            ConflictKind::Fn => "fn",
+            ConflictKind::Test => "test",
+        }
+    }
+}
+
+impl fmt::Display for ConflictKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Something both sides changed differently.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub kind: ConflictKind,
+    /// Item name, import path, long symbol name or header field
+    pub name: String,
+    /// Semantic hash of each version of an item, or the value of a header
+    /// field or the short name of a symbol; `None` where the side does not
+    /// have it.
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} `{}` ", self.kind, self.name)?;
+        match (&self.base, &self.ours, &self.theirs) {
+            (None, _, _) => f.write_str("added differently on both sides"),
+            (Some(_), None, _) => f.write_str("deleted in ours but changed in theirs"),
+            (Some(_), _, None) => f.write_str("changed in ours but deleted in theirs"),
+            _ => f.write_str("changed differently on both sides"),
+        }
+    }
+}
+
+/// Outcome of [`merge`].
+#[derive(Debug, Clone)]
+pub struct Merge {
+    /// The merged cell, with our version of every conflict
+    pub module: Module,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Outcome of [`merge_sources`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergedSource {
+    /// The merged cell, with markers around conflicting items
+    pub source: String,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Merge the changes `ours` and `theirs` each made to `base`.
+pub fn merge(base: &Module, ours: &Module, theirs: &Module) -> Merge {
+    merge_items(base, ours, theirs).0
+}
+
+/// Merge the cells `ours` and `theirs`, both edited from `base`, and print
+/// the result in `mode`.
+pub fn merge_sources(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    mode: Mode,
+) -> Result<MergedSource, MergeError> {
+    let parse = |side: &'static str, source: &str| {
+        z1_parse::parse_module(source).map_err(|error| MergeError::Parse { side, error })
+    };
+    let (merged, items) = merge_items(
+        &parse("base", base)?,
+        &parse("ours", ours)?,
+        &parse("theirs", theirs)?,
+    );
+    let options = FmtOptions::default();
+    let mut source = format_module(&merged.module, mode, &options)?;
+    if items.is_empty() {
+        return Ok(MergedSource {
+            source,
+            conflicts: merged.conflicts,
+        });
+    }
+
+    // Each version of a conflicting item is printed in place of the merged
+    // one, so it reads as it would in the merged cell
+    let print = |index: usize, item: &Option<Item>| -> Result<(String, Span), MergeError> {
+        let mut module = merged.module.clone();
+        if let Some(item) = item {
+            module.items[index] = item.clone();
+        }
+        let text = format_module(&module, mode, &options)?;
+        let key = key_of(&module.items[index]);
+        let printed = parse("merged", &text)?;
+        let span = printed
+            .items
+            .iter()
+            .find(|item| key_of(item) == key)
+            .map(item_span)
+            .ok_or(FmtError::Unsupported("merged item not printed"))?;
+        let body = match item {
+            Some(_) => text[span.start as usize..span.end as usize].to_string() + "\n",
+            None => String::new(),
+        };
+        Ok((body, span))
+    };
+    let mut replacements = Vec::new();
+    for conflict in &items {
+        let (ours, span) = print(conflict.index, &conflict.ours)?;
+        let (base, _) = print(conflict.index, &conflict.base)?;
+        let (theirs, _) = print(conflict.index, &conflict.theirs)?;
+        let hash = |item: &Option<Item>| match item {
+            Some(item) => item_semantic_hash(item, HashAlgo::default()),
+            None => "(deleted)".to_string(),
+        };
+        let marked = format!(
+            "<<<<<<< ours {}\n{ours}||||||| base {}\n{base}=======\n{theirs}>>>>>>> theirs {}",
+            hash(&conflict.ours),
+            hash(&conflict.base),
+            hash(&conflict.theirs),
+        );
+        replacements.push((span, marked));
+    }
+    replacements.sort_by_key(|(span, _)| std::cmp::Reverse(span.start));
+    for (span, marked) in replacements {
+        source.replace_range(span.start as usize..span.end as usize, &marked);
+    }
+    Ok(MergedSource {
+        source,
+        conflicts: merged.conflicts,
+    })
+}
+
+/// An item of the merged cell that conflicts, with each version of it.
+struct ItemConflict {
+    index: usize,
+    base: Option<Item>,
+    ours: Option<Item>,
+    theirs: Option<Item>,
+}
+
+/// Identity of an item across versions of a cell.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Key {
+    Import(String),
+    Type(Ident),
+    Fn(Ident),
+    Test(String),
+    /// Every `#sym` table of the cell, merged into one
+    Symbols,
+}
+
+fn key_of(item: &Item) -> Key {
+    match item {
+        Item::Import(import) => Key::Import(import.path.clone()),
+        Item::Type(decl) => Key::Type(decl.name),
+        Item::Fn(decl) => Key::Fn(decl.name),
+        Item::Test(test) => Key::Test(test.name.clone()),
+        Item::Symbol(_) => Key::Symbols,
+    }
+}
+
+fn item_span(item: &Item) -> Span {
+    match item {
+        Item::Import(import) => import.span,
+        Item::Symbol(symbols) => symbols.span,
+        Item::Type(decl) => decl.span,
+        Item::Fn(decl) => decl.span,
+        Item::Test(test) => test.span,
+    }
+}
+
+/// The items of `module` by key, and the order of the keys.
+fn keyed(module: &Module) -> (BTreeMap<Key, &Item>, Vec<Key>) {
+    let mut items = BTreeMap::new();
+    let mut order = Vec::new();
+    for item in &module.items {
+        let key = key_of(item);
+        if !items.contains_key(&key) {
+            order.push(key.clone());
+        }
+        items.entry(key).or_insert(item);
+    }
+    (items, order)
+}
+
+/// Pairs of every `#sym` table of `module`, long name to short.
+fn symbols(module: &Module) -> Vec<(String, String)> {
+    module
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Symbol(symbols) => Some(symbols),
+            _ => None,
+        })
+        .flat_map(|symbols| &symbols.pairs)
+        .map(|pair| (pair.long.to_string(), pair.short.to_string()))
+        .collect()
+}
+
+/// The version of something a three-way merge keeps: ours or theirs when
+/// only that side changed it, or `Err` with ours when both changed it
+/// differently. Versions are compared by `same`.
+fn pick<T: Clone>(
+    base: &Option<T>,
+    ours: &Option<T>,
+    theirs: &Option<T>,
+    same: impl Fn(&Option<T>, &Option<T>) -> bool,
+) -> Result<Option<T>, Option<T>> {
+    if same(ours, theirs) || same(base, theirs) {
+        Ok(ours.clone())
+    } else if same(base, ours) {
+        Ok(theirs.clone())
+    } else {
+        Err(ours.clone().or_else(|| theirs.clone()))
+    }
+}
+
+fn merge_items(base: &Module, ours: &Module, theirs: &Module) -> (Merge, Vec<ItemConflict>) {
+    let mut module = ours.clone();
+    let mut conflicts = Vec::new();
+    merge_header(base, ours, theirs, &mut module, &mut conflicts);
+
+    let (base_items, _) = keyed(base);
+    let (our_items, mut order) = keyed(ours);
+    let (their_items, their_order) = keyed(theirs);
+    // Items only theirs has go after the item they follow there
+    for (idx, key) in their_order.iter().enumerate() {
+        if order.contains(key) {
+            continue;
+        }
+        let at = their_order[..idx]
+            .iter()
+            .rev()
+            .find_map(|before| order.iter().position(|key| key == before))
+            .map_or(0, |position| position + 1);
+        order.insert(at, key.clone());
+    }
+
+    let format_hash = |item: &Option<Item>| {
+        item.as_ref()
+            .map(|item| item_format_hash(item, HashAlgo::default()))
+    };
+    let same = |left: &Option<Item>, right: &Option<Item>| format_hash(left) == format_hash(right);
+    let mut items = Vec::new();
+    let mut item_conflicts = Vec::new();
+    for key in order {
+        if key == Key::Symbols {
+            let pairs = merge_symbols(base, ours, theirs, &mut conflicts);
+            if !pairs.is_empty() {
+                items.push(Item::Symbol(SymbolMap {
+                    pairs,
+                    span: Span::default(),
+                }));
+            }
+            continue;
+        }
+        let version = |items: &BTreeMap<Key, &Item>| items.get(&key).map(|item| (*item).clone());
+        let (base, ours, theirs) = (
+            version(&base_items),
+            version(&our_items),
+            version(&their_items),
+        );
+        match pick(&base, &ours, &theirs, same) {
+            Ok(item) => items.extend(item),
+            Err(item) => {
+                let semantic = |item: &Option<Item>| {
+                    item.as_ref()
+                        .map(|item| item_semantic_hash(item, HashAlgo::default()))
+                };
+                let (kind, name) = match &key {
+                    Key::Import(path) => (ConflictKind::Import, path.clone()),
+                    Key::Type(name) => (ConflictKind::Type, name.to_string()),
+                    Key::Fn(name) => (ConflictKind::Fn, name.to_string()),
+                    Key::Test(name) => (ConflictKind::Test, name.clone()),
+                    Key::Symbols => unreachable!("symbol tables are merged by pair"),
+                };
+                conflicts.push(Conflict {
+                    kind,
+                    name,
+                    base: semantic(&base),
+                    ours: semantic(&ours),
+                    theirs: semantic(&theirs),
+                });
+                item_conflicts.push(ItemConflict {
+                    index: items.len(),
+                    base,
+                    ours,
+                    theirs,
+                });
+                items.extend(item);
+            }
+        }
+    }
+    module.items = items;
+    (Merge { module, conflicts }, item_conflicts)
+}
+
+fn merge_header(
+    base: &Module,
+    ours: &Module,
+    theirs: &Module,
+    module: &mut Module,
+    conflicts: &mut Vec<Conflict>,
+) {
+    let mut field = |name: &str, base: Option<String>, ours: Option<String>, theirs| match pick(
+        &base,
+        &ours,
+        &theirs,
+        |left, right| left == right,
+    ) {
+        Ok(value) => value,
+        Err(value) => {
+            conflicts.push(Conflict {
+                kind: ConflictKind::Header,
+                name: name.to_string(),
+                base,
+                ours,
+                theirs,
+            });
+            value
+        }
+    };
+    let path = |module: &Module| Some(module.path.to_string());
+    if let Some(path) = field("module", path(base), path(ours), path(theirs)) {
+        module.path.0 = path.split('.').map(Symbol::intern).collect();
+    }
+    module.version = field(
+        "version",
+        base.version.clone(),
+        ours.version.clone(),
+        theirs.version.clone(),
+    );
+    let ctx = |module: &Module| module.ctx_budget.map(|budget| budget.to_string());
+    module.ctx_budget =
+        field("ctx", ctx(base), ctx(ours), ctx(theirs)).and_then(|budget| budget.parse().ok());
+
+    // Caps merge as a set: either side may add or remove one
+    let mut caps: Vec<String> = ours.caps.clone();
+    caps.extend(theirs.caps.iter().cloned());
+    module.caps = Vec::new();
+    for cap in caps {
+        let has = |module: &Module| module.caps.contains(&cap);
+        let keep = match (has(base), has(ours), has(theirs)) {
+            (true, ours, theirs) => ours && theirs,
+            (false, ours, theirs) => ours || theirs,
+        };
+        if keep && !module.caps.contains(&cap) {
+            module.caps.push(cap);
+        }
+    }
+}
+
+/// Merge the `#sym` tables pair by pair, keyed by long name.
+fn merge_symbols(
+    base: &Module,
+    ours: &Module,
+    theirs: &Module,
+    conflicts: &mut Vec<Conflict>,
+) -> Vec<SymbolPair> {
+    let (base, ours, theirs) = (symbols(base), symbols(ours), symbols(theirs));
+    let short = |pairs: &[(String, String)], long: &str| {
+        pairs
+            .iter()
+            .find(|(name, _)| name == long)
+            .map(|(_, short)| short.clone())
+    };
+    let mut longs: Vec<&String> = ours.iter().map(|(long, _)| long).collect();
+    for (long, _) in &theirs {
+        if !longs.contains(&long) {
+            longs.push(long);
+        }
+    }
+    let mut pairs = Vec::new();
+    for long in longs {
+        let (b, o, t) = (short(&base, long), short(&ours, long), short(&theirs, long));
+        let short = match pick(&b, &o, &t, |left, right| left == right) {
+            Ok(short) => short,
+            Err(short) => {
+                conflicts.push(Conflict {
+                    kind: ConflictKind::Symbol,
+                    name: long.clone(),
+                    base: b,
+                    ours: o,
+                    theirs: t,
+                });
+                short
+            }
+        };
+        if let Some(short) = short {
+            pairs.push(SymbolPair {
+                long: long.into(),
+                short: short.into(),
+                span: Span::default(),
+            });
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "m app:1.0 ctx=256 caps=[net]\n\
+        u \"std/http\" as H only [listen]\n\
+        #sym { handler: h }\n\
+        t Count = U32\n\
+        f handler()->Count eff [pure] { ret 1; }\n\
+        f serve(p: U16)->Unit eff [net] { H.listen(p, handler); }\n";
+
+    fn merge_strs(ours: &str, theirs: &str) -> MergedSource {
+        merge_sources(BASE, ours, theirs, Mode::Compact).unwrap()
+    }
+
+    #[test]
+    fn changes_to_different_items_merge() {
+        let ours = BASE
+            .replace("ret 1;", "ret 2;")
+            .replace("caps=[net]", "caps=[net, time]");
+        let theirs = BASE
+            .replace("t Count = U32\n", "t Count = U64\n")
+            .replace("#sym { handler: h }", "#sym { handler: h, serve: sv }")
+            .replace("{ H.listen", "{  H.listen")
+            + "f stop()->Unit eff [net] { ret (); }\n";
+        let merged = merge_strs(&ours, &theirs);
+        assert!(merged.conflicts.is_empty(), "{:?}", merged.conflicts);
+        let module = z1_parse::parse_module(&merged.source).unwrap();
+        assert_eq!(module.caps, ["net", "time"]);
+        let source = &merged.source;
+        assert!(source.contains("ret 2;"), "{source}");
+        assert!(source.contains("t Count = U64"), "{source}");
+        assert!(source.contains("serve: sv"), "{source}");
+        assert!(source.contains("f stop()"), "{source}");
+    }
+
+    #[test]
+    fn conflicting_edits_are_marked_per_item() {
+        let ours = BASE
+            .replace("ret 1;", "ret 2;")
+            .replace("ctx=256", "ctx=300");
+        let theirs = BASE
+            .replace("ret 1;", "ret 3;")
+            .replace("ctx=256", "ctx=512")
+            .replace(
+                "f serve(p: U16)->Unit eff [net] { H.listen(p, handler); }\n",
+                "",
+            );
+        let merged = merge_strs(&ours, &theirs);
+        let conflicts: Vec<String> = merged.conflicts.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            conflicts,
+            [
+                "header `ctx` changed differently on both sides",
+                "fn `handler` changed differently on both sides"
+            ]
+        );
+        let handler = &merged.conflicts[1];
+        assert!(handler.ours.as_deref().unwrap().starts_with("sha3-256:"));
+        assert_ne!(handler.ours, handler.theirs);
+
+        let source = &merged.source;
+        let ours_at = source.find("<<<<<<< ours sha3-256:").unwrap();
+        let base_at = source.find("||||||| base sha3-256:").unwrap();
+        let theirs_at = source.find(">>>>>>> theirs sha3-256:").unwrap();
+        assert!(ours_at < base_at && base_at < theirs_at, "{source}");
+        assert!(source[ours_at..base_at].contains("ret 2;"), "{source}");
+        assert!(source[base_at..theirs_at].contains("ret 1;"), "{source}");
+        assert!(source[base_at..theirs_at].contains("=======\nf h()->Count eff [pure] { ret 3; }"));
+        // Only theirs removed `serve`, so it goes; the header keeps ours
+        assert!(!source.contains("f serve"), "{source}");
+        assert!(source.starts_with("m app:1.0 ctx=300"), "{source}");
+    }
+
+    #[test]
+    fn deleting_a_changed_item_conflicts() {
+        let ours = BASE.replace("ret 1;", "ret 2;");
+        let theirs = BASE.replace("f handler()->Count eff [pure] { ret 1; }\n", "");
+        let merged = merge_strs(&ours, &theirs);
+        assert_eq!(
+            merged.conflicts[0].to_string(),
+            "fn `handler` changed in ours but deleted in theirs"
+        );
+        assert!(merged.source.contains(">>>>>>> theirs (deleted)"));
+
+        let merged = merge(
+            &z1_parse::parse_module(BASE).unwrap(),
+            &z1_parse::parse_module(&ours).unwrap(),
+            &z1_parse::parse_module(&theirs).unwrap(),
+        );
+        assert!(merged
+            .module
+            .items
+            .iter()
+            .any(|item| matches!(item, Item::Fn(f) if f.name == "handler")));
+    }
+}
@@ -0,0 +1,267 @@
+//! Syntax migrations for Zero1 cells.
+//!
+//! Each step from one syntax version to the next is a [`Migration`]: a list
+//! of rewrite rules applied to the parsed cell. A cell that any rule
+//! rewrites is printed again by the formatter, in the mode it is written
+//! in; cells no rule touches are left byte for byte. Steps chain, so a
+//! migration across several versions applies every step in between, and
+//! rules leave cells already in the newer syntax unchanged.
+//!
+//! ## Usage
+//!
+//! ```rust
+//! use z1_fmt::Mode;
+//! use z1_migrate::{migrate_source, plan};
+//!
+//! let steps = plan("0.1", "0.2").unwrap();
+//! let migrated = migrate_source("m app:1.0\nf id(x: U32)->U32 { ret x; }\n", Mode::Compact, &steps)
+//!     .unwrap();
+//! assert!(migrated.source.contains("eff [pure]"));
+//! ```
+
+use thiserror::Error;
+use z1_ast::{Item, Module};
+use z1_fmt::{format_module, FmtOptions, Mode};
+
+/// Syntax version the toolchain parses and formats.
+pub const CURRENT_SYNTAX: &str = "0.2";
+
+/// Errors that can occur while migrating a cell.
+#[derive(Debug, Error)]
+pub enum MigrateError {
+    #[error("unknown syntax version '{version}' (known: {known})")]
+    UnknownVersion { version: String, known: String },
+
+    #[error("no migration from syntax {from} to {to}; migrations only upgrade")]
+    NoPath { from: String, to: String },
+
+    #[error(transparent)]
+    Parse(#[from] z1_parse::ParseError),
+
+    #[error(transparent)]
+    Format(#[from] z1_fmt::FmtError),
+}
+
+/// A rewrite of the cell AST, part of a [`Migration`].
+#[derive(Debug, Clone, Copy)]
+pub struct Rule {
+    /// Stable identifier, such as `explicit-pure`
+    pub id: &'static str,
+    /// What the rule rewrites
+    pub summary: &'static str,
+    rewrite: fn(&mut Module) -> usize,
+}
+
+impl Rule {
+    /// Rewrites `module` in place, returning the number of rewrites.
+    pub fn apply(&self, module: &mut Module) -> usize {
+        (self.rewrite)(module)
+    }
+}
+
+/// The rules upgrading cells from one syntax version to the next.
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    pub from: &'static str,
+    pub to: &'static str,
+    pub rules: &'static [Rule],
+}
+
+/// Every migration, oldest first.
+pub const MIGRATIONS: &[Migration] = &[Migration {
+    from: "0.1",
+    to: "0.2",
+    rules: &[
+        Rule {
+            id: "explicit-pure",
+            summary: "Functions without an effect list declare `eff [pure]`",
+            rewrite: explicit_pure,
+        },
+        Rule {
+            id: "lowercase-effects",
+            summary: "Effect and capability names are lowercase (`Net` becomes `net`)",
+            rewrite: lowercase_effects,
+        },
+    ],
+}];
+
+/// Count of rewrites a rule made in a cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rewrite {
+    pub rule: &'static str,
+    pub count: usize,
+}
+
+/// A cell after migration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Migrated {
+    /// Migrated source; the original source when nothing was rewritten
+    pub source: String,
+    /// Rules that rewrote the cell, in the order they were applied
+    pub rewrites: Vec<Rewrite>,
+}
+
+impl Migrated {
+    pub fn changed(&self) -> bool {
+        !self.rewrites.is_empty()
+    }
+}
+
+/// Known syntax versions, oldest first.
+pub fn versions() -> Vec<&'static str> {
+    let mut versions: Vec<&'static str> = MIGRATIONS.iter().map(|step| step.from).collect();
+    versions.push(CURRENT_SYNTAX);
+    versions
+}
+
+/// The migrations leading from syntax `from` to syntax `to`, in order.
+///
+/// # Errors
+///
+/// Returns `MigrateError::UnknownVersion` for a version no migration names
+/// and `MigrateError::NoPath` when `to` is older than `from`.
+pub fn plan(from: &str, to: &str) -> Result<Vec<&'static Migration>, MigrateError> {
+    let known = versions();
+    for version in [from, to] {
+        if !known.contains(&version) {
+            return Err(MigrateError::UnknownVersion {
+                version: version.to_string(),
+                known: known.join(", "),
+            });
+        }
+    }
+
+    let mut steps = Vec::new();
+    let mut current = from;
+    while current != to {
+        let Some(step) = MIGRATIONS.iter().find(|step| step.from == current) else {
+            return Err(MigrateError::NoPath {
+                from: from.to_string(),
+                to: to.to_string(),
+            });
+        };
+        steps.push(step);
+        current = step.to;
+    }
+    Ok(steps)
+}
+
+/// Applies `steps` to `source`, formatting a rewritten cell in `mode`.
+pub fn migrate_source(
+    source: &str,
+    mode: Mode,
+    steps: &[&Migration],
+) -> Result<Migrated, MigrateError> {
+    let mut module = z1_parse::parse_module(source)?;
+    let mut rewrites = Vec::new();
+    for rule in steps.iter().flat_map(|step| step.rules) {
+        let count = rule.apply(&mut module);
+        if count > 0 {
+            rewrites.push(Rewrite {
+                rule: rule.id,
+                count,
+            });
+        }
+    }
+    let source = if rewrites.is_empty() {
+        source.to_string()
+    } else {
+        format_module(&module, mode, &FmtOptions::default())?
+    };
+    Ok(Migrated { source, rewrites })
+}
+
+fn explicit_pure(module: &mut Module) -> usize {
+    let mut count = 0;
+    for item in &mut module.items {
+        if let Item::Fn(decl) = item {
+            if decl.effects.is_empty() {
+                decl.effects.push("pure".into());
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+fn lowercase_effects(module: &mut Module) -> usize {
+    let mut count = 0;
+    for cap in &mut module.caps {
+        if let Some(lower) = lowercased(cap) {
+            *cap = lower;
+            count += 1;
+        }
+    }
+    for item in &mut module.items {
+        if let Item::Fn(decl) = item {
+            for effect in &mut decl.effects {
+                if let Some(lower) = lowercased(effect) {
+                    *effect = lower.into();
+                    count += 1;
+                }
+            }
+        }
+    }
+    count
+}
+
+/// `name` in lower case, if it has upper case letters.
+fn lowercased(name: &str) -> Option<String> {
+    name.chars()
+        .any(char::is_uppercase)
+        .then(|| name.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plans_chain_of_steps() {
+        assert_eq!(plan("0.1", "0.2").unwrap().len(), 1);
+        assert!(plan("0.2", "0.2").unwrap().is_empty());
+        assert!(matches!(
+            plan("0.2", "0.1"),
+            Err(MigrateError::NoPath { .. })
+        ));
+        let err = plan("0.0", "0.2").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown syntax version '0.0' (known: 0.1, 0.2)"
+        );
+    }
+
+    #[test]
+    fn rewrites_legacy_effects() {
+        let source = "m app:1.0 caps=[Net]\nf get()->Unit eff [Net] { ret (); }\nf id(x: U32)->U32 { ret x; }\n";
+        let migrated = migrate_source(source, Mode::Compact, &plan("0.1", "0.2").unwrap()).unwrap();
+        assert_eq!(
+            migrated.source,
+            "m app:1.0 caps=[net]\nf get()->Unit eff [net] { ret (); }\n\nf id(x: U32)->U32 eff [pure] { ret x; }\n"
+        );
+        assert_eq!(
+            migrated.rewrites,
+            [
+                Rewrite {
+                    rule: "explicit-pure",
+                    count: 1
+                },
+                Rewrite {
+                    rule: "lowercase-effects",
+                    count: 2
+                },
+            ]
+        );
+
+        // Migrated cells are left alone, down to their layout
+        let again = migrate_source(
+            &migrated.source,
+            Mode::Compact,
+            &plan("0.1", "0.2").unwrap(),
+        );
+        assert!(!again.unwrap().changed());
+        let spaced = "m app:1.0\n\n\nf id(x: U32)->U32 eff [pure] { ret x; }\n";
+        let migrated = migrate_source(spaced, Mode::Compact, &plan("0.1", "0.2").unwrap()).unwrap();
+        assert_eq!(migrated.source, spaced);
+    }
+}
@@ -0,0 +1,29 @@
+//! Lex and parse throughput on large synthetic cells.
+//!
+//! ```bash
+//! cargo bench -p z1-parse --bench front_end
+//! ```
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use z1_parse::synthetic_cell;
+
+/// Functions per cell: about 5, 50 and 500 KB of source
+const SIZES: &[usize] = &[10, 100, 1000];
+
+fn front_end(c: &mut Criterion) {
+    let mut group = c.benchmark_group("front_end");
+    for &functions in SIZES {
+        let cell = synthetic_cell(functions);
+        group.throughput(Throughput::Bytes(cell.len() as u64));
+        group.bench_with_input(BenchmarkId::new("lex", cell.len()), &cell, |b, cell| {
+            b.iter(|| z1_lex::lex(black_box(cell)))
+        });
+        group.bench_with_input(BenchmarkId::new("parse", cell.len()), &cell, |b, cell| {
+            b.iter(|| z1_parse::parse_module(black_box(cell)).expect("synthetic cell parses"))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, front_end);
+criterion_main!(benches);
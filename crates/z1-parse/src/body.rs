@@ -0,0 +1,671 @@
+//! Statement and expression parsing for function and test bodies.
+//!
+//! The cell parser hands the tokens between the braces of each body to a
+//! [`BodyParser`], which fills [`Block::statements`]; spans stay those of
+//! the cell. The same parser reads `.z1t` spec bodies and fixture values
+//! through [`parse_test_body`] and [`parse_expr`].
+//!
+//! In test bodies, `assert <expr>;` is accepted as sugar for
+//! `assert(<expr>);`.
+
+use z1_ast::{
+    AssignStmt, BinOp, Block, ElseBlock, Expr, ExprStmt, IfStmt, LetStmt, Literal, RecordInit,
+    ReturnStmt, Span, Stmt, Symbol, TypeExpr, UnaryOp, WhileStmt,
+};
+use z1_lex::{lex, Token, TokenKind};
+
+use crate::ParseError;
+
+/// Parse the statements of a function body, with or without its braces.
+pub fn parse_body(source: &str) -> Result<Vec<Stmt>, ParseError> {
+    parse_source_body(source, false)
+}
+
+/// Parse the statements of a test body, with or without its braces,
+/// accepting `assert <expr>;`.
+pub fn parse_test_body(source: &str) -> Result<Vec<Stmt>, ParseError> {
+    parse_source_body(source, true)
+}
+
+/// Parse a single expression.
+pub fn parse_expr(source: &str) -> Result<Expr, ParseError> {
+    let mut parser = BodyParser::new(lex(source), false);
+    let expr = parser.expr(true)?;
+    parser.expect(TokenKind::Eof, "end of expression")?;
+    Ok(expr)
+}
+
+fn parse_source_body(source: &str, tests: bool) -> Result<Vec<Stmt>, ParseError> {
+    let mut parser = BodyParser::new(lex(source), tests);
+    let braced = parser.eat(TokenKind::LBrace);
+    let stmts = parser.stmts()?;
+    if braced {
+        parser.expect(TokenKind::RBrace, "closing }")?;
+    }
+    parser.expect(TokenKind::Eof, "end of body")?;
+    Ok(stmts)
+}
+
+/// Statements of the body `tokens`, the tokens between its braces; `close`
+/// is the span of the closing brace.
+pub(crate) fn statements(
+    tokens: &[Token<'_>],
+    close: Span,
+    tests: bool,
+) -> Result<Vec<Stmt>, ParseError> {
+    let mut tokens = tokens.to_vec();
+    tokens.push(Token {
+        kind: TokenKind::Eof,
+        lexeme: "",
+        span: close,
+    });
+    let mut parser = BodyParser::new(tokens, tests);
+    let stmts = parser.stmts()?;
+    parser.expect(TokenKind::Eof, "end of body")?;
+    Ok(stmts)
+}
+
+struct BodyParser<'src> {
+    tokens: Vec<Token<'src>>,
+    pos: usize,
+    /// Whether `assert <expr>;` is accepted
+    tests: bool,
+}
+
+impl<'src> BodyParser<'src> {
+    fn new(tokens: Vec<Token<'src>>, tests: bool) -> Self {
+        Self {
+            tokens,
+            pos: 0,
+            tests,
+        }
+    }
+
+    fn peek(&self) -> &Token<'src> {
+        &self.tokens[self.pos.min(self.tokens.len() - 1)]
+    }
+
+    fn peek_at(&self, ahead: usize) -> &Token<'src> {
+        &self.tokens[(self.pos + ahead).min(self.tokens.len() - 1)]
+    }
+
+    fn advance(&mut self) -> Token<'src> {
+        let token = *self.peek();
+        if self.pos < self.tokens.len() - 1 {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn eat(&mut self, kind: TokenKind) -> bool {
+        if self.peek().kind == kind {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(
+        &mut self,
+        kind: TokenKind,
+        expected: &'static str,
+    ) -> Result<Token<'src>, ParseError> {
+        if self.peek().kind == kind {
+            Ok(self.advance())
+        } else {
+            Err(self.unexpected(expected))
+        }
+    }
+
+    fn unexpected(&self, expected: &'static str) -> ParseError {
+        let token = self.peek();
+        ParseError::Unexpected {
+            expected,
+            found: token.kind,
+            span: token.span,
+        }
+    }
+
+    /// An identifier; item keywords such as the compact `t` and `f` mean
+    /// nothing inside a body and name bindings and fields there.
+    fn name(&mut self, expected: &'static str) -> Result<Token<'src>, ParseError> {
+        if is_name(self.peek().kind) {
+            Ok(self.advance())
+        } else {
+            Err(self.unexpected(expected))
+        }
+    }
+
+    /// Statements up to a closing brace or the end of input.
+    fn stmts(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        let mut stmts = Vec::new();
+        while !matches!(self.peek().kind, TokenKind::RBrace | TokenKind::Eof) {
+            if self.eat(TokenKind::Semi) {
+                continue;
+            }
+            stmts.push(self.stmt()?);
+        }
+        Ok(stmts)
+    }
+
+    fn block(&mut self) -> Result<Block, ParseError> {
+        let open = self.expect(TokenKind::LBrace, "{")?;
+        let statements = self.stmts()?;
+        let close = self.expect(TokenKind::RBrace, "closing }")?;
+        Ok(Block {
+            raw: String::new(),
+            statements,
+            span: Span::new(open.span.start, close.span.end),
+        })
+    }
+
+    /// End of a statement: `;`, or nothing before a closing brace.
+    fn end_stmt(&mut self) -> Result<(), ParseError> {
+        if self.eat(TokenKind::Semi)
+            || matches!(self.peek().kind, TokenKind::RBrace | TokenKind::Eof)
+        {
+            Ok(())
+        } else {
+            Err(self.unexpected(";"))
+        }
+    }
+
+    /// End of the statement that started at `start`, up to the last token
+    /// consumed.
+    fn span_from(&self, start: u32) -> Span {
+        let end = self.tokens[self.pos.saturating_sub(1)].span.end;
+        Span::new(start, end.max(start))
+    }
+
+    fn stmt(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.peek().span.start;
+        match self.peek().kind {
+            TokenKind::KwLet => {
+                self.advance();
+                let mutable = self.eat(TokenKind::KwMut);
+                let name = Symbol::intern(self.name("binding name")?.lexeme);
+                let ty = if self.eat(TokenKind::Colon) {
+                    Some(self.type_expr()?)
+                } else {
+                    None
+                };
+                self.expect(TokenKind::Eq, "=")?;
+                let init = self.expr(true)?;
+                let span = self.span_from(start);
+                self.end_stmt()?;
+                Ok(Stmt::Let(LetStmt {
+                    mutable,
+                    name,
+                    ty,
+                    init,
+                    span,
+                }))
+            }
+            TokenKind::KwReturn => {
+                self.advance();
+                let value = if matches!(
+                    self.peek().kind,
+                    TokenKind::Semi | TokenKind::RBrace | TokenKind::Eof
+                ) {
+                    None
+                } else {
+                    Some(self.expr(true)?)
+                };
+                let span = self.span_from(start);
+                self.end_stmt()?;
+                Ok(Stmt::Return(ReturnStmt { value, span }))
+            }
+            TokenKind::KwIf => Ok(Stmt::If(self.if_stmt()?)),
+            TokenKind::KwWhile => {
+                self.advance();
+                let cond = self.expr(false)?;
+                let body = self.block()?;
+                let span = Span::new(start, body.span.end);
+                Ok(Stmt::While(WhileStmt { cond, body, span }))
+            }
+            TokenKind::Ident
+                if self.tests
+                    && self.peek().lexeme == "assert"
+                    && self.peek_at(1).kind != TokenKind::LParen =>
+            {
+                let name = self.advance();
+                let arg = self.expr(true)?;
+                let span = self.span_from(start);
+                self.end_stmt()?;
+                Ok(Stmt::Expr(ExprStmt {
+                    expr: Expr::Call {
+                        func: Box::new(Expr::Ident(Symbol::intern(name.lexeme), name.span)),
+                        args: vec![arg],
+                        span,
+                    },
+                    span,
+                }))
+            }
+            _ => {
+                let expr = self.expr(true)?;
+                if self.eat(TokenKind::Eq) {
+                    let value = self.expr(true)?;
+                    let span = self.span_from(start);
+                    self.end_stmt()?;
+                    return Ok(Stmt::Assign(AssignStmt {
+                        target: expr,
+                        value,
+                        span,
+                    }));
+                }
+                let span = self.span_from(start);
+                self.end_stmt()?;
+                Ok(Stmt::Expr(ExprStmt { expr, span }))
+            }
+        }
+    }
+
+    fn if_stmt(&mut self) -> Result<IfStmt, ParseError> {
+        let start = self.expect(TokenKind::KwIf, "if")?.span.start;
+        let cond = self.expr(false)?;
+        let then_block = self.block()?;
+        let mut end = then_block.span.end;
+        let else_block = if self.eat(TokenKind::KwElse) {
+            let block = if self.peek().kind == TokenKind::KwIf {
+                let nested = self.if_stmt()?;
+                end = nested.span.end;
+                ElseBlock::If(nested)
+            } else {
+                let block = self.block()?;
+                end = block.span.end;
+                ElseBlock::Block(block)
+            };
+            Some(Box::new(block))
+        } else {
+            None
+        };
+        Ok(IfStmt {
+            cond,
+            then_block,
+            else_block,
+            span: Span::new(start, end),
+        })
+    }
+
+    fn type_expr(&mut self) -> Result<TypeExpr, ParseError> {
+        let name = self.name("type name")?;
+        let base = name.lexeme.split('.').map(Symbol::intern).collect();
+        if !self.eat(TokenKind::LBracket) {
+            return Ok(TypeExpr::Path(base));
+        }
+        let mut args = vec![self.type_expr()?];
+        while self.eat(TokenKind::Comma) {
+            args.push(self.type_expr()?);
+        }
+        self.expect(TokenKind::RBracket, "closing ] of type arguments")?;
+        Ok(TypeExpr::Generic { base, args })
+    }
+
+    /// An expression; `records` is false in `if`/`while` conditions, where a
+    /// `{` opens the block rather than a record literal.
+    fn expr(&mut self, records: bool) -> Result<Expr, ParseError> {
+        self.binary(0, records)
+    }
+
+    fn binary(&mut self, min_prec: u8, records: bool) -> Result<Expr, ParseError> {
+        let mut lhs = self.unary(records)?;
+        while let Some((op, prec)) = binop(self.peek().kind) {
+            if prec < min_prec {
+                break;
+            }
+            self.advance();
+            let rhs = self.binary(prec + 1, records)?;
+            let span = Span::new(expr_span(&lhs).start, expr_span(&rhs).end);
+            lhs = Expr::BinOp {
+                lhs: Box::new(lhs),
+                op,
+                rhs: Box::new(rhs),
+                span,
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self, records: bool) -> Result<Expr, ParseError> {
+        let op = match self.peek().kind {
+            TokenKind::Minus => Some(UnaryOp::Neg),
+            TokenKind::Not => Some(UnaryOp::Not),
+            TokenKind::Ident if self.peek().lexeme == "await" => Some(UnaryOp::Await),
+            _ => None,
+        };
+        let Some(op) = op else {
+            return self.postfix(records);
+        };
+        let start = self.advance().span.start;
+        let expr = self.unary(records)?;
+        let span = Span::new(start, expr_span(&expr).end);
+        Ok(Expr::UnaryOp {
+            op,
+            expr: Box::new(expr),
+            span,
+        })
+    }
+
+    fn postfix(&mut self, records: bool) -> Result<Expr, ParseError> {
+        let mut expr = self.primary(records)?;
+        loop {
+            match self.peek().kind {
+                TokenKind::LParen => {
+                    self.advance();
+                    let mut args = Vec::new();
+                    while self.peek().kind != TokenKind::RParen {
+                        args.push(self.expr(true)?);
+                        if !self.eat(TokenKind::Comma) {
+                            break;
+                        }
+                    }
+                    let close = self.expect(TokenKind::RParen, ")")?;
+                    let span = Span::new(expr_span(&expr).start, close.span.end);
+                    expr = Expr::Call {
+                        func: Box::new(expr),
+                        args,
+                        span,
+                    };
+                }
+                TokenKind::Dot => {
+                    self.advance();
+                    let field = self.name("field name")?;
+                    expr = fields(expr, field.lexeme, field.span);
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+
+    fn primary(&mut self, records: bool) -> Result<Expr, ParseError> {
+        let token = *self.peek();
+        match token.kind {
+            TokenKind::Number => {
+                self.advance();
+                let n = token.lexeme.parse().map_err(|_| ParseError::Invalid {
+                    message: format!("number `{}` out of range", token.lexeme),
+                    span: token.span,
+                })?;
+                Ok(Expr::Literal(Literal::Int(n), token.span))
+            }
+            TokenKind::String => {
+                self.advance();
+                Ok(Expr::Literal(
+                    Literal::Str(unescape(token.lexeme)),
+                    token.span,
+                ))
+            }
+            TokenKind::KwTrue | TokenKind::KwFalse => {
+                self.advance();
+                Ok(Expr::Literal(
+                    Literal::Bool(token.kind == TokenKind::KwTrue),
+                    token.span,
+                ))
+            }
+            TokenKind::LParen => {
+                self.advance();
+                if self.peek().kind == TokenKind::RParen {
+                    let close = self.advance();
+                    return Ok(Expr::Literal(
+                        Literal::Unit,
+                        Span::new(token.span.start, close.span.end),
+                    ));
+                }
+                let inner = self.expr(true)?;
+                let close = self.expect(TokenKind::RParen, ")")?;
+                Ok(Expr::Paren(
+                    Box::new(inner),
+                    Span::new(token.span.start, close.span.end),
+                ))
+            }
+            TokenKind::LBrace if records => self.record(token.span.start),
+            kind if is_name(kind) => {
+                self.advance();
+                // The lexer keeps dotted names such as `H.listen` in one token
+                let mut segments = token.lexeme.split('.');
+                let first = segments.next().unwrap_or_default();
+                let mut expr = Expr::Ident(Symbol::intern(first), token.span);
+                for segment in segments {
+                    expr = fields(expr, segment, token.span);
+                }
+                // `Point { x: 1 }`, `Point { ..p }` or `Ok{ }`: the type name
+                // is checked by the typechecker, not here
+                if records
+                    && self.peek().kind == TokenKind::LBrace
+                    && (matches!(self.peek_at(1).kind, TokenKind::RBrace | TokenKind::DotDot)
+                        || is_name(self.peek_at(1).kind)
+                            && self.peek_at(2).kind == TokenKind::Colon)
+                {
+                    return self.record(token.span.start);
+                }
+                Ok(expr)
+            }
+            _ => Err(self.unexpected("expression")),
+        }
+    }
+
+    /// Record literal or, after `..base`, record update.
+    fn record(&mut self, start: u32) -> Result<Expr, ParseError> {
+        self.expect(TokenKind::LBrace, "{")?;
+        let mut base = None;
+        if self.eat(TokenKind::DotDot) {
+            base = Some(self.expr(true)?);
+            if !self.eat(TokenKind::Comma) && self.peek().kind != TokenKind::RBrace {
+                return Err(self.unexpected("`,` or `}` after `..base`"));
+            }
+        }
+        let mut fields = Vec::new();
+        while self.peek().kind != TokenKind::RBrace {
+            if self.peek().kind == TokenKind::DotDot {
+                return Err(ParseError::Invalid {
+                    message: "`..base` must come first in a record".to_string(),
+                    span: self.peek().span,
+                });
+            }
+            let name = self.name("field name")?;
+            self.expect(TokenKind::Colon, ":")?;
+            let value = self.expr(true)?;
+            let span = Span::new(name.span.start, expr_span(&value).end);
+            fields.push(RecordInit {
+                name: Symbol::intern(name.lexeme),
+                value,
+                span,
+            });
+            if !self.eat(TokenKind::Comma) {
+                break;
+            }
+        }
+        let close = self.expect(TokenKind::RBrace, "closing } of record")?;
+        let span = Span::new(start, close.span.end);
+        Ok(match base {
+            Some(base) => Expr::RecordUpdate {
+                base: Box::new(base),
+                fields,
+                span,
+            },
+            None => Expr::Record { fields, span },
+        })
+    }
+}
+
+/// Whether a token of `kind` names something inside a body.
+fn is_name(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Ident
+            | TokenKind::KwModule
+            | TokenKind::KwUse
+            | TokenKind::KwAs
+            | TokenKind::KwOnly
+            | TokenKind::KwCtx
+            | TokenKind::KwCaps
+            | TokenKind::KwType
+            | TokenKind::KwFn
+            | TokenKind::KwEff
+    )
+}
+
+/// `base.a.b` for a dotted `field` name.
+fn fields(mut base: Expr, field: &str, span: Span) -> Expr {
+    for segment in field.split('.') {
+        base = Expr::Field {
+            base: Box::new(base),
+            field: Symbol::intern(segment),
+            span,
+        };
+    }
+    base
+}
+
+/// Binary operator of `kind` and its precedence (higher binds tighter).
+fn binop(kind: TokenKind) -> Option<(BinOp, u8)> {
+    Some(match kind {
+        TokenKind::Or => (BinOp::Or, 1),
+        TokenKind::And => (BinOp::And, 2),
+        TokenKind::EqEq => (BinOp::Eq, 3),
+        TokenKind::Ne => (BinOp::Ne, 3),
+        TokenKind::Lt => (BinOp::Lt, 4),
+        TokenKind::Le => (BinOp::Le, 4),
+        TokenKind::Gt => (BinOp::Gt, 4),
+        TokenKind::Ge => (BinOp::Ge, 4),
+        TokenKind::Plus => (BinOp::Add, 5),
+        TokenKind::Minus => (BinOp::Sub, 5),
+        TokenKind::Star => (BinOp::Mul, 6),
+        TokenKind::Slash => (BinOp::Div, 6),
+        TokenKind::Percent => (BinOp::Mod, 6),
+        _ => return None,
+    })
+}
+
+/// Span of `expr`.
+pub fn expr_span(expr: &Expr) -> Span {
+    match expr {
+        Expr::Ident(_, span)
+        | Expr::Literal(_, span)
+        | Expr::Path(_, span)
+        | Expr::Paren(_, span)
+        | Expr::BinOp { span, .. }
+        | Expr::UnaryOp { span, .. }
+        | Expr::Call { span, .. }
+        | Expr::Field { span, .. }
+        | Expr::Record { span, .. }
+        | Expr::RecordUpdate { span, .. } => *span,
+    }
+}
+
+/// Contents of a string literal lexeme, quotes removed and escapes resolved.
+fn unescape(lexeme: &str) -> String {
+    let inner = lexeme
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(lexeme);
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_statements_and_precedence() {
+        let stmts = parse_body(
+            "{ let mut x: U32 = 1 + 2 * 3; x = x - 1; if x > 2 && !done { ret H.Res{ status: 200 }; } else { log.info(\"a\\\"b\"); } }",
+        )
+        .unwrap();
+        assert_eq!(stmts.len(), 3);
+        let Stmt::Let(binding) = &stmts[0] else {
+            panic!("{stmts:?}");
+        };
+        assert!(binding.mutable);
+        assert_eq!(binding.span, Span::new(2, 28));
+        let Expr::BinOp { op, rhs, .. } = &binding.init else {
+            panic!("{binding:?}");
+        };
+        assert_eq!(*op, BinOp::Add);
+        assert!(matches!(**rhs, Expr::BinOp { op: BinOp::Mul, .. }));
+        assert!(matches!(stmts[1], Stmt::Assign(_)));
+
+        let Stmt::If(branch) = &stmts[2] else {
+            panic!("{stmts:?}");
+        };
+        let Stmt::Return(ret) = &branch.then_block.statements[0] else {
+            panic!("{branch:?}");
+        };
+        assert!(matches!(ret.value, Some(Expr::Record { .. })));
+        let Some(else_block) = &branch.else_block else {
+            panic!("{branch:?}");
+        };
+        let ElseBlock::Block(else_block) = &**else_block else {
+            panic!("{else_block:?}");
+        };
+        let Stmt::Expr(call) = &else_block.statements[0] else {
+            panic!("{else_block:?}");
+        };
+        let Expr::Call { func, args, .. } = &call.expr else {
+            panic!("{call:?}");
+        };
+        assert!(matches!(&**func, Expr::Field { field, .. } if field == "info"));
+        assert_eq!(
+            args[0],
+            Expr::Literal(Literal::Str("a\"b".into()), expr_span(&args[0]))
+        );
+    }
+
+    #[test]
+    fn assert_sugar_and_fixture_values() {
+        let stmts = parse_test_body("assert 1 + 1 == 2; assert_eq(x, 42)").unwrap();
+        assert_eq!(stmts.len(), 2);
+        assert!(matches!(
+            &stmts[0],
+            Stmt::Expr(ExprStmt { expr: Expr::Call { args, .. }, .. }) if args.len() == 1
+        ));
+        // Only test bodies take the sugar
+        assert!(parse_body("assert 1 + 1 == 2;").is_err());
+
+        assert!(matches!(
+            parse_expr("42").unwrap(),
+            Expr::Literal(Literal::Int(42), _)
+        ));
+        assert!(matches!(
+            parse_expr("{ status: 200, body: \"ok\" }").unwrap(),
+            Expr::Record { fields, .. } if fields.len() == 2
+        ));
+        assert!(parse_body("let = 1;").is_err());
+        // Compact item keywords name bindings in bodies
+        assert_eq!(parse_body("let t = f(1); ret t.u;").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn parses_record_updates() {
+        let Expr::RecordUpdate { base, fields, span } = parse_expr("Point { ..p, x: 2 }").unwrap()
+        else {
+            panic!("expected record update");
+        };
+        assert!(matches!(*base, Expr::Ident(ref name, _) if name == "p"));
+        assert_eq!(fields.len(), 1);
+        assert_eq!(span, Span::new(0, 19));
+        assert!(matches!(
+            parse_expr("{ ..load(1) }").unwrap(),
+            Expr::RecordUpdate { fields, .. } if fields.is_empty()
+        ));
+        // The base comes first, once
+        assert!(parse_expr("{ x: 2, ..p }").is_err());
+        assert!(parse_expr("{ ..p ..q }").is_err());
+    }
+}
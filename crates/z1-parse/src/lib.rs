@@ -1,7 +1,13 @@
+pub mod body;
+mod synthetic;
+mod template;
+
+pub use synthetic::synthetic_cell;
+
 use thiserror::Error;
 use z1_ast::{
-    Block, FnDecl, Import, Item, Module, ModulePath, Param, RecordField, Span, SymbolMap,
-    SymbolPair, TypeDecl, TypeExpr,
+    Block, Expansion, FnDecl, Ident, Import, Item, Module, ModulePath, Param, RecordField, Span,
+    Symbol, SymbolMap, SymbolPair, Template, TestDecl, TypeDecl, TypeExpr, UnionVariant,
 };
 use z1_fmt::SymbolTable;
 use z1_lex::{lex, Token, TokenKind};
@@ -23,15 +29,31 @@ pub enum ParseError {
     Invalid { message: String, span: Span },
 }
 
+impl ParseError {
+    /// The same error, reported at `span`.
+    fn at(self, span: Span) -> Self {
+        match self {
+            ParseError::Unexpected {
+                expected, found, ..
+            } => ParseError::Unexpected {
+                expected,
+                found,
+                span,
+            },
+            ParseError::Invalid { message, .. } => ParseError::Invalid { message, span },
+        }
+    }
+}
+
 struct Parser<'a> {
     source: &'a str,
-    tokens: Vec<Token>,
+    tokens: Vec<Token<'a>>,
     pos: usize,
     symtable: SymbolTable,
 }
 
 impl<'a> Parser<'a> {
-    fn new(source: &'a str, tokens: Vec<Token>) -> Self {
+    fn new(source: &'a str, tokens: Vec<Token<'a>>) -> Self {
         Self {
             source,
             tokens,
@@ -41,7 +63,7 @@ impl<'a> Parser<'a> {
     }
 
     /// Normalize an identifier to its canonical long form using the symbol table
-    fn normalize_ident(&self, ident: &str) -> String {
+    fn normalize_ident(&self, ident: &str) -> Ident {
         self.symtable.normalize_ident(ident)
     }
 
@@ -49,21 +71,31 @@ impl<'a> Parser<'a> {
         let start_span = self.peek().span;
         self.expect(TokenKind::KwModule, "module keyword")?;
         let path_token = self.expect(TokenKind::Ident, "module path")?;
-        let path = ModulePath::from_parts(
-            path_token
-                .lexeme
-                .split('.')
-                .map(|part| part.to_string())
-                .collect(),
-        );
+        let path =
+            ModulePath::from_parts(path_token.lexeme.split('.').map(Symbol::intern).collect());
         let version = self.parse_version()?;
         let mut ctx_budget = None;
         let mut caps = Vec::new();
+        let mut targets = Vec::new();
+        let mut toolchain = None;
 
         loop {
             match self.peek().kind {
                 TokenKind::KwCtx => ctx_budget = Some(self.parse_ctx_budget()?),
                 TokenKind::KwCaps => caps = self.parse_caps()?,
+                // Not keywords: both are ordinary identifiers outside the header
+                TokenKind::Ident if self.peek().lexeme == "targets" => {
+                    self.advance();
+                    self.expect(TokenKind::Eq, "equals after targets")?;
+                    targets = self.parse_name_list("target name", "] after targets list")?;
+                }
+                TokenKind::Ident
+                    if self.peek().lexeme == "toolchain" && self.nth(1).kind == TokenKind::Ge =>
+                {
+                    self.advance();
+                    self.advance();
+                    toolchain = Some(self.parse_version_number()?);
+                }
                 _ => break,
             }
         }
@@ -94,39 +126,207 @@ impl<'a> Parser<'a> {
 
         // Now parse all items (including symbol map again, which is OK)
         let mut items = Vec::new();
+        let mut templates: Vec<Template> = Vec::new();
+        let mut expansions = Vec::new();
+        // Index in `items` at which each expansion adds its items
+        let mut sites = Vec::new();
         while !self.at(TokenKind::Eof) {
-            match self.peek().kind {
-                TokenKind::KwUse => {
-                    let import = self.parse_import()?;
-                    items.push(Item::Import(import));
-                }
-                TokenKind::Sym => {
-                    let sym = self.parse_symbol_map()?;
-                    items.push(Item::Symbol(sym));
-                }
-                TokenKind::KwType => {
-                    let ty = self.parse_type_decl()?;
-                    items.push(Item::Type(ty));
-                }
-                TokenKind::KwFn => {
-                    let func = self.parse_fn_decl()?;
-                    items.push(Item::Fn(func));
-                }
-                TokenKind::Semi => {
-                    self.advance();
-                }
-                _ => {
-                    // Skip tokens we don't understand yet to avoid infinite loops.
-                    self.advance();
+            // Not keywords either: both only open an item followed by a name
+            if self.at_template_item("template") {
+                let template = self.parse_template()?;
+                if templates.iter().any(|other| other.name == template.name) {
+                    return Err(ParseError::Invalid {
+                        message: format!("template `{}` is declared twice", template.name),
+                        span: template.span,
+                    });
                 }
+                templates.push(template);
+            } else if self.at_template_item("expand") {
+                sites.push(items.len());
+                expansions.push(self.parse_expansion()?);
+            } else {
+                items.extend(self.parse_item()?);
             }
         }
 
+        // Templates may be expanded before they are declared
+        let mut expanded = Vec::with_capacity(expansions.len());
+        for (idx, expansion) in expansions.iter().enumerate() {
+            let ordinal = 1 + expansions[..idx]
+                .iter()
+                .filter(|other| other.template == expansion.template)
+                .count();
+            expanded.push(self.expand(&templates, expansion, ordinal)?);
+        }
+        for (site, generated) in sites.into_iter().zip(expanded).rev() {
+            items.splice(site..site, generated);
+        }
+
         let span = Span::new(
             start_span.start,
             self.tokens[self.pos.saturating_sub(1)].span.end,
         );
-        Ok(Module::new(path, version, ctx_budget, caps, items, span))
+        let mut module = Module::new(path, version, ctx_budget, caps, items, span);
+        module.targets = targets;
+        module.toolchain = toolchain;
+        module.templates = templates;
+        module.expansions = expansions;
+        Ok(module)
+    }
+
+    /// The item at the current token; `None` for a token opening no item,
+    /// which is skipped.
+    fn parse_item(&mut self) -> Result<Option<Item>, ParseError> {
+        let item = match self.peek().kind {
+            TokenKind::KwUse => Item::Import(self.parse_import()?),
+            TokenKind::Sym => Item::Symbol(self.parse_symbol_map()?),
+            TokenKind::KwType => Item::Type(self.parse_type_decl()?),
+            TokenKind::KwFn => Item::Fn(self.parse_fn_decl()?),
+            TokenKind::Hash if self.nth(1).lexeme == "test" => Item::Test(self.parse_test_decl()?),
+            _ => {
+                // Skip tokens we don't understand yet to avoid infinite loops.
+                self.advance();
+                return Ok(None);
+            }
+        };
+        Ok(Some(item))
+    }
+
+    /// Whether the current token is `word` opening a template item, as in
+    /// `template handler(...)` or `expand handler(...)`.
+    fn at_template_item(&self, word: &str) -> bool {
+        self.at(TokenKind::Ident)
+            && self.peek().lexeme == word
+            && self.nth(1).kind == TokenKind::Ident
+            && self.nth(2).kind == TokenKind::LParen
+    }
+
+    /// `template handler(name, path) { ...items... }`
+    fn parse_template(&mut self) -> Result<Template, ParseError> {
+        let start = self.advance().span;
+        let name = self.expect(TokenKind::Ident, "template name")?;
+        self.expect(TokenKind::LParen, "( after template name")?;
+        let mut params: Vec<Ident> = Vec::new();
+        while !self.at(TokenKind::RParen) && !self.at(TokenKind::Eof) {
+            let param = self.expect(TokenKind::Ident, "template parameter")?;
+            if param.lexeme.contains('.') || params.iter().any(|p| p == param.lexeme) {
+                return Err(ParseError::Invalid {
+                    message: format!("invalid template parameter `{}`", param.lexeme),
+                    span: param.span,
+                });
+            }
+            params.push(Symbol::intern(param.lexeme));
+            if self.at(TokenKind::Comma) {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        self.expect(TokenKind::RParen, ") after template parameters")?;
+        let body = self.parse_block()?;
+        Ok(Template {
+            name: Symbol::intern(name.lexeme),
+            params,
+            span: Span::new(start.start, body.span.end),
+            body,
+            doc: self.doc_comment(start.start),
+        })
+    }
+
+    /// `expand handler(get_user, "/users")`, each argument kept as written.
+    fn parse_expansion(&mut self) -> Result<Expansion, ParseError> {
+        let start = self.advance().span;
+        let template = self.expect(TokenKind::Ident, "template name")?;
+        self.expect(TokenKind::LParen, "( after template name")?;
+        let mut args = Vec::new();
+        let mut arg: Option<Span> = None;
+        let mut depth = 0usize;
+        loop {
+            let token = self.advance();
+            match token.kind {
+                TokenKind::Eof => {
+                    return Err(ParseError::Unexpected {
+                        expected: ") after template arguments",
+                        found: token.kind,
+                        span: token.span,
+                    })
+                }
+                TokenKind::Comma | TokenKind::RParen if depth == 0 => {
+                    match arg.take() {
+                        Some(span) => args
+                            .push(self.source[span.start as usize..span.end as usize].to_string()),
+                        // `expand handler()` takes no arguments
+                        None if token.kind == TokenKind::RParen && args.is_empty() => {}
+                        None => {
+                            return Err(ParseError::Invalid {
+                                message: "empty template argument".into(),
+                                span: token.span,
+                            })
+                        }
+                    }
+                    if token.kind == TokenKind::RParen {
+                        return Ok(Expansion {
+                            template: Symbol::intern(template.lexeme),
+                            args,
+                            span: Span::new(start.start, token.span.end),
+                        });
+                    }
+                    continue;
+                }
+                TokenKind::LParen | TokenKind::LBracket | TokenKind::LBrace => depth += 1,
+                TokenKind::RParen | TokenKind::RBracket | TokenKind::RBrace => {
+                    depth = depth.saturating_sub(1)
+                }
+                _ => {}
+            }
+            let span = arg.get_or_insert(token.span);
+            span.end = token.span.end;
+        }
+    }
+
+    /// The items `expansion` adds, parsed with the symbol table of the cell.
+    /// They all carry the span of `expansion`, as do errors in them.
+    fn expand(
+        &self,
+        templates: &[Template],
+        expansion: &Expansion,
+        ordinal: usize,
+    ) -> Result<Vec<Item>, ParseError> {
+        let invalid = |message: String| ParseError::Invalid {
+            message,
+            span: expansion.span,
+        };
+        let template = templates
+            .iter()
+            .find(|template| template.name == expansion.template)
+            .ok_or_else(|| invalid(format!("unknown template `{}`", expansion.template)))?;
+        if template.params.len() != expansion.args.len() {
+            return Err(invalid(format!(
+                "template `{}` takes {} argument(s), {} given",
+                template.name,
+                template.params.len(),
+                expansion.args.len()
+            )));
+        }
+
+        let source = template::instantiate(template, &expansion.args, ordinal);
+        let mut parser = Parser::new(&source, lex(&source));
+        parser.symtable = self.symtable.clone();
+        let mut items = Vec::new();
+        while !parser.at(TokenKind::Eof) {
+            if parser.at_template_item("template") || parser.at_template_item("expand") {
+                return Err(invalid(format!(
+                    "template `{}` declares or expands a template",
+                    template.name
+                )));
+            }
+            let item = parser.parse_item().map_err(|err| err.at(expansion.span))?;
+            items.extend(item);
+        }
+        for item in &mut items {
+            template::respan(item, expansion.span);
+        }
+        Ok(items)
     }
 
     fn parse_version(&mut self) -> Result<Option<String>, ParseError> {
@@ -134,15 +334,19 @@ impl<'a> Parser<'a> {
             return Ok(None);
         }
         self.advance();
+        self.parse_version_number().map(Some)
+    }
+
+    fn parse_version_number(&mut self) -> Result<String, ParseError> {
         let mut parts = Vec::new();
         let number = self.expect(TokenKind::Number, "version number")?;
-        parts.push(number.lexeme.clone());
+        parts.push(number.lexeme.to_string());
         while self.at(TokenKind::Dot) {
             self.advance();
             let segment = self.expect(TokenKind::Number, "version segment")?;
-            parts.push(segment.lexeme.clone());
+            parts.push(segment.lexeme.to_string());
         }
-        Ok(Some(parts.join(".")))
+        Ok(parts.join("."))
     }
 
     fn parse_ctx_budget(&mut self) -> Result<u32, ParseError> {
@@ -158,19 +362,28 @@ impl<'a> Parser<'a> {
     fn parse_caps(&mut self) -> Result<Vec<String>, ParseError> {
         self.expect(TokenKind::KwCaps, "caps keyword")?;
         self.expect(TokenKind::Eq, "equals after caps")?;
+        self.parse_name_list("capability name", "] after caps list")
+    }
+
+    /// A bracketed, comma-separated list of names, like `[net, fs.ro]`.
+    fn parse_name_list(
+        &mut self,
+        what: &'static str,
+        closing: &'static str,
+    ) -> Result<Vec<String>, ParseError> {
         self.expect(TokenKind::LBracket, "opening bracket")?;
-        let mut caps = Vec::new();
+        let mut names = Vec::new();
         while !self.at(TokenKind::RBracket) && !self.at(TokenKind::Eof) {
-            let cap = self.expect(TokenKind::Ident, "capability name")?;
-            caps.push(cap.lexeme.clone());
+            let name = self.expect(TokenKind::Ident, what)?;
+            names.push(name.lexeme.to_string());
             if self.at(TokenKind::Comma) {
                 self.advance();
             } else {
                 break;
             }
         }
-        self.expect(TokenKind::RBracket, "] after caps list")?;
-        Ok(caps)
+        self.expect(TokenKind::RBracket, closing)?;
+        Ok(names)
     }
 
     fn parse_import(&mut self) -> Result<Import, ParseError> {
@@ -180,7 +393,7 @@ impl<'a> Parser<'a> {
             self.advance();
             let alias_token = self.expect(TokenKind::Ident, "alias identifier")?;
             // Normalize alias to long form
-            Some(self.normalize_ident(&alias_token.lexeme))
+            Some(self.normalize_ident(alias_token.lexeme))
         } else {
             None
         };
@@ -191,7 +404,7 @@ impl<'a> Parser<'a> {
             while !self.at(TokenKind::RBracket) && !self.at(TokenKind::Eof) {
                 let item = self.expect(TokenKind::Ident, "only identifier")?;
                 // Normalize each imported item to long form
-                list.push(self.normalize_ident(&item.lexeme));
+                list.push(self.normalize_ident(item.lexeme));
                 if self.at(TokenKind::Comma) {
                     self.advance();
                 } else {
@@ -209,7 +422,7 @@ impl<'a> Parser<'a> {
         }
 
         Ok(Import {
-            path: strip_quotes(&path_token.lexeme),
+            path: strip_quotes(path_token.lexeme),
             alias,
             only,
             span: Span::new(start.start, self.previous().span.end),
@@ -226,8 +439,8 @@ impl<'a> Parser<'a> {
             let short = self.expect_ident_or_keyword("short identifier")?;
             let span = Span::new(long.span.start, short.span.end);
             pairs.push(SymbolPair {
-                long: long.lexeme,
-                short: short.lexeme,
+                long: Symbol::intern(long.lexeme),
+                short: Symbol::intern(short.lexeme),
                 span,
             });
             if self.at(TokenKind::Comma) {
@@ -248,6 +461,7 @@ impl<'a> Parser<'a> {
     fn parse_type_decl(&mut self) -> Result<TypeDecl, ParseError> {
         let start = self.expect(TokenKind::KwType, "type keyword")?.span;
         let name = self.expect_ident_or_keyword("type name")?;
+        let type_params = self.parse_type_params()?;
         self.expect(TokenKind::Eq, "equals in type declaration")?;
         let expr = self.parse_type_expr()?;
         if self.at(TokenKind::Semi) {
@@ -255,15 +469,20 @@ impl<'a> Parser<'a> {
         }
         let end_span = self.previous().span;
         Ok(TypeDecl {
-            name: self.normalize_ident(&name.lexeme), // Normalize to long form
+            name: self.normalize_ident(name.lexeme), // Normalize to long form
+            type_params,
             expr,
             span: Span::new(start.start, end_span.end),
+            doc: self.doc_comment(start.start),
         })
     }
 
     fn parse_type_expr(&mut self) -> Result<TypeExpr, ParseError> {
         match self.peek().kind {
             TokenKind::LBrace => self.parse_record_type(),
+            TokenKind::Ident if matches!(self.nth(1).kind, TokenKind::LParen | TokenKind::Pipe) => {
+                self.parse_union_type()
+            }
             TokenKind::Ident => self.parse_path_type(),
             _ => Err(ParseError::Unexpected {
                 expected: "type expression",
@@ -275,13 +494,45 @@ impl<'a> Parser<'a> {
 
     fn parse_path_type(&mut self) -> Result<TypeExpr, ParseError> {
         let ident = self.expect(TokenKind::Ident, "type identifier")?;
-        let mut segments = vec![self.normalize_ident(&ident.lexeme)]; // Normalize
+        let mut segments = vec![self.normalize_ident(ident.lexeme)]; // Normalize
         while self.at(TokenKind::Dot) {
             self.advance();
             let segment = self.expect(TokenKind::Ident, "path segment")?;
-            segments.push(self.normalize_ident(&segment.lexeme)); // Normalize
+            segments.push(self.normalize_ident(segment.lexeme)); // Normalize
+        }
+        if !self.at(TokenKind::LBracket) {
+            return Ok(TypeExpr::Path(segments));
+        }
+        self.advance();
+        let mut args = vec![self.parse_type_expr()?];
+        while self.at(TokenKind::Comma) {
+            self.advance();
+            args.push(self.parse_type_expr()?);
         }
-        Ok(TypeExpr::Path(segments))
+        self.expect(TokenKind::RBracket, "closing ] in type arguments")?;
+        Ok(TypeExpr::Generic {
+            base: segments,
+            args,
+        })
+    }
+
+    /// `[T, U]` after the name of a type or function; empty without brackets.
+    fn parse_type_params(&mut self) -> Result<Vec<Ident>, ParseError> {
+        let mut params = Vec::new();
+        if !self.at(TokenKind::LBracket) {
+            return Ok(params);
+        }
+        self.advance();
+        loop {
+            let param = self.expect(TokenKind::Ident, "type parameter")?;
+            params.push(self.normalize_ident(param.lexeme));
+            if !self.at(TokenKind::Comma) {
+                break;
+            }
+            self.advance();
+        }
+        self.expect(TokenKind::RBracket, "closing ] in type parameters")?;
+        Ok(params)
     }
 
     fn parse_record_type(&mut self) -> Result<TypeExpr, ParseError> {
@@ -293,7 +544,7 @@ impl<'a> Parser<'a> {
             let ty = self.parse_type_expr()?;
             let field_span = Span::new(name.span.start, self.previous().span.end);
             fields.push(RecordField {
-                name: self.normalize_ident(&name.lexeme), // Normalize field name
+                name: self.normalize_ident(name.lexeme), // Normalize field name
                 ty: Box::new(ty),
                 span: field_span,
             });
@@ -307,9 +558,37 @@ impl<'a> Parser<'a> {
         Ok(TypeExpr::Record(fields))
     }
 
+    /// `Name(T) | Name | ...`; a lone `Name(T)` is a union of one variant.
+    fn parse_union_type(&mut self) -> Result<TypeExpr, ParseError> {
+        let mut variants = vec![self.parse_union_variant()?];
+        while self.at(TokenKind::Pipe) {
+            self.advance();
+            variants.push(self.parse_union_variant()?);
+        }
+        Ok(TypeExpr::Union(variants))
+    }
+
+    fn parse_union_variant(&mut self) -> Result<UnionVariant, ParseError> {
+        let name = self.expect(TokenKind::Ident, "union variant name")?;
+        let payload = if self.at(TokenKind::LParen) {
+            self.advance();
+            let ty = self.parse_type_expr()?;
+            self.expect(TokenKind::RParen, "closing ) in union variant")?;
+            Some(Box::new(ty))
+        } else {
+            None
+        };
+        Ok(UnionVariant {
+            name: self.normalize_ident(name.lexeme),
+            payload,
+            span: Span::new(name.span.start, self.previous().span.end),
+        })
+    }
+
     fn parse_fn_decl(&mut self) -> Result<FnDecl, ParseError> {
         let start = self.expect(TokenKind::KwFn, "fn keyword")?.span;
         let name = self.expect_ident_or_keyword("function name")?;
+        let type_params = self.parse_type_params()?;
         self.expect(TokenKind::LParen, "opening ( in parameter list")?;
         let params = self.parse_params()?;
         self.expect(TokenKind::RParen, "closing ) in parameter list")?;
@@ -320,17 +599,42 @@ impl<'a> Parser<'a> {
         } else {
             Vec::new()
         };
-        let body = self.parse_block()?;
+        let body = self.parse_body(false)?;
         Ok(FnDecl {
-            name: self.normalize_ident(&name.lexeme), // CRITICAL: Normalize function name
+            name: self.normalize_ident(name.lexeme), // CRITICAL: Normalize function name
+            type_params,
             params,
             ret,
             effects,
             span: Span::new(start.start, body.span.end),
             body,
+            doc: self.doc_comment(start.start),
         })
     }
 
+    /// `///` lines right above the line of `start`, without their markers.
+    /// The lexer skips them as comments, so they are read off the source.
+    fn doc_comment(&self, start: u32) -> Option<String> {
+        let line_start = self.source[..start as usize]
+            .rfind('\n')
+            .map_or(0, |idx| idx + 1);
+        let mut lines: Vec<&str> = self.source[..line_start]
+            .lines()
+            .rev()
+            .map(str::trim_start)
+            .take_while(|line| line.starts_with("///"))
+            .map(|line| {
+                let text = &line[3..];
+                text.strip_prefix(' ').unwrap_or(text).trim_end()
+            })
+            .collect();
+        if lines.is_empty() {
+            return None;
+        }
+        lines.reverse();
+        Some(lines.join("\n"))
+    }
+
     fn parse_params(&mut self) -> Result<Vec<Param>, ParseError> {
         let mut params = Vec::new();
         while !self.at(TokenKind::RParen) && !self.at(TokenKind::Eof) {
@@ -343,7 +647,7 @@ impl<'a> Parser<'a> {
             let ty = self.parse_type_expr()?;
             let span = Span::new(name.span.start, self.previous().span.end);
             params.push(Param {
-                name: self.normalize_ident(&name.lexeme), // Normalize parameter name
+                name: self.normalize_ident(name.lexeme), // Normalize parameter name
                 ty,
                 span,
             });
@@ -356,13 +660,13 @@ impl<'a> Parser<'a> {
         Ok(params)
     }
 
-    fn parse_effects(&mut self) -> Result<Vec<String>, ParseError> {
+    fn parse_effects(&mut self) -> Result<Vec<Ident>, ParseError> {
         self.expect(TokenKind::KwEff, "eff keyword")?;
         self.expect(TokenKind::LBracket, "opening [ in effect list")?;
         let mut effects = Vec::new();
         while !self.at(TokenKind::RBracket) && !self.at(TokenKind::Eof) {
             let effect = self.expect(TokenKind::Ident, "effect identifier")?;
-            effects.push(effect.lexeme.clone());
+            effects.push(Symbol::intern(effect.lexeme));
             if self.at(TokenKind::Comma) {
                 self.advance();
             } else {
@@ -373,6 +677,34 @@ impl<'a> Parser<'a> {
         Ok(effects)
     }
 
+    fn parse_test_decl(&mut self) -> Result<TestDecl, ParseError> {
+        let start = self.expect(TokenKind::Hash, "#test directive")?.span;
+        self.expect(TokenKind::Ident, "test keyword")?;
+        let name = self.expect(TokenKind::String, "test name")?;
+        let body = self.parse_body(true)?;
+        Ok(TestDecl {
+            name: strip_quotes(name.lexeme),
+            span: Span::new(start.start, body.span.end),
+            body,
+        })
+    }
+
+    /// A function or test body, its statements parsed; `tests` accepts the
+    /// `assert <expr>;` sugar.
+    fn parse_body(&mut self, tests: bool) -> Result<Block, ParseError> {
+        let open = self.pos;
+        let mut block = self.parse_block()?;
+        let close = self.pos - 1;
+        block.statements = body::statements(
+            &self.tokens[open + 1..close],
+            self.tokens[close].span,
+            tests,
+        )?;
+        Ok(block)
+    }
+
+    /// A braced block kept as raw text; template bodies hold items and are
+    /// only parsed once expanded.
     fn parse_block(&mut self) -> Result<Block, ParseError> {
         let open = self.expect(TokenKind::LBrace, "opening { in block")?;
         let mut depth = 1;
@@ -406,7 +738,7 @@ impl<'a> Parser<'a> {
         })
     }
 
-    fn expect(&mut self, kind: TokenKind, expected: &'static str) -> Result<Token, ParseError> {
+    fn expect(&mut self, kind: TokenKind, expected: &'static str) -> Result<Token<'a>, ParseError> {
         if self.peek().kind == kind {
             Ok(self.advance())
         } else {
@@ -420,7 +752,7 @@ impl<'a> Parser<'a> {
 
     /// Accept either an identifier or a keyword token as an identifier
     /// This is needed in contexts like symbol maps where keywords can be used as names
-    fn expect_ident_or_keyword(&mut self, expected: &'static str) -> Result<Token, ParseError> {
+    fn expect_ident_or_keyword(&mut self, expected: &'static str) -> Result<Token<'a>, ParseError> {
         let token = self.peek();
         match token.kind {
             TokenKind::Ident
@@ -453,19 +785,23 @@ impl<'a> Parser<'a> {
         self.peek().kind == kind
     }
 
-    fn advance(&mut self) -> Token {
-        let token = self.tokens[self.pos].clone();
+    fn advance(&mut self) -> Token<'a> {
+        let token = self.tokens[self.pos];
         self.pos = usize::min(self.pos + 1, self.tokens.len() - 1);
         token
     }
 
-    fn previous(&self) -> &Token {
+    fn previous(&self) -> &Token<'a> {
         &self.tokens[self.pos.saturating_sub(1)]
     }
 
-    fn peek(&self) -> &Token {
+    fn peek(&self) -> &Token<'a> {
         &self.tokens[self.pos]
     }
+
+    fn nth(&self, ahead: usize) -> &Token<'a> {
+        &self.tokens[usize::min(self.pos + ahead, self.tokens.len() - 1)]
+    }
 }
 
 fn strip_quotes(input: &str) -> String {
@@ -530,4 +866,205 @@ mod tests {
             other => panic!("expected fn decl, got {other:?}"),
         }
     }
+
+    #[test]
+    fn parses_inline_tests() {
+        let source = "m calc:1.0\nf add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }\n\
+                      #test \"adds\" { let t = add(1, 2); assert_eq(t, 3); }\n";
+        let module = parse_module(source).expect("module parses");
+        assert_eq!(module.items.len(), 2);
+        match &module.items[1] {
+            Item::Test(test) => {
+                assert_eq!(test.name, "adds");
+                assert_eq!(test.body.raw, "{ let t = add(1, 2); assert_eq(t, 3); }");
+            }
+            other => panic!("expected test, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn captures_doc_comments() {
+        let source = "m calc:1.0\n// not a doc\n\n/// A sum.\n///\n///   indented\nf add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }\n\
+                      /// Whole numbers.\nt Count = U32;\nf zero()->U32 eff [pure] { ret 0; }\n";
+        let module = parse_module(source).expect("module parses");
+        let docs: Vec<Option<&str>> = module
+            .items
+            .iter()
+            .map(|item| match item {
+                Item::Fn(decl) => decl.doc.as_deref(),
+                Item::Type(decl) => decl.doc.as_deref(),
+                other => panic!("unexpected item {other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            docs,
+            [Some("A sum.\n\n  indented"), Some("Whole numbers."), None]
+        );
+    }
+
+    #[test]
+    fn parses_union_types() {
+        let source = "m calc:1.0\nt Res = Ok(U32) | Err({ code: U32 }) | Gone;\n\
+                      t Only = Some(U32)\nf get()->Ok(U32) | None eff [pure] { ret 0; }\n";
+        let module = parse_module(source).expect("module parses");
+        let variants = |ty: &TypeExpr| match ty {
+            TypeExpr::Union(variants) => variants
+                .iter()
+                .map(|v| (v.name, v.payload.is_some()))
+                .collect::<Vec<_>>(),
+            other => panic!("expected union, got {other:?}"),
+        };
+        let types = module
+            .items
+            .iter()
+            .map(|item| match item {
+                Item::Type(decl) => variants(&decl.expr),
+                Item::Fn(decl) => variants(&decl.ret),
+                other => panic!("unexpected item {other:?}"),
+            })
+            .collect::<Vec<_>>();
+        let named = |list: &[(&str, bool)]| {
+            list.iter()
+                .map(|(name, payload)| (Ident::from(*name), *payload))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            types,
+            [
+                named(&[("Ok", true), ("Err", true), ("Gone", false)]),
+                named(&[("Some", true)]),
+                named(&[("Ok", true), ("None", false)]),
+            ]
+        );
+
+        let err = parse_module("m calc:1.0\nt Res = Ok(U32) | ;\n").unwrap_err();
+        assert!(matches!(
+            err,
+            ParseError::Unexpected {
+                expected: "union variant name",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn parses_type_parameters() {
+        let source = "m calc:1.0\nt Pair[T] = { first: T, second: T }\n\
+                      f swap[T, U](p: Pair[T])->Map[T, List[U]] eff [pure] { ret p; }\n";
+        let module = parse_module(source).expect("module parses");
+        let Item::Type(pair) = &module.items[0] else {
+            panic!("expected a type");
+        };
+        assert_eq!(pair.type_params, ["T"]);
+        let Item::Fn(swap) = &module.items[1] else {
+            panic!("expected a function");
+        };
+        assert_eq!(swap.type_params, ["T", "U"]);
+        let path = |name: &str| TypeExpr::Path(vec![name.into()]);
+        assert_eq!(
+            swap.params[0].ty,
+            TypeExpr::Generic {
+                base: vec!["Pair".into()],
+                args: vec![path("T")],
+            }
+        );
+        assert_eq!(
+            swap.ret,
+            TypeExpr::Generic {
+                base: vec!["Map".into()],
+                args: vec![
+                    path("T"),
+                    TypeExpr::Generic {
+                        base: vec!["List".into()],
+                        args: vec![path("U")],
+                    },
+                ],
+            }
+        );
+
+        let err = parse_module("m calc:1.0\nt Pair[] = U32\n").unwrap_err();
+        assert!(matches!(
+            err,
+            ParseError::Unexpected {
+                expected: "type parameter",
+                ..
+            }
+        ));
+    }
+
+    const TEMPLATED: &str = "m api:1.0\n\
+        template handler(name, path) {\n\
+          f helper()->Str eff [pure] { ret path; }\n\
+          f name(id: U32)->Str eff [pure] { let route: Str = helper(); ret route; }\n\
+        }\n\
+        expand handler(get_user, \"/users\")\n\
+        f main()->U32 eff [pure] { ret 0; }\n\
+        expand handler(get_post, \"/posts\")\n\
+        template scale(name, factor) { f name(x: U32)->U32 eff [pure] { ret x * factor; } }\n\
+        expand scale(bump, x + 1)\n";
+
+    fn functions(module: &Module) -> Vec<(&str, &str)> {
+        module
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Fn(decl) => Some((decl.name.as_str(), decl.body.raw.as_str())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn expands_templates_hygienically() {
+        let module = parse_module(TEMPLATED).expect("module parses");
+        assert_eq!(module.templates.len(), 2);
+        assert_eq!(module.expansions.len(), 3);
+        assert_eq!(module.expansions[0].args, ["get_user", "\"/users\""]);
+        assert_eq!(
+            functions(&module),
+            [
+                ("helper_handler_1", "{ ret \"/users\"; }"),
+                (
+                    "get_user",
+                    "{ let route: Str = helper_handler_1(); ret route; }"
+                ),
+                ("main", "{ ret 0; }"),
+                ("helper_handler_2", "{ ret \"/posts\"; }"),
+                (
+                    "get_post",
+                    "{ let route: Str = helper_handler_2(); ret route; }"
+                ),
+                // `x` of the argument is not the parameter of the template
+                ("bump", "{ ret x_scale_1 * (x + 1); }"),
+            ]
+        );
+        match &module.items[1] {
+            Item::Fn(decl) => assert_eq!(decl.span, module.expansions[0].span),
+            other => panic!("expected fn decl, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_expansions() {
+        let invalid = |source: &str| match parse_module(source) {
+            Err(ParseError::Invalid { message, .. }) => message,
+            other => panic!("expected an invalid expansion, got {other:?}"),
+        };
+        assert_eq!(
+            invalid("m a:1.0\nexpand missing(x)\n"),
+            "unknown template `missing`"
+        );
+        assert_eq!(
+            invalid("m a:1.0\ntemplate one(x) { }\nexpand one(a, b)\n"),
+            "template `one` takes 1 argument(s), 2 given"
+        );
+        assert_eq!(
+            invalid("m a:1.0\ntemplate one(x, x) { }\n"),
+            "invalid template parameter `x`"
+        );
+        assert_eq!(
+            invalid("m a:1.0\ntemplate one(x) { expand one(x) }\nexpand one(a)\n"),
+            "template `one` declares or expands a template"
+        );
+    }
 }
@@ -0,0 +1,47 @@
+//! Large synthetic cells for benchmarking the front end.
+//!
+//! Real cells are small, so their lex and parse times are lost in noise.
+//! [`synthetic_cell`] writes a cell of any size out of the constructs real
+//! cells use: a symbol map, imports, record types and functions whose bodies
+//! bind, branch, loop, call and build records.
+
+use std::fmt::Write;
+
+/// A cell with `functions` functions, each with a record type of its own.
+/// It parses and type checks, but outgrows the default policy limits.
+pub fn synthetic_cell(functions: usize) -> String {
+    let mut cell = String::from(
+        "module bench.synthetic : 1.0\n  ctx = 1000000\n  caps = [net]\n\n\
+         #sym { handler: h, serve: sv }\n\n\
+         use \"std/http\" as H only [listen, Req, Res]\n",
+    );
+    for idx in 0..functions {
+        let _ = write!(
+            cell,
+            "\ntype Point{idx} = {{ x: U32, y: U32, label: Str }}\n\n\
+             /// Moves a point {idx} steps along both axes.\n\
+             fn step{idx}(p: Point{idx}, n: U32) -> Point{idx}\n  eff [pure]\n{{\n  \
+             let mut i = 0;\n  \
+             let mut x = p.x;\n  \
+             // Walk one unit at a time\n  \
+             while i < n {{\n    x = x + 1;\n    i = i + 1;\n  }}\n  \
+             if x > {idx} && !(p.y == 0) {{\n    \
+             ret Point{idx} {{ x: x, y: p.y * 2, label: \"moved {idx}\" }};\n  \
+             }} else {{\n    \
+             ret Point{idx} {{ ..p, y: (p.y + {idx}) % 7 }};\n  }}\n}}\n",
+        );
+    }
+    cell
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn synthetic_cells_parse() {
+        let module = crate::parse_module(&synthetic_cell(3)).expect("synthetic cell parses");
+        // Imports, symbol map, then a type and a function per step
+        assert_eq!(module.items.len(), 2 + 3 * 2);
+    }
+}
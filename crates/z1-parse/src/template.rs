@@ -0,0 +1,306 @@
+//! Template expansion.
+//!
+//! An expansion of a template is the template body with each parameter
+//! replaced by the text of its argument, parsed as items of the cell.
+//! Expansion is hygienic:
+//!
+//! - types and functions the template declares under a name of its own, not
+//!   a parameter, get the suffix `_<template>_<n>` in its `n`th expansion,
+//!   so expanding a template twice does not declare them twice;
+//! - function parameters and `let` bindings of the template are renamed the
+//!   same way when an argument mentions them, so they cannot capture it.
+//!
+//! Dotted names such as `req.size` are matched by their first segment. An
+//! argument with an operator in it is put in parentheses, so that
+//! `expand scale(bump, x + 1)` multiplies by `x + 1` as a whole.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use z1_ast::{Block, ElseBlock, Expr, IfStmt, Item, Span, Stmt, Template, TypeExpr};
+use z1_lex::{lex, Token, TokenKind};
+
+/// Source of the items the `ordinal`th expansion (from 1) of `template`
+/// adds, with `args` for its parameters.
+pub(crate) fn instantiate(template: &Template, args: &[String], ordinal: usize) -> String {
+    let raw = template.body.raw.as_str();
+    let body = raw
+        .strip_prefix('{')
+        .and_then(|body| body.strip_suffix('}'))
+        .unwrap_or(raw);
+    let tokens = lex(body);
+    let args: Vec<String> = args.iter().map(|arg| grouped(arg)).collect();
+    let params: BTreeMap<&str, &str> = template
+        .params
+        .iter()
+        .map(|param| param.as_str())
+        .zip(args.iter().map(String::as_str))
+        .collect();
+    let mentioned: BTreeSet<String> = args
+        .iter()
+        .flat_map(|arg| lex(arg))
+        .filter(|token| token.kind == TokenKind::Ident)
+        .map(|token| head(token.lexeme).0.to_string())
+        .collect();
+
+    let mut renamed = BTreeMap::new();
+    for (name, binding) in bindings(&tokens) {
+        if params.contains_key(name) {
+            continue;
+        }
+        if binding == Binding::Declaration || mentioned.contains(name) {
+            renamed.insert(name, format!("{name}_{}_{ordinal}", template.name));
+        }
+    }
+
+    let mut out = String::with_capacity(body.len());
+    let mut copied = 0;
+    for token in tokens.iter().filter(|token| token.kind == TokenKind::Ident) {
+        let (name, rest) = head(token.lexeme);
+        let replacement = match params.get(name) {
+            Some(arg) => *arg,
+            None => match renamed.get(name) {
+                Some(fresh) => fresh.as_str(),
+                None => continue,
+            },
+        };
+        out.push_str(&body[copied..token.span.start as usize]);
+        out.push_str(replacement);
+        out.push_str(rest);
+        copied = token.span.end as usize;
+    }
+    out.push_str(&body[copied..]);
+    out
+}
+
+/// `arg` as spliced into a template body.
+fn grouped(arg: &str) -> String {
+    let operator = lex(arg).iter().any(|token| {
+        matches!(
+            token.kind,
+            TokenKind::Plus
+                | TokenKind::Minus
+                | TokenKind::Star
+                | TokenKind::Slash
+                | TokenKind::Percent
+                | TokenKind::EqEq
+                | TokenKind::Ne
+                | TokenKind::Le
+                | TokenKind::Ge
+                | TokenKind::And
+                | TokenKind::Or
+                | TokenKind::Not
+        )
+    });
+    if operator {
+        format!("({arg})")
+    } else {
+        arg.to_string()
+    }
+}
+
+/// Give `item`, and everything in it, the span `span`.
+pub(crate) fn respan(item: &mut Item, span: Span) {
+    fn respan_type(expr: &mut TypeExpr, span: Span) {
+        match expr {
+            TypeExpr::Path(_) => {}
+            TypeExpr::Record(fields) => {
+                for field in fields {
+                    field.span = span;
+                    respan_type(&mut field.ty, span);
+                }
+            }
+            TypeExpr::Union(variants) => {
+                for variant in variants {
+                    variant.span = span;
+                    if let Some(payload) = &mut variant.payload {
+                        respan_type(payload, span);
+                    }
+                }
+            }
+            TypeExpr::Generic { args, .. } => {
+                for arg in args {
+                    respan_type(arg, span);
+                }
+            }
+        }
+    }
+
+    fn respan_block(block: &mut Block, span: Span) {
+        block.span = span;
+        for stmt in &mut block.statements {
+            match stmt {
+                Stmt::Let(stmt) => {
+                    stmt.span = span;
+                    if let Some(ty) = &mut stmt.ty {
+                        respan_type(ty, span);
+                    }
+                    respan_expr(&mut stmt.init, span);
+                }
+                Stmt::Assign(stmt) => {
+                    stmt.span = span;
+                    respan_expr(&mut stmt.target, span);
+                    respan_expr(&mut stmt.value, span);
+                }
+                Stmt::If(stmt) => respan_if(stmt, span),
+                Stmt::While(stmt) => {
+                    stmt.span = span;
+                    respan_expr(&mut stmt.cond, span);
+                    respan_block(&mut stmt.body, span);
+                }
+                Stmt::Return(stmt) => {
+                    stmt.span = span;
+                    if let Some(value) = &mut stmt.value {
+                        respan_expr(value, span);
+                    }
+                }
+                Stmt::Expr(stmt) => {
+                    stmt.span = span;
+                    respan_expr(&mut stmt.expr, span);
+                }
+            }
+        }
+    }
+
+    fn respan_if(stmt: &mut IfStmt, span: Span) {
+        stmt.span = span;
+        respan_expr(&mut stmt.cond, span);
+        respan_block(&mut stmt.then_block, span);
+        match stmt.else_block.as_deref_mut() {
+            Some(ElseBlock::Block(block)) => respan_block(block, span),
+            Some(ElseBlock::If(nested)) => respan_if(nested, span),
+            None => {}
+        }
+    }
+
+    fn respan_expr(expr: &mut Expr, span: Span) {
+        match expr {
+            Expr::Ident(_, at) | Expr::Literal(_, at) | Expr::Path(_, at) => *at = span,
+            Expr::Paren(inner, at) => {
+                *at = span;
+                respan_expr(inner, span);
+            }
+            Expr::BinOp {
+                lhs, rhs, span: at, ..
+            } => {
+                *at = span;
+                respan_expr(lhs, span);
+                respan_expr(rhs, span);
+            }
+            Expr::UnaryOp { expr, span: at, .. } => {
+                *at = span;
+                respan_expr(expr, span);
+            }
+            Expr::Call {
+                func,
+                args,
+                span: at,
+            } => {
+                *at = span;
+                respan_expr(func, span);
+                for arg in args {
+                    respan_expr(arg, span);
+                }
+            }
+            Expr::Field { base, span: at, .. } => {
+                *at = span;
+                respan_expr(base, span);
+            }
+            Expr::Record { fields, span: at } => {
+                *at = span;
+                for field in fields {
+                    field.span = span;
+                    respan_expr(&mut field.value, span);
+                }
+            }
+            Expr::RecordUpdate {
+                base,
+                fields,
+                span: at,
+            } => {
+                *at = span;
+                respan_expr(base, span);
+                for field in fields {
+                    field.span = span;
+                    respan_expr(&mut field.value, span);
+                }
+            }
+        }
+    }
+
+    match item {
+        Item::Import(import) => import.span = span,
+        Item::Symbol(map) => {
+            map.span = span;
+            for pair in &mut map.pairs {
+                pair.span = span;
+            }
+        }
+        Item::Type(decl) => {
+            decl.span = span;
+            respan_type(&mut decl.expr, span);
+        }
+        Item::Fn(decl) => {
+            decl.span = span;
+            respan_block(&mut decl.body, span);
+            respan_type(&mut decl.ret, span);
+            for param in &mut decl.params {
+                param.span = span;
+                respan_type(&mut param.ty, span);
+            }
+        }
+        Item::Test(test) => {
+            test.span = span;
+            respan_block(&mut test.body, span);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Binding {
+    /// A type or function of the template
+    Declaration,
+    /// A function parameter or `let` binding
+    Local,
+}
+
+/// Names the template body `tokens` binds.
+fn bindings<'src>(tokens: &[Token<'src>]) -> Vec<(&'src str, Binding)> {
+    let mut found = Vec::new();
+    let mut depth = 0usize;
+    // Inside the parameter list of a function declaration
+    let mut in_params = false;
+    for (idx, token) in tokens.iter().enumerate() {
+        let before = |back: usize| idx.checked_sub(back).map(|idx| tokens[idx].kind);
+        match token.kind {
+            TokenKind::LBrace => depth += 1,
+            TokenKind::RBrace => depth = depth.saturating_sub(1),
+            TokenKind::RParen => in_params = false,
+            TokenKind::Ident
+                if depth == 0 && matches!(before(1), Some(TokenKind::KwType | TokenKind::KwFn)) =>
+            {
+                found.push((token.lexeme, Binding::Declaration));
+                in_params = before(1) == Some(TokenKind::KwFn);
+            }
+            TokenKind::Ident
+                if in_params
+                    && tokens.get(idx + 1).map(|next| next.kind) == Some(TokenKind::Colon) =>
+            {
+                found.push((token.lexeme, Binding::Local));
+            }
+            TokenKind::Ident
+                if before(1) == Some(TokenKind::KwLet)
+                    || (before(1) == Some(TokenKind::KwMut)
+                        && before(2) == Some(TokenKind::KwLet)) =>
+            {
+                found.push((head(token.lexeme).0, Binding::Local));
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+/// `name` split before its first `.`, if any.
+fn head(name: &str) -> (&str, &str) {
+    name.split_at(name.find('.').unwrap_or(name.len()))
+}
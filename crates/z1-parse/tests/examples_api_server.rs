@@ -63,11 +63,11 @@ fn test_api_server_has_correct_types() {
     let source = std::fs::read_to_string(&path).unwrap();
     let module = parse_module(&source).unwrap();
 
-    let type_names: Vec<String> = module
+    let type_names: Vec<z1_ast::Ident> = module
         .items
         .iter()
         .filter_map(|item| match item {
-            z1_ast::Item::Type(t) => Some(t.name.clone()),
+            z1_ast::Item::Type(t) => Some(t.name),
             _ => None,
         })
         .collect();
@@ -78,11 +78,11 @@ fn test_api_server_has_correct_types() {
         "Expected at least 5 type definitions"
     );
 
-    assert!(type_names.contains(&"User".to_string()));
-    assert!(type_names.contains(&"UserList".to_string()));
-    assert!(type_names.contains(&"Response".to_string()));
-    assert!(type_names.contains(&"StatusResponse".to_string()));
-    assert!(type_names.contains(&"Router".to_string()));
+    assert!(type_names.contains(&"User".into()));
+    assert!(type_names.contains(&"UserList".into()));
+    assert!(type_names.contains(&"Response".into()));
+    assert!(type_names.contains(&"StatusResponse".into()));
+    assert!(type_names.contains(&"Router".into()));
 }
 
 #[test]
@@ -91,30 +91,30 @@ fn test_api_server_has_handler_functions() {
     let source = std::fs::read_to_string(&path).unwrap();
     let module = parse_module(&source).unwrap();
 
-    let fn_names: Vec<String> = module
+    let fn_names: Vec<z1_ast::Ident> = module
         .items
         .iter()
         .filter_map(|item| match item {
-            z1_ast::Item::Fn(f) => Some(f.name.clone()),
+            z1_ast::Item::Fn(f) => Some(f.name),
             _ => None,
         })
         .collect();
 
     // Core handlers
-    assert!(fn_names.contains(&"handleStatus".to_string()));
-    assert!(fn_names.contains(&"handleListUsers".to_string()));
-    assert!(fn_names.contains(&"handleGetUser".to_string()));
-    assert!(fn_names.contains(&"handleCreateUser".to_string()));
-    assert!(fn_names.contains(&"handleUpdateUser".to_string()));
-    assert!(fn_names.contains(&"handleDeleteUser".to_string()));
-    assert!(fn_names.contains(&"handleStatic".to_string()));
+    assert!(fn_names.contains(&"handleStatus".into()));
+    assert!(fn_names.contains(&"handleListUsers".into()));
+    assert!(fn_names.contains(&"handleGetUser".into()));
+    assert!(fn_names.contains(&"handleCreateUser".into()));
+    assert!(fn_names.contains(&"handleUpdateUser".into()));
+    assert!(fn_names.contains(&"handleDeleteUser".into()));
+    assert!(fn_names.contains(&"handleStatic".into()));
 
     // Routing
-    assert!(fn_names.contains(&"routeRequest".to_string()));
-    assert!(fn_names.contains(&"handleRequest".to_string()));
+    assert!(fn_names.contains(&"routeRequest".into()));
+    assert!(fn_names.contains(&"handleRequest".into()));
 
     // Entry point
-    assert!(fn_names.contains(&"main".to_string()));
+    assert!(fn_names.contains(&"main".into()));
 }
 
 #[test]
@@ -136,14 +136,14 @@ fn test_api_server_imports_http_server() {
     assert_eq!(imports.len(), 1);
     let import = imports[0];
     assert_eq!(import.path, "std/http/server");
-    assert_eq!(import.alias, Some("http".to_string()));
+    assert_eq!(import.alias, Some("http".into()));
 
     // Should have only clause
     let only_items = &import.only;
-    assert!(only_items.contains(&"HttpRequest".to_string()));
-    assert!(only_items.contains(&"HttpResponse".to_string()));
-    assert!(only_items.contains(&"createServer".to_string()));
-    assert!(only_items.contains(&"listen".to_string()));
+    assert!(only_items.contains(&"HttpRequest".into()));
+    assert!(only_items.contains(&"HttpResponse".into()));
+    assert!(only_items.contains(&"createServer".into()));
+    assert!(only_items.contains(&"listen".into()));
 }
 
 #[test]
@@ -164,9 +164,9 @@ fn test_api_server_main_function_effects() {
         .expect("main function not found");
 
     // Should have net, async, fs effects
-    assert!(main_fn.effects.contains(&"net".to_string()));
-    assert!(main_fn.effects.contains(&"async".to_string()));
-    assert!(main_fn.effects.contains(&"fs".to_string()));
+    assert!(main_fn.effects.contains(&"net".into()));
+    assert!(main_fn.effects.contains(&"async".into()));
+    assert!(main_fn.effects.contains(&"fs".into()));
 }
 
 #[test]
@@ -64,11 +64,11 @@ fn test_cli_tool_has_correct_types() {
     let source = std::fs::read_to_string(&path).unwrap();
     let module = parse_module(&source).unwrap();
 
-    let type_names: Vec<String> = module
+    let type_names: Vec<z1_ast::Ident> = module
         .items
         .iter()
         .filter_map(|item| match item {
-            z1_ast::Item::Type(t) => Some(t.name.clone()),
+            z1_ast::Item::Type(t) => Some(t.name),
             _ => None,
         })
         .collect();
@@ -79,9 +79,9 @@ fn test_cli_tool_has_correct_types() {
         "Expected at least 3 type definitions"
     );
 
-    assert!(type_names.contains(&"Config".to_string()));
-    assert!(type_names.contains(&"ProcessStats".to_string()));
-    assert!(type_names.contains(&"ProcessResult".to_string()));
+    assert!(type_names.contains(&"Config".into()));
+    assert!(type_names.contains(&"ProcessStats".into()));
+    assert!(type_names.contains(&"ProcessResult".into()));
 }
 
 #[test]
@@ -90,35 +90,35 @@ fn test_cli_tool_has_processing_functions() {
     let source = std::fs::read_to_string(&path).unwrap();
     let module = parse_module(&source).unwrap();
 
-    let fn_names: Vec<String> = module
+    let fn_names: Vec<z1_ast::Ident> = module
         .items
         .iter()
         .filter_map(|item| match item {
-            z1_ast::Item::Fn(f) => Some(f.name.clone()),
+            z1_ast::Item::Fn(f) => Some(f.name),
             _ => None,
         })
         .collect();
 
     // Argument and config handling
-    assert!(fn_names.contains(&"parseArgs".to_string()));
-    assert!(fn_names.contains(&"loadConfig".to_string()));
-    assert!(fn_names.contains(&"validateConfig".to_string()));
+    assert!(fn_names.contains(&"parseArgs".into()));
+    assert!(fn_names.contains(&"loadConfig".into()));
+    assert!(fn_names.contains(&"validateConfig".into()));
 
     // File processing
-    assert!(fn_names.contains(&"processFile".to_string()));
-    assert!(fn_names.contains(&"processLine".to_string()));
-    assert!(fn_names.contains(&"transformText".to_string()));
+    assert!(fn_names.contains(&"processFile".into()));
+    assert!(fn_names.contains(&"processLine".into()));
+    assert!(fn_names.contains(&"transformText".into()));
 
     // I/O operations
-    assert!(fn_names.contains(&"writeOutput".to_string()));
+    assert!(fn_names.contains(&"writeOutput".into()));
 
     // Utilities
-    assert!(fn_names.contains(&"countLines".to_string()));
-    assert!(fn_names.contains(&"printStats".to_string()));
-    assert!(fn_names.contains(&"printHelp".to_string()));
+    assert!(fn_names.contains(&"countLines".into()));
+    assert!(fn_names.contains(&"printStats".into()));
+    assert!(fn_names.contains(&"printHelp".into()));
 
     // Entry point
-    assert!(fn_names.contains(&"main".to_string()));
+    assert!(fn_names.contains(&"main".into()));
 }
 
 #[test]
@@ -147,10 +147,10 @@ fn test_cli_tool_imports_stdlib_modules() {
 
     // Verify aliases
     let args_import = imports.iter().find(|i| i.path == "std/env/args").unwrap();
-    assert_eq!(args_import.alias, Some("args".to_string()));
+    assert_eq!(args_import.alias, Some("args".into()));
 
     let fs_import = imports.iter().find(|i| i.path == "std/fs/core").unwrap();
-    assert_eq!(fs_import.alias, Some("fs".to_string()));
+    assert_eq!(fs_import.alias, Some("fs".into()));
 }
 
 #[test]
@@ -171,9 +171,9 @@ fn test_cli_tool_main_function_effects() {
         .expect("main function not found");
 
     // Should have env, fs, unsafe effects
-    assert!(main_fn.effects.contains(&"env".to_string()));
-    assert!(main_fn.effects.contains(&"fs".to_string()));
-    assert!(main_fn.effects.contains(&"unsafe".to_string()));
+    assert!(main_fn.effects.contains(&"env".into()));
+    assert!(main_fn.effects.contains(&"fs".into()));
+    assert!(main_fn.effects.contains(&"unsafe".into()));
 }
 
 #[test]
@@ -194,7 +194,7 @@ fn test_cli_tool_process_file_effects() {
         .expect("processFile function not found");
 
     // Should have fs effect for file I/O
-    assert!(process_fn.effects.contains(&"fs".to_string()));
+    assert!(process_fn.effects.contains(&"fs".into()));
 }
 
 #[test]
@@ -222,7 +222,7 @@ fn test_cli_tool_pure_transformations() {
             .unwrap_or_else(|| panic!("Function {fn_name} not found"));
 
         assert!(
-            func.effects.contains(&"pure".to_string()),
+            func.effects.contains(&"pure".into()),
             "Function {fn_name} should have pure effect"
         );
     }
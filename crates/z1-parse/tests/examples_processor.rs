@@ -65,9 +65,9 @@ fn test_processor_has_correct_imports() {
 
     assert_eq!(imports.len(), 2);
     assert_eq!(imports[0].path, "std/fs/core");
-    assert_eq!(imports[0].alias, Some("Fs".to_string()));
+    assert_eq!(imports[0].alias, Some("Fs".into()));
     assert_eq!(imports[1].path, "std/crypto/hash");
-    assert_eq!(imports[1].alias, Some("Hash".to_string()));
+    assert_eq!(imports[1].alias, Some("Hash".into()));
 }
 
 #[test]
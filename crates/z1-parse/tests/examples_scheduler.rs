@@ -65,9 +65,9 @@ fn test_scheduler_has_correct_imports() {
 
     assert_eq!(imports.len(), 2);
     assert_eq!(imports[0].path, "std/time/core");
-    assert_eq!(imports[0].alias, Some("Time".to_string()));
+    assert_eq!(imports[0].alias, Some("Time".into()));
     assert_eq!(imports[1].path, "std/time/timer");
-    assert_eq!(imports[1].alias, Some("Timer".to_string()));
+    assert_eq!(imports[1].alias, Some("Timer".into()));
 }
 
 #[test]
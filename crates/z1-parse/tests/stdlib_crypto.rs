@@ -102,17 +102,17 @@ fn test_crypto_hash_has_correct_types() {
     let source = std::fs::read_to_string(&path).unwrap();
     let module = parse_module(&source).unwrap();
 
-    let type_names: Vec<String> = module
+    let type_names: Vec<z1_ast::Ident> = module
         .items
         .iter()
         .filter_map(|item| match item {
-            z1_ast::Item::Type(t) => Some(t.name.clone()),
+            z1_ast::Item::Type(t) => Some(t.name),
             _ => None,
         })
         .collect();
 
     assert!(
-        type_names.contains(&"HashAlgorithm".to_string()),
+        type_names.contains(&"HashAlgorithm".into()),
         "HashAlgorithm type should be defined"
     );
 }
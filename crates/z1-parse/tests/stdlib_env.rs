@@ -98,16 +98,16 @@ fn test_env_vars_has_correct_types() {
     let source = std::fs::read_to_string(&path).unwrap();
     let module = parse_module(&source).unwrap();
 
-    let type_names: Vec<String> = module
+    let type_names: Vec<z1_ast::Ident> = module
         .items
         .iter()
         .filter_map(|item| match item {
-            z1_ast::Item::Type(t) => Some(t.name.clone()),
+            z1_ast::Item::Type(t) => Some(t.name),
             _ => None,
         })
         .collect();
 
-    assert!(type_names.contains(&"EnvVar".to_string()));
+    assert!(type_names.contains(&"EnvVar".into()));
 }
 
 #[test]
@@ -116,16 +116,16 @@ fn test_env_args_has_correct_types() {
     let source = std::fs::read_to_string(&path).unwrap();
     let module = parse_module(&source).unwrap();
 
-    let type_names: Vec<String> = module
+    let type_names: Vec<z1_ast::Ident> = module
         .items
         .iter()
         .filter_map(|item| match item {
-            z1_ast::Item::Type(t) => Some(t.name.clone()),
+            z1_ast::Item::Type(t) => Some(t.name),
             _ => None,
         })
         .collect();
 
-    assert!(type_names.contains(&"Args".to_string()));
+    assert!(type_names.contains(&"Args".into()));
 }
 
 #[test]
@@ -134,16 +134,16 @@ fn test_env_process_has_correct_types() {
     let source = std::fs::read_to_string(&path).unwrap();
     let module = parse_module(&source).unwrap();
 
-    let type_names: Vec<String> = module
+    let type_names: Vec<z1_ast::Ident> = module
         .items
         .iter()
         .filter_map(|item| match item {
-            z1_ast::Item::Type(t) => Some(t.name.clone()),
+            z1_ast::Item::Type(t) => Some(t.name),
             _ => None,
         })
         .collect();
 
-    assert!(type_names.contains(&"ProcessInfo".to_string()));
+    assert!(type_names.contains(&"ProcessInfo".into()));
 }
 
 #[test]
@@ -101,17 +101,17 @@ fn test_fs_core_has_correct_types() {
     let source = std::fs::read_to_string(&path).unwrap();
     let module = parse_module(&source).unwrap();
 
-    let type_names: Vec<String> = module
+    let type_names: Vec<z1_ast::Ident> = module
         .items
         .iter()
         .filter_map(|item| match item {
-            z1_ast::Item::Type(t) => Some(t.name.clone()),
+            z1_ast::Item::Type(t) => Some(t.name),
             _ => None,
         })
         .collect();
 
-    assert!(type_names.contains(&"ReadResult".to_string()));
-    assert!(type_names.contains(&"WriteResult".to_string()));
+    assert!(type_names.contains(&"ReadResult".into()));
+    assert!(type_names.contains(&"WriteResult".into()));
 }
 
 #[test]
@@ -120,17 +120,17 @@ fn test_fs_dir_has_correct_types() {
     let source = std::fs::read_to_string(&path).unwrap();
     let module = parse_module(&source).unwrap();
 
-    let type_names: Vec<String> = module
+    let type_names: Vec<z1_ast::Ident> = module
         .items
         .iter()
         .filter_map(|item| match item {
-            z1_ast::Item::Type(t) => Some(t.name.clone()),
+            z1_ast::Item::Type(t) => Some(t.name),
             _ => None,
         })
         .collect();
 
-    assert!(type_names.contains(&"ListResult".to_string()));
-    assert!(type_names.contains(&"DirResult".to_string()));
+    assert!(type_names.contains(&"ListResult".into()));
+    assert!(type_names.contains(&"DirResult".into()));
 }
 
 #[test]
@@ -139,16 +139,16 @@ fn test_fs_path_has_correct_types() {
     let source = std::fs::read_to_string(&path).unwrap();
     let module = parse_module(&source).unwrap();
 
-    let type_names: Vec<String> = module
+    let type_names: Vec<z1_ast::Ident> = module
         .items
         .iter()
         .filter_map(|item| match item {
-            z1_ast::Item::Type(t) => Some(t.name.clone()),
+            z1_ast::Item::Type(t) => Some(t.name),
             _ => None,
         })
         .collect();
 
-    assert!(type_names.contains(&"ExtOption".to_string()));
+    assert!(type_names.contains(&"ExtOption".into()));
 }
 
 #[test]
@@ -72,18 +72,18 @@ fn test_time_core_has_correct_types() {
     let source = std::fs::read_to_string(&path).unwrap();
     let module = parse_module(&source).unwrap();
 
-    let type_names: Vec<String> = module
+    let type_names: Vec<z1_ast::Ident> = module
         .items
         .iter()
         .filter_map(|item| match item {
-            z1_ast::Item::Type(t) => Some(t.name.clone()),
+            z1_ast::Item::Type(t) => Some(t.name),
             _ => None,
         })
         .collect();
 
-    assert!(type_names.contains(&"Timestamp".to_string()));
-    assert!(type_names.contains(&"Duration".to_string()));
-    assert!(type_names.contains(&"DateTime".to_string()));
+    assert!(type_names.contains(&"Timestamp".into()));
+    assert!(type_names.contains(&"Duration".into()));
+    assert!(type_names.contains(&"DateTime".into()));
 }
 
 #[test]
@@ -92,16 +92,16 @@ fn test_time_timer_has_correct_types() {
     let source = std::fs::read_to_string(&path).unwrap();
     let module = parse_module(&source).unwrap();
 
-    let type_names: Vec<String> = module
+    let type_names: Vec<z1_ast::Ident> = module
         .items
         .iter()
         .filter_map(|item| match item {
-            z1_ast::Item::Type(t) => Some(t.name.clone()),
+            z1_ast::Item::Type(t) => Some(t.name),
             _ => None,
         })
         .collect();
 
-    assert!(type_names.contains(&"Timer".to_string()));
+    assert!(type_names.contains(&"Timer".into()));
 }
 
 #[test]
@@ -0,0 +1,237 @@
+//! The front half of the Zero1 toolchain, for the browser.
+//!
+//! Built for `wasm32-unknown-unknown`, this crate exports the formatter,
+//! the checkers and the context estimate to JavaScript through
+//! `wasm-bindgen`, so a playground can run them on every keystroke without
+//! a server. Everything crosses the boundary as strings: cells go in,
+//! formatted text or a JSON [`Report`] comes out.
+//!
+//! Each call interns the names of its cell in a [`Session`] of its own, so
+//! a page left open does not accumulate them.
+//!
+//! Nothing here touches a file system or spawns a thread. The pipeline is
+//! [`z1_driver`] without its `fs` feature, so `std` imports resolve to the
+//! bundled standard library and other imports stay opaque.
+//!
+//! ## Usage
+//!
+//! From JavaScript, after `wasm-bindgen --target web`:
+//!
+//! ```js
+//! import init, { check, format } from "./z1_playground.js";
+//!
+//! await init();
+//! const report = JSON.parse(check(source));
+//! for (const d of report.diagnostics) {
+//!   console.log(`${d.range?.start.line}:${d.range?.start.column} ${d.message}`);
+//! }
+//! editor.setValue(format(source, "relaxed"));
+//! ```
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+use z1_ast::{Module, Session, Span};
+use z1_driver::{ArtifactKind, CompileOptions, CompileResult, Compiler, Diagnostic, Target};
+use z1_fmt::{FmtOptions, Mode};
+
+/// Version of the toolchain the playground was built from.
+#[wasm_bindgen]
+pub fn version() -> String {
+    env!("CARGO_PKG_VERSION").to_string()
+}
+
+/// `source` formatted in `mode`, `"compact"` or `"relaxed"`.
+///
+/// # Errors
+///
+/// Throws the parse error when `source` is not a valid cell, or when `mode`
+/// is neither.
+#[wasm_bindgen]
+pub fn format(source: &str, mode: &str) -> Result<String, String> {
+    let mode = match mode {
+        "compact" => Mode::Compact,
+        "relaxed" => Mode::Relaxed,
+        other => return Err(format!("unknown format mode `{other}`")),
+    };
+    Session::new().enter(|| {
+        let module = z1_parse::parse_module(source).map_err(|err| err.to_string())?;
+        z1_fmt::format_module(&module, mode, &FmtOptions::default()).map_err(|err| err.to_string())
+    })
+}
+
+/// Check the cell `source`: parse, resolve, type and effect check,
+/// estimate and gate it. Returns a JSON [`Report`] without files.
+#[wasm_bindgen]
+pub fn check(source: &str) -> String {
+    Session::new().enter(|| {
+        let result = compiler(Target::TypeScript).compile_source(source);
+        to_json(&Report::new(source, &result, false))
+    })
+}
+
+/// Check and compile the cell `source` to `target`, `"ts"` or `"wat"`.
+/// Returns a JSON [`Report`] whose files hold the generated code.
+///
+/// # Errors
+///
+/// Throws when `target` is neither.
+#[wasm_bindgen]
+pub fn compile(source: &str, target: &str) -> Result<String, String> {
+    let target = match target {
+        "ts" => Target::TypeScript,
+        "wat" => Target::Wat,
+        other => return Err(format!("unknown playground target `{other}`")),
+    };
+    Session::new().enter(|| {
+        let result = compiler(target).compile_source(source);
+        Ok(to_json(&Report::new(source, &result, true)))
+    })
+}
+
+fn compiler(target: Target) -> Compiler {
+    Compiler::new(CompileOptions {
+        target,
+        ..CompileOptions::default()
+    })
+}
+
+fn to_json(report: &Report) -> String {
+    serde_json::to_string(report).expect("reports serialize")
+}
+
+/// What [`check`] and [`compile`] return, as JSON.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    /// Whether no stage reported an error
+    pub ok: bool,
+    pub diagnostics: Vec<Reported>,
+    /// Context estimate, when the cell parses
+    pub context: Option<Context>,
+    /// Semantic hash, when the cell parses
+    pub semhash: Option<String>,
+    /// Generated files, from [`compile`] when it succeeds
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<File>,
+}
+
+/// A diagnostic with its span as editor positions.
+#[derive(Debug, Serialize)]
+pub struct Reported {
+    #[serde(flatten)]
+    pub diagnostic: Diagnostic,
+    pub range: Option<Range>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+/// One-based line and column; columns count UTF-16 code units, as
+/// JavaScript strings do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Context {
+    pub total_tokens: u32,
+    /// The cell's `ctx=` budget
+    pub budget: Option<u32>,
+    pub functions: Vec<FnContext>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FnContext {
+    pub name: String,
+    pub tokens: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct File {
+    pub kind: ArtifactKind,
+    pub name: String,
+    pub contents: String,
+}
+
+impl Report {
+    fn new(source: &str, result: &CompileResult, files: bool) -> Self {
+        let diagnostics = result
+            .diagnostics
+            .iter()
+            .map(|diagnostic| Reported {
+                diagnostic: diagnostic.clone(),
+                range: diagnostic.span.map(|span| range(source, span)),
+            })
+            .collect();
+        let files = if files {
+            result
+                .artifacts
+                .iter()
+                .map(|artifact| File {
+                    kind: artifact.kind,
+                    name: artifact.file_name.clone(),
+                    contents: String::from_utf8_lossy(&artifact.contents).into_owned(),
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        Self {
+            ok: result.is_ok(),
+            diagnostics,
+            context: result.module.as_ref().and_then(context),
+            semhash: result
+                .module
+                .as_ref()
+                .map(|module| z1_hash::module_hashes(module).semantic),
+            files,
+        }
+    }
+}
+
+/// The context estimate of `module`, whether or not it fits its budget;
+/// going over is reported as a diagnostic already.
+fn context(module: &Module) -> Option<Context> {
+    let config = z1_ctx::EstimateConfig {
+        enforce_budget: false,
+        ..z1_ctx::EstimateConfig::default()
+    };
+    let estimate = z1_ctx::estimate_cell_with_config(module, &config).ok()?;
+    Some(Context {
+        total_tokens: estimate.total_tokens,
+        budget: estimate.budget,
+        functions: estimate
+            .functions
+            .into_iter()
+            .map(|function| FnContext {
+                name: function.name,
+                tokens: function.tokens,
+            })
+            .collect(),
+    })
+}
+
+/// Editor positions of the byte offsets of `span` in `source`.
+pub fn range(source: &str, span: Span) -> Range {
+    Range {
+        start: position(source, span.start as usize),
+        end: position(source, span.end as usize),
+    }
+}
+
+fn position(source: &str, offset: usize) -> Position {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    Position {
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].encode_utf16().count() + 1,
+    }
+}
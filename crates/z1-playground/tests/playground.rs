@@ -0,0 +1,78 @@
+use serde_json::Value;
+use z1_ast::Span;
+use z1_playground::{check, compile, format, range, Position};
+
+fn json(report: &str) -> Value {
+    serde_json::from_str(report).expect("valid JSON")
+}
+
+#[test]
+fn formats_in_either_mode() {
+    let source = "m app:1.0\nf  main()->U32   eff [pure] { ret 1; }\n";
+    let compact = format(source, "compact").unwrap();
+    assert!(compact.contains("f main()->U32 eff [pure]"), "{compact}");
+    let relaxed = format(&compact, "relaxed").unwrap();
+    assert!(relaxed.starts_with("module app : 1.0"), "{relaxed}");
+
+    assert!(format("m app:1.0\nf main(->U32\n", "compact").is_err());
+    assert_eq!(
+        format(source, "pretty").unwrap_err(),
+        "unknown format mode `pretty`"
+    );
+}
+
+#[test]
+fn checks_report_diagnostics_with_editor_positions() {
+    let report = json(&check(
+        "m app:1.0 caps=[net]\nf main()->U32 eff [pure] { ret 1; }\n",
+    ));
+    assert_eq!(report["ok"], true);
+    let warning = &report["diagnostics"][0];
+    assert_eq!(warning["severity"], "warning");
+    assert_eq!(warning["stage"], "effects");
+    assert_eq!(warning["code"], "W001");
+    assert!(report["semhash"].as_str().unwrap().starts_with("sha3-256:"));
+    assert_eq!(report["context"]["functions"][0]["name"], "main");
+    assert!(report.get("files").is_none());
+
+    let report = json(&check("m app:1.0\nf main(->U32\n"));
+    assert_eq!(report["ok"], false);
+    let error = &report["diagnostics"][0];
+    assert_eq!(error["stage"], "parse");
+    assert_eq!(error["range"]["start"]["line"], 2);
+    assert!(report["context"].is_null() && report["semhash"].is_null());
+}
+
+#[test]
+fn compiles_to_typescript_and_wat() {
+    let source = "m app:1.0\nf main()->U32 eff [pure] { ret 1; }\n";
+    let report = json(&compile(source, "ts").unwrap());
+    assert_eq!(report["files"][0]["kind"], "output");
+    assert_eq!(report["files"][0]["name"], "app.ts");
+    assert!(report["files"][0]["contents"]
+        .as_str()
+        .unwrap()
+        .contains("function main"));
+
+    let report = json(&compile(source, "wat").unwrap());
+    assert!(report["files"][0]["contents"]
+        .as_str()
+        .unwrap()
+        .contains("(module"));
+    assert!(compile(source, "wasm").is_err());
+}
+
+#[test]
+fn columns_count_utf16_code_units() {
+    let source = "m app:1.0\n// é𝄞\nf main";
+    let start = source.find("f main").unwrap() as u32;
+    assert_eq!(
+        range(source, Span::new(start, start + 1)).start,
+        Position { line: 3, column: 1 }
+    );
+    let end = source.find('\n').unwrap() as u32 + 10;
+    assert_eq!(
+        range(source, Span::new(end, end)).end,
+        Position { line: 2, column: 7 }
+    );
+}
@@ -7,15 +7,19 @@
 //!
 //! These limits are designed to keep code small, modular, and tractable for LLM agents.
 
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use z1_ast::{FnDecl, Item, Module, TypeExpr};
+use z1_ast::{Block, ElseBlock, Expr, FnDecl, IfStmt, Item, Module, Stmt, TypeExpr};
 use z1_ctx::estimate_cell;
 use z1_effects::{check_module as check_effects, EffectError};
 
 /// Policy limits configuration.
 ///
-/// These defaults align with vision.md section 9.
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// These defaults align with vision.md section 9. Limits are read from the
+/// `[policy]` table of a project's `z1.toml`; omitted ones keep their
+/// defaults.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct PolicyLimits {
     /// Maximum AST nodes per cell (default: 200)
     pub cell_max_ast_nodes: usize,
@@ -90,6 +94,34 @@ pub enum PolicyViolation {
     CellContextBudgetExceeded { limit: u32, actual: u32 },
 }
 
+impl PolicyViolation {
+    /// Key of the `[policy]` table that sets the limit broken, if the limit
+    /// is configurable.
+    pub fn limit_key(&self) -> Option<&'static str> {
+        match self {
+            PolicyViolation::AstNodeLimitExceeded { .. } => Some("cell_max_ast_nodes"),
+            PolicyViolation::ExportLimitExceeded { .. } => Some("cell_max_exports"),
+            PolicyViolation::FaninLimitExceeded { .. } => Some("deps_max_fanin"),
+            PolicyViolation::ParamLimitExceeded { .. } => Some("fn_max_params"),
+            PolicyViolation::LocalsLimitExceeded { .. } => Some("fn_max_locals"),
+            PolicyViolation::ContextBudgetExceeded { .. } => Some("ctx_max_per_fn"),
+            PolicyViolation::EffectNotInCapabilities { .. }
+            | PolicyViolation::CellContextBudgetExceeded { .. } => None,
+        }
+    }
+
+    /// Function the violation is about, for function-level limits.
+    pub fn fn_name(&self) -> Option<&str> {
+        match self {
+            PolicyViolation::ParamLimitExceeded { fn_name, .. }
+            | PolicyViolation::LocalsLimitExceeded { fn_name, .. }
+            | PolicyViolation::ContextBudgetExceeded { fn_name, .. }
+            | PolicyViolation::EffectNotInCapabilities { fn_name, .. } => Some(fn_name),
+            _ => None,
+        }
+    }
+}
+
 /// Policy checker with configurable limits.
 pub struct PolicyChecker {
     limits: PolicyLimits,
@@ -189,11 +221,71 @@ impl PolicyChecker {
                 count += fn_decl.params.len() * 2; // param name + type
                 count += Self::count_type_expr_nodes(&fn_decl.ret);
                 count += fn_decl.effects.len();
-                // Body: rough estimate based on character count
-                // Every 10 chars ~= 1 AST node (very rough heuristic)
-                count += fn_decl.body.raw.len() / 10;
+                count += Self::count_block_nodes(&fn_decl.body);
                 count
             }
+            // Inline tests share the cell's budget
+            Item::Test(test) => 1 + Self::count_block_nodes(&test.body),
+        }
+    }
+
+    fn count_block_nodes(block: &Block) -> usize {
+        block.statements.iter().map(Self::count_stmt_nodes).sum()
+    }
+
+    fn count_stmt_nodes(stmt: &Stmt) -> usize {
+        1 + match stmt {
+            Stmt::Let(stmt) => {
+                1 + stmt.ty.as_ref().map_or(0, Self::count_type_expr_nodes)
+                    + Self::count_expr_nodes(&stmt.init)
+            }
+            Stmt::Assign(stmt) => {
+                Self::count_expr_nodes(&stmt.target) + Self::count_expr_nodes(&stmt.value)
+            }
+            Stmt::If(stmt) => Self::count_if_nodes(stmt),
+            Stmt::While(stmt) => {
+                Self::count_expr_nodes(&stmt.cond) + Self::count_block_nodes(&stmt.body)
+            }
+            Stmt::Return(stmt) => stmt.value.as_ref().map_or(0, Self::count_expr_nodes),
+            Stmt::Expr(stmt) => Self::count_expr_nodes(&stmt.expr),
+        }
+    }
+
+    fn count_if_nodes(stmt: &IfStmt) -> usize {
+        Self::count_expr_nodes(&stmt.cond)
+            + Self::count_block_nodes(&stmt.then_block)
+            + match stmt.else_block.as_deref() {
+                Some(ElseBlock::Block(block)) => Self::count_block_nodes(block),
+                // An `else if` counts as a statement of the else block
+                Some(ElseBlock::If(nested)) => 1 + Self::count_if_nodes(nested),
+                None => 0,
+            }
+    }
+
+    fn count_expr_nodes(expr: &Expr) -> usize {
+        1 + match expr {
+            Expr::Ident(..) | Expr::Literal(..) => 0,
+            Expr::Path(segments, _) => segments.len(),
+            Expr::BinOp { lhs, rhs, .. } => {
+                Self::count_expr_nodes(lhs) + Self::count_expr_nodes(rhs)
+            }
+            Expr::UnaryOp { expr, .. } | Expr::Paren(expr, _) => Self::count_expr_nodes(expr),
+            Expr::Call { func, args, .. } => {
+                Self::count_expr_nodes(func)
+                    + args.iter().map(Self::count_expr_nodes).sum::<usize>()
+            }
+            Expr::Field { base, .. } => 1 + Self::count_expr_nodes(base),
+            Expr::Record { fields, .. } => fields
+                .iter()
+                .map(|field| 1 + Self::count_expr_nodes(&field.value))
+                .sum(),
+            Expr::RecordUpdate { base, fields, .. } => {
+                Self::count_expr_nodes(base)
+                    + fields
+                        .iter()
+                        .map(|field| 1 + Self::count_expr_nodes(&field.value))
+                        .sum::<usize>()
+            }
         }
     }
 
@@ -206,6 +298,15 @@ impl PolicyChecker {
                     .map(|f| 1 + Self::count_type_expr_nodes(&f.ty))
                     .sum::<usize>()
             }
+            TypeExpr::Union(variants) => {
+                1 + variants
+                    .iter()
+                    .map(|v| 1 + v.payload.as_deref().map_or(0, Self::count_type_expr_nodes))
+                    .sum::<usize>()
+            }
+            TypeExpr::Generic { base, args } => {
+                base.len() + args.iter().map(Self::count_type_expr_nodes).sum::<usize>()
+            }
         }
     }
 
@@ -267,7 +368,7 @@ impl PolicyChecker {
         let actual = fn_decl.params.len();
         if actual > self.limits.fn_max_params {
             Err(PolicyViolation::ParamLimitExceeded {
-                fn_name: fn_decl.name.clone(),
+                fn_name: fn_decl.name.to_string(),
                 limit: self.limits.fn_max_params,
                 actual,
             })
@@ -276,17 +377,35 @@ impl PolicyChecker {
         }
     }
 
-    /// Count local variables in function body.
-    /// This is a rough heuristic: count occurrences of "let " in the body.
+    /// Count the `let` bindings of a function body, nested blocks included.
     fn count_locals(fn_decl: &FnDecl) -> usize {
-        fn_decl.body.raw.matches("let ").count()
+        fn block_locals(block: &Block) -> usize {
+            block.statements.iter().map(stmt_locals).sum()
+        }
+        fn if_locals(stmt: &IfStmt) -> usize {
+            block_locals(&stmt.then_block)
+                + match stmt.else_block.as_deref() {
+                    Some(ElseBlock::Block(block)) => block_locals(block),
+                    Some(ElseBlock::If(nested)) => if_locals(nested),
+                    None => 0,
+                }
+        }
+        fn stmt_locals(stmt: &Stmt) -> usize {
+            match stmt {
+                Stmt::Let(_) => 1,
+                Stmt::If(stmt) => if_locals(stmt),
+                Stmt::While(stmt) => block_locals(&stmt.body),
+                Stmt::Assign(_) | Stmt::Return(_) | Stmt::Expr(_) => 0,
+            }
+        }
+        block_locals(&fn_decl.body)
     }
 
     fn check_locals_limit(&self, fn_decl: &FnDecl) -> Result<(), PolicyViolation> {
         let actual = Self::count_locals(fn_decl);
         if actual > self.limits.fn_max_locals {
             Err(PolicyViolation::LocalsLimitExceeded {
-                fn_name: fn_decl.name.clone(),
+                fn_name: fn_decl.name.to_string(),
                 limit: self.limits.fn_max_locals,
                 actual,
             })
@@ -349,6 +468,9 @@ impl PolicyChecker {
                     },
                     EffectError::UnknownEffect {
                         fn_name, effect, ..
+                    }
+                    | EffectError::ImportedEffect {
+                        fn_name, effect, ..
                     } => PolicyViolation::EffectNotInCapabilities {
                         fn_name,
                         effect,
@@ -364,45 +486,53 @@ impl PolicyChecker {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use z1_ast::{Block, Import, ModulePath, Param, Span, SymbolMap, TypeDecl};
+    use z1_ast::{Import, ModulePath, Param, Span, SymbolMap, TypeDecl};
 
     fn make_module(caps: Vec<&str>, ctx_budget: Option<u32>, items: Vec<Item>) -> Module {
         Module {
-            path: ModulePath::from_parts(vec!["test".to_string()]),
+            path: ModulePath::from_parts(vec!["test".into()]),
             version: Some("1.0".to_string()),
             ctx_budget,
             caps: caps.into_iter().map(String::from).collect(),
+            targets: Vec::new(),
+            toolchain: None,
             items,
+            templates: Vec::new(),
+            expansions: Vec::new(),
             span: Span::new(0, 100),
         }
     }
 
     fn make_fn(name: &str, params: usize, effects: Vec<&str>, body: &str) -> FnDecl {
         FnDecl {
-            name: name.to_string(),
+            name: name.into(),
+            type_params: vec![],
             params: (0..params)
                 .map(|i| Param {
-                    name: format!("p{i}"),
-                    ty: TypeExpr::Path(vec!["U32".to_string()]),
+                    name: format!("p{i}").into(),
+                    ty: TypeExpr::Path(vec!["U32".into()]),
                     span: Span::new(0, 1),
                 })
                 .collect(),
-            ret: TypeExpr::Path(vec!["Unit".to_string()]),
-            effects: effects.into_iter().map(String::from).collect(),
+            ret: TypeExpr::Path(vec!["Unit".into()]),
+            effects: effects.into_iter().map(z1_ast::Ident::from).collect(),
             body: Block {
                 raw: body.to_string(),
-                statements: Vec::new(),
+                statements: z1_parse::body::parse_body(body).unwrap(),
                 span: Span::new(0, body.len() as u32),
             },
             span: Span::new(0, 10),
+            doc: None,
         }
     }
 
     fn make_type(name: &str) -> TypeDecl {
         TypeDecl {
-            name: name.to_string(),
-            expr: TypeExpr::Path(vec!["U32".to_string()]),
+            name: name.into(),
+            type_params: vec![],
+            expr: TypeExpr::Path(vec!["U32".into()]),
             span: Span::new(0, 10),
+            doc: None,
         }
     }
 
@@ -548,7 +678,7 @@ mod tests {
 
     #[test]
     fn test_function_with_32_locals_passes() {
-        let body = "let ".repeat(32) + "ret Unit";
+        let body = "let x = 0; ".repeat(32) + "ret Unit";
         let module = make_module(vec![], None, vec![Item::Fn(make_fn("f", 0, vec![], &body))]);
         let checker = PolicyChecker::with_defaults();
         assert!(checker.check_module(&module).is_ok());
@@ -556,7 +686,7 @@ mod tests {
 
     #[test]
     fn test_function_with_33_locals_fails() {
-        let body = "let ".repeat(33) + "ret Unit";
+        let body = "let x = 0; ".repeat(33) + "ret Unit";
         let module = make_module(vec![], None, vec![Item::Fn(make_fn("f", 0, vec![], &body))]);
         let checker = PolicyChecker::with_defaults();
         let result = checker.check_module(&module);
@@ -588,7 +718,7 @@ mod tests {
     fn test_function_exceeding_context_budget_fails() {
         // Create a very large function body to exceed 256 tokens
         // At 3.8 chars/token, 256 tokens = ~973 chars
-        let large_body = "x".repeat(1000);
+        let large_body = "x; ".repeat(334);
         let module = make_module(
             vec![],
             None,
@@ -874,4 +1004,26 @@ mod tests {
             "Should have CellContextBudgetExceeded violation, got: {violations:?}"
         );
     }
+
+    #[test]
+    fn test_violation_names_its_limit_and_function() {
+        let checker = PolicyChecker::new(PolicyLimits {
+            fn_max_params: 2,
+            ..PolicyLimits::default()
+        });
+        let module = make_module(
+            vec![],
+            None,
+            vec![Item::Fn(make_fn("wide", 3, vec!["pure"], "{}"))],
+        );
+        let violations = checker.check_module(&module).unwrap_err();
+        assert_eq!(violations[0].limit_key(), Some("fn_max_params"));
+        assert_eq!(violations[0].fn_name(), Some("wide"));
+
+        let cell = PolicyViolation::CellContextBudgetExceeded {
+            limit: 1,
+            actual: 2,
+        };
+        assert_eq!((cell.limit_key(), cell.fn_name()), (None, None));
+    }
 }
@@ -2,6 +2,7 @@
 
 use crate::types::{ProvenanceChain, ProvenanceEntry};
 use sha3::{Digest, Sha3_256};
+use std::collections::HashSet;
 use std::fs;
 use std::path::Path;
 use thiserror::Error;
@@ -20,10 +21,27 @@ pub enum ChainError {
 }
 
 /// Compute the hash of a provenance entry for Merkle chain linking.
+///
+/// Uses the same `sha3-256:`-prefixed form that [`verify_chain`] checks
+/// `prev` links against.
+///
+/// [`verify_chain`]: crate::verify_chain
 pub fn compute_entry_hash(entry: &ProvenanceEntry) -> String {
-    let json = serde_json::to_string(entry).expect("entry serialization failed");
+    crate::verify::compute_entry_hash(entry)
+}
+
+/// Merkle root over the sorted hashes of `entries`; empty for no entries.
+pub(crate) fn merkle_root<'a>(entries: impl IntoIterator<Item = &'a ProvenanceEntry>) -> String {
+    let mut hashes: Vec<String> = entries.into_iter().map(compute_entry_hash).collect();
+    if hashes.is_empty() {
+        return String::new();
+    }
+    hashes.sort();
+
     let mut hasher = Sha3_256::new();
-    hasher.update(json.as_bytes());
+    for entry_hash in &hashes {
+        hasher.update(entry_hash.as_bytes());
+    }
     hex::encode(hasher.finalize())
 }
 
@@ -31,11 +49,20 @@ pub fn compute_entry_hash(entry: &ProvenanceEntry) -> String {
 pub trait ProvenanceChainExt {
     /// Append a new entry to the chain.
     ///
-    /// Automatically sets the `prev` field to link to the previous entry.
-    /// Returns the hash of the appended entry.
+    /// Automatically links the entry to the current heads: `prev` is set to
+    /// the first head and `merged` to the rest, so appending to a chain with
+    /// several branches joins them. Returns the hash of the appended entry.
     fn append(&mut self, entry: ProvenanceEntry) -> Result<String, ChainError>;
 
+    /// Hashes of the entries no later entry links to, in chain order.
+    ///
+    /// A chain without unmerged branches has a single head, its last entry.
+    fn heads(&self) -> Vec<String>;
+
     /// Compute the Merkle root of all entries.
+    ///
+    /// The root depends only on the set of entries, not on their order, so
+    /// replicas that merged the same branches agree on it.
     fn compute_merkle_root(&self) -> String;
 
     /// Update the merkle_root field by recomputing it.
@@ -45,6 +72,9 @@ pub trait ProvenanceChainExt {
     fn load_from_file<P: AsRef<Path>>(path: P) -> Result<ProvenanceChain, ChainError>;
 
     /// Save the provenance chain to a JSON file.
+    ///
+    /// The chain is written to a temporary file next to `path` and renamed
+    /// into place, so readers never observe a partially written chain.
     fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), ChainError>;
 
     /// Get an entry by index.
@@ -65,14 +95,10 @@ impl ProvenanceChainExt for ProvenanceChain {
             ));
         }
 
-        // Set prev to hash of last entry (if any)
-        if let Some(last_entry) = self.entries.last() {
-            let prev_hash = compute_entry_hash(last_entry);
-            entry.prev = Some(prev_hash);
-        } else {
-            // First entry has no previous
-            entry.prev = None;
-        }
+        // Link to the current heads; the first entry has no parents
+        let mut heads = self.heads().into_iter();
+        entry.prev = heads.next();
+        entry.merged = heads.collect();
 
         // Compute hash of new entry
         let entry_hash = compute_entry_hash(&entry);
@@ -86,20 +112,22 @@ impl ProvenanceChainExt for ProvenanceChain {
         Ok(entry_hash)
     }
 
-    fn compute_merkle_root(&self) -> String {
-        if self.entries.is_empty() {
-            return String::new();
-        }
-
-        let mut hasher = Sha3_256::new();
-
-        for entry in &self.entries {
-            let entry_hash = compute_entry_hash(entry);
-            hasher.update(entry_hash.as_bytes());
-        }
+    fn heads(&self) -> Vec<String> {
+        let linked: HashSet<&str> = self
+            .entries
+            .iter()
+            .flat_map(|entry| entry.prev.iter().chain(&entry.merged))
+            .map(String::as_str)
+            .collect();
+        self.entries
+            .iter()
+            .map(compute_entry_hash)
+            .filter(|hash| !linked.contains(hash.as_str()))
+            .collect()
+    }
 
-        let result = hasher.finalize();
-        hex::encode(result)
+    fn compute_merkle_root(&self) -> String {
+        merkle_root(&self.entries)
     }
 
     fn update_merkle_root(&mut self) {
@@ -113,8 +141,12 @@ impl ProvenanceChainExt for ProvenanceChain {
     }
 
     fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), ChainError> {
+        let path = path.as_ref();
         let json = serde_json::to_string_pretty(&self)?;
-        fs::write(path, json)?;
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, path)?;
         Ok(())
     }
 
@@ -133,17 +165,24 @@ mod tests {
         ProvenanceEntry {
             entry_id: id.to_string(),
             prev: None,
+            merged: vec![],
             actor: actor.to_string(),
             model: "test-model".to_string(),
             prompt_sha3: "test_prompt_hash".to_string(),
             prompt_excerpt: "Test prompt".to_string(),
+            redaction: None,
             tools: vec!["test-tool".to_string()],
             diff_sha3: "test_diff_hash".to_string(),
+            cells: vec![],
             timestamp: Utc::now(),
             signatures: vec![Signature {
                 by: actor.to_string(),
                 sig: "ed25519:test_sig".to_string(),
             }],
+            keyless: vec![],
+            rotation: None,
+            checkpoint: None,
+            timestamp_token: None,
         }
     }
 
@@ -178,6 +217,17 @@ mod tests {
         assert!(chain.entries[2].prev.is_some());
     }
 
+    #[test]
+    fn test_appended_chain_verifies() {
+        let mut chain = ProvenanceChain::new();
+        for i in 1..=3 {
+            let entry = create_test_entry(&format!("cell:test@v{i}"), "agent:test");
+            chain.append(entry).unwrap();
+        }
+
+        assert!(crate::verify_chain(&chain).is_ok());
+    }
+
     #[test]
     fn test_compute_merkle_root_empty_chain() {
         let chain = ProvenanceChain::new();
@@ -0,0 +1,325 @@
+//! Checkpoints and compaction of long chains.
+//!
+//! A checkpoint entry commits to the Merkle root of its ancestors and carries
+//! the state that verifying later entries needs: the keys of signers that
+//! rotated and, in its `cells`, the recorded state of every cell. Compaction
+//! then drops the ancestors; they can be archived and checked against the
+//! checkpoint later.
+
+use crate::chain::{merkle_root, ChainError, ProvenanceChainExt};
+use crate::types::{CellBinding, Checkpoint, ProvenanceChain, ProvenanceEntry};
+use crate::verify::{ancestry, parent_indices, starts_compacted, verify_chain, VerificationError};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use thiserror::Error;
+
+/// Errors that can occur while checkpointing or compacting a chain.
+#[derive(Debug, Error)]
+pub enum CompactError {
+    #[error("Chain is invalid: {0}")]
+    Invalid(#[from] VerificationError),
+
+    #[error("Chain has no checkpoint entry")]
+    NoCheckpoint,
+
+    #[error("Entry {0} is on a branch the checkpoint does not cover")]
+    Uncovered(String),
+
+    #[error("Invalid entry: {0}")]
+    InvalidEntry(#[from] ChainError),
+}
+
+/// Append `entry` to `chain` as a checkpoint of every entry before it.
+///
+/// The entry's `checkpoint` and `cells` are filled in; it links to all
+/// current heads, so unmerged branches are joined. Returns the hash of the
+/// appended entry.
+pub fn append_checkpoint(
+    chain: &mut ProvenanceChain,
+    mut entry: ProvenanceEntry,
+) -> Result<String, CompactError> {
+    let parents = parent_indices(chain)?;
+
+    // Last binding in chain order wins, as in `verify_chain_sources`
+    let mut cells: BTreeMap<&str, &str> = BTreeMap::new();
+    for binding in chain.entries.iter().flat_map(|entry| &entry.cells) {
+        cells.insert(&binding.module, &binding.semhash);
+    }
+    entry.cells = cells
+        .into_iter()
+        .map(|(module, semhash)| CellBinding {
+            module: module.to_string(),
+            semhash: semhash.to_string(),
+        })
+        .collect();
+
+    entry.checkpoint = Some(Checkpoint {
+        root: merkle_root(&chain.entries),
+        entries: chain.len(),
+        keys: rotated_keys(chain, &parents),
+    });
+    Ok(chain.append(entry)?)
+}
+
+/// Keys of the signers that rotated in `chain`, as of all its heads.
+///
+/// Follows the same rules as signature verification: each branch inherits
+/// the most rotated key of each signer.
+fn rotated_keys(chain: &ProvenanceChain, parents: &[Vec<usize>]) -> BTreeMap<String, String> {
+    let mut states: Vec<HashMap<&str, (&str, u32)>> = Vec::with_capacity(chain.len());
+    for (index, (entry, parents)) in chain.entries.iter().zip(parents).enumerate() {
+        let mut state: HashMap<&str, (&str, u32)> = HashMap::new();
+        if let Some(checkpoint) = entry
+            .checkpoint
+            .as_ref()
+            .filter(|_| starts_compacted(index, entry))
+        {
+            for (signer, key) in &checkpoint.keys {
+                state.insert(signer, (key, 0));
+            }
+        }
+        for &parent in parents {
+            inherit(&mut state, &states[parent]);
+        }
+        if let Some(rotation) = &entry.rotation {
+            let generation = state.get(rotation.signer.as_str()).map_or(0, |&(_, g)| g);
+            state.insert(&rotation.signer, (&rotation.new_key, generation + 1));
+        }
+        states.push(state);
+    }
+
+    let linked: HashSet<usize> = parents.iter().flatten().copied().collect();
+    let mut heads_state = HashMap::new();
+    for (index, state) in states.iter().enumerate() {
+        if !linked.contains(&index) {
+            inherit(&mut heads_state, state);
+        }
+    }
+    heads_state
+        .into_iter()
+        .map(|(signer, (key, _))| (signer.to_string(), key.to_string()))
+        .collect()
+}
+
+fn inherit<'a>(
+    state: &mut HashMap<&'a str, (&'a str, u32)>,
+    from: &HashMap<&'a str, (&'a str, u32)>,
+) {
+    for (&signer, &(key, generation)) in from {
+        if state
+            .get(signer)
+            .map_or(true, |&(_, current)| generation > current)
+        {
+            state.insert(signer, (key, generation));
+        }
+    }
+}
+
+/// Drop the ancestors of the latest checkpoint entry from `chain`.
+///
+/// Returns the dropped entries as a chain of their own, for archiving. Fails
+/// when an entry after the checkpoint links to an entry before it, i.e. a
+/// branch forked before the checkpoint was merged afterwards; `chain` is
+/// left unchanged in that case.
+pub fn compact(chain: &mut ProvenanceChain) -> Result<ProvenanceChain, CompactError> {
+    verify_chain(chain)?;
+    let parents = parent_indices(chain)?;
+    let target = chain
+        .entries
+        .iter()
+        .rposition(|entry| entry.checkpoint.is_some())
+        .ok_or(CompactError::NoCheckpoint)?;
+
+    let mut archived = ancestry(&parents, target);
+    archived[target] = false;
+    for (index, entry) in chain.entries.iter().enumerate() {
+        let uncovered = match index.cmp(&target) {
+            Ordering::Less => !archived[index],
+            Ordering::Equal => false,
+            Ordering::Greater => parents[index].iter().any(|&parent| archived[parent]),
+        };
+        if uncovered {
+            return Err(CompactError::Uncovered(entry.entry_id.clone()));
+        }
+    }
+
+    let history: Vec<ProvenanceEntry> = chain.entries.drain(..target).collect();
+    chain.update_merkle_root();
+    let mut history = ProvenanceChain {
+        entries: history,
+        merkle_root: String::new(),
+    };
+    history.update_merkle_root();
+    Ok(history)
+}
+
+/// Verify that `history` is exactly the history `checkpoint` committed to.
+///
+/// `history` must be a valid chain whose heads are the checkpoint's parents
+/// and whose Merkle root and length match the checkpoint.
+pub fn verify_checkpoint(
+    history: &ProvenanceChain,
+    checkpoint: &ProvenanceEntry,
+) -> Result<(), VerificationError> {
+    let invalid = |reason: &str| {
+        VerificationError::InvalidCheckpoint(checkpoint.entry_id.clone(), reason.into())
+    };
+    let committed = checkpoint
+        .checkpoint
+        .as_ref()
+        .ok_or_else(|| invalid("entry is not a checkpoint"))?;
+    verify_chain(history)?;
+
+    if committed.entries != history.len() || committed.root != merkle_root(&history.entries) {
+        return Err(invalid("history does not match its root"));
+    }
+    let mut heads = history.heads();
+    let mut links: Vec<String> = checkpoint
+        .prev
+        .iter()
+        .chain(&checkpoint.merged)
+        .cloned()
+        .collect();
+    heads.sort();
+    links.sort();
+    if heads != links {
+        return Err(invalid("history heads are not the checkpoint's parents"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge::merge_chains;
+    use crate::signature::{endorse_rotation, keygen, sign_entry};
+    use crate::source::verify_chain_sources;
+    use crate::verify::verify_chain_signatures;
+    use chrono::Utc;
+
+    fn entry(id: &str, cells: &[(&str, &str)]) -> ProvenanceEntry {
+        ProvenanceEntry {
+            entry_id: id.to_string(),
+            prev: None,
+            merged: vec![],
+            actor: "dev:alice".to_string(),
+            model: "test-model".to_string(),
+            prompt_sha3: "abc123".to_string(),
+            prompt_excerpt: "test prompt".to_string(),
+            redaction: None,
+            tools: vec![],
+            diff_sha3: "def456".to_string(),
+            cells: cells
+                .iter()
+                .map(|(module, semhash)| CellBinding {
+                    module: module.to_string(),
+                    semhash: semhash.to_string(),
+                })
+                .collect(),
+            timestamp: Utc::now(),
+            signatures: vec![],
+            keyless: vec![],
+            rotation: None,
+            checkpoint: None,
+            timestamp_token: None,
+        }
+    }
+
+    fn sign_latest(chain: &mut ProvenanceChain, key: &[u8; 32]) {
+        let latest = chain.entries.last_mut().unwrap();
+        latest.signatures.push(sign_entry(latest, key, "dev:alice"));
+        chain.update_merkle_root();
+    }
+
+    #[test]
+    fn test_compacted_chain_stays_verifiable() {
+        let (old_private, old_public) = keygen();
+        let (new_private, new_public) = keygen();
+        let mut chain = ProvenanceChain::new();
+
+        chain
+            .append(entry("cell:app@v1", &[("app", "a1")]))
+            .unwrap();
+        sign_latest(&mut chain, &old_private);
+        let mut rotation = entry("cell:lib@v1", &[("lib", "l1")]);
+        rotation.rotation = Some(endorse_rotation("dev:alice", &old_private, &new_public));
+        chain.append(rotation).unwrap();
+        sign_latest(&mut chain, &old_private);
+        chain
+            .append(entry("cell:app@v2", &[("app", "a2")]))
+            .unwrap();
+        sign_latest(&mut chain, &new_private);
+
+        append_checkpoint(&mut chain, entry("checkpoint:1", &[])).unwrap();
+        sign_latest(&mut chain, &new_private);
+        chain
+            .append(entry("cell:app@v3", &[("app", "a3")]))
+            .unwrap();
+        sign_latest(&mut chain, &new_private);
+
+        let keys = HashMap::from([("dev:alice".to_string(), old_public)]);
+        verify_chain(&chain).unwrap();
+        verify_chain_signatures(&chain, &keys, None).unwrap();
+
+        let history = compact(&mut chain).unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(chain.len(), 2);
+        verify_chain(&chain).unwrap();
+        verify_checkpoint(&history, &chain.entries[0]).unwrap();
+
+        // The checkpoint carries the rotated key and the cell state
+        verify_chain_signatures(&chain, &keys, None).unwrap();
+        let tree = HashMap::from([
+            ("app".to_string(), "a3".to_string()),
+            ("lib".to_string(), "l1".to_string()),
+        ]);
+        let report = verify_chain_sources(&chain, &tree, None).unwrap();
+        assert_eq!(report.verified, ["app", "lib"]);
+
+        // Compacting again has nothing left to drop
+        assert!(compact(&mut chain).unwrap().is_empty());
+
+        let mut tampered = history.clone();
+        tampered.entries.pop();
+        assert!(matches!(
+            verify_checkpoint(&tampered, &chain.entries[0]),
+            Err(VerificationError::InvalidCheckpoint(..))
+        ));
+    }
+
+    #[test]
+    fn test_checkpoint_must_match_history() {
+        let mut chain = ProvenanceChain::new();
+        chain.append(entry("cell:app@v1", &[])).unwrap();
+        append_checkpoint(&mut chain, entry("checkpoint:1", &[])).unwrap();
+        verify_chain(&chain).unwrap();
+
+        chain.entries[1].checkpoint.as_mut().unwrap().root = "forged".to_string();
+        assert!(matches!(
+            verify_chain(&chain),
+            Err(VerificationError::InvalidCheckpoint(..))
+        ));
+        assert!(matches!(
+            compact(&mut ProvenanceChain::new()),
+            Err(CompactError::NoCheckpoint)
+        ));
+    }
+
+    #[test]
+    fn test_compact_rejects_uncovered_branch() {
+        let mut ours = ProvenanceChain::new();
+        ours.append(entry("cell:app@v1", &[])).unwrap();
+        let mut theirs = ours.clone();
+        ours.append(entry("cell:app@v2", &[])).unwrap();
+        theirs.append(entry("cell:lib@v1", &[])).unwrap();
+
+        append_checkpoint(&mut ours, entry("checkpoint:1", &[])).unwrap();
+        merge_chains(&mut ours, &theirs).unwrap();
+        let before = ours.clone();
+        assert!(matches!(
+            compact(&mut ours),
+            Err(CompactError::Uncovered(id)) if id == "cell:lib@v1"
+        ));
+        assert_eq!(ours, before);
+    }
+}
@@ -0,0 +1,186 @@
+//! Test evidence: entries recording a run of the test suite.
+//!
+//! A test entry binds every cell the run tested to its semantic hash and
+//! keeps the outcome in its description, which the prompt hash covers and
+//! the excerpt repeats:
+//!
+//! ```text
+//! z1-cli/0.1.0 test passed=12 failed=0 skipped=1 xfailed=0
+//! ```
+//!
+//! Policies that only accept tested cells check a source tree with
+//! [`untested_cells`]: every cell must be bound, at its current hash, by a
+//! run without failures.
+
+use crate::types::{ProvenanceChain, ProvenanceEntry};
+use std::collections::{HashMap, HashSet};
+
+/// Prefix of the IDs of test entries.
+pub const TEST_ENTRY_PREFIX: &str = "test:";
+
+/// Outcome of a test run, as recorded in a test entry.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TestOutcome {
+    pub passed: usize,
+    /// Failures, unexpected successes included
+    pub failed: usize,
+    pub skipped: usize,
+    /// Tests expected to fail that did
+    pub xfailed: usize,
+}
+
+impl TestOutcome {
+    /// Whether the run counts as evidence: something passed and nothing
+    /// failed.
+    pub fn succeeded(&self) -> bool {
+        self.passed > 0 && self.failed == 0
+    }
+
+    /// Description of the run by `toolchain`, which [`TestOutcome::of`]
+    /// reads back.
+    pub fn describe(&self, toolchain: &str) -> String {
+        format!(
+            "{toolchain} test passed={} failed={} skipped={} xfailed={}",
+            self.passed, self.failed, self.skipped, self.xfailed
+        )
+    }
+
+    /// Outcome recorded by `entry`; `None` unless it is a test entry with a
+    /// readable description (a redacted excerpt is not).
+    pub fn of(entry: &ProvenanceEntry) -> Option<Self> {
+        if !entry.entry_id.starts_with(TEST_ENTRY_PREFIX) {
+            return None;
+        }
+        let mut words = entry.prompt_excerpt.split_whitespace().skip(1);
+        if words.next() != Some("test") {
+            return None;
+        }
+        let mut outcome = TestOutcome::default();
+        for word in words {
+            let (key, value) = word.split_once('=')?;
+            let value = value.parse().ok()?;
+            match key {
+                "passed" => outcome.passed = value,
+                "failed" => outcome.failed = value,
+                "skipped" => outcome.skipped = value,
+                "xfailed" => outcome.xfailed = value,
+                _ => return None,
+            }
+        }
+        Some(outcome)
+    }
+}
+
+/// Modules of `tree` that no successful test run recorded at their current
+/// semantic hash, sorted.
+///
+/// `tree` maps dotted module paths to semantic hashes, as for
+/// [`crate::verify_chain_sources`]. A later run that tested the same hash
+/// and failed withdraws the evidence of earlier ones.
+pub fn untested_cells(chain: &ProvenanceChain, tree: &HashMap<String, String>) -> Vec<String> {
+    let mut tested: HashSet<(&str, &str)> = HashSet::new();
+    for entry in &chain.entries {
+        let Some(outcome) = TestOutcome::of(entry) else {
+            continue;
+        };
+        for cell in &entry.cells {
+            let key = (cell.module.as_str(), cell.semhash.as_str());
+            if outcome.succeeded() {
+                tested.insert(key);
+            } else {
+                tested.remove(&key);
+            }
+        }
+    }
+
+    let mut untested: Vec<String> = tree
+        .iter()
+        .filter(|(module, semhash)| !tested.contains(&(module.as_str(), semhash.as_str())))
+        .map(|(module, _)| module.clone())
+        .collect();
+    untested.sort();
+    untested
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::ProvenanceChainExt;
+    use crate::types::CellBinding;
+    use chrono::Utc;
+
+    fn entry(id: &str, excerpt: &str, cells: &[(&str, &str)]) -> ProvenanceEntry {
+        ProvenanceEntry {
+            entry_id: id.to_string(),
+            prev: None,
+            merged: vec![],
+            actor: "toolchain:z1-cli".to_string(),
+            model: "none".to_string(),
+            prompt_sha3: "abc123".to_string(),
+            prompt_excerpt: excerpt.to_string(),
+            redaction: None,
+            tools: vec![],
+            diff_sha3: "def456".to_string(),
+            cells: cells
+                .iter()
+                .map(|(module, semhash)| CellBinding {
+                    module: module.to_string(),
+                    semhash: semhash.to_string(),
+                })
+                .collect(),
+            timestamp: Utc::now(),
+            signatures: vec![],
+            keyless: vec![],
+            rotation: None,
+            checkpoint: None,
+            timestamp_token: None,
+        }
+    }
+
+    #[test]
+    fn test_outcome_round_trips_through_the_description() {
+        let outcome = TestOutcome {
+            passed: 3,
+            failed: 1,
+            skipped: 2,
+            xfailed: 0,
+        };
+        let description = outcome.describe("z1-cli/0.1.0");
+        assert_eq!(
+            description,
+            "z1-cli/0.1.0 test passed=3 failed=1 skipped=2 xfailed=0"
+        );
+        assert_eq!(
+            TestOutcome::of(&entry("test:0", &description, &[])),
+            Some(outcome)
+        );
+        assert!(!outcome.succeeded());
+
+        assert_eq!(
+            TestOutcome::of(&entry("build:a@h", &description, &[])),
+            None
+        );
+        assert_eq!(TestOutcome::of(&entry("test:0", "[REDACTED]", &[])), None);
+    }
+
+    #[test]
+    fn test_untested_cells_need_a_passing_run_at_their_hash() {
+        let passed = "z1-cli/0.1.0 test passed=2 failed=0 skipped=0 xfailed=0";
+        let failed = "z1-cli/0.1.0 test passed=1 failed=1 skipped=0 xfailed=0";
+        let mut chain = ProvenanceChain::new();
+        for entry in [
+            entry("cell:app@v1", "write app", &[("app", "h1")]),
+            entry("test:1", passed, &[("app", "h1"), ("lib", "l1")]),
+            entry("test:2", passed, &[("util", "u1")]),
+            entry("test:3", failed, &[("util", "u1")]),
+        ] {
+            chain.append(entry).unwrap();
+        }
+
+        let tree: HashMap<String, String> = [("app", "h1"), ("lib", "l2"), ("util", "u1")]
+            .iter()
+            .map(|(module, hash)| (module.to_string(), hash.to_string()))
+            .collect();
+        assert_eq!(untested_cells(&chain, &tree), ["lib", "util"]);
+    }
+}
@@ -0,0 +1,38 @@
+//! Requests to the Sigstore and timestamp services.
+
+use std::io::Read;
+use std::time::Duration;
+
+/// How long a service may take to answer.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// POST `body` as `content_type` to `url` and return the response body.
+/// Errors carry the status and body of a failed response.
+pub(crate) fn post(url: &str, content_type: &str, body: &[u8]) -> Result<Vec<u8>, String> {
+    let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+    match agent
+        .post(url)
+        .set("Content-Type", content_type)
+        .send_bytes(body)
+    {
+        Ok(response) => {
+            let mut bytes = Vec::new();
+            response
+                .into_reader()
+                .read_to_end(&mut bytes)
+                .map_err(|err| format!("reading the response failed: {err}"))?;
+            Ok(bytes)
+        }
+        Err(ureq::Error::Status(status, response)) => {
+            let text = response.into_string().unwrap_or_default();
+            Err(format!("HTTP {status}: {}", text.trim()))
+        }
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// POST `body` as JSON to `url` and parse the JSON response.
+pub(crate) fn post_json(url: &str, body: &serde_json::Value) -> Result<serde_json::Value, String> {
+    let response = post(url, "application/json", body.to_string().as_bytes())?;
+    serde_json::from_slice(&response).map_err(|err| format!("invalid JSON response: {err}"))
+}
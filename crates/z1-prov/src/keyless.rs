@@ -0,0 +1,639 @@
+//! Keyless signing of provenance entries with certified identities.
+//!
+//! Follows the Sigstore model: the signer generates an ephemeral key, a
+//! [`CertificateAuthority`] certifies it for the signer's OIDC identity for a
+//! few minutes, and a [`TransparencyLog`] records the signature, proving it
+//! was made while the certificate was valid. Verifiers trust root and log
+//! keys plus a list of identities instead of every signer's key.
+//!
+//! Authorities and logs plug in through the traits. [`FulcioAuthority`] and
+//! [`RekorLog`](crate::RekorLog) are clients of Sigstore's Fulcio and Rekor,
+//! issuing X.509 certificates over ECDSA P-256 keys; [`LocalCertificateAuthority`]
+//! and [`LocalTransparencyLog`] are backed by local Ed25519 keys, e.g. a CI
+//! service's own CA.
+//!
+//! [`FulcioAuthority`]: crate::FulcioAuthority
+
+use crate::signature::{decode_key, entry_hash, verify_hash};
+use crate::types::{KeylessSignature, LogEntry, ProvenanceEntry, Signature, SigningCertificate};
+use crate::{sigstore, x509};
+use chrono::{DateTime, Duration, Utc};
+use der::Decode;
+use ed25519_dalek::{Signer, SigningKey};
+use p256::pkcs8::EncodePublicKey;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use thiserror::Error;
+
+/// Default validity of a leaf certificate.
+pub const CERT_VALIDITY: Duration = Duration::minutes(10);
+
+/// Errors that can occur during keyless signing.
+#[derive(Debug, Error)]
+pub enum KeylessError {
+    #[error("Certificate authority {0} failed: {1}")]
+    Authority(String, String),
+
+    #[error("Transparency log {0} failed: {1}")]
+    Log(String, String),
+}
+
+/// An identity authenticated by an OIDC issuer.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct Identity {
+    /// Subject, e.g. a CI workflow URI or an email
+    pub subject: String,
+    /// OIDC issuer URL
+    pub issuer: String,
+}
+
+/// What a verifier trusts for keyless signatures.
+///
+/// Local authorities and logs are trusted by their Ed25519 keys; Sigstore's
+/// by root certificates and Rekor public keys in PEM, as its trusted root
+/// lists them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeylessTrust {
+    /// Root certificate authority IDs and their Ed25519 public keys (hex),
+    /// or any names for X.509 root certificates (PEM)
+    pub roots: HashMap<String, String>,
+    /// Transparency log IDs and their Ed25519 public keys (hex), or Rekor
+    /// log IDs (the hex SHA-256 of the key) and their public keys (PEM)
+    pub logs: HashMap<String, String>,
+    /// Accepted signer identities; empty accepts any certified identity
+    #[serde(default)]
+    pub identities: Vec<Identity>,
+}
+
+/// Algorithm of the ephemeral keys a certificate authority certifies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAlgorithm {
+    Ed25519,
+    /// ECDSA over P-256 with SHA-256, as Sigstore uses
+    EcdsaP256,
+}
+
+/// A signing key generated for one signature and dropped afterwards.
+pub enum EphemeralKey {
+    Ed25519(SigningKey),
+    EcdsaP256(p256::ecdsa::SigningKey),
+}
+
+impl EphemeralKey {
+    pub fn generate(algorithm: KeyAlgorithm) -> Self {
+        match algorithm {
+            KeyAlgorithm::Ed25519 => Self::Ed25519(SigningKey::from_bytes(&crate::keygen().0)),
+            KeyAlgorithm::EcdsaP256 => loop {
+                let mut bytes = [0u8; 32];
+                getrandom::fill(&mut bytes).expect("failed to generate random bytes");
+                // Out of range scalars are astronomically rare; draw again
+                if let Ok(key) = p256::ecdsa::SigningKey::from_slice(&bytes) {
+                    break Self::EcdsaP256(key);
+                }
+            },
+        }
+    }
+
+    /// Public key as certificates record it: Ed25519 (hex), or a DER
+    /// SubjectPublicKeyInfo (hex) for ECDSA.
+    pub fn public_key(&self) -> String {
+        match self {
+            Self::Ed25519(key) => hex::encode(key.verifying_key().to_bytes()),
+            Self::EcdsaP256(key) => hex::encode(
+                key.verifying_key()
+                    .to_public_key_der()
+                    .expect("P-256 keys encode")
+                    .as_bytes(),
+            ),
+        }
+    }
+
+    /// Sign `message` (e.g., "ed25519:ab8...2f1", or "ecdsa-p256:3045..."
+    /// for a DER ECDSA signature over its SHA-256).
+    pub fn sign(&self, message: &[u8]) -> String {
+        match self {
+            Self::Ed25519(key) => format!("ed25519:{}", hex::encode(key.sign(message).to_bytes())),
+            Self::EcdsaP256(key) => {
+                let sig: p256::ecdsa::DerSignature = key.sign(message);
+                format!("ecdsa-p256:{}", hex::encode(sig.as_bytes()))
+            }
+        }
+    }
+}
+
+/// A service that certifies ephemeral keys for authenticated identities.
+pub trait CertificateAuthority {
+    /// Algorithm of the keys the authority certifies.
+    fn algorithm(&self) -> KeyAlgorithm {
+        KeyAlgorithm::Ed25519
+    }
+
+    /// Certificate chain for `key`, leaf first.
+    fn issue(
+        &self,
+        identity: &Identity,
+        key: &EphemeralKey,
+    ) -> Result<Vec<SigningCertificate>, KeylessError>;
+}
+
+/// An append-only log of keyless signatures.
+pub trait TransparencyLog {
+    /// Identifier of the log, e.g. its URL.
+    fn id(&self) -> &str;
+
+    /// Record `sig`, made with the key `leaf` certifies over `message`, and
+    /// return proof of inclusion.
+    fn submit(
+        &self,
+        message: &[u8],
+        sig: &str,
+        leaf: &SigningCertificate,
+    ) -> Result<LogEntry, KeylessError>;
+}
+
+/// A certificate authority backed by a local Ed25519 key.
+pub struct LocalCertificateAuthority {
+    name: String,
+    signing_key: SigningKey,
+    chain: Vec<SigningCertificate>,
+    validity: Duration,
+}
+
+impl LocalCertificateAuthority {
+    /// A root authority; verifiers trust `id` with this key's public key.
+    pub fn root(id: impl Into<String>, private_key: &[u8; 32]) -> Self {
+        Self {
+            name: id.into(),
+            signing_key: SigningKey::from_bytes(private_key),
+            chain: Vec::new(),
+            validity: CERT_VALIDITY,
+        }
+    }
+
+    /// An intermediate authority holding `cert`, issued by another authority.
+    pub fn intermediate(cert: SigningCertificate, private_key: &[u8; 32]) -> Self {
+        Self {
+            name: cert.subject.clone(),
+            signing_key: SigningKey::from_bytes(private_key),
+            chain: vec![cert],
+            validity: CERT_VALIDITY,
+        }
+    }
+
+    /// Issue leaf certificates valid for `validity` instead of [`CERT_VALIDITY`].
+    pub fn with_validity(mut self, validity: Duration) -> Self {
+        self.validity = validity;
+        self
+    }
+
+    /// Certify an intermediate authority named `name`, valid for `validity`.
+    pub fn certify_authority(
+        &self,
+        name: &str,
+        public_key: &[u8; 32],
+        validity: Duration,
+    ) -> SigningCertificate {
+        let identity = Identity {
+            subject: name.to_string(),
+            issuer: self.name.clone(),
+        };
+        self.certify(&identity, public_key, Utc::now(), validity)
+    }
+
+    fn certify(
+        &self,
+        identity: &Identity,
+        public_key: &[u8; 32],
+        not_before: DateTime<Utc>,
+        validity: Duration,
+    ) -> SigningCertificate {
+        let mut cert = SigningCertificate {
+            subject: identity.subject.clone(),
+            issuer: identity.issuer.clone(),
+            public_key: hex::encode(public_key),
+            not_before,
+            not_after: not_before + validity,
+            ca: self.name.clone(),
+            sig: String::new(),
+            x509: None,
+        };
+        let sig = self.signing_key.sign(&certificate_hash(&cert));
+        cert.sig = format!("ed25519:{}", hex::encode(sig.to_bytes()));
+        cert
+    }
+}
+
+impl CertificateAuthority for LocalCertificateAuthority {
+    fn issue(
+        &self,
+        identity: &Identity,
+        key: &EphemeralKey,
+    ) -> Result<Vec<SigningCertificate>, KeylessError> {
+        let EphemeralKey::Ed25519(key) = key else {
+            return Err(KeylessError::Authority(
+                self.name.clone(),
+                "only Ed25519 keys are certified".into(),
+            ));
+        };
+        let public_key = key.verifying_key().to_bytes();
+        let leaf = self.certify(identity, &public_key, Utc::now(), self.validity);
+        Ok(std::iter::once(leaf)
+            .chain(self.chain.iter().cloned())
+            .collect())
+    }
+}
+
+/// A transparency log backed by a local Ed25519 key.
+pub struct LocalTransparencyLog {
+    id: String,
+    signing_key: SigningKey,
+    next_index: AtomicU64,
+}
+
+impl LocalTransparencyLog {
+    pub fn new(id: impl Into<String>, private_key: &[u8; 32]) -> Self {
+        Self {
+            id: id.into(),
+            signing_key: SigningKey::from_bytes(private_key),
+            next_index: AtomicU64::new(0),
+        }
+    }
+
+    /// Continue numbering entries at `index`.
+    pub fn with_next_index(self, index: u64) -> Self {
+        self.next_index.store(index, Ordering::SeqCst);
+        self
+    }
+
+    /// Record `body` as if integrated at `time`.
+    pub fn submit_at(&self, body: &str, time: DateTime<Utc>) -> LogEntry {
+        let index = self.next_index.fetch_add(1, Ordering::SeqCst);
+        let sig = self
+            .signing_key
+            .sign(&log_entry_hash(&self.id, index, time, body));
+        LogEntry {
+            log_id: self.id.clone(),
+            index,
+            integrated_time: time,
+            set: format!("ed25519:{}", hex::encode(sig.to_bytes())),
+            body: None,
+            inclusion_proof: None,
+        }
+    }
+}
+
+impl TransparencyLog for LocalTransparencyLog {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn submit(
+        &self,
+        message: &[u8],
+        sig: &str,
+        leaf: &SigningCertificate,
+    ) -> Result<LogEntry, KeylessError> {
+        Ok(self.submit_at(&log_body(message, sig, leaf), Utc::now()))
+    }
+}
+
+/// Sign `entry` as `identity` with a fresh key certified by `ca`, and record
+/// the signature in `log`. The private key is discarded afterwards.
+pub fn sign_keyless(
+    entry: &ProvenanceEntry,
+    identity: &Identity,
+    ca: &dyn CertificateAuthority,
+    log: &dyn TransparencyLog,
+) -> Result<KeylessSignature, KeylessError> {
+    let key = EphemeralKey::generate(ca.algorithm());
+    let chain = ca.issue(identity, &key)?;
+    let leaf = chain
+        .first()
+        .ok_or_else(|| KeylessError::Authority(identity.issuer.clone(), "empty chain".into()))?;
+    if leaf.public_key != key.public_key() {
+        return Err(KeylessError::Authority(
+            leaf.ca.clone(),
+            "certificate does not match the key".into(),
+        ));
+    }
+
+    let hash = entry_hash(entry);
+    let sig = key.sign(&hash);
+    let log_entry = log.submit(&hash, &sig, leaf)?;
+    Ok(KeylessSignature {
+        chain,
+        sig,
+        log_entry,
+    })
+}
+
+/// Verify a keyless signature on `entry` and return the signer's identity.
+///
+/// Checks the leaf signature over the entry, the log's signed entry
+/// timestamp (and for Rekor, that the entry records this signature and its
+/// inclusion proof against the checkpoint the log signed), that every
+/// certificate was valid when the log integrated the signature, that the
+/// chain leads to a trusted root, and that the identity is accepted. Errors
+/// describe the first failed check.
+pub fn verify_keyless_signature(
+    entry: &ProvenanceEntry,
+    signature: &KeylessSignature,
+    trust: &KeylessTrust,
+) -> Result<Identity, String> {
+    let leaf = signature
+        .chain
+        .first()
+        .ok_or("certificate chain is empty")?;
+    let hash = entry_hash(entry);
+    verify_leaf_signature(&hash, &signature.sig, leaf)?;
+
+    let log_entry = &signature.log_entry;
+    if log_entry.body.is_some() {
+        sigstore::verify_rekor_entry(&hash, &signature.sig, leaf, log_entry, trust)?;
+    } else {
+        verify_local_entry(&hash, signature, trust)?;
+    }
+
+    let time = log_entry.integrated_time;
+    let x509 = signature.chain.iter().filter(|cert| cert.x509.is_some());
+    match x509.count() {
+        0 => verify_local_chain(&signature.chain, trust, time)?,
+        n if n == signature.chain.len() => sigstore::verify_chain(&signature.chain, trust, time)?,
+        _ => return Err("certificate chain mixes X.509 and other certificates".into()),
+    }
+
+    let identity = Identity {
+        subject: leaf.subject.clone(),
+        issuer: leaf.issuer.clone(),
+    };
+    if !trust.identities.is_empty() && !trust.identities.contains(&identity) {
+        return Err(format!(
+            "identity {} from {} is not accepted",
+            identity.subject, identity.issuer
+        ));
+    }
+    Ok(identity)
+}
+
+/// Check `sig` over `hash` by the key `leaf` certifies.
+fn verify_leaf_signature(
+    hash: &[u8; 32],
+    sig: &str,
+    leaf: &SigningCertificate,
+) -> Result<(), String> {
+    let verified = match sig.strip_prefix("ecdsa-p256:") {
+        Some(der) => {
+            let key = hex::decode(&leaf.public_key)
+                .ok()
+                .and_then(|der| x509_cert::spki::SubjectPublicKeyInfoOwned::from_der(&der).ok())
+                .ok_or("leaf certificate has a bad key")?;
+            let der = hex::decode(der).map_err(|_| "signature is not hex")?;
+            x509::verify_signature(&key, &x509::ECDSA_WITH_SHA_256, hash, &der).is_ok()
+        }
+        None => {
+            let key = decode_key(&leaf.public_key).ok_or("leaf certificate has a bad key")?;
+            let sig = Signature {
+                by: leaf.subject.clone(),
+                sig: sig.to_string(),
+            };
+            verify_hash(hash, &sig, &key)
+        }
+    };
+    if verified {
+        Ok(())
+    } else {
+        Err("signature does not verify with the certified key".into())
+    }
+}
+
+/// Check the signed entry timestamp of a local log.
+fn verify_local_entry(
+    hash: &[u8; 32],
+    signature: &KeylessSignature,
+    trust: &KeylessTrust,
+) -> Result<(), String> {
+    let leaf = &signature.chain[0];
+    let log_entry = &signature.log_entry;
+    let log_key = trust
+        .logs
+        .get(&log_entry.log_id)
+        .and_then(|key| decode_key(key))
+        .ok_or_else(|| format!("transparency log {} is not trusted", log_entry.log_id))?;
+    let set = Signature {
+        by: log_entry.log_id.clone(),
+        sig: log_entry.set.clone(),
+    };
+    let logged = log_entry_hash(
+        &log_entry.log_id,
+        log_entry.index,
+        log_entry.integrated_time,
+        &log_body(hash, &signature.sig, leaf),
+    );
+    if !verify_hash(&logged, &set, &log_key) {
+        return Err(format!(
+            "log entry {} of {} does not verify",
+            log_entry.index, log_entry.log_id
+        ));
+    }
+    Ok(())
+}
+
+/// Check a chain of local certificates at `time`.
+fn verify_local_chain(
+    chain: &[SigningCertificate],
+    trust: &KeylessTrust,
+    time: DateTime<Utc>,
+) -> Result<(), String> {
+    for (position, cert) in chain.iter().enumerate() {
+        if time < cert.not_before || time > cert.not_after {
+            return Err(format!(
+                "certificate for {} was not valid when logged",
+                cert.subject
+            ));
+        }
+        let issuer_key = match chain.get(position + 1) {
+            Some(parent) if parent.subject == cert.ca => decode_key(&parent.public_key),
+            Some(_) => return Err(format!("certificate for {} breaks the chain", cert.subject)),
+            None => trust.roots.get(&cert.ca).and_then(|key| decode_key(key)),
+        }
+        .ok_or_else(|| format!("certificate authority {} is not trusted", cert.ca))?;
+        let cert_sig = Signature {
+            by: cert.ca.clone(),
+            sig: cert.sig.clone(),
+        };
+        if !verify_hash(&certificate_hash(cert), &cert_sig, &issuer_key) {
+            return Err(format!("certificate for {} does not verify", cert.subject));
+        }
+    }
+    Ok(())
+}
+
+/// Hash of the statement a certificate signs.
+fn certificate_hash(cert: &SigningCertificate) -> [u8; 32] {
+    let not_before = cert
+        .not_before
+        .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true);
+    let not_after = cert
+        .not_after
+        .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true);
+    digest(&[
+        "z1-certificate",
+        &cert.subject,
+        &cert.issuer,
+        &cert.public_key,
+        &not_before,
+        &not_after,
+        &cert.ca,
+    ])
+}
+
+/// Digest of a signature as submitted to a transparency log.
+fn log_body(entry_hash: &[u8], sig: &str, leaf: &SigningCertificate) -> String {
+    let hash = hex::encode(entry_hash);
+    let leaf = hex::encode(certificate_hash(leaf));
+    format!(
+        "sha3-256:{}",
+        hex::encode(digest(&["z1-keyless", &hash, sig, &leaf]))
+    )
+}
+
+/// Hash of the statement a log's signed entry timestamp signs.
+fn log_entry_hash(log_id: &str, index: u64, time: DateTime<Utc>, body: &str) -> [u8; 32] {
+    let time = time.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true);
+    digest(&["z1-log-entry", log_id, &index.to_string(), &time, body])
+}
+
+fn digest(parts: &[&str]) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signature::keygen;
+
+    fn make_test_entry() -> ProvenanceEntry {
+        ProvenanceEntry {
+            entry_id: "cell:test@v1".to_string(),
+            prev: None,
+            merged: vec![],
+            actor: "ci:test".to_string(),
+            model: "none".to_string(),
+            prompt_sha3: "abc123".to_string(),
+            prompt_excerpt: "test prompt".to_string(),
+            redaction: None,
+            tools: vec![],
+            diff_sha3: "def456".to_string(),
+            cells: vec![],
+            timestamp: Utc::now(),
+            signatures: vec![],
+            keyless: vec![],
+            rotation: None,
+            checkpoint: None,
+            timestamp_token: None,
+        }
+    }
+
+    fn ci_identity() -> Identity {
+        Identity {
+            subject: "https://ci.example/org/repo/build.yml@refs/heads/main".to_string(),
+            issuer: "https://token.ci.example".to_string(),
+        }
+    }
+
+    struct Setup {
+        ca: LocalCertificateAuthority,
+        log: LocalTransparencyLog,
+        trust: KeylessTrust,
+    }
+
+    fn setup() -> Setup {
+        let (root_private, root_public) = keygen();
+        let (log_private, log_public) = keygen();
+        Setup {
+            ca: LocalCertificateAuthority::root("ca:root", &root_private),
+            log: LocalTransparencyLog::new("log:test", &log_private),
+            trust: KeylessTrust {
+                roots: [("ca:root".to_string(), hex::encode(root_public))].into(),
+                logs: [("log:test".to_string(), hex::encode(log_public))].into(),
+                identities: vec![ci_identity()],
+            },
+        }
+    }
+
+    #[test]
+    fn test_sign_and_verify_keyless() {
+        let Setup { ca, log, trust } = setup();
+        let entry = make_test_entry();
+
+        let signature = sign_keyless(&entry, &ci_identity(), &ca, &log).unwrap();
+        assert_eq!(signature.chain.len(), 1);
+        assert_eq!(
+            verify_keyless_signature(&entry, &signature, &trust).unwrap(),
+            ci_identity()
+        );
+
+        // Another identity is certified but not accepted
+        let other = Identity {
+            subject: "mallory@example.com".to_string(),
+            ..ci_identity()
+        };
+        let signature = sign_keyless(&entry, &other, &ca, &log).unwrap();
+        assert!(verify_keyless_signature(&entry, &signature, &trust)
+            .unwrap_err()
+            .contains("not accepted"));
+    }
+
+    #[test]
+    fn test_keyless_through_intermediate() {
+        let Setup { ca, log, trust } = setup();
+        let (intermediate_private, intermediate_public) = keygen();
+        let cert =
+            ca.certify_authority("ca:intermediate", &intermediate_public, Duration::days(30));
+        let intermediate = LocalCertificateAuthority::intermediate(cert, &intermediate_private);
+        let entry = make_test_entry();
+
+        let signature = sign_keyless(&entry, &ci_identity(), &intermediate, &log).unwrap();
+        assert_eq!(signature.chain.len(), 2);
+        assert!(verify_keyless_signature(&entry, &signature, &trust).is_ok());
+
+        // An intermediate the root never certified is rejected
+        let (rogue_private, rogue_public) = keygen();
+        let rogue = LocalCertificateAuthority::root("ca:rogue", &rogue_private);
+        let cert = rogue.certify_authority("ca:intermediate", &rogue_public, Duration::days(30));
+        let mut forged = signature.clone();
+        forged.chain[1] = cert;
+        assert!(verify_keyless_signature(&entry, &forged, &trust).is_err());
+    }
+
+    #[test]
+    fn test_keyless_rejects_tampering_and_expired_certificates() {
+        let Setup { ca, log, trust } = setup();
+        let mut entry = make_test_entry();
+        let signature = sign_keyless(&entry, &ci_identity(), &ca, &log).unwrap();
+
+        // Moving the log time outside the certificate breaks the log signature
+        let mut late = signature.clone();
+        late.log_entry.integrated_time += Duration::hours(1);
+        assert!(verify_keyless_signature(&entry, &late, &trust).is_err());
+
+        // A correctly logged signature made after the certificate expired
+        let leaf = &signature.chain[0];
+        let body = log_body(&entry_hash(&entry), &signature.sig, leaf);
+        let mut expired = signature.clone();
+        expired.log_entry = log.submit_at(&body, leaf.not_after + Duration::minutes(1));
+        assert!(verify_keyless_signature(&entry, &expired, &trust)
+            .unwrap_err()
+            .contains("not valid when logged"));
+
+        entry.diff_sha3 = "changed".to_string();
+        assert!(verify_keyless_signature(&entry, &signature, &trust).is_err());
+    }
+}
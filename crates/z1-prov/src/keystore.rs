@@ -0,0 +1,357 @@
+//! Passphrase-encrypted keystore for named signing keys.
+//!
+//! Each key is stored as `<name>.json` in the keystore directory. The private
+//! key is encrypted with ChaCha20-Poly1305 under a key derived from the
+//! passphrase with Argon2id; the public key stays in the clear so keys can be
+//! listed and published without unlocking them.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use chrono::{DateTime, Utc};
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Version of the key file format.
+pub const KEYSTORE_VERSION: u32 = 1;
+
+/// Errors that can occur during keystore operations.
+#[derive(Debug, Error)]
+pub enum KeystoreError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("Invalid key name `{0}`: use letters, digits, '-', '_' and '.'")]
+    InvalidName(String),
+
+    #[error("Key `{0}` already exists")]
+    AlreadyExists(String),
+
+    #[error("Key `{0}` not found")]
+    NotFound(String),
+
+    #[error("Wrong passphrase for key `{0}`")]
+    WrongPassphrase(String),
+
+    #[error("Key file for `{0}` is corrupt: {1}")]
+    Corrupt(String, String),
+}
+
+/// Argon2id cost parameters used to derive the encryption key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    /// Memory cost in KiB
+    pub m_cost: u32,
+    /// Number of iterations
+    pub t_cost: u32,
+    /// Degree of parallelism
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    /// The OWASP-recommended Argon2id baseline (19 MiB, 2 iterations).
+    fn default() -> Self {
+        Self {
+            m_cost: 19 * 1024,
+            t_cost: 2,
+            p_cost: 1,
+        }
+    }
+}
+
+/// Public metadata of a stored key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredKey {
+    pub name: String,
+    pub public_key: [u8; 32],
+    pub created: DateTime<Utc>,
+}
+
+/// On-disk representation of one key.
+#[derive(Debug, Serialize, Deserialize)]
+struct KeyFile {
+    version: u32,
+    name: String,
+    public_key: String,
+    created: DateTime<Utc>,
+    kdf: KdfParams,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// A directory of passphrase-encrypted Ed25519 keys.
+#[derive(Debug, Clone)]
+pub struct Keystore {
+    dir: PathBuf,
+    kdf: KdfParams,
+}
+
+impl Keystore {
+    /// Keystore rooted at `dir`. The directory is created on first write.
+    pub fn open(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            kdf: KdfParams::default(),
+        }
+    }
+
+    /// Encrypt new keys with `kdf` instead of the default cost.
+    pub fn with_kdf(mut self, kdf: KdfParams) -> Self {
+        self.kdf = kdf;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Generate a new keypair and store it as `name`.
+    pub fn generate(&self, name: &str, passphrase: &str) -> Result<StoredKey, KeystoreError> {
+        let (private_key, _) = crate::keygen();
+        self.import(name, &private_key, passphrase)
+    }
+
+    /// Store an existing private key as `name`.
+    pub fn import(
+        &self,
+        name: &str,
+        private_key: &[u8; 32],
+        passphrase: &str,
+    ) -> Result<StoredKey, KeystoreError> {
+        let path = self.key_path(name)?;
+        if path.exists() {
+            return Err(KeystoreError::AlreadyExists(name.to_string()));
+        }
+
+        let mut salt = [0u8; 16];
+        let mut nonce = [0u8; 12];
+        getrandom::fill(&mut salt).expect("failed to generate random bytes");
+        getrandom::fill(&mut nonce).expect("failed to generate random bytes");
+        let cipher = cipher(name, passphrase, &salt, self.kdf)?;
+        let ciphertext = cipher
+            .encrypt(&Nonce::from(nonce), private_key.as_slice())
+            .map_err(|_| KeystoreError::Corrupt(name.to_string(), "encryption failed".into()))?;
+
+        let key = StoredKey {
+            name: name.to_string(),
+            public_key: SigningKey::from_bytes(private_key)
+                .verifying_key()
+                .to_bytes(),
+            created: Utc::now(),
+        };
+        let file = KeyFile {
+            version: KEYSTORE_VERSION,
+            name: key.name.clone(),
+            public_key: hex::encode(key.public_key),
+            created: key.created,
+            kdf: self.kdf,
+            salt: hex::encode(salt),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        };
+
+        fs::create_dir_all(&self.dir)?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&file)?)?;
+        restrict_permissions(&tmp)?;
+        fs::rename(&tmp, &path)?;
+        Ok(key)
+    }
+
+    /// Decrypt the private key stored as `name`.
+    pub fn unlock(&self, name: &str, passphrase: &str) -> Result<[u8; 32], KeystoreError> {
+        let file = self.read(name)?;
+        let corrupt = |what: &str| KeystoreError::Corrupt(name.to_string(), what.to_string());
+
+        let salt = hex::decode(&file.salt).map_err(|_| corrupt("invalid salt"))?;
+        let nonce: [u8; 12] = hex::decode(&file.nonce)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| corrupt("invalid nonce"))?;
+        let ciphertext =
+            hex::decode(&file.ciphertext).map_err(|_| corrupt("invalid ciphertext"))?;
+
+        let plaintext = cipher(name, passphrase, &salt, file.kdf)?
+            .decrypt(&Nonce::from(nonce), ciphertext.as_slice())
+            .map_err(|_| KeystoreError::WrongPassphrase(name.to_string()))?;
+        let private_key: [u8; 32] = plaintext
+            .try_into()
+            .map_err(|_| corrupt("private key must be 32 bytes"))?;
+
+        let public_key = SigningKey::from_bytes(&private_key)
+            .verifying_key()
+            .to_bytes();
+        if hex::encode(public_key) != file.public_key {
+            return Err(corrupt("public key does not match private key"));
+        }
+        Ok(private_key)
+    }
+
+    /// Public metadata of the key stored as `name`.
+    pub fn get(&self, name: &str) -> Result<StoredKey, KeystoreError> {
+        let file = self.read(name)?;
+        let public_key = hex::decode(&file.public_key)
+            .ok()
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+            .ok_or_else(|| KeystoreError::Corrupt(name.to_string(), "invalid public key".into()))?;
+        Ok(StoredKey {
+            name: file.name,
+            public_key,
+            created: file.created,
+        })
+    }
+
+    /// All stored keys, sorted by name.
+    pub fn list(&self) -> Result<Vec<StoredKey>, KeystoreError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut keys = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    keys.push(self.get(name)?);
+                }
+            }
+        }
+        keys.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(keys)
+    }
+
+    fn read(&self, name: &str) -> Result<KeyFile, KeystoreError> {
+        let path = self.key_path(name)?;
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Err(KeystoreError::NotFound(name.to_string()))
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let file: KeyFile = serde_json::from_str(&contents)?;
+        if file.version != KEYSTORE_VERSION {
+            return Err(KeystoreError::Corrupt(
+                name.to_string(),
+                format!("unsupported version {}", file.version),
+            ));
+        }
+        Ok(file)
+    }
+
+    fn key_path(&self, name: &str) -> Result<PathBuf, KeystoreError> {
+        let valid = !name.is_empty()
+            && !name.starts_with('.')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid {
+            return Err(KeystoreError::InvalidName(name.to_string()));
+        }
+        Ok(self.dir.join(format!("{name}.json")))
+    }
+}
+
+/// Cipher keyed by Argon2id over the passphrase.
+fn cipher(
+    name: &str,
+    passphrase: &str,
+    salt: &[u8],
+    kdf: KdfParams,
+) -> Result<ChaCha20Poly1305, KeystoreError> {
+    let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(32))
+        .map_err(|err| KeystoreError::Corrupt(name.to_string(), err.to_string()))?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|err| KeystoreError::Corrupt(name.to_string(), err.to_string()))?;
+    Ok(ChaCha20Poly1305::new(&key.into()))
+}
+
+#[cfg(unix)]
+fn restrict_permissions(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cheap parameters so tests do not pay the production KDF cost.
+    const TEST_KDF: KdfParams = KdfParams {
+        m_cost: 64,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    fn test_store() -> (tempfile::TempDir, Keystore) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Keystore::open(dir.path().join("keys")).with_kdf(TEST_KDF);
+        (dir, store)
+    }
+
+    #[test]
+    fn test_generate_and_unlock() {
+        let (_dir, store) = test_store();
+        let key = store.generate("alice", "correct horse").unwrap();
+
+        let private_key = store.unlock("alice", "correct horse").unwrap();
+        let public_key = SigningKey::from_bytes(&private_key)
+            .verifying_key()
+            .to_bytes();
+        assert_eq!(public_key, key.public_key);
+        assert_eq!(store.get("alice").unwrap(), key);
+
+        let contents = fs::read_to_string(store.dir().join("alice.json")).unwrap();
+        assert!(!contents.contains(&hex::encode(private_key)));
+    }
+
+    #[test]
+    fn test_wrong_passphrase_is_rejected() {
+        let (_dir, store) = test_store();
+        store.generate("alice", "correct horse").unwrap();
+
+        assert!(matches!(
+            store.unlock("alice", "battery staple"),
+            Err(KeystoreError::WrongPassphrase(_))
+        ));
+    }
+
+    #[test]
+    fn test_names_are_unique_and_validated() {
+        let (_dir, store) = test_store();
+        store.generate("bob", "pw").unwrap();
+        store.generate("alice", "pw").unwrap();
+
+        assert!(matches!(
+            store.generate("bob", "pw"),
+            Err(KeystoreError::AlreadyExists(_))
+        ));
+        assert!(matches!(
+            store.generate("../escape", "pw"),
+            Err(KeystoreError::InvalidName(_))
+        ));
+        assert!(matches!(
+            store.unlock("carol", "pw"),
+            Err(KeystoreError::NotFound(_))
+        ));
+
+        let names: Vec<_> = store.list().unwrap().into_iter().map(|k| k.name).collect();
+        assert_eq!(names, ["alice", "bob"]);
+    }
+}
@@ -14,14 +14,21 @@
 //! let entry = ProvenanceEntry {
 //!     entry_id: "cell:example@v1".to_string(),
 //!     prev: None,
+//!     merged: vec![],
 //!     actor: "agent:test/1.0".to_string(),
 //!     model: "llm-test-2025".to_string(),
 //!     prompt_sha3: "abc123".to_string(),
 //!     prompt_excerpt: "Create example cell".to_string(),
+//!     redaction: None,
 //!     tools: vec!["z1-fmt".to_string()],
 //!     diff_sha3: "def456".to_string(),
+//!     cells: vec![],
 //!     timestamp: Utc::now(),
 //!     signatures: vec![],
+//!     keyless: vec![],
+//!     rotation: None,
+//!     checkpoint: None,
+//!     timestamp_token: None,
 //! };
 //!
 //! let hash = chain.append(entry).unwrap();
@@ -29,11 +36,58 @@
 //! ```
 
 mod chain;
+mod checkpoint;
+mod evidence;
+mod http;
+mod keyless;
+mod keystore;
+mod merge;
+mod query;
+mod redact;
+mod report;
+mod rfc3161;
 mod signature;
+mod sigstore;
+mod source;
+#[cfg(test)]
+mod testing;
+mod timestamp;
 mod types;
 mod verify;
+mod x509;
 
 pub use chain::{compute_entry_hash, ChainError, ProvenanceChainExt};
-pub use signature::{keygen, sign_entry, verify_signature};
-pub use types::{ProvenanceChain, ProvenanceEntry, Signature};
-pub use verify::{verify_chain, verify_chain_signatures, VerificationError};
+pub use checkpoint::{append_checkpoint, compact, verify_checkpoint, CompactError};
+pub use evidence::{untested_cells, TestOutcome, TEST_ENTRY_PREFIX};
+pub use keyless::{
+    sign_keyless, verify_keyless_signature, CertificateAuthority, EphemeralKey, Identity,
+    KeyAlgorithm, KeylessError, KeylessTrust, LocalCertificateAuthority, LocalTransparencyLog,
+    TransparencyLog, CERT_VALIDITY,
+};
+pub use keystore::{KdfParams, Keystore, KeystoreError, StoredKey, KEYSTORE_VERSION};
+pub use merge::{merge_chains, MergeError, MergeOutcome};
+pub use query::{
+    entries_by_actor, entries_by_hash, entries_by_model, entries_for_cell, entries_in_range,
+    ProvenanceQuery,
+};
+pub use redact::{RedactionError, RedactionPolicy, DEFAULT_EXCERPT_LEN, REDACTED, SECRET_PATTERNS};
+pub use report::{Check, CheckStatus, EntryReport, ReportOptions, VerificationReport};
+pub use rfc3161::{verify_rfc3161_token, Rfc3161Authority};
+pub use signature::{
+    endorse_rotation, keygen, sign_entry, sign_entry_with, verify_rotation, verify_signature,
+    CommandSigner, EntrySigner, SignerError, SoftwareSigner,
+};
+pub use sigstore::{FulcioAuthority, RekorLog, FULCIO_URL, REKOR_URL};
+pub use source::{verify_chain_sources, SourceReport};
+pub use timestamp::{
+    entry_imprint, timestamp_entry, verify_timestamp_token, AuthorityKey, LocalAuthority,
+    TimestampAuthority, TimestampError, CLOCK_SKEW, MAX_STAMP_DELAY,
+};
+pub use types::{
+    CellBinding, Checkpoint, InclusionProof, KeyRotation, KeylessSignature, LogEntry,
+    ProvenanceChain, ProvenanceEntry, Signature, SigningCertificate, TimestampToken,
+};
+pub use verify::{
+    verify_chain, verify_chain_keyless, verify_chain_signatures, verify_chain_timestamps,
+    VerificationError,
+};
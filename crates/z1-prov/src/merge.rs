@@ -0,0 +1,240 @@
+//! Merging provenance chains that diverged from a common root.
+//!
+//! Agents working in parallel copy a chain and append to their copies. A
+//! merge brings the other copy's new entries into this one, leaving two
+//! heads; the next [`append`] links to both and so records the merge.
+//!
+//! [`append`]: crate::ProvenanceChainExt::append
+
+use crate::chain::{compute_entry_hash, merkle_root};
+use crate::types::{ProvenanceChain, ProvenanceEntry};
+use crate::verify::{verify_chain, VerificationError};
+use std::collections::HashSet;
+use thiserror::Error;
+
+/// Errors that can occur while merging chains.
+#[derive(Debug, Error)]
+pub enum MergeError {
+    #[error("Chains do not share a root entry")]
+    Unrelated,
+
+    #[error("Chain is invalid: {0}")]
+    Invalid(#[from] VerificationError),
+
+    #[error("Entry {0} was recorded differently on both branches")]
+    ConflictingEntry(String),
+
+    #[error("Signer {0} rotated keys on both branches")]
+    ConflictingRotation(String),
+}
+
+/// What a merge changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeOutcome {
+    /// The other chain had no entries missing from this one.
+    UpToDate,
+    /// Only the other chain had new entries; this many were appended.
+    FastForward(usize),
+    /// Both chains had new entries; this many were appended and the chain
+    /// now has several heads until the next append joins them.
+    Diverged(usize),
+}
+
+/// Bring the entries of `theirs` that `ours` lacks into `ours`.
+///
+/// Conflict rules:
+/// - both chains must start from the same root entry,
+/// - an entry ID may not be recorded on both branches with different content,
+/// - a signer may not rotate keys on both branches.
+///
+/// `ours` is left unchanged when the merge fails.
+pub fn merge_chains(
+    ours: &mut ProvenanceChain,
+    theirs: &ProvenanceChain,
+) -> Result<MergeOutcome, MergeError> {
+    verify_chain(ours)?;
+    verify_chain(theirs)?;
+
+    let (Some(our_root), Some(their_root)) = (ours.entries.first(), theirs.entries.first()) else {
+        if ours.is_empty() && !theirs.is_empty() {
+            ours.entries = theirs.entries.clone();
+            ours.merkle_root = merkle_root(&ours.entries);
+            return Ok(MergeOutcome::FastForward(theirs.len()));
+        }
+        return Ok(MergeOutcome::UpToDate);
+    };
+    if compute_entry_hash(our_root) != compute_entry_hash(their_root) {
+        return Err(MergeError::Unrelated);
+    }
+
+    let our_hashes: HashSet<String> = ours.entries.iter().map(compute_entry_hash).collect();
+    let their_hashes: HashSet<String> = theirs.entries.iter().map(compute_entry_hash).collect();
+    let their_new: Vec<&ProvenanceEntry> = theirs
+        .entries
+        .iter()
+        .filter(|entry| !our_hashes.contains(&compute_entry_hash(entry)))
+        .collect();
+    if their_new.is_empty() {
+        return Ok(MergeOutcome::UpToDate);
+    }
+    let our_new: Vec<&ProvenanceEntry> = ours
+        .entries
+        .iter()
+        .filter(|entry| !their_hashes.contains(&compute_entry_hash(entry)))
+        .collect();
+
+    for theirs in &their_new {
+        if our_new.iter().any(|ours| ours.entry_id == theirs.entry_id) {
+            return Err(MergeError::ConflictingEntry(theirs.entry_id.clone()));
+        }
+    }
+    let our_rotations: HashSet<&str> = our_new
+        .iter()
+        .filter_map(|entry| entry.rotation.as_ref())
+        .map(|rotation| rotation.signer.as_str())
+        .collect();
+    for rotation in their_new.iter().filter_map(|entry| entry.rotation.as_ref()) {
+        if our_rotations.contains(rotation.signer.as_str()) {
+            return Err(MergeError::ConflictingRotation(rotation.signer.clone()));
+        }
+    }
+
+    // Their entries are already in parent-first order and only link to
+    // shared entries or to each other
+    let appended = their_new.len();
+    let fast_forward = our_new.is_empty();
+    ours.entries.extend(their_new.into_iter().cloned());
+    ours.merkle_root = merkle_root(&ours.entries);
+
+    Ok(if fast_forward {
+        MergeOutcome::FastForward(appended)
+    } else {
+        MergeOutcome::Diverged(appended)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::ProvenanceChainExt;
+    use crate::signature::{endorse_rotation, keygen};
+    use chrono::Utc;
+
+    fn entry(id: &str) -> ProvenanceEntry {
+        ProvenanceEntry {
+            entry_id: id.to_string(),
+            prev: None,
+            merged: vec![],
+            actor: "agent:test".to_string(),
+            model: "test-model".to_string(),
+            prompt_sha3: "abc123".to_string(),
+            prompt_excerpt: "test prompt".to_string(),
+            redaction: None,
+            tools: vec![],
+            diff_sha3: "def456".to_string(),
+            cells: vec![],
+            timestamp: Utc::now(),
+            signatures: vec![],
+            keyless: vec![],
+            rotation: None,
+            checkpoint: None,
+            timestamp_token: None,
+        }
+    }
+
+    /// A shared root with one entry appended on each side.
+    fn forked() -> (ProvenanceChain, ProvenanceChain) {
+        let mut base = ProvenanceChain::new();
+        base.append(entry("cell:app@v1")).unwrap();
+        let mut ours = base.clone();
+        let mut theirs = base;
+        ours.append(entry("cell:app@v2-ours")).unwrap();
+        theirs.append(entry("cell:app@v2-theirs")).unwrap();
+        (ours, theirs)
+    }
+
+    #[test]
+    fn test_merge_diverged_chains_and_join_heads() {
+        let (mut ours, theirs) = forked();
+        let (original, mut reverse) = (ours.clone(), theirs.clone());
+
+        assert_eq!(
+            merge_chains(&mut ours, &theirs).unwrap(),
+            MergeOutcome::Diverged(1)
+        );
+        assert_eq!(ours.heads().len(), 2);
+        verify_chain(&ours).unwrap();
+
+        // Both replicas hold the same entries, so they agree on the root
+        merge_chains(&mut reverse, &original).unwrap();
+        assert_eq!(ours.merkle_root, reverse.merkle_root);
+
+        let heads = ours.heads();
+        ours.append(entry("merge:app")).unwrap();
+        let merge = ours.latest().unwrap();
+        assert_eq!(merge.prev.as_ref(), Some(&heads[0]));
+        assert_eq!(merge.merged, heads[1..]);
+        assert_eq!(ours.heads().len(), 1);
+        verify_chain(&ours).unwrap();
+
+        assert_eq!(
+            merge_chains(&mut ours, &theirs).unwrap(),
+            MergeOutcome::UpToDate
+        );
+    }
+
+    #[test]
+    fn test_merge_fast_forwards() {
+        let (ours, _) = forked();
+        let mut behind = ours.clone();
+        behind.entries.truncate(1);
+
+        assert_eq!(
+            merge_chains(&mut behind, &ours).unwrap(),
+            MergeOutcome::FastForward(1)
+        );
+        assert_eq!(behind.entries, ours.entries);
+        assert_eq!(behind.heads().len(), 1);
+    }
+
+    #[test]
+    fn test_merge_conflicts() {
+        let mut unrelated = ProvenanceChain::new();
+        unrelated.append(entry("cell:other@v1")).unwrap();
+        let (mut ours, _) = forked();
+        assert!(matches!(
+            merge_chains(&mut ours, &unrelated),
+            Err(MergeError::Unrelated)
+        ));
+
+        // The same entry ID recorded differently on both branches
+        let mut base = ProvenanceChain::new();
+        base.append(entry("cell:app@v1")).unwrap();
+        let mut ours = base.clone();
+        let mut theirs = base.clone();
+        ours.append(entry("cell:app@v2")).unwrap();
+        let mut other = entry("cell:app@v2");
+        other.actor = "agent:other".to_string();
+        theirs.append(other).unwrap();
+        let before = ours.clone();
+        assert!(matches!(
+            merge_chains(&mut ours, &theirs),
+            Err(MergeError::ConflictingEntry(id)) if id == "cell:app@v2"
+        ));
+        assert_eq!(ours, before);
+
+        // Both branches rotating the same signer's key
+        let (old_private, _) = keygen();
+        let mut ours = base.clone();
+        let mut theirs = base;
+        for (chain, id) in [(&mut ours, "rotate-a"), (&mut theirs, "rotate-b")] {
+            let mut rotation = entry(id);
+            rotation.rotation = Some(endorse_rotation("dev:alice", &old_private, &keygen().1));
+            chain.append(rotation).unwrap();
+        }
+        assert!(matches!(
+            merge_chains(&mut ours, &theirs),
+            Err(MergeError::ConflictingRotation(signer)) if signer == "dev:alice"
+        ));
+    }
+}
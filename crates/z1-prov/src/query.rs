@@ -0,0 +1,256 @@
+//! Filtering provenance entries for audits.
+
+use crate::chain::compute_entry_hash;
+use crate::types::{ProvenanceChain, ProvenanceEntry};
+use chrono::{DateTime, Utc};
+
+/// Criteria an entry must meet to be selected. Unset criteria match every
+/// entry; set criteria must all match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProvenanceQuery {
+    /// Actor ID; also matches versioned actors, so `agent:foo` selects
+    /// `agent:foo/1.2`
+    pub actor: Option<String>,
+    /// Exact model name
+    pub model: Option<String>,
+    /// Entries at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Entries before this time
+    pub until: Option<DateTime<Utc>>,
+    /// Cell name (e.g. `http.server`), matched against entry IDs and cell
+    /// bindings, or a full entry ID
+    pub cell: Option<String>,
+    /// Prefix of the entry hash, prompt hash or diff hash; the `sha3-256:`
+    /// prefix is optional
+    pub hash: Option<String>,
+}
+
+impl ProvenanceQuery {
+    /// Whether `entry` meets every set criterion.
+    pub fn matches(&self, entry: &ProvenanceEntry) -> bool {
+        let actor = self.actor.as_deref().map_or(true, |actor| {
+            entry.actor == actor
+                || entry
+                    .actor
+                    .strip_prefix(actor)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        });
+        let model = self
+            .model
+            .as_deref()
+            .map_or(true, |model| entry.model == model);
+        let since = self.since.map_or(true, |since| entry.timestamp >= since);
+        let until = self.until.map_or(true, |until| entry.timestamp < until);
+        let cell = self.cell.as_deref().map_or(true, |cell| {
+            entry.entry_id == cell
+                || entry_cell(&entry.entry_id) == Some(cell)
+                || entry.cells.iter().any(|binding| binding.module == cell)
+        });
+        let hash = self.hash.as_deref().map_or(true, |hash| {
+            let hash = strip_algo(hash);
+            [
+                compute_entry_hash(entry).as_str(),
+                &entry.prompt_sha3,
+                &entry.diff_sha3,
+            ]
+            .into_iter()
+            .any(|candidate| strip_algo(candidate).starts_with(hash))
+        });
+        actor && model && since && until && cell && hash
+    }
+
+    /// Entries of `chain` that match, with their positions in the chain.
+    pub fn filter<'a>(
+        &'a self,
+        chain: &'a ProvenanceChain,
+    ) -> impl Iterator<Item = (usize, &'a ProvenanceEntry)> + 'a {
+        chain
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| self.matches(entry))
+    }
+}
+
+/// Entries recorded by `actor` (or a versioned form of it).
+pub fn entries_by_actor<'a>(chain: &'a ProvenanceChain, actor: &str) -> Vec<&'a ProvenanceEntry> {
+    select(
+        chain,
+        ProvenanceQuery {
+            actor: Some(actor.to_string()),
+            ..Default::default()
+        },
+    )
+}
+
+/// Entries generated with `model`.
+pub fn entries_by_model<'a>(chain: &'a ProvenanceChain, model: &str) -> Vec<&'a ProvenanceEntry> {
+    select(
+        chain,
+        ProvenanceQuery {
+            model: Some(model.to_string()),
+            ..Default::default()
+        },
+    )
+}
+
+/// Entries dated within `[since, until)`.
+pub fn entries_in_range(
+    chain: &ProvenanceChain,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> Vec<&ProvenanceEntry> {
+    select(
+        chain,
+        ProvenanceQuery {
+            since: Some(since),
+            until: Some(until),
+            ..Default::default()
+        },
+    )
+}
+
+/// Entries concerning the cell `cell`.
+pub fn entries_for_cell<'a>(chain: &'a ProvenanceChain, cell: &str) -> Vec<&'a ProvenanceEntry> {
+    select(
+        chain,
+        ProvenanceQuery {
+            cell: Some(cell.to_string()),
+            ..Default::default()
+        },
+    )
+}
+
+/// Entries whose entry, prompt or diff hash starts with `hash`.
+pub fn entries_by_hash<'a>(chain: &'a ProvenanceChain, hash: &str) -> Vec<&'a ProvenanceEntry> {
+    select(
+        chain,
+        ProvenanceQuery {
+            hash: Some(hash.to_string()),
+            ..Default::default()
+        },
+    )
+}
+
+fn select(chain: &ProvenanceChain, query: ProvenanceQuery) -> Vec<&ProvenanceEntry> {
+    chain
+        .entries
+        .iter()
+        .filter(|entry| query.matches(entry))
+        .collect()
+}
+
+/// Cell name of an entry ID of the form `<kind>:<cell>@<version>`.
+fn entry_cell(entry_id: &str) -> Option<&str> {
+    let (_, rest) = entry_id.split_once(':')?;
+    Some(rest.split_once('@').map_or(rest, |(cell, _)| cell))
+}
+
+fn strip_algo(hash: &str) -> &str {
+    hash.strip_prefix("sha3-256:").unwrap_or(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::ProvenanceChainExt;
+    use chrono::{Duration, TimeZone};
+
+    fn entry(id: &str, actor: &str, model: &str, day: u32) -> ProvenanceEntry {
+        ProvenanceEntry {
+            entry_id: id.to_string(),
+            prev: None,
+            merged: vec![],
+            actor: actor.to_string(),
+            model: model.to_string(),
+            prompt_sha3: "sha3-256:abc123".to_string(),
+            prompt_excerpt: "test prompt".to_string(),
+            redaction: None,
+            tools: vec![],
+            diff_sha3: format!("sha3-256:d1ff{day:02}"),
+            cells: vec![],
+            timestamp: Utc.with_ymd_and_hms(2025, 1, day, 12, 0, 0).unwrap(),
+            signatures: vec![],
+            keyless: vec![],
+            rotation: None,
+            checkpoint: None,
+            timestamp_token: None,
+        }
+    }
+
+    fn test_chain() -> ProvenanceChain {
+        let mut chain = ProvenanceChain::new();
+        for entry in [
+            entry("cell:http.server@v1", "agent:foo/1.0", "model-a", 1),
+            entry("cell:http.client@v1", "agent:bar/2.0", "model-b", 2),
+            entry("build:http.server@abc", "agent:foo/1.1", "none", 3),
+            entry("cell:http.server@v2", "dev:alice", "model-a", 4),
+        ] {
+            chain.append(entry).unwrap();
+        }
+        chain
+    }
+
+    fn ids<'a>(entries: impl IntoIterator<Item = &'a ProvenanceEntry>) -> Vec<&'a str> {
+        entries.into_iter().map(|e| e.entry_id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_query_single_criteria() {
+        let chain = test_chain();
+
+        assert_eq!(
+            ids(entries_by_actor(&chain, "agent:foo")),
+            ["cell:http.server@v1", "build:http.server@abc"]
+        );
+        assert!(entries_by_actor(&chain, "agent:fo").is_empty());
+        assert_eq!(
+            ids(entries_by_model(&chain, "model-a")),
+            ["cell:http.server@v1", "cell:http.server@v2"]
+        );
+        assert_eq!(
+            ids(entries_for_cell(&chain, "http.server")),
+            [
+                "cell:http.server@v1",
+                "build:http.server@abc",
+                "cell:http.server@v2"
+            ]
+        );
+        assert_eq!(
+            ids(entries_for_cell(&chain, "cell:http.client@v1")),
+            ["cell:http.client@v1"]
+        );
+
+        let start = Utc.with_ymd_and_hms(2025, 1, 2, 0, 0, 0).unwrap();
+        assert_eq!(
+            ids(entries_in_range(&chain, start, start + Duration::days(2))),
+            ["cell:http.client@v1", "build:http.server@abc"]
+        );
+
+        assert_eq!(
+            ids(entries_by_hash(&chain, "d1ff03")),
+            ["build:http.server@abc"]
+        );
+        let entry_hash = compute_entry_hash(&chain.entries[3]);
+        assert_eq!(
+            ids(entries_by_hash(&chain, &entry_hash[..20])),
+            ["cell:http.server@v2"]
+        );
+    }
+
+    #[test]
+    fn test_query_combines_criteria() {
+        let chain = test_chain();
+        let query = ProvenanceQuery {
+            actor: Some("agent:foo".to_string()),
+            since: Some(Utc.with_ymd_and_hms(2025, 1, 2, 0, 0, 0).unwrap()),
+            ..Default::default()
+        };
+
+        let selected: Vec<_> = query.filter(&chain).collect();
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].0, 2);
+        assert_eq!(selected[0].1.entry_id, "build:http.server@abc");
+        assert_eq!(ProvenanceQuery::default().filter(&chain).count(), 4);
+    }
+}
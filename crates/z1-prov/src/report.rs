@@ -0,0 +1,324 @@
+//! Per-entry verification reports.
+//!
+//! The `verify_chain*` functions stop at the first failure. A
+//! [`VerificationReport`] instead runs every check on every entry and records
+//! the outcome of each, so CI can publish the full picture as an artifact.
+
+use crate::keyless::KeylessTrust;
+use crate::timestamp::AuthorityKey;
+use crate::types::ProvenanceChain;
+use crate::verify::{
+    apply_rotation, check_checkpoint, check_keyless, check_redaction, check_timestamp_authority,
+    check_timestamp_token, compute_entry_hash, current_keys, inherit_keys, initial_keys,
+    verify_entry_signatures, KeyState, Links, VerificationError,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// What a verification report checks beyond the chain's structure.
+#[derive(Debug, Clone, Default)]
+pub struct ReportOptions {
+    /// Initial signer keys; signatures are skipped when unset
+    pub public_keys: Option<HashMap<String, [u8; 32]>>,
+    /// Signers that must sign every entry
+    pub required_signers: Option<Vec<String>>,
+    /// Timestamp authority keys; token signatures are not checked when unset
+    pub authorities: Option<HashMap<String, AuthorityKey>>,
+    /// Fail entries without a timestamp token (requires `authorities`)
+    pub require_timestamps: bool,
+    /// Trust for keyless signatures; skipped when unset
+    pub keyless: Option<KeylessTrust>,
+    /// Fail entries without a keyless signature (requires `keyless`)
+    pub require_keyless: bool,
+}
+
+/// Outcome of one check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Passed,
+    Failed,
+    /// Not checked, because the check was not requested or does not apply
+    Skipped,
+}
+
+/// A check's outcome and, for failures, the reason.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Check {
+    pub status: CheckStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Check {
+    fn passed() -> Self {
+        Self {
+            status: CheckStatus::Passed,
+            detail: None,
+        }
+    }
+
+    fn skipped(reason: &str) -> Self {
+        Self {
+            status: CheckStatus::Skipped,
+            detail: Some(reason.to_string()),
+        }
+    }
+
+    fn from_result(result: Result<(), VerificationError>) -> Self {
+        match result {
+            Ok(()) => Self::passed(),
+            Err(err) => Self {
+                status: CheckStatus::Failed,
+                detail: Some(err.to_string()),
+            },
+        }
+    }
+
+    /// Whether the check failed.
+    pub fn failed(&self) -> bool {
+        self.status == CheckStatus::Failed
+    }
+}
+
+/// Checks run on one entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryReport {
+    /// Position of the entry in the chain
+    pub index: usize,
+    pub entry_id: String,
+    /// Hash of the entry (`sha3-256:...`)
+    pub hash: String,
+    /// Parent links, and for checkpoints the commitment to their ancestors
+    pub link: Check,
+    /// Signatures and key rotation
+    pub signatures: Check,
+    /// Timestamp token consistency and authority signature
+    pub timestamp: Check,
+    /// Keyless signatures
+    pub keyless: Check,
+    /// Redaction policy of the prompt excerpt
+    pub policy: Check,
+}
+
+impl EntryReport {
+    /// Whether any check on the entry failed.
+    pub fn failed(&self) -> bool {
+        [
+            &self.link,
+            &self.signatures,
+            &self.timestamp,
+            &self.keyless,
+            &self.policy,
+        ]
+        .into_iter()
+        .any(Check::failed)
+    }
+}
+
+/// Result of checking every entry of a chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerificationReport {
+    /// Merkle root recorded in the chain
+    pub merkle_root: String,
+    /// When the report was produced
+    pub generated_at: DateTime<Utc>,
+    /// Whether no check failed
+    pub valid: bool,
+    /// Number of entries with a failed check
+    pub failed: usize,
+    pub entries: Vec<EntryReport>,
+}
+
+impl VerificationReport {
+    /// Run every check on every entry of `chain`.
+    ///
+    /// An entry whose links do not resolve is treated as having no parents
+    /// for the remaining checks, so one bad link does not hide the state of
+    /// the entries after it.
+    pub fn generate(chain: &ProvenanceChain, options: &ReportOptions) -> Self {
+        let mut links = Links::default();
+        let mut parents: Vec<Vec<usize>> = Vec::with_capacity(chain.len());
+        let mut latest_stamp: Vec<Option<DateTime<Utc>>> = Vec::with_capacity(chain.len());
+        let initial = options.public_keys.as_ref().map(initial_keys);
+        let mut key_states: Vec<KeyState> = Vec::with_capacity(chain.len());
+        let mut entries = Vec::with_capacity(chain.len());
+
+        for (index, entry) in chain.entries.iter().enumerate() {
+            let link = match links.resolve(index, entry) {
+                Ok(entry_parents) => {
+                    parents.push(entry_parents);
+                    check_checkpoint(chain, &parents, index)
+                }
+                Err(err) => {
+                    parents.push(Vec::new());
+                    Err(err)
+                }
+            };
+            let entry_parents = &parents[index];
+
+            let signatures = match &initial {
+                Some(initial) => {
+                    let (state, result) =
+                        match inherit_keys(index, entry, entry_parents, &key_states, initial) {
+                            Ok(mut state) => {
+                                let result = verify_entry_signatures(
+                                    entry,
+                                    &current_keys(&state),
+                                    options.required_signers.as_deref(),
+                                )
+                                .and_then(|()| apply_rotation(entry, &mut state));
+                                (state, result)
+                            }
+                            Err(err) => (initial.clone(), Err(err)),
+                        };
+                    key_states.push(state);
+                    Check::from_result(result)
+                }
+                None => Check::skipped("no public keys given"),
+            };
+
+            let inherited = entry_parents.iter().filter_map(|&p| latest_stamp[p]).max();
+            let timestamp = match check_timestamp_token(entry, inherited) {
+                Ok(latest) => {
+                    latest_stamp.push(latest);
+                    match &options.authorities {
+                        Some(authorities)
+                            if options.require_timestamps || entry.timestamp_token.is_some() =>
+                        {
+                            Check::from_result(check_timestamp_authority(
+                                entry,
+                                authorities,
+                                options.require_timestamps,
+                            ))
+                        }
+                        None if entry.timestamp_token.is_some() => {
+                            Check::skipped("no authority keys given")
+                        }
+                        _ => Check::skipped("no timestamp token"),
+                    }
+                }
+                Err(err) => {
+                    latest_stamp.push(inherited);
+                    Check::from_result(Err(err))
+                }
+            };
+
+            let keyless = match &options.keyless {
+                Some(trust) if options.require_keyless || !entry.keyless.is_empty() => {
+                    Check::from_result(check_keyless(entry, trust, options.require_keyless))
+                }
+                Some(_) => Check::skipped("no keyless signatures"),
+                None => Check::skipped("no keyless trust given"),
+            };
+
+            let policy = match entry.redaction {
+                Some(_) => Check::from_result(check_redaction(entry)),
+                None => Check::skipped("no redaction policy"),
+            };
+
+            entries.push(EntryReport {
+                index,
+                entry_id: entry.entry_id.clone(),
+                hash: compute_entry_hash(entry),
+                link: Check::from_result(link),
+                signatures,
+                timestamp,
+                keyless,
+                policy,
+            });
+        }
+
+        let failed = entries.iter().filter(|entry| entry.failed()).count();
+        Self {
+            merkle_root: chain.merkle_root.clone(),
+            generated_at: Utc::now(),
+            valid: failed == 0,
+            failed,
+            entries,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::ProvenanceChainExt;
+    use crate::redact::RedactionPolicy;
+    use crate::signature::{keygen, sign_entry};
+    use crate::types::ProvenanceEntry;
+
+    fn entry(id: &str) -> ProvenanceEntry {
+        ProvenanceEntry {
+            entry_id: id.to_string(),
+            prev: None,
+            merged: vec![],
+            actor: "dev:alice".to_string(),
+            model: "test-model".to_string(),
+            prompt_sha3: "abc123".to_string(),
+            prompt_excerpt: "test prompt".to_string(),
+            redaction: None,
+            tools: vec![],
+            diff_sha3: "def456".to_string(),
+            cells: vec![],
+            timestamp: Utc::now(),
+            signatures: vec![],
+            keyless: vec![],
+            rotation: None,
+            checkpoint: None,
+            timestamp_token: None,
+        }
+    }
+
+    #[test]
+    fn test_report_lists_every_failure() {
+        let (private_key, public_key) = keygen();
+        let mut chain = ProvenanceChain::new();
+        for id in ["cell:a@v1", "cell:b@v1", "cell:c@v1"] {
+            chain.append(entry(id)).unwrap();
+            let latest = chain.entries.last_mut().unwrap();
+            latest
+                .signatures
+                .push(sign_entry(latest, &private_key, "dev:alice"));
+        }
+
+        // Forge the first signature and break the last entry's policy
+        chain.entries[0].signatures[0].sig = chain.entries[1].signatures[0].sig.clone();
+        chain.entries[2].redaction = Some(RedactionPolicy {
+            max_len: 4,
+            ..RedactionPolicy::default()
+        });
+
+        let options = ReportOptions {
+            public_keys: Some(HashMap::from([("dev:alice".to_string(), public_key)])),
+            ..Default::default()
+        };
+        let report = VerificationReport::generate(&chain, &options);
+
+        assert!(!report.valid);
+        assert_eq!(report.failed, 3);
+        assert!(report.entries[0].signatures.failed());
+        // Entries after the forged one no longer link to it
+        assert!(report.entries[1].link.failed());
+        assert_eq!(report.entries[1].signatures.status, CheckStatus::Passed);
+        assert!(report.entries[2].policy.failed());
+        assert!(report.entries[2].signatures.failed());
+        assert_eq!(report.entries[0].timestamp.status, CheckStatus::Skipped);
+    }
+
+    #[test]
+    fn test_report_serializes_statuses() {
+        let mut chain = ProvenanceChain::new();
+        chain.append(entry("cell:a@v1")).unwrap();
+
+        let report = VerificationReport::generate(&chain, &ReportOptions::default());
+        assert!(report.valid);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["entries"][0]["link"]["status"], "passed");
+        assert_eq!(json["entries"][0]["signatures"]["status"], "skipped");
+        let parsed: VerificationReport = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, report);
+    }
+}
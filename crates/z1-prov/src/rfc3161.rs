@@ -0,0 +1,720 @@
+//! RFC 3161 time-stamping.
+//!
+//! [`Rfc3161Authority`] asks a time-stamping service, such as a public TSA or
+//! Sigstore's timestamp authority, to countersign the SHA-256 of an entry's
+//! imprint. The service's clock sets the time, not the stamper's, and its
+//! certificate chains to a root the verifier trusts, so a token cannot be
+//! made for any time but the one at which the service saw the imprint.
+
+use crate::timestamp::{TimestampAuthority, TimestampError};
+use crate::types::TimestampToken;
+use crate::{http, x509};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use cms::cert::CertificateChoices;
+use cms::content_info::ContentInfo;
+use cms::signed_data::{SignedData, SignerIdentifier, SignerInfo};
+use der::asn1::{Int, OctetString, SetOfVec};
+use der::oid::db::{rfc5280, rfc5911, rfc5912};
+use der::oid::ObjectIdentifier;
+use der::{Any, Decode, Encode, Sequence, Tag, Tagged};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use x509_cert::ext::pkix::name::GeneralName;
+use x509_cert::ext::pkix::SubjectKeyIdentifier;
+use x509_cert::ext::Extensions;
+use x509_cert::spki::AlgorithmIdentifierOwned;
+use x509_cert::Certificate;
+
+/// id-ct-TSTInfo, the content type of a time-stamp token.
+const ID_CT_TST_INFO: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.16.1.4");
+
+/// Hash of the imprint the service stamps.
+#[derive(Clone, Debug, Eq, PartialEq, Sequence)]
+struct MessageImprint {
+    hash_algorithm: AlgorithmIdentifierOwned,
+    hashed_message: OctetString,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Sequence)]
+struct TimeStampReq {
+    version: u8,
+    message_imprint: MessageImprint,
+    #[asn1(optional = "true")]
+    req_policy: Option<ObjectIdentifier>,
+    #[asn1(optional = "true")]
+    nonce: Option<u64>,
+    #[asn1(default = "Default::default")]
+    cert_req: bool,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Sequence)]
+struct PkiStatusInfo {
+    status: u8,
+    #[asn1(optional = "true")]
+    status_string: Option<Vec<String>>,
+    #[asn1(optional = "true")]
+    fail_info: Option<der::asn1::BitString>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Sequence)]
+struct TimeStampResp {
+    status: PkiStatusInfo,
+    #[asn1(optional = "true")]
+    time_stamp_token: Option<ContentInfo>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Sequence)]
+struct Accuracy {
+    #[asn1(optional = "true")]
+    seconds: Option<u32>,
+    #[asn1(context_specific = "0", tag_mode = "IMPLICIT", optional = "true")]
+    millis: Option<u16>,
+    #[asn1(context_specific = "1", tag_mode = "IMPLICIT", optional = "true")]
+    micros: Option<u16>,
+}
+
+/// What a token asserts: the imprint's hash and the time the service saw it.
+#[derive(Clone, Debug, Eq, PartialEq, Sequence)]
+struct TstInfo {
+    version: u8,
+    policy: ObjectIdentifier,
+    message_imprint: MessageImprint,
+    serial_number: Int,
+    /// A GeneralizedTime, read by hand as it may have fractional seconds
+    gen_time: Any,
+    #[asn1(optional = "true")]
+    accuracy: Option<Accuracy>,
+    #[asn1(default = "Default::default")]
+    ordering: bool,
+    #[asn1(optional = "true")]
+    nonce: Option<u64>,
+    #[asn1(context_specific = "0", tag_mode = "EXPLICIT", optional = "true")]
+    tsa: Option<GeneralName>,
+    #[asn1(
+        context_specific = "1",
+        tag_mode = "IMPLICIT",
+        constructed = "true",
+        optional = "true"
+    )]
+    extensions: Option<Extensions>,
+}
+
+/// A client of an RFC 3161 time-stamping service.
+pub struct Rfc3161Authority {
+    id: String,
+    url: String,
+}
+
+impl Rfc3161Authority {
+    /// Service at `url`, which also identifies it on tokens.
+    pub fn new(url: impl Into<String>) -> Self {
+        let url = url.into();
+        Self {
+            id: url.clone(),
+            url,
+        }
+    }
+
+    /// Identify the service as `id` on tokens instead of by its URL.
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = id.into();
+        self
+    }
+
+    fn error(&self, reason: String) -> TimestampError {
+        TimestampError::Authority(self.url.clone(), reason)
+    }
+}
+
+impl TimestampAuthority for Rfc3161Authority {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn stamp(&self, imprint: &str) -> Result<TimestampToken, TimestampError> {
+        let mut nonce = [0u8; 8];
+        getrandom::fill(&mut nonce).expect("failed to generate random bytes");
+        let nonce = u64::from_be_bytes(nonce);
+        let request = TimeStampReq {
+            version: 1,
+            message_imprint: message_imprint(imprint),
+            req_policy: None,
+            nonce: Some(nonce),
+            cert_req: true,
+        }
+        .to_der()
+        .map_err(|err| self.error(format!("request does not encode: {err}")))?;
+        let response = http::post(&self.url, "application/timestamp-query", &request)
+            .map_err(|reason| self.error(reason))?;
+        let response = TimeStampResp::from_der(&response)
+            .map_err(|err| self.error(format!("malformed response: {err}")))?;
+        // 0 is granted, 1 granted with modifications
+        if response.status.status > 1 {
+            let reasons = response.status.status_string.unwrap_or_default();
+            return Err(self.error(format!(
+                "refused with status {}: {}",
+                response.status.status,
+                reasons.join("; ")
+            )));
+        }
+        let der = response
+            .time_stamp_token
+            .ok_or_else(|| self.error("the response has no token".into()))?
+            .to_der()
+            .map_err(|err| self.error(format!("token does not encode: {err}")))?;
+        let token = Token::parse(&der).map_err(|reason| self.error(reason))?;
+        if token.info.nonce != Some(nonce) {
+            return Err(self.error("the token does not answer this request".into()));
+        }
+        if !token.stamps(imprint) {
+            return Err(self.error("the token is for another imprint".into()));
+        }
+        Ok(TimestampToken {
+            authority: self.id.clone(),
+            time: token.time,
+            imprint: imprint.to_string(),
+            sig: token.sig(),
+            rfc3161: Some(STANDARD.encode(der)),
+        })
+    }
+}
+
+/// The SHA-256 of `imprint`, as the service stamps it.
+fn message_imprint(imprint: &str) -> MessageImprint {
+    MessageImprint {
+        hash_algorithm: AlgorithmIdentifierOwned {
+            oid: rfc5912::ID_SHA_256,
+            parameters: None,
+        },
+        hashed_message: OctetString::new(Sha256::digest(imprint.as_bytes()).to_vec())
+            .expect("a hash fits an octet string"),
+    }
+}
+
+/// A decoded time-stamp token: a CMS signed-data message over a TSTInfo.
+struct Token {
+    signed_data: SignedData,
+    signer: SignerInfo,
+    /// DER of the TSTInfo, the content the signer's message digest covers
+    content: Vec<u8>,
+    info: TstInfo,
+    time: DateTime<Utc>,
+}
+
+impl Token {
+    fn parse(der: &[u8]) -> Result<Self, String> {
+        let content_info =
+            ContentInfo::from_der(der).map_err(|err| format!("malformed token: {err}"))?;
+        if content_info.content_type != rfc5911::ID_SIGNED_DATA {
+            return Err("token is not signed data".into());
+        }
+        let signed_data: SignedData = content_info
+            .content
+            .decode_as()
+            .map_err(|err| format!("malformed signed data: {err}"))?;
+        let encapsulated = &signed_data.encap_content_info;
+        if encapsulated.econtent_type != ID_CT_TST_INFO {
+            return Err("token does not hold a TSTInfo".into());
+        }
+        let content = encapsulated
+            .econtent
+            .as_ref()
+            .and_then(|content| content.decode_as::<OctetString>().ok())
+            .ok_or("token has no TSTInfo")?
+            .into_bytes();
+        let info =
+            TstInfo::from_der(&content).map_err(|err| format!("malformed TSTInfo: {err}"))?;
+        let time = generalized_time(&info.gen_time).ok_or("token has a malformed time")?;
+        let signer = match signed_data.signer_infos.0.as_slice() {
+            [signer] => signer.clone(),
+            _ => return Err("token must have exactly one signer".into()),
+        };
+        Ok(Self {
+            signed_data,
+            signer,
+            content,
+            info,
+            time,
+        })
+    }
+
+    /// Whether the token stamps `imprint`. Services may write the hash
+    /// algorithm's absent parameters as NULL.
+    fn stamps(&self, imprint: &str) -> bool {
+        let expected = message_imprint(imprint);
+        let stamped = &self.info.message_imprint;
+        stamped.hash_algorithm.oid == expected.hash_algorithm.oid
+            && stamped.hashed_message == expected.hashed_message
+    }
+
+    /// The signature as a token records it.
+    fn sig(&self) -> String {
+        format!("rfc3161:{}", hex::encode(self.signer.signature.as_bytes()))
+    }
+
+    /// Certificates the token carries.
+    fn certificates(&self) -> Vec<Certificate> {
+        self.signed_data
+            .certificates
+            .iter()
+            .flat_map(|certs| certs.0.iter())
+            .filter_map(|cert| match cert {
+                CertificateChoices::Certificate(cert) => Some(cert.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// A GeneralizedTime in UTC, with or without fractional seconds.
+fn generalized_time(value: &Any) -> Option<DateTime<Utc>> {
+    if value.tag() != Tag::GeneralizedTime {
+        return None;
+    }
+    let text = std::str::from_utf8(value.value()).ok()?;
+    NaiveDateTime::parse_from_str(text, "%Y%m%d%H%M%S%.fZ")
+        .ok()
+        .map(|time| time.and_utc())
+}
+
+/// Check an RFC 3161 `token` against the trusted root certificates (PEM):
+/// the TSTInfo stamps the token's imprint at its time, the service signed
+/// it, and the service's certificate is for time-stamping and chained to a
+/// root when the token was issued.
+pub fn verify_rfc3161_token(token: &TimestampToken, roots: &str) -> Result<(), String> {
+    let der = STANDARD
+        .decode(token.rfc3161.as_deref().unwrap_or_default())
+        .map_err(|_| "token is not base64".to_string())?;
+    let parsed = Token::parse(&der)?;
+    if !parsed.stamps(&token.imprint) {
+        return Err("token stamps another imprint".into());
+    }
+    if parsed.time != token.time || parsed.sig() != token.sig {
+        return Err("token does not match its RFC 3161 encoding".into());
+    }
+
+    let certs = parsed.certificates();
+    let signer_cert = certs
+        .iter()
+        .find(|cert| identifies(&parsed.signer.sid, cert))
+        .ok_or("token does not carry its signer's certificate")?;
+    verify_signed_attributes(&parsed)?;
+    let attributes = parsed
+        .signer
+        .signed_attrs
+        .as_ref()
+        .map(Encode::to_der)
+        .transpose()
+        .map_err(|err| format!("signed attributes do not encode: {err}"))?
+        .unwrap_or_default();
+    x509::verify_signature(
+        &signer_cert.tbs_certificate.subject_public_key_info,
+        &signature_algorithm(&parsed.signer)?,
+        &attributes,
+        parsed.signer.signature.as_bytes(),
+    )
+    .map_err(|reason| format!("token signature: {reason}"))?;
+    if !x509::allows_usage(signer_cert, &rfc5280::ID_KP_TIME_STAMPING) {
+        return Err(format!(
+            "certificate for {} is not for time-stamping",
+            signer_cert.tbs_certificate.subject
+        ));
+    }
+
+    let mut chain = vec![signer_cert.clone()];
+    while let Some(issuer) = certs.iter().find(|cert| {
+        let last = &chain[chain.len() - 1].tbs_certificate;
+        cert.tbs_certificate.subject == last.issuer && last.subject != last.issuer
+    }) {
+        if chain.contains(issuer) {
+            break;
+        }
+        chain.push(issuer.clone());
+    }
+    x509::verify_chain(&chain, &x509::parse_pem(roots)?, parsed.time)
+}
+
+/// Whether `cert` is the one `sid` names.
+fn identifies(sid: &SignerIdentifier, cert: &Certificate) -> bool {
+    let tbs = &cert.tbs_certificate;
+    match sid {
+        SignerIdentifier::IssuerAndSerialNumber(id) => {
+            id.issuer == tbs.issuer && id.serial_number == tbs.serial_number
+        }
+        SignerIdentifier::SubjectKeyIdentifier(id) => {
+            x509::extension(cert, &rfc5280::ID_CE_SUBJECT_KEY_IDENTIFIER)
+                .and_then(|value| SubjectKeyIdentifier::from_der(value).ok())
+                .is_some_and(|key_id| key_id == *id)
+        }
+    }
+}
+
+/// Check the signed attributes: they name a TSTInfo and hold its digest.
+fn verify_signed_attributes(token: &Token) -> Result<(), String> {
+    let attributes = token
+        .signer
+        .signed_attrs
+        .as_ref()
+        .ok_or("token has no signed attributes")?;
+    let value = |oid: ObjectIdentifier| {
+        attributes
+            .iter()
+            .find(|attribute| attribute.oid == oid)
+            .and_then(|attribute| single(&attribute.values))
+    };
+    let content_type = value(rfc5911::ID_CONTENT_TYPE)
+        .and_then(|value| value.decode_as::<ObjectIdentifier>().ok());
+    if content_type != Some(ID_CT_TST_INFO) {
+        return Err("signed attributes name another content type".into());
+    }
+    let digest = match token.signer.digest_alg.oid {
+        rfc5912::ID_SHA_256 => Sha256::digest(&token.content).to_vec(),
+        rfc5912::ID_SHA_384 => Sha384::digest(&token.content).to_vec(),
+        rfc5912::ID_SHA_512 => Sha512::digest(&token.content).to_vec(),
+        other => return Err(format!("unsupported digest algorithm {other}")),
+    };
+    let signed_digest =
+        value(rfc5911::ID_MESSAGE_DIGEST).and_then(|value| value.decode_as::<OctetString>().ok());
+    if signed_digest.as_ref().map(OctetString::as_bytes) != Some(digest.as_slice()) {
+        return Err("signed digest does not match the TSTInfo".into());
+    }
+    Ok(())
+}
+
+fn single(values: &SetOfVec<Any>) -> Option<&Any> {
+    match values.as_slice() {
+        [value] => Some(value),
+        _ => None,
+    }
+}
+
+/// Signature algorithm of `signer`, combining a bare key algorithm, as many
+/// services record it, with the digest algorithm.
+fn signature_algorithm(signer: &SignerInfo) -> Result<ObjectIdentifier, String> {
+    let algorithm = signer.signature_algorithm.oid;
+    let digest = signer.digest_alg.oid;
+    Ok(match (algorithm, digest) {
+        (rfc5912::RSA_ENCRYPTION, rfc5912::ID_SHA_256) => rfc5912::SHA_256_WITH_RSA_ENCRYPTION,
+        (rfc5912::RSA_ENCRYPTION, rfc5912::ID_SHA_384) => rfc5912::SHA_384_WITH_RSA_ENCRYPTION,
+        (rfc5912::RSA_ENCRYPTION, rfc5912::ID_SHA_512) => rfc5912::SHA_512_WITH_RSA_ENCRYPTION,
+        (rfc5912::ID_EC_PUBLIC_KEY, rfc5912::ID_SHA_256) => rfc5912::ECDSA_WITH_SHA_256,
+        (rfc5912::ID_EC_PUBLIC_KEY, rfc5912::ID_SHA_384) => rfc5912::ECDSA_WITH_SHA_384,
+        (rfc5912::ID_EC_PUBLIC_KEY, rfc5912::ID_SHA_512) => rfc5912::ECDSA_WITH_SHA_512,
+        (rfc5912::RSA_ENCRYPTION | rfc5912::ID_EC_PUBLIC_KEY, other) => {
+            return Err(format!("unsupported digest algorithm {other}"))
+        }
+        (algorithm, _) => algorithm,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{p256_key, p256_spki, serve, TestCa};
+    use crate::timestamp::{timestamp_entry, AuthorityKey, LocalAuthority};
+    use crate::types::{ProvenanceChain, ProvenanceEntry};
+    use crate::verify::verify_chain_timestamps;
+    use crate::VerificationError;
+    use chrono::Duration;
+    use cms::builder::{SignedDataBuilder, SignerInfoBuilder};
+    use cms::cert::IssuerAndSerialNumber;
+    use cms::signed_data::{EncapsulatedContentInfo, SignerInfos};
+    use p256::ecdsa::signature::{Keypair, Signer};
+    use std::collections::HashMap;
+    use x509_cert::ext::pkix::ExtendedKeyUsage;
+    use x509_cert::spki::{
+        DynSignatureAlgorithmIdentifier, SignatureBitStringEncoding, SubjectPublicKeyInfoOwned,
+    };
+
+    /// How a fake service misbehaves.
+    #[derive(Clone, Copy, Default)]
+    struct Tsa {
+        /// Sign with RSA, naming only the key algorithm as OpenSSL does
+        rsa: bool,
+        /// Certify the key without the time-stamping usage
+        any_usage: bool,
+        /// Answer with a nonce of its own
+        replay: bool,
+        /// Stamp this far from the current time
+        clock: Duration,
+    }
+
+    fn sha256() -> AlgorithmIdentifierOwned {
+        AlgorithmIdentifierOwned {
+            oid: rfc5912::ID_SHA_256,
+            parameters: None,
+        }
+    }
+
+    /// A token for `info`, signed with `key` certified by `leaf`.
+    fn sign_token<S, Sig>(key: &S, leaf: &Certificate, ca: &TestCa, info: &TstInfo) -> ContentInfo
+    where
+        S: Keypair + DynSignatureAlgorithmIdentifier + Signer<Sig>,
+        Sig: SignatureBitStringEncoding,
+    {
+        let content = OctetString::new(info.to_der().unwrap()).unwrap();
+        let encapsulated = EncapsulatedContentInfo {
+            econtent_type: ID_CT_TST_INFO,
+            econtent: Some(Any::encode_from(&content).unwrap()),
+        };
+        let sid = SignerIdentifier::IssuerAndSerialNumber(IssuerAndSerialNumber {
+            issuer: leaf.tbs_certificate.issuer.clone(),
+            serial_number: leaf.tbs_certificate.serial_number.clone(),
+        });
+        let signer = SignerInfoBuilder::new(key, sid, sha256(), &encapsulated, None).unwrap();
+        SignedDataBuilder::new(&encapsulated)
+            .add_digest_algorithm(sha256())
+            .unwrap()
+            .add_certificate(CertificateChoices::Certificate(leaf.clone()))
+            .unwrap()
+            .add_certificate(CertificateChoices::Certificate(ca.intermediate.clone()))
+            .unwrap()
+            .add_signer_info::<S, Sig>(signer)
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    /// Replace the signature algorithm with the bare key algorithm.
+    fn name_key_algorithm_only(token: ContentInfo) -> ContentInfo {
+        let mut signed_data: SignedData = token.content.decode_as().unwrap();
+        let mut signers = signed_data.signer_infos.0.into_vec();
+        signers[0].signature_algorithm.oid = rfc5912::RSA_ENCRYPTION;
+        signed_data.signer_infos = SignerInfos(signers.try_into().unwrap());
+        ContentInfo {
+            content: Any::encode_from(&signed_data).unwrap(),
+            ..token
+        }
+    }
+
+    /// A random source for RSA key generation.
+    struct Random;
+
+    impl rsa::rand_core::RngCore for Random {
+        fn next_u32(&mut self) -> u32 {
+            rsa::rand_core::impls::next_u32_via_fill(self)
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            rsa::rand_core::impls::next_u64_via_fill(self)
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            getrandom::fill(dest).unwrap();
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rsa::rand_core::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    impl rsa::rand_core::CryptoRng for Random {}
+
+    /// An RFC 3161 service certified by `ca`; returns its URL.
+    fn serve_tsa(ca: TestCa, tsa: Tsa) -> String {
+        enum Key {
+            Ecdsa(p256::ecdsa::SigningKey),
+            Rsa(Box<rsa::pkcs1v15::SigningKey<Sha256>>),
+        }
+        let (key, spki) = if tsa.rsa {
+            let key = rsa::RsaPrivateKey::new(&mut Random, 1024).unwrap();
+            let spki = SubjectPublicKeyInfoOwned::from_key(key.to_public_key()).unwrap();
+            (
+                Key::Rsa(Box::new(rsa::pkcs1v15::SigningKey::new(key))),
+                spki,
+            )
+        } else {
+            let key = p256_key();
+            let spki = p256_spki(&key);
+            (Key::Ecdsa(key), spki)
+        };
+        let leaf = ca.issue(
+            spki,
+            Utc::now() - Duration::days(1),
+            Duration::days(2),
+            |cert| {
+                if !tsa.any_usage {
+                    let usage = ExtendedKeyUsage(vec![rfc5280::ID_KP_TIME_STAMPING]);
+                    cert.add_extension(&usage).unwrap();
+                }
+            },
+        );
+        serve(move |_, body| {
+            let request = TimeStampReq::from_der(body).unwrap();
+            let time = Utc::now() + tsa.clock;
+            let info = TstInfo {
+                version: 1,
+                policy: ObjectIdentifier::new_unwrap("1.3.6.1.4.1.4146.2.3"),
+                message_imprint: request.message_imprint,
+                serial_number: Int::new(&[1]).unwrap(),
+                gen_time: Any::new(
+                    Tag::GeneralizedTime,
+                    time.format("%Y%m%d%H%M%S%.3fZ").to_string().into_bytes(),
+                )
+                .unwrap(),
+                accuracy: None,
+                ordering: false,
+                nonce: request.nonce.map(|nonce| nonce ^ u64::from(tsa.replay)),
+                tsa: None,
+                extensions: None,
+            };
+            let token = match &key {
+                Key::Ecdsa(key) => {
+                    sign_token::<_, p256::ecdsa::DerSignature>(key, &leaf, &ca, &info)
+                }
+                Key::Rsa(key) => name_key_algorithm_only(
+                    sign_token::<_, rsa::pkcs1v15::Signature>(key.as_ref(), &leaf, &ca, &info),
+                ),
+            };
+            let response = TimeStampResp {
+                status: PkiStatusInfo {
+                    status: 0,
+                    status_string: None,
+                    fail_info: None,
+                },
+                time_stamp_token: Some(token),
+            };
+            (200, response.to_der().unwrap())
+        })
+    }
+
+    fn make_test_entry() -> ProvenanceEntry {
+        ProvenanceEntry {
+            entry_id: "cell:test@v1".to_string(),
+            prev: None,
+            merged: vec![],
+            actor: "test-actor".to_string(),
+            model: "test-model".to_string(),
+            prompt_sha3: "abc123".to_string(),
+            prompt_excerpt: "test prompt".to_string(),
+            redaction: None,
+            tools: vec![],
+            diff_sha3: "def456".to_string(),
+            cells: vec![],
+            timestamp: Utc::now(),
+            signatures: vec![],
+            keyless: vec![],
+            rotation: None,
+            checkpoint: None,
+            timestamp_token: None,
+        }
+    }
+
+    /// A chain of one entry stamped by a service behaving as `tsa`, and the
+    /// trusted root of its certificate authority.
+    fn stamp(tsa: Tsa) -> (ProvenanceChain, String) {
+        let ca = TestCa::new("tsa");
+        let root = ca.root_pem();
+        let authority = Rfc3161Authority::new(serve_tsa(ca, tsa)).with_id("tsa:test");
+        let mut entry = make_test_entry();
+        timestamp_entry(&mut entry, &authority).unwrap();
+        let mut chain = ProvenanceChain::new();
+        chain.add_entry(entry);
+        (chain, root)
+    }
+
+    fn trusting(root: String) -> HashMap<String, AuthorityKey> {
+        [("tsa:test".to_string(), AuthorityKey::X509(root))].into()
+    }
+
+    fn rejection(chain: &ProvenanceChain, root: &str) -> String {
+        match verify_chain_timestamps(chain, &trusting(root.to_string()), true) {
+            Err(VerificationError::InvalidTimestamp(_, reason)) => reason,
+            other => panic!("expected an invalid timestamp, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_stamp_and_verify_rfc3161_token() {
+        let (mut chain, root) = stamp(Tsa::default());
+        let token = chain.entries[0].timestamp_token.clone().unwrap();
+        assert_eq!(token.authority, "tsa:test");
+        assert!(token.sig.starts_with("rfc3161:"));
+        assert!((Utc::now() - token.time).abs() < Duration::minutes(1));
+        assert!(crate::verify::verify_chain(&chain).is_ok());
+        assert!(verify_chain_timestamps(&chain, &trusting(root.clone()), true).is_ok());
+
+        // Another authority's root, or a key for a local authority
+        let reason = rejection(&chain, &TestCa::new("tsa").root_pem());
+        assert!(reason.contains("does not verify"), "{reason}");
+        let local: HashMap<_, _> = [("tsa:test".to_string(), [7u8; 32].into())].into();
+        assert!(verify_chain_timestamps(&chain, &local, true).is_err());
+
+        // The time and imprint are read from the signed token
+        let entry = &mut chain.entries[0];
+        entry.timestamp_token.as_mut().unwrap().time -= Duration::days(1);
+        let reason = rejection(&chain, &root);
+        assert!(reason.contains("does not match"), "{reason}");
+        let entry = &mut chain.entries[0];
+        entry.timestamp_token = Some(token.clone());
+        entry.diff_sha3 = "changed".to_string();
+        entry.timestamp_token.as_mut().unwrap().imprint = crate::entry_imprint(entry);
+        let reason = rejection(&chain, &root);
+        assert!(reason.contains("another imprint"), "{reason}");
+
+        // A local authority's token can claim any time
+        let local = LocalAuthority::new("tsa:test", &[7u8; 32]);
+        let entry = &mut chain.entries[0];
+        entry.timestamp_token = Some(local.stamp_at(&crate::entry_imprint(entry), token.time));
+        let reason = rejection(&chain, &root);
+        assert!(reason.contains("issued no RFC 3161 token"), "{reason}");
+    }
+
+    #[test]
+    fn test_rfc3161_rsa_token() {
+        let (chain, root) = stamp(Tsa {
+            rsa: true,
+            ..Tsa::default()
+        });
+        assert!(verify_chain_timestamps(&chain, &trusting(root), true).is_ok());
+    }
+
+    #[test]
+    fn test_rfc3161_rejects_misissued_tokens() {
+        let (chain, root) = stamp(Tsa {
+            any_usage: true,
+            ..Tsa::default()
+        });
+        let reason = rejection(&chain, &root);
+        assert!(reason.contains("not for time-stamping"), "{reason}");
+
+        // The certificate must cover the stamped time
+        let (chain, root) = stamp(Tsa {
+            clock: Duration::days(3),
+            ..Tsa::default()
+        });
+        let reason = rejection(&chain, &root);
+        assert!(reason.contains("was not valid at"), "{reason}");
+
+        let ca = TestCa::new("tsa");
+        let url = serve_tsa(
+            ca,
+            Tsa {
+                replay: true,
+                ..Tsa::default()
+            },
+        );
+        let err = timestamp_entry(&mut make_test_entry(), &Rfc3161Authority::new(url))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("does not answer this request"), "{err}");
+    }
+
+    #[test]
+    fn test_generalized_time() {
+        let time = |text: &str| {
+            generalized_time(&Any::new(Tag::GeneralizedTime, text.as_bytes()).unwrap())
+        };
+        assert_eq!(
+            time("20261017120000Z").unwrap().to_rfc3339(),
+            "2026-10-17T12:00:00+00:00"
+        );
+        assert_eq!(
+            time("20261017120000.25Z").unwrap().timestamp_millis() % 1000,
+            250
+        );
+        assert_eq!(time("20261017120000+0100"), None);
+    }
+}
@@ -1,8 +1,15 @@
 //! Ed25519 signature generation and verification.
+//!
+//! Keys are either held in memory or kept on a device behind an
+//! [`EntrySigner`].
 
-use crate::types::{ProvenanceEntry, Signature};
+use crate::types::{KeyRotation, ProvenanceEntry, Signature};
 use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
 use sha3::{Digest, Sha3_256};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use thiserror::Error;
 
 /// Generate a new Ed25519 keypair.
 ///
@@ -25,11 +32,14 @@ pub fn keygen() -> ([u8; 32], [u8; 32]) {
 /// 1. Serializing the entry to canonical JSON (sorted keys, no whitespace)
 /// 2. Computing SHA3-256 of the serialized bytes
 ///
-/// The signatures field is excluded from the hash to avoid circular dependencies.
-fn entry_hash(entry: &ProvenanceEntry) -> [u8; 32] {
+/// The signatures, keyless signatures and timestamp token are excluded from the hash to avoid
+/// circular dependencies, so an entry can be signed and stamped in any order.
+pub(crate) fn entry_hash(entry: &ProvenanceEntry) -> [u8; 32] {
     // Create a copy without signatures for hashing
     let mut hashable = entry.clone();
     hashable.signatures.clear();
+    hashable.keyless.clear();
+    hashable.timestamp_token = None;
 
     // Serialize to canonical JSON (sorted keys)
     let json = serde_json::to_string(&hashable).expect("entry serialization failed");
@@ -60,6 +70,206 @@ pub fn sign_entry(entry: &ProvenanceEntry, private_key: &[u8; 32], signer_id: &s
     }
 }
 
+/// Errors that can occur while signing with a [`EntrySigner`].
+#[derive(Debug, Error)]
+pub enum SignerError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Signing device failed: {0}")]
+    Device(String),
+
+    #[error("Signature does not verify against the expected public key")]
+    KeyMismatch,
+}
+
+/// A source of Ed25519 signatures.
+///
+/// Implementations need not expose the private key, so it can stay on a
+/// hardware token instead of the machine that records provenance.
+pub trait EntrySigner {
+    /// Public key of the signing key, if known.
+    fn public_key(&self) -> Option<[u8; 32]>;
+
+    /// Ed25519 signature over `hash`.
+    fn sign_hash(&self, hash: &[u8; 32]) -> Result<[u8; 64], SignerError>;
+}
+
+/// Signer holding an Ed25519 private key in memory.
+pub struct SoftwareSigner {
+    key: SigningKey,
+}
+
+impl SoftwareSigner {
+    pub fn new(private_key: &[u8; 32]) -> Self {
+        Self {
+            key: SigningKey::from_bytes(private_key),
+        }
+    }
+}
+
+impl EntrySigner for SoftwareSigner {
+    fn public_key(&self) -> Option<[u8; 32]> {
+        Some(self.key.verifying_key().to_bytes())
+    }
+
+    fn sign_hash(&self, hash: &[u8; 32]) -> Result<[u8; 64], SignerError> {
+        Ok(self.key.sign(hash).to_bytes())
+    }
+}
+
+/// Signer that runs an external program, such as a PKCS#11 or vendor tool
+/// talking to a hardware token.
+///
+/// The program receives the 32-byte hash on stdin and must write the
+/// signature to stdout, either as 64 raw bytes or hex-encoded with an
+/// optional `ed25519:` prefix.
+#[derive(Clone)]
+pub struct CommandSigner {
+    program: String,
+    args: Vec<String>,
+    /// Environment variables set for the program only, which may be secret
+    env: Vec<(String, String)>,
+    public_key: Option<[u8; 32]>,
+}
+
+/// Environment variable `pkcs11-tool` reads the token PIN from.
+const PKCS11_PIN_VAR: &str = "Z1_PKCS11_PIN";
+
+impl CommandSigner {
+    pub fn new(program: impl Into<String>, args: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            program: program.into(),
+            args: args.into_iter().collect(),
+            env: Vec::new(),
+            public_key: None,
+        }
+    }
+
+    /// Signer for an Ed25519 key on a PKCS#11 token (e.g. a YubiKey through
+    /// `libykcs11`), using OpenSC's `pkcs11-tool` and the EdDSA mechanism.
+    ///
+    /// The PIN reaches `pkcs11-tool` through its environment (`--pin
+    /// env:Z1_PKCS11_PIN`), never its command line, which other users of the
+    /// machine can read. Without a PIN the token is used without logging in.
+    pub fn pkcs11(module: &Path, key_id: &str, pin: Option<&str>) -> Self {
+        let mut args = vec![
+            "--module".to_string(),
+            module.display().to_string(),
+            "--sign".to_string(),
+            "--mechanism".to_string(),
+            "EDDSA".to_string(),
+            "--id".to_string(),
+            key_id.to_string(),
+        ];
+        if pin.is_some() {
+            args.extend([
+                "--login".to_string(),
+                "--pin".to_string(),
+                format!("env:{PKCS11_PIN_VAR}"),
+            ]);
+        }
+        let signer = Self::new("pkcs11-tool", args);
+        match pin {
+            Some(pin) => signer.with_env(PKCS11_PIN_VAR, pin),
+            None => signer,
+        }
+    }
+
+    /// Set the environment variable `name` for the program.
+    pub fn with_env(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((name.into(), value.into()));
+        self
+    }
+
+    /// Check every signature against `public_key`, so a wrong slot or
+    /// device fails instead of producing an unverifiable signature.
+    pub fn with_public_key(mut self, public_key: [u8; 32]) -> Self {
+        self.public_key = Some(public_key);
+        self
+    }
+}
+
+/// Debug output leaves out the values of environment variables.
+impl std::fmt::Debug for CommandSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let env: Vec<&str> = self.env.iter().map(|(name, _)| name.as_str()).collect();
+        f.debug_struct("CommandSigner")
+            .field("program", &self.program)
+            .field("args", &self.args)
+            .field("env", &env)
+            .field("public_key", &self.public_key.map(hex::encode))
+            .finish()
+    }
+}
+
+impl EntrySigner for CommandSigner {
+    fn public_key(&self) -> Option<[u8; 32]> {
+        self.public_key
+    }
+
+    fn sign_hash(&self, hash: &[u8; 32]) -> Result<[u8; 64], SignerError> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .envs(self.env.iter().map(|(name, value)| (name, value)))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(hash)?;
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(SignerError::Device(format!(
+                "{} exited with {}",
+                self.program, output.status
+            )));
+        }
+
+        let signature: [u8; 64] = match <[u8; 64]>::try_from(output.stdout.as_slice()) {
+            Ok(raw) => raw,
+            Err(_) => {
+                let text = String::from_utf8_lossy(&output.stdout);
+                let text = text.trim();
+                hex::decode(text.strip_prefix("ed25519:").unwrap_or(text))
+                    .ok()
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .ok_or_else(|| {
+                        SignerError::Device(format!(
+                            "{} did not print an Ed25519 signature",
+                            self.program
+                        ))
+                    })?
+            }
+        };
+        if let Some(public_key) = &self.public_key {
+            let encoded = Signature {
+                by: String::new(),
+                sig: format!("ed25519:{}", hex::encode(signature)),
+            };
+            if !verify_hash(hash, &encoded, public_key) {
+                return Err(SignerError::KeyMismatch);
+            }
+        }
+        Ok(signature)
+    }
+}
+
+/// Sign a provenance entry with any [`EntrySigner`].
+pub fn sign_entry_with(
+    entry: &ProvenanceEntry,
+    signer: &dyn EntrySigner,
+    signer_id: &str,
+) -> Result<Signature, SignerError> {
+    let signature = signer.sign_hash(&entry_hash(entry))?;
+    Ok(Signature {
+        by: signer_id.to_string(),
+        sig: format!("ed25519:{}", hex::encode(signature)),
+    })
+}
+
 /// Verify a signature on a provenance entry.
 ///
 /// # Arguments
@@ -74,6 +284,11 @@ pub fn verify_signature(
     signature: &Signature,
     public_key: &[u8; 32],
 ) -> bool {
+    verify_hash(&entry_hash(entry), signature, public_key)
+}
+
+/// Verify an Ed25519 signature over a 32-byte hash.
+pub(crate) fn verify_hash(hash: &[u8; 32], signature: &Signature, public_key: &[u8; 32]) -> bool {
     // Parse the signature (expect "ed25519:..." format)
     let sig_hex = match signature.sig.strip_prefix("ed25519:") {
         Some(hex_str) => hex_str,
@@ -99,8 +314,58 @@ pub fn verify_signature(
         Err(_) => return false,
     };
 
-    let hash = entry_hash(entry);
-    verifying_key.verify(&hash, &signature_obj).is_ok()
+    verifying_key.verify(hash, &signature_obj).is_ok()
+}
+
+/// Hash of the statement a rotation endorsement signs.
+fn rotation_hash(signer: &str, old_key: &str, new_key: &str) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    for part in ["z1-key-rotation", signer, old_key, new_key] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.finalize().into()
+}
+
+/// Endorse `new_public_key` as the next key of `signer`, signed with the
+/// signer's current private key.
+pub fn endorse_rotation(
+    signer: &str,
+    old_private_key: &[u8; 32],
+    new_public_key: &[u8; 32],
+) -> KeyRotation {
+    let signing_key = SigningKey::from_bytes(old_private_key);
+    let old_key = hex::encode(signing_key.verifying_key().to_bytes());
+    let new_key = hex::encode(new_public_key);
+    let signature = signing_key.sign(&rotation_hash(signer, &old_key, &new_key));
+
+    KeyRotation {
+        signer: signer.to_string(),
+        old_key,
+        new_key,
+        endorsement: format!("ed25519:{}", hex::encode(signature.to_bytes())),
+    }
+}
+
+/// Check that a rotation is endorsed by its `old_key`.
+pub fn verify_rotation(rotation: &KeyRotation) -> bool {
+    let Some(old_key) = decode_key(&rotation.old_key) else {
+        return false;
+    };
+    if decode_key(&rotation.new_key).is_none() {
+        return false;
+    }
+    let endorsement = Signature {
+        by: rotation.signer.clone(),
+        sig: rotation.endorsement.clone(),
+    };
+    let hash = rotation_hash(&rotation.signer, &rotation.old_key, &rotation.new_key);
+    verify_hash(&hash, &endorsement, &old_key)
+}
+
+/// Decode a hex-encoded 32-byte public key.
+pub(crate) fn decode_key(hex_key: &str) -> Option<[u8; 32]> {
+    hex::decode(hex_key).ok()?.try_into().ok()
 }
 
 #[cfg(test)]
@@ -112,14 +377,21 @@ mod tests {
         ProvenanceEntry {
             entry_id: "cell:test@v1".to_string(),
             prev: None,
+            merged: vec![],
             actor: "test-actor".to_string(),
             model: "test-model".to_string(),
             prompt_sha3: "abc123".to_string(),
             prompt_excerpt: "test prompt".to_string(),
+            redaction: None,
             tools: vec![],
             diff_sha3: "def456".to_string(),
+            cells: vec![],
             timestamp: Utc::now(),
             signatures: vec![],
+            keyless: vec![],
+            rotation: None,
+            checkpoint: None,
+            timestamp_token: None,
         }
     }
 
@@ -214,4 +486,99 @@ mod tests {
         // Verification should fail because the signature doesn't match the entry
         assert!(!verify_signature(&entry, &wrong_sig, &valid_pub));
     }
+
+    #[test]
+    fn test_rotation_endorsed_by_old_key() {
+        let (old_private, old_public) = keygen();
+        let (_, new_public) = keygen();
+
+        let rotation = endorse_rotation("dev:alice", &old_private, &new_public);
+        assert_eq!(rotation.old_key, hex::encode(old_public));
+        assert!(verify_rotation(&rotation));
+
+        let mut forged = rotation.clone();
+        forged.new_key = hex::encode(keygen().1);
+        assert!(!verify_rotation(&forged));
+
+        let mut renamed = rotation;
+        renamed.signer = "dev:mallory".to_string();
+        assert!(!verify_rotation(&renamed));
+    }
+
+    #[test]
+    fn test_software_signer_matches_sign_entry() {
+        let (private_key, public_key) = keygen();
+        let entry = make_test_entry();
+        let signer = SoftwareSigner::new(&private_key);
+
+        let signature = sign_entry_with(&entry, &signer, "dev:alice").unwrap();
+        assert_eq!(signature, sign_entry(&entry, &private_key, "dev:alice"));
+        assert_eq!(signer.public_key(), Some(public_key));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_signer_checks_device_output() {
+        let (private_key, public_key) = keygen();
+        let entry = make_test_entry();
+        let expected = sign_entry(&entry, &private_key, "dev:alice");
+
+        // Stands in for a token that signs with `private_key`
+        let script = format!("cat >/dev/null; echo {}", expected.sig);
+        let device = CommandSigner::new("sh", ["-c".to_string(), script]);
+        let signature = sign_entry_with(
+            &entry,
+            &device.clone().with_public_key(public_key),
+            "dev:alice",
+        )
+        .unwrap();
+        assert_eq!(signature, expected);
+        assert!(verify_signature(&entry, &signature, &public_key));
+
+        let (_, other_key) = keygen();
+        assert!(matches!(
+            sign_entry_with(&entry, &device.with_public_key(other_key), "dev:alice"),
+            Err(SignerError::KeyMismatch)
+        ));
+        let failing = CommandSigner::new(
+            "sh",
+            ["-c".to_string(), "cat >/dev/null; exit 3".to_string()],
+        );
+        assert!(matches!(
+            sign_entry_with(&entry, &failing, "dev:alice"),
+            Err(SignerError::Device(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pkcs11_pin_stays_off_the_command_line() {
+        let signer =
+            CommandSigner::pkcs11(Path::new("/usr/lib/libykcs11.so"), "01", Some("123456"));
+        assert!(signer.args.ends_with(&[
+            "--login".to_string(),
+            "--pin".to_string(),
+            "env:Z1_PKCS11_PIN".to_string()
+        ]));
+        assert!(!signer.args.iter().any(|arg| arg.contains("123456")));
+        assert!(!format!("{signer:?}").contains("123456"));
+        let signer = CommandSigner::pkcs11(Path::new("/usr/lib/libykcs11.so"), "01", None);
+        assert!(!signer.args.iter().any(|arg| arg == "--login"));
+
+        // The variable reaches the program
+        let (private_key, public_key) = keygen();
+        let entry = make_test_entry();
+        let expected = sign_entry(&entry, &private_key, "dev:alice");
+        let script = format!(
+            "cat >/dev/null; [ \"$Z1_PKCS11_PIN\" = 123456 ] && echo {}",
+            expected.sig
+        );
+        let device = CommandSigner::new("sh", ["-c".to_string(), script])
+            .with_env(PKCS11_PIN_VAR, "123456")
+            .with_public_key(public_key);
+        assert_eq!(
+            sign_entry_with(&entry, &device, "dev:alice").unwrap(),
+            expected
+        );
+    }
 }
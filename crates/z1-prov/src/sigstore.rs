@@ -0,0 +1,831 @@
+//! Sigstore keyless signing: Fulcio certificates and Rekor log entries.
+//!
+//! [`FulcioAuthority`] exchanges an OIDC identity token for an X.509
+//! certificate chain over an ephemeral P-256 key, and [`RekorLog`] records
+//! the signature as a `hashedrekord` entry, returning its signed entry
+//! timestamp and inclusion proof. Both speak the public APIs of
+//! [`FULCIO_URL`] and [`REKOR_URL`] and of any deployment of their own.
+//!
+//! Verification needs no network: the chain is checked against trusted root
+//! certificates, and the log entry against the log's key, the signature it
+//! records, and the checkpoint the log signed for its tree.
+
+use crate::keyless::{
+    CertificateAuthority, EphemeralKey, Identity, KeyAlgorithm, KeylessError, KeylessTrust,
+    TransparencyLog,
+};
+use crate::types::{InclusionProof, LogEntry, SigningCertificate};
+use crate::{http, x509};
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use der::asn1::Utf8StringRef;
+use der::oid::db::rfc5280;
+use der::oid::ObjectIdentifier;
+use der::pem::LineEnding;
+use der::{Decode, Encode, EncodePem};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use x509_cert::ext::pkix::name::GeneralName;
+use x509_cert::ext::pkix::SubjectAltName;
+use x509_cert::spki::SubjectPublicKeyInfoOwned;
+use x509_cert::Certificate;
+
+/// Sigstore's public-good certificate authority.
+pub const FULCIO_URL: &str = "https://fulcio.sigstore.dev";
+
+/// Sigstore's public-good transparency log.
+pub const REKOR_URL: &str = "https://rekor.sigstore.dev";
+
+/// Fulcio extension naming the OIDC issuer, a DER UTF8String.
+const OIDC_ISSUER: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.8");
+
+/// Earlier form of [`OIDC_ISSUER`], the bare issuer URL.
+const OIDC_ISSUER_V1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.1");
+
+/// A client of a Fulcio certificate authority.
+pub struct FulcioAuthority {
+    url: String,
+    token: String,
+}
+
+impl FulcioAuthority {
+    /// Fulcio at `url`, authenticating with the OIDC identity `token`.
+    pub fn new(url: impl Into<String>, token: impl Into<String>) -> Self {
+        Self {
+            url: url.into().trim_end_matches('/').to_string(),
+            token: token.into(),
+        }
+    }
+
+    /// Identity the token asserts: its email, or else its subject, and its
+    /// issuer. Fulcio certifies the identity it reads from the token itself.
+    pub fn identity(&self) -> Result<Identity, KeylessError> {
+        let claims = self
+            .token
+            .split('.')
+            .nth(1)
+            .and_then(|claims| URL_SAFE_NO_PAD.decode(claims.trim_end_matches('=')).ok())
+            .and_then(|claims| serde_json::from_slice::<Value>(&claims).ok())
+            .ok_or_else(|| self.error("the identity token is not a JWT".into()))?;
+        let claim = |name: &str| claims.get(name).and_then(Value::as_str);
+        let subject = claim("email").or_else(|| claim("sub"));
+        match (subject, claim("iss")) {
+            (Some(subject), Some(issuer)) => Ok(Identity {
+                subject: subject.to_string(),
+                issuer: issuer.to_string(),
+            }),
+            _ => Err(self.error("the identity token has no subject or issuer".into())),
+        }
+    }
+
+    fn error(&self, reason: String) -> KeylessError {
+        KeylessError::Authority(self.url.clone(), reason)
+    }
+}
+
+impl CertificateAuthority for FulcioAuthority {
+    fn algorithm(&self) -> KeyAlgorithm {
+        KeyAlgorithm::EcdsaP256
+    }
+
+    fn issue(
+        &self,
+        identity: &Identity,
+        key: &EphemeralKey,
+    ) -> Result<Vec<SigningCertificate>, KeylessError> {
+        let EphemeralKey::EcdsaP256(signing_key) = key else {
+            return Err(self.error("only ECDSA P-256 keys are certified".into()));
+        };
+        let public_key = {
+            use p256::pkcs8::EncodePublicKey;
+            signing_key
+                .verifying_key()
+                .to_public_key_pem(LineEnding::LF)
+                .map_err(|err| self.error(err.to_string()))?
+        };
+        // Proof of possession: the key's signature over the token's subject
+        let proof = signature_bytes(&key.sign(identity.subject.as_bytes()))
+            .ok_or_else(|| self.error("the key did not sign".into()))?;
+        let request = json!({
+            "credentials": { "oidcIdentityToken": self.token },
+            "publicKeyRequest": {
+                "publicKey": { "algorithm": "ECDSA", "content": public_key },
+                "proofOfPossession": STANDARD.encode(proof),
+            },
+        });
+        let response = http::post_json(&format!("{}/api/v2/signingCert", self.url), &request)
+            .map_err(|reason| self.error(reason))?;
+        let pems = [
+            "signedCertificateEmbeddedSct",
+            "signedCertificateDetachedSct",
+        ]
+        .iter()
+        .find_map(|key| response[key]["chain"]["certificates"].as_array())
+        .ok_or_else(|| self.error("the response has no certificate chain".into()))?;
+        let mut chain = Vec::new();
+        for pem in pems {
+            let pem = pem
+                .as_str()
+                .ok_or_else(|| self.error("a certificate is not PEM".into()))?;
+            for cert in x509::parse_pem(pem).map_err(|reason| self.error(reason))? {
+                chain.push(signing_certificate(&cert).map_err(|reason| self.error(reason))?);
+            }
+        }
+        Ok(chain)
+    }
+}
+
+/// A client of a Rekor transparency log.
+pub struct RekorLog {
+    url: String,
+}
+
+impl RekorLog {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn error(&self, reason: String) -> KeylessError {
+        KeylessError::Log(self.url.clone(), reason)
+    }
+}
+
+impl TransparencyLog for RekorLog {
+    fn id(&self) -> &str {
+        &self.url
+    }
+
+    fn submit(
+        &self,
+        message: &[u8],
+        sig: &str,
+        leaf: &SigningCertificate,
+    ) -> Result<LogEntry, KeylessError> {
+        let record = hashed_rekord(message, sig, leaf).map_err(|reason| self.error(reason))?;
+        let response = http::post_json(&format!("{}/api/v1/log/entries", self.url), &record)
+            .map_err(|reason| self.error(reason))?;
+        log_entry(&response).ok_or_else(|| self.error("the response is not a log entry".into()))
+    }
+}
+
+/// The entry of a Rekor response, keyed by its UUID.
+fn log_entry(response: &Value) -> Option<LogEntry> {
+    let (_uuid, entry) = response.as_object()?.iter().next()?;
+    let verification = &entry["verification"];
+    let proof = &verification["inclusionProof"];
+    let set = STANDARD
+        .decode(verification["signedEntryTimestamp"].as_str()?)
+        .ok()?;
+    Some(LogEntry {
+        log_id: entry["logID"].as_str()?.to_string(),
+        index: entry["logIndex"].as_u64()?,
+        integrated_time: DateTime::from_timestamp(entry["integratedTime"].as_i64()?, 0)?,
+        set: format!("ecdsa-p256:{}", hex::encode(set)),
+        body: Some(entry["body"].as_str()?.to_string()),
+        inclusion_proof: Some(InclusionProof {
+            index: proof["logIndex"].as_u64()?,
+            tree_size: proof["treeSize"].as_u64()?,
+            root_hash: proof["rootHash"].as_str()?.to_string(),
+            hashes: proof["hashes"]
+                .as_array()?
+                .iter()
+                .map(|hash| hash.as_str().map(str::to_string))
+                .collect::<Option<_>>()?,
+            checkpoint: proof["checkpoint"].as_str()?.to_string(),
+        }),
+    })
+}
+
+/// The `hashedrekord` entry recording `sig`, by the key `leaf` certifies,
+/// over `message`.
+fn hashed_rekord(message: &[u8], sig: &str, leaf: &SigningCertificate) -> Result<Value, String> {
+    let der = leaf
+        .x509
+        .as_deref()
+        .ok_or("Rekor records signatures by X.509 certificates only")?;
+    let cert = x509::parse_der(
+        &STANDARD
+            .decode(der)
+            .map_err(|_| "certificate is not base64")?,
+    )?;
+    let pem = cert
+        .to_pem(LineEnding::LF)
+        .map_err(|err| format!("certificate does not encode: {err}"))?;
+    let sig = signature_bytes(sig).ok_or("signature is not an ECDSA signature")?;
+    Ok(json!({
+        "apiVersion": "0.0.1",
+        "kind": "hashedrekord",
+        "spec": {
+            "data": {
+                "hash": { "algorithm": "sha256", "value": hex::encode(Sha256::digest(message)) },
+            },
+            "signature": {
+                "content": STANDARD.encode(sig),
+                "publicKey": { "content": STANDARD.encode(pem) },
+            },
+        },
+    }))
+}
+
+/// DER bytes of an "ecdsa-p256:" signature.
+fn signature_bytes(sig: &str) -> Option<Vec<u8>> {
+    hex::decode(sig.strip_prefix("ecdsa-p256:")?).ok()
+}
+
+/// The fields of an X.509 certificate as a [`SigningCertificate`]: a leaf's
+/// subject is the identity in its subject alternative name and its issuer
+/// the OIDC issuer Fulcio recorded; an authority's are its names.
+pub(crate) fn signing_certificate(cert: &Certificate) -> Result<SigningCertificate, String> {
+    let tbs = &cert.tbs_certificate;
+    let identity = x509::extension(cert, &rfc5280::ID_CE_SUBJECT_ALT_NAME)
+        .and_then(|value| SubjectAltName::from_der(value).ok())
+        .and_then(|names| {
+            names.0.into_iter().find_map(|name| match name {
+                GeneralName::Rfc822Name(name) => Some(name.to_string()),
+                GeneralName::UniformResourceIdentifier(name) => Some(name.to_string()),
+                _ => None,
+            })
+        });
+    let issuer = x509::extension(cert, &OIDC_ISSUER)
+        .and_then(|value| Utf8StringRef::from_der(value).ok())
+        .map(|issuer| issuer.to_string())
+        .or_else(|| {
+            x509::extension(cert, &OIDC_ISSUER_V1)
+                .and_then(|value| String::from_utf8(value.to_vec()).ok())
+        });
+    let (not_before, not_after) = x509::validity(cert);
+    let public_key = tbs
+        .subject_public_key_info
+        .to_der()
+        .map_err(|err| format!("certificate key does not encode: {err}"))?;
+    let der = cert
+        .to_der()
+        .map_err(|err| format!("certificate does not encode: {err}"))?;
+    Ok(SigningCertificate {
+        subject: identity.unwrap_or_else(|| tbs.subject.to_string()),
+        issuer: issuer.unwrap_or_else(|| tbs.issuer.to_string()),
+        public_key: hex::encode(public_key),
+        not_before,
+        not_after,
+        ca: tbs.issuer.to_string(),
+        sig: format!("x509:{}", hex::encode(cert.signature.raw_bytes())),
+        x509: Some(STANDARD.encode(der)),
+    })
+}
+
+/// Check an X.509 `chain` at `time` against the trusted root certificates,
+/// that every certificate's fields are those of its encoding, and that the
+/// leaf is for code signing.
+pub(crate) fn verify_chain(
+    chain: &[SigningCertificate],
+    trust: &KeylessTrust,
+    time: DateTime<Utc>,
+) -> Result<(), String> {
+    let mut certs = Vec::new();
+    for cert in chain {
+        let der = STANDARD
+            .decode(cert.x509.as_deref().unwrap_or_default())
+            .map_err(|_| format!("certificate for {} is not base64", cert.subject))?;
+        let parsed = x509::parse_der(&der)?;
+        if signing_certificate(&parsed)? != *cert {
+            return Err(format!(
+                "certificate for {} does not match its X.509 encoding",
+                cert.subject
+            ));
+        }
+        certs.push(parsed);
+    }
+    let mut roots = Vec::new();
+    for pem in trust
+        .roots
+        .values()
+        .filter(|root| root.contains("BEGIN CERTIFICATE"))
+    {
+        roots.extend(x509::parse_pem(pem)?);
+    }
+    x509::verify_chain(&certs, &roots, time)?;
+    if !x509::allows_code_signing(&certs[0]) {
+        return Err(format!(
+            "certificate for {} is not for code signing",
+            chain[0].subject
+        ));
+    }
+    Ok(())
+}
+
+/// Check a Rekor entry recording `sig`, by the key `leaf` certifies, over
+/// `message`: its signed entry timestamp, that its body records this
+/// signature, and its inclusion proof against the checkpoint the log signed.
+pub(crate) fn verify_rekor_entry(
+    message: &[u8],
+    sig: &str,
+    leaf: &SigningCertificate,
+    entry: &LogEntry,
+    trust: &KeylessTrust,
+) -> Result<(), String> {
+    let name = format!("log entry {} of {}", entry.index, entry.log_id);
+    let key = trust
+        .logs
+        .get(&entry.log_id)
+        .filter(|key| key.contains("BEGIN PUBLIC KEY"))
+        .ok_or_else(|| format!("transparency log {} is not trusted", entry.log_id))?;
+    let key = x509::parse_public_key_pem(key)?;
+    // Rekor names a log by the hash of its key
+    let key_der = key
+        .to_der()
+        .map_err(|err| format!("log key does not encode: {err}"))?;
+    if hex::encode(Sha256::digest(&key_der)) != entry.log_id {
+        return Err(format!(
+            "key of transparency log {} does not match its ID",
+            entry.log_id
+        ));
+    }
+
+    let body = entry.body.as_deref().unwrap_or_default();
+    let payload = format!(
+        r#"{{"body":"{body}","integratedTime":{},"logID":"{}","logIndex":{}}}"#,
+        entry.integrated_time.timestamp(),
+        entry.log_id,
+        entry.index
+    );
+    let set = signature_bytes(&entry.set).ok_or_else(|| format!("{name} has a bad timestamp"))?;
+    x509::verify_signature(&key, &x509::ECDSA_WITH_SHA_256, payload.as_bytes(), &set)
+        .map_err(|_| format!("{name} does not verify"))?;
+
+    let body = STANDARD
+        .decode(body)
+        .map_err(|_| format!("{name} has a body that is not base64"))?;
+    let recorded: Value =
+        serde_json::from_slice(&body).map_err(|_| format!("{name} has a body that is not JSON"))?;
+    if recorded != hashed_rekord(message, sig, leaf)? {
+        return Err(format!("{name} records another signature"));
+    }
+
+    let proof = entry
+        .inclusion_proof
+        .as_ref()
+        .ok_or_else(|| format!("{name} has no inclusion proof"))?;
+    let hashes = proof
+        .hashes
+        .iter()
+        .map(|hash| hex::decode(hash).ok()?.try_into().ok())
+        .collect::<Option<Vec<[u8; 32]>>>()
+        .ok_or_else(|| format!("{name} has a malformed inclusion proof"))?;
+    let root = root_from_inclusion_proof(proof.index, proof.tree_size, leaf_hash(&body), &hashes)
+        .filter(|root| hex::encode(root) == proof.root_hash)
+        .ok_or_else(|| format!("{name} is not included in the tree its proof names"))?;
+    let (size, signed_root) = verify_checkpoint(&proof.checkpoint, &key)
+        .map_err(|reason| format!("{name} has a bad checkpoint: {reason}"))?;
+    if size != proof.tree_size || signed_root != root {
+        return Err(format!(
+            "{name} has a checkpoint for another tree than its proof"
+        ));
+    }
+    Ok(())
+}
+
+/// Check the log's signature on a checkpoint, a signed note whose text
+/// names the log, the tree size and the root hash, and return the size and
+/// root it commits to.
+fn verify_checkpoint(
+    checkpoint: &str,
+    key: &SubjectPublicKeyInfoOwned,
+) -> Result<(u64, [u8; 32]), String> {
+    let (text, signatures) = checkpoint.split_once("\n\n").ok_or("no signature block")?;
+    let mut lines = text.lines().skip(1);
+    let size = lines
+        .next()
+        .and_then(|size| size.parse().ok())
+        .ok_or("no tree size")?;
+    let root = lines
+        .next()
+        .and_then(|root| STANDARD.decode(root).ok())
+        .and_then(|root| root.try_into().ok())
+        .ok_or("no root hash")?;
+    // Signature lines are "— <name> <base64 of key hint and signature>"
+    let signed = format!("{text}\n");
+    let verified = signatures
+        .lines()
+        .filter_map(|line| line.strip_prefix("\u{2014} ")?.rsplit_once(' '))
+        .filter_map(|(_, sig)| STANDARD.decode(sig).ok())
+        .any(|sig| {
+            sig.len() > 4
+                && x509::verify_signature(
+                    key,
+                    &x509::ECDSA_WITH_SHA_256,
+                    signed.as_bytes(),
+                    &sig[4..],
+                )
+                .is_ok()
+        });
+    if !verified {
+        return Err("not signed by the log".into());
+    }
+    Ok((size, root))
+}
+
+/// RFC 6962 hash of a leaf.
+fn leaf_hash(data: &[u8]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update([0])
+        .chain_update(data)
+        .finalize()
+        .into()
+}
+
+/// RFC 6962 hash of an interior node.
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update([1])
+        .chain_update(left)
+        .chain_update(right)
+        .finalize()
+        .into()
+}
+
+/// Root of a tree of `size` leaves reached from the leaf at `index` along
+/// `proof`, as RFC 9162 (section 2.1.3.2) computes it.
+fn root_from_inclusion_proof(
+    index: u64,
+    size: u64,
+    leaf: [u8; 32],
+    proof: &[[u8; 32]],
+) -> Option<[u8; 32]> {
+    if index >= size {
+        return None;
+    }
+    // Below `inner` levels the path has siblings on both sides; above it the
+    // leaf is on the tree's right border
+    let inner = (u64::BITS - (index ^ (size - 1)).leading_zeros()) as usize;
+    let border = (index >> inner).count_ones() as usize;
+    if proof.len() != inner + border {
+        return None;
+    }
+    let mut hash = leaf;
+    for (level, sibling) in proof[..inner].iter().enumerate() {
+        hash = if (index >> level) & 1 == 0 {
+            node_hash(&hash, sibling)
+        } else {
+            node_hash(sibling, &hash)
+        };
+    }
+    for sibling in &proof[inner..] {
+        hash = node_hash(sibling, &hash);
+    }
+    Some(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyless::{sign_keyless, verify_keyless_signature};
+    use crate::testing::{p256_key, p256_spki, serve, TestCa};
+    use crate::types::{KeylessSignature, ProvenanceEntry};
+    use der::asn1::Ia5String;
+    use der::{DecodePem, EncodeValue, FixedTag, Length, Tag, Writer};
+    use p256::ecdsa::signature::Signer;
+    use p256::pkcs8::EncodePublicKey;
+    use std::sync::{Arc, Mutex};
+    use x509_cert::ext::pkix::ExtendedKeyUsage;
+    use x509_cert::ext::AsExtension;
+
+    const EMAIL: &str = "dev@example.com";
+    const ISSUER: &str = "https://accounts.example.com";
+
+    /// Fulcio's OIDC issuer extension.
+    struct OidcIssuer(&'static str);
+
+    impl der::oid::AssociatedOid for OidcIssuer {
+        const OID: ObjectIdentifier = OIDC_ISSUER;
+    }
+
+    impl FixedTag for OidcIssuer {
+        const TAG: Tag = Tag::Utf8String;
+    }
+
+    impl EncodeValue for OidcIssuer {
+        fn value_len(&self) -> der::Result<Length> {
+            Utf8StringRef::new(self.0)?.value_len()
+        }
+
+        fn encode_value(&self, writer: &mut impl Writer) -> der::Result<()> {
+            Utf8StringRef::new(self.0)?.encode_value(writer)
+        }
+    }
+
+    impl AsExtension for OidcIssuer {
+        fn critical(&self, _: &x509_cert::name::Name, _: &[x509_cert::ext::Extension]) -> bool {
+            false
+        }
+    }
+
+    fn token(email: &str) -> String {
+        let claims = json!({ "iss": ISSUER, "sub": "1234", "email": email });
+        format!(
+            "{}.{}.sig",
+            URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256"}"#),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        )
+    }
+
+    /// A Fulcio that certifies keys whose proof of possession verifies for
+    /// the email of the token.
+    fn serve_fulcio(ca: TestCa) -> String {
+        serve(move |path, body| {
+            assert_eq!(path, "/api/v2/signingCert");
+            let request: Value = serde_json::from_slice(body).unwrap();
+            let token = request["credentials"]["oidcIdentityToken"]
+                .as_str()
+                .unwrap();
+            let email = FulcioAuthority::new("", token).identity().unwrap().subject;
+            let key = &request["publicKeyRequest"];
+            let spki = SubjectPublicKeyInfoOwned::from_pem(
+                key["publicKey"]["content"].as_str().unwrap().as_bytes(),
+            )
+            .unwrap();
+            let proof = STANDARD
+                .decode(key["proofOfPossession"].as_str().unwrap())
+                .unwrap();
+            if x509::verify_signature(&spki, &x509::ECDSA_WITH_SHA_256, email.as_bytes(), &proof)
+                .is_err()
+            {
+                return (400, b"bad proof of possession".to_vec());
+            }
+            let leaf = ca.issue(spki, Utc::now(), chrono::Duration::minutes(10), |cert| {
+                let san = GeneralName::Rfc822Name(Ia5String::new(&email).unwrap());
+                cert.add_extension(&SubjectAltName(vec![san])).unwrap();
+                cert.add_extension(&OidcIssuer(ISSUER)).unwrap();
+                let usage = ExtendedKeyUsage(vec![rfc5280::ID_KP_CODE_SIGNING]);
+                cert.add_extension(&usage).unwrap();
+            });
+            let chain = json!({ "certificates": ca.chain_pems(&leaf) });
+            let response = json!({ "signedCertificateEmbeddedSct": { "chain": chain } });
+            (201, response.to_string().into_bytes())
+        })
+    }
+
+    /// Merkle tree hash of `leaves` (RFC 6962, section 2.1).
+    fn tree_hash(leaves: &[[u8; 32]]) -> [u8; 32] {
+        if leaves.len() == 1 {
+            return leaves[0];
+        }
+        let split = leaves.len().next_power_of_two() / 2;
+        node_hash(&tree_hash(&leaves[..split]), &tree_hash(&leaves[split..]))
+    }
+
+    /// Audit path of the leaf at `index` (RFC 6962, section 2.1.1).
+    fn audit_path(index: usize, leaves: &[[u8; 32]]) -> Vec<[u8; 32]> {
+        if leaves.len() == 1 {
+            return vec![];
+        }
+        let split = leaves.len().next_power_of_two() / 2;
+        let (mut path, sibling) = if index < split {
+            (audit_path(index, &leaves[..split]), &leaves[split..])
+        } else {
+            (
+                audit_path(index - split, &leaves[split..]),
+                &leaves[..split],
+            )
+        };
+        path.push(tree_hash(sibling));
+        path
+    }
+
+    struct Rekor {
+        url: String,
+        key: p256::ecdsa::SigningKey,
+    }
+
+    impl Rekor {
+        fn log_id(&self) -> String {
+            let der = self.key.verifying_key().to_public_key_der().unwrap();
+            hex::encode(Sha256::digest(der.as_bytes()))
+        }
+
+        fn key_pem(&self) -> String {
+            self.key
+                .verifying_key()
+                .to_public_key_pem(LineEnding::LF)
+                .unwrap()
+        }
+    }
+
+    /// A Rekor holding a few earlier entries, answering with a signed entry
+    /// timestamp, an inclusion proof and a signed checkpoint.
+    fn serve_rekor() -> Rekor {
+        let key = p256_key();
+        let signer = key.clone();
+        let log_id = hex::encode(Sha256::digest(
+            key.verifying_key().to_public_key_der().unwrap().as_bytes(),
+        ));
+        let leaves = Arc::new(Mutex::new(
+            (0u8..5).map(|i| leaf_hash(&[i])).collect::<Vec<_>>(),
+        ));
+        let url = serve(move |path, body| {
+            assert_eq!(path, "/api/v1/log/entries");
+            let mut leaves = leaves.lock().unwrap();
+            let index = leaves.len();
+            leaves.push(leaf_hash(body));
+            let root = tree_hash(&leaves);
+            let sign = |message: &str| -> Vec<u8> {
+                let sig: p256::ecdsa::DerSignature = signer.sign(message.as_bytes());
+                sig.as_bytes().to_vec()
+            };
+            let note = format!("rekor.test\n{}\n{}\n", leaves.len(), STANDARD.encode(root));
+            let mut note_sig = vec![0, 1, 2, 3];
+            note_sig.extend(sign(&note));
+            let checkpoint = format!(
+                "{note}\n\u{2014} rekor.test {}\n",
+                STANDARD.encode(note_sig)
+            );
+            let body = STANDARD.encode(body);
+            let time = Utc::now().timestamp();
+            let set = sign(&format!(
+                r#"{{"body":"{body}","integratedTime":{time},"logID":"{log_id}","logIndex":{index}}}"#
+            ));
+            let hashes: Vec<_> = audit_path(index, &leaves).iter().map(hex::encode).collect();
+            let response = json!({ "24296fb2": {
+                "body": body,
+                "integratedTime": time,
+                "logID": log_id,
+                "logIndex": index,
+                "verification": {
+                    "signedEntryTimestamp": STANDARD.encode(set),
+                    "inclusionProof": {
+                        "checkpoint": checkpoint,
+                        "hashes": hashes,
+                        "logIndex": index,
+                        "rootHash": hex::encode(root),
+                        "treeSize": leaves.len(),
+                    },
+                },
+            }});
+            (201, response.to_string().into_bytes())
+        });
+        Rekor { url, key }
+    }
+
+    fn make_test_entry(diff: &str) -> ProvenanceEntry {
+        ProvenanceEntry {
+            entry_id: "cell:test@v1".to_string(),
+            prev: None,
+            merged: vec![],
+            actor: "dev".to_string(),
+            model: "none".to_string(),
+            prompt_sha3: "abc123".to_string(),
+            prompt_excerpt: "test prompt".to_string(),
+            redaction: None,
+            tools: vec![],
+            diff_sha3: diff.to_string(),
+            cells: vec![],
+            timestamp: Utc::now(),
+            signatures: vec![],
+            keyless: vec![],
+            rotation: None,
+            checkpoint: None,
+            timestamp_token: None,
+        }
+    }
+
+    struct Setup {
+        fulcio: FulcioAuthority,
+        rekor: Rekor,
+        log: RekorLog,
+        trust: KeylessTrust,
+    }
+
+    fn setup() -> Setup {
+        let ca = TestCa::new("fulcio");
+        let trusted_root = ca.root_pem();
+        let fulcio = FulcioAuthority::new(serve_fulcio(ca), token(EMAIL));
+        let rekor = serve_rekor();
+        let log = RekorLog::new(&rekor.url);
+        let trust = KeylessTrust {
+            roots: [("fulcio".to_string(), trusted_root)].into(),
+            logs: [(rekor.log_id(), rekor.key_pem())].into(),
+            identities: vec![],
+        };
+        Setup {
+            fulcio,
+            rekor,
+            log,
+            trust,
+        }
+    }
+
+    #[test]
+    fn test_sign_with_fulcio_and_rekor() {
+        let Setup {
+            fulcio, log, trust, ..
+        } = setup();
+        let entry = make_test_entry("def456");
+        let identity = fulcio.identity().unwrap();
+        assert_eq!(identity.subject, EMAIL);
+
+        let signature = sign_keyless(&entry, &identity, &fulcio, &log).unwrap();
+        assert_eq!(signature.chain.len(), 3);
+        assert_eq!(signature.log_entry.index, 5);
+        assert_eq!(
+            verify_keyless_signature(&entry, &signature, &trust).unwrap(),
+            Identity {
+                subject: EMAIL.to_string(),
+                issuer: ISSUER.to_string(),
+            }
+        );
+
+        // Fulcio refuses keys whose proof is for another identity
+        let other = Identity {
+            subject: "mallory@example.com".to_string(),
+            ..identity
+        };
+        assert!(sign_keyless(&entry, &other, &fulcio, &log)
+            .unwrap_err()
+            .to_string()
+            .contains("bad proof of possession"));
+    }
+
+    #[test]
+    fn test_rekor_entries_and_chains_reject_tampering() {
+        let Setup {
+            fulcio,
+            rekor,
+            log,
+            trust,
+        } = setup();
+        let entry = make_test_entry("def456");
+        let identity = fulcio.identity().unwrap();
+        let signature = sign_keyless(&entry, &identity, &fulcio, &log).unwrap();
+        let other = sign_keyless(&make_test_entry("other"), &identity, &fulcio, &log).unwrap();
+        let rejects = |signature: &KeylessSignature, reason: &str| {
+            let err = verify_keyless_signature(&entry, signature, &trust).unwrap_err();
+            assert!(err.contains(reason), "{err}");
+        };
+
+        let mut forged = signature.clone();
+        forged.chain[0].subject = "mallory@example.com".to_string();
+        rejects(&forged, "does not match its X.509 encoding");
+
+        // Another entry's log record, correctly signed by the log
+        let mut forged = signature.clone();
+        forged.log_entry = other.log_entry.clone();
+        rejects(&forged, "records another signature");
+
+        let mut forged = signature.clone();
+        let proof = forged.log_entry.inclusion_proof.as_mut().unwrap();
+        proof.hashes[0] = hex::encode([0u8; 32]);
+        rejects(&forged, "is not included");
+
+        // A checkpoint of the later tree is signed, but not for this proof
+        let mut forged = signature.clone();
+        let proof = forged.log_entry.inclusion_proof.as_mut().unwrap();
+        proof.checkpoint = other.log_entry.inclusion_proof.unwrap().checkpoint;
+        rejects(&forged, "checkpoint for another tree");
+
+        let mut forged = signature.clone();
+        let proof = forged.log_entry.inclusion_proof.as_mut().unwrap();
+        proof.checkpoint = proof.checkpoint.replacen("\n6\n", "\n7\n", 1);
+        rejects(&forged, "not signed by the log");
+
+        let mut forged = signature.clone();
+        forged.log_entry.integrated_time += chrono::Duration::hours(1);
+        rejects(&forged, "does not verify");
+
+        // A chain from another authority, or a log under another key
+        let rogue = FulcioAuthority::new(serve_fulcio(TestCa::new("fulcio")), token(EMAIL));
+        let forged = sign_keyless(&entry, &identity, &rogue, &log).unwrap();
+        rejects(&forged, "does not verify");
+        let mut trust = trust.clone();
+        let rogue_key = p256_spki(&p256_key()).to_pem(LineEnding::LF).unwrap();
+        trust.logs.insert(rekor.log_id(), rogue_key);
+        let err = verify_keyless_signature(&entry, &signature, &trust).unwrap_err();
+        assert!(err.contains("does not match its ID"), "{err}");
+    }
+
+    #[test]
+    fn test_root_from_inclusion_proof() {
+        let leaves: Vec<_> = (0u8..9).map(|i| leaf_hash(&[i])).collect();
+        for size in 1..=leaves.len() {
+            let leaves = &leaves[..size];
+            let root = tree_hash(leaves);
+            for index in 0..size {
+                let path = audit_path(index, leaves);
+                let (index, size_u64) = (index as u64, size as u64);
+                assert_eq!(
+                    root_from_inclusion_proof(index, size_u64, leaves[index as usize], &path),
+                    Some(root)
+                );
+                assert_ne!(
+                    root_from_inclusion_proof(index, size_u64, leaf_hash(b"x"), &path),
+                    Some(root)
+                );
+                assert_eq!(
+                    root_from_inclusion_proof(size_u64, size_u64, leaves[0], &path),
+                    None
+                );
+            }
+        }
+    }
+}
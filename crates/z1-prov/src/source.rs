@@ -0,0 +1,166 @@
+//! Checking a chain's cell bindings against a source tree.
+//!
+//! Entries bind the cells they touched to semantic hashes. Replaying the
+//! bindings gives the state of every recorded cell, which must match the
+//! semantic hashes recomputed from the tree.
+
+use crate::types::ProvenanceChain;
+use crate::verify::{ancestry, parent_indices, VerificationError};
+use std::collections::{BTreeMap, HashMap};
+
+/// Outcome of a successful source verification.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceReport {
+    /// Modules whose semantic hash matches the chain, sorted
+    pub verified: Vec<String>,
+    /// Modules in the tree that no entry binds, sorted
+    pub unrecorded: Vec<String>,
+}
+
+/// Verify that the cells recorded in `chain` match `tree`.
+///
+/// `tree` maps dotted module paths to semantic hashes as computed from the
+/// source. Without `at`, the chain's final state is checked: for each module
+/// the binding recorded last in chain order wins. With `at`, only that entry
+/// and its ancestors are replayed, so an older checkout can be checked
+/// against the state the chain recorded for it.
+pub fn verify_chain_sources(
+    chain: &ProvenanceChain,
+    tree: &HashMap<String, String>,
+    at: Option<&str>,
+) -> Result<SourceReport, VerificationError> {
+    let parents = parent_indices(chain)?;
+    let included = match at {
+        Some(entry_id) => {
+            let target = chain
+                .entries
+                .iter()
+                .rposition(|entry| entry.entry_id == entry_id)
+                .ok_or_else(|| VerificationError::UnknownEntry(entry_id.to_string()))?;
+            ancestry(&parents, target)
+        }
+        None => vec![true; chain.len()],
+    };
+
+    let mut recorded: BTreeMap<&str, &str> = BTreeMap::new();
+    for (entry, _) in chain.entries.iter().zip(&included).filter(|(_, &inc)| inc) {
+        for cell in &entry.cells {
+            recorded.insert(&cell.module, &cell.semhash);
+        }
+    }
+
+    let mut report = SourceReport::default();
+    for (&module, &expected) in &recorded {
+        let actual = tree
+            .get(module)
+            .ok_or_else(|| VerificationError::SourceMissing(module.to_string()))?;
+        if actual != expected {
+            return Err(VerificationError::SourceMismatch(
+                module.to_string(),
+                expected.to_string(),
+                actual.clone(),
+            ));
+        }
+        report.verified.push(module.to_string());
+    }
+    report.unrecorded = tree
+        .keys()
+        .filter(|module| !recorded.contains_key(module.as_str()))
+        .cloned()
+        .collect();
+    report.unrecorded.sort();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::ProvenanceChainExt;
+    use crate::types::{CellBinding, ProvenanceEntry};
+    use chrono::Utc;
+
+    fn entry(id: &str, cells: &[(&str, &str)]) -> ProvenanceEntry {
+        ProvenanceEntry {
+            entry_id: id.to_string(),
+            prev: None,
+            merged: vec![],
+            actor: "agent:test".to_string(),
+            model: "test-model".to_string(),
+            prompt_sha3: "abc123".to_string(),
+            prompt_excerpt: "test prompt".to_string(),
+            redaction: None,
+            tools: vec![],
+            diff_sha3: "def456".to_string(),
+            cells: cells
+                .iter()
+                .map(|(module, semhash)| CellBinding {
+                    module: module.to_string(),
+                    semhash: semhash.to_string(),
+                })
+                .collect(),
+            timestamp: Utc::now(),
+            signatures: vec![],
+            keyless: vec![],
+            rotation: None,
+            checkpoint: None,
+            timestamp_token: None,
+        }
+    }
+
+    fn tree(cells: &[(&str, &str)]) -> HashMap<String, String> {
+        cells
+            .iter()
+            .map(|(module, hash)| (module.to_string(), hash.to_string()))
+            .collect()
+    }
+
+    fn test_chain() -> ProvenanceChain {
+        let mut chain = ProvenanceChain::new();
+        for entry in [
+            entry("cell:app@v1", &[("app", "h1")]),
+            entry("cell:lib@v1", &[("lib", "l1")]),
+            entry("cell:app@v2", &[("app", "h2")]),
+        ] {
+            chain.append(entry).unwrap();
+        }
+        chain
+    }
+
+    #[test]
+    fn test_sources_match_latest_bindings() {
+        let chain = test_chain();
+        let report = verify_chain_sources(
+            &chain,
+            &tree(&[("app", "h2"), ("lib", "l1"), ("util", "u1")]),
+            None,
+        )
+        .unwrap();
+        assert_eq!(report.verified, ["app", "lib"]);
+        assert_eq!(report.unrecorded, ["util"]);
+
+        assert!(matches!(
+            verify_chain_sources(&chain, &tree(&[("app", "h1"), ("lib", "l1")]), None),
+            Err(VerificationError::SourceMismatch(module, _, _)) if module == "app"
+        ));
+        assert!(matches!(
+            verify_chain_sources(&chain, &tree(&[("app", "h2")]), None),
+            Err(VerificationError::SourceMissing(module)) if module == "lib"
+        ));
+    }
+
+    #[test]
+    fn test_sources_at_historical_entry() {
+        let chain = test_chain();
+        let old_tree = tree(&[("app", "h1"), ("lib", "l1")]);
+
+        let report = verify_chain_sources(&chain, &old_tree, Some("cell:lib@v1")).unwrap();
+        assert_eq!(report.verified, ["app", "lib"]);
+        let report = verify_chain_sources(&chain, &old_tree, Some("cell:app@v1")).unwrap();
+        assert_eq!(report.unrecorded, ["lib"]);
+        assert!(verify_chain_sources(&chain, &old_tree, Some("cell:app@v2")).is_err());
+        assert!(matches!(
+            verify_chain_sources(&chain, &old_tree, Some("cell:nope")),
+            Err(VerificationError::UnknownEntry(_))
+        ));
+    }
+}
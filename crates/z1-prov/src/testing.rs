@@ -0,0 +1,192 @@
+//! Test doubles of the certificate authorities and services that keyless
+//! signing and timestamping talk to.
+
+use chrono::{DateTime, Duration, Utc};
+use der::pem::LineEnding;
+use der::EncodePem;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::str::FromStr;
+use std::thread;
+use x509_cert::builder::{Builder, CertificateBuilder, Profile};
+use x509_cert::name::Name;
+use x509_cert::serial_number::SerialNumber;
+use x509_cert::spki::SubjectPublicKeyInfoOwned;
+use x509_cert::time::{Time, Validity};
+use x509_cert::Certificate;
+
+/// A fresh P-256 key.
+pub(crate) fn p256_key() -> p256::ecdsa::SigningKey {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).unwrap();
+    p256::ecdsa::SigningKey::from_slice(&bytes).unwrap()
+}
+
+/// A fresh P-384 key.
+pub(crate) fn p384_key() -> p384::ecdsa::SigningKey {
+    let mut bytes = [0u8; 48];
+    getrandom::fill(&mut bytes).unwrap();
+    p384::ecdsa::SigningKey::from_slice(&bytes).unwrap()
+}
+
+/// Key information of a P-256 key.
+pub(crate) fn p256_spki(key: &p256::ecdsa::SigningKey) -> SubjectPublicKeyInfoOwned {
+    SubjectPublicKeyInfoOwned::from_key(*key.verifying_key()).unwrap()
+}
+
+/// A root and an intermediate certificate authority over P-384 keys, as
+/// Sigstore's are.
+pub(crate) struct TestCa {
+    pub root: Certificate,
+    pub intermediate: Certificate,
+    intermediate_key: p384::ecdsa::SigningKey,
+    serial: std::sync::atomic::AtomicU32,
+}
+
+impl TestCa {
+    pub fn new(name: &str) -> Self {
+        let root_key = p384_key();
+        let root_name = Name::from_str(&format!("CN={name} root,O=z1")).unwrap();
+        let spki = SubjectPublicKeyInfoOwned::from_key(*root_key.verifying_key()).unwrap();
+        let validity = validity(Utc::now() - Duration::days(1), Duration::days(365));
+        let root = CertificateBuilder::new(
+            Profile::Root,
+            SerialNumber::from(1u32),
+            validity,
+            root_name.clone(),
+            spki,
+            &root_key,
+        )
+        .unwrap()
+        .build::<p384::ecdsa::DerSignature>()
+        .unwrap();
+
+        let intermediate_key = p384_key();
+        let spki = SubjectPublicKeyInfoOwned::from_key(*intermediate_key.verifying_key()).unwrap();
+        let intermediate = CertificateBuilder::new(
+            Profile::SubCA {
+                issuer: root_name,
+                path_len_constraint: Some(0),
+            },
+            SerialNumber::from(2u32),
+            validity,
+            Name::from_str(&format!("CN={name} intermediate,O=z1")).unwrap(),
+            spki,
+            &root_key,
+        )
+        .unwrap()
+        .build::<p384::ecdsa::DerSignature>()
+        .unwrap();
+
+        Self {
+            root,
+            intermediate,
+            intermediate_key,
+            serial: 3.into(),
+        }
+    }
+
+    pub fn root_pem(&self) -> String {
+        self.root.to_pem(LineEnding::LF).unwrap()
+    }
+
+    /// A leaf certificate for `spki`, valid for `lifetime` from
+    /// `not_before`, with the extensions `extend` adds.
+    pub fn issue(
+        &self,
+        spki: SubjectPublicKeyInfoOwned,
+        not_before: DateTime<Utc>,
+        lifetime: Duration,
+        extend: impl FnOnce(&mut CertificateBuilder<'_, p384::ecdsa::SigningKey>),
+    ) -> Certificate {
+        let serial = self
+            .serial
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let mut builder = CertificateBuilder::new(
+            Profile::Leaf {
+                issuer: self.intermediate.tbs_certificate.subject.clone(),
+                enable_key_agreement: false,
+                enable_key_encipherment: false,
+            },
+            SerialNumber::from(serial),
+            validity(not_before, lifetime),
+            Name::default(),
+            spki,
+            &self.intermediate_key,
+        )
+        .unwrap();
+        extend(&mut builder);
+        builder.build::<p384::ecdsa::DerSignature>().unwrap()
+    }
+
+    /// PEM certificates of `leaf` and the authorities above it.
+    pub fn chain_pems(&self, leaf: &Certificate) -> Vec<String> {
+        [leaf, &self.intermediate, &self.root]
+            .iter()
+            .map(|cert| cert.to_pem(LineEnding::LF).unwrap())
+            .collect()
+    }
+}
+
+fn validity(not_before: DateTime<Utc>, lifetime: Duration) -> Validity {
+    let time = |time: DateTime<Utc>| {
+        Time::try_from(
+            std::time::UNIX_EPOCH + std::time::Duration::from_secs(time.timestamp() as u64),
+        )
+        .unwrap()
+    };
+    Validity {
+        not_before: time(not_before),
+        not_after: time(not_before + lifetime),
+    }
+}
+
+/// Serve HTTP on a local port, answering each request with the status and
+/// body `handler` returns for its path and body; returns the base URL.
+pub(crate) fn serve<F>(handler: F) -> String
+where
+    F: Fn(&str, &[u8]) -> (u16, Vec<u8>) + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            let (head_len, body_len) = loop {
+                let n = stream.read(&mut buf).unwrap_or(0);
+                if n == 0 {
+                    break (request.len(), 0);
+                }
+                request.extend_from_slice(&buf[..n]);
+                if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                    let head = String::from_utf8_lossy(&request[..end]).to_lowercase();
+                    let length = head
+                        .lines()
+                        .find_map(|line| line.strip_prefix("content-length:"))
+                        .and_then(|length| length.trim().parse().ok())
+                        .unwrap_or(0);
+                    break (end + 4, length);
+                }
+            };
+            while request.len() < head_len + body_len {
+                let n = stream.read(&mut buf).unwrap_or(0);
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            let head = String::from_utf8_lossy(&request[..head_len]).to_string();
+            let path = head.split(' ').nth(1).unwrap_or_default().to_string();
+            let (status, body) = handler(&path, &request[head_len..]);
+            let _ = write!(
+                stream,
+                "HTTP/1.1 {status} OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(&body);
+        }
+    });
+    url
+}
@@ -0,0 +1,192 @@
+//! Trusted timestamping of provenance entries.
+//!
+//! Entry timestamps are self-reported. A [`TimestampAuthority`] countersigns
+//! an entry's imprint with the time it observed it, so an entry cannot later
+//! be backdated. The imprint covers everything but the signatures and the
+//! token itself, so entries can be signed and stamped in either order.
+//!
+//! Use an RFC 3161 service through [`Rfc3161Authority`]: whoever holds a
+//! [`LocalAuthority`]'s key can stamp any time, so its tokens are only as
+//! good as the key holder's word.
+//!
+//! [`Rfc3161Authority`]: crate::Rfc3161Authority
+
+use crate::signature::{entry_hash, verify_hash};
+use crate::types::{ProvenanceEntry, Signature, TimestampToken};
+use chrono::{DateTime, Duration, Utc};
+use ed25519_dalek::{Signer, SigningKey};
+use sha3::{Digest, Sha3_256};
+use thiserror::Error;
+
+/// Tolerated clock skew between the entry author and the authority.
+pub const CLOCK_SKEW: Duration = Duration::minutes(5);
+
+/// Longest accepted delay between an entry's own timestamp and its token.
+pub const MAX_STAMP_DELAY: Duration = Duration::hours(1);
+
+/// Errors that can occur while obtaining a timestamp.
+#[derive(Debug, Error)]
+pub enum TimestampError {
+    #[error("Timestamp authority {0} failed: {1}")]
+    Authority(String, String),
+}
+
+/// What a verifier trusts a timestamp authority by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthorityKey {
+    /// Ed25519 public key of a [`LocalAuthority`]
+    Ed25519([u8; 32]),
+    /// Root certificates (PEM) an RFC 3161 authority's certificate chains to
+    X509(String),
+}
+
+impl From<[u8; 32]> for AuthorityKey {
+    fn from(public_key: [u8; 32]) -> Self {
+        Self::Ed25519(public_key)
+    }
+}
+
+/// A service that countersigns entry imprints with the current time.
+pub trait TimestampAuthority {
+    /// Identifier recorded as the token's `authority`.
+    fn id(&self) -> &str;
+
+    /// Issue a token for `imprint`.
+    fn stamp(&self, imprint: &str) -> Result<TimestampToken, TimestampError>;
+}
+
+/// A timestamp authority backed by a local Ed25519 key, e.g. one run by a
+/// build service whose public key verifiers trust. Its holder can stamp any
+/// time with [`LocalAuthority::stamp_at`].
+pub struct LocalAuthority {
+    id: String,
+    signing_key: SigningKey,
+}
+
+impl LocalAuthority {
+    pub fn new(id: impl Into<String>, private_key: &[u8; 32]) -> Self {
+        Self {
+            id: id.into(),
+            signing_key: SigningKey::from_bytes(private_key),
+        }
+    }
+
+    /// Issue a token asserting `time` instead of the current time.
+    pub fn stamp_at(&self, imprint: &str, time: DateTime<Utc>) -> TimestampToken {
+        let hash = token_hash(&self.id, time, imprint);
+        let sig = self.signing_key.sign(&hash);
+        TimestampToken {
+            authority: self.id.clone(),
+            time,
+            imprint: imprint.to_string(),
+            sig: format!("ed25519:{}", hex::encode(sig.to_bytes())),
+            rfc3161: None,
+        }
+    }
+}
+
+impl TimestampAuthority for LocalAuthority {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn stamp(&self, imprint: &str) -> Result<TimestampToken, TimestampError> {
+        Ok(self.stamp_at(imprint, Utc::now()))
+    }
+}
+
+/// Imprint of an entry as stamped by a timestamp authority.
+pub fn entry_imprint(entry: &ProvenanceEntry) -> String {
+    format!("sha3-256:{}", hex::encode(entry_hash(entry)))
+}
+
+/// Obtain a token for `entry` from `authority` and attach it.
+pub fn timestamp_entry(
+    entry: &mut ProvenanceEntry,
+    authority: &dyn TimestampAuthority,
+) -> Result<(), TimestampError> {
+    let token = authority.stamp(&entry_imprint(entry))?;
+    entry.timestamp_token = Some(token);
+    Ok(())
+}
+
+/// Check that `token` was signed by the authority holding `public_key`.
+pub fn verify_timestamp_token(token: &TimestampToken, public_key: &[u8; 32]) -> bool {
+    let signature = Signature {
+        by: token.authority.clone(),
+        sig: token.sig.clone(),
+    };
+    verify_hash(
+        &token_hash(&token.authority, token.time, &token.imprint),
+        &signature,
+        public_key,
+    )
+}
+
+/// Hash of the statement a timestamp token signs.
+fn token_hash(authority: &str, time: DateTime<Utc>, imprint: &str) -> [u8; 32] {
+    let time = time.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true);
+    let mut hasher = Sha3_256::new();
+    for part in ["z1-timestamp", authority, &time, imprint] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signature::keygen;
+
+    fn make_test_entry() -> ProvenanceEntry {
+        ProvenanceEntry {
+            entry_id: "cell:test@v1".to_string(),
+            prev: None,
+            merged: vec![],
+            actor: "test-actor".to_string(),
+            model: "test-model".to_string(),
+            prompt_sha3: "abc123".to_string(),
+            prompt_excerpt: "test prompt".to_string(),
+            redaction: None,
+            tools: vec![],
+            diff_sha3: "def456".to_string(),
+            cells: vec![],
+            timestamp: Utc::now(),
+            signatures: vec![],
+            keyless: vec![],
+            rotation: None,
+            checkpoint: None,
+            timestamp_token: None,
+        }
+    }
+
+    #[test]
+    fn test_stamp_and_verify_token() {
+        let (private_key, public_key) = keygen();
+        let authority = LocalAuthority::new("tsa:test", &private_key);
+        let mut entry = make_test_entry();
+
+        timestamp_entry(&mut entry, &authority).unwrap();
+        let token = entry.timestamp_token.clone().unwrap();
+        assert_eq!(token.authority, "tsa:test");
+        assert_eq!(token.imprint, entry_imprint(&entry));
+        assert!(verify_timestamp_token(&token, &public_key));
+
+        let mut backdated = token;
+        backdated.time -= Duration::days(1);
+        assert!(!verify_timestamp_token(&backdated, &public_key));
+    }
+
+    #[test]
+    fn test_signatures_survive_stamping() {
+        let (private_key, public_key) = keygen();
+        let mut entry = make_test_entry();
+        let signature = crate::sign_entry(&entry, &private_key, "signer");
+        entry.signatures.push(signature.clone());
+
+        let authority = LocalAuthority::new("tsa:test", &keygen().0);
+        timestamp_entry(&mut entry, &authority).unwrap();
+        assert!(crate::verify_signature(&entry, &signature, &public_key));
+    }
+}
@@ -1,8 +1,10 @@
 //! Provenance data structures.
 
+use crate::redact::RedactionPolicy;
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
 
@@ -15,6 +17,142 @@ pub struct Signature {
     pub sig: String,
 }
 
+/// Endorsement of a signer's new key by its previous key.
+///
+/// Carried by a rotation entry: entries after it must be signed by
+/// `signer` with `new_key`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KeyRotation {
+    /// Signer whose key is rotated
+    pub signer: String,
+    /// Previous Ed25519 public key (hex)
+    pub old_key: String,
+    /// New Ed25519 public key (hex)
+    pub new_key: String,
+    /// Signature by the previous key over the rotation (e.g., "ed25519:ab8...2f1")
+    pub endorsement: String,
+}
+
+/// A cell an entry produced or modified, identified by its semantic hash.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CellBinding {
+    /// Dotted module path (e.g., "http.server")
+    pub module: String,
+    /// Semantic hash of the cell after the change (e.g., "sha3-256:...")
+    pub semhash: String,
+}
+
+/// Proof from a timestamp authority that an entry existed at `time`.
+///
+/// The authority signs the entry's imprint together with the time it
+/// observed it. Tokens of an RFC 3161 authority carry their DER encoding,
+/// from which the time, imprint and signature are read.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TimestampToken {
+    /// Identifier of the timestamp authority
+    pub authority: String,
+    /// Time asserted by the authority
+    pub time: DateTime<Utc>,
+    /// Imprint of the stamped entry (e.g., "sha3-256:ab8...2f1")
+    pub imprint: String,
+    /// Authority signature over imprint and time (e.g., "ed25519:ab8...2f1",
+    /// or "rfc3161:3045..." for the signature of an RFC 3161 token)
+    pub sig: String,
+    /// DER encoding of an RFC 3161 TimeStampToken (base64)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rfc3161: Option<String>,
+}
+
+/// Short-lived certificate binding an identity to a signing key.
+///
+/// Issued by a certificate authority such as Sigstore's Fulcio: the subject
+/// authenticated with an OIDC issuer and the certificate is only valid for a
+/// few minutes, long enough to sign one entry. Certificates of an X.509
+/// authority carry their DER encoding, from which every other field is read.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SigningCertificate {
+    /// Certified identity (e.g., a CI workflow URI or an email), or the
+    /// authority name for intermediate certificates
+    pub subject: String,
+    /// OIDC issuer that authenticated the subject
+    pub issuer: String,
+    /// Certified public key: Ed25519 (hex), or a DER SubjectPublicKeyInfo
+    /// (hex) for X.509 certificates
+    pub public_key: String,
+    /// Start of the validity period
+    pub not_before: DateTime<Utc>,
+    /// End of the validity period
+    pub not_after: DateTime<Utc>,
+    /// Subject of the issuing certificate, or the ID of a trusted root
+    pub ca: String,
+    /// Issuer signature over the certificate (e.g., "ed25519:ab8...2f1")
+    pub sig: String,
+    /// DER encoding of an X.509 certificate (base64)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x509: Option<String>,
+}
+
+/// Inclusion of a keyless signature in a transparency log such as
+/// Sigstore's Rekor.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LogEntry {
+    /// Identifier of the transparency log
+    pub log_id: String,
+    /// Position of the signature in the log
+    pub index: u64,
+    /// Time the log integrated the signature
+    pub integrated_time: DateTime<Utc>,
+    /// Log signature over the entry (signed entry timestamp, e.g., "ed25519:ab8...2f1")
+    pub set: String,
+    /// Entry as the log stored it (base64), for logs that return it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// Proof that the entry is in the log's Merkle tree
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inclusion_proof: Option<InclusionProof>,
+}
+
+/// RFC 6962 proof that an entry is included in a transparency log's tree.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InclusionProof {
+    /// Position of the entry in the tree
+    pub index: u64,
+    /// Size of the tree the proof is for
+    pub tree_size: u64,
+    /// Root hash of that tree (hex)
+    pub root_hash: String,
+    /// Hashes on the path from the entry to the root (hex)
+    pub hashes: Vec<String>,
+    /// Signed note in which the log commits to the tree size and root
+    pub checkpoint: String,
+}
+
+/// Signature by an ephemeral key certified for an identity.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KeylessSignature {
+    /// Certificate chain, leaf first; each certificate is issued by the next
+    pub chain: Vec<SigningCertificate>,
+    /// Signature by the leaf key (e.g., "ed25519:ab8...2f1")
+    pub sig: String,
+    /// Proof that the signature was logged while the leaf was valid
+    pub log_entry: LogEntry,
+}
+
+/// Commitment to the history before a checkpoint entry.
+///
+/// Carries the state that verifying later entries needs, so the history can
+/// be truncated and archived.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Merkle root of the checkpoint's ancestors
+    pub root: String,
+    /// Number of ancestors
+    pub entries: usize,
+    /// Keys of signers that rotated, as of the checkpoint (hex public keys)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<String, String>,
+}
+
 /// A single entry in the provenance chain.
 ///
 /// Each entry records metadata about a code modification, including:
@@ -32,6 +170,10 @@ pub struct ProvenanceEntry {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub prev: Option<String>,
 
+    /// Hashes of further parents joined by a merge entry
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub merged: Vec<String>,
+
     /// Actor who made the change (human or agent)
     pub actor: String,
 
@@ -44,6 +186,10 @@ pub struct ProvenanceEntry {
     /// First 80 chars of prompt for human reference
     pub prompt_excerpt: String,
 
+    /// Redaction policy the excerpt was produced under, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redaction: Option<RedactionPolicy>,
+
     /// Tools used during generation
     #[serde(default)]
     pub tools: Vec<String>,
@@ -51,20 +197,42 @@ pub struct ProvenanceEntry {
     /// SHA3-256 hash of the diff/changes
     pub diff_sha3: String,
 
+    /// Cells as they stand after the change
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cells: Vec<CellBinding>,
+
     /// Timestamp of the change
     pub timestamp: DateTime<Utc>,
 
     /// Cryptographic signatures on this entry
     #[serde(default)]
     pub signatures: Vec<Signature>,
+
+    /// Signatures by certified identities instead of long-lived keys
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keyless: Vec<KeylessSignature>,
+
+    /// Key rotation recorded by this entry, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<KeyRotation>,
+
+    /// History committed to by this entry, if it is a checkpoint
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkpoint: Option<Checkpoint>,
+
+    /// Trusted timestamp for this entry, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_token: Option<TimestampToken>,
 }
 
 /// A complete provenance chain.
 ///
-/// Contains an ordered list of provenance entries forming a Merkle chain.
+/// Contains provenance entries forming a Merkle DAG, listed so that every
+/// entry comes after its parents. A chain without merges is a simple list
+/// where each entry links to the one before it.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ProvenanceChain {
-    /// Ordered list of provenance entries (oldest first)
+    /// Provenance entries, parents before children (oldest first)
     pub entries: Vec<ProvenanceEntry>,
 
     /// Merkle root computed over all entries
@@ -92,11 +260,7 @@ impl ProvenanceChain {
             self.merkle_root = String::new();
             return;
         }
-        // For now, use the hash of the last entry as the merkle root
-        // In a full implementation, this would compute a proper Merkle tree root
-        if let Some(last) = self.entries.last() {
-            self.merkle_root = crate::verify::compute_entry_hash(last);
-        }
+        self.merkle_root = crate::chain::merkle_root(&self.entries);
     }
 
     /// Get the most recent entry in the chain.
@@ -1,7 +1,14 @@
 //! Provenance chain verification.
 
-use crate::signature::verify_signature;
+use crate::chain::merkle_root;
+use crate::keyless::{verify_keyless_signature, KeylessTrust};
+use crate::rfc3161::verify_rfc3161_token;
+use crate::signature::{decode_key, verify_rotation, verify_signature};
+use crate::timestamp::{
+    entry_imprint, verify_timestamp_token, AuthorityKey, CLOCK_SKEW, MAX_STAMP_DELAY,
+};
 use crate::types::{ProvenanceChain, ProvenanceEntry};
+use chrono::{DateTime, Utc};
 use sha3::{Digest, Sha3_256};
 use std::collections::HashMap;
 use thiserror::Error;
@@ -26,6 +33,39 @@ pub enum VerificationError {
 
     #[error("Public key for {0} not provided")]
     MissingPublicKey(String),
+
+    #[error("Entry {0} has an invalid key rotation: {1}")]
+    InvalidRotation(String, String),
+
+    #[error("Entry {0} has an invalid timestamp token: {1}")]
+    InvalidTimestamp(String, String),
+
+    #[error("Entry {0} has no timestamp token")]
+    MissingTimestamp(String),
+
+    #[error("Public key for timestamp authority {0} not provided")]
+    MissingAuthorityKey(String),
+
+    #[error("Entry {0} has an invalid keyless signature: {1}")]
+    InvalidKeyless(String, String),
+
+    #[error("Entry {0} has no keyless signature")]
+    MissingKeyless(String),
+
+    #[error("Entry {0} has an invalid checkpoint: {1}")]
+    InvalidCheckpoint(String, String),
+
+    #[error("Entry {0} violates its redaction policy: {1}")]
+    InvalidRedaction(String, String),
+
+    #[error("Entry {0} not found in chain")]
+    UnknownEntry(String),
+
+    #[error("Cell {0} does not match the chain: recorded {1}, found {2}")]
+    SourceMismatch(String, String, String),
+
+    #[error("Cell {0} is recorded in the chain but missing from the tree")]
+    SourceMissing(String),
 }
 
 /// Compute the hash of a provenance entry for Merkle chain linking.
@@ -38,39 +78,238 @@ pub fn compute_entry_hash(entry: &ProvenanceEntry) -> String {
     format!("sha3-256:{}", hex::encode(hasher.finalize()))
 }
 
-/// Verify the Merkle chain structure of a provenance chain.
+/// Verify the Merkle structure of a provenance chain.
+///
+/// Ensures that the first entry has no parents and every later entry's
+/// `prev` and `merged` links reference earlier entries, and that timestamp
+/// tokens are consistent: each token stamps its entry's imprint, the entry's
+/// own timestamp lies within [`MAX_STAMP_DELAY`] before the token time
+/// (allowing [`CLOCK_SKEW`]), and no token is older than a token of one of
+/// the entry's ancestors. Token signatures are checked by
+/// [`verify_chain_timestamps`].
 ///
-/// Ensures that each entry's `prev` field correctly references the hash
-/// of the previous entry.
+/// Entries that record a redaction policy must have an excerpt the policy
+/// permits, and checkpoint entries must commit to their ancestors. A chain
+/// may start at a checkpoint whose ancestors were compacted away; its links
+/// are then taken on trust.
 pub fn verify_chain(chain: &ProvenanceChain) -> Result<(), VerificationError> {
-    if chain.is_empty() {
-        return Ok(()); // Empty chain is valid
+    let parents = parent_indices(chain)?;
+
+    for index in 0..chain.len() {
+        check_checkpoint(chain, &parents, index)?;
+    }
+    for entry in &chain.entries {
+        check_redaction(entry)?;
     }
 
-    let mut prev_hash: Option<String> = None;
+    // Latest token time in each entry's ancestry, itself included
+    let mut latest_stamp: Vec<Option<DateTime<Utc>>> = Vec::with_capacity(chain.len());
+    for (entry, parents) in chain.entries.iter().zip(&parents) {
+        let inherited = parents.iter().filter_map(|&p| latest_stamp[p]).max();
+        latest_stamp.push(check_timestamp_token(entry, inherited)?);
+    }
 
-    for entry in &chain.entries {
-        // Check that prev matches the hash of the previous entry
-        if prev_hash != entry.prev {
-            return Err(VerificationError::InvalidMerkleLink(
+    Ok(())
+}
+
+/// Indices of each entry's parents (`prev` first, then `merged`), checking
+/// that every link references an earlier entry.
+pub(crate) fn parent_indices(
+    chain: &ProvenanceChain,
+) -> Result<Vec<Vec<usize>>, VerificationError> {
+    let mut links = Links::default();
+    chain
+        .entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| links.resolve(index, entry))
+        .collect()
+}
+
+/// Resolution of parent links in chain order.
+#[derive(Default)]
+pub(crate) struct Links {
+    seen: HashMap<String, usize>,
+    prev_hash: Option<String>,
+}
+
+impl Links {
+    /// Indices of the parents of the entry at `index`, `prev` first. The
+    /// entry is recorded even when a link is bad, so later entries can still
+    /// link to it.
+    pub(crate) fn resolve(
+        &mut self,
+        index: usize,
+        entry: &ProvenanceEntry,
+    ) -> Result<Vec<usize>, VerificationError> {
+        let parents = self.parents(index, entry);
+
+        // Compute hash for later links
+        let hash = compute_entry_hash(entry);
+        self.seen.insert(hash.clone(), index);
+        self.prev_hash = Some(hash);
+        parents
+    }
+
+    fn parents(
+        &self,
+        index: usize,
+        entry: &ProvenanceEntry,
+    ) -> Result<Vec<usize>, VerificationError> {
+        let bad_link = |expected: &str, got: Option<&String>| {
+            VerificationError::InvalidMerkleLink(
                 entry.entry_id.clone(),
-                prev_hash.clone().unwrap_or_else(|| "None".to_string()),
-                entry.prev.clone(),
-            ));
+                expected.to_string(),
+                got.cloned(),
+            )
+        };
+
+        // The parents of a compacted chain's first entry were archived
+        if starts_compacted(index, entry) {
+            return Ok(Vec::new());
+        }
+        let mut entry_parents = Vec::with_capacity(1 + entry.merged.len());
+        match &entry.prev {
+            None if index == 0 => {}
+            Some(prev) if index > 0 => {
+                let expected = self.prev_hash.as_deref().unwrap_or("None");
+                let &parent = self
+                    .seen
+                    .get(prev)
+                    .ok_or_else(|| bad_link(expected, Some(prev)))?;
+                entry_parents.push(parent);
+            }
+            prev => {
+                return Err(bad_link(
+                    self.prev_hash.as_deref().unwrap_or("None"),
+                    prev.as_ref(),
+                ))
+            }
+        }
+        for parent in &entry.merged {
+            let &index = self
+                .seen
+                .get(parent)
+                .ok_or_else(|| bad_link("an earlier entry", Some(parent)))?;
+            if entry_parents.contains(&index) {
+                return Err(bad_link("distinct parents", Some(parent)));
+            }
+            entry_parents.push(index);
         }
+        Ok(entry_parents)
+    }
+}
 
-        // Compute hash for next iteration
-        prev_hash = Some(compute_entry_hash(entry));
+/// Whether `entry`, at `index`, is a checkpoint whose ancestors were
+/// compacted away.
+pub(crate) fn starts_compacted(index: usize, entry: &ProvenanceEntry) -> bool {
+    index == 0 && entry.checkpoint.is_some() && entry.prev.is_some()
+}
+
+/// Mask of the entry at `index` and its ancestors.
+pub(crate) fn ancestry(parents: &[Vec<usize>], index: usize) -> Vec<bool> {
+    let mut included = vec![false; parents.len()];
+    let mut pending = vec![index];
+    while let Some(index) = pending.pop() {
+        if !included[index] {
+            included[index] = true;
+            pending.extend(&parents[index]);
+        }
     }
+    included
+}
 
+/// Check that a checkpoint entry commits to its ancestors. `parents` must
+/// cover the chain up to `index`.
+pub(crate) fn check_checkpoint(
+    chain: &ProvenanceChain,
+    parents: &[Vec<usize>],
+    index: usize,
+) -> Result<(), VerificationError> {
+    let entry = &chain.entries[index];
+    let Some(checkpoint) = &entry.checkpoint else {
+        return Ok(());
+    };
+    if starts_compacted(index, entry) {
+        return Ok(());
+    }
+    let invalid =
+        |reason: &str| VerificationError::InvalidCheckpoint(entry.entry_id.clone(), reason.into());
+    let mut ancestors = ancestry(&parents[..=index], index);
+    ancestors[index] = false;
+    if checkpoint.entries != ancestors.iter().filter(|&&a| a).count() {
+        return Err(invalid("entry count does not match its ancestors"));
+    }
+    let history = chain
+        .entries
+        .iter()
+        .zip(&ancestors)
+        .filter_map(|(entry, &ancestor)| ancestor.then_some(entry));
+    if checkpoint.root != merkle_root(history) {
+        return Err(invalid("root does not match its ancestors"));
+    }
     Ok(())
 }
 
+/// Check that an entry's excerpt is permitted by its redaction policy.
+pub(crate) fn check_redaction(entry: &ProvenanceEntry) -> Result<(), VerificationError> {
+    let Some(policy) = &entry.redaction else {
+        return Ok(());
+    };
+    let invalid =
+        |reason: String| VerificationError::InvalidRedaction(entry.entry_id.clone(), reason);
+    if !policy
+        .permits(&entry.prompt_excerpt)
+        .map_err(|err| invalid(err.to_string()))?
+    {
+        return Err(invalid("excerpt is not redacted".to_string()));
+    }
+    Ok(())
+}
+
+/// Check an entry's timestamp token for consistency, given the latest token
+/// time among its ancestors. Returns the latest token time including the
+/// entry's own.
+pub(crate) fn check_timestamp_token(
+    entry: &ProvenanceEntry,
+    inherited: Option<DateTime<Utc>>,
+) -> Result<Option<DateTime<Utc>>, VerificationError> {
+    let Some(token) = &entry.timestamp_token else {
+        return Ok(inherited);
+    };
+
+    let invalid =
+        |reason: &str| VerificationError::InvalidTimestamp(entry.entry_id.clone(), reason.into());
+    if token.imprint != entry_imprint(entry) {
+        return Err(invalid("token does not stamp this entry"));
+    }
+    if entry.timestamp > token.time + CLOCK_SKEW {
+        return Err(invalid("entry is dated after its token"));
+    }
+    if entry.timestamp < token.time - MAX_STAMP_DELAY {
+        return Err(invalid("entry is dated too long before its token"));
+    }
+    if inherited.is_some_and(|last| token.time < last) {
+        return Err(invalid("token is older than an ancestor's token"));
+    }
+    Ok(Some(token.time))
+}
+
+/// A signer's current key and the number of rotations that led to it.
+pub(crate) type KeyState = HashMap<String, ([u8; 32], u32)>;
+
 /// Verify all signatures in a provenance chain.
 ///
+/// Key rotations are followed along parent links: a rotation entry must be
+/// endorsed by the signer's current key, and the entries descending from it
+/// must be signed with the new key. A merge inherits the most rotated key of
+/// each signer; two different keys after the same number of rotations are a
+/// conflict. A checkpoint must record the keys rotated before it; when the
+/// chain starts at a compacted checkpoint, its keys replace `public_keys`.
+///
 /// # Arguments
 /// * `chain` - The provenance chain to verify
-/// * `public_keys` - Map from signer IDs to their Ed25519 public keys (32 bytes)
+/// * `public_keys` - Map from signer IDs to their initial Ed25519 public keys (32 bytes)
 /// * `required_signers` - Optional set of signer IDs that must sign every entry
 ///
 /// # Returns
@@ -79,15 +318,205 @@ pub fn verify_chain_signatures(
     chain: &ProvenanceChain,
     public_keys: &HashMap<String, [u8; 32]>,
     required_signers: Option<&[String]>,
+) -> Result<(), VerificationError> {
+    let parents = parent_indices(chain)?;
+    let initial = initial_keys(public_keys);
+    let mut states: Vec<KeyState> = Vec::with_capacity(chain.len());
+
+    for (index, (entry, parents)) in chain.entries.iter().zip(&parents).enumerate() {
+        let mut state = inherit_keys(index, entry, parents, &states, &initial)?;
+        verify_entry_signatures(entry, &current_keys(&state), required_signers)?;
+        apply_rotation(entry, &mut state)?;
+        states.push(state);
+    }
+    Ok(())
+}
+
+/// Key state before any rotation.
+pub(crate) fn initial_keys(public_keys: &HashMap<String, [u8; 32]>) -> KeyState {
+    public_keys
+        .iter()
+        .map(|(signer, key)| (signer.clone(), (*key, 0)))
+        .collect()
+}
+
+/// Current key of each signer.
+pub(crate) fn current_keys(state: &KeyState) -> HashMap<String, [u8; 32]> {
+    state
+        .iter()
+        .map(|(signer, (key, _))| (signer.clone(), *key))
+        .collect()
+}
+
+/// Key state an entry is signed under: its first parent's state, joined
+/// with the other parents' and checked against (or, at the start of a
+/// compacted chain, replaced by) the keys a checkpoint records.
+pub(crate) fn inherit_keys(
+    index: usize,
+    entry: &ProvenanceEntry,
+    parents: &[usize],
+    states: &[KeyState],
+    initial: &KeyState,
+) -> Result<KeyState, VerificationError> {
+    let mut state = match parents.first() {
+        Some(&prev) => states[prev].clone(),
+        None => initial.clone(),
+    };
+    for &parent in parents.iter().skip(1) {
+        for (signer, &(key, generation)) in &states[parent] {
+            match state.get(signer) {
+                Some(&(current, current_gen)) if current_gen == generation && current != key => {
+                    return Err(VerificationError::InvalidRotation(
+                        entry.entry_id.clone(),
+                        "merged branches rotated a key differently".into(),
+                    ));
+                }
+                Some(&(_, current_gen)) if current_gen >= generation => {}
+                _ => {
+                    state.insert(signer.clone(), (key, generation));
+                }
+            }
+        }
+    }
+
+    if let Some(checkpoint) = &entry.checkpoint {
+        let invalid = |reason: &str| {
+            VerificationError::InvalidCheckpoint(entry.entry_id.clone(), reason.into())
+        };
+        if starts_compacted(index, entry) {
+            for (signer, key) in &checkpoint.keys {
+                let key = decode_key(key).ok_or_else(|| invalid("bad key"))?;
+                state.insert(signer.clone(), (key, 0));
+            }
+        } else {
+            let unrecorded = state.iter().any(|(signer, &(key, generation))| {
+                generation > 0 && checkpoint.keys.get(signer) != Some(&hex::encode(key))
+            });
+            let stale = checkpoint.keys.iter().any(|(signer, key)| {
+                state
+                    .get(signer)
+                    .is_some_and(|(current, _)| &hex::encode(current) != key)
+            });
+            if unrecorded || stale {
+                return Err(invalid("keys do not match the rotations before it"));
+            }
+        }
+    }
+    Ok(state)
+}
+
+/// Apply the key rotation an entry records, if any, to `state`.
+pub(crate) fn apply_rotation(
+    entry: &ProvenanceEntry,
+    state: &mut KeyState,
+) -> Result<(), VerificationError> {
+    let Some(rotation) = &entry.rotation else {
+        return Ok(());
+    };
+    let invalid =
+        |reason: &str| VerificationError::InvalidRotation(entry.entry_id.clone(), reason.into());
+    let &(current, generation) = state
+        .get(&rotation.signer)
+        .ok_or_else(|| VerificationError::MissingPublicKey(rotation.signer.clone()))?;
+    if hex::encode(current) != rotation.old_key {
+        return Err(invalid("old key is not the signer's current key"));
+    }
+    if !verify_rotation(rotation) {
+        return Err(invalid("endorsement by the old key does not verify"));
+    }
+    let new_key = decode_key(&rotation.new_key).ok_or_else(|| invalid("bad new key"))?;
+    state.insert(rotation.signer.clone(), (new_key, generation + 1));
+    Ok(())
+}
+
+/// Verify the timestamp tokens of a provenance chain against trusted
+/// authorities.
+///
+/// # Arguments
+/// * `chain` - The provenance chain to verify
+/// * `authorities` - Map from authority IDs to their Ed25519 public keys,
+///   or for RFC 3161 authorities the root certificates they chain to
+/// * `require` - Fail on entries without a timestamp token
+pub fn verify_chain_timestamps(
+    chain: &ProvenanceChain,
+    authorities: &HashMap<String, AuthorityKey>,
+    require: bool,
+) -> Result<(), VerificationError> {
+    for entry in &chain.entries {
+        check_timestamp_authority(entry, authorities, require)?;
+    }
+    Ok(())
+}
+
+/// Check the authority signature on an entry's timestamp token.
+pub(crate) fn check_timestamp_authority(
+    entry: &ProvenanceEntry,
+    authorities: &HashMap<String, AuthorityKey>,
+    require: bool,
+) -> Result<(), VerificationError> {
+    let Some(token) = &entry.timestamp_token else {
+        if require {
+            return Err(VerificationError::MissingTimestamp(entry.entry_id.clone()));
+        }
+        return Ok(());
+    };
+    let key = authorities
+        .get(&token.authority)
+        .ok_or_else(|| VerificationError::MissingAuthorityKey(token.authority.clone()))?;
+    let verified = match (key, &token.rfc3161) {
+        (AuthorityKey::Ed25519(public_key), None) => verify_timestamp_token(token, public_key)
+            .then_some(())
+            .ok_or_else(|| format!("signature by {} failed verification", token.authority)),
+        (AuthorityKey::X509(roots), Some(_)) => verify_rfc3161_token(token, roots)
+            .map_err(|reason| format!("token from {}: {reason}", token.authority)),
+        (AuthorityKey::Ed25519(_), Some(_)) => Err(format!(
+            "{} is trusted by key but issued an RFC 3161 token",
+            token.authority
+        )),
+        (AuthorityKey::X509(_), None) => Err(format!(
+            "{} is trusted by certificate but issued no RFC 3161 token",
+            token.authority
+        )),
+    };
+    verified.map_err(|reason| VerificationError::InvalidTimestamp(entry.entry_id.clone(), reason))
+}
+
+/// Verify the keyless signatures of a provenance chain against trusted
+/// certificate authorities, transparency logs and identities.
+///
+/// # Arguments
+/// * `chain` - The provenance chain to verify
+/// * `trust` - Trusted roots, logs and accepted identities
+/// * `require` - Fail on entries without a keyless signature
+pub fn verify_chain_keyless(
+    chain: &ProvenanceChain,
+    trust: &KeylessTrust,
+    require: bool,
 ) -> Result<(), VerificationError> {
     for entry in &chain.entries {
-        verify_entry_signatures(entry, public_keys, required_signers)?;
+        check_keyless(entry, trust, require)?;
+    }
+    Ok(())
+}
+
+/// Check the keyless signatures on a single entry.
+pub(crate) fn check_keyless(
+    entry: &ProvenanceEntry,
+    trust: &KeylessTrust,
+    require: bool,
+) -> Result<(), VerificationError> {
+    if require && entry.keyless.is_empty() {
+        return Err(VerificationError::MissingKeyless(entry.entry_id.clone()));
+    }
+    for signature in &entry.keyless {
+        verify_keyless_signature(entry, signature, trust)
+            .map_err(|reason| VerificationError::InvalidKeyless(entry.entry_id.clone(), reason))?;
     }
     Ok(())
 }
 
 /// Verify all signatures on a single provenance entry.
-fn verify_entry_signatures(
+pub(crate) fn verify_entry_signatures(
     entry: &ProvenanceEntry,
     public_keys: &HashMap<String, [u8; 32]>,
     required_signers: Option<&[String]>,
@@ -124,21 +553,28 @@ fn verify_entry_signatures(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::signature::{keygen, sign_entry};
+    use crate::signature::{endorse_rotation, keygen, sign_entry};
     use chrono::Utc;
 
     fn make_test_entry(id: &str, prev: Option<String>) -> ProvenanceEntry {
         ProvenanceEntry {
             entry_id: id.to_string(),
             prev,
+            merged: vec![],
             actor: "test-actor".to_string(),
             model: "test-model".to_string(),
             prompt_sha3: "abc123".to_string(),
             prompt_excerpt: "test prompt".to_string(),
+            redaction: None,
             tools: vec![],
             diff_sha3: "def456".to_string(),
+            cells: vec![],
             timestamp: Utc::now(),
             signatures: vec![],
+            keyless: vec![],
+            rotation: None,
+            checkpoint: None,
+            timestamp_token: None,
         }
     }
 
@@ -191,6 +627,88 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_verify_checks_recorded_redaction() {
+        let policy = crate::RedactionPolicy::secrets();
+        let mut entry = make_test_entry("entry1", None);
+        entry.prompt_excerpt = policy.excerpt("login with password=hunter2").unwrap();
+        entry.redaction = Some(policy);
+        let mut chain = ProvenanceChain::new();
+        chain.add_entry(entry);
+        assert!(verify_chain(&chain).is_ok());
+
+        chain.entries[0].prompt_excerpt = "login with password=hunter2".to_string();
+        assert!(matches!(
+            verify_chain(&chain),
+            Err(VerificationError::InvalidRedaction(..))
+        ));
+    }
+
+    #[test]
+    fn test_verify_merged_branches() {
+        let mut chain = ProvenanceChain::new();
+        let root = make_test_entry("root", None);
+        let root_hash = compute_entry_hash(&root);
+        chain.add_entry(root);
+        let left = make_test_entry("left", Some(root_hash.clone()));
+        let left_hash = compute_entry_hash(&left);
+        chain.add_entry(left);
+        let right = make_test_entry("right", Some(root_hash));
+        let right_hash = compute_entry_hash(&right);
+        chain.add_entry(right);
+
+        let mut merge = make_test_entry("merge", Some(left_hash.clone()));
+        merge.merged = vec![right_hash];
+        chain.add_entry(merge.clone());
+        assert!(verify_chain(&chain).is_ok());
+
+        // Merged parents must be earlier entries distinct from `prev`
+        for merged in [left_hash, "sha3-256:unknown".to_string()] {
+            chain.entries[3].merged = vec![merged];
+            assert!(matches!(
+                verify_chain(&chain),
+                Err(VerificationError::InvalidMerkleLink(..))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_verify_signatures_across_merge() {
+        let (old_private, old_public) = keygen();
+        let (new_private, new_public) = keygen();
+        let public_keys: HashMap<_, _> = [("signer1".to_string(), old_public)].into();
+
+        let mut chain = ProvenanceChain::new();
+        let root = make_test_entry("root", None);
+        let root_hash = compute_entry_hash(&root);
+        chain.add_entry(root);
+
+        // One branch rotates the key, the other keeps signing with the old one
+        let mut rotation = make_test_entry("rotate", Some(root_hash.clone()));
+        rotation.rotation = Some(endorse_rotation("signer1", &old_private, &new_public));
+        let rotation_hash = compute_entry_hash(&rotation);
+        chain.add_entry(rotation);
+        let mut side = make_test_entry("side", Some(root_hash));
+        side.signatures
+            .push(sign_entry(&side, &old_private, "signer1"));
+        let side_hash = compute_entry_hash(&side);
+        chain.add_entry(side);
+
+        // The merge inherits the rotated key
+        let mut merge = make_test_entry("merge", Some(side_hash));
+        merge.merged = vec![rotation_hash];
+        merge.signatures = vec![sign_entry(&merge, &new_private, "signer1")];
+        chain.add_entry(merge.clone());
+        assert!(verify_chain_signatures(&chain, &public_keys, None).is_ok());
+
+        merge.signatures = vec![sign_entry(&merge, &old_private, "signer1")];
+        chain.entries[3] = merge;
+        assert!(matches!(
+            verify_chain_signatures(&chain, &public_keys, None),
+            Err(VerificationError::InvalidSignature(..))
+        ));
+    }
+
     #[test]
     fn test_verify_chain_signatures_valid() {
         let (private_key, public_key) = keygen();
@@ -289,4 +807,100 @@ mod tests {
             Err(VerificationError::MissingPublicKey(..))
         ));
     }
+
+    #[test]
+    fn test_verify_chain_follows_key_rotation() {
+        let (old_private, old_public) = keygen();
+        let (new_private, new_public) = keygen();
+        let public_keys: HashMap<_, _> = [("signer1".to_string(), old_public)].into();
+
+        let mut chain = ProvenanceChain::new();
+        let mut rotation = make_test_entry("rotate", None);
+        rotation.rotation = Some(endorse_rotation("signer1", &old_private, &new_public));
+        let sig = sign_entry(&rotation, &old_private, "signer1");
+        rotation.signatures.push(sig);
+        let rotation_hash = compute_entry_hash(&rotation);
+        chain.add_entry(rotation);
+
+        let mut after = make_test_entry("entry2", Some(rotation_hash));
+        let sig = sign_entry(&after, &new_private, "signer1");
+        after.signatures.push(sig);
+        chain.add_entry(after.clone());
+        assert!(verify_chain_signatures(&chain, &public_keys, None).is_ok());
+
+        // The retired key no longer verifies after the rotation
+        after.signatures = vec![sign_entry(&after, &old_private, "signer1")];
+        chain.entries[1] = after;
+        assert!(matches!(
+            verify_chain_signatures(&chain, &public_keys, None),
+            Err(VerificationError::InvalidSignature(..))
+        ));
+    }
+
+    #[test]
+    fn test_verify_rotation_requires_current_key() {
+        let (trusted_private, trusted_public) = keygen();
+        let (other_private, _) = keygen();
+        let (_, new_public) = keygen();
+        let public_keys: HashMap<_, _> = [("signer1".to_string(), trusted_public)].into();
+
+        // Endorsed, but by a key the verifier does not trust for signer1
+        let mut entry = make_test_entry("rotate", None);
+        entry.rotation = Some(endorse_rotation("signer1", &other_private, &new_public));
+        let sig = sign_entry(&entry, &trusted_private, "signer1");
+        entry.signatures.push(sig);
+
+        let mut chain = ProvenanceChain::new();
+        chain.add_entry(entry);
+        assert!(matches!(
+            verify_chain_signatures(&chain, &public_keys, None),
+            Err(VerificationError::InvalidRotation(..))
+        ));
+    }
+
+    #[test]
+    fn test_verify_chain_checks_timestamp_tokens() {
+        use crate::timestamp::{timestamp_entry, LocalAuthority};
+
+        let (tsa_private, tsa_public) = keygen();
+        let authority = LocalAuthority::new("tsa:test", &tsa_private);
+        let authorities: HashMap<_, _> = [("tsa:test".to_string(), tsa_public.into())].into();
+
+        let mut chain = ProvenanceChain::new();
+        let mut entry = make_test_entry("entry1", None);
+        timestamp_entry(&mut entry, &authority).unwrap();
+        chain.add_entry(entry.clone());
+        assert!(verify_chain(&chain).is_ok());
+        assert!(verify_chain_timestamps(&chain, &authorities, true).is_ok());
+
+        // Backdating the entry after stamping changes its imprint
+        let mut backdated = entry.clone();
+        backdated.timestamp -= chrono::Duration::days(30);
+        chain.entries[0] = backdated.clone();
+        assert!(matches!(
+            verify_chain(&chain),
+            Err(VerificationError::InvalidTimestamp(..))
+        ));
+
+        // Stamping a backdated entry is caught by the allowed delay
+        timestamp_entry(&mut backdated, &authority).unwrap();
+        chain.entries[0] = backdated;
+        assert!(matches!(
+            verify_chain(&chain),
+            Err(VerificationError::InvalidTimestamp(..))
+        ));
+
+        chain.entries[0] = entry;
+        let unstamped = make_test_entry("entry2", Some(compute_entry_hash(&chain.entries[0])));
+        chain.add_entry(unstamped);
+        assert!(verify_chain_timestamps(&chain, &authorities, false).is_ok());
+        assert!(matches!(
+            verify_chain_timestamps(&chain, &authorities, true),
+            Err(VerificationError::MissingTimestamp(..))
+        ));
+        assert!(matches!(
+            verify_chain_timestamps(&chain, &HashMap::new(), false),
+            Err(VerificationError::MissingAuthorityKey(..))
+        ));
+    }
 }
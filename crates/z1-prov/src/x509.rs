@@ -0,0 +1,222 @@
+//! X.509 certificate chains and ECDSA and RSA signatures.
+//!
+//! Sigstore's Fulcio and RFC 3161 timestamp authorities certify their keys
+//! with X.509 chains of ECDSA certificates over P-256 or P-384, or of RSA
+//! certificates. A chain is accepted when every certificate was valid at the
+//! time in question, each is signed by the next, every issuer is a
+//! certificate authority, and the last is signed by a trusted root.
+
+use chrono::{DateTime, Utc};
+use der::oid::db::{rfc5280, rfc5912};
+use der::oid::ObjectIdentifier;
+use der::{Decode, Encode};
+use p256::ecdsa::signature::hazmat::PrehashVerifier;
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::{Pkcs1v15Sign, RsaPublicKey};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use x509_cert::ext::pkix::{BasicConstraints, ExtendedKeyUsage};
+use x509_cert::spki::SubjectPublicKeyInfoOwned;
+use x509_cert::Certificate;
+
+/// ecdsa-with-SHA256, the algorithm of Rekor's and ephemeral P-256 keys.
+pub(crate) const ECDSA_WITH_SHA_256: ObjectIdentifier = rfc5912::ECDSA_WITH_SHA_256;
+
+/// Certificates of a PEM bundle, in order.
+pub(crate) fn parse_pem(pem: &str) -> Result<Vec<Certificate>, String> {
+    let certs = Certificate::load_pem_chain(pem.as_bytes())
+        .map_err(|err| format!("invalid PEM certificate: {err}"))?;
+    if certs.is_empty() {
+        return Err("no certificate in PEM".into());
+    }
+    Ok(certs)
+}
+
+/// A certificate from its DER encoding.
+pub(crate) fn parse_der(der: &[u8]) -> Result<Certificate, String> {
+    Certificate::from_der(der).map_err(|err| format!("invalid certificate: {err}"))
+}
+
+/// A public key from a PEM `PUBLIC KEY` block.
+pub(crate) fn parse_public_key_pem(pem: &str) -> Result<SubjectPublicKeyInfoOwned, String> {
+    use der::DecodePem;
+    SubjectPublicKeyInfoOwned::from_pem(pem.trim().as_bytes())
+        .map_err(|err| format!("invalid PEM public key: {err}"))
+}
+
+/// Start and end of a certificate's validity period.
+pub(crate) fn validity(cert: &Certificate) -> (DateTime<Utc>, DateTime<Utc>) {
+    let time = |time: &x509_cert::time::Time| {
+        let since_epoch = time.to_unix_duration();
+        DateTime::from_timestamp(since_epoch.as_secs() as i64, since_epoch.subsec_nanos())
+            .unwrap_or_default()
+    };
+    let validity = &cert.tbs_certificate.validity;
+    (time(&validity.not_before), time(&validity.not_after))
+}
+
+/// Value of the extension `oid` of `cert`, if present.
+pub(crate) fn extension<'a>(cert: &'a Certificate, oid: &ObjectIdentifier) -> Option<&'a [u8]> {
+    cert.tbs_certificate
+        .extensions
+        .iter()
+        .flatten()
+        .find(|ext| ext.extn_id == *oid)
+        .map(|ext| ext.extn_value.as_bytes())
+}
+
+/// Whether `cert` may be used to sign code, as Fulcio's leaves are.
+pub(crate) fn allows_code_signing(cert: &Certificate) -> bool {
+    allows_usage(cert, &rfc5280::ID_KP_CODE_SIGNING)
+}
+
+/// Whether `cert` names `usage` among its extended key usages.
+pub(crate) fn allows_usage(cert: &Certificate, usage: &ObjectIdentifier) -> bool {
+    extension(cert, &rfc5280::ID_CE_EXT_KEY_USAGE)
+        .and_then(|value| ExtendedKeyUsage::from_der(value).ok())
+        .is_some_and(|usages| usages.0.contains(usage))
+}
+
+/// Check the `signature` over `message` by `key`: ECDSA (DER) over P-256
+/// or P-384, or RSA PKCS #1 v1.5, with the hash `algorithm` names.
+pub(crate) fn verify_signature(
+    key: &SubjectPublicKeyInfoOwned,
+    algorithm: &ObjectIdentifier,
+    message: &[u8],
+    signature: &[u8],
+) -> Result<(), String> {
+    let (digest, padding) = match *algorithm {
+        rfc5912::ECDSA_WITH_SHA_256 | rfc5912::SHA_256_WITH_RSA_ENCRYPTION => (
+            Sha256::digest(message).to_vec(),
+            Pkcs1v15Sign::new::<Sha256>(),
+        ),
+        rfc5912::ECDSA_WITH_SHA_384 | rfc5912::SHA_384_WITH_RSA_ENCRYPTION => (
+            Sha384::digest(message).to_vec(),
+            Pkcs1v15Sign::new::<Sha384>(),
+        ),
+        rfc5912::ECDSA_WITH_SHA_512 | rfc5912::SHA_512_WITH_RSA_ENCRYPTION => (
+            Sha512::digest(message).to_vec(),
+            Pkcs1v15Sign::new::<Sha512>(),
+        ),
+        other => return Err(format!("unsupported signature algorithm {other}")),
+    };
+    let rsa = matches!(
+        *algorithm,
+        rfc5912::SHA_256_WITH_RSA_ENCRYPTION
+            | rfc5912::SHA_384_WITH_RSA_ENCRYPTION
+            | rfc5912::SHA_512_WITH_RSA_ENCRYPTION
+    );
+    let point = key.subject_public_key.raw_bytes();
+    let verified = match key.algorithm.oid {
+        rfc5912::RSA_ENCRYPTION => {
+            if !rsa {
+                return Err(format!("RSA key cannot check {algorithm} signatures"));
+            }
+            let key = RsaPublicKey::from_pkcs1_der(point).map_err(|_| "invalid RSA public key")?;
+            key.verify(padding, &digest, signature).is_ok()
+        }
+        rfc5912::ID_EC_PUBLIC_KEY if !rsa => {
+            let curve = key
+                .algorithm
+                .parameters
+                .as_ref()
+                .and_then(|params| params.decode_as::<ObjectIdentifier>().ok());
+            match curve {
+                Some(rfc5912::SECP_256_R_1) => {
+                    let key = p256::ecdsa::VerifyingKey::from_sec1_bytes(point)
+                        .map_err(|_| "invalid P-256 public key")?;
+                    let signature = p256::ecdsa::DerSignature::from_bytes(signature)
+                        .map_err(|_| "malformed ECDSA signature")?;
+                    key.verify_prehash(&digest, &signature).is_ok()
+                }
+                Some(rfc5912::SECP_384_R_1) => {
+                    let key = p384::ecdsa::VerifyingKey::from_sec1_bytes(point)
+                        .map_err(|_| "invalid P-384 public key")?;
+                    let signature = p384::ecdsa::DerSignature::from_bytes(signature)
+                        .map_err(|_| "malformed ECDSA signature")?;
+                    key.verify_prehash(&digest, &signature).is_ok()
+                }
+                _ => return Err("unsupported elliptic curve".into()),
+            }
+        }
+        other => return Err(format!("{other} key cannot check {algorithm} signatures")),
+    };
+    if verified {
+        Ok(())
+    } else {
+        Err("signature does not verify".into())
+    }
+}
+
+/// Check that `issuer` issued and signed `cert`.
+pub(crate) fn verify_issued(cert: &Certificate, issuer: &Certificate) -> Result<(), String> {
+    let subject = cert.tbs_certificate.subject.to_string();
+    if cert.tbs_certificate.issuer != issuer.tbs_certificate.subject {
+        return Err(format!("certificate for {subject} breaks the chain"));
+    }
+    let is_ca = extension(issuer, &rfc5280::ID_CE_BASIC_CONSTRAINTS)
+        .and_then(|value| BasicConstraints::from_der(value).ok())
+        .is_some_and(|constraints| constraints.ca);
+    if !is_ca {
+        return Err(format!(
+            "issuer {} of {subject} is not a certificate authority",
+            issuer.tbs_certificate.subject
+        ));
+    }
+    let tbs = cert
+        .tbs_certificate
+        .to_der()
+        .map_err(|err| format!("certificate for {subject} does not encode: {err}"))?;
+    let signature = cert
+        .signature
+        .as_bytes()
+        .ok_or_else(|| format!("certificate for {subject} has a malformed signature"))?;
+    verify_signature(
+        &issuer.tbs_certificate.subject_public_key_info,
+        &cert.signature_algorithm.oid,
+        &tbs,
+        signature,
+    )
+    .map_err(|reason| format!("certificate for {subject} does not verify: {reason}"))
+}
+
+/// Check `chain`, leaf first, at `time`: every certificate was valid then,
+/// each is issued by the next, and the last is issued by one of `roots` or
+/// is one of them.
+pub(crate) fn verify_chain(
+    chain: &[Certificate],
+    roots: &[Certificate],
+    time: DateTime<Utc>,
+) -> Result<(), String> {
+    let last = chain.last().ok_or("certificate chain is empty")?;
+    for (position, cert) in chain.iter().enumerate() {
+        let (not_before, not_after) = validity(cert);
+        if time < not_before || time > not_after {
+            return Err(format!(
+                "certificate for {} was not valid at {time}",
+                cert.tbs_certificate.subject
+            ));
+        }
+        if let Some(issuer) = chain.get(position + 1) {
+            verify_issued(cert, issuer)?;
+        }
+    }
+    if roots.contains(last) {
+        return Ok(());
+    }
+    let mut failure = None;
+    for root in roots
+        .iter()
+        .filter(|root| root.tbs_certificate.subject == last.tbs_certificate.issuer)
+    {
+        match verify_issued(last, root) {
+            Ok(()) => return Ok(()),
+            Err(reason) => failure = Some(reason),
+        }
+    }
+    Err(failure.unwrap_or_else(|| {
+        format!(
+            "certificate authority {} is not trusted",
+            last.tbs_certificate.issuer
+        )
+    }))
+}
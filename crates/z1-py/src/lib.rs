@@ -0,0 +1,312 @@
+//! Python bindings: the `z1` module.
+//!
+//! Agent orchestrators written in Python want to know whether a cell
+//! parses, how many tokens it costs and whether it passes the policy gates,
+//! without shelling out to the CLI for every candidate. This crate exposes
+//! those answers as plain functions returning frozen, dataclass-like result
+//! objects: their fields are read-only attributes, they compare by value
+//! and print as `Estimate(total_tokens=12, ...)`.
+//!
+//! It is built into a wheel with maturin (see `pyproject.toml`); the module
+//! reads no files, so cells are passed as source text. Each call interns
+//! the names of its cell in a [`Session`] of its own, dropped when it
+//! returns, so a long-lived interpreter does not accumulate them.
+//!
+//! ## Usage
+//!
+//! ```python
+//! import z1
+//!
+//! estimate = z1.estimate(source)
+//! if estimate.budget is not None and estimate.total_tokens > estimate.budget:
+//!     print(max(estimate.functions, key=lambda f: f.tokens))
+//! for d in z1.check(source):
+//!     print(f"{d.line}:{d.column} {d.severity}[{d.code}] {d.message}")
+//! source = z1.format(source, "relaxed")
+//! ```
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use z1_ast::{Item, Module, Session, Span};
+use z1_driver::{CompileOptions, Compiler};
+use z1_fmt::{FmtOptions, Mode};
+use z1_policy::{PolicyChecker, PolicyLimits};
+
+create_exception!(
+    z1,
+    ParseError,
+    PyValueError,
+    "The source is not a valid Zero1 cell."
+);
+
+/// A parsed cell, summarized.
+#[pyclass(frozen, get_all, eq, module = "z1")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cell {
+    /// Dotted module path
+    pub name: String,
+    pub version: Option<String>,
+    /// The `ctx=` budget
+    pub ctx_budget: Option<u32>,
+    pub caps: Vec<String>,
+    /// Import paths, in source order
+    pub imports: Vec<String>,
+    /// Function names, in source order
+    pub functions: Vec<String>,
+    pub semhash: String,
+}
+
+/// Token estimate of a cell.
+#[pyclass(frozen, get_all, eq, module = "z1")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Estimate {
+    pub total_tokens: u32,
+    /// The `ctx=` budget
+    pub budget: Option<u32>,
+    /// Characters of the compact form the estimate is based on
+    pub chars: usize,
+    pub functions: Vec<FnEstimate>,
+}
+
+#[pyclass(frozen, get_all, eq, module = "z1")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FnEstimate {
+    pub name: String,
+    pub tokens: u32,
+    pub chars: usize,
+}
+
+/// A policy gate the cell fails.
+#[pyclass(frozen, get_all, eq, module = "z1")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub message: String,
+    /// The function at fault, for per-function limits
+    pub function: Option<String>,
+}
+
+/// A problem found by [`check`].
+#[pyclass(frozen, get_all, eq, module = "z1")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// `"error"` or `"warning"`
+    pub severity: String,
+    /// Pipeline stage, e.g. `"typeck"`
+    pub stage: String,
+    /// Code explained by `z1 explain`
+    pub code: Option<String>,
+    pub message: String,
+    /// One-based line and column (in characters) of the start, when known
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+macro_rules! dataclass_repr {
+    ($($class:ident: $($field:ident),+;)+) => {$(
+        #[pymethods]
+        impl $class {
+            fn __repr__(slf: &Bound<'_, Self>) -> PyResult<String> {
+                let fields = [$(
+                    format!(
+                        concat!(stringify!($field), "={}"),
+                        slf.getattr(stringify!($field))?.repr()?
+                    ),
+                )+];
+                Ok(format!(concat!(stringify!($class), "({})"), fields.join(", ")))
+            }
+        }
+    )+};
+}
+
+dataclass_repr! {
+    Cell: name, version, ctx_budget, caps, imports, functions, semhash;
+    Estimate: total_tokens, budget, chars, functions;
+    FnEstimate: name, tokens, chars;
+    Violation: message, function;
+    Diagnostic: severity, stage, code, message, line, column;
+}
+
+/// Parse `source`, raising `z1.ParseError` when it is not a valid cell.
+#[pyfunction]
+pub fn parse(source: &str) -> PyResult<Cell> {
+    Session::new().enter(|| {
+        let module = parse_module(source)?;
+        let mut cell = Cell {
+            name: module.path.to_string(),
+            version: module.version.clone(),
+            ctx_budget: module.ctx_budget,
+            caps: module.caps.clone(),
+            imports: Vec::new(),
+            functions: Vec::new(),
+            semhash: z1_hash::module_hashes(&module).semantic,
+        };
+        for item in &module.items {
+            match item {
+                Item::Import(import) => cell.imports.push(import.path.clone()),
+                Item::Fn(decl) => cell.functions.push(decl.name.to_string()),
+                _ => {}
+            }
+        }
+        Ok(cell)
+    })
+}
+
+/// Estimate the tokens of `source`, whole and per function. Going over the
+/// budget is not an error here; compare `total_tokens` with `budget`.
+#[pyfunction]
+#[pyo3(signature = (source, chars_per_token = z1_ctx::DEFAULT_CHARS_PER_TOKEN))]
+pub fn estimate(source: &str, chars_per_token: f64) -> PyResult<Estimate> {
+    Session::new().enter(|| {
+        if chars_per_token <= 0.0 {
+            return Err(PyValueError::new_err("chars_per_token must be positive"));
+        }
+        let module = parse_module(source)?;
+        let config = z1_ctx::EstimateConfig {
+            chars_per_token,
+            enforce_budget: false,
+        };
+        let estimate = z1_ctx::estimate_cell_with_config(&module, &config)
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        Ok(Estimate {
+            total_tokens: estimate.total_tokens,
+            budget: estimate.budget,
+            chars: estimate.char_count,
+            functions: estimate
+                .functions
+                .into_iter()
+                .map(|function| FnEstimate {
+                    name: function.name,
+                    tokens: function.tokens,
+                    chars: function.chars,
+                })
+                .collect(),
+        })
+    })
+}
+
+/// The policy gates `source` fails, under the default limits but for those
+/// given; an empty list when it passes.
+#[pyfunction]
+#[pyo3(signature = (
+    source,
+    *,
+    cell_max_ast_nodes = None,
+    cell_max_exports = None,
+    deps_max_fanin = None,
+    fn_max_params = None,
+    fn_max_locals = None,
+    ctx_max_per_fn = None,
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn check_policy(
+    source: &str,
+    cell_max_ast_nodes: Option<usize>,
+    cell_max_exports: Option<usize>,
+    deps_max_fanin: Option<usize>,
+    fn_max_params: Option<usize>,
+    fn_max_locals: Option<usize>,
+    ctx_max_per_fn: Option<u32>,
+) -> PyResult<Vec<Violation>> {
+    Session::new().enter(|| {
+        let module = parse_module(source)?;
+        let defaults = PolicyLimits::default();
+        let limits = PolicyLimits {
+            cell_max_ast_nodes: cell_max_ast_nodes.unwrap_or(defaults.cell_max_ast_nodes),
+            cell_max_exports: cell_max_exports.unwrap_or(defaults.cell_max_exports),
+            deps_max_fanin: deps_max_fanin.unwrap_or(defaults.deps_max_fanin),
+            fn_max_params: fn_max_params.unwrap_or(defaults.fn_max_params),
+            fn_max_locals: fn_max_locals.unwrap_or(defaults.fn_max_locals),
+            ctx_max_per_fn: ctx_max_per_fn.unwrap_or(defaults.ctx_max_per_fn),
+        };
+        let violations = match PolicyChecker::new(limits).check_module(&module) {
+            Ok(()) => Vec::new(),
+            Err(violations) => violations
+                .iter()
+                .map(|violation| Violation {
+                    message: violation.to_string(),
+                    function: violation.fn_name().map(str::to_string),
+                })
+                .collect(),
+        };
+        Ok(violations)
+    })
+}
+
+/// Run every check `z1 check` runs on `source`: parse, std imports, types,
+/// effects, context budget and policy. Returns the diagnostics found, parse
+/// errors included; the checks stop at the first failing stage.
+#[pyfunction]
+pub fn check(source: &str) -> Vec<Diagnostic> {
+    Session::new().enter(|| {
+        let compiler = Compiler::new(CompileOptions::default());
+        let result = compiler.compile_source(source);
+        result
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let (line, column) = diagnostic.span.map(|span| position(source, span)).unzip();
+                Diagnostic {
+                    severity: diagnostic.severity.name().to_string(),
+                    stage: diagnostic.stage.name().to_string(),
+                    code: diagnostic.code.map(str::to_string),
+                    message: diagnostic.message.clone(),
+                    line,
+                    column,
+                }
+            })
+            .collect()
+    })
+}
+
+/// `source` formatted in `mode`, `"compact"` or `"relaxed"`.
+#[pyfunction]
+#[pyo3(signature = (source, mode = "compact"))]
+pub fn format(source: &str, mode: &str) -> PyResult<String> {
+    Session::new().enter(|| {
+        let mode = match mode {
+            "compact" => Mode::Compact,
+            "relaxed" => Mode::Relaxed,
+            other => {
+                return Err(PyValueError::new_err(format!(
+                    "unknown format mode `{other}`"
+                )))
+            }
+        };
+        let module = parse_module(source)?;
+        z1_fmt::format_module(&module, mode, &FmtOptions::default())
+            .map_err(|err| PyValueError::new_err(err.to_string()))
+    })
+}
+
+fn parse_module(source: &str) -> PyResult<Module> {
+    z1_parse::parse_module(source).map_err(|err| ParseError::new_err(err.to_string()))
+}
+
+/// One-based line and column, in characters, of the start of `span`.
+fn position(source: &str, span: Span) -> (usize, usize) {
+    let before = source.get(..span.start as usize).unwrap_or(source);
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// The `z1` Python module.
+#[pymodule]
+pub fn z1(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    module.add("ParseError", module.py().get_type::<ParseError>())?;
+    module.add_class::<Cell>()?;
+    module.add_class::<Estimate>()?;
+    module.add_class::<FnEstimate>()?;
+    module.add_class::<Violation>()?;
+    module.add_class::<Diagnostic>()?;
+    module.add_function(wrap_pyfunction!(parse, module)?)?;
+    module.add_function(wrap_pyfunction!(estimate, module)?)?;
+    module.add_function(wrap_pyfunction!(check_policy, module)?)?;
+    module.add_function(wrap_pyfunction!(check, module)?)?;
+    module.add_function(wrap_pyfunction!(format, module)?)?;
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use pyo3::ffi::c_str;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+const CELL: &str = "m app:1.0 ctx=4 caps=[net]\nu \"std/http\" as H only [listen]\n\
+                    f serve(port: U16)->Unit eff [net] { ret Unit; }\n\
+                    f main()->U32 eff [pure] { ret 1; }\n";
+
+#[test]
+fn parses_and_estimates_cells() {
+    let cell = z1::parse(CELL).unwrap();
+    assert_eq!(cell.name, "app");
+    assert_eq!(cell.ctx_budget, Some(4));
+    assert_eq!(cell.imports, ["std/http"]);
+    assert_eq!(cell.functions, ["serve", "main"]);
+    assert!(cell.semhash.starts_with("sha3-256:"));
+
+    // Over budget, but estimated all the same
+    let estimate = z1::estimate(CELL, 3.8).unwrap();
+    assert_eq!(estimate.budget, Some(4));
+    assert!(estimate.total_tokens > 4);
+    let names: Vec<&str> = estimate.functions.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, ["serve", "main"]);
+    assert!(z1::estimate(CELL, 0.0).is_err());
+}
+
+#[test]
+fn checks_policies_and_the_whole_cell() {
+    let ok = z1::check_policy(CELL, None, None, None, None, None, None).unwrap();
+    assert!(ok.is_empty());
+    let violations = z1::check_policy(CELL, None, None, None, Some(0), None, None).unwrap();
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].function.as_deref(), Some("serve"));
+
+    let diagnostics = z1::check(CELL);
+    let error = diagnostics
+        .iter()
+        .find(|d| d.severity == "error")
+        .expect("over budget");
+    assert_eq!(
+        (error.stage.as_str(), error.code.as_deref()),
+        ("ctx", Some("C001"))
+    );
+
+    let diagnostics = z1::check("m app:1.0\nf main(->U32\n");
+    assert_eq!(diagnostics[0].stage, "parse");
+    assert_eq!(diagnostics[0].line, Some(2));
+}
+
+#[test]
+fn runs_from_python() {
+    Python::attach(|py| {
+        let module = pyo3::wrap_pymodule!(z1::z1)(py);
+        let globals = PyDict::new(py);
+        globals.set_item("z1", module).unwrap();
+        globals.set_item("source", CELL).unwrap();
+        py.run(
+            c_str!(
+                r#"
+estimate = z1.estimate(source)
+assert repr(estimate.functions[1]).startswith("FnEstimate(name='main', tokens="), estimate
+assert estimate == z1.estimate(source)
+assert z1.format(source, "relaxed").startswith("module app : 1.0")
+try:
+    z1.parse("m app:1.0\nf main(->U32\n")
+    raise AssertionError("no ParseError")
+except z1.ParseError as err:
+    assert isinstance(err, ValueError)
+try:
+    estimate.total_tokens = 0
+    raise AssertionError("not frozen")
+except AttributeError:
+    pass
+"#
+            ),
+            Some(&globals),
+            None,
+        )
+        .unwrap();
+    });
+}
@@ -0,0 +1,54 @@
+"""Type stubs for the `z1` extension module."""
+
+from typing import Literal, Optional
+
+__version__: str
+
+class ParseError(ValueError): ...
+
+class Cell:
+    name: str
+    version: Optional[str]
+    ctx_budget: Optional[int]
+    caps: list[str]
+    imports: list[str]
+    functions: list[str]
+    semhash: str
+
+class FnEstimate:
+    name: str
+    tokens: int
+    chars: int
+
+class Estimate:
+    total_tokens: int
+    budget: Optional[int]
+    chars: int
+    functions: list[FnEstimate]
+
+class Violation:
+    message: str
+    function: Optional[str]
+
+class Diagnostic:
+    severity: Literal["error", "warning"]
+    stage: str
+    code: Optional[str]
+    message: str
+    line: Optional[int]
+    column: Optional[int]
+
+def parse(source: str) -> Cell: ...
+def estimate(source: str, chars_per_token: float = 3.8) -> Estimate: ...
+def check_policy(
+    source: str,
+    *,
+    cell_max_ast_nodes: Optional[int] = None,
+    cell_max_exports: Optional[int] = None,
+    deps_max_fanin: Optional[int] = None,
+    fn_max_params: Optional[int] = None,
+    fn_max_locals: Optional[int] = None,
+    ctx_max_per_fn: Optional[int] = None,
+) -> list[Violation]: ...
+def check(source: str) -> list[Diagnostic]: ...
+def format(source: str, mode: Literal["compact", "relaxed"] = "compact") -> str: ...
@@ -0,0 +1,891 @@
+//! Refactorings of Zero1 cells.
+//!
+//! [`rename`] renames the declaration an identifier resolves to, and every
+//! mention of that declaration: the signatures and bodies that use it, and
+//! the `#sym` table, whether it is the long or the short name of a pair.
+//! A name resolves to a function, type, variant or import alias of the cell
+//! first, then to a record field, then to the parameters and locals of the
+//! functions that declare it. Only mentions of that declaration change: a
+//! field or local that shares the name of a renamed function is left alone,
+//! as is a function or field that shares the name of a renamed local, and
+//! so are members of an import reached through its alias (`H.Req`), which
+//! belong to the imported cell. The rename is checked before anything
+//! changes: it fails, leaving the cell as it was, when the new name is not
+//! an identifier, is already a field or local it would be confused with,
+//! or is otherwise already used, or when the old one is not declared in the
+//! cell or comes from an import.
+//!
+//! [`rename`] works on the parsed cell and leaves spans as they were;
+//! [`rename_source`] edits the text of a cell in place, so comments and
+//! layout are kept and only the renamed identifiers change.
+//!
+//! ## Usage
+//!
+//! ```rust
+//! use z1_refactor::rename_source;
+//!
+//! let renamed = rename_source(
+//!     "m app:1.0\n#sym { total: t1 }\nf total(x: U32)->U32 eff [pure] { ret x; }\n\
+//!      f twice(x: U32)->U32 eff [pure] { ret total(x) + total(x); }\n",
+//!     "total",
+//!     "sum",
+//! )
+//! .unwrap();
+//! assert_eq!(renamed.count, 4);
+//! assert!(renamed.source.contains("#sym { sum: t1 }"));
+//! assert!(renamed.source.contains("ret sum(x) + sum(x);"));
+//! ```
+
+use std::collections::BTreeSet;
+
+use thiserror::Error;
+use z1_ast::{Block, ElseBlock, Expr, Ident, Item, Module, Stmt, Symbol, TypeExpr};
+use z1_lex::{lex, TokenKind};
+
+/// Errors that can occur while renaming an identifier.
+#[derive(Debug, Error)]
+pub enum RenameError {
+    #[error("`{0}` is not a valid identifier")]
+    InvalidName(String),
+
+    #[error("`{0}` is not declared in the cell")]
+    NotDeclared(String),
+
+    #[error("`{name}` is imported from `{import}`; rename it there")]
+    Imported { name: String, import: String },
+
+    #[error("`{name}` is already a field of `{record}`")]
+    Field { name: String, record: String },
+
+    #[error("`{name}` is already a local of `{scope}`")]
+    Local { name: String, scope: String },
+
+    #[error("`{0}` is already used in the cell")]
+    Taken(String),
+
+    #[error(transparent)]
+    Parse(#[from] z1_parse::ParseError),
+}
+
+/// A cell's text after [`rename_source`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenamedSource {
+    pub source: String,
+    /// Identifiers renamed
+    pub count: usize,
+}
+
+/// Rename `old` to `new` throughout `module`, returning the number of
+/// identifiers renamed. Function bodies are renamed in their statements and
+/// in their source text alike.
+pub fn rename(module: &mut Module, old: &str, new: &str) -> Result<usize, RenameError> {
+    let renamer = Renamer::new(module, old, new)?;
+    let mut count = 0;
+    for item in &mut module.items {
+        match item {
+            Item::Import(import) => {
+                if let Some(alias) = &mut import.alias {
+                    count += renamer.name(alias, Role::Item);
+                }
+            }
+            Item::Symbol(symbols) => {
+                for pair in &mut symbols.pairs {
+                    count += renamer.name(&mut pair.long, Role::Symbol)
+                        + renamer.name(&mut pair.short, Role::Symbol);
+                }
+            }
+            Item::Type(decl) => {
+                let scope = decl.type_params.clone();
+                count += renamer.name(&mut decl.name, Role::Item)
+                    + renamer.type_params(&mut decl.type_params);
+                count += renamer.type_expr(&mut decl.expr, &scope);
+            }
+            Item::Fn(decl) => {
+                let mut scope = decl.type_params.clone();
+                scope.extend(decl.params.iter().map(|param| param.name));
+                count += renamer.name(&mut decl.name, Role::Item)
+                    + renamer.type_params(&mut decl.type_params);
+                for param in &mut decl.params {
+                    count += renamer.name(&mut param.name, Role::Local)
+                        + renamer.type_expr(&mut param.ty, &scope);
+                }
+                count += renamer.type_expr(&mut decl.ret, &scope)
+                    + renamer.block(&mut decl.body, &scope);
+            }
+            Item::Test(test) => count += renamer.block(&mut test.body, &[]),
+        }
+    }
+    Ok(count)
+}
+
+/// Rename `old` to `new` in the cell `source`, editing only the renamed
+/// identifiers.
+pub fn rename_source(source: &str, old: &str, new: &str) -> Result<RenamedSource, RenameError> {
+    let module = z1_parse::parse_module(source)?;
+    let renamer = Renamer::new(&module, old, new)?;
+    // The header names the module and its caps, never a declaration
+    let start = module
+        .items
+        .first()
+        .map_or(source.len(), |item| item_start(item) as usize);
+    let (body, count) = renamer.text(&source[start..], &[]);
+    let source = format!("{}{body}", &source[..start]);
+    z1_parse::parse_module(&source)?;
+    Ok(RenamedSource { source, count })
+}
+
+/// Whether `name` lexes as a single undotted identifier, not a keyword.
+pub fn is_identifier(name: &str) -> bool {
+    !name.contains('.')
+        && matches!(
+            lex(name).as_slice(),
+            [token, _eof] if token.kind == TokenKind::Ident && token.lexeme == name
+        )
+}
+
+fn item_start(item: &Item) -> u32 {
+    match item {
+        Item::Import(import) => import.span.start,
+        Item::Symbol(symbols) => symbols.span.start,
+        Item::Type(decl) => decl.span.start,
+        Item::Fn(decl) => decl.span.start,
+        Item::Test(test) => test.span.start,
+    }
+}
+
+/// What a mention of a name refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    /// A function, type, variant or import alias of the cell
+    Item,
+    /// A field of a record
+    Field,
+    /// A parameter, type parameter or local
+    Local,
+    /// A name of the `#sym` table, renamed whatever the name declares
+    Symbol,
+    /// A member of an import, which belongs to the imported cell
+    Import,
+}
+
+/// A checked rename of `old` to `new`.
+struct Renamer {
+    old: Ident,
+    new: Ident,
+    /// What `old` resolves to; only its mentions are renamed
+    role: Role,
+    /// Import aliases, whose members are not renamed
+    aliases: BTreeSet<Ident>,
+}
+
+impl Renamer {
+    fn new(module: &Module, old: &str, new: &str) -> Result<Self, RenameError> {
+        if !is_identifier(new) {
+            return Err(RenameError::InvalidName(new.to_string()));
+        }
+        let mut aliases = BTreeSet::new();
+        for item in &module.items {
+            if let Item::Import(import) = item {
+                if import.only.iter().any(|name| name == old) {
+                    return Err(RenameError::Imported {
+                        name: old.to_string(),
+                        import: import.path.clone(),
+                    });
+                }
+                aliases.extend(import.alias);
+            }
+        }
+        let (old, new) = (Symbol::intern(old), Symbol::intern(new));
+        let names = Names::of(module, &aliases);
+        let role = names
+            .resolve(old)
+            .ok_or_else(|| RenameError::NotDeclared(old.to_string()))?;
+        if old != new {
+            names.check(role, old, new)?;
+        }
+        Ok(Self {
+            old,
+            new,
+            role,
+            aliases,
+        })
+    }
+
+    /// Whether a mention of `name` as `role` is renamed.
+    fn renames(&self, name: Ident, role: Role) -> bool {
+        name == self.old && (role == self.role || role == Role::Symbol)
+    }
+
+    fn name(&self, name: &mut Ident, role: Role) -> usize {
+        if !self.renames(*name, role) {
+            return 0;
+        }
+        *name = self.new;
+        1
+    }
+
+    /// Rename a name that refers to a local in `scope` or else to an item.
+    fn reference(&self, name: &mut Ident, scope: &[Ident]) -> usize {
+        let role = if scope.contains(name) {
+            Role::Local
+        } else {
+            Role::Item
+        };
+        self.name(name, role)
+    }
+
+    /// Rename the segments of a path, but not the members of an import.
+    fn path(&self, segments: &mut [Ident], scope: &[Ident]) -> usize {
+        let Some((first, fields)) = segments.split_first_mut() else {
+            return 0;
+        };
+        let alias = !scope.contains(first) && self.aliases.contains(first);
+        let mut count = self.reference(first, scope);
+        if !alias {
+            count += fields
+                .iter_mut()
+                .map(|field| self.name(field, Role::Field))
+                .sum::<usize>();
+        }
+        count
+    }
+
+    fn type_params(&self, params: &mut [Ident]) -> usize {
+        params
+            .iter_mut()
+            .map(|param| self.name(param, Role::Local))
+            .sum()
+    }
+
+    fn type_expr(&self, expr: &mut TypeExpr, scope: &[Ident]) -> usize {
+        match expr {
+            TypeExpr::Path(segments) => self.path(segments, scope),
+            TypeExpr::Record(fields) => fields
+                .iter_mut()
+                .map(|field| {
+                    self.name(&mut field.name, Role::Field) + self.type_expr(&mut field.ty, scope)
+                })
+                .sum(),
+            TypeExpr::Union(variants) => variants
+                .iter_mut()
+                .map(|variant| {
+                    self.name(&mut variant.name, Role::Item)
+                        + variant
+                            .payload
+                            .as_deref_mut()
+                            .map_or(0, |payload| self.type_expr(payload, scope))
+                })
+                .sum(),
+            TypeExpr::Generic { base, args } => {
+                self.path(base, scope)
+                    + args
+                        .iter_mut()
+                        .map(|arg| self.type_expr(arg, scope))
+                        .sum::<usize>()
+            }
+        }
+    }
+
+    /// Rename a block's text and statements; the text has every mention
+    /// once, so only its renames are counted. `scope` holds the locals
+    /// declared around the block, by their names before the rename.
+    fn block(&self, block: &mut Block, scope: &[Ident]) -> usize {
+        let (raw, count) = self.text(&block.raw, scope);
+        block.raw = raw;
+        let mut scope = scope.to_vec();
+        for stmt in &mut block.statements {
+            self.stmt(stmt, &mut scope);
+        }
+        count
+    }
+
+    fn stmt(&self, stmt: &mut Stmt, scope: &mut Vec<Ident>) {
+        match stmt {
+            Stmt::Let(decl) => {
+                if let Some(ty) = &mut decl.ty {
+                    self.type_expr(ty, scope);
+                }
+                // The local is in scope after its initializer
+                self.expr(&mut decl.init, scope);
+                scope.push(decl.name);
+                self.name(&mut decl.name, Role::Local);
+            }
+            Stmt::Assign(assign) => {
+                self.expr(&mut assign.target, scope);
+                self.expr(&mut assign.value, scope);
+            }
+            Stmt::If(stmt) => self.if_stmt(stmt, scope),
+            Stmt::While(stmt) => {
+                self.expr(&mut stmt.cond, scope);
+                self.block(&mut stmt.body, scope);
+            }
+            Stmt::Return(stmt) => {
+                if let Some(value) = &mut stmt.value {
+                    self.expr(value, scope);
+                }
+            }
+            Stmt::Expr(stmt) => self.expr(&mut stmt.expr, scope),
+        }
+    }
+
+    fn if_stmt(&self, stmt: &mut z1_ast::IfStmt, scope: &[Ident]) {
+        self.expr(&mut stmt.cond, scope);
+        self.block(&mut stmt.then_block, scope);
+        match stmt.else_block.as_deref_mut() {
+            Some(ElseBlock::Block(block)) => {
+                self.block(block, scope);
+            }
+            Some(ElseBlock::If(stmt)) => self.if_stmt(stmt, scope),
+            None => {}
+        }
+    }
+
+    fn expr(&self, expr: &mut Expr, scope: &[Ident]) {
+        match expr {
+            Expr::Ident(name, _) => {
+                self.reference(name, scope);
+            }
+            Expr::Literal(..) => {}
+            Expr::BinOp { lhs, rhs, .. } => {
+                self.expr(lhs, scope);
+                self.expr(rhs, scope);
+            }
+            Expr::UnaryOp { expr, .. } | Expr::Paren(expr, _) => self.expr(expr, scope),
+            Expr::Call { func, args, .. } => {
+                self.expr(func, scope);
+                args.iter_mut().for_each(|arg| self.expr(arg, scope));
+            }
+            Expr::Field { base, field, .. } => {
+                let import = matches!(
+                    &**base,
+                    Expr::Ident(alias, _) if !scope.contains(alias) && self.aliases.contains(alias)
+                );
+                if !import {
+                    self.name(field, Role::Field);
+                }
+                self.expr(base, scope);
+            }
+            Expr::Record { fields, .. } => {
+                for field in fields {
+                    self.name(&mut field.name, Role::Field);
+                    self.expr(&mut field.value, scope);
+                }
+            }
+            Expr::RecordUpdate { base, fields, .. } => {
+                self.expr(base, scope);
+                for field in fields {
+                    self.name(&mut field.name, Role::Field);
+                    self.expr(&mut field.value, scope);
+                }
+            }
+            Expr::Path(segments, _) => {
+                self.path(segments, scope);
+            }
+        }
+    }
+
+    /// Rename the identifiers of `text`, a part of a cell after its header
+    /// with the locals of `scope` around it, leaving everything between
+    /// them as it is.
+    fn text(&self, text: &str, scope: &[Ident]) -> (String, usize) {
+        let mut out = String::with_capacity(text.len());
+        let mut copied = 0;
+        let mut count = 0;
+        for mention in mentions(text, &self.aliases, scope) {
+            if !self.renames(mention.name, mention.role) {
+                continue;
+            }
+            out.push_str(&text[copied..mention.start]);
+            out.push_str(self.new.as_str());
+            copied = mention.end;
+            count += 1;
+        }
+        out.push_str(&text[copied..]);
+        (out, count)
+    }
+}
+
+/// An identifier in the text of a cell, and what it refers to.
+struct Mention {
+    start: usize,
+    end: usize,
+    name: Ident,
+    role: Role,
+    /// Whether the mention declares a parameter or local
+    binding: bool,
+}
+
+/// Bracketed lists whose identifiers are not ordinary mentions.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum List {
+    /// `eff [...]`, effects rather than names
+    Effects,
+    /// `only [...]` of an import
+    Only,
+    /// `#sym { ... }`
+    Symbols,
+    /// `[T, U]` after the name of a function or type
+    TypeParams,
+}
+
+/// The identifiers of `text`, a part of a cell after its header, in order,
+/// each resolved against the locals in scope where it appears; `scope`
+/// holds the locals around the text.
+fn mentions(text: &str, aliases: &BTreeSet<Ident>, scope: &[Ident]) -> Vec<Mention> {
+    let tokens = lex(text);
+    let mut mentions = Vec::new();
+    // Locals in scope with the brace depth they were declared at; a `let`
+    // is in scope from the end of its statement
+    let mut locals: Vec<(Ident, usize)> = scope.iter().map(|name| (*name, 0)).collect();
+    let mut pending: Option<(Ident, usize)> = None;
+    let mut open: Vec<TokenKind> = Vec::new();
+    let mut list: Option<(List, usize)> = None;
+    let kind_at = |idx: Option<usize>| idx.and_then(|idx| tokens.get(idx)).map(|t| t.kind);
+    for (idx, token) in tokens.iter().enumerate() {
+        let depth = open
+            .iter()
+            .filter(|kind| **kind == TokenKind::LBrace)
+            .count();
+        let prev = kind_at(idx.checked_sub(1));
+        match token.kind {
+            // Each item starts a new scope
+            TokenKind::KwUse
+            | TokenKind::Sym
+            | TokenKind::KwType
+            | TokenKind::KwFn
+            | TokenKind::Hash
+                if open.is_empty() =>
+            {
+                locals.clear();
+                pending = None;
+            }
+            TokenKind::LBrace | TokenKind::LParen | TokenKind::LBracket => {
+                let entered = match (token.kind, prev) {
+                    (TokenKind::LBracket, Some(TokenKind::KwEff)) => Some(List::Effects),
+                    (TokenKind::LBracket, Some(TokenKind::KwOnly)) => Some(List::Only),
+                    (TokenKind::LBrace, Some(TokenKind::Sym)) => Some(List::Symbols),
+                    (TokenKind::LBracket, Some(TokenKind::Ident))
+                        if matches!(
+                            kind_at(idx.checked_sub(2)),
+                            Some(TokenKind::KwFn | TokenKind::KwType)
+                        ) =>
+                    {
+                        Some(List::TypeParams)
+                    }
+                    _ => None,
+                };
+                if let Some(entered) = entered {
+                    list = Some((entered, open.len()));
+                }
+                open.push(token.kind);
+            }
+            TokenKind::RBrace | TokenKind::RParen | TokenKind::RBracket => {
+                open.pop();
+                if list.is_some_and(|(_, at)| open.len() <= at) {
+                    list = None;
+                }
+                let depth = open
+                    .iter()
+                    .filter(|kind| **kind == TokenKind::LBrace)
+                    .count();
+                locals.retain(|(_, at)| *at <= depth);
+            }
+            TokenKind::Semi => {
+                if let Some(local) = pending.filter(|(_, at)| *at == depth) {
+                    locals.push(local);
+                    pending = None;
+                }
+            }
+            _ => {}
+        }
+        if token.kind != TokenKind::Ident {
+            continue;
+        }
+        let start = token.span.start as usize;
+        let mut mention = |offset: usize, name: &str, role: Role, binding: bool| {
+            mentions.push(Mention {
+                start: start + offset,
+                end: start + offset + name.len(),
+                name: Symbol::intern(name),
+                role,
+                binding,
+            });
+        };
+        let next = kind_at(Some(idx + 1));
+        let role = match list.map(|(list, _)| list) {
+            Some(List::Effects) => continue,
+            Some(List::Only) => Role::Import,
+            Some(List::Symbols) => Role::Symbol,
+            Some(List::TypeParams) => {
+                locals.push((Symbol::intern(token.lexeme), depth));
+                mention(0, token.lexeme, Role::Local, true);
+                continue;
+            }
+            None => match prev {
+                // `#test "name"`
+                Some(TokenKind::Hash) => continue,
+                Some(TokenKind::KwFn | TokenKind::KwType | TokenKind::KwAs) => Role::Item,
+                Some(TokenKind::Dot) => Role::Field,
+                _ if prev == Some(TokenKind::KwLet)
+                    || prev == Some(TokenKind::KwMut)
+                        && kind_at(idx.checked_sub(2)) == Some(TokenKind::KwLet) =>
+                {
+                    pending = Some((Symbol::intern(token.lexeme), depth));
+                    mention(0, token.lexeme, Role::Local, true);
+                    continue;
+                }
+                _ if next == Some(TokenKind::Colon) && open.last() == Some(&TokenKind::LParen) => {
+                    locals.push((Symbol::intern(token.lexeme), depth));
+                    mention(0, token.lexeme, Role::Local, true);
+                    continue;
+                }
+                _ if next == Some(TokenKind::Colon) && open.last() == Some(&TokenKind::LBrace) => {
+                    Role::Field
+                }
+                _ => {
+                    // Dotted names such as `req.size` or `H.Req` lex as one
+                    // token: a reference and the fields or members after it
+                    let mut offset = 0;
+                    let mut import = false;
+                    for (n, segment) in token.lexeme.split('.').enumerate() {
+                        let name = Symbol::intern(segment);
+                        let role = if n > 0 {
+                            if import {
+                                Role::Import
+                            } else {
+                                Role::Field
+                            }
+                        } else if locals.iter().any(|(local, _)| *local == name) {
+                            Role::Local
+                        } else {
+                            import = aliases.contains(&name);
+                            Role::Item
+                        };
+                        mention(offset, segment, role, false);
+                        offset += segment.len() + 1;
+                    }
+                    continue;
+                }
+            },
+        };
+        mention(0, token.lexeme, role, false);
+    }
+    mentions
+}
+
+/// Names a cell declares, and every name it uses outside a local scope.
+#[derive(Default)]
+struct Names {
+    /// Functions, types, variants and import aliases
+    items: BTreeSet<Ident>,
+    /// Names of the `#sym` table
+    symbols: BTreeSet<Ident>,
+    /// Fields of each record, by the type or variant it belongs to
+    records: Vec<(Ident, BTreeSet<Ident>)>,
+    /// Parameters, type parameters and locals, by the declaration they
+    /// belong to
+    locals: Vec<(String, BTreeSet<Ident>)>,
+    used: BTreeSet<Ident>,
+}
+
+impl Names {
+    fn of(module: &Module, aliases: &BTreeSet<Ident>) -> Self {
+        let mut names = Self::default();
+        for item in &module.items {
+            match item {
+                Item::Import(import) => {
+                    names.items.extend(import.alias);
+                    names.used.extend(import.only.iter().cloned());
+                }
+                Item::Symbol(symbols) => {
+                    for pair in &symbols.pairs {
+                        names.symbols.insert(pair.long);
+                        names.symbols.insert(pair.short);
+                    }
+                }
+                Item::Type(decl) => {
+                    names.items.insert(decl.name);
+                    names.type_expr(&decl.expr, decl.name, &decl.type_params);
+                    let locals = decl.type_params.iter().copied().collect();
+                    names.locals.push((decl.name.to_string(), locals));
+                }
+                Item::Fn(decl) => {
+                    names.items.insert(decl.name);
+                    let mut scope = decl.type_params.clone();
+                    scope.extend(decl.params.iter().map(|param| param.name));
+                    for param in &decl.params {
+                        names.type_expr(&param.ty, decl.name, &scope);
+                    }
+                    names.type_expr(&decl.ret, decl.name, &scope);
+                    let mut locals: BTreeSet<Ident> = scope.iter().copied().collect();
+                    names.body(&decl.body.raw, aliases, &scope, &mut locals);
+                    names.locals.push((decl.name.to_string(), locals));
+                }
+                Item::Test(test) => {
+                    let mut locals = BTreeSet::new();
+                    names.body(&test.body.raw, aliases, &[], &mut locals);
+                    names
+                        .locals
+                        .push((format!("test \"{}\"", test.name), locals));
+                }
+            }
+        }
+        names.used.extend(names.items.iter().copied());
+        names.used.extend(names.symbols.iter().copied());
+        names
+    }
+
+    /// What a mention of `name` at the top of the cell resolves to.
+    fn resolve(&self, name: Ident) -> Option<Role> {
+        if self.items.contains(&name) {
+            Some(Role::Item)
+        } else if self
+            .records
+            .iter()
+            .any(|(_, fields)| fields.contains(&name))
+        {
+            Some(Role::Field)
+        } else if self.locals.iter().any(|(_, locals)| locals.contains(&name)) {
+            Some(Role::Local)
+        } else if self.symbols.contains(&name) {
+            Some(Role::Item)
+        } else {
+            None
+        }
+    }
+
+    /// Check that renaming `old`, resolved to `role`, to `new` leaves every
+    /// mention of both referring to what it did.
+    fn check(&self, role: Role, old: Ident, new: Ident) -> Result<(), RenameError> {
+        let field = |old: Option<Ident>| {
+            self.records
+                .iter()
+                .find(|(_, fields)| {
+                    fields.contains(&new) && old.map_or(true, |old| fields.contains(&old))
+                })
+                .map(|(record, _)| RenameError::Field {
+                    name: new.to_string(),
+                    record: record.to_string(),
+                })
+        };
+        let local = |old: Option<Ident>| {
+            self.locals
+                .iter()
+                .find(|(_, locals)| {
+                    locals.contains(&new) && old.map_or(true, |old| locals.contains(&old))
+                })
+                .map(|(scope, _)| RenameError::Local {
+                    name: new.to_string(),
+                    scope: scope.clone(),
+                })
+        };
+        let taken = || {
+            self.used
+                .contains(&new)
+                .then(|| RenameError::Taken(new.to_string()))
+        };
+        let err = match role {
+            // Fields live apart from other names, but a new item name
+            // would be read as the field or local it shares a name with
+            Role::Item => field(None).or_else(|| local(None)).or_else(taken),
+            Role::Field => field(Some(old)),
+            _ => local(Some(old)).or_else(taken),
+        };
+        err.map_or(Ok(()), Err)
+    }
+
+    fn type_expr(&mut self, expr: &TypeExpr, owner: Ident, scope: &[Ident]) {
+        match expr {
+            TypeExpr::Path(segments) => self.path(segments, scope),
+            TypeExpr::Record(fields) => {
+                let names = fields.iter().map(|field| field.name).collect();
+                self.records.push((owner, names));
+                for field in fields {
+                    self.type_expr(&field.ty, owner, scope);
+                }
+            }
+            TypeExpr::Union(variants) => {
+                for variant in variants {
+                    self.items.insert(variant.name);
+                    if let Some(payload) = &variant.payload {
+                        self.type_expr(payload, variant.name, scope);
+                    }
+                }
+            }
+            TypeExpr::Generic { base, args } => {
+                self.path(base, scope);
+                for arg in args {
+                    self.type_expr(arg, owner, scope);
+                }
+            }
+        }
+    }
+
+    fn path(&mut self, segments: &[Ident], scope: &[Ident]) {
+        if let Some(first) = segments.first().filter(|first| !scope.contains(first)) {
+            self.used.insert(*first);
+        }
+    }
+
+    /// The locals a body declares, and the names it uses outside them.
+    fn body(
+        &mut self,
+        raw: &str,
+        aliases: &BTreeSet<Ident>,
+        scope: &[Ident],
+        locals: &mut BTreeSet<Ident>,
+    ) {
+        for mention in mentions(raw, aliases, scope) {
+            match mention.role {
+                Role::Local if mention.binding => {
+                    locals.insert(mention.name);
+                }
+                Role::Item => {
+                    self.used.insert(mention.name);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CELL: &str = "m app:1.0 caps=[net]\n\
+        u \"std/http\" as H only [listen]\n\
+        #sym { handler: h }\n\
+        t Req = { path: Str, size: U32 }\n\
+        // handler answers every request\n\
+        f handler(req: Req)->H.Res eff [pure] { let size = req.size; ret H.Res { status: size }; }\n\
+        f serve(p: U16)->Unit eff [net] { H.listen(p, handler); }\n";
+
+    #[test]
+    fn renames_declarations_bodies_and_symbols() {
+        let renamed = rename_source(CELL, "handler", "respond").unwrap();
+        assert_eq!(renamed.count, 3);
+        assert!(renamed.source.contains("#sym { respond: h }"));
+        assert!(renamed.source.contains("f respond(req: Req)"));
+        assert!(renamed.source.contains("H.listen(p, respond);"));
+        // Comments are left as they are
+        assert!(renamed.source.contains("// handler answers every request"));
+
+        let mut module = z1_parse::parse_module(CELL).unwrap();
+        let spans: Vec<_> = module.items.iter().map(item_start).collect();
+        assert_eq!(rename(&mut module, "handler", "respond").unwrap(), 3);
+        let Item::Fn(decl) = &module.items[3] else {
+            panic!("expected a function");
+        };
+        assert_eq!(decl.name, "respond");
+        assert_eq!(
+            module.items.iter().map(item_start).collect::<Vec<_>>(),
+            spans
+        );
+        let Item::Fn(serve) = &module.items[4] else {
+            panic!("expected a function");
+        };
+        assert!(serve.body.raw.contains("H.listen(p, respond);"));
+    }
+
+    #[test]
+    fn leaves_import_members_alone() {
+        // `Res` and `status` here belong to `std/http`; `size` resolves to
+        // the field of `Req`, not the local of the same name
+        let renamed = rename_source(CELL, "size", "bytes").unwrap();
+        assert_eq!(renamed.count, 2, "{}", renamed.source);
+        assert!(renamed.source.contains("t Req = { path: Str, bytes: U32 }"));
+        assert!(renamed
+            .source
+            .contains("let size = req.bytes; ret H.Res { status: size };"));
+
+        let renamed = rename_source(CELL, "H", "Http").unwrap();
+        assert!(renamed.source.contains("as Http only"));
+        assert!(renamed.source.contains("Http.listen(p, handler);"));
+        assert_eq!(renamed.count, 4, "{}", renamed.source);
+    }
+
+    #[test]
+    fn renames_only_the_declaration_a_name_resolves_to() {
+        const SHADOWED: &str = "m rn:1.0\n\
+            t P = { count: U32 }\n\
+            f count(p: P)->U32 eff [pure] { let n = count(p); let count = p.count; ret count + n; }\n\
+            f twice(count: P)->U32 eff [pure] { ret count.count; }\n\
+            f both(p: P)->U32 eff [pure] { ret count(p) + count(p); }\n";
+        let renamed = rename_source(SHADOWED, "count", "total").unwrap();
+        assert_eq!(renamed.count, 4, "{}", renamed.source);
+        assert!(renamed.source.contains("t P = { count: U32 }"));
+        assert!(renamed.source.contains(
+            "f total(p: P)->U32 eff [pure] { let n = total(p); let count = p.count; ret count + n; }"
+        ));
+        assert!(renamed
+            .source
+            .contains("f twice(count: P)->U32 eff [pure] { ret count.count; }"));
+        assert!(renamed.source.contains("ret total(p) + total(p);"));
+
+        let mut module = z1_parse::parse_module(SHADOWED).unwrap();
+        assert_eq!(rename(&mut module, "count", "total").unwrap(), 4);
+        let Item::Fn(decl) = &module.items[1] else {
+            panic!("expected a function");
+        };
+        assert_eq!(decl.name, "total");
+        let [Stmt::Let(call), Stmt::Let(local), Stmt::Return(_)] = decl.body.statements.as_slice()
+        else {
+            panic!("expected two lets and a return");
+        };
+        assert!(matches!(&call.init, Expr::Call { func, .. }
+            if matches!(&**func, Expr::Ident(name, _) if name == "total")));
+        assert_eq!(local.name, "count");
+        assert!(matches!(&local.init, Expr::Field { field, .. } if field == "count"));
+
+        // A local resolves to the parameters and locals of its functions
+        let renamed = rename_source(CELL, "req", "request").unwrap();
+        assert_eq!(renamed.count, 2, "{}", renamed.source);
+        assert!(renamed.source.contains("f handler(request: Req)"));
+        assert!(renamed.source.contains("let size = request.size;"));
+    }
+
+    #[test]
+    fn rejects_renames_onto_fields_and_locals() {
+        let cell = "m rn:1.0\n\
+            t P = { count: U32, total: U32 }\n\
+            f count(p: P)->U32 eff [pure] { let sum = p.count; ret sum; }\n";
+        let err = |old, new| rename_source(cell, old, new).unwrap_err().to_string();
+        assert_eq!(err("count", "total"), "`total` is already a field of `P`");
+        assert_eq!(err("count", "sum"), "`sum` is already a local of `count`");
+        assert_eq!(err("sum", "p"), "`p` is already a local of `count`");
+        assert_eq!(err("sum", "P"), "`P` is already used in the cell");
+        assert_eq!(err("total", "count"), "`count` is already a field of `P`");
+        // Fields and locals do not clash with each other
+        assert!(rename_source(cell, "sum", "total").is_ok());
+        assert!(rename_source(CELL, "path", "req").is_ok());
+    }
+
+    #[test]
+    fn rejects_renames_that_would_change_the_cell() {
+        let err = |old, new| rename_source(CELL, old, new).unwrap_err().to_string();
+        assert_eq!(err("handler", "fn"), "`fn` is not a valid identifier");
+        assert_eq!(err("handler", "a b"), "`a b` is not a valid identifier");
+        assert_eq!(err("handler", "H.h"), "`H.h` is not a valid identifier");
+        assert_eq!(
+            err("handle", "respond"),
+            "`handle` is not declared in the cell"
+        );
+        assert_eq!(
+            err("listen", "serve_on"),
+            "`listen` is imported from `std/http`; rename it there"
+        );
+        assert_eq!(
+            err("handler", "serve"),
+            "`serve` is already used in the cell"
+        );
+        assert_eq!(err("handler", "Str"), "`Str` is already used in the cell");
+        // Renaming the short name of a pair keeps its long name
+        let renamed = rename_source(CELL, "h", "hd").unwrap();
+        assert!(renamed.source.contains("#sym { handler: hd }"));
+
+        let mut module = z1_parse::parse_module(CELL).unwrap();
+        let before = module.clone();
+        assert!(rename(&mut module, "handler", "serve").is_err());
+        assert_eq!(module, before);
+    }
+}
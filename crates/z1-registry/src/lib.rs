@@ -0,0 +1,80 @@
+//! Package registry for sharing cells between projects
+//!
+//! A registry is a directory, typically on shared storage:
+//!
+//! ```text
+//! <registry>/objects/<algo>/<hex>             cells, content-addressed
+//! <registry>/packages/<name>/<version>.json   package records
+//! <registry>/packages/<name>/<version>.z1p    provenance chains
+//! ```
+//!
+//! Cells are kept in a [`z1_store::Store`], so a cell shared by several
+//! packages or versions is stored once and every read is re-hashed. A
+//! [`PackageRecord`] maps each cell's path to its semantic hash and carries
+//! a digest over the whole record; a published version is never replaced.
+//! When a package comes with a provenance chain, the chain is verified on
+//! publish and again on fetch, and must bind every module it records to the
+//! published cells.
+//!
+//! Projects pin what they fetched in a [`Lockfile`] (`z1.lock`). Packages
+//! are fetched without their own dependencies.
+
+mod lockfile;
+mod registry;
+
+use std::io;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+pub use lockfile::{LockedPackage, Lockfile, LOCK_FILE};
+pub use registry::{Package, PackageRecord, Registry};
+
+#[derive(Debug, Error)]
+pub enum RegistryError {
+    #[error("I/O error at {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error(transparent)]
+    Store(#[from] z1_store::StoreError),
+    #[error("invalid package name `{0}`")]
+    InvalidName(String),
+    #[error("invalid version `{version}`: {message}")]
+    InvalidVersion { version: String, message: String },
+    #[error("package `{0}` not found in the registry")]
+    UnknownPackage(String),
+    #[error("package `{name}` has no version {version}")]
+    UnknownVersion { name: String, version: String },
+    #[error("{name} {version} is already published")]
+    AlreadyPublished { name: String, version: String },
+    #[error("package record {path} is malformed: {message}")]
+    MalformedRecord { path: PathBuf, message: String },
+    #[error("{name} {version} does not match its digest: recorded {expected}, computed {actual}")]
+    DigestMismatch {
+        name: String,
+        version: String,
+        expected: String,
+        actual: String,
+    },
+    #[error(
+        "{name} {version} in the registry does not match z1.lock: locked {locked}, found {found}"
+    )]
+    LockMismatch {
+        name: String,
+        version: String,
+        locked: String,
+        found: String,
+    },
+    #[error("provenance of {name} {version} is invalid: {message}")]
+    Provenance {
+        name: String,
+        version: String,
+        message: String,
+    },
+    #[error("invalid lockfile {path}: {message}")]
+    Lockfile { path: PathBuf, message: String },
+}
+
+fn io_error(path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> RegistryError {
+    let path = path.into();
+    move |source| RegistryError::Io { path, source }
+}
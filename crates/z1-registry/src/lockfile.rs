@@ -0,0 +1,131 @@
+//! `z1.lock`: the registry packages a project fetched, pinned by digest.
+
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{io_error, PackageRecord, RegistryError};
+
+/// File name of the lockfile, next to `z1.toml`.
+pub const LOCK_FILE: &str = "z1.lock";
+
+const HEADER: &str = "# Generated by `z1 add`. Pins every registry package by digest.\n\n";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lockfile {
+    /// Pinned packages, sorted by name
+    #[serde(default, rename = "package")]
+    pub packages: Vec<LockedPackage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+    /// Digest of the package record
+    pub digest: String,
+}
+
+impl Lockfile {
+    /// Read the lockfile at `path`; a missing file is an empty lockfile.
+    pub fn load(path: &Path) -> Result<Self, RegistryError> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(source) => {
+                return Err(RegistryError::Io {
+                    path: path.to_path_buf(),
+                    source,
+                })
+            }
+        };
+        toml::from_str(&text).map_err(|err| RegistryError::Lockfile {
+            path: path.to_path_buf(),
+            message: err.message().to_string(),
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), RegistryError> {
+        let text = toml::to_string(self).expect("lockfiles serialize");
+        fs::write(path, format!("{HEADER}{text}")).map_err(io_error(path))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LockedPackage> {
+        self.packages.iter().find(|package| package.name == name)
+    }
+
+    /// Pin `record`, replacing any pin of the same package.
+    pub fn pin(&mut self, record: &PackageRecord) {
+        self.packages.retain(|package| package.name != record.name);
+        self.packages.push(LockedPackage {
+            name: record.name.clone(),
+            version: record.version.clone(),
+            digest: record.digest.clone(),
+        });
+        self.packages.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    /// Check that `record` is what the lockfile pinned for its package.
+    pub fn check(&self, record: &PackageRecord) -> Result<(), RegistryError> {
+        match self.get(&record.name) {
+            Some(locked) if locked.version == record.version && locked.digest != record.digest => {
+                Err(RegistryError::LockMismatch {
+                    name: record.name.clone(),
+                    version: record.version.clone(),
+                    locked: locked.digest.clone(),
+                    found: record.digest.clone(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn record(version: &str, digest: &str) -> PackageRecord {
+        PackageRecord {
+            name: "json".to_string(),
+            version: version.to_string(),
+            cells: BTreeMap::new(),
+            provenance: None,
+            digest: digest.to_string(),
+        }
+    }
+
+    #[test]
+    fn pins_round_trip_and_replace() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(LOCK_FILE);
+        assert_eq!(Lockfile::load(&path).unwrap(), Lockfile::default());
+
+        let mut lock = Lockfile::default();
+        lock.pin(&record("0.1.0", "sha3-256:aa"));
+        lock.pin(&record("0.2.0", "sha3-256:bb"));
+        lock.save(&path).unwrap();
+
+        let loaded = Lockfile::load(&path).unwrap();
+        assert_eq!(loaded.packages.len(), 1);
+        assert_eq!(loaded.get("json").unwrap().version, "0.2.0");
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .starts_with("# Generated"));
+    }
+
+    #[test]
+    fn republished_versions_do_not_match() {
+        let mut lock = Lockfile::default();
+        lock.pin(&record("0.1.0", "sha3-256:aa"));
+        assert!(lock.check(&record("0.1.0", "sha3-256:aa")).is_ok());
+        assert!(lock.check(&record("0.2.0", "sha3-256:cc")).is_ok());
+        assert!(matches!(
+            lock.check(&record("0.1.0", "sha3-256:bb")),
+            Err(RegistryError::LockMismatch { .. })
+        ));
+    }
+}
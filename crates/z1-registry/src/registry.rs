@@ -0,0 +1,444 @@
+//! Publishing packages to and fetching them from a registry directory.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use z1_ast::Module;
+use z1_hash::{hash_bytes, HashAlgo};
+use z1_prov::{verify_chain, verify_chain_sources, ProvenanceChain};
+use z1_store::Store;
+
+use crate::{io_error, RegistryError};
+
+/// Name of the provenance chain written next to a fetched package's cells.
+const PROVENANCE_FILE: &str = "provenance.z1p";
+
+/// A package about to be published.
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub name: String,
+    pub version: Version,
+    /// Cells by `/`-separated path relative to the package's source root
+    pub cells: Vec<(String, Module)>,
+    /// Chain recording how the cells were produced
+    pub provenance: Option<ProvenanceChain>,
+}
+
+/// A published version of a package.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageRecord {
+    pub name: String,
+    pub version: String,
+    /// Semantic hash of each cell by path; cells are stored in compact form,
+    /// so every path ends in `.z1c`
+    pub cells: BTreeMap<String, String>,
+    /// Hash of the provenance chain file, if the package has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<String>,
+    /// Hash over everything above
+    pub digest: String,
+}
+
+impl PackageRecord {
+    fn compute_digest(&self) -> String {
+        let mut text = format!("z1-package\n{}\n{}\n", self.name, self.version);
+        for (path, semhash) in &self.cells {
+            text.push_str(&format!("cell {path} {semhash}\n"));
+        }
+        if let Some(provenance) = &self.provenance {
+            text.push_str(&format!("provenance {provenance}\n"));
+        }
+        hash_bytes(text.as_bytes(), HashAlgo::default())
+    }
+}
+
+/// A registry rooted at a directory.
+#[derive(Debug, Clone)]
+pub struct Registry {
+    root: PathBuf,
+    store: Store,
+}
+
+impl Registry {
+    /// Registry at `root`. Nothing is created until the first publish.
+    pub fn open(root: impl AsRef<Path>) -> Self {
+        let root = root.as_ref().to_path_buf();
+        Self {
+            store: Store::at(&root),
+            root,
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Published versions of `name`, lowest first.
+    pub fn versions(&self, name: &str) -> Result<Vec<Version>, RegistryError> {
+        check_name(name)?;
+        let dir = self.package_dir(name);
+        if !dir.is_dir() {
+            return Err(RegistryError::UnknownPackage(name.to_string()));
+        }
+        let mut versions = Vec::new();
+        for entry in fs::read_dir(&dir).map_err(io_error(&dir))? {
+            let path = entry.map_err(io_error(&dir))?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                if let Ok(version) = Version::parse(&stem) {
+                    versions.push(version);
+                }
+            }
+        }
+        versions.sort();
+        Ok(versions)
+    }
+
+    /// Highest published version of `name`, ignoring pre-releases unless
+    /// nothing else is published.
+    pub fn latest(&self, name: &str) -> Result<Version, RegistryError> {
+        let versions = self.versions(name)?;
+        versions
+            .iter()
+            .rev()
+            .find(|version| version.pre.is_empty())
+            .or(versions.last())
+            .cloned()
+            .ok_or_else(|| RegistryError::UnknownPackage(name.to_string()))
+    }
+
+    /// The record of `name` at `version`, checked against its digest.
+    pub fn record(&self, name: &str, version: &Version) -> Result<PackageRecord, RegistryError> {
+        check_name(name)?;
+        let path = self.record_path(name, version);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Err(RegistryError::UnknownVersion {
+                    name: name.to_string(),
+                    version: version.to_string(),
+                })
+            }
+            Err(source) => return Err(RegistryError::Io { path, source }),
+        };
+        let malformed = |message: String| RegistryError::MalformedRecord {
+            path: path.clone(),
+            message,
+        };
+        let record: PackageRecord =
+            serde_json::from_str(&text).map_err(|err| malformed(err.to_string()))?;
+        if record.name != name || record.version != version.to_string() {
+            return Err(malformed(format!(
+                "records {} {} instead",
+                record.name, record.version
+            )));
+        }
+        if let Some(path) = record.cells.keys().find(|path| !is_cell_path(path)) {
+            return Err(malformed(format!("`{path}` is not a relative cell path")));
+        }
+        let actual = record.compute_digest();
+        if actual != record.digest {
+            return Err(RegistryError::DigestMismatch {
+                name: record.name,
+                version: record.version,
+                expected: record.digest,
+                actual,
+            });
+        }
+        Ok(record)
+    }
+
+    /// Publish `package`, failing if its version is already published.
+    pub fn publish(&self, package: &Package) -> Result<PackageRecord, RegistryError> {
+        check_name(&package.name)?;
+        let record_path = self.record_path(&package.name, &package.version);
+        if record_path.exists() {
+            return Err(RegistryError::AlreadyPublished {
+                name: package.name.clone(),
+                version: package.version.to_string(),
+            });
+        }
+
+        let mut cells = BTreeMap::new();
+        let mut tree = HashMap::new();
+        for (path, module) in &package.cells {
+            let path = Path::new(path).with_extension("z1c");
+            let path = path.to_string_lossy().replace('\\', "/");
+            if !is_cell_path(&path) {
+                return Err(RegistryError::MalformedRecord {
+                    path: record_path.clone(),
+                    message: format!("`{path}` is not a relative cell path"),
+                });
+            }
+            let semhash = self.store.put(module)?;
+            tree.insert(module.path.to_string(), semhash.clone());
+            if cells.insert(path.clone(), semhash).is_some() {
+                return Err(RegistryError::MalformedRecord {
+                    path: record_path.clone(),
+                    message: format!("two cells are published as `{path}`"),
+                });
+            }
+        }
+
+        let dir = self.package_dir(&package.name);
+        fs::create_dir_all(&dir).map_err(io_error(&dir))?;
+        let provenance = match &package.provenance {
+            Some(chain) => {
+                verify_provenance(chain, &tree, &package.name, &package.version.to_string())?;
+                let bytes = serde_json::to_vec_pretty(chain).expect("chains serialize");
+                let path = record_path.with_extension("z1p");
+                fs::write(&path, &bytes).map_err(io_error(&path))?;
+                Some(hash_bytes(&bytes, HashAlgo::default()))
+            }
+            None => None,
+        };
+
+        let mut record = PackageRecord {
+            name: package.name.clone(),
+            version: package.version.to_string(),
+            cells,
+            provenance,
+            digest: String::new(),
+        };
+        record.digest = record.compute_digest();
+        let json = serde_json::to_string_pretty(&record).expect("records serialize");
+        // Write then rename so a record is never visible half written
+        let tmp = record_path.with_extension("json.tmp");
+        fs::write(&tmp, json).map_err(io_error(&tmp))?;
+        fs::rename(&tmp, &record_path).map_err(io_error(&record_path))?;
+        Ok(record)
+    }
+
+    /// Write the cells (and provenance chain) of `record` to `dest`,
+    /// replacing whatever was there. Every cell is checked against its hash
+    /// and the chain against the cells before anything is written.
+    pub fn fetch(&self, record: &PackageRecord, dest: &Path) -> Result<(), RegistryError> {
+        let mut files = Vec::new();
+        let mut tree = HashMap::new();
+        for (path, semhash) in &record.cells {
+            let text = self.store.read(semhash)?;
+            let module = self.store.get(semhash)?;
+            tree.insert(module.path.to_string(), semhash.clone());
+            files.push((dest.join(path), text.into_bytes()));
+        }
+
+        if let Some(expected) = &record.provenance {
+            let version =
+                Version::parse(&record.version).map_err(|err| RegistryError::InvalidVersion {
+                    version: record.version.clone(),
+                    message: err.to_string(),
+                })?;
+            let path = self
+                .record_path(&record.name, &version)
+                .with_extension("z1p");
+            let bytes = fs::read(&path).map_err(io_error(&path))?;
+            let actual = hash_bytes(&bytes, HashAlgo::default());
+            if &actual != expected {
+                return Err(RegistryError::Provenance {
+                    name: record.name.clone(),
+                    version: record.version.clone(),
+                    message: format!("chain hashes to {actual}, record pins {expected}"),
+                });
+            }
+            let chain: ProvenanceChain =
+                serde_json::from_slice(&bytes).map_err(|err| RegistryError::Provenance {
+                    name: record.name.clone(),
+                    version: record.version.clone(),
+                    message: err.to_string(),
+                })?;
+            verify_provenance(&chain, &tree, &record.name, &record.version)?;
+            files.push((dest.join(PROVENANCE_FILE), bytes));
+        }
+
+        if dest.exists() {
+            fs::remove_dir_all(dest).map_err(io_error(dest))?;
+        }
+        for (path, contents) in files {
+            let dir = path.parent().expect("package files have a parent");
+            fs::create_dir_all(dir).map_err(io_error(dir))?;
+            fs::write(&path, contents).map_err(io_error(&path))?;
+        }
+        Ok(())
+    }
+
+    fn package_dir(&self, name: &str) -> PathBuf {
+        self.root.join("packages").join(name)
+    }
+
+    fn record_path(&self, name: &str, version: &Version) -> PathBuf {
+        self.package_dir(name).join(format!("{version}.json"))
+    }
+}
+
+/// Check the chain's integrity and that it binds its modules to `tree`.
+fn verify_provenance(
+    chain: &ProvenanceChain,
+    tree: &HashMap<String, String>,
+    name: &str,
+    version: &str,
+) -> Result<(), RegistryError> {
+    verify_chain(chain)
+        .and_then(|()| verify_chain_sources(chain, tree, None).map(|_| ()))
+        .map_err(|err| RegistryError::Provenance {
+            name: name.to_string(),
+            version: version.to_string(),
+            message: err.to_string(),
+        })
+}
+
+/// Package names are single import segments.
+fn check_name(name: &str) -> Result<(), RegistryError> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(RegistryError::InvalidName(name.to_string()))
+    }
+}
+
+/// A relative `.z1c` path that stays inside the package.
+fn is_cell_path(path: &str) -> bool {
+    let path = Path::new(path);
+    path.extension().is_some_and(|ext| ext == "z1c")
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use tempfile::TempDir;
+    use z1_prov::{CellBinding, ProvenanceChainExt, ProvenanceEntry};
+
+    const PARSE: &str = "m json.parse:1.0 caps=[]\nf parse(s: Str)->Str eff [pure] { ret s; }\n";
+
+    fn package(version: &str, source: &str) -> Package {
+        Package {
+            name: "json".to_string(),
+            version: Version::parse(version).unwrap(),
+            cells: vec![(
+                "parse.z1r".to_string(),
+                z1_parse::parse_module(source).unwrap(),
+            )],
+            provenance: None,
+        }
+    }
+
+    fn chain_for(module: &str, semhash: &str) -> ProvenanceChain {
+        let mut chain = ProvenanceChain::new();
+        chain
+            .append(ProvenanceEntry {
+                entry_id: "cell:json.parse@v1".to_string(),
+                prev: None,
+                merged: vec![],
+                actor: "dev:test".to_string(),
+                model: String::new(),
+                prompt_sha3: String::new(),
+                prompt_excerpt: String::new(),
+                redaction: None,
+                tools: vec![],
+                diff_sha3: String::new(),
+                cells: vec![CellBinding {
+                    module: module.to_string(),
+                    semhash: semhash.to_string(),
+                }],
+                timestamp: Utc::now(),
+                signatures: vec![],
+                keyless: vec![],
+                rotation: None,
+                checkpoint: None,
+                timestamp_token: None,
+            })
+            .unwrap();
+        chain
+    }
+
+    #[test]
+    fn publish_then_fetch_round_trips() {
+        let dir = TempDir::new().unwrap();
+        let registry = Registry::open(dir.path().join("registry"));
+        let record = registry.publish(&package("0.1.0", PARSE)).unwrap();
+        assert!(record.cells.contains_key("parse.z1c"));
+
+        let fetched = registry.record("json", &Version::new(0, 1, 0)).unwrap();
+        assert_eq!(fetched, record);
+
+        let dest = dir.path().join("out");
+        registry.fetch(&record, &dest).unwrap();
+        let text = fs::read_to_string(dest.join("parse.z1c")).unwrap();
+        assert!(text.starts_with("m json.parse:1.0"));
+
+        assert!(matches!(
+            registry.publish(&package("0.1.0", PARSE)),
+            Err(RegistryError::AlreadyPublished { .. })
+        ));
+    }
+
+    #[test]
+    fn latest_prefers_releases() {
+        let dir = TempDir::new().unwrap();
+        let registry = Registry::open(dir.path());
+        for version in ["0.9.0", "0.10.0", "1.0.0-rc.1"] {
+            registry.publish(&package(version, PARSE)).unwrap();
+        }
+        assert_eq!(registry.latest("json").unwrap(), Version::new(0, 10, 0));
+        assert_eq!(registry.versions("json").unwrap().len(), 3);
+        assert!(matches!(
+            registry.latest("yaml"),
+            Err(RegistryError::UnknownPackage(_))
+        ));
+    }
+
+    #[test]
+    fn tampered_records_and_objects_are_rejected() {
+        let dir = TempDir::new().unwrap();
+        let registry = Registry::open(dir.path());
+        let record = registry.publish(&package("0.1.0", PARSE)).unwrap();
+
+        // Swap the cell for another object without updating the digest
+        let other = registry
+            .store
+            .put(&z1_parse::parse_module("m json.parse:1.0 caps=[]\n").unwrap())
+            .unwrap();
+        let mut tampered = record.clone();
+        tampered.cells.insert("parse.z1c".to_string(), other);
+        let path = registry.record_path("json", &Version::new(0, 1, 0));
+        fs::write(&path, serde_json::to_string(&tampered).unwrap()).unwrap();
+        assert!(matches!(
+            registry.record("json", &Version::new(0, 1, 0)),
+            Err(RegistryError::DigestMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn provenance_must_bind_the_published_cells() {
+        let dir = TempDir::new().unwrap();
+        let registry = Registry::open(dir.path());
+        let module = z1_parse::parse_module(PARSE).unwrap();
+        let semhash = z1_hash::module_hashes(&module).semantic;
+
+        let mut published = package("0.1.0", PARSE);
+        published.provenance = Some(chain_for("json.parse", &semhash));
+        let record = registry.publish(&published).unwrap();
+        assert!(record.provenance.is_some());
+        let dest = dir.path().join("out");
+        registry.fetch(&record, &dest).unwrap();
+        assert!(dest.join(PROVENANCE_FILE).is_file());
+
+        let mut stale = package("0.2.0", PARSE);
+        stale.provenance = Some(chain_for("json.parse", "sha3-256:00"));
+        assert!(matches!(
+            registry.publish(&stale),
+            Err(RegistryError::Provenance { .. })
+        ));
+    }
+}
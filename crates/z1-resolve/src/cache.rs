@@ -0,0 +1,100 @@
+//! Parsed cells kept across loads.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use z1_ast::Module;
+
+/// Source and parse of the cells read through it, shared by its clones.
+///
+/// An entry is reused while the file keeps its length and modification time,
+/// so a cell that has not changed is neither read nor parsed again.
+#[derive(Debug, Clone, Default)]
+pub struct CellCache {
+    entries: Arc<Mutex<HashMap<PathBuf, CacheEntry>>>,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    len: u64,
+    modified: Option<SystemTime>,
+    cell: CachedCell,
+}
+
+/// A cell as read from disk.
+#[derive(Debug, Clone)]
+pub struct CachedCell {
+    pub source: String,
+    /// The parsed cell; `None` when the source does not parse
+    pub module: Option<Module>,
+}
+
+impl CellCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The cell at `path`, read and parsed only if it changed since it was
+    /// last loaded.
+    pub fn load(&self, path: &Path) -> io::Result<CachedCell> {
+        let metadata = fs::metadata(path)?;
+        let (len, modified) = (metadata.len(), metadata.modified().ok());
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(entry) = entries.get(path) {
+            if entry.len == len && entry.modified == modified && modified.is_some() {
+                return Ok(entry.cell.clone());
+            }
+        }
+
+        let source = fs::read_to_string(path)?;
+        let module = z1_parse::parse_module(&source).ok();
+        let cell = CachedCell { source, module };
+        entries.insert(
+            path.to_path_buf(),
+            CacheEntry {
+                len,
+                modified,
+                cell: cell.clone(),
+            },
+        );
+        Ok(cell)
+    }
+
+    /// Number of cells held.
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reloads_changed_cells() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.z1c");
+        fs::write(&path, "m app:1.0\n").unwrap();
+
+        let cache = CellCache::new();
+        assert!(cache.load(&path).unwrap().module.is_some());
+        assert_eq!(cache.len(), 1);
+
+        fs::write(&path, "m app:1.0\nf oops(").unwrap();
+        let cell = cache.load(&path).unwrap();
+        assert!(cell.source.ends_with("oops("));
+        assert!(cell.module.is_none());
+        assert_eq!(cache.clone().len(), 1);
+    }
+}
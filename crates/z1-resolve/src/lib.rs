@@ -0,0 +1,76 @@
+//! Project manifests and import resolution
+//!
+//! A project is a directory holding a `z1.toml` manifest (see [`Manifest`]).
+//! Its cells live under the manifest's source roots, laid out by module
+//! path: `http.server` is `src/http/server.z1c` or `src/http/server.z1r`.
+//! An import such as `"std/http/server"` whose first segment names a
+//! dependency is looked up in that dependency's source roots; any other
+//! import is looked up in the project's own. Registry dependencies are
+//! looked up where `z1 add` installed them, under [`PACKAGES_DIR`]. Resolution is deterministic: a
+//! cell found under two roots is an error rather than a first match.
+//!
+//! Without a `std` dependency, `std/...` imports that no root holds resolve
+//! to the standard library bundled with the toolchain (see [`load_std`]).
+
+mod cache;
+mod manifest;
+mod resolver;
+
+use std::io;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+pub use cache::{CachedCell, CellCache};
+pub use manifest::{
+    set_registry_dependency, CliDefaults, Dependency, Manifest, ProjectInfo, TargetSettings,
+    MANIFEST_FILE,
+};
+pub use resolver::{load_std, Project, ResolvedModule, Resolver, CELL_EXTENSIONS, PACKAGES_DIR};
+
+#[derive(Debug, Error)]
+pub enum ResolveError {
+    #[error("I/O error at {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error("invalid manifest {path}: {message}")]
+    Manifest { path: PathBuf, message: String },
+    #[error("dependency `{name}` {version} is not installed; run `z1 add`")]
+    NotInstalled { name: String, version: String },
+    #[error(
+        "cannot resolve import `{import}`: the standard library {} has no such module",
+        z1_std::VERSION
+    )]
+    NotInStd { import: String },
+    #[error("invalid import path `{0}`")]
+    InvalidImport(String),
+    #[error(
+        "cannot resolve import `{import}`: no cell in {}",
+        display_paths(searched)
+    )]
+    NotFound {
+        import: String,
+        searched: Vec<PathBuf>,
+    },
+    #[error("import `{import}` is ambiguous: found {} and {}", first.display(), second.display())]
+    Ambiguous {
+        import: String,
+        first: PathBuf,
+        second: PathBuf,
+    },
+    #[error("failed to parse {}: {message}", path.display())]
+    Parse { path: PathBuf, message: String },
+    #[error("{} defines module `{found}` but is imported as `{import}`", path.display())]
+    ModuleMismatch {
+        import: String,
+        path: PathBuf,
+        found: String,
+    },
+}
+
+fn display_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,318 @@
+//! The `z1.toml` project manifest.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use z1_policy::PolicyLimits;
+
+use crate::ResolveError;
+
+/// File name of the project manifest.
+pub const MANIFEST_FILE: &str = "z1.toml";
+
+/// A parsed `z1.toml`.
+///
+/// ```toml
+/// [project]
+/// name = "app"
+/// version = "0.1.0"
+/// sources = ["src"]
+///
+/// [dependencies]
+/// std = { path = "../stdlib" }
+/// json = { version = "0.2.0" }
+///
+/// [target]
+/// targets = ["ts", "wasm"]
+/// out-dir = "dist"
+///
+/// [policy]
+/// fn_max_params = 4
+///
+/// [defaults]
+/// fmt-mode = "compact"
+/// opt-level = 2
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub project: ProjectInfo,
+    /// Dependencies by the import prefix they are reached under
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, Dependency>,
+    #[serde(default)]
+    pub target: TargetSettings,
+    /// Limits enforced by lint and compile checks
+    #[serde(default)]
+    pub policy: PolicyLimits,
+    #[serde(default)]
+    pub defaults: CliDefaults,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectInfo {
+    pub name: String,
+    pub version: String,
+    /// Source roots, relative to the manifest
+    #[serde(default = "default_sources")]
+    pub sources: Vec<PathBuf>,
+}
+
+/// A dependency on cells in another directory or a registry package;
+/// exactly one of `path` and `version` is set.
+///
+/// A directory holding its own `z1.toml` contributes its source roots;
+/// any other directory (such as the standard library) is a source root
+/// itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Dependency {
+    /// Directory of the dependency, relative to the manifest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// Exact version of a registry package installed by `z1 add`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+/// Build settings used when the command line does not give them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct TargetSettings {
+    /// Targets `z1 build` compiles for (`ts`, `wasm`)
+    #[serde(default)]
+    pub targets: Vec<String>,
+    /// Output directory, relative to the manifest
+    pub out_dir: Option<PathBuf>,
+}
+
+/// Defaults for command-line flags of the commands run inside the project;
+/// a flag given on the command line wins.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct CliDefaults {
+    /// Mode `z1 fmt` formats in (`compact`, `relaxed`)
+    pub fmt_mode: Option<String>,
+    /// Symbol map ordering of `z1 fmt` (`respect`, `reflow`)
+    pub symmap: Option<String>,
+    /// Characters-per-token ratio of `z1 ctx`, `z1 context` and `z1 pack`
+    pub chars_per_token: Option<f64>,
+    /// Optimization level of `z1 compile` (0, 1 or 2)
+    pub opt_level: Option<u8>,
+}
+
+fn default_sources() -> Vec<PathBuf> {
+    vec![PathBuf::from("src")]
+}
+
+impl Manifest {
+    /// Read and parse the manifest at `path`.
+    pub fn load(path: &Path) -> Result<Self, ResolveError> {
+        let text = fs::read_to_string(path).map_err(|source| ResolveError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::parse(&text).map_err(|message| ResolveError::Manifest {
+            path: path.to_path_buf(),
+            message,
+        })
+    }
+
+    /// Parse manifest text, returning the TOML error message on failure.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let manifest: Manifest = toml::from_str(text).map_err(|err| err.message().to_string())?;
+        if manifest.project.name.is_empty() {
+            return Err("project name is empty".to_string());
+        }
+        if let Some(name) = manifest
+            .dependencies
+            .keys()
+            .find(|name| name.is_empty() || name.contains(['/', '.']))
+        {
+            return Err(format!(
+                "dependency name `{name}` must be a single import segment"
+            ));
+        }
+        let defaults = &manifest.defaults;
+        if let Some(mode) = defaults
+            .fmt_mode
+            .as_deref()
+            .filter(|mode| !matches!(*mode, "compact" | "relaxed"))
+        {
+            return Err(format!(
+                "unknown fmt-mode `{mode}` (expected compact or relaxed)"
+            ));
+        }
+        if let Some(style) = defaults
+            .symmap
+            .as_deref()
+            .filter(|style| !matches!(*style, "respect" | "reflow"))
+        {
+            return Err(format!(
+                "unknown symmap `{style}` (expected respect or reflow)"
+            ));
+        }
+        if defaults
+            .chars_per_token
+            .is_some_and(|ratio| !(ratio > 0.0 && ratio.is_finite()))
+        {
+            return Err("chars-per-token must be a positive number".to_string());
+        }
+        if let Some(level) = defaults.opt_level.filter(|level| *level > 2) {
+            return Err(format!("unknown opt-level {level} (expected 0, 1 or 2)"));
+        }
+        for (name, dependency) in &manifest.dependencies {
+            if dependency.path.is_some() == dependency.version.is_some() {
+                return Err(format!(
+                    "dependency `{name}` needs exactly one of `path` and `version`"
+                ));
+            }
+        }
+        Ok(manifest)
+    }
+}
+
+/// Set `name = { version = "..." }` in the `[dependencies]` of the manifest
+/// at `path`, keeping the rest of the file as written.
+pub fn set_registry_dependency(path: &Path, name: &str, version: &str) -> Result<(), ResolveError> {
+    let invalid = |message: String| ResolveError::Manifest {
+        path: path.to_path_buf(),
+        message,
+    };
+    let text = fs::read_to_string(path).map_err(|source| ResolveError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let mut doc: toml_edit::DocumentMut = text
+        .parse()
+        .map_err(|err: toml_edit::TomlError| invalid(err.message().to_string()))?;
+    let dependencies = doc
+        .entry("dependencies")
+        .or_insert_with(toml_edit::table)
+        .as_table_like_mut()
+        .ok_or_else(|| invalid("`dependencies` is not a table".to_string()))?;
+    let mut entry = toml_edit::InlineTable::new();
+    entry.insert("version", version.into());
+    dependencies.insert(name, toml_edit::value(entry));
+
+    let text = doc.to_string();
+    Manifest::parse(&text).map_err(invalid)?;
+    fs::write(path, text).map_err(|source| ResolveError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_fill_omitted_tables() {
+        let manifest = Manifest::parse("[project]\nname = \"app\"\nversion = \"0.1.0\"\n").unwrap();
+        assert_eq!(manifest.project.sources, vec![PathBuf::from("src")]);
+        assert!(manifest.dependencies.is_empty());
+        assert_eq!(manifest.target, TargetSettings::default());
+        assert_eq!(manifest.policy, PolicyLimits::default());
+        assert_eq!(manifest.defaults, CliDefaults::default());
+    }
+
+    #[test]
+    fn flag_defaults_are_validated() {
+        let base = "[project]\nname = \"app\"\nversion = \"0.1.0\"\n[defaults]\n";
+        let manifest = Manifest::parse(&format!(
+            "{base}fmt-mode = \"compact\"\nsymmap = \"reflow\"\nchars-per-token = 4.0\nopt-level = 2\n"
+        ))
+        .unwrap();
+        assert_eq!(manifest.defaults.fmt_mode.as_deref(), Some("compact"));
+        assert_eq!(manifest.defaults.symmap.as_deref(), Some("reflow"));
+        assert_eq!(manifest.defaults.chars_per_token, Some(4.0));
+        assert_eq!(manifest.defaults.opt_level, Some(2));
+
+        for bad in [
+            "fmt-mode = \"dense\"",
+            "symmap = \"sorted\"",
+            "chars-per-token = 0.0",
+            "opt-level = 3",
+            "target = \"ts\"",
+        ] {
+            assert!(Manifest::parse(&format!("{base}{bad}\n")).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn full_manifest_parses() {
+        let manifest = Manifest::parse(
+            r#"
+[project]
+name = "app"
+version = "1.2.0"
+sources = ["src", "gen"]
+
+[dependencies]
+std = { path = "../stdlib" }
+
+[target]
+targets = ["ts", "wasm"]
+out-dir = "dist"
+
+[policy]
+fn_max_params = 4
+"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.project.sources.len(), 2);
+        assert_eq!(
+            manifest.dependencies["std"].path,
+            Some(PathBuf::from("../stdlib"))
+        );
+        assert_eq!(manifest.target.targets, ["ts", "wasm"]);
+        assert_eq!(manifest.target.out_dir, Some(PathBuf::from("dist")));
+        assert_eq!(manifest.policy.fn_max_params, 4);
+        assert_eq!(
+            manifest.policy.cell_max_exports,
+            PolicyLimits::default().cell_max_exports
+        );
+    }
+
+    #[test]
+    fn unknown_keys_and_bad_dependency_names_are_rejected() {
+        let base = "[project]\nname = \"app\"\nversion = \"0.1.0\"\n";
+        assert!(Manifest::parse(&format!("{base}[policy]\nfn_max_parms = 4\n")).is_err());
+        assert!(Manifest::parse(&format!(
+            "{base}[dependencies]\n\"a/b\" = {{ path = \"x\" }}\n"
+        ))
+        .unwrap_err()
+        .contains("single import segment"));
+        assert!(Manifest::parse("[project]\nname = \"app\"\n").is_err());
+        assert!(Manifest::parse(&format!(
+            "{base}[dependencies]\njson = {{ path = \"x\", version = \"1.0.0\" }}\n"
+        ))
+        .unwrap_err()
+        .contains("exactly one"));
+    }
+
+    #[test]
+    fn registry_dependencies_are_added_in_place() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(MANIFEST_FILE);
+        fs::write(
+            &path,
+            "[project]\nname = \"app\" # the app\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        set_registry_dependency(&path, "json", "0.2.0").unwrap();
+        set_registry_dependency(&path, "json", "0.3.0").unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.contains("name = \"app\" # the app"));
+        let manifest = Manifest::load(&path).unwrap();
+        assert_eq!(
+            manifest.dependencies["json"].version.as_deref(),
+            Some("0.3.0")
+        );
+    }
+}
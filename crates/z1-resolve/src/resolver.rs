@@ -0,0 +1,491 @@
+//! Mapping import paths to cells on disk.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use z1_ast::{Item, Module};
+
+use crate::{CellCache, Manifest, ResolveError, MANIFEST_FILE};
+
+/// Directory, relative to the project root, that `z1 add` installs registry
+/// packages into as `<name>/<version>/`.
+pub const PACKAGES_DIR: &str = ".z1/packages";
+
+/// Cell file extensions in order of preference. A root holding both forms
+/// of a cell resolves to the compact one.
+pub const CELL_EXTENSIONS: [&str; 2] = ["z1c", "z1r"];
+
+/// A project directory and its manifest.
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub root: PathBuf,
+    pub manifest: Manifest,
+}
+
+impl Project {
+    /// Load the project whose manifest is `root/z1.toml`.
+    pub fn load(root: &Path) -> Result<Self, ResolveError> {
+        let manifest = Manifest::load(&root.join(MANIFEST_FILE))?;
+        Ok(Self {
+            root: root.to_path_buf(),
+            manifest,
+        })
+    }
+
+    /// The project enclosing `start` (a file or directory): the nearest
+    /// ancestor holding a `z1.toml`, if any.
+    pub fn discover(start: &Path) -> Result<Option<Self>, ResolveError> {
+        let start = std::path::absolute(start).map_err(|source| ResolveError::Io {
+            path: start.to_path_buf(),
+            source,
+        })?;
+        match start
+            .ancestors()
+            .find(|dir| dir.join(MANIFEST_FILE).is_file())
+        {
+            Some(root) => Self::load(root).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Source roots of the project, in manifest order.
+    pub fn source_roots(&self) -> Vec<PathBuf> {
+        self.manifest
+            .project
+            .sources
+            .iter()
+            .map(|source| self.root.join(source))
+            .collect()
+    }
+
+    /// Build output directory from `[target]`, if set.
+    pub fn out_dir(&self) -> Option<PathBuf> {
+        self.manifest
+            .target
+            .out_dir
+            .as_ref()
+            .map(|dir| self.root.join(dir))
+    }
+
+    /// A resolver over the project's source roots and direct dependencies.
+    pub fn resolver(&self) -> Result<Resolver, ResolveError> {
+        let mut packages = BTreeMap::new();
+        for (name, dependency) in &self.manifest.dependencies {
+            let dir = match (&dependency.path, &dependency.version) {
+                (Some(path), _) => self.root.join(path),
+                (None, Some(version)) => {
+                    let dir = self.root.join(PACKAGES_DIR).join(name).join(version);
+                    if !dir.is_dir() {
+                        return Err(ResolveError::NotInstalled {
+                            name: name.clone(),
+                            version: version.clone(),
+                        });
+                    }
+                    dir
+                }
+                (None, None) => unreachable!("manifests are validated on parse"),
+            };
+            let roots = if dir.join(MANIFEST_FILE).is_file() {
+                Self::load(&dir)?.source_roots()
+            } else if dir.is_dir() {
+                vec![dir]
+            } else {
+                return Err(ResolveError::Manifest {
+                    path: self.root.join(MANIFEST_FILE),
+                    message: format!(
+                        "dependency `{name}` points to {}, which is not a directory",
+                        dir.display()
+                    ),
+                });
+            };
+            packages.insert(name.clone(), roots);
+        }
+        Ok(Resolver::new(self.source_roots(), packages))
+    }
+}
+
+/// A cell an import resolved to.
+#[derive(Debug, Clone)]
+pub struct ResolvedModule {
+    pub import: String,
+    /// File of the cell; `<std 1.0>/http.z1c` and the like for cells of the
+    /// bundled standard library
+    pub path: PathBuf,
+    pub module: Module,
+    /// Whether the cell comes from the bundled standard library
+    pub bundled: bool,
+}
+
+/// Load `import` from the standard library bundled with the toolchain;
+/// `None` unless it names a module under `std`.
+pub fn load_std(import: &str) -> Option<Result<ResolvedModule, ResolveError>> {
+    let segments = split(import);
+    if segments.len() < 2 || segments[0] != "std" {
+        return None;
+    }
+    let Some(module) = z1_std::interface(import) else {
+        return Some(Err(ResolveError::NotInStd {
+            import: import.to_string(),
+        }));
+    };
+    let path = format!("<std {}>/{}.z1c", z1_std::VERSION, segments[1..].join("/"));
+    Some(Ok(ResolvedModule {
+        import: import.to_string(),
+        path: PathBuf::from(path),
+        module,
+        bundled: true,
+    }))
+}
+
+/// Resolves import paths against local source roots and named packages.
+#[derive(Debug, Clone, Default)]
+pub struct Resolver {
+    roots: Vec<PathBuf>,
+    packages: BTreeMap<String, Vec<PathBuf>>,
+    /// Cells by dotted module path, for imports the roots do not hold
+    modules: BTreeMap<String, PathBuf>,
+    cache: Option<CellCache>,
+}
+
+impl Resolver {
+    pub fn new(roots: Vec<PathBuf>, packages: BTreeMap<String, Vec<PathBuf>>) -> Self {
+        Self {
+            roots,
+            packages,
+            modules: BTreeMap::new(),
+            cache: None,
+        }
+    }
+
+    /// Also resolve an import to the cell `modules` maps its dotted module
+    /// path to, when no root holds it. Workspaces without a manifest index
+    /// their cells this way, as they need not be laid out by module path.
+    pub fn with_modules(mut self, modules: BTreeMap<String, PathBuf>) -> Self {
+        self.modules = modules;
+        self
+    }
+
+    /// Load imported cells through `cache`, so unchanged cells are not read
+    /// and parsed again.
+    pub fn with_cache(mut self, cache: CellCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// The cell file `import` names.
+    pub fn resolve(&self, import: &str) -> Result<PathBuf, ResolveError> {
+        let (roots, segments) = self.lookup(import)?;
+        let mut found: Option<PathBuf> = None;
+        for root in roots {
+            let mut stem = root.clone();
+            stem.extend(&segments);
+            let Some(path) = CELL_EXTENSIONS
+                .iter()
+                .map(|ext| stem.with_extension(ext))
+                .find(|path| path.is_file())
+            else {
+                continue;
+            };
+            if let Some(first) = found {
+                return Err(ResolveError::Ambiguous {
+                    import: import.to_string(),
+                    first,
+                    second: path,
+                });
+            }
+            found = Some(path);
+        }
+        if found.is_none() {
+            found = self.modules.get(&split(import).join(".")).cloned();
+        }
+        found.ok_or_else(|| ResolveError::NotFound {
+            import: import.to_string(),
+            searched: roots.to_vec(),
+        })
+    }
+
+    /// Resolve `import` and parse the cell, checking that its header names
+    /// the imported module. A `std` import no root holds comes from the
+    /// bundled standard library unless `std` is a dependency.
+    pub fn load(&self, import: &str) -> Result<ResolvedModule, ResolveError> {
+        let path = match self.resolve(import) {
+            Err(err @ ResolveError::NotFound { .. }) if !self.packages.contains_key("std") => {
+                return load_std(import).unwrap_or(Err(err));
+            }
+            result => result?,
+        };
+        let io_error = |source| ResolveError::Io {
+            path: path.clone(),
+            source,
+        };
+        let (source, module) = match &self.cache {
+            Some(cache) => {
+                let cell = cache.load(&path).map_err(io_error)?;
+                (cell.source, cell.module)
+            }
+            None => (fs::read_to_string(&path).map_err(io_error)?, None),
+        };
+        let module = match module {
+            Some(module) => module,
+            None => z1_parse::parse_module(&source).map_err(|err| ResolveError::Parse {
+                path: path.clone(),
+                message: err.to_string(),
+            })?,
+        };
+
+        // A package cell may name itself with or without the package prefix
+        let (_, segments) = self.lookup(import)?;
+        let found = module.path.to_string();
+        let full = split(import).join(".");
+        if found != full && found != segments.join(".") {
+            return Err(ResolveError::ModuleMismatch {
+                import: import.to_string(),
+                path,
+                found,
+            });
+        }
+        Ok(ResolvedModule {
+            import: import.to_string(),
+            path,
+            module,
+            bundled: false,
+        })
+    }
+
+    /// Load every import of `module`, keyed by import path.
+    pub fn load_imports(&self, module: &Module) -> Result<BTreeMap<String, Module>, ResolveError> {
+        let mut imports = BTreeMap::new();
+        for item in &module.items {
+            if let Item::Import(import) = item {
+                if !imports.contains_key(&import.path) {
+                    let resolved = self.load(&import.path)?;
+                    imports.insert(resolved.import, resolved.module);
+                }
+            }
+        }
+        Ok(imports)
+    }
+
+    /// Roots to search for `import` and the path segments within them.
+    fn lookup<'a>(
+        &'a self,
+        import: &'a str,
+    ) -> Result<(&'a [PathBuf], Vec<&'a str>), ResolveError> {
+        let segments = split(import);
+        if segments.is_empty() || !segments.iter().all(|segment| is_ident(segment)) {
+            return Err(ResolveError::InvalidImport(import.to_string()));
+        }
+        if segments.len() > 1 {
+            if let Some(roots) = self.packages.get(segments[0]) {
+                return Ok((roots, segments[1..].to_vec()));
+            }
+        }
+        Ok((&self.roots, segments))
+    }
+}
+
+/// Segments of an import path; `std/http` and `std.http` are the same import.
+fn split(import: &str) -> Vec<&str> {
+    import.split(['/', '.']).collect()
+}
+
+fn is_ident(segment: &str) -> bool {
+    let mut chars = segment.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn cell(module: &str) -> String {
+        format!("module {module} : 1.0\n  caps = []\n\nfn f() -> Unit\n  eff [pure]\n{{\n}}\n")
+    }
+
+    /// An `app` project depending on a plain `std` directory.
+    fn project() -> (TempDir, Project) {
+        let dir = TempDir::new().unwrap();
+        write(
+            &dir.path().join("app/z1.toml"),
+            "[project]\nname = \"app\"\nversion = \"0.1.0\"\n\n\
+             [dependencies]\nstd = { path = \"../stdlib\" }\n",
+        );
+        write(
+            &dir.path().join("app/src/util/text.z1r"),
+            &cell("util.text"),
+        );
+        write(
+            &dir.path().join("stdlib/http/server.z1c"),
+            &cell("std.http.server"),
+        );
+        write(
+            &dir.path().join("stdlib/http/server.z1r"),
+            &cell("std.http.server"),
+        );
+        let project = Project::load(&dir.path().join("app")).unwrap();
+        (dir, project)
+    }
+
+    #[test]
+    fn resolves_local_and_package_imports() {
+        let (dir, project) = project();
+        let resolver = project.resolver().unwrap();
+
+        let local = resolver.resolve("util/text").unwrap();
+        assert_eq!(local, dir.path().join("app/src/util/text.z1r"));
+        assert_eq!(resolver.resolve("util.text").unwrap(), local);
+
+        // The compact form wins when both exist
+        let std = resolver.resolve("std/http/server").unwrap();
+        assert_eq!(std, dir.path().join("app/../stdlib/http/server.z1c"));
+
+        let loaded = resolver.load("std/http/server").unwrap();
+        assert_eq!(loaded.module.path.0, ["std", "http", "server"]);
+    }
+
+    #[test]
+    fn missing_and_invalid_imports_are_errors() {
+        let (_dir, project) = project();
+        let resolver = project.resolver().unwrap();
+        assert!(matches!(
+            resolver.resolve("std/http/client"),
+            Err(ResolveError::NotFound { .. })
+        ));
+        assert!(matches!(
+            resolver.resolve("../etc/passwd"),
+            Err(ResolveError::InvalidImport(_))
+        ));
+    }
+
+    #[test]
+    fn cells_in_two_roots_are_ambiguous() {
+        let (dir, mut project) = project();
+        write(
+            &dir.path().join("app/gen/util/text.z1c"),
+            &cell("util.text"),
+        );
+        project.manifest.project.sources = vec!["src".into(), "gen".into()];
+        let err = project
+            .resolver()
+            .unwrap()
+            .resolve("util/text")
+            .unwrap_err();
+        assert!(matches!(err, ResolveError::Ambiguous { .. }), "{err}");
+    }
+
+    #[test]
+    fn indexed_modules_back_up_the_roots() {
+        let (dir, project) = project();
+        let lib = dir.path().join("app/lib.z1c");
+        write(&lib, &cell("app.lib"));
+        let resolver = project
+            .resolver()
+            .unwrap()
+            .with_modules(BTreeMap::from([("app.lib".to_string(), lib.clone())]));
+        assert_eq!(resolver.resolve("app/lib").unwrap(), lib);
+        assert_eq!(
+            resolver.load("app.lib").unwrap().module.path.0,
+            ["app", "lib"]
+        );
+        // The roots still come first
+        assert_eq!(
+            resolver.resolve("util/text").unwrap(),
+            dir.path().join("app/src/util/text.z1r")
+        );
+    }
+
+    #[test]
+    fn std_imports_fall_back_to_the_bundled_library() {
+        let resolver = Resolver::default();
+        let http = resolver.load("std/http").unwrap();
+        assert_eq!(http.module.path.0, ["std", "http"]);
+        assert_eq!(http.path, PathBuf::from("<std 1.0>/http.z1c"));
+        assert!(http.bundled);
+        assert!(matches!(
+            resolver.load("std/calendar"),
+            Err(ResolveError::NotInStd { .. })
+        ));
+        assert!(matches!(
+            resolver.load("calendar"),
+            Err(ResolveError::NotFound { .. })
+        ));
+        assert!(load_std("util/text").is_none());
+
+        // A `std` dependency replaces the bundled library
+        let (_dir, project) = project();
+        let resolver = project.resolver().unwrap();
+        assert!(matches!(
+            resolver.load("std/io"),
+            Err(ResolveError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn header_must_match_import() {
+        let (dir, project) = project();
+        write(&dir.path().join("app/src/other.z1r"), &cell("util.text"));
+        let err = project.resolver().unwrap().load("other").unwrap_err();
+        assert!(matches!(err, ResolveError::ModuleMismatch { .. }), "{err}");
+    }
+
+    #[test]
+    fn discover_walks_up_to_the_manifest() {
+        let (dir, _) = project();
+        let nested = dir.path().join("app/src/util/text.z1r");
+        let project = Project::discover(&nested).unwrap().unwrap();
+        assert_eq!(project.manifest.project.name, "app");
+        assert!(Project::discover(&dir.path().join("stdlib"))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn dependency_projects_contribute_their_sources() {
+        let dir = TempDir::new().unwrap();
+        write(
+            &dir.path().join("app/z1.toml"),
+            "[project]\nname = \"app\"\nversion = \"0.1.0\"\n\n\
+             [dependencies]\nlib = { path = \"../lib\" }\n",
+        );
+        write(
+            &dir.path().join("lib/z1.toml"),
+            "[project]\nname = \"lib\"\nversion = \"0.1.0\"\nsources = [\"cells\"]\n",
+        );
+        write(&dir.path().join("lib/cells/json.z1r"), &cell("json"));
+        let project = Project::load(&dir.path().join("app")).unwrap();
+        let loaded = project.resolver().unwrap().load("lib/json").unwrap();
+        assert!(loaded.path.ends_with("cells/json.z1r"));
+    }
+
+    #[test]
+    fn registry_dependencies_resolve_to_installed_packages() {
+        let dir = TempDir::new().unwrap();
+        write(
+            &dir.path().join("z1.toml"),
+            "[project]\nname = \"app\"\nversion = \"0.1.0\"\n\n\
+             [dependencies]\njson = { version = \"0.2.0\" }\n",
+        );
+        let project = Project::load(dir.path()).unwrap();
+        assert!(matches!(
+            project.resolver(),
+            Err(ResolveError::NotInstalled { .. })
+        ));
+
+        write(
+            &dir.path().join(".z1/packages/json/0.2.0/parse.z1c"),
+            &cell("json.parse"),
+        );
+        let loaded = project.resolver().unwrap().load("json/parse").unwrap();
+        assert_eq!(loaded.module.path.0, ["json", "parse"]);
+    }
+}
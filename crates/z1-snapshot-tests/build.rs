@@ -0,0 +1,40 @@
+//! Generate one snapshot test per fixture cell.
+//!
+//! Every `fixtures/cells/*.z1c` becomes a `#[test]` named after the cell in
+//! `$OUT_DIR/fixtures.rs`, so adding a fixture adds its test and a failure
+//! names the fixture that changed.
+
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    let cells = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../fixtures/cells");
+    println!("cargo:rerun-if-changed={}", cells.display());
+
+    let mut stems: Vec<String> = fs::read_dir(&cells)
+        .expect("fixtures/cells should exist")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "z1c"))
+        .map(|path| path.file_stem().unwrap().to_string_lossy().into_owned())
+        .collect();
+    stems.sort();
+
+    let mut tests = String::new();
+    for stem in stems {
+        let name: String = stem
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        tests.push_str(&format!(
+            "#[test]\nfn fixture_{name}() {{\n    check_fixture({stem:?});\n}}\n\n"
+        ));
+    }
+    let out = Path::new(&std::env::var("OUT_DIR").unwrap()).join("fixtures.rs");
+    fs::write(out, tests).unwrap();
+}
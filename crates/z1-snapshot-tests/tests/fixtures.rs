@@ -0,0 +1,105 @@
+//! IR and codegen snapshots of the fixture cells.
+//!
+//! Each `fixtures/cells/<name>.z1c` is compiled through the full pipeline of
+//! [`z1_driver::Compiler`] at O0, O1 and O2, and its optimized IR, TypeScript
+//! and WAT are compared with `fixtures/snapshots/<name>/<level>.{ir,ts,wat}`.
+//! A change to any stage of the pipeline thus shows up as a diff of these
+//! files. Run with `Z1_UPDATE_GOLDEN=1` to rewrite them after an intentional
+//! change, then review the diff.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use z1_driver::{CompileOptions, CompileResult, Compiler, Target};
+use z1_ir::optimize::OptLevel;
+
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../..")
+}
+
+fn compile(source: &str, target: Target, opt_level: OptLevel) -> CompileResult {
+    Compiler::new(CompileOptions {
+        target,
+        opt_level,
+        ..CompileOptions::default()
+    })
+    .compile_source(source)
+}
+
+/// Output of `result`, panicking with its diagnostics when it failed.
+fn output(result: &CompileResult, fixture: &str) -> String {
+    assert!(
+        result.is_ok(),
+        "{fixture} should compile: {:?}",
+        result.errors().collect::<Vec<_>>()
+    );
+    String::from_utf8(result.output().unwrap().contents.clone()).unwrap()
+}
+
+/// The first line where `expected` and `actual` differ.
+fn first_difference(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    let mut line = 1;
+    loop {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(old), Some(new)) if old == new => line += 1,
+            (old, new) => {
+                return format!(
+                    "line {line}:\n    expected: {}\n    actual:   {}",
+                    old.unwrap_or("<end of file>"),
+                    new.unwrap_or("<end of file>")
+                )
+            }
+        }
+    }
+}
+
+/// Snapshot the fixture `stem` at every optimization level.
+fn check_fixture(stem: &str) {
+    let root = workspace_root();
+    let fixture = format!("fixtures/cells/{stem}.z1c");
+    let source = fs::read_to_string(root.join(&fixture)).unwrap();
+    let dir = root.join("fixtures/snapshots").join(stem);
+    let update = std::env::var_os("Z1_UPDATE_GOLDEN").is_some();
+    let mut mismatches = Vec::new();
+
+    for (level, opt_level) in [
+        ("O0", OptLevel::O0),
+        ("O1", OptLevel::O1),
+        ("O2", OptLevel::O2),
+    ] {
+        let ts = compile(&source, Target::TypeScript, opt_level);
+        let wat = compile(&source, Target::Wat, opt_level);
+        let snapshots = [
+            ("ir", format!("{:#?}\n", ts.ir.as_ref().unwrap())),
+            ("ts", output(&ts, &fixture)),
+            ("wat", output(&wat, &fixture)),
+        ];
+        for (extension, actual) in snapshots {
+            let path = dir.join(format!("{level}.{extension}"));
+            if update {
+                fs::create_dir_all(&dir).unwrap();
+                fs::write(&path, &actual).unwrap();
+                continue;
+            }
+            let display = path.strip_prefix(&root).unwrap_or(&path).display();
+            match fs::read_to_string(&path) {
+                Ok(expected) if expected == actual => {}
+                Ok(expected) => mismatches.push(format!(
+                    "{display} differs at {}",
+                    first_difference(&expected, &actual)
+                )),
+                Err(_) => mismatches.push(format!("{display} is missing")),
+            }
+        }
+    }
+
+    assert!(
+        mismatches.is_empty(),
+        "snapshots of {fixture} out of date (rerun with Z1_UPDATE_GOLDEN=1):\n{}",
+        mismatches.join("\n")
+    );
+}
+
+include!(concat!(env!("OUT_DIR"), "/fixtures.rs"));
@@ -0,0 +1,580 @@
+//! Split planning for Zero1 cells.
+//!
+//! A cell over its context budget or the policy's cell limits (AST nodes,
+//! exports, imports) is split into smaller cells. The planner groups each
+//! type and function with the declarations it mentions in a cycle with it,
+//! orders the groups so every group comes after what it depends on, and
+//! packs them in that order into cells that stay within the limits. Every
+//! dependency then points to an earlier cell, so the cells import each other
+//! without cycles.
+//!
+//! Each cell gets the header of the original with only the capabilities its
+//! functions need, the original imports it still uses (narrowed to the names
+//! it uses), imports of the names it takes from earlier cells, and the
+//! symbol map entries it refers to. The last cell keeps the original module
+//! path; the others are named after it with a `_1`, `_2`, ... suffix. Inline
+//! `#test` blocks go to the last cell holding a declaration they mention.
+//!
+//! ## Usage
+//!
+//! ```rust
+//! use z1_policy::PolicyLimits;
+//! use z1_split::{plan_split, SplitOptions};
+//!
+//! let module = z1_parse::parse_module(
+//!     "m app:1.0\nf one()->U32 eff [pure] { ret 1; }\nf two()->U32 eff [pure] { ret one() + one(); }\n",
+//! )
+//! .unwrap();
+//! let options = SplitOptions {
+//!     limits: PolicyLimits { cell_max_exports: 1, ..PolicyLimits::default() },
+//!     budget: None,
+//! };
+//! let plan = plan_split(&module, &options).unwrap();
+//! assert_eq!(plan.parts.len(), 2);
+//! assert_eq!(plan.parts[0].import_path(), "app_1");
+//! assert_eq!(plan.parts[1].names, ["two"]);
+//! ```
+
+use std::collections::BTreeSet;
+
+use thiserror::Error;
+use z1_ast::{Ident, Import, Item, Module, ModulePath, Span, Symbol, SymbolMap};
+use z1_ctx::{block_words, type_words, CtxError, EstimateConfig};
+use z1_fmt::{format_module, FmtError, FmtOptions, Mode};
+use z1_policy::{PolicyChecker, PolicyLimits, PolicyViolation};
+
+/// Errors that can occur while planning a split.
+#[derive(Debug, Error)]
+pub enum SplitError {
+    #[error(transparent)]
+    Estimate(#[from] CtxError),
+
+    #[error(transparent)]
+    Format(#[from] FmtError),
+}
+
+/// Limits the cells of a split must stay within.
+#[derive(Debug, Clone, Default)]
+pub struct SplitOptions {
+    pub limits: PolicyLimits,
+    /// Context budget of each new cell (default: the budget of the original)
+    pub budget: Option<u32>,
+}
+
+/// The cells a cell is split into, in dependency order.
+#[derive(Debug)]
+pub struct SplitPlan {
+    pub parts: Vec<Part>,
+}
+
+impl SplitPlan {
+    /// Whether the cell is left whole: it is within its limits, or it holds
+    /// a single group of declarations that cannot be divided.
+    pub fn is_whole(&self) -> bool {
+        self.parts.len() < 2
+    }
+}
+
+/// One cell of a split.
+#[derive(Debug)]
+pub struct Part {
+    pub module: Module,
+    /// Types and functions moved into the cell, in their original order
+    pub names: Vec<String>,
+    /// Estimated context tokens
+    pub tokens: u32,
+    /// Cell limits the cell still breaks, when a group of declarations that
+    /// mention each other is too large for any cell
+    pub violations: Vec<PolicyViolation>,
+}
+
+impl Part {
+    /// Path other cells import this one by, such as `http/server_1`.
+    pub fn import_path(&self) -> String {
+        Symbol::join(&self.module.path.0, "/")
+    }
+
+    /// Source of the cell, formatted in `mode`.
+    pub fn render(&self, mode: Mode) -> Result<String, SplitError> {
+        Ok(format_module(&self.module, mode, &FmtOptions::default())?)
+    }
+}
+
+/// Plans how to split `module` so every cell stays within `options`.
+///
+/// A cell already within its limits comes back as a single part.
+pub fn plan_split(module: &Module, options: &SplitOptions) -> Result<SplitPlan, SplitError> {
+    let planner = Planner::new(module, options);
+    let mut parts: Vec<Vec<usize>> = Vec::new();
+    let mut owner: Vec<Option<usize>> = vec![None; planner.decls.len()];
+    let mut current: Vec<usize> = Vec::new();
+    for group in planner.groups() {
+        let mut candidate = current.clone();
+        candidate.extend(&group);
+        candidate.sort_unstable();
+        let part = planner.part(&candidate, &owner, parts.len())?;
+        if current.is_empty() || part.violations.is_empty() {
+            current = candidate;
+            continue;
+        }
+        for &decl in &current {
+            owner[decl] = Some(parts.len());
+        }
+        parts.push(std::mem::replace(&mut current, group));
+        current.sort_unstable();
+    }
+    if !current.is_empty() || parts.is_empty() {
+        parts.push(current);
+    }
+
+    let last = parts.len() - 1;
+    let tests: Vec<(usize, &Item)> = module
+        .items
+        .iter()
+        .filter_map(|item| {
+            let Item::Test(test) = item else {
+                return None;
+            };
+            let mut words = BTreeSet::new();
+            block_words(&test.body, &mut words);
+            let home = planner
+                .decls
+                .iter()
+                .enumerate()
+                .filter(|(_, decl)| words.contains(&decl.name))
+                .filter_map(|(idx, _)| parts.iter().position(|members| members.contains(&idx)))
+                .max()
+                .unwrap_or(last);
+            Some((home, item))
+        })
+        .collect();
+
+    let mut planned = Vec::with_capacity(parts.len());
+    for (idx, members) in parts.iter().enumerate() {
+        let mut part = planner.part(members, &owner, idx)?;
+        let before = part.module.items.len();
+        part.module.items.extend(
+            tests
+                .iter()
+                .filter(|(home, _)| *home == idx)
+                .map(|(_, item)| (*item).clone()),
+        );
+        if idx == last {
+            part.module.path = module.path.clone();
+        }
+        if idx == last || part.module.items.len() > before {
+            planner.measure(&mut part)?;
+        }
+        planned.push(part);
+    }
+    Ok(SplitPlan { parts: planned })
+}
+
+/// A type or function of the cell being split.
+struct Decl<'a> {
+    name: Ident,
+    item: &'a Item,
+    /// Names the declaration mentions
+    words: BTreeSet<Ident>,
+    /// Other declarations it mentions
+    deps: BTreeSet<usize>,
+}
+
+struct Planner<'a> {
+    module: &'a Module,
+    options: &'a SplitOptions,
+    decls: Vec<Decl<'a>>,
+}
+
+impl<'a> Planner<'a> {
+    fn new(module: &'a Module, options: &'a SplitOptions) -> Self {
+        let mut decls: Vec<Decl> = module
+            .items
+            .iter()
+            .filter_map(|item| {
+                let (name, words) = match item {
+                    Item::Type(decl) => {
+                        let mut words = BTreeSet::new();
+                        type_words(&decl.expr, &mut words);
+                        (decl.name, words)
+                    }
+                    Item::Fn(decl) => {
+                        let mut words = BTreeSet::new();
+                        for param in &decl.params {
+                            type_words(&param.ty, &mut words);
+                        }
+                        type_words(&decl.ret, &mut words);
+                        block_words(&decl.body, &mut words);
+                        (decl.name, words)
+                    }
+                    Item::Import(_) | Item::Symbol(_) | Item::Test(_) => return None,
+                };
+                Some(Decl {
+                    name,
+                    item,
+                    words,
+                    deps: BTreeSet::new(),
+                })
+            })
+            .collect();
+        for idx in 0..decls.len() {
+            let deps = (0..decls.len())
+                .filter(|&other| other != idx && decls[idx].words.contains(&decls[other].name))
+                .collect();
+            decls[idx].deps = deps;
+        }
+        Planner {
+            module,
+            options,
+            decls,
+        }
+    }
+
+    /// Declarations that mention each other in a cycle, each group after the
+    /// groups it depends on (Tarjan's algorithm).
+    fn groups(&self) -> Vec<Vec<usize>> {
+        struct Tarjan<'p> {
+            deps: Vec<&'p BTreeSet<usize>>,
+            index: Vec<Option<usize>>,
+            low: Vec<usize>,
+            stack: Vec<usize>,
+            on_stack: Vec<bool>,
+            next: usize,
+            groups: Vec<Vec<usize>>,
+        }
+
+        impl Tarjan<'_> {
+            fn visit(&mut self, node: usize) {
+                self.index[node] = Some(self.next);
+                self.low[node] = self.next;
+                self.next += 1;
+                self.stack.push(node);
+                self.on_stack[node] = true;
+                for &dep in self.deps[node] {
+                    match self.index[dep] {
+                        None => {
+                            self.visit(dep);
+                            self.low[node] = self.low[node].min(self.low[dep]);
+                        }
+                        Some(index) if self.on_stack[dep] => {
+                            self.low[node] = self.low[node].min(index);
+                        }
+                        Some(_) => {}
+                    }
+                }
+                if Some(self.low[node]) == self.index[node] {
+                    let mut group = Vec::new();
+                    while let Some(member) = self.stack.pop() {
+                        self.on_stack[member] = false;
+                        group.push(member);
+                        if member == node {
+                            break;
+                        }
+                    }
+                    group.sort_unstable();
+                    self.groups.push(group);
+                }
+            }
+        }
+
+        let count = self.decls.len();
+        let mut tarjan = Tarjan {
+            deps: self.decls.iter().map(|decl| &decl.deps).collect(),
+            index: vec![None; count],
+            low: vec![0; count],
+            stack: Vec::new(),
+            on_stack: vec![false; count],
+            next: 0,
+            groups: Vec::new(),
+        };
+        for node in 0..count {
+            if tarjan.index[node].is_none() {
+                tarjan.visit(node);
+            }
+        }
+        tarjan.groups
+    }
+
+    /// The cell holding `members`, the `idx`th of the split, with imports of
+    /// the declarations `owner` places in earlier cells.
+    fn part(
+        &self,
+        members: &[usize],
+        owner: &[Option<usize>],
+        idx: usize,
+    ) -> Result<Part, SplitError> {
+        let module = self.module;
+        let mut words = BTreeSet::new();
+        for &member in members {
+            words.extend(self.decls[member].words.iter().copied());
+            words.insert(self.decls[member].name);
+        }
+
+        let mut items = Vec::new();
+        for item in &module.items {
+            let Item::Import(import) = item else {
+                continue;
+            };
+            let only: Vec<Ident> = import
+                .only
+                .iter()
+                .filter(|name| words.contains(*name))
+                .copied()
+                .collect();
+            let used = match &import.alias {
+                _ if !import.only.is_empty() => !only.is_empty(),
+                Some(alias) => words.contains(alias),
+                None => true,
+            };
+            if used {
+                items.push(Item::Import(Import {
+                    only,
+                    ..import.clone()
+                }));
+            }
+        }
+
+        // Names taken from each earlier cell, in declaration order
+        let mut imported: Vec<(usize, Vec<usize>)> = Vec::new();
+        for (decl, part) in owner.iter().enumerate() {
+            let Some(part) = *part else {
+                continue;
+            };
+            if !members
+                .iter()
+                .any(|&member| self.decls[member].deps.contains(&decl))
+            {
+                continue;
+            }
+            match imported.iter_mut().find(|(from, _)| *from == part) {
+                Some((_, names)) => names.push(decl),
+                None => imported.push((part, vec![decl])),
+            }
+        }
+        imported.sort_by_key(|(part, _)| *part);
+        for (part, names) in &imported {
+            items.push(Item::Import(Import {
+                path: Symbol::join(&part_path(&module.path, *part).0, "/"),
+                alias: None,
+                only: names.iter().map(|&decl| self.decls[decl].name).collect(),
+                span: Span::default(),
+            }));
+        }
+
+        for item in &module.items {
+            let Item::Symbol(map) = item else {
+                continue;
+            };
+            let pairs: Vec<_> = map
+                .pairs
+                .iter()
+                .filter(|pair| words.contains(&pair.long) || words.contains(&pair.short))
+                .cloned()
+                .collect();
+            if !pairs.is_empty() {
+                items.push(Item::Symbol(SymbolMap {
+                    pairs,
+                    span: map.span,
+                }));
+            }
+        }
+        items.extend(
+            members
+                .iter()
+                .map(|&member| self.decls[member].item.clone()),
+        );
+
+        // Effects of the cell's functions and of those it imports from
+        // earlier cells, which its capabilities must cover
+        let effects: BTreeSet<String> = members
+            .iter()
+            .chain(imported.iter().flat_map(|(_, names)| names))
+            .filter_map(|&decl| match self.decls[decl].item {
+                Item::Fn(decl) => Some(&decl.effects),
+                _ => None,
+            })
+            .flatten()
+            .map(|effect| effect.to_lowercase())
+            .collect();
+        let caps = module
+            .caps
+            .iter()
+            .filter(|cap| {
+                let base = cap.split('.').next().unwrap_or(cap);
+                effects.contains(&base.to_lowercase())
+            })
+            .cloned()
+            .collect();
+
+        let mut part = Part {
+            module: Module {
+                path: part_path(&module.path, idx),
+                version: module.version.clone(),
+                ctx_budget: self.options.budget.or(module.ctx_budget),
+                caps,
+                targets: module.targets.clone(),
+                toolchain: module.toolchain.clone(),
+                // The items templates added are kept as written out
+                items,
+                templates: Vec::new(),
+                expansions: Vec::new(),
+                span: module.span,
+            },
+            names: members
+                .iter()
+                .map(|&member| self.decls[member].name.to_string())
+                .collect(),
+            tokens: 0,
+            violations: Vec::new(),
+        };
+        self.measure(&mut part)?;
+        Ok(part)
+    }
+
+    /// Estimate the tokens of `part` and collect the cell limits it breaks.
+    fn measure(&self, part: &mut Part) -> Result<(), SplitError> {
+        let config = EstimateConfig {
+            enforce_budget: false,
+            ..EstimateConfig::default()
+        };
+        part.tokens = z1_ctx::estimate_cell_with_config(&part.module, &config)?.total_tokens;
+        let checker = PolicyChecker::new(self.options.limits.clone());
+        part.violations = match checker.check_module(&part.module) {
+            Ok(()) => Vec::new(),
+            Err(violations) => violations
+                .into_iter()
+                .filter(|violation| {
+                    matches!(
+                        violation,
+                        PolicyViolation::AstNodeLimitExceeded { .. }
+                            | PolicyViolation::ExportLimitExceeded { .. }
+                            | PolicyViolation::FaninLimitExceeded { .. }
+                            | PolicyViolation::CellContextBudgetExceeded { .. }
+                    )
+                })
+                .collect(),
+        };
+        Ok(())
+    }
+}
+
+/// Module path of the `idx`th cell split from `path`: `a.b` becomes `a.b_1`
+/// for the first.
+fn part_path(path: &ModulePath, idx: usize) -> ModulePath {
+    let mut segments = path.0.clone();
+    if let Some(last) = segments.last_mut() {
+        *last = Symbol::intern(&format!("{last}_{}", idx + 1));
+    }
+    ModulePath(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(exports: usize) -> SplitOptions {
+        SplitOptions {
+            limits: PolicyLimits {
+                cell_max_exports: exports,
+                ..PolicyLimits::default()
+            },
+            budget: None,
+        }
+    }
+
+    #[test]
+    fn splits_in_dependency_order() {
+        let module = z1_parse::parse_module(
+            "m app.server:1.0 ctx=500 caps=[net, time]\n\
+             u \"std/http\" as H only [listen, Req]\n\
+             #sym { handler: h, tick: t }\n\
+             t Count = U32\n\
+             f handler(r: H.Req)->Count eff [net] { ret helper(); }\n\
+             f helper()->Count eff [pure] { ret 1; }\n\
+             f tick()->Unit eff [time] { ret (); }\n\
+             f main()->Unit eff [net] { ret H.listen(handler(r)); }\n",
+        )
+        .unwrap();
+        let plan = plan_split(&module, &limits(2)).unwrap();
+
+        let names: Vec<_> = plan.parts.iter().map(|part| part.names.clone()).collect();
+        assert_eq!(
+            names,
+            [
+                vec!["Count", "helper"],
+                vec!["handler", "tick"],
+                vec!["main"]
+            ]
+        );
+        let first = plan.parts[0].render(Mode::Compact).unwrap();
+        assert!(first.starts_with("m app.server_1:1.0 ctx=500\n"), "{first}");
+        assert!(!first.contains("std/http"), "{first}");
+
+        let second = plan.parts[1].render(Mode::Compact).unwrap();
+        assert!(
+            second.starts_with("m app.server_2:1.0 ctx=500 caps=[net,time]\n"),
+            "{second}"
+        );
+        assert!(
+            second.contains("u \"std/http\" as H only [Req]"),
+            "{second}"
+        );
+        assert!(
+            second.contains("u \"app/server_1\" only [Count, helper]"),
+            "{second}"
+        );
+        assert!(second.contains("#sym { handler: h, tick: t }"), "{second}");
+
+        let last = &plan.parts[2];
+        assert_eq!(last.import_path(), "app/server");
+        assert_eq!(last.module.caps, ["net"]);
+        assert!(plan.parts.iter().all(|part| part.violations.is_empty()));
+    }
+
+    #[test]
+    fn inline_tests_follow_what_they_test() {
+        let module = z1_parse::parse_module(
+            "m app:1.0\n\
+             f one()->U32 eff [pure] { ret 1; }\n\
+             f two()->U32 eff [pure] { ret one() + one(); }\n\
+             #test \"one\" { assert_eq(one(), 1); }\n\
+             #test \"both\" { assert_eq(two(), one() + 1); }\n\
+             #test \"nothing\" { assert true; }\n",
+        )
+        .unwrap();
+        let plan = plan_split(&module, &limits(1)).unwrap();
+        let tests: Vec<Vec<&str>> = plan
+            .parts
+            .iter()
+            .map(|part| {
+                part.module
+                    .items
+                    .iter()
+                    .filter_map(|item| match item {
+                        Item::Test(test) => Some(test.name.as_str()),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(tests, [vec!["one"], vec!["both", "nothing"]]);
+    }
+
+    #[test]
+    fn keeps_cycles_and_small_cells_whole() {
+        let module = z1_parse::parse_module(
+            "m app:1.0\n\
+             f even(n: U32)->Bool eff [pure] { ret odd(n); }\n\
+             f odd(n: U32)->Bool eff [pure] { ret even(n); }\n",
+        )
+        .unwrap();
+        let plan = plan_split(&module, &limits(1)).unwrap();
+        assert!(plan.is_whole());
+        assert_eq!(plan.parts[0].names, ["even", "odd"]);
+        assert_eq!(plan.parts[0].violations.len(), 1);
+
+        let plan = plan_split(&module, &limits(5)).unwrap();
+        assert!(plan.is_whole());
+        assert!(plan.parts[0].violations.is_empty());
+    }
+}
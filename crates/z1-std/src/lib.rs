@@ -0,0 +1,181 @@
+//! The Zero1 standard library, bundled with the toolchain.
+//!
+//! The cells under `stdlib/` are compiled into this crate, so `std/...`
+//! imports resolve without a dependency or any file on disk: the resolver
+//! falls back to [`cell`] when no `std` dependency is configured, and the
+//! type and effect checkers read the signatures of [`interface`].
+//!
+//! Each cell has a runtime shim per backend where one exists, implementing
+//! its functions in the host language. The compiler writes the shim next to
+//! its output under the name the generated import refers to, e.g.
+//! `std_http.ts` for `use "std/http"` with the TypeScript backend.
+
+use z1_ast::{Module, Session};
+
+/// Version of the bundled standard library, declared by each of its cells.
+pub const VERSION: &str = "1.0";
+
+/// A standard library cell.
+#[derive(Debug)]
+pub struct StdCell {
+    /// Dotted module path, like `std.http.server`
+    pub module: &'static str,
+    /// Compact source
+    pub source: &'static str,
+    ts_shim: Option<&'static str>,
+    wasm_shim: Option<&'static str>,
+}
+
+/// Backend a runtime shim is written for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// A TypeScript module exporting the cell's functions and types
+    TypeScript,
+    /// A JavaScript module creating the WASM host imports of the cell
+    Wasm,
+}
+
+impl StdCell {
+    /// Source of the runtime shim for `backend`, if the cell has one.
+    pub fn shim(&self, backend: Backend) -> Option<&'static str> {
+        match backend {
+            Backend::TypeScript => self.ts_shim,
+            Backend::Wasm => self.wasm_shim,
+        }
+    }
+}
+
+macro_rules! cell {
+    ($module:literal, $file:literal) => {
+        cell!($module, $file, None, None)
+    };
+    ($module:literal, $file:literal, ts = $ts:literal) => {
+        cell!(
+            $module,
+            $file,
+            Some(include_str!(concat!("../../../stdlib/shims/ts/", $ts))),
+            None
+        )
+    };
+    ($module:literal, $file:literal, ts = $ts:literal, wasm = $wasm:literal) => {
+        cell!(
+            $module,
+            $file,
+            Some(include_str!(concat!("../../../stdlib/shims/ts/", $ts))),
+            Some(include_str!(concat!("../../../stdlib/shims/wasm/", $wasm)))
+        )
+    };
+    ($module:literal, $file:literal, $ts:expr, $wasm:expr) => {
+        StdCell {
+            module: $module,
+            source: include_str!(concat!("../../../stdlib/", $file)),
+            ts_shim: $ts,
+            wasm_shim: $wasm,
+        }
+    };
+}
+
+/// Every cell of the standard library, sorted by module path.
+pub const CELLS: &[StdCell] = &[
+    cell!("std.crypto.hash", "crypto/hash.z1c"),
+    cell!("std.crypto.hmac", "crypto/hmac.z1c"),
+    cell!("std.crypto.random", "crypto/random.z1c"),
+    cell!("std.env.args", "env/args.z1c"),
+    cell!("std.env.process", "env/process.z1c"),
+    cell!("std.env.vars", "env/vars.z1c"),
+    cell!("std.fs.core", "fs/core.z1c"),
+    cell!("std.fs.dir", "fs/dir.z1c"),
+    cell!("std.fs.path", "fs/path.z1c"),
+    cell!("std.http", "http.z1c", ts = "std_http.ts"),
+    cell!("std.http.client", "http/client.z1c"),
+    cell!("std.http.server", "http/server.z1c"),
+    cell!("std.io", "io.z1c", ts = "std_io.ts", wasm = "std_io.js"),
+    cell!(
+        "std.math",
+        "math.z1c",
+        ts = "std_math.ts",
+        wasm = "std_math.js"
+    ),
+    cell!("std.str", "str.z1c", ts = "std_str.ts", wasm = "std_str.js"),
+    cell!("std.time.core", "time/core.z1c"),
+    cell!("std.time.timer", "time/timer.z1c"),
+];
+
+/// The cell `import` names, like `std/http` or `std.http`; `None` unless it
+/// is a standard library cell.
+pub fn cell(import: &str) -> Option<&'static StdCell> {
+    let module = import.replace('/', ".");
+    CELLS.iter().find(|cell| cell.module == module)
+}
+
+/// The bundled cells, parsed once per [`Session`].
+struct Interfaces(Vec<Module>);
+
+/// The parsed cell `import` names, whose declarations are its interface.
+pub fn interface(import: &str) -> Option<Module> {
+    let modules = Session::current().local(|| {
+        Interfaces(
+            CELLS
+                .iter()
+                .map(|cell| {
+                    z1_parse::parse_module(cell.source).unwrap_or_else(|err| {
+                        panic!("bundled cell {} is invalid: {err}", cell.module)
+                    })
+                })
+                .collect(),
+        )
+    });
+    let cell = cell(import)?;
+    let index = CELLS.iter().position(|other| std::ptr::eq(other, cell))?;
+    modules.0.get(index).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn cells_declare_their_module_and_version() {
+        for cell in CELLS {
+            let module = interface(cell.module).unwrap();
+            assert_eq!(module.path.to_string(), cell.module);
+            assert_eq!(module.version.as_deref(), Some(VERSION), "{}", cell.module);
+            assert!(
+                z1_typeck::check_module_with_imports(&module, &BTreeMap::new()).is_ok(),
+                "{} does not type check",
+                cell.module
+            );
+            assert!(
+                z1_effects::check_module(&module).is_ok(),
+                "{} does not effect check",
+                cell.module
+            );
+        }
+        assert!(CELLS.windows(2).all(|pair| pair[0].module < pair[1].module));
+    }
+
+    #[test]
+    fn imports_name_cells_either_way() {
+        let http = cell("std/http").unwrap();
+        assert_eq!(http.module, "std.http");
+        assert!(std::ptr::eq(http, cell("std.http").unwrap()));
+        assert!(http.shim(Backend::TypeScript).is_some());
+        assert!(http.shim(Backend::Wasm).is_none());
+        assert!(cell("std/calendar").is_none());
+        assert!(cell("http").is_none());
+
+        let server = interface("std/http/server").unwrap();
+        assert_eq!(server.path.0, ["std", "http", "server"]);
+    }
+
+    #[test]
+    fn math_specs_pass() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../stdlib/math.z1t");
+        let source = std::fs::read_to_string(path).unwrap();
+        let file = z1_test::parse_test_file(&source).unwrap();
+        let results = z1_test::TestRunner::default().run_file_at(&file, path.as_ref());
+        assert_eq!(results.failed, 0, "{:?}", results.failures);
+        assert_eq!(results.passed, 6);
+    }
+}
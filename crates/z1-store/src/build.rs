@@ -0,0 +1,442 @@
+//! Incremental build state
+//!
+//! `.z1/buildstate` records, for every target and every cell built
+//! successfully for it, the cell's semantic hash and the API hash of each
+//! workspace dependency at that time. A cell needs rebuilding for a target
+//! when it was never built for it, its own semantic hash changed, or a
+//! dependency's API hash no longer matches. Body-only edits to a dependency
+//! therefore rebuild just that dependency, and building for one target keeps
+//! the state of the others.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::io;
+
+use thiserror::Error;
+use z1_ast::{Item, Module};
+use z1_hash::module_hashes;
+
+use crate::Store;
+
+/// File name of the build state inside the store directory.
+pub const BUILDSTATE_FILE: &str = "buildstate";
+
+const BUILDSTATE_HEADER: &str = "z1buildstate 1";
+
+#[derive(Debug, Error)]
+pub enum BuildError {
+    #[error("I/O error on build state: {0}")]
+    Io(#[from] io::Error),
+    #[error("build state line {line}: {message}")]
+    Malformed { line: usize, message: String },
+    #[error("module `{module}` is defined by both {first} and {second}")]
+    DuplicateModule {
+        module: String,
+        first: String,
+        second: String,
+    },
+    #[error("import cycle between {0}")]
+    Cycle(String),
+}
+
+/// A workspace cell as seen by the build graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CellInfo {
+    /// Dotted module path
+    pub module: String,
+    /// Path of the cell, relative to the workspace
+    pub path: String,
+    pub semantic: String,
+    pub api: String,
+    /// Imported modules as dotted paths (`std/fs/core` → `std.fs.core`)
+    pub imports: Vec<String>,
+}
+
+impl CellInfo {
+    pub fn from_module(path: impl Into<String>, module: &Module) -> Self {
+        let hashes = module_hashes(module);
+        let mut imports: Vec<String> = module
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Import(import) => Some(import.path.replace('/', ".")),
+                _ => None,
+            })
+            .collect();
+        imports.sort();
+        imports.dedup();
+        Self {
+            module: module.path.to_string(),
+            path: path.into(),
+            semantic: hashes.semantic,
+            api: hashes.api,
+            imports,
+        }
+    }
+}
+
+/// Why a cell has to be rebuilt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DirtyReason {
+    New,
+    Changed,
+    /// The API of this workspace dependency changed since the last build
+    DependencyApi(String),
+}
+
+impl fmt::Display for DirtyReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DirtyReason::New => write!(f, "new"),
+            DirtyReason::Changed => write!(f, "changed"),
+            DirtyReason::DependencyApi(dep) => write!(f, "API of `{dep}` changed"),
+        }
+    }
+}
+
+/// Cells in dependency order, and the ones that need rebuilding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildPlan {
+    /// Every cell, dependencies first
+    pub order: Vec<CellInfo>,
+    /// Cells to rebuild, in `order`
+    pub dirty: Vec<(CellInfo, DirtyReason)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BuiltCell {
+    semantic: String,
+    /// Workspace dependencies and their API hash at build time
+    deps: Vec<(String, String)>,
+}
+
+/// Contents of `.z1/buildstate`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildState {
+    /// Cells built for each target, by module
+    targets: BTreeMap<String, BTreeMap<String, BuiltCell>>,
+}
+
+impl BuildState {
+    /// Load the build state of `store`; a missing file is an empty state.
+    pub fn load(store: &Store) -> Result<Self, BuildError> {
+        match fs::read_to_string(store.root().join(BUILDSTATE_FILE)) {
+            Ok(text) => Self::parse(&text),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self, store: &Store) -> Result<(), BuildError> {
+        fs::create_dir_all(store.root())?;
+        let path = store.root().join(BUILDSTATE_FILE);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, self.to_string())?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    /// Order `cells` by their imports and select the ones to rebuild for
+    /// `target`. Imports of modules outside the workspace are ignored.
+    pub fn plan(&self, cells: &[CellInfo], target: &str) -> Result<BuildPlan, BuildError> {
+        let mut by_module: BTreeMap<&str, &CellInfo> = BTreeMap::new();
+        for cell in cells {
+            if let Some(first) = by_module.insert(&cell.module, cell) {
+                return Err(BuildError::DuplicateModule {
+                    module: cell.module.clone(),
+                    first: first.path.clone(),
+                    second: cell.path.clone(),
+                });
+            }
+        }
+        let order = topological_order(&by_module)?;
+
+        let built_cells = self.targets.get(target);
+        let dirty = order
+            .iter()
+            .filter_map(|cell| {
+                let reason = match built_cells.and_then(|built| built.get(&cell.module)) {
+                    None => DirtyReason::New,
+                    Some(built) if built.semantic != cell.semantic => DirtyReason::Changed,
+                    Some(built) => {
+                        let current = workspace_deps(cell, &by_module);
+                        let changed = current
+                            .iter()
+                            .chain(&built.deps)
+                            .find(|dep| !current.contains(dep) || !built.deps.contains(dep))?;
+                        DirtyReason::DependencyApi(changed.0.clone())
+                    }
+                };
+                Some(((*cell).clone(), reason))
+            })
+            .collect();
+
+        Ok(BuildPlan {
+            order: order.into_iter().cloned().collect(),
+            dirty,
+        })
+    }
+
+    /// Record a successful build of `cell` for `target`.
+    pub fn record(&mut self, cell: &CellInfo, cells: &[CellInfo], target: &str) {
+        let by_module = cells.iter().map(|c| (c.module.as_str(), c)).collect();
+        self.targets.entry(target.to_string()).or_default().insert(
+            cell.module.clone(),
+            BuiltCell {
+                semantic: cell.semantic.clone(),
+                deps: workspace_deps(cell, &by_module),
+            },
+        );
+    }
+
+    /// Drop cells that no longer exist in the workspace.
+    pub fn retain(&mut self, cells: &[CellInfo]) {
+        for built in self.targets.values_mut() {
+            built.retain(|module, _| cells.iter().any(|cell| cell.module == *module));
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self, BuildError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(idx, line)| (idx + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+        let malformed = |line: usize, message: &str| BuildError::Malformed {
+            line,
+            message: message.to_string(),
+        };
+        match lines.next() {
+            Some((_, BUILDSTATE_HEADER)) => {}
+            _ => return Err(malformed(1, "missing header")),
+        }
+
+        // Cells belong to the target named before them
+        let mut state = Self::default();
+        let mut target = None;
+        for (line, content) in lines {
+            let fields: Vec<&str> = content.split_whitespace().collect();
+            match fields.as_slice() {
+                ["target", name] => {
+                    target = Some(state.targets.entry(name.to_string()).or_default());
+                }
+                ["cell", module, semantic] => {
+                    target
+                        .as_mut()
+                        .ok_or_else(|| malformed(line, "cell before its target"))?
+                        .insert(
+                            module.to_string(),
+                            BuiltCell {
+                                semantic: semantic.to_string(),
+                                deps: Vec::new(),
+                            },
+                        );
+                }
+                ["dep", module, dep, api] => target
+                    .as_mut()
+                    .and_then(|built| built.get_mut(*module))
+                    .ok_or_else(|| malformed(line, "dep before its cell"))?
+                    .deps
+                    .push((dep.to_string(), api.to_string())),
+                _ => return Err(malformed(line, "unknown entry")),
+            }
+        }
+        Ok(state)
+    }
+}
+
+impl fmt::Display for BuildState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{BUILDSTATE_HEADER}")?;
+        for (target, cells) in &self.targets {
+            writeln!(f, "target {target}")?;
+            for (module, built) in cells {
+                writeln!(f, "cell {module} {}", built.semantic)?;
+                for (dep, api) in &built.deps {
+                    writeln!(f, "dep {module} {dep} {api}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn workspace_deps(cell: &CellInfo, by_module: &BTreeMap<&str, &CellInfo>) -> Vec<(String, String)> {
+    cell.imports
+        .iter()
+        .filter_map(|import| by_module.get(import.as_str()))
+        .map(|dep| (dep.module.clone(), dep.api.clone()))
+        .collect()
+}
+
+/// Dependencies before dependents; ties broken by module path.
+fn topological_order<'a>(
+    by_module: &BTreeMap<&'a str, &'a CellInfo>,
+) -> Result<Vec<&'a CellInfo>, BuildError> {
+    let mut remaining: BTreeMap<&str, BTreeSet<&str>> = by_module
+        .iter()
+        .map(|(module, cell)| {
+            let deps = cell
+                .imports
+                .iter()
+                .map(String::as_str)
+                .filter(|import| by_module.contains_key(import) && import != module)
+                .collect();
+            (*module, deps)
+        })
+        .collect();
+
+    let mut order = Vec::with_capacity(remaining.len());
+    while !remaining.is_empty() {
+        let ready: Vec<&str> = remaining
+            .iter()
+            .filter(|(_, deps)| deps.is_empty())
+            .map(|(module, _)| *module)
+            .collect();
+        if ready.is_empty() {
+            let cycle: Vec<&str> = remaining.keys().copied().collect();
+            return Err(BuildError::Cycle(cycle.join(", ")));
+        }
+        for module in ready {
+            remaining.remove(module);
+            for deps in remaining.values_mut() {
+                deps.remove(module);
+            }
+            order.push(by_module[module]);
+        }
+    }
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn cell(module: &str, semantic: &str, api: &str, imports: &[&str]) -> CellInfo {
+        CellInfo {
+            module: module.to_string(),
+            path: format!("{module}.z1c"),
+            semantic: semantic.to_string(),
+            api: api.to_string(),
+            imports: imports.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn dirty(plan: &BuildPlan) -> Vec<(&str, DirtyReason)> {
+        plan.dirty
+            .iter()
+            .map(|(cell, reason)| (cell.module.as_str(), reason.clone()))
+            .collect()
+    }
+
+    fn build_all(state: &mut BuildState, cells: &[CellInfo]) {
+        for cell in cells {
+            state.record(cell, cells, "ts");
+        }
+    }
+
+    #[test]
+    fn only_api_changes_propagate_to_dependents() {
+        let cells = vec![
+            cell("app.main", "s1", "a1", &["app.lib", "std.fs.core"]),
+            cell("app.lib", "s2", "a2", &["app.util"]),
+            cell("app.util", "s3", "a3", &[]),
+        ];
+        let mut state = BuildState::default();
+        let plan = state.plan(&cells, "ts").unwrap();
+        let order: Vec<_> = plan.order.iter().map(|c| c.module.as_str()).collect();
+        assert_eq!(order, ["app.util", "app.lib", "app.main"]);
+        assert_eq!(plan.dirty.len(), 3);
+
+        build_all(&mut state, &cells);
+        assert!(state.plan(&cells, "ts").unwrap().dirty.is_empty());
+
+        // Body edit: same API, only the cell itself rebuilds
+        let mut edited = cells.clone();
+        edited[2].semantic = "s3'".to_string();
+        assert_eq!(
+            dirty(&state.plan(&edited, "ts").unwrap()),
+            [("app.util", DirtyReason::Changed)]
+        );
+
+        // Interface edit: direct dependents rebuild too
+        edited[2].api = "a3'".to_string();
+        assert_eq!(
+            dirty(&state.plan(&edited, "ts").unwrap()),
+            [
+                ("app.util", DirtyReason::Changed),
+                (
+                    "app.lib",
+                    DirtyReason::DependencyApi("app.util".to_string())
+                )
+            ]
+        );
+
+        assert_eq!(state.plan(&cells, "wasm").unwrap().dirty.len(), 3);
+    }
+
+    #[test]
+    fn targets_are_tracked_separately() {
+        let dir = TempDir::new().unwrap();
+        let store = Store::open(dir.path());
+        let cells = vec![
+            cell("app.main", "s1", "a1", &["app.lib"]),
+            cell("app.lib", "s2", "a2", &[]),
+        ];
+        let mut state = BuildState::default();
+        build_all(&mut state, &cells);
+        for cell in &cells {
+            state.record(cell, &cells, "wasm");
+        }
+        state.save(&store).unwrap();
+        let state = BuildState::load(&store).unwrap();
+        assert!(state.plan(&cells, "ts").unwrap().dirty.is_empty());
+        assert!(state.plan(&cells, "wasm").unwrap().dirty.is_empty());
+
+        // Building one target leaves the other's record alone
+        let mut edited = cells.clone();
+        edited[1].semantic = "s2'".to_string();
+        let mut after = state.clone();
+        after.record(&edited[1], &edited, "ts");
+        assert!(after.plan(&edited, "ts").unwrap().dirty.is_empty());
+        assert_eq!(
+            dirty(&after.plan(&edited, "wasm").unwrap()),
+            [("app.lib", DirtyReason::Changed)]
+        );
+    }
+
+    #[test]
+    fn state_round_trips_through_the_store() {
+        let dir = TempDir::new().unwrap();
+        let store = Store::open(dir.path());
+        let cells = vec![
+            cell("app.main", "s1", "a1", &["app.lib"]),
+            cell("app.lib", "s2", "a2", &[]),
+        ];
+        let mut state = BuildState::load(&store).unwrap();
+        build_all(&mut state, &cells);
+        state.save(&store).unwrap();
+        assert_eq!(BuildState::load(&store).unwrap(), state);
+    }
+
+    #[test]
+    fn cycles_and_duplicates_are_rejected() {
+        let state = BuildState::default();
+        let cycle = vec![
+            cell("a", "s", "a", &["b"]),
+            cell("b", "s", "a", &["a"]),
+            cell("c", "s", "a", &[]),
+        ];
+        assert!(matches!(
+            state.plan(&cycle, "ts"),
+            Err(BuildError::Cycle(modules)) if modules == "a, b"
+        ));
+        let duplicate = vec![cell("a", "s", "a", &[]), cell("a", "t", "a", &[])];
+        assert!(matches!(
+            state.plan(&duplicate, "ts"),
+            Err(BuildError::DuplicateModule { .. })
+        ));
+    }
+}
@@ -0,0 +1,257 @@
+//! Incremental check state
+//!
+//! `.z1/checkstate` records, for every pass of static checks (`lint` runs
+//! all of them, `build` the ones compilation needs), the cells whose last
+//! check came out clean: the cell's format hash and the API hash of each
+//! import at that time, under a fingerprint of the checker configuration. A
+//! clean cell whose own hash and imports' API hashes still match passes
+//! again without re-checking; an edit to the body of an import leaves its
+//! dependents clean. Cells with diagnostics are never recorded, so their
+//! diagnostics are always reported afresh.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+
+use thiserror::Error;
+use z1_ast::Module;
+use z1_hash::module_hashes;
+
+use crate::Store;
+
+/// File name of the check state inside the store directory.
+pub const CHECKSTATE_FILE: &str = "checkstate";
+
+const CHECKSTATE_HEADER: &str = "z1checkstate 1";
+
+#[derive(Debug, Error)]
+pub enum CheckStateError {
+    #[error("I/O error on check state: {0}")]
+    Io(#[from] io::Error),
+    #[error("check state line {line}: {message}")]
+    Malformed { line: usize, message: String },
+}
+
+/// What the checks of a cell depend on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckKey {
+    /// Format hash of the cell
+    pub cell: String,
+    /// Imports by import path, with their API hash
+    pub deps: Vec<(String, String)>,
+}
+
+impl CheckKey {
+    /// Key of `module` checked against `imports`.
+    pub fn new<'a>(
+        module: &Module,
+        imports: impl IntoIterator<Item = (&'a str, &'a Module)>,
+    ) -> Self {
+        let mut deps: Vec<(String, String)> = imports
+            .into_iter()
+            .map(|(import, dep)| (import.to_string(), module_hashes(dep).api))
+            .collect();
+        deps.sort();
+        Self {
+            cell: module_hashes(module).format,
+            deps,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Pass {
+    /// Fingerprint of the checker configuration
+    config: String,
+    /// Clean cells, by module
+    cells: BTreeMap<String, CheckKey>,
+}
+
+/// Contents of `.z1/checkstate`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheckState {
+    passes: BTreeMap<String, Pass>,
+}
+
+impl CheckState {
+    /// Load the check state of `store`; a missing file is an empty state.
+    pub fn load(store: &Store) -> Result<Self, CheckStateError> {
+        match fs::read_to_string(store.root().join(CHECKSTATE_FILE)) {
+            Ok(text) => Self::parse(&text),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self, store: &Store) -> Result<(), CheckStateError> {
+        fs::create_dir_all(store.root())?;
+        let path = store.root().join(CHECKSTATE_FILE);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, self.to_string())?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    /// Whether `module` came out clean from `pass`, under `config`, when it
+    /// and its imports last had the hashes of `key`.
+    pub fn is_clean(&self, pass: &str, config: &str, module: &str, key: &CheckKey) -> bool {
+        self.passes
+            .get(pass)
+            .filter(|recorded| recorded.config == config)
+            .and_then(|recorded| recorded.cells.get(module))
+            == Some(key)
+    }
+
+    /// Record a clean check of `module` by `pass`. Cells recorded under
+    /// another configuration are dropped.
+    pub fn record(&mut self, pass: &str, config: &str, module: &str, key: CheckKey) {
+        let recorded = self.passes.entry(pass.to_string()).or_default();
+        if recorded.config != config {
+            *recorded = Pass {
+                config: config.to_string(),
+                cells: BTreeMap::new(),
+            };
+        }
+        recorded.cells.insert(module.to_string(), key);
+    }
+
+    /// Drop the record of `module` for `pass`, after a check that was not
+    /// clean.
+    pub fn forget(&mut self, pass: &str, module: &str) {
+        if let Some(recorded) = self.passes.get_mut(pass) {
+            recorded.cells.remove(module);
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self, CheckStateError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(idx, line)| (idx + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+        let malformed = |line: usize, message: &str| CheckStateError::Malformed {
+            line,
+            message: message.to_string(),
+        };
+        match lines.next() {
+            Some((_, CHECKSTATE_HEADER)) => {}
+            _ => return Err(malformed(1, "missing header")),
+        }
+
+        // Cells belong to the pass named before them
+        let mut state = Self::default();
+        let mut pass = None;
+        for (line, content) in lines {
+            let fields: Vec<&str> = content.split_whitespace().collect();
+            match fields.as_slice() {
+                ["pass", name, config] => {
+                    let recorded = state.passes.entry(name.to_string()).or_default();
+                    recorded.config = config.to_string();
+                    pass = Some(recorded);
+                }
+                ["cell", module, hash] => {
+                    pass.as_mut()
+                        .ok_or_else(|| malformed(line, "cell before its pass"))?
+                        .cells
+                        .insert(
+                            module.to_string(),
+                            CheckKey {
+                                cell: hash.to_string(),
+                                deps: Vec::new(),
+                            },
+                        );
+                }
+                ["dep", module, import, api] => pass
+                    .as_mut()
+                    .and_then(|recorded| recorded.cells.get_mut(*module))
+                    .ok_or_else(|| malformed(line, "dep before its cell"))?
+                    .deps
+                    .push((import.to_string(), api.to_string())),
+                _ => return Err(malformed(line, "unknown entry")),
+            }
+        }
+        Ok(state)
+    }
+}
+
+impl fmt::Display for CheckState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{CHECKSTATE_HEADER}")?;
+        for (pass, recorded) in &self.passes {
+            writeln!(f, "pass {pass} {}", recorded.config)?;
+            for (module, key) in &recorded.cells {
+                writeln!(f, "cell {module} {}", key.cell)?;
+                for (import, api) in &key.deps {
+                    writeln!(f, "dep {module} {import} {api}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const MAIN: &str = "m app.main:1.0 caps=[]\nu \"app/lib\" only [twice]\n\
+                        f run()->U32 eff [pure] { ret twice(2); }\n";
+    const LIB: &str = "m app.lib:1.0 caps=[]\nf twice(x: U32)->U32 eff [pure] { ret x + x; }\n";
+
+    fn parse(source: &str) -> Module {
+        z1_parse::parse_module(source).unwrap()
+    }
+
+    #[test]
+    fn only_api_changes_of_imports_invalidate_a_clean_cell() {
+        let main = parse(MAIN);
+        let lib = parse(LIB);
+        let key = CheckKey::new(&main, [("app/lib", &lib)]);
+        let mut state = CheckState::default();
+        assert!(!state.is_clean("lint", "c1", "app.main", &key));
+        state.record("lint", "c1", "app.main", key.clone());
+        assert!(state.is_clean("lint", "c1", "app.main", &key));
+        assert!(!state.is_clean("build", "c1", "app.main", &key));
+        assert!(!state.is_clean("lint", "c2", "app.main", &key));
+
+        // A body edit keeps the API hash of the import
+        let body = parse("m app.lib:1.0 caps=[]\nf twice(x: U32)->U32 eff [pure] { ret x * 2; }\n");
+        assert!(state.is_clean(
+            "lint",
+            "c1",
+            "app.main",
+            &CheckKey::new(&main, [("app/lib", &body)])
+        ));
+        let signature =
+            parse("m app.lib:1.0 caps=[]\nf twice(x: U64)->U64 eff [pure] { ret x + x; }\n");
+        assert!(!state.is_clean(
+            "lint",
+            "c1",
+            "app.main",
+            &CheckKey::new(&main, [("app/lib", &signature)])
+        ));
+
+        state.forget("lint", "app.main");
+        assert!(!state.is_clean("lint", "c1", "app.main", &key));
+    }
+
+    #[test]
+    fn state_round_trips_through_the_store() {
+        let dir = TempDir::new().unwrap();
+        let store = Store::open(dir.path());
+        let main = parse(MAIN);
+        let lib = parse(LIB);
+        let mut state = CheckState::load(&store).unwrap();
+        state.record(
+            "lint",
+            "c1",
+            "app.main",
+            CheckKey::new(&main, [("app/lib", &lib)]),
+        );
+        state.record("build", "c2", "app.lib", CheckKey::new(&lib, []));
+        state.save(&store).unwrap();
+        assert_eq!(CheckState::load(&store).unwrap(), state);
+    }
+}
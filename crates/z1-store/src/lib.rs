@@ -0,0 +1,276 @@
+//! Content-addressed cell store
+//!
+//! Cells are stored in canonical compact form under
+//! `.z1/objects/<algo>/<hex>`, keyed by their semantic hash. Two cells that
+//! differ only in formatting share one object, and every read re-hashes the
+//! object so a corrupted store is detected rather than trusted.
+//!
+//! The store directory also holds `buildstate`, the record of the last
+//! incremental build (see [`BuildState`]), and `checkstate`, the cells whose
+//! last static checks came out clean (see [`CheckState`]).
+
+mod build;
+mod checks;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+use z1_ast::Module;
+use z1_fmt::{format_module, FmtOptions, Mode};
+use z1_hash::{module_hashes_with, split_hash, HashAlgo};
+
+pub use build::{BuildError, BuildPlan, BuildState, CellInfo, DirtyReason, BUILDSTATE_FILE};
+pub use checks::{CheckKey, CheckState, CheckStateError, CHECKSTATE_FILE};
+
+/// Directory holding the store, relative to the workspace root.
+pub const STORE_DIR: &str = ".z1";
+
+#[derive(Debug, Error)]
+pub enum StoreError {
+    #[error("I/O error at {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error("failed to parse cell: {0}")]
+    Parse(#[from] z1_parse::ParseError),
+    #[error("failed to format cell: {0}")]
+    Format(#[from] z1_fmt::FmtError),
+    #[error("`{0}` is not a semantic hash")]
+    InvalidHash(String),
+    #[error("object {0} not found")]
+    NotFound(String),
+    #[error("object {expected} is corrupt: contents hash to {actual}")]
+    Corrupt { expected: String, actual: String },
+}
+
+/// A cell store rooted at `<workspace>/.z1`.
+#[derive(Debug, Clone)]
+pub struct Store {
+    root: PathBuf,
+    algo: HashAlgo,
+}
+
+impl Store {
+    /// Store for the workspace at `workspace`. Nothing is created until the
+    /// first [`Store::put`].
+    pub fn open(workspace: impl AsRef<Path>) -> Self {
+        Self {
+            root: workspace.as_ref().join(STORE_DIR),
+            algo: HashAlgo::default(),
+        }
+    }
+
+    /// Store whose objects live directly under `root/objects`, for stores
+    /// outside a workspace such as a package registry.
+    pub fn at(root: impl AsRef<Path>) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            algo: HashAlgo::default(),
+        }
+    }
+
+    /// Key new objects with `algo` instead of the default.
+    pub fn with_algo(mut self, algo: HashAlgo) -> Self {
+        self.algo = algo;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Store `module` and return its semantic hash.
+    ///
+    /// Storing a cell whose semantic hash is already present is a no-op.
+    pub fn put(&self, module: &Module) -> Result<String, StoreError> {
+        let semhash = module_hashes_with(module, self.algo).semantic;
+        let path = self.object_path(&semhash)?;
+        if path.exists() {
+            return Ok(semhash);
+        }
+        let compact = format_module(module, Mode::Compact, &FmtOptions::default())?;
+        let dir = path.parent().expect("object paths have a parent");
+        fs::create_dir_all(dir).map_err(|source| io_error(dir, source))?;
+        // Write then rename so readers never observe a partial object
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, compact).map_err(|source| io_error(&tmp, source))?;
+        fs::rename(&tmp, &path).map_err(|source| io_error(&path, source))?;
+        Ok(semhash)
+    }
+
+    /// Parse `source` and store it.
+    pub fn put_source(&self, source: &str) -> Result<String, StoreError> {
+        self.put(&z1_parse::parse_module(source)?)
+    }
+
+    pub fn contains(&self, semhash: &str) -> bool {
+        self.object_path(semhash).is_ok_and(|path| path.is_file())
+    }
+
+    /// Canonical compact text of an object, verified against its hash.
+    pub fn read(&self, semhash: &str) -> Result<String, StoreError> {
+        self.load(semhash).map(|(text, _)| text)
+    }
+
+    /// Parsed module of an object, verified against its hash.
+    pub fn get(&self, semhash: &str) -> Result<Module, StoreError> {
+        self.load(semhash).map(|(_, module)| module)
+    }
+
+    /// Write an object to `dest` in the requested formatting mode.
+    pub fn materialize(&self, semhash: &str, mode: Mode, dest: &Path) -> Result<(), StoreError> {
+        let (text, module) = self.load(semhash)?;
+        let text = match mode {
+            Mode::Compact => text,
+            Mode::Relaxed => format_module(&module, mode, &FmtOptions::default())?,
+        };
+        fs::write(dest, text).map_err(|source| io_error(dest, source))
+    }
+
+    /// Semantic hashes of all stored objects, sorted.
+    pub fn list(&self) -> Result<Vec<String>, StoreError> {
+        let mut hashes = Vec::new();
+        for algo in HashAlgo::ALL {
+            let dir = self.root.join("objects").join(algo.prefix());
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(source) => return Err(io_error(&dir, source)),
+            };
+            for entry in entries {
+                let entry = entry.map_err(|source| io_error(&dir, source))?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let hash = format!("{algo}:{name}");
+                if split_hash(&hash).is_some() {
+                    hashes.push(hash);
+                }
+            }
+        }
+        hashes.sort();
+        Ok(hashes)
+    }
+
+    fn load(&self, semhash: &str) -> Result<(String, Module), StoreError> {
+        let path = self.object_path(semhash)?;
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Err(StoreError::NotFound(semhash.to_string()))
+            }
+            Err(source) => return Err(io_error(&path, source)),
+        };
+        let module = z1_parse::parse_module(&text)?;
+        let (algo, hex) = split_hash(semhash).expect("validated by object_path");
+        let actual = module_hashes_with(&module, algo).semantic;
+        if split_hash(&actual).map(|(_, actual_hex)| actual_hex) != Some(hex) {
+            return Err(StoreError::Corrupt {
+                expected: semhash.to_string(),
+                actual,
+            });
+        }
+        Ok((text, module))
+    }
+
+    fn object_path(&self, semhash: &str) -> Result<PathBuf, StoreError> {
+        let (algo, hex) =
+            split_hash(semhash).ok_or_else(|| StoreError::InvalidHash(semhash.to_string()))?;
+        Ok(self
+            .root
+            .join("objects")
+            .join(algo.prefix())
+            .join(hex.to_ascii_lowercase()))
+    }
+}
+
+/// Root of the workspace containing `start`: the nearest of `start` and its
+/// ancestors that holds a store directory.
+pub fn find_workspace(start: impl AsRef<Path>) -> Option<PathBuf> {
+    start
+        .as_ref()
+        .ancestors()
+        .find(|dir| dir.join(STORE_DIR).is_dir())
+        .map(Path::to_path_buf)
+}
+
+fn io_error(path: &Path, source: io::Error) -> StoreError {
+    StoreError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const COMPACT: &str = include_str!("../../../fixtures/fmt/http_server.compact.z1c");
+    const RELAXED: &str = include_str!("../../../fixtures/fmt/http_server.relaxed.z1r");
+
+    #[test]
+    fn workspace_is_found_from_subdirectories() {
+        let dir = TempDir::new().unwrap();
+        let nested = dir.path().join("src/app");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find_workspace(&nested), None);
+
+        fs::create_dir(dir.path().join(STORE_DIR)).unwrap();
+        assert_eq!(find_workspace(&nested).as_deref(), Some(dir.path()));
+    }
+
+    #[test]
+    fn formatting_variants_share_one_object() {
+        let dir = TempDir::new().unwrap();
+        let store = Store::open(dir.path());
+
+        let from_compact = store.put_source(COMPACT).unwrap();
+        let from_relaxed = store.put_source(RELAXED).unwrap();
+        assert_eq!(from_compact, from_relaxed);
+        assert!(store.contains(&from_compact));
+        assert_eq!(store.list().unwrap(), vec![from_compact.clone()]);
+
+        let module = store.get(&from_compact).unwrap();
+        assert_eq!(
+            z1_hash::module_hashes(&module).semantic,
+            from_compact,
+            "stored objects round-trip to the same semantic hash"
+        );
+    }
+
+    #[test]
+    fn materialize_writes_requested_mode() {
+        let dir = TempDir::new().unwrap();
+        let store = Store::open(dir.path()).with_algo(HashAlgo::Blake3);
+        let semhash = store.put_source(RELAXED).unwrap();
+        assert!(semhash.starts_with("blake3:"));
+
+        let dest = dir.path().join("out.z1r");
+        store.materialize(&semhash, Mode::Relaxed, &dest).unwrap();
+        let text = fs::read_to_string(&dest).unwrap();
+        assert!(text.starts_with("module http.server"));
+    }
+
+    #[test]
+    fn corrupt_and_missing_objects_are_rejected() {
+        let dir = TempDir::new().unwrap();
+        let store = Store::open(dir.path());
+        let semhash = store.put_source(COMPACT).unwrap();
+
+        let path = store.object_path(&semhash).unwrap();
+        let tampered = fs::read_to_string(&path)
+            .unwrap()
+            .replace("ctx=128", "ctx=256");
+        fs::write(&path, tampered).unwrap();
+        assert!(matches!(
+            store.read(&semhash),
+            Err(StoreError::Corrupt { .. })
+        ));
+
+        let missing = format!("sha3-256:{}", "0".repeat(64));
+        assert!(matches!(store.get(&missing), Err(StoreError::NotFound(_))));
+        assert!(matches!(
+            store.get("not-a-hash"),
+            Err(StoreError::InvalidHash(_))
+        ));
+    }
+}
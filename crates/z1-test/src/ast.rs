@@ -5,9 +5,18 @@ use z1_ast::{Block, Ident, Span, TypeExpr};
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TestFile {
     pub config: TestConfig,
+    /// Cells whose functions the specs call
+    pub uses: Vec<CellUse>,
+    pub mocks: Vec<Mock>,
     pub fixtures: Vec<Fixture>,
+    /// `before { ... }` blocks, run in order ahead of every spec body, in
+    /// its scope
+    pub before: Vec<Block>,
+    /// `after { ... }` blocks, run in order after every spec, even failed
+    pub after: Vec<Block>,
     pub specs: Vec<Spec>,
     pub props: Vec<Prop>,
+    pub snapshots: Vec<Snapshot>,
     pub span: Span,
 }
 
@@ -15,9 +24,14 @@ impl TestFile {
     pub fn new() -> Self {
         Self {
             config: TestConfig::default(),
+            uses: Vec::new(),
+            mocks: Vec::new(),
             fixtures: Vec::new(),
+            before: Vec::new(),
+            after: Vec::new(),
             specs: Vec::new(),
             props: Vec::new(),
+            snapshots: Vec::new(),
             span: Span::default(),
         }
     }
@@ -32,11 +46,20 @@ impl Default for TestFile {
 /// Test configuration (file-level)
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub struct TestConfig {
+    /// Time limit of each spec, in milliseconds
     pub timeout_ms: Option<u32>,
+    /// Time limit of all the tests of a file, in milliseconds
+    pub file_timeout_ms: Option<u32>,
+    /// Statements and loop iterations a spec may run
+    pub max_steps: Option<u64>,
+    /// Bytes the variables of a spec may hold at once
+    pub max_memory: Option<u64>,
     pub tags_include: Vec<String>,
     pub tags_exclude: Vec<String>,
     pub parallel: Option<u32>,
     pub seed: Option<u64>,
+    /// Run only tests whose name contains this text
+    pub filter: Option<String>,
 }
 
 /// Spec test (unit test with assertions)
@@ -68,13 +91,81 @@ pub struct GenBinding {
     pub span: Span,
 }
 
-/// Test attributes (timeout, tags, skip, only)
+/// Snapshot test comparing a pipeline stage's output with a golden file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub name: String,
+    pub attrs: TestAttrs,
+    /// Cell to render, relative to the test file
+    pub cell: String,
+    pub emit: SnapshotKind,
+    pub span: Span,
+}
+
+/// Pipeline stage whose output a snapshot captures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SnapshotKind {
+    /// Formatter output in compact mode
+    Fmt,
+    /// Formatter output in relaxed mode
+    Relaxed,
+    /// IR dump, as lowered before optimization
+    Ir,
+    /// Generated TypeScript
+    Ts,
+}
+
+impl SnapshotKind {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "fmt" => Some(SnapshotKind::Fmt),
+            "relaxed" => Some(SnapshotKind::Relaxed),
+            "ir" => Some(SnapshotKind::Ir),
+            "ts" => Some(SnapshotKind::Ts),
+            _ => None,
+        }
+    }
+}
+
+/// `use "cells/app.z1c";`: a cell under test, relative to the test file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CellUse {
+    pub path: String,
+    pub span: Span,
+}
+
+/// Canned behavior for an effect, named as the cell calls it (`http.get`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mock {
+    pub name: String,
+    pub response: MockResponse,
+    pub span: Span,
+}
+
+/// What a mocked effect does when called
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MockResponse {
+    /// `mock "log.info";`: returns `()`
+    Unit,
+    /// `mock "time.now" returns [100, 250];`: one value per call, the last
+    /// repeating; source text of each expression
+    Returns(Vec<String>),
+    /// `mock "fs.write" fails "disk full";`
+    Fails(String),
+}
+
+/// Test attributes (timeout, tags, skip, only, xfail)
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub struct TestAttrs {
     pub timeout_ms: Option<u32>,
     pub tags: Vec<String>,
     pub skip: bool,
     pub only: bool,
+    /// `#[xfail(...)]`: the test is known to fail; passing is reported as an
+    /// unexpected success
+    pub xfail: bool,
+    /// Why the test is skipped or expected to fail, from its marker
+    pub reason: Option<String>,
 }
 
 /// Fixture declaration
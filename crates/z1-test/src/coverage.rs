@@ -0,0 +1,352 @@
+//! Statement coverage of the cells that specs run.
+//!
+//! With [`crate::TestRunner::coverage`] on, the interpreter counts the
+//! statements it runs and the counts are mapped back to the source lines of
+//! the cells the test file `use`s (or, for inline tests, of their own cell).
+//! Reports of several test files add up with [`CoverageReport::merge`], and
+//! are written as a per-function summary or in lcov format for coverage
+//! tools and CI dashboards.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+use z1_ast::{ElseBlock, FnDecl, Stmt};
+
+/// Coverage of cells, by path.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoverageReport {
+    pub cells: BTreeMap<String, Vec<FunctionCoverage>>,
+}
+
+/// Coverage of one function of a cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionCoverage {
+    pub name: String,
+    /// One-based line of the declaration
+    pub line: usize,
+    /// Times the function was called
+    pub calls: u64,
+    /// Statements in source order
+    pub statements: Vec<StatementHits>,
+}
+
+/// Times one statement ran.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatementHits {
+    /// One-based line of the statement
+    pub line: usize,
+    pub hits: u64,
+}
+
+impl FunctionCoverage {
+    /// Statements of `decl` with no hits; its body must be parsed.
+    pub(crate) fn new(decl: &FnDecl, source: &str) -> Self {
+        let mut statements = Vec::new();
+        collect(&decl.body.statements, source, &mut statements);
+        FunctionCoverage {
+            name: decl.name.to_string(),
+            line: line_of(source, decl.span.start as usize),
+            calls: 0,
+            statements,
+        }
+    }
+
+    /// Statements that ran at least once.
+    pub fn covered(&self) -> usize {
+        self.statements.iter().filter(|stmt| stmt.hits > 0).count()
+    }
+
+    /// Share of statements that ran, in percent; a function without
+    /// statements is covered once called.
+    pub fn percent(&self) -> f64 {
+        match self.statements.len() {
+            0 if self.calls > 0 => 100.0,
+            0 => 0.0,
+            total => self.covered() as f64 * 100.0 / total as f64,
+        }
+    }
+
+    /// Lines with statements that never ran.
+    pub fn missed_lines(&self) -> Vec<usize> {
+        let mut lines: Vec<usize> = self
+            .statements
+            .iter()
+            .filter(|stmt| stmt.hits == 0)
+            .map(|stmt| stmt.line)
+            .collect();
+        lines.dedup();
+        lines
+    }
+}
+
+impl CoverageReport {
+    /// No hits yet for any function of the cell at `path`.
+    pub fn for_cell(path: &Path) -> Result<Self, String> {
+        let (_, functions) = crate::exec::load_cell(path)?;
+        Ok(Self::of(path, functions))
+    }
+
+    pub(crate) fn of(path: &Path, functions: Vec<FunctionCoverage>) -> Self {
+        let mut cells = BTreeMap::new();
+        cells.insert(normalize(path), functions);
+        CoverageReport { cells }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Add the hits counted by the interpreter.
+    pub(crate) fn record(&mut self, hits: &z1_eval::Coverage) {
+        for function in self.cells.values_mut().flatten() {
+            let Some(counted) = hits.function(&function.name) else {
+                continue;
+            };
+            function.calls += counted.calls;
+            for (stmt, hits) in function.statements.iter_mut().zip(&counted.statements) {
+                stmt.hits += hits;
+            }
+        }
+    }
+
+    /// Add the hits of `other`, such as the report of another test file.
+    pub fn merge(&mut self, other: CoverageReport) {
+        for (path, functions) in other.cells {
+            let Some(totals) = self.cells.get_mut(&path) else {
+                self.cells.insert(path, functions);
+                continue;
+            };
+            for function in functions {
+                match totals.iter_mut().find(|total| total.name == function.name) {
+                    Some(total) => {
+                        total.calls += function.calls;
+                        for (stmt, other) in total.statements.iter_mut().zip(&function.statements) {
+                            stmt.hits += other.hits;
+                        }
+                    }
+                    None => totals.push(function),
+                }
+            }
+        }
+    }
+
+    /// Statements that ran, and all statements.
+    pub fn totals(&self) -> (usize, usize) {
+        self.cells
+            .values()
+            .flatten()
+            .fold((0, 0), |(covered, total), function| {
+                (
+                    covered + function.covered(),
+                    total + function.statements.len(),
+                )
+            })
+    }
+
+    /// Coverage of every function, by cell, with the lines never run.
+    pub fn write_summary(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "\nCoverage:")?;
+        for (path, functions) in &self.cells {
+            writeln!(out, "  {path}")?;
+            for function in functions {
+                write!(
+                    out,
+                    "    {:>5.1}%  {} ({}/{})",
+                    function.percent(),
+                    function.name,
+                    function.covered(),
+                    function.statements.len()
+                )?;
+                let missed = function.missed_lines();
+                if missed.is_empty() {
+                    writeln!(out)?;
+                } else {
+                    let lines: Vec<String> = missed.iter().map(ToString::to_string).collect();
+                    writeln!(out, ", not run: line {}", lines.join(", "))?;
+                }
+            }
+        }
+        let (covered, total) = self.totals();
+        let percent = if total == 0 {
+            0.0
+        } else {
+            covered as f64 * 100.0 / total as f64
+        };
+        writeln!(out, "  Total: {percent:.1}% ({covered}/{total} statements)")
+    }
+
+    /// The report in lcov tracefile format.
+    pub fn write_lcov(&self, out: &mut dyn Write) -> io::Result<()> {
+        for (path, functions) in &self.cells {
+            writeln!(out, "TN:")?;
+            writeln!(out, "SF:{path}")?;
+            for function in functions {
+                writeln!(out, "FN:{},{}", function.line, function.name)?;
+            }
+            for function in functions {
+                writeln!(out, "FNDA:{},{}", function.calls, function.name)?;
+            }
+            writeln!(out, "FNF:{}", functions.len())?;
+            let hit = functions.iter().filter(|f| f.calls > 0).count();
+            writeln!(out, "FNH:{hit}")?;
+
+            // A line runs as often as its most run statement
+            let mut lines = BTreeMap::new();
+            for stmt in functions.iter().flat_map(|f| &f.statements) {
+                let hits = lines.entry(stmt.line).or_insert(0);
+                *hits = stmt.hits.max(*hits);
+            }
+            for (line, hits) in &lines {
+                writeln!(out, "DA:{line},{hits}")?;
+            }
+            writeln!(out, "LF:{}", lines.len())?;
+            writeln!(
+                out,
+                "LH:{}",
+                lines.values().filter(|hits| **hits > 0).count()
+            )?;
+            writeln!(out, "end_of_record")?;
+        }
+        Ok(())
+    }
+}
+
+/// Statements in the pre-order the interpreter numbers them in.
+fn collect(stmts: &[Stmt], source: &str, out: &mut Vec<StatementHits>) {
+    for stmt in stmts {
+        let span = match stmt {
+            Stmt::Let(stmt) => stmt.span,
+            Stmt::Assign(stmt) => stmt.span,
+            Stmt::If(stmt) => stmt.span,
+            Stmt::While(stmt) => stmt.span,
+            Stmt::Return(stmt) => stmt.span,
+            Stmt::Expr(stmt) => stmt.span,
+        };
+        out.push(StatementHits {
+            line: line_of(source, span.start as usize),
+            hits: 0,
+        });
+        match stmt {
+            Stmt::If(stmt) => {
+                let mut stmt = stmt;
+                loop {
+                    collect(&stmt.then_block.statements, source, out);
+                    match stmt.else_block.as_deref() {
+                        Some(ElseBlock::Block(block)) => collect(&block.statements, source, out),
+                        // An `else if` is a statement of the else block
+                        Some(ElseBlock::If(nested)) => {
+                            out.push(StatementHits {
+                                line: line_of(source, nested.span.start as usize),
+                                hits: 0,
+                            });
+                            stmt = nested;
+                            continue;
+                        }
+                        None => {}
+                    }
+                    break;
+                }
+            }
+            Stmt::While(stmt) => collect(&stmt.body.statements, source, out),
+            _ => {}
+        }
+    }
+}
+
+fn line_of(source: &str, offset: usize) -> usize {
+    source[..offset.min(source.len())].matches('\n').count() + 1
+}
+
+/// `path` without `.` components and with `dir/..` pairs removed, so a cell
+/// reached from several test files is reported once.
+fn normalize(path: &Path) -> String {
+    let mut parts: Vec<Component> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(parts.last(), Some(Component::Normal(_))) => {
+                parts.pop();
+            }
+            other => parts.push(other),
+        }
+    }
+    let path: PathBuf = parts.iter().collect();
+    path.to_string_lossy().replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_test_file;
+    use crate::runner::TestRunner;
+
+    const CELL: &str = "m abs:1.0\n\
+                        f diff(a: U32, b: U32)->U32 eff [pure] {\n  \
+                          if a < b {\n    ret b - a;\n  } else if a == b {\n    ret 0;\n  }\n  \
+                          ret a - b;\n}\n\
+                        f unused()->U32 eff [pure] { ret 1; }\n";
+
+    fn report() -> CoverageReport {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("abs.z1c"), CELL).unwrap();
+        let file = parse_test_file(
+            r#"use "abs.z1c";
+               spec "less" { assert_eq(diff(1, 2), 1); }
+               spec "again" { assert_eq(diff(3, 5), 2); }"#,
+        )
+        .unwrap();
+        let results = TestRunner::default()
+            .coverage(true)
+            .run_file_at(&file, &dir.path().join("abs.z1t"));
+        assert_eq!(results.passed, 2);
+
+        let mut report = results.coverage;
+        let functions = report.cells.pop_first().unwrap().1;
+        report.cells.insert("cells/abs.z1c".to_string(), functions);
+        report
+    }
+
+    #[test]
+    fn statements_map_to_lines_and_add_up() {
+        let mut report = report();
+        let diff = &report.cells["cells/abs.z1c"][0];
+        assert_eq!(diff.line, 2);
+        let lines: Vec<usize> = diff.statements.iter().map(|s| s.line).collect();
+        assert_eq!(lines, [3, 4, 5, 6, 8]);
+        assert_eq!(diff.calls, 2);
+        assert_eq!(diff.covered(), 2);
+        assert_eq!(diff.missed_lines(), [5, 6, 8]);
+
+        report.merge(report.clone());
+        assert_eq!(report.cells["cells/abs.z1c"][0].calls, 4);
+        assert_eq!(report.totals(), (2, 6));
+
+        let mut out = Vec::new();
+        report.write_summary(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(
+            text.contains("     40.0%  diff (2/5), not run: line 5, 6, 8\n"),
+            "{text}"
+        );
+        assert!(text.contains("      0.0%  unused (0/1), not run: line 10\n"));
+        assert!(text.contains("  Total: 33.3% (2/6 statements)"), "{text}");
+
+        let mut out = Vec::new();
+        report.write_lcov(&mut out).unwrap();
+        let lcov = String::from_utf8(out).unwrap();
+        assert!(lcov.starts_with("TN:\nSF:cells/abs.z1c\nFN:2,diff\nFN:10,unused\n"));
+        assert!(lcov.contains("FNDA:4,diff\nFNDA:0,unused\nFNF:2\nFNH:1\n"));
+        assert!(lcov.contains("DA:3,4\nDA:4,4\nDA:5,0\n"), "{lcov}");
+        assert!(lcov.ends_with("LF:6\nLH:2\nend_of_record\n"), "{lcov}");
+    }
+
+    #[test]
+    fn normalizes_paths() {
+        assert_eq!(
+            normalize(Path::new("tests/./../cells/a.z1c")),
+            "cells/a.z1c"
+        );
+        assert_eq!(normalize(Path::new("../a.z1c")), "../a.z1c");
+    }
+}
@@ -0,0 +1,164 @@
+//! Structural diffs of the values compared by `assert_eq`.
+//!
+//! Two records that differ are compared field by field, nested records
+//! included, so a failure names the fields that differ instead of printing
+//! both records whole:
+//!
+//! ```text
+//! assert_eq: records differ in 2 fields
+//!   .status
+//!   - 200
+//!   + 404
+//!   .user.name
+//!   - "ann"
+//!   + "bob"
+//! ```
+//!
+//! Lines starting with `-` hold the left value and lines starting with `+`
+//! the right one; a field only one side has gets only its own line. The
+//! pretty reporter colors them.
+
+use z1_ast::Ident;
+use z1_eval::Value;
+
+/// A field whose values differ between two records.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    /// Field path from the compared values, like `.user.name`
+    pub path: String,
+    /// Value on the left, `None` when only the right has the field
+    pub left: Option<Value>,
+    /// Value on the right, `None` when only the left has the field
+    pub right: Option<Value>,
+}
+
+/// Fields that differ between `left` and `right`, in the order of `left`
+/// followed by the fields only `right` has. Values that are not both
+/// records differ as a whole, at the empty path.
+pub fn diff_values(left: &Value, right: &Value) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    walk(String::new(), left, right, &mut differences);
+    differences
+}
+
+/// Message of a failed `assert_eq(left, right)`: a field-by-field diff of
+/// records, or both values.
+pub fn explain_mismatch(left: &Value, right: &Value) -> String {
+    let (Value::Record(_), Value::Record(_)) = (left, right) else {
+        return format!("{left} != {right}");
+    };
+    let differences = diff_values(left, right);
+    let mut message = match differences.len() {
+        1 => "records differ in 1 field".to_string(),
+        count => format!("records differ in {count} fields"),
+    };
+    for difference in &differences {
+        message.push_str(&format!("\n  {}", difference.path));
+        if let Some(left) = &difference.left {
+            message.push_str(&format!("\n  - {left}"));
+        }
+        if let Some(right) = &difference.right {
+            message.push_str(&format!("\n  + {right}"));
+        }
+    }
+    message
+}
+
+fn walk(path: String, left: &Value, right: &Value, out: &mut Vec<Difference>) {
+    match (left, right) {
+        (Value::Record(left_fields), Value::Record(right_fields)) => {
+            for (name, value) in left_fields {
+                let field = format!("{path}.{name}");
+                match field_of(right_fields, name) {
+                    Some(other) => walk(field, value, other, out),
+                    None => out.push(Difference {
+                        path: field,
+                        left: Some(value.clone()),
+                        right: None,
+                    }),
+                }
+            }
+            for (name, value) in right_fields {
+                if field_of(left_fields, name).is_none() {
+                    out.push(Difference {
+                        path: format!("{path}.{name}"),
+                        left: None,
+                        right: Some(value.clone()),
+                    });
+                }
+            }
+        }
+        _ if left.equals(right) => {}
+        _ => out.push(Difference {
+            path,
+            left: Some(left.clone()),
+            right: Some(right.clone()),
+        }),
+    }
+}
+
+fn field_of<'a>(fields: &'a [(Ident, Value)], name: &str) -> Option<&'a Value> {
+    fields
+        .iter()
+        .find(|(field, _)| field == name)
+        .map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(fields: &[(&str, Value)]) -> Value {
+        Value::Record(
+            fields
+                .iter()
+                .map(|(name, value)| ((*name).into(), value.clone()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn records_differ_field_by_field() {
+        let left = record(&[
+            ("status", Value::U32(200)),
+            ("user", record(&[("name", Value::Str("ann".into()))])),
+            ("body", Value::Str("ok".into())),
+            ("retries", Value::Int(0)),
+        ]);
+        let right = record(&[
+            ("retries", Value::U32(0)),
+            ("status", Value::U32(404)),
+            ("user", record(&[("name", Value::Str("bob".into()))])),
+            ("error", Value::Bool(true)),
+        ]);
+        let paths: Vec<String> = diff_values(&left, &right)
+            .into_iter()
+            .map(|difference| difference.path)
+            .collect();
+        assert_eq!(paths, [".status", ".user.name", ".body", ".error"]);
+
+        assert_eq!(
+            explain_mismatch(&left, &right),
+            "records differ in 4 fields\n  \
+             .status\n  - 200\n  + 404\n  \
+             .user.name\n  - \"ann\"\n  + \"bob\"\n  \
+             .body\n  - \"ok\"\n  \
+             .error\n  + true"
+        );
+    }
+
+    #[test]
+    fn other_values_differ_as_a_whole() {
+        assert_eq!(explain_mismatch(&Value::U32(4), &Value::U32(5)), "4 != 5");
+        let record = record(&[("n", Value::U32(1))]);
+        assert_eq!(explain_mismatch(&record, &Value::Unit), "{ n: 1 } != ()");
+        assert_eq!(
+            diff_values(&Value::U32(4), &Value::U32(5)),
+            [Difference {
+                path: String::new(),
+                left: Some(Value::U32(4)),
+                right: Some(Value::U32(5)),
+            }]
+        );
+    }
+}
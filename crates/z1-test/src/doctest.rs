@@ -0,0 +1,175 @@
+//! Doc tests: fenced examples in the doc comments of a cell.
+//!
+//! ```z1c
+//! /// Adds two numbers.
+//! ///
+//! /// ```
+//! /// assert_eq(add(1, 2), 3);
+//! /// ```
+//! f add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }
+//! ```
+//!
+//! Every fence without a language, or tagged `z1`, becomes a spec run
+//! against the cell, as inline tests are; fences in other languages are
+//! prose. The `ignore` and `xfail` flags, as in `z1,ignore`, mark an example
+//! as skipped or expected to fail.
+
+use std::path::Path;
+
+use z1_ast::{Block, Item, Module};
+
+use crate::ast::{CellUse, Spec, TestAttrs, TestFile};
+
+/// A fenced example of a doc comment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocExample {
+    /// Statements of the example
+    pub code: String,
+    pub attrs: TestAttrs,
+}
+
+/// Test file running the doc examples of `module`, read from `path`; run it
+/// with [`crate::TestRunner::run_file_at`] and the same `path`. `None` when
+/// no declaration has examples.
+pub fn doc_tests(module: &Module, path: &Path) -> Option<TestFile> {
+    let mut specs = Vec::new();
+    for item in &module.items {
+        let (name, doc, span) = match item {
+            Item::Fn(decl) => (&decl.name, &decl.doc, decl.span),
+            Item::Type(decl) => (&decl.name, &decl.doc, decl.span),
+            _ => continue,
+        };
+        let examples = doc.as_deref().map(examples).unwrap_or_default();
+        for (idx, example) in examples.into_iter().enumerate() {
+            specs.push(Spec {
+                name: format!("{name} (example {})", idx + 1),
+                attrs: example.attrs,
+                body: Block {
+                    raw: format!("{{\n{}\n}}", example.code),
+                    statements: Vec::new(),
+                    span,
+                },
+                span,
+            });
+        }
+    }
+    if specs.is_empty() {
+        return None;
+    }
+    let cell = path.file_name()?.to_string_lossy().into_owned();
+    Some(TestFile {
+        uses: vec![CellUse {
+            path: cell,
+            span: module.span,
+        }],
+        specs,
+        span: module.span,
+        ..TestFile::new()
+    })
+}
+
+/// Runnable examples of the doc comment `doc`, in order. A fence left open
+/// runs to the end of the comment.
+pub fn examples(doc: &str) -> Vec<DocExample> {
+    let mut examples = Vec::new();
+    let mut open: Option<(Option<TestAttrs>, Vec<&str>)> = None;
+    for line in doc.lines() {
+        let fence = line.trim_start().strip_prefix("```");
+        match (&mut open, fence) {
+            (None, Some(info)) => open = Some((attrs(info), Vec::new())),
+            (Some(_), Some(_)) => {
+                if let Some((Some(attrs), code)) = open.take() {
+                    examples.push(DocExample {
+                        code: code.join("\n"),
+                        attrs,
+                    });
+                }
+            }
+            (Some((_, code)), None) => code.push(line),
+            (None, None) => {}
+        }
+    }
+    if let Some((Some(attrs), code)) = open {
+        examples.push(DocExample {
+            code: code.join("\n"),
+            attrs,
+        });
+    }
+    examples
+}
+
+/// Attributes of an example from the info string of its fence, `None` when
+/// the fence is not z1 code.
+fn attrs(info: &str) -> Option<TestAttrs> {
+    let mut attrs = TestAttrs::default();
+    for word in info
+        .split(',')
+        .map(str::trim)
+        .filter(|word| !word.is_empty())
+    {
+        match word {
+            "z1" | "z1c" | "z1t" => {}
+            "ignore" => {
+                attrs.skip = true;
+                attrs.reason = Some("ignored example".to_string());
+            }
+            "xfail" => attrs.xfail = true,
+            _ => return None,
+        }
+    }
+    Some(attrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::TestRunner;
+
+    #[test]
+    fn extracts_fenced_examples() {
+        let doc = "Adds.\n\n```\nlet t = add(1, 2);\nassert_eq(t, 3);\n```\n\
+                   ```text\nadd(a, b)\n```\n```z1,ignore\nadd();\n```\n```z1";
+        let found = examples(doc);
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0].code, "let t = add(1, 2);\nassert_eq(t, 3);");
+        assert_eq!(found[0].attrs, TestAttrs::default());
+        assert!(found[1].attrs.skip);
+        assert_eq!(found[1].attrs.reason.as_deref(), Some("ignored example"));
+        assert_eq!(found[2].code, "");
+    }
+
+    #[test]
+    fn runs_the_examples_of_a_cell() {
+        let source = "m calc:1.0\n\
+                      /// Adds two numbers.\n\
+                      ///\n\
+                      /// ```\n\
+                      /// assert_eq(add(1, 2), 3);\n\
+                      /// ```\n\
+                      ///\n\
+                      /// ```z1\n\
+                      /// assert_eq(add(2, 2), 5);\n\
+                      /// ```\n\
+                      f add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }\n\
+                      f zero()->U32 eff [pure] { ret 0; }\n";
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("calc.z1c");
+        std::fs::write(&path, source).unwrap();
+
+        let module = z1_parse::parse_module(source).unwrap();
+        let file = doc_tests(&module, &path).unwrap();
+        let names: Vec<&str> = file.specs.iter().map(|spec| spec.name.as_str()).collect();
+        assert_eq!(names, ["add (example 1)", "add (example 2)"]);
+        let results = TestRunner::default().run_file_at(&file, &path);
+        assert_eq!(results.passed, 1);
+        assert_eq!(results.failures[0].name, "add (example 2)");
+        assert_eq!(
+            results.failures[0].error,
+            "Assertion failed: assert_eq: 4 != 5"
+        );
+
+        let plain =
+            z1_parse::parse_module("m calc:1.0\n/// Zero.\nf zero()->U32 { ret 0; }\n").unwrap();
+        assert!(doc_tests(&plain, &path).is_none());
+    }
+}
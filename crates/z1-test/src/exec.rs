@@ -0,0 +1,402 @@
+//! Spec execution with the reference interpreter.
+//!
+//! Each spec becomes a function appended to the cells the test file `use`s,
+//! with the fixtures bound as leading `let`s and the `before` blocks run
+//! ahead of its body. The `after` blocks become a second function, run next
+//! with the same mocks even when the spec fails. The module is lowered to IR and
+//! the function run by [`z1_eval`], effects going to a fresh [`TestHost`].
+//! When coverage is on, the statements run are counted for the cells.
+//! Running out of time or steps is a timeout, not an ordinary failure.
+
+use std::fs;
+use std::path::Path;
+
+use z1_ast::{Block, FnDecl, Item, LetStmt, Module, ModulePath, Span, Stmt, TypeExpr};
+use z1_eval::{EvalError, EvalOptions, Interpreter, NoHost, Value};
+use z1_parse::body::{parse_expr, parse_test_body};
+
+use crate::ast::{MockResponse, Spec, TestFile};
+use crate::coverage::{CoverageReport, FunctionCoverage};
+use crate::mock::{MockBehavior, TestHost, BUILTINS};
+use crate::runner::TestError;
+
+/// Name of the function a spec body runs as
+const SPEC_FN: &str = "__spec";
+/// Name of the function `after` blocks run as
+const AFTER_FN: &str = "__after";
+
+/// Resource limits of one spec run
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Limits {
+    pub(crate) options: EvalOptions,
+    /// What the deadline of `options` stands for, reported when it is reached
+    pub(crate) timeout: String,
+}
+
+/// Everything specs of one file run against
+#[derive(Debug, Default)]
+pub(crate) struct SpecEnv {
+    /// Types and functions of the used cells, bodies parsed
+    items: Vec<Item>,
+    /// Fixture bindings
+    fixtures: Vec<Stmt>,
+    /// Statements of the `before` and `after` blocks
+    before: Vec<Stmt>,
+    after: Vec<Stmt>,
+    host: TestHost,
+    /// Statements of the used cells, none run yet
+    coverage: CoverageReport,
+}
+
+impl SpecEnv {
+    /// Load the cells, fixtures and mocks of `file`, resolving cells against
+    /// `dir`, the directory of the test file. `rand` effects draw from a
+    /// generator seeded with `seed`, afresh for every spec.
+    pub(crate) fn load(file: &TestFile, dir: Option<&Path>, seed: u64) -> Result<Self, String> {
+        let mut env = SpecEnv {
+            host: TestHost::new().seed(seed),
+            ..SpecEnv::default()
+        };
+
+        for cell in &file.uses {
+            let dir = dir.ok_or_else(|| {
+                format!(
+                    "Cannot resolve `use \"{}\"` without the path of the test file",
+                    cell.path
+                )
+            })?;
+            let path = dir.join(&cell.path);
+            let (items, functions) = load_cell(&path)?;
+            env.items.extend(items);
+            env.coverage.merge(CoverageReport::of(&path, functions));
+        }
+
+        for fixture in &file.fixtures {
+            let init = parse_expr(&fixture.body.raw)
+                .map_err(|err| format!("Invalid fixture `{}`: {err}", fixture.name))?;
+            env.fixtures.push(Stmt::Let(LetStmt {
+                mutable: false,
+                name: fixture.name,
+                ty: fixture.ty.clone(),
+                init,
+                span: fixture.span,
+            }));
+        }
+
+        for (hooks, blocks, kind) in [
+            (&mut env.before, &file.before, "before"),
+            (&mut env.after, &file.after, "after"),
+        ] {
+            for block in blocks {
+                hooks.extend(
+                    parse_test_body(&block.raw)
+                        .map_err(|err| format!("Invalid {kind} block: {err}"))?,
+                );
+            }
+        }
+
+        for mock in &file.mocks {
+            let behavior = match &mock.response {
+                MockResponse::Unit => MockBehavior::Returns(vec![Value::Unit]),
+                MockResponse::Returns(values) => MockBehavior::Returns(
+                    values
+                        .iter()
+                        .map(|value| evaluate(value))
+                        .collect::<Result<_, _>>()
+                        .map_err(|err| format!("Invalid mock \"{}\": {err}", mock.name))?,
+                ),
+                MockResponse::Fails(message) => MockBehavior::Fails(message.clone()),
+            };
+            env.host = env.host.mock(&mock.name, behavior);
+        }
+
+        Ok(env)
+    }
+
+    /// Run `spec` with fresh mocks, clock and generator: call counts start at
+    /// zero and the clock at [`crate::CLOCK_START`] for every spec.
+    /// Statements run are added to `coverage` when given.
+    pub(crate) fn run(
+        &self,
+        spec: &Spec,
+        coverage: Option<&mut z1_eval::Coverage>,
+        limits: &Limits,
+    ) -> Result<(), TestError> {
+        let body = parse_test_body(&spec.body.raw).map_err(|err| TestError::Failed {
+            message: format!("Invalid spec body: {err}"),
+        })?;
+        let statements = self
+            .fixtures
+            .iter()
+            .chain(&self.before)
+            .cloned()
+            .chain(body)
+            .collect();
+
+        let mut items = self.items.clone();
+        items.push(Item::Fn(function(SPEC_FN, "Unit", statements)));
+        if !self.after.is_empty() {
+            let statements = self.fixtures.iter().chain(&self.after).cloned().collect();
+            items.push(Item::Fn(function(AFTER_FN, "Unit", statements)));
+        }
+        let module = module(items);
+        let ir = z1_ir::lower_to_ir(&module).map_err(|err| TestError::Failed {
+            message: err.to_string(),
+        })?;
+
+        let mut host = self.host.clone();
+        let mut interpreter = Interpreter::with_options(&ir, &mut host, limits.options.clone());
+        if coverage.is_some() {
+            interpreter = interpreter.track_coverage();
+        }
+        let result = interpreter.call(SPEC_FN, vec![]);
+        let after = if self.after.is_empty() {
+            Ok(Value::Unit)
+        } else {
+            interpreter.call(AFTER_FN, vec![])
+        };
+        if let (Some(total), Some(counted)) = (coverage, interpreter.coverage()) {
+            total.merge(counted);
+        }
+        // The spec's own failure is the one worth reporting
+        outcome(result, limits)?;
+        outcome(after, limits).map_err(|err| match err {
+            TestError::AssertionFailed { message } => TestError::AssertionFailed {
+                message: format!("in after: {message}"),
+            },
+            TestError::Failed { message } => TestError::Failed {
+                message: format!("in after: {message}"),
+            },
+            other => other,
+        })
+    }
+
+    /// Coverage of the used cells, given the hits counted by [`SpecEnv::run`]
+    pub(crate) fn coverage(&self, hits: &z1_eval::Coverage) -> CoverageReport {
+        let mut report = self.coverage.clone();
+        report.record(hits);
+        report
+    }
+}
+
+/// Types and functions of the cell at `path`, with function bodies parsed,
+/// and its statements for coverage.
+pub(crate) fn load_cell(path: &Path) -> Result<(Vec<Item>, Vec<FunctionCoverage>), String> {
+    let source = fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+    let module = z1_parse::parse_module(&source)
+        .map_err(|err| format!("Failed to parse {}: {err}", path.display()))?;
+
+    let mut items = Vec::new();
+    let mut functions = Vec::new();
+    for item in module.items {
+        match item {
+            Item::Fn(decl) => {
+                functions.push(FunctionCoverage::new(&decl, &source));
+                items.push(Item::Fn(decl));
+            }
+            Item::Type(decl) => items.push(Item::Type(decl)),
+            Item::Import(_) | Item::Symbol(_) | Item::Test(_) => {}
+        }
+    }
+    Ok((items, functions))
+}
+
+/// The test outcome of a call of the interpreter
+fn outcome(result: Result<Value, EvalError>, limits: &Limits) -> Result<(), TestError> {
+    match result {
+        Ok(_) => Ok(()),
+        Err(EvalError::TimeLimit) => Err(TestError::Timeout {
+            message: limits.timeout.clone(),
+        }),
+        Err(err @ EvalError::StepLimit(_)) => Err(TestError::Timeout {
+            message: err.to_string(),
+        }),
+        Err(EvalError::Host { name, message }) if BUILTINS.contains(&name.as_str()) => {
+            Err(TestError::AssertionFailed {
+                message: format!("{name}: {message}"),
+            })
+        }
+        Err(EvalError::UnhandledCall(name)) => Err(TestError::Failed {
+            message: format!(
+                "effect `{name}` is not mocked; declare it with `mock \"{name}\" returns ...;`"
+            ),
+        }),
+        Err(err) => Err(TestError::Failed {
+            message: err.to_string(),
+        }),
+    }
+}
+
+/// Evaluate a mock value, such as `200` or `Res { status: 200 }`.
+fn evaluate(source: &str) -> Result<Value, String> {
+    let expr = parse_expr(source).map_err(|err| err.to_string())?;
+    let span = Span::default();
+    let ret = Stmt::Return(z1_ast::ReturnStmt {
+        value: Some(expr),
+        span,
+    });
+    // The return type is left unresolved so the value keeps its own type
+    let module = module(vec![Item::Fn(function("__value", "_", vec![ret]))]);
+    let ir = z1_ir::lower_to_ir(&module).map_err(|err| err.to_string())?;
+    z1_eval::run(&ir, "__value", vec![], &mut NoHost).map_err(|err| err.to_string())
+}
+
+fn function(name: &str, ret: &str, statements: Vec<Stmt>) -> FnDecl {
+    FnDecl {
+        name: name.into(),
+        type_params: vec![],
+        params: Vec::new(),
+        ret: TypeExpr::Path(vec![ret.into()]),
+        effects: Vec::new(),
+        body: Block {
+            raw: String::new(),
+            statements,
+            span: Span::default(),
+        },
+        span: Span::default(),
+        doc: None,
+    }
+}
+
+fn module(items: Vec<Item>) -> Module {
+    Module::new(
+        ModulePath(vec!["test".into()]),
+        None,
+        None,
+        Vec::new(),
+        items,
+        Span::default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_test_file;
+
+    const CELL: &str = "m clock:1.0 caps=[time]
+f elapsed(since: U64)->U64 eff [time] {
+  let now: U64 = time.now();
+  if now < since { ret 0; }
+  ret now - since;
+}
+";
+
+    fn spec_results(tests: &str) -> Vec<Result<(), TestError>> {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("clock.z1c"), CELL).unwrap();
+        let file = parse_test_file(tests).unwrap();
+        let env = SpecEnv::load(&file, Some(dir.path()), 7).unwrap();
+        file.specs
+            .iter()
+            .map(|spec| env.run(spec, None, &Limits::default()))
+            .collect()
+    }
+
+    #[test]
+    fn specs_call_cells_with_mocked_effects() {
+        let results = spec_results(
+            r#"
+            use "clock.z1c";
+            mock "time.now" returns [100, 250];
+            fixture start: U64 = { 40 };
+
+            spec "sequence" {
+              assert_eq(elapsed(start), 60);
+              assert_eq(elapsed(start), 210);
+              assert_eq(elapsed(300), 0);
+              assert_eq(calls("time.now"), 3);
+              assert called_with("time.now");
+            }
+
+            spec "fresh counts" { assert_eq(calls("time.now"), 0); }
+
+            spec "wrong" { assert_eq(elapsed(0), 99); }
+            "#,
+        );
+        assert!(results[0].is_ok(), "{:?}", results[0]);
+        assert!(results[1].is_ok(), "{:?}", results[1]);
+        let Err(TestError::AssertionFailed { message }) = &results[2] else {
+            panic!("{:?}", results[2]);
+        };
+        assert_eq!(message, "assert_eq: 100 != 99");
+    }
+
+    #[test]
+    fn before_and_after_blocks_wrap_every_spec() {
+        let results = spec_results(
+            r#"
+            use "clock.z1c";
+            mock "time.now" returns [100, 250];
+            fixture start: U64 = { 40 };
+            fixture window = { { from: 40, to: 300 } };
+            before {
+              let first: U64 = elapsed(start);
+            }
+            after {
+              assert_eq(calls("time.now"), 2);
+            }
+
+            spec "sees setup" {
+              assert_eq(first, 60);
+              assert_eq(window.to - window.from, 260);
+              assert_eq(elapsed(start), 210);
+            }
+            spec "teardown checks" { assert_eq(first, 60); }
+            spec "own failure wins" { assert_eq(first, 0); }
+            "#,
+        );
+        assert!(results[0].is_ok(), "{:?}", results[0]);
+        let message = results[1].as_ref().unwrap_err().to_string();
+        assert_eq!(message, "Assertion failed: in after: assert_eq: 1 != 2");
+        let message = results[2].as_ref().unwrap_err().to_string();
+        assert_eq!(message, "Assertion failed: assert_eq: 60 != 0");
+    }
+
+    #[test]
+    fn time_and_randomness_are_controlled() {
+        let results = spec_results(
+            r#"
+            use "clock.z1c";
+            spec "clock" {
+              assert_eq(elapsed(0), 1700000000000);
+              advance(250);
+              time.sleep(1);
+              assert_eq(elapsed(1700000000000), 1250);
+            }
+            spec "fresh clock" { assert_eq(elapsed(1700000000000), 0); }
+            spec "seeded" {
+              let roll: U32 = rand.range(1, 7);
+              assert(roll >= 1 && roll < 7);
+              assert_eq(calls("rand.range"), 1);
+            }
+            "#,
+        );
+        for result in &results {
+            assert!(result.is_ok(), "{result:?}");
+        }
+    }
+
+    #[test]
+    fn unmocked_and_failing_effects_fail_the_spec() {
+        let results = spec_results(
+            r#"
+            use "clock.z1c";
+            spec "unmocked" { http.get("/health"); }
+            "#,
+        );
+        let message = results[0].as_ref().unwrap_err().to_string();
+        assert!(message.contains("mock \"http.get\" returns"), "{message}");
+
+        let results = spec_results(
+            r#"
+            use "clock.z1c";
+            mock "time.now" fails "clock unavailable";
+            spec "fails" { elapsed(1); }
+            "#,
+        );
+        let message = results[0].as_ref().unwrap_err().to_string();
+        assert!(message.contains("clock unavailable"), "{message}");
+    }
+}
@@ -0,0 +1,78 @@
+//! Inline tests: `#test` blocks written in a cell.
+//!
+//! ```z1c
+//! f add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }
+//!
+//! #test "adds" { assert_eq(add(1, 2), 3); }
+//! ```
+//!
+//! Each block runs as a spec of a test file that `use`s the cell, so it can
+//! call every function of the cell.
+
+use std::path::Path;
+
+use z1_ast::{Item, Module};
+
+use crate::ast::{CellUse, Spec, TestFile};
+
+/// Test file running the `#test` blocks of `module`, read from `path`; run
+/// it with [`crate::TestRunner::run_file_at`] and the same `path`. `None`
+/// when the cell has no inline tests.
+pub fn inline_tests(module: &Module, path: &Path) -> Option<TestFile> {
+    let specs: Vec<Spec> = module
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Test(test) => Some(Spec {
+                name: test.name.clone(),
+                attrs: Default::default(),
+                body: test.body.clone(),
+                span: test.span,
+            }),
+            _ => None,
+        })
+        .collect();
+    if specs.is_empty() {
+        return None;
+    }
+    let cell = path.file_name()?.to_string_lossy().into_owned();
+    Some(TestFile {
+        uses: vec![CellUse {
+            path: cell,
+            span: module.span,
+        }],
+        specs,
+        span: module.span,
+        ..TestFile::new()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::TestRunner;
+
+    #[test]
+    fn runs_the_tests_of_a_cell() {
+        let source = "m calc:1.0\nf add(a: U32, b: U32)->U32 eff [pure] { ret a + b; }\n\
+                      #test \"adds\" { assert_eq(add(1, 2), 3); }\n\
+                      #test \"wraps\" { assert_eq(add(4294967295, 1), 1); }\n";
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("calc.z1c");
+        std::fs::write(&path, source).unwrap();
+
+        let module = z1_parse::parse_module(source).unwrap();
+        let file = inline_tests(&module, &path).unwrap();
+        assert_eq!(file.uses[0].path, "calc.z1c");
+        let results = TestRunner::default().run_file_at(&file, &path);
+        assert_eq!(results.passed, 1);
+        assert_eq!(results.failures[0].name, "wraps");
+        assert_eq!(
+            results.failures[0].error,
+            "Assertion failed: assert_eq: 0 != 1"
+        );
+
+        let plain = z1_parse::parse_module("m calc:1.0\n").unwrap();
+        assert!(inline_tests(&plain, &path).is_none());
+    }
+}
@@ -14,6 +14,11 @@ pub enum TestTokenKind {
     KwSpec,
     KwProp,
     KwFixture,
+    KwSnapshot,
+    KwUse,
+    KwMock,
+    KwBefore,
+    KwAfter,
     KwConfig,
     KwForAll,
     KwRuns,
@@ -43,6 +48,7 @@ pub enum TestTokenKind {
     Semi,
     Eq,
     Arrow,
+    Hash,
 
     Unknown,
     Eof,
@@ -64,6 +70,21 @@ enum RawTestToken {
     #[token("fixture")]
     KwFixture,
 
+    #[token("snapshot")]
+    KwSnapshot,
+
+    #[token("use")]
+    KwUse,
+
+    #[token("mock")]
+    KwMock,
+
+    #[token("before")]
+    KwBefore,
+
+    #[token("after")]
+    KwAfter,
+
     #[token("config")]
     KwConfig,
 
@@ -134,6 +155,8 @@ enum RawTestToken {
     Eq,
     #[token("->")]
     Arrow,
+    #[token("#")]
+    Hash,
 }
 
 impl From<RawTestToken> for TestTokenKind {
@@ -142,6 +165,11 @@ impl From<RawTestToken> for TestTokenKind {
             RawTestToken::KwSpec => TestTokenKind::KwSpec,
             RawTestToken::KwProp => TestTokenKind::KwProp,
             RawTestToken::KwFixture => TestTokenKind::KwFixture,
+            RawTestToken::KwSnapshot => TestTokenKind::KwSnapshot,
+            RawTestToken::KwUse => TestTokenKind::KwUse,
+            RawTestToken::KwMock => TestTokenKind::KwMock,
+            RawTestToken::KwBefore => TestTokenKind::KwBefore,
+            RawTestToken::KwAfter => TestTokenKind::KwAfter,
             RawTestToken::KwConfig => TestTokenKind::KwConfig,
             RawTestToken::KwForAll => TestTokenKind::KwForAll,
             RawTestToken::KwRuns => TestTokenKind::KwRuns,
@@ -169,6 +197,7 @@ impl From<RawTestToken> for TestTokenKind {
             RawTestToken::Semi => TestTokenKind::Semi,
             RawTestToken::Eq => TestTokenKind::Eq,
             RawTestToken::Arrow => TestTokenKind::Arrow,
+            RawTestToken::Hash => TestTokenKind::Hash,
             RawTestToken::Error => TestTokenKind::Unknown,
         }
     }
@@ -1,9 +1,24 @@
 pub mod ast;
+pub mod coverage;
+pub mod diff;
+pub mod doctest;
+mod exec;
+pub mod inline;
 pub mod lexer;
+pub mod mock;
 pub mod parser;
+pub mod report;
 pub mod runner;
+pub mod snapshot;
 
 pub use ast::*;
+pub use coverage::*;
+pub use diff::*;
+pub use doctest::*;
+pub use inline::*;
 pub use lexer::*;
+pub use mock::*;
 pub use parser::*;
+pub use report::*;
 pub use runner::*;
+pub use snapshot::*;
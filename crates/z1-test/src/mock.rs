@@ -0,0 +1,345 @@
+//! Effect mocking for spec tests.
+//!
+//! Specs run cells with the reference interpreter, whose effects (calls to
+//! anything outside the module, such as `http.get` or `time.now`) go to a
+//! [`TestHost`]. Each `mock` item of the test file gives one effect canned
+//! behavior, and every call is recorded so specs can assert on it:
+//!
+//! ```z1t
+//! mock "time.now" returns [100, 250];
+//! mock "http.get" returns Res { status: 200, body: "ok" };
+//! mock "fs.write" fails "disk full";
+//! mock "log.info";
+//!
+//! spec "fetches once" {
+//!   assert_eq(fetch("/health"), 200);
+//!   assert_eq(calls("http.get"), 1);
+//!   assert called_with("http.get", "/health");
+//! }
+//! ```
+//!
+//! The host also provides the assertion builtins: `assert`, `assert_eq` and
+//! `assert_ne`. A failed `assert_eq` of records lists the fields that differ
+//! (see [`crate::diff`]).
+//!
+//! Time and randomness are controlled so specs are reproducible. Unless
+//! mocked, `time.now` reads a clock that starts at [`CLOCK_START`] and only
+//! moves when the spec calls `time.sleep` or the `advance(millis)` builtin,
+//! and `rand.u32`, `rand.u64` and `rand.range(min, max)` draw from a
+//! generator seeded by the runner.
+
+use std::collections::HashMap;
+
+use z1_eval::{EvalError, Host, Value};
+
+/// Calls handled by the host itself rather than by mocks
+pub const BUILTINS: &[&str] = &[
+    "assert",
+    "assert_eq",
+    "assert_ne",
+    "calls",
+    "called_with",
+    "advance",
+];
+
+/// Effects the host answers from its clock and generator when not mocked
+pub const CONTROLLED: &[&str] = &[
+    "time.now",
+    "time.nowMillis",
+    "time.sleep",
+    "time.sleepMillis",
+    "rand.u32",
+    "rand.u64",
+    "rand.range",
+];
+
+/// Milliseconds since the Unix epoch the test clock starts at
+/// (2023-11-14T22:13:20Z)
+pub const CLOCK_START: u64 = 1_700_000_000_000;
+
+/// Evaluated behavior of a mocked effect
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockBehavior {
+    /// Values returned by successive calls; the last one repeats
+    Returns(Vec<Value>),
+    /// Every call fails with this message
+    Fails(String),
+}
+
+/// A call to a mocked effect
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedCall {
+    pub name: String,
+    pub args: Vec<Value>,
+}
+
+/// Host for spec tests: mocked effects, recorded calls, assertions, and the
+/// controlled clock and generator.
+#[derive(Debug, Clone)]
+pub struct TestHost {
+    mocks: HashMap<String, MockBehavior>,
+    calls: Vec<RecordedCall>,
+    /// Milliseconds since the Unix epoch
+    clock: u64,
+    /// State of the generator
+    rng: u64,
+}
+
+impl Default for TestHost {
+    fn default() -> Self {
+        Self {
+            mocks: HashMap::new(),
+            calls: Vec::new(),
+            clock: CLOCK_START,
+            rng: 0,
+        }
+    }
+}
+
+impl TestHost {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Seed the generator behind the `rand` effects
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.rng = seed;
+        self
+    }
+
+    /// Mock the effect `name`, replacing any previous mock for it
+    pub fn mock(mut self, name: impl Into<String>, behavior: MockBehavior) -> Self {
+        self.mocks.insert(name.into(), behavior);
+        self
+    }
+
+    /// Calls made to mocked effects, in order
+    pub fn calls(&self) -> &[RecordedCall] {
+        &self.calls
+    }
+
+    fn count(&self, name: &str) -> usize {
+        self.calls.iter().filter(|call| call.name == name).count()
+    }
+
+    /// Next number of the generator (SplitMix64)
+    fn draw(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn controlled(&mut self, name: &str, args: &[Value]) -> Result<Value, String> {
+        match (name, args) {
+            ("time.now" | "time.nowMillis", []) => Ok(Value::U64(self.clock)),
+            ("time.sleep", [seconds]) => {
+                self.clock = self
+                    .clock
+                    .saturating_add(unsigned(seconds)?.saturating_mul(1000));
+                Ok(Value::Unit)
+            }
+            ("time.sleepMillis", [ms]) => {
+                self.clock = self.clock.saturating_add(unsigned(ms)?);
+                Ok(Value::Unit)
+            }
+            ("rand.u32", []) => Ok(Value::U32(self.draw() as u32)),
+            ("rand.u64", []) => Ok(Value::U64(self.draw())),
+            ("rand.range", [min, max]) => {
+                let (low, high) = (unsigned(min)?, unsigned(max)?);
+                if low >= high {
+                    return Err(format!("empty range {min}..{max}"));
+                }
+                let n = low + self.draw() % (high - low);
+                // The result has the type of the bounds
+                Ok(match min {
+                    Value::U16(_) => Value::U16(n as u16),
+                    Value::U32(_) => Value::U32(n as u32),
+                    Value::U64(_) => Value::U64(n),
+                    _ => Value::Int(n as i64),
+                })
+            }
+            _ => Err(format!("invalid arguments ({})", describe(args))),
+        }
+    }
+
+    fn builtin(&mut self, name: &str, args: &[Value]) -> Result<Value, String> {
+        match (name, args) {
+            ("assert", [Value::Bool(true)]) | ("assert", [Value::Bool(true), Value::Str(_)]) => {
+                Ok(Value::Unit)
+            }
+            ("assert", [Value::Bool(false)]) => Err("expected true".to_string()),
+            ("assert", [Value::Bool(false), Value::Str(message)]) => Err(message.clone()),
+            ("assert_eq", [left, right]) if left.equals(right) => Ok(Value::Unit),
+            ("assert_eq", [left, right]) => Err(crate::diff::explain_mismatch(left, right)),
+            ("assert_ne", [left, right]) if !left.equals(right) => Ok(Value::Unit),
+            ("assert_ne", [left, right]) => Err(format!("{left} == {right}")),
+            ("advance", [ms]) => {
+                self.clock = self.clock.saturating_add(unsigned(ms)?);
+                Ok(Value::Unit)
+            }
+            ("calls", [Value::Str(effect)]) => Ok(Value::U32(self.count(effect) as u32)),
+            ("called_with", [Value::Str(effect), expected @ ..]) => {
+                Ok(Value::Bool(self.calls.iter().any(|call| {
+                    call.name == *effect
+                        && call.args.len() == expected.len()
+                        && call.args.iter().zip(expected).all(|(a, b)| a.equals(b))
+                })))
+            }
+            _ => Err(format!("invalid arguments ({})", describe(args))),
+        }
+    }
+}
+
+impl Host for TestHost {
+    fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, EvalError> {
+        if BUILTINS.contains(&name) {
+            return self
+                .builtin(name, &args)
+                .map_err(|message| EvalError::Host {
+                    name: name.to_string(),
+                    message,
+                });
+        }
+        let Some(behavior) = self.mocks.get(name) else {
+            if !CONTROLLED.contains(&name) {
+                return Err(EvalError::UnhandledCall(name.to_string()));
+            }
+            let result = self
+                .controlled(name, &args)
+                .map_err(|message| EvalError::Host {
+                    name: name.to_string(),
+                    message,
+                });
+            self.calls.push(RecordedCall {
+                name: name.to_string(),
+                args,
+            });
+            return result;
+        };
+        let nth = self.count(name);
+        let result = match behavior {
+            MockBehavior::Returns(values) => Ok(values
+                .get(nth)
+                .or(values.last())
+                .cloned()
+                .unwrap_or(Value::Unit)),
+            MockBehavior::Fails(message) => Err(EvalError::Host {
+                name: name.to_string(),
+                message: message.clone(),
+            }),
+        };
+        self.calls.push(RecordedCall {
+            name: name.to_string(),
+            args,
+        });
+        result
+    }
+}
+
+/// A duration or bound, from any unsigned integer value
+fn unsigned(value: &Value) -> Result<u64, String> {
+    match value {
+        Value::U16(n) => Ok(u64::from(*n)),
+        Value::U32(n) => Ok(u64::from(*n)),
+        Value::U64(n) => Ok(*n),
+        Value::Int(n) if *n >= 0 => Ok(*n as u64),
+        other => Err(format!("expected an unsigned integer, got {other}")),
+    }
+}
+
+fn describe(args: &[Value]) -> String {
+    args.iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mocks_return_in_sequence_and_record_calls() {
+        let mut host = TestHost::new()
+            .mock(
+                "time.now",
+                MockBehavior::Returns(vec![Value::Int(1), Value::Int(2)]),
+            )
+            .mock("fs.write", MockBehavior::Fails("disk full".to_string()));
+
+        let now = |host: &mut TestHost| host.call("time.now", vec![]).unwrap();
+        assert_eq!(now(&mut host), Value::Int(1));
+        assert_eq!(now(&mut host), Value::Int(2));
+        assert_eq!(now(&mut host), Value::Int(2));
+
+        let path = Value::Str("a.txt".to_string());
+        assert_eq!(
+            host.call("fs.write", vec![path.clone()]),
+            Err(EvalError::Host {
+                name: "fs.write".to_string(),
+                message: "disk full".to_string()
+            })
+        );
+        assert_eq!(
+            host.call("http.get", vec![]),
+            Err(EvalError::UnhandledCall("http.get".to_string()))
+        );
+
+        let count = host.call("calls", vec![Value::Str("time.now".into())]);
+        assert_eq!(count, Ok(Value::U32(3)));
+        let called = host.call("called_with", vec![Value::Str("fs.write".into()), path]);
+        assert_eq!(called, Ok(Value::Bool(true)));
+        assert_eq!(host.calls().len(), 4);
+    }
+
+    #[test]
+    fn clock_and_generator_are_controlled() {
+        let mut host = TestHost::new().seed(7);
+        assert_eq!(host.call("time.now", vec![]), Ok(Value::U64(CLOCK_START)));
+        host.call("time.sleep", vec![Value::U32(2)]).unwrap();
+        host.call("advance", vec![Value::Int(5)]).unwrap();
+        assert_eq!(
+            host.call("time.now", vec![]),
+            Ok(Value::U64(CLOCK_START + 2005))
+        );
+
+        let draws = |host: &mut TestHost| -> Vec<Value> {
+            (0..3)
+                .map(|_| host.call("rand.range", vec![Value::U32(1), Value::U32(7)]))
+                .collect::<Result<_, _>>()
+                .unwrap()
+        };
+        let first = draws(&mut host);
+        assert_eq!(first, draws(&mut TestHost::new().seed(7)));
+        assert_ne!(first, draws(&mut TestHost::new().seed(8)));
+        assert!(first
+            .iter()
+            .all(|value| matches!(value, Value::U32(n) if (1..7).contains(n))));
+        assert!(host
+            .call("rand.range", vec![Value::U32(3), Value::U32(3)])
+            .is_err());
+
+        // Mocks win over the clock
+        let mut host = TestHost::new().mock("time.now", MockBehavior::Returns(vec![Value::U64(1)]));
+        assert_eq!(host.call("time.now", vec![]), Ok(Value::U64(1)));
+    }
+
+    #[test]
+    fn assertions_report_their_operands() {
+        let mut host = TestHost::new();
+        assert_eq!(
+            host.call("assert_eq", vec![Value::U32(3), Value::Int(3)]),
+            Ok(Value::Unit)
+        );
+        let Err(EvalError::Host { message, .. }) =
+            host.call("assert_eq", vec![Value::U32(3), Value::Int(4)])
+        else {
+            panic!("assert_eq should fail");
+        };
+        assert_eq!(message, "3 != 4");
+        assert!(host.call("assert", vec![Value::Bool(false)]).is_err());
+        assert!(host.call("assert", vec![Value::Int(1)]).is_err());
+    }
+}
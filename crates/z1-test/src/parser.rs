@@ -20,11 +20,30 @@ pub enum ParseError {
 pub struct Parser {
     tokens: Vec<TestToken>,
     pos: usize,
+    /// Source the tokens were lexed from; blocks keep their exact text when set
+    source: Option<String>,
+    /// Attributes from `#[...]` markers, for the test item that follows them
+    markers: Option<TestAttrs>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<TestToken>) -> Self {
-        Self { tokens, pos: 0 }
+        Self {
+            tokens,
+            pos: 0,
+            source: None,
+            markers: None,
+        }
+    }
+
+    /// Parser over `source`, keeping the exact text of blocks and values
+    pub fn with_source(source: &str) -> Self {
+        Self {
+            tokens: lex_test(source),
+            pos: 0,
+            source: Some(source.to_string()),
+            markers: None,
+        }
     }
 
     fn current(&self) -> &TestToken {
@@ -77,7 +96,21 @@ impl Parser {
 
         // Parse items
         while self.peek() != TestTokenKind::Eof {
+            if self.markers.is_some()
+                && !matches!(
+                    self.peek(),
+                    TestTokenKind::Hash
+                        | TestTokenKind::KwSpec
+                        | TestTokenKind::KwProp
+                        | TestTokenKind::KwSnapshot
+                )
+            {
+                return Err(misplaced_markers());
+            }
             match self.peek() {
+                TestTokenKind::Hash => {
+                    self.parse_marker()?;
+                }
                 TestTokenKind::KwSpec => {
                     test_file.specs.push(self.parse_spec()?);
                 }
@@ -87,6 +120,23 @@ impl Parser {
                 TestTokenKind::KwFixture => {
                     test_file.fixtures.push(self.parse_fixture()?);
                 }
+                TestTokenKind::KwSnapshot => {
+                    test_file.snapshots.push(self.parse_snapshot()?);
+                }
+                TestTokenKind::KwUse => {
+                    test_file.uses.push(self.parse_use()?);
+                }
+                TestTokenKind::KwMock => {
+                    test_file.mocks.push(self.parse_mock()?);
+                }
+                TestTokenKind::KwBefore => {
+                    self.advance();
+                    test_file.before.push(self.parse_block()?);
+                }
+                TestTokenKind::KwAfter => {
+                    self.advance();
+                    test_file.after.push(self.parse_block()?);
+                }
                 _ => {
                     return Err(ParseError::InvalidSyntax {
                         message: format!("Unexpected token: {:?}", self.peek()),
@@ -95,6 +145,10 @@ impl Parser {
             }
         }
 
+        if self.markers.is_some() {
+            return Err(misplaced_markers());
+        }
+
         let end_span = self.current().span;
         test_file.span = Span::new(start_span.start, end_span.end);
 
@@ -130,6 +184,18 @@ impl Parser {
                     let value = self.expect(TestTokenKind::Number)?;
                     config.timeout_ms = Some(value.lexeme.parse().unwrap_or(0));
                 }
+                "file_timeout_ms" => {
+                    let value = self.expect(TestTokenKind::Number)?;
+                    config.file_timeout_ms = Some(value.lexeme.parse().unwrap_or(0));
+                }
+                "max_steps" => {
+                    let value = self.expect(TestTokenKind::Number)?;
+                    config.max_steps = Some(value.lexeme.parse().unwrap_or(0));
+                }
+                "max_memory" => {
+                    let value = self.expect(TestTokenKind::Number)?;
+                    config.max_memory = Some(value.lexeme.parse().unwrap_or(0));
+                }
                 "parallel" => {
                     let value = self.expect(TestTokenKind::Number)?;
                     config.parallel = Some(value.lexeme.parse().unwrap_or(1));
@@ -237,7 +303,7 @@ impl Parser {
         let end = self.current().span;
 
         Ok(GenBinding {
-            name: name.lexeme,
+            name: name.lexeme.into(),
             ty,
             span: Span::new(start.start, end.end),
         })
@@ -246,7 +312,7 @@ impl Parser {
     fn parse_type_expr(&mut self) -> Result<TypeExpr, ParseError> {
         // Simplified type parser - just handles identifiers for MVP
         let token = self.expect(TestTokenKind::Ident)?;
-        Ok(TypeExpr::Path(vec![token.lexeme]))
+        Ok(TypeExpr::Path(vec![token.lexeme.into()]))
     }
 
     fn parse_fixture(&mut self) -> Result<Fixture, ParseError> {
@@ -268,15 +334,219 @@ impl Parser {
         let end = self.current().span;
 
         Ok(Fixture {
-            name: name.lexeme,
+            name: name.lexeme.into(),
             ty,
             body,
             span: Span::new(start.start, end.end),
         })
     }
 
+    fn parse_snapshot(&mut self) -> Result<Snapshot, ParseError> {
+        let start = self.current().span;
+        self.expect(TestTokenKind::KwSnapshot)?;
+
+        let name_token = self.expect(TestTokenKind::String)?;
+        let name = name_token.lexeme.trim_matches('"').to_string();
+
+        let attrs = self.parse_attrs()?;
+        self.expect(TestTokenKind::LBrace)?;
+
+        let mut cell = None;
+        let mut emit = None;
+        while self.peek() != TestTokenKind::RBrace {
+            let key = self.expect(TestTokenKind::Ident)?;
+            self.expect(TestTokenKind::Colon)?;
+
+            match key.lexeme.as_str() {
+                "cell" => {
+                    let value = self.expect(TestTokenKind::String)?;
+                    cell = Some(value.lexeme.trim_matches('"').to_string());
+                }
+                "emit" => {
+                    let value = self.expect(TestTokenKind::Ident)?;
+                    emit = Some(SnapshotKind::parse(&value.lexeme).ok_or_else(|| {
+                        ParseError::InvalidSyntax {
+                            message: format!(
+                                "Unknown snapshot kind: {} (expected fmt, relaxed, ir or ts)",
+                                value.lexeme
+                            ),
+                        }
+                    })?);
+                }
+                _ => {
+                    return Err(ParseError::InvalidSyntax {
+                        message: format!("Unknown snapshot key: {}", key.lexeme),
+                    });
+                }
+            }
+
+            self.match_token(TestTokenKind::Comma);
+        }
+        self.expect(TestTokenKind::RBrace)?;
+
+        let missing = |key: &str| ParseError::InvalidSyntax {
+            message: format!("Snapshot \"{name}\" is missing `{key}`"),
+        };
+        let end = self.current().span;
+
+        Ok(Snapshot {
+            cell: cell.ok_or_else(|| missing("cell"))?,
+            emit: emit.ok_or_else(|| missing("emit"))?,
+            name,
+            attrs,
+            span: Span::new(start.start, end.end),
+        })
+    }
+
+    fn parse_use(&mut self) -> Result<CellUse, ParseError> {
+        let start = self.current().span;
+        self.expect(TestTokenKind::KwUse)?;
+        let path = self.expect(TestTokenKind::String)?;
+        let end = self.expect(TestTokenKind::Semi)?.span;
+
+        Ok(CellUse {
+            path: path.lexeme.trim_matches('"').to_string(),
+            span: Span::new(start.start, end.end),
+        })
+    }
+
+    fn parse_mock(&mut self) -> Result<Mock, ParseError> {
+        let start = self.current().span;
+        self.expect(TestTokenKind::KwMock)?;
+
+        let name_token = self.expect(TestTokenKind::String)?;
+        let name = name_token.lexeme.trim_matches('"').to_string();
+
+        let response = match self.current().lexeme.as_str() {
+            "returns" => {
+                self.advance();
+                let mut values = Vec::new();
+                if self.match_token(TestTokenKind::LBracket) {
+                    while self.peek() != TestTokenKind::RBracket {
+                        values.push(
+                            self.parse_value(&[TestTokenKind::Comma, TestTokenKind::RBracket])?,
+                        );
+                        if !self.match_token(TestTokenKind::Comma) {
+                            break;
+                        }
+                    }
+                    self.expect(TestTokenKind::RBracket)?;
+                    if values.is_empty() {
+                        return Err(ParseError::InvalidSyntax {
+                            message: format!("Mock \"{name}\" returns no values"),
+                        });
+                    }
+                } else {
+                    values.push(self.parse_value(&[TestTokenKind::Semi])?);
+                }
+                MockResponse::Returns(values)
+            }
+            "fails" => {
+                self.advance();
+                let message = self.expect(TestTokenKind::String)?;
+                MockResponse::Fails(message.lexeme.trim_matches('"').to_string())
+            }
+            _ => MockResponse::Unit,
+        };
+        let end = self.expect(TestTokenKind::Semi)?.span;
+
+        Ok(Mock {
+            name,
+            response,
+            span: Span::new(start.start, end.end),
+        })
+    }
+
+    /// Source text of an expression, up to one of `stops` outside brackets
+    fn parse_value(&mut self, stops: &[TestTokenKind]) -> Result<String, ParseError> {
+        let first = self.pos;
+        let mut depth = 0usize;
+        loop {
+            let kind = self.peek();
+            if kind == TestTokenKind::Eof {
+                return Err(ParseError::UnexpectedEof);
+            }
+            if depth == 0 && stops.contains(&kind) {
+                break;
+            }
+            match kind {
+                TestTokenKind::LParen | TestTokenKind::LBrace | TestTokenKind::LBracket => {
+                    depth += 1
+                }
+                TestTokenKind::RParen | TestTokenKind::RBrace | TestTokenKind::RBracket => {
+                    depth = depth.saturating_sub(1)
+                }
+                _ => {}
+            }
+            self.advance();
+        }
+        if self.pos == first {
+            return Err(ParseError::UnexpectedToken {
+                expected: "expression".to_string(),
+                got: format!("{:?}", self.peek()),
+                pos: self.current().span.start,
+            });
+        }
+        Ok(self.text(first, self.pos))
+    }
+
+    /// Text of the tokens `first..end`: the source slice when the source is
+    /// known, the lexemes joined by spaces otherwise
+    fn text(&self, first: usize, end: usize) -> String {
+        if first >= end {
+            return String::new();
+        }
+        match &self.source {
+            Some(source) => {
+                let start = self.tokens[first].span.start as usize;
+                let end = self.tokens[end - 1].span.end as usize;
+                source[start..end].to_string()
+            }
+            None => self.tokens[first..end]
+                .iter()
+                .map(|token| token.lexeme.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+
+    /// `#[skip]`, `#[skip("reason")]`, `#[xfail]` or `#[xfail("reason")]`,
+    /// applied to the next test item
+    fn parse_marker(&mut self) -> Result<(), ParseError> {
+        self.expect(TestTokenKind::Hash)?;
+        self.expect(TestTokenKind::LBracket)?;
+        let name = self.current().clone();
+        self.advance();
+        let reason = if self.match_token(TestTokenKind::LParen) {
+            let reason = self.expect(TestTokenKind::String)?;
+            self.expect(TestTokenKind::RParen)?;
+            Some(reason.lexeme.trim_matches('"').to_string())
+        } else {
+            None
+        };
+        self.expect(TestTokenKind::RBracket)?;
+
+        let markers = self.markers.get_or_insert_with(TestAttrs::default);
+        match name.lexeme.as_str() {
+            "skip" => markers.skip = true,
+            "xfail" => markers.xfail = true,
+            _ => {
+                return Err(ParseError::InvalidSyntax {
+                    message: format!(
+                        "Unknown marker: #[{}] (expected skip or xfail)",
+                        name.lexeme
+                    ),
+                })
+            }
+        }
+        if reason.is_some() {
+            markers.reason = reason;
+        }
+        Ok(())
+    }
+
     fn parse_attrs(&mut self) -> Result<TestAttrs, ParseError> {
-        let mut attrs = TestAttrs::default();
+        let mut attrs = self.markers.take().unwrap_or_default();
 
         if !self.match_token(TestTokenKind::KwWith) {
             return Ok(attrs);
@@ -346,28 +616,18 @@ impl Parser {
         let start = self.current().span;
         self.expect(TestTokenKind::LBrace)?;
 
-        let mut content = String::new();
+        let first = self.pos;
+        let mut last = self.pos;
         let mut depth = 1;
 
         while depth > 0 && self.peek() != TestTokenKind::Eof {
-            let token = self.current();
-            match token.kind {
-                TestTokenKind::LBrace => {
-                    depth += 1;
-                    content.push_str(&token.lexeme);
-                    content.push(' ');
-                }
-                TestTokenKind::RBrace => {
-                    depth -= 1;
-                    if depth > 0 {
-                        content.push_str(&token.lexeme);
-                        content.push(' ');
-                    }
-                }
-                _ => {
-                    content.push_str(&token.lexeme);
-                    content.push(' ');
-                }
+            match self.peek() {
+                TestTokenKind::LBrace => depth += 1,
+                TestTokenKind::RBrace => depth -= 1,
+                _ => {}
+            }
+            if depth > 0 {
+                last = self.pos + 1;
             }
             self.advance();
         }
@@ -375,17 +635,21 @@ impl Parser {
         let end = self.current().span;
 
         Ok(Block {
-            raw: content.trim().to_string(),
+            raw: self.text(first, last).trim().to_string(),
             statements: Vec::new(),
             span: Span::new(start.start, end.end),
         })
     }
 }
 
+fn misplaced_markers() -> ParseError {
+    ParseError::InvalidSyntax {
+        message: "`#[...]` markers must precede a spec, prop or snapshot".to_string(),
+    }
+}
+
 pub fn parse_test_file(source: &str) -> Result<TestFile, ParseError> {
-    let tokens = lex_test(source);
-    let mut parser = Parser::new(tokens);
-    parser.parse_test_file()
+    Parser::with_source(source).parse_test_file()
 }
 
 #[cfg(test)]
@@ -428,6 +692,12 @@ mod tests {
         assert!(result.is_ok());
         let file = result.unwrap();
         assert_eq!(file.config.timeout_ms, Some(3000));
+
+        let input = "config { file_timeout_ms: 60000, max_steps: 100000, max_memory: 1048576 }";
+        let config = parse_test_file(input).unwrap().config;
+        assert_eq!(config.file_timeout_ms, Some(60000));
+        assert_eq!(config.max_steps, Some(100000));
+        assert_eq!(config.max_memory, Some(1048576));
     }
 
     #[test]
@@ -483,6 +753,85 @@ mod tests {
         assert_eq!(file.props.len(), 1);
     }
 
+    #[test]
+    fn parse_snapshot() {
+        let input = r#"
+            snapshot "server ts" with { tags: ["codegen"] } { cell: "cells/server.z1c", emit: ts }
+        "#;
+        let file = parse_test_file(input).unwrap();
+        assert_eq!(file.snapshots.len(), 1);
+        let snapshot = &file.snapshots[0];
+        assert_eq!(snapshot.name, "server ts");
+        assert_eq!(snapshot.cell, "cells/server.z1c");
+        assert_eq!(snapshot.emit, SnapshotKind::Ts);
+        assert_eq!(snapshot.attrs.tags, ["codegen"]);
+
+        assert!(parse_test_file(r#"snapshot "x" { cell: "a.z1c", emit: wat }"#).is_err());
+        assert!(parse_test_file(r#"snapshot "x" { emit: ir }"#).is_err());
+    }
+
+    #[test]
+    fn parse_uses_and_mocks() {
+        let input = r#"
+            use "cells/fetch.z1c";
+            mock "log.info";
+            mock "http.get" returns Res { status: 200, body: "ok" };
+            mock "time.now" returns [100, 250];
+            mock "fs.write" fails "disk full";
+            spec "keeps source" { assert_eq(now() - 100, 150); }
+        "#;
+        let file = parse_test_file(input).unwrap();
+        assert_eq!(file.uses[0].path, "cells/fetch.z1c");
+        let responses: Vec<_> = file.mocks.iter().map(|m| &m.response).collect();
+        assert_eq!(
+            responses,
+            [
+                &MockResponse::Unit,
+                &MockResponse::Returns(vec!["Res { status: 200, body: \"ok\" }".into()]),
+                &MockResponse::Returns(vec!["100".into(), "250".into()]),
+                &MockResponse::Fails("disk full".into()),
+            ]
+        );
+        assert_eq!(file.specs[0].body.raw, "assert_eq(now() - 100, 150);");
+
+        assert!(parse_test_file(r#"mock "t" returns [];"#).is_err());
+
+        let file = parse_test_file("before { let x = 1; } after { reset(); } before { }").unwrap();
+        assert_eq!(file.before.len(), 2);
+        assert_eq!(file.before[0].raw, "let x = 1;");
+        assert_eq!(file.after[0].raw, "reset();");
+        assert!(parse_test_file(r#"mock "t" returns ;"#).is_err());
+    }
+
+    #[test]
+    fn parse_markers() {
+        let input = r#"
+            #[xfail("division by zero is not reported yet")]
+            spec "divides" with { tags: ["math"] } { }
+            #[skip]
+            prop "commutes" for_all (x: U32) { }
+            #[skip("slow")]
+            snapshot "server" { cell: "server.z1c", emit: ts }
+            spec "plain" { }
+        "#;
+        let file = parse_test_file(input).unwrap();
+        let attrs = &file.specs[0].attrs;
+        assert!(attrs.xfail && !attrs.skip);
+        assert_eq!(
+            attrs.reason.as_deref(),
+            Some("division by zero is not reported yet")
+        );
+        assert_eq!(attrs.tags, ["math"]);
+        assert!(file.props[0].attrs.skip);
+        assert_eq!(file.props[0].attrs.reason, None);
+        assert_eq!(file.snapshots[0].attrs.reason.as_deref(), Some("slow"));
+        assert_eq!(file.specs[1].attrs, TestAttrs::default());
+
+        assert!(parse_test_file(r#"#[flaky] spec "x" { }"#).is_err());
+        assert!(parse_test_file(r#"#[skip] fixture x = { 1 };"#).is_err());
+        assert!(parse_test_file("#[skip]").is_err());
+    }
+
     #[test]
     fn reject_invalid_syntax() {
         let input = "invalid syntax here";
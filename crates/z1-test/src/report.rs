@@ -0,0 +1,627 @@
+//! Reporters printing the results of a test run.
+//!
+//! A run is reported once every file has run, by one of:
+//!
+//! - [`PrettyReporter`]: human-readable text, with totals and failures
+//! - [`JsonReporter`]: a single JSON document with per-test outcomes
+//! - [`JunitReporter`]: JUnit XML, for CI test dashboards
+//!
+//! Each test is reported with its duration and the line and column it is
+//! declared at, so failures can be traced back to the `.z1t` file. Tests
+//! marked `xfail` that fail are reported apart from failures; those that pass
+//! are reported as unexpected successes and fail the run. Tests that ran out
+//! of time or steps fail as timeouts, reported as such. Runs with failures
+//! name the seed of their `rand` effects, to reproduce them with `--seed`.
+//! Failed comparisons of records list the fields that differ, which the
+//! pretty reporter can color.
+
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use serde_json::json;
+
+use crate::runner::{TestCase, TestResult, TestResults};
+
+/// Results of one test file.
+#[derive(Debug, Clone)]
+pub struct FileReport {
+    /// Path of the test file, as shown to the user
+    pub path: String,
+    /// Source of the test file, to locate tests by line
+    pub source: String,
+    pub results: TestResults,
+}
+
+impl FileReport {
+    /// One-based line and column of the declaration of `case`.
+    pub fn location(&self, case: &TestCase) -> (usize, usize) {
+        let offset = (case.span.start as usize).min(self.source.len());
+        let before = &self.source[..offset];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |idx| idx + 1) + 1;
+        (line, column)
+    }
+}
+
+/// Prints the results of a test run.
+pub trait Reporter {
+    fn report(&self, files: &[FileReport], out: &mut dyn Write) -> io::Result<()>;
+}
+
+/// Human-readable text output.
+#[derive(Debug, Clone, Copy)]
+pub struct PrettyReporter {
+    /// Print every test with its outcome and duration, not only failures
+    pub verbose: bool,
+    /// Print the files, failures and updated snapshots
+    pub details: bool,
+    /// Print the totals
+    pub summary: bool,
+    /// Color the lines of value diffs: left values red, right values green
+    pub color: bool,
+}
+
+impl Default for PrettyReporter {
+    fn default() -> Self {
+        Self {
+            verbose: false,
+            details: true,
+            summary: true,
+            color: false,
+        }
+    }
+}
+
+impl Reporter for PrettyReporter {
+    fn report(&self, files: &[FileReport], out: &mut dyn Write) -> io::Result<()> {
+        let totals = Totals::of(files);
+        if self.details {
+            for file in files {
+                writeln!(out, "Running tests from: {}", file.path)?;
+                if self.verbose {
+                    for case in &file.results.cases {
+                        let outcome = match case.result {
+                            TestResult::Passed => "ok",
+                            TestResult::Failed(_) => "FAILED",
+                            TestResult::TimedOut(_) => "TIMEOUT",
+                            TestResult::Skipped => "skipped",
+                            TestResult::XFailed(_) => "xfail",
+                            TestResult::XPassed => "XPASS",
+                        };
+                        write!(
+                            out,
+                            "  {outcome:<7} {} {} ({})",
+                            case.kind.as_str(),
+                            case.name,
+                            millis_text(case.duration)
+                        )?;
+                        match &case.reason {
+                            Some(reason) => writeln!(out, ": {reason}")?,
+                            None => writeln!(out)?,
+                        }
+                    }
+                }
+                for golden in &file.results.snapshots_updated {
+                    writeln!(out, "  Updated snapshot {}", display_path(golden))?;
+                }
+            }
+        }
+
+        if self.summary {
+            writeln!(out, "\nTest Results:")?;
+            writeln!(out, "  Passed:  {}", totals.passed)?;
+            writeln!(out, "  Failed:  {}", totals.failed)?;
+            if totals.timed_out > 0 {
+                writeln!(out, "  Timed out: {}", totals.timed_out)?;
+            }
+            writeln!(out, "  Skipped: {}", totals.skipped)?;
+            if totals.xfailed > 0 {
+                writeln!(out, "  Expected failures: {}", totals.xfailed)?;
+            }
+            if totals.updated > 0 {
+                writeln!(out, "  Snapshots updated: {}", totals.updated)?;
+            }
+            writeln!(out, "  Time:    {}", millis_text(totals.duration))?;
+        }
+
+        if self.details && totals.failed > 0 {
+            writeln!(out, "\nFailures:")?;
+            for file in files {
+                for case in &file.results.cases {
+                    if let Some(error) = case.failure() {
+                        let (line, column) = file.location(case);
+                        let mut lines = error.lines();
+                        writeln!(
+                            out,
+                            "  - {} ({}:{line}:{column}): {}",
+                            case.name,
+                            file.path,
+                            lines.next().unwrap_or_default()
+                        )?;
+                        for line in lines {
+                            writeln!(out, "    {}", self.paint(line))?;
+                        }
+                    }
+                }
+            }
+            let mut seeds: Vec<u64> = files
+                .iter()
+                .filter(|file| file.results.failed > 0)
+                .map(|file| file.results.seed)
+                .collect();
+            seeds.sort_unstable();
+            seeds.dedup();
+            for seed in seeds {
+                writeln!(out, "\nSeed: {seed} (reproduce with --seed {seed})")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PrettyReporter {
+    /// `line` of a failure message, colored when it holds a side of a diff.
+    fn paint(&self, line: &str) -> String {
+        let code = match line.trim_start().get(..2) {
+            Some("- ") => "31",
+            Some("+ ") => "32",
+            _ => return line.to_string(),
+        };
+        if self.color {
+            format!("\x1b[{code}m{line}\x1b[0m")
+        } else {
+            line.to_string()
+        }
+    }
+}
+
+/// A single JSON document: the totals, the failures, and every test by file.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonReporter;
+
+impl Reporter for JsonReporter {
+    fn report(&self, files: &[FileReport], out: &mut dyn Write) -> io::Result<()> {
+        let totals = Totals::of(files);
+        let mut failures = Vec::new();
+        let mut updated = Vec::new();
+        let mut file_docs = Vec::new();
+        for file in files {
+            let mut tests = Vec::new();
+            for case in &file.results.cases {
+                let (line, column) = file.location(case);
+                let mut test = json!({
+                    "name": case.name,
+                    "kind": case.kind.as_str(),
+                    "status": status(&case.result),
+                    "duration_ms": millis(case.duration),
+                    "line": line,
+                    "column": column,
+                });
+                if let Some(reason) = &case.reason {
+                    test["reason"] = json!(reason);
+                }
+                if let TestResult::XFailed(error) = &case.result {
+                    test["error"] = json!(error);
+                }
+                if let Some(error) = case.failure() {
+                    test["error"] = json!(error);
+                    failures.push(json!({
+                        "name": case.name,
+                        "error": error,
+                        "file": file.path,
+                        "line": line,
+                        "column": column,
+                    }));
+                }
+                tests.push(test);
+            }
+            updated.extend(
+                file.results
+                    .snapshots_updated
+                    .iter()
+                    .map(|p| display_path(p)),
+            );
+            file_docs.push(json!({
+                "path": file.path,
+                "passed": file.results.passed,
+                "failed": file.results.failed,
+                "skipped": file.results.skipped,
+                "xfailed": file.results.xfailed,
+                "timed_out": file.results.timed_out,
+                "seed": file.results.seed,
+                "tests": tests,
+            }));
+        }
+
+        let mut doc = json!({
+            "passed": totals.passed,
+            "failed": totals.failed,
+            "skipped": totals.skipped,
+            "xfailed": totals.xfailed,
+            "timed_out": totals.timed_out,
+            "duration_ms": millis(totals.duration),
+            "failures": failures,
+            "files": file_docs,
+        });
+        if !updated.is_empty() {
+            doc["snapshots_updated"] = json!(updated);
+        }
+        let text = serde_json::to_string_pretty(&doc).map_err(io::Error::other)?;
+        writeln!(out, "{text}")
+    }
+}
+
+/// JUnit XML: one `<testsuite>` per file and one `<testcase>` per test.
+///
+/// JUnit has no expected failures: they are reported as skipped tests.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JunitReporter;
+
+impl Reporter for JunitReporter {
+    fn report(&self, files: &[FileReport], out: &mut dyn Write) -> io::Result<()> {
+        let totals = Totals::of(files);
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            out,
+            r#"<testsuites name="z1 test" tests="{}" failures="{}" skipped="{}" time="{}">"#,
+            totals.tests(),
+            totals.failed,
+            totals.skipped + totals.xfailed,
+            seconds(totals.duration)
+        )?;
+        for file in files {
+            let results = &file.results;
+            let path = escape_xml(&file.path);
+            writeln!(
+                out,
+                r#"  <testsuite name="{path}" tests="{}" failures="{}" skipped="{}" time="{}">"#,
+                results.cases.len(),
+                results.failed,
+                results.skipped + results.xfailed,
+                seconds(results.cases.iter().map(|case| case.duration).sum())
+            )?;
+            writeln!(out, "    <properties>")?;
+            writeln!(
+                out,
+                r#"      <property name="seed" value="{}"/>"#,
+                results.seed
+            )?;
+            writeln!(out, "    </properties>")?;
+            for case in &results.cases {
+                let (line, _) = file.location(case);
+                let open = format!(
+                    r#"    <testcase name="{}" classname="{path}" file="{path}" line="{line}" time="{}""#,
+                    escape_xml(&case.name),
+                    seconds(case.duration)
+                );
+                let reason = case.reason.as_deref().unwrap_or_default();
+                match &case.result {
+                    TestResult::Passed => writeln!(out, "{open}/>")?,
+                    TestResult::Skipped if reason.is_empty() => {
+                        writeln!(out, "{open}>")?;
+                        writeln!(out, "      <skipped/>")?;
+                        writeln!(out, "    </testcase>")?;
+                    }
+                    TestResult::Skipped => {
+                        writeln!(out, "{open}>")?;
+                        writeln!(out, r#"      <skipped message="{}"/>"#, escape_xml(reason))?;
+                        writeln!(out, "    </testcase>")?;
+                    }
+                    TestResult::XFailed(error) => {
+                        let message = if reason.is_empty() {
+                            "expected failure".to_string()
+                        } else {
+                            format!("expected failure: {reason}")
+                        };
+                        writeln!(out, "{open}>")?;
+                        writeln!(
+                            out,
+                            r#"      <skipped message="{}">{}</skipped>"#,
+                            escape_xml(&message),
+                            escape_xml(error)
+                        )?;
+                        writeln!(out, "    </testcase>")?;
+                    }
+                    TestResult::Failed(_) | TestResult::TimedOut(_) | TestResult::XPassed => {
+                        let error = case.failure().unwrap_or_default();
+                        let message = error.lines().next().unwrap_or_default();
+                        let kind = match case.result {
+                            TestResult::XPassed => "unexpected success",
+                            TestResult::TimedOut(_) => "timeout",
+                            _ => case.kind.as_str(),
+                        };
+                        writeln!(out, "{open}>")?;
+                        writeln!(
+                            out,
+                            r#"      <failure message="{}" type="{kind}">{}</failure>"#,
+                            escape_xml(message),
+                            escape_xml(&error)
+                        )?;
+                        writeln!(out, "    </testcase>")?;
+                    }
+                }
+            }
+            writeln!(out, "  </testsuite>")?;
+        }
+        writeln!(out, "</testsuites>")
+    }
+}
+
+/// Totals across the files of a run.
+struct Totals {
+    passed: usize,
+    failed: usize,
+    skipped: usize,
+    xfailed: usize,
+    timed_out: usize,
+    updated: usize,
+    duration: Duration,
+}
+
+impl Totals {
+    fn of(files: &[FileReport]) -> Self {
+        let mut totals = Totals {
+            passed: 0,
+            failed: 0,
+            skipped: 0,
+            xfailed: 0,
+            timed_out: 0,
+            updated: 0,
+            duration: Duration::ZERO,
+        };
+        for file in files {
+            totals.passed += file.results.passed;
+            totals.failed += file.results.failed;
+            totals.skipped += file.results.skipped;
+            totals.xfailed += file.results.xfailed;
+            totals.timed_out += file.results.timed_out;
+            totals.updated += file.results.snapshots_updated.len();
+            totals.duration += file.results.cases.iter().map(|case| case.duration).sum();
+        }
+        totals
+    }
+
+    fn tests(&self) -> usize {
+        self.passed + self.failed + self.skipped + self.xfailed
+    }
+}
+
+fn status(result: &TestResult) -> &'static str {
+    match result {
+        TestResult::Passed => "passed",
+        TestResult::Failed(_) => "failed",
+        TestResult::TimedOut(_) => "timeout",
+        TestResult::Skipped => "skipped",
+        TestResult::XFailed(_) => "xfailed",
+        TestResult::XPassed => "xpassed",
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    (duration.as_secs_f64() * 1_000_000.0).round() / 1000.0
+}
+
+fn millis_text(duration: Duration) -> String {
+    format!("{:.2}ms", duration.as_secs_f64() * 1000.0)
+}
+
+fn seconds(duration: Duration) -> String {
+    format!("{:.6}", duration.as_secs_f64())
+}
+
+fn display_path(path: &Path) -> String {
+    let path = path.strip_prefix(".").unwrap_or(path);
+    path.to_string_lossy().replace('\\', "/")
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_test_file;
+    use crate::runner::TestRunner;
+
+    fn report(source: &str) -> Vec<FileReport> {
+        let file = parse_test_file(source).unwrap();
+        let results = TestRunner::default().run_file_at(&file, Path::new("math.z1t"));
+        vec![FileReport {
+            path: "math.z1t".to_string(),
+            source: source.to_string(),
+            results,
+        }]
+    }
+
+    fn render(reporter: &dyn Reporter, files: &[FileReport]) -> String {
+        let mut out = Vec::new();
+        reporter.report(files, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    const SOURCE: &str = "spec \"adds\" { assert 1 + 1 == 2; }\n\
+                          spec \"skipped\" with { skip: true } { }\n\
+                          snapshot \"a < b\" { cell: \"missing.z1c\", emit: fmt }\n";
+
+    #[test]
+    fn json_lists_tests_with_locations() {
+        let files = report(SOURCE);
+        let doc: serde_json::Value = serde_json::from_str(&render(&JsonReporter, &files)).unwrap();
+        assert_eq!(doc["passed"], 1);
+        assert_eq!(doc["failed"], 1);
+        assert_eq!(doc["skipped"], 1);
+        let tests = doc["files"][0]["tests"].as_array().unwrap();
+        assert_eq!(tests.len(), 3);
+        assert_eq!(tests[1]["status"], "skipped");
+        assert_eq!(tests[2]["kind"], "snapshot");
+        assert_eq!(tests[2]["line"], 3);
+        assert_eq!(doc["failures"][0]["name"], "a < b");
+        assert_eq!(doc["failures"][0]["file"], "math.z1t");
+        assert!(doc.get("snapshots_updated").is_none());
+    }
+
+    #[test]
+    fn junit_escapes_and_marks_outcomes() {
+        let xml = render(&JunitReporter, &report(SOURCE));
+        assert!(xml.starts_with("<?xml"));
+        assert!(
+            xml.contains(r#"<testsuites name="z1 test" tests="3" failures="1" skipped="1""#),
+            "{xml}"
+        );
+        assert!(xml.contains(r#"<testcase name="adds" classname="math.z1t""#));
+        assert!(xml.contains("<skipped/>"));
+        assert!(xml.contains(r#"<testcase name="a &lt; b""#), "{xml}");
+        assert!(xml.contains(r#"line="3""#), "{xml}");
+        assert!(xml.contains(r#"<failure message="Failed to read"#), "{xml}");
+    }
+
+    #[test]
+    fn xfail_outcomes_are_reported_apart() {
+        let files = report(
+            "#[xfail(\"known bug\")]\nspec \"broken\" { assert false; }\n\
+             #[xfail]\nspec \"fixed\" { }\n",
+        );
+        let doc: serde_json::Value = serde_json::from_str(&render(&JsonReporter, &files)).unwrap();
+        assert_eq!(doc["xfailed"], 1);
+        assert_eq!(doc["failed"], 1);
+        let tests = doc["files"][0]["tests"].as_array().unwrap();
+        assert_eq!(tests[0]["status"], "xfailed");
+        assert_eq!(tests[0]["reason"], "known bug");
+        assert_eq!(tests[1]["status"], "xpassed");
+        assert_eq!(doc["failures"][0]["name"], "fixed");
+
+        let xml = render(&JunitReporter, &files);
+        assert!(xml.contains(r#"failures="1" skipped="1""#), "{xml}");
+        assert!(xml.contains(r#"<skipped message="expected failure: known bug">"#));
+        assert!(xml.contains(r#"type="unexpected success""#), "{xml}");
+
+        let reporter = PrettyReporter {
+            verbose: true,
+            ..PrettyReporter::default()
+        };
+        let text = render(&reporter, &files);
+        assert!(text.contains("  xfail   spec broken ("), "{text}");
+        assert!(text.contains("): known bug\n"), "{text}");
+        assert!(text.contains("  XPASS   spec fixed ("), "{text}");
+        assert!(text.contains("  Expected failures: 1"), "{text}");
+        assert!(
+            text.contains("  - fixed (math.z1t:4:1): Unexpected success"),
+            "{text}"
+        );
+    }
+
+    #[test]
+    fn pretty_lists_failures_with_locations() {
+        let reporter = PrettyReporter {
+            verbose: true,
+            ..PrettyReporter::default()
+        };
+        let text = render(&reporter, &report(SOURCE));
+        assert!(text.contains("  ok      spec adds ("), "{text}");
+        assert!(text.contains("  skipped spec skipped ("), "{text}");
+        assert!(text.contains("  Failed:  1"), "{text}");
+        assert!(
+            text.contains("  - a < b (math.z1t:3:1): Failed to read"),
+            "{text}"
+        );
+    }
+
+    #[test]
+    fn timeouts_are_reported_as_such() {
+        let files = report("spec \"spins\" with { timeout: 10 } { while true { } }\n");
+        let reporter = PrettyReporter {
+            verbose: true,
+            ..PrettyReporter::default()
+        };
+        let text = render(&reporter, &files);
+        assert!(text.contains("  TIMEOUT spec spins ("), "{text}");
+        assert!(text.contains("  Timed out: 1"), "{text}");
+        assert!(
+            text.contains("  - spins (math.z1t:1:1): Timed out: time limit of 10 ms exceeded"),
+            "{text}"
+        );
+
+        let doc: serde_json::Value = serde_json::from_str(&render(&JsonReporter, &files)).unwrap();
+        assert_eq!(
+            (doc["failed"].as_u64(), doc["timed_out"].as_u64()),
+            (Some(1), Some(1))
+        );
+        assert_eq!(doc["files"][0]["tests"][0]["status"], "timeout");
+        let xml = render(&JunitReporter, &files);
+        assert!(xml.contains(r#"type="timeout""#), "{xml}");
+    }
+
+    #[test]
+    fn record_failures_list_differing_fields() {
+        let files = report(
+            "spec \"res\" { assert_eq({ status: 200, body: \"ok\" }, { status: 404, body: \"ok\" }); }\n",
+        );
+        let text = render(&PrettyReporter::default(), &files);
+        assert!(
+            text.contains(
+                "  - res (math.z1t:1:1): Assertion failed: assert_eq: records differ in 1 field\n      \
+                 .status\n      - 200\n      + 404\n"
+            ),
+            "{text}"
+        );
+
+        let reporter = PrettyReporter {
+            color: true,
+            ..PrettyReporter::default()
+        };
+        let text = render(&reporter, &files);
+        assert!(text.contains("    \x1b[31m  - 200\x1b[0m\n"), "{text}");
+        assert!(text.contains("    \x1b[32m  + 404\x1b[0m\n"), "{text}");
+
+        let xml = render(&JunitReporter, &files);
+        assert!(
+            xml.contains(
+                r#"<failure message="Assertion failed: assert_eq: records differ in 1 field""#
+            ),
+            "{xml}"
+        );
+    }
+
+    #[test]
+    fn failed_runs_name_their_seed() {
+        let file = parse_test_file("spec \"roll\" { assert_eq(rand.u32(), 0); }").unwrap();
+        let config = crate::ast::TestConfig {
+            seed: Some(42),
+            ..Default::default()
+        };
+        let results = TestRunner::new(config).run_file(&file);
+        assert_eq!((results.failed, results.seed), (1, 42));
+        let files = vec![FileReport {
+            path: "roll.z1t".to_string(),
+            source: String::new(),
+            results,
+        }];
+
+        let text = render(&PrettyReporter::default(), &files);
+        assert!(
+            text.ends_with("\nSeed: 42 (reproduce with --seed 42)\n"),
+            "{text}"
+        );
+        let doc: serde_json::Value = serde_json::from_str(&render(&JsonReporter, &files)).unwrap();
+        assert_eq!(doc["files"][0]["seed"], 42);
+        let xml = render(&JunitReporter, &files);
+        assert!(
+            xml.contains(r#"<property name="seed" value="42"/>"#),
+            "{xml}"
+        );
+    }
+}
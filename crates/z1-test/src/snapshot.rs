@@ -0,0 +1,188 @@
+//! Snapshot (golden file) tests.
+//!
+//! A `snapshot` item renders a cell through one stage of the pipeline and
+//! compares the output with a golden file stored next to the test file, under
+//! `snapshots/<test file stem>/<snapshot name>.snap`:
+//!
+//! ```z1t
+//! snapshot "server in relaxed form" { cell: "cells/server.z1c", emit: relaxed }
+//! ```
+//!
+//! Running the tests with `update` set (`z1 test --update-snapshots`) writes
+//! the golden files instead of comparing against them.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::ast::{Snapshot, SnapshotKind};
+
+/// Outcome of checking one snapshot against its golden file.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SnapshotOutcome {
+    /// The output matches the golden file
+    Matched,
+    /// The golden file was written (or rewritten) with the output
+    Updated(PathBuf),
+}
+
+/// Render the cell of `snapshot`, resolved against `dir`, through its stage.
+pub fn render_snapshot(snapshot: &Snapshot, dir: &Path) -> Result<String, String> {
+    let path = dir.join(&snapshot.cell);
+    let source = fs::read_to_string(&path)
+        .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+    let module = z1_parse::parse_module(&source)
+        .map_err(|err| format!("Failed to parse {}: {err}", path.display()))?;
+    let fmt = |mode| {
+        z1_fmt::format_module(&module, mode, &z1_fmt::FmtOptions::default())
+            .map_err(|err| format!("Failed to format {}: {err}", path.display()))
+    };
+    let lower = || {
+        z1_ir::lower_to_ir(&module)
+            .map_err(|err| format!("Failed to lower {}: {err}", path.display()))
+    };
+    match snapshot.emit {
+        SnapshotKind::Fmt => fmt(z1_fmt::Mode::Compact),
+        SnapshotKind::Relaxed => fmt(z1_fmt::Mode::Relaxed),
+        SnapshotKind::Ir => Ok(format!("{:#?}\n", lower()?)),
+        SnapshotKind::Ts => Ok(z1_codegen_ts::generate_typescript(&lower()?)),
+    }
+}
+
+/// Golden file of `snapshot`, declared in the test file at `test_path`.
+pub fn snapshot_path(test_path: &Path, snapshot: &Snapshot) -> PathBuf {
+    let stem = test_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let slug: String = snapshot
+        .name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    test_path
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join("snapshots")
+        .join(stem)
+        .join(format!("{slug}.snap"))
+}
+
+/// Check `snapshot`, declared in the test file at `test_path`, against its
+/// golden file, or write the golden file when `update` is set.
+pub fn check_snapshot(
+    snapshot: &Snapshot,
+    test_path: &Path,
+    update: bool,
+) -> Result<SnapshotOutcome, String> {
+    let dir = test_path.parent().unwrap_or(Path::new(""));
+    let actual = render_snapshot(snapshot, dir)?;
+    let golden = snapshot_path(test_path, snapshot);
+    let expected = fs::read_to_string(&golden).ok();
+    if expected.as_deref() == Some(actual.as_str()) {
+        return Ok(SnapshotOutcome::Matched);
+    }
+    if update {
+        if let Some(parent) = golden.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| format!("Failed to create {}: {err}", parent.display()))?;
+        }
+        fs::write(&golden, &actual)
+            .map_err(|err| format!("Failed to write {}: {err}", golden.display()))?;
+        return Ok(SnapshotOutcome::Updated(golden));
+    }
+    match expected {
+        None => Err(format!(
+            "No snapshot at {}; run with --update-snapshots to record it",
+            golden.display()
+        )),
+        Some(expected) => Err(format!(
+            "Snapshot {} differs{}; run with --update-snapshots to accept the new output",
+            golden.display(),
+            first_difference(&expected, &actual)
+        )),
+    }
+}
+
+/// The first line where `expected` and `actual` differ, for failure messages.
+fn first_difference(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    let mut line = 1;
+    loop {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(old), Some(new)) if old == new => line += 1,
+            (None, None) => return String::new(),
+            (old, new) => {
+                return format!(
+                    " at line {line}:\n  expected: {}\n  actual:   {}",
+                    old.unwrap_or("<end of file>"),
+                    new.unwrap_or("<end of file>")
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use z1_ast::Span;
+
+    fn snapshot(name: &str, emit: SnapshotKind) -> Snapshot {
+        Snapshot {
+            name: name.to_string(),
+            attrs: Default::default(),
+            cell: "app.z1c".to_string(),
+            emit,
+            span: Span::default(),
+        }
+    }
+
+    #[test]
+    fn golden_files_live_next_to_the_test_file() {
+        let path = snapshot_path(
+            Path::new("tests/fmt.z1t"),
+            &snapshot("App, relaxed", SnapshotKind::Relaxed),
+        );
+        assert_eq!(path, Path::new("tests/snapshots/fmt/app__relaxed.snap"));
+    }
+
+    #[test]
+    fn records_then_compares() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("app.z1c"),
+            "m app:1.0\nf one()->U32 eff [pure] { ret 1; }\n",
+        )
+        .unwrap();
+        let test_path = dir.path().join("app.z1t");
+        let relaxed = snapshot("relaxed", SnapshotKind::Relaxed);
+
+        let missing = check_snapshot(&relaxed, &test_path, false).unwrap_err();
+        assert!(missing.contains("--update-snapshots"), "{missing}");
+
+        let golden = snapshot_path(&test_path, &relaxed);
+        assert_eq!(
+            check_snapshot(&relaxed, &test_path, true),
+            Ok(SnapshotOutcome::Updated(golden.clone()))
+        );
+        assert!(fs::read_to_string(&golden)
+            .unwrap()
+            .starts_with("module app : 1.0"));
+        assert_eq!(
+            check_snapshot(&relaxed, &test_path, false),
+            Ok(SnapshotOutcome::Matched)
+        );
+
+        fs::write(&golden, "module app : 2.0\n").unwrap();
+        let changed = check_snapshot(&relaxed, &test_path, false).unwrap_err();
+        assert!(changed.contains("at line 1:"), "{changed}");
+        assert!(changed.contains("expected: module app : 2.0"), "{changed}");
+    }
+}
@@ -92,3 +92,31 @@ fn run_mixed_fixture_with_skip() {
     assert_eq!(results.passed, 3);
     assert_eq!(results.skipped, 1);
 }
+
+#[test]
+fn run_snapshot_fixture() {
+    let path = std::path::Path::new("../../fixtures/tests/snapshot.z1t");
+    let source = std::fs::read_to_string(path).expect("Failed to read fixture");
+
+    let file = parse_test_file(&source).unwrap();
+    assert_eq!(file.snapshots.len(), 3);
+    let mut runner = TestRunner::default();
+    let results = runner.run_file_at(&file, path);
+
+    // Golden files are checked in under fixtures/tests/snapshots/
+    assert_eq!(results.passed, 3, "{:?}", results.failures);
+    assert!(results.snapshots_updated.is_empty());
+}
+
+#[test]
+fn run_mocks_fixture() {
+    let path = std::path::Path::new("../../fixtures/tests/mocks.z1t");
+    let source = std::fs::read_to_string(path).expect("Failed to read fixture");
+
+    let file = parse_test_file(&source).unwrap();
+    assert_eq!(file.mocks.len(), 2);
+    let mut runner = TestRunner::default();
+    let results = runner.run_file_at(&file, path);
+
+    assert_eq!(results.passed, 3, "{:?}", results.failures);
+}
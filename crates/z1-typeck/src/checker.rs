@@ -1,12 +1,14 @@
 use crate::env::{effect_to_capability, Context};
 use crate::errors::{TypeError, TypeResult};
 use crate::types::{Type, TypeEnv};
-use std::collections::HashSet;
-use z1_ast::{FnDecl, Import, Item, Module, TypeDecl};
+use std::collections::{BTreeMap, BTreeSet};
+use z1_ast::{FnDecl, Ident, Import, Item, Module, Symbol, TypeDecl};
 
 pub struct TypeChecker {
     type_env: TypeEnv,
     context: Context,
+    /// Resolved imported modules, keyed by import path
+    imports: BTreeMap<String, Module>,
 }
 
 impl TypeChecker {
@@ -14,6 +16,16 @@ impl TypeChecker {
         Self {
             type_env: TypeEnv::new(),
             context: Context::new(),
+            imports: BTreeMap::new(),
+        }
+    }
+
+    /// A checker that resolves imported names against `imports`, keyed by
+    /// import path. Imports missing from the map stay opaque.
+    pub fn with_imports(imports: BTreeMap<String, Module>) -> Self {
+        Self {
+            imports,
+            ..Self::new()
         }
     }
 
@@ -37,6 +49,9 @@ impl TypeChecker {
                 Item::Symbol(_) => {
                     // Symbol maps are formatting-only, ignored for type checking
                 }
+                Item::Test(_) => {
+                    // Inline tests run under `z1 test`, not type checked here
+                }
             }
         }
 
@@ -53,7 +68,7 @@ impl TypeChecker {
     /// Collect a type declaration into the type environment.
     fn collect_type_decl(&mut self, decl: &TypeDecl) -> TypeResult<()> {
         let ty = Type::from_ast(&decl.expr);
-        self.type_env.define_type(decl.name.clone(), ty);
+        self.type_env.define_type(decl.name, ty);
         Ok(())
     }
 
@@ -84,33 +99,57 @@ impl TypeChecker {
             effects: decl.effects.clone(),
         };
 
-        self.context.define_function(decl.name.clone(), func_type);
+        self.context.define_function(decl.name, func_type);
         Ok(())
     }
 
-    /// Process an import statement (stubbed for MVP - full resolution requires module system).
+    /// Process an import statement.
+    ///
+    /// Names imported from a resolved module take their declared types; a
+    /// name the module does not declare is an error. Without the module,
+    /// imported names are registered as opaque path types.
     fn process_import(&mut self, import: &Import) -> TypeResult<()> {
-        // For MVP, we register the alias if present
         if let Some(alias) = &import.alias {
-            // We don't have the full module system yet, so we just track the alias
-            // In a complete implementation, this would load the imported module and
-            // resolve the types from it
-            let import_path = import.path.split('/').map(|s| s.to_string()).collect();
-            self.type_env.register_alias(alias.clone(), import_path);
+            let import_path = import.path.split('/').map(Symbol::intern).collect();
+            self.type_env.register_alias(*alias, import_path);
         }
 
-        // For now, we stub imported types as Path types
-        // A full implementation would resolve these from the imported module
         for name in &import.only {
             let qualified_name = if let Some(alias) = &import.alias {
-                vec![alias.clone(), name.clone()]
+                vec![*alias, *name]
             } else {
-                vec![name.clone()]
+                vec![*name]
+            };
+
+            let Some(module) = self.imports.get(&import.path) else {
+                let imported_type = Type::Path(qualified_name.clone());
+                self.type_env.register_import(qualified_name, imported_type);
+                continue;
             };
 
-            // Register as an opaque path type for now
-            let imported_type = Type::Path(qualified_name.clone());
-            self.type_env.register_import(qualified_name, imported_type);
+            let declared = module.items.iter().find_map(|item| match item {
+                Item::Type(decl) if &decl.name == name => Some(Type::from_ast(&decl.expr)),
+                Item::Fn(decl) if &decl.name == name => Some(Type::Function {
+                    params: decl.params.iter().map(|p| Type::from_ast(&p.ty)).collect(),
+                    ret: Box::new(Type::from_ast(&decl.ret)),
+                    effects: decl.effects.clone(),
+                }),
+                _ => None,
+            });
+            match declared {
+                Some(func @ Type::Function { .. }) => {
+                    self.context
+                        .define_function(Symbol::intern(&Symbol::join(&qualified_name, ".")), func);
+                }
+                Some(ty) => self.type_env.register_import(qualified_name, ty),
+                None => {
+                    return Err(TypeError::UnknownImport {
+                        name: name.to_string(),
+                        import: import.path.clone(),
+                        span: import.span,
+                    })
+                }
+            }
         }
 
         Ok(())
@@ -148,12 +187,11 @@ impl TypeChecker {
         // Add parameters to the function context
         for param in &decl.params {
             let param_type = self.resolve_type(&param.ty, param.span)?;
-            func_ctx.define_variable(param.name.clone(), param_type);
+            func_ctx.define_variable(param.name, param_type);
         }
 
-        // For MVP, we don't have full statement AST yet (body.raw is String)
-        // So we can't type check the function body in detail
-        // This is a known limitation documented in PROGRESS.md
+        // Bodies are parsed into statements but not type checked in detail
+        // yet; this is a known limitation documented in PROGRESS.md
 
         // We do basic validation: check that the function signature is well-formed
         let ret_type = self.resolve_type(&decl.ret, decl.span)?;
@@ -172,13 +210,13 @@ impl TypeChecker {
     /// Check that a call site's effects are compatible with the calling context.
     pub fn check_effect_compatibility(
         &self,
-        required_effects: &[String],
-        available_effects: &HashSet<String>,
+        required_effects: &[Ident],
+        available_effects: &BTreeSet<Ident>,
     ) -> TypeResult<()> {
         for effect in required_effects {
             if effect != "pure" && !available_effects.contains(effect) {
                 return Err(TypeError::EffectNotPermitted {
-                    effect: effect.clone(),
+                    effect: effect.to_string(),
                 });
             }
         }
@@ -202,6 +240,44 @@ impl TypeChecker {
         Ok(())
     }
 
+    /// Check a record update `{ ..base, field: value }` (public for testing).
+    ///
+    /// Every updated field must exist on the base record and keep its type;
+    /// the update has the type of its base.
+    pub fn check_record_update(
+        &self,
+        base: &Type,
+        fields: &[(Ident, Type)],
+        span: z1_ast::Span,
+    ) -> TypeResult<Type> {
+        let resolved = match base {
+            Type::Path(path) => self.type_env.lookup(path).unwrap_or_else(|| base.clone()),
+            _ => base.clone(),
+        };
+        let Type::Record(declared) = &resolved else {
+            return Err(TypeError::mismatch(
+                "record".to_string(),
+                base.display_name(),
+                span,
+            ));
+        };
+        for (name, ty) in fields {
+            let Some(field_ty) = declared.get(name) else {
+                return Err(TypeError::RecordFieldMismatch {
+                    message: format!("{} has no field '{name}'", base.display_name()),
+                });
+            };
+            if !field_ty.structural_eq(ty) {
+                return Err(TypeError::mismatch(
+                    field_ty.display_name(),
+                    ty.display_name(),
+                    span,
+                ));
+            }
+        }
+        Ok(base.clone())
+    }
+
     /// Check function call arity and types (public for testing).
     pub fn check_call(
         &self,
@@ -283,12 +359,12 @@ mod tests {
         let span = make_span();
 
         let mut fields1 = BTreeMap::new();
-        fields1.insert("x".to_string(), Box::new(Type::U32));
-        fields1.insert("y".to_string(), Box::new(Type::Bool));
+        fields1.insert("x".into(), Box::new(Type::U32));
+        fields1.insert("y".into(), Box::new(Type::Bool));
 
         let mut fields2 = BTreeMap::new();
-        fields2.insert("y".to_string(), Box::new(Type::Bool));
-        fields2.insert("x".to_string(), Box::new(Type::U32));
+        fields2.insert("y".into(), Box::new(Type::Bool));
+        fields2.insert("x".into(), Box::new(Type::U32));
 
         let rec1 = Type::Record(fields1);
         let rec2 = Type::Record(fields2);
@@ -304,7 +380,7 @@ mod tests {
         let func_type = Type::Function {
             params: vec![Type::U32, Type::Bool],
             ret: Box::new(Type::Str),
-            effects: vec!["pure".to_string()],
+            effects: vec!["pure".into()],
         };
 
         // Correct arity
@@ -324,7 +400,7 @@ mod tests {
         let func_type = Type::Function {
             params: vec![Type::U32],
             ret: Box::new(Type::Bool),
-            effects: vec!["pure".to_string()],
+            effects: vec!["pure".into()],
         };
 
         // Correct type
@@ -338,26 +414,60 @@ mod tests {
         assert!(checker.check_call(&func_type, &args, span).is_err());
     }
 
+    #[test]
+    fn test_record_update_check() {
+        let mut checker = TypeChecker::new();
+        let span = make_span();
+
+        let mut fields = BTreeMap::new();
+        fields.insert("x".into(), Box::new(Type::U32));
+        fields.insert("y".into(), Box::new(Type::U32));
+        checker
+            .type_env
+            .define_type("Point".into(), Type::Record(fields));
+        let point = Type::Path(vec!["Point".into()]);
+
+        let update = vec![("x".into(), Type::U32)];
+        assert_eq!(
+            checker.check_record_update(&point, &update, span),
+            Ok(point.clone())
+        );
+
+        // Unknown field
+        let update = vec![("z".into(), Type::U32)];
+        assert!(matches!(
+            checker.check_record_update(&point, &update, span),
+            Err(TypeError::RecordFieldMismatch { .. })
+        ));
+
+        // Wrong field type
+        let update = vec![("y".into(), Type::Str)];
+        assert!(checker.check_record_update(&point, &update, span).is_err());
+
+        // Not a record
+        assert!(checker.check_record_update(&Type::U32, &[], span).is_err());
+    }
+
     #[test]
     fn test_effect_compatibility() {
         let checker = TypeChecker::new();
-        let mut available = HashSet::new();
-        available.insert("pure".to_string());
-        available.insert("net".to_string());
+        let mut available = BTreeSet::new();
+        available.insert("pure".into());
+        available.insert("net".into());
 
         // Pure is always allowed
         assert!(checker
-            .check_effect_compatibility(&["pure".to_string()], &available)
+            .check_effect_compatibility(&["pure".into()], &available)
             .is_ok());
 
         // Net is available
         assert!(checker
-            .check_effect_compatibility(&["net".to_string()], &available)
+            .check_effect_compatibility(&["net".into()], &available)
             .is_ok());
 
         // Fs is not available
         assert!(checker
-            .check_effect_compatibility(&["fs".to_string()], &available)
+            .check_effect_compatibility(&["fs".into()], &available)
             .is_err());
     }
 
@@ -367,12 +477,14 @@ mod tests {
         checker.context.set_capabilities(vec!["net".to_string()]);
 
         let fn_decl = FnDecl {
-            name: "test_fn".to_string(),
+            name: "test_fn".into(),
+            type_params: vec![],
             params: vec![],
-            ret: z1_ast::TypeExpr::Path(vec!["Unit".to_string()]),
-            effects: vec!["net".to_string()],
+            ret: z1_ast::TypeExpr::Path(vec!["Unit".into()]),
+            effects: vec!["net".into()],
             body: z1_ast::Block::default(),
             span: make_span(),
+            doc: None,
         };
 
         // Should succeed - net capability is granted
@@ -380,12 +492,14 @@ mod tests {
 
         // Should fail - fs capability is not granted
         let fn_decl_fs = FnDecl {
-            name: "test_fn_fs".to_string(),
+            name: "test_fn_fs".into(),
+            type_params: vec![],
             params: vec![],
-            ret: z1_ast::TypeExpr::Path(vec!["Unit".to_string()]),
-            effects: vec!["fs".to_string()],
+            ret: z1_ast::TypeExpr::Path(vec!["Unit".into()]),
+            effects: vec!["fs".into()],
             body: z1_ast::Block::default(),
             span: make_span(),
+            doc: None,
         };
 
         assert!(checker.collect_function_signature(&fn_decl_fs).is_err());
@@ -1,29 +1,29 @@
 use crate::types::Type;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet};
 use z1_ast::Ident;
 
 /// Typing context for variables, functions, and effects during type checking.
 pub struct Context {
     /// Function signatures (name -> type)
-    functions: HashMap<Ident, Type>,
+    functions: BTreeMap<Ident, Type>,
 
     /// Variable types in current scope (name -> type)
-    variables: HashMap<Ident, Type>,
+    variables: BTreeMap<Ident, Type>,
 
     /// Effects available in the current context
-    available_effects: HashSet<Ident>,
+    available_effects: BTreeSet<Ident>,
 
     /// Capabilities granted by the module
-    granted_capabilities: HashSet<String>,
+    granted_capabilities: BTreeSet<String>,
 }
 
 impl Context {
     pub fn new() -> Self {
         Self {
-            functions: HashMap::new(),
-            variables: HashMap::new(),
-            available_effects: HashSet::new(),
-            granted_capabilities: HashSet::new(),
+            functions: BTreeMap::new(),
+            variables: BTreeMap::new(),
+            available_effects: BTreeSet::new(),
+            granted_capabilities: BTreeSet::new(),
         }
     }
 
@@ -58,7 +58,7 @@ impl Context {
     }
 
     /// Get all available effects.
-    pub fn available_effects(&self) -> &HashSet<Ident> {
+    pub fn available_effects(&self) -> &BTreeSet<Ident> {
         &self.available_effects
     }
 
@@ -67,9 +67,12 @@ impl Context {
         self.granted_capabilities = caps.into_iter().collect();
     }
 
-    /// Check if a capability is granted.
+    /// Check if a capability is granted, directly or through a
+    /// fine-grained form such as `fs.ro`.
     pub fn has_capability(&self, cap: &str) -> bool {
-        self.granted_capabilities.contains(cap)
+        self.granted_capabilities.iter().any(|granted| {
+            granted == cap || granted.split_once('.').is_some_and(|(base, _)| base == cap)
+        })
     }
 
     /// Create a new context inheriting functions and capabilities but with empty variables.
@@ -77,13 +80,13 @@ impl Context {
     pub fn enter_function(&self, effects: &[Ident]) -> Self {
         let mut ctx = Self {
             functions: self.functions.clone(),
-            variables: HashMap::new(),
+            variables: BTreeMap::new(),
             available_effects: effects.iter().cloned().collect(),
             granted_capabilities: self.granted_capabilities.clone(),
         };
 
         // Pure functions can always be called
-        ctx.available_effects.insert("pure".to_string());
+        ctx.available_effects.insert("pure".into());
 
         ctx
     }
@@ -130,24 +133,21 @@ mod tests {
         let func_type = Type::Function {
             params: vec![Type::U32],
             ret: Box::new(Type::Bool),
-            effects: vec!["pure".to_string()],
+            effects: vec!["pure".into()],
         };
 
-        ctx.define_function("test_fn".to_string(), func_type.clone());
-        assert_eq!(
-            ctx.lookup_function(&"test_fn".to_string()),
-            Some(&func_type)
-        );
-        assert_eq!(ctx.lookup_function(&"missing".to_string()), None);
+        ctx.define_function("test_fn".into(), func_type.clone());
+        assert_eq!(ctx.lookup_function(&"test_fn".into()), Some(&func_type));
+        assert_eq!(ctx.lookup_function(&"missing".into()), None);
     }
 
     #[test]
     fn test_effect_tracking() {
         let mut ctx = Context::new();
-        assert!(!ctx.has_effect(&"net".to_string()));
+        assert!(!ctx.has_effect(&"net".into()));
 
-        ctx.add_effect("net".to_string());
-        assert!(ctx.has_effect(&"net".to_string()));
+        ctx.add_effect("net".into());
+        assert!(ctx.has_effect(&"net".into()));
     }
 
     #[test]
@@ -158,27 +158,31 @@ mod tests {
         assert!(ctx.has_capability("net"));
         assert!(ctx.has_capability("time"));
         assert!(!ctx.has_capability("fs"));
+
+        ctx.set_capabilities(vec!["fs.ro".to_string()]);
+        assert!(ctx.has_capability("fs"));
+        assert!(!ctx.has_capability("f"));
     }
 
     #[test]
     fn test_enter_function_scope() {
         let mut ctx = Context::new();
-        ctx.define_function("outer".to_string(), Type::Bool);
-        ctx.define_variable("x".to_string(), Type::U32);
-        ctx.add_effect("net".to_string());
+        ctx.define_function("outer".into(), Type::Bool);
+        ctx.define_variable("x".into(), Type::U32);
+        ctx.add_effect("net".into());
 
-        let func_ctx = ctx.enter_function(&["fs".to_string()]);
+        let func_ctx = ctx.enter_function(&["fs".into()]);
 
         // Functions are inherited
-        assert!(func_ctx.lookup_function(&"outer".to_string()).is_some());
+        assert!(func_ctx.lookup_function(&"outer".into()).is_some());
 
         // Variables are NOT inherited (new scope)
-        assert!(func_ctx.lookup_variable(&"x".to_string()).is_none());
+        assert!(func_ctx.lookup_variable(&"x".into()).is_none());
 
         // Effects are replaced with new ones
-        assert!(!func_ctx.has_effect(&"net".to_string()));
-        assert!(func_ctx.has_effect(&"fs".to_string()));
-        assert!(func_ctx.has_effect(&"pure".to_string())); // pure always available
+        assert!(!func_ctx.has_effect(&"net".into()));
+        assert!(func_ctx.has_effect(&"fs".into()));
+        assert!(func_ctx.has_effect(&"pure".into())); // pure always available
     }
 
     #[test]
@@ -44,6 +44,13 @@ pub enum TypeError {
 
     #[error("Duplicate definition: {message}")]
     DuplicateDefinition { message: String },
+
+    #[error("'{name}' is not declared by imported module '{import}' at {span:?}")]
+    UnknownImport {
+        name: String,
+        import: String,
+        span: Span,
+    },
 }
 
 impl TypeError {
@@ -10,6 +10,7 @@ pub use errors::{TypeError, TypeResult};
 pub use types::{Type, TypeEnv};
 pub use warnings::{collect_warnings, TypeWarning};
 
+use std::collections::BTreeMap;
 use z1_ast::{Module, TypeExpr};
 
 /// Type check a complete module and return any errors found.
@@ -18,6 +19,16 @@ pub fn check_module(module: &Module) -> TypeResult<()> {
     checker.check_module(module)
 }
 
+/// Type check a module whose imports have been resolved, keyed by import
+/// path. Imported names must be declared by the imported module.
+pub fn check_module_with_imports(
+    module: &Module,
+    imports: &BTreeMap<String, Module>,
+) -> TypeResult<()> {
+    let mut checker = TypeChecker::with_imports(imports.clone());
+    checker.check_module(module)
+}
+
 /// Convert an AST TypeExpr to our internal Type representation.
 /// This is used for testing and debugging.
 pub fn type_from_ast(expr: &TypeExpr) -> Type {
@@ -1,5 +1,5 @@
-use std::collections::{BTreeMap, HashMap};
-use z1_ast::{Ident, TypeExpr as AstTypeExpr};
+use std::collections::BTreeMap;
+use z1_ast::{Ident, Symbol, TypeExpr as AstTypeExpr};
 
 /// Internal representation of types for type checking.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -21,7 +21,7 @@ pub enum Type {
     /// Sum type / union (variant label to optional payload type)
     Sum(BTreeMap<Ident, Option<Box<Type>>>),
 
-    /// Generic type application (e.g., `List<T>`)
+    /// Generic type application (e.g., `List[T]`)
     Generic {
         base: Box<Type>,
         args: Vec<Type>,
@@ -58,10 +58,23 @@ impl Type {
                 let mut map = BTreeMap::new();
                 for field in fields {
                     let ty = Type::from_ast(&field.ty);
-                    map.insert(field.name.clone(), Box::new(ty));
+                    map.insert(field.name, Box::new(ty));
                 }
                 Type::Record(map)
             }
+            AstTypeExpr::Union(variants) => Type::Sum(
+                variants
+                    .iter()
+                    .map(|variant| {
+                        let payload = variant.payload.as_deref().map(Type::from_ast);
+                        (variant.name, payload.map(Box::new))
+                    })
+                    .collect(),
+            ),
+            AstTypeExpr::Generic { base, args } => Type::Generic {
+                base: Box::new(Type::Path(base.clone())),
+                args: args.iter().map(Type::from_ast).collect(),
+            },
         }
     }
 
@@ -82,7 +95,7 @@ impl Type {
             Type::U16 => "U16".to_string(),
             Type::U32 => "U32".to_string(),
             Type::U64 => "U64".to_string(),
-            Type::Path(path) => path.join("."),
+            Type::Path(path) => Symbol::join(path, "."),
             Type::Record(fields) => {
                 let field_strs: Vec<_> = fields
                     .iter()
@@ -97,7 +110,7 @@ impl Type {
                         if let Some(ty) = payload {
                             format!("{}({})", name, ty.display_name())
                         } else {
-                            name.clone()
+                            name.to_string()
                         }
                     })
                     .collect();
@@ -116,7 +129,7 @@ impl Type {
                 let eff_str = if effects.is_empty() {
                     String::new()
                 } else {
-                    format!(" eff [{}]", effects.join(", "))
+                    format!(" eff [{}]", Symbol::join(effects, ", "))
                 };
                 format!(
                     "({}) -> {}{}",
@@ -232,21 +245,21 @@ impl Type {
 /// Type environment for tracking type definitions and imported types.
 pub struct TypeEnv {
     /// Type definitions in the current module
-    types: HashMap<Ident, Type>,
+    types: BTreeMap<Ident, Type>,
 
     /// Imported types (qualified paths)
-    imports: HashMap<Vec<Ident>, Type>,
+    imports: BTreeMap<Vec<Ident>, Type>,
 
     /// Import aliases (alias -> full path)
-    aliases: HashMap<Ident, Vec<Ident>>,
+    aliases: BTreeMap<Ident, Vec<Ident>>,
 }
 
 impl TypeEnv {
     pub fn new() -> Self {
         Self {
-            types: HashMap::new(),
-            imports: HashMap::new(),
-            aliases: HashMap::new(),
+            types: BTreeMap::new(),
+            imports: BTreeMap::new(),
+            aliases: BTreeMap::new(),
         }
     }
 
@@ -308,12 +321,12 @@ mod tests {
     #[test]
     fn test_record_structural_equality() {
         let mut fields1 = BTreeMap::new();
-        fields1.insert("x".to_string(), Box::new(Type::U32));
-        fields1.insert("y".to_string(), Box::new(Type::Bool));
+        fields1.insert("x".into(), Box::new(Type::U32));
+        fields1.insert("y".into(), Box::new(Type::Bool));
 
         let mut fields2 = BTreeMap::new();
-        fields2.insert("y".to_string(), Box::new(Type::Bool));
-        fields2.insert("x".to_string(), Box::new(Type::U32));
+        fields2.insert("y".into(), Box::new(Type::Bool));
+        fields2.insert("x".into(), Box::new(Type::U32));
 
         let rec1 = Type::Record(fields1);
         let rec2 = Type::Record(fields2);
@@ -324,10 +337,10 @@ mod tests {
     #[test]
     fn test_record_field_mismatch() {
         let mut fields1 = BTreeMap::new();
-        fields1.insert("x".to_string(), Box::new(Type::U32));
+        fields1.insert("x".into(), Box::new(Type::U32));
 
         let mut fields2 = BTreeMap::new();
-        fields2.insert("y".to_string(), Box::new(Type::U32));
+        fields2.insert("y".into(), Box::new(Type::U32));
 
         let rec1 = Type::Record(fields1);
         let rec2 = Type::Record(fields2);
@@ -337,9 +350,9 @@ mod tests {
 
     #[test]
     fn test_path_equality() {
-        let path1 = Type::Path(vec!["http".to_string(), "Req".to_string()]);
-        let path2 = Type::Path(vec!["http".to_string(), "Req".to_string()]);
-        let path3 = Type::Path(vec!["http".to_string(), "Res".to_string()]);
+        let path1 = Type::Path(vec!["http".into(), "Req".into()]);
+        let path2 = Type::Path(vec!["http".into(), "Req".into()]);
+        let path3 = Type::Path(vec!["http".into(), "Res".into()]);
 
         assert!(path1.structural_eq(&path2));
         assert!(!path1.structural_eq(&path3));
@@ -351,8 +364,8 @@ mod tests {
         assert_eq!(ty.display_name(), "Bool");
 
         let mut fields = BTreeMap::new();
-        fields.insert("ok".to_string(), Box::new(Type::Bool));
-        fields.insert("msg".to_string(), Box::new(Type::Str));
+        fields.insert("ok".into(), Box::new(Type::Bool));
+        fields.insert("msg".into(), Box::new(Type::Str));
         let rec = Type::Record(fields);
 
         // Display name includes fields
@@ -5,8 +5,10 @@
 //! - Unused function parameters
 //! - Shadowed variables
 //! - Redundant type annotations
+//! - Unused imports
 
-use z1_ast::{FnDecl, Module, Span};
+use std::collections::HashSet;
+use z1_ast::{Block, ElseBlock, Expr, FnDecl, IfStmt, Import, Item, Module, Span, Stmt, TypeExpr};
 
 /// A warning detected during type checking.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -29,6 +31,14 @@ pub enum TypeWarning {
         name: String,
         span: Span,
     },
+    /// Names of an import that nothing refers to; `entire` when none of the
+    /// import is used.
+    UnusedImport {
+        path: String,
+        names: Vec<String>,
+        entire: bool,
+        span: Span,
+    },
 }
 
 impl std::fmt::Display for TypeWarning {
@@ -46,6 +56,18 @@ impl std::fmt::Display for TypeWarning {
             TypeWarning::RedundantTypeAnnotation { name, .. } => {
                 write!(f, "Type annotation for '{name}' is redundant")
             }
+            TypeWarning::UnusedImport {
+                path, entire: true, ..
+            } => {
+                write!(f, "Import '{path}' is unused")
+            }
+            TypeWarning::UnusedImport { path, names, .. } => {
+                write!(
+                    f,
+                    "Unused names imported from '{path}': {}",
+                    names.join(", ")
+                )
+            }
         }
     }
 }
@@ -55,7 +77,8 @@ impl TypeWarning {
         match self {
             TypeWarning::UnusedVariable { span, .. }
             | TypeWarning::UnusedParameter { span, .. }
-            | TypeWarning::RedundantTypeAnnotation { span, .. } => *span,
+            | TypeWarning::RedundantTypeAnnotation { span, .. }
+            | TypeWarning::UnusedImport { span, .. } => *span,
             TypeWarning::ShadowedVariable { shadow_span, .. } => *shadow_span,
         }
     }
@@ -71,9 +94,196 @@ pub fn collect_warnings(module: &Module) -> Vec<TypeWarning> {
         }
     }
 
+    let referenced = referenced_names(module);
+    for item in &module.items {
+        if let Item::Import(import) = item {
+            warnings.extend(check_import(import, &referenced));
+        }
+    }
+
     warnings
 }
 
+/// Warn about the names of `import` missing from `referenced`.
+///
+/// An import without `only` is used through its alias; one with neither
+/// could be used in any way and is never reported.
+fn check_import(import: &Import, referenced: &HashSet<&str>) -> Option<TypeWarning> {
+    let names: Vec<String> = if import.only.is_empty() {
+        let alias = import.alias.as_deref()?;
+        if referenced.contains(alias) {
+            return None;
+        }
+        Vec::new()
+    } else {
+        import
+            .only
+            .iter()
+            .filter(|name| !referenced.contains(name.as_str()))
+            .map(|name| name.to_string())
+            .collect()
+    };
+    if !import.only.is_empty() && names.is_empty() {
+        return None;
+    }
+    Some(TypeWarning::UnusedImport {
+        path: import.path.clone(),
+        entire: names.len() == import.only.len(),
+        names,
+        span: import.span,
+    })
+}
+
+/// Every identifier a module's declarations and bodies refer to.
+///
+/// Every identifier of a body counts, whatever its role in it, a field name
+/// as much as a variable, so an import is never reported unused when it may
+/// be used; names in string literals do not. Short names of the symbol map
+/// count for their long names too.
+fn referenced_names(module: &Module) -> HashSet<&str> {
+    let mut names = HashSet::new();
+    for item in &module.items {
+        match item {
+            Item::Type(decl) => type_names(&decl.expr, &mut names),
+            Item::Fn(decl) => {
+                for param in &decl.params {
+                    type_names(&param.ty, &mut names);
+                }
+                type_names(&decl.ret, &mut names);
+                block_names(&decl.body, &mut names);
+            }
+            Item::Test(test) => block_names(&test.body, &mut names),
+            Item::Import(_) | Item::Symbol(_) => {}
+        }
+    }
+    for item in &module.items {
+        if let Item::Symbol(map) = item {
+            for pair in &map.pairs {
+                if names.contains(pair.short.as_str()) {
+                    names.insert(pair.long.as_str());
+                }
+            }
+        }
+    }
+    names
+}
+
+/// Add `name` to `names`, split into its segments when qualified, like the
+/// lexer keeps `H.Req` in one identifier.
+fn add_name<'a>(name: &'a str, names: &mut HashSet<&'a str>) {
+    names.extend(name.split('.').filter(|segment| !segment.is_empty()));
+}
+
+fn type_names<'a>(ty: &'a TypeExpr, names: &mut HashSet<&'a str>) {
+    match ty {
+        TypeExpr::Path(segments) => {
+            for segment in segments {
+                add_name(segment, names);
+            }
+        }
+        TypeExpr::Record(fields) => {
+            for field in fields {
+                type_names(&field.ty, names);
+            }
+        }
+        TypeExpr::Union(variants) => {
+            for payload in variants.iter().filter_map(|v| v.payload.as_deref()) {
+                type_names(payload, names);
+            }
+        }
+        TypeExpr::Generic { base, args } => {
+            for segment in base {
+                add_name(segment, names);
+            }
+            for arg in args {
+                type_names(arg, names);
+            }
+        }
+    }
+}
+
+fn block_names<'a>(block: &'a Block, names: &mut HashSet<&'a str>) {
+    for stmt in &block.statements {
+        stmt_names(stmt, names);
+    }
+}
+
+fn stmt_names<'a>(stmt: &'a Stmt, names: &mut HashSet<&'a str>) {
+    match stmt {
+        Stmt::Let(stmt) => {
+            if let Some(ty) = &stmt.ty {
+                type_names(ty, names);
+            }
+            expr_names(&stmt.init, names);
+        }
+        Stmt::Assign(stmt) => {
+            expr_names(&stmt.target, names);
+            expr_names(&stmt.value, names);
+        }
+        Stmt::If(stmt) => if_names(stmt, names),
+        Stmt::While(stmt) => {
+            expr_names(&stmt.cond, names);
+            block_names(&stmt.body, names);
+        }
+        Stmt::Return(stmt) => {
+            if let Some(value) = &stmt.value {
+                expr_names(value, names);
+            }
+        }
+        Stmt::Expr(stmt) => expr_names(&stmt.expr, names),
+    }
+}
+
+fn if_names<'a>(stmt: &'a IfStmt, names: &mut HashSet<&'a str>) {
+    expr_names(&stmt.cond, names);
+    block_names(&stmt.then_block, names);
+    match stmt.else_block.as_deref() {
+        Some(ElseBlock::Block(block)) => block_names(block, names),
+        Some(ElseBlock::If(stmt)) => if_names(stmt, names),
+        None => {}
+    }
+}
+
+fn expr_names<'a>(expr: &'a Expr, names: &mut HashSet<&'a str>) {
+    match expr {
+        Expr::Ident(name, _) => add_name(name, names),
+        Expr::Literal(..) => {}
+        Expr::BinOp { lhs, rhs, .. } => {
+            expr_names(lhs, names);
+            expr_names(rhs, names);
+        }
+        Expr::UnaryOp { expr, .. } | Expr::Paren(expr, _) => expr_names(expr, names),
+        Expr::Call { func, args, .. } => {
+            expr_names(func, names);
+            for arg in args {
+                expr_names(arg, names);
+            }
+        }
+        Expr::Field { base, field, .. } => {
+            expr_names(base, names);
+            add_name(field, names);
+        }
+        Expr::Record { fields, .. } => {
+            for field in fields {
+                add_name(&field.name, names);
+                expr_names(&field.value, names);
+            }
+        }
+        Expr::RecordUpdate { base, fields, .. } => {
+            expr_names(base, names);
+            for field in fields {
+                add_name(&field.name, names);
+                expr_names(&field.value, names);
+            }
+        }
+        Expr::Path(segments, _) => {
+            for segment in segments {
+                add_name(segment, names);
+            }
+        }
+    }
+}
+
 /// Check for warnings in a function declaration.
 fn check_function_warnings(fn_decl: &FnDecl) -> Vec<TypeWarning> {
     let warnings = Vec::new();
@@ -154,15 +364,101 @@ mod tests {
         use z1_ast::ModulePath;
 
         let module = Module {
-            path: ModulePath::from_parts(vec!["test".to_string()]),
+            path: ModulePath::from_parts(vec!["test".into()]),
             version: None,
             ctx_budget: None,
             caps: vec![],
+            targets: Vec::new(),
+            toolchain: None,
             items: vec![],
+            templates: Vec::new(),
+            expansions: Vec::new(),
             span: Span::new(0, 0),
         };
 
         let warnings = collect_warnings(&module);
         assert_eq!(warnings.len(), 0);
     }
+
+    #[test]
+    fn test_collect_warnings_unused_imports() {
+        let source = "m app.main:1.0 caps=[]\n\
+            u \"std/http\" as H only [listen, Req, Res]\n\
+            u \"util/text\" only [shout, whisper]\n\
+            u \"util/math\" only [square]\n\
+            u \"std/time\" as T\n\
+            u \"std/core\"\n\
+            f handle(q: H.Req)->H.Res eff [pure] { ret shout(q); }\n";
+        let module = z1_parse::parse_module(source).unwrap();
+
+        let unused: Vec<(String, Vec<String>, bool)> = collect_warnings(&module)
+            .into_iter()
+            .map(|warning| match warning {
+                TypeWarning::UnusedImport {
+                    path,
+                    names,
+                    entire,
+                    ..
+                } => (path, names, entire),
+                other => panic!("unexpected warning {other}"),
+            })
+            .collect();
+        assert_eq!(
+            unused,
+            [
+                ("std/http".to_string(), vec!["listen".to_string()], false),
+                ("util/text".to_string(), vec!["whisper".to_string()], false),
+                ("util/math".to_string(), vec!["square".to_string()], true),
+                ("std/time".to_string(), vec![], true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_names_in_strings_and_comments_are_not_uses() {
+        let source = "m app.main:1.0 caps=[]\n\
+            u \"util/text\" only [shout, whisper]\n\
+            u \"std/time\" as T\n\
+            f greet()->Str eff [pure] {\n\
+              // whisper(T.now()) would be quieter\n\
+              ret shout(\"whisper\");\n\
+            }\n";
+        let module = z1_parse::parse_module(source).unwrap();
+        let unused: Vec<String> = collect_warnings(&module)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            unused,
+            [
+                "Unused names imported from 'util/text': whisper",
+                "Import 'std/time' is unused",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_names_in_nested_statements_are_uses() {
+        let source = "m app.main:1.0 caps=[]\n\
+            u \"std/http\" as H only [Req, Res, listen]\n\
+            u \"util/text\" only [shout, whisper, trim]\n\
+            u \"std/time\" as T\n\
+            f serve(ok: Bool)->Unit eff [pure] {\n\
+              let req: H.Req = H.Req{ path: \"/\" };\n\
+              while ok { if ok { shout(req); } else { let r = { ..req, path: whisper(req.path) }; } }\n\
+              ret T.now();\n\
+            }\n";
+        let module = z1_parse::parse_module(source).unwrap();
+        let unused: Vec<String> = collect_warnings(&module)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            unused,
+            [
+                "Unused names imported from 'std/http': Res, listen",
+                "Unused names imported from 'util/text': trim",
+            ]
+        );
+    }
 }
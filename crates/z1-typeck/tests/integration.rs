@@ -1,5 +1,5 @@
 use z1_ast::{FnDecl, Import, Item, Module, ModulePath, Param, Span, TypeDecl, TypeExpr};
-use z1_typeck::{check_module, Type, TypeError};
+use z1_typeck::{check_module, check_module_with_imports, Type, TypeError};
 
 fn make_span() -> Span {
     Span::new(0, 0)
@@ -7,7 +7,7 @@ fn make_span() -> Span {
 
 fn make_module(items: Vec<Item>) -> Module {
     Module::new(
-        ModulePath::from_parts(vec!["test".to_string()]),
+        ModulePath::from_parts(vec!["test".into()]),
         Some("1.0".to_string()),
         Some(128),
         vec!["net".to_string()],
@@ -19,20 +19,22 @@ fn make_module(items: Vec<Item>) -> Module {
 #[test]
 fn test_simple_module_with_type_decl() {
     let type_decl = TypeDecl {
-        name: "Point".to_string(),
+        name: "Point".into(),
+        type_params: vec![],
         expr: TypeExpr::Record(vec![
             z1_ast::RecordField {
-                name: "x".to_string(),
-                ty: Box::new(TypeExpr::Path(vec!["U32".to_string()])),
+                name: "x".into(),
+                ty: Box::new(TypeExpr::Path(vec!["U32".into()])),
                 span: make_span(),
             },
             z1_ast::RecordField {
-                name: "y".to_string(),
-                ty: Box::new(TypeExpr::Path(vec!["U32".to_string()])),
+                name: "y".into(),
+                ty: Box::new(TypeExpr::Path(vec!["U32".into()])),
                 span: make_span(),
             },
         ]),
         span: make_span(),
+        doc: None,
     };
 
     let module = make_module(vec![Item::Type(type_decl)]);
@@ -43,23 +45,25 @@ fn test_simple_module_with_type_decl() {
 #[test]
 fn test_function_with_pure_effect() {
     let fn_decl = FnDecl {
-        name: "add".to_string(),
+        name: "add".into(),
+        type_params: vec![],
         params: vec![
             Param {
-                name: "a".to_string(),
-                ty: TypeExpr::Path(vec!["U32".to_string()]),
+                name: "a".into(),
+                ty: TypeExpr::Path(vec!["U32".into()]),
                 span: make_span(),
             },
             Param {
-                name: "b".to_string(),
-                ty: TypeExpr::Path(vec!["U32".to_string()]),
+                name: "b".into(),
+                ty: TypeExpr::Path(vec!["U32".into()]),
                 span: make_span(),
             },
         ],
-        ret: TypeExpr::Path(vec!["U32".to_string()]),
-        effects: vec!["pure".to_string()],
+        ret: TypeExpr::Path(vec!["U32".into()]),
+        effects: vec!["pure".into()],
         body: z1_ast::Block::default(),
         span: make_span(),
+        doc: None,
     };
 
     let module = make_module(vec![Item::Fn(fn_decl)]);
@@ -70,12 +74,14 @@ fn test_function_with_pure_effect() {
 #[test]
 fn test_function_requires_capability() {
     let fn_decl = FnDecl {
-        name: "fetch".to_string(),
+        name: "fetch".into(),
+        type_params: vec![],
         params: vec![],
-        ret: TypeExpr::Path(vec!["Unit".to_string()]),
-        effects: vec!["net".to_string()],
+        ret: TypeExpr::Path(vec!["Unit".into()]),
+        effects: vec!["net".into()],
         body: z1_ast::Block::default(),
         span: make_span(),
+        doc: None,
     };
 
     let module = make_module(vec![Item::Fn(fn_decl)]);
@@ -87,12 +93,14 @@ fn test_function_requires_capability() {
 #[test]
 fn test_function_missing_capability() {
     let fn_decl = FnDecl {
-        name: "read_file".to_string(),
+        name: "read_file".into(),
+        type_params: vec![],
         params: vec![],
-        ret: TypeExpr::Path(vec!["Unit".to_string()]),
-        effects: vec!["fs".to_string()],
+        ret: TypeExpr::Path(vec!["Unit".into()]),
+        effects: vec!["fs".into()],
         body: z1_ast::Block::default(),
         span: make_span(),
+        doc: None,
     };
 
     // Module only has net capability, not fs
@@ -113,8 +121,8 @@ fn test_function_missing_capability() {
 fn test_import_with_alias() {
     let import = Import {
         path: "std/http".to_string(),
-        alias: Some("H".to_string()),
-        only: vec!["Req".to_string(), "Res".to_string()],
+        alias: Some("H".into()),
+        only: vec!["Req".into(), "Res".into()],
         span: make_span(),
     };
 
@@ -129,23 +137,25 @@ fn test_function_with_imported_types() {
     // First import the types
     let import = Import {
         path: "std/http".to_string(),
-        alias: Some("H".to_string()),
-        only: vec!["Req".to_string(), "Res".to_string()],
+        alias: Some("H".into()),
+        only: vec!["Req".into(), "Res".into()],
         span: make_span(),
     };
 
     // Then use them in a function
     let fn_decl = FnDecl {
-        name: "handler".to_string(),
+        name: "handler".into(),
+        type_params: vec![],
         params: vec![Param {
-            name: "req".to_string(),
-            ty: TypeExpr::Path(vec!["H".to_string(), "Req".to_string()]),
+            name: "req".into(),
+            ty: TypeExpr::Path(vec!["H".into(), "Req".into()]),
             span: make_span(),
         }],
-        ret: TypeExpr::Path(vec!["H".to_string(), "Res".to_string()]),
-        effects: vec!["pure".to_string()],
+        ret: TypeExpr::Path(vec!["H".into(), "Res".into()]),
+        effects: vec!["pure".into()],
         body: z1_ast::Block::default(),
         span: make_span(),
+        doc: None,
     };
 
     let module = make_module(vec![Item::Import(import), Item::Fn(fn_decl)]);
@@ -154,18 +164,56 @@ fn test_function_with_imported_types() {
     assert!(check_module(&module).is_ok());
 }
 
+#[test]
+fn test_resolved_imports_must_declare_names() {
+    let http = Module::new(
+        ModulePath::from_parts(vec!["std".into(), "http".into()]),
+        Some("1.0".to_string()),
+        None,
+        vec![],
+        vec![Item::Type(TypeDecl {
+            name: "Req".into(),
+            type_params: vec![],
+            expr: TypeExpr::Path(vec!["Str".into()]),
+            span: make_span(),
+            doc: None,
+        })],
+        make_span(),
+    );
+    let imports = [("std/http".to_string(), http)].into_iter().collect();
+
+    let import = |only: &str| Import {
+        path: "std/http".to_string(),
+        alias: Some("H".into()),
+        only: vec![only.into()],
+        span: make_span(),
+    };
+
+    let module = make_module(vec![Item::Import(import("Req"))]);
+    assert!(check_module_with_imports(&module, &imports).is_ok());
+
+    let module = make_module(vec![Item::Import(import("Res"))]);
+    match check_module_with_imports(&module, &imports) {
+        Err(TypeError::UnknownImport { name, import, .. }) => {
+            assert_eq!(name, "Res");
+            assert_eq!(import, "std/http");
+        }
+        other => panic!("expected UnknownImport, got {other:?}"),
+    }
+}
+
 #[test]
 fn test_structural_record_types() {
     use std::collections::BTreeMap;
 
     // Create two structurally equal record types
     let mut fields1 = BTreeMap::new();
-    fields1.insert("x".to_string(), Box::new(Type::U32));
-    fields1.insert("y".to_string(), Box::new(Type::Bool));
+    fields1.insert("x".into(), Box::new(Type::U32));
+    fields1.insert("y".into(), Box::new(Type::Bool));
 
     let mut fields2 = BTreeMap::new();
-    fields2.insert("y".to_string(), Box::new(Type::Bool));
-    fields2.insert("x".to_string(), Box::new(Type::U32));
+    fields2.insert("y".into(), Box::new(Type::Bool));
+    fields2.insert("x".into(), Box::new(Type::U32));
 
     let rec1 = Type::Record(fields1);
     let rec2 = Type::Record(fields2);
@@ -179,60 +227,66 @@ fn test_http_server_example() {
     // Recreate the http_server.z1c example
     let import = Import {
         path: "std/http".to_string(),
-        alias: Some("H".to_string()),
-        only: vec!["listen".to_string(), "Req".to_string(), "Res".to_string()],
+        alias: Some("H".into()),
+        only: vec!["listen".into(), "Req".into(), "Res".into()],
         span: make_span(),
     };
 
     let health_type = TypeDecl {
-        name: "Health".to_string(),
+        name: "Health".into(),
+        type_params: vec![],
         expr: TypeExpr::Record(vec![
             z1_ast::RecordField {
-                name: "ok".to_string(),
-                ty: Box::new(TypeExpr::Path(vec!["Bool".to_string()])),
+                name: "ok".into(),
+                ty: Box::new(TypeExpr::Path(vec!["Bool".into()])),
                 span: make_span(),
             },
             z1_ast::RecordField {
-                name: "msg".to_string(),
-                ty: Box::new(TypeExpr::Path(vec!["Str".to_string()])),
+                name: "msg".into(),
+                ty: Box::new(TypeExpr::Path(vec!["Str".into()])),
                 span: make_span(),
             },
         ]),
         span: make_span(),
+        doc: None,
     };
 
     let handler_fn = FnDecl {
-        name: "handler".to_string(),
+        name: "handler".into(),
+        type_params: vec![],
         params: vec![Param {
-            name: "q".to_string(),
-            ty: TypeExpr::Path(vec!["H".to_string(), "Req".to_string()]),
+            name: "q".into(),
+            ty: TypeExpr::Path(vec!["H".into(), "Req".into()]),
             span: make_span(),
         }],
-        ret: TypeExpr::Path(vec!["H".to_string(), "Res".to_string()]),
-        effects: vec!["pure".to_string()],
+        ret: TypeExpr::Path(vec!["H".into(), "Res".into()]),
+        effects: vec!["pure".into()],
         body: z1_ast::Block {
             raw: "ret H.Res{ status:200, body:\"ok\" };".to_string(),
             statements: vec![],
             span: make_span(),
         },
         span: make_span(),
+        doc: None,
     };
 
     let serve_fn = FnDecl {
-        name: "serve".to_string(),
+        name: "serve".into(),
+        type_params: vec![],
         params: vec![Param {
-            name: "p".to_string(),
-            ty: TypeExpr::Path(vec!["U16".to_string()]),
+            name: "p".into(),
+            ty: TypeExpr::Path(vec!["U16".into()]),
             span: make_span(),
         }],
-        ret: TypeExpr::Path(vec!["Unit".to_string()]),
-        effects: vec!["net".to_string()],
+        ret: TypeExpr::Path(vec!["Unit".into()]),
+        effects: vec!["net".into()],
         body: z1_ast::Block {
             raw: "H.listen(p, handler);".to_string(),
             statements: vec![],
             span: make_span(),
         },
         span: make_span(),
+        doc: None,
     };
 
     let module = make_module(vec![
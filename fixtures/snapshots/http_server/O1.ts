@@ -0,0 +1,36 @@
+// Generated by Zero1 compiler
+// TypeScript output from module: http.server
+// Version: 1.0
+
+import { listen, Req, Res } from './std_http.js';
+
+/** Host operations of this module, by the capability granting them */
+export interface Capabilities {
+  net: {
+    listen: typeof listen;
+  };
+}
+
+let capabilities: Capabilities = {
+  net: { listen },
+};
+
+/** Perform the effects of this module through `granted` from now on */
+export function provideCapabilities(granted: Capabilities): void {
+  capabilities = granted;
+}
+
+/** WASM layout: 8 bytes, align 4 (ok@0, msg@4) */
+export interface Health {
+  ok: boolean;
+  msg: string;
+}
+
+export function handler(q: H.Req): H.Res {
+  return { status: 200, body: "ok" };
+}
+
+export function serve(p: number): void {
+  H.listen(p, h);
+}
+
@@ -0,0 +1,53 @@
+;; Generated by Zero1 compiler
+;; WebAssembly output from module: demo.arith
+;; Version: 1.0
+
+(module $demo.arith
+  ;; Linear memory
+  (memory $mem 1)
+  (export "memory" (memory $mem))
+  
+  (func $add (param $a i32) (param $b i32) (result i32)
+    
+    local.get $a
+    local.get $b
+    i32.add
+    return
+  )
+  (export "add" (func $add))
+  
+  (func $clamp (param $x i32) (param $lo i32) (param $hi i32) (result i32)
+    
+    local.get $x
+    local.get $lo
+    i32.lt_u
+    (if
+      (then
+        local.get $lo
+        return
+      )
+    )
+    local.get $x
+    local.get $hi
+    i32.gt_u
+    (if
+      (then
+        local.get $hi
+        return
+      )
+    )
+    local.get $x
+    return
+  )
+  (export "clamp" (func $clamp))
+  
+  (func $wide (param $x i64) (result i64)
+    
+    local.get $x
+    i64.const 2
+    i64.mul
+    return
+  )
+  (export "wide" (func $wide))
+  
+)
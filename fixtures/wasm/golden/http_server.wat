@@ -0,0 +1,89 @@
+;; Generated by Zero1 compiler
+;; WebAssembly output from module: http.server
+;; Version: 1.0
+
+(module $http.server
+  ;; Imports
+  (import "std_http" "listen" (func $listen (param i32) (result i32)))
+  (import "std_http" "Req" (func $Req (param i32) (result i32)))
+  (import "std_http" "Res" (func $Res (param i32) (result i32)))
+  
+  ;; Linear memory
+  (memory $mem 1)
+  (export "memory" (memory $mem))
+  
+  ;; Type definitions
+  ;; type Health = Record([("ok", Bool), ("msg", Str)])
+  
+  (func $handler (param $q i32) (result i32)
+    (local $__rec0 i32)
+    
+    i32.const 8
+    call $alloc
+    local.set $__rec0
+    local.get $__rec0
+    i32.const 200
+    i32.store offset=0 ;; .status
+    local.get $__rec0
+    i32.const 1024 ;; string "ok"
+    i32.store offset=4 ;; .body
+    local.get $__rec0
+    return
+  )
+  (export "handler" (func $handler))
+  
+  (func $serve (param $p i32)
+    
+    ;; Warning: call to unknown function
+    unreachable
+  )
+  (export "serve" (func $serve))
+  
+  ;; String literals
+  (data (i32.const 1024) "\02\00\00\00ok")
+  
+  ;; Bump allocator
+  (global $heap_ptr (mut i32) (i32.const 1032))
+  (func $alloc (param $size i32) (result i32)
+    (local $ptr i32)
+    global.get $heap_ptr
+    local.set $ptr
+    local.get $ptr
+    local.get $size
+    i32.add
+    i32.const 7
+    i32.add
+    i32.const -8
+    i32.and
+    global.set $heap_ptr
+    ;; Grow memory when the heap passes the end of the last page
+    global.get $heap_ptr
+    memory.size
+    i32.const 16
+    i32.shl
+    i32.gt_u
+    (if
+      (then
+        global.get $heap_ptr
+        memory.size
+        i32.const 16
+        i32.shl
+        i32.sub
+        i32.const 65535
+        i32.add
+        i32.const 16
+        i32.shr_u
+        memory.grow
+        i32.const -1
+        i32.eq
+        (if
+          (then
+            unreachable
+          )
+        )
+      )
+    )
+    local.get $ptr
+  )
+  
+)
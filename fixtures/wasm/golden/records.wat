@@ -0,0 +1,85 @@
+;; Generated by Zero1 compiler
+;; WebAssembly output from module: demo.records
+;; Version: 1.0
+
+(module $demo.records
+  ;; Linear memory
+  (memory $mem 1)
+  (export "memory" (memory $mem))
+  
+  ;; Type definitions
+  ;; type Point = Record([("x", U32), ("y", U32)])
+  ;; type Label = Record([("text", Str), ("point", Named("Point"))])
+  
+  (func $origin (result i32)
+    (local $__rec0 i32)
+    
+    i32.const 8
+    call $alloc
+    local.set $__rec0
+    local.get $__rec0
+    i32.const 0
+    i32.store offset=0 ;; .x
+    local.get $__rec0
+    i32.const 0
+    i32.store offset=4 ;; .y
+    local.get $__rec0
+    return
+  )
+  (export "origin" (func $origin))
+  
+  (func $greeting (result i32)
+    
+    i32.const 1024 ;; string "hello"
+    return
+  )
+  (export "greeting" (func $greeting))
+  
+  ;; String literals
+  (data (i32.const 1024) "\05\00\00\00hello")
+  
+  ;; Bump allocator
+  (global $heap_ptr (mut i32) (i32.const 1040))
+  (func $alloc (param $size i32) (result i32)
+    (local $ptr i32)
+    global.get $heap_ptr
+    local.set $ptr
+    local.get $ptr
+    local.get $size
+    i32.add
+    i32.const 7
+    i32.add
+    i32.const -8
+    i32.and
+    global.set $heap_ptr
+    ;; Grow memory when the heap passes the end of the last page
+    global.get $heap_ptr
+    memory.size
+    i32.const 16
+    i32.shl
+    i32.gt_u
+    (if
+      (then
+        global.get $heap_ptr
+        memory.size
+        i32.const 16
+        i32.shl
+        i32.sub
+        i32.const 65535
+        i32.add
+        i32.const 16
+        i32.shr_u
+        memory.grow
+        i32.const -1
+        i32.eq
+        (if
+          (then
+            unreachable
+          )
+        )
+      )
+    )
+    local.get $ptr
+  )
+  
+)
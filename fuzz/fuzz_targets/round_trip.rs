@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    if let Err(violation) = z1_fuzz::check_round_trip(source) {
+        panic!("{violation}");
+    }
+});
@@ -0,0 +1,22 @@
+// Runtime shim for std/http (TypeScript backend)
+import { createServer } from 'node:http';
+
+export type Req = { method: string; path: string; body: string };
+export type Res = { status: number; body: string };
+export type Handler = (req: Req) => Res;
+
+export function listen(port: number, handler: Handler): void {
+  createServer((request, response) => {
+    let body = '';
+    request.on('data', (chunk) => (body += chunk));
+    request.on('end', () => {
+      const res = handler({ method: request.method ?? 'GET', path: request.url ?? '/', body });
+      response.writeHead(res.status);
+      response.end(res.body);
+    });
+  }).listen(port);
+}
+
+export function respond(status: number, body: string): Res {
+  return { status, body };
+}
@@ -0,0 +1,23 @@
+// Runtime shim for std/io (TypeScript backend)
+import { readSync } from 'node:fs';
+
+export function print(text: string): void {
+  process.stdout.write(text);
+}
+
+export function println(text: string): void {
+  process.stdout.write(text + '\n');
+}
+
+export function eprintln(text: string): void {
+  process.stderr.write(text + '\n');
+}
+
+export function readLine(): string {
+  const bytes: number[] = [];
+  const byte = Buffer.alloc(1);
+  while (readSync(0, byte, 0, 1, null) === 1 && byte[0] !== 0x0a) {
+    bytes.push(byte[0]);
+  }
+  return Buffer.from(bytes).toString('utf8').replace(/\r$/, '');
+}
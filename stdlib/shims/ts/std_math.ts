@@ -0,0 +1,31 @@
+// Runtime shim for std/math (TypeScript backend)
+export function min(a: number, b: number): number {
+  return Math.min(a, b);
+}
+
+export function max(a: number, b: number): number {
+  return Math.max(a, b);
+}
+
+export function clamp(x: number, lo: number, hi: number): number {
+  return Math.min(Math.max(x, lo), hi);
+}
+
+export function absDiff(a: number, b: number): number {
+  return Math.abs(a - b);
+}
+
+export function pow(base: number, exp: number): number {
+  return Number(BigInt(base) ** BigInt(exp) & 0xffffffffn);
+}
+
+export function gcd(a: number, b: number): number {
+  while (b !== 0) {
+    [a, b] = [b, a % b];
+  }
+  return a;
+}
+
+export function isqrt(x: number): number {
+  return Math.floor(Math.sqrt(x));
+}
@@ -0,0 +1,46 @@
+// Runtime shim for std/str (TypeScript backend)
+const encoder = new TextEncoder();
+
+export function len(s: string): number {
+  return encoder.encode(s).length;
+}
+
+export function concat(a: string, b: string): string {
+  return a + b;
+}
+
+export function contains(s: string, part: string): boolean {
+  return s.includes(part);
+}
+
+export function startsWith(s: string, prefix: string): boolean {
+  return s.startsWith(prefix);
+}
+
+export function endsWith(s: string, suffix: string): boolean {
+  return s.endsWith(suffix);
+}
+
+export function toUpper(s: string): string {
+  return s.toUpperCase();
+}
+
+export function toLower(s: string): string {
+  return s.toLowerCase();
+}
+
+export function trim(s: string): string {
+  return s.trim();
+}
+
+export function repeat(s: string, count: number): string {
+  return s.repeat(count);
+}
+
+export function fromU32(n: number): string {
+  return (n >>> 0).toString();
+}
+
+export function parseU32(s: string): number {
+  return /^[0-9]+$/.test(s) ? Number(s) >>> 0 : 0;
+}
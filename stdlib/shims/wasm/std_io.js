@@ -0,0 +1,33 @@
+// Host imports for std/io (WASM backend), the `std_io` import module.
+//
+// `glue` returns the bound exports of the instance, as `instantiate` from
+// the JS glue resolves them, for reading and writing strings:
+//
+//   let glue;
+//   glue = await instantiate(source, { std_io: stdIo(() => glue) });
+//
+// Its functions perform the `fs` effect: a cell with `caps=[fs]` imports
+// them from the `fs` import module instead, `{ fs: stdIo(() => glue) }`.
+import { readSync } from 'node:fs';
+
+export function stdIo(glue) {
+  return {
+    print: (text) => {
+      process.stdout.write(glue().readStr(text));
+    },
+    println: (text) => {
+      process.stdout.write(glue().readStr(text) + '\n');
+    },
+    eprintln: (text) => {
+      process.stderr.write(glue().readStr(text) + '\n');
+    },
+    readLine: () => {
+      const bytes = [];
+      const byte = Buffer.alloc(1);
+      while (readSync(0, byte, 0, 1, null) === 1 && byte[0] !== 0x0a) {
+        bytes.push(byte[0]);
+      }
+      return glue().writeStr(Buffer.from(bytes).toString('utf8').replace(/\r$/, ''));
+    },
+  };
+}
@@ -0,0 +1,22 @@
+// Host imports for std/math (WASM backend), the `std_math` import module.
+//
+// Numbers cross as i32 and are read unsigned:
+//
+//   await instantiate(source, { std_math: stdMath() });
+export function stdMath() {
+  return {
+    min: (a, b) => Math.min(a >>> 0, b >>> 0),
+    max: (a, b) => Math.max(a >>> 0, b >>> 0),
+    clamp: (x, lo, hi) => Math.min(Math.max(x >>> 0, lo >>> 0), hi >>> 0),
+    absDiff: (a, b) => Math.abs((a >>> 0) - (b >>> 0)),
+    pow: (base, exp) => Number(BigInt(base >>> 0) ** BigInt(exp >>> 0) & 0xffffffffn),
+    gcd: (a, b) => {
+      let [x, y] = [a >>> 0, b >>> 0];
+      while (y !== 0) {
+        [x, y] = [y, x % y];
+      }
+      return x;
+    },
+    isqrt: (x) => Math.floor(Math.sqrt(x >>> 0)),
+  };
+}
@@ -0,0 +1,29 @@
+// Host imports for std/str (WASM backend), the `std_str` import module.
+//
+// `glue` returns the bound exports of the instance, as `instantiate` from
+// the JS glue resolves them, for reading and writing strings:
+//
+//   let glue;
+//   glue = await instantiate(source, { std_str: stdStr(() => glue) });
+const encoder = new TextEncoder();
+
+export function stdStr(glue) {
+  const read = (ptr) => glue().readStr(ptr);
+  const write = (value) => glue().writeStr(value);
+  return {
+    len: (s) => encoder.encode(read(s)).length,
+    concat: (a, b) => write(read(a) + read(b)),
+    contains: (s, part) => Number(read(s).includes(read(part))),
+    startsWith: (s, prefix) => Number(read(s).startsWith(read(prefix))),
+    endsWith: (s, suffix) => Number(read(s).endsWith(read(suffix))),
+    toUpper: (s) => write(read(s).toUpperCase()),
+    toLower: (s) => write(read(s).toLowerCase()),
+    trim: (s) => write(read(s).trim()),
+    repeat: (s, count) => write(read(s).repeat(count >>> 0)),
+    fromU32: (n) => write((n >>> 0).toString()),
+    parseU32: (s) => {
+      const text = read(s);
+      return /^[0-9]+$/.test(text) ? Number(text) | 0 : 0;
+    },
+  };
+}
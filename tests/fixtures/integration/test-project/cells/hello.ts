@@ -5,6 +5,6 @@
 export type name = string;
 
 export function greet(name: name): string {
+  return "Hello";
 }
 
-export { name, greet };